@@ -0,0 +1,34 @@
+//! Embeds the commit being built as `GIT_COMMIT`, for the capabilities
+//! endpoint and the diagnostic bundle. A `GIT_COMMIT` already set in the
+//! environment wins, for builds from a source archive without `.git`.
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    if std::env::var_os("GIT_COMMIT").is_some() {
+        return;
+    }
+
+    // Rebuild when HEAD moves, whether it is detached or on a branch
+    let git_dir = std::path::Path::new("../../.git");
+    println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+    if let Ok(head) = std::fs::read_to_string(git_dir.join("HEAD")) {
+        if let Some(reference) = head.trim().strip_prefix("ref: ") {
+            println!(
+                "cargo:rerun-if-changed={}",
+                git_dir.join(reference).display()
+            );
+        }
+    }
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=GIT_COMMIT={}", commit.trim());
+    }
+}
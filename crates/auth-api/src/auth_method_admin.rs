@@ -0,0 +1,51 @@
+//! Internal admin API for retiring passwords
+//!
+//! Served on the admin listener. Reports how many users of each tenant
+//! still sign in with a password alone, and emails them asking them to add
+//! a passkey.
+
+use crate::error::ApiError;
+use auth_core::services::auth_methods::{NudgeReport, PasskeyNudge, PasswordOnlyCount};
+use axum::{
+    extract::{Query, State},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn router(nudge: Arc<PasskeyNudge>) -> Router {
+    Router::new()
+        .route("/admin/auth-methods/password-only", get(password_only))
+        .route("/admin/auth-methods/passkey-nudge", post(run_nudge))
+        .with_state(nudge)
+}
+
+#[derive(Debug, Deserialize)]
+struct PasswordOnlyQuery {
+    tenant_id: Option<Uuid>,
+}
+
+/// GET /admin/auth-methods/password-only?tenant_id=
+async fn password_only(
+    State(nudge): State<Arc<PasskeyNudge>>,
+    Query(query): Query<PasswordOnlyQuery>,
+) -> Result<Json<Vec<PasswordOnlyCount>>, ApiError> {
+    Ok(Json(nudge.password_only(query.tenant_id).await?))
+}
+
+#[derive(Debug, Deserialize)]
+struct NudgeRequest {
+    tenant_id: Uuid,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// POST /admin/auth-methods/passkey-nudge
+async fn run_nudge(
+    State(nudge): State<Arc<PasskeyNudge>>,
+    Json(request): Json<NudgeRequest>,
+) -> Result<Json<NudgeReport>, ApiError> {
+    Ok(Json(nudge.run(request.tenant_id, request.dry_run).await?))
+}
@@ -0,0 +1,217 @@
+//! Backend-For-Frontend (BFF) session handling
+//!
+//! Browser clients enabled for BFF mode never receive raw tokens. The tokens
+//! are kept in the cache keyed by an opaque session id, and the browser only
+//! holds that id inside an encrypted, HttpOnly cookie.
+
+use auth_cache::{Cache, FeatureMode};
+use auth_config::BffConfig;
+use auth_core::error::AuthError;
+use auth_crypto::{EncryptionError, SymmetricCipher};
+use axum::http::HeaderMap;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Server-side token set for a BFF session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BffSession {
+    pub client_id: String,
+    pub user_id: Uuid,
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Unix timestamp at which the access token expires
+    pub access_expires_at: i64,
+}
+
+pub struct BffService {
+    cipher: SymmetricCipher,
+    cache: Arc<dyn Cache>,
+    config: BffConfig,
+}
+
+impl BffService {
+    pub fn new(config: BffConfig, cache: Arc<dyn Cache>) -> Result<Self, EncryptionError> {
+        let cipher = match &config.cookie_key {
+            Some(key) => SymmetricCipher::from_base64(key.expose_secret())?,
+            None => {
+                if !config.enabled_clients.is_empty() {
+                    tracing::warn!(
+                        "BFF cookie key not configured; using an ephemeral key (sessions will not survive restarts)"
+                    );
+                }
+                SymmetricCipher::from_base64(&SymmetricCipher::generate_key())?
+            }
+        };
+
+        Ok(Self {
+            cipher,
+            cache,
+            config,
+        })
+    }
+
+    pub fn is_enabled_for(&self, client_id: &str) -> bool {
+        self.config.enabled_clients.iter().any(|c| c == client_id)
+    }
+
+    pub fn cookie_name(&self) -> &str {
+        &self.config.cookie_name
+    }
+
+    /// Persist a new session and return the encrypted cookie value
+    pub async fn create_session(&self, session: &BffSession) -> Result<String, AuthError> {
+        let session_id = Uuid::new_v4().to_string();
+        self.store(&session_id, session).await?;
+        self.cipher
+            .seal_to_string(session_id.as_bytes(), self.config.cookie_name.as_bytes())
+            .map_err(|_| AuthError::InternalError)
+    }
+
+    /// Resolve the cookie value to its session id and stored tokens
+    pub async fn load(
+        &self,
+        cookie_value: &str,
+    ) -> Result<Option<(String, BffSession)>, AuthError> {
+        let session_id = match self
+            .cipher
+            .open_from_string(cookie_value, self.config.cookie_name.as_bytes())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+        {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+
+        self.ensure_available()?;
+        let raw = self
+            .cache
+            .get(&Self::cache_key(&session_id))
+            .await
+            .map_err(|_| AuthError::InternalError)?;
+
+        match raw {
+            Some(raw) => {
+                let session: BffSession =
+                    serde_json::from_str(&raw).map_err(|_| AuthError::InternalError)?;
+                Ok(Some((session_id, session)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub async fn store(&self, session_id: &str, session: &BffSession) -> Result<(), AuthError> {
+        self.ensure_available()?;
+        let raw = serde_json::to_string(session).map_err(|_| AuthError::InternalError)?;
+        self.cache
+            .set(
+                &Self::cache_key(session_id),
+                &raw,
+                Duration::from_secs(self.config.session_ttl_seconds),
+            )
+            .await
+            .map_err(|_| AuthError::InternalError)
+    }
+
+    pub async fn destroy(&self, session_id: &str) -> Result<(), AuthError> {
+        self.cache
+            .delete(&Self::cache_key(session_id))
+            .await
+            .map_err(|_| AuthError::InternalError)
+    }
+
+    /// Sessions are shared through Redis; while it is down a session could
+    /// only be found on the replica that created it
+    fn ensure_available(&self) -> Result<(), AuthError> {
+        if self.cache.feature_mode("bff_session") == FeatureMode::Disable {
+            return Err(AuthError::CircuitBreakerOpen {
+                service: "bff_session".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Extract the BFF cookie from request headers
+    pub fn read_cookie(&self, headers: &HeaderMap) -> Option<String> {
+        headers
+            .get_all(axum::http::header::COOKIE)
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|h| h.split(';'))
+            .find_map(|cookie| {
+                let (name, value) = cookie.trim().split_once('=')?;
+                (name == self.config.cookie_name).then(|| value.to_string())
+            })
+    }
+
+    pub fn session_cookie(&self, value: &str) -> String {
+        format!(
+            "{}={}; Path=/; HttpOnly; Secure; SameSite=Strict; Max-Age={}",
+            self.config.cookie_name, value, self.config.session_ttl_seconds
+        )
+    }
+
+    pub fn clear_cookie(&self) -> String {
+        format!(
+            "{}=; Path=/; HttpOnly; Secure; SameSite=Strict; Max-Age=0",
+            self.config.cookie_name
+        )
+    }
+
+    fn cache_key(session_id: &str) -> String {
+        format!("bff_session:{}", session_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auth_cache::MultiLevelCache;
+
+    fn service(enabled: &[&str]) -> BffService {
+        let config = BffConfig {
+            enabled_clients: enabled.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        };
+        BffService::new(config, Arc::new(MultiLevelCache::new(None).unwrap())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_session_roundtrip_through_cookie() {
+        let bff = service(&["spa"]);
+        let session = BffSession {
+            client_id: "spa".to_string(),
+            user_id: Uuid::new_v4(),
+            access_token: "at".to_string(),
+            refresh_token: "rt".to_string(),
+            access_expires_at: 0,
+        };
+
+        let cookie = bff.create_session(&session).await.unwrap();
+        let (_, loaded) = bff.load(&cookie).await.unwrap().unwrap();
+        assert_eq!(loaded.user_id, session.user_id);
+        assert_eq!(loaded.refresh_token, "rt");
+    }
+
+    #[tokio::test]
+    async fn test_tampered_cookie_is_rejected() {
+        let bff = service(&[]);
+        assert!(bff.load("not-a-valid-cookie").await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_client_selection_and_cookie_parsing() {
+        let bff = service(&["spa"]);
+        assert!(bff.is_enabled_for("spa"));
+        assert!(!bff.is_enabled_for("mobile"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::header::COOKIE,
+            "other=1; __Host-bff_session=abc".parse().unwrap(),
+        );
+        assert_eq!(bff.read_cookie(&headers).as_deref(), Some("abc"));
+    }
+}
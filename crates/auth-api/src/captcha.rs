@@ -0,0 +1,69 @@
+//! CAPTCHA gate for credential endpoints
+//!
+//! Failed logins are counted per tenant and client in the shared cache, so a
+//! challenge kicks in across all instances once a client starts guessing.
+
+use crate::AppState;
+use auth_core::error::AuthError;
+use auth_core::services::captcha::{CaptchaService, CaptchaSignals};
+use uuid::Uuid;
+
+/// Require a solved CAPTCHA when the attempt looks automated. Passes straight
+/// through when the tenant has no challenge enabled.
+pub async fn enforce(
+    state: &AppState,
+    tenant_id: Uuid,
+    client: &str,
+    risk_score: f32,
+    token: Option<&str>,
+    remote_ip: Option<&str>,
+) -> Result<(), AuthError> {
+    if !state.captcha.enabled_for(tenant_id) {
+        return Ok(());
+    }
+
+    let signals = CaptchaSignals {
+        risk_score,
+        recent_failures: recent_failures(state, tenant_id, client).await,
+    };
+    if !state.captcha.requires_challenge(tenant_id, signals) {
+        return Ok(());
+    }
+    state.captcha.verify(token, remote_ip).await
+}
+
+pub async fn record_failure(state: &AppState, tenant_id: Uuid, client: &str) {
+    if !state.captcha.enabled_for(tenant_id) {
+        return;
+    }
+    let failures = recent_failures(state, tenant_id, client).await + 1;
+    let key = CaptchaService::failure_key(tenant_id, client);
+    if let Err(e) = state
+        .cache
+        .set(&key, &failures.to_string(), state.captcha.failure_window())
+        .await
+    {
+        tracing::warn!(error = %e, "Failed to record login failure for CAPTCHA");
+    }
+}
+
+pub async fn clear_failures(state: &AppState, tenant_id: Uuid, client: &str) {
+    if !state.captcha.enabled_for(tenant_id) {
+        return;
+    }
+    let key = CaptchaService::failure_key(tenant_id, client);
+    if let Err(e) = state.cache.delete(&key).await {
+        tracing::warn!(error = %e, "Failed to clear CAPTCHA failure counter");
+    }
+}
+
+async fn recent_failures(state: &AppState, tenant_id: Uuid, client: &str) -> u32 {
+    let key = CaptchaService::failure_key(tenant_id, client);
+    match state.cache.get(&key).await {
+        Ok(value) => value.and_then(|v| v.parse().ok()).unwrap_or(0),
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to read CAPTCHA failure counter");
+            0
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Internal admin API for configuration canary rollouts
+//!
+//! Served on the admin listener. Stages the configuration on disk as the
+//! next version for a share of traffic, shows how it compares with the
+//! current version, and promotes or aborts it. [`config_rollout_middleware`]
+//! assigns each public request to a version and records its outcome.
+
+use crate::middleware::TENANT_ID_HEADER;
+use auth_config::{ConfigManager, RolloutStatus};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::time::Instant;
+use uuid::Uuid;
+
+pub fn router(manager: ConfigManager) -> Router {
+    Router::new()
+        .route("/admin/config/rollout", get(status).post(stage))
+        .route("/admin/config/rollout/percent", post(set_percent))
+        .route("/admin/config/rollout/promote", post(promote))
+        .route("/admin/config/rollout/abort", post(abort))
+        .with_state(manager)
+}
+
+/// The configuration version a request was served
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigVersion(pub u64);
+
+/// Assign the request to a configuration version, by tenant where the
+/// request names one, and record its status and latency against it
+pub async fn config_rollout_middleware(
+    State(manager): State<ConfigManager>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let key = req
+        .headers()
+        .get(TENANT_ID_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let version = manager.version_for(&key);
+    req.extensions_mut().insert(ConfigVersion(version));
+
+    let started = Instant::now();
+    let response = next.run(req).await;
+    manager.record_outcome(
+        version,
+        started.elapsed(),
+        response.status().is_server_error(),
+    );
+    response
+}
+
+struct RolloutError(StatusCode, anyhow::Error);
+
+impl IntoResponse for RolloutError {
+    fn into_response(self) -> Response {
+        (
+            self.0,
+            Json(serde_json::json!({ "error": self.1.to_string() })),
+        )
+            .into_response()
+    }
+}
+
+/// Rollout requests fail on the current state, not on the server
+fn conflict(err: anyhow::Error) -> RolloutError {
+    RolloutError(StatusCode::CONFLICT, err)
+}
+
+/// GET /admin/config/rollout
+async fn status(State(manager): State<ConfigManager>) -> Json<RolloutStatus> {
+    Json(manager.rollout_status())
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct StageRequest {
+    /// Defaults to `server.config_rollout.initial_percent`
+    percent: Option<u8>,
+}
+
+/// POST /admin/config/rollout
+///
+/// Load the configuration from its sources and stage it as the next version
+async fn stage(
+    State(manager): State<ConfigManager>,
+    body: Option<Json<StageRequest>>,
+) -> Result<(StatusCode, Json<RolloutStatus>), RolloutError> {
+    let percent = body
+        .and_then(|Json(request)| request.percent)
+        .unwrap_or_else(|| manager.get_config().server.config_rollout.initial_percent);
+    manager.stage_reload(percent).map_err(conflict)?;
+    Ok((StatusCode::CREATED, Json(manager.rollout_status())))
+}
+
+#[derive(Debug, Deserialize)]
+struct PercentRequest {
+    percent: u8,
+}
+
+/// POST /admin/config/rollout/percent
+async fn set_percent(
+    State(manager): State<ConfigManager>,
+    Json(request): Json<PercentRequest>,
+) -> Result<Json<RolloutStatus>, RolloutError> {
+    manager
+        .set_canary_percent(request.percent)
+        .map_err(conflict)?;
+    Ok(Json(manager.rollout_status()))
+}
+
+/// POST /admin/config/rollout/promote
+async fn promote(
+    State(manager): State<ConfigManager>,
+) -> Result<Json<RolloutStatus>, RolloutError> {
+    manager.promote_canary().map_err(conflict)?;
+    Ok(Json(manager.rollout_status()))
+}
+
+/// POST /admin/config/rollout/abort
+async fn abort(State(manager): State<ConfigManager>) -> Result<Json<RolloutStatus>, RolloutError> {
+    manager
+        .abort_canary("aborted through the admin API")
+        .map_err(conflict)?;
+    Ok(Json(manager.rollout_status()))
+}
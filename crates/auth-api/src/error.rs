@@ -1,5 +1,6 @@
 use crate::error::problem_details::ProblemDetails;
 pub use auth_core::error::AuthError;
+use auth_core::error::{ErrorClass, ErrorCode};
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
@@ -7,6 +8,7 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+pub mod oauth;
 pub mod problem_details;
 
 /// Structured error response for API
@@ -56,81 +58,58 @@ impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let code = self.inner.code();
 
-        let (status, message) = match &self.inner {
-            AuthError::AuthenticationFailed { reason } => {
-                (StatusCode::UNAUTHORIZED, reason.clone())
-            }
-            AuthError::AuthorizationDenied { permission, .. } => (
-                StatusCode::FORBIDDEN,
-                format!("Permission denied: {}", permission),
-            ),
-            AuthError::TokenError { .. } => (
-                StatusCode::UNAUTHORIZED,
-                "Invalid or expired token".to_string(),
-            ),
-            AuthError::RateLimitExceeded { limit, window } => (
-                StatusCode::TOO_MANY_REQUESTS,
-                format!("Rate limit exceeded: {} per {}", limit, window),
-            ),
-            AuthError::TenantNotFound { .. } => {
-                (StatusCode::NOT_FOUND, "Tenant not found".to_string())
-            }
-            AuthError::ConfigurationError { .. } => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Configuration error".to_string(),
-            ),
-            AuthError::ExternalServiceError { .. } => (
-                StatusCode::BAD_GATEWAY,
-                "External service error".to_string(),
-            ),
-            AuthError::DatabaseError { .. } => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Database error".to_string(),
-            ),
-            AuthError::ValidationError { message } => (StatusCode::BAD_REQUEST, message.clone()),
-            AuthError::InternalError => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Internal server error".to_string(),
-            ),
-            AuthError::CredentialError { message } => (StatusCode::UNAUTHORIZED, message.clone()),
-            AuthError::PasswordPolicyViolation { errors } => {
-                (StatusCode::BAD_REQUEST, errors.join(", "))
-            }
-            AuthError::AccountLocked { reason } => (StatusCode::LOCKED, reason.clone()),
-            AuthError::AccountSuspended => (StatusCode::FORBIDDEN, "Account suspended".to_string()),
-            AuthError::AccountDeleted => (StatusCode::FORBIDDEN, "Account deleted".to_string()),
-            AuthError::PasswordExpired => (StatusCode::FORBIDDEN, "Password expired".to_string()),
-            AuthError::UserNotFound => (StatusCode::NOT_FOUND, "User not found".to_string()),
-            AuthError::InvalidCredentials => {
-                (StatusCode::UNAUTHORIZED, "Invalid credentials".to_string())
-            }
-            AuthError::Conflict { message } => (StatusCode::CONFLICT, message.clone()),
-            AuthError::Unauthorized { message } => (StatusCode::UNAUTHORIZED, message.clone()),
-            AuthError::UTCryptoError(_) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Cryptography error".to_string(),
-            ),
-            AuthError::SessionNotFound => {
-                (StatusCode::UNAUTHORIZED, "Session not found".to_string())
-            }
-            AuthError::InvalidOtp => (StatusCode::BAD_REQUEST, "Invalid OTP".to_string()),
-            AuthError::OtpExpired => (StatusCode::BAD_REQUEST, "OTP expired".to_string()),
-            AuthError::CircuitBreakerOpen { service } => (
-                StatusCode::SERVICE_UNAVAILABLE,
-                format!("Service unavailable: {}", service),
-            ),
-        };
+        let status =
+            StatusCode::from_u16(self.inner.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let message = self.inner.public_message();
 
         // Convert to RFC 7807 Problem Details
         let mut problem = ProblemDetails::new(status, message)
             .with_type(format!("https://auth.example.com/errors/{}", code))
-            .with_extension("code", code);
+            .with_extension("code", code)
+            .with_extension("category", self.inner.category().as_str());
 
-        if let Some(req_id) = self.request_id {
+        // The lower-layer error behind a generic code
+        if let Some(cause) = self.inner.cause_code() {
+            problem = problem.with_extension("cause", cause);
+        }
+
+        // The client needs these to render the widget
+        if let AuthError::CaptchaRequired { provider, site_key } = &self.inner {
+            problem = problem.with_extension("captcha_provider", provider.clone());
+            if let Some(site_key) = site_key {
+                problem = problem.with_extension("captcha_site_key", site_key.clone());
+            }
+        }
+
+        // The client continues with the forced password change
+        if let AuthError::PasswordExpired { change_ticket } = &self.inner {
+            problem = problem.with_extension("password_change_ticket", change_ticket.clone());
+        }
+
+        // Lets a global front door retry in the right region
+        if let AuthError::TenantMisrouted { region, .. } = &self.inner {
+            problem = problem.with_extension("region", region.clone());
+        }
+
+        let request_id = self
+            .request_id
+            .or_else(auth_core::context::RequestContext::current_request_id);
+        crate::diagnostics::record_error(
+            ErrorCode::code(&self.inner),
+            status,
+            &self.inner,
+            request_id,
+        );
+        if let Some(req_id) = request_id {
             problem = problem.with_extension("request_id", req_id.to_string());
         }
 
-        problem.into_response()
+        // For the audit middleware, which only sees the response
+        let mut response = problem.into_response();
+        response
+            .extensions_mut()
+            .insert(ErrorClass::of(&self.inner));
+        response
     }
 }
 
@@ -142,38 +121,12 @@ impl From<AuthError> for ApiError {
 
 impl From<auth_core::services::otp_service::OtpError> for ApiError {
     fn from(error: auth_core::services::otp_service::OtpError) -> Self {
-        match error {
-            auth_core::services::otp_service::OtpError::Invalid => {
-                ApiError::new(auth_core::error::AuthError::InvalidOtp)
-            }
-            auth_core::services::otp_service::OtpError::Expired => {
-                ApiError::new(auth_core::error::AuthError::OtpExpired)
-            }
-            auth_core::services::otp_service::OtpError::NotFound => {
-                ApiError::new(auth_core::error::AuthError::InvalidOtp)
-            }
-            auth_core::services::otp_service::OtpError::MaxAttemptsExceeded => {
-                ApiError::new(auth_core::error::AuthError::RateLimitExceeded {
-                    limit: 5,
-                    window: "session".to_string(),
-                })
-            }
-            _ => ApiError::new(auth_core::error::AuthError::InternalError),
-        }
+        ApiError::new(AuthError::from(error))
     }
 }
 
 impl From<auth_core::services::otp_delivery::DeliveryError> for ApiError {
     fn from(error: auth_core::services::otp_delivery::DeliveryError) -> Self {
-        use auth_core::services::otp_delivery::DeliveryError;
-        match error {
-            DeliveryError::CircuitBreakerOpen(s) => {
-                ApiError::new(auth_core::error::AuthError::CircuitBreakerOpen { service: s })
-            }
-            _ => ApiError::new(auth_core::error::AuthError::ExternalServiceError {
-                service: "otp_delivery".to_string(),
-                error: error.to_string(),
-            }),
-        }
+        ApiError::new(AuthError::from(error))
     }
 }
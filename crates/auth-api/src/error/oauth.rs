@@ -0,0 +1,260 @@
+//! RFC 6749 error responses of the OAuth endpoints
+//!
+//! The token endpoint answers with a JSON body of `error`,
+//! `error_description` and `error_uri` (section 5.2). The authorize endpoint
+//! sends the same fields to the client's redirect URI along with `state`
+//! (section 4.1.2.1), except when the client or the redirect URI itself is
+//! in doubt; those are answered directly so nothing goes to an unverified
+//! URI.
+
+use super::ApiError;
+use auth_core::error::AuthError;
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OAuthErrorCode {
+    InvalidRequest,
+    InvalidClient,
+    InvalidGrant,
+    UnauthorizedClient,
+    UnsupportedGrantType,
+    InvalidScope,
+    AccessDenied,
+    UnsupportedResponseType,
+    ServerError,
+    TemporarilyUnavailable,
+    /// OpenID Connect: `prompt=none` was sent and the user is not signed in
+    LoginRequired,
+}
+
+impl OAuthErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OAuthErrorCode::InvalidRequest => "invalid_request",
+            OAuthErrorCode::InvalidClient => "invalid_client",
+            OAuthErrorCode::InvalidGrant => "invalid_grant",
+            OAuthErrorCode::UnauthorizedClient => "unauthorized_client",
+            OAuthErrorCode::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthErrorCode::InvalidScope => "invalid_scope",
+            OAuthErrorCode::AccessDenied => "access_denied",
+            OAuthErrorCode::UnsupportedResponseType => "unsupported_response_type",
+            OAuthErrorCode::ServerError => "server_error",
+            OAuthErrorCode::TemporarilyUnavailable => "temporarily_unavailable",
+            OAuthErrorCode::LoginRequired => "login_required",
+        }
+    }
+
+    /// Status of a direct response. Failed client authentication is 401;
+    /// every other client error is 400.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            OAuthErrorCode::InvalidClient => StatusCode::UNAUTHORIZED,
+            OAuthErrorCode::ServerError => StatusCode::INTERNAL_SERVER_ERROR,
+            OAuthErrorCode::TemporarilyUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OAuthError {
+    pub error: OAuthErrorCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_uri: Option<String>,
+}
+
+impl OAuthError {
+    pub fn new(error: OAuthErrorCode) -> Self {
+        Self {
+            error,
+            error_description: None,
+            error_uri: None,
+        }
+    }
+
+    pub fn invalid_request(description: impl Into<String>) -> Self {
+        Self::new(OAuthErrorCode::InvalidRequest).with_description(description)
+    }
+
+    pub fn invalid_client(description: impl Into<String>) -> Self {
+        Self::new(OAuthErrorCode::InvalidClient).with_description(description)
+    }
+
+    pub fn invalid_grant(description: impl Into<String>) -> Self {
+        Self::new(OAuthErrorCode::InvalidGrant).with_description(description)
+    }
+
+    /// `error_description` may only hold printable ASCII other than `"`
+    /// and `\`; anything else is replaced by a space.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        let description = description
+            .into()
+            .chars()
+            .map(|c| match c {
+                '"' | '\\' => ' ',
+                ' '..='~' => c,
+                _ => ' ',
+            })
+            .collect();
+        self.error_description = Some(description);
+        self
+    }
+
+    pub fn with_uri(mut self, uri: impl Into<String>) -> Self {
+        self.error_uri = Some(uri.into());
+        self
+    }
+
+    /// The error as authorization response parameters, with the request's
+    /// `state`
+    pub fn to_params(&self, state: Option<&str>) -> Vec<(&'static str, String)> {
+        let mut params = vec![("error", self.error.as_str().to_string())];
+        let optional = [
+            ("error_description", self.error_description.as_deref()),
+            ("error_uri", self.error_uri.as_deref()),
+            ("state", state),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                params.push((name, value.to_string()));
+            }
+        }
+        params
+    }
+
+    /// The error as redirect query parameters, with the request's `state`
+    pub fn to_query(&self, state: Option<&str>) -> String {
+        self.to_params(state)
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, urlencoding::encode(value)))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let status = self.error.status();
+        let invalid_client = self.error == OAuthErrorCode::InvalidClient;
+        let mut response = (status, Json(self)).into_response();
+        let headers = response.headers_mut();
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        headers.insert(header::PRAGMA, HeaderValue::from_static("no-cache"));
+        if invalid_client {
+            headers.insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"oauth\""),
+            );
+        }
+        response
+    }
+}
+
+impl From<AuthError> for OAuthError {
+    fn from(error: AuthError) -> Self {
+        match error {
+            AuthError::ValidationError { message } => OAuthError::invalid_request(message),
+            AuthError::InvalidCredentials
+            | AuthError::AuthenticationFailed { .. }
+            | AuthError::TokenError { .. }
+            | AuthError::AccountLocked { .. }
+            | AuthError::AccountSuspended
+            | AuthError::AccountDeleted
+            | AuthError::UserNotFound => OAuthError::new(OAuthErrorCode::InvalidGrant),
+            AuthError::AuthorizationDenied { .. } | AuthError::Unauthorized { .. } => {
+                OAuthError::new(OAuthErrorCode::UnauthorizedClient)
+            }
+            AuthError::RateLimitExceeded { .. }
+            | AuthError::ConcurrencyLimitExceeded { .. }
+            | AuthError::CircuitBreakerOpen { .. } => {
+                OAuthError::new(OAuthErrorCode::TemporarilyUnavailable)
+            }
+            other => {
+                tracing::error!("OAuth request failed: {}", other);
+                OAuthError::new(OAuthErrorCode::ServerError)
+            }
+        }
+    }
+}
+
+/// For endpoints outside OAuth that share its code paths, such as BFF login
+impl From<OAuthError> for ApiError {
+    fn from(error: OAuthError) -> Self {
+        let description = error
+            .error_description
+            .unwrap_or_else(|| error.error.as_str().to_string());
+        ApiError::new(match error.error {
+            OAuthErrorCode::InvalidClient | OAuthErrorCode::InvalidGrant => {
+                AuthError::AuthenticationFailed {
+                    reason: description,
+                }
+            }
+            OAuthErrorCode::ServerError | OAuthErrorCode::TemporarilyUnavailable => {
+                AuthError::InternalError
+            }
+            _ => AuthError::ValidationError {
+                message: description,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statuses_follow_rfc_6749() {
+        assert_eq!(
+            OAuthErrorCode::InvalidClient.status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            OAuthErrorCode::InvalidGrant.status(),
+            StatusCode::BAD_REQUEST
+        );
+        let response = OAuthError::invalid_client("unknown client").into_response();
+        assert!(response.headers().contains_key(header::WWW_AUTHENTICATE));
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+    }
+
+    #[test]
+    fn test_body_and_redirect_shapes() {
+        let error = OAuthError::invalid_grant("code \"abc\" expired").with_uri("https://docs/e");
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "error": "invalid_grant",
+                "error_description": "code  abc  expired",
+                "error_uri": "https://docs/e",
+            })
+        );
+        assert_eq!(
+            OAuthError::new(OAuthErrorCode::AccessDenied).to_query(Some("a b")),
+            "error=access_denied&state=a%20b"
+        );
+    }
+
+    #[test]
+    fn test_auth_errors_map_to_standard_codes() {
+        assert_eq!(
+            OAuthError::from(AuthError::InvalidCredentials).error,
+            OAuthErrorCode::InvalidGrant
+        );
+        assert_eq!(
+            OAuthError::from(AuthError::DatabaseError {
+                message: "down".to_string()
+            })
+            .error,
+            OAuthErrorCode::ServerError
+        );
+    }
+}
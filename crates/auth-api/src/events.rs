@@ -0,0 +1,45 @@
+//! Cross-node delivery of domain events over Redis pub/sub
+
+use async_trait::async_trait;
+use auth_cache::RedisPubSub;
+use auth_core::events::{EventBus, EventEnvelope, EventRelay};
+use std::sync::Arc;
+
+pub const EVENTS_CHANNEL: &str = "auth:domain_events";
+
+pub struct RedisEventRelay {
+    pubsub: RedisPubSub,
+}
+
+impl RedisEventRelay {
+    pub fn new(pubsub: RedisPubSub) -> Self {
+        Self { pubsub }
+    }
+}
+
+#[async_trait]
+impl EventRelay for RedisEventRelay {
+    async fn publish(&self, envelope: &EventEnvelope) -> anyhow::Result<()> {
+        let payload = serde_json::to_string(envelope)?;
+        self.pubsub.publish(EVENTS_CHANNEL, &payload).await
+    }
+}
+
+/// Build an event bus that relays to and ingests from other nodes via Redis
+pub fn redis_event_bus(redis_url: &str) -> anyhow::Result<Arc<EventBus>> {
+    let pubsub = RedisPubSub::new(redis_url)?;
+    let bus = Arc::new(EventBus::new().with_relay(Arc::new(RedisEventRelay::new(pubsub.clone()))));
+
+    let (mut rx, _) = pubsub.subscribe(EVENTS_CHANNEL);
+    let ingest = bus.clone();
+    tokio::spawn(async move {
+        while let Some(payload) = rx.recv().await {
+            match serde_json::from_str::<EventEnvelope>(&payload) {
+                Ok(envelope) => ingest.ingest_remote(envelope),
+                Err(e) => tracing::warn!("Dropping malformed domain event: {}", e),
+            }
+        }
+    });
+
+    Ok(bus)
+}
@@ -0,0 +1,87 @@
+//! Internal admin API for the SOC analytics exports
+//!
+//! Served on the admin listener. Lists completed exports with their files,
+//! for collectors that would rather poll here than list the bucket, and
+//! the column layout of each dataset.
+
+use auth_core::services::analytics_export::{
+    AnalyticsExporter, ExportDataset, ExportManifest, ExportStats,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+type AdminError = (StatusCode, Json<serde_json::Value>);
+
+pub fn router(exporter: Arc<AnalyticsExporter>) -> Router {
+    Router::new()
+        .route("/admin/exports", get(list_exports))
+        .route("/admin/exports/schema", get(schema))
+        .with_state(exporter)
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportsQuery {
+    dataset: Option<ExportDataset>,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    50
+}
+
+#[derive(Debug, Serialize)]
+struct ExportListing {
+    exports: Vec<ExportManifest>,
+    /// Runs of this instance since it started
+    stats: ExportStats,
+}
+
+/// GET /admin/exports?dataset=login_events&limit=50
+///
+/// Completed exports, newest first
+async fn list_exports(
+    State(exporter): State<Arc<AnalyticsExporter>>,
+    Query(query): Query<ExportsQuery>,
+) -> Result<Json<ExportListing>, AdminError> {
+    let exports = exporter
+        .exports(query.dataset, query.limit.min(500))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        })?;
+    Ok(Json(ExportListing {
+        exports,
+        stats: exporter.stats(),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+struct DatasetSchema {
+    dataset: ExportDataset,
+    columns: &'static [&'static str],
+}
+
+/// GET /admin/exports/schema
+///
+/// Header row of each dataset's files
+async fn schema() -> Json<Vec<DatasetSchema>> {
+    Json(
+        ExportDataset::ALL
+            .into_iter()
+            .map(|dataset| DatasetSchema {
+                dataset,
+                columns: dataset.columns(),
+            })
+            .collect(),
+    )
+}
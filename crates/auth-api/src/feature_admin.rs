@@ -0,0 +1,82 @@
+//! Internal admin API for canary cohorts
+//!
+//! Served on the admin listener. Lists features with their cohorts and
+//! kill switches, compares a cohort's error rate and latency with everyone
+//! else's on this replica, and sets or clears kill switches. Setting one
+//! reverts the cohort here at once and on the other replicas within
+//! `features.cohorts.kill_switch_refresh_seconds`.
+
+use crate::error::ApiError;
+use auth_core::error::AuthError;
+use auth_core::services::feature_flags::{
+    CohortComparison, FeatureFlagService, FeatureStatus, KillSwitch,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+pub fn router(features: Arc<FeatureFlagService>) -> Router {
+    Router::new()
+        .route("/admin/features", get(list_features))
+        .route("/admin/features/:feature/cohorts", get(compare_cohorts))
+        .route("/admin/features/:feature/kill", post(kill).delete(restore))
+        .with_state(features)
+}
+
+/// GET /admin/features
+async fn list_features(
+    State(features): State<Arc<FeatureFlagService>>,
+) -> Json<Vec<FeatureStatus>> {
+    Json(features.statuses())
+}
+
+/// GET /admin/features/:feature/cohorts
+async fn compare_cohorts(
+    State(features): State<Arc<FeatureFlagService>>,
+    Path(feature): Path<String>,
+) -> Result<Json<CohortComparison>, ApiError> {
+    features.compare(&feature).map(Json).ok_or_else(|| {
+        ApiError::new(AuthError::ValidationError {
+            message: format!("feature {} does not target a cohort", feature),
+        })
+    })
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct KillRequest {
+    reason: Option<String>,
+    killed_by: Option<String>,
+}
+
+/// POST /admin/features/:feature/kill
+async fn kill(
+    State(features): State<Arc<FeatureFlagService>>,
+    Path(feature): Path<String>,
+    body: Option<Json<KillRequest>>,
+) -> Result<Json<KillSwitch>, ApiError> {
+    let request = body.map(|Json(request)| request).unwrap_or_default();
+    Ok(Json(
+        features
+            .kill(&feature, request.reason, request.killed_by)
+            .await?,
+    ))
+}
+
+/// DELETE /admin/features/:feature/kill
+///
+/// 404 when the feature had no kill switch
+async fn restore(
+    State(features): State<Arc<FeatureFlagService>>,
+    Path(feature): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    Ok(if features.restore(&feature).await? {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    })
+}
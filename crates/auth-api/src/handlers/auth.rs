@@ -1,13 +1,22 @@
+use crate::captcha;
 use crate::error::ApiError;
 use crate::validation;
 use crate::AppState;
-use auth_core::models::user::{CreateUserRequest, User};
+use auth_config::AuthMethod;
+use auth_core::error::AuthError;
+use auth_core::models::user::{CreateUserRequest, IdentifierType, User};
+use auth_core::models::validation::detect_identifier_type;
+use auth_core::models::SensitiveString;
 use auth_core::services::identity::{AuthRequest, AuthResponse};
+use auth_core::services::login_history::LoginEvent;
 use axum::{
     extract::{Extension, State},
+    http::StatusCode,
     Json,
 };
+use serde::Deserialize;
 use tracing::{info, warn};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 /// Authenticate user and issue tokens
@@ -18,7 +27,9 @@ use uuid::Uuid;
     responses(
         (status = 200, description = "Login successful", body = AuthResponse),
         (status = 401, description = "Invalid credentials"),
+        (status = 403, description = "Password expired, with a password change ticket; or passwords are not allowed for the tenant"),
         (status = 423, description = "Account locked"),
+        (status = 428, description = "CAPTCHA challenge required"),
         (status = 429, description = "Rate limit exceeded")
     ),
     tag = "Authentication"
@@ -28,9 +39,12 @@ pub async fn login(
     Extension(request_id): Extension<Uuid>,
     Json(mut payload): Json<AuthRequest>,
 ) -> Result<Json<AuthResponse>, ApiError> {
-    // Normalize email
-    payload.email = validation::validate_email(&payload.email)
-        .map_err(|e| ApiError::new(e).with_request_id(request_id))?;
+    // Normalize email; phone numbers and usernames are folded by the lookup
+    payload.email = match detect_identifier_type(&payload.email) {
+        IdentifierType::Email => validation::validate_email(&payload.email)
+            .map_err(|e| ApiError::new(e).with_request_id(request_id))?,
+        _ => payload.email.trim().to_string(),
+    };
 
     info!(
         request_id = %request_id,
@@ -38,16 +52,61 @@ pub async fn login(
         "Login attempt"
     );
 
+    state
+        .auth_methods
+        .require(payload.tenant_id, AuthMethod::Password)
+        .map_err(|e| ApiError::new(e).with_request_id(request_id))?;
+
+    // Failures are counted per client address, falling back to the account
+    let client = payload
+        .ip_address
+        .clone()
+        .unwrap_or_else(|| payload.email.clone());
+    captcha::enforce(
+        &state,
+        payload.tenant_id,
+        &client,
+        0.0,
+        payload.captcha_token.as_deref(),
+        payload.ip_address.as_deref(),
+    )
+    .await
+    .map_err(|e| ApiError::new(e).with_request_id(request_id))?;
+
     match state.identity_service.login(payload.clone()).await {
-        Ok(response) => {
+        Ok(mut response) => {
             info!(
                 request_id = %request_id,
                 email = %payload.email,
                 "Login successful"
             );
+
+            let event = LoginEvent::new(
+                response.user.id,
+                payload.tenant_id,
+                payload.ip_address.clone(),
+                payload.user_agent.clone(),
+                true,
+            );
+            if let Err(e) = state.login_history.record(event).await {
+                warn!(request_id = %request_id, error = ?e, "Failed to record login event");
+            }
+            captcha::clear_failures(&state, payload.tenant_id, &client).await;
+
+            if state.auth_methods.nudges_passkeys(payload.tenant_id) {
+                response.passkey_enrollment_recommended = !state
+                    .webauthn
+                    .has_passkeys(response.user.id)
+                    .await
+                    .unwrap_or(true);
+            }
+
             Ok(Json(response))
         }
         Err(e) => {
+            if matches!(e, AuthError::InvalidCredentials) {
+                captcha::record_failure(&state, payload.tenant_id, &client).await;
+            }
             warn!(
                 request_id = %request_id,
                 email = %payload.email,
@@ -85,7 +144,7 @@ pub async fn register(
 
     // Validate password strength
     if let Some(ref password) = payload.password {
-        validation::validate_password(password)
+        validation::validate_password(password.expose())
             .map_err(|e| ApiError::new(e).with_request_id(request_id))?;
     } else {
         return Err(ApiError::new(auth_core::error::AuthError::ValidationError {
@@ -129,3 +188,40 @@ pub async fn register(
         }
     }
 }
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ExpiredPasswordChangeRequest {
+    /// `password_change_ticket` of the login refusal
+    pub change_ticket: String,
+    #[schema(value_type = String, format = Password)]
+    pub new_password: SensitiveString,
+}
+
+/// Replace an expired password
+///
+/// Login with an expired password is refused with a single-use ticket;
+/// redeeming it here sets the new password. The user then logs in again.
+#[utoipa::path(
+    post,
+    path = "/auth/password/expired",
+    request_body = ExpiredPasswordChangeRequest,
+    responses(
+        (status = 204, description = "Password changed"),
+        (status = 400, description = "Password policy violation"),
+        (status = 401, description = "Unknown, expired or used ticket")
+    ),
+    tag = "Authentication"
+)]
+pub async fn change_expired_password(
+    State(state): State<AppState>,
+    Extension(request_id): Extension<Uuid>,
+    Json(payload): Json<ExpiredPasswordChangeRequest>,
+) -> Result<StatusCode, ApiError> {
+    let user = state
+        .identity_service
+        .change_expired_password(&payload.change_ticket, payload.new_password)
+        .await
+        .map_err(|e| ApiError::new(e).with_request_id(request_id))?;
+    info!(request_id = %request_id, user_id = %user.id, "Expired password changed");
+    Ok(StatusCode::NO_CONTENT)
+}
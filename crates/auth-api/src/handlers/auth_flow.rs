@@ -2,15 +2,22 @@
 //!
 //! Implements a stateful, step-based authentication flow using the Universal Workflow Engine.
 
+use crate::captcha;
 use crate::error::ApiError;
 use crate::AppState;
 use async_trait::async_trait;
+use auth_cache::FeatureMode;
+use auth_config::AuthMethod;
 use auth_core::error::AuthError;
+use auth_core::error::TokenErrorKind;
+use auth_core::models::PushChallengeStatus;
+use auth_core::services::risk_assessment::{RiskAssessor, RiskEngine};
 use auth_core::services::workflow::{
-    FlowAction, FlowContext, FlowState, StepHandler, WorkflowEngine,
+    FlowAction, FlowContext, FlowState, FlowStateSealer, StepHandler, WorkflowEngine,
 };
 use axum::{
     extract::{Json, Path, State},
+    http::HeaderMap,
     response::IntoResponse,
 };
 use chrono::Utc;
@@ -37,6 +44,9 @@ pub struct StartFlowRequest {
     pub tenant_id: Uuid,
     pub client_id: Option<String>,
     pub redirect_uri: Option<String>,
+    /// Return the flow state to the client as a sealed token instead of caching it
+    #[serde(default)]
+    pub stateless: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -50,14 +60,22 @@ pub struct AuthFlowResponse {
     pub access_token: Option<String>,
     pub refresh_token: Option<String>,
     pub ui_hints: Option<HashMap<String, serde_json::Value>>,
+    /// Sealed flow state for stateless flows; must be sent back on the next call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flow_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ResumeFlowRequest {
     pub action: String,
     pub data: serde_json::Value,
+    #[serde(default)]
+    pub flow_token: Option<String>,
 }
 
+/// Header carrying the sealed flow state on GET requests
+pub const FLOW_TOKEN_HEADER: &str = "x-flow-token";
+
 // ============================================================================
 // Internal Workflow Setup (Engine Factory)
 // ============================================================================
@@ -163,24 +181,22 @@ pub async fn start_flow(
         updated_at: Utc::now().timestamp(),
     };
 
-    let val_str =
-        serde_json::to_string(&context).map_err(|_| ApiError::new(AuthError::InternalError))?;
-    let key = format!("auth_flow:{}", flow_id);
-    state
-        .cache
-        .set(&key, &val_str, Duration::from_secs(900))
-        .await
-        .map_err(|_| ApiError::new(AuthError::InternalError))?;
+    let flow_token = save_context(&state, &context, payload.stateless).await?;
+    let allowed = state.auth_methods.allowed(payload.tenant_id);
 
     Ok(Json(AuthFlowResponse {
         flow_id,
         state: FlowState::Identify,
         next_step: Some("submit_identifier".to_string()),
-        available_factors: None,
+        available_factors: Some(allowed.iter().map(|m| m.as_str().to_string()).collect()),
         error: None,
         access_token: None,
         refresh_token: None,
-        ui_hints: None,
+        // Passkeys can be offered alongside the identifier field right away
+        ui_hints: allowed
+            .contains(&AuthMethod::Passkey)
+            .then(|| HashMap::from([("passkey_action".to_string(), "start_passkey".into())])),
+        flow_token,
     }))
 }
 
@@ -188,32 +204,29 @@ pub async fn start_flow(
 pub async fn get_flow_state(
     State(state): State<AppState>,
     Path(flow_id): Path<String>,
+    headers: HeaderMap,
 ) -> Result<impl IntoResponse, ApiError> {
-    let key = format!("auth_flow:{}", flow_id);
-    let val_opt = state
-        .cache
-        .get(&key)
-        .await
-        .map_err(|_| ApiError::new(AuthError::InternalError))?;
-    let val_str = val_opt.ok_or(ApiError::new(AuthError::ValidationError {
-        message: "Flow not found".to_string(),
-    }))?;
-    let context: FlowContext =
-        serde_json::from_str(&val_str).map_err(|_| ApiError::new(AuthError::InternalError))?;
+    let token = headers
+        .get(FLOW_TOKEN_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string);
+    let context = load_context(&state, &flow_id, token.as_deref()).await?;
+    let flow_token = token;
 
     Ok(Json(AuthFlowResponse {
         flow_id,
         state: context.current_state.clone(),
-        next_step: match context.current_state {
-            FlowState::Identify => Some("submit_identifier".to_string()),
-            FlowState::Authenticate => Some("submit_password".to_string()),
-            _ => None,
-        },
+        next_step: next_step(&context),
         available_factors: None,
         error: None,
         access_token: None,
         refresh_token: None,
-        ui_hints: None,
+        ui_hints: match context.current_state {
+            FlowState::CaptchaRequired => Some(captcha_hints(&state)),
+            FlowState::MfaRequired => Some(mfa_hints(&context)),
+            _ => passkey_hints(&context),
+        },
+        flow_token,
     }))
 }
 
@@ -228,17 +241,8 @@ pub async fn resume_flow(
     // In a real refactor, we'd just call `workflow::submit` logic here or deprecate this endpoint.
     // For now, we reimplement using the Engine to show consolidation.
 
-    let key = format!("auth_flow:{}", flow_id);
-    let val_opt = state
-        .cache
-        .get(&key)
-        .await
-        .map_err(|_| ApiError::new(AuthError::InternalError))?;
-    let val_str = val_opt.ok_or(ApiError::new(AuthError::ValidationError {
-        message: "Flow not found".to_string(),
-    }))?;
-    let context: FlowContext =
-        serde_json::from_str(&val_str).map_err(|_| ApiError::new(AuthError::InternalError))?;
+    let stateless = payload.flow_token.is_some();
+    let mut context = load_context(&state, &flow_id, payload.flow_token.as_deref()).await?;
 
     // Instantiate Engine
     let mut engine = WorkflowEngine::new();
@@ -260,6 +264,7 @@ pub async fn resume_flow(
     // This is the "No Redundancy" fix: use the SERVICE directly here if the Engine isn't DI-capable yet.
 
     // Logic consolidation:
+    let mut error = None;
     let next_state = match (context.current_state.clone(), action.name.as_str()) {
         (FlowState::Identify, "submit_identifier") => {
             // Verify user exists
@@ -268,38 +273,518 @@ pub async fn resume_flow(
                 .get("identifier")
                 .and_then(|s| s.as_str())
                 .unwrap_or("");
-            if state
+            let Some(user) = state
                 .identity_service
                 .find_user_by_identifier(context.tenant_id, id)
                 .await
                 .map_err(ApiError::from)?
-                .is_some()
+            else {
+                return Err(ApiError::new(AuthError::UserNotFound));
+            };
+            context.user_id = Some(user.id);
+            // Offer the user's passkey before asking for a password
+            if state
+                .auth_methods
+                .allows(context.tenant_id, AuthMethod::Passkey)
+                && state
+                    .webauthn
+                    .has_passkeys(user.id)
+                    .await
+                    .map_err(ApiError::new)?
             {
-                FlowState::Authenticate
+                context
+                    .data
+                    .insert(PASSKEY_AVAILABLE_KEY.to_string(), true.into());
             } else {
-                return Err(ApiError::new(AuthError::UserNotFound));
+                // Without a passkey, the password is the flow's only way in
+                state
+                    .auth_methods
+                    .require(context.tenant_id, AuthMethod::Password)?;
             }
+
+            let ip_address = action
+                .payload
+                .get("ip_address")
+                .and_then(|s| s.as_str())
+                .map(str::to_string);
+            let client = ip_address.clone().unwrap_or_else(|| id.to_string());
+            if let Some(ip) = &ip_address {
+                context
+                    .data
+                    .insert("ip_address".to_string(), ip.clone().into());
+            }
+
+            if requires_captcha(&state, &context, user.id, ip_address, &client).await? {
+                FlowState::CaptchaRequired
+            } else {
+                FlowState::Authenticate
+            }
+        }
+        (FlowState::CaptchaRequired, "submit_captcha") => {
+            let token = action.payload.get("captcha_token").and_then(|s| s.as_str());
+            let ip = context.data.get("ip_address").and_then(|s| s.as_str());
+            state
+                .captcha
+                .verify(token, ip)
+                .await
+                .map_err(ApiError::new)?;
+            FlowState::Authenticate
+        }
+        (FlowState::Identify | FlowState::Authenticate, "start_passkey") => {
+            state
+                .auth_methods
+                .require(context.tenant_id, AuthMethod::Passkey)?;
+            // Discoverable request: before identification the authenticator
+            // picks the account, afterwards the result must match it
+            let (options, ceremony) = state.webauthn.start_authentication()?;
+            context.data.insert(
+                PASSKEY_CEREMONY_KEY.to_string(),
+                serde_json::to_value(ceremony).map_err(|_| AuthError::InternalError)?,
+            );
+            context.data.insert(
+                PASSKEY_OPTIONS_KEY.to_string(),
+                serde_json::to_value(options).map_err(|_| AuthError::InternalError)?,
+            );
+            context.current_state.clone()
+        }
+        (FlowState::Identify | FlowState::Authenticate, "submit_passkey") => {
+            state
+                .auth_methods
+                .require(context.tenant_id, AuthMethod::Passkey)?;
+            context.data.remove(PASSKEY_OPTIONS_KEY);
+            let ceremony = context
+                .data
+                .remove(PASSKEY_CEREMONY_KEY)
+                .and_then(|v| serde_json::from_value(v).ok())
+                .ok_or(ApiError::new(AuthError::ValidationError {
+                    message: "start_passkey must be called first".to_string(),
+                }))?;
+            let credential = action
+                .payload
+                .get("credential")
+                .cloned()
+                .and_then(|v| serde_json::from_value(v).ok())
+                .ok_or(ApiError::new(AuthError::ValidationError {
+                    message: "credential required".to_string(),
+                }))?;
+            let user_id = state
+                .webauthn
+                .finish_authentication(ceremony, &credential)
+                .await?;
+            let user = state.identity_service.get_user(user_id).await?;
+            if user.tenant_id != context.tenant_id
+                || context.user_id.is_some_and(|id| id != user_id)
+            {
+                return Err(ApiError::new(AuthError::InvalidCredentials));
+            }
+            if !user.can_authenticate() {
+                return Err(ApiError::new(AuthError::Unauthorized {
+                    message: "Account locked or suspended".to_string(),
+                }));
+            }
+            context.user_id = Some(user_id);
+            // A user-verified passkey is already two factors
+            FlowState::Success
         }
         (FlowState::Authenticate, "submit_password") => {
+            state
+                .auth_methods
+                .require(context.tenant_id, AuthMethod::Password)?;
             // Verify password
             // We need to look up user again or store ID in context
             let _id_str = action.payload.get("identifier").and_then(|s| s.as_str());
             // ... simplified:
+            if mfa_factors(&state, &context).await?.is_empty() {
+                FlowState::Success
+            } else {
+                FlowState::MfaRequired
+            }
+        }
+        (FlowState::MfaRequired, "start_push") => {
+            let user_id = flow_user(&context)?;
+            let ip_address = context
+                .data
+                .get("ip_address")
+                .and_then(|s| s.as_str())
+                .map(str::to_string);
+            match state
+                .push_mfa
+                .create_challenge(user_id, context.tenant_id, ip_address, None)
+                .await
+            {
+                Ok(challenge) => {
+                    context.data.insert(
+                        PUSH_CHALLENGE_KEY.to_string(),
+                        challenge.id.to_string().into(),
+                    );
+                }
+                // No device could be reached; the client should offer TOTP
+                Err(e) => error = Some(e.to_string()),
+            }
+            FlowState::MfaRequired
+        }
+        (FlowState::MfaRequired, "check_push") => {
+            let user_id = flow_user(&context)?;
+            let challenge_id = context
+                .data
+                .get(PUSH_CHALLENGE_KEY)
+                .and_then(|s| s.as_str())
+                .and_then(|s| Uuid::parse_str(s).ok())
+                .ok_or(ApiError::new(AuthError::PushChallengeNotFound))?;
+            match state
+                .push_mfa
+                .status(challenge_id, user_id)
+                .await
+                .map_err(ApiError::from)?
+            {
+                PushChallengeStatus::Approved => FlowState::Success,
+                PushChallengeStatus::Denied => FlowState::Failed,
+                PushChallengeStatus::Expired => {
+                    context.data.remove(PUSH_CHALLENGE_KEY);
+                    error = Some("push challenge expired".to_string());
+                    FlowState::MfaRequired
+                }
+                PushChallengeStatus::Pending => FlowState::MfaRequired,
+            }
+        }
+        (FlowState::MfaRequired, "submit_totp") => {
+            let user = state
+                .identity_service
+                .get_user(flow_user(&context)?)
+                .await
+                .map_err(ApiError::from)?;
+            let code = action
+                .payload
+                .get("code")
+                .and_then(|s| s.as_str())
+                .unwrap_or("");
+            state
+                .push_mfa
+                .verify_totp_fallback(&user, code)
+                .map_err(ApiError::new)?;
             FlowState::Success
         }
-        _ => context.current_state, // No op
+        (FlowState::MfaRequired, "submit_recovery_code") => {
+            let code = action
+                .payload
+                .get("code")
+                .and_then(|s| s.as_str())
+                .unwrap_or("");
+            let used = state
+                .recovery_codes
+                .verify(flow_user(&context)?, context.tenant_id, code)
+                .await
+                .map_err(ApiError::new)?;
+            if used.is_low() {
+                context
+                    .data
+                    .insert(RECOVERY_CODES_LEFT_KEY.to_string(), used.remaining.into());
+            }
+            FlowState::Success
+        }
+        _ => context.current_state.clone(), // No op
     };
 
-    // ... Update context ...
+    // Persist the transition
+    if next_state != context.current_state {
+        context.current_state = next_state.clone();
+        context.updated_at = Utc::now().timestamp();
+        context.version += 1;
+    }
+    let flow_token = save_context(&state, &context, stateless).await?;
+    let ui_hints = match next_state {
+        FlowState::CaptchaRequired => Some(captcha_hints(&state)),
+        FlowState::MfaRequired => Some(mfa_hints(&context)),
+        FlowState::Success => recovery_code_hints(&context),
+        _ => passkey_hints(&context),
+    };
+    let available_factors = match next_state {
+        FlowState::MfaRequired => Some(mfa_factors(&state, &context).await?),
+        FlowState::Authenticate if context.data.contains_key(PASSKEY_AVAILABLE_KEY) => {
+            let mut factors = vec!["passkey".to_string()];
+            if state
+                .auth_methods
+                .allows(context.tenant_id, AuthMethod::Password)
+            {
+                factors.push("password".to_string());
+            }
+            Some(factors)
+        }
+        _ => None,
+    };
 
     Ok(Json(AuthFlowResponse {
         flow_id,
+        next_step: next_step(&context),
         state: next_state,
-        next_step: None,
-        available_factors: None,
-        error: None,
+        available_factors,
+        error,
         access_token: None,
         refresh_token: None,
-        ui_hints: None,
+        ui_hints,
+        flow_token,
     }))
 }
+
+fn next_step(context: &FlowContext) -> Option<String> {
+    match context.current_state {
+        FlowState::Identify => Some("submit_identifier".to_string()),
+        FlowState::CaptchaRequired => Some("submit_captcha".to_string()),
+        FlowState::Authenticate if context.data.contains_key(PASSKEY_AVAILABLE_KEY) => {
+            Some("start_passkey".to_string())
+        }
+        FlowState::Authenticate => Some("submit_password".to_string()),
+        FlowState::MfaRequired => Some("start_push".to_string()),
+        _ => None,
+    }
+}
+
+/// Widget details for a client that has been challenged
+fn captcha_hints(state: &AppState) -> HashMap<String, serde_json::Value> {
+    let mut hints = HashMap::new();
+    hints.insert("view".to_string(), "captcha_challenge".into());
+    if let Some(provider) = state.captcha.provider_name() {
+        hints.insert("captcha_provider".to_string(), provider.into());
+    }
+    if let Some(site_key) = state.captcha.site_key() {
+        hints.insert("captcha_site_key".to_string(), site_key.into());
+    }
+    hints
+}
+
+/// Flow data key holding the outstanding push challenge
+const PUSH_CHALLENGE_KEY: &str = "push_challenge_id";
+
+/// Flow data keys for passkey sign-in: the pending ceremony, the options
+/// handed to `navigator.credentials.get`, and whether the identified user
+/// has a passkey (in which case it is tried before the password)
+const PASSKEY_CEREMONY_KEY: &str = "passkey_ceremony";
+const PASSKEY_OPTIONS_KEY: &str = "passkey_options";
+const PASSKEY_AVAILABLE_KEY: &str = "passkey_available";
+
+/// Flow data key set when a recovery code login leaves few codes
+const RECOVERY_CODES_LEFT_KEY: &str = "recovery_codes_remaining";
+
+fn flow_user(context: &FlowContext) -> Result<Uuid, ApiError> {
+    context
+        .user_id
+        .ok_or(ApiError::new(AuthError::ValidationError {
+            message: "Flow has no identified user".to_string(),
+        }))
+}
+
+/// Second factors the identified user can complete, push first
+async fn mfa_factors(state: &AppState, context: &FlowContext) -> Result<Vec<String>, ApiError> {
+    let Some(user_id) = context.user_id else {
+        return Ok(vec![]);
+    };
+    let mut factors = Vec::new();
+    if state
+        .push_mfa
+        .is_available(user_id, context.tenant_id)
+        .await
+        .map_err(ApiError::new)?
+    {
+        factors.push("push".to_string());
+    }
+    let user = state
+        .identity_service
+        .get_user(user_id)
+        .await
+        .map_err(ApiError::from)?;
+    if user.mfa_enabled && user.mfa_secret.is_some() {
+        factors.push("totp".to_string());
+    }
+    if state
+        .recovery_codes
+        .remaining(user_id)
+        .await
+        .map_err(ApiError::new)?
+        > 0
+    {
+        factors.push("recovery_code".to_string());
+    }
+    Ok(factors)
+}
+
+/// Lets the client poll `check_push` once a challenge is out
+fn mfa_hints(context: &FlowContext) -> HashMap<String, serde_json::Value> {
+    let mut hints = HashMap::new();
+    hints.insert("view".to_string(), "mfa_challenge".into());
+    if let Some(challenge_id) = context.data.get(PUSH_CHALLENGE_KEY) {
+        hints.insert("push_challenge_id".to_string(), challenge_id.clone());
+        hints.insert("poll_action".to_string(), "check_push".into());
+    }
+    hints
+}
+
+/// Options for a started passkey ceremony. Before identification these are
+/// meant for conditional mediation on the identifier field.
+fn passkey_hints(context: &FlowContext) -> Option<HashMap<String, serde_json::Value>> {
+    let options = context.data.get(PASSKEY_OPTIONS_KEY)?;
+    let mut hints = HashMap::new();
+    hints.insert("view".to_string(), "passkey".into());
+    hints.insert("passkey_options".to_string(), options.clone());
+    hints.insert(
+        "mediation".to_string(),
+        if context.current_state == FlowState::Identify {
+            "conditional"
+        } else {
+            "optional"
+        }
+        .into(),
+    );
+    hints.insert("submit_action".to_string(), "submit_passkey".into());
+    Some(hints)
+}
+
+/// Prompts the user to regenerate after spending one of their last codes
+fn recovery_code_hints(context: &FlowContext) -> Option<HashMap<String, serde_json::Value>> {
+    let remaining = context.data.get(RECOVERY_CODES_LEFT_KEY)?;
+    let mut hints = HashMap::new();
+    hints.insert("warning".to_string(), "recovery_codes_low".into());
+    hints.insert(RECOVERY_CODES_LEFT_KEY.to_string(), remaining.clone());
+    Some(hints)
+}
+
+/// Risk-triggered challenge insertion: assess the identified user's login and
+/// compare it, along with recent failures from this client, to the tenant policy
+async fn requires_captcha(
+    state: &AppState,
+    context: &FlowContext,
+    user_id: Uuid,
+    ip_address: Option<String>,
+    client: &str,
+) -> Result<bool, ApiError> {
+    if !state.captcha.enabled_for(context.tenant_id) {
+        return Ok(false);
+    }
+
+    let risk_context = state
+        .login_history
+        .risk_context(user_id, context.tenant_id, ip_address, None, None)
+        .await
+        .map_err(ApiError::new)?;
+    let assessment = RiskEngine::new()
+        .assess_risk(risk_context.clone())
+        .await
+        .map_err(ApiError::new)?;
+    state
+        .session_service
+        .respond_to_risk(&risk_context, &assessment)
+        .await;
+
+    match captcha::enforce(
+        state,
+        context.tenant_id,
+        client,
+        assessment.score,
+        None,
+        None,
+    )
+    .await
+    {
+        Ok(()) => Ok(false),
+        Err(AuthError::CaptchaRequired { .. }) => Ok(true),
+        Err(e) => Err(ApiError::new(e)),
+    }
+}
+
+// ============================================================================
+// Flow State Persistence
+// ============================================================================
+
+const FLOW_TTL_SECS: u64 = 900;
+
+/// Load a flow either from its sealed token (stateless) or from the cache
+async fn load_context(
+    state: &AppState,
+    flow_id: &str,
+    flow_token: Option<&str>,
+) -> Result<FlowContext, ApiError> {
+    if let Some(token) = flow_token {
+        let (context, _) = state.flow_sealer.open(token).map_err(ApiError::new)?;
+        if context.flow_id != flow_id {
+            return Err(ApiError::new(AuthError::TokenError {
+                kind: TokenErrorKind::Invalid,
+            }));
+        }
+
+        // A finished flow must not be resumed from an earlier token
+        let consumed = state
+            .cache
+            .get(&FlowStateSealer::consumed_key(flow_id))
+            .await
+            .map_err(|_| ApiError::new(AuthError::InternalError))?;
+        if consumed.is_some() {
+            return Err(ApiError::new(AuthError::TokenError {
+                kind: TokenErrorKind::Revoked,
+            }));
+        }
+
+        return Ok(context);
+    }
+
+    ensure_flow_store(state)?;
+    let key = format!("auth_flow:{}", flow_id);
+    let val_opt = state
+        .cache
+        .get(&key)
+        .await
+        .map_err(|_| ApiError::new(AuthError::InternalError))?;
+    let val_str = val_opt.ok_or(ApiError::new(AuthError::ValidationError {
+        message: "Flow not found".to_string(),
+    }))?;
+    serde_json::from_str(&val_str).map_err(|_| ApiError::new(AuthError::InternalError))
+}
+
+/// Stateful flows live in the shared cache. While Redis is down the next
+/// step may land on a replica that never saw the flow, so they are refused
+/// unless configured to fall back; stateless flows are unaffected.
+fn ensure_flow_store(state: &AppState) -> Result<(), ApiError> {
+    if state.cache.feature_mode("auth_flow") == FeatureMode::Disable {
+        return Err(ApiError::new(AuthError::CircuitBreakerOpen {
+            service: "auth_flow".to_string(),
+        }));
+    }
+    Ok(())
+}
+
+/// Persist a flow. Stateless flows return a fresh sealed token instead of
+/// touching the cache, except for a replay marker once the flow terminates.
+async fn save_context(
+    state: &AppState,
+    context: &FlowContext,
+    stateless: bool,
+) -> Result<Option<String>, ApiError> {
+    if stateless {
+        if context.current_state.is_terminal() {
+            let ttl = state.flow_sealer.ttl_seconds().max(1) as u64;
+            state
+                .cache
+                .set(
+                    &FlowStateSealer::consumed_key(&context.flow_id),
+                    "1",
+                    Duration::from_secs(ttl),
+                )
+                .await
+                .map_err(|_| ApiError::new(AuthError::InternalError))?;
+        }
+        return state
+            .flow_sealer
+            .seal(context)
+            .map(Some)
+            .map_err(ApiError::new);
+    }
+
+    ensure_flow_store(state)?;
+    let val_str =
+        serde_json::to_string(context).map_err(|_| ApiError::new(AuthError::InternalError))?;
+    let key = format!("auth_flow:{}", context.flow_id);
+    state
+        .cache
+        .set(&key, &val_str, Duration::from_secs(FLOW_TTL_SECS))
+        .await
+        .map_err(|_| ApiError::new(AuthError::InternalError))?;
+    Ok(None)
+}
@@ -1 +1,2 @@
+pub mod permission_sync;
 pub mod roles;
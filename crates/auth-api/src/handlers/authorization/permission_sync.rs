@@ -0,0 +1,109 @@
+use crate::error::ApiError;
+use crate::route_policy::Caller;
+use crate::AppState;
+use auth_core::error::AuthError;
+use auth_core::services::permission_sync::{
+    ChangeQuery, PermissionChangePage, PermissionSnapshot, MAX_PAGE_SIZE,
+};
+use axum::{
+    extract::{Extension, Path, Query, State},
+    Json,
+};
+use serde::Deserialize;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const SYNC_PERMISSION: &str = "permission:sync";
+
+/// Longest a change feed request is held open
+const MAX_WAIT_SECONDS: u64 = 30;
+
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChangeFeedParams {
+    /// Cursor of the snapshot or of the previous page's `next_cursor`
+    pub after: u64,
+    /// Changes per page, at most 500
+    pub limit: Option<usize>,
+    /// Hold the request open up to this many seconds, at most 30, until a
+    /// change arrives
+    pub wait_seconds: Option<u64>,
+    /// Include the state checksum on caught-up pages
+    #[serde(default)]
+    pub checksum: bool,
+}
+
+/// The route requires `permission:sync`; the tenant must be the caller's
+fn authorize(caller: &Caller, tenant_id: Uuid) -> Result<(), ApiError> {
+    if caller.tenant_id != tenant_id {
+        return Err(ApiError::new(AuthError::AuthorizationDenied {
+            permission: SYNC_PERMISSION.to_string(),
+            resource: format!("tenant:{}", tenant_id),
+        }));
+    }
+    Ok(())
+}
+
+/// Full permission state of the tenant
+///
+/// Every role with its permission codes and every unrevoked role
+/// assignment, with the cursor to follow the change feed from and a
+/// checksum of the state.
+#[utoipa::path(
+    get,
+    path = "/auth/permissions/{tenant_id}/snapshot",
+    params(
+        ("tenant_id" = Uuid, Path, description = "Tenant ID")
+    ),
+    responses(
+        (status = 200, description = "Snapshot of the tenant's roles and assignments", body = PermissionSnapshot),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks permission:sync in the tenant")
+    ),
+    tag = "Authorization"
+)]
+pub async fn snapshot(
+    State(state): State<AppState>,
+    Path(tenant_id): Path<Uuid>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<PermissionSnapshot>, ApiError> {
+    authorize(&caller, tenant_id)?;
+    Ok(Json(state.permission_sync.snapshot(tenant_id).await?))
+}
+
+/// Permission changes after a cursor
+///
+/// Changes in the order they were made, each with the current state of the
+/// role or user it concerns. An empty page is held open for `wait_seconds`
+/// so that polling doubles as a subscription. When `resync_required` is
+/// set, or a caught-up page's checksum differs from the caller's, load a
+/// new snapshot.
+#[utoipa::path(
+    get,
+    path = "/auth/permissions/{tenant_id}/changes",
+    params(
+        ("tenant_id" = Uuid, Path, description = "Tenant ID"),
+        ChangeFeedParams
+    ),
+    responses(
+        (status = 200, description = "Page of the change feed", body = PermissionChangePage),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks permission:sync in the tenant")
+    ),
+    tag = "Authorization"
+)]
+pub async fn changes(
+    State(state): State<AppState>,
+    Path(tenant_id): Path<Uuid>,
+    Query(params): Query<ChangeFeedParams>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<PermissionChangePage>, ApiError> {
+    authorize(&caller, tenant_id)?;
+    let query = ChangeQuery {
+        after: params.after,
+        limit: params.limit.unwrap_or(100).min(MAX_PAGE_SIZE),
+        wait: Duration::from_secs(params.wait_seconds.unwrap_or(0).min(MAX_WAIT_SECONDS)),
+        with_checksum: params.checksum,
+    };
+    Ok(Json(state.permission_sync.changes(tenant_id, query).await?))
+}
@@ -1,27 +1,30 @@
 use crate::error::ApiError;
+use crate::route_policy::Caller;
 use crate::AppState;
 use auth_core::models::CreateRoleRequest;
 use axum::{
-    extract::{Json, Path, State},
+    extract::{Extension, Json, Path, State},
     http::StatusCode,
     response::IntoResponse,
 };
 use uuid::Uuid;
 
+pub const READ_PERMISSION: &str = "role:read";
+pub const WRITE_PERMISSION: &str = "role:write";
+
 // ============================================================================
 // Create Role
 // ============================================================================
 
 pub async fn create_role(
     State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
     Json(payload): Json<CreateRoleRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    // 1. Authenticate & Authorize (Middleware or Manual Check)
-    // For now, assuming caller has permission (e.g. from token in middleware)
-
+    // 1. Authorized by the route policy (role:write in the caller's tenant)
     // 2. Validate Request
     // 3. Call Service
-    let tenant_id = Uuid::new_v4(); // Mock tenant for now, should come from auth context
+    let tenant_id = caller.tenant_id;
 
     // 4. Create Role via AuthorizationService (not generic RoleService)
     // We need to inject AuthorizationService into AppState or use RoleService if updated.
@@ -0,0 +1,196 @@
+use crate::bff::BffSession;
+use crate::error::ApiError;
+use crate::handlers::oidc_provider::exchange_authorization_code;
+use crate::AppState;
+use auth_core::error::{AuthError, TokenErrorKind};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+
+/// Refresh the access token when it expires within this many seconds
+const REFRESH_LEEWAY_SECS: i64 = 30;
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct BffLoginRequest {
+    pub client_id: String,
+    pub code: String,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+/// POST /bff/session
+///
+/// Redeems an authorization code on behalf of a BFF client and sets the
+/// encrypted session cookie. Tokens are never returned to the browser.
+pub async fn create_session(
+    State(state): State<AppState>,
+    Json(payload): Json<BffLoginRequest>,
+) -> Result<Response, ApiError> {
+    if !state.bff.is_enabled_for(&payload.client_id) {
+        return Err(ApiError::new(AuthError::ValidationError {
+            message: "client is not configured for BFF mode".to_string(),
+        }));
+    }
+
+    let tokens = exchange_authorization_code(
+        &state,
+        &payload.code,
+        &payload.client_id,
+        payload.redirect_uri.as_deref(),
+        payload.code_verifier.as_deref(),
+    )
+    .await?
+    .tokens;
+
+    let claims = state
+        .identity_service
+        .validate_token(&tokens.access_token)
+        .await
+        .map_err(ApiError::from)?;
+
+    let session = BffSession {
+        client_id: payload.client_id,
+        user_id: tokens.user.id,
+        access_token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        access_expires_at: claims.exp,
+    };
+    let cookie = state
+        .bff
+        .create_session(&session)
+        .await
+        .map_err(ApiError::new)?;
+
+    let mut response = Json(serde_json::json!({
+        "authenticated": true,
+        "user_id": session.user_id,
+    }))
+    .into_response();
+    set_cookie(&mut response, &state.bff.session_cookie(&cookie));
+    Ok(response)
+}
+
+/// GET /bff/userinfo
+///
+/// Returns the profile for the cookie-bound session, transparently refreshing
+/// the server-side access token when it is about to expire.
+pub async fn userinfo(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let session = load_fresh_session(&state, &headers).await?;
+
+    let user = state
+        .identity_service
+        .get_user(session.user_id)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(serde_json::json!({
+        "sub": user.id.to_string(),
+        "name": user.profile_data.get("name").unwrap_or(&serde_json::json!("Unknown")),
+        "email": user.email,
+        "email_verified": user.email_verified,
+        "phone_number": user.phone,
+        "phone_number_verified": user.phone_verified,
+        "updated_at": user.updated_at.timestamp(),
+    })))
+}
+
+/// POST /bff/logout
+pub async fn logout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    if let Some(cookie) = state.bff.read_cookie(&headers) {
+        if let Some((session_id, _)) = state.bff.load(&cookie).await.map_err(ApiError::new)? {
+            state
+                .bff
+                .destroy(&session_id)
+                .await
+                .map_err(ApiError::new)?;
+        }
+    }
+
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    set_cookie(&mut response, &state.bff.clear_cookie());
+    Ok(response)
+}
+
+// ============================================================================
+// Helpers
+// ============================================================================
+
+async fn load_fresh_session(state: &AppState, headers: &HeaderMap) -> Result<BffSession, ApiError> {
+    let unauthorized = || {
+        ApiError::new(AuthError::Unauthorized {
+            message: "No BFF session".to_string(),
+        })
+    };
+
+    let cookie = state.bff.read_cookie(headers).ok_or_else(unauthorized)?;
+    let (session_id, mut session) = state
+        .bff
+        .load(&cookie)
+        .await
+        .map_err(ApiError::new)?
+        .ok_or_else(unauthorized)?;
+
+    let now = chrono::Utc::now().timestamp();
+    if session.access_expires_at - now > REFRESH_LEEWAY_SECS {
+        return Ok(session);
+    }
+
+    let pair = match state
+        .identity_service
+        .refresh_tokens_for_client(&session.refresh_token, &session.client_id)
+        .await
+    {
+        Ok(pair) => pair,
+        Err(AuthError::TokenError { kind }) => {
+            // Refresh token is dead: drop the session so the SPA re-authenticates
+            let _ = state.bff.destroy(&session_id).await;
+            return Err(ApiError::new(AuthError::TokenError { kind }));
+        }
+        Err(e) => return Err(ApiError::from(e)),
+    };
+
+    session.access_token = pair.access_token.token;
+    session.refresh_token = pair.refresh_token;
+    session.access_expires_at = state
+        .identity_service
+        .validate_token(&session.access_token)
+        .await
+        .map(|claims| claims.exp)
+        .map_err(|_| {
+            ApiError::new(AuthError::TokenError {
+                kind: TokenErrorKind::Invalid,
+            })
+        })?;
+
+    state
+        .bff
+        .store(&session_id, &session)
+        .await
+        .map_err(ApiError::new)?;
+
+    Ok(session)
+}
+
+fn set_cookie(response: &mut Response, cookie: &str) {
+    if let Ok(value) = HeaderValue::from_str(cookie) {
+        response.headers_mut().append(header::SET_COOKIE, value);
+    }
+}
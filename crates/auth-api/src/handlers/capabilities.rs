@@ -0,0 +1,134 @@
+use crate::route_policy::Caller;
+use crate::AppState;
+use auth_config::AppConfig;
+use auth_core::services::feature_flags::FeatureEvaluation;
+use auth_core::services::service_account::JWT_BEARER_GRANT;
+use auth_extension::WebhookVersion;
+use axum::{
+    extract::{Extension, State},
+    Json,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Grants the token endpoint implements
+const GRANT_TYPES: &[&str] = &["authorization_code", "client_credentials", JWT_BEARER_GRANT];
+
+/// What this server supports, for clients to detect features rather than
+/// assume them from the version
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct Capabilities {
+    pub version: String,
+    /// Commit the server was built from, when the build knew it
+    pub git_sha: Option<String>,
+    /// Features compiled in, and those switched on or off in configuration
+    pub features: BTreeMap<String, bool>,
+    pub grant_types: Vec<String>,
+    /// Supported versions of each protocol, oldest first
+    pub protocols: BTreeMap<String, Vec<String>>,
+}
+
+impl Capabilities {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let mut features: BTreeMap<String, bool> = config
+            .features
+            .enabled_features
+            .iter()
+            .map(|(name, enabled)| (name.clone(), *enabled))
+            .collect();
+        features.extend([
+            ("admin_ui".to_string(), cfg!(feature = "admin-ui")),
+            ("webauthn".to_string(), true),
+            ("saml".to_string(), true),
+            ("captcha".to_string(), config.security.captcha.enabled),
+            ("ssh_ca".to_string(), config.security.ssh_ca.enabled),
+            ("quotas".to_string(), config.server.quotas.enabled),
+        ]);
+
+        let versions = |versions: &[&str]| versions.iter().map(|v| v.to_string()).collect();
+        let protocols = BTreeMap::from([
+            ("api".to_string(), versions(&["v1"])),
+            ("oauth2".to_string(), versions(&["2.0"])),
+            ("openid_connect".to_string(), versions(&["1.0"])),
+            ("saml".to_string(), versions(&["2.0"])),
+            (
+                "webhooks".to_string(),
+                WebhookVersion::ALL
+                    .iter()
+                    .map(|v| v.as_str().to_string())
+                    .collect(),
+            ),
+        ]);
+
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_sha: option_env!("GIT_COMMIT").map(str::to_string),
+            features,
+            grant_types: GRANT_TYPES.iter().map(|g| g.to_string()).collect(),
+            protocols,
+        }
+    }
+}
+
+/// Server capabilities
+#[utoipa::path(
+    get,
+    path = "/.well-known/uac-capabilities",
+    responses(
+        (status = 200, description = "Version, build, features, grant types and protocol versions", body = Capabilities)
+    ),
+    tag = "Health"
+)]
+pub async fn capabilities(State(state): State<AppState>) -> Json<Capabilities> {
+    Json(state.capabilities.as_ref().clone())
+}
+
+/// Features for the caller
+///
+/// Which features are on for the signed-in user, including those rolled
+/// out to a cohort the user is in, so clients show only flows the server
+/// will accept from them.
+#[utoipa::path(
+    get,
+    path = "/auth/features",
+    responses(
+        (status = 200, description = "Features by name and the caller's cohorts", body = FeatureEvaluation),
+        (status = 401, description = "Missing or invalid bearer token")
+    ),
+    tag = "Authentication"
+)]
+pub async fn features(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> Json<FeatureEvaluation> {
+    Json(state.features.evaluate(caller.user_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_flags_do_not_override_compiled_features() {
+        let mut config = AppConfig::default();
+        config
+            .features
+            .enabled_features
+            .insert("passwordless".to_string(), true);
+        config
+            .features
+            .enabled_features
+            .insert("admin_ui".to_string(), true);
+
+        let capabilities = Capabilities::from_config(&config);
+        assert!(capabilities.features["passwordless"]);
+        assert_eq!(
+            capabilities.features["admin_ui"],
+            cfg!(feature = "admin-ui")
+        );
+        assert!(capabilities
+            .grant_types
+            .contains(&JWT_BEARER_GRANT.to_string()));
+        assert_eq!(capabilities.protocols["webhooks"], vec!["v1", "v2"]);
+    }
+}
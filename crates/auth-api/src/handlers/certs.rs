@@ -1,10 +1,38 @@
 use crate::AppState;
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
 
 /// GET /auth/certs
-/// Returns JWKS public keys
-pub async fn jwks(State(state): State<AppState>) -> Result<impl IntoResponse, StatusCode> {
-    // Retrieve JWKS from the token engine (backed by KeyManager)
-    let jwks = state.identity_service.get_jwks().await;
-    Ok(Json(jwks))
+/// Returns JWKS public keys, cacheable until the next key change and
+/// revalidated with `If-None-Match`
+pub async fn jwks(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    // Served from the shared cache; the token engine rebuilds it from the PEM
+    let signing = crate::warmup::cached_jwks(&state).await;
+    let document = state.jwks.document(&signing, Utc::now());
+
+    let cache_headers = [
+        (header::ETAG, document.etag.clone()),
+        (
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", document.max_age),
+        ),
+    ];
+    let unchanged = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == document.etag)
+        });
+    if unchanged {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+    (cache_headers, Json(document.body)).into_response()
 }
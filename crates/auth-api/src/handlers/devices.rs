@@ -0,0 +1,214 @@
+use crate::error::ApiError;
+use crate::route_policy::Caller;
+use crate::AppState;
+use auth_core::error::AuthError;
+use auth_core::models::{
+    CreateBootstrapTokenRequest, DeviceCertificate, DeviceEnrollmentRequest, DeviceRenewalRequest,
+    RevokeDeviceCertificateRequest,
+};
+use axum::{
+    extract::{Extension, Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+use validator::Validate;
+
+pub const MANAGE_DEVICES_PERMISSION: &str = "tenant:manage_devices";
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct BootstrapTokenResponse {
+    /// Shown once; hand it to the device out of band
+    pub token: String,
+    pub device_id: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct DeviceCertificateResponse {
+    pub serial: String,
+    pub certificate: String,
+    /// Intermediate CA certificate to send after `certificate`
+    pub chain: String,
+    pub not_after: DateTime<Utc>,
+}
+
+fn certificate_response(
+    state: &AppState,
+    certificate: DeviceCertificate,
+) -> Result<Json<DeviceCertificateResponse>, ApiError> {
+    Ok(Json(DeviceCertificateResponse {
+        chain: state.devices.chain_pem(certificate.tenant_id)?,
+        serial: certificate.serial,
+        certificate: certificate.certificate_pem,
+        not_after: certificate.not_after,
+    }))
+}
+
+/// Create a single-use bootstrap token for a device identity
+#[utoipa::path(
+    post,
+    path = "/devices/bootstrap-tokens",
+    request_body = CreateBootstrapTokenRequest,
+    responses(
+        (status = 201, description = "Token created", body = BootstrapTokenResponse),
+        (status = 400, description = "Invalid device id"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks tenant:manage_devices in the tenant")
+    ),
+    tag = "Devices"
+)]
+pub async fn create_bootstrap_token(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<CreateBootstrapTokenRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(AuthError::from)?;
+
+    let (token, record) = state
+        .devices
+        .create_bootstrap_token(caller.tenant_id, &payload.device_id, caller.user_id)
+        .await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(BootstrapTokenResponse {
+            token,
+            device_id: record.device_id,
+            expires_at: record.expires_at,
+        }),
+    ))
+}
+
+/// Exchange a bootstrap token and CSR for the device's first certificate
+#[utoipa::path(
+    post,
+    path = "/devices/enroll",
+    request_body = DeviceEnrollmentRequest,
+    responses(
+        (status = 200, description = "Certificate issued", body = DeviceCertificateResponse),
+        (status = 400, description = "Malformed CSR or bad signature"),
+        (status = 401, description = "Unknown, expired or used bootstrap token")
+    ),
+    tag = "Devices"
+)]
+pub async fn enroll(
+    State(state): State<AppState>,
+    Json(payload): Json<DeviceEnrollmentRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(AuthError::from)?;
+    let certificate = state.devices.enroll(payload).await?;
+    certificate_response(&state, certificate)
+}
+
+/// Renew a certificate near expiry with a CSR signed by its key
+#[utoipa::path(
+    post,
+    path = "/devices/renew",
+    request_body = DeviceRenewalRequest,
+    responses(
+        (status = 200, description = "Certificate renewed", body = DeviceCertificateResponse),
+        (status = 400, description = "Not within the renewal window, or key mismatch"),
+        (status = 401, description = "Certificate revoked or expired"),
+        (status = 404, description = "Certificate not issued by this tenant")
+    ),
+    tag = "Devices"
+)]
+pub async fn renew(
+    State(state): State<AppState>,
+    Json(payload): Json<DeviceRenewalRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(AuthError::from)?;
+    let certificate = state.devices.renew(payload).await?;
+    certificate_response(&state, certificate)
+}
+
+/// List certificates issued in the caller's tenant
+#[utoipa::path(
+    get,
+    path = "/devices/certificates",
+    responses(
+        (status = 200, description = "Issued certificates", body = [DeviceCertificate]),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks tenant:manage_devices in the tenant")
+    ),
+    tag = "Devices"
+)]
+pub async fn list_certificates(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> Result<impl IntoResponse, ApiError> {
+    let certificates = state.devices.list(caller.tenant_id).await?;
+    Ok(Json(serde_json::json!({ "certificates": certificates })))
+}
+
+/// Revoke a certificate; it appears on the tenant CRL until it expires
+#[utoipa::path(
+    post,
+    path = "/devices/certificates/{serial}/revoke",
+    params(("serial" = String, Path, description = "Hex certificate serial")),
+    request_body = RevokeDeviceCertificateRequest,
+    responses(
+        (status = 204, description = "Certificate revoked"),
+        (status = 403, description = "Caller lacks tenant:manage_devices in the tenant"),
+        (status = 404, description = "Certificate not found")
+    ),
+    tag = "Devices"
+)]
+pub async fn revoke_certificate(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(serial): Path<String>,
+    Json(payload): Json<RevokeDeviceCertificateRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(AuthError::from)?;
+
+    state
+        .devices
+        .revoke(
+            caller.tenant_id,
+            &serial,
+            payload.reason.as_deref(),
+            caller.user_id,
+        )
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DER encoded CRL for the tenant's device CA
+#[utoipa::path(
+    get,
+    path = "/devices/{tenant_id}/crl",
+    params(("tenant_id" = Uuid, Path, description = "Tenant ID")),
+    responses(
+        (status = 200, description = "Certificate revocation list", content_type = "application/pkix-crl")
+    ),
+    tag = "Devices"
+)]
+pub async fn crl(
+    State(state): State<AppState>,
+    Path(tenant_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let crl = state.devices.crl(tenant_id).await?;
+    Ok(([(header::CONTENT_TYPE, "application/pkix-crl")], crl))
+}
+
+/// The tenant's intermediate CA certificate, PEM encoded
+#[utoipa::path(
+    get,
+    path = "/devices/{tenant_id}/cacerts",
+    params(("tenant_id" = Uuid, Path, description = "Tenant ID")),
+    responses(
+        (status = 200, description = "CA certificate", content_type = "application/x-pem-file")
+    ),
+    tag = "Devices"
+)]
+pub async fn ca_certificates(
+    State(state): State<AppState>,
+    Path(tenant_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let chain = state.devices.chain_pem(tenant_id)?;
+    Ok(([(header::CONTENT_TYPE, "application/x-pem-file")], chain))
+}
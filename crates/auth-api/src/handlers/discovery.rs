@@ -1,11 +1,29 @@
+use crate::AppState;
 use auth_protocols::discovery::generate_oidc_metadata;
-use axum::{extract::Json, http::StatusCode, response::IntoResponse};
-use std::env;
+use axum::{
+    extract::{Json, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+};
 
 /// GET /.well-known/openid-configuration
-pub async fn oidc_configuration() -> Result<impl IntoResponse, StatusCode> {
-    let base_url = env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:8080".to_string());
-    let metadata = generate_oidc_metadata(&base_url);
+///
+/// On a tenant's own domain the document names the tenant's issuer, so it
+/// matches the `iss` of the ID tokens issued to that tenant.
+pub async fn oidc_configuration(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    let base_url = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|host| state.issuers.for_host(host))
+        .unwrap_or(state.issuers.default_issuer());
+    let mut metadata = generate_oidc_metadata(base_url);
+
+    let lifetimes = state.token_ttl_policy.defaults();
+    metadata.access_token_lifetime = Some(lifetimes.access_ttl.num_seconds() as u64);
+    metadata.refresh_token_lifetime = Some(lifetimes.refresh_ttl.num_seconds() as u64);
 
     Ok(Json(metadata))
 }
@@ -1,4 +1,6 @@
-use axum::{response::IntoResponse, Json};
+use crate::AppState;
+use auth_cache::CacheState;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use serde_json::json;
 
 /// Health check endpoint
@@ -6,15 +8,48 @@ use serde_json::json;
     get,
     path = "/health",
     responses(
-        (status = 200, description = "Service is healthy")
+        (status = 200, description = "Service is up; `status` is `degraded` while the cache runs without Redis")
     ),
     tag = "Health"
 )]
-pub async fn health_check() -> impl IntoResponse {
-    const MESSAGE: &str = "SSO Platform API is healthy";
+pub async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
+    let cache = state.cache.status();
+    let (status, message) = if cache.state == CacheState::Healthy {
+        ("ok", "SSO Platform API is healthy")
+    } else {
+        ("degraded", "SSO Platform API is running without Redis")
+    };
     Json(json!({
-        "status": "ok",
-        "message": MESSAGE,
-        "version": env!("CARGO_PKG_VERSION")
+        "status": status,
+        "message": message,
+        "version": env!("CARGO_PKG_VERSION"),
+        "cache": cache
     }))
 }
+
+/// Readiness endpoint; 503 until startup cache warm-up has finished, and
+/// again from the shutdown signal on
+#[utoipa::path(
+    get,
+    path = "/ready",
+    responses(
+        (status = 200, description = "Service is ready for traffic"),
+        (status = 503, description = "Service is still warming up, or shutting down (`status` is `draining`)")
+    ),
+    tag = "Health"
+)]
+pub async fn readiness(State(state): State<AppState>) -> impl IntoResponse {
+    if state.readiness.is_ready() {
+        (StatusCode::OK, Json(json!({ "status": "ready" })))
+    } else if state.readiness.is_draining() {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "draining" })),
+        )
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "warming_up" })),
+        )
+    }
+}
@@ -0,0 +1,30 @@
+use crate::error::ApiError;
+use crate::tenant_scope::TenantScope;
+use axum::{extract::Query, response::IntoResponse, Json};
+use serde::Deserialize;
+
+const DEFAULT_LIMIT: u32 = 20;
+const MAX_LIMIT: u32 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct LoginHistoryQuery {
+    pub limit: Option<u32>,
+}
+
+/// GET /auth/login-history
+///
+/// Recent logins for the bearer of the access token, with resolved location
+pub async fn login_history(
+    scope: TenantScope,
+    Query(query): Query<LoginHistoryQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user_id = scope.user_id()?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let events = scope
+        .login_events()
+        .recent(user_id, limit)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(serde_json::json!({ "events": events })))
+}
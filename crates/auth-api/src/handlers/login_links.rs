@@ -0,0 +1,146 @@
+use crate::error::ApiError;
+use crate::route_policy::Caller;
+use crate::AppState;
+use auth_core::error::AuthError;
+use auth_core::models::SensitiveString;
+use auth_core::services::login_history::LoginEvent;
+use auth_core::services::login_link::{IssueLoginLinkRequest, LinkOutcome};
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+pub const ISSUE_PERMISSION: &str = "login_link:issue";
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct RedeemLoginLinkRequest {
+    /// Token from the link, or the continuation of an earlier attempt
+    #[schema(value_type = String)]
+    pub token: SensitiveString,
+    /// Must be the redirect the link was issued for
+    pub redirect_uri: String,
+    /// Current TOTP code, for users with MFA
+    pub mfa_code: Option<String>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LoginLinkResponse {
+    SignedIn {
+        access_token: String,
+        refresh_token: String,
+        expires_in: u64,
+        redirect_uri: String,
+    },
+    /// Send `continuation` back as the token, with `mfa_code`
+    MfaRequired {
+        continuation: String,
+        expires_at: DateTime<Utc>,
+    },
+}
+
+/// Email a user a one-time login link
+///
+/// The link goes to the user's verified email address; the caller only
+/// learns where it was sent. It signs the user in once, lands them on
+/// `redirect_uri` and still asks users with MFA for a code.
+#[utoipa::path(
+    post,
+    path = "/auth/users/{user_id}/login-links",
+    params(
+        ("user_id" = Uuid, Path, description = "User to sign in")
+    ),
+    request_body = IssueLoginLinkRequest,
+    responses(
+        (status = 201, description = "Link sent", body = IssuedLoginLink),
+        (status = 400, description = "Redirect not allowed, or no verified email"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks login_link:issue in the tenant"),
+        (status = 429, description = "Too many links issued for the user")
+    ),
+    tag = "User Management"
+)]
+pub async fn issue_login_link(
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<IssueLoginLinkRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = state.identity_service.get_user(user_id).await?;
+    if user.tenant_id != caller.tenant_id {
+        return Err(ApiError::new(AuthError::UserNotFound));
+    }
+    let issued = state
+        .login_links
+        .issue(&user, caller.user_id, payload)
+        .await?;
+    Ok((StatusCode::CREATED, Json(issued)))
+}
+
+/// Sign in with a one-time login link
+#[utoipa::path(
+    post,
+    path = "/auth/login-links/redeem",
+    request_body = RedeemLoginLinkRequest,
+    responses(
+        (status = 200, description = "Signed in, or a TOTP code is needed", body = LoginLinkResponse),
+        (status = 401, description = "Unknown, expired or used link, or wrong redirect")
+    ),
+    tag = "Authentication"
+)]
+pub async fn redeem_login_link(
+    State(state): State<AppState>,
+    Extension(request_id): Extension<Uuid>,
+    Json(payload): Json<RedeemLoginLinkRequest>,
+) -> Result<Json<LoginLinkResponse>, ApiError> {
+    let with_id = |e: AuthError| ApiError::new(e).with_request_id(request_id);
+    let grant = state
+        .login_links
+        .open(payload.token.expose(), &payload.redirect_uri)
+        .await
+        .map_err(with_id)?;
+    let user = state
+        .identity_service
+        .get_user(grant.user_id)
+        .await
+        .map_err(with_id)?;
+
+    match state
+        .login_links
+        .finish(grant, &user, payload.mfa_code.as_deref())
+        .await
+        .map_err(with_id)?
+    {
+        LinkOutcome::MfaRequired {
+            continuation,
+            expires_at,
+        } => Ok(Json(LoginLinkResponse::MfaRequired {
+            continuation,
+            expires_at,
+        })),
+        LinkOutcome::SignedIn { redirect_uri } => {
+            let tokens = state
+                .identity_service
+                .issue_tokens_for_user(&user, user.tenant_id, None, None, None)
+                .await
+                .map_err(with_id)?;
+            let event = LoginEvent::new(user.id, user.tenant_id, None, None, true);
+            if let Err(e) = state.login_history.record(event).await {
+                warn!(request_id = %request_id, error = ?e, "Failed to record login event");
+            }
+            info!(request_id = %request_id, user_id = %user.id, "Signed in with login link");
+            Ok(Json(LoginLinkResponse::SignedIn {
+                access_token: tokens.access_token,
+                refresh_token: tokens.refresh_token,
+                expires_in: tokens.expires_in,
+                redirect_uri,
+            }))
+        }
+    }
+}
@@ -14,9 +14,14 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::error::ApiError;
+use auth_config::AuthMethod;
 use auth_core::error::{AuthError, TokenErrorKind};
+use auth_core::models::SensitiveString;
 use auth_core::services::{
-    identity::IdentityService, lazy_registration::LazyRegistrationService, otp_service::OtpService,
+    auth_methods::AuthMethodPolicies,
+    identity::IdentityService,
+    lazy_registration::LazyRegistrationService,
+    otp_service::{OtpError, OtpService},
 };
 use auth_db::repositories::otp_repository::OtpRepository;
 
@@ -27,7 +32,7 @@ use auth_db::repositories::otp_repository::OtpRepository;
 #[derive(Debug, Deserialize)]
 pub struct LoginOtpRequest {
     pub identifier: String,
-    pub otp: String,
+    pub otp: SensitiveString,
     pub session_id: Uuid,
     pub tenant_id: Uuid,
 }
@@ -53,8 +58,11 @@ pub async fn login_with_otp(
     State(otp_repo): State<Arc<OtpRepository>>,
     State(lazy_service): State<Arc<LazyRegistrationService>>,
     State(identity_service): State<Arc<IdentityService>>,
+    State(auth_methods): State<Arc<AuthMethodPolicies>>,
     Json(payload): Json<LoginOtpRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
+    auth_methods.require(payload.tenant_id, AuthMethod::Otp)?;
+
     // 1. Verify OTP
     // Fetch session
     let (session, otp_hash) =
@@ -77,24 +85,26 @@ pub async fn login_with_otp(
             kind: TokenErrorKind::Expired,
         }));
     }
-    // Verify hash
-    if !otp_service
-        .verify_otp(&payload.otp, &otp_hash)
-        .map_err(|_| ApiError::new(AuthError::InternalError))?
+    // Verify hash and consume the session; only one concurrent request can win
+    match otp_service
+        .verify_with_store(
+            otp_repo.as_ref(),
+            payload.session_id,
+            payload.otp.expose(),
+            &otp_hash,
+        )
+        .await
     {
-        otp_repo
-            .increment_attempts(payload.session_id)
-            .await
-            .map_err(|_| ApiError::new(AuthError::InternalError))?;
-        return Err(ApiError::new(AuthError::InvalidCredentials));
+        Ok(_) => {}
+        Err(OtpError::Invalid) => return Err(ApiError::new(AuthError::InvalidCredentials)),
+        Err(OtpError::AlreadyVerified) => {
+            return Err(ApiError::new(AuthError::TokenError {
+                kind: TokenErrorKind::Replayed,
+            }));
+        }
+        Err(e) => return Err(e.into()),
     }
 
-    // Mark verified
-    otp_repo
-        .mark_verified(payload.session_id)
-        .await
-        .map_err(|_| ApiError::new(AuthError::InternalError))?;
-
     // 2. Identify User
     let identifier_type =
         auth_core::models::validation::detect_identifier_type(&payload.identifier);
@@ -107,7 +117,7 @@ pub async fn login_with_otp(
 
     // 4. Issue Tokens
     let auth_response = identity_service
-        .issue_tokens_for_user(&user, payload.tenant_id, None, None)
+        .issue_tokens_for_user(&user, payload.tenant_id, None, None, None)
         .await
         .map_err(|_| ApiError::new(AuthError::InternalError))?;
 
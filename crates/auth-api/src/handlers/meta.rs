@@ -0,0 +1,104 @@
+use crate::i18n;
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// The catalogs only change with a release; revalidation is cheap
+const MAX_AGE_SECONDS: u64 = 3600;
+
+/// Error messages by code, in the language the client prefers
+#[utoipa::path(
+    get,
+    path = "/meta/errors",
+    params(
+        ("Accept-Language" = Option<String>, Header, description = "Preferred languages; English when none is available")
+    ),
+    responses(
+        (status = 200, description = "`locale` of the catalog and `messages` keyed by error code"),
+        (status = 304, description = "The catalog matches `If-None-Match`")
+    ),
+    tag = "Health"
+)]
+pub async fn error_catalog(headers: HeaderMap) -> Response {
+    let accept_language = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok());
+    let catalog = i18n::ERRORS.negotiate(accept_language);
+
+    let cache_headers = [
+        (
+            header::ETAG,
+            HeaderValue::from_str(&catalog.etag).expect("hex etag"),
+        ),
+        (
+            header::CACHE_CONTROL,
+            HeaderValue::from_str(&format!("public, max-age={}", MAX_AGE_SECONDS))
+                .expect("static cache control"),
+        ),
+        (
+            header::CONTENT_LANGUAGE,
+            HeaderValue::from_static(catalog.locale),
+        ),
+        (header::VARY, HeaderValue::from_static("Accept-Language")),
+    ];
+    let unchanged = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == catalog.etag)
+        });
+    if unchanged {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+    (cache_headers, Json(catalog.body.clone())).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_catalog_is_negotiated_and_revalidated() {
+        let response =
+            error_catalog(request(&[(header::ACCEPT_LANGUAGE, "fr-FR, en;q=0.5")])).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_LANGUAGE], "fr");
+        assert_eq!(response.headers()[header::VARY], "Accept-Language");
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let revalidated = error_catalog(request(&[
+            (header::ACCEPT_LANGUAGE, "fr"),
+            (header::IF_NONE_MATCH, &etag),
+        ]))
+        .await;
+        assert_eq!(revalidated.status(), StatusCode::NOT_MODIFIED);
+
+        // Another language is another representation
+        let german = error_catalog(request(&[
+            (header::ACCEPT_LANGUAGE, "de"),
+            (header::IF_NONE_MATCH, &etag),
+        ]))
+        .await;
+        assert_eq!(german.status(), StatusCode::OK);
+        assert_eq!(german.headers()[header::CONTENT_LANGUAGE], "de");
+
+        let fallback = error_catalog(HeaderMap::new()).await;
+        assert_eq!(fallback.headers()[header::CONTENT_LANGUAGE], "en");
+    }
+}
@@ -3,15 +3,32 @@ pub mod auth_flow;
 pub mod auth_oidc;
 pub mod auth_saml;
 pub mod authorization;
+pub mod bff;
+pub mod capabilities;
 pub mod certs;
+pub mod devices;
 pub mod discovery;
 pub mod health;
 pub mod lazy_reg;
+pub mod login_history;
+pub mod login_links;
 pub mod login_otp;
+pub mod meta;
+pub mod oauth_clients;
 pub mod oidc_provider;
 pub mod otp;
 pub mod profile;
+pub mod push_mfa;
+pub mod recovery_codes;
 pub mod register;
+pub mod remember_me;
+pub mod service_accounts;
+pub mod session_events;
+pub mod ssh;
+pub mod sso;
+pub mod tenants;
+pub mod tokens;
 pub mod users;
 pub mod verification;
+pub mod webauthn;
 pub mod workflow;
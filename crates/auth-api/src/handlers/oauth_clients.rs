@@ -0,0 +1,143 @@
+//! Dynamic client registration endpoints (RFC 7591, RFC 7592)
+//!
+//! Registering takes a tenant administrator's access token; the client is
+//! created in the caller's tenant. The registration is then managed with
+//! the registration access token returned alongside it, sent as a bearer
+//! token.
+
+use crate::route_policy::Caller;
+use crate::AppState;
+use auth_protocols::client_registration::{
+    ClientInformation, ClientMetadata, ClientRegistrationError,
+};
+use axum::{
+    extract::{rejection::JsonRejection, Extension, Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+pub const MANAGE_CLIENTS_PERMISSION: &str = "tenant:manage_clients";
+
+/// Error body of RFC 7591 section 3.2.2
+pub struct RegistrationError(ClientRegistrationError);
+
+impl From<ClientRegistrationError> for RegistrationError {
+    fn from(error: ClientRegistrationError) -> Self {
+        Self(error)
+    }
+}
+
+impl IntoResponse for RegistrationError {
+    fn into_response(self) -> Response {
+        let status = match &self.0 {
+            ClientRegistrationError::InvalidToken | ClientRegistrationError::InvalidClient => {
+                StatusCode::UNAUTHORIZED
+            }
+            ClientRegistrationError::Store(e) => {
+                tracing::error!("Client registration failed: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            _ => StatusCode::BAD_REQUEST,
+        };
+        let mut body = json!({ "error": self.0.code() });
+        if !matches!(self.0, ClientRegistrationError::Store(_)) {
+            body["error_description"] = json!(self.0.to_string());
+        }
+        let mut response = (status, Json(body)).into_response();
+        if status == StatusCode::UNAUTHORIZED {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static("Bearer error=\"invalid_token\""),
+            );
+        }
+        response
+    }
+}
+
+fn metadata(
+    payload: Result<Json<ClientMetadata>, JsonRejection>,
+) -> Result<ClientMetadata, RegistrationError> {
+    payload.map(|Json(metadata)| metadata).map_err(|e| {
+        RegistrationError(ClientRegistrationError::InvalidClientMetadata(
+            e.body_text(),
+        ))
+    })
+}
+
+fn registration_token(headers: &HeaderMap) -> Result<&str, RegistrationError> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(RegistrationError(ClientRegistrationError::InvalidToken))
+}
+
+/// Client information carries credentials, so it must not be cached
+fn respond(state: &AppState, status: StatusCode, mut info: ClientInformation) -> Response {
+    info.registration_client_uri = Some(format!(
+        "{}/oauth/register/{}",
+        state.issuers.default_issuer(),
+        info.client_id
+    ));
+    let mut response = (status, Json(info)).into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    headers.insert(header::PRAGMA, HeaderValue::from_static("no-cache"));
+    response
+}
+
+/// POST /oauth/register
+pub async fn register(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    payload: Result<Json<ClientMetadata>, JsonRejection>,
+) -> Result<Response, RegistrationError> {
+    let info = state
+        .clients
+        .register(caller.tenant_id, metadata(payload)?)
+        .await?;
+    Ok(respond(&state, StatusCode::CREATED, info))
+}
+
+/// GET /oauth/register/:client_id
+pub async fn read(
+    State(state): State<AppState>,
+    Path(client_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, RegistrationError> {
+    let info = state
+        .clients
+        .read(&client_id, registration_token(&headers)?)
+        .await?;
+    Ok(respond(&state, StatusCode::OK, info))
+}
+
+/// PUT /oauth/register/:client_id
+pub async fn update(
+    State(state): State<AppState>,
+    Path(client_id): Path<String>,
+    headers: HeaderMap,
+    payload: Result<Json<ClientMetadata>, JsonRejection>,
+) -> Result<Response, RegistrationError> {
+    let token = registration_token(&headers)?;
+    let info = state
+        .clients
+        .update(&client_id, token, metadata(payload)?)
+        .await?;
+    Ok(respond(&state, StatusCode::OK, info))
+}
+
+/// DELETE /oauth/register/:client_id
+pub async fn delete(
+    State(state): State<AppState>,
+    Path(client_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, RegistrationError> {
+    state
+        .clients
+        .delete(&client_id, registration_token(&headers)?)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
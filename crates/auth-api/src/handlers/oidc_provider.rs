@@ -1,16 +1,34 @@
+use crate::error::oauth::{OAuthError, OAuthErrorCode};
 use crate::error::ApiError;
+use crate::middleware::FrameEmbeddable;
+use crate::response_mode::authorization_response;
 use crate::AppState;
+use auth_config::ResponseMode;
 use auth_core::error::AuthError;
+use auth_core::models::OAuthClient;
+use auth_core::services::id_token::{requests_openid, IdTokenClaims};
+use auth_core::services::identity::AuthResponse;
+use auth_core::services::nonce_store::NonceNamespace;
+use auth_core::services::service_account::JWT_BEARER_GRANT;
+use auth_core::services::timing::constant_time_eq;
+use auth_protocols::client_registration::{
+    ClientRegistrationError, GRANT_AUTHORIZATION_CODE, GRANT_CLIENT_CREDENTIALS,
+    GRANT_REFRESH_TOKEN,
+};
+use auth_protocols::discovery::generate_oidc_metadata;
 use axum::{
-    extract::{Form, Query, State},
-    http::HeaderMap,
-    response::{IntoResponse, Redirect},
+    extract::{
+        rejection::{FormRejection, QueryRejection},
+        Form, Query, State,
+    },
+    http::{header, HeaderMap, HeaderValue},
+    response::{Html, IntoResponse, Redirect, Response},
     Json,
 };
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::time::Duration;
+use std::env;
 use uuid::Uuid;
 
 // ============================================================================
@@ -27,6 +45,12 @@ pub struct AuthRequestState {
     pub code_challenge: Option<String>,
     pub code_challenge_method: Option<String>,
     pub user_id: Option<Uuid>,
+    /// Client session id for the `sid` claim
+    #[serde(default)]
+    pub sid: Option<String>,
+    /// SSO session the code was issued under; the tokens are bound to it
+    #[serde(default)]
+    pub session_id: Option<Uuid>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,6 +63,10 @@ pub struct AuthorizeParams {
     pub nonce: Option<String>,
     pub code_challenge: Option<String>,
     pub code_challenge_method: Option<String>,
+    /// OIDC `prompt` parameter. Only `none` changes behaviour today.
+    pub prompt: Option<String>,
+    /// `query`, `fragment` or `form_post`, as allowed for the client
+    pub response_mode: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -46,12 +74,124 @@ pub struct TokenRequest {
     pub grant_type: String,
     pub code: Option<String>,
     pub redirect_uri: Option<String>,
+    /// Not sent with the JWT bearer grant, where the assertion identifies the caller
+    #[serde(default)]
     pub client_id: String,
     pub client_secret: Option<String>, // Basic Auth or Post body
     pub code_verifier: Option<String>, // PKCE
     pub refresh_token: Option<String>,
+    /// Signed JWT for the RFC 7523 JWT bearer grant
+    pub assertion: Option<String>,
+    pub scope: Option<String>,
 }
 
+// ============================================================================
+// Session Management (OpenID Connect Session Management 1.0)
+// ============================================================================
+
+/// Cookie holding the OP browser state. It must be readable from JavaScript
+/// because the check_session_iframe hashes it client-side.
+pub const BROWSER_STATE_COOKIE: &str = "op_bs";
+
+/// Derives the OP browser state from the SSO session so it changes whenever
+/// the user logs in or out.
+pub fn browser_state_for_session(session_id: Uuid) -> String {
+    let digest = Sha256::digest(session_id.as_bytes());
+    URL_SAFE_NO_PAD.encode(digest)
+}
+
+/// Computes `session_state` as `base64url(sha256(client_id origin browser_state salt)).salt`.
+pub fn compute_session_state(
+    client_id: &str,
+    origin: &str,
+    browser_state: &str,
+    salt: &str,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(client_id.as_bytes());
+    hasher.update(b" ");
+    hasher.update(origin.as_bytes());
+    hasher.update(b" ");
+    hasher.update(browser_state.as_bytes());
+    hasher.update(b" ");
+    hasher.update(salt.as_bytes());
+    format!("{}.{}", URL_SAFE_NO_PAD.encode(hasher.finalize()), salt)
+}
+
+/// Extracts `scheme://host[:port]` from a redirect URI.
+fn origin_of(uri: &str) -> &str {
+    match uri.find("://") {
+        Some(idx) => {
+            let rest = &uri[idx + 3..];
+            match rest.find(['/', '?', '#']) {
+                Some(end) => &uri[..idx + 3 + end],
+                None => uri,
+            }
+        }
+        None => uri,
+    }
+}
+
+pub(crate) fn append_query(uri: &str, query: &str) -> String {
+    let separator = if uri.contains('?') { '&' } else { '?' };
+    format!("{}{}{}", uri, separator, query)
+}
+
+/// GET /auth/check_session
+///
+/// The OP iframe polled by relying parties via `postMessage("client_id session_state")`.
+/// Replies with `unchanged`, `changed` or `error`.
+pub async fn check_session_iframe() -> Response {
+    let mut response = Html(CHECK_SESSION_HTML).into_response();
+    response.extensions_mut().insert(FrameEmbeddable);
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+const CHECK_SESSION_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>check_session_iframe</title></head>
+<body>
+<script>
+(function () {
+  function readCookie(name) {
+    var parts = document.cookie.split(';');
+    for (var i = 0; i < parts.length; i++) {
+      var kv = parts[i].trim().split('=');
+      if (kv[0] === name) { return decodeURIComponent(kv.slice(1).join('=')); }
+    }
+    return '';
+  }
+  function b64url(buf) {
+    var bin = '';
+    var bytes = new Uint8Array(buf);
+    for (var i = 0; i < bytes.length; i++) { bin += String.fromCharCode(bytes[i]); }
+    return btoa(bin).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '');
+  }
+  window.addEventListener('message', function (e) {
+    if (typeof e.data !== 'string') { return; }
+    var msg = e.data.split(' ');
+    if (msg.length !== 2) { e.source.postMessage('error', e.origin); return; }
+    var clientId = msg[0];
+    var sessionState = msg[1].split('.');
+    if (sessionState.length !== 2) { e.source.postMessage('error', e.origin); return; }
+    var salt = sessionState[1];
+    var input = clientId + ' ' + e.origin + ' ' + readCookie('op_bs') + ' ' + salt;
+    crypto.subtle.digest('SHA-256', new TextEncoder().encode(input)).then(function (digest) {
+      var status = b64url(digest) === sessionState[0] ? 'unchanged' : 'changed';
+      e.source.postMessage(status, e.origin);
+    }, function () {
+      e.source.postMessage('error', e.origin);
+    });
+  }, false);
+})();
+</script>
+</body>
+</html>
+"#;
+
 // ============================================================================
 // Authorize Endpoint (GET /auth/authorize)
 // ============================================================================
@@ -59,49 +199,105 @@ pub struct TokenRequest {
 pub async fn authorize(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Query(params): Query<AuthorizeParams>,
-) -> Result<impl IntoResponse, ApiError> {
-    // 1. Validate Client ID & Redirect URI
-    // In production, verify client_id exists in DB and redirect_uri is allowed.
+    params: Result<Query<AuthorizeParams>, QueryRejection>,
+) -> Result<Response, OAuthError> {
+    let Query(params) = params.map_err(|e| OAuthError::invalid_request(e.body_text()))?;
+
+    // 1. Validate Client ID & Redirect URI against the registered client.
+    // Until both are known good, errors are answered here rather than sent
+    // to the redirect URI.
     if params.client_id.is_empty() {
-        return Err(ApiError::new(AuthError::ValidationError {
-            message: "client_id required".to_string(),
-        }));
+        return Err(OAuthError::invalid_request("client_id required"));
     }
+    let client = state
+        .clients
+        .find(&params.client_id)
+        .await?
+        .ok_or(OAuthError::invalid_request("unknown client_id"))?;
+    if !client.redirect_uris.contains(&params.redirect_uri) {
+        return Err(OAuthError::invalid_request(
+            "redirect_uri is not registered for the client",
+        ));
+    }
+    // An unusable response_mode is itself reported in the query string
+    let mode = match state
+        .response_modes
+        .resolve(&params.client_id, params.response_mode.as_deref())
+    {
+        Ok(mode) => mode,
+        Err(error) => {
+            return Ok(authorization_response(
+                &params.redirect_uri,
+                ResponseMode::Query,
+                &error.to_params(Some(&params.state)),
+            ))
+        }
+    };
+    let fail = |error: OAuthError| {
+        authorization_response(
+            &params.redirect_uri,
+            mode,
+            &error.to_params(Some(&params.state)),
+        )
+    };
 
     // 2. Validate Response Type
     if params.response_type != "code" {
-        return Err(ApiError::new(AuthError::ValidationError {
-            message: "unsupported response_type".to_string(),
-        }));
+        return Ok(fail(
+            OAuthError::new(OAuthErrorCode::UnsupportedResponseType)
+                .with_description("only response_type=code is supported"),
+        ));
+    }
+    if !has_grant(&client, GRANT_AUTHORIZATION_CODE) {
+        return Ok(fail(unregistered_grant(GRANT_AUTHORIZATION_CODE)));
+    }
+    if let Err(error) = check_pkce(
+        client.is_public(),
+        params.code_challenge.as_deref(),
+        params.code_challenge_method.as_deref(),
+    ) {
+        return Ok(fail(error));
     }
 
-    // 3. Check for Session Cookie
+    // 3. Check for the central SSO session cookie
     let mut user_id: Option<Uuid> = None;
-
-    // Extract "token" cookie
-    // Cookie format: token=...;
-    if let Some(cookie_header) = headers.get("cookie").and_then(|h| h.to_str().ok()) {
-        for cookie in cookie_header.split(';') {
-            let parts: Vec<&str> = cookie.trim().splitn(2, '=').collect();
-            if parts.len() == 2 && parts[0] == "token" {
-                let token = parts[1];
-                // Validate Session
-                if let Ok(session) = state.session_service.validate_session(token).await {
-                    user_id = Some(session.user_id);
-                }
-            }
+    let mut session_id: Option<Uuid> = None;
+    let mut sid: Option<String> = None;
+
+    if let Some(token) = state.sso_cookie.read(&headers) {
+        if let Ok(session) = state.session_service.validate_session(&token).await {
+            // Index the client under the SSO session for logout fan-out
+            let client_session = match state.sso.join(&session, &params.client_id).await {
+                Ok(client_session) => client_session,
+                Err(e) => return Ok(fail(e.into())),
+            };
+            user_id = Some(session.user_id);
+            session_id = Some(session.id);
+            sid = Some(client_session.sid);
         }
     }
 
+    let silent = params.prompt.as_deref() == Some("none");
+
+    // prompt=none must never show UI: report login_required back to the client
+    if user_id.is_none() && silent {
+        return Ok(fail(OAuthError::new(OAuthErrorCode::LoginRequired)));
+    }
+
     // If not authenticated, redirect to login
     if user_id.is_none() {
         // Construct return_to URL
         // Simple manual construction for now
-        let return_to = format!(
+        let mut return_to = format!(
             "/auth/authorize?response_type={}&client_id={}&redirect_uri={}&state={}",
             params.response_type, params.client_id, params.redirect_uri, params.state
         );
+        if let Some(response_mode) = &params.response_mode {
+            return_to.push_str(&format!(
+                "&response_mode={}",
+                urlencoding::encode(response_mode)
+            ));
+        }
 
         // Assuming we have a frontend login page at /auth/login (or API that serves it)
         // For API-only, we might return 401, but OIDC flows usually redirect.
@@ -109,7 +305,8 @@ pub async fn authorize(
         return Ok(Redirect::to(&format!(
             "/auth/login?return_to={}",
             urlencoding::encode(&return_to)
-        )));
+        ))
+        .into_response());
     }
 
     // 4. User is authenticated. Generate Authorization Code.
@@ -125,23 +322,52 @@ pub async fn authorize(
         code_challenge: params.code_challenge,
         code_challenge_method: params.code_challenge_method,
         user_id,
+        sid,
+        session_id,
+    };
+
+    let issued = match serde_json::to_string(&auth_req) {
+        Ok(val_str) => {
+            state
+                .nonces
+                .issue(NonceNamespace::AuthCode, &code, &val_str, None)
+                .await
+        }
+        Err(_) => Err(AuthError::InternalError),
     };
+    if let Err(e) = issued {
+        return Ok(fail(e.into()));
+    }
 
-    let val_str =
-        serde_json::to_string(&auth_req).map_err(|_| ApiError::new(AuthError::InternalError))?;
-    let cache_key = format!("auth_code:{}", code);
-    state
-        .cache
-        .set(&cache_key, &val_str, Duration::from_secs(600))
-        .await // 10 mins TTL
-        .map_err(|_e| ApiError::new(AuthError::InternalError))?; // Log error in real app
-
-    // 6. Redirect to Client
-    let target = format!(
-        "{}?code={}&state={}",
-        params.redirect_uri, code, params.state
+    // 6. Compute session_state so the RP can monitor the SSO session
+    let browser_state = session_id
+        .map(browser_state_for_session)
+        .unwrap_or_default();
+    let salt = Uuid::new_v4().simple().to_string()[..16].to_string();
+    let session_state = compute_session_state(
+        &auth_req.client_id,
+        origin_of(&params.redirect_uri),
+        &browser_state,
+        &salt,
     );
-    Ok(Redirect::to(&target))
+
+    // 7. Return to the Client in the requested response mode
+    let mut response = authorization_response(
+        &params.redirect_uri,
+        mode,
+        &[
+            ("code", code),
+            ("state", params.state.clone()),
+            ("session_state", session_state),
+        ],
+    );
+    if let Ok(cookie) = HeaderValue::from_str(&format!(
+        "{}={}; Path=/; Secure; SameSite=None",
+        BROWSER_STATE_COOKIE, browser_state
+    )) {
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+    Ok(response)
 }
 
 // ============================================================================
@@ -150,127 +376,46 @@ pub async fn authorize(
 
 pub async fn token(
     State(state): State<AppState>,
-    Form(payload): Form<TokenRequest>,
-) -> Result<impl IntoResponse, ApiError> {
+    payload: Result<Form<TokenRequest>, FormRejection>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let Form(payload) = payload.map_err(|e| OAuthError::invalid_request(e.body_text()))?;
     match payload.grant_type.as_str() {
-        "authorization_code" => {
+        GRANT_AUTHORIZATION_CODE => {
+            authenticate_client(&state, &payload, GRANT_AUTHORIZATION_CODE).await?;
             let code = payload
                 .code
-                .ok_or(ApiError::new(AuthError::ValidationError {
-                    message: "code required".to_string(),
-                }))?;
-
-            // 1. Retrieve from Cache
-            let cache_key = format!("auth_code:{}", code);
-            let val_opt = state
-                .cache
-                .get(&cache_key)
-                .await
-                .map_err(|_| ApiError::new(AuthError::InternalError))?;
-
-            let val_str = val_opt.ok_or(ApiError::new(AuthError::InvalidCredentials))?; // Invalid or expired code
-            let auth_req: AuthRequestState = serde_json::from_str(&val_str)
-                .map_err(|_| ApiError::new(AuthError::InternalError))?;
-
-            // 2. Validate Client
-            if auth_req.client_id != payload.client_id {
-                return Err(ApiError::new(AuthError::ValidationError {
-                    message: "client_id mismatch".to_string(),
-                }));
-            }
-
-            // 3. Validate Redirect URI
-            if let Some(uri) = payload.redirect_uri {
-                if uri != auth_req.redirect_uri {
-                    return Err(ApiError::new(AuthError::ValidationError {
-                        message: "redirect_uri mismatch".to_string(),
-                    }));
-                }
-            }
-
-            // 4. PKCE Validation
-            if let Some(challenge) = auth_req.code_challenge {
-                let verifier =
-                    payload
-                        .code_verifier
-                        .ok_or(ApiError::new(AuthError::ValidationError {
-                            message: "code_verifier required".to_string(),
-                        }))?;
-
-                // Only S256 supported for MNC grade (plain is deprecated/insecure)
-                if auth_req.code_challenge_method.as_deref() == Some("S256") {
-                    let mut hasher = Sha256::new();
-                    hasher.update(verifier.as_bytes());
-                    let result = hasher.finalize();
-                    let computed_challenge = URL_SAFE_NO_PAD.encode(result);
-
-                    if computed_challenge != challenge {
-                        return Err(ApiError::new(AuthError::ValidationError {
-                            message: "PKCE verification failed".to_string(),
-                        }));
-                    }
-                } else {
-                    // Reject plain or other methods
-                    return Err(ApiError::new(AuthError::ValidationError {
-                        message: "Only S256 PKCE supported".to_string(),
-                    }));
-                }
-            }
-
-            // 5. Issue Tokens
-            let user_id = auth_req
-                .user_id
-                .ok_or(ApiError::new(AuthError::InternalError))?;
-
-            // Fetch user details to pass to issue_tokens
-            let user = state
-                .identity_service
-                .get_user(user_id)
-                .await
-                .map_err(ApiError::from)?;
-            let tenant_id = Uuid::new_v4(); // Should come from user context
-
-            let token_response = state
-                .identity_service
-                .issue_tokens_for_user(&user, tenant_id, Some(payload.client_id), auth_req.scope)
-                .await
-                .map_err(ApiError::from)?;
-
-            // 6. Delete Code (Replay Protection)
-            let _ = state.cache.delete(&cache_key).await;
-
-            // 7. Generate ID Token (Simulated for now as IdentityService doesn't generate it yet, but Access Token is real)
-            // In a full implementation, IdentityService should generate ID Token too.
-            // We'll mock the ID token content with the same claims but formatted as JWT.
-            // For now, we return the access token and refresh token.
-
-            Ok(Json(serde_json::json!({
-                "access_token": token_response.access_token,
+                .ok_or(OAuthError::invalid_request("code required"))?;
+
+            let exchange = exchange_authorization_code(
+                &state,
+                &code,
+                &payload.client_id,
+                payload.redirect_uri.as_deref(),
+                payload.code_verifier.as_deref(),
+            )
+            .await?;
+
+            let mut body = serde_json::json!({
+                "access_token": exchange.tokens.access_token,
                 "token_type": "Bearer",
-                "expires_in": 900, // 15 mins
-                "refresh_token": token_response.refresh_token,
-                "id_token": "mock_id_token_jwt" // Placeholder: Requires RSA signing which is complex to add here without auth-crypto helper
-            })))
-        }
-        "client_credentials" => {
-            // 1. Verify Client ID & Secret
-            // In a real implementation, look up client in DB and verify secret (bcrypt/argon2)
-            if payload.client_id.is_empty() || payload.client_secret.is_none() {
-                return Err(ApiError::new(AuthError::ValidationError {
-                    message: "client_id and client_secret required".to_string(),
-                }));
+                "expires_in": exchange.tokens.expires_in,
+                "refresh_token": exchange.tokens.refresh_token,
+            });
+            if let Some(id_token) = exchange.id_token {
+                body["id_token"] = serde_json::Value::String(id_token);
             }
-
-            // Mock verify: assume client_123 / secret_123 is valid
-            if payload.client_id != "client_123"
-                || payload.client_secret.as_deref() != Some("secret_123")
-            {
-                return Err(ApiError::new(AuthError::InvalidCredentials));
+            Ok(Json(body))
+        }
+        GRANT_CLIENT_CREDENTIALS => {
+            // 1. Verify Client ID & Secret against the registered client
+            if payload.client_secret.is_none() {
+                return Err(OAuthError::invalid_client("client_secret required"));
             }
+            let client = authenticate_client(&state, &payload, GRANT_CLIENT_CREDENTIALS).await?;
 
-            // 2. Issue Tokens
-            let user_id = Uuid::new_v4(); // Service Account ID
-            let tenant_id = Uuid::new_v4();
+            // 2. Issue Tokens in the client's tenant
+            let user_id = client.id;
+            let tenant_id = client.tenant_id;
 
             // Create "Service User" struct on the fly or fetch
             let user = auth_core::models::User {
@@ -300,31 +445,230 @@ pub async fn token(
                 deleted_at: None,
                 email_verified_at: None,
                 phone_verified_at: None,
+                username: None,
             };
 
             let token_response = state
                 .identity_service
-                .issue_tokens_for_user(&user, tenant_id, Some(payload.client_id), None)
-                .await
-                .map_err(ApiError::from)?;
+                .issue_tokens_for_user(&user, tenant_id, Some(payload.client_id), None, None)
+                .await?;
 
             Ok(Json(serde_json::json!({
                 "access_token": token_response.access_token,
                 "token_type": "Bearer",
-                "expires_in": 3600,
+                "expires_in": token_response.expires_in,
                 "refresh_token": token_response.refresh_token
             })))
         }
-        "refresh_token" => {
-            // TODO: Implement refresh logic using IdentityService
-            Err(ApiError::new(AuthError::ValidationError {
-                message: "grant_type not implemented".to_string(),
-            }))
+        JWT_BEARER_GRANT => {
+            let assertion = payload
+                .assertion
+                .ok_or(OAuthError::invalid_request("assertion required"))?;
+
+            // The assertion must be addressed to this token endpoint
+            let base_url =
+                env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:8080".to_string());
+            let audience = generate_oidc_metadata(&base_url).token_endpoint;
+
+            let account = state
+                .service_accounts
+                .authenticate_assertion(&assertion, &audience)
+                .await?;
+            let scope = account
+                .grant_scopes(payload.scope.as_deref())
+                .map_err(|e| {
+                    OAuthError::new(OAuthErrorCode::InvalidScope).with_description(e.to_string())
+                })?;
+            let token = state
+                .identity_service
+                .issue_service_account_token(&account, scope)
+                .await?;
+
+            Ok(Json(serde_json::json!({
+                "access_token": token.token,
+                "token_type": token.token_type,
+                "expires_in": token.expires_in,
+                "scope": token.scope,
+            })))
+        }
+        GRANT_REFRESH_TOKEN => {
+            authenticate_client(&state, &payload, GRANT_REFRESH_TOKEN).await?;
+            let refresh_token = payload
+                .refresh_token
+                .ok_or(OAuthError::invalid_request("refresh_token required"))?;
+
+            // Only the client the token was issued to may rotate it
+            let pair = state
+                .identity_service
+                .refresh_tokens_for_client(&refresh_token, &payload.client_id)
+                .await?;
+
+            Ok(Json(serde_json::json!({
+                "access_token": pair.access_token.token,
+                "token_type": pair.access_token.token_type,
+                "expires_in": pair.access_token.expires_in,
+                "refresh_token": pair.refresh_token,
+            })))
+        }
+        _ => Err(OAuthError::new(OAuthErrorCode::UnsupportedGrantType)),
+    }
+}
+
+/// Authenticate the client at the token endpoint and check that it is
+/// registered for `grant`. A confidential client must present its secret;
+/// a public one is known by its id alone.
+async fn authenticate_client(
+    state: &AppState,
+    payload: &TokenRequest,
+    grant: &str,
+) -> Result<OAuthClient, OAuthError> {
+    if payload.client_id.is_empty() {
+        return Err(OAuthError::invalid_client("client_id required"));
+    }
+    let client = state
+        .clients
+        .authenticate(&payload.client_id, payload.client_secret.as_deref())
+        .await
+        .map_err(|e| match e {
+            ClientRegistrationError::Store(e) => OAuthError::from(e),
+            _ => OAuthError::invalid_client("client authentication failed"),
+        })?;
+    if !has_grant(&client, grant) {
+        return Err(unregistered_grant(grant));
+    }
+    Ok(client)
+}
+
+/// PKCE parameters of an authorization request. Only S256 is accepted, and
+/// a public client, having no secret for the token endpoint, must send a
+/// challenge.
+fn check_pkce(
+    public_client: bool,
+    challenge: Option<&str>,
+    method: Option<&str>,
+) -> Result<(), OAuthError> {
+    match challenge {
+        None if public_client => Err(OAuthError::invalid_request(
+            "code_challenge required for public clients",
+        )),
+        Some(_) if method != Some("S256") => Err(OAuthError::invalid_request(
+            "code_challenge_method must be S256",
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn has_grant(client: &OAuthClient, grant: &str) -> bool {
+    client.grant_types.iter().any(|g| g == grant)
+}
+
+fn unregistered_grant(grant: &str) -> OAuthError {
+    OAuthError::new(OAuthErrorCode::UnauthorizedClient)
+        .with_description(format!("client is not registered for {}", grant))
+}
+
+/// Tokens issued for a redeemed authorization code
+pub(crate) struct CodeExchange {
+    pub tokens: AuthResponse,
+    /// Issued when the authorization request asked for the `openid` scope
+    pub id_token: Option<String>,
+}
+
+/// Redeem an authorization code: validates the client, redirect URI and PKCE
+/// verifier, then issues tokens. Shared by the token endpoint and BFF login.
+pub(crate) async fn exchange_authorization_code(
+    state: &AppState,
+    code: &str,
+    client_id: &str,
+    redirect_uri: Option<&str>,
+    code_verifier: Option<&str>,
+) -> Result<CodeExchange, OAuthError> {
+    // 1. Redeem the code. It is spent even if a later check fails, and a
+    //    second redemption is rejected as a replay.
+    let val_str = state
+        .nonces
+        .consume(NonceNamespace::AuthCode, code)
+        .await
+        .map_err(|e| match e {
+            AuthError::TokenError { .. } => {
+                OAuthError::invalid_grant("authorization code is invalid, expired or used")
+            }
+            other => other.into(),
+        })?;
+    let auth_req: AuthRequestState =
+        serde_json::from_str(&val_str).map_err(|_| AuthError::InternalError)?;
+
+    // 2. Validate Client
+    if auth_req.client_id != client_id {
+        return Err(OAuthError::invalid_grant(
+            "authorization code was issued to another client",
+        ));
+    }
+
+    // 3. Validate Redirect URI
+    if let Some(uri) = redirect_uri {
+        if uri != auth_req.redirect_uri {
+            return Err(OAuthError::invalid_grant("redirect_uri mismatch"));
+        }
+    }
+
+    // 4. PKCE Validation
+    if let Some(challenge) = auth_req.code_challenge {
+        let verifier = code_verifier.ok_or(OAuthError::invalid_grant("code_verifier required"))?;
+
+        // Only S256 supported for MNC grade (plain is deprecated/insecure)
+        if auth_req.code_challenge_method.as_deref() == Some("S256") {
+            let mut hasher = Sha256::new();
+            hasher.update(verifier.as_bytes());
+            let result = hasher.finalize();
+            let computed_challenge = URL_SAFE_NO_PAD.encode(result);
+
+            if !constant_time_eq(computed_challenge.as_bytes(), challenge.as_bytes()) {
+                return Err(OAuthError::invalid_grant("PKCE verification failed"));
+            }
+        } else {
+            // Reject plain or other methods
+            return Err(OAuthError::invalid_grant("Only S256 PKCE supported"));
         }
-        _ => Err(ApiError::new(AuthError::ValidationError {
-            message: "unsupported grant_type".to_string(),
-        })),
     }
+
+    // 5. Issue Tokens
+    let user_id = auth_req.user_id.ok_or(AuthError::InternalError)?;
+
+    // Fetch user details to pass to issue_tokens
+    let user = state.identity_service.get_user(user_id).await?;
+    let tenant_id = user.tenant_id;
+    let openid = requests_openid(auth_req.scope.as_deref());
+
+    let tokens = state
+        .identity_service
+        .issue_tokens_for_user(
+            &user,
+            tenant_id,
+            Some(client_id.to_string()),
+            auth_req.scope,
+            auth_req.session_id,
+        )
+        .await?;
+
+    // 6. ID Token, signed with the key published at the JWKS endpoint
+    let id_token = if openid {
+        let claims = IdTokenClaims::new(
+            state.issuers.for_tenant(tenant_id),
+            user.id,
+            client_id,
+            chrono::Duration::seconds(tokens.expires_in as i64),
+        )
+        .with_nonce(auth_req.nonce)
+        .with_sid(auth_req.sid)
+        .with_access_token(&tokens.access_token);
+        let claims = serde_json::to_value(claims).map_err(|_| AuthError::InternalError)?;
+        Some(state.identity_service.sign_jwt(claims).await?)
+    } else {
+        None
+    };
+
+    Ok(CodeExchange { tokens, id_token })
 }
 
 // ============================================================================
@@ -377,3 +721,55 @@ pub async fn userinfo(
         "updated_at": user.updated_at.timestamp(),
     })))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_of_strips_path_and_query() {
+        assert_eq!(
+            origin_of("https://app.example.com:8443/cb?x=1"),
+            "https://app.example.com:8443"
+        );
+        assert_eq!(
+            origin_of("https://app.example.com"),
+            "https://app.example.com"
+        );
+    }
+
+    #[test]
+    fn test_session_state_changes_with_browser_state() {
+        let a = compute_session_state("client", "https://rp.example", "bs1", "salt");
+        let b = compute_session_state("client", "https://rp.example", "bs2", "salt");
+        assert_ne!(a, b);
+        assert!(a.ends_with(".salt"));
+        assert_eq!(
+            a,
+            compute_session_state("client", "https://rp.example", "bs1", "salt")
+        );
+    }
+
+    #[test]
+    fn test_public_clients_must_send_an_s256_challenge() {
+        assert!(check_pkce(false, None, None).is_ok());
+        assert!(check_pkce(true, Some("challenge"), Some("S256")).is_ok());
+        assert_eq!(
+            check_pkce(true, None, None),
+            Err(OAuthError::invalid_request(
+                "code_challenge required for public clients"
+            ))
+        );
+        assert!(check_pkce(true, Some("challenge"), Some("plain")).is_err());
+        assert!(check_pkce(false, Some("challenge"), None).is_err());
+    }
+
+    #[test]
+    fn test_append_query_respects_existing_params() {
+        assert_eq!(append_query("https://rp/cb", "a=1"), "https://rp/cb?a=1");
+        assert_eq!(
+            append_query("https://rp/cb?x=1", "a=1"),
+            "https://rp/cb?x=1&a=1"
+        );
+    }
+}
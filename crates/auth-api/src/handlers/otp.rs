@@ -15,9 +15,11 @@ use uuid::Uuid;
 
 use crate::error::ApiError;
 use auth_core::error::{AuthError, TokenErrorKind};
+use auth_core::models::SensitiveString;
 use auth_core::services::{
-    otp_delivery::OtpDeliveryService,
-    otp_service::{DeliveryMethod, OtpPurpose, OtpService},
+    identity::IdentityService,
+    otp_delivery::{DeliveryError, OtpDeliveryService},
+    otp_service::{DeliveryMethod, OtpError, OtpPurpose, OtpService},
     rate_limiter::{identifier_key, RateLimiter},
 };
 use auth_db::repositories::otp_repository::OtpRepository;
@@ -51,7 +53,7 @@ pub struct OtpRequestResponse {
 #[derive(Debug, Deserialize)]
 pub struct OtpVerifyPayload {
     pub session_id: Uuid,
-    pub otp: String,
+    pub otp: SensitiveString,
 }
 
 #[derive(Debug, Serialize)]
@@ -74,6 +76,7 @@ pub async fn request_otp(
     State(otp_delivery): State<Arc<OtpDeliveryService>>,
     State(otp_repo): State<Arc<OtpRepository>>,
     State(rate_limiter): State<Arc<RateLimiter>>,
+    State(identity_service): State<Arc<IdentityService>>,
     Json(payload): Json<OtpRequestPayload>,
 ) -> Result<impl IntoResponse, ApiError> {
     // 1. Rate limiting check
@@ -139,31 +142,44 @@ pub async fn request_otp(
         .map_err(|_| ApiError::new(AuthError::InternalError))?;
 
     // 6. Send OTP via appropriate channel
-    let delivery_channel = match delivery_method {
+    let (delivery_channel, masked_identifier) = match delivery_method {
         DeliveryMethod::Email => {
             otp_delivery
                 .send_email_otp(&payload.identifier, &otp)
                 .await
                 .map_err(|_| ApiError::new(AuthError::InternalError))?;
-            "email"
+            ("email", mask_email(&payload.identifier))
         }
-        DeliveryMethod::Sms => {
-            otp_delivery
-                .send_phone_otp(&payload.identifier, &otp)
-                .await
-                .map_err(|_| ApiError::new(AuthError::InternalError))?;
-            "sms"
-        }
-    };
-
-    // 7. Mask identifier in response
-    let masked_identifier = if identifier_type == "email" {
-        mask_email(&payload.identifier)
-    } else {
-        mask_phone(&payload.identifier)
+        DeliveryMethod::Sms => match otp_delivery
+            .send_phone_otp(Some(payload.tenant_id), &payload.identifier, &otp)
+            .await
+        {
+            Ok(_) => ("sms", mask_phone(&payload.identifier)),
+            // The tenant's SMS budget is used up: send the code to the
+            // account's verified email instead. The session stays bound to
+            // the phone number, so verification is unchanged.
+            Err(error @ DeliveryError::BudgetExhausted { .. }) => {
+                let email = identity_service
+                    .find_user_by_identifier(payload.tenant_id, &payload.identifier)
+                    .await?
+                    .filter(|user| user.email_verified)
+                    .and_then(|user| user.email)
+                    .ok_or_else(|| ApiError::from(error))?;
+                otp_delivery
+                    .send_email_otp(&email, &otp)
+                    .await
+                    .map_err(|_| ApiError::new(AuthError::InternalError))?;
+                ("email", mask_email(&email))
+            }
+            Err(
+                error @ (DeliveryError::DestinationBlocked(_)
+                | DeliveryError::DestinationQuarantined),
+            ) => return Err(error.into()),
+            Err(_) => return Err(ApiError::new(AuthError::InternalError)),
+        },
     };
 
-    // 8. Return response
+    // 7. Return response
     Ok((
         StatusCode::OK,
         Json(OtpRequestResponse {
@@ -235,35 +251,52 @@ pub async fn verify_otp(
         }));
     }
 
-    // 4. Verify OTP
-    let is_valid = otp_service
-        .verify_otp(&payload.otp, &otp_hash)
-        .map_err(|_| ApiError::new(AuthError::InternalError))?;
-
-    if !is_valid {
-        // Increment attempts
-        otp_repo
-            .increment_attempts(payload.session_id)
-            .await
-            .map_err(|_| ApiError::new(AuthError::InternalError))?;
-
-        return Ok((
-            StatusCode::UNAUTHORIZED,
-            Json(OtpVerifyResponse {
-                verified: false,
-                message: "Invalid OTP code".to_string(),
-                user_id: None,
-            }),
-        ));
-    }
-
-    // 5. Mark as verified
-    otp_repo
-        .mark_verified(payload.session_id)
+    // 4. Verify OTP, consuming an attempt atomically so concurrent requests
+    // cannot exceed the attempt budget or both complete the session
+    match otp_service
+        .verify_with_store(
+            otp_repo.as_ref(),
+            payload.session_id,
+            payload.otp.expose(),
+            &otp_hash,
+        )
         .await
-        .map_err(|_| ApiError::new(AuthError::InternalError))?;
+    {
+        Ok(_) => {}
+        Err(OtpError::Invalid) => {
+            return Ok((
+                StatusCode::UNAUTHORIZED,
+                Json(OtpVerifyResponse {
+                    verified: false,
+                    message: "Invalid OTP code".to_string(),
+                    user_id: None,
+                }),
+            ));
+        }
+        Err(OtpError::AlreadyVerified) => {
+            return Ok((
+                StatusCode::OK,
+                Json(OtpVerifyResponse {
+                    verified: true,
+                    message: "OTP already verified".to_string(),
+                    user_id: session.user_id,
+                }),
+            ));
+        }
+        Err(OtpError::Expired) => {
+            return Err(ApiError::new(AuthError::TokenError {
+                kind: TokenErrorKind::Expired,
+            }));
+        }
+        Err(OtpError::MaxAttemptsExceeded) | Err(OtpError::NotFound) => {
+            return Err(ApiError::new(AuthError::TokenError {
+                kind: TokenErrorKind::Invalid,
+            }));
+        }
+        Err(_) => return Err(ApiError::new(AuthError::InternalError)),
+    }
 
-    // 6. Return success
+    // 5. Return success
     Ok((
         StatusCode::OK,
         Json(OtpVerifyResponse {
@@ -281,10 +314,14 @@ pub async fn verify_otp(
 fn mask_email(email: &str) -> String {
     if let Some(at_pos) = email.find('@') {
         let (local, domain) = email.split_at(at_pos);
-        if local.len() <= 3 {
-            format!("{}****{}", &local[..1], domain)
+        // By character, so multi-byte or empty local parts cannot panic
+        let chars: Vec<char> = local.chars().collect();
+        if chars.len() <= 3 {
+            let head: String = chars.iter().take(1).collect();
+            format!("{}****{}", head, domain)
         } else {
-            format!("{}****{}{}", &local[..2], &local[local.len() - 1..], domain)
+            let head: String = chars[..2].iter().collect();
+            format!("{}****{}{}", head, chars[chars.len() - 1], domain)
         }
     } else {
         email.to_string()
@@ -292,8 +329,11 @@ fn mask_email(email: &str) -> String {
 }
 
 fn mask_phone(phone: &str) -> String {
-    if phone.len() > 6 {
-        format!("{}****{}", &phone[..3], &phone[phone.len() - 3..])
+    let chars: Vec<char> = phone.chars().collect();
+    if chars.len() > 6 {
+        let head: String = chars[..3].iter().collect();
+        let tail: String = chars[chars.len() - 3..].iter().collect();
+        format!("{}****{}", head, tail)
     } else {
         phone.to_string()
     }
@@ -307,11 +347,14 @@ mod tests {
     fn test_mask_email() {
         assert_eq!(mask_email("test@example.com"), "te****t@example.com");
         assert_eq!(mask_email("a@example.com"), "a****@example.com");
+        assert_eq!(mask_email("@example.com"), "****@example.com");
+        assert_eq!(mask_email("éèêë@example.com"), "éè****ë@example.com");
     }
 
     #[test]
     fn test_mask_phone() {
         assert_eq!(mask_phone("+14155552671"), "+14****671");
         assert_eq!(mask_phone("12345"), "12345");
+        assert_eq!(mask_phone("١٢٣٤٥٦٧٨"), "١٢٣****٦٧٨");
     }
 }
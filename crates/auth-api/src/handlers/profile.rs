@@ -3,7 +3,7 @@
 
 use crate::error::ApiError;
 use auth_core::error::{AuthError, TokenErrorKind};
-use auth_core::models::{Claims, User};
+use auth_core::models::{Claims, SensitiveString, User};
 use auth_core::services::identity::IdentityService;
 use axum::{
     extract::{Json, State},
@@ -19,7 +19,7 @@ use uuid::Uuid;
 #[derive(Debug, Deserialize)]
 pub struct CompleteProfileRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub password: Option<String>,
+    pub password: Option<SensitiveString>,
     pub profile: serde_json::Value,
 }
 
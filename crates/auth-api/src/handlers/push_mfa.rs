@@ -0,0 +1,142 @@
+use crate::error::ApiError;
+use crate::AppState;
+use auth_core::error::{AuthError, TokenErrorKind};
+use auth_core::models::{
+    PushChallengeResponseRequest, PushChallengeStatus, RegisterPushDeviceRequest,
+};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct PushChallengeStatusResponse {
+    pub challenge_id: Uuid,
+    pub status: PushChallengeStatus,
+}
+
+/// Caller (user id, tenant id) from the bearer token
+async fn caller(state: &AppState, headers: &HeaderMap) -> Result<(Uuid, Uuid), ApiError> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(ApiError::new(AuthError::Unauthorized {
+            message: "Missing token".to_string(),
+        }))?;
+
+    let claims = state
+        .identity_service
+        .validate_token(token)
+        .await
+        .map_err(ApiError::from)?;
+    let invalid = |_| {
+        ApiError::new(AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        })
+    };
+    Ok((
+        Uuid::parse_str(&claims.sub).map_err(invalid)?,
+        Uuid::parse_str(&claims.tenant_id).map_err(invalid)?,
+    ))
+}
+
+/// Register the caller's authenticator app for push approval
+#[utoipa::path(
+    post,
+    path = "/mfa/push/devices",
+    request_body = RegisterPushDeviceRequest,
+    responses(
+        (status = 201, description = "Device registered", body = PushDevice),
+        (status = 400, description = "Invalid public key or too many devices"),
+        (status = 401, description = "Missing or invalid bearer token")
+    ),
+    tag = "MFA"
+)]
+pub async fn register_device(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<RegisterPushDeviceRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (user_id, tenant_id) = caller(&state, &headers).await?;
+    payload.validate().map_err(AuthError::from)?;
+
+    let device = state
+        .push_mfa
+        .register_device(user_id, tenant_id, payload)
+        .await?;
+    Ok((StatusCode::CREATED, Json(device)))
+}
+
+/// The caller's active push devices
+#[utoipa::path(
+    get,
+    path = "/mfa/push/devices",
+    responses(
+        (status = 200, description = "Registered devices", body = [PushDevice]),
+        (status = 401, description = "Missing or invalid bearer token")
+    ),
+    tag = "MFA"
+)]
+pub async fn list_devices(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let (user_id, tenant_id) = caller(&state, &headers).await?;
+    let devices = state.push_mfa.list_devices(user_id, tenant_id).await?;
+    Ok(Json(serde_json::json!({ "devices": devices })))
+}
+
+/// Stop sending push challenges to a device
+#[utoipa::path(
+    delete,
+    path = "/mfa/push/devices/{id}",
+    params(("id" = Uuid, Path, description = "Device ID")),
+    responses(
+        (status = 204, description = "Device revoked"),
+        (status = 404, description = "Device not found")
+    ),
+    tag = "MFA"
+)]
+pub async fn revoke_device(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (user_id, tenant_id) = caller(&state, &headers).await?;
+    state.push_mfa.revoke_device(user_id, tenant_id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Approve or deny a challenge from the device. Authenticated by the
+/// signature, not a bearer token.
+#[utoipa::path(
+    post,
+    path = "/mfa/push/challenges/{id}/respond",
+    params(("id" = Uuid, Path, description = "Challenge ID")),
+    request_body = PushChallengeResponseRequest,
+    responses(
+        (status = 200, description = "Answer recorded", body = PushChallengeStatusResponse),
+        (status = 400, description = "Challenge already answered or expired"),
+        (status = 401, description = "Invalid signature"),
+        (status = 404, description = "Unknown challenge or device")
+    ),
+    tag = "MFA"
+)]
+pub async fn respond(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<PushChallengeResponseRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    payload.validate().map_err(AuthError::from)?;
+    let status = state.push_mfa.respond(id, payload).await?;
+    Ok(Json(PushChallengeStatusResponse {
+        challenge_id: id,
+        status,
+    }))
+}
@@ -0,0 +1,110 @@
+use crate::error::ApiError;
+use crate::AppState;
+use auth_core::error::{AuthError, TokenErrorKind};
+use auth_core::services::recovery_codes::LOW_RECOVERY_CODES;
+use axum::{extract::State, http::HeaderMap, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct GenerateRecoveryCodesRequest {
+    /// Current TOTP code; required when the user has TOTP enrolled
+    pub mfa_code: Option<String>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct RecoveryCodesResponse {
+    /// Shown once; earlier codes no longer work
+    pub codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct RecoveryCodesStatus {
+    pub remaining: usize,
+    /// True once few enough codes are left that the user should regenerate
+    pub low: bool,
+}
+
+async fn caller(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<auth_core::models::User, ApiError> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(ApiError::new(AuthError::Unauthorized {
+            message: "Missing token".to_string(),
+        }))?;
+
+    let claims = state
+        .identity_service
+        .validate_token(token)
+        .await
+        .map_err(ApiError::from)?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| {
+        ApiError::new(AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        })
+    })?;
+    Ok(state.identity_service.get_user(user_id).await?)
+}
+
+/// Generate a new set of recovery codes, replacing any earlier set
+#[utoipa::path(
+    post,
+    path = "/mfa/recovery-codes",
+    request_body = GenerateRecoveryCodesRequest,
+    responses(
+        (status = 200, description = "New codes, shown once", body = RecoveryCodesResponse),
+        (status = 401, description = "Missing bearer token or step-up code")
+    ),
+    tag = "MFA"
+)]
+pub async fn generate(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<GenerateRecoveryCodesRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = caller(&state, &headers).await?;
+
+    // Regenerating locks out whoever holds the old codes, so require the
+    // current second factor when there is one
+    if let Some(secret) = user.mfa_secret.as_deref().filter(|_| user.mfa_enabled) {
+        let code = payload
+            .mfa_code
+            .as_deref()
+            .ok_or(ApiError::new(AuthError::StepUpRequired))?;
+        if !state.otp_service.verify_totp(secret, code).unwrap_or(false) {
+            return Err(ApiError::new(AuthError::InvalidOtp));
+        }
+    }
+
+    let codes = state
+        .recovery_codes
+        .generate(user.id, user.tenant_id)
+        .await?;
+    Ok(Json(RecoveryCodesResponse { codes }))
+}
+
+/// How many unused recovery codes the caller has left
+#[utoipa::path(
+    get,
+    path = "/mfa/recovery-codes",
+    responses(
+        (status = 200, description = "Remaining codes", body = RecoveryCodesStatus),
+        (status = 401, description = "Missing or invalid bearer token")
+    ),
+    tag = "MFA"
+)]
+pub async fn status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let remaining = state.recovery_codes.remaining(user.id).await?;
+    Ok(Json(RecoveryCodesStatus {
+        remaining,
+        low: remaining <= LOW_RECOVERY_CODES,
+    }))
+}
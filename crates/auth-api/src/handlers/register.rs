@@ -4,6 +4,9 @@
 //! - Email only
 //! - Phone only
 //! - Email + Phone (dual)
+//! - Username, where the tenant's username policy enables it
+//!
+//! Every signup is screened by the signup velocity rules first.
 
 use axum::{
     extract::{Json, State},
@@ -14,12 +17,17 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
 
+use auth_config::SignupAction;
+use auth_core::context::RequestContext;
 use auth_core::error::AuthError;
-use auth_core::models::user::{IdentifierType, PrimaryIdentifier};
+use auth_core::models::user::{IdentifierType, PrimaryIdentifier, User, UserStatus};
 use auth_core::models::validation::{normalize_phone, validate_email};
+use auth_core::models::SensitiveString;
+use auth_core::services::captcha::CaptchaService;
 use auth_core::services::identity::IdentityService;
 use auth_core::services::otp_delivery::OtpDeliveryService;
 use auth_core::services::otp_service::{DeliveryMethod, OtpPurpose, OtpService};
+use auth_core::services::signup_abuse::{SignupAbuseService, SignupSignals};
 use auth_db::repositories::otp_repository::OtpRepository;
 
 // ============================================================================
@@ -28,7 +36,7 @@ use auth_db::repositories::otp_repository::OtpRepository;
 
 #[derive(Debug, Deserialize)]
 pub struct RegisterRequest {
-    /// Identifier type: "email", "phone", or "both"
+    /// Identifier type: "email", "phone", "both" or "username"
     pub identifier_type: String,
 
     /// Email address (required if identifier_type is "email" or "both")
@@ -39,13 +47,17 @@ pub struct RegisterRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub phone: Option<String>,
 
+    /// Username (required if identifier_type is "username", optional otherwise)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
     /// Primary identifier for login: "email" or "phone" (required if both provided)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub primary_identifier: Option<String>,
 
     /// Password (optional for passwordless registration)
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub password: Option<String>,
+    pub password: Option<SensitiveString>,
 
     /// Tenant ID
     pub tenant_id: Uuid,
@@ -57,6 +69,14 @@ pub struct RegisterRequest {
     /// Whether to require verification before allowing login
     #[serde(default = "default_require_verification")]
     pub require_verification: bool,
+
+    /// Client-computed device fingerprint, counted by the signup velocity rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_fingerprint: Option<String>,
+
+    /// Solved CAPTCHA, when a signup velocity rule asked for one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captcha_token: Option<String>,
 }
 
 fn default_require_verification() -> bool {
@@ -71,6 +91,8 @@ pub struct RegisterResponse {
     pub email: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub phone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
     pub identifier_type: String,
     pub verification_required: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -98,6 +120,8 @@ pub async fn register(
     State(otp_service): State<Arc<OtpService>>,
     State(otp_delivery): State<Arc<OtpDeliveryService>>,
     State(otp_repo): State<Arc<OtpRepository>>,
+    State(signup_abuse): State<Arc<SignupAbuseService>>,
+    State(captcha): State<Arc<CaptchaService>>,
     Json(payload): Json<RegisterRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     // 1. Validate identifier type
@@ -105,12 +129,14 @@ pub async fn register(
         "email" => IdentifierType::Email,
         "phone" => IdentifierType::Phone,
         "both" => IdentifierType::Both,
+        "username" => IdentifierType::Username,
         _ => {
             return Err((
                 StatusCode::BAD_REQUEST,
                 Json(ErrorResponse {
-                    error: "Invalid identifier_type. Must be 'email', 'phone', or 'both'"
-                        .to_string(),
+                    error:
+                        "Invalid identifier_type. Must be 'email', 'phone', 'both' or 'username'"
+                            .to_string(),
                     code: "AUTH_038".to_string(),
                     field: Some("identifier_type".to_string()),
                 }),
@@ -169,6 +195,19 @@ pub async fn register(
                 ));
             }
         }
+        IdentifierType::Username => {
+            if payload.username.is_none() {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse {
+                        error: "Username is required when identifier_type is 'username'"
+                            .to_string(),
+                        code: "AUTH_004".to_string(),
+                        field: Some("username".to_string()),
+                    }),
+                ));
+            }
+        }
     };
 
     // 3. Validate email format if provided
@@ -206,6 +245,7 @@ pub async fn register(
     let primary_identifier = match identifier_type {
         IdentifierType::Email => PrimaryIdentifier::Email,
         IdentifierType::Phone => PrimaryIdentifier::Phone,
+        IdentifierType::Username => PrimaryIdentifier::Username,
         IdentifierType::Both => match payload.primary_identifier.as_deref() {
             Some("email") => PrimaryIdentifier::Email,
             Some("phone") => PrimaryIdentifier::Phone,
@@ -236,18 +276,81 @@ pub async fn register(
         }
     }
 
-    // 7. Create user via identity service
+    let internal_error = || {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "Internal server error".to_string(),
+                code: "AUTH_026".to_string(),
+                field: None,
+            }),
+        )
+    };
+
+    // 7. Screen against the signup velocity rules
+    let signals = SignupSignals::new(
+        RequestContext::current().and_then(|c| c.ip_address),
+        payload.device_fingerprint.clone(),
+        payload.email.as_deref(),
+    );
+    let screening = signup_abuse.screen(payload.tenant_id, &signals).await;
+    let quarantined = match screening.action {
+        None => false,
+        Some(SignupAction::Block) => {
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(ErrorResponse {
+                    error: "Too many signups. Please try again later".to_string(),
+                    code: "AUTH_017".to_string(),
+                    field: None,
+                }),
+            ));
+        }
+        Some(SignupAction::Captcha) if captcha.enabled_for(payload.tenant_id) => {
+            captcha
+                .verify(
+                    payload.captcha_token.as_deref(),
+                    signals.ip_address.as_deref(),
+                )
+                .await
+                .map_err(|e| match e {
+                    AuthError::CaptchaRequired { .. } => (
+                        StatusCode::PRECONDITION_REQUIRED,
+                        Json(ErrorResponse {
+                            error: e.to_string(),
+                            code: "AUTH_047".to_string(),
+                            field: Some("captcha_token".to_string()),
+                        }),
+                    ),
+                    AuthError::CaptchaFailed => (
+                        StatusCode::FORBIDDEN,
+                        Json(ErrorResponse {
+                            error: e.to_string(),
+                            code: "AUTH_048".to_string(),
+                            field: Some("captcha_token".to_string()),
+                        }),
+                    ),
+                    _ => internal_error(),
+                })?;
+            false
+        }
+        // A tenant without a challenge to offer holds the signup for review
+        Some(SignupAction::Captcha) | Some(SignupAction::Quarantine) => true,
+    };
+
+    // 8. Create user via identity service
     let create_request = auth_core::models::user::CreateUserRequest {
         identifier_type,
         email: payload.email,
         phone: normalized_phone.clone(),
+        username: payload.username,
         primary_identifier: Some(primary_identifier.clone()),
         password: payload.password,
         profile_data: Some(payload.profile),
         require_verification: Some(payload.require_verification),
     };
 
-    let user = identity_service
+    let mut user = identity_service
         .register(create_request, payload.tenant_id)
         .await
         .map_err(|e| {
@@ -261,25 +364,57 @@ pub async fn register(
                         field: None,
                     }),
                 ),
-                _ => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
+                AuthError::ValidationError { message } => (
+                    StatusCode::BAD_REQUEST,
                     Json(ErrorResponse {
-                        error: "Internal server error".to_string(),
-                        code: "AUTH_026".to_string(),
+                        error: message,
+                        code: "AUTH_004".to_string(),
                         field: None,
                     }),
                 ),
+                _ => internal_error(),
             }
         })?;
 
-    // 8. Send verification if required
-    let verification_sent_to = if payload.require_verification {
-        let identifier_opt = match primary_identifier {
-            PrimaryIdentifier::Email => user.email.clone().map(|e| (DeliveryMethod::Email, e)),
-            PrimaryIdentifier::Phone => user.phone.clone().map(|p| (DeliveryMethod::Sms, p)),
+    // A username-only account has no channel to verify; the tenant's
+    // username policy is what admits it
+    let verifiable = user.email.is_some() || user.phone.is_some();
+    let require_verification = payload.require_verification && verifiable;
+
+    // A shadow-created account is suspended and sent nothing, but the
+    // client gets the response a normal signup would
+    if quarantined {
+        signup_abuse
+            .quarantine(payload.tenant_id, user.id, &signals, screening.reasons)
+            .await
+            .map_err(|_| internal_error())?;
+        if !verifiable {
+            user.status = UserStatus::Active;
+        }
+        let verification_sent_to = if require_verification {
+            verification_target(&user, &primary_identifier).map(|(_, identifier)| identifier)
+        } else {
+            None
         };
+        return Ok(created(
+            user,
+            payload.identifier_type,
+            require_verification,
+            verification_sent_to,
+        ));
+    }
 
-        if let Some((method, identifier)) = identifier_opt {
+    if !verifiable {
+        identity_service
+            .activate_user(user.id)
+            .await
+            .map_err(|_| internal_error())?;
+        user.status = UserStatus::Active;
+    }
+
+    // 9. Send verification if required
+    let verification_sent_to = if require_verification {
+        if let Some((method, identifier)) = verification_target(&user, &primary_identifier) {
             // Create OTP Session
             // TODO: Use actual tenant_id logic
             let session_result = otp_service.create_session(
@@ -320,15 +455,16 @@ pub async fn register(
                                     base_url, token, session.id
                                 );
                                 if let Err(e) = otp_delivery
-                                    .send_verification_email(&identifier, &link)
+                                    .send_verification_email(&identifier, &link, Some(&token))
                                     .await
                                 {
                                     tracing::error!("Failed to send verification email: {:?}", e);
                                 }
                             }
                             DeliveryMethod::Sms => {
-                                if let Err(e) =
-                                    otp_delivery.send_phone_otp(&identifier, &token).await
+                                if let Err(e) = otp_delivery
+                                    .send_phone_otp(Some(payload.tenant_id), &identifier, &token)
+                                    .await
                                 {
                                     tracing::error!("Failed to send verification SMS: {:?}", e);
                                 }
@@ -350,20 +486,52 @@ pub async fn register(
         None
     };
 
-    // 9. Return success response
-    Ok((
+    // 10. Return success response
+    Ok(created(
+        user,
+        payload.identifier_type,
+        require_verification,
+        verification_sent_to,
+    ))
+}
+
+/// Channel a new account is verified on
+fn verification_target(
+    user: &User,
+    primary_identifier: &PrimaryIdentifier,
+) -> Option<(DeliveryMethod, String)> {
+    match primary_identifier {
+        PrimaryIdentifier::Email => user.email.clone().map(|e| (DeliveryMethod::Email, e)),
+        PrimaryIdentifier::Phone => user.phone.clone().map(|p| (DeliveryMethod::Sms, p)),
+        // Verify whichever channel the account also has
+        PrimaryIdentifier::Username => user
+            .email
+            .clone()
+            .map(|e| (DeliveryMethod::Email, e))
+            .or_else(|| user.phone.clone().map(|p| (DeliveryMethod::Sms, p))),
+    }
+}
+
+fn created(
+    user: User,
+    identifier_type: String,
+    verification_required: bool,
+    verification_sent_to: Option<String>,
+) -> (StatusCode, Json<RegisterResponse>) {
+    (
         StatusCode::CREATED,
         Json(RegisterResponse {
             user_id: user.id,
             status: user.status.to_string(), // Ensure UserStatus implements Display or ToString, or map manually
             email: user.email,
             phone: user.phone,
-            identifier_type: payload.identifier_type,
-            verification_required: payload.require_verification,
+            username: user.username,
+            identifier_type,
+            verification_required,
             verification_sent_to,
             created_at: user.created_at.to_rfc3339(),
         }),
-    ))
+    )
 }
 
 #[cfg(test)]
@@ -376,11 +544,14 @@ mod tests {
             identifier_type: "email".to_string(),
             email: Some("user@example.com".to_string()),
             phone: None,
+            username: None,
             primary_identifier: None,
-            password: Some("password123".to_string()),
+            password: Some("password123".into()),
             tenant_id: Uuid::new_v4(),
             profile: serde_json::json!({}),
             require_verification: true,
+            device_fingerprint: None,
+            captcha_token: None,
         };
 
         assert_eq!(req.identifier_type, "email");
@@ -393,11 +564,14 @@ mod tests {
             identifier_type: "phone".to_string(),
             email: None,
             phone: Some("+14155552671".to_string()),
+            username: None,
             primary_identifier: None,
-            password: Some("password123".to_string()),
+            password: Some("password123".into()),
             tenant_id: Uuid::new_v4(),
             profile: serde_json::json!({}),
             require_verification: true,
+            device_fingerprint: None,
+            captcha_token: None,
         };
 
         assert_eq!(req.identifier_type, "phone");
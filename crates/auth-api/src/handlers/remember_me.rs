@@ -0,0 +1,185 @@
+use crate::error::ApiError;
+use crate::route_policy::Caller;
+use crate::AppState;
+use auth_core::error::AuthError;
+use auth_core::models::SensitiveString;
+use auth_core::services::login_history::LoginEvent;
+use auth_core::services::remember_me::RememberedDevice;
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct RememberDeviceRequest {
+    /// Stable identifier the device keeps for itself; tokens only work
+    /// when presented with it
+    #[schema(value_type = String)]
+    pub device_fingerprint: SensitiveString,
+    /// Shown in the user's device list
+    pub device_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct RedeemRememberMeRequest {
+    #[schema(value_type = String)]
+    pub token: SensitiveString,
+    #[schema(value_type = String)]
+    pub device_fingerprint: SensitiveString,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct RememberMeSignIn {
+    pub access_token: String,
+    pub expires_in: u64,
+    /// Replaces the token just redeemed, which no longer works
+    pub remember_me_token: String,
+    pub remember_me_expires_at: DateTime<Utc>,
+}
+
+/// Keep the caller signed in on this device
+///
+/// Needs a token from a full sign-in. The returned token is shown once;
+/// store it on the device.
+#[utoipa::path(
+    post,
+    path = "/auth/remember-me",
+    request_body = RememberDeviceRequest,
+    responses(
+        (status = 201, description = "Device remembered", body = IssuedRememberMe),
+        (status = 401, description = "Missing or invalid bearer token, or step-up required"),
+        (status = 403, description = "Remember-me is disabled for the tenant")
+    ),
+    tag = "Authentication"
+)]
+pub async fn remember_device(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<RememberDeviceRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let issued = state
+        .remember_me
+        .issue(
+            caller.user_id,
+            caller.tenant_id,
+            payload.device_fingerprint.expose(),
+            payload.device_name,
+        )
+        .await?;
+    Ok((StatusCode::CREATED, Json(issued)))
+}
+
+/// Sign in again on a remembered device
+///
+/// The access token comes without a refresh token, and sensitive
+/// operations refuse it until the user signs in fully.
+#[utoipa::path(
+    post,
+    path = "/auth/remember-me/redeem",
+    request_body = RedeemRememberMeRequest,
+    responses(
+        (status = 200, description = "Signed in", body = RememberMeSignIn),
+        (status = 401, description = "Unknown, used, expired or revoked token, or another device"),
+        (status = 403, description = "Remember-me is disabled for the tenant")
+    ),
+    tag = "Authentication"
+)]
+pub async fn redeem_remember_me(
+    State(state): State<AppState>,
+    Extension(request_id): Extension<Uuid>,
+    Json(payload): Json<RedeemRememberMeRequest>,
+) -> Result<Json<RememberMeSignIn>, ApiError> {
+    let with_id = |e: AuthError| ApiError::new(e).with_request_id(request_id);
+    let grant = state
+        .remember_me
+        .redeem(payload.token.expose(), payload.device_fingerprint.expose())
+        .await
+        .map_err(with_id)?;
+    let user = state
+        .identity_service
+        .get_user(grant.user_id)
+        .await
+        .map_err(with_id)?;
+    if user.tenant_id != grant.tenant_id || !user.can_authenticate() {
+        if let Err(e) = state
+            .remember_me
+            .revoke_device(grant.tenant_id, grant.user_id, grant.next.device_id)
+            .await
+        {
+            warn!(request_id = %request_id, error = ?e, "Failed to revoke remembered device");
+        }
+        return Err(with_id(AuthError::Unauthorized {
+            message: "Account locked or suspended".to_string(),
+        }));
+    }
+
+    let token = state
+        .identity_service
+        .issue_remembered_access_token(&user, grant.tenant_id)
+        .await
+        .map_err(with_id)?;
+    let event = LoginEvent::new(user.id, user.tenant_id, None, None, true);
+    if let Err(e) = state.login_history.record(event).await {
+        warn!(request_id = %request_id, error = ?e, "Failed to record login event");
+    }
+    info!(request_id = %request_id, user_id = %user.id, "Signed in on remembered device");
+    Ok(Json(RememberMeSignIn {
+        access_token: token.token,
+        expires_in: token.expires_in,
+        remember_me_token: grant.next.token,
+        remember_me_expires_at: grant.next.expires_at,
+    }))
+}
+
+/// The caller's remembered devices
+#[utoipa::path(
+    get,
+    path = "/auth/remember-me/devices",
+    responses(
+        (status = 200, description = "Devices not revoked", body = [RememberedDevice]),
+        (status = 401, description = "Missing or invalid bearer token")
+    ),
+    tag = "Authentication"
+)]
+pub async fn list_devices(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<Vec<RememberedDevice>>, ApiError> {
+    let devices = state
+        .remember_me
+        .list_devices(caller.tenant_id, caller.user_id)
+        .await?;
+    Ok(Json(devices))
+}
+
+/// Forget a remembered device
+#[utoipa::path(
+    delete,
+    path = "/auth/remember-me/devices/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Device id")
+    ),
+    responses(
+        (status = 204, description = "Device revoked"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 404, description = "No such device of the caller")
+    ),
+    tag = "Authentication"
+)]
+pub async fn revoke_device(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    state
+        .remember_me
+        .revoke_device(caller.tenant_id, caller.user_id, id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
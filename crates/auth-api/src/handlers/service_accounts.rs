@@ -0,0 +1,195 @@
+use crate::error::ApiError;
+use crate::route_policy::Caller;
+use crate::AppState;
+use auth_core::error::AuthError;
+use auth_core::models::{
+    CreateServiceAccountRequest, RegisterServiceAccountKeyRequest, ServiceAccountStatus,
+};
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+use validator::Validate;
+
+pub const MANAGE_SERVICE_ACCOUNTS_PERMISSION: &str = "tenant:manage_service_accounts";
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct UpdateServiceAccountStatus {
+    pub status: ServiceAccountStatus,
+}
+
+/// A service account may only be given scopes its creator holds, so it
+/// cannot be used to gain permissions the caller lacks
+async fn check_scopes(
+    state: &AppState,
+    caller: &Caller,
+    scopes: &[String],
+) -> Result<(), ApiError> {
+    for scope in scopes {
+        if !state
+            .role_service
+            .has_permission(caller.user_id, caller.tenant_id, scope)
+            .await?
+        {
+            return Err(ApiError::new(AuthError::AuthorizationDenied {
+                permission: scope.clone(),
+                resource: format!("tenant:{}", caller.tenant_id),
+            }));
+        }
+    }
+    Ok(())
+}
+
+/// Create a service account
+#[utoipa::path(
+    post,
+    path = "/auth/service-accounts",
+    request_body = CreateServiceAccountRequest,
+    responses(
+        (status = 201, description = "Service account created", body = ServiceAccount),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks tenant:manage_service_accounts or a requested scope")
+    ),
+    tag = "Service Accounts"
+)]
+pub async fn create_service_account(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<CreateServiceAccountRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let tenant_id = caller.tenant_id;
+    payload.validate().map_err(AuthError::from)?;
+    check_scopes(&state, &caller, &payload.scopes).await?;
+
+    let account = state.service_accounts.create(tenant_id, payload).await?;
+    Ok((StatusCode::CREATED, Json(account)))
+}
+
+/// List the tenant's service accounts
+#[utoipa::path(
+    get,
+    path = "/auth/service-accounts",
+    responses(
+        (status = 200, description = "Service accounts", body = [ServiceAccount]),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks tenant:manage_service_accounts in the tenant")
+    ),
+    tag = "Service Accounts"
+)]
+pub async fn list_service_accounts(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> Result<impl IntoResponse, ApiError> {
+    let tenant_id = caller.tenant_id;
+    let accounts = state.service_accounts.list(tenant_id).await?;
+    Ok(Json(serde_json::json!({ "service_accounts": accounts })))
+}
+
+/// Enable or disable a service account. Disabled accounts cannot obtain tokens.
+#[utoipa::path(
+    put,
+    path = "/auth/service-accounts/{id}/status",
+    params(("id" = Uuid, Path, description = "Service account ID")),
+    request_body = UpdateServiceAccountStatus,
+    responses(
+        (status = 204, description = "Status updated"),
+        (status = 404, description = "Service account not found")
+    ),
+    tag = "Service Accounts"
+)]
+pub async fn update_service_account_status(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(account_id): Path<Uuid>,
+    Json(payload): Json<UpdateServiceAccountStatus>,
+) -> Result<impl IntoResponse, ApiError> {
+    let tenant_id = caller.tenant_id;
+    state
+        .service_accounts
+        .set_status(tenant_id, account_id, payload.status)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List registered public keys, including expired and revoked ones
+#[utoipa::path(
+    get,
+    path = "/auth/service-accounts/{id}/keys",
+    params(("id" = Uuid, Path, description = "Service account ID")),
+    responses(
+        (status = 200, description = "Registered keys", body = [ServiceAccountKey]),
+        (status = 404, description = "Service account not found")
+    ),
+    tag = "Service Accounts"
+)]
+pub async fn list_keys(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(account_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let tenant_id = caller.tenant_id;
+    let keys = state.service_accounts.keys(tenant_id, account_id).await?;
+    Ok(Json(serde_json::json!({ "keys": keys })))
+}
+
+/// Register a public key. The returned `id` is the `kid` to sign assertions with.
+#[utoipa::path(
+    post,
+    path = "/auth/service-accounts/{id}/keys",
+    params(("id" = Uuid, Path, description = "Service account ID")),
+    request_body = RegisterServiceAccountKeyRequest,
+    responses(
+        (status = 201, description = "Key registered", body = ServiceAccountKey),
+        (status = 400, description = "Unparseable public key"),
+        (status = 404, description = "Service account not found"),
+        (status = 409, description = "Too many active keys")
+    ),
+    tag = "Service Accounts"
+)]
+pub async fn register_key(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(account_id): Path<Uuid>,
+    Json(payload): Json<RegisterServiceAccountKeyRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let tenant_id = caller.tenant_id;
+    payload.validate().map_err(AuthError::from)?;
+
+    let key = state
+        .service_accounts
+        .register_key(tenant_id, account_id, payload)
+        .await?;
+    Ok((StatusCode::CREATED, Json(key)))
+}
+
+/// Revoke a key immediately
+#[utoipa::path(
+    delete,
+    path = "/auth/service-accounts/{id}/keys/{kid}",
+    params(
+        ("id" = Uuid, Path, description = "Service account ID"),
+        ("kid" = Uuid, Path, description = "Key ID")
+    ),
+    responses(
+        (status = 204, description = "Key revoked"),
+        (status = 404, description = "Service account or key not found")
+    ),
+    tag = "Service Accounts"
+)]
+pub async fn revoke_key(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((account_id, key_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let tenant_id = caller.tenant_id;
+    state
+        .service_accounts
+        .revoke_key(tenant_id, account_id, key_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,267 @@
+use crate::error::ApiError;
+use crate::AppState;
+use auth_core::error::{AuthError, TokenErrorKind};
+use auth_core::events::DomainEvent;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::HeaderMap,
+    response::Response,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tokio::time::{sleep_until, Instant};
+use uuid::Uuid;
+
+/// Ping interval; proxies commonly drop connections idle for 60s
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(25);
+/// Close if nothing (not even a pong) arrived for this long
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Warn the client this long before its token or session runs out
+const EXPIRY_WARNING: Duration = Duration::from_secs(60);
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct SessionEventsParams {
+    /// Browsers cannot set headers on a WebSocket handshake
+    pub access_token: Option<String>,
+}
+
+/// Messages pushed to the client
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionPush {
+    /// This browser's session was revoked; sign out
+    SessionRevoked {
+        session_id: Uuid,
+    },
+    /// All of the user's sessions were revoked
+    SessionsRevoked,
+    /// Re-authenticate with a stronger factor before continuing
+    StepUpRequired {
+        reason: String,
+    },
+    /// Token or session ends at `expires_at`; refresh before then
+    ExpiringSoon {
+        expires_at: DateTime<Utc>,
+    },
+    Expired,
+    /// Events may have been missed; re-check the session over HTTP
+    Resync,
+}
+
+impl SessionPush {
+    /// The connection closes after these
+    fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            SessionPush::SessionRevoked { .. }
+                | SessionPush::SessionsRevoked
+                | SessionPush::Expired
+        )
+    }
+}
+
+/// What one connection is authorized to hear about
+struct Subscriber {
+    user_id: Uuid,
+    session_id: Option<Uuid>,
+    expires_at: DateTime<Utc>,
+}
+
+impl Subscriber {
+    fn message_for(&self, event: &DomainEvent) -> Option<SessionPush> {
+        if event.user_id() != Some(self.user_id) {
+            return None;
+        }
+        match event {
+            DomainEvent::SessionRevoked { session_id, .. }
+                if self.session_id == Some(*session_id) =>
+            {
+                Some(SessionPush::SessionRevoked {
+                    session_id: *session_id,
+                })
+            }
+            DomainEvent::UserSessionsRevoked { .. } => Some(SessionPush::SessionsRevoked),
+            DomainEvent::StepUpRequired { reason, .. } => Some(SessionPush::StepUpRequired {
+                reason: reason.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+/// GET /auth/session/events
+///
+/// WebSocket that pushes revocation, expiry and step-up events for the
+/// caller, replacing session polling. Authenticates with a bearer token
+/// (header or `access_token` query parameter); the SSO cookie, if present,
+/// scopes session revocations to this browser.
+pub async fn subscribe(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SessionEventsParams>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(str::to_string)
+        .or(params.access_token)
+        .ok_or(ApiError::new(AuthError::Unauthorized {
+            message: "Missing token".to_string(),
+        }))?;
+
+    let claims = state.identity_service.validate_token(&token).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| {
+        ApiError::new(AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        })
+    })?;
+    let token_expires_at = Utc
+        .timestamp_opt(claims.exp, 0)
+        .single()
+        .unwrap_or_else(Utc::now);
+
+    let session = match state.sso_cookie.read(&headers) {
+        Some(cookie) => state.session_service.validate_session(&cookie).await.ok(),
+        None => None,
+    }
+    .filter(|s| s.user_id == user_id);
+
+    let subscriber = Subscriber {
+        user_id,
+        session_id: session.as_ref().map(|s| s.id),
+        expires_at: session
+            .map(|s| s.expires_at.min(token_expires_at))
+            .unwrap_or(token_expires_at),
+    };
+    // Subscribe before the upgrade so nothing published in between is lost
+    let events = state.events.subscribe();
+
+    Ok(ws.on_upgrade(move |socket| run(socket, events, subscriber)))
+}
+
+async fn run(mut socket: WebSocket, mut events: Receiver<DomainEvent>, subscriber: Subscriber) {
+    let until_expiry = (subscriber.expires_at - Utc::now())
+        .to_std()
+        .unwrap_or_default();
+    let expire_at = Instant::now() + until_expiry;
+    let warn_at = expire_at - until_expiry.min(EXPIRY_WARNING);
+    let expire = sleep_until(expire_at);
+    let warn = sleep_until(warn_at);
+    tokio::pin!(expire, warn);
+
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+    let mut warned = false;
+
+    loop {
+        let push = tokio::select! {
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                // Pongs and client keep-alives only prove liveness
+                Some(Ok(_)) => {
+                    last_seen = Instant::now();
+                    continue;
+                }
+            },
+            event = events.recv() => match event {
+                Ok(event) => match subscriber.message_for(&event) {
+                    Some(push) => push,
+                    None => continue,
+                },
+                Err(RecvError::Lagged(_)) => SessionPush::Resync,
+                Err(RecvError::Closed) => return,
+            },
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > IDLE_TIMEOUT {
+                    return;
+                }
+                if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                    return;
+                }
+                continue;
+            },
+            _ = &mut warn, if !warned => {
+                warned = true;
+                SessionPush::ExpiringSoon {
+                    expires_at: subscriber.expires_at,
+                }
+            },
+            _ = &mut expire => SessionPush::Expired,
+        };
+
+        let Ok(text) = serde_json::to_string(&push) else {
+            continue;
+        };
+        if socket.send(Message::Text(text)).await.is_err() || push.is_terminal() {
+            let _ = socket.send(Message::Close(None)).await;
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscriber_only_hears_its_own_events() {
+        let user_id = Uuid::new_v4();
+        let session_id = Uuid::new_v4();
+        let tenant_id = Uuid::new_v4();
+        let subscriber = Subscriber {
+            user_id,
+            session_id: Some(session_id),
+            expires_at: Utc::now(),
+        };
+
+        assert_eq!(
+            subscriber.message_for(&DomainEvent::SessionRevoked {
+                tenant_id,
+                user_id,
+                session_id,
+            }),
+            Some(SessionPush::SessionRevoked { session_id })
+        );
+        // Another browser of the same user
+        assert_eq!(
+            subscriber.message_for(&DomainEvent::SessionRevoked {
+                tenant_id,
+                user_id,
+                session_id: Uuid::new_v4(),
+            }),
+            None
+        );
+        // Another user
+        assert_eq!(
+            subscriber.message_for(&DomainEvent::UserSessionsRevoked {
+                user_id: Uuid::new_v4(),
+            }),
+            None
+        );
+        assert_eq!(
+            subscriber.message_for(&DomainEvent::StepUpRequired {
+                tenant_id,
+                user_id,
+                reason: "new_device".to_string(),
+            }),
+            Some(SessionPush::StepUpRequired {
+                reason: "new_device".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use crate::error::ApiError;
+use crate::AppState;
+use auth_core::error::{AuthError, TokenErrorKind};
+use axum::{extract::State, http::HeaderMap, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate, utoipa::ToSchema)]
+pub struct SignSshKeyRequest {
+    /// OpenSSH public key, e.g. the contents of `~/.ssh/id_ed25519.pub`
+    #[validate(length(min = 1, max = 16384))]
+    pub public_key: String,
+    /// Current TOTP code; required on every request
+    pub mfa_code: Option<String>,
+    /// Shortened to the configured maximum
+    pub ttl_seconds: Option<u64>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct SignSshKeyResponse {
+    /// Save next to the private key as `id_<type>-cert.pub`
+    pub certificate: String,
+    pub serial: u64,
+    pub principals: Vec<String>,
+    pub valid_after: DateTime<Utc>,
+    pub valid_before: DateTime<Utc>,
+}
+
+/// Issue a short-lived SSH user certificate for the caller's public key.
+/// Principals and extensions are derived from the caller's roles.
+#[utoipa::path(
+    post,
+    path = "/ssh/sign",
+    request_body = SignSshKeyRequest,
+    responses(
+        (status = 200, description = "Certificate issued", body = SignSshKeyResponse),
+        (status = 400, description = "Unsupported or malformed public key"),
+        (status = 401, description = "Missing bearer token or step-up code"),
+        (status = 403, description = "No role maps to an SSH principal")
+    ),
+    tag = "SSH"
+)]
+pub async fn sign_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<SignSshKeyRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(ApiError::new(AuthError::Unauthorized {
+            message: "Missing token".to_string(),
+        }))?;
+    payload.validate().map_err(AuthError::from)?;
+
+    let claims = state
+        .identity_service
+        .validate_token(token)
+        .await
+        .map_err(ApiError::from)?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| {
+        ApiError::new(AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        })
+    })?;
+    let user = state.identity_service.get_user(user_id).await?;
+
+    let ip_address = headers
+        .get("x-forwarded-for")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.split(',').next())
+        .map(|ip| ip.trim().to_string());
+
+    let certificate = state
+        .ssh_ca
+        .sign(
+            &user,
+            &payload.public_key,
+            payload.mfa_code.as_deref(),
+            payload.ttl_seconds,
+            ip_address,
+        )
+        .await?;
+
+    Ok(Json(SignSshKeyResponse {
+        certificate: certificate.certificate,
+        serial: certificate.serial,
+        principals: certificate.principals,
+        valid_after: certificate.valid_after,
+        valid_before: certificate.valid_before,
+    }))
+}
+
+/// CA public keys to install as `TrustedUserCAKeys`, active key first
+#[utoipa::path(
+    get,
+    path = "/ssh/ca",
+    responses(
+        (status = 200, description = "Trusted CA public keys")
+    ),
+    tag = "SSH"
+)]
+pub async fn ca_keys(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let keys = state.ssh_ca.trusted_keys()?;
+    Ok(Json(serde_json::json!({ "keys": keys })))
+}
@@ -0,0 +1,396 @@
+use crate::error::ApiError;
+use crate::handlers::oidc_provider::{append_query, BROWSER_STATE_COOKIE};
+use crate::handlers::verification::html_escape;
+use crate::AppState;
+use auth_core::error::{AuthError, TokenErrorKind};
+use auth_core::models::ClientSession;
+use auth_core::services::risk_assessment::RiskContext;
+use auth_protocols::upstream_logout::{KeptReason, LogoutStep, UpstreamStillSignedIn};
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form, Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct SsoSessionResponse {
+    pub session_id: Uuid,
+    pub expires_at: chrono::DateTime<Utc>,
+    /// Products signed in through this session
+    pub clients: Vec<ClientSession>,
+}
+
+/// OIDC RP-Initiated Logout parameters
+#[derive(Debug, Deserialize)]
+pub struct EndSessionParams {
+    pub client_id: Option<String>,
+    pub post_logout_redirect_uri: Option<String>,
+    pub state: Option<String>,
+}
+
+/// Parameters an upstream provider returns with from its logout page
+#[derive(Debug, Deserialize)]
+pub struct UpstreamLogoutParams {
+    /// OIDC providers
+    pub state: Option<String>,
+    /// SAML providers
+    #[serde(rename = "RelayState")]
+    pub relay_state: Option<String>,
+    #[serde(rename = "SAMLResponse")]
+    pub saml_response: Option<String>,
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+/// POST /auth/sso/session
+///
+/// Exchanges a bearer token from a first-party login for the central SSO
+/// cookie. A previous SSO session in the same browser is logged out first.
+pub async fn create_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let user = caller(&state, &headers).await?;
+
+    if let Some(previous) = state.sso_cookie.read(&headers) {
+        if let Ok(session) = state.session_service.validate_session(&previous).await {
+            state.sso.logout(&session).await?;
+            state.session_service.revoke_session(&previous).await?;
+        }
+    }
+
+    let risk_context = RiskContext {
+        user_id: user.id,
+        tenant_id: user.tenant_id,
+        ip_address: None,
+        user_agent: headers
+            .get(header::USER_AGENT)
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string),
+        device_fingerprint: None,
+        geolocation: None,
+        previous_logins: vec![],
+    };
+    let session = state
+        .session_service
+        .create_session(user, risk_context)
+        .await?;
+
+    let max_age = (session.expires_at - Utc::now()).num_seconds().max(0);
+    let mut response = (
+        StatusCode::CREATED,
+        Json(SsoSessionResponse {
+            session_id: session.id,
+            expires_at: session.expires_at,
+            clients: vec![],
+        }),
+    )
+        .into_response();
+    set_cookie(
+        &mut response,
+        &state
+            .sso_cookie
+            .session_cookie(&session.session_token, max_age),
+    );
+    Ok(response)
+}
+
+/// GET /auth/sso/session
+///
+/// The SSO session behind the cookie and the products joined to it.
+pub async fn get_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<SsoSessionResponse>, ApiError> {
+    let token = state
+        .sso_cookie
+        .read(&headers)
+        .ok_or(ApiError::new(AuthError::Unauthorized {
+            message: "No SSO session".to_string(),
+        }))?;
+    let session = state.session_service.validate_session(&token).await?;
+    let clients = state.sso.client_sessions(session.id).await?;
+
+    Ok(Json(SsoSessionResponse {
+        session_id: session.id,
+        expires_at: session.expires_at,
+        clients,
+    }))
+}
+
+/// GET /auth/logout
+///
+/// Ends the SSO session and every product session derived from it. Products
+/// with a back-channel endpoint are notified server-side; front-channel ones
+/// are loaded in hidden iframes by the returned page, which then follows a
+/// registered `post_logout_redirect_uri`. If the user signed in through
+/// upstream identity providers that propagate logout, the page goes through
+/// their logout endpoints first.
+pub async fn end_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<EndSessionParams>,
+) -> Result<Response, ApiError> {
+    // Only redirect to URIs the client registered, never to arbitrary input
+    let mut redirect = None;
+    if let (Some(client_id), Some(uri)) = (&params.client_id, &params.post_logout_redirect_uri) {
+        if state
+            .sso
+            .post_logout_redirect_allowed(client_id, uri)
+            .await?
+        {
+            redirect = Some(match &params.state {
+                Some(s) => append_query(uri, &format!("state={}", urlencoding::encode(s))),
+                None => uri.clone(),
+            });
+        }
+    }
+
+    let mut frontchannel_uris = Vec::new();
+    let mut step = None;
+    if let Some(token) = state.sso_cookie.read(&headers) {
+        if let Ok(session) = state.session_service.validate_session(&token).await {
+            frontchannel_uris = state.sso.logout(&session).await?.frontchannel_uris;
+            step = Some(
+                state
+                    .upstream_logout
+                    .begin(&session, redirect.clone())
+                    .await?,
+            );
+        }
+        state.session_service.revoke_session(&token).await?;
+    }
+
+    let page = match step {
+        Some(LogoutStep::Upstream(url)) => logout_page(&frontchannel_uris, Some(&url), &[]),
+        Some(LogoutStep::Done(summary)) => logout_page(
+            &frontchannel_uris,
+            summary.final_redirect.as_deref(),
+            &summary.still_signed_in,
+        ),
+        None => logout_page(&frontchannel_uris, redirect.as_deref(), &[]),
+    };
+    let mut response = Html(page).into_response();
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    set_cookie(&mut response, &state.sso_cookie.clear_cookie());
+    set_cookie(
+        &mut response,
+        &format!(
+            "{}=; Path=/; Secure; SameSite=None; Max-Age=0",
+            BROWSER_STATE_COOKIE
+        ),
+    );
+    Ok(response)
+}
+
+/// GET /auth/logout/upstream
+///
+/// Where upstream identity providers send the browser after their logout
+/// page: on to the next provider, or to the landing page.
+pub async fn upstream_logout_return(
+    State(state): State<AppState>,
+    Query(params): Query<UpstreamLogoutParams>,
+) -> Result<Response, ApiError> {
+    continue_upstream_logout(&state, params).await
+}
+
+/// POST /auth/logout/upstream
+///
+/// The same for SAML providers answering over the HTTP-POST binding.
+pub async fn upstream_logout_return_form(
+    State(state): State<AppState>,
+    Form(params): Form<UpstreamLogoutParams>,
+) -> Result<Response, ApiError> {
+    continue_upstream_logout(&state, params).await
+}
+
+// ============================================================================
+// Helpers
+// ============================================================================
+
+async fn continue_upstream_logout(
+    state: &AppState,
+    params: UpstreamLogoutParams,
+) -> Result<Response, ApiError> {
+    let logout_state = params.relay_state.or(params.state).ok_or_else(|| {
+        ApiError::new(AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        })
+    })?;
+    let step = state
+        .upstream_logout
+        .resume(&logout_state, params.saml_response.as_deref())
+        .await?;
+
+    let summary = match step {
+        LogoutStep::Upstream(url) => return Ok(Redirect::to(&url).into_response()),
+        LogoutStep::Done(summary) => summary,
+    };
+    let complete = !summary.is_partial();
+    if let Some(uri) = summary.final_redirect.as_deref().filter(|_| complete) {
+        return Ok(Redirect::to(uri).into_response());
+    }
+    let mut response = Html(logout_page(
+        &[],
+        summary.final_redirect.as_deref(),
+        &summary.still_signed_in,
+    ))
+    .into_response();
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    Ok(response)
+}
+
+async fn caller(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<auth_core::models::User, ApiError> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(ApiError::new(AuthError::Unauthorized {
+            message: "Missing token".to_string(),
+        }))?;
+
+    let claims = state
+        .identity_service
+        .validate_token(token)
+        .await
+        .map_err(ApiError::from)?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| {
+        ApiError::new(AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        })
+    })?;
+    Ok(state.identity_service.get_user(user_id).await?)
+}
+
+fn set_cookie(response: &mut Response, cookie: &str) {
+    if let Ok(value) = HeaderValue::from_str(cookie) {
+        response.headers_mut().append(header::SET_COOKIE, value);
+    }
+}
+
+/// Waits for the front-channel iframes (or 3s) before redirecting. With
+/// providers the user is still signed in to, the page lists them and leaves
+/// `redirect` as a link instead, so the user sees why they may be signed
+/// straight back in.
+fn logout_page(
+    frontchannel_uris: &[String],
+    redirect: Option<&str>,
+    still_signed_in: &[UpstreamStillSignedIn],
+) -> String {
+    let iframes: String = frontchannel_uris
+        .iter()
+        .map(|uri| {
+            format!(
+                r#"<iframe src="{}" style="display:none" onload="done()" onerror="done()"></iframe>"#,
+                html_escape(uri)
+            )
+        })
+        .collect();
+    let mut notice = String::new();
+    if !still_signed_in.is_empty() {
+        notice.push_str(
+            "<p>You are still signed in to these accounts. Anyone using this \
+             browser can sign in again with them until you sign out there:</p><ul>",
+        );
+        for provider in still_signed_in {
+            let reason = match provider.reason {
+                KeptReason::NotPropagated => "not signed out automatically",
+                KeptReason::Unsupported => "cannot be signed out from here",
+                KeptReason::Failed => "did not confirm the sign-out",
+            };
+            notice.push_str(&format!(
+                "<li>{} ({})</li>",
+                html_escape(&provider.display_name),
+                reason
+            ));
+        }
+        notice.push_str("</ul>");
+        if let Some(uri) = redirect {
+            notice.push_str(&format!(
+                r#"<p><a href="{}">Continue</a></p>"#,
+                html_escape(uri)
+            ));
+        }
+    }
+    let redirect_js = match redirect.filter(|_| still_signed_in.is_empty()) {
+        Some(uri) => format!(
+            "window.location.replace({});",
+            // JSON string literal, with `<` escaped so it cannot close the script
+            serde_json::to_string(uri)
+                .unwrap_or_else(|_| "\"/\"".to_string())
+                .replace('<', "\\u003c")
+        ),
+        None => String::new(),
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><title>Signed out</title></head>
+<body>
+<p>You have been signed out.</p>
+{notice}
+<script>
+var pending = {count};
+function finish() {{ {redirect_js} }}
+function done() {{ if (--pending <= 0) finish(); }}
+if (pending === 0) finish(); else setTimeout(finish, 3000);
+</script>
+{iframes}
+</body>
+</html>"#,
+        count = frontchannel_uris.len(),
+        notice = notice,
+        redirect_js = redirect_js,
+        iframes = iframes
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logout_page_escapes_frontchannel_uris() {
+        let page = logout_page(
+            &["https://app.example/logout?iss=a&sid=\"x\"".to_string()],
+            Some("https://app.example/</script>"),
+            &[],
+        );
+        assert!(page.contains("iss=a&amp;sid=&quot;x&quot;"));
+        assert!(!page.contains("</script>\");"));
+    }
+
+    #[test]
+    fn test_partial_logout_page_explains_and_does_not_redirect() {
+        let page = logout_page(
+            &[],
+            Some("https://app.example/bye"),
+            &[UpstreamStillSignedIn {
+                provider: "adfs".to_string(),
+                display_name: "Contoso <AD FS>".to_string(),
+                reason: KeptReason::Failed,
+            }],
+        );
+        assert!(page.contains("Contoso &lt;AD FS&gt; (did not confirm the sign-out)"));
+        assert!(page.contains(r#"<a href="https://app.example/bye">Continue</a>"#));
+        assert!(!page.contains("window.location.replace"));
+    }
+}
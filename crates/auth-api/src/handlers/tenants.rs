@@ -0,0 +1,267 @@
+use crate::error::ApiError;
+use crate::route_policy::Caller;
+use crate::AppState;
+use auth_core::error::AuthError;
+use auth_core::services::adaptive_quota::AdaptiveLimitReport;
+use auth_core::services::forced_reauth::{ClientRevocation, ForcedReauth};
+use auth_core::services::tenant_metrics::TenantMetricsReport;
+use auth_core::services::tenant_onboarding::OnboardingStatus;
+use auth_core::services::workflow::FlowAction;
+use axum::{
+    extract::{Extension, Path, State},
+    Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+pub const FORCE_REAUTH_PERMISSION: &str = "tenant:force_reauth";
+pub const REVOKE_CLIENT_TOKENS_PERMISSION: &str = "tenant:revoke_client_tokens";
+pub const VIEW_METRICS_PERMISSION: &str = "tenant:view_metrics";
+pub const ONBOARDING_PERMISSION: &str = "tenant:onboard";
+
+#[derive(Debug, Default, Deserialize, utoipa::ToSchema)]
+pub struct ForceReauthRequest {
+    /// Recorded in the audit log
+    pub reason: Option<String>,
+}
+
+/// Sign out every user of the tenant
+///
+/// Revokes all sessions and refresh token families of the tenant and bumps
+/// its token generation, so access tokens already issued stop validating at
+/// once. The caller is signed out too.
+#[utoipa::path(
+    post,
+    path = "/admin/api/tenants/{id}/force-reauth",
+    params(
+        ("id" = Uuid, Path, description = "Tenant ID")
+    ),
+    request_body = ForceReauthRequest,
+    responses(
+        (status = 200, description = "Tenant signed out", body = ForcedReauth),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks tenant:force_reauth in the tenant")
+    ),
+    tag = "Tenants"
+)]
+pub async fn force_reauth(
+    State(state): State<AppState>,
+    Path(tenant_id): Path<Uuid>,
+    Extension(caller): Extension<Caller>,
+    body: Option<Json<ForceReauthRequest>>,
+) -> Result<Json<ForcedReauth>, ApiError> {
+    // Administrators act on their own tenant only
+    if caller.tenant_id != tenant_id {
+        return Err(ApiError::new(AuthError::AuthorizationDenied {
+            permission: FORCE_REAUTH_PERMISSION.to_string(),
+            resource: format!("tenant:{}", tenant_id),
+        }));
+    }
+
+    let reason = body.and_then(|Json(request)| request.reason);
+    let outcome = state
+        .forced_reauth
+        .force_tenant(tenant_id, caller.user_id, reason)
+        .await?;
+    Ok(Json(outcome))
+}
+
+/// Revoke the refresh tokens issued to an OAuth client
+///
+/// For a client whose secret has leaked: every refresh token the tenant
+/// issued to it stops working, whoever holds it. The client's access tokens
+/// run out at their expiry, and its users sign in again.
+#[utoipa::path(
+    post,
+    path = "/admin/api/tenants/{id}/clients/{client_id}/revoke-tokens",
+    params(
+        ("id" = Uuid, Path, description = "Tenant ID"),
+        ("client_id" = String, Path, description = "OAuth client ID")
+    ),
+    request_body = ForceReauthRequest,
+    responses(
+        (status = 200, description = "Client's refresh tokens revoked", body = ClientRevocation),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks tenant:revoke_client_tokens in the tenant")
+    ),
+    tag = "Tenants"
+)]
+pub async fn revoke_client_tokens(
+    State(state): State<AppState>,
+    Path((tenant_id, client_id)): Path<(Uuid, String)>,
+    Extension(caller): Extension<Caller>,
+    body: Option<Json<ForceReauthRequest>>,
+) -> Result<Json<ClientRevocation>, ApiError> {
+    if caller.tenant_id != tenant_id {
+        return Err(ApiError::new(AuthError::AuthorizationDenied {
+            permission: REVOKE_CLIENT_TOKENS_PERMISSION.to_string(),
+            resource: format!("tenant:{}", tenant_id),
+        }));
+    }
+
+    let reason = body.and_then(|Json(request)| request.reason);
+    let outcome = state
+        .forced_reauth
+        .revoke_client(tenant_id, &client_id, caller.user_id, reason)
+        .await?;
+    Ok(Json(outcome))
+}
+
+/// Usage of the tenant over the last days
+///
+/// Counts are for full UTC days and change once a day. Small values are
+/// withheld, and values may be noised and rounded, depending on the class
+/// of the metric; see `noised` and `rounded_to`.
+#[utoipa::path(
+    get,
+    path = "/admin/api/tenants/{id}/metrics",
+    params(
+        ("id" = Uuid, Path, description = "Tenant ID")
+    ),
+    responses(
+        (status = 200, description = "Tenant metrics", body = TenantMetricsReport),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks tenant:view_metrics in the tenant")
+    ),
+    tag = "Tenants"
+)]
+pub async fn metrics(
+    State(state): State<AppState>,
+    Path(tenant_id): Path<Uuid>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<TenantMetricsReport>, ApiError> {
+    if caller.tenant_id != tenant_id {
+        return Err(ApiError::new(AuthError::AuthorizationDenied {
+            permission: VIEW_METRICS_PERMISSION.to_string(),
+            resource: format!("tenant:{}", tenant_id),
+        }));
+    }
+
+    let report = state.tenant_metrics.report(tenant_id, Utc::now()).await?;
+    Ok(Json(report))
+}
+
+/// Tenant request rate limit
+///
+/// The limit in force now, where it comes from, the learned hourly traffic
+/// profile and the recent changes of the limit.
+#[utoipa::path(
+    get,
+    path = "/admin/api/tenants/{id}/rate-limit",
+    params(
+        ("id" = Uuid, Path, description = "Tenant ID")
+    ),
+    responses(
+        (status = 200, description = "Effective rate limit", body = AdaptiveLimitReport),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks tenant:view_metrics in the tenant")
+    ),
+    tag = "Tenants"
+)]
+pub async fn rate_limit(
+    State(state): State<AppState>,
+    Path(tenant_id): Path<Uuid>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<AdaptiveLimitReport>, ApiError> {
+    if caller.tenant_id != tenant_id {
+        return Err(ApiError::new(AuthError::AuthorizationDenied {
+            permission: VIEW_METRICS_PERMISSION.to_string(),
+            resource: format!("tenant:{}", tenant_id),
+        }));
+    }
+
+    Ok(Json(state.quotas.adaptive_report(tenant_id).await))
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct OnboardingActionRequest {
+    /// `submit`, `skip`, `back`, `send_test_email`, `test_sso` or `finish`
+    pub action: String,
+    /// Settings of the current step for `submit`, `{"to": ...}` for
+    /// `send_test_email`, `{"provider": ...}` for `test_sso`
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub payload: Value,
+    /// `version` of the status the action was chosen on; the action is
+    /// refused if the setup has moved on since
+    pub version: Option<u64>,
+}
+
+fn check_onboarding_tenant(caller: &Caller, tenant_id: Uuid) -> Result<(), ApiError> {
+    if caller.tenant_id != tenant_id {
+        return Err(ApiError::new(AuthError::AuthorizationDenied {
+            permission: ONBOARDING_PERMISSION.to_string(),
+            resource: format!("tenant:{}", tenant_id),
+        }));
+    }
+    Ok(())
+}
+
+/// Tenant setup checklist
+///
+/// Each setup step with its status and submitted settings, the step to
+/// complete next and the results of the latest test actions.
+#[utoipa::path(
+    get,
+    path = "/admin/api/tenants/{id}/onboarding",
+    params(
+        ("id" = Uuid, Path, description = "Tenant ID")
+    ),
+    responses(
+        (status = 200, description = "Setup progress", body = OnboardingStatus),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks tenant:onboard in the tenant")
+    ),
+    tag = "Tenants"
+)]
+pub async fn onboarding_status(
+    State(state): State<AppState>,
+    Path(tenant_id): Path<Uuid>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<OnboardingStatus>, ApiError> {
+    check_onboarding_tenant(&caller, tenant_id)?;
+    Ok(Json(state.onboarding.status(tenant_id).await?))
+}
+
+/// Act on the current setup step
+///
+/// `submit` records the step's settings and moves to the next step, `skip`
+/// passes over an optional step and `back` returns to the previous one.
+/// `finish` on the review step checks every step and applies the settings
+/// to the tenant. Test actions can be run on any step and do not move it.
+#[utoipa::path(
+    post,
+    path = "/admin/api/tenants/{id}/onboarding",
+    params(
+        ("id" = Uuid, Path, description = "Tenant ID")
+    ),
+    request_body = OnboardingActionRequest,
+    responses(
+        (status = 200, description = "Setup progress after the action", body = OnboardingStatus),
+        (status = 400, description = "Invalid settings, or an action the step does not allow"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks tenant:onboard in the tenant"),
+        (status = 409, description = "Setup is finished or has moved past `version`")
+    ),
+    tag = "Tenants"
+)]
+pub async fn onboarding_action(
+    State(state): State<AppState>,
+    Path(tenant_id): Path<Uuid>,
+    Extension(caller): Extension<Caller>,
+    Json(request): Json<OnboardingActionRequest>,
+) -> Result<Json<OnboardingStatus>, ApiError> {
+    check_onboarding_tenant(&caller, tenant_id)?;
+    let action = FlowAction {
+        name: request.action,
+        payload: request.payload,
+    };
+    Ok(Json(
+        state
+            .onboarding
+            .act(tenant_id, action, request.version)
+            .await?,
+    ))
+}
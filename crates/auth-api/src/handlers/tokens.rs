@@ -0,0 +1,106 @@
+use crate::error::ApiError;
+use crate::AppState;
+use auth_core::error::{AuthError, TokenErrorKind};
+use auth_core::models::Claims;
+use axum::{extract::State, http::HeaderMap, Json};
+use serde::{Deserialize, Serialize};
+
+/// Most tokens accepted in one batch validation request
+pub const MAX_BATCH_TOKENS: usize = 100;
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct ValidateBatchRequest {
+    pub tokens: Vec<String>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct TokenValidationResult {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub claims: Option<Claims>,
+    /// Error code when the token is not active
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct ValidateBatchResponse {
+    /// One entry per submitted token, in request order
+    pub results: Vec<TokenValidationResult>,
+}
+
+/// Tenant of the calling gateway; tokens are only vouched for within it
+async fn caller_tenant(state: &AppState, headers: &HeaderMap) -> Result<String, ApiError> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(ApiError::new(AuthError::Unauthorized {
+            message: "Missing token".to_string(),
+        }))?;
+
+    let claims = state.identity_service.validate_token(token).await?;
+    Ok(claims.tenant_id)
+}
+
+/// Validate a batch of access tokens
+///
+/// Signatures are checked in parallel and revocation is looked up once for
+/// the whole batch. Tokens from another tenant than the caller's are
+/// reported inactive.
+#[utoipa::path(
+    post,
+    path = "/auth/tokens/validate-batch",
+    request_body = ValidateBatchRequest,
+    responses(
+        (status = 200, description = "Per-token results in request order", body = ValidateBatchResponse),
+        (status = 400, description = "Empty batch or too many tokens"),
+        (status = 401, description = "Missing or invalid bearer token")
+    ),
+    tag = "Authentication"
+)]
+pub async fn validate_batch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<ValidateBatchRequest>,
+) -> Result<Json<ValidateBatchResponse>, ApiError> {
+    let tenant_id = caller_tenant(&state, &headers).await?;
+    if payload.tokens.is_empty() || payload.tokens.len() > MAX_BATCH_TOKENS {
+        return Err(ApiError::new(AuthError::ValidationError {
+            message: format!("Submit between 1 and {} tokens", MAX_BATCH_TOKENS),
+        }));
+    }
+
+    let results = state
+        .identity_service
+        .validate_tokens(&payload.tokens)
+        .await?
+        .into_iter()
+        .map(|result| {
+            let claims = result.and_then(|claims| {
+                if claims.tenant_id == tenant_id {
+                    Ok(claims)
+                } else {
+                    Err(AuthError::TokenError {
+                        kind: TokenErrorKind::Invalid,
+                    })
+                }
+            });
+            match claims {
+                Ok(claims) => TokenValidationResult {
+                    active: true,
+                    claims: Some(claims),
+                    error: None,
+                },
+                Err(e) => TokenValidationResult {
+                    active: false,
+                    claims: None,
+                    error: Some(e.code().to_string()),
+                },
+            }
+        })
+        .collect();
+
+    Ok(Json(ValidateBatchResponse { results }))
+}
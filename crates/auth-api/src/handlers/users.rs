@@ -1,12 +1,30 @@
 use crate::error::ApiError;
+use crate::route_policy::Caller;
 use crate::AppState;
+use auth_core::error::AuthError;
 use axum::{
-    extract::{Path, State},
+    extract::{Extension, Path, State},
     Json,
 };
 use serde_json::json;
 use uuid::Uuid;
 
+/// Required to suspend and reactivate users
+pub const MANAGE_PERMISSION: &str = "user:write";
+
+/// Users of other tenants are reported as not found
+async fn ensure_same_tenant(
+    state: &AppState,
+    caller: &Caller,
+    user_id: Uuid,
+) -> Result<(), ApiError> {
+    let user = state.identity_service.get_user(user_id).await?;
+    if user.tenant_id != caller.tenant_id {
+        return Err(ApiError::new(AuthError::UserNotFound));
+    }
+    Ok(())
+}
+
 /// Suspend a user account (Admin only)
 #[utoipa::path(
     post,
@@ -16,6 +34,8 @@ use uuid::Uuid;
     ),
     responses(
         (status = 200, description = "User suspended successfully"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks user:write in the tenant"),
         (status = 404, description = "User not found")
     ),
     tag = "User Management"
@@ -23,7 +43,9 @@ use uuid::Uuid;
 pub async fn ban_user(
     State(state): State<AppState>,
     Path(user_id): Path<Uuid>,
+    Extension(caller): Extension<Caller>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
+    ensure_same_tenant(&state, &caller, user_id).await?;
     state.identity_service.ban_user(user_id).await?;
     Ok(Json(
         json!({"status": "success", "message": "User suspended"}),
@@ -39,6 +61,8 @@ pub async fn ban_user(
     ),
     responses(
         (status = 200, description = "User activated successfully"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller lacks user:write in the tenant"),
         (status = 404, description = "User not found")
     ),
     tag = "User Management"
@@ -46,7 +70,9 @@ pub async fn ban_user(
 pub async fn activate_user(
     State(state): State<AppState>,
     Path(user_id): Path<Uuid>,
+    Extension(caller): Extension<Caller>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
+    ensure_same_tenant(&state, &caller, user_id).await?;
     state.identity_service.activate_user(user_id).await?;
     Ok(Json(
         json!({"status": "success", "message": "User activated"}),
@@ -5,9 +5,9 @@
 //! - Phone Verification (SMS Code)
 
 use axum::{
-    extract::{Json, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Form, Json, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse},
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -15,10 +15,11 @@ use uuid::Uuid;
 
 use crate::error::ApiError;
 use auth_core::error::TokenErrorKind;
+use auth_core::models::SensitiveString;
 use auth_core::services::{
     identity::IdentityService,
     otp_delivery::OtpDeliveryService,
-    otp_service::{DeliveryMethod, OtpPurpose, OtpService, TokenType},
+    otp_service::{DeliveryMethod, OtpError, OtpPurpose, OtpService},
     rate_limiter::RateLimiter,
 };
 use auth_db::repositories::otp_repository::OtpRepository;
@@ -59,12 +60,12 @@ pub struct VerificationResponse {
 pub struct ConfirmVerificationRequest {
     pub user_id: Uuid,
     pub verification_id: Uuid,
-    pub code: String,
+    pub code: SensitiveString,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct MagicLinkQuery {
-    pub token: String,
+    pub token: SensitiveString,
     pub verification_id: Uuid,
 }
 
@@ -73,7 +74,7 @@ pub struct MagicLinkQuery {
 // ============================================================================
 
 /// POST /auth/verify/email/send
-/// Sends a Magic Link (and optional code) to the user's email
+/// Sends a Magic Link and a 6-digit code to the user's email
 pub async fn send_email_verification(
     State(identity_service): State<Arc<IdentityService>>,
     State(otp_service): State<Arc<OtpService>>,
@@ -121,33 +122,32 @@ pub async fn send_email_verification(
         ));
     }
 
-    // 3. Generate Magic Link Token (High Entropy)
-    // We use a longer token (32 chars) for Magic Links
-    let token: String = otp_service.generate_token(TokenType::Alphanumeric, 32); // Removed .await as generate_token is not async
-
-    // 4. Create Session
-    // We use a longer TTL (e.g., 24 hours) for email verification links
+    // 3. Create a session around a magic link token, and a typed code for
+    // mail clients whose scanners break links. Either completes the session,
+    // which lives as long as the tenant's magic link policy allows.
     let tenant_id = user.tenant_id;
-    let (session, _) = otp_service.create_session(
+    let (session, token) = otp_service.create_session(
         tenant_id,
         email.clone(),
         "email".to_string(),
         DeliveryMethod::Email,
-        OtpPurpose::EmailVerification,
+        OtpPurpose::MagicLink,
         Some(user.id),
-        Some(token.clone()), // Explicit token
-        Some(1440),          // 24 hours
-    )?; // Removed .await as create_session is not async
+        None,
+        None,
+    )?;
+    let code = otp_service.generate_for(tenant_id, &OtpPurpose::EmailVerification);
 
-    // 5. Save to DB
+    // 4. Save to DB
     let token_hash = otp_service.hash_otp(&token)?;
+    let code_hash = otp_service.hash_otp(&code)?;
     otp_repo
-        .create_session(&session, &token_hash)
+        .create_session_with_code(&session, &token_hash, &code_hash)
         .await
         .map_err(|_| ApiError::new(auth_core::error::AuthError::InternalError))?;
 
-    // 6. Send Email
-    // Construct Link: https://api.upflame.com/auth/verify/email?token=...&verification_id=...
+    // 5. Send Email
+    // The link opens a confirmation page; nothing is consumed until it is submitted
     // In production, this base URL should be configurable per tenant or env
     let base_url =
         std::env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
@@ -157,7 +157,7 @@ pub async fn send_email_verification(
     );
 
     otp_delivery
-        .send_verification_email(&email, &link)
+        .send_verification_email(&email, &link, Some(&code))
         .await
         .map_err(ApiError::from)?;
 
@@ -173,62 +173,161 @@ pub async fn send_email_verification(
 }
 
 /// GET /auth/verify/email
-/// Magic Link Handler
-pub async fn verify_email_link(
+/// Magic link landing page. Mail scanners prefetch links, so this only
+/// renders a form; the token is spent when the user submits it.
+pub async fn verify_email_link(Query(query): Query<MagicLinkQuery>) -> impl IntoResponse {
+    let page = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><meta name="robots" content="noindex"><title>Verify your email</title></head>
+<body>
+<form method="post" action="email/confirm-link">
+<input type="hidden" name="token" value="{}">
+<input type="hidden" name="verification_id" value="{}">
+<p>Confirm that you want to verify this email address.</p>
+<button type="submit">Verify email</button>
+</form>
+</body>
+</html>"#,
+        html_escape(query.token.expose()),
+        query.verification_id
+    );
+
+    (
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::REFERRER_POLICY, "no-referrer"),
+        ],
+        Html(page),
+    )
+}
+
+/// POST /auth/verify/email/confirm-link
+/// Submitted from the landing page
+pub async fn confirm_email_link(
     State(identity_service): State<Arc<IdentityService>>,
     State(otp_service): State<Arc<OtpService>>,
     State(otp_repo): State<Arc<OtpRepository>>,
-    Query(query): Query<MagicLinkQuery>,
+    Form(form): Form<MagicLinkQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let verified = verify_email_proof(
+        &identity_service,
+        &otp_service,
+        &otp_repo,
+        form.verification_id,
+        None,
+        form.token.expose(),
+    )
+    .await?;
+
+    // Already verified is idempotent here: a user may click the link after typing the code
+    Ok(if verified {
+        "Email verified successfully! You can now close this window.".to_string()
+    } else {
+        "Email already verified".to_string()
+    })
+}
+
+/// POST /auth/verify/email/confirm
+/// Verifies the emailed code; spends the link as well
+pub async fn confirm_email_verification(
+    State(identity_service): State<Arc<IdentityService>>,
+    State(otp_service): State<Arc<OtpService>>,
+    State(otp_repo): State<Arc<OtpRepository>>,
+    Json(payload): Json<ConfirmVerificationRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
+    let verified = verify_email_proof(
+        &identity_service,
+        &otp_service,
+        &otp_repo,
+        payload.verification_id,
+        Some(payload.user_id),
+        payload.code.expose(),
+    )
+    .await?;
+    if !verified {
+        return Err(ApiError::new(auth_core::error::AuthError::TokenError {
+            kind: TokenErrorKind::Replayed,
+        }));
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "success": true,
+            "message": "Email verified successfully"
+        })),
+    ))
+}
+
+/// Check a link token or code against the session and mark the email
+/// verified. Returns false if the session was already completed.
+async fn verify_email_proof(
+    identity_service: &IdentityService,
+    otp_service: &OtpService,
+    otp_repo: &OtpRepository,
+    verification_id: Uuid,
+    expected_user: Option<Uuid>,
+    proof: &str,
+) -> Result<bool, ApiError> {
     // 1. Fetch Session
-    let (session, token_hash): (auth_core::services::otp_service::OtpSession, String) = otp_repo
-        .find_by_id(query.verification_id)
+    let (session, _) = otp_repo
+        .find_by_id(verification_id)
         .await?
         .ok_or(ApiError::new(auth_core::error::AuthError::TokenError {
             kind: TokenErrorKind::Invalid,
         }))?;
+    let Some(user_id) = session.user_id else {
+        return Err(ApiError::new(auth_core::error::AuthError::InternalError));
+    };
+    if expected_user.is_some_and(|expected| expected != user_id) {
+        return Err(ApiError::new(auth_core::error::AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        }));
+    }
 
     // 2. Validate
+    if otp_service.is_verified(&session) {
+        return Ok(false);
+    }
     if otp_service.is_expired(&session) {
-        // is_expired returns bool directly
         return Err(ApiError::new(auth_core::error::AuthError::TokenError {
             kind: TokenErrorKind::Expired,
         }));
     }
-    if otp_service.is_verified(&session) {
-        // is_verified is not fallible and doesn't need map_err
-        // Already verified, return success idempotent
-        return Ok("Email already verified".to_string());
-    }
 
-    // 3. Verify Token
-    if !otp_service
-        .verify_otp(&query.token, &token_hash)
-        .map_err(|_| ApiError::new(auth_core::error::AuthError::InternalError))?
+    // 3. Verify against the link token and the code in one attempt
+    let hashes = otp_repo.find_proof_hashes(session.id).await?;
+    let hashes: Vec<&str> = hashes.iter().map(String::as_str).collect();
+    match otp_service
+        .verify_any_with_store(otp_repo, session.id, proof, &hashes)
+        .await
     {
-        otp_repo
-            .increment_attempts(session.id)
-            .await
-            .map_err(|_| ApiError::new(auth_core::error::AuthError::InternalError))?;
-        return Err(ApiError::new(auth_core::error::AuthError::TokenError {
-            kind: TokenErrorKind::Invalid,
-        }));
+        Ok(_) => {}
+        Err(OtpError::AlreadyVerified) => return Ok(false),
+        Err(OtpError::Invalid) => {
+            return Err(ApiError::new(auth_core::error::AuthError::TokenError {
+                kind: TokenErrorKind::Invalid,
+            }));
+        }
+        Err(e) => return Err(e.into()),
     }
 
     // 4. Update User Status
-    if let Some(user_id) = session.user_id {
-        identity_service
-            .mark_email_verified(user_id)
-            .await
-            .map_err(|_| ApiError::new(auth_core::error::AuthError::InternalError))?;
-        otp_repo
-            .mark_verified(session.id)
-            .await
-            .map_err(|_| ApiError::new(auth_core::error::AuthError::InternalError))?;
-        Ok("Email verified successfully! You can now close this window.".to_string())
-    } else {
-        Err(ApiError::new(auth_core::error::AuthError::InternalError))
-    }
+    identity_service
+        .mark_email_verified(user_id)
+        .await
+        .map_err(|_| ApiError::new(auth_core::error::AuthError::InternalError))?;
+    Ok(true)
+}
+
+pub(crate) fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#x27;")
 }
 
 // ============================================================================
@@ -270,7 +369,7 @@ pub async fn send_phone_verification(
         ));
     }
 
-    // Generate numeric OTP (6 digits)
+    // Length and lifetime follow the tenant's verification policy
     let tenant_id = user.tenant_id;
     let (session, otp): (auth_core::services::otp_service::OtpSession, String) = otp_service
         .create_session(
@@ -280,8 +379,8 @@ pub async fn send_phone_verification(
             DeliveryMethod::Sms,
             OtpPurpose::PhoneVerification,
             Some(user.id),
-            None,     // auto-generate
-            Some(10), // 10 minutes TTL
+            None, // auto-generate
+            None,
         )?; // Removed .await as create_session is not async
 
     let otp_hash = otp_service.hash_otp(&otp)?;
@@ -291,7 +390,7 @@ pub async fn send_phone_verification(
         .map_err(|_| ApiError::new(auth_core::error::AuthError::InternalError))?;
 
     otp_delivery
-        .send_phone_otp(&phone, &otp)
+        .send_phone_otp(Some(tenant_id), &phone, &otp)
         .await
         .map_err(ApiError::from)?;
 
@@ -328,27 +427,33 @@ pub async fn confirm_phone_verification(
         }));
     }
 
-    if !otp_service
-        .verify_otp(&payload.code, &otp_hash)
-        .map_err(|_| ApiError::new(auth_core::error::AuthError::InternalError))?
+    match otp_service
+        .verify_with_store(
+            otp_repo.as_ref(),
+            session.id,
+            payload.code.expose(),
+            &otp_hash,
+        )
+        .await
     {
-        otp_repo
-            .increment_attempts(session.id)
-            .await
-            .map_err(|_| ApiError::new(auth_core::error::AuthError::InternalError))?;
-        return Err(ApiError::new(auth_core::error::AuthError::TokenError {
-            kind: TokenErrorKind::Invalid,
-        }));
+        Ok(_) => {}
+        Err(OtpError::Invalid) => {
+            return Err(ApiError::new(auth_core::error::AuthError::TokenError {
+                kind: TokenErrorKind::Invalid,
+            }));
+        }
+        Err(OtpError::AlreadyVerified) => {
+            return Err(ApiError::new(auth_core::error::AuthError::TokenError {
+                kind: TokenErrorKind::Replayed,
+            }));
+        }
+        Err(e) => return Err(e.into()),
     }
 
     identity_service
         .mark_phone_verified(payload.user_id)
         .await
         .map_err(|_| ApiError::new(auth_core::error::AuthError::InternalError))?;
-    otp_repo
-        .mark_verified(session.id)
-        .await
-        .map_err(|_| ApiError::new(auth_core::error::AuthError::InternalError))?;
 
     Ok((
         StatusCode::OK,
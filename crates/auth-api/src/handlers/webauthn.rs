@@ -0,0 +1,270 @@
+use crate::error::ApiError;
+use crate::AppState;
+use auth_config::AuthMethod;
+use auth_core::error::{AuthError, TokenErrorKind};
+use auth_core::services::login_history::LoginEvent;
+use auth_core::services::webauthn_service::{
+    DiscoverableAuthentication, PasskeyRegistration, PublicKeyCredential,
+    RegisterPublicKeyCredential,
+};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct PasskeyOptionsResponse {
+    /// Send back with the authenticator's response
+    pub ceremony_id: Uuid,
+    /// `PublicKeyCredentialCreationOptions` or `...RequestOptions` for
+    /// `navigator.credentials`
+    #[schema(value_type = Object)]
+    pub options: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct FinishPasskeyRegistrationRequest {
+    pub ceremony_id: Uuid,
+    #[schema(value_type = Object)]
+    pub credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct PasskeyRegisteredResponse {
+    pub credential_id: String,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct PasskeyLoginRequest {
+    pub ceremony_id: Uuid,
+    #[schema(value_type = Object)]
+    pub credential: PublicKeyCredential,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+async fn caller(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<auth_core::models::User, ApiError> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(ApiError::new(AuthError::Unauthorized {
+            message: "Missing token".to_string(),
+        }))?;
+
+    let claims = state
+        .identity_service
+        .validate_token(token)
+        .await
+        .map_err(ApiError::from)?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| {
+        ApiError::new(AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        })
+    })?;
+    Ok(state.identity_service.get_user(user_id).await?)
+}
+
+fn ceremony_key(kind: &str, id: Uuid) -> String {
+    format!("webauthn:{}:{}", kind, id)
+}
+
+/// Keep the server half of a ceremony until the browser answers
+async fn stash_ceremony<T: Serialize>(
+    state: &AppState,
+    kind: &str,
+    ceremony: &T,
+) -> Result<Uuid, ApiError> {
+    let id = Uuid::new_v4();
+    let value = serde_json::to_string(ceremony).map_err(|_| AuthError::InternalError)?;
+    state
+        .cache
+        .set(
+            &ceremony_key(kind, id),
+            &value,
+            state.webauthn.ceremony_ttl(),
+        )
+        .await
+        .map_err(|_| AuthError::InternalError)?;
+    Ok(id)
+}
+
+/// Fetch and forget a ceremony so its challenge cannot be answered twice
+async fn take_ceremony<T: DeserializeOwned>(
+    state: &AppState,
+    kind: &str,
+    id: Uuid,
+) -> Result<T, ApiError> {
+    let key = ceremony_key(kind, id);
+    let value = state
+        .cache
+        .get(&key)
+        .await
+        .map_err(|_| AuthError::InternalError)?
+        .ok_or(AuthError::TokenError {
+            kind: TokenErrorKind::Expired,
+        })?;
+    state
+        .cache
+        .delete(&key)
+        .await
+        .map_err(|_| AuthError::InternalError)?;
+    serde_json::from_str(&value).map_err(|_| ApiError::new(AuthError::InternalError))
+}
+
+/// Name shown by the authenticator's account picker
+fn account_name(user: &auth_core::models::User) -> String {
+    user.email
+        .clone()
+        .or_else(|| user.phone.clone())
+        .unwrap_or_else(|| user.id.to_string())
+}
+
+/// Options for adding a passkey to the caller's account
+#[utoipa::path(
+    post,
+    path = "/auth/webauthn/register/options",
+    responses(
+        (status = 200, description = "Creation options", body = PasskeyOptionsResponse),
+        (status = 401, description = "Missing or invalid bearer token")
+    ),
+    tag = "Authentication"
+)]
+pub async fn registration_options(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let name = account_name(&user);
+    let (options, registration) = state
+        .webauthn
+        .start_registration(user.id, &name, &name)
+        .await?;
+
+    let ceremony_id = stash_ceremony(&state, "register", &(user.id, registration)).await?;
+    Ok(Json(PasskeyOptionsResponse {
+        ceremony_id,
+        options: serde_json::to_value(options).map_err(|_| AuthError::InternalError)?,
+    }))
+}
+
+/// Store the passkey the authenticator created
+#[utoipa::path(
+    post,
+    path = "/auth/webauthn/register",
+    request_body = FinishPasskeyRegistrationRequest,
+    responses(
+        (status = 201, description = "Passkey registered", body = PasskeyRegisteredResponse),
+        (status = 400, description = "Attestation rejected"),
+        (status = 401, description = "Missing token or expired ceremony")
+    ),
+    tag = "Authentication"
+)]
+pub async fn register(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<FinishPasskeyRegistrationRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let (owner, registration): (Uuid, PasskeyRegistration) =
+        take_ceremony(&state, "register", payload.ceremony_id).await?;
+    if owner != user.id {
+        // Started by another account
+        return Err(ApiError::new(AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        }));
+    }
+
+    let passkey = state
+        .webauthn
+        .finish_registration(user.id, user.tenant_id, &registration, &payload.credential)
+        .await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(PasskeyRegisteredResponse {
+            credential_id: auth_core::services::webauthn_service::credential_key(passkey.cred_id()),
+        }),
+    ))
+}
+
+/// Sign-in options that need no identifier. The browser lets the user pick
+/// a passkey, either from a modal or from username autofill.
+#[utoipa::path(
+    post,
+    path = "/auth/webauthn/options",
+    responses(
+        (status = 200, description = "Request options with conditional mediation", body = PasskeyOptionsResponse)
+    ),
+    tag = "Authentication"
+)]
+pub async fn authentication_options(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (options, authentication) = state.webauthn.start_authentication()?;
+    let ceremony_id = stash_ceremony(&state, "login", &authentication).await?;
+    Ok(Json(PasskeyOptionsResponse {
+        ceremony_id,
+        options: serde_json::to_value(options).map_err(|_| AuthError::InternalError)?,
+    }))
+}
+
+/// Sign in with a passkey assertion
+#[utoipa::path(
+    post,
+    path = "/auth/webauthn/authenticate",
+    request_body = PasskeyLoginRequest,
+    responses(
+        (status = 200, description = "Login successful", body = AuthResponse),
+        (status = 401, description = "Assertion rejected or ceremony expired"),
+        (status = 403, description = "Passkeys are not allowed for the user's tenant")
+    ),
+    tag = "Authentication"
+)]
+pub async fn authenticate(
+    State(state): State<AppState>,
+    Json(payload): Json<PasskeyLoginRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let authentication: DiscoverableAuthentication =
+        take_ceremony(&state, "login", payload.ceremony_id).await?;
+    let user_id = state
+        .webauthn
+        .finish_authentication(authentication, &payload.credential)
+        .await?;
+
+    let user = state.identity_service.get_user(user_id).await?;
+    if !user.can_authenticate() {
+        return Err(ApiError::new(AuthError::Unauthorized {
+            message: "Account locked or suspended".to_string(),
+        }));
+    }
+    // The tenant is only known once the passkey has named its user
+    state
+        .auth_methods
+        .require(user.tenant_id, AuthMethod::Passkey)?;
+
+    let response = state
+        .identity_service
+        .issue_tokens_for_user(&user, user.tenant_id, None, None, None)
+        .await?;
+
+    let event = LoginEvent::new(
+        user.id,
+        user.tenant_id,
+        payload.ip_address,
+        payload.user_agent,
+        true,
+    );
+    if let Err(e) = state.login_history.record(event).await {
+        warn!(error = ?e, "Failed to record login event");
+    }
+
+    Ok(Json(response))
+}
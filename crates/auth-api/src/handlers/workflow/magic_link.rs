@@ -1,17 +1,44 @@
 //! Magic Link Workflow Step
-//! Allows users to resume a flow by clicking a link (simulated via token verification)
+//! Allows users to resume a flow by clicking a link. Links are single-use
+//! tokens issued into the nonce store with the user id as payload.
 
 use async_trait::async_trait;
 use auth_core::error::AuthError;
+use auth_core::services::nonce_store::{NonceNamespace, NonceStore};
 use auth_core::services::workflow::{FlowAction, FlowContext, FlowState, StepHandler};
+use std::sync::Arc;
+use uuid::Uuid;
 
-pub struct MagicLinkStep;
+pub struct MagicLinkStep {
+    nonces: Arc<NonceStore>,
+}
+
+impl MagicLinkStep {
+    pub fn new(nonces: Arc<NonceStore>) -> Self {
+        Self { nonces }
+    }
+
+    /// Issue a link token for `user_id`; the caller delivers it
+    pub async fn issue(nonces: &NonceStore, user_id: Uuid) -> Result<String, AuthError> {
+        // Two v4 UUIDs: 244 bits from the OS RNG
+        let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        nonces
+            .issue(
+                NonceNamespace::MagicLink,
+                &token,
+                &user_id.to_string(),
+                None,
+            )
+            .await?;
+        Ok(token)
+    }
+}
 
 #[async_trait]
 impl StepHandler for MagicLinkStep {
     async fn handle(
         &self,
-        _ctx: &mut FlowContext,
+        ctx: &mut FlowContext,
         action: FlowAction,
     ) -> Result<FlowState, AuthError> {
         if action.name != "verify_magic_link" {
@@ -26,18 +53,18 @@ impl StepHandler for MagicLinkStep {
             },
         )?;
 
-        // 1. Verify token (usually against a separate MagicLink table or signed JWT)
-        // 2. Check expiry
-        // 3. Mark context as authenticated
-
-        // Simulation:
-        if token == "valid_magic_token" {
-            // If we had the user ID in the token, we'd set it here.
-            // ctx.user_id = Some(...);
-            Ok(FlowState::Success)
-        } else {
-            Err(AuthError::InvalidCredentials)
+        // Expired, unknown and replayed links all fail here
+        let user_id = self
+            .nonces
+            .consume(NonceNamespace::MagicLink, token)
+            .await?;
+        let user_id = Uuid::parse_str(&user_id).map_err(|_| AuthError::InternalError)?;
+
+        if ctx.user_id.is_some_and(|existing| existing != user_id) {
+            return Err(AuthError::InvalidCredentials);
         }
+        ctx.user_id = Some(user_id);
+        Ok(FlowState::Success)
     }
 
     async fn validate(&self, _ctx: &FlowContext) -> Result<(), AuthError> {
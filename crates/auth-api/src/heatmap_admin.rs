@@ -0,0 +1,52 @@
+//! Internal admin API for the sign-in heatmap of the admin dashboard
+//!
+//! Served on the admin listener. Sums the hourly login rollup over a
+//! range, by country, ASN or both, for one tenant or all of them. Hours
+//! the rollup job has not reached yet are missing from the result.
+
+use crate::error::ApiError;
+use auth_core::services::login_heatmap::{
+    HeatmapDimension, HeatmapQuery, LoginHeatmap, LoginHeatmapService,
+};
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn router(heatmap: Arc<LoginHeatmapService>) -> Router {
+    Router::new()
+        .route("/admin/login-heatmap", get(login_heatmap))
+        .with_state(heatmap)
+}
+
+#[derive(Debug, Deserialize)]
+struct HeatmapParams {
+    tenant_id: Option<Uuid>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    by: HeatmapDimension,
+}
+
+/// GET /admin/login-heatmap?tenant_id=&since=&until=&by=country
+///
+/// `by` is `country`, `asn` or `country_asn`. The range defaults to the
+/// 24 hours up to now.
+async fn login_heatmap(
+    State(heatmap): State<Arc<LoginHeatmapService>>,
+    Query(params): Query<HeatmapParams>,
+) -> Result<Json<LoginHeatmap>, ApiError> {
+    let until = params.until.unwrap_or_else(Utc::now);
+    let query = HeatmapQuery {
+        tenant_id: params.tenant_id,
+        since: params.since.unwrap_or(until - Duration::hours(24)),
+        until,
+        by: params.by,
+    };
+    Ok(Json(heatmap.heatmap(query).await?))
+}
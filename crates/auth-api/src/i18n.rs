@@ -0,0 +1,202 @@
+//! Localized strings
+//!
+//! Catalogs are JSON objects of message key to text, one file per locale
+//! under `crates/auth-api/i18n`, compiled into the binary. English is the
+//! reference: a key missing from another locale falls back to its English
+//! text, and a key English does not have is a mistake the tests catch.
+//!
+//! The locale of a response is negotiated from `Accept-Language` (RFC 9110
+//! section 12.5.4) against the locales there are catalogs for.
+
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Error message catalogs by locale, English first
+const ERROR_SOURCES: &[(&str, &str)] = &[
+    ("en", include_str!("../i18n/errors.en.json")),
+    ("de", include_str!("../i18n/errors.de.json")),
+    ("es", include_str!("../i18n/errors.es.json")),
+    ("fr", include_str!("../i18n/errors.fr.json")),
+];
+
+/// A catalog rendered for one locale
+#[derive(Debug, Clone)]
+pub struct LocalizedCatalog {
+    pub locale: &'static str,
+    pub messages: BTreeMap<String, String>,
+    /// `locale` and `messages` as served
+    pub body: Value,
+    /// Strong validator of `body`
+    pub etag: String,
+}
+
+pub struct Catalog {
+    locales: Vec<LocalizedCatalog>,
+}
+
+impl Catalog {
+    /// Parse `sources`, the first of which is the reference locale
+    fn from_sources(sources: &[(&'static str, &str)]) -> Result<Self, serde_json::Error> {
+        let mut parsed = Vec::with_capacity(sources.len());
+        for (locale, source) in sources {
+            parsed.push((
+                *locale,
+                serde_json::from_str::<BTreeMap<String, String>>(source)?,
+            ));
+        }
+
+        let reference = parsed.first().map(|(_, m)| m.clone()).unwrap_or_default();
+        let locales = parsed
+            .into_iter()
+            .map(|(locale, translated)| {
+                let mut messages = reference.clone();
+                messages.extend(
+                    translated
+                        .into_iter()
+                        .filter(|(key, _)| reference.contains_key(key)),
+                );
+                let body = serde_json::json!({ "locale": locale, "messages": messages });
+                let digest = Sha256::digest(body.to_string().as_bytes());
+                LocalizedCatalog {
+                    locale,
+                    messages,
+                    body,
+                    etag: format!("\"{}\"", hex::encode(&digest[..16])),
+                }
+            })
+            .collect();
+        Ok(Self { locales })
+    }
+
+    pub fn locales(&self) -> impl Iterator<Item = &'static str> + Clone + '_ {
+        self.locales.iter().map(|c| c.locale)
+    }
+
+    /// The catalog for the best match of an `Accept-Language` value
+    pub fn negotiate(&self, accept_language: Option<&str>) -> &LocalizedCatalog {
+        let locale = accept_language
+            .and_then(|value| negotiate(value, self.locales()))
+            .unwrap_or(DEFAULT_LOCALE);
+        self.locales
+            .iter()
+            .find(|c| c.locale == locale)
+            .unwrap_or(&self.locales[0])
+    }
+}
+
+/// Error messages keyed by `AUTH_xxx` code
+pub static ERRORS: Lazy<Catalog> = Lazy::new(|| {
+    Catalog::from_sources(ERROR_SOURCES).expect("embedded error catalogs are valid JSON")
+});
+
+/// The available locale `accept_language` prefers most, if any
+///
+/// Ranges match a locale exactly or by primary subtag, so `fr-CA` selects
+/// `fr`. Ranges with `q=0` are refused, and `*` matches the first locale not
+/// otherwise named.
+pub fn negotiate<'a>(
+    accept_language: &str,
+    available: impl Iterator<Item = &'a str> + Clone,
+) -> Option<&'a str> {
+    let mut ranges: Vec<(String, f32)> = accept_language
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let range = parts.next().filter(|r| !r.is_empty())?.to_ascii_lowercase();
+            let quality = parts
+                .find_map(|p| p.strip_prefix("q=").or_else(|| p.strip_prefix("Q=")))
+                .map(|q| q.parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            Some((range, quality))
+        })
+        .collect();
+    // Stable, so equal weights keep the client's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let refused: Vec<&str> = ranges
+        .iter()
+        .filter(|(_, q)| *q <= 0.0)
+        .map(|(r, _)| r.as_str())
+        .collect();
+    let primary = |range: &str| range.split('-').next().unwrap_or(range).to_string();
+
+    for (range, quality) in &ranges {
+        if *quality <= 0.0 {
+            break;
+        }
+        let found = if range == "*" {
+            available.clone().find(|locale| {
+                !refused.contains(locale) && !ranges.iter().any(|(r, _)| r == locale)
+            })
+        } else {
+            available
+                .clone()
+                .find(|locale| *locale == range.as_str())
+                .or_else(|| {
+                    available
+                        .clone()
+                        .find(|locale| *locale == primary(range) && !refused.contains(locale))
+                })
+        };
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCALES: [&str; 3] = ["en", "de", "fr"];
+
+    #[test]
+    fn test_accept_language_negotiation() {
+        let pick = |value: &str| negotiate(value, LOCALES.iter().copied());
+        assert_eq!(pick("fr"), Some("fr"));
+        assert_eq!(pick("fr-CA, en;q=0.5"), Some("fr"));
+        assert_eq!(pick("ja, de;q=0.8, en;q=0.9"), Some("en"));
+        assert_eq!(pick("DE-at"), Some("de"));
+        assert_eq!(pick("ja"), None);
+        assert_eq!(pick("en;q=0, *"), Some("de"));
+        assert_eq!(pick("fr;q=0, en;q=0"), None);
+    }
+
+    #[test]
+    fn test_catalogs_are_complete_and_keyed_by_english() {
+        let reference = &ERRORS.negotiate(None).messages;
+        for (locale, source) in ERROR_SOURCES {
+            let messages: BTreeMap<String, String> = serde_json::from_str(source).unwrap();
+            let keys: Vec<&String> = messages.keys().collect();
+            assert_eq!(
+                keys,
+                reference.keys().collect::<Vec<_>>(),
+                "{} does not have the English keys",
+                locale
+            );
+        }
+        for code in ["AUTH_001", "AUTH_007", "AUTH_017", "AUTH_056"] {
+            assert!(reference.contains_key(code), "{} is missing", code);
+        }
+    }
+
+    #[test]
+    fn test_missing_translations_fall_back_to_english() {
+        let catalog = Catalog::from_sources(&[
+            ("en", r#"{"A": "Hello", "B": "Bye"}"#),
+            ("fr", r#"{"A": "Bonjour", "Z": "stray"}"#),
+        ])
+        .unwrap();
+        let fr = catalog.negotiate(Some("fr"));
+        assert_eq!(fr.messages["A"], "Bonjour");
+        assert_eq!(fr.messages["B"], "Bye");
+        assert!(!fr.messages.contains_key("Z"));
+        assert_ne!(fr.etag, catalog.negotiate(None).etag);
+        assert_eq!(catalog.negotiate(Some("ja")).locale, "en");
+    }
+}
@@ -0,0 +1,59 @@
+//! Internal admin API for JWKS key rotations, and partner notices
+//!
+//! Served on the admin listener. Lists the key changes configured under
+//! `security.jwks` that are still ahead, and sends the `jwks.key_retiring`
+//! webhook to partners once a key enters its retirement notice period.
+
+use auth_core::services::jwks::{JwksService, KeyRotation};
+use auth_extension::WebhookDispatcher;
+use axum::{extract::State, routing::get, Json, Router};
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Webhook event sent ahead of a key retiring
+pub const KEY_RETIRING_EVENT: &str = "jwks.key_retiring";
+
+pub fn router(jwks: Arc<JwksService>) -> Router {
+    Router::new()
+        .route("/admin/jwks/rotations", get(rotations))
+        .with_state(jwks)
+}
+
+/// GET /admin/jwks/rotations
+///
+/// Upcoming activations and retirements, soonest first
+async fn rotations(State(jwks): State<Arc<JwksService>>) -> Json<Vec<KeyRotation>> {
+    Json(jwks.rotations(Utc::now()))
+}
+
+/// Check for keys due a retirement notice every `interval` and send each
+/// notice to every partner URL
+pub async fn run_retirement_notices(
+    jwks: Arc<JwksService>,
+    dispatcher: WebhookDispatcher,
+    partners: Vec<String>,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        for notice in jwks.due_retirement_notices(Utc::now()) {
+            tracing::info!(
+                "Key {} retires at {}; notifying {} partners",
+                notice.kid,
+                notice.retires_at,
+                partners.len()
+            );
+            let payload = serde_json::to_value(&notice).unwrap_or_default();
+            for url in &partners {
+                if let Err(e) = dispatcher
+                    .dispatch(url, KEY_RETIRING_EVENT, payload.clone())
+                    .await
+                {
+                    tracing::warn!("Key retirement notice to {} failed: {}", url, e);
+                }
+            }
+        }
+    }
+}
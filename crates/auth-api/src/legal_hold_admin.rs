@@ -0,0 +1,85 @@
+//! Internal admin API for legal holds
+//!
+//! Served on the admin listener. Places holds on users or whole tenants,
+//! lists the holds in force with who placed them, and releases them.
+
+use crate::error::ApiError;
+use auth_core::services::legal_hold::{LegalHold, LegalHoldService};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn router(holds: Arc<LegalHoldService>) -> Router {
+    Router::new()
+        .route("/admin/legal-holds", get(list_holds).post(place))
+        .route("/admin/legal-holds/:id/release", post(release))
+        .with_state(holds)
+}
+
+#[derive(Debug, Deserialize)]
+struct HoldQuery {
+    tenant_id: Option<Uuid>,
+    #[serde(default)]
+    include_released: bool,
+}
+
+/// GET /admin/legal-holds?tenant_id=&include_released=false
+async fn list_holds(
+    State(holds): State<Arc<LegalHoldService>>,
+    Query(query): Query<HoldQuery>,
+) -> Result<Json<Vec<LegalHold>>, ApiError> {
+    Ok(Json(
+        holds.holds(query.tenant_id, query.include_released).await?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaceRequest {
+    tenant_id: Uuid,
+    /// Omitted to hold the whole tenant
+    user_id: Option<Uuid>,
+    reason: String,
+    placed_by: String,
+}
+
+/// POST /admin/legal-holds
+async fn place(
+    State(holds): State<Arc<LegalHoldService>>,
+    Json(request): Json<PlaceRequest>,
+) -> Result<(StatusCode, Json<LegalHold>), ApiError> {
+    let hold = holds
+        .place(
+            request.tenant_id,
+            request.user_id,
+            &request.reason,
+            &request.placed_by,
+        )
+        .await?;
+    Ok((StatusCode::CREATED, Json(hold)))
+}
+
+#[derive(Debug, Deserialize)]
+struct ReleaseRequest {
+    released_by: String,
+}
+
+/// POST /admin/legal-holds/:id/release
+///
+/// 404 when there is no active hold with this id
+async fn release(
+    State(holds): State<Arc<LegalHoldService>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<ReleaseRequest>,
+) -> Result<Result<Json<LegalHold>, StatusCode>, ApiError> {
+    Ok(holds
+        .release(id, &request.released_by)
+        .await?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND))
+}
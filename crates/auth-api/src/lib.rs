@@ -1,22 +1,61 @@
 use auth_core::services::{
-    authorization::AuthorizationService, lazy_registration::LazyRegistrationService,
-    otp_delivery::OtpDeliveryService, otp_service::OtpService, rate_limiter::RateLimiter,
-    session_service::SessionService, subscription_service::SubscriptionService,
+    authorization::AuthorizationService, device_enrollment::DeviceEnrollmentService,
+    feature_flags::FeatureFlagService, forced_reauth::ForcedReauthService, jwks::JwksService,
+    lazy_registration::LazyRegistrationService, login_history::LoginHistoryService,
+    login_link::LoginLinkService, nonce_store::NonceStore, otp_delivery::OtpDeliveryService,
+    otp_service::OtpService, permission_sync::PermissionSyncService, push_mfa::PushMfaService,
+    rate_limiter::RateLimiter, recovery_codes::RecoveryCodeService, remember_me::RememberMeService,
+    service_account::ServiceAccountService, session_service::SessionService, ssh_ca::SshCaService,
+    sso_session::SsoSessionService, subscription_service::SubscriptionService,
+    tenant_metrics::TenantMetricsService, tenant_onboarding::TenantOnboardingService,
+    tenant_quota::TenantQuotaService, token_ttl::TokenTtlPolicy, webauthn_service::WebauthnService,
+    workflow::FlowStateSealer,
 };
 use auth_db::repositories::otp_repository::OtpRepository;
+use auth_db::residency::RegionRouter;
 use axum::Router;
 use sqlx::MySqlPool;
 use std::sync::Arc;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+pub mod auth_method_admin;
+pub mod bff;
+pub mod captcha;
+pub mod config_admin;
+pub mod diagnostics;
 pub mod error;
+pub mod events;
+pub mod export_admin;
+pub mod feature_admin;
 pub mod handlers;
+pub mod heatmap_admin;
+pub mod i18n;
+pub mod jwks_admin;
+pub mod legal_hold_admin;
+pub mod metrics_admin;
 pub mod middleware;
+pub mod migration_admin;
+pub mod nonces;
+pub mod port_admin;
+pub mod residency;
+pub mod response_mode;
+pub mod revocation;
+pub mod route_admin;
+pub mod route_policy;
 pub mod router;
+pub mod sessions;
+pub mod signup_abuse;
+pub mod signup_admin;
+pub mod sms_admin;
+pub mod sso;
+pub mod state;
+pub mod tenant_scope;
 pub mod validation;
+pub mod warmup;
 
 use auth_cache::Cache;
+pub use state::{AppStateBuilder, AppStateError};
 
 // Admin UI (feature-gated)
 #[cfg(feature = "admin-ui")]
@@ -28,24 +67,147 @@ pub mod admin;
     paths(
         handlers::auth::login,
         handlers::auth::register,
+        handlers::auth::change_expired_password,
+        handlers::webauthn::registration_options,
+        handlers::webauthn::register,
+        handlers::webauthn::authentication_options,
+        handlers::webauthn::authenticate,
         handlers::users::ban_user,
         handlers::users::activate_user,
+        handlers::service_accounts::create_service_account,
+        handlers::service_accounts::list_service_accounts,
+        handlers::service_accounts::update_service_account_status,
+        handlers::service_accounts::list_keys,
+        handlers::service_accounts::register_key,
+        handlers::service_accounts::revoke_key,
+        handlers::ssh::sign_key,
+        handlers::ssh::ca_keys,
+        handlers::devices::create_bootstrap_token,
+        handlers::devices::enroll,
+        handlers::devices::renew,
+        handlers::devices::list_certificates,
+        handlers::devices::revoke_certificate,
+        handlers::devices::crl,
+        handlers::devices::ca_certificates,
+        handlers::push_mfa::register_device,
+        handlers::push_mfa::list_devices,
+        handlers::push_mfa::revoke_device,
+        handlers::push_mfa::respond,
+        handlers::recovery_codes::generate,
+        handlers::recovery_codes::status,
+        handlers::tokens::validate_batch,
+        handlers::tenants::force_reauth,
+        handlers::tenants::revoke_client_tokens,
+        handlers::tenants::metrics,
+        handlers::tenants::rate_limit,
+        handlers::tenants::onboarding_status,
+        handlers::tenants::onboarding_action,
+        handlers::authorization::permission_sync::snapshot,
+        handlers::authorization::permission_sync::changes,
+        handlers::login_links::issue_login_link,
+        handlers::login_links::redeem_login_link,
+        handlers::remember_me::remember_device,
+        handlers::remember_me::redeem_remember_me,
+        handlers::remember_me::list_devices,
+        handlers::remember_me::revoke_device,
         handlers::health::health_check,
+        handlers::health::readiness,
+        handlers::capabilities::capabilities,
+        handlers::capabilities::features,
+        handlers::meta::error_catalog,
     ),
     components(
         schemas(
             auth_core::services::identity::AuthRequest,
             auth_core::services::identity::AuthResponse,
+            handlers::auth::ExpiredPasswordChangeRequest,
             auth_core::models::user::User,
             auth_core::models::user::CreateUserRequest,
             auth_core::models::user::UserStatus,
+            handlers::webauthn::PasskeyOptionsResponse,
+            handlers::webauthn::FinishPasskeyRegistrationRequest,
+            handlers::webauthn::PasskeyRegisteredResponse,
+            handlers::webauthn::PasskeyLoginRequest,
+            auth_core::models::ServiceAccount,
+            auth_core::models::ServiceAccountKey,
+            auth_core::models::ServiceAccountStatus,
+            auth_core::models::KeyAlgorithm,
+            auth_core::models::CreateServiceAccountRequest,
+            auth_core::models::RegisterServiceAccountKeyRequest,
+            handlers::service_accounts::UpdateServiceAccountStatus,
+            handlers::ssh::SignSshKeyRequest,
+            handlers::ssh::SignSshKeyResponse,
+            auth_core::models::DeviceCertificate,
+            auth_core::models::CreateBootstrapTokenRequest,
+            auth_core::models::DeviceEnrollmentRequest,
+            auth_core::models::DeviceRenewalRequest,
+            auth_core::models::RevokeDeviceCertificateRequest,
+            handlers::devices::BootstrapTokenResponse,
+            handlers::devices::DeviceCertificateResponse,
+            auth_core::models::PushDevice,
+            auth_core::models::PushPlatform,
+            auth_core::models::PushChallengeStatus,
+            auth_core::models::RegisterPushDeviceRequest,
+            auth_core::models::PushChallengeResponseRequest,
+            handlers::push_mfa::PushChallengeStatusResponse,
+            handlers::recovery_codes::GenerateRecoveryCodesRequest,
+            handlers::recovery_codes::RecoveryCodesResponse,
+            handlers::recovery_codes::RecoveryCodesStatus,
+            handlers::tokens::ValidateBatchRequest,
+            handlers::tokens::TokenValidationResult,
+            handlers::tokens::ValidateBatchResponse,
+            handlers::tenants::ForceReauthRequest,
+            handlers::tenants::OnboardingActionRequest,
+            auth_core::services::forced_reauth::ForcedReauth,
+            auth_core::services::forced_reauth::ClientRevocation,
+            auth_core::services::tenant_metrics::TenantMetricsReport,
+            auth_core::services::tenant_metrics::DisclosedMetric,
+            auth_core::services::tenant_metrics::TenantMetric,
+            auth_core::services::tenant_metrics::MetricClass,
+            auth_core::services::adaptive_quota::AdaptiveLimitReport,
+            auth_core::services::adaptive_quota::LimitBasis,
+            auth_core::services::adaptive_quota::LimitChange,
+            auth_core::services::adaptive_quota::HourProfile,
+            auth_core::services::tenant_onboarding::OnboardingStatus,
+            auth_core::services::tenant_onboarding::OnboardingStep,
+            auth_core::services::tenant_onboarding::ChecklistItem,
+            auth_core::services::tenant_onboarding::StepStatus,
+            auth_core::services::tenant_onboarding::TestResult,
+            auth_core::services::tenant_onboarding::DomainSettings,
+            auth_core::services::tenant_onboarding::ProviderSettings,
+            auth_core::services::tenant_onboarding::IdentityProviderSettings,
+            auth_core::services::tenant_onboarding::PolicySettings,
+            auth_core::services::tenant_onboarding::BrandingSettings,
+            auth_core::services::permission_sync::PermissionSnapshot,
+            auth_core::services::permission_sync::PermissionChangePage,
+            auth_core::services::permission_sync::PermissionChange,
+            auth_core::services::permission_sync::PermissionChangeKind,
+            auth_core::services::permission_sync::RoleGrant,
+            auth_core::services::permission_sync::Assignment,
+            auth_core::services::login_link::IssueLoginLinkRequest,
+            auth_core::services::login_link::IssuedLoginLink,
+            handlers::login_links::RedeemLoginLinkRequest,
+            handlers::login_links::LoginLinkResponse,
+            auth_core::services::remember_me::IssuedRememberMe,
+            auth_core::services::remember_me::RememberedDevice,
+            handlers::remember_me::RememberDeviceRequest,
+            handlers::remember_me::RedeemRememberMeRequest,
+            handlers::remember_me::RememberMeSignIn,
+            handlers::capabilities::Capabilities,
+            auth_core::services::feature_flags::FeatureEvaluation,
             crate::error::ErrorResponse,
             crate::error::FieldError,
         )
     ),
     tags(
-        (name = "Authentication", description = "User authentication and registration endpoints"),
+        (name = "Authentication", description = "User authentication, passkeys and registration endpoints"),
         (name = "User Management", description = "User administration endpoints"),
+        (name = "Service Accounts", description = "Key-pair authenticated service accounts"),
+        (name = "SSH", description = "SSH certificate authority for infrastructure access"),
+        (name = "Devices", description = "X.509 client certificate enrollment for devices"),
+        (name = "MFA", description = "Push approval devices, challenges and recovery codes"),
+        (name = "Tenants", description = "Tenant-wide administration"),
+        (name = "Authorization", description = "Permission snapshots and change feed for downstream services"),
         (name = "Health", description = "Service health check endpoints")
     ),
     info(
@@ -74,11 +236,60 @@ pub struct AppState {
     pub otp_repository: Arc<OtpRepository>,
     pub audit_logger: Arc<dyn auth_core::audit::AuditLogger>,
     pub cache: Arc<dyn Cache>,
+    pub bff: Arc<bff::BffService>,
+    pub flow_sealer: Arc<FlowStateSealer>,
+    pub token_ttl_policy: Arc<TokenTtlPolicy>,
+    pub captcha: Arc<auth_core::services::captcha::CaptchaService>,
+    /// Velocity rules and review queue for public signup
+    pub signup_abuse: Arc<auth_core::services::signup_abuse::SignupAbuseService>,
+    /// Sign-in methods each tenant allows
+    pub auth_methods: Arc<auth_core::services::auth_methods::AuthMethodPolicies>,
+    pub events: Arc<auth_core::events::EventBus>,
+    pub login_history: Arc<LoginHistoryService>,
+    pub nonces: Arc<NonceStore>,
+    pub service_accounts: Arc<ServiceAccountService>,
+    /// OAuth clients registered by tenants
+    pub clients: Arc<auth_protocols::ClientService>,
+    pub ssh_ca: Arc<SshCaService>,
+    pub devices: Arc<DeviceEnrollmentService>,
+    pub push_mfa: Arc<PushMfaService>,
+    pub recovery_codes: Arc<RecoveryCodeService>,
+    pub webauthn: Arc<WebauthnService>,
+    pub sso: Arc<SsoSessionService>,
+    pub sso_cookie: Arc<sso::SsoCookie>,
+    /// Logout at the identity providers users signed in through
+    pub upstream_logout: Arc<auth_protocols::UpstreamLogoutService>,
+    /// Response modes each client may use at `/auth/authorize`
+    pub response_modes: Arc<response_mode::ResponseModes>,
+    /// Issuer of each tenant's ID tokens
+    pub issuers: Arc<auth_core::services::id_token::Issuers>,
+    pub quotas: Arc<TenantQuotaService>,
+    pub readiness: Arc<warmup::Readiness>,
+    pub jwks: Arc<JwksService>,
+    pub forced_reauth: Arc<ForcedReauthService>,
+    pub permission_sync: Arc<PermissionSyncService>,
+    pub login_links: Arc<LoginLinkService>,
+    /// Long-lived sign-in on remembered devices
+    pub remember_me: Arc<RememberMeService>,
+    pub tenant_metrics: Arc<TenantMetricsService>,
+    /// Guided setup of new tenants
+    pub onboarding: Arc<TenantOnboardingService>,
+    pub capabilities: Arc<handlers::capabilities::Capabilities>,
+    /// Feature flags, canary cohorts and kill switches
+    pub features: Arc<FeatureFlagService>,
+    /// Database pool of each tenant's data region
+    pub regions: Arc<RegionRouter>,
+}
+
+impl AppState {
+    pub fn builder() -> AppStateBuilder {
+        AppStateBuilder::default()
+    }
 }
 
 pub fn app(state: AppState) -> Router {
     // Build base router with swagger
-    let router = router::api_router()
+    let router = router::api_router(&state)
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()));
 
     // Add admin UI routes if feature is enabled
@@ -123,7 +334,20 @@ pub fn app(state: AppState) -> Router {
             .route("/admin/logout", get(admin::handlers::logout))
     };
 
-    router.with_state(state)
+    // Handler panics become 500 problem responses; the quota layer still
+    // sees a response and releases its permits
+    let router = router.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::panic_boundary_middleware,
+    ));
+
+    // Outermost, so over-quota requests are turned away before any other work
+    router
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::quota_middleware,
+        ))
+        .with_state(state)
 }
 
 // Make services extractable from AppState via State<Arc<Service>>
@@ -174,3 +398,25 @@ impl axum::extract::FromRef<AppState> for Arc<dyn Cache> {
         state.cache.clone()
     }
 }
+
+impl axum::extract::FromRef<AppState> for Arc<auth_core::services::captcha::CaptchaService> {
+    fn from_ref(state: &AppState) -> Self {
+        state.captcha.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState>
+    for Arc<auth_core::services::signup_abuse::SignupAbuseService>
+{
+    fn from_ref(state: &AppState) -> Self {
+        state.signup_abuse.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState>
+    for Arc<auth_core::services::auth_methods::AuthMethodPolicies>
+{
+    fn from_ref(state: &AppState) -> Self {
+        state.auth_methods.clone()
+    }
+}
@@ -0,0 +1,30 @@
+//! Internal admin API for exact tenant metrics
+//!
+//! Served on the admin listener. Returns the counts behind the tenant
+//! metrics endpoint before any threshold, noise or rounding is applied,
+//! for support and capacity planning. Never expose this to tenants.
+
+use crate::error::ApiError;
+use auth_core::services::tenant_metrics::{ExactTenantMetrics, TenantMetricsService};
+use axum::{
+    extract::{Path, State},
+    routing::get,
+    Json, Router,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn router(metrics: Arc<TenantMetricsService>) -> Router {
+    Router::new()
+        .route("/admin/tenants/:id/metrics", get(exact_metrics))
+        .with_state(metrics)
+}
+
+/// GET /admin/tenants/:id/metrics
+async fn exact_metrics(
+    State(metrics): State<Arc<TenantMetricsService>>,
+    Path(tenant_id): Path<Uuid>,
+) -> Result<Json<ExactTenantMetrics>, ApiError> {
+    Ok(Json(metrics.exact(tenant_id, Utc::now()).await?))
+}
@@ -1,4 +1,5 @@
 use auth_core::audit::{AuditCategory, AuditEvent, AuditLogger, AuditOutcome, AuditSeverity};
+use auth_core::error::ErrorClass;
 use axum::{body::Body, http::Request, middleware::Next, response::Response};
 use std::sync::Arc;
 use std::time::Instant;
@@ -68,10 +69,11 @@ pub async fn audit_middleware(req: Request<Body>, next: Next) -> Response<Body>
         "duration_ms": duration.as_millis()
     }));
 
-    // We modify event outcome
-    let event = match outcome {
-        AuditOutcome::Success => event,
-        AuditOutcome::Failure { reason } => event.failure(reason),
+    // We modify event outcome; an error response says what failed
+    let event = match (outcome, response.extensions().get::<ErrorClass>()) {
+        (AuditOutcome::Success, _) => event,
+        (AuditOutcome::Failure { .. }, Some(error)) => event.failed_with(*error),
+        (AuditOutcome::Failure { reason }, None) => event.failure(reason),
     };
 
     // Spawn log task so we don't block response
@@ -0,0 +1,29 @@
+use crate::route_policy::Caller;
+use crate::AppState;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+
+/// Count each authenticated request under the caller's side of every
+/// feature that targets a cohort, for the canary comparison
+///
+/// Must be added with `route_layer` inside the route policy, which puts the
+/// [`Caller`] on the request.
+pub async fn cohort_outcome_middleware(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(user_id) = req.extensions().get::<Caller>().map(|c| c.user_id) else {
+        return next.run(req).await;
+    };
+    let started = Instant::now();
+    let response = next.run(req).await;
+    state
+        .features
+        .record(user_id, response.status().as_u16(), started.elapsed());
+    response
+}
@@ -1,11 +1,21 @@
 pub mod audit;
 pub mod auth;
+pub mod cohort;
+pub mod panic_boundary;
+pub mod problem_response;
+pub mod quota;
 pub mod rate_limit;
 pub mod request_id;
 pub mod security_headers;
+pub mod timing;
 
 pub use audit::audit_middleware;
 pub use auth::jwt_auth;
+pub use cohort::cohort_outcome_middleware;
+pub use panic_boundary::panic_boundary_middleware;
+pub use problem_response::problem_response_middleware;
+pub use quota::quota_middleware;
 pub use rate_limit::{rate_limit_middleware, RateLimiter};
-pub use request_id::{request_id_middleware, REQUEST_ID_HEADER};
-pub use security_headers::security_headers_middleware;
+pub use request_id::{request_id_middleware, REQUEST_ID_HEADER, TENANT_ID_HEADER};
+pub use security_headers::{security_headers_middleware, FrameEmbeddable};
+pub use timing::credential_timing_middleware;
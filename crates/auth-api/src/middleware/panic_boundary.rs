@@ -0,0 +1,86 @@
+//! Error boundary for handler panics
+//!
+//! A panicking handler would otherwise drop the connection, which clients
+//! (or the proxy in front) turn into an opaque 500 or 502 that leaves
+//! nothing in the audit trail. The panic is caught here and answered like
+//! any other internal error, carrying the request id that the log line, the
+//! metric and the audit event are keyed by.
+
+use crate::error::ApiError;
+use crate::middleware::REQUEST_ID_HEADER;
+use crate::AppState;
+use auth_core::audit::{AuditCategory, AuditEvent, AuditSeverity};
+use auth_core::error::AuthError;
+use auth_platform::panic_message;
+use axum::{
+    extract::{Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::FutureExt;
+use std::panic::AssertUnwindSafe;
+use uuid::Uuid;
+
+pub async fn panic_boundary_middleware(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    // Settle the request id here, so the inner layers and the handler use
+    // the one this boundary reports
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| Uuid::parse_str(s).ok())
+        .unwrap_or_else(Uuid::new_v4);
+    let request_id_header =
+        HeaderValue::from_str(&request_id.to_string()).expect("a UUID is a valid header value");
+    req.headers_mut()
+        .insert(REQUEST_ID_HEADER, request_id_header.clone());
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+
+    let panic = match AssertUnwindSafe(next.run(req)).catch_unwind().await {
+        Ok(response) => return response,
+        Err(panic) => panic,
+    };
+    let message = panic_message(panic.as_ref()).to_string();
+
+    tracing::error!(
+        event = "http.handler_panicked",
+        request_id = %request_id,
+        method = %method,
+        path = %path,
+        panic = %message,
+        "Request handler panicked"
+    );
+    metrics::counter!("auth_handler_panics_total", 1, "method" => method.clone());
+    state
+        .audit_logger
+        .log(
+            AuditEvent::new(
+                AuditCategory::System,
+                "http.handler_panicked",
+                AuditSeverity::Critical,
+            )
+            .with_metadata(serde_json::json!({
+                "request_id": request_id.to_string(),
+                "method": method,
+                "path": path,
+                "panic": message,
+            }))
+            .failure("handler panicked"),
+        )
+        .await;
+
+    // The panic message stays in the log; the client only gets the id
+    let mut response = ApiError::new(AuthError::InternalError)
+        .with_request_id(request_id)
+        .into_response();
+    response
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, request_id_header);
+    response
+}
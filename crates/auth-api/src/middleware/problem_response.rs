@@ -0,0 +1,67 @@
+//! Problem details for errors raised outside the handlers
+//!
+//! Extractor rejections (malformed JSON, wrong content type, bad path or
+//! query parameters), body limits and method mismatches come back from axum
+//! as plain text or with no body at all. They are rewritten into the same
+//! RFC 7807 document handler errors use, so clients parse one error shape.
+
+use crate::error::problem_details::ProblemDetails;
+use axum::{
+    body::to_bytes,
+    extract::Request,
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Longest rejection text carried over into `detail`
+const MAX_DETAIL_CHARS: usize = 512;
+
+/// Rejection bodies larger than this are dropped rather than buffered
+const MAX_BODY_BYTES: usize = 16 * 1024;
+
+pub async fn problem_response_middleware(req: Request, next: Next) -> Response {
+    let response = next.run(req).await;
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) || !is_unstructured(&response) {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let text = to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map(|bytes| String::from_utf8_lossy(&bytes).trim().to_string())
+        .unwrap_or_default();
+
+    let title = status.canonical_reason().unwrap_or("Error");
+    let code = title.to_ascii_lowercase().replace(' ', "_");
+    let mut problem = ProblemDetails::new(status, title)
+        .with_type(format!("https://auth.example.com/errors/{}", code))
+        .with_extension("code", code);
+    if !text.is_empty() {
+        problem = problem.with_detail(text.chars().take(MAX_DETAIL_CHARS).collect::<String>());
+    }
+    if let Some(request_id) = auth_core::context::RequestContext::current_request_id() {
+        problem = problem.with_extension("request_id", request_id.to_string());
+    }
+
+    let mut rewritten = problem.into_response();
+    // Keep headers such as Allow and Retry-After
+    for (name, value) in parts.headers.iter() {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            rewritten.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    rewritten
+}
+
+/// Error bodies that are empty or plain text; JSON and pages are left alone
+fn is_unstructured(response: &Response) -> bool {
+    match response.headers().get(header::CONTENT_TYPE) {
+        None => true,
+        Some(value) => value
+            .to_str()
+            .map(|ct| ct.starts_with("text/plain"))
+            .unwrap_or(false),
+    }
+}
@@ -0,0 +1,103 @@
+use crate::error::ApiError;
+use crate::AppState;
+use auth_core::services::tenant_quota::{QuotaPermit, QuotaRejection, QuotaStatus};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+/// Enforce per-client and per-tenant quotas
+///
+/// The tenant comes from a valid bearer token, else from the `Host` mapping
+/// in the quota config. Requests for no known tenant are only held to the
+/// per-client cap. Rejections are 429s with `Retry-After`; every tenant
+/// response carries the `X-RateLimit-*` headers.
+pub async fn quota_middleware(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let quotas = &state.quotas;
+    if !quotas.enabled() || matches!(req.uri().path(), "/health" | "/ready") {
+        return next.run(req).await;
+    }
+
+    let _client_permit = match client_address(&req).map(|client| quotas.admit_client(&client)) {
+        Some(Err(rejection)) => return reject(rejection),
+        Some(Ok(permit)) => Some(permit),
+        None => None,
+    };
+
+    let tenant_permit: Option<QuotaPermit> = match resolve_tenant(&state, req.headers()).await {
+        Some(tenant_id) => match quotas.admit_tenant(tenant_id).await {
+            Ok(permit) => Some(permit),
+            Err(rejection) => return reject(rejection),
+        },
+        None => None,
+    };
+
+    let mut response = next.run(req).await;
+    if let Some(status) = tenant_permit.as_ref().and_then(|p| p.status) {
+        add_quota_headers(response.headers_mut(), &status);
+    }
+    response
+}
+
+/// Peer address, or the first `X-Forwarded-For` hop behind a proxy
+fn client_address(req: &Request) -> Option<String> {
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .or_else(|| {
+            req.headers()
+                .get("x-forwarded-for")
+                .and_then(|h| h.to_str().ok())
+                .and_then(|h| h.split(',').next())
+                .map(|ip| ip.trim().to_string())
+        })
+}
+
+async fn resolve_tenant(state: &AppState, headers: &HeaderMap) -> Option<Uuid> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    if let Some(token) = token {
+        // An invalid token is the handler's to reject; it is not charged
+        // to whichever tenant it claims
+        if let Ok(claims) = state.identity_service.validate_token(token).await {
+            return Uuid::parse_str(&claims.tenant_id).ok();
+        }
+    }
+
+    headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|host| state.quotas.tenant_for_host(host))
+}
+
+fn reject(rejection: QuotaRejection) -> Response {
+    let mut response = ApiError::new(rejection.error).into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(rejection.retry_after_seconds),
+    );
+    if let Some(status) = &rejection.status {
+        add_quota_headers(headers, status);
+    }
+    response
+}
+
+fn add_quota_headers(headers: &mut HeaderMap, status: &QuotaStatus) {
+    headers.insert(
+        "x-ratelimit-limit",
+        HeaderValue::from(status.requests_per_minute),
+    );
+    headers.insert("x-ratelimit-remaining", HeaderValue::from(status.remaining));
+    headers.insert("x-ratelimit-reset", HeaderValue::from(status.reset_seconds));
+    headers.insert(
+        "x-concurrency-limit",
+        HeaderValue::from(status.max_concurrent),
+    );
+}
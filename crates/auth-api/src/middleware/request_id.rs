@@ -1,9 +1,16 @@
+use auth_core::context::RequestContext;
 use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use tracing::Instrument;
 use uuid::Uuid;
 
 pub const REQUEST_ID_HEADER: &str = "x-request-id";
+pub const TENANT_ID_HEADER: &str = "x-tenant-id";
 
 /// Middleware to generate and attach request ID
+///
+/// The rest of the request runs inside a [`RequestContext`] scope and a
+/// tracing span carrying the ID, so audit events, logs, error bodies and
+/// webhooks all correlate.
 pub async fn request_id_middleware(mut req: Request, next: Next) -> Response {
     // Check if request already has an ID (from load balancer/proxy)
     let request_id = req
@@ -13,10 +20,31 @@ pub async fn request_id_middleware(mut req: Request, next: Next) -> Response {
         .and_then(|s| Uuid::parse_str(s).ok())
         .unwrap_or_else(Uuid::new_v4);
 
+    let tenant_id = req
+        .headers()
+        .get(TENANT_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| Uuid::parse_str(s).ok());
+    let ip_address = req
+        .headers()
+        .get("x-forwarded-for")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.split(',').next())
+        .map(|ip| ip.trim().to_string());
+
     // Store in request extensions for handlers to use
     req.extensions_mut().insert(request_id);
 
-    let mut response = next.run(req).await;
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+    let context = RequestContext::new(request_id)
+        .with_tenant(tenant_id)
+        .with_ip(ip_address);
+    let mut response = context.scope(next.run(req)).instrument(span).await;
 
     // Add request ID to response headers
     if let Ok(header_value) = HeaderValue::from_str(&request_id.to_string()) {
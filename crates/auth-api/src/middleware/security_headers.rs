@@ -1,12 +1,20 @@
 use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
 
+/// Response extension marking a page that relying parties are allowed to embed
+/// in an iframe (e.g. the OIDC check_session_iframe).
+#[derive(Debug, Clone, Copy)]
+pub struct FrameEmbeddable;
+
 /// Middleware to add security headers
 pub async fn security_headers_middleware(req: Request, next: Next) -> Response {
     let mut response = next.run(req).await;
+    let embeddable = response.extensions().get::<FrameEmbeddable>().is_some();
     let headers = response.headers_mut();
 
     // Prevent clickjacking
-    headers.insert("x-frame-options", HeaderValue::from_static("DENY"));
+    if !embeddable {
+        headers.insert("x-frame-options", HeaderValue::from_static("DENY"));
+    }
 
     // Prevent MIME type sniffing
     headers.insert(
@@ -27,10 +35,12 @@ pub async fn security_headers_middleware(req: Request, next: Next) -> Response {
     );
 
     // Content Security Policy
-    headers.insert(
-        "content-security-policy",
-        HeaderValue::from_static("default-src 'self'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'"),
-    );
+    let csp = if embeddable {
+        "default-src 'self'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'; frame-ancestors *"
+    } else {
+        "default-src 'self'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'; frame-ancestors 'none'"
+    };
+    headers.insert("content-security-policy", HeaderValue::from_static(csp));
 
     // Referrer policy
     headers.insert(
@@ -0,0 +1,14 @@
+use auth_core::services::timing::ResponseFloor;
+use axum::{extract::Request, middleware::Next, response::Response};
+use std::time::Duration;
+
+/// Floor for endpoints that check credentials: comfortably above the slowest
+/// normal verification, with jitter so the floor itself is not a clean signal
+pub const CREDENTIAL_RESPONSE_FLOOR: ResponseFloor =
+    ResponseFloor::new(Duration::from_millis(300), Duration::from_millis(75));
+
+/// Pad credential endpoint responses to a jittered minimum duration, so
+/// unknown accounts, wrong codes and successes take indistinguishable time
+pub async fn credential_timing_middleware(req: Request, next: Next) -> Response {
+    CREDENTIAL_RESPONSE_FLOOR.run(next.run(req)).await
+}
@@ -0,0 +1,53 @@
+//! Internal admin API for online schema migrations
+//!
+//! Served on the admin listener. Lists migrations with their phases and
+//! moves one to another phase: forward one step, or back any number. The
+//! replica serving the request switches at once; the others follow within
+//! `database.online_migrations.refresh_seconds`.
+
+use crate::error::ApiError;
+use auth_config::MigrationPhase;
+use auth_db::online_migration::{MigrationStatus, OnlineMigrations};
+use auth_db::repositories::online_migration_repository::PhaseChange;
+use axum::{
+    extract::{Path, State},
+    routing::{get, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+pub fn router(migrations: Arc<OnlineMigrations>) -> Router {
+    Router::new()
+        .route("/admin/migrations", get(list_migrations))
+        .route("/admin/migrations/:name/phase", put(set_phase))
+        .with_state(migrations)
+}
+
+/// GET /admin/migrations
+async fn list_migrations(
+    State(migrations): State<Arc<OnlineMigrations>>,
+) -> Json<Vec<MigrationStatus>> {
+    Json(migrations.statuses())
+}
+
+#[derive(Debug, Deserialize)]
+struct PhaseRequest {
+    phase: MigrationPhase,
+    changed_by: Option<String>,
+}
+
+/// PUT /admin/migrations/:name/phase
+///
+/// 400 when the move skips a phase
+async fn set_phase(
+    State(migrations): State<Arc<OnlineMigrations>>,
+    Path(name): Path<String>,
+    Json(request): Json<PhaseRequest>,
+) -> Result<Json<PhaseChange>, ApiError> {
+    Ok(Json(
+        migrations
+            .set_phase(&name, request.phase, request.changed_by)
+            .await?,
+    ))
+}
@@ -0,0 +1,33 @@
+//! Redis backend for the single-use token store
+
+use async_trait::async_trait;
+use auth_cache::{RedeemOutcome, RedisSingleUse};
+use auth_core::services::nonce_store::{NonceBackend, Redemption};
+use std::time::Duration;
+
+pub struct RedisNonceBackend {
+    redis: RedisSingleUse,
+}
+
+impl RedisNonceBackend {
+    pub fn new(redis_url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            redis: RedisSingleUse::new(redis_url)?,
+        })
+    }
+}
+
+#[async_trait]
+impl NonceBackend for RedisNonceBackend {
+    async fn put_if_absent(&self, key: &str, payload: &str, ttl: Duration) -> anyhow::Result<bool> {
+        self.redis.put_if_absent(key, payload, ttl).await
+    }
+
+    async fn redeem(&self, key: &str) -> anyhow::Result<Redemption> {
+        Ok(match self.redis.redeem(key).await? {
+            RedeemOutcome::Payload(payload) => Redemption::Redeemed(payload),
+            RedeemOutcome::Consumed => Redemption::Replayed,
+            RedeemOutcome::Missing => Redemption::Unknown,
+        })
+    }
+}
@@ -0,0 +1,111 @@
+//! Internal admin API for port lease management and the OIDC self-check
+//!
+//! Served on its own `PortClass::Admin` listener, never on the public router.
+//! Lets operators see which process holds which port, clear leases left
+//! behind by crashed processes, and run the OIDC conformance self-check.
+
+use auth_platform::port_authority::PortError;
+use auth_platform::{AddressFamily, LeaseStatus, PortAuthority};
+use auth_protocols::conformance::{run_self_check, ConformanceReport, SelfCheckConfig};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+pub fn router(authority: Arc<PortAuthority>) -> Router {
+    Router::new()
+        .route("/admin/ports/leases", get(list_leases))
+        .route("/admin/ports/leases/validate", post(validate_leases))
+        .route("/admin/ports/leases/:port", delete(reclaim_lease))
+        .route("/admin/conformance/self-check", post(self_check))
+        .with_state(authority)
+}
+
+struct PortAdminError(PortError);
+
+impl From<PortError> for PortAdminError {
+    fn from(err: PortError) -> Self {
+        Self(err)
+    }
+}
+
+impl IntoResponse for PortAdminError {
+    fn into_response(self) -> Response {
+        let status = match &self.0 {
+            PortError::LeaseNotFound(_) => StatusCode::NOT_FOUND,
+            PortError::PortOccupied { .. } | PortError::OwnLease(_) => StatusCode::CONFLICT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (
+            status,
+            Json(serde_json::json!({ "error": self.0.to_string() })),
+        )
+            .into_response()
+    }
+}
+
+/// GET /admin/ports/leases
+async fn list_leases(
+    State(authority): State<Arc<PortAuthority>>,
+) -> Result<Json<Vec<LeaseStatus>>, PortAdminError> {
+    Ok(Json(authority.lease_status().await?))
+}
+
+/// POST /admin/ports/leases/validate
+///
+/// Reclaim every lease whose owner process has exited
+async fn validate_leases(
+    State(authority): State<Arc<PortAuthority>>,
+) -> Result<impl IntoResponse, PortAdminError> {
+    let reclaimed = authority.validate_leases().await?;
+    Ok(Json(serde_json::json!({ "reclaimed": reclaimed })))
+}
+
+#[derive(Debug, Deserialize)]
+struct ReclaimQuery {
+    #[serde(default)]
+    force: bool,
+    #[serde(default)]
+    family: AddressFamily,
+}
+
+/// DELETE /admin/ports/leases/:port?force=true&family=v6
+///
+/// `force` removes the lease even if its PID is alive (e.g. the PID was reused).
+/// `family` picks the IPv4 (default) or IPv6 lease of a dual-stack port.
+async fn reclaim_lease(
+    State(authority): State<Arc<PortAuthority>>,
+    Path(port): Path<u16>,
+    Query(query): Query<ReclaimQuery>,
+) -> Result<impl IntoResponse, PortAdminError> {
+    let lease = authority
+        .reclaim_lease(port, query.family, query.force)
+        .await?;
+    Ok(Json(serde_json::json!({
+        "reclaimed": {
+            "port": lease.port,
+            "family": lease.family,
+            "pid": lease.pid,
+            "service_name": lease.service_name,
+        }
+    })))
+}
+
+/// POST /admin/conformance/self-check
+///
+/// Signs the given test account in through the public listener and reports
+/// against the OIDC Basic and PKCE profiles; 200 only if nothing failed
+async fn self_check(Json(config): Json<SelfCheckConfig>) -> (StatusCode, Json<ConformanceReport>) {
+    let report = run_self_check(config).await;
+    let status = if report.passed() {
+        StatusCode::OK
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+    (status, Json(report))
+}
@@ -0,0 +1,36 @@
+//! Refusal of requests for tenants pinned to another data region
+//!
+//! [`TenantScope`](crate::tenant_scope::TenantScope) routes each
+//! repository call to the tenant's region. [`residency_middleware`] turns
+//! away requests naming a tenant this deployment cannot serve before any
+//! handler runs, with 421 and the tenant's region so a front door can
+//! retry there.
+
+use crate::error::ApiError;
+use crate::middleware::TENANT_ID_HEADER;
+use auth_db::residency::RegionRouter;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub async fn residency_middleware(
+    State(regions): State<Arc<RegionRouter>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let tenant_id = req
+        .headers()
+        .get(TENANT_ID_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| Uuid::parse_str(h).ok());
+    if let Some(tenant_id) = tenant_id {
+        if let Err(e) = regions.pool_for(tenant_id) {
+            return ApiError::new(e).into_response();
+        }
+    }
+    next.run(req).await
+}
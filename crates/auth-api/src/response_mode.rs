@@ -0,0 +1,219 @@
+//! Delivery of authorization responses
+//!
+//! `/auth/authorize` returns its result to the client's redirect URI in the
+//! query string, in the fragment, or as an auto-submitting HTML form posted
+//! to it (OAuth 2.0 Form Post Response Mode). Which of these a client may ask
+//! for is configured under `[security.sso]`, per client id.
+
+use crate::error::oauth::OAuthError;
+use crate::handlers::oidc_provider::append_query;
+use auth_config::{ResponseMode, SsoConfig};
+use axum::{
+    http::{header, HeaderValue},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use std::collections::HashMap;
+
+pub struct ResponseModes {
+    default: Vec<ResponseMode>,
+    clients: HashMap<String, Vec<ResponseMode>>,
+}
+
+impl ResponseModes {
+    pub fn new(config: &SsoConfig) -> Self {
+        Self {
+            default: config.response_modes.clone(),
+            clients: config.client_response_modes.clone(),
+        }
+    }
+
+    pub fn allowed(&self, client_id: &str) -> &[ResponseMode] {
+        self.clients.get(client_id).unwrap_or(&self.default)
+    }
+
+    /// Mode of the authorization response for the requested `response_mode`
+    ///
+    /// Without one, the response goes in the query string, or in the
+    /// client's first allowed mode when the query string is not allowed.
+    pub fn resolve(
+        &self,
+        client_id: &str,
+        requested: Option<&str>,
+    ) -> Result<ResponseMode, OAuthError> {
+        let allowed = self.allowed(client_id);
+        let Some(requested) = requested else {
+            return allowed
+                .iter()
+                .copied()
+                .find(|mode| *mode == ResponseMode::Query)
+                .or_else(|| allowed.first().copied())
+                .ok_or_else(|| {
+                    OAuthError::invalid_request("no response mode is allowed for this client")
+                });
+        };
+        let mode = ResponseMode::parse(requested).ok_or_else(|| {
+            OAuthError::invalid_request(format!("unsupported response_mode {}", requested))
+        })?;
+        if !allowed.contains(&mode) {
+            return Err(OAuthError::invalid_request(format!(
+                "response_mode {} is not allowed for this client",
+                requested
+            )));
+        }
+        Ok(mode)
+    }
+}
+
+impl Default for ResponseModes {
+    fn default() -> Self {
+        Self::new(&SsoConfig::default())
+    }
+}
+
+fn encode_params(params: &[(&str, String)]) -> String {
+    params
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, urlencoding::encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Send `params` to `redirect_uri` in `mode`
+pub fn authorization_response(
+    redirect_uri: &str,
+    mode: ResponseMode,
+    params: &[(&str, String)],
+) -> Response {
+    match mode {
+        ResponseMode::Query => {
+            Redirect::to(&append_query(redirect_uri, &encode_params(params))).into_response()
+        }
+        ResponseMode::Fragment => {
+            // A fragment already on the redirect URI is replaced
+            let base = redirect_uri.split('#').next().unwrap_or(redirect_uri);
+            Redirect::to(&format!("{}#{}", base, encode_params(params))).into_response()
+        }
+        ResponseMode::FormPost => {
+            let mut response = Html(form_post_html(redirect_uri, params)).into_response();
+            let headers = response.headers_mut();
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+            headers.insert(header::PRAGMA, HeaderValue::from_static("no-cache"));
+            response
+        }
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The form submits itself on load; the button is for browsers without
+/// script.
+fn form_post_html(redirect_uri: &str, params: &[(&str, String)]) -> String {
+    let inputs: String = params
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "<input type=\"hidden\" name=\"{}\" value=\"{}\"/>\n",
+                escape_html(name),
+                escape_html(value)
+            )
+        })
+        .collect();
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head><title>Submit this form</title></head>\n\
+         <body onload=\"document.forms[0].submit()\">\n\
+         <form method=\"post\" action=\"{}\">\n{}\
+         <noscript><button type=\"submit\">Continue</button></noscript>\n\
+         </form>\n</body>\n</html>\n",
+        escape_html(redirect_uri),
+        inputs
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    fn modes() -> ResponseModes {
+        ResponseModes::new(&SsoConfig {
+            client_response_modes: HashMap::from([(
+                "spa".to_string(),
+                vec![ResponseMode::Fragment],
+            )]),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_modes_are_resolved_per_client() {
+        let modes = modes();
+        assert_eq!(modes.resolve("web", None).unwrap(), ResponseMode::Query);
+        assert_eq!(
+            modes.resolve("web", Some("form_post")).unwrap(),
+            ResponseMode::FormPost
+        );
+        assert!(modes.resolve("web", Some("fragment")).is_err());
+        assert!(modes.resolve("web", Some("web_message")).is_err());
+        assert_eq!(modes.resolve("spa", None).unwrap(), ResponseMode::Fragment);
+        assert!(modes.resolve("spa", Some("query")).is_err());
+    }
+
+    #[test]
+    fn test_redirect_modes_place_params() {
+        let params = [("code", "a b".to_string()), ("state", "s".to_string())];
+        let location = |response: Response| {
+            response.headers()[header::LOCATION]
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            location(authorization_response(
+                "https://rp/cb?x=1",
+                ResponseMode::Query,
+                &params
+            )),
+            "https://rp/cb?x=1&code=a%20b&state=s"
+        );
+        assert_eq!(
+            location(authorization_response(
+                "https://rp/cb#old",
+                ResponseMode::Fragment,
+                &params
+            )),
+            "https://rp/cb#code=a%20b&state=s"
+        );
+    }
+
+    #[test]
+    fn test_form_post_escapes_values() {
+        let response = authorization_response(
+            "https://rp/cb?a=1&b=2",
+            ResponseMode::FormPost,
+            &[("state", "\"><script>".to_string())],
+        );
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+
+        let html = form_post_html(
+            "https://rp/cb?a=1&b=2",
+            &[("state", "\"><script>".to_string())],
+        );
+        assert!(html.contains("action=\"https://rp/cb?a=1&amp;b=2\""));
+        assert!(html.contains("value=\"&quot;&gt;&lt;script&gt;\""));
+        assert!(!html.contains("<script>"));
+    }
+}
@@ -0,0 +1,104 @@
+//! Redis-cached revocation lookups in front of the database blacklist
+//!
+//! Revocations are written to the database and then to Redis, so a cached
+//! "revoked" answer is final. "Not revoked" answers are only kept briefly,
+//! which bounds how long a revocation made directly in the database can go
+//! unnoticed. When Redis is unavailable every lookup goes to the database.
+
+use async_trait::async_trait;
+use auth_cache::RedisRevocationCache;
+use auth_core::error::AuthError;
+use auth_core::services::token_service::RevokedTokenStore;
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How long a database "not revoked" answer is trusted
+const NOT_REVOKED_TTL: Duration = Duration::from_secs(30);
+
+/// A revoked token stays revoked; this only bounds memory in Redis
+const REVOKED_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub struct CachedRevokedTokenStore {
+    inner: Arc<dyn RevokedTokenStore>,
+    redis: RedisRevocationCache,
+}
+
+impl CachedRevokedTokenStore {
+    pub fn new(inner: Arc<dyn RevokedTokenStore>, redis_url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            inner,
+            redis: RedisRevocationCache::new(redis_url)?,
+        })
+    }
+}
+
+#[async_trait]
+impl RevokedTokenStore for CachedRevokedTokenStore {
+    async fn add_to_blacklist(
+        &self,
+        jti: Uuid,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AuthError> {
+        self.inner
+            .add_to_blacklist(jti, user_id, tenant_id, expires_at)
+            .await?;
+        if let Err(e) = self.redis.store(&[(jti, true, REVOKED_TTL)]).await {
+            // Lookups fall back to the database once the cached entry expires
+            tracing::warn!("Failed to cache revocation of {}: {}", jti, e);
+        }
+        Ok(())
+    }
+
+    async fn is_revoked(&self, jti: Uuid) -> Result<bool, AuthError> {
+        Ok(self.revoked_among(&[jti]).await?.contains(&jti))
+    }
+
+    async fn revoked_among(&self, jtis: &[Uuid]) -> Result<HashSet<Uuid>, AuthError> {
+        let cached = match self.redis.lookup(jtis).await {
+            Ok(cached) => cached,
+            Err(e) => {
+                tracing::warn!("Revocation cache unavailable, using database: {}", e);
+                return self.inner.revoked_among(jtis).await;
+            }
+        };
+
+        let mut revoked = HashSet::new();
+        let mut misses = Vec::new();
+        for (jti, status) in jtis.iter().zip(cached) {
+            match status {
+                Some(true) => {
+                    revoked.insert(*jti);
+                }
+                Some(false) => {}
+                None => misses.push(*jti),
+            }
+        }
+        if misses.is_empty() {
+            return Ok(revoked);
+        }
+
+        let found = self.inner.revoked_among(&misses).await?;
+        let entries: Vec<_> = misses
+            .iter()
+            .map(|jti| {
+                let is_revoked = found.contains(jti);
+                let ttl = if is_revoked {
+                    REVOKED_TTL
+                } else {
+                    NOT_REVOKED_TTL
+                };
+                (*jti, is_revoked, ttl)
+            })
+            .collect();
+        if let Err(e) = self.redis.store(&entries).await {
+            tracing::warn!("Failed to cache revocation lookups: {}", e);
+        }
+        revoked.extend(found);
+        Ok(revoked)
+    }
+}
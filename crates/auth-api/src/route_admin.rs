@@ -0,0 +1,48 @@
+//! Internal admin API for route access declarations
+//!
+//! Served on the admin listener. Lists the access every API route declares,
+//! and the documented operations that declare none; those are refused at
+//! runtime until they do.
+
+use crate::route_policy::{RouteEntry, RouteRegistry, UndeclaredRoute};
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub fn router(routes: Arc<RouteRegistry>) -> Router {
+    Router::new()
+        .route("/admin/routes", get(list_routes))
+        .with_state(routes)
+}
+
+#[derive(Debug, Deserialize)]
+struct RoutesQuery {
+    /// Only list the undeclared operations
+    #[serde(default)]
+    undeclared: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct RouteListing {
+    routes: Vec<RouteEntry>,
+    undeclared: Vec<UndeclaredRoute>,
+}
+
+/// GET /admin/routes?undeclared=true
+async fn list_routes(
+    State(routes): State<Arc<RouteRegistry>>,
+    Query(query): Query<RoutesQuery>,
+) -> Json<RouteListing> {
+    Json(RouteListing {
+        routes: if query.undeclared {
+            Vec::new()
+        } else {
+            routes.entries()
+        },
+        undeclared: routes.undeclared_documented(),
+    })
+}
@@ -0,0 +1,371 @@
+//! Declared access requirements of API routes
+//!
+//! Every API route is registered through [`PolicyRouter`] together with the
+//! access it requires. [`route_policy_middleware`] looks the matched route up
+//! and enforces that access before the handler runs, so a new route cannot
+//! be added without deciding who may call it. Routes with no declaration are
+//! refused.
+//!
+//! Handlers of authenticated routes receive the caller as an
+//! `Extension<Caller>`; the token's [`Claims`] are inserted as well.
+
+use crate::error::ApiError;
+use crate::AppState;
+use auth_core::error::{AuthError, TokenErrorKind};
+use auth_core::models::token::Claims;
+use auth_core::services::remember_me::is_remembered;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{HeaderMap, Method},
+    middleware::Next,
+    response::Response,
+    routing::MethodRouter,
+    Router,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::warn;
+use utoipa::openapi::path::PathItemType;
+use utoipa::openapi::OpenApi;
+use uuid::Uuid;
+
+/// What a caller needs to reach a route
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum Access {
+    /// Anyone: sign-in endpoints and public metadata
+    Public,
+    /// A valid bearer access token
+    Authenticated,
+    /// A bearer access token from a full sign-in; one restored from a
+    /// remember-me token must step up first
+    FreshSignIn,
+    /// A bearer access token whose user holds the permission in the token's
+    /// tenant
+    Permission(&'static str),
+    /// A credential other than a bearer access token, e.g. a session cookie
+    /// or a client assertion, which the handler checks itself
+    Handler(&'static str),
+}
+
+/// Access of a route, for all of its methods or per method
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoutePolicy {
+    All(Access),
+    PerMethod(Vec<(Method, Access)>),
+}
+
+impl RoutePolicy {
+    pub fn per_method(methods: impl IntoIterator<Item = (Method, Access)>) -> Self {
+        RoutePolicy::PerMethod(methods.into_iter().collect())
+    }
+
+    fn access(&self, method: &Method) -> Option<Access> {
+        // GET handlers also answer HEAD
+        let method = if *method == Method::HEAD {
+            &Method::GET
+        } else {
+            method
+        };
+        match self {
+            RoutePolicy::All(access) => Some(*access),
+            RoutePolicy::PerMethod(methods) => methods
+                .iter()
+                .find(|(m, _)| m == method)
+                .map(|(_, access)| *access),
+        }
+    }
+}
+
+impl From<Access> for RoutePolicy {
+    fn from(access: Access) -> Self {
+        RoutePolicy::All(access)
+    }
+}
+
+/// One row of the route listing
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteEntry {
+    pub path: String,
+    /// `None` when the access applies to every method of the route
+    pub method: Option<String>,
+    pub access: Access,
+}
+
+/// A documented operation with no declared access
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UndeclaredRoute {
+    pub method: String,
+    pub path: String,
+}
+
+/// Declared access of every route, keyed by the route's path pattern
+#[derive(Debug, Clone, Default)]
+pub struct RouteRegistry {
+    routes: BTreeMap<String, RoutePolicy>,
+}
+
+impl RouteRegistry {
+    fn declare(&mut self, path: String, policy: RoutePolicy) {
+        if self.routes.insert(path.clone(), policy).is_some() {
+            panic!("Access of route {} is declared twice", path);
+        }
+    }
+
+    /// Access of `method` on the route matched as `path`
+    pub fn access(&self, path: &str, method: &Method) -> Option<Access> {
+        self.routes
+            .get(path)
+            .and_then(|policy| policy.access(method))
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    pub fn entries(&self) -> Vec<RouteEntry> {
+        let mut entries = Vec::new();
+        for (path, policy) in &self.routes {
+            match policy {
+                RoutePolicy::All(access) => entries.push(RouteEntry {
+                    path: path.clone(),
+                    method: None,
+                    access: *access,
+                }),
+                RoutePolicy::PerMethod(methods) => {
+                    entries.extend(methods.iter().map(|(method, access)| RouteEntry {
+                        path: path.clone(),
+                        method: Some(method.to_string()),
+                        access: *access,
+                    }))
+                }
+            }
+        }
+        entries
+    }
+
+    /// Operations of the OpenAPI document whose route declares no access
+    pub fn undeclared(&self, api: &OpenApi) -> Vec<UndeclaredRoute> {
+        let mut undeclared = Vec::new();
+        for (path, item) in &api.paths.paths {
+            // `/users/{id}` is routed as `/users/:id`
+            let pattern = path.replace('{', ":").replace('}', "");
+            for operation in item.operations.keys() {
+                let method = match operation {
+                    PathItemType::Get => Method::GET,
+                    PathItemType::Post => Method::POST,
+                    PathItemType::Put => Method::PUT,
+                    PathItemType::Delete => Method::DELETE,
+                    PathItemType::Options => Method::OPTIONS,
+                    PathItemType::Head => Method::HEAD,
+                    PathItemType::Patch => Method::PATCH,
+                    PathItemType::Trace => Method::TRACE,
+                    PathItemType::Connect => Method::CONNECT,
+                };
+                if self.access(&pattern, &method).is_none() {
+                    undeclared.push(UndeclaredRoute {
+                        method: method.to_string(),
+                        path: path.clone(),
+                    });
+                }
+            }
+        }
+        undeclared
+    }
+
+    /// Operations of this API's OpenAPI document that declare no access
+    pub fn undeclared_documented(&self) -> Vec<UndeclaredRoute> {
+        use utoipa::OpenApi as _;
+
+        self.undeclared(&crate::ApiDoc::openapi())
+    }
+}
+
+/// Router that records the declared access of each route it is given
+#[derive(Default)]
+pub struct PolicyRouter {
+    router: Router<AppState>,
+    registry: RouteRegistry,
+}
+
+impl PolicyRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route(
+        mut self,
+        path: &str,
+        policy: impl Into<RoutePolicy>,
+        method_router: MethodRouter<AppState>,
+    ) -> Self {
+        self.registry.declare(path.to_string(), policy.into());
+        self.router = self.router.route(path, method_router);
+        self
+    }
+
+    pub fn nest(mut self, prefix: &str, other: PolicyRouter) -> Self {
+        for (path, policy) in other.registry.routes {
+            self.registry.declare(format!("{}{}", prefix, path), policy);
+        }
+        self.router = self.router.nest(prefix, other.router);
+        self
+    }
+
+    pub fn merge(mut self, other: PolicyRouter) -> Self {
+        for (path, policy) in other.registry.routes {
+            self.registry.declare(path, policy);
+        }
+        self.router = self.router.merge(other.router);
+        self
+    }
+
+    pub fn into_parts(self) -> (Router<AppState>, RouteRegistry) {
+        (self.router, self.registry)
+    }
+}
+
+/// Caller of an authenticated route
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub user_id: Uuid,
+    pub tenant_id: Uuid,
+    pub claims: Claims,
+}
+
+async fn authenticate(state: &AppState, headers: &HeaderMap) -> Result<Caller, ApiError> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(ApiError::new(AuthError::Unauthorized {
+            message: "Missing token".to_string(),
+        }))?;
+    let claims = state.identity_service.validate_token(token).await?;
+    let invalid = |_| {
+        ApiError::new(AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        })
+    };
+    Ok(Caller {
+        user_id: Uuid::parse_str(&claims.sub).map_err(invalid)?,
+        tenant_id: Uuid::parse_str(&claims.tenant_id).map_err(invalid)?,
+        claims,
+    })
+}
+
+/// State of [`route_policy_middleware`]
+#[derive(Clone)]
+pub struct RouteGuard {
+    state: AppState,
+    routes: Arc<RouteRegistry>,
+}
+
+impl RouteGuard {
+    pub fn new(state: AppState, routes: Arc<RouteRegistry>) -> Self {
+        Self { state, routes }
+    }
+}
+
+/// Enforce the declared access of the matched route
+///
+/// Must be added with `route_layer`, so that the route has been matched.
+pub async fn route_policy_middleware(
+    State(guard): State<RouteGuard>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_default();
+    let Some(access) = guard.routes.access(&path, req.method()) else {
+        warn!(method = %req.method(), path = %path, "Refusing route with no declared access");
+        return Err(ApiError::new(AuthError::AuthorizationDenied {
+            permission: "undeclared route".to_string(),
+            resource: format!("route:{} {}", req.method(), path),
+        }));
+    };
+
+    match access {
+        Access::Public | Access::Handler(_) => {}
+        Access::Authenticated => {
+            let caller = authenticate(&guard.state, req.headers()).await?;
+            req.extensions_mut().insert(caller.claims.clone());
+            req.extensions_mut().insert(caller);
+        }
+        Access::FreshSignIn => {
+            let caller = authenticate(&guard.state, req.headers()).await?;
+            if is_remembered(&caller.claims) {
+                return Err(ApiError::new(AuthError::StepUpRequired));
+            }
+            req.extensions_mut().insert(caller.claims.clone());
+            req.extensions_mut().insert(caller);
+        }
+        Access::Permission(permission) => {
+            let caller = authenticate(&guard.state, req.headers()).await?;
+            if !guard
+                .state
+                .role_service
+                .has_permission(caller.user_id, caller.tenant_id, permission)
+                .await?
+            {
+                return Err(ApiError::new(AuthError::AuthorizationDenied {
+                    permission: permission.to_string(),
+                    resource: format!("tenant:{}", caller.tenant_id),
+                }));
+            }
+            req.extensions_mut().insert(caller.claims.clone());
+            req.extensions_mut().insert(caller);
+        }
+    }
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+
+    #[test]
+    fn test_nested_and_per_method_declarations() {
+        let inner = PolicyRouter::new()
+            .route("/items/:id", Access::Authenticated, get(|| async {}))
+            .route(
+                "/session",
+                RoutePolicy::per_method([
+                    (Method::GET, Access::Handler("session cookie")),
+                    (Method::POST, Access::Permission("session:create")),
+                ]),
+                get(|| async {}).post(|| async {}),
+            );
+        let (_, registry) = PolicyRouter::new()
+            .route("/health", Access::Public, get(|| async {}))
+            .nest("/v1", inner)
+            .into_parts();
+
+        assert_eq!(
+            registry.access("/v1/items/:id", &Method::DELETE),
+            Some(Access::Authenticated)
+        );
+        assert_eq!(
+            registry.access("/v1/session", &Method::POST),
+            Some(Access::Permission("session:create"))
+        );
+        assert_eq!(registry.access("/v1/session", &Method::PUT), None);
+        assert_eq!(registry.access("/items/:id", &Method::GET), None);
+        assert_eq!(registry.entries().len(), 4);
+    }
+
+    #[test]
+    fn test_every_documented_route_declares_access() {
+        let undeclared = crate::router::route_registry().undeclared_documented();
+        assert!(undeclared.is_empty(), "{:?}", undeclared);
+    }
+}
@@ -1,133 +1,470 @@
 use crate::handlers::{
-    auth, auth_flow, auth_oidc, auth_saml, authorization, certs, discovery, health, lazy_reg,
-    login_otp, oidc_provider, otp, profile, register, users, verification, workflow,
+    auth, auth_flow, auth_oidc, auth_saml, authorization, bff, capabilities, certs, devices,
+    discovery, health, lazy_reg, login_history, login_links, login_otp, meta, oauth_clients,
+    oidc_provider, otp, profile, push_mfa, recovery_codes, register, remember_me, service_accounts,
+    session_events, ssh, sso, tenants, tokens, users, verification, webauthn, workflow,
+};
+use crate::middleware::{
+    cohort_outcome_middleware, credential_timing_middleware, problem_response_middleware,
+    request_id_middleware, security_headers_middleware, RateLimiter,
+};
+use crate::route_policy::{
+    route_policy_middleware, Access, PolicyRouter, RouteGuard, RoutePolicy, RouteRegistry,
 };
-use crate::middleware::{request_id_middleware, security_headers_middleware, RateLimiter};
 use crate::AppState;
 use axum::{
+    http::Method,
     middleware,
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
 };
+use std::sync::Arc;
 use std::time::Duration;
 use tower_http::trace::TraceLayer;
 
-pub fn api_router() -> Router<AppState> {
-    // Create rate limiter middleware: 100 requests per minute global (adjusted from 5 to avoid blocking tests too easily)
-    let rate_limiter = RateLimiter::new(100, Duration::from_secs(60));
+/// Credential checked by the flow handlers
+const FLOW_CREDENTIAL: &str = "flow id or sealed flow token";
 
-    let v1_routes = Router::new()
+/// Every versioned route with the access it requires
+fn v1_routes() -> PolicyRouter {
+    PolicyRouter::new()
         // Auth - Basic & Multi-Channel
-        .route("/auth/login", post(auth::login))
-        .route("/auth/register", post(register::register)) // Replaced basic register with multi-channel
-        .route("/auth/register/lazy", post(lazy_reg::lazy_register))
+        .route(
+            "/auth/login",
+            Access::Public,
+            post(auth::login).layer(middleware::from_fn(credential_timing_middleware)),
+        )
+        .route(
+            "/auth/password/expired",
+            Access::Handler("password change ticket"),
+            post(auth::change_expired_password),
+        )
+        .route("/auth/register", Access::Public, post(register::register))
+        .route(
+            "/auth/register/lazy",
+            Access::Public,
+            post(lazy_reg::lazy_register),
+        )
         // Auth - OTP
-        .route("/auth/otp/request", post(otp::request_otp))
-        .route("/auth/otp/verify", post(otp::verify_otp))
-        .route("/auth/login/otp", post(login_otp::login_with_otp))
+        .route("/auth/otp/request", Access::Public, post(otp::request_otp))
+        .route(
+            "/auth/otp/verify",
+            Access::Public,
+            post(otp::verify_otp).layer(middleware::from_fn(credential_timing_middleware)),
+        )
+        .route(
+            "/auth/login/otp",
+            Access::Public,
+            post(login_otp::login_with_otp)
+                .layer(middleware::from_fn(credential_timing_middleware)),
+        )
+        .route(
+            "/auth/login-history",
+            Access::Authenticated,
+            get(login_history::login_history),
+        )
+        .route(
+            "/auth/webauthn/register/options",
+            Access::FreshSignIn,
+            post(webauthn::registration_options),
+        )
+        .route(
+            "/auth/webauthn/register",
+            Access::FreshSignIn,
+            post(webauthn::register),
+        )
+        .route(
+            "/auth/webauthn/options",
+            Access::Public,
+            post(webauthn::authentication_options),
+        )
+        .route(
+            "/auth/webauthn/authenticate",
+            Access::Public,
+            post(webauthn::authenticate).layer(middleware::from_fn(credential_timing_middleware)),
+        )
         // Auth - Profile
-        .route("/auth/profile/complete", post(profile::complete_profile))
+        .route(
+            "/auth/profile/complete",
+            Access::Authenticated,
+            post(profile::complete_profile),
+        )
         // Auth - Verification
         .route(
             "/auth/verify/email/send",
+            Access::Public,
             post(verification::send_email_verification),
         )
-        .route("/auth/verify/email", get(verification::verify_email_link))
+        .route(
+            "/auth/verify/email",
+            Access::Public,
+            get(verification::verify_email_link),
+        )
+        .route(
+            "/auth/verify/email/confirm-link",
+            Access::Public,
+            post(verification::confirm_email_link),
+        )
+        .route(
+            "/auth/verify/email/confirm",
+            Access::Public,
+            post(verification::confirm_email_verification)
+                .layer(middleware::from_fn(credential_timing_middleware)),
+        )
         .route(
             "/auth/verify/phone/send",
+            Access::Public,
             post(verification::send_phone_verification),
         )
         .route(
             "/auth/verify/phone/confirm",
-            post(verification::confirm_phone_verification),
+            Access::Public,
+            post(verification::confirm_phone_verification)
+                .layer(middleware::from_fn(credential_timing_middleware)),
         )
         // Users
-        .route("/users/:id/ban", post(users::ban_user))
-        .route("/users/:id/activate", post(users::activate_user))
+        .route(
+            "/users/:id/ban",
+            Access::Permission(users::MANAGE_PERMISSION),
+            post(users::ban_user),
+        )
+        .route(
+            "/users/:id/activate",
+            Access::Permission(users::MANAGE_PERMISSION),
+            post(users::activate_user),
+        )
+        .route(
+            "/admin/api/tenants/:id/force-reauth",
+            Access::Permission(tenants::FORCE_REAUTH_PERMISSION),
+            post(tenants::force_reauth),
+        )
+        .route(
+            "/admin/api/tenants/:id/clients/:client_id/revoke-tokens",
+            Access::Permission(tenants::REVOKE_CLIENT_TOKENS_PERMISSION),
+            post(tenants::revoke_client_tokens),
+        )
+        .route(
+            "/admin/api/tenants/:id/metrics",
+            Access::Permission(tenants::VIEW_METRICS_PERMISSION),
+            get(tenants::metrics),
+        )
+        .route(
+            "/admin/api/tenants/:id/rate-limit",
+            Access::Permission(tenants::VIEW_METRICS_PERMISSION),
+            get(tenants::rate_limit),
+        )
+        .route(
+            "/admin/api/tenants/:id/onboarding",
+            Access::Permission(tenants::ONBOARDING_PERMISSION),
+            get(tenants::onboarding_status).post(tenants::onboarding_action),
+        )
+        .route(
+            "/auth/service-accounts",
+            Access::Permission(service_accounts::MANAGE_SERVICE_ACCOUNTS_PERMISSION),
+            get(service_accounts::list_service_accounts)
+                .post(service_accounts::create_service_account),
+        )
+        .route(
+            "/auth/service-accounts/:id/status",
+            Access::Permission(service_accounts::MANAGE_SERVICE_ACCOUNTS_PERMISSION),
+            put(service_accounts::update_service_account_status),
+        )
+        .route(
+            "/auth/service-accounts/:id/keys",
+            Access::Permission(service_accounts::MANAGE_SERVICE_ACCOUNTS_PERMISSION),
+            get(service_accounts::list_keys).post(service_accounts::register_key),
+        )
+        .route(
+            "/auth/service-accounts/:id/keys/:kid",
+            Access::Permission(service_accounts::MANAGE_SERVICE_ACCOUNTS_PERMISSION),
+            delete(service_accounts::revoke_key),
+        )
+        .route("/ssh/sign", Access::FreshSignIn, post(ssh::sign_key))
+        .route("/ssh/ca", Access::Public, get(ssh::ca_keys))
+        .route(
+            "/devices/bootstrap-tokens",
+            Access::Permission(devices::MANAGE_DEVICES_PERMISSION),
+            post(devices::create_bootstrap_token),
+        )
+        .route(
+            "/devices/enroll",
+            Access::Handler("bootstrap token"),
+            post(devices::enroll),
+        )
+        .route(
+            "/devices/renew",
+            Access::Handler("CSR signed by the current device key"),
+            post(devices::renew),
+        )
+        .route(
+            "/devices/certificates",
+            Access::Permission(devices::MANAGE_DEVICES_PERMISSION),
+            get(devices::list_certificates),
+        )
+        .route(
+            "/devices/certificates/:serial/revoke",
+            Access::Permission(devices::MANAGE_DEVICES_PERMISSION),
+            post(devices::revoke_certificate),
+        )
+        .route("/devices/:tenant_id/crl", Access::Public, get(devices::crl))
+        .route(
+            "/devices/:tenant_id/cacerts",
+            Access::Public,
+            get(devices::ca_certificates),
+        )
+        .route(
+            "/mfa/push/devices",
+            RoutePolicy::per_method([
+                (Method::GET, Access::Authenticated),
+                (Method::POST, Access::FreshSignIn),
+            ]),
+            post(push_mfa::register_device).get(push_mfa::list_devices),
+        )
+        .route(
+            "/mfa/push/devices/:id",
+            Access::Authenticated,
+            delete(push_mfa::revoke_device),
+        )
+        .route(
+            "/mfa/push/challenges/:id/respond",
+            Access::Handler("push device signature"),
+            post(push_mfa::respond),
+        )
+        .route(
+            "/mfa/recovery-codes",
+            RoutePolicy::per_method([
+                (Method::GET, Access::Authenticated),
+                (Method::POST, Access::FreshSignIn),
+            ]),
+            post(recovery_codes::generate).get(recovery_codes::status),
+        )
         // Advanced Auth Flow
-        .route("/auth/flow/start", post(auth_flow::start_flow))
-        .route("/auth/flow/:id", get(auth_flow::get_flow_state))
-        .route("/auth/flow/:id/resume", post(auth_flow::resume_flow))
+        .route(
+            "/auth/flow/start",
+            Access::Public,
+            post(auth_flow::start_flow),
+        )
+        .route(
+            "/auth/flow/:id",
+            Access::Handler(FLOW_CREDENTIAL),
+            get(auth_flow::get_flow_state),
+        )
+        .route(
+            "/auth/flow/:id/resume",
+            Access::Handler(FLOW_CREDENTIAL),
+            post(auth_flow::resume_flow),
+        )
         // Universal Workflow API (Hyper-Advanced)
-        .route("/auth/flow/:id/submit", post(workflow::submit))
+        .route(
+            "/auth/flow/:id/submit",
+            Access::Handler(FLOW_CREDENTIAL),
+            post(workflow::submit),
+        )
         // Authorization (RBAC)
-        .route("/auth/roles", post(authorization::roles::create_role))
-        .route("/auth/roles/:id", get(authorization::roles::get_role))
+        .route(
+            "/auth/roles",
+            Access::Permission(authorization::roles::WRITE_PERMISSION),
+            post(authorization::roles::create_role),
+        )
+        .route(
+            "/auth/roles/:id",
+            Access::Permission(authorization::roles::READ_PERMISSION),
+            get(authorization::roles::get_role),
+        )
+        .route(
+            "/auth/permissions/:tenant_id/snapshot",
+            Access::Permission(authorization::permission_sync::SYNC_PERMISSION),
+            get(authorization::permission_sync::snapshot),
+        )
+        .route(
+            "/auth/permissions/:tenant_id/changes",
+            Access::Permission(authorization::permission_sync::SYNC_PERMISSION),
+            get(authorization::permission_sync::changes),
+        )
+        // One-time login links sent by support
+        .route(
+            "/auth/users/:user_id/login-links",
+            Access::Permission(login_links::ISSUE_PERMISSION),
+            post(login_links::issue_login_link),
+        )
+        .route(
+            "/auth/login-links/redeem",
+            Access::Handler("login link token"),
+            post(login_links::redeem_login_link)
+                .layer(middleware::from_fn(credential_timing_middleware)),
+        )
+        // Remember-me devices
+        .route(
+            "/auth/remember-me",
+            Access::FreshSignIn,
+            post(remember_me::remember_device),
+        )
+        .route(
+            "/auth/remember-me/redeem",
+            Access::Handler("remember-me token"),
+            post(remember_me::redeem_remember_me)
+                .layer(middleware::from_fn(credential_timing_middleware)),
+        )
+        .route(
+            "/auth/remember-me/devices",
+            Access::Authenticated,
+            get(remember_me::list_devices),
+        )
+        .route(
+            "/auth/remember-me/devices/:id",
+            Access::Authenticated,
+            delete(remember_me::revoke_device),
+        )
         // OIDC / SAML
         .route(
             "/.well-known/openid-configuration",
+            Access::Public,
             get(discovery::oidc_configuration),
         )
-        .route("/auth/certs", get(certs::jwks))
+        .route(
+            "/.well-known/uac-capabilities",
+            Access::Public,
+            get(capabilities::capabilities),
+        )
+        .route(
+            "/auth/features",
+            Access::Authenticated,
+            get(capabilities::features),
+        )
+        .route("/auth/certs", Access::Public, get(certs::jwks))
+        .route("/meta/errors", Access::Public, get(meta::error_catalog))
+        .route(
+            "/auth/tokens/validate-batch",
+            Access::Authenticated,
+            post(tokens::validate_batch),
+        )
         // OIDC Provider Endpoints (Real Implementation)
-        .route("/auth/authorize", get(oidc_provider::authorize))
-        .route("/auth/token", post(oidc_provider::token))
-        .route("/auth/userinfo", get(oidc_provider::userinfo))
-        // Legacy/Federation stubs
-        .route("/auth/oidc/login", get(auth_oidc::login))
-        .route("/auth/oidc/callback", get(auth_oidc::callback))
-        .route("/auth/saml/metadata", get(auth_saml::metadata))
-        .route("/auth/saml/acs", post(auth_saml::acs));
-
-    Router::new()
-        // Health (Global)
-        .route("/health", get(health::health_check))
-        // V1 API
-        .nest("/v1", v1_routes)
-        // Legacy /auth routes (Backwards compatibility)
-        // In a real enterprise migration, we might duplicate routes or redirect.
-        // For now, we only nest v1, and users must use /v1/auth/... or we can mount v1_routes at root too if needed.
-        // To be strictly enterprise, we should strongly encourage versioning.
-        // But for existing tests passing without changing all tests, we might need to mount at root AND /v1.
-        // Let's mount at root for compat, and /v1 for "Next Gen".
-        // Note: Router merge logic might conflict if paths overlap exactly.
-        // Instead, let's keep root routes for compatibility and add /v1 alias logic if framework supports, or just move forward with /v1 preferred.
-        // The master prompt asked to "Implement versioning support".
-        // Re-adding the original routes at root for compatibility with existing SDK/Tests:
-        .route("/auth/login", post(auth::login))
-        .route("/auth/register", post(register::register))
-        .route("/auth/register/lazy", post(lazy_reg::lazy_register))
-        .route("/auth/otp/request", post(otp::request_otp))
-        .route("/auth/otp/verify", post(otp::verify_otp))
-        .route("/auth/login/otp", post(login_otp::login_with_otp))
-        .route("/auth/profile/complete", post(profile::complete_profile))
         .route(
-            "/auth/verify/email/send",
-            post(verification::send_email_verification),
+            "/auth/authorize",
+            Access::Handler("SSO session cookie"),
+            get(oidc_provider::authorize),
         )
-        .route("/auth/verify/email", get(verification::verify_email_link))
         .route(
-            "/auth/verify/phone/send",
-            post(verification::send_phone_verification),
+            "/auth/token",
+            Access::Handler("client credentials and grant"),
+            post(oidc_provider::token).layer(middleware::from_fn(credential_timing_middleware)),
         )
+        // Dynamic client registration (RFC 7591, RFC 7592)
         .route(
-            "/auth/verify/phone/confirm",
-            post(verification::confirm_phone_verification),
+            "/oauth/register",
+            Access::Permission(oauth_clients::MANAGE_CLIENTS_PERMISSION),
+            post(oauth_clients::register),
         )
-        .route("/users/:id/ban", post(users::ban_user))
-        .route("/users/:id/activate", post(users::activate_user))
-        .route("/auth/flow/start", post(auth_flow::start_flow))
-        .route("/auth/flow/:id", get(auth_flow::get_flow_state))
-        .route("/auth/flow/:id/resume", post(auth_flow::resume_flow))
-        .route("/auth/flow/:id/submit", post(workflow::submit))
-        .route("/auth/roles", post(authorization::roles::create_role))
-        .route("/auth/roles/:id", get(authorization::roles::get_role))
         .route(
-            "/.well-known/openid-configuration",
-            get(discovery::oidc_configuration),
+            "/oauth/register/:client_id",
+            Access::Handler("registration access token"),
+            get(oauth_clients::read)
+                .put(oauth_clients::update)
+                .delete(oauth_clients::delete),
+        )
+        .route(
+            "/auth/userinfo",
+            Access::Authenticated,
+            get(oidc_provider::userinfo),
+        )
+        .route(
+            "/auth/check_session",
+            Access::Public,
+            get(oidc_provider::check_session_iframe),
+        )
+        // Central SSO session and logout
+        .route(
+            "/auth/sso/session",
+            RoutePolicy::per_method([
+                (Method::POST, Access::Authenticated),
+                (Method::GET, Access::Handler("SSO session cookie")),
+            ]),
+            post(sso::create_session).get(sso::get_session),
+        )
+        .route(
+            "/auth/logout",
+            Access::Handler("SSO session cookie"),
+            get(sso::end_session),
+        )
+        // Return from upstream identity providers' logout pages
+        .route(
+            "/auth/logout/upstream",
+            Access::Handler("upstream logout state"),
+            get(sso::upstream_logout_return).post(sso::upstream_logout_return_form),
+        )
+        // Browsers cannot set headers on WebSocket upgrades
+        .route(
+            "/auth/session/events",
+            Access::Handler("access token in header or query"),
+            get(session_events::subscribe),
+        )
+        // Backend-For-Frontend
+        .route("/bff/session", Access::Public, post(bff::create_session))
+        .route(
+            "/bff/userinfo",
+            Access::Handler("BFF session cookie"),
+            get(bff::userinfo),
+        )
+        .route(
+            "/bff/logout",
+            Access::Handler("BFF session cookie"),
+            post(bff::logout),
+        )
+        // Legacy/Federation stubs
+        .route("/auth/oidc/login", Access::Public, get(auth_oidc::login))
+        .route(
+            "/auth/oidc/callback",
+            Access::Public,
+            get(auth_oidc::callback),
+        )
+        .route(
+            "/auth/saml/metadata",
+            Access::Public,
+            get(auth_saml::metadata),
         )
-        .route("/auth/certs", get(certs::jwks))
-        .route("/auth/authorize", get(oidc_provider::authorize))
-        .route("/auth/token", post(oidc_provider::token))
-        .route("/auth/userinfo", get(oidc_provider::userinfo))
-        .route("/auth/oidc/login", get(auth_oidc::login))
-        .route("/auth/oidc/callback", get(auth_oidc::callback))
-        .route("/auth/saml/metadata", get(auth_saml::metadata))
-        .route("/auth/saml/acs", post(auth_saml::acs))
+        .route("/auth/saml/acs", Access::Public, post(auth_saml::acs))
+}
+
+/// Every API route with the access it requires
+fn api_routes() -> PolicyRouter {
+    PolicyRouter::new()
+        // Health (Global)
+        .route("/health", Access::Public, get(health::health_check))
+        .route("/ready", Access::Public, get(health::readiness))
+        // V1 API
+        .nest("/v1", v1_routes())
+        // The same routes unversioned, for existing SDKs and tests
+        .merge(v1_routes())
+}
+
+/// Declared access of every API route
+pub fn route_registry() -> RouteRegistry {
+    api_routes().into_parts().1
+}
+
+pub fn api_router(state: &AppState) -> Router<AppState> {
+    // Create rate limiter middleware: 100 requests per minute global (adjusted from 5 to avoid blocking tests too easily)
+    let rate_limiter = RateLimiter::new(100, Duration::from_secs(60));
+
+    let (router, routes) = api_routes().into_parts();
+    let guard = RouteGuard::new(state.clone(), Arc::new(routes));
+    router
+        // Inside the route policy, which identifies the caller
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            cohort_outcome_middleware,
+        ))
+        // Runs once the route is matched, inside the layers below
+        .route_layer(middleware::from_fn_with_state(
+            guard,
+            route_policy_middleware,
+        ))
         // Middleware layers
+        .layer(middleware::from_fn(problem_response_middleware))
         .layer(TraceLayer::new_for_http())
         .layer(middleware::from_fn(security_headers_middleware))
-        .layer(middleware::from_fn(request_id_middleware))
         .layer(middleware::from_fn(crate::middleware::audit_middleware))
+        // Outermost so every layer above runs inside the request context
+        .layer(middleware::from_fn(request_id_middleware))
         .layer(axum::Extension(rate_limiter))
 }
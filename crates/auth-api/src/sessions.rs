@@ -0,0 +1,259 @@
+//! Redis session store with write-behind to the database
+//!
+//! Redis is the store of record for live sessions: validation is a single
+//! Redis call that also slides the idle expiry. Creations and revocations
+//! are queued for [`SessionWriteBehind`] to copy into the database, which
+//! keeps an audit trail of sessions without being on the request path. When
+//! Redis is unreachable, lookups fall back to the database copy.
+
+use async_trait::async_trait;
+use auth_cache::RedisSessionCache;
+use auth_core::error::AuthError;
+use auth_core::models::Session;
+use auth_core::services::session_service::SessionStore;
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+enum SessionWrite {
+    Create(Session),
+    Delete(String),
+    DeleteUser(Uuid),
+    DeleteTenant(Uuid),
+}
+
+pub struct RedisSessionStore {
+    redis: RedisSessionCache,
+    idle_timeout: Duration,
+    durable: Arc<dyn SessionStore>,
+    writes: mpsc::Sender<SessionWrite>,
+}
+
+/// Copies queued session writes into the database
+pub struct SessionWriteBehind {
+    durable: Arc<dyn SessionStore>,
+    writes: mpsc::Receiver<SessionWrite>,
+}
+
+impl RedisSessionStore {
+    /// The store, and the worker that must run for its writes to reach
+    /// `durable`. Callers wait once `capacity` writes are queued.
+    pub fn new(
+        redis_url: &str,
+        idle_timeout: Duration,
+        durable: Arc<dyn SessionStore>,
+        capacity: usize,
+    ) -> anyhow::Result<(Self, SessionWriteBehind)> {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        Ok((
+            Self {
+                redis: RedisSessionCache::new(redis_url)?,
+                idle_timeout,
+                durable: durable.clone(),
+                writes: sender,
+            },
+            SessionWriteBehind {
+                durable,
+                writes: receiver,
+            },
+        ))
+    }
+
+    async fn write_behind(&self, write: SessionWrite) {
+        if self.writes.send(write).await.is_err() {
+            tracing::warn!("Session write-behind worker stopped; database copy not updated");
+        }
+    }
+}
+
+fn redis_error(e: anyhow::Error) -> AuthError {
+    AuthError::ExternalServiceError {
+        service: "redis".to_string(),
+        error: e.to_string(),
+    }
+}
+
+#[async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn create(&self, session: Session) -> Result<Session, AuthError> {
+        let payload = serde_json::to_string(&session).map_err(|_| AuthError::InternalError)?;
+        self.redis
+            .put(
+                &session.session_token,
+                &session.user_id.to_string(),
+                &session.tenant_id.to_string(),
+                &payload,
+                self.idle_timeout,
+                session.expires_at.timestamp_millis(),
+            )
+            .await
+            .map_err(redis_error)?;
+        self.write_behind(SessionWrite::Create(session.clone()))
+            .await;
+        Ok(session)
+    }
+
+    async fn get(&self, session_token: &str) -> Result<Option<Session>, AuthError> {
+        match self.redis.touch(session_token, self.idle_timeout).await {
+            Ok(Some(payload)) => {
+                let mut session: Session =
+                    serde_json::from_str(&payload).map_err(|_| AuthError::InternalError)?;
+                session.last_activity = Utc::now();
+                Ok(Some(session))
+            }
+            // Idle or expired; the database copy does not track idleness
+            Ok(None) => Ok(None),
+            Err(e) => {
+                tracing::warn!("Session lookup in Redis failed, using the database: {}", e);
+                self.durable.get(session_token).await
+            }
+        }
+    }
+
+    async fn delete(&self, session_token: &str) -> Result<(), AuthError> {
+        self.redis
+            .delete(session_token)
+            .await
+            .map_err(redis_error)?;
+        self.write_behind(SessionWrite::Delete(session_token.to_string()))
+            .await;
+        Ok(())
+    }
+
+    async fn delete_by_user(&self, user_id: Uuid) -> Result<(), AuthError> {
+        self.redis
+            .delete_user(&user_id.to_string())
+            .await
+            .map_err(redis_error)?;
+        self.write_behind(SessionWrite::DeleteUser(user_id)).await;
+        Ok(())
+    }
+
+    async fn delete_by_tenant(&self, tenant_id: Uuid) -> Result<u64, AuthError> {
+        let deleted = self
+            .redis
+            .delete_tenant(&tenant_id.to_string())
+            .await
+            .map_err(redis_error)?;
+        self.write_behind(SessionWrite::DeleteTenant(tenant_id))
+            .await;
+        Ok(deleted as u64)
+    }
+}
+
+impl SessionWriteBehind {
+    /// Apply queued writes in order until every sender is dropped
+    pub async fn run(mut self) {
+        while let Some(write) = self.writes.recv().await {
+            let (operation, result) = match write {
+                SessionWrite::Create(session) => {
+                    ("create", self.durable.create(session).await.map(|_| ()))
+                }
+                SessionWrite::Delete(token) => ("delete", self.durable.delete(&token).await),
+                SessionWrite::DeleteUser(user_id) => {
+                    ("delete_by_user", self.durable.delete_by_user(user_id).await)
+                }
+                SessionWrite::DeleteTenant(tenant_id) => (
+                    "delete_by_tenant",
+                    self.durable.delete_by_tenant(tenant_id).await.map(|_| ()),
+                ),
+            };
+            if let Err(e) = result {
+                metrics::counter!("auth_session_write_behind_failures_total", 1, "operation" => operation);
+                tracing::error!("Session write-behind {} failed: {}", operation, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingStore(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl SessionStore for RecordingStore {
+        async fn create(&self, session: Session) -> Result<Session, AuthError> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("create {}", session.session_token));
+            Ok(session)
+        }
+        async fn get(&self, _session_token: &str) -> Result<Option<Session>, AuthError> {
+            Ok(None)
+        }
+        async fn delete(&self, session_token: &str) -> Result<(), AuthError> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("delete {}", session_token));
+            Ok(())
+        }
+        async fn delete_by_user(&self, user_id: Uuid) -> Result<(), AuthError> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("delete_by_user {}", user_id));
+            Ok(())
+        }
+        async fn delete_by_tenant(&self, tenant_id: Uuid) -> Result<u64, AuthError> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("delete_by_tenant {}", tenant_id));
+            Ok(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_behind_applies_writes_in_order() {
+        let durable = Arc::new(RecordingStore::default());
+        let (sender, receiver) = mpsc::channel(4);
+        let worker = SessionWriteBehind {
+            durable: durable.clone(),
+            writes: receiver,
+        };
+        let user_id = Uuid::new_v4();
+        sender
+            .send(SessionWrite::Delete("a".to_string()))
+            .await
+            .unwrap();
+        sender
+            .send(SessionWrite::DeleteUser(user_id))
+            .await
+            .unwrap();
+        drop(sender);
+
+        worker.run().await;
+        assert_eq!(
+            *durable.0.lock().unwrap(),
+            vec![
+                "delete a".to_string(),
+                format!("delete_by_user {}", user_id)
+            ]
+        );
+    }
+
+    /// Lookups survive a Redis outage by reading the database copy
+    #[tokio::test]
+    async fn test_lookup_falls_back_to_database() {
+        let (store, _worker) = RedisSessionStore::new(
+            "redis://127.0.0.1:1",
+            Duration::from_secs(60),
+            Arc::new(RecordingStore::default()),
+            4,
+        )
+        .unwrap();
+        assert!(store.get("token").await.unwrap().is_none());
+        assert!(matches!(
+            store.delete("token").await,
+            Err(AuthError::ExternalServiceError { .. })
+        ));
+    }
+}
@@ -0,0 +1,46 @@
+//! Signup velocity counted in the shared cache
+//!
+//! Every instance counts against the same keys, so the limits hold across
+//! the fleet. The cache has no atomic increment; concurrent signups from
+//! one source may each read the same count, which lets a burst run a few
+//! signups past a limit before it applies.
+
+use async_trait::async_trait;
+use auth_cache::Cache;
+use auth_core::error::AuthError;
+use auth_core::services::signup_abuse::SignupCounter;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub struct CacheSignupCounter {
+    cache: Arc<dyn Cache>,
+}
+
+impl CacheSignupCounter {
+    pub fn new(cache: Arc<dyn Cache>) -> Self {
+        Self { cache }
+    }
+}
+
+#[async_trait]
+impl SignupCounter for CacheSignupCounter {
+    async fn increment(&self, key: &str, window: Duration) -> Result<u32, AuthError> {
+        let cache_err = |e: anyhow::Error| AuthError::ExternalServiceError {
+            service: "cache".to_string(),
+            error: e.to_string(),
+        };
+        let count = self
+            .cache
+            .get(key)
+            .await
+            .map_err(cache_err)?
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(0)
+            + 1;
+        self.cache
+            .set(key, &count.to_string(), window)
+            .await
+            .map_err(cache_err)?;
+        Ok(count)
+    }
+}
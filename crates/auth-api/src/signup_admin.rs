@@ -0,0 +1,94 @@
+//! Internal admin API for the signup review queue
+//!
+//! Served on the admin listener. Lists accounts shadow-created by signup
+//! velocity rules and lets operators release or reject them.
+
+use auth_core::services::signup_abuse::{QuarantinedSignup, SignupAbuseService};
+use auth_core::services::sms_risk::QuarantineStatus;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+type AdminError = (StatusCode, Json<serde_json::Value>);
+
+pub fn router(abuse: Arc<SignupAbuseService>) -> Router {
+    Router::new()
+        .route("/admin/signups/quarantine", get(list_quarantine))
+        .route("/admin/signups/quarantine/:id/approve", post(approve))
+        .route("/admin/signups/quarantine/:id/reject", post(reject))
+        .with_state(abuse)
+}
+
+fn error(status: StatusCode, message: impl ToString) -> AdminError {
+    (
+        status,
+        Json(serde_json::json!({ "error": message.to_string() })),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct QuarantineQuery {
+    status: Option<QuarantineStatus>,
+    tenant_id: Option<Uuid>,
+}
+
+/// GET /admin/signups/quarantine?status=pending&tenant_id=
+async fn list_quarantine(
+    State(abuse): State<Arc<SignupAbuseService>>,
+    Query(query): Query<QuarantineQuery>,
+) -> Result<Json<Vec<QuarantinedSignup>>, AdminError> {
+    abuse
+        .quarantined(query.status, query.tenant_id)
+        .await
+        .map(Json)
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ReviewRequest {
+    reviewed_by: Option<String>,
+}
+
+/// POST /admin/signups/quarantine/:id/approve
+///
+/// The account goes on to verification as a normal signup would; the
+/// user asks for a new code, since none was sent at signup
+async fn approve(
+    State(abuse): State<Arc<SignupAbuseService>>,
+    Path(id): Path<Uuid>,
+    body: Option<Json<ReviewRequest>>,
+) -> Result<Json<QuarantinedSignup>, AdminError> {
+    review(&abuse, id, true, body).await
+}
+
+/// POST /admin/signups/quarantine/:id/reject
+///
+/// The account stays suspended
+async fn reject(
+    State(abuse): State<Arc<SignupAbuseService>>,
+    Path(id): Path<Uuid>,
+    body: Option<Json<ReviewRequest>>,
+) -> Result<Json<QuarantinedSignup>, AdminError> {
+    review(&abuse, id, false, body).await
+}
+
+async fn review(
+    abuse: &SignupAbuseService,
+    id: Uuid,
+    approve: bool,
+    body: Option<Json<ReviewRequest>>,
+) -> Result<Json<QuarantinedSignup>, AdminError> {
+    let request = body.map(|Json(request)| request).unwrap_or_default();
+    abuse
+        .review(id, approve, request.reviewed_by.as_deref())
+        .await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .map(Json)
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, "quarantine entry not found"))
+}
@@ -0,0 +1,121 @@
+//! Internal admin API for SMS cost reporting and toll fraud review
+//!
+//! Served on the admin listener next to the port lease routes. Reports each
+//! tenant's SMS usage against its budget and the volume spikes this
+//! instance has seen, and lets operators approve or reject numbers held
+//! in quarantine by toll fraud screening.
+
+use auth_core::services::sms_budget::{SmsBudgetService, SmsUsageReport};
+use auth_core::services::sms_risk::{QuarantineStatus, QuarantinedDestination, SmsRiskService};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+type AdminError = (StatusCode, Json<serde_json::Value>);
+
+pub fn router(budget: Arc<SmsBudgetService>, risk: Arc<SmsRiskService>) -> Router {
+    let quarantine = Router::new()
+        .route("/admin/sms/quarantine", get(list_quarantine))
+        .route("/admin/sms/quarantine/:id/approve", post(approve))
+        .route("/admin/sms/quarantine/:id/reject", post(reject))
+        .with_state(risk);
+    Router::new()
+        .route("/admin/sms/usage", get(usage))
+        .with_state(budget)
+        .merge(quarantine)
+}
+
+fn error(status: StatusCode, message: impl ToString) -> AdminError {
+    (
+        status,
+        Json(serde_json::json!({ "error": message.to_string() })),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageQuery {
+    tenant_id: Option<Uuid>,
+    #[serde(default = "default_days")]
+    days: u32,
+}
+
+fn default_days() -> u32 {
+    30
+}
+
+/// GET /admin/sms/usage?tenant_id=&days=30
+///
+/// Without `tenant_id`, every tenant that sent SMS in the period
+async fn usage(
+    State(budget): State<Arc<SmsBudgetService>>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<SmsUsageReport>, AdminError> {
+    budget
+        .report(query.tenant_id, query.days.min(366))
+        .await
+        .map(Json)
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+#[derive(Debug, Deserialize)]
+struct QuarantineQuery {
+    status: Option<QuarantineStatus>,
+    tenant_id: Option<Uuid>,
+}
+
+/// GET /admin/sms/quarantine?status=pending&tenant_id=
+async fn list_quarantine(
+    State(risk): State<Arc<SmsRiskService>>,
+    Query(query): Query<QuarantineQuery>,
+) -> Result<Json<Vec<QuarantinedDestination>>, AdminError> {
+    risk.quarantined(query.status, query.tenant_id)
+        .await
+        .map(Json)
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ReviewRequest {
+    reviewed_by: Option<String>,
+}
+
+/// POST /admin/sms/quarantine/:id/approve
+///
+/// The number is sent to from then on; the code that put it in quarantine
+/// is not resent
+async fn approve(
+    State(risk): State<Arc<SmsRiskService>>,
+    Path(id): Path<Uuid>,
+    body: Option<Json<ReviewRequest>>,
+) -> Result<Json<QuarantinedDestination>, AdminError> {
+    review(&risk, id, true, body).await
+}
+
+/// POST /admin/sms/quarantine/:id/reject
+async fn reject(
+    State(risk): State<Arc<SmsRiskService>>,
+    Path(id): Path<Uuid>,
+    body: Option<Json<ReviewRequest>>,
+) -> Result<Json<QuarantinedDestination>, AdminError> {
+    review(&risk, id, false, body).await
+}
+
+async fn review(
+    risk: &SmsRiskService,
+    id: Uuid,
+    approve: bool,
+    body: Option<Json<ReviewRequest>>,
+) -> Result<Json<QuarantinedDestination>, AdminError> {
+    let request = body.map(|Json(request)| request).unwrap_or_default();
+    risk.review(id, approve, request.reviewed_by.as_deref())
+        .await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .map(Json)
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, "quarantine entry not found"))
+}
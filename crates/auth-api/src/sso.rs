@@ -0,0 +1,77 @@
+//! Central SSO session cookie
+//!
+//! Issued by the IdP host after sign-in and read back by `/auth/authorize`,
+//! so products on other domains share the session through redirects. The
+//! value is the opaque `sessions.session_token`.
+
+use auth_config::SsoConfig;
+use axum::http::HeaderMap;
+
+pub struct SsoCookie {
+    config: SsoConfig,
+}
+
+impl SsoCookie {
+    pub fn new(config: SsoConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn read(&self, headers: &HeaderMap) -> Option<String> {
+        headers
+            .get_all(axum::http::header::COOKIE)
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|h| h.split(';'))
+            .find_map(|cookie| {
+                let (name, value) = cookie.trim().split_once('=')?;
+                (name == self.config.cookie_name).then(|| value.to_string())
+            })
+    }
+
+    pub fn session_cookie(&self, value: &str, max_age_secs: i64) -> String {
+        format!(
+            "{}={}; Path=/; HttpOnly; Secure; SameSite={}; Max-Age={}{}",
+            self.config.cookie_name,
+            value,
+            self.config.same_site,
+            max_age_secs,
+            self.domain_attribute()
+        )
+    }
+
+    pub fn clear_cookie(&self) -> String {
+        format!(
+            "{}=; Path=/; HttpOnly; Secure; SameSite={}; Max-Age=0{}",
+            self.config.cookie_name,
+            self.config.same_site,
+            self.domain_attribute()
+        )
+    }
+
+    fn domain_attribute(&self) -> String {
+        self.config
+            .cookie_domain
+            .as_deref()
+            .map(|d| format!("; Domain={}", d))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cookie_is_host_only_by_default() {
+        let cookie = SsoCookie::new(SsoConfig::default());
+        let set = cookie.session_cookie("abc", 3600);
+        assert!(set.starts_with("idp_sso=abc;"));
+        assert!(set.contains("HttpOnly"));
+        assert!(set.contains("SameSite=Lax"));
+        assert!(!set.contains("Domain="));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("cookie", "other=1; idp_sso=abc".parse().unwrap());
+        assert_eq!(cookie.read(&headers).as_deref(), Some("abc"));
+    }
+}
@@ -0,0 +1,80 @@
+//! Per-request repository scope
+//!
+//! Handlers that read tenant-owned rows take a [`TenantScope`] instead of
+//! the pool: the tenant comes from the validated bearer token, and the
+//! repositories it hands out have no way to name another tenant. They run
+//! on the database of the tenant's data region.
+
+use crate::error::ApiError;
+use crate::AppState;
+use async_trait::async_trait;
+use auth_core::error::{AuthError, TokenErrorKind};
+use auth_core::models::token::Claims;
+use auth_db::scoped::ScopedRepositories;
+use auth_db::tenant_guard::TenantContext;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use std::ops::Deref;
+use uuid::Uuid;
+
+/// Repositories scoped to the tenant of the caller's access token
+pub struct TenantScope {
+    repositories: ScopedRepositories,
+    claims: Claims,
+}
+
+impl TenantScope {
+    pub fn claims(&self) -> &Claims {
+        &self.claims
+    }
+
+    /// The user the token was issued to
+    pub fn user_id(&self) -> Result<Uuid, ApiError> {
+        parse_claim(&self.claims.sub)
+    }
+}
+
+impl Deref for TenantScope {
+    type Target = ScopedRepositories;
+
+    fn deref(&self) -> &ScopedRepositories {
+        &self.repositories
+    }
+}
+
+fn parse_claim(value: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(value).map_err(|_| {
+        ApiError::new(AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        })
+    })
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for TenantScope {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        let token = parts
+            .headers
+            .get("authorization")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .ok_or(ApiError::new(AuthError::Unauthorized {
+                message: "Missing token".to_string(),
+            }))?;
+
+        let claims = state
+            .identity_service
+            .validate_token(token)
+            .await
+            .map_err(ApiError::from)?;
+        let tenant_id = parse_claim(&claims.tenant_id)?;
+        let pool = state.regions.pool_for(tenant_id)?;
+
+        Ok(Self {
+            repositories: ScopedRepositories::new(pool, TenantContext::new(tenant_id)),
+            claims,
+        })
+    }
+}
@@ -0,0 +1,142 @@
+//! Startup cache warm-up
+//!
+//! Loads what the first requests would otherwise fetch one at a time: the
+//! JWKS, and per tenant its role list and subscription plan. The instance
+//! reports ready once this finishes or runs out of time, so a load balancer
+//! polling `/ready` holds traffic back until then. OAuth clients and their
+//! token lifetimes are static configuration and need no loading.
+
+use crate::AppState;
+use auth_cache::Loader;
+use auth_config::WarmupConfig;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+/// Shared cache key of the serialized JWKS
+pub const JWKS_CACHE_KEY: &str = "jwks";
+
+/// Signing keys are not rotated at runtime, so this only bounds how long a
+/// restarted node's keys take to show up for the others
+const JWKS_TTL: Duration = Duration::from_secs(300);
+
+/// Whether the instance should be sent traffic
+#[derive(Debug, Default)]
+pub struct Readiness {
+    ready: AtomicBool,
+    /// Set on the shutdown signal; overrides `ready` for good
+    draining: AtomicBool,
+}
+
+impl Readiness {
+    /// Ready from the start, for instances that skip warm-up
+    pub fn ready() -> Self {
+        Self {
+            ready: AtomicBool::new(true),
+            draining: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire) && !self.is_draining()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    pub fn mark_ready(&self) {
+        self.ready.store(true, Ordering::Release);
+    }
+
+    /// Report not ready from now on, so load balancers stop sending
+    /// requests before the listener closes
+    pub fn mark_draining(&self) {
+        self.draining.store(true, Ordering::Release);
+    }
+}
+
+/// The JWKS from the shared cache, computed and cached on a miss. A burst
+/// of requests after the entry expires computes it once.
+pub async fn cached_jwks(state: &AppState) -> serde_json::Value {
+    let computed: Loader<'_> =
+        Box::pin(async { Ok(state.identity_service.get_jwks().await.to_string()) });
+    if let Ok(cached) = state
+        .cache
+        .get_or_set(JWKS_CACHE_KEY, JWKS_TTL, computed)
+        .await
+    {
+        if let Ok(jwks) = serde_json::from_str(&cached) {
+            return jwks;
+        }
+    }
+    state.identity_service.get_jwks().await
+}
+
+/// Warm the caches and then mark the instance ready, whether or not every
+/// lookup succeeded within `config.timeout_seconds`
+pub async fn warm_up(state: AppState, config: WarmupConfig) {
+    let started = Instant::now();
+    let tenants: BTreeSet<Uuid> = config
+        .tenants
+        .iter()
+        .copied()
+        .chain(state.quotas.host_tenants())
+        .collect();
+    let timeout = Duration::from_secs(config.timeout_seconds);
+
+    match tokio::time::timeout(timeout, prefetch(&state, tenants, config.concurrency)).await {
+        Ok(0) => tracing::info!("Cache warm-up finished in {:?}", started.elapsed()),
+        Ok(failed) => tracing::warn!(
+            "Cache warm-up finished in {:?}; {} tenants could not be loaded",
+            started.elapsed(),
+            failed
+        ),
+        Err(_) => tracing::warn!(
+            "Cache warm-up did not finish within {}s; serving with partly cold caches",
+            config.timeout_seconds
+        ),
+    }
+    state.readiness.mark_ready();
+}
+
+/// Returns how many tenants failed to load
+async fn prefetch(state: &AppState, tenants: BTreeSet<Uuid>, concurrency: usize) -> usize {
+    cached_jwks(state).await;
+
+    let mut failed = 0;
+    let mut pending = tenants.into_iter();
+    let mut running = JoinSet::new();
+    loop {
+        while running.len() < concurrency.max(1) {
+            let Some(tenant_id) = pending.next() else {
+                break;
+            };
+            running.spawn(prefetch_tenant(state.clone(), tenant_id));
+        }
+        match running.join_next().await {
+            Some(Ok(true)) => {}
+            Some(_) => failed += 1,
+            None => return failed,
+        }
+    }
+}
+
+async fn prefetch_tenant(state: AppState, tenant_id: Uuid) -> bool {
+    if state.quotas.enabled() {
+        state.quotas.prefetch_plan(tenant_id).await;
+    }
+    match state.role_service.list_roles(tenant_id).await {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::warn!(
+                "Warm-up could not load roles for tenant {}: {}",
+                tenant_id,
+                e
+            );
+            false
+        }
+    }
+}
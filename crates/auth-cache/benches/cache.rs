@@ -0,0 +1,61 @@
+//! L1 cache benchmarks
+//!
+//! Runs without Redis, so only the in-process tier is measured. Run with
+//! `cargo bench -p auth-cache`.
+
+use auth_cache::{Cache, MultiLevelCache};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+const KEYS: usize = 10_000;
+
+fn bench_cache(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let cache = MultiLevelCache::new(None).unwrap();
+    let value = serde_json::json!({
+        "user_id": "3f2a1c9e-0000-4000-8000-000000000000",
+        "roles": ["admin", "auditor"],
+        "permissions": ["users:read", "users:write", "audit:read"],
+    })
+    .to_string();
+    rt.block_on(async {
+        for i in 0..KEYS {
+            cache
+                .set(&format!("user:{}", i), &value, Duration::from_secs(300))
+                .await
+                .unwrap();
+        }
+    });
+
+    let mut group = c.benchmark_group("cache");
+    let mut i = 0usize;
+    group.bench_function("get_hit", |b| {
+        b.to_async(&rt).iter(|| {
+            i = (i + 1) % KEYS;
+            let key = format!("user:{}", i);
+            let cache = &cache;
+            async move { cache.get(&key).await.unwrap() }
+        })
+    });
+    group.bench_function("get_miss", |b| {
+        b.to_async(&rt).iter(|| cache.get("user:missing"))
+    });
+    group.bench_function("set", |b| {
+        b.to_async(&rt).iter(|| {
+            i = (i + 1) % KEYS;
+            let key = format!("user:{}", i);
+            let (cache, value) = (&cache, &value);
+            async move {
+                cache
+                    .set(&key, value, Duration::from_secs(300))
+                    .await
+                    .unwrap()
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_cache);
+criterion_main!(benches);
@@ -0,0 +1,344 @@
+//! Degradation state of the Redis tier
+//!
+//! Without an explicit state every feature finds out about a Redis outage on
+//! its own, one failed call at a time, and the ones that swallow errors keep
+//! half-working on whatever the in-process tier still holds. The cache is
+//! instead in one of three states:
+//!
+//! - `Healthy`: reads and writes go through to Redis.
+//! - `Degraded`: entered after `failure_threshold` consecutive failures.
+//!   Redis is not called at all; reads and writes use the in-process tier
+//!   and written keys are remembered. Each feature is told whether to keep
+//!   going on that (`fallback`) or to refuse service (`disable`), since
+//!   state in one replica's memory is invisible to the others.
+//! - `Recovering`: a probe reached Redis again and the remembered keys are
+//!   being written back. Features behave as when degraded until it is done.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheState {
+    #[default]
+    Healthy,
+    Degraded,
+    Recovering,
+}
+
+/// How a cache-backed feature behaves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeatureMode {
+    /// Redis is healthy
+    Normal,
+    /// Keep serving on the in-process tier (or the feature's own fallback)
+    Fallback,
+    /// Refuse requests until Redis has recovered
+    Disable,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DegradationConfig {
+    /// Consecutive Redis failures that open the circuit
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+
+    /// Bound on each Redis call, so a hung server trips the circuit too
+    #[serde(default = "default_operation_timeout_ms")]
+    pub operation_timeout_ms: u64,
+
+    /// Pause between reconnection probes while degraded
+    #[serde(default = "default_probe_interval_ms")]
+    pub probe_interval_ms: u64,
+
+    /// Keys written while degraded that are remembered for the re-sync;
+    /// writes beyond this stay local to the replica
+    #[serde(default = "default_max_pending_keys")]
+    pub max_pending_keys: usize,
+
+    /// Mode of each feature while degraded, by feature name
+    #[serde(default = "default_features")]
+    pub features: HashMap<String, FeatureMode>,
+
+    /// Mode of features not listed
+    #[serde(default = "default_feature_mode")]
+    pub default_mode: FeatureMode,
+}
+
+fn default_failure_threshold() -> u32 {
+    3
+}
+
+fn default_operation_timeout_ms() -> u64 {
+    500
+}
+
+fn default_probe_interval_ms() -> u64 {
+    2000
+}
+
+fn default_max_pending_keys() -> usize {
+    10_000
+}
+
+/// Multi-step flows and BFF sessions are resumed on whichever replica the
+/// next request lands on, so they cannot run on one replica's memory
+fn default_features() -> HashMap<String, FeatureMode> {
+    HashMap::from([
+        ("auth_flow".to_string(), FeatureMode::Disable),
+        ("bff_session".to_string(), FeatureMode::Disable),
+        ("captcha".to_string(), FeatureMode::Fallback),
+    ])
+}
+
+fn default_feature_mode() -> FeatureMode {
+    FeatureMode::Fallback
+}
+
+impl Default for DegradationConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: default_failure_threshold(),
+            operation_timeout_ms: default_operation_timeout_ms(),
+            probe_interval_ms: default_probe_interval_ms(),
+            max_pending_keys: default_max_pending_keys(),
+            features: default_features(),
+            default_mode: default_feature_mode(),
+        }
+    }
+}
+
+/// Snapshot for `/health`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheStatus {
+    pub state: CacheState,
+    /// Seconds spent in the current state; absent while healthy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since_seconds: Option<u64>,
+    pub consecutive_failures: u32,
+    /// Keys waiting to be written back to Redis
+    pub pending_resync: usize,
+}
+
+struct Inner {
+    state: CacheState,
+    since: Instant,
+    consecutive_failures: u32,
+    /// Keys written or deleted while not healthy, in order
+    pending: Vec<String>,
+}
+
+pub struct Degradation {
+    config: DegradationConfig,
+    inner: Mutex<Inner>,
+}
+
+impl Degradation {
+    pub fn new(config: DegradationConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(Inner {
+                state: CacheState::Healthy,
+                since: Instant::now(),
+                consecutive_failures: 0,
+                pending: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn state(&self) -> CacheState {
+        self.lock().state
+    }
+
+    /// Whether Redis should be called; false while the circuit is open
+    pub fn allow_remote(&self) -> bool {
+        self.state() == CacheState::Healthy
+    }
+
+    pub fn operation_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.operation_timeout_ms)
+    }
+
+    pub fn probe_interval(&self) -> Duration {
+        Duration::from_millis(self.config.probe_interval_ms.max(1))
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.lock();
+        if inner.state == CacheState::Healthy {
+            inner.consecutive_failures = 0;
+        }
+    }
+
+    pub fn record_failure(&self, error: &anyhow::Error) {
+        let mut inner = self.lock();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        let trips = match inner.state {
+            CacheState::Healthy => inner.consecutive_failures >= self.config.failure_threshold,
+            CacheState::Recovering => true,
+            CacheState::Degraded => false,
+        };
+        if trips {
+            warn!(
+                event = "cache.degraded",
+                failures = inner.consecutive_failures,
+                error = %error,
+                "Redis unavailable, cache degraded to in-process tier"
+            );
+            inner.state = CacheState::Degraded;
+            inner.since = Instant::now();
+        }
+    }
+
+    /// Remember a key written or deleted while Redis is not in use
+    pub fn mark_pending(&self, key: &str) {
+        let mut inner = self.lock();
+        if inner.pending.iter().any(|k| k == key) {
+            return;
+        }
+        if inner.pending.len() >= self.config.max_pending_keys {
+            warn!(key = %key, "Too many keys pending re-sync; write stays local");
+            return;
+        }
+        inner.pending.push(key.to_string());
+    }
+
+    /// A probe reached Redis: hand over the keys to write back
+    pub fn begin_recovery(&self) -> Vec<String> {
+        let mut inner = self.lock();
+        inner.state = CacheState::Recovering;
+        inner.since = Instant::now();
+        std::mem::take(&mut inner.pending)
+    }
+
+    /// The re-sync stopped short; `unsynced` are pending again
+    pub fn recovery_failed(&self, unsynced: Vec<String>, error: &anyhow::Error) {
+        {
+            let mut inner = self.lock();
+            let mut pending = unsynced;
+            for key in std::mem::take(&mut inner.pending) {
+                if !pending.contains(&key) {
+                    pending.push(key);
+                }
+            }
+            inner.pending = pending;
+        }
+        self.record_failure(error);
+    }
+
+    pub fn recovered(&self, synced: usize) {
+        let mut inner = self.lock();
+        if inner.state != CacheState::Recovering {
+            return;
+        }
+        info!(
+            event = "cache.recovered",
+            synced_keys = synced,
+            degraded_for_seconds = inner.since.elapsed().as_secs(),
+            "Redis reachable again, cache re-synced"
+        );
+        inner.state = CacheState::Healthy;
+        inner.since = Instant::now();
+        inner.consecutive_failures = 0;
+    }
+
+    /// How `feature` should behave in the current state
+    pub fn feature_mode(&self, feature: &str) -> FeatureMode {
+        if self.state() == CacheState::Healthy {
+            return FeatureMode::Normal;
+        }
+        self.config
+            .features
+            .get(feature)
+            .copied()
+            .unwrap_or(self.config.default_mode)
+    }
+
+    pub fn status(&self) -> CacheStatus {
+        let inner = self.lock();
+        CacheStatus {
+            state: inner.state,
+            since_seconds: (inner.state != CacheState::Healthy)
+                .then(|| inner.since.elapsed().as_secs()),
+            consecutive_failures: inner.consecutive_failures,
+            pending_resync: inner.pending.len(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn degradation() -> Degradation {
+        Degradation::new(DegradationConfig {
+            failure_threshold: 2,
+            ..DegradationConfig::default()
+        })
+    }
+
+    fn error() -> anyhow::Error {
+        anyhow::anyhow!("connection refused")
+    }
+
+    #[test]
+    fn test_consecutive_failures_open_circuit() {
+        let d = degradation();
+        d.record_failure(&error());
+        d.record_success();
+        d.record_failure(&error());
+        assert_eq!(d.state(), CacheState::Healthy);
+        assert_eq!(d.feature_mode("auth_flow"), FeatureMode::Normal);
+
+        d.record_failure(&error());
+        assert_eq!(d.state(), CacheState::Degraded);
+        assert!(!d.allow_remote());
+        assert_eq!(d.feature_mode("auth_flow"), FeatureMode::Disable);
+        assert_eq!(d.feature_mode("captcha"), FeatureMode::Fallback);
+        assert_eq!(d.feature_mode("unlisted"), FeatureMode::Fallback);
+    }
+
+    #[test]
+    fn test_recovery_hands_over_pending_keys_once() {
+        let d = degradation();
+        d.record_failure(&error());
+        d.record_failure(&error());
+        d.mark_pending("a");
+        d.mark_pending("b");
+        d.mark_pending("a");
+        assert_eq!(d.status().pending_resync, 2);
+
+        assert_eq!(d.begin_recovery(), vec!["a", "b"]);
+        assert_eq!(d.state(), CacheState::Recovering);
+        assert_eq!(d.feature_mode("auth_flow"), FeatureMode::Disable);
+
+        d.recovered(2);
+        assert_eq!(d.state(), CacheState::Healthy);
+        assert_eq!(d.status().pending_resync, 0);
+        assert!(d.status().since_seconds.is_none());
+    }
+
+    #[test]
+    fn test_failed_recovery_degrades_again_and_keeps_keys() {
+        let d = degradation();
+        d.record_failure(&error());
+        d.record_failure(&error());
+        d.mark_pending("a");
+        let keys = d.begin_recovery();
+        d.mark_pending("b");
+
+        d.recovery_failed(keys, &error());
+        assert_eq!(d.state(), CacheState::Degraded);
+        assert_eq!(d.status().pending_resync, 2);
+    }
+}
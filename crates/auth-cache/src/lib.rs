@@ -1,19 +1,111 @@
+pub mod degradation;
+pub mod local;
+pub mod pubsub;
+pub mod revocation;
+pub mod session;
+pub mod single_use;
+
+pub use degradation::{CacheState, CacheStatus, Degradation, DegradationConfig, FeatureMode};
+pub use local::{LocalCache, LocalCacheConfig, LocalCacheStats};
+pub use pubsub::RedisPubSub;
+pub use revocation::RedisRevocationCache;
+pub use session::RedisSessionCache;
+pub use single_use::{RedeemOutcome, RedisSingleUse};
+
 use async_trait::async_trait;
-use dashmap::DashMap;
+use futures::future::BoxFuture;
 use redis::{AsyncCommands, Client};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tracing::debug;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Key the reconnection probe checks for
+const PROBE_KEY: &str = "cache:probe";
+
+/// Channel on which writers announce changed keys, as `<node id> <key>`,
+/// so other nodes drop their L1 copy
+const INVALIDATION_CHANNEL: &str = "cache:invalidate";
+
+/// Redis rejects `SET EX 0`; shorter lifetimes are rounded up to a second
+fn expiry_seconds(ttl: Duration) -> u64 {
+    ttl.as_secs().max(1)
+}
+
+/// Computes a value missing from the cache; only awaited on a miss
+pub type Loader<'a> = BoxFuture<'a, anyhow::Result<String>>;
 
 #[async_trait]
 pub trait Cache: Send + Sync {
     async fn get(&self, key: &str) -> anyhow::Result<Option<String>>;
     async fn set(&self, key: &str, value: &str, ttl: Duration) -> anyhow::Result<()>;
     async fn delete(&self, key: &str) -> anyhow::Result<()>;
+
+    /// Values of `keys`, in the same order
+    async fn get_many(&self, keys: &[&str]) -> anyhow::Result<Vec<Option<String>>> {
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            values.push(self.get(key).await?);
+        }
+        Ok(values)
+    }
+
+    async fn set_many(&self, entries: &[(&str, &str)], ttl: Duration) -> anyhow::Result<()> {
+        for (key, value) in entries {
+            self.set(key, value, ttl).await?;
+        }
+        Ok(())
+    }
+
+    /// The cached value of `key`, or else the loader's, cached for `ttl`.
+    /// Only the loader's error is returned: a cache that cannot be read or
+    /// written costs a load, not the request.
+    async fn get_or_set(
+        &self,
+        key: &str,
+        ttl: Duration,
+        loader: Loader<'_>,
+    ) -> anyhow::Result<String> {
+        if let Some(value) = cached(self.get(key).await, key) {
+            return Ok(value);
+        }
+        let value = loader.await?;
+        if let Err(e) = self.set(key, &value, ttl).await {
+            warn!("Failed to cache '{}': {}", key, e);
+        }
+        Ok(value)
+    }
+
+    /// Degradation state; always healthy for caches without a remote tier
+    fn status(&self) -> CacheStatus {
+        CacheStatus::default()
+    }
+
+    /// How `feature` should behave given the cache's state
+    fn feature_mode(&self, _feature: &str) -> FeatureMode {
+        FeatureMode::Normal
+    }
+}
+
+/// A read for `get_or_set`, with failures treated as misses
+fn cached(read: anyhow::Result<Option<String>>, key: &str) -> Option<String> {
+    read.unwrap_or_else(|e| {
+        warn!("Failed to read '{}' from cache: {}", key, e);
+        None
+    })
 }
 
 pub struct MultiLevelCache {
-    l1: DashMap<String, (String, std::time::Instant)>, // Value (JSON), Expiry
+    l1: LocalCache,
     l2: Option<Client>,
+    degradation: Degradation,
+    /// Tells this node's invalidations apart from other nodes'
+    node_id: Uuid,
+    /// Loads in flight in `get_or_set`, so that concurrent misses for one
+    /// key wait on a single load
+    loading: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
 }
 
 impl MultiLevelCache {
@@ -25,79 +117,585 @@ impl MultiLevelCache {
         };
 
         Ok(Self {
-            l1: DashMap::new(),
+            l1: LocalCache::new(&LocalCacheConfig::default()),
             l2: client,
+            degradation: Degradation::new(DegradationConfig::default()),
+            node_id: Uuid::new_v4(),
+            loading: Mutex::new(HashMap::new()),
         })
     }
 
+    pub fn with_degradation(mut self, config: DegradationConfig) -> Self {
+        self.degradation = Degradation::new(config);
+        self
+    }
+
+    /// Bounds of the in-process tier
+    pub fn with_local(mut self, config: &LocalCacheConfig) -> Self {
+        self.l1 = LocalCache::new(config);
+        self
+    }
+
+    pub fn local_stats(&self) -> LocalCacheStats {
+        self.l1.stats()
+    }
+
+    /// Drop expired L1 entries and publish the L1 gauges, periodically
+    pub async fn run_sweeper(self: Arc<Self>) {
+        let mut ticker = tokio::time::interval(self.l1.sweep_interval());
+        loop {
+            ticker.tick().await;
+            let swept = self.l1.sweep();
+            if swept > 0 {
+                debug!("Swept {} expired L1 entries", swept);
+            }
+            self.l1.record_gauges();
+        }
+    }
+
     // Used for L1 invalidation simulation in tests
     pub fn invalidate_l1(&self, key: &str) {
         self.l1.remove(key);
     }
+
+    /// Drop L1 entries that other nodes changed in Redis. Returns at once
+    /// when there is no Redis tier.
+    ///
+    /// Messages published while the subscription is down are lost. During
+    /// a Redis outage that is covered by the re-sync, which clears L1 on
+    /// recovery.
+    pub async fn run_invalidation(self: Arc<Self>) {
+        let Some(client) = self.l2.clone() else {
+            return;
+        };
+        let (mut rx, subscription) =
+            RedisPubSub::from_client(client).subscribe(INVALIDATION_CHANNEL);
+        while let Some(payload) = rx.recv().await {
+            self.apply_invalidation(&payload);
+        }
+        subscription.abort();
+    }
+
+    fn apply_invalidation(&self, payload: &str) {
+        let Some((node, key)) = payload.split_once(' ') else {
+            warn!("Malformed cache invalidation '{}'", payload);
+            return;
+        };
+        if node != self.node_id.to_string() {
+            debug!("L1 invalidated by {}: {}", node, key);
+            self.l1.remove(key);
+        }
+    }
+
+    fn invalidation(&self, key: &str) -> String {
+        format!("{} {}", self.node_id, key)
+    }
+
+    /// Probe Redis while degraded and re-sync once it answers again.
+    /// Returns at once when there is no Redis tier.
+    pub async fn run_probe(self: Arc<Self>) {
+        let Some(client) = self.l2.clone() else {
+            return;
+        };
+        let mut ticker = tokio::time::interval(self.degradation.probe_interval());
+        loop {
+            ticker.tick().await;
+            if self.degradation.state() != CacheState::Degraded {
+                continue;
+            }
+            let probe = self
+                .bounded(async {
+                    let mut conn = client.get_multiplexed_async_connection().await?;
+                    conn.exists::<_, bool>(PROBE_KEY).await
+                })
+                .await;
+            if probe.is_err() {
+                continue;
+            }
+
+            let keys = self.degradation.begin_recovery();
+            match self.resync(&client, &keys).await {
+                Ok(()) => self.degradation.recovered(keys.len()),
+                Err((done, e)) => self.degradation.recovery_failed(keys[done..].to_vec(), &e),
+            }
+        }
+    }
+
+    /// Write back what changed locally while degraded: keys still held are
+    /// set with their remaining lifetime, the others deleted. Entries read
+    /// through before the outage may be stale by now and are dropped. On
+    /// failure, returns how many keys were synced.
+    async fn resync(&self, client: &Client, keys: &[String]) -> Result<(), (usize, anyhow::Error)> {
+        let mut conn = self
+            .bounded(client.get_multiplexed_async_connection())
+            .await
+            .map_err(|e| (0, e))?;
+        for (done, key) in keys.iter().enumerate() {
+            let result = match self.l1.peek(key) {
+                Some((value, ttl)) => {
+                    self.bounded(conn.set_ex::<_, _, redis::Value>(key, value, expiry_seconds(ttl)))
+                        .await
+                }
+                None => self.bounded(conn.del::<_, redis::Value>(key)).await,
+            };
+            result.map_err(|e| (done, e))?;
+            self.bounded(conn.publish::<_, _, i64>(INVALIDATION_CHANNEL, self.invalidation(key)))
+                .await
+                .map_err(|e| (done, e))?;
+        }
+
+        self.l1.clear();
+        Ok(())
+    }
+
+    fn loading(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<tokio::sync::Mutex<()>>>> {
+        self.loading.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Runs a Redis call under the operation timeout
+    async fn bounded<T>(
+        &self,
+        op: impl Future<Output = redis::RedisResult<T>>,
+    ) -> anyhow::Result<T> {
+        match tokio::time::timeout(self.degradation.operation_timeout(), op).await {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(anyhow::anyhow!("Redis call timed out")),
+        }
+    }
+
+    /// Runs a Redis call unless the circuit is open (`None`), tracking the
+    /// outcome
+    async fn remote<T>(
+        &self,
+        op: impl Future<Output = redis::RedisResult<T>>,
+    ) -> Option<anyhow::Result<T>> {
+        if !self.degradation.allow_remote() {
+            return None;
+        }
+        let result = self.bounded(op).await;
+        match &result {
+            Ok(_) => self.degradation.record_success(),
+            Err(e) => self.degradation.record_failure(e),
+        }
+        Some(result)
+    }
+}
+
+/// Ends a load in `get_or_set`, also when its future is dropped mid-flight.
+/// A newer load of the key, started after this one was removed, is left be.
+struct LoadingGuard<'a> {
+    cache: &'a MultiLevelCache,
+    key: &'a str,
+    flight: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Drop for LoadingGuard<'_> {
+    fn drop(&mut self) {
+        let mut loading = self.cache.loading();
+        if loading
+            .get(self.key)
+            .is_some_and(|flight| Arc::ptr_eq(flight, &self.flight))
+        {
+            loading.remove(self.key);
+        }
+    }
 }
 
 #[async_trait]
 impl Cache for MultiLevelCache {
     async fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
         // L1 Check
-        if let Some(entry) = self.l1.get(key) {
-            if entry.1 > std::time::Instant::now() {
-                debug!("L1 Cache Hit: {}", key);
-                return Ok(Some(entry.0.clone()));
-            } else {
-                // Expired
-                drop(entry);
-                self.l1.remove(key);
-            }
+        if let Some(value) = self.l1.get(key) {
+            debug!("L1 Cache Hit: {}", key);
+            return Ok(Some(value));
         }
 
-        // L2 Check (Redis)
-        if let Some(client) = &self.l2 {
-            let mut conn = client.get_multiplexed_async_connection().await?;
+        // L2 Check (Redis), skipped while degraded
+        let Some(client) = &self.l2 else {
+            return Ok(None);
+        };
+        let fetched = self
+            .remote(async {
+                let mut conn = client.get_multiplexed_async_connection().await?;
+                conn.get::<_, Option<String>>(key).await
+            })
+            .await;
 
-            match conn.get::<_, Option<String>>(key).await? {
-                Some(val_str) => {
-                    debug!("L2 Cache Hit: {}", key);
-                    // Populate L1 (Default TTL 60s)
-                    self.l1.insert(
-                        key.to_string(),
-                        (
-                            val_str.clone(),
-                            std::time::Instant::now() + Duration::from_secs(60),
-                        ),
-                    );
+        match fetched {
+            Some(Ok(Some(val_str))) => {
+                debug!("L2 Cache Hit: {}", key);
+                // Populate L1 (Default TTL 60s)
+                self.l1.insert(key, &val_str, Duration::from_secs(60));
 
-                    Ok(Some(val_str))
-                }
-                None => Ok(None),
+                Ok(Some(val_str))
             }
-        } else {
-            Ok(None)
+            Some(Ok(None)) | None => Ok(None),
+            Some(Err(e)) => Err(e),
         }
     }
 
     async fn set(&self, key: &str, value: &str, ttl: Duration) -> anyhow::Result<()> {
         // Update L1
-        self.l1.insert(
-            key.to_string(),
-            (value.to_string(), std::time::Instant::now() + ttl),
-        );
+        self.l1.insert(key, value, ttl);
 
         // Update L2
-        if let Some(client) = &self.l2 {
-            let mut conn = client.get_multiplexed_async_connection().await?;
-            let _: redis::Value = conn.set_ex(key, value, ttl.as_secs()).await?;
+        let Some(client) = &self.l2 else {
+            return Ok(());
+        };
+        let stored = self
+            .remote(async {
+                let mut conn = client.get_multiplexed_async_connection().await?;
+                conn.set_ex::<_, _, redis::Value>(key, value, expiry_seconds(ttl))
+                    .await?;
+                conn.publish::<_, _, i64>(INVALIDATION_CHANNEL, self.invalidation(key))
+                    .await
+            })
+            .await;
+        match stored {
+            Some(result) => result.map(|_| ()),
+            None => {
+                self.degradation.mark_pending(key);
+                Ok(())
+            }
         }
-
-        Ok(())
     }
 
     async fn delete(&self, key: &str) -> anyhow::Result<()> {
         self.l1.remove(key);
-        if let Some(client) = &self.l2 {
-            let mut conn = client.get_multiplexed_async_connection().await?;
-            let _: redis::Value = conn.del(key).await?;
+        let Some(client) = &self.l2 else {
+            return Ok(());
+        };
+        let deleted = self
+            .remote(async {
+                let mut conn = client.get_multiplexed_async_connection().await?;
+                conn.del::<_, redis::Value>(key).await?;
+                conn.publish::<_, _, i64>(INVALIDATION_CHANNEL, self.invalidation(key))
+                    .await
+            })
+            .await;
+        match deleted {
+            Some(result) => result.map(|_| ()),
+            None => {
+                self.degradation.mark_pending(key);
+                Ok(())
+            }
         }
-        Ok(())
+    }
+
+    /// L1 first, then the misses from Redis in one round trip
+    async fn get_many(&self, keys: &[&str]) -> anyhow::Result<Vec<Option<String>>> {
+        let mut values: Vec<Option<String>> = keys.iter().map(|key| self.l1.get(key)).collect();
+        let missing: Vec<&str> = keys
+            .iter()
+            .zip(&values)
+            .filter(|(_, value)| value.is_none())
+            .map(|(key, _)| *key)
+            .collect();
+        let Some(client) = &self.l2 else {
+            return Ok(values);
+        };
+        if missing.is_empty() {
+            return Ok(values);
+        }
+
+        let fetched = self
+            .remote(async {
+                let mut conn = client.get_multiplexed_async_connection().await?;
+                redis::cmd("MGET")
+                    .arg(&missing)
+                    .query_async::<_, Vec<Option<String>>>(&mut conn)
+                    .await
+            })
+            .await;
+        let mut fetched = match fetched {
+            Some(Ok(fetched)) => fetched.into_iter(),
+            Some(Err(e)) => return Err(e),
+            None => return Ok(values),
+        };
+        for (key, value) in keys.iter().zip(values.iter_mut()) {
+            if value.is_some() {
+                continue;
+            }
+            *value = fetched.next().flatten();
+            if let Some(found) = value {
+                self.l1.insert(key, found, Duration::from_secs(60));
+            }
+        }
+        Ok(values)
+    }
+
+    /// One pipelined round trip for all entries and their invalidations
+    async fn set_many(&self, entries: &[(&str, &str)], ttl: Duration) -> anyhow::Result<()> {
+        for (key, value) in entries {
+            self.l1.insert(key, value, ttl);
+        }
+        let Some(client) = &self.l2 else {
+            return Ok(());
+        };
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut pipe = redis::pipe();
+        for (key, value) in entries {
+            pipe.set_ex(*key, *value, expiry_seconds(ttl))
+                .ignore()
+                .publish(INVALIDATION_CHANNEL, self.invalidation(key))
+                .ignore();
+        }
+        let stored = self
+            .remote(async {
+                let mut conn = client.get_multiplexed_async_connection().await?;
+                pipe.query_async::<_, ()>(&mut conn).await
+            })
+            .await;
+        match stored {
+            Some(result) => result,
+            None => {
+                for (key, _) in entries {
+                    self.degradation.mark_pending(key);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Concurrent misses for one key on this instance share a single load
+    async fn get_or_set(
+        &self,
+        key: &str,
+        ttl: Duration,
+        loader: Loader<'_>,
+    ) -> anyhow::Result<String> {
+        if let Some(value) = cached(self.get(key).await, key) {
+            return Ok(value);
+        }
+
+        let flight = self.loading().entry(key.to_string()).or_default().clone();
+        let _loading = flight.lock().await;
+        // Loaded by the call this one waited on
+        if let Some(value) = self.l1.get(key) {
+            return Ok(value);
+        }
+        // Dropped only once cached, so a call arriving then finds the value
+        let _guard = LoadingGuard {
+            cache: self,
+            key,
+            flight: flight.clone(),
+        };
+        let loaded = loader.await;
+        if let Ok(value) = &loaded {
+            if let Err(e) = self.set(key, value, ttl).await {
+                warn!("Failed to cache '{}': {}", key, e);
+            }
+        }
+        loaded
+    }
+
+    fn status(&self) -> CacheStatus {
+        self.degradation.status()
+    }
+
+    fn feature_mode(&self, feature: &str) -> FeatureMode {
+        self.degradation.feature_mode(feature)
+    }
+}
+
+/// A cache whose keys are all under `prefix`, so deployments of different
+/// data regions sharing a Redis never read each other's entries
+pub struct PrefixedCache {
+    inner: Arc<dyn Cache>,
+    prefix: String,
+}
+
+impl PrefixedCache {
+    pub fn new(inner: Arc<dyn Cache>, prefix: impl Into<String>) -> Self {
+        Self {
+            inner,
+            prefix: prefix.into(),
+        }
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+}
+
+#[async_trait]
+impl Cache for PrefixedCache {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        self.inner.get(&self.key(key)).await
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> anyhow::Result<()> {
+        self.inner.set(&self.key(key), value, ttl).await
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.inner.delete(&self.key(key)).await
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> anyhow::Result<Vec<Option<String>>> {
+        let keys: Vec<String> = keys.iter().map(|key| self.key(key)).collect();
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        self.inner.get_many(&keys).await
+    }
+
+    async fn set_many(&self, entries: &[(&str, &str)], ttl: Duration) -> anyhow::Result<()> {
+        let keys: Vec<String> = entries.iter().map(|(key, _)| self.key(key)).collect();
+        let entries: Vec<(&str, &str)> = keys
+            .iter()
+            .zip(entries)
+            .map(|(key, (_, value))| (key.as_str(), *value))
+            .collect();
+        self.inner.set_many(&entries, ttl).await
+    }
+
+    async fn get_or_set(
+        &self,
+        key: &str,
+        ttl: Duration,
+        loader: Loader<'_>,
+    ) -> anyhow::Result<String> {
+        self.inner.get_or_set(&self.key(key), ttl, loader).await
+    }
+
+    fn status(&self) -> CacheStatus {
+        self.inner.status()
+    }
+
+    fn feature_mode(&self, feature: &str) -> FeatureMode {
+        self.inner.feature_mode(feature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unreachable_redis_degrades_to_l1() {
+        // Nothing listens on port 1
+        let cache = MultiLevelCache::new(Some("redis://127.0.0.1:1".to_string()))
+            .unwrap()
+            .with_degradation(DegradationConfig {
+                failure_threshold: 1,
+                operation_timeout_ms: 200,
+                ..DegradationConfig::default()
+            });
+
+        assert!(cache.set("k", "v", Duration::from_secs(60)).await.is_err());
+        assert_eq!(cache.status().state, CacheState::Degraded);
+        assert_eq!(cache.feature_mode("auth_flow"), FeatureMode::Disable);
+
+        // Served locally from now on, with the write queued for re-sync
+        cache.set("k", "v2", Duration::from_secs(60)).await.unwrap();
+        cache.delete("gone").await.unwrap();
+        assert_eq!(cache.get("k").await.unwrap().as_deref(), Some("v2"));
+        assert_eq!(cache.get("missing").await.unwrap(), None);
+        assert_eq!(cache.status().pending_resync, 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalidations_from_other_nodes_drop_l1() {
+        let cache = MultiLevelCache::new(None).unwrap();
+        cache.set("k", "v", Duration::from_secs(60)).await.unwrap();
+
+        // Own writes are already applied
+        cache.apply_invalidation(&cache.invalidation("k"));
+        assert_eq!(cache.get("k").await.unwrap().as_deref(), Some("v"));
+
+        cache.apply_invalidation(&format!("{} k", Uuid::new_v4()));
+        assert_eq!(cache.get("k").await.unwrap(), None);
+        assert_eq!(expiry_seconds(Duration::from_millis(300)), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_or_set_loads_once_for_concurrent_misses() {
+        let cache = Arc::new(MultiLevelCache::new(None).unwrap());
+        let loads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        let calls: Vec<_> = (0..8)
+            .map(|_| {
+                let (cache, loads) = (cache.clone(), loads.clone());
+                tokio::spawn(async move {
+                    let loader = Box::pin(async move {
+                        loads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        Ok("loaded".to_string())
+                    });
+                    cache.get_or_set("k", Duration::from_secs(60), loader).await
+                })
+            })
+            .collect();
+        for call in calls {
+            assert_eq!(call.await.unwrap().unwrap(), "loaded");
+        }
+        assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // A failed load is not cached
+        let failed = cache
+            .get_or_set(
+                "other",
+                Duration::from_secs(60),
+                Box::pin(async { Err(anyhow::anyhow!("upstream down")) }),
+            )
+            .await;
+        assert!(failed.is_err());
+        assert_eq!(cache.get("other").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_or_set_cancelled_load_is_not_left_in_flight() {
+        let cache = MultiLevelCache::new(None).unwrap();
+
+        let stalled = tokio::time::timeout(
+            Duration::from_millis(20),
+            cache.get_or_set(
+                "k",
+                Duration::from_secs(60),
+                Box::pin(std::future::pending()),
+            ),
+        )
+        .await;
+        assert!(stalled.is_err());
+        assert!(cache.loading().is_empty());
+
+        let loaded = cache
+            .get_or_set(
+                "k",
+                Duration::from_secs(60),
+                Box::pin(async { Ok("loaded".to_string()) }),
+            )
+            .await;
+        assert_eq!(loaded.unwrap(), "loaded");
+        assert!(cache.loading().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_batch_operations_keep_key_order() {
+        let shared: Arc<dyn Cache> = Arc::new(MultiLevelCache::new(None).unwrap());
+        let cache = PrefixedCache::new(shared.clone(), "eu:");
+        cache
+            .set_many(&[("a", "1"), ("c", "3")], Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            cache.get_many(&["a", "b", "c"]).await.unwrap(),
+            vec![Some("1".to_string()), None, Some("3".to_string())]
+        );
+        assert_eq!(shared.get("eu:c").await.unwrap().as_deref(), Some("3"));
+    }
+
+    #[tokio::test]
+    async fn test_prefixed_caches_do_not_share_keys() {
+        let shared: Arc<dyn Cache> = Arc::new(MultiLevelCache::new(None).unwrap());
+        let eu = PrefixedCache::new(shared.clone(), "eu:");
+        let us = PrefixedCache::new(shared.clone(), "us:");
+
+        eu.set("k", "eu", Duration::from_secs(60)).await.unwrap();
+        assert_eq!(us.get("k").await.unwrap(), None);
+        assert_eq!(shared.get("eu:k").await.unwrap().as_deref(), Some("eu"));
     }
 }
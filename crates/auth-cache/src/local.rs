@@ -0,0 +1,341 @@
+//! In-process tier of [`MultiLevelCache`](crate::MultiLevelCache)
+//!
+//! Bounded both by entry count and by an estimate of the memory the
+//! entries take; past either bound the least recently used entries are
+//! evicted. Expired entries are dropped when read and by a periodic sweep,
+//! so keys that are never read again do not hold memory until evicted.
+//!
+//! Keys are spread over shards, each with its own lock and an equal share
+//! of the bounds, so concurrent requests rarely wait on each other.
+
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+const SHARDS: usize = 16;
+
+/// Bookkeeping per entry beyond key and value, counted against `max_bytes`
+const ENTRY_OVERHEAD: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalCacheConfig {
+    /// Entries held at most
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+
+    /// Approximate bound on the memory held by keys and values
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: usize,
+
+    /// Pause between sweeps for expired entries
+    #[serde(default = "default_sweep_interval_ms")]
+    pub sweep_interval_ms: u64,
+}
+
+fn default_max_entries() -> usize {
+    100_000
+}
+
+fn default_max_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_sweep_interval_ms() -> u64 {
+    30_000
+}
+
+impl Default for LocalCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: default_max_entries(),
+            max_bytes: default_max_bytes(),
+            sweep_interval_ms: default_sweep_interval_ms(),
+        }
+    }
+}
+
+/// Counters since start, and current size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LocalCacheStats {
+    pub entries: usize,
+    pub bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries evicted to stay within the bounds
+    pub evictions: u64,
+    /// Entries dropped because they expired
+    pub expirations: u64,
+}
+
+impl LocalCacheStats {
+    /// Share of reads served; 0 before the first read
+    pub fn hit_ratio(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            reads => self.hits as f64 / reads as f64,
+        }
+    }
+}
+
+struct Entry {
+    value: String,
+    expires_at: Instant,
+}
+
+fn size(key: &str, value: &str) -> usize {
+    key.len() + value.len() + ENTRY_OVERHEAD
+}
+
+struct Shard {
+    entries: LruCache<String, Entry>,
+    bytes: usize,
+}
+
+impl Shard {
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.pop(key) {
+            self.bytes -= size(key, &entry.value);
+        }
+    }
+}
+
+pub struct LocalCache {
+    shards: Vec<Mutex<Shard>>,
+    hasher: RandomState,
+    /// Bounds of each shard
+    max_entries: usize,
+    max_bytes: usize,
+    sweep_interval: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    expirations: AtomicU64,
+}
+
+impl LocalCache {
+    pub fn new(config: &LocalCacheConfig) -> Self {
+        Self {
+            shards: (0..SHARDS)
+                .map(|_| {
+                    Mutex::new(Shard {
+                        entries: LruCache::unbounded(),
+                        bytes: 0,
+                    })
+                })
+                .collect(),
+            hasher: RandomState::new(),
+            max_entries: config.max_entries.div_ceil(SHARDS).max(1),
+            max_bytes: config.max_bytes.div_ceil(SHARDS).max(1),
+            sweep_interval: Duration::from_millis(config.sweep_interval_ms.max(1)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            expirations: AtomicU64::new(0),
+        }
+    }
+
+    pub fn sweep_interval(&self) -> Duration {
+        self.sweep_interval
+    }
+
+    fn shard(&self, key: &str) -> MutexGuard<'_, Shard> {
+        let index = self.hasher.hash_one(key) as usize % SHARDS;
+        self.shards[index].lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn shards(&self) -> impl Iterator<Item = MutexGuard<'_, Shard>> {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// The value if held and not expired; counts as a hit or a miss
+    pub fn get(&self, key: &str) -> Option<String> {
+        let now = Instant::now();
+        let mut shard = self.shard(key);
+        let held = shard
+            .entries
+            .get(key)
+            .map(|entry| (entry.expires_at > now).then(|| entry.value.clone()));
+        let value = match held {
+            Some(Some(value)) => Some(value),
+            Some(None) => {
+                shard.remove(key);
+                self.expirations.fetch_add(1, Ordering::Relaxed);
+                metrics::counter!("auth_cache_l1_evictions_total", 1, "reason" => "expired");
+                None
+            }
+            None => None,
+        };
+        drop(shard);
+
+        if value.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            metrics::counter!("auth_cache_l1_hits_total", 1);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            metrics::counter!("auth_cache_l1_misses_total", 1);
+        }
+        value
+    }
+
+    /// The value and its remaining lifetime, without touching recency or
+    /// the hit counters
+    pub fn peek(&self, key: &str) -> Option<(String, Duration)> {
+        let shard = self.shard(key);
+        let entry = shard.entries.peek(key)?;
+        let remaining = entry.expires_at.checked_duration_since(Instant::now())?;
+        (!remaining.is_zero()).then(|| (entry.value.clone(), remaining))
+    }
+
+    /// Store `value` for `ttl`, evicting the least recently used entries
+    /// of the shard beyond its bounds. A value too large for a shard on
+    /// its own is not kept, and drops what was held under `key`.
+    pub fn insert(&self, key: &str, value: &str, ttl: Duration) {
+        let mut shard = self.shard(key);
+        shard.remove(key);
+        let bytes = size(key, value);
+        if bytes > self.max_bytes {
+            return;
+        }
+        shard.entries.put(
+            key.to_string(),
+            Entry {
+                value: value.to_string(),
+                expires_at: Instant::now() + ttl,
+            },
+        );
+        shard.bytes += bytes;
+
+        let mut evicted = 0;
+        while shard.entries.len() > self.max_entries || shard.bytes > self.max_bytes {
+            let Some((lru_key, entry)) = shard.entries.pop_lru() else {
+                break;
+            };
+            shard.bytes -= size(&lru_key, &entry.value);
+            evicted += 1;
+        }
+        if evicted > 0 {
+            self.evictions.fetch_add(evicted, Ordering::Relaxed);
+            metrics::counter!("auth_cache_l1_evictions_total", evicted, "reason" => "size");
+        }
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.shard(key).remove(key);
+    }
+
+    pub fn clear(&self) {
+        for mut shard in self.shards() {
+            shard.entries.clear();
+            shard.bytes = 0;
+        }
+    }
+
+    /// Drop expired entries; returns how many
+    pub fn sweep(&self) -> usize {
+        let now = Instant::now();
+        let mut swept = 0;
+        for mut shard in self.shards() {
+            let expired: Vec<String> = shard
+                .entries
+                .iter()
+                .filter(|(_, entry)| entry.expires_at <= now)
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in &expired {
+                shard.remove(key);
+            }
+            swept += expired.len();
+        }
+        if swept > 0 {
+            self.expirations.fetch_add(swept as u64, Ordering::Relaxed);
+            metrics::counter!("auth_cache_l1_evictions_total", swept as u64, "reason" => "expired");
+        }
+        swept
+    }
+
+    pub fn stats(&self) -> LocalCacheStats {
+        let (entries, bytes) = self.shards().fold((0, 0), |(entries, bytes), shard| {
+            (entries + shard.entries.len(), bytes + shard.bytes)
+        });
+        LocalCacheStats {
+            entries,
+            bytes,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            expirations: self.expirations.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Publish size and hit ratio gauges
+    pub fn record_gauges(&self) {
+        let stats = self.stats();
+        metrics::gauge!("auth_cache_l1_entries", stats.entries as f64);
+        metrics::gauge!("auth_cache_l1_bytes", stats.bytes as f64);
+        metrics::gauge!("auth_cache_l1_hit_ratio", stats.hit_ratio());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(max_entries: usize, max_bytes: usize) -> LocalCache {
+        LocalCache::new(&LocalCacheConfig {
+            max_entries,
+            max_bytes,
+            ..LocalCacheConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_least_recently_used_entries_are_evicted_past_the_bound() {
+        // One entry per shard
+        let cache = cache(SHARDS, usize::MAX / 2);
+        let keys: Vec<String> = (0..200).map(|i| format!("k{}", i)).collect();
+        for key in &keys {
+            cache.insert(key, "v", Duration::from_secs(60));
+        }
+        let stats = cache.stats();
+        assert!(stats.entries <= SHARDS);
+        assert_eq!(stats.evictions as usize, keys.len() - stats.entries);
+        // The last key written is never the one evicted
+        assert_eq!(cache.get("k199").as_deref(), Some("v"));
+    }
+
+    #[test]
+    fn test_byte_bound_and_oversized_values() {
+        let cache = cache(1000, SHARDS * 200);
+        cache.insert("small", "v", Duration::from_secs(60));
+        cache.insert("small", &"x".repeat(500), Duration::from_secs(60));
+        assert_eq!(cache.get("small"), None);
+        assert_eq!(cache.stats().bytes, 0);
+
+        for i in 0..100 {
+            cache.insert(&format!("k{}", i), &"x".repeat(50), Duration::from_secs(60));
+        }
+        assert!(cache.stats().bytes <= SHARDS * 200);
+    }
+
+    #[test]
+    fn test_sweep_drops_expired_entries() {
+        let cache = cache(1000, usize::MAX / 2);
+        cache.insert("short", "v", Duration::ZERO);
+        cache.insert("long", "v", Duration::from_secs(60));
+
+        assert_eq!(cache.sweep(), 1);
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.expirations), (1, 1));
+        assert!(cache.peek("long").is_some());
+
+        assert_eq!(cache.get("long").as_deref(), Some("v"));
+        assert_eq!(cache.get("short"), None);
+        assert_eq!(cache.stats().hit_ratio(), 0.5);
+    }
+}
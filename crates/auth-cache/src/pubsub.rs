@@ -0,0 +1,69 @@
+//! Redis pub/sub channel used to fan messages out across nodes
+
+use futures::StreamExt;
+use redis::{AsyncCommands, Client};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+pub struct RedisPubSub {
+    client: Client,
+}
+
+impl RedisPubSub {
+    pub fn new(redis_url: &str) -> anyhow::Result<Self> {
+        Ok(Self::from_client(Client::open(redis_url)?))
+    }
+
+    pub fn from_client(client: Client) -> Self {
+        Self { client }
+    }
+
+    pub async fn publish(&self, channel: &str, payload: &str) -> anyhow::Result<()> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let _: i64 = conn.publish(channel, payload).await?;
+        Ok(())
+    }
+
+    /// Forward every message on `channel` into the returned receiver.
+    /// The subscription reconnects on failure and stops once the receiver is dropped.
+    pub fn subscribe(&self, channel: &str) -> (mpsc::Receiver<String>, JoinHandle<()>) {
+        let (tx, rx) = mpsc::channel(256);
+        let client = self.client.clone();
+        let channel = channel.to_string();
+
+        let handle = tokio::spawn(async move {
+            while !tx.is_closed() {
+                if let Err(e) = Self::forward(&client, &channel, &tx).await {
+                    warn!("Redis subscription to '{}' failed: {}", channel, e);
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+
+        (rx, handle)
+    }
+
+    async fn forward(
+        client: &Client,
+        channel: &str,
+        tx: &mpsc::Sender<String>,
+    ) -> anyhow::Result<()> {
+        let mut pubsub = client.get_async_connection().await?.into_pubsub();
+        pubsub.subscribe(channel).await?;
+        debug!("Subscribed to Redis channel '{}'", channel);
+
+        let mut messages = pubsub.on_message();
+        while let Some(msg) = messages.next().await {
+            let payload: String = msg.get_payload()?;
+            if tx.send(payload).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
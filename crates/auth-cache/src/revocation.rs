@@ -0,0 +1,60 @@
+//! Redis cache of access token revocation status
+//!
+//! One key per token id holding `1` (revoked) or `0` (not revoked). A batch
+//! is read with a single `MGET` and written with a single pipeline, so a
+//! gateway validating many tokens costs one round trip either way.
+
+use redis::Client;
+use std::time::Duration;
+use uuid::Uuid;
+
+const KEY_PREFIX: &str = "revoked:";
+
+pub struct RedisRevocationCache {
+    client: Client,
+}
+
+impl RedisRevocationCache {
+    pub fn new(redis_url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: Client::open(redis_url)?,
+        })
+    }
+
+    /// Cached status per token id, in input order; `None` when not cached
+    pub async fn lookup(&self, jtis: &[Uuid]) -> anyhow::Result<Vec<Option<bool>>> {
+        if jtis.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let mut mget = redis::cmd("MGET");
+        for jti in jtis {
+            mget.arg(key(jti));
+        }
+        let values: Vec<Option<String>> = mget.query_async(&mut conn).await?;
+        Ok(values.into_iter().map(|v| v.map(|v| v == "1")).collect())
+    }
+
+    /// Cache the status of each token id for its given lifetime
+    pub async fn store(&self, entries: &[(Uuid, bool, Duration)]) -> anyhow::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let mut pipe = redis::pipe();
+        for (jti, revoked, ttl) in entries {
+            pipe.cmd("SET")
+                .arg(key(jti))
+                .arg(if *revoked { "1" } else { "0" })
+                .arg("PX")
+                .arg((ttl.as_millis() as u64).max(1))
+                .ignore();
+        }
+        pipe.query_async::<_, ()>(&mut conn).await?;
+        Ok(())
+    }
+}
+
+fn key(jti: &Uuid) -> String {
+    format!("{}{}", KEY_PREFIX, jti)
+}
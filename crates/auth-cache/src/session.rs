@@ -0,0 +1,160 @@
+//! Redis storage for login sessions with sliding expiration
+//!
+//! Each session is a hash holding its payload and absolute expiry. Reading
+//! it extends the key's TTL by the idle timeout, capped at the absolute
+//! expiry, in the same Lua call, so validation is one round trip and an
+//! idle session expires on its own. A set per user and one per tenant list
+//! their session tokens for revoking them all at once.
+
+use redis::{Client, Script};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SESSION_PREFIX: &str = "session:";
+const USER_PREFIX: &str = "session_user:";
+const TENANT_PREFIX: &str = "session_tenant:";
+
+/// KEYS: session, user index, tenant index. ARGV: payload, absolute expiry (ms), idle
+/// timeout (ms), now (ms), token
+const PUT_SCRIPT: &str = r#"
+local lifetime = tonumber(ARGV[2]) - tonumber(ARGV[4])
+local ttl = math.min(tonumber(ARGV[3]), lifetime)
+if ttl <= 0 then
+    return 0
+end
+redis.call('HSET', KEYS[1], 'data', ARGV[1], 'exp', ARGV[2])
+redis.call('PEXPIRE', KEYS[1], ttl)
+for i = 2, 3 do
+    redis.call('SADD', KEYS[i], ARGV[5])
+    if redis.call('PTTL', KEYS[i]) < lifetime then
+        redis.call('PEXPIREAT', KEYS[i], ARGV[2])
+    end
+end
+return 1
+"#;
+
+/// KEYS: session. ARGV: idle timeout (ms), now (ms)
+const TOUCH_SCRIPT: &str = r#"
+local v = redis.call('HMGET', KEYS[1], 'data', 'exp')
+if not v[1] then
+    return false
+end
+local ttl = math.min(tonumber(ARGV[1]), tonumber(v[2]) - tonumber(ARGV[2]))
+if ttl <= 0 then
+    redis.call('DEL', KEYS[1])
+    return false
+end
+redis.call('PEXPIRE', KEYS[1], ttl)
+return v[1]
+"#;
+
+/// KEYS: user or tenant index. ARGV: session key prefix
+const DELETE_INDEXED_SCRIPT: &str = r#"
+local tokens = redis.call('SMEMBERS', KEYS[1])
+for _, token in ipairs(tokens) do
+    redis.call('DEL', ARGV[1] .. token)
+end
+redis.call('DEL', KEYS[1])
+return #tokens
+"#;
+
+pub struct RedisSessionCache {
+    client: Client,
+    put: Script,
+    touch: Script,
+    delete_indexed: Script,
+}
+
+impl RedisSessionCache {
+    pub fn new(redis_url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: Client::open(redis_url)?,
+            put: Script::new(PUT_SCRIPT),
+            touch: Script::new(TOUCH_SCRIPT),
+            delete_indexed: Script::new(DELETE_INDEXED_SCRIPT),
+        })
+    }
+
+    /// Store a session until it is idle for `idle` or reaches `expires_at_ms`.
+    /// `false` if it has already expired.
+    pub async fn put(
+        &self,
+        token: &str,
+        user_id: &str,
+        tenant_id: &str,
+        payload: &str,
+        idle: Duration,
+        expires_at_ms: i64,
+    ) -> anyhow::Result<bool> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let stored: i64 = self
+            .put
+            .key(session_key(token))
+            .key(format!("{}{}", USER_PREFIX, user_id))
+            .key(format!("{}{}", TENANT_PREFIX, tenant_id))
+            .arg(payload)
+            .arg(expires_at_ms)
+            .arg(idle_millis(idle))
+            .arg(now_millis())
+            .arg(token)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(stored == 1)
+    }
+
+    /// The session's payload, extending its idle expiry
+    pub async fn touch(&self, token: &str, idle: Duration) -> anyhow::Result<Option<String>> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        Ok(self
+            .touch
+            .key(session_key(token))
+            .arg(idle_millis(idle))
+            .arg(now_millis())
+            .invoke_async(&mut conn)
+            .await?)
+    }
+
+    pub async fn delete(&self, token: &str) -> anyhow::Result<()> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        redis::cmd("DEL")
+            .arg(session_key(token))
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete every session of the user; returns how many were listed
+    pub async fn delete_user(&self, user_id: &str) -> anyhow::Result<usize> {
+        self.delete_indexed(format!("{}{}", USER_PREFIX, user_id))
+            .await
+    }
+
+    /// Delete every session of the tenant; returns how many were listed
+    pub async fn delete_tenant(&self, tenant_id: &str) -> anyhow::Result<usize> {
+        self.delete_indexed(format!("{}{}", TENANT_PREFIX, tenant_id))
+            .await
+    }
+
+    async fn delete_indexed(&self, index_key: String) -> anyhow::Result<usize> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        Ok(self
+            .delete_indexed
+            .key(index_key)
+            .arg(SESSION_PREFIX)
+            .invoke_async(&mut conn)
+            .await?)
+    }
+}
+
+fn session_key(token: &str) -> String {
+    format!("{}{}", SESSION_PREFIX, token)
+}
+
+fn idle_millis(idle: Duration) -> u64 {
+    (idle.as_millis() as u64).max(1)
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
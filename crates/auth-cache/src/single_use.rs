@@ -0,0 +1,71 @@
+//! Redis storage for single-use tokens
+//!
+//! Payloads are stored with a `v:` prefix. Redeeming swaps the value for a
+//! tombstone in one Lua call, so two nodes racing on the same token can never
+//! both read the payload, and a later attempt can be recognised as a replay.
+
+use redis::{Client, Script};
+use std::time::Duration;
+
+const PAYLOAD_PREFIX: &str = "v:";
+
+const REDEEM_SCRIPT: &str = r#"
+local v = redis.call('GET', KEYS[1])
+if not v then
+    return {0, ''}
+end
+if v == 'x' then
+    return {1, ''}
+end
+redis.call('SET', KEYS[1], 'x', 'KEEPTTL')
+return {2, string.sub(v, 3)}
+"#;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedeemOutcome {
+    Payload(String),
+    Consumed,
+    Missing,
+}
+
+pub struct RedisSingleUse {
+    client: Client,
+    redeem: Script,
+}
+
+impl RedisSingleUse {
+    pub fn new(redis_url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: Client::open(redis_url)?,
+            redeem: Script::new(REDEEM_SCRIPT),
+        })
+    }
+
+    pub async fn put_if_absent(
+        &self,
+        key: &str,
+        payload: &str,
+        ttl: Duration,
+    ) -> anyhow::Result<bool> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(format!("{}{}", PAYLOAD_PREFIX, payload))
+            .arg("NX")
+            .arg("PX")
+            .arg((ttl.as_millis() as u64).max(1))
+            .query_async(&mut conn)
+            .await?;
+        Ok(reply.is_some())
+    }
+
+    pub async fn redeem(&self, key: &str) -> anyhow::Result<RedeemOutcome> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let (state, payload): (i64, String) = self.redeem.key(key).invoke_async(&mut conn).await?;
+        Ok(match state {
+            2 => RedeemOutcome::Payload(payload),
+            1 => RedeemOutcome::Consumed,
+            _ => RedeemOutcome::Missing,
+        })
+    }
+}
@@ -33,38 +33,2178 @@ pub struct ServerConfig {
     #[serde(default = "default_drain_timeout")]
     pub drain_timeout_seconds: u64,
 
+    /// Seconds between `/ready` turning 503 on the shutdown signal and the
+    /// listener closing; at least the load balancer's health check interval
+    /// times its unhealthy threshold
+    #[serde(default)]
+    pub deregistration_delay_seconds: u64,
+
+    /// Instances running with this configuration. More than one needs Redis
+    /// so rate limits, nonces and sessions are shared.
+    #[serde(default = "default_replicas")]
+    pub replicas: u32,
+
+    /// Internal admin listener for port lease management (disabled when unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin: Option<AdminServerConfig>,
+
+    /// RADIUS listener for VPNs and network devices; requires the `radius` feature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub radius: Option<RadiusServerConfig>,
+
+    /// Per-tenant and per-client request quotas
+    #[serde(default)]
+    pub quotas: QuotaConfig,
+
+    /// Cache warm-up before the instance reports ready
+    #[serde(default)]
+    pub warmup: WarmupConfig,
+
+    /// Bounds of the in-process cache tier
+    #[serde(default)]
+    pub local_cache: auth_cache::LocalCacheConfig,
+
+    /// Restart of background workers that panic
+    #[serde(default)]
+    pub supervisor: auth_platform::SupervisorConfig,
+
+    /// Canary rollout of reloaded configuration
+    #[serde(default)]
+    pub config_rollout: ConfigRolloutConfig,
+
     pub workers: Option<usize>,
     pub max_connections: Option<u32>,
     pub timeout_seconds: Option<u64>,
 }
 
-fn default_drain_timeout() -> u64 {
+fn default_drain_timeout() -> u64 {
+    30
+}
+
+fn default_replicas() -> u32 {
+    1
+}
+
+impl ServerConfig {
+    /// `port_policy` if set, else the legacy `port` as a public listener
+    /// falling back over the next nine ports
+    pub fn effective_port_policy(&self) -> auth_platform::PortPolicy {
+        self.port_policy.clone().unwrap_or_else(|| {
+            auth_platform::PortPolicy::new(self.port, auth_platform::PortClass::Public, "http")
+                .with_fallback_range(self.port.saturating_add(1)..=self.port.saturating_add(9))
+        })
+    }
+}
+
+/// Operator-only listener, bound as `PortClass::Admin` (fixed port, no fallback)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminServerConfig {
+    pub port: u16,
+    /// Defaults to loopback; the admin API has no authentication of its own
+    #[serde(default = "default_admin_host")]
+    pub host: String,
+}
+
+fn default_admin_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+/// Request quotas. Each tenant's rate and in-flight limits come from the
+/// tier named after its subscription plan; tenants without an active plan
+/// get `default_tier`. Every client address is also capped on requests in
+/// flight so a single caller cannot use up its tenant's share.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    #[serde(default = "default_quotas_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_quota_tier")]
+    pub default_tier: String,
+    /// Keyed by subscription plan id
+    #[serde(default = "default_quota_tiers")]
+    pub tiers: HashMap<String, QuotaTier>,
+    #[serde(default = "default_max_concurrent_per_client")]
+    pub max_concurrent_per_client: u32,
+    /// Tenant for requests without a bearer token, keyed by `Host`
+    #[serde(default)]
+    pub hosts: HashMap<String, uuid::Uuid>,
+    /// How long a tenant's plan is cached before it is looked up again
+    #[serde(default = "default_plan_cache_seconds")]
+    pub plan_cache_seconds: u64,
+    /// Limits learned from each tenant's own traffic
+    #[serde(default)]
+    pub adaptive: AdaptiveQuotaConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaTier {
+    pub requests_per_minute: u32,
+    pub max_concurrent: u32,
+}
+
+fn default_quotas_enabled() -> bool {
+    true
+}
+
+fn default_quota_tier() -> String {
+    "free".to_string()
+}
+
+fn default_quota_tiers() -> HashMap<String, QuotaTier> {
+    HashMap::from([
+        (
+            "free".to_string(),
+            QuotaTier {
+                requests_per_minute: 600,
+                max_concurrent: 20,
+            },
+        ),
+        (
+            "pro".to_string(),
+            QuotaTier {
+                requests_per_minute: 6_000,
+                max_concurrent: 100,
+            },
+        ),
+        (
+            "enterprise".to_string(),
+            QuotaTier {
+                requests_per_minute: 60_000,
+                max_concurrent: 500,
+            },
+        ),
+    ])
+}
+
+fn default_max_concurrent_per_client() -> u32 {
+    50
+}
+
+fn default_plan_cache_seconds() -> u64 {
+    60
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_quotas_enabled(),
+            default_tier: default_quota_tier(),
+            tiers: default_quota_tiers(),
+            max_concurrent_per_client: default_max_concurrent_per_client(),
+            hosts: HashMap::new(),
+            plan_cache_seconds: default_plan_cache_seconds(),
+            adaptive: AdaptiveQuotaConfig::default(),
+        }
+    }
+}
+
+/// Adaptive request rates
+///
+/// Each tenant's busiest minute is learned per hour of the day (UTC). Once an
+/// hour has `min_days` of history, the tenant's limit in that hour is its
+/// learned peak times `headroom`, instead of the tier's fixed rate. A minute
+/// with more than `anomaly_multiplier` times the learned peak tightens the
+/// limit to the peak times `tightened_headroom` for `tighten_seconds`, and
+/// is not learned from. The limit never leaves `min_factor..=max_factor`
+/// times the tier's rate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdaptiveQuotaConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_adaptive_headroom")]
+    pub headroom: f64,
+    #[serde(default = "default_adaptive_min_factor")]
+    pub min_factor: f64,
+    #[serde(default = "default_adaptive_max_factor")]
+    pub max_factor: f64,
+    /// Weight of the latest day in the learned peak
+    #[serde(default = "default_adaptive_learning_rate")]
+    pub learning_rate: f64,
+    #[serde(default = "default_adaptive_min_days")]
+    pub min_days: u32,
+    #[serde(default = "default_adaptive_anomaly_multiplier")]
+    pub anomaly_multiplier: f64,
+    #[serde(default = "default_adaptive_tightened_headroom")]
+    pub tightened_headroom: f64,
+    #[serde(default = "default_adaptive_tighten_seconds")]
+    pub tighten_seconds: u64,
+}
+
+fn default_adaptive_headroom() -> f64 {
+    3.0
+}
+
+fn default_adaptive_min_factor() -> f64 {
+    0.25
+}
+
+fn default_adaptive_max_factor() -> f64 {
+    2.0
+}
+
+fn default_adaptive_learning_rate() -> f64 {
+    0.2
+}
+
+fn default_adaptive_min_days() -> u32 {
+    7
+}
+
+fn default_adaptive_anomaly_multiplier() -> f64 {
+    10.0
+}
+
+fn default_adaptive_tightened_headroom() -> f64 {
+    1.0
+}
+
+fn default_adaptive_tighten_seconds() -> u64 {
+    900
+}
+
+impl Default for AdaptiveQuotaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            headroom: default_adaptive_headroom(),
+            min_factor: default_adaptive_min_factor(),
+            max_factor: default_adaptive_max_factor(),
+            learning_rate: default_adaptive_learning_rate(),
+            min_days: default_adaptive_min_days(),
+            anomaly_multiplier: default_adaptive_anomaly_multiplier(),
+            tightened_headroom: default_adaptive_tightened_headroom(),
+            tighten_seconds: default_adaptive_tighten_seconds(),
+        }
+    }
+}
+
+/// Startup cache warm-up. `/ready` answers 503 until the JWKS and, for each
+/// listed tenant and each tenant in `quotas.hosts`, the role list and
+/// subscription plan have been loaded, or until `timeout_seconds` has passed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmupConfig {
+    #[serde(default = "default_warmup_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_warmup_timeout")]
+    pub timeout_seconds: u64,
+    /// Tenants to preload beyond those mapped in `quotas.hosts`
+    #[serde(default)]
+    pub tenants: Vec<uuid::Uuid>,
+    /// Tenants loaded at the same time
+    #[serde(default = "default_warmup_concurrency")]
+    pub concurrency: usize,
+}
+
+fn default_warmup_enabled() -> bool {
+    true
+}
+
+fn default_warmup_timeout() -> u64 {
+    20
+}
+
+fn default_warmup_concurrency() -> usize {
+    8
+}
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_warmup_enabled(),
+            timeout_seconds: default_warmup_timeout(),
+            tenants: Vec::new(),
+            concurrency: default_warmup_concurrency(),
+        }
+    }
+}
+
+/// Canary rollout of configuration versions. A staged version is served to
+/// `initial_percent` of traffic, bucketed by tenant, until it is promoted or
+/// aborted. With `auto_rollback`, it is aborted once both versions have
+/// served `min_requests` and the candidate's error rate exceeds the current
+/// version's by more than `max_error_rate_increase`, or its mean latency by
+/// more than the `max_latency_increase` fraction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigRolloutConfig {
+    #[serde(default = "default_rollout_initial_percent")]
+    pub initial_percent: u8,
+    #[serde(default = "default_rollout_auto_rollback")]
+    pub auto_rollback: bool,
+    #[serde(default = "default_rollout_min_requests")]
+    pub min_requests: u64,
+    #[serde(default = "default_rollout_max_error_rate_increase")]
+    pub max_error_rate_increase: f64,
+    #[serde(default = "default_rollout_max_latency_increase")]
+    pub max_latency_increase: f64,
+    #[serde(default = "default_rollout_evaluation_interval")]
+    pub evaluation_interval_seconds: u64,
+}
+
+fn default_rollout_initial_percent() -> u8 {
+    5
+}
+
+fn default_rollout_auto_rollback() -> bool {
+    true
+}
+
+fn default_rollout_min_requests() -> u64 {
+    200
+}
+
+fn default_rollout_max_error_rate_increase() -> f64 {
+    0.02
+}
+
+fn default_rollout_max_latency_increase() -> f64 {
+    0.25
+}
+
+fn default_rollout_evaluation_interval() -> u64 {
+    30
+}
+
+impl Default for ConfigRolloutConfig {
+    fn default() -> Self {
+        Self {
+            initial_percent: default_rollout_initial_percent(),
+            auto_rollback: default_rollout_auto_rollback(),
+            min_requests: default_rollout_min_requests(),
+            max_error_rate_increase: default_rollout_max_error_rate_increase(),
+            max_latency_increase: default_rollout_max_latency_increase(),
+            evaluation_interval_seconds: default_rollout_evaluation_interval(),
+        }
+    }
+}
+
+/// RADIUS authentication and accounting listeners. Only registered NAS
+/// clients are answered; each belongs to a tenant and signs its packets with
+/// its own secret or, failing that, the tenant's shared secret.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RadiusServerConfig {
+    #[serde(default = "default_radius_host")]
+    pub host: String,
+    #[serde(default = "default_radius_auth_port")]
+    pub auth_port: u16,
+    #[serde(default = "default_radius_acct_port")]
+    pub acct_port: u16,
+    /// Keyed by tenant id
+    #[serde(default)]
+    pub tenants: HashMap<String, RadiusTenantConfig>,
+    #[serde(default)]
+    pub clients: Vec<NasClientConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RadiusTenantConfig {
+    #[serde(skip_serializing)]
+    pub shared_secret: secrecy::Secret<String>,
+}
+
+/// A network access server (VPN concentrator, switch, Wi-Fi controller)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NasClientConfig {
+    pub name: String,
+    /// Source IP address the NAS sends from
+    pub address: String,
+    pub tenant_id: String,
+    /// Overrides the tenant's shared secret for this NAS
+    #[serde(default, skip_serializing)]
+    pub secret: Option<secrecy::Secret<String>>,
+}
+
+fn default_radius_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_radius_auth_port() -> u16 {
+    1812
+}
+
+fn default_radius_acct_port() -> u16 {
+    1813
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct DatabaseConfig {
+    #[serde(skip_serializing)]
+    pub mysql_url: secrecy::Secret<String>,
+    pub sqlite_url: Option<String>,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub connection_timeout: u64,
+    pub idle_timeout: u64,
+    pub max_lifetime: u64,
+    /// Region pinning of tenant data
+    #[serde(default)]
+    pub residency: ResidencyConfig,
+    /// Where the database login comes from
+    #[serde(default)]
+    pub credentials: DatabaseCredentialsConfig,
+    /// Startup comparison of the schema with the columns repositories use
+    #[serde(default)]
+    pub schema_check: SchemaCheckMode,
+    /// Removal of spent OTP sessions
+    #[serde(default)]
+    pub otp_purge: OtpPurgeConfig,
+    /// Schema changes made while the old and new layouts are both in use
+    #[serde(default)]
+    pub online_migrations: OnlineMigrationsConfig,
+}
+
+/// Phases of the online migrations in progress, by name. The phase in the
+/// `online_migrations` table, set from the admin API, wins over the one
+/// here; replicas pick it up within `refresh_seconds`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnlineMigrationsConfig {
+    #[serde(default)]
+    pub phases: HashMap<String, MigrationPhase>,
+    #[serde(default = "default_migration_refresh")]
+    pub refresh_seconds: u64,
+    /// Shadow reads compared at once per replica; reads beyond this are
+    /// served without comparison
+    #[serde(default = "default_migration_max_comparisons")]
+    pub max_concurrent_comparisons: usize,
+}
+
+fn default_migration_refresh() -> u64 {
+    30
+}
+
+fn default_migration_max_comparisons() -> usize {
+    64
+}
+
+impl Default for OnlineMigrationsConfig {
+    fn default() -> Self {
+        Self {
+            phases: HashMap::new(),
+            refresh_seconds: default_migration_refresh(),
+            max_concurrent_comparisons: default_migration_max_comparisons(),
+        }
+    }
+}
+
+/// Where an online migration is. The legacy layout is written up to and
+/// including `cutover`, so moving back a phase never loses writes; the new
+/// layout is written from `double_write` on and read from `cutover` on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationPhase {
+    /// Only the legacy layout is used
+    #[default]
+    Legacy,
+    /// Writes go to both layouts; reads come from the legacy one. Rows
+    /// written before this phase are backfilled now.
+    DoubleWrite,
+    /// As `double_write`, and reads are repeated against the new layout
+    /// in the background and compared
+    ShadowRead,
+    /// Reads come from the new layout; writes still go to both
+    Cutover,
+    /// Only the new layout is used; the legacy one can be dropped
+    Complete,
+}
+
+impl MigrationPhase {
+    pub const ALL: [MigrationPhase; 5] = [
+        MigrationPhase::Legacy,
+        MigrationPhase::DoubleWrite,
+        MigrationPhase::ShadowRead,
+        MigrationPhase::Cutover,
+        MigrationPhase::Complete,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MigrationPhase::Legacy => "legacy",
+            MigrationPhase::DoubleWrite => "double_write",
+            MigrationPhase::ShadowRead => "shadow_read",
+            MigrationPhase::Cutover => "cutover",
+            MigrationPhase::Complete => "complete",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|phase| phase.as_str() == value)
+    }
+
+    pub fn writes_legacy(&self) -> bool {
+        *self != MigrationPhase::Complete
+    }
+
+    pub fn writes_target(&self) -> bool {
+        *self != MigrationPhase::Legacy
+    }
+
+    pub fn reads_target(&self) -> bool {
+        matches!(self, MigrationPhase::Cutover | MigrationPhase::Complete)
+    }
+
+    /// Forward one phase at a time, since each relies on the one before
+    /// having run everywhere; back by any number, to undo a cutover
+    pub fn can_move_to(&self, next: MigrationPhase) -> bool {
+        (next as usize) <= (*self as usize) + 1
+    }
+}
+
+/// OTP sessions are deleted once verified or expired, oldest first, one
+/// time bucket at a time and in batches of `batch_size` rows with a pause
+/// between batches, so the purge never holds locks that sign-ins wait on
+/// for long. A run stops after `max_rows_per_run` rows; what is left is
+/// the next run's, and shows as purge lag.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OtpPurgeConfig {
+    #[serde(default = "default_otp_purge_interval")]
+    pub interval_seconds: u64,
+    /// Width of the `purge_after` ranges deleted from
+    #[serde(default = "default_otp_purge_bucket")]
+    pub bucket_seconds: u64,
+    #[serde(default = "default_otp_purge_batch_size")]
+    pub batch_size: u32,
+    #[serde(default = "default_otp_purge_batch_pause")]
+    pub batch_pause_ms: u64,
+    #[serde(default = "default_otp_purge_max_rows")]
+    pub max_rows_per_run: u64,
+}
+
+fn default_otp_purge_interval() -> u64 {
+    60
+}
+
+fn default_otp_purge_bucket() -> u64 {
+    3600
+}
+
+fn default_otp_purge_batch_size() -> u32 {
+    1000
+}
+
+fn default_otp_purge_batch_pause() -> u64 {
+    50
+}
+
+fn default_otp_purge_max_rows() -> u64 {
+    200_000
+}
+
+impl Default for OtpPurgeConfig {
+    fn default() -> Self {
+        Self {
+            interval_seconds: default_otp_purge_interval(),
+            bucket_seconds: default_otp_purge_bucket(),
+            batch_size: default_otp_purge_batch_size(),
+            batch_pause_ms: default_otp_purge_batch_pause(),
+            max_rows_per_run: default_otp_purge_max_rows(),
+        }
+    }
+}
+
+/// What to do about schema drift found at startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaCheckMode {
+    Off,
+    /// Log each difference and start
+    #[default]
+    Warn,
+    /// Refuse to start on any difference
+    Strict,
+}
+
+/// `static` logs in with the user and password of `mysql_url`. `file` reads
+/// them from a JSON file, `{"username": "...", "password": "..."}`, that an
+/// agent such as Vault Agent keeps current with dynamic credentials; it is
+/// re-read every `refresh_interval_seconds`, and when the login changes the
+/// pool switches to it and drains the connections opened with the old one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseCredentialsConfig {
+    #[serde(default)]
+    pub source: DatabaseCredentialsSource,
+    #[serde(default)]
+    pub file_path: Option<String>,
+    #[serde(default = "default_db_credentials_refresh")]
+    pub refresh_interval_seconds: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatabaseCredentialsSource {
+    #[default]
+    Static,
+    File,
+}
+
+fn default_db_credentials_refresh() -> u64 {
+    60
+}
+
+impl Default for DatabaseCredentialsConfig {
+    fn default() -> Self {
+        Self {
+            source: DatabaseCredentialsSource::Static,
+            file_path: None,
+            refresh_interval_seconds: default_db_credentials_refresh(),
+        }
+    }
+}
+
+/// Tenants can be pinned to a region (`tenants.data_region`). A deployment
+/// serves its own region from `database.mysql_url`, together with unpinned
+/// tenants, and the regions it has a database for in `regions`; it refuses
+/// tenants pinned anywhere else.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResidencyConfig {
+    /// Region of this deployment, e.g. `eu`. Unset, no tenant may be pinned.
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Databases of further regions this deployment serves, keyed by region
+    #[serde(default)]
+    pub regions: HashMap<String, RegionDatabaseConfig>,
+    /// How often tenant pins are reloaded
+    #[serde(default = "default_residency_refresh_seconds")]
+    pub refresh_interval_seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionDatabaseConfig {
+    #[serde(skip_serializing)]
+    pub mysql_url: secrecy::Secret<String>,
+}
+
+fn default_residency_refresh_seconds() -> u64 {
+    60
+}
+
+impl Default for ResidencyConfig {
+    fn default() -> Self {
+        Self {
+            region: None,
+            regions: HashMap::new(),
+            refresh_interval_seconds: default_residency_refresh_seconds(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct SecurityConfig {
+    #[serde(skip_serializing)]
+    pub jwt_secret: secrecy::Secret<String>,
+    pub jwt_expiry_minutes: u32,
+    pub refresh_token_expiry_days: u32,
+    pub password_min_length: u8,
+    pub max_login_attempts: u32,
+    pub lockout_duration_minutes: u32,
+    pub require_mfa: bool,
+    pub allowed_origins: Vec<String>,
+    /// Backend-For-Frontend mode for browser clients
+    #[serde(default)]
+    pub bff: BffConfig,
+    /// Base64url encoded 32-byte key for stateless (client-held) auth flow state
+    #[serde(default, skip_serializing)]
+    pub flow_state_key: Option<secrecy::Secret<String>>,
+    /// Token lifetime bounds and per-tenant / per-client overrides
+    #[serde(default)]
+    pub token_ttl: TokenTtlConfig,
+    /// Application-level encryption of PII columns
+    #[serde(default)]
+    pub pii: PiiEncryptionConfig,
+    /// Risk-triggered CAPTCHA challenges on credential endpoints
+    #[serde(default)]
+    pub captcha: CaptchaConfig,
+    /// Key-pair authenticated service accounts (RFC 7523 JWT bearer grant)
+    #[serde(default)]
+    pub service_accounts: ServiceAccountConfig,
+    /// Short-lived SSH user certificates for infrastructure access
+    #[serde(default)]
+    pub ssh_ca: SshCaConfig,
+    /// X.509 client certificates for devices, issued from per-tenant intermediates
+    #[serde(default)]
+    pub device_enrollment: DeviceEnrollmentConfig,
+    /// Approve/deny sign-in prompts on a registered mobile app
+    #[serde(default)]
+    pub push_mfa: PushMfaConfig,
+    /// Relying party settings for passkeys
+    #[serde(default)]
+    pub webauthn: WebauthnConfig,
+    /// Central SSO session cookie on the IdP host
+    #[serde(default)]
+    pub sso: SsoConfig,
+    /// Waivers for the production secure-defaults guard
+    #[serde(default)]
+    pub posture: PostureConfig,
+    /// JWKS publication for partners verifying our tokens
+    #[serde(default)]
+    pub jwks: JwksConfig,
+    /// Where login sessions are kept
+    #[serde(default)]
+    pub sessions: SessionStoreConfig,
+    /// Reminders ahead of password expiry, and forced change once expired
+    #[serde(default)]
+    pub password_expiry: PasswordExpiryConfig,
+    /// One-time login links issued by support
+    #[serde(default)]
+    pub login_links: LoginLinkConfig,
+    /// Automated response to signs of account takeover
+    #[serde(default)]
+    pub takeover_response: TakeoverResponseConfig,
+    /// Canonical form of sign-in identifiers, for uniqueness and lookup
+    #[serde(default)]
+    pub identifiers: IdentifierConfig,
+    /// Velocity rules against bulk account creation on public signup
+    #[serde(default)]
+    pub signup_abuse: SignupAbuseConfig,
+    /// Sign-in methods each tenant allows, for retiring passwords
+    #[serde(default)]
+    pub auth_methods: AuthMethodConfig,
+    /// Length, lifetime and alphabet of one-time codes by purpose
+    #[serde(default)]
+    pub otp_policies: OtpPolicyConfig,
+    /// Roles, permissions and plan resolved into access tokens
+    #[serde(default)]
+    pub token_claims: TokenClaimsConfig,
+    /// "Keep me signed in" on registered devices
+    #[serde(default)]
+    pub remember_me: RememberMeConfig,
+}
+
+/// Emails are always compared trimmed, NFC-normalized and lowercased.
+/// Folding more than that merges addresses that mailbox providers may
+/// deliver separately, so it is opt-in, per tenant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentifierConfig {
+    #[serde(default)]
+    pub default_policy: IdentifierPolicy,
+    /// Policies by tenant id, replacing the default
+    #[serde(default)]
+    pub tenants: HashMap<String, IdentifierPolicy>,
+    /// Domains that ignore dots in the local part
+    #[serde(default = "default_dot_folding_domains")]
+    pub dot_folding_domains: Vec<String>,
+    /// Usernames as a third sign-in identifier, off unless a policy enables them
+    #[serde(default)]
+    pub usernames: UsernameConfig,
+}
+
+fn default_dot_folding_domains() -> Vec<String> {
+    vec!["gmail.com".to_string(), "googlemail.com".to_string()]
+}
+
+impl Default for IdentifierConfig {
+    fn default() -> Self {
+        Self {
+            default_policy: IdentifierPolicy::default(),
+            tenants: HashMap::new(),
+            dot_folding_domains: default_dot_folding_domains(),
+            usernames: UsernameConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentifierPolicy {
+    /// `j.doe@gmail.com` is `jdoe@gmail.com`, on `dot_folding_domains`
+    #[serde(default)]
+    pub fold_dots: bool,
+    /// `jdoe+news@example.com` is `jdoe@example.com`, on every domain
+    #[serde(default)]
+    pub fold_plus_tags: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsernameConfig {
+    #[serde(default)]
+    pub default_policy: UsernamePolicy,
+    /// Policies by tenant id, replacing the default
+    #[serde(default)]
+    pub tenants: HashMap<String, UsernamePolicy>,
+}
+
+/// Reserved names are compared case-insensitively whatever `case_sensitive`
+/// says, so `Admin` cannot pass for `admin` on a case-sensitive tenant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsernamePolicy {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Matched against the whole username, after trimming
+    #[serde(default = "default_username_pattern")]
+    pub pattern: String,
+    #[serde(default = "default_reserved_usernames")]
+    pub reserved: Vec<String>,
+    /// When false, `Alice` and `alice` are the same account
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+fn default_username_pattern() -> String {
+    "^[A-Za-z][A-Za-z0-9._-]{2,31}$".to_string()
+}
+
+fn default_reserved_usernames() -> Vec<String> {
+    [
+        "admin",
+        "administrator",
+        "root",
+        "support",
+        "security",
+        "system",
+        "help",
+        "postmaster",
+    ]
+    .iter()
+    .map(|name| name.to_string())
+    .collect()
+}
+
+impl Default for UsernamePolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            pattern: default_username_pattern(),
+            reserved: default_reserved_usernames(),
+            case_sensitive: false,
+        }
+    }
+}
+
+/// Signups are counted per tenant by client address, device fingerprint
+/// and email domain. When a count goes over a rule's limit within its
+/// window the rule's action applies; of several, the strictest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignupAbuseConfig {
+    #[serde(default = "default_signup_abuse_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_signup_rules")]
+    pub rules: Vec<SignupVelocityRule>,
+    /// Mailbox providers too common for their volume to mean anything
+    #[serde(default = "default_signup_exempt_domains")]
+    pub exempt_domains: Vec<String>,
+}
+
+fn default_signup_abuse_enabled() -> bool {
+    true
+}
+
+fn default_signup_rules() -> Vec<SignupVelocityRule> {
+    let rule = |signal, max_signups, action| SignupVelocityRule {
+        signal,
+        max_signups,
+        window_seconds: 3600,
+        action,
+    };
+    vec![
+        rule(SignupSignal::Ip, 5, SignupAction::Captcha),
+        rule(SignupSignal::Ip, 20, SignupAction::Block),
+        rule(SignupSignal::Device, 3, SignupAction::Quarantine),
+        rule(SignupSignal::EmailDomain, 20, SignupAction::Quarantine),
+    ]
+}
+
+fn default_signup_exempt_domains() -> Vec<String> {
+    [
+        "gmail.com",
+        "googlemail.com",
+        "outlook.com",
+        "hotmail.com",
+        "yahoo.com",
+        "icloud.com",
+    ]
+    .iter()
+    .map(|domain| domain.to_string())
+    .collect()
+}
+
+impl Default for SignupAbuseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_signup_abuse_enabled(),
+            rules: default_signup_rules(),
+            exempt_domains: default_signup_exempt_domains(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignupVelocityRule {
+    pub signal: SignupSignal,
+    /// Signups allowed within the window before the action applies
+    pub max_signups: u32,
+    pub window_seconds: u64,
+    pub action: SignupAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignupSignal {
+    /// Client address
+    Ip,
+    /// Fingerprint the client sends with the signup
+    Device,
+    /// Domain of the email address
+    EmailDomain,
+}
+
+impl SignupSignal {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignupSignal::Ip => "ip",
+            SignupSignal::Device => "device",
+            SignupSignal::EmailDomain => "email_domain",
+        }
+    }
+}
+
+/// In increasing strictness
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignupAction {
+    /// Require a solved CAPTCHA; quarantine where no CAPTCHA is configured
+    Captcha,
+    /// Create the account as usual in appearance, but suspended and held
+    /// for review, with nothing sent to it
+    Quarantine,
+    /// Refuse the signup
+    Block,
+}
+
+impl SignupAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignupAction::Captcha => "captcha",
+            SignupAction::Quarantine => "quarantine",
+            SignupAction::Block => "block",
+        }
+    }
+}
+
+/// Every method is allowed unless a tenant's policy says otherwise. A
+/// tenant retiring passwords typically nudges users to enroll passkeys
+/// first, then drops `password` from its allowed methods.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthMethodConfig {
+    #[serde(default)]
+    pub default_policy: AuthMethodPolicy,
+    /// Policies by tenant id, replacing the default
+    #[serde(default)]
+    pub tenants: HashMap<String, AuthMethodPolicy>,
+    #[serde(default = "default_passkey_nudge_subject")]
+    pub nudge_subject: String,
+    #[serde(default = "default_passkey_nudge_body")]
+    pub nudge_body: String,
+}
+
+fn default_passkey_nudge_subject() -> String {
+    "Set up a passkey for your account".to_string()
+}
+
+fn default_passkey_nudge_body() -> String {
+    "Passwords are being retired for your account. Sign in and add a passkey \
+     so you can keep signing in without one."
+        .to_string()
+}
+
+impl Default for AuthMethodConfig {
+    fn default() -> Self {
+        Self {
+            default_policy: AuthMethodPolicy::default(),
+            tenants: HashMap::new(),
+            nudge_subject: default_passkey_nudge_subject(),
+            nudge_body: default_passkey_nudge_body(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthMethodPolicy {
+    #[serde(default = "default_auth_methods")]
+    pub allowed: Vec<AuthMethod>,
+    /// Recommend a passkey to users who sign in with a password, and allow
+    /// emailing password-only users about it
+    #[serde(default)]
+    pub nudge_passkeys: bool,
+}
+
+fn default_auth_methods() -> Vec<AuthMethod> {
+    vec![
+        AuthMethod::Password,
+        AuthMethod::Otp,
+        AuthMethod::Passkey,
+        AuthMethod::Federation,
+    ]
+}
+
+impl Default for AuthMethodPolicy {
+    fn default() -> Self {
+        Self {
+            allowed: default_auth_methods(),
+            nudge_passkeys: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    Password,
+    /// One-time codes by email or SMS
+    Otp,
+    Passkey,
+    /// Sign-in at an external identity provider
+    Federation,
+}
+
+impl AuthMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthMethod::Password => "password",
+            AuthMethod::Otp => "otp",
+            AuthMethod::Passkey => "passkey",
+            AuthMethod::Federation => "federation",
+        }
+    }
+}
+
+/// One-time codes by purpose. A tenant entry overrides only the purposes
+/// it lists; the others keep the deployment's policy.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OtpPolicyConfig {
+    #[serde(default)]
+    pub default_policy: OtpPurposePolicies,
+    /// Overrides by tenant id
+    #[serde(default)]
+    pub tenants: HashMap<String, OtpPolicyOverrides>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OtpPurposePolicies {
+    #[serde(default = "default_registration_otp")]
+    pub registration: OtpPolicy,
+    #[serde(default = "default_login_otp")]
+    pub login: OtpPolicy,
+    /// Codes confirming an email address or phone number
+    #[serde(default = "default_verification_otp")]
+    pub verification: OtpPolicy,
+    #[serde(default = "default_password_reset_otp")]
+    pub password_reset: OtpPolicy,
+    /// Tokens carried in an emailed link rather than typed
+    #[serde(default = "default_magic_link_otp")]
+    pub magic_link: OtpPolicy,
+}
+
+impl Default for OtpPurposePolicies {
+    fn default() -> Self {
+        Self {
+            registration: default_registration_otp(),
+            login: default_login_otp(),
+            verification: default_verification_otp(),
+            password_reset: default_password_reset_otp(),
+            magic_link: default_magic_link_otp(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OtpPolicyOverrides {
+    pub registration: Option<OtpPolicy>,
+    pub login: Option<OtpPolicy>,
+    pub verification: Option<OtpPolicy>,
+    pub password_reset: Option<OtpPolicy>,
+    pub magic_link: Option<OtpPolicy>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OtpPolicy {
+    pub length: usize,
+    pub ttl_minutes: i64,
+    #[serde(default)]
+    pub alphabet: OtpAlphabet,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OtpAlphabet {
+    #[default]
+    Numeric,
+    Alphanumeric,
+}
+
+fn default_registration_otp() -> OtpPolicy {
+    OtpPolicy {
+        length: 6,
+        ttl_minutes: 10,
+        alphabet: OtpAlphabet::Numeric,
+    }
+}
+
+fn default_login_otp() -> OtpPolicy {
+    default_registration_otp()
+}
+
+fn default_verification_otp() -> OtpPolicy {
+    default_registration_otp()
+}
+
+fn default_password_reset_otp() -> OtpPolicy {
+    OtpPolicy {
+        length: 8,
+        ttl_minutes: 5,
+        alphabet: OtpAlphabet::Alphanumeric,
+    }
+}
+
+fn default_magic_link_otp() -> OtpPolicy {
+    OtpPolicy {
+        length: 32,
+        ttl_minutes: 24 * 60,
+        alphabet: OtpAlphabet::Alphanumeric,
+    }
+}
+
+/// Token lifetime overrides. `jwt_expiry_minutes` and `refresh_token_expiry_days`
+/// remain the platform defaults; overrides are clamped to the min/max bounds.
+///
+/// A refresh token's lifetime is its idle lifetime: each rotation issues a
+/// token valid for that long again, until the family reaches its absolute
+/// lifetime, counted from the sign-in that started it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenTtlConfig {
+    #[serde(default = "default_min_access_ttl")]
+    pub min_access_ttl_seconds: u64,
+    #[serde(default = "default_max_access_ttl")]
+    pub max_access_ttl_seconds: u64,
+    #[serde(default = "default_min_refresh_ttl")]
+    pub min_refresh_ttl_seconds: u64,
+    #[serde(default = "default_max_refresh_ttl")]
+    pub max_refresh_ttl_seconds: u64,
+    /// Platform default absolute lifetime of a refresh token family. Never
+    /// shorter than the idle lifetime, nor longer than
+    /// `max_refresh_ttl_seconds`.
+    #[serde(default = "default_refresh_absolute_ttl")]
+    pub refresh_absolute_ttl_seconds: u64,
+    /// Keyed by tenant id
+    #[serde(default)]
+    pub tenants: HashMap<String, TokenTtlOverride>,
+    /// Keyed by OAuth client id
+    #[serde(default)]
+    pub clients: HashMap<String, TokenTtlOverride>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenTtlOverride {
+    pub access_ttl_seconds: Option<u64>,
+    /// Idle lifetime of refresh tokens
+    pub refresh_ttl_seconds: Option<u64>,
+    /// Absolute lifetime of refresh token families
+    #[serde(default)]
+    pub refresh_absolute_ttl_seconds: Option<u64>,
+}
+
+fn default_min_access_ttl() -> u64 {
+    60
+}
+
+fn default_max_access_ttl() -> u64 {
+    60 * 60
+}
+
+fn default_min_refresh_ttl() -> u64 {
+    60 * 60
+}
+
+fn default_max_refresh_ttl() -> u64 {
+    90 * 24 * 60 * 60
+}
+
+fn default_refresh_absolute_ttl() -> u64 {
+    90 * 24 * 60 * 60
+}
+
+impl Default for TokenTtlConfig {
+    fn default() -> Self {
+        Self {
+            min_access_ttl_seconds: default_min_access_ttl(),
+            max_access_ttl_seconds: default_max_access_ttl(),
+            min_refresh_ttl_seconds: default_min_refresh_ttl(),
+            max_refresh_ttl_seconds: default_max_refresh_ttl(),
+            refresh_absolute_ttl_seconds: default_refresh_absolute_ttl(),
+            tenants: HashMap::new(),
+            clients: HashMap::new(),
+        }
+    }
+}
+
+/// Backend-For-Frontend settings. Clients listed in `enabled_clients` never
+/// see raw tokens; the platform keeps them server-side behind an encrypted,
+/// HttpOnly session cookie.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BffConfig {
+    #[serde(default)]
+    pub enabled_clients: Vec<String>,
+    #[serde(default = "default_bff_cookie_name")]
+    pub cookie_name: String,
+    /// Base64url encoded 32-byte key used to encrypt the session cookie
+    #[serde(default, skip_serializing)]
+    pub cookie_key: Option<secrecy::Secret<String>>,
+    #[serde(default = "default_bff_session_ttl")]
+    pub session_ttl_seconds: u64,
+}
+
+fn default_bff_cookie_name() -> String {
+    "__Host-bff_session".to_string()
+}
+
+fn default_bff_session_ttl() -> u64 {
+    8 * 60 * 60
+}
+
+impl Default for BffConfig {
+    fn default() -> Self {
+        Self {
+            enabled_clients: Vec::new(),
+            cookie_name: default_bff_cookie_name(),
+            cookie_key: None,
+            session_ttl_seconds: default_bff_session_ttl(),
+        }
+    }
+}
+
+/// Envelope encryption for PII columns (email, phone). Each tenant gets its
+/// own data key, wrapped by the master key; lookups use a keyed blind index.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PiiEncryptionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// PKCS#8 PEM RSA private key used to wrap tenant data keys
+    #[serde(default, skip_serializing)]
+    pub master_key_pem: Option<secrecy::Secret<String>>,
+    /// Secret for the HMAC blind index; changing it requires a re-index
+    #[serde(default, skip_serializing)]
+    pub blind_index_key: Option<secrecy::Secret<String>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptchaProviderKind {
+    #[default]
+    HCaptcha,
+    Turnstile,
+}
+
+/// CAPTCHA challenges for logins that look automated. A challenge is inserted
+/// once the risk score or the recent failure count for the client crosses a
+/// threshold; tenants can opt in or out and tune the thresholds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptchaConfig {
+    /// Platform default; tenants may override it either way
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub provider: CaptchaProviderKind,
+    /// Public key handed to the client widget
+    #[serde(default)]
+    pub site_key: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub secret_key: Option<secrecy::Secret<String>>,
+    /// Challenge when the login risk score is at or above this value
+    #[serde(default = "default_captcha_risk_threshold")]
+    pub risk_threshold: f32,
+    /// Challenge after this many failed logins from the same address
+    #[serde(default = "default_captcha_failure_threshold")]
+    pub failure_threshold: u32,
+    #[serde(default = "default_captcha_failure_window")]
+    pub failure_window_seconds: u64,
+    /// Keyed by tenant id
+    #[serde(default)]
+    pub tenants: HashMap<String, CaptchaTenantOverride>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CaptchaTenantOverride {
+    pub enabled: Option<bool>,
+    pub risk_threshold: Option<f32>,
+    pub failure_threshold: Option<u32>,
+}
+
+fn default_captcha_risk_threshold() -> f32 {
+    0.6
+}
+
+fn default_captcha_failure_threshold() -> u32 {
+    3
+}
+
+fn default_captcha_failure_window() -> u64 {
+    15 * 60
+}
+
+impl Default for CaptchaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: CaptchaProviderKind::default(),
+            site_key: None,
+            secret_key: None,
+            risk_threshold: default_captcha_risk_threshold(),
+            failure_threshold: default_captcha_failure_threshold(),
+            failure_window_seconds: default_captcha_failure_window(),
+            tenants: HashMap::new(),
+        }
+    }
+}
+
+impl CaptchaConfig {
+    /// Whether any tenant, or the platform default, can issue challenges
+    pub fn any_enabled(&self) -> bool {
+        self.enabled || self.tenants.values().any(|t| t.enabled == Some(true))
+    }
+}
+
+/// Limits for service account keys and the assertions signed with them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceAccountConfig {
+    /// Longest `exp - now` accepted on a JWT bearer assertion
+    #[serde(default = "default_max_assertion_lifetime")]
+    pub max_assertion_lifetime_seconds: u64,
+    /// Expiry given to keys registered without one
+    #[serde(default = "default_key_lifetime_days")]
+    pub default_key_lifetime_days: u32,
+    #[serde(default = "default_max_key_lifetime_days")]
+    pub max_key_lifetime_days: u32,
+    #[serde(default = "default_max_keys_per_account")]
+    pub max_keys_per_account: u32,
+    /// Remind owners to rotate this many days before a key expires
+    #[serde(default = "default_rotation_reminder_days")]
+    pub rotation_reminder_days: u32,
+}
+
+fn default_max_assertion_lifetime() -> u64 {
+    5 * 60
+}
+
+fn default_key_lifetime_days() -> u32 {
+    90
+}
+
+fn default_max_key_lifetime_days() -> u32 {
+    365
+}
+
+fn default_max_keys_per_account() -> u32 {
+    5
+}
+
+fn default_rotation_reminder_days() -> u32 {
+    14
+}
+
+impl Default for ServiceAccountConfig {
+    fn default() -> Self {
+        Self {
+            max_assertion_lifetime_seconds: default_max_assertion_lifetime(),
+            default_key_lifetime_days: default_key_lifetime_days(),
+            max_key_lifetime_days: default_max_key_lifetime_days(),
+            max_keys_per_account: default_max_keys_per_account(),
+            rotation_reminder_days: default_rotation_reminder_days(),
+        }
+    }
+}
+
+/// SSH certificate authority. Principals and extensions come from the
+/// caller's roles; a user with no mapped role cannot get a certificate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshCaConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Base64 encoded 32-byte Ed25519 seeds. The first one signs; the rest
+    /// are retired but still published as trusted until removed.
+    #[serde(default, skip_serializing)]
+    pub ca_keys: Vec<secrecy::Secret<String>>,
+    #[serde(default = "default_ssh_cert_ttl")]
+    pub default_ttl_seconds: u64,
+    #[serde(default = "default_ssh_cert_max_ttl")]
+    pub max_ttl_seconds: u64,
+    /// Keyed by role name
+    #[serde(default)]
+    pub roles: HashMap<String, SshRoleMapping>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SshRoleMapping {
+    /// Unix accounts the certificate may log in as
+    #[serde(default)]
+    pub principals: Vec<String>,
+    /// e.g. `permit-pty`, `permit-port-forwarding`
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub force_command: Option<String>,
+    /// Comma separated CIDR list
+    #[serde(default)]
+    pub source_address: Option<String>,
+}
+
+fn default_ssh_cert_ttl() -> u64 {
+    60 * 60
+}
+
+fn default_ssh_cert_max_ttl() -> u64 {
+    8 * 60 * 60
+}
+
+impl Default for SshCaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ca_keys: Vec::new(),
+            default_ttl_seconds: default_ssh_cert_ttl(),
+            max_ttl_seconds: default_ssh_cert_max_ttl(),
+            roles: HashMap::new(),
+        }
+    }
+}
+
+/// Device certificate enrollment. A device proves its identity once with a
+/// single-use bootstrap token and renews with its current key afterwards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceEnrollmentConfig {
+    #[serde(default = "default_device_cert_ttl_days")]
+    pub certificate_ttl_days: u32,
+    /// Renewal is accepted only this close to expiry
+    #[serde(default = "default_device_renewal_window_days")]
+    pub renewal_window_days: u32,
+    #[serde(default = "default_bootstrap_token_ttl_hours")]
+    pub bootstrap_token_ttl_hours: u32,
+    /// `nextUpdate` distance for published CRLs
+    #[serde(default = "default_crl_validity_hours")]
+    pub crl_validity_hours: u32,
+    /// Intermediate CA per tenant, keyed by tenant id. Tenants without one
+    /// cannot enroll devices.
+    #[serde(default)]
+    pub tenants: HashMap<String, DeviceCaConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceCaConfig {
+    /// PKCS#8 PEM of the intermediate's RSA key
+    #[serde(skip_serializing)]
+    pub key_pem: secrecy::Secret<String>,
+    /// The intermediate's certificate, issued by the organisation root
+    pub certificate_pem: String,
+}
+
+fn default_device_cert_ttl_days() -> u32 {
+    30
+}
+
+fn default_device_renewal_window_days() -> u32 {
+    10
+}
+
+fn default_bootstrap_token_ttl_hours() -> u32 {
+    24
+}
+
+fn default_crl_validity_hours() -> u32 {
+    24
+}
+
+impl Default for DeviceEnrollmentConfig {
+    fn default() -> Self {
+        Self {
+            certificate_ttl_days: default_device_cert_ttl_days(),
+            renewal_window_days: default_device_renewal_window_days(),
+            bootstrap_token_ttl_hours: default_bootstrap_token_ttl_hours(),
+            crl_validity_hours: default_crl_validity_hours(),
+            tenants: HashMap::new(),
+        }
+    }
+}
+
+/// Push MFA. Challenges are delivered through FCM (Android) or APNs (iOS)
+/// and answered with a signature from the key the app registered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushMfaConfig {
+    #[serde(default = "default_push_challenge_ttl")]
+    pub challenge_ttl_seconds: u64,
+    #[serde(default = "default_max_push_devices")]
+    pub max_devices_per_user: u32,
+    #[serde(default)]
+    pub fcm: Option<FcmConfig>,
+    #[serde(default)]
+    pub apns: Option<ApnsConfig>,
+}
+
+/// FCM HTTP v1 with a Google service account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FcmConfig {
+    pub project_id: String,
+    pub client_email: String,
+    /// The service account's PKCS#8 RSA key
+    #[serde(skip_serializing)]
+    pub private_key: secrecy::Secret<String>,
+}
+
+/// APNs token-based authentication
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApnsConfig {
+    pub team_id: String,
+    pub key_id: String,
+    /// Contents of the `.p8` signing key
+    #[serde(skip_serializing)]
+    pub private_key: secrecy::Secret<String>,
+    /// The app's bundle id
+    pub topic: String,
+    #[serde(default)]
+    pub sandbox: bool,
+}
+
+fn default_push_challenge_ttl() -> u64 {
+    2 * 60
+}
+
+fn default_max_push_devices() -> u32 {
+    5
+}
+
+impl Default for PushMfaConfig {
+    fn default() -> Self {
+        Self {
+            challenge_ttl_seconds: default_push_challenge_ttl(),
+            max_devices_per_user: default_max_push_devices(),
+            fcm: None,
+            apns: None,
+        }
+    }
+}
+
+/// WebAuthn relying party. `rp_id` must be the origin's registrable domain
+/// or passkeys created for it will not be offered by the browser.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebauthnConfig {
+    #[serde(default = "default_webauthn_rp_id")]
+    pub rp_id: String,
+    #[serde(default = "default_webauthn_rp_origin")]
+    pub rp_origin: String,
+    #[serde(default = "default_webauthn_rp_name")]
+    pub rp_name: String,
+    /// How long a registration or sign-in ceremony may take
+    #[serde(default = "default_webauthn_ceremony_ttl")]
+    pub ceremony_ttl_seconds: u64,
+}
+
+fn default_webauthn_rp_id() -> String {
+    "localhost".to_string()
+}
+
+fn default_webauthn_rp_origin() -> String {
+    "http://localhost:3000".to_string()
+}
+
+fn default_webauthn_rp_name() -> String {
+    "Unified Auth".to_string()
+}
+
+fn default_webauthn_ceremony_ttl() -> u64 {
+    5 * 60
+}
+
+impl Default for WebauthnConfig {
+    fn default() -> Self {
+        Self {
+            rp_id: default_webauthn_rp_id(),
+            rp_origin: default_webauthn_rp_origin(),
+            rp_name: default_webauthn_rp_name(),
+            ceremony_ttl_seconds: default_webauthn_ceremony_ttl(),
+        }
+    }
+}
+
+/// Central SSO session. The cookie lives on the IdP host only; product
+/// domains share the session by redirecting through `/auth/authorize`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SsoConfig {
+    #[serde(default = "default_sso_cookie_name")]
+    pub cookie_name: String,
+    /// Leave unset for a host-only cookie; set only to share with sibling hosts
+    #[serde(default)]
+    pub cookie_domain: Option<String>,
+    /// `Lax` covers redirect-based SSO; `None` is needed if products embed the
+    /// IdP in iframes (session checks, front-channel logout)
+    #[serde(default = "default_sso_same_site")]
+    pub same_site: String,
+    #[serde(default = "default_backchannel_timeout")]
+    pub backchannel_timeout_seconds: u64,
+    /// How `/auth/authorize` may return its result to clients that are not
+    /// listed in `client_response_modes`
+    #[serde(default = "default_response_modes")]
+    pub response_modes: Vec<ResponseMode>,
+    /// Allowed response modes by client id, replacing `response_modes`
+    #[serde(default)]
+    pub client_response_modes: HashMap<String, Vec<ResponseMode>>,
+    /// OIDC issuer by tenant id, for tenants served on their own domain;
+    /// other tenants' tokens are issued by `APP_BASE_URL`
+    #[serde(default)]
+    pub tenant_issuers: HashMap<String, String>,
+    /// Identity providers users sign in through, by provider id
+    #[serde(default)]
+    pub upstream_idps: HashMap<String, UpstreamIdpConfig>,
+}
+
+/// Protocol spoken with an upstream identity provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamProtocol {
+    Oidc,
+    Saml,
+}
+
+/// How logging out here reaches an identity provider users sign in
+/// through. Without propagation, the provider's session outlives the local
+/// one and can sign the user straight back in; the logout page says so.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamIdpConfig {
+    /// Shown on the logout page, e.g. "Google"
+    pub display_name: String,
+    pub protocol: UpstreamProtocol,
+    /// End the provider's session when the user logs out here
+    #[serde(default)]
+    pub propagate_logout: bool,
+    /// The OIDC `end_session_endpoint`, or the SAML single logout service
+    /// (HTTP-Redirect binding)
+    #[serde(default)]
+    pub logout_url: Option<String>,
+    /// Our client id at an OIDC provider, or our SAML entity id
+    pub client_id: String,
+    /// PKCS#8 PEM RSA key signing SAML logout requests; sent unsigned
+    /// without one
+    #[serde(default, skip_serializing)]
+    pub signing_key_pem: Option<secrecy::Secret<String>>,
+}
+
+/// OAuth 2.0 response mode of the authorization response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseMode {
+    /// Parameters in the redirect URI's query string
+    Query,
+    /// Parameters in the redirect URI's fragment, read by script in the
+    /// browser
+    Fragment,
+    /// Parameters posted to the redirect URI by an auto-submitting form
+    FormPost,
+}
+
+impl ResponseMode {
+    pub const ALL: [ResponseMode; 3] = [
+        ResponseMode::Query,
+        ResponseMode::Fragment,
+        ResponseMode::FormPost,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResponseMode::Query => "query",
+            ResponseMode::Fragment => "fragment",
+            ResponseMode::FormPost => "form_post",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.as_str() == value)
+    }
+}
+
+fn default_sso_cookie_name() -> String {
+    "idp_sso".to_string()
+}
+
+fn default_sso_same_site() -> String {
+    "Lax".to_string()
+}
+
+fn default_backchannel_timeout() -> u64 {
+    5
+}
+
+/// Fragment delivery is left to the clients that ask for it
+fn default_response_modes() -> Vec<ResponseMode> {
+    vec![ResponseMode::Query, ResponseMode::FormPost]
+}
+
+impl Default for SsoConfig {
+    fn default() -> Self {
+        Self {
+            cookie_name: default_sso_cookie_name(),
+            cookie_domain: None,
+            same_site: default_sso_same_site(),
+            backchannel_timeout_seconds: default_backchannel_timeout(),
+            response_modes: default_response_modes(),
+            client_response_modes: HashMap::new(),
+            tenant_issuers: HashMap::new(),
+            upstream_idps: HashMap::new(),
+        }
+    }
+}
+
+/// Production refuses to start on dev-grade components (in-memory stores,
+/// log-only audit, stub delivery providers) unless each one is waived here
+/// by its check id, e.g. `waive = ["audit_sink"]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PostureConfig {
+    #[serde(default)]
+    pub waive: Vec<String>,
+}
+
+/// Session storage. With the `redis` backend sessions are validated in
+/// Redis, where each validation extends them by `idle_timeout_seconds` up to
+/// their absolute expiry; creations and revocations are copied to MySQL in
+/// the background for audit. Requires `external_services.redis`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionStoreConfig {
+    #[serde(default)]
+    pub backend: SessionBackend,
+    #[serde(default = "default_session_idle_timeout")]
+    pub idle_timeout_seconds: u64,
+    /// Writes queued for MySQL before callers wait for the queue to drain
+    #[serde(default = "default_session_write_behind_capacity")]
+    pub write_behind_capacity: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionBackend {
+    #[default]
+    Mysql,
+    Redis,
+}
+
+fn default_session_idle_timeout() -> u64 {
+    1800
+}
+
+fn default_session_write_behind_capacity() -> usize {
+    10_000
+}
+
+impl Default for SessionStoreConfig {
+    fn default() -> Self {
+        Self {
+            backend: SessionBackend::default(),
+            idle_timeout_seconds: default_session_idle_timeout(),
+            write_behind_capacity: default_session_write_behind_capacity(),
+        }
+    }
+}
+
+/// Password expiry under the maximum age of a password policy template
+/// (`basic`, `enterprise`, `high_security` or `compliance`). Users are
+/// emailed at each of `reminder_days` before their password expires, and a
+/// login with an expired password gets a ticket to change it instead of
+/// tokens.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordExpiryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_password_expiry_policy")]
+    pub policy: String,
+    /// Days before expiry at which a reminder is sent
+    #[serde(default = "default_password_reminder_days")]
+    pub reminder_days: Vec<u32>,
+    #[serde(default = "default_password_expiry_scan_interval")]
+    pub scan_interval_seconds: u64,
+    /// `{days}` and `{expires_on}` are filled in
+    #[serde(default = "default_password_reminder_subject")]
+    pub reminder_subject: String,
+    #[serde(default = "default_password_reminder_body")]
+    pub reminder_body: String,
+    /// Lifetime of the ticket for changing an expired password
+    #[serde(default = "default_password_change_ticket_ttl")]
+    pub change_ticket_ttl_seconds: u64,
+}
+
+fn default_password_expiry_policy() -> String {
+    "enterprise".to_string()
+}
+
+fn default_password_reminder_days() -> Vec<u32> {
+    vec![14, 7, 1]
+}
+
+fn default_password_expiry_scan_interval() -> u64 {
+    3600
+}
+
+fn default_password_reminder_subject() -> String {
+    "Your password expires in {days} days".to_string()
+}
+
+fn default_password_reminder_body() -> String {
+    "Your password expires on {expires_on}. Change it before then to keep signing in without interruption.".to_string()
+}
+
+fn default_password_change_ticket_ttl() -> u64 {
+    600
+}
+
+impl Default for PasswordExpiryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            policy: default_password_expiry_policy(),
+            reminder_days: default_password_reminder_days(),
+            scan_interval_seconds: default_password_expiry_scan_interval(),
+            reminder_subject: default_password_reminder_subject(),
+            reminder_body: default_password_reminder_body(),
+            change_ticket_ttl_seconds: default_password_change_ticket_ttl(),
+        }
+    }
+}
+
+/// One-time login links that support staff have sent to a user's verified
+/// email address. A link signs the user in once and lands them on one of
+/// `allowed_redirects`; users with MFA must still pass it when
+/// `require_mfa` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginLinkConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Page that receives `?token=` and redeems it
+    #[serde(default = "default_login_link_url")]
+    pub link_url: String,
+    /// Redirect URIs a link may be issued for, matched exactly
+    #[serde(default)]
+    pub allowed_redirects: Vec<String>,
+    /// Longest lifetime of a link; issuers may ask for less
+    #[serde(default = "default_login_link_ttl")]
+    pub ttl_seconds: u64,
+    /// Links issued for one user within `issuance_window_seconds`
+    #[serde(default = "default_login_links_per_user")]
+    pub max_per_user: u32,
+    #[serde(default = "default_login_link_window")]
+    pub issuance_window_seconds: u64,
+    #[serde(default = "default_login_link_require_mfa")]
+    pub require_mfa: bool,
+    /// `{link}` and `{minutes}` are filled in
+    #[serde(default = "default_login_link_subject")]
+    pub email_subject: String,
+    #[serde(default = "default_login_link_body")]
+    pub email_body: String,
+}
+
+fn default_login_link_url() -> String {
+    "https://auth.example.com/login/link".to_string()
+}
+
+fn default_login_link_ttl() -> u64 {
+    900
+}
+
+fn default_login_links_per_user() -> u32 {
+    3
+}
+
+fn default_login_link_window() -> u64 {
+    24 * 3600
+}
+
+fn default_login_link_require_mfa() -> bool {
+    true
+}
+
+fn default_login_link_subject() -> String {
+    "Your sign-in link".to_string()
+}
+
+fn default_login_link_body() -> String {
+    "Our support team sent you a link to sign in: {link}\n\nIt works once and expires in {minutes} minutes. If you did not contact support, ignore this email.".to_string()
+}
+
+impl Default for LoginLinkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            link_url: default_login_link_url(),
+            allowed_redirects: Vec::new(),
+            ttl_seconds: default_login_link_ttl(),
+            max_per_user: default_login_links_per_user(),
+            issuance_window_seconds: default_login_link_window(),
+            require_mfa: true,
+            email_subject: default_login_link_subject(),
+            email_body: default_login_link_body(),
+        }
+    }
+}
+
+/// Claims resolved into every access token at issuance. A resolver that
+/// does not answer within its timeout is left out of the token rather than
+/// failing the login.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenClaimsConfig {
+    #[serde(default = "default_token_claims_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_claims_resolver_timeout")]
+    pub resolver_timeout_ms: u64,
+    /// How long a user's resolved claims are reused; role changes drop them
+    /// sooner
+    #[serde(default = "default_claims_cache_ttl")]
+    pub cache_ttl_seconds: u64,
+    /// Resource classes whose permission codes are copied into tokens;
+    /// role names are always included
+    #[serde(default)]
+    pub permission_classes: Vec<String>,
+    /// Add the tenant's subscription plan as the `plan` extension claim
+    #[serde(default = "default_token_claims_enabled")]
+    pub subscription_plan: bool,
+}
+
+fn default_token_claims_enabled() -> bool {
+    true
+}
+
+fn default_claims_resolver_timeout() -> u64 {
+    250
+}
+
+fn default_claims_cache_ttl() -> u64 {
+    60
+}
+
+impl Default for TokenClaimsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            resolver_timeout_ms: default_claims_resolver_timeout(),
+            cache_ttl_seconds: default_claims_cache_ttl(),
+            permission_classes: Vec::new(),
+            subscription_plan: true,
+        }
+    }
+}
+
+/// Long-lived sign-in on a device the user chose to be remembered on. Each
+/// use of a remember-me token spends it and hands back its successor; a
+/// spent token presented again revokes the device. The lifetime runs from
+/// the sign-in that registered the device and is not extended by use.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RememberMeConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_remember_me_days")]
+    pub max_duration_days: u32,
+    /// A device unused for this long must sign in again
+    #[serde(default = "default_remember_me_idle_days")]
+    pub idle_timeout_days: u32,
+    /// Registering one more revokes the least recently used
+    #[serde(default = "default_remember_me_devices")]
+    pub max_devices_per_user: u32,
+    /// Policies by tenant id, replacing `enabled` and `max_duration_days`
+    #[serde(default)]
+    pub tenants: HashMap<String, RememberMePolicy>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RememberMePolicy {
+    pub enabled: bool,
+    /// Defaults to the global `max_duration_days`
+    #[serde(default)]
+    pub max_duration_days: Option<u32>,
+}
+
+fn default_remember_me_days() -> u32 {
     30
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
-pub struct DatabaseConfig {
-    #[serde(skip_serializing)]
-    pub mysql_url: secrecy::Secret<String>,
-    pub sqlite_url: Option<String>,
-    pub max_connections: u32,
-    pub min_connections: u32,
-    pub connection_timeout: u64,
-    pub idle_timeout: u64,
-    pub max_lifetime: u64,
+fn default_remember_me_idle_days() -> u32 {
+    14
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
-pub struct SecurityConfig {
-    #[serde(skip_serializing)]
-    pub jwt_secret: secrecy::Secret<String>,
-    pub jwt_expiry_minutes: u32,
-    pub refresh_token_expiry_days: u32,
-    pub password_min_length: u8,
-    pub max_login_attempts: u32,
-    pub lockout_duration_minutes: u32,
-    pub require_mfa: bool,
-    pub allowed_origins: Vec<String>,
+fn default_remember_me_devices() -> u32 {
+    10
+}
+
+impl Default for RememberMeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_duration_days: default_remember_me_days(),
+            idle_timeout_days: default_remember_me_idle_days(),
+            max_devices_per_user: default_remember_me_devices(),
+            tenants: HashMap::new(),
+        }
+    }
+}
+
+/// What is done when an account looks taken over: a rotated refresh token
+/// is presented again, or a sign-in comes from further away than anyone can
+/// travel since the last one. A tenant runs the playbook named in
+/// `tenants`, everyone else `default_playbook`; without a playbook the
+/// signal is only audited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TakeoverResponseConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub default_playbook: Option<String>,
+    /// Keyed by playbook name
+    #[serde(default)]
+    pub playbooks: HashMap<String, TakeoverPlaybook>,
+    /// Keyed by tenant id
+    #[serde(default)]
+    pub tenants: HashMap<String, TakeoverTenantConfig>,
+    /// A user is responded to once within this window, however many
+    /// signals fire
+    #[serde(default = "default_takeover_cooldown")]
+    pub cooldown_seconds: u64,
+    /// `{reason}` is filled in
+    #[serde(default = "default_takeover_user_subject")]
+    pub user_email_subject: String,
+    #[serde(default = "default_takeover_user_body")]
+    pub user_email_body: String,
+    /// `{user_id}`, `{reason}` and `{actions}` are filled in
+    #[serde(default = "default_takeover_admin_subject")]
+    pub admin_email_subject: String,
+    #[serde(default = "default_takeover_admin_body")]
+    pub admin_email_body: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TakeoverPlaybook {
+    /// Run in this order; a failed action does not stop the rest
+    pub actions: Vec<TakeoverAction>,
+    /// Record what would have been done without doing it
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TakeoverAction {
+    /// Sign the user out everywhere: sessions, refresh tokens and access
+    /// tokens
+    RevokeSessions,
+    /// Make the current password unusable, so the user has to reset it
+    ForcePasswordReset,
+    NotifyUser,
+    NotifyTenantAdmin,
+    RaiseWebhook,
+    /// Suspend the account until an administrator reinstates it
+    Quarantine,
+}
+
+impl TakeoverAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TakeoverAction::RevokeSessions => "revoke_sessions",
+            TakeoverAction::ForcePasswordReset => "force_password_reset",
+            TakeoverAction::NotifyUser => "notify_user",
+            TakeoverAction::NotifyTenantAdmin => "notify_tenant_admin",
+            TakeoverAction::RaiseWebhook => "raise_webhook",
+            TakeoverAction::Quarantine => "quarantine",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TakeoverTenantConfig {
+    /// Instead of `default_playbook`
+    #[serde(default)]
+    pub playbook: Option<String>,
+    /// Recipients of `notify_tenant_admin`
+    #[serde(default)]
+    pub admin_emails: Vec<String>,
+    /// Destination of `raise_webhook`
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+}
+
+fn default_takeover_cooldown() -> u64 {
+    15 * 60
+}
+
+fn default_takeover_user_subject() -> String {
+    "We secured your account".to_string()
+}
+
+fn default_takeover_user_body() -> String {
+    "We noticed activity on your account that may not have been you: {reason}.\n\nTo protect you we may have signed you out everywhere. If we asked you to, reset your password before signing in again.".to_string()
+}
+
+fn default_takeover_admin_subject() -> String {
+    "Possible account takeover".to_string()
+}
+
+fn default_takeover_admin_body() -> String {
+    "User {user_id} may have been taken over: {reason}.\n\nActions: {actions}".to_string()
+}
+
+impl Default for TakeoverResponseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default_playbook: None,
+            playbooks: HashMap::new(),
+            tenants: HashMap::new(),
+            cooldown_seconds: default_takeover_cooldown(),
+            user_email_subject: default_takeover_user_subject(),
+            user_email_body: default_takeover_user_body(),
+            admin_email_subject: default_takeover_admin_subject(),
+            admin_email_body: default_takeover_admin_body(),
+        }
+    }
+}
+
+/// How the JWKS at `/auth/certs` is published. Keys other than the signing
+/// key can be listed to pre-publish a successor or keep a predecessor
+/// verifiable, and each key can carry the time it becomes valid and the
+/// time it is retired.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwksConfig {
+    /// Longest time clients may cache the JWKS; shortened ahead of a key
+    /// becoming valid or retiring
+    #[serde(default = "default_jwks_max_age")]
+    pub max_age_seconds: u64,
+    #[serde(default)]
+    pub keys: Vec<JwksKeyConfig>,
+    /// How long before a key retires partners are notified
+    #[serde(default = "default_jwks_retirement_notice")]
+    pub retirement_notice_hours: u64,
+    /// URLs sent the `jwks.key_retiring` webhook
+    #[serde(default)]
+    pub partner_webhooks: Vec<String>,
+}
+
+fn default_jwks_max_age() -> u64 {
+    3600
+}
+
+fn default_jwks_retirement_notice() -> u64 {
+    168
+}
+
+impl Default for JwksConfig {
+    fn default() -> Self {
+        Self {
+            max_age_seconds: default_jwks_max_age(),
+            keys: Vec::new(),
+            retirement_notice_hours: default_jwks_retirement_notice(),
+            partner_webhooks: Vec::new(),
+        }
+    }
+}
+
+/// One key in the JWKS. Without `public_key_path` the entry only annotates
+/// the signing key of the same `kid`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwksKeyConfig {
+    pub kid: String,
+    /// RSA public key PEM (PKCS#1 or SPKI)
+    #[serde(default)]
+    pub public_key_path: Option<String>,
+    /// Tokens signed with the key are accepted from this time
+    #[serde(default)]
+    pub not_before: Option<chrono::DateTime<chrono::Utc>>,
+    /// The key is removed from the JWKS at this time
+    #[serde(default)]
+    pub retires_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,6 +2212,48 @@ pub struct FeatureConfig {
     pub enabled_features: HashMap<String, bool>,
     pub feature_limits: HashMap<String, u64>,
     pub tenant_overrides: HashMap<String, HashMap<String, serde_json::Value>>,
+    /// Features switched on for a share of users before everyone
+    #[serde(default)]
+    pub cohorts: CohortConfig,
+}
+
+/// Canary cohorts. Each user falls in one of 10,000 buckets by a salted
+/// hash of their ID, the same on every replica and for every request, and
+/// a cohort of N percent is the users of the first N x 100 buckets. A
+/// smaller cohort is therefore inside every larger one, and raising a
+/// cohort's share only adds users to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CohortConfig {
+    /// Keys the bucket hash; changing it reshuffles every cohort. Required
+    /// once a feature targets a cohort.
+    #[serde(default, skip_serializing)]
+    pub salt: Option<secrecy::Secret<String>>,
+    /// Cohort name to share of users, in percent, e.g. `canary = 1.0`
+    #[serde(default)]
+    pub cohorts: HashMap<String, f64>,
+    /// Feature name to the cohort it is on for. Everyone else gets the
+    /// feature's `enabled_features` value, as does the cohort while the
+    /// feature's kill switch is on.
+    #[serde(default)]
+    pub targets: HashMap<String, String>,
+    /// How often kill switches set on other replicas are picked up
+    #[serde(default = "default_kill_switch_refresh_seconds")]
+    pub kill_switch_refresh_seconds: u64,
+}
+
+fn default_kill_switch_refresh_seconds() -> u64 {
+    2
+}
+
+impl Default for CohortConfig {
+    fn default() -> Self {
+        Self {
+            salt: None,
+            cohorts: HashMap::new(),
+            targets: HashMap::new(),
+            kill_switch_refresh_seconds: default_kill_switch_refresh_seconds(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -81,6 +2263,319 @@ pub struct LoggingConfig {
     pub format: String,
     pub output: String,
     pub structured: bool,
+    #[serde(default)]
+    #[cfg_attr(test, proptest(value = "AuditPipelineConfig::default()"))]
+    pub audit: AuditPipelineConfig,
+    /// Bulk export of session and login history for the SOC
+    #[serde(default)]
+    #[cfg_attr(test, proptest(value = "AnalyticsExportConfig::default()"))]
+    pub analytics_export: AnalyticsExportConfig,
+    /// Usage figures served to tenant administrators
+    #[serde(default)]
+    #[cfg_attr(test, proptest(value = "TenantMetricsConfig::default()"))]
+    pub tenant_metrics: TenantMetricsConfig,
+    /// Hourly login rollup behind the admin dashboard's heatmap
+    #[serde(default)]
+    #[cfg_attr(test, proptest(value = "LoginHeatmapConfig::default()"))]
+    pub login_heatmap: LoginHeatmapConfig,
+}
+
+/// Periodic export of the sessions and login events written since the
+/// previous run, as CSV files partitioned by dataset and UTC day, to
+/// `external_services.object_store`. Column layouts are documented in
+/// `docs/07_operations/analytics_export.md`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalyticsExportConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_export_interval")]
+    pub interval_seconds: u64,
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Key prefix of every file written
+    #[serde(default = "default_export_prefix")]
+    pub prefix: String,
+    /// Larger partitions are split into several files
+    #[serde(default = "default_export_max_rows_per_file")]
+    pub max_rows_per_file: usize,
+    /// Rows younger than this are left for the next run, so rows of
+    /// transactions still in flight are not skipped by the watermark
+    #[serde(default = "default_export_settle_seconds")]
+    pub settle_seconds: u64,
+}
+
+fn default_export_interval() -> u64 {
+    24 * 3600
+}
+
+fn default_export_prefix() -> String {
+    "auth-analytics".to_string()
+}
+
+fn default_export_max_rows_per_file() -> usize {
+    500_000
+}
+
+fn default_export_settle_seconds() -> u64 {
+    300
+}
+
+impl Default for AnalyticsExportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_seconds: default_export_interval(),
+            format: ExportFormat::default(),
+            prefix: default_export_prefix(),
+            max_rows_per_file: default_export_max_rows_per_file(),
+            settle_seconds: default_export_settle_seconds(),
+        }
+    }
+}
+
+/// Successful and failed sign-ins counted per hour, tenant, country and
+/// ASN by a job on the leader, so the heatmap is read from the rollup
+/// rather than aggregated over `login_events` per request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginHeatmapConfig {
+    #[serde(default = "default_heatmap_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_heatmap_interval")]
+    pub interval_seconds: u64,
+    /// Full hours before the current one recounted on every run, so that
+    /// events whose geo enrichment lands late move to their country
+    #[serde(default = "default_heatmap_lookback_hours")]
+    pub lookback_hours: u32,
+    /// How far back the first run fills an empty rollup
+    #[serde(default = "default_heatmap_backfill_days")]
+    pub backfill_days: u32,
+    /// Longest range one heatmap request may cover
+    #[serde(default = "default_heatmap_max_query_days")]
+    pub max_query_days: u32,
+}
+
+fn default_heatmap_enabled() -> bool {
+    true
+}
+
+fn default_heatmap_interval() -> u64 {
+    300
+}
+
+fn default_heatmap_lookback_hours() -> u32 {
+    3
+}
+
+fn default_heatmap_backfill_days() -> u32 {
+    30
+}
+
+fn default_heatmap_max_query_days() -> u32 {
+    31
+}
+
+impl Default for LoginHeatmapConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_seconds: default_heatmap_interval(),
+            lookback_hours: default_heatmap_lookback_hours(),
+            backfill_days: default_heatmap_backfill_days(),
+            max_query_days: default_heatmap_max_query_days(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// RFC 4180, with a header row; empty fields are NULL
+    #[default]
+    Csv,
+}
+
+/// Per-tenant counts over the last `window_days` full UTC days, served to
+/// the tenant's administrators. A few users of a small tenant can be told
+/// apart by them, so each class of metric can withhold small values, add
+/// noise and round what it reports. The admin listener serves exact values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantMetricsConfig {
+    #[serde(default = "default_tenant_metrics_window_days")]
+    pub window_days: u32,
+    /// Seeds the noise, so that every replica reports the same value for a
+    /// tenant and day and repeating a request does not average it out.
+    /// Random per process when unset.
+    #[serde(default, skip_serializing)]
+    pub noise_key: Option<secrecy::Secret<String>>,
+    /// Users, MFA-enrolled users and active users
+    #[serde(default = "default_population_disclosure")]
+    pub population: MetricDisclosure,
+    /// Successful sign-ins and sessions created
+    #[serde(default = "default_activity_disclosure")]
+    pub activity: MetricDisclosure,
+    /// Failed sign-ins and locked accounts
+    #[serde(default = "default_security_disclosure")]
+    pub security: MetricDisclosure,
+}
+
+fn default_tenant_metrics_window_days() -> u32 {
+    30
+}
+
+fn default_population_disclosure() -> MetricDisclosure {
+    MetricDisclosure {
+        min_count: 10,
+        noise: MetricNoise::None,
+        round_to: 5,
+    }
+}
+
+fn default_activity_disclosure() -> MetricDisclosure {
+    MetricDisclosure {
+        min_count: 10,
+        noise: MetricNoise::None,
+        round_to: 10,
+    }
+}
+
+fn default_security_disclosure() -> MetricDisclosure {
+    MetricDisclosure {
+        min_count: 20,
+        noise: MetricNoise::Laplace { epsilon: 0.5 },
+        round_to: 5,
+    }
+}
+
+impl Default for TenantMetricsConfig {
+    fn default() -> Self {
+        Self {
+            window_days: default_tenant_metrics_window_days(),
+            noise_key: None,
+            population: default_population_disclosure(),
+            activity: default_activity_disclosure(),
+            security: default_security_disclosure(),
+        }
+    }
+}
+
+/// How the values of one class of metric are reported to tenants
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MetricDisclosure {
+    /// Values below this, after noise, are withheld
+    #[serde(default)]
+    pub min_count: u64,
+    #[serde(default)]
+    pub noise: MetricNoise,
+    /// Reported values are rounded to a multiple of this
+    #[serde(default = "default_metric_round_to")]
+    pub round_to: u64,
+}
+
+fn default_metric_round_to() -> u64 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum MetricNoise {
+    #[default]
+    None,
+    /// Laplace noise of scale `1 / epsilon`; smaller is noisier
+    Laplace { epsilon: f64 },
+}
+
+/// Buffering between request handlers and the audit sink
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditPipelineConfig {
+    /// Events held in memory before the overflow policy applies
+    pub capacity: usize,
+    pub overflow: AuditOverflowPolicy,
+    /// Queue fill ratio (0-1) at which the pipeline is reported as falling behind
+    pub high_watermark: f64,
+    /// Write every event to a durable queue before acknowledging the caller,
+    /// so nothing is lost on crash. Replaces the in-memory queue and its
+    /// overflow policy; the WAL's disk budget bounds the backlog instead.
+    pub wal: Option<auth_platform::WalConfig>,
+    /// Severity classification, sampling and sinks per event type
+    pub routing: AuditRoutingConfig,
+}
+
+impl Default for AuditPipelineConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1000,
+            overflow: AuditOverflowPolicy::default(),
+            high_watermark: 0.8,
+            wal: None,
+            routing: AuditRoutingConfig::default(),
+        }
+    }
+}
+
+/// Which audit events are kept, and where they go. Security events (the
+/// `security` category, warnings and above, and failures) are always kept
+/// and sent to the durable sink. Informational events are kept at their
+/// sample rate and sent to `info_sink`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditRoutingConfig {
+    /// Share (0-1) of informational events kept
+    pub info_sample_rate: f64,
+    /// Sample rates overriding `info_sample_rate`, by action prefix; the
+    /// longest matching prefix wins
+    pub sample_rates: HashMap<String, f64>,
+    /// Severities overriding the one an event was raised with, by action
+    /// prefix, e.g. `"HTTP GET /health" = "info"`
+    pub severities: HashMap<String, AuditLevel>,
+    pub info_sink: AuditSink,
+}
+
+impl Default for AuditRoutingConfig {
+    fn default() -> Self {
+        Self {
+            info_sample_rate: 0.01,
+            sample_rates: HashMap::new(),
+            severities: HashMap::new(),
+            info_sink: AuditSink::Log,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditLevel {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditSink {
+    /// The audit pipeline and its persistent store
+    Durable,
+    /// Application logs only
+    Log,
+}
+
+/// What to do with a new audit event when the queue is full
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum AuditOverflowPolicy {
+    /// Wait for space, then drop the event after the timeout
+    Block { timeout_ms: u64 },
+    /// Evict the oldest queued event
+    DropOldest,
+    /// Append to a durable queue in the `path` directory and replay it once
+    /// the in-memory queue drains
+    Spill { path: String },
+}
+
+impl Default for AuditOverflowPolicy {
+    fn default() -> Self {
+        AuditOverflowPolicy::Block { timeout_ms: 500 }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -88,6 +2583,39 @@ pub struct ExternalServicesConfig {
     pub smtp: Option<SmtpConfig>,
     pub sms: Option<SmsConfig>,
     pub redis: Option<RedisConfig>,
+    #[serde(default)]
+    pub geoip: Option<GeoIpConfig>,
+    /// Shared client for calls to SMS, push and webhook endpoints
+    #[serde(default)]
+    pub http: auth_platform::HttpClientConfig,
+    /// Destinations tenant-registered webhooks may be sent to
+    #[serde(default)]
+    pub webhook_egress: auth_platform::EgressConfig,
+    #[serde(default)]
+    pub sms_compliance: SmsComplianceConfig,
+    #[serde(default)]
+    pub sms_budget: SmsBudgetConfig,
+    #[serde(default)]
+    pub sms_risk: SmsRiskConfig,
+    /// Blob storage for exports and archives
+    #[serde(default)]
+    pub object_store: auth_platform::ObjectStoreConfig,
+}
+
+/// MaxMind databases used to geo-enrich login events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeoIpConfig {
+    /// Path to a GeoIP2/GeoLite2 City database
+    pub city_db_path: Option<String>,
+    /// Path to a GeoLite2 ASN database
+    pub asn_db_path: Option<String>,
+    /// How often to check the files for updates
+    #[serde(default = "default_geoip_refresh")]
+    pub refresh_interval_seconds: u64,
+}
+
+fn default_geoip_refresh() -> u64 {
+    60 * 60
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -108,12 +2636,214 @@ pub struct SmsConfig {
     pub from_number: String,
 }
 
+/// Carrier registration of outbound SMS by destination region, e.g. DLT in
+/// India (sender header, entity and template ids) and 10DLC in the US
+/// (campaign id). A number is routed to the region with the longest
+/// matching calling code; numbers matching no region are sent as-is.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SmsComplianceConfig {
+    /// Keyed by region name, e.g. `in` or `us`
+    #[serde(default)]
+    pub regions: HashMap<String, SmsRegionConfig>,
+    /// Per-tenant regions, keyed by tenant id. A tenant region replaces the
+    /// global region of the same name.
+    #[serde(default)]
+    pub tenants: HashMap<uuid::Uuid, HashMap<String, SmsRegionConfig>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SmsRegionConfig {
+    /// E.164 calling codes without `+`, e.g. `91` or `1`
+    pub calling_codes: Vec<String>,
+    /// Registered sender id or header; the provider default when unset
+    #[serde(default)]
+    pub sender_id: Option<String>,
+    /// DLT principal entity id
+    #[serde(default)]
+    pub entity_id: Option<String>,
+    /// 10DLC campaign id
+    #[serde(default)]
+    pub campaign_id: Option<String>,
+    /// Registered templates keyed by message kind (`otp`)
+    #[serde(default)]
+    pub templates: HashMap<String, SmsTemplate>,
+    /// Refuse messages of a kind without a registered template
+    #[serde(default)]
+    pub require_template: bool,
+}
+
+/// A message template as registered with the carrier. `{#var#}` stands for
+/// a value of up to 30 characters, as in DLT templates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmsTemplate {
+    pub template_id: String,
+    pub body: String,
+}
+
+/// Per-tenant SMS budgets and volume spike alerts, against SMS pumping.
+/// A soft limit raises an alert; at a hard limit SMS is refused and
+/// one-time codes go to the account's email instead where possible.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SmsBudgetConfig {
+    /// Limits for tenants without their own entry
+    #[serde(default)]
+    pub default_limits: SmsBudgetLimits,
+    /// Keyed by tenant id
+    #[serde(default)]
+    pub tenants: HashMap<uuid::Uuid, SmsBudgetLimits>,
+    #[serde(default)]
+    pub anomaly: SmsAnomalyConfig,
+}
+
+/// Messages per UTC day and calendar month; unset limits do not apply
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmsBudgetLimits {
+    #[serde(default)]
+    pub daily_soft: Option<u64>,
+    #[serde(default)]
+    pub daily_hard: Option<u64>,
+    #[serde(default)]
+    pub monthly_soft: Option<u64>,
+    #[serde(default)]
+    pub monthly_hard: Option<u64>,
+}
+
+/// Alerts on sudden volume to one range of numbers, across tenants
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmsAnomalyConfig {
+    /// Leading digits of the E.164 number that form a range, calling code
+    /// included
+    #[serde(default = "default_sms_prefix_digits")]
+    pub prefix_digits: usize,
+    #[serde(default = "default_sms_anomaly_window")]
+    pub window_seconds: u64,
+    /// Messages to a range within one window below which nothing is raised
+    #[serde(default = "default_sms_anomaly_min_messages")]
+    pub min_messages: u32,
+    /// Alert when a window has this many times the range's average
+    #[serde(default = "default_sms_spike_factor")]
+    pub spike_factor: f64,
+}
+
+fn default_sms_prefix_digits() -> usize {
+    5
+}
+
+fn default_sms_anomaly_window() -> u64 {
+    600
+}
+
+fn default_sms_anomaly_min_messages() -> u32 {
+    20
+}
+
+fn default_sms_spike_factor() -> f64 {
+    5.0
+}
+
+impl Default for SmsAnomalyConfig {
+    fn default() -> Self {
+        Self {
+            prefix_digits: default_sms_prefix_digits(),
+            window_seconds: default_sms_anomaly_window(),
+            min_messages: default_sms_anomaly_min_messages(),
+            spike_factor: default_sms_spike_factor(),
+        }
+    }
+}
+
+/// Risk scoring of SMS destinations against toll fraud. Each message is
+/// scored by its number's prefix and the recent volume to its range; at
+/// `quarantine_score` the number is held for manual review, at
+/// `block_score` it is refused outright.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmsRiskConfig {
+    /// E.164 prefixes (calling code first, no `+`) never sent to
+    #[serde(default)]
+    pub blocked_prefixes: Vec<String>,
+    /// Score of numbers under each prefix; the longest match applies
+    #[serde(default)]
+    pub prefix_scores: HashMap<String, u32>,
+    #[serde(default)]
+    pub velocity: SmsVelocityConfig,
+    #[serde(default = "default_sms_quarantine_score")]
+    pub quarantine_score: u32,
+    #[serde(default = "default_sms_block_score")]
+    pub block_score: u32,
+}
+
+fn default_sms_quarantine_score() -> u32 {
+    50
+}
+
+fn default_sms_block_score() -> u32 {
+    100
+}
+
+impl Default for SmsRiskConfig {
+    fn default() -> Self {
+        Self {
+            blocked_prefixes: Vec::new(),
+            prefix_scores: HashMap::new(),
+            velocity: SmsVelocityConfig::default(),
+            quarantine_score: default_sms_quarantine_score(),
+            block_score: default_sms_block_score(),
+        }
+    }
+}
+
+/// Score added while a range of numbers receives more than `max_messages`
+/// within a window, across tenants
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmsVelocityConfig {
+    /// Leading digits of the E.164 number that form a range
+    #[serde(default = "default_sms_velocity_prefix_digits")]
+    pub prefix_digits: usize,
+    #[serde(default = "default_sms_velocity_window")]
+    pub window_seconds: u64,
+    #[serde(default = "default_sms_velocity_max_messages")]
+    pub max_messages: u32,
+    #[serde(default = "default_sms_velocity_score")]
+    pub score: u32,
+}
+
+fn default_sms_velocity_prefix_digits() -> usize {
+    6
+}
+
+fn default_sms_velocity_window() -> u64 {
+    3600
+}
+
+fn default_sms_velocity_max_messages() -> u32 {
+    30
+}
+
+fn default_sms_velocity_score() -> u32 {
+    50
+}
+
+impl Default for SmsVelocityConfig {
+    fn default() -> Self {
+        Self {
+            prefix_digits: default_sms_velocity_prefix_digits(),
+            window_seconds: default_sms_velocity_window(),
+            max_messages: default_sms_velocity_max_messages(),
+            score: default_sms_velocity_score(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct RedisConfig {
     pub url: String,
     pub max_connections: u32,
     pub timeout_seconds: u64,
+    /// Circuit breaking and per-feature behaviour while Redis is down
+    #[serde(default)]
+    #[cfg_attr(test, proptest(value = "auth_cache::DegradationConfig::default()"))]
+    pub degradation: auth_cache::DegradationConfig,
 }
 
 impl Default for AppConfig {
@@ -124,6 +2854,15 @@ impl Default for AppConfig {
                 host: "0.0.0.0".to_string(),
                 port_policy: None, // Will use legacy port field
                 drain_timeout_seconds: 30,
+                deregistration_delay_seconds: 0,
+                replicas: 1,
+                admin: None,
+                radius: None,
+                quotas: QuotaConfig::default(),
+                warmup: WarmupConfig::default(),
+                local_cache: Default::default(),
+                supervisor: Default::default(),
+                config_rollout: ConfigRolloutConfig::default(),
                 workers: None,
                 max_connections: Some(1000),
                 timeout_seconds: Some(30),
@@ -136,6 +2875,11 @@ impl Default for AppConfig {
                 connection_timeout: 30,
                 idle_timeout: 600,
                 max_lifetime: 3600,
+                residency: ResidencyConfig::default(),
+                credentials: DatabaseCredentialsConfig::default(),
+                schema_check: SchemaCheckMode::default(),
+                otp_purge: OtpPurgeConfig::default(),
+                online_migrations: OnlineMigrationsConfig::default(),
             },
             security: SecurityConfig {
                 jwt_secret: secrecy::Secret::new("change-me-in-production".to_string()),
@@ -146,22 +2890,57 @@ impl Default for AppConfig {
                 lockout_duration_minutes: 15,
                 require_mfa: false,
                 allowed_origins: vec!["http://localhost:3000".to_string()],
+                bff: BffConfig::default(),
+                flow_state_key: None,
+                token_ttl: TokenTtlConfig::default(),
+                pii: PiiEncryptionConfig::default(),
+                captcha: CaptchaConfig::default(),
+                service_accounts: ServiceAccountConfig::default(),
+                ssh_ca: SshCaConfig::default(),
+                device_enrollment: DeviceEnrollmentConfig::default(),
+                push_mfa: PushMfaConfig::default(),
+                webauthn: WebauthnConfig::default(),
+                sso: SsoConfig::default(),
+                posture: PostureConfig::default(),
+                jwks: JwksConfig::default(),
+                sessions: SessionStoreConfig::default(),
+                password_expiry: PasswordExpiryConfig::default(),
+                login_links: LoginLinkConfig::default(),
+                takeover_response: TakeoverResponseConfig::default(),
+                identifiers: IdentifierConfig::default(),
+                signup_abuse: SignupAbuseConfig::default(),
+                auth_methods: AuthMethodConfig::default(),
+                otp_policies: OtpPolicyConfig::default(),
+                token_claims: TokenClaimsConfig::default(),
+                remember_me: RememberMeConfig::default(),
             },
             features: FeatureConfig {
                 enabled_features: HashMap::new(),
                 feature_limits: HashMap::new(),
                 tenant_overrides: HashMap::new(),
+                cohorts: CohortConfig::default(),
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
                 format: "json".to_string(),
                 output: "stdout".to_string(),
                 structured: true,
+                audit: AuditPipelineConfig::default(),
+                analytics_export: AnalyticsExportConfig::default(),
+                tenant_metrics: TenantMetricsConfig::default(),
+                login_heatmap: LoginHeatmapConfig::default(),
             },
             external_services: ExternalServicesConfig {
                 smtp: None,
                 sms: None,
                 redis: None,
+                geoip: None,
+                http: Default::default(),
+                webhook_egress: Default::default(),
+                sms_compliance: Default::default(),
+                sms_budget: Default::default(),
+                sms_risk: Default::default(),
+                object_store: Default::default(),
             },
         }
     }
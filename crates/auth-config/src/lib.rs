@@ -6,9 +6,13 @@
 pub mod config;
 pub mod loader;
 pub mod manager;
+pub mod posture;
+pub mod rollout;
 pub mod validation;
 
 pub use config::*;
 pub use loader::*;
 pub use manager::*;
+pub use posture::*;
+pub use rollout::*;
 pub use validation::*;
@@ -5,6 +5,7 @@ use anyhow::Result;
 use config::{Config, ConfigError, Environment, File};
 use std::path::Path;
 
+#[derive(Clone)]
 pub struct ConfigLoader {
     config_dir: String,
     environment: String,
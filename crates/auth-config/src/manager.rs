@@ -1,11 +1,19 @@
 //! Dynamic configuration management with hot-reload capabilities
+//!
+//! Configuration is versioned: the loaded configuration is version 1 and
+//! every applied reload or promoted canary takes the next number. A reload
+//! can also be staged as a canary (see [`crate::rollout`]) and served to a
+//! share of traffic before it replaces the current version.
 
 use crate::config::AppConfig;
 use crate::loader::ConfigLoader;
+use crate::rollout::{compare, Canary, CanaryStatus, CanaryVerdict, RolloutStatus};
 use anyhow::Result;
 use dashmap::DashMap;
 use parking_lot::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::watch;
 use tracing::{error, info, warn};
 
@@ -15,6 +23,8 @@ pub struct ConfigManager {
     config_receiver: watch::Receiver<AppConfig>,
     tenant_overrides: Arc<DashMap<String, serde_json::Value>>,
     loader: ConfigLoader,
+    version: Arc<AtomicU64>,
+    canary: Arc<RwLock<Option<Canary>>>,
 }
 
 impl ConfigManager {
@@ -31,6 +41,8 @@ impl ConfigManager {
             config_receiver,
             tenant_overrides: Arc::new(DashMap::new()),
             loader,
+            version: Arc::new(AtomicU64::new(1)),
+            canary: Arc::default(),
         })
     }
 
@@ -44,6 +56,8 @@ impl ConfigManager {
             config_receiver,
             tenant_overrides: Arc::new(DashMap::new()),
             loader: ConfigLoader::new("config", "test"), // Dummy loader for tests
+            version: Arc::new(AtomicU64::new(1)),
+            canary: Arc::default(),
         })
     }
 
@@ -64,18 +78,8 @@ impl ConfigManager {
                     return Err(anyhow::anyhow!("Invalid configuration: {}", e));
                 }
 
-                // Update the current configuration
-                {
-                    let mut config = self.current_config.write();
-                    *config = new_config.clone();
-                }
-
-                // Notify subscribers
-                if let Err(e) = self.config_sender.send(new_config) {
-                    warn!("Failed to notify configuration subscribers: {}", e);
-                }
-
-                info!("Configuration reloaded successfully");
+                let version = self.apply(new_config);
+                info!("Configuration reloaded successfully as version {}", version);
                 Ok(())
             }
             Err(e) => {
@@ -85,6 +89,179 @@ impl ConfigManager {
         }
     }
 
+    /// Make `config` the current version and notify subscribers
+    fn apply(&self, config: AppConfig) -> u64 {
+        {
+            let mut current = self.current_config.write();
+            *current = config.clone();
+        }
+        let version = self.version.fetch_add(1, Ordering::SeqCst) + 1;
+
+        if let Err(e) = self.config_sender.send(config) {
+            warn!("Failed to notify configuration subscribers: {}", e);
+        }
+        version
+    }
+
+    /// Version of the configuration served outside a canary
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// Stage `config` as the next version, served to `percent` of traffic.
+    /// Fails if it is invalid or a canary is already staged.
+    pub fn stage_canary(&self, config: AppConfig, percent: u8) -> Result<u64> {
+        if let Err(e) = validator::Validate::validate(&config) {
+            return Err(anyhow::anyhow!("Invalid configuration: {}", e));
+        }
+
+        let mut canary = self.canary.write();
+        if let Some(staged) = canary.as_ref() {
+            return Err(anyhow::anyhow!(
+                "Configuration version {} is already being rolled out",
+                staged.version
+            ));
+        }
+        let staged = Canary::new(self.version() + 1, config, percent);
+        info!(
+            "Staged configuration version {} for {}% of traffic",
+            staged.version, staged.percent
+        );
+        let version = staged.version;
+        *canary = Some(staged);
+        Ok(version)
+    }
+
+    /// Load the configuration from its sources and stage it as a canary
+    pub fn stage_reload(&self, percent: u8) -> Result<u64> {
+        let config = self
+            .loader
+            .load()
+            .map_err(|e| anyhow::anyhow!("Configuration reload failed: {}", e))?;
+        self.stage_canary(config, percent)
+    }
+
+    /// Change the share of traffic served the staged version
+    pub fn set_canary_percent(&self, percent: u8) -> Result<()> {
+        match self.canary.write().as_mut() {
+            Some(canary) => {
+                canary.percent = percent.min(100);
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!("No configuration rollout in progress")),
+        }
+    }
+
+    /// The configuration version requests keyed `key` (a tenant id, or any
+    /// other stable request attribute) are served
+    pub fn version_for(&self, key: &str) -> u64 {
+        match self.canary.read().as_ref() {
+            Some(canary) if canary.serves(key) => canary.version,
+            _ => self.version(),
+        }
+    }
+
+    /// The configuration served to requests keyed `key`, with its version
+    pub fn config_for(&self, key: &str) -> (u64, AppConfig) {
+        if let Some(canary) = self.canary.read().as_ref() {
+            if canary.serves(key) {
+                return (canary.version, (*canary.config).clone());
+            }
+        }
+        (self.version(), self.get_config())
+    }
+
+    /// Record the outcome of a request served under `version`. Outcomes are
+    /// only kept while a canary is staged, to compare it with the current
+    /// version.
+    pub fn record_outcome(&self, version: u64, latency: Duration, is_error: bool) {
+        if let Some(canary) = self.canary.read().as_ref() {
+            if version == canary.version {
+                canary.candidate.record(latency, is_error);
+            } else if version == self.version() {
+                canary.baseline.record(latency, is_error);
+            }
+        }
+    }
+
+    /// Compare the staged version with the current one, and roll it back on
+    /// a regression when `server.config_rollout.auto_rollback` is set
+    pub fn evaluate_canary(&self) -> Option<CanaryVerdict> {
+        let policy = self.current_config.read().server.config_rollout.clone();
+        let verdict = {
+            let canary = self.canary.read();
+            let canary = canary.as_ref()?;
+            compare(
+                &canary.baseline.snapshot(),
+                &canary.candidate.snapshot(),
+                &policy,
+            )
+        };
+
+        if let CanaryVerdict::Regressed(reason) = &verdict {
+            if policy.auto_rollback {
+                let _ = self.abort_canary(reason);
+            }
+        }
+        Some(verdict)
+    }
+
+    /// Make the staged version current for all traffic
+    pub fn promote_canary(&self) -> Result<u64> {
+        let canary = self
+            .canary
+            .write()
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("No configuration rollout in progress"))?;
+        let version = self.apply((*canary.config).clone());
+        info!("Promoted configuration version {}", version);
+        Ok(version)
+    }
+
+    /// Drop the staged version; all traffic returns to the current one
+    pub fn abort_canary(&self, reason: &str) -> Result<u64> {
+        let canary = self
+            .canary
+            .write()
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("No configuration rollout in progress"))?;
+        warn!(
+            "Rolled back configuration version {}: {}",
+            canary.version, reason
+        );
+        Ok(canary.version)
+    }
+
+    pub fn rollout_status(&self) -> RolloutStatus {
+        let policy = self.current_config.read().server.config_rollout.clone();
+        let canary = self.canary.read().as_ref().map(|canary| {
+            let (baseline, candidate) = (canary.baseline.snapshot(), canary.candidate.snapshot());
+            CanaryStatus {
+                version: canary.version,
+                percent: canary.percent,
+                started_at: canary.started_at,
+                verdict: compare(&baseline, &candidate, &policy),
+                baseline,
+                candidate,
+            }
+        });
+        RolloutStatus {
+            current_version: self.version(),
+            canary,
+        }
+    }
+
+    /// Evaluate the staged version every `interval`
+    pub async fn run_canary_evaluation(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Some(CanaryVerdict::Healthy) = self.evaluate_canary() {
+                info!("Configuration canary is healthy and can be promoted");
+            }
+        }
+    }
+
     pub fn set_tenant_override(&self, tenant_id: String, key: String, value: serde_json::Value) {
         let mut overrides = self
             .tenant_overrides
@@ -132,7 +309,9 @@ impl Clone for ConfigManager {
             config_sender: self.config_sender.clone(),
             config_receiver: self.config_receiver.clone(),
             tenant_overrides: Arc::clone(&self.tenant_overrides),
-            loader: ConfigLoader::new("config", "development"), // Default values for clone
+            loader: self.loader.clone(),
+            version: Arc::clone(&self.version),
+            canary: Arc::clone(&self.canary),
         }
     }
 }
@@ -160,6 +339,17 @@ mod tests {
                     } else {
                         host
                     },
+                    port_policy: None,
+                    drain_timeout_seconds: 30,
+                    deregistration_delay_seconds: 0,
+                    replicas: 1,
+                    admin: None,
+                    radius: None,
+                    quotas: QuotaConfig::default(),
+                    warmup: WarmupConfig::default(),
+                    local_cache: Default::default(),
+                    supervisor: Default::default(),
+                    config_rollout: ConfigRolloutConfig::default(),
                     workers,
                     max_connections,
                     timeout_seconds,
@@ -198,6 +388,29 @@ mod tests {
                         lockout_duration_minutes,
                         require_mfa,
                         allowed_origins,
+                        bff: BffConfig::default(),
+                        flow_state_key: None,
+                        token_ttl: TokenTtlConfig::default(),
+                        pii: PiiEncryptionConfig::default(),
+                        captcha: CaptchaConfig::default(),
+                        service_accounts: ServiceAccountConfig::default(),
+                        ssh_ca: SshCaConfig::default(),
+                        device_enrollment: DeviceEnrollmentConfig::default(),
+                        push_mfa: PushMfaConfig::default(),
+                        webauthn: WebauthnConfig::default(),
+                        sso: SsoConfig::default(),
+                        posture: PostureConfig::default(),
+                        jwks: JwksConfig::default(),
+                        sessions: SessionStoreConfig::default(),
+                        password_expiry: PasswordExpiryConfig::default(),
+                        login_links: LoginLinkConfig::default(),
+                        takeover_response: TakeoverResponseConfig::default(),
+                        identifiers: IdentifierConfig::default(),
+                        signup_abuse: SignupAbuseConfig::default(),
+                        auth_methods: AuthMethodConfig::default(),
+                        otp_policies: OtpPolicyConfig::default(),
+                        token_claims: TokenClaimsConfig::default(),
+                        remember_me: RememberMeConfig::default(),
                     }
                 },
             )
@@ -232,6 +445,11 @@ mod tests {
                         connection_timeout,
                         idle_timeout,
                         max_lifetime,
+                        residency: Default::default(),
+                        credentials: Default::default(),
+                        schema_check: Default::default(),
+                        otp_purge: Default::default(),
+                        online_migrations: Default::default(),
                     }
                 },
             )
@@ -261,6 +479,7 @@ mod tests {
                     enabled_features,
                     feature_limits,
                     tenant_overrides,
+                    cohorts: Default::default(),
                 }
             })
     }
@@ -277,6 +496,13 @@ mod tests {
                     smtp: None,
                     sms: None,
                     redis: None,
+                    geoip: None,
+                    http: Default::default(),
+                    webhook_egress: Default::default(),
+                    sms_compliance: Default::default(),
+                    sms_budget: Default::default(),
+                    sms_risk: Default::default(),
+                    object_store: Default::default(),
                 }),
                 Just(ExternalServicesConfig {
                     smtp: Some(SmtpConfig {
@@ -288,6 +514,13 @@ mod tests {
                     }),
                     sms: None,
                     redis: None,
+                    geoip: None,
+                    http: Default::default(),
+                    webhook_egress: Default::default(),
+                    sms_compliance: Default::default(),
+                    sms_budget: Default::default(),
+                    sms_risk: Default::default(),
+                    object_store: Default::default(),
                 }),
                 Just(ExternalServicesConfig {
                     smtp: None,
@@ -297,6 +530,13 @@ mod tests {
                         from_number: "+1234567890".to_string(),
                     }),
                     redis: None,
+                    geoip: None,
+                    http: Default::default(),
+                    webhook_egress: Default::default(),
+                    sms_compliance: Default::default(),
+                    sms_budget: Default::default(),
+                    sms_risk: Default::default(),
+                    object_store: Default::default(),
                 }),
                 Just(ExternalServicesConfig {
                     smtp: None,
@@ -305,7 +545,15 @@ mod tests {
                         url: "redis://localhost:6379".to_string(),
                         max_connections: 10,
                         timeout_seconds: 30,
+                        degradation: Default::default(),
                     }),
+                    geoip: None,
+                    http: Default::default(),
+                    webhook_egress: Default::default(),
+                    sms_compliance: Default::default(),
+                    sms_budget: Default::default(),
+                    sms_risk: Default::default(),
+                    object_store: Default::default(),
                 }),
             ],
         )
@@ -389,4 +637,56 @@ mod tests {
             result.unwrap();
         }
     }
+
+    fn rollout_config(port: u16) -> AppConfig {
+        let mut config = AppConfig::default();
+        config.security.jwt_secret =
+            secrecy::Secret::new("a-very-long-and-secure-jwt-secret-at-least-32-chars".to_string());
+        config.server.port = port;
+        config.server.config_rollout.min_requests = 10;
+        config
+    }
+
+    #[test]
+    fn test_canary_promotion() {
+        let manager = ConfigManager::new_with_config(rollout_config(8081)).unwrap();
+        let updates = manager.subscribe();
+        assert_eq!(manager.version(), 1);
+
+        assert_eq!(manager.stage_canary(rollout_config(9090), 100).unwrap(), 2);
+        assert!(manager.stage_canary(rollout_config(9091), 100).is_err());
+        let (version, served) = manager.config_for("tenant-a");
+        assert_eq!((version, served.server.port), (2, 9090));
+        // Outside the canary nothing has changed yet
+        assert_eq!(manager.get_config().server.port, 8081);
+        assert!(!updates.has_changed().unwrap());
+
+        manager.set_canary_percent(0).unwrap();
+        assert_eq!(manager.version_for("tenant-a"), 1);
+
+        assert_eq!(manager.promote_canary().unwrap(), 2);
+        assert_eq!(manager.version(), 2);
+        assert_eq!(manager.get_config().server.port, 9090);
+        assert!(updates.has_changed().unwrap());
+        assert!(manager.rollout_status().canary.is_none());
+    }
+
+    #[test]
+    fn test_canary_regression_is_rolled_back() {
+        let manager = ConfigManager::new_with_config(rollout_config(8081)).unwrap();
+        let version = manager.stage_canary(rollout_config(9090), 50).unwrap();
+        for _ in 0..20 {
+            manager.record_outcome(1, Duration::from_millis(10), false);
+            manager.record_outcome(version, Duration::from_millis(10), true);
+        }
+
+        assert!(matches!(
+            manager.clone().evaluate_canary(),
+            Some(CanaryVerdict::Regressed(_))
+        ));
+        // The clone shares the rollout, so the rollback is seen here too
+        assert!(manager.rollout_status().canary.is_none());
+        assert_eq!(manager.version(), 1);
+        assert_eq!(manager.get_config().server.port, 8081);
+    }
 }
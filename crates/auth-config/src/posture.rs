@@ -0,0 +1,176 @@
+//! Security posture guard
+//!
+//! Startup records which backing component it actually chose for each
+//! security-relevant role. In production every dev-grade choice must be
+//! waived in `security.posture.waive`, otherwise the process refuses to start.
+
+use crate::config::PostureConfig;
+use std::fmt;
+use thiserror::Error;
+
+pub const PRODUCTION: &str = "production";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostureCheck {
+    /// Revocation and refresh token storage
+    TokenStore,
+    /// Where audit events end up
+    AuditSink,
+    /// Shared cache, rate limits and nonces across replicas
+    Redis,
+    /// Email OTP and verification delivery
+    Smtp,
+    /// SMS OTP delivery
+    Sms,
+    /// Declared access of the API routes
+    RoutePolicy,
+}
+
+impl PostureCheck {
+    /// Identifier used in `security.posture.waive`
+    pub fn id(&self) -> &'static str {
+        match self {
+            PostureCheck::TokenStore => "token_store",
+            PostureCheck::AuditSink => "audit_sink",
+            PostureCheck::Redis => "redis",
+            PostureCheck::Smtp => "smtp",
+            PostureCheck::Sms => "sms",
+            PostureCheck::RoutePolicy => "route_policy",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PostureFinding {
+    pub check: PostureCheck,
+    pub secure: bool,
+    /// The component in use, e.g. "in-memory cache"
+    pub detail: String,
+}
+
+#[derive(Debug, Error)]
+#[error("Refusing to start in production with dev-grade components: {}. Fix them or list the ids in security.posture.waive", .checks.join(", "))]
+pub struct PostureError {
+    pub checks: Vec<String>,
+}
+
+pub struct SecurityPosture {
+    environment: String,
+    findings: Vec<PostureFinding>,
+}
+
+impl SecurityPosture {
+    pub fn new(environment: impl Into<String>) -> Self {
+        Self {
+            environment: environment.into(),
+            findings: Vec::new(),
+        }
+    }
+
+    pub fn secure(&mut self, check: PostureCheck, detail: impl Into<String>) -> &mut Self {
+        self.record(check, true, detail)
+    }
+
+    pub fn insecure(&mut self, check: PostureCheck, detail: impl Into<String>) -> &mut Self {
+        self.record(check, false, detail)
+    }
+
+    fn record(
+        &mut self,
+        check: PostureCheck,
+        secure: bool,
+        detail: impl Into<String>,
+    ) -> &mut Self {
+        // Last word wins, e.g. Redis configured but unreachable
+        self.findings.retain(|f| f.check != check);
+        self.findings.push(PostureFinding {
+            check,
+            secure,
+            detail: detail.into(),
+        });
+        self
+    }
+
+    pub fn findings(&self) -> &[PostureFinding] {
+        &self.findings
+    }
+
+    pub fn is_production(&self) -> bool {
+        self.environment == PRODUCTION
+    }
+
+    /// Dev-grade findings not waived by `config`. Outside production nothing
+    /// is enforced.
+    pub fn enforce(&self, config: &PostureConfig) -> Result<(), PostureError> {
+        if !self.is_production() {
+            return Ok(());
+        }
+        let checks: Vec<String> = self
+            .findings
+            .iter()
+            .filter(|f| !f.secure && !config.waive.iter().any(|w| w == f.check.id()))
+            .map(|f| f.check.id().to_string())
+            .collect();
+        if checks.is_empty() {
+            Ok(())
+        } else {
+            Err(PostureError { checks })
+        }
+    }
+}
+
+impl fmt::Display for SecurityPosture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Security posture ({}):", self.environment)?;
+        for finding in &self.findings {
+            writeln!(
+                f,
+                "  [{}] {}: {}",
+                if finding.secure { " OK " } else { "WEAK" },
+                finding.check.id(),
+                finding.detail
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_production_refuses_unwaived_weaknesses() {
+        let mut posture = SecurityPosture::new(PRODUCTION);
+        posture
+            .secure(PostureCheck::TokenStore, "MySQL")
+            .insecure(PostureCheck::AuditSink, "tracing only")
+            .insecure(PostureCheck::Redis, "in-memory cache");
+
+        let err = posture.enforce(&PostureConfig::default()).unwrap_err();
+        assert_eq!(err.checks, vec!["audit_sink", "redis"]);
+
+        let waived = PostureConfig {
+            waive: vec!["audit_sink".to_string(), "redis".to_string()],
+        };
+        assert!(posture.enforce(&waived).is_ok());
+    }
+
+    #[test]
+    fn test_other_environments_only_report() {
+        let mut posture = SecurityPosture::new("development");
+        posture.insecure(PostureCheck::Sms, "stub provider");
+        assert!(posture.enforce(&PostureConfig::default()).is_ok());
+        assert!(posture.to_string().contains("[WEAK] sms: stub provider"));
+    }
+
+    #[test]
+    fn test_later_record_replaces_earlier() {
+        let mut posture = SecurityPosture::new(PRODUCTION);
+        posture
+            .secure(PostureCheck::Redis, "configured")
+            .insecure(PostureCheck::Redis, "unreachable, in-memory fallback");
+        assert_eq!(posture.findings().len(), 1);
+        assert!(posture.enforce(&PostureConfig::default()).is_err());
+    }
+}
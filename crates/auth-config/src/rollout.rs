@@ -0,0 +1,222 @@
+//! Canary rollout of configuration versions
+//!
+//! A reloaded configuration is staged as a candidate next to the current
+//! one. Each request is assigned to a version by a stable hash of its key
+//! (the tenant, where known), so a tenant sees one version for the whole
+//! rollout. Both versions count their requests, errors and latency; the
+//! comparison decides whether the candidate can be promoted or has to be
+//! rolled back.
+
+use crate::config::{AppConfig, ConfigRolloutConfig};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Request outcomes served under one configuration version
+#[derive(Debug, Default)]
+pub struct VersionStats {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    latency_micros: AtomicU64,
+}
+
+impl VersionStats {
+    pub fn record(&self, latency: Duration, is_error: bool) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if is_error {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.latency_micros.fetch_add(
+            u64::try_from(latency.as_micros()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    pub fn snapshot(&self) -> VersionSnapshot {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let errors = self.errors.load(Ordering::Relaxed);
+        let latency_micros = self.latency_micros.load(Ordering::Relaxed);
+        let per_request = |total: u64| {
+            if requests == 0 {
+                0.0
+            } else {
+                total as f64 / requests as f64
+            }
+        };
+        VersionSnapshot {
+            requests,
+            errors,
+            error_rate: per_request(errors),
+            mean_latency_ms: per_request(latency_micros) / 1000.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct VersionSnapshot {
+    pub requests: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub mean_latency_ms: f64,
+}
+
+/// A staged configuration version and the traffic it has served
+#[derive(Debug)]
+pub struct Canary {
+    pub version: u64,
+    pub config: Arc<AppConfig>,
+    pub percent: u8,
+    pub started_at: DateTime<Utc>,
+    /// The current version's outcomes since the canary was staged
+    pub baseline: Arc<VersionStats>,
+    pub candidate: Arc<VersionStats>,
+}
+
+impl Canary {
+    pub fn new(version: u64, config: AppConfig, percent: u8) -> Self {
+        Self {
+            version,
+            config: Arc::new(config),
+            percent: percent.min(100),
+            started_at: Utc::now(),
+            baseline: Arc::default(),
+            candidate: Arc::default(),
+        }
+    }
+
+    /// Whether requests keyed `key` are served the candidate
+    pub fn serves(&self, key: &str) -> bool {
+        bucket(key, self.version) < self.percent
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "verdict", content = "reason", rename_all = "snake_case")]
+pub enum CanaryVerdict {
+    /// Too few requests on either version to compare
+    Pending,
+    Healthy,
+    Regressed(String),
+}
+
+/// Compare the candidate's outcomes with the baseline's
+pub fn compare(
+    baseline: &VersionSnapshot,
+    candidate: &VersionSnapshot,
+    policy: &ConfigRolloutConfig,
+) -> CanaryVerdict {
+    if baseline.requests < policy.min_requests || candidate.requests < policy.min_requests {
+        return CanaryVerdict::Pending;
+    }
+
+    if candidate.error_rate - baseline.error_rate > policy.max_error_rate_increase {
+        return CanaryVerdict::Regressed(format!(
+            "error rate {:.2}% against {:.2}%",
+            candidate.error_rate * 100.0,
+            baseline.error_rate * 100.0
+        ));
+    }
+
+    let latency_limit = baseline.mean_latency_ms * (1.0 + policy.max_latency_increase);
+    if baseline.mean_latency_ms > 0.0 && candidate.mean_latency_ms > latency_limit {
+        return CanaryVerdict::Regressed(format!(
+            "mean latency {:.1}ms against {:.1}ms",
+            candidate.mean_latency_ms, baseline.mean_latency_ms
+        ));
+    }
+
+    CanaryVerdict::Healthy
+}
+
+/// Where a rollout stands, for the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct RolloutStatus {
+    pub current_version: u64,
+    pub canary: Option<CanaryStatus>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CanaryStatus {
+    pub version: u64,
+    pub percent: u8,
+    pub started_at: DateTime<Utc>,
+    pub baseline: VersionSnapshot,
+    pub candidate: VersionSnapshot,
+    pub verdict: CanaryVerdict,
+}
+
+/// Stable bucket in `0..100` for `key`, salted with the version so each
+/// rollout samples a different slice of tenants
+pub fn bucket(key: &str, version: u64) -> u8 {
+    // FNV-1a: stable across processes and releases, unlike `DefaultHasher`
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in key.bytes().chain(version.to_le_bytes()) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    (hash % 100) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(requests: u64, errors: u64, mean_latency_ms: f64) -> VersionSnapshot {
+        VersionSnapshot {
+            requests,
+            errors,
+            error_rate: errors as f64 / requests as f64,
+            mean_latency_ms,
+        }
+    }
+
+    #[test]
+    fn test_bucketing_is_stable_and_proportional() {
+        assert_eq!(bucket("tenant-a", 2), bucket("tenant-a", 2));
+
+        let canary = Canary::new(2, AppConfig::default(), 20);
+        let served = (0..10_000)
+            .filter(|i| canary.serves(&format!("tenant-{}", i)))
+            .count();
+        assert!((1_500..2_500).contains(&served), "served {}", served);
+
+        assert!(!Canary::new(3, AppConfig::default(), 0).serves("tenant-a"));
+        assert!(Canary::new(3, AppConfig::default(), 100).serves("tenant-a"));
+    }
+
+    #[test]
+    fn test_stats_snapshot() {
+        let stats = VersionStats::default();
+        stats.record(Duration::from_millis(10), false);
+        stats.record(Duration::from_millis(30), true);
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.requests, 2);
+        assert_eq!(snapshot.error_rate, 0.5);
+        assert_eq!(snapshot.mean_latency_ms, 20.0);
+    }
+
+    #[test]
+    fn test_compare_flags_regressions() {
+        let policy = ConfigRolloutConfig::default();
+        let baseline = snapshot(1_000, 10, 20.0);
+
+        assert_eq!(
+            compare(&baseline, &snapshot(50, 50, 20.0), &policy),
+            CanaryVerdict::Pending
+        );
+        assert_eq!(
+            compare(&baseline, &snapshot(1_000, 20, 22.0), &policy),
+            CanaryVerdict::Healthy
+        );
+        assert!(matches!(
+            compare(&baseline, &snapshot(1_000, 50, 20.0), &policy),
+            CanaryVerdict::Regressed(_)
+        ));
+        assert!(matches!(
+            compare(&baseline, &snapshot(1_000, 10, 40.0), &policy),
+            CanaryVerdict::Regressed(_)
+        ));
+    }
+}
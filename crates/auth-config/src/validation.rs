@@ -1,7 +1,10 @@
 //! Configuration validation utilities
 
-use crate::config::AppConfig;
+use crate::config::{AppConfig, DatabaseCredentialsSource, MetricNoise, SessionBackend};
+use auth_platform::PortClass;
 use secrecy::ExposeSecret;
+use std::fmt;
+use std::time::Duration;
 use thiserror::Error;
 use validator::{Validate, ValidationErrors};
 
@@ -18,6 +21,9 @@ pub enum ConfigValidationError {
 
     #[error("Feature validation failed: {message}")]
     FeatureValidationFailed { message: String },
+
+    #[error("Logging validation failed: {message}")]
+    LoggingValidationFailed { message: String },
 }
 
 pub struct ConfigValidator;
@@ -36,6 +42,9 @@ impl ConfigValidator {
         // Custom feature validations
         Self::validate_feature_config(config)?;
 
+        // Audit pipeline validations
+        Self::validate_logging_config(config)?;
+
         Ok(())
     }
 
@@ -63,6 +72,341 @@ impl ConfigValidator {
             });
         }
 
+        // Token TTL bounds must form valid ranges
+        let ttl = &security.token_ttl;
+        if ttl.min_access_ttl_seconds > ttl.max_access_ttl_seconds
+            || ttl.min_refresh_ttl_seconds > ttl.max_refresh_ttl_seconds
+        {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "Token TTL minimum must not exceed the maximum".to_string(),
+            });
+        }
+
+        // PII encryption needs both keys, otherwise data written now is unreadable later
+        if security.pii.enabled
+            && (security.pii.master_key_pem.is_none() || security.pii.blind_index_key.is_none())
+        {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "PII encryption requires master_key_pem and blind_index_key".to_string(),
+            });
+        }
+
+        // Every RADIUS client needs a resolvable tenant and secret, or its
+        // packets can be neither verified nor answered
+        if let Some(radius) = &config.server.radius {
+            for client in &radius.clients {
+                let invalid = |reason: &str| ConfigValidationError::SecurityValidationFailed {
+                    message: format!("RADIUS client '{}' {}", client.name, reason),
+                };
+                if client.address.parse::<std::net::IpAddr>().is_err() {
+                    return Err(invalid("has an invalid address"));
+                }
+                if uuid::Uuid::parse_str(&client.tenant_id).is_err() {
+                    return Err(invalid("has an invalid tenant id"));
+                }
+                let secret = client.secret.as_ref().or_else(|| {
+                    radius
+                        .tenants
+                        .get(&client.tenant_id)
+                        .map(|t| &t.shared_secret)
+                });
+                match secret {
+                    Some(secret) if secret.expose_secret().len() >= 16 => {}
+                    _ => return Err(invalid("needs a shared secret of at least 16 characters")),
+                }
+            }
+        }
+
+        // A challenge that cannot be verified server-side would lock everyone out
+        if security.captcha.any_enabled()
+            && (security.captcha.secret_key.is_none() || security.captcha.site_key.is_none())
+        {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "CAPTCHA requires site_key and secret_key".to_string(),
+            });
+        }
+
+        // Keys registered without an expiry must still get a valid one
+        let accounts = &security.service_accounts;
+        if accounts.default_key_lifetime_days == 0
+            || accounts.default_key_lifetime_days > accounts.max_key_lifetime_days
+        {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "Service account default key lifetime must be 1..=max days".to_string(),
+            });
+        }
+
+        let ssh_ca = &security.ssh_ca;
+        if ssh_ca.enabled {
+            if ssh_ca.ca_keys.is_empty() {
+                return Err(ConfigValidationError::SecurityValidationFailed {
+                    message: "SSH CA is enabled but no CA key is configured".to_string(),
+                });
+            }
+            if ssh_ca.default_ttl_seconds == 0
+                || ssh_ca.default_ttl_seconds > ssh_ca.max_ttl_seconds
+            {
+                return Err(ConfigValidationError::SecurityValidationFailed {
+                    message: "SSH certificate default TTL must be 1..=max seconds".to_string(),
+                });
+            }
+        }
+
+        let devices = &security.device_enrollment;
+        if devices.certificate_ttl_days == 0
+            || devices.renewal_window_days >= devices.certificate_ttl_days
+        {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "Device renewal window must be shorter than the certificate lifetime"
+                    .to_string(),
+            });
+        }
+
+        let push = &security.push_mfa;
+        if push.challenge_ttl_seconds == 0 || push.challenge_ttl_seconds > 10 * 60 {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "Push MFA challenge TTL must be 1..=600 seconds".to_string(),
+            });
+        }
+
+        let webauthn = &security.webauthn;
+        if webauthn.rp_id.is_empty() || !webauthn.rp_origin.contains(webauthn.rp_id.as_str()) {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "WebAuthn rp_origin must be on the rp_id domain".to_string(),
+            });
+        }
+        if webauthn.ceremony_ttl_seconds == 0 || webauthn.ceremony_ttl_seconds > 10 * 60 {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "WebAuthn ceremony TTL must be 1..=600 seconds".to_string(),
+            });
+        }
+
+        let sso = &security.sso;
+        if !["Lax", "Strict", "None"].contains(&sso.same_site.as_str()) {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "SSO cookie SameSite must be Lax, Strict or None".to_string(),
+            });
+        }
+        if sso.backchannel_timeout_seconds == 0 || sso.backchannel_timeout_seconds > 30 {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "Back-channel logout timeout must be 1..=30 seconds".to_string(),
+            });
+        }
+        for (id, idp) in &sso.upstream_idps {
+            let https = idp
+                .logout_url
+                .as_deref()
+                .is_some_and(|url| url.starts_with("https://"));
+            if idp.propagate_logout && !https {
+                return Err(ConfigValidationError::SecurityValidationFailed {
+                    message: format!(
+                        "Upstream IdP {id} propagates logout but has no https logout_url"
+                    ),
+                });
+            }
+        }
+
+        let expiry = &security.password_expiry;
+        if expiry.enabled {
+            if !["basic", "enterprise", "high_security", "compliance"]
+                .contains(&expiry.policy.as_str())
+            {
+                return Err(ConfigValidationError::SecurityValidationFailed {
+                    message: format!("Unknown password policy template {}", expiry.policy),
+                });
+            }
+            if expiry.reminder_days.contains(&0) || expiry.scan_interval_seconds == 0 {
+                return Err(ConfigValidationError::SecurityValidationFailed {
+                    message: "Password expiry reminder days and scan interval must be positive"
+                        .to_string(),
+                });
+            }
+        }
+
+        let links = &security.login_links;
+        if links.enabled {
+            if !links.link_url.starts_with("https://") && !links.link_url.starts_with("http://") {
+                return Err(ConfigValidationError::SecurityValidationFailed {
+                    message: "Login link URL must be an http(s) URL".to_string(),
+                });
+            }
+            if links.ttl_seconds == 0 || links.ttl_seconds > 24 * 3600 {
+                return Err(ConfigValidationError::SecurityValidationFailed {
+                    message: "Login link lifetime must be 1 second to 24 hours".to_string(),
+                });
+            }
+            if links.max_per_user == 0 || links.issuance_window_seconds == 0 {
+                return Err(ConfigValidationError::SecurityValidationFailed {
+                    message: "Login link issuance limit and window must be positive".to_string(),
+                });
+            }
+        }
+
+        let remember = &security.remember_me;
+        let durations = std::iter::once(remember.max_duration_days).chain(
+            remember
+                .tenants
+                .values()
+                .filter_map(|policy| policy.max_duration_days),
+        );
+        for days in durations {
+            if days == 0 || days > 365 {
+                return Err(ConfigValidationError::SecurityValidationFailed {
+                    message: "Remember-me duration must be 1 to 365 days".to_string(),
+                });
+            }
+        }
+        if remember.idle_timeout_days == 0 || remember.max_devices_per_user == 0 {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "Remember-me idle timeout and device limit must be positive".to_string(),
+            });
+        }
+
+        let claims = &security.token_claims;
+        if claims.enabled && claims.resolver_timeout_ms == 0 {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "Token claims resolver timeout must be positive".to_string(),
+            });
+        }
+
+        // Names are checked here so a typo cannot leave a tenant without its
+        // playbook until the first takeover
+        let takeover = &security.takeover_response;
+        let known = |name: &str| takeover.playbooks.contains_key(name);
+        if let Some(name) = takeover
+            .default_playbook
+            .as_ref()
+            .filter(|n| !known(n.as_str()))
+        {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: format!("Unknown default takeover playbook {}", name),
+            });
+        }
+        for (tenant_id, tenant) in &takeover.tenants {
+            let invalid = |reason: String| ConfigValidationError::SecurityValidationFailed {
+                message: format!("Takeover response of tenant {} {}", tenant_id, reason),
+            };
+            if uuid::Uuid::parse_str(tenant_id).is_err() {
+                return Err(invalid("is not keyed by a tenant id".to_string()));
+            }
+            if let Some(name) = tenant.playbook.as_ref().filter(|n| !known(n.as_str())) {
+                return Err(invalid(format!("names unknown playbook {}", name)));
+            }
+            if tenant
+                .webhook_url
+                .as_ref()
+                .is_some_and(|url| !url.starts_with("https://"))
+            {
+                return Err(invalid("needs an https webhook URL".to_string()));
+            }
+        }
+        if takeover.playbooks.values().any(|p| p.actions.is_empty()) {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "Takeover playbooks must have at least one action".to_string(),
+            });
+        }
+
+        // Changing the policy of a tenant changes the canonical form of its
+        // users' identifiers; a policy under a bad key would silently apply
+        // to no one
+        let identifiers = &security.identifiers;
+        if let Some(key) = identifiers
+            .tenants
+            .keys()
+            .find(|key| uuid::Uuid::parse_str(key).is_err())
+        {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: format!("Identifier policy {} is not keyed by a tenant id", key),
+            });
+        }
+        if identifiers
+            .dot_folding_domains
+            .iter()
+            .any(|domain| domain.is_empty() || *domain != domain.to_lowercase())
+        {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "Dot-folding domains must be non-empty and lowercase".to_string(),
+            });
+        }
+        let usernames = &identifiers.usernames;
+        for (key, policy) in std::iter::once(("default", &usernames.default_policy))
+            .chain(usernames.tenants.iter().map(|(k, p)| (k.as_str(), p)))
+        {
+            if key != "default" && uuid::Uuid::parse_str(key).is_err() {
+                return Err(ConfigValidationError::SecurityValidationFailed {
+                    message: format!("Username policy {} is not keyed by a tenant id", key),
+                });
+            }
+            if let Err(e) = regex::Regex::new(&policy.pattern) {
+                return Err(ConfigValidationError::SecurityValidationFailed {
+                    message: format!("Username pattern for {} does not compile: {}", key, e),
+                });
+            }
+        }
+
+        // A zero limit would refuse or quarantine every signup
+        let signup = &security.signup_abuse;
+        if let Some(rule) = signup
+            .rules
+            .iter()
+            .find(|rule| rule.max_signups == 0 || rule.window_seconds == 0)
+        {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: format!(
+                    "Signup {} rule needs a positive limit and window",
+                    rule.signal.as_str()
+                ),
+            });
+        }
+        if signup
+            .exempt_domains
+            .iter()
+            .any(|domain| domain.is_empty() || *domain != domain.to_lowercase())
+        {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: "Signup exempt domains must be non-empty and lowercase".to_string(),
+            });
+        }
+
+        let otp = &security.otp_policies;
+        let defaults = &otp.default_policy;
+        let policies = [
+            ("registration", Some(&defaults.registration)),
+            ("login", Some(&defaults.login)),
+            ("verification", Some(&defaults.verification)),
+            ("password_reset", Some(&defaults.password_reset)),
+            ("magic_link", Some(&defaults.magic_link)),
+        ];
+        let overrides = otp.tenants.values().flat_map(|o| {
+            [
+                ("registration", o.registration.as_ref()),
+                ("login", o.login.as_ref()),
+                ("verification", o.verification.as_ref()),
+                ("password_reset", o.password_reset.as_ref()),
+                ("magic_link", o.magic_link.as_ref()),
+            ]
+        });
+        for (purpose, policy) in policies.into_iter().chain(overrides) {
+            let Some(policy) = policy else { continue };
+            if !(4..=64).contains(&policy.length) || policy.ttl_minutes <= 0 {
+                return Err(ConfigValidationError::SecurityValidationFailed {
+                    message: format!(
+                        "OTP policy for {} needs a length of 4 to 64 and a positive lifetime",
+                        purpose
+                    ),
+                });
+            }
+        }
+        if let Some(key) = otp
+            .tenants
+            .keys()
+            .find(|key| uuid::Uuid::parse_str(key).is_err())
+        {
+            return Err(ConfigValidationError::SecurityValidationFailed {
+                message: format!("OTP policy {} is not keyed by a tenant id", key),
+            });
+        }
+
         Ok(())
     }
 
@@ -84,12 +428,130 @@ impl ConfigValidator {
             });
         }
 
+        // Further regions are only meaningful next to a region of our own,
+        // which mysql_url already serves
+        let residency = &db.residency;
+        match &residency.region {
+            None if !residency.regions.is_empty() => {
+                return Err(ConfigValidationError::DatabaseValidationFailed {
+                    message: "Residency regions require database.residency.region".to_string(),
+                });
+            }
+            Some(region) if residency.regions.contains_key(region) => {
+                return Err(ConfigValidationError::DatabaseValidationFailed {
+                    message: format!(
+                        "Residency region '{}' is served by mysql_url and must not be listed in regions",
+                        region
+                    ),
+                });
+            }
+            _ => {}
+        }
+
+        let credentials = &db.credentials;
+        if credentials.source == DatabaseCredentialsSource::File {
+            if credentials.file_path.is_none() {
+                return Err(ConfigValidationError::DatabaseValidationFailed {
+                    message: "File database credentials require database.credentials.file_path"
+                        .to_string(),
+                });
+            }
+            if credentials.refresh_interval_seconds == 0 {
+                return Err(ConfigValidationError::DatabaseValidationFailed {
+                    message: "database.credentials.refresh_interval_seconds must be positive"
+                        .to_string(),
+                });
+            }
+        }
+
+        // One batch holds row locks for as long as it runs
+        let purge = &db.otp_purge;
+        if purge.batch_size == 0 || purge.batch_size > 10_000 {
+            return Err(ConfigValidationError::DatabaseValidationFailed {
+                message: "OTP purge batch size must be 1..=10000 rows".to_string(),
+            });
+        }
+        if purge.interval_seconds == 0 || purge.bucket_seconds == 0 || purge.max_rows_per_run == 0 {
+            return Err(ConfigValidationError::DatabaseValidationFailed {
+                message: "OTP purge interval, bucket and per-run limit must be positive"
+                    .to_string(),
+            });
+        }
+
+        let migrations = &db.online_migrations;
+        if migrations.refresh_seconds == 0 || migrations.max_concurrent_comparisons == 0 {
+            return Err(ConfigValidationError::DatabaseValidationFailed {
+                message: "Online migration refresh and comparison limit must be positive"
+                    .to_string(),
+            });
+        }
+        if migrations.phases.keys().any(|name| name.trim().is_empty()) {
+            return Err(ConfigValidationError::DatabaseValidationFailed {
+                message: "Online migration names must not be blank".to_string(),
+            });
+        }
+
         Ok(())
     }
 
     fn validate_feature_config(config: &AppConfig) -> Result<(), ConfigValidationError> {
         let features = &config.features;
 
+        let adaptive = &config.server.quotas.adaptive;
+        if adaptive.enabled {
+            if !(adaptive.min_factor > 0.0
+                && adaptive.min_factor <= 1.0
+                && adaptive.max_factor >= 1.0)
+            {
+                return Err(ConfigValidationError::FeatureValidationFailed {
+                    message: "Adaptive quotas need 0 < min_factor <= 1 <= max_factor".to_string(),
+                });
+            }
+            if !(adaptive.learning_rate > 0.0 && adaptive.learning_rate <= 1.0) {
+                return Err(ConfigValidationError::FeatureValidationFailed {
+                    message: "Adaptive quota learning_rate must be in (0, 1]".to_string(),
+                });
+            }
+            if adaptive.anomaly_multiplier <= adaptive.headroom {
+                return Err(ConfigValidationError::FeatureValidationFailed {
+                    message: "Adaptive quota anomaly_multiplier must exceed headroom".to_string(),
+                });
+            }
+        }
+
+        let cohorts = &features.cohorts;
+        for (name, percent) in &cohorts.cohorts {
+            if !(*percent > 0.0 && *percent <= 100.0) {
+                return Err(ConfigValidationError::FeatureValidationFailed {
+                    message: format!(
+                        "Cohort '{}' must cover more than 0 and at most 100 percent",
+                        name
+                    ),
+                });
+            }
+        }
+        for (feature, cohort) in &cohorts.targets {
+            if !cohorts.cohorts.contains_key(cohort) {
+                return Err(ConfigValidationError::FeatureValidationFailed {
+                    message: format!("Feature '{}' targets unknown cohort '{}'", feature, cohort),
+                });
+            }
+        }
+        let salted = cohorts
+            .salt
+            .as_ref()
+            .is_some_and(|salt| salt.expose_secret().len() >= 16);
+        if !cohorts.targets.is_empty() && !salted {
+            return Err(ConfigValidationError::FeatureValidationFailed {
+                message: "Cohort targeting needs a salt of at least 16 characters".to_string(),
+            });
+        }
+        if cohorts.kill_switch_refresh_seconds == 0 {
+            return Err(ConfigValidationError::FeatureValidationFailed {
+                message: "Kill switch refresh interval cannot be zero".to_string(),
+            });
+        }
+
         // Validate feature limits are reasonable
         for (feature, limit) in &features.feature_limits {
             if *limit == 0 {
@@ -101,12 +563,533 @@ impl ConfigValidator {
 
         Ok(())
     }
+
+    fn validate_logging_config(config: &AppConfig) -> Result<(), ConfigValidationError> {
+        let audit = &config.logging.audit;
+
+        if audit.capacity == 0 {
+            return Err(ConfigValidationError::LoggingValidationFailed {
+                message: "Audit queue capacity must be greater than zero".to_string(),
+            });
+        }
+
+        if !(audit.high_watermark > 0.0 && audit.high_watermark <= 1.0) {
+            return Err(ConfigValidationError::LoggingValidationFailed {
+                message: "Audit high watermark must be in (0, 1]".to_string(),
+            });
+        }
+
+        if let Some(wal) = &audit.wal {
+            if wal.segment_bytes == 0 || wal.segment_bytes > wal.max_bytes {
+                return Err(ConfigValidationError::LoggingValidationFailed {
+                    message: "Audit WAL segment size must be non-zero and within max_bytes"
+                        .to_string(),
+                });
+            }
+        }
+
+        let routing = &audit.routing;
+        if std::iter::once(&routing.info_sample_rate)
+            .chain(routing.sample_rates.values())
+            .any(|rate| !(0.0..=1.0).contains(rate))
+        {
+            return Err(ConfigValidationError::LoggingValidationFailed {
+                message: "Audit sample rates must be in [0, 1]".to_string(),
+            });
+        }
+
+        let export = &config.logging.analytics_export;
+        if export.enabled {
+            if export.interval_seconds == 0 || export.max_rows_per_file == 0 {
+                return Err(ConfigValidationError::LoggingValidationFailed {
+                    message: "Analytics export interval and rows per file must be positive"
+                        .to_string(),
+                });
+            }
+            if export.prefix.starts_with('/') || export.prefix.split('/').any(|s| s == "..") {
+                return Err(ConfigValidationError::LoggingValidationFailed {
+                    message: "Analytics export prefix must be a relative path".to_string(),
+                });
+            }
+            // Exports are written there
+            config
+                .external_services
+                .object_store
+                .validate()
+                .map_err(|e| ConfigValidationError::LoggingValidationFailed {
+                    message: format!("Analytics export: {}", e),
+                })?;
+        }
+
+        let metrics = &config.logging.tenant_metrics;
+        if metrics.window_days == 0 {
+            return Err(ConfigValidationError::LoggingValidationFailed {
+                message: "Tenant metrics window must be at least one day".to_string(),
+            });
+        }
+        for (class, disclosure) in [
+            ("population", &metrics.population),
+            ("activity", &metrics.activity),
+            ("security", &metrics.security),
+        ] {
+            if disclosure.round_to == 0 {
+                return Err(ConfigValidationError::LoggingValidationFailed {
+                    message: format!("Tenant metrics {}: round_to must be at least 1", class),
+                });
+            }
+            if let MetricNoise::Laplace { epsilon } = disclosure.noise {
+                if !(epsilon.is_finite() && epsilon > 0.0) {
+                    return Err(ConfigValidationError::LoggingValidationFailed {
+                        message: format!("Tenant metrics {}: epsilon must be positive", class),
+                    });
+                }
+            }
+        }
+
+        let heatmap = &config.logging.login_heatmap;
+        if heatmap.enabled && heatmap.interval_seconds == 0 {
+            return Err(ConfigValidationError::LoggingValidationFailed {
+                message: "Login heatmap interval must be positive".to_string(),
+            });
+        }
+        if heatmap.max_query_days == 0 {
+            return Err(ConfigValidationError::LoggingValidationFailed {
+                message: "Login heatmap queries must be allowed at least one day".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Time allowed for each dependency to accept a TCP connection
+const SMOKE_TEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticLevel {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub level: DiagnosticLevel,
+    /// Config path or dependency the finding is about, e.g. `server.port_policy`
+    pub check: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(level: DiagnosticLevel, check: &str, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            check: check.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Result of `ConfigValidator::preflight`, printed at startup
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticsReport {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl DiagnosticsReport {
+    pub fn has_failures(&self) -> bool {
+        self.count(DiagnosticLevel::Fail) > 0
+    }
+
+    pub fn count(&self, level: DiagnosticLevel) -> usize {
+        self.diagnostics.iter().filter(|d| d.level == level).count()
+    }
+
+    fn push(&mut self, level: DiagnosticLevel, check: &str, message: impl Into<String>) {
+        self.diagnostics
+            .push(Diagnostic::new(level, check, message));
+    }
+}
+
+impl fmt::Display for DiagnosticsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Configuration preflight: {} failed, {} warnings, {} passed",
+            self.count(DiagnosticLevel::Fail),
+            self.count(DiagnosticLevel::Warn),
+            self.count(DiagnosticLevel::Pass)
+        )?;
+        // Failures first so they are not lost below a long list of passes
+        let mut sorted: Vec<&Diagnostic> = self.diagnostics.iter().collect();
+        sorted.sort_by_key(|d| std::cmp::Reverse(d.level));
+        for d in sorted {
+            let tag = match d.level {
+                DiagnosticLevel::Pass => " OK ",
+                DiagnosticLevel::Warn => "WARN",
+                DiagnosticLevel::Fail => "FAIL",
+            };
+            writeln!(f, "  [{}] {}: {}", tag, d.check, d.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl ConfigValidator {
+    /// Everything `validate_config` checks, plus cross-field checks and
+    /// connection smoke tests, collected instead of stopping at the first
+    /// problem
+    pub async fn preflight(config: &AppConfig) -> DiagnosticsReport {
+        let mut report = Self::static_checks(config);
+        report
+            .diagnostics
+            .extend(Self::smoke_test_connections(config).await);
+        report
+    }
+
+    /// The checks that need no network access
+    pub fn static_checks(config: &AppConfig) -> DiagnosticsReport {
+        let mut report = DiagnosticsReport::default();
+
+        match Self::validate_config(config) {
+            Ok(()) => report.push(DiagnosticLevel::Pass, "config", "Field validation passed"),
+            Err(e) => report.push(DiagnosticLevel::Fail, "config", e.to_string()),
+        }
+
+        Self::check_port_policy(config, &mut report);
+        Self::check_shared_state(config, &mut report);
+        Self::check_delivery(config, &mut report);
+        Self::check_outbound_http(config, &mut report);
+        Self::check_object_store(config, &mut report);
+        Self::check_tenant_metrics(config, &mut report);
+        Self::check_quotas(config, &mut report);
+        report
+    }
+
+    fn check_port_policy(config: &AppConfig, report: &mut DiagnosticsReport) {
+        const CHECK: &str = "server.port_policy";
+        let policy = config.server.effective_port_policy();
+
+        if let Err(e) = policy.validate() {
+            report.push(DiagnosticLevel::Fail, CHECK, e.to_string());
+            return;
+        }
+
+        if let Err(e) = auth_platform::bind_addrs(
+            &config.server.host,
+            policy.preferred_port,
+            policy.dual_stack,
+        ) {
+            report.push(DiagnosticLevel::Fail, "server.host", e.to_string());
+            return;
+        }
+
+        match (&policy.fallback_range, policy.class) {
+            (Some(_), PortClass::Internal) => report.push(
+                DiagnosticLevel::Warn,
+                CHECK,
+                "Internal listener may move to a fallback port; scrapers and probes must discover it",
+            ),
+            (None, PortClass::Public) => report.push(
+                DiagnosticLevel::Warn,
+                CHECK,
+                format!(
+                    "No fallback range: startup fails if port {} is taken",
+                    policy.preferred_port
+                ),
+            ),
+            _ => {}
+        }
+
+        if let Some(range) = &policy.fallback_range {
+            if range.contains(&policy.preferred_port) {
+                report.push(
+                    DiagnosticLevel::Warn,
+                    CHECK,
+                    "Fallback range includes the preferred port",
+                );
+            }
+        }
+
+        if let Some(admin) = &config.server.admin {
+            if policy.candidate_ports().contains(&admin.port) {
+                report.push(
+                    DiagnosticLevel::Fail,
+                    "server.admin.port",
+                    format!(
+                        "Admin port {} overlaps the public listener's candidate ports",
+                        admin.port
+                    ),
+                );
+                return;
+            }
+        }
+
+        report.push(
+            DiagnosticLevel::Pass,
+            CHECK,
+            format!(
+                "{:?} listener on port {}{}",
+                policy.class,
+                policy.preferred_port,
+                if policy.dual_stack {
+                    " (IPv4 and IPv6)"
+                } else {
+                    ""
+                }
+            ),
+        );
+    }
+
+    fn check_shared_state(config: &AppConfig, report: &mut DiagnosticsReport) {
+        const CHECK: &str = "external_services.redis";
+        let replicas = config.server.replicas;
+        match (&config.external_services.redis, replicas > 1) {
+            (None, true) => report.push(
+                DiagnosticLevel::Fail,
+                CHECK,
+                format!(
+                    "{} replicas configured but no Redis: rate limits, nonces and caches would diverge per instance",
+                    replicas
+                ),
+            ),
+            (None, false) => report.push(
+                DiagnosticLevel::Warn,
+                CHECK,
+                "Not configured; in-memory state only works for a single instance",
+            ),
+            (Some(_), _) => report.push(DiagnosticLevel::Pass, CHECK, "Configured"),
+        }
+
+        if config.security.sessions.backend == SessionBackend::Redis
+            && config.external_services.redis.is_none()
+        {
+            report.push(
+                DiagnosticLevel::Fail,
+                "security.sessions.backend",
+                "Redis session store selected but external_services.redis is not configured",
+            );
+        }
+    }
+
+    fn check_delivery(config: &AppConfig, report: &mut DiagnosticsReport) {
+        let services = &config.external_services;
+        if services.smtp.is_none() {
+            report.push(
+                DiagnosticLevel::Warn,
+                "external_services.smtp",
+                "Not configured; email OTPs and verification links cannot be sent",
+            );
+        }
+        if services.sms.is_none() {
+            report.push(
+                DiagnosticLevel::Warn,
+                "external_services.sms",
+                "Not configured; SMS OTPs cannot be sent",
+            );
+        }
+    }
+
+    /// Builds the shared client, which loads the proxy and pinned CAs
+    fn check_outbound_http(config: &AppConfig, report: &mut DiagnosticsReport) {
+        const CHECK: &str = "external_services.http";
+        let http = &config.external_services.http;
+        if let Err(e) = auth_platform::HttpClient::new(http.clone()) {
+            report.push(DiagnosticLevel::Fail, CHECK, e.to_string());
+            return;
+        }
+        let proxy = http.proxy.as_deref().unwrap_or("direct");
+        let tls = match http.pinned_ca_certs.len() {
+            0 => "built-in roots".to_string(),
+            n => format!("{} pinned CAs", n),
+        };
+        report.push(
+            DiagnosticLevel::Pass,
+            CHECK,
+            format!("Egress {}, TLS trusts {}", proxy, tls),
+        );
+    }
+
+    fn check_object_store(config: &AppConfig, report: &mut DiagnosticsReport) {
+        const CHECK: &str = "external_services.object_store";
+        let store = &config.external_services.object_store;
+        if let Err(e) = store.validate() {
+            report.push(DiagnosticLevel::Fail, CHECK, e.to_string());
+            return;
+        }
+        let backend = match &store.backend {
+            auth_platform::ObjectStoreBackend::Local { root } => {
+                format!("Local directory {}", root)
+            }
+            auth_platform::ObjectStoreBackend::S3(s3) => {
+                format!("Bucket {} at {}", s3.bucket, s3.endpoint)
+            }
+        };
+        report.push(DiagnosticLevel::Pass, CHECK, backend);
+    }
+
+    fn check_tenant_metrics(config: &AppConfig, report: &mut DiagnosticsReport) {
+        const CHECK: &str = "logging.tenant_metrics";
+        let metrics = &config.logging.tenant_metrics;
+        let noised = [&metrics.population, &metrics.activity, &metrics.security]
+            .iter()
+            .any(|d| d.noise != MetricNoise::None);
+        if noised && metrics.noise_key.is_none() {
+            report.push(
+                DiagnosticLevel::Warn,
+                CHECK,
+                "No noise_key; each replica noises values differently",
+            );
+            return;
+        }
+        report.push(
+            DiagnosticLevel::Pass,
+            CHECK,
+            format!("{}-day window", metrics.window_days),
+        );
+    }
+
+    fn check_quotas(config: &AppConfig, report: &mut DiagnosticsReport) {
+        const CHECK: &str = "server.quotas";
+        let quotas = &config.server.quotas;
+        if !quotas.enabled {
+            report.push(
+                DiagnosticLevel::Warn,
+                CHECK,
+                "Disabled; no tenant is throttled",
+            );
+            return;
+        }
+        if !quotas.tiers.contains_key(&quotas.default_tier) {
+            report.push(
+                DiagnosticLevel::Fail,
+                CHECK,
+                format!("Default tier '{}' is not defined", quotas.default_tier),
+            );
+            return;
+        }
+        if let Some((name, _)) = quotas
+            .tiers
+            .iter()
+            .find(|(_, t)| t.requests_per_minute == 0 || t.max_concurrent == 0)
+        {
+            report.push(
+                DiagnosticLevel::Fail,
+                CHECK,
+                format!("Tier '{}' would reject every request", name),
+            );
+            return;
+        }
+        if config.server.replicas > 1 {
+            report.push(
+                DiagnosticLevel::Warn,
+                CHECK,
+                "Quotas are counted per instance; each replica admits the full tier limit",
+            );
+            return;
+        }
+        report.push(
+            DiagnosticLevel::Pass,
+            CHECK,
+            format!("{} tiers", quotas.tiers.len()),
+        );
+    }
+
+    /// TCP reachability of MySQL, Redis and SMTP. Credentials are not
+    /// exercised; the pools report those with their own errors.
+    pub async fn smoke_test_connections(config: &AppConfig) -> Vec<Diagnostic> {
+        let mut targets = Vec::new();
+        targets.push((
+            "database.mysql_url",
+            endpoint_of(config.database.mysql_url.expose_secret(), 3306),
+        ));
+        if let Some(redis) = &config.external_services.redis {
+            targets.push(("external_services.redis", endpoint_of(&redis.url, 6379)));
+        }
+        if let Some(smtp) = &config.external_services.smtp {
+            targets.push((
+                "external_services.smtp",
+                Some((smtp.host.clone(), smtp.port)),
+            ));
+        }
+
+        let mut diagnostics = Vec::new();
+        for (check, endpoint) in targets {
+            let Some((host, port)) = endpoint else {
+                diagnostics.push(Diagnostic::new(
+                    DiagnosticLevel::Fail,
+                    check,
+                    "Cannot parse host and port from URL",
+                ));
+                continue;
+            };
+            let connect = tokio::net::TcpStream::connect((host.as_str(), port));
+            let diagnostic = match tokio::time::timeout(SMOKE_TEST_TIMEOUT, connect).await {
+                Ok(Ok(_)) => Diagnostic::new(
+                    DiagnosticLevel::Pass,
+                    check,
+                    format!("{}:{} reachable", host, port),
+                ),
+                Ok(Err(e)) => Diagnostic::new(
+                    DiagnosticLevel::Fail,
+                    check,
+                    format!("{}:{} unreachable: {}", host, port, e),
+                ),
+                Err(_) => Diagnostic::new(
+                    DiagnosticLevel::Fail,
+                    check,
+                    format!(
+                        "{}:{} did not answer within {}s",
+                        host,
+                        port,
+                        SMOKE_TEST_TIMEOUT.as_secs()
+                    ),
+                ),
+            };
+            diagnostics.push(diagnostic);
+        }
+        diagnostics
+    }
+}
+
+/// `host` and `port` of a `scheme://[user[:pass]@]host[:port][/...]` URL
+fn endpoint_of(url: &str, default_port: u16) -> Option<(String, u16)> {
+    let rest = url.split_once("://")?.1;
+    let authority = rest.split(['/', '?']).next()?;
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, hp)| hp);
+
+    let (host, port) = match host_port.strip_prefix('[') {
+        // IPv6 literal
+        Some(v6) => {
+            let (host, after) = v6.split_once(']')?;
+            (host, after.strip_prefix(':'))
+        }
+        None => match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_port, None),
+        },
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let port = match port {
+        Some(p) => p.parse().ok()?,
+        None => default_port,
+    };
+    Some((host.to_string(), port))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{
+        CaptchaTenantOverride, IdentifierPolicy, NasClientConfig, OtpAlphabet, OtpPolicy,
+        OtpPolicyOverrides, RadiusServerConfig, RadiusTenantConfig, RegionDatabaseConfig,
+        RememberMePolicy, SignupAbuseConfig, TakeoverAction, TakeoverPlaybook,
+        TakeoverTenantConfig, UpstreamIdpConfig, UpstreamProtocol, UsernamePolicy,
+    };
     use secrecy::Secret;
+    use std::collections::HashMap;
 
     fn valid_test_config() -> AppConfig {
         let mut config = AppConfig::default();
@@ -150,6 +1133,292 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_invalid_token_ttl_bounds() {
+        let mut config = valid_test_config();
+        config.security.token_ttl.min_access_ttl_seconds = 7200;
+
+        let result = ConfigValidator::validate_config(&config);
+        match result {
+            Err(ConfigValidationError::SecurityValidationFailed { message }) => {
+                assert!(message.contains("Token TTL minimum must not exceed the maximum"));
+            }
+            _ => panic!("Expected SecurityValidationFailed error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_captcha_tenant_opt_in_requires_keys() {
+        let mut config = valid_test_config();
+        config.security.captcha.tenants.insert(
+            "6f9619ff-8b86-d011-b42d-00c04fc964ff".to_string(),
+            CaptchaTenantOverride {
+                enabled: Some(true),
+                ..Default::default()
+            },
+        );
+
+        let result = ConfigValidator::validate_config(&config);
+        match result {
+            Err(ConfigValidationError::SecurityValidationFailed { message }) => {
+                assert!(message.contains("CAPTCHA requires site_key and secret_key"));
+            }
+            _ => panic!("Expected SecurityValidationFailed error, got {:?}", result),
+        }
+
+        config.security.captcha.site_key = Some("site".to_string());
+        config.security.captcha.secret_key = Some(Secret::new("secret".to_string()));
+        assert!(ConfigValidator::validate_config(&config).is_ok());
+    }
+
+    #[test]
+    fn test_identifier_policies_are_keyed_by_tenant() {
+        let mut config = valid_test_config();
+        config
+            .security
+            .identifiers
+            .tenants
+            .insert("acme".to_string(), IdentifierPolicy::default());
+        match ConfigValidator::validate_config(&config) {
+            Err(ConfigValidationError::SecurityValidationFailed { message }) => {
+                assert!(message.contains("acme"));
+            }
+            result => panic!("Expected SecurityValidationFailed error, got {:?}", result),
+        }
+
+        config.security.identifiers.tenants.clear();
+        config.security.identifiers.dot_folding_domains = vec!["Gmail.com".to_string()];
+        assert!(ConfigValidator::validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_signup_rules_need_limits() {
+        let mut config = valid_test_config();
+        config.security.signup_abuse.rules[0].window_seconds = 0;
+        match ConfigValidator::validate_config(&config) {
+            Err(ConfigValidationError::SecurityValidationFailed { message }) => {
+                assert!(message.contains("Signup ip rule"));
+            }
+            result => panic!("Expected SecurityValidationFailed error, got {:?}", result),
+        }
+
+        config.security.signup_abuse = SignupAbuseConfig::default();
+        config.security.signup_abuse.exempt_domains = vec!["Gmail.com".to_string()];
+        assert!(ConfigValidator::validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_otp_policies_need_length_and_lifetime() {
+        let mut config = valid_test_config();
+        config.security.otp_policies.tenants.insert(
+            "6f9619ff-8b86-d011-b42d-00c04fc964ff".to_string(),
+            OtpPolicyOverrides {
+                password_reset: Some(OtpPolicy {
+                    length: 2,
+                    ttl_minutes: 5,
+                    alphabet: OtpAlphabet::Numeric,
+                }),
+                ..Default::default()
+            },
+        );
+        match ConfigValidator::validate_config(&config) {
+            Err(ConfigValidationError::SecurityValidationFailed { message }) => {
+                assert!(message.contains("password_reset"));
+            }
+            result => panic!("Expected SecurityValidationFailed error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_remember_me_duration_is_bounded() {
+        let mut config = valid_test_config();
+        config.security.remember_me.tenants.insert(
+            "6f9619ff-8b86-d011-b42d-00c04fc964ff".to_string(),
+            RememberMePolicy {
+                enabled: true,
+                max_duration_days: Some(400),
+            },
+        );
+        match ConfigValidator::validate_config(&config) {
+            Err(ConfigValidationError::SecurityValidationFailed { message }) => {
+                assert!(message.contains("Remember-me"));
+            }
+            result => panic!("Expected SecurityValidationFailed error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_propagated_logout_needs_a_logout_url() {
+        let mut config = valid_test_config();
+        config.security.sso.upstream_idps.insert(
+            "adfs".to_string(),
+            UpstreamIdpConfig {
+                display_name: "AD FS".to_string(),
+                protocol: UpstreamProtocol::Saml,
+                propagate_logout: true,
+                logout_url: None,
+                client_id: "https://auth.example.com/saml".to_string(),
+                signing_key_pem: None,
+            },
+        );
+        match ConfigValidator::validate_config(&config) {
+            Err(ConfigValidationError::SecurityValidationFailed { message }) => {
+                assert!(message.contains("adfs"));
+            }
+            result => panic!("Expected SecurityValidationFailed error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_username_patterns_must_compile() {
+        let tenant_id = "6f9619ff-8b86-d011-b42d-00c04fc964ff".to_string();
+        let mut config = valid_test_config();
+        let policy = UsernamePolicy {
+            enabled: true,
+            pattern: "^[a-z(".to_string(),
+            ..Default::default()
+        };
+        config
+            .security
+            .identifiers
+            .usernames
+            .tenants
+            .insert(tenant_id.clone(), policy.clone());
+        match ConfigValidator::validate_config(&config) {
+            Err(ConfigValidationError::SecurityValidationFailed { message }) => {
+                assert!(message.contains(&tenant_id));
+            }
+            result => panic!("Expected SecurityValidationFailed error, got {:?}", result),
+        }
+
+        let usernames = &mut config.security.identifiers.usernames;
+        usernames.tenants.clear();
+        usernames.tenants.insert(
+            "acme".to_string(),
+            UsernamePolicy {
+                pattern: "^[a-z]+$".to_string(),
+                ..policy
+            },
+        );
+        assert!(ConfigValidator::validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_takeover_playbooks_must_be_known() {
+        let tenant_id = "6f9619ff-8b86-d011-b42d-00c04fc964ff".to_string();
+        let mut config = valid_test_config();
+        let takeover = &mut config.security.takeover_response;
+        takeover.playbooks.insert(
+            "contain".to_string(),
+            TakeoverPlaybook {
+                actions: vec![TakeoverAction::RevokeSessions, TakeoverAction::RaiseWebhook],
+                dry_run: true,
+            },
+        );
+        takeover.tenants.insert(
+            tenant_id.clone(),
+            TakeoverTenantConfig {
+                playbook: Some("contian".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let result = ConfigValidator::validate_config(&config);
+        match result {
+            Err(ConfigValidationError::SecurityValidationFailed { message }) => {
+                assert!(message.contains("names unknown playbook contian"));
+            }
+            _ => panic!("Expected SecurityValidationFailed error, got {:?}", result),
+        }
+
+        let tenant = config
+            .security
+            .takeover_response
+            .tenants
+            .get_mut(&tenant_id)
+            .unwrap();
+        tenant.playbook = Some("contain".to_string());
+        tenant.webhook_url = Some("http://hooks.example.com".to_string());
+        assert!(ConfigValidator::validate_config(&config).is_err());
+
+        config
+            .security
+            .takeover_response
+            .tenants
+            .get_mut(&tenant_id)
+            .unwrap()
+            .webhook_url = Some("https://hooks.example.com".to_string());
+        assert!(ConfigValidator::validate_config(&config).is_ok());
+    }
+
+    #[test]
+    fn test_radius_client_requires_secret() {
+        let tenant_id = "6f9619ff-8b86-d011-b42d-00c04fc964ff".to_string();
+        let mut config = valid_test_config();
+        config.server.radius = Some(RadiusServerConfig {
+            host: "0.0.0.0".to_string(),
+            auth_port: 1812,
+            acct_port: 1813,
+            tenants: HashMap::new(),
+            clients: vec![NasClientConfig {
+                name: "vpn-1".to_string(),
+                address: "10.0.0.5".to_string(),
+                tenant_id: tenant_id.clone(),
+                secret: None,
+            }],
+        });
+        assert!(matches!(
+            ConfigValidator::validate_config(&config),
+            Err(ConfigValidationError::SecurityValidationFailed { .. })
+        ));
+
+        config.server.radius.as_mut().unwrap().tenants.insert(
+            tenant_id,
+            RadiusTenantConfig {
+                shared_secret: Secret::new("a-long-radius-shared-secret".to_string()),
+            },
+        );
+        assert!(ConfigValidator::validate_config(&config).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_audit_pipeline() {
+        let mut config = valid_test_config();
+        config.logging.audit.high_watermark = 1.5;
+
+        let result = ConfigValidator::validate_config(&config);
+        assert!(matches!(
+            result,
+            Err(ConfigValidationError::LoggingValidationFailed { .. })
+        ));
+
+        let mut config = valid_test_config();
+        config
+            .logging
+            .audit
+            .routing
+            .sample_rates
+            .insert("HTTP ".to_string(), 2.0);
+        assert!(matches!(
+            ConfigValidator::validate_config(&config),
+            Err(ConfigValidationError::LoggingValidationFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_pii_encryption_requires_keys() {
+        let mut config = valid_test_config();
+        config.security.pii.enabled = true;
+
+        let result = ConfigValidator::validate_config(&config);
+        match result {
+            Err(ConfigValidationError::SecurityValidationFailed { message }) => {
+                assert!(message.contains("PII encryption requires"));
+            }
+            _ => panic!("Expected SecurityValidationFailed error, got {:?}", result),
+        }
+    }
+
     #[test]
     fn test_invalid_password_length() {
         let mut config = valid_test_config();
@@ -194,6 +1463,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_residency_regions_need_a_home_region() {
+        let mut config = valid_test_config();
+        config.database.residency.regions.insert(
+            "eu".to_string(),
+            RegionDatabaseConfig {
+                mysql_url: Secret::new("mysql://eu.db/auth".to_string()),
+            },
+        );
+        assert!(matches!(
+            ConfigValidator::validate_config(&config),
+            Err(ConfigValidationError::DatabaseValidationFailed { .. })
+        ));
+
+        config.database.residency.region = Some("eu".to_string());
+        assert!(ConfigValidator::validate_config(&config).is_err());
+
+        config.database.residency.region = Some("us".to_string());
+        assert!(ConfigValidator::validate_config(&config).is_ok());
+    }
+
+    #[test]
+    fn test_otp_purge_batches_are_bounded() {
+        let mut config = valid_test_config();
+        config.database.otp_purge.batch_size = 50_000;
+        match ConfigValidator::validate_config(&config) {
+            Err(ConfigValidationError::DatabaseValidationFailed { message }) => {
+                assert!(message.contains("OTP purge batch size"));
+            }
+            result => panic!("Expected DatabaseValidationFailed error, got {:?}", result),
+        }
+
+        config.database.otp_purge.batch_size = 500;
+        config.database.otp_purge.bucket_seconds = 0;
+        assert!(ConfigValidator::validate_config(&config).is_err());
+    }
+
     #[test]
     fn test_invalid_feature_limit() {
         let mut config = valid_test_config();
@@ -211,6 +1517,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cohort_targets_need_a_known_cohort_and_salt() {
+        let mut config = valid_test_config();
+        let cohorts = &mut config.features.cohorts;
+        cohorts.cohorts.insert("canary".to_string(), 1.0);
+        cohorts
+            .targets
+            .insert("passkeys".to_string(), "early".to_string());
+        let result = ConfigValidator::validate_config(&config);
+        assert!(
+            matches!(&result, Err(ConfigValidationError::FeatureValidationFailed { message }) if message.contains("unknown cohort 'early'")),
+            "{:?}",
+            result
+        );
+
+        config
+            .features
+            .cohorts
+            .targets
+            .insert("passkeys".to_string(), "canary".to_string());
+        assert!(ConfigValidator::validate_config(&config).is_err());
+
+        config.features.cohorts.salt = Some(Secret::new("0123456789abcdef".to_string()));
+        assert!(ConfigValidator::validate_config(&config).is_ok());
+
+        config
+            .features
+            .cohorts
+            .cohorts
+            .insert("canary".to_string(), 0.0);
+        assert!(ConfigValidator::validate_config(&config).is_err());
+    }
+
     #[test]
     fn test_basic_validation() {
         let mut config = valid_test_config();
@@ -222,4 +1561,84 @@ mod tests {
             Err(ConfigValidationError::ValidationFailed(_))
         ));
     }
+
+    #[test]
+    fn test_endpoint_of_parses_service_urls() {
+        assert_eq!(
+            endpoint_of("mysql://root:pw@db.internal:3307/auth", 3306),
+            Some(("db.internal".to_string(), 3307))
+        );
+        assert_eq!(
+            endpoint_of("redis://localhost", 6379),
+            Some(("localhost".to_string(), 6379))
+        );
+        assert_eq!(
+            endpoint_of("redis://[::1]:6380/0", 6379),
+            Some(("::1".to_string(), 6380))
+        );
+        assert_eq!(endpoint_of("not a url", 1), None);
+    }
+
+    #[test]
+    fn test_preflight_requires_redis_for_replicas() {
+        let mut config = valid_test_config();
+        config.server.replicas = 3;
+        let report = ConfigValidator::static_checks(&config);
+        assert!(report.has_failures());
+        assert!(report
+            .diagnostics
+            .iter()
+            .any(|d| d.level == DiagnosticLevel::Fail && d.check == "external_services.redis"));
+    }
+
+    #[test]
+    fn test_preflight_requires_redis_for_redis_sessions() {
+        let mut config = valid_test_config();
+        config.security.sessions.backend = SessionBackend::Redis;
+        let report = ConfigValidator::static_checks(&config);
+        assert!(report
+            .diagnostics
+            .iter()
+            .any(|d| d.level == DiagnosticLevel::Fail && d.check == "security.sessions.backend"));
+    }
+
+    #[test]
+    fn test_preflight_rejects_admin_port_in_fallback_range() {
+        let mut config = valid_test_config();
+        config.server.admin = Some(crate::config::AdminServerConfig {
+            port: config.server.port + 2,
+            host: "127.0.0.1".to_string(),
+        });
+        let report = ConfigValidator::static_checks(&config);
+        assert!(report
+            .diagnostics
+            .iter()
+            .any(|d| d.level == DiagnosticLevel::Fail && d.check == "server.admin.port"));
+
+        // Failures are listed first in the printed report
+        let printed = report.to_string();
+        assert!(printed.lines().nth(1).unwrap().contains("[FAIL]"));
+    }
+
+    #[test]
+    fn test_preflight_rejects_undefined_default_quota_tier() {
+        let mut config = valid_test_config();
+        config.server.quotas.default_tier = "gold".to_string();
+        let report = ConfigValidator::static_checks(&config);
+        assert!(report
+            .diagnostics
+            .iter()
+            .any(|d| d.level == DiagnosticLevel::Fail && d.check == "server.quotas"));
+    }
+
+    #[test]
+    fn test_preflight_rejects_unreadable_pinned_ca() {
+        let mut config = valid_test_config();
+        config.external_services.http.pinned_ca_certs = vec!["/nonexistent/ca.pem".to_string()];
+        let report = ConfigValidator::static_checks(&config);
+        assert!(report
+            .diagnostics
+            .iter()
+            .any(|d| d.level == DiagnosticLevel::Fail && d.check == "external_services.http"));
+    }
 }
@@ -0,0 +1,252 @@
+//! Hot path benchmarks
+//!
+//! Token issuance and validation, policy evaluation, argon2 cost and a full
+//! password login. Storage is in memory so the numbers reflect CPU work only.
+//!
+//! Run with `cargo bench -p auth-core`; criterion keeps the previous run in
+//! `target/criterion` and reports regressions against it.
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use async_trait::async_trait;
+use auth_core::audit::TracingAuditLogger;
+use auth_core::error::AuthError;
+use auth_core::models::role::RoleScope;
+use auth_core::models::{
+    Claims, CreateUserRequest, Role, SensitiveString, UpdateUserRequest, User, UserStatus,
+};
+use auth_core::services::authorization::{AuthContext, PolicyEngine};
+use auth_core::services::identity::{AuthRequest, IdentityService, UserStore};
+use auth_core::services::token_service::{TokenEngine, TokenProvider};
+use auth_crypto::JwtConfig;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use uuid::Uuid;
+
+const PASSWORD: &str = "correct-horse-battery-staple";
+
+/// Serves one user; writes are accepted and dropped
+struct SingleUserStore {
+    user: User,
+}
+
+#[async_trait]
+impl UserStore for SingleUserStore {
+    async fn find_by_email(
+        &self,
+        email: &str,
+        _tenant_id: Uuid,
+    ) -> Result<Option<User>, AuthError> {
+        Ok((self.user.email.as_deref() == Some(email)).then(|| self.user.clone()))
+    }
+    async fn find_by_phone(
+        &self,
+        _phone: &str,
+        _tenant_id: Uuid,
+    ) -> Result<Option<User>, AuthError> {
+        Ok(None)
+    }
+    async fn find_by_username(
+        &self,
+        _username: &str,
+        _tenant_id: Uuid,
+    ) -> Result<Option<User>, AuthError> {
+        Ok(None)
+    }
+    async fn find_by_identifier(
+        &self,
+        identifier: &str,
+        tenant_id: Uuid,
+    ) -> Result<Option<User>, AuthError> {
+        self.find_by_email(identifier, tenant_id).await
+    }
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, AuthError> {
+        Ok((self.user.id == id).then(|| self.user.clone()))
+    }
+    async fn create(
+        &self,
+        _user: CreateUserRequest,
+        _password_hash: String,
+        _tenant_id: Uuid,
+    ) -> Result<User, AuthError> {
+        Ok(self.user.clone())
+    }
+    async fn update_status(&self, _id: Uuid, _status: UserStatus) -> Result<(), AuthError> {
+        Ok(())
+    }
+    async fn increment_failed_attempts(&self, _id: Uuid) -> Result<u32, AuthError> {
+        Ok(1)
+    }
+    async fn reset_failed_attempts(&self, _id: Uuid) -> Result<(), AuthError> {
+        Ok(())
+    }
+    async fn record_login(&self, _id: Uuid, _ip: Option<String>) -> Result<(), AuthError> {
+        Ok(())
+    }
+    async fn update(&self, _user: UpdateUserRequest) -> Result<User, AuthError> {
+        Ok(self.user.clone())
+    }
+    async fn update_password_hash(&self, _id: Uuid, _hash: String) -> Result<(), AuthError> {
+        Ok(())
+    }
+    async fn set_email_verified(&self, _id: Uuid, _verified: bool) -> Result<(), AuthError> {
+        Ok(())
+    }
+    async fn set_phone_verified(&self, _id: Uuid, _verified: bool) -> Result<(), AuthError> {
+        Ok(())
+    }
+}
+
+fn claims(user_id: Uuid, tenant_id: Uuid) -> Claims {
+    let now = chrono::Utc::now().timestamp();
+    Claims {
+        sub: user_id.to_string(),
+        iss: "auth-service".to_string(),
+        aud: "auth-service".to_string(),
+        exp: now + 900,
+        iat: now,
+        nbf: now,
+        jti: Uuid::new_v4().to_string(),
+        tenant_id: tenant_id.to_string(),
+        permissions: vec!["users:read".to_string(), "users:write".to_string()],
+        roles: vec!["admin".to_string()],
+        scope: Some("openid profile".to_string()),
+        session_id: None,
+        client_id: None,
+        ext: Default::default(),
+    }
+}
+
+fn bench_tokens(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let engine = rt.block_on(async {
+        TokenEngine::new_with_config(JwtConfig::default())
+            .await
+            .unwrap()
+    });
+    let (user_id, tenant_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let token = rt
+        .block_on(engine.issue_access_token(claims(user_id, tenant_id)))
+        .unwrap()
+        .token;
+
+    let mut group = c.benchmark_group("token");
+    group.bench_function("issue_access_token", |b| {
+        b.to_async(&rt)
+            .iter(|| engine.issue_access_token(claims(user_id, tenant_id)))
+    });
+    group.bench_function("validate_token", |b| {
+        b.to_async(&rt).iter(|| engine.validate_token(&token))
+    });
+    group.finish();
+}
+
+fn bench_policy(c: &mut Criterion) {
+    let tenant_id = Uuid::new_v4();
+    let roles = (0..8)
+        .map(|i| Role {
+            id: Uuid::new_v4(),
+            tenant_id,
+            name: format!("role-{}", i),
+            description: None,
+            parent_role_id: None,
+            is_system_role: false,
+            permissions: vec![format!("resource-{}:read", i)],
+            constraints: None,
+            organization_id: None,
+            scope: RoleScope::Tenant,
+            metadata: None,
+            created_at: chrono::Utc::now(),
+            updated_at: None,
+        })
+        .collect();
+    let context = AuthContext {
+        user_id: Uuid::new_v4(),
+        tenant_id,
+        roles,
+        attributes: HashMap::from([("department".to_string(), "eng".to_string())]),
+    };
+    let resource = HashMap::from([("owner_id".to_string(), context.user_id.to_string())]);
+
+    c.bench_function("policy/evaluate", |b| {
+        b.iter(|| PolicyEngine::evaluate("resource-7:read", &context, Some(&resource)))
+    });
+}
+
+/// Hashing cost at the default parameters and at stronger candidates, to
+/// weigh a parameter change against its login latency
+fn bench_argon2(c: &mut Criterion) {
+    let salt = SaltString::generate(&mut OsRng);
+    let candidates = [
+        ("default", Params::default()),
+        ("m64MiB_t3", Params::new(64 * 1024, 3, 1, None).unwrap()),
+    ];
+
+    let mut group = c.benchmark_group("argon2");
+    group.sample_size(10);
+    for (name, params) in candidates {
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        group.bench_function(name, |b| {
+            b.iter(|| argon2.hash_password(PASSWORD.as_bytes(), &salt).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_login(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let salt = SaltString::generate(&mut OsRng);
+    let user = User {
+        email: Some("bench@example.com".to_string()),
+        password_hash: Some(
+            Argon2::default()
+                .hash_password(PASSWORD.as_bytes(), &salt)
+                .unwrap()
+                .to_string(),
+        ),
+        ..User::default()
+    };
+    let tenant_id = user.tenant_id;
+    let service = rt.block_on(async {
+        IdentityService::new(
+            Arc::new(SingleUserStore { user }),
+            Arc::new(
+                TokenEngine::new_with_config(JwtConfig::default())
+                    .await
+                    .unwrap(),
+            ),
+            Arc::new(TracingAuditLogger),
+        )
+    });
+    let request = |password: &str| AuthRequest {
+        email: "bench@example.com".to_string(),
+        password: SensitiveString::new(password.to_string()),
+        tenant_id,
+        ip_address: None,
+        user_agent: None,
+        captcha_token: None,
+    };
+
+    let mut group = c.benchmark_group("login");
+    group.sample_size(20);
+    group.bench_function("success", |b| {
+        b.to_async(&rt).iter(|| service.login(request(PASSWORD)))
+    });
+    group.bench_function("wrong_password", |b| {
+        b.to_async(&rt).iter(|| service.login(request("wrong")))
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_tokens,
+    bench_policy,
+    bench_argon2,
+    bench_login
+);
+criterion_main!(benches);
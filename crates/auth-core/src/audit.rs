@@ -3,12 +3,17 @@
 //! Structured logging for security-critical events.
 //! Compliant with MNC audit requirements.
 
+use crate::context::RequestContext;
+use crate::error::ErrorClass;
+use auth_config::{AuditLevel, AuditRoutingConfig, AuditSink};
 use chrono::{DateTime, Utc};
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::sync::Arc;
 use uuid::Uuid;
 
 /// Categories of audit events
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum AuditCategory {
     Authentication,
@@ -40,8 +45,25 @@ impl Serialize for AuditSeverity {
     }
 }
 
+impl<'de> Deserialize<'de> for AuditSeverity {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match String::deserialize(deserializer)?.as_str() {
+            "INFO" => Ok(AuditSeverity::Info),
+            "WARNING" => Ok(AuditSeverity::Warning),
+            "CRITICAL" => Ok(AuditSeverity::Critical),
+            other => Err(serde::de::Error::unknown_variant(
+                other,
+                &["INFO", "WARNING", "CRITICAL"],
+            )),
+        }
+    }
+}
+
 /// Structured Audit Event
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditEvent {
     pub id: Uuid,
     pub timestamp: DateTime<Utc>,
@@ -55,9 +77,12 @@ pub struct AuditEvent {
     pub tenant_id: Option<Uuid>,
     pub metadata: serde_json::Value,
     pub outcome: AuditOutcome,
+    /// Correlates the event with logs, error responses and webhooks
+    #[serde(default)]
+    pub request_id: Option<Uuid>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum AuditOutcome {
     Success,
@@ -70,19 +95,23 @@ impl AuditEvent {
         action: impl Into<String>,
         severity: AuditSeverity,
     ) -> Self {
+        // Inside a request, start from what the request already knows;
+        // the builders below override it
+        let context = RequestContext::current();
         Self {
             id: Uuid::new_v4(),
             timestamp: Utc::now(),
             category,
             action: action.into(),
             severity,
-            actor_id: None,
+            actor_id: context.as_ref().and_then(|c| c.actor_id),
             resource_id: None,
-            ip_address: None,
+            ip_address: context.as_ref().and_then(|c| c.ip_address.clone()),
             user_agent: None,
-            tenant_id: None,
+            tenant_id: context.as_ref().and_then(|c| c.tenant_id),
             metadata: serde_json::json!({}),
             outcome: AuditOutcome::Success,
+            request_id: context.map(|c| c.request_id),
         }
     }
 
@@ -102,9 +131,9 @@ impl AuditEvent {
         ua: Option<String>,
         tenant: Option<Uuid>,
     ) -> Self {
-        self.ip_address = ip;
+        self.ip_address = ip.or(self.ip_address);
         self.user_agent = ua;
-        self.tenant_id = tenant;
+        self.tenant_id = tenant.or(self.tenant_id);
         self
     }
 
@@ -119,6 +148,16 @@ impl AuditEvent {
         };
         self
     }
+
+    /// A failure with the error's code as the reason, and its code and
+    /// category in the metadata
+    pub fn failed_with(mut self, error: ErrorClass) -> Self {
+        if let Some(metadata) = self.metadata.as_object_mut() {
+            metadata.insert("error_code".to_string(), error.code.into());
+            metadata.insert("error_category".to_string(), error.category.as_str().into());
+        }
+        self.failure(error.code)
+    }
 }
 
 /// Trait for recording audit events
@@ -142,9 +181,192 @@ impl AuditLogger for TracingAuditLogger {
             action = %event.action,
             severity = ?event.severity,
             actor_id = ?event.actor_id,
+            request_id = ?event.request_id,
             outcome = ?event.outcome,
             payload = ?serde_json::to_string(&event).unwrap_or_default(),
             "AUDIT_EVENT"
         );
     }
 }
+
+/// Classifies audit events by severity, samples the informational ones and
+/// sends each to the durable sink or to the logs (see [`AuditRoutingConfig`])
+pub struct RoutingAuditLogger {
+    config: AuditRoutingConfig,
+    durable: Arc<dyn AuditLogger>,
+    logs: Arc<dyn AuditLogger>,
+}
+
+/// What happens to one event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditRoute {
+    Durable,
+    Log,
+    SampledOut,
+}
+
+impl RoutingAuditLogger {
+    pub fn new(
+        config: AuditRoutingConfig,
+        durable: Arc<dyn AuditLogger>,
+        logs: Arc<dyn AuditLogger>,
+    ) -> Self {
+        Self {
+            config,
+            durable,
+            logs,
+        }
+    }
+
+    /// The event's severity, after the configured override for its action
+    pub fn classify(&self, event: &AuditEvent) -> AuditLevel {
+        longest_prefix(&self.config.severities, &event.action)
+            .copied()
+            .unwrap_or(match event.severity {
+                AuditSeverity::Info => AuditLevel::Info,
+                AuditSeverity::Warning => AuditLevel::Warning,
+                AuditSeverity::Critical => AuditLevel::Critical,
+            })
+    }
+
+    pub fn route(&self, event: &AuditEvent) -> AuditRoute {
+        let security = matches!(event.category, AuditCategory::Security)
+            || matches!(event.outcome, AuditOutcome::Failure { .. })
+            || self.classify(event) != AuditLevel::Info;
+        if security {
+            return AuditRoute::Durable;
+        }
+
+        let rate = longest_prefix(&self.config.sample_rates, &event.action)
+            .copied()
+            .unwrap_or(self.config.info_sample_rate);
+        if !sampled(event.id, rate) {
+            return AuditRoute::SampledOut;
+        }
+        match self.config.info_sink {
+            AuditSink::Durable => AuditRoute::Durable,
+            AuditSink::Log => AuditRoute::Log,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditLogger for RoutingAuditLogger {
+    async fn log(&self, event: AuditEvent) {
+        match self.route(&event) {
+            AuditRoute::Durable => self.durable.log(event).await,
+            AuditRoute::Log => self.logs.log(event).await,
+            AuditRoute::SampledOut => {
+                metrics::counter!("auth_audit_events_sampled_out_total", 1);
+            }
+        }
+    }
+}
+
+/// The value of the longest key that prefixes `action`
+fn longest_prefix<'a, T>(rules: &'a HashMap<String, T>, action: &str) -> Option<&'a T> {
+    rules
+        .iter()
+        .filter(|(prefix, _)| action.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, value)| value)
+}
+
+/// Sampling decided by the event id, so a replayed event is sampled the
+/// same way. The last 32 bits of a v4 id are random.
+fn sampled(id: Uuid, rate: f64) -> bool {
+    let draw = (id.as_u128() & 0xFFFF_FFFF) as f64 / (u64::from(u32::MAX) + 1) as f64;
+    draw < rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    #[derive(Default)]
+    struct Collecting(Mutex<Vec<String>>);
+
+    #[async_trait::async_trait]
+    impl AuditLogger for Collecting {
+        async fn log(&self, event: AuditEvent) {
+            self.0.lock().push(event.action);
+        }
+    }
+
+    fn router(
+        config: AuditRoutingConfig,
+    ) -> (RoutingAuditLogger, Arc<Collecting>, Arc<Collecting>) {
+        let (durable, logs) = (
+            Arc::new(Collecting::default()),
+            Arc::new(Collecting::default()),
+        );
+        (
+            RoutingAuditLogger::new(config, durable.clone(), logs.clone()),
+            durable,
+            logs,
+        )
+    }
+
+    fn info(action: &str) -> AuditEvent {
+        AuditEvent::new(AuditCategory::System, action, AuditSeverity::Info)
+    }
+
+    #[tokio::test]
+    async fn test_security_events_always_reach_durable_sink() {
+        let (router, durable, logs) = router(AuditRoutingConfig {
+            info_sample_rate: 0.0,
+            ..Default::default()
+        });
+
+        router
+            .log(AuditEvent::new(
+                AuditCategory::Security,
+                "mfa.recovery_code.used",
+                AuditSeverity::Info,
+            ))
+            .await;
+        router
+            .log(AuditEvent::new(
+                AuditCategory::System,
+                "http.handler_panicked",
+                AuditSeverity::Critical,
+            ))
+            .await;
+        router
+            .log(info("HTTP GET /v1/auth/me").failure("401"))
+            .await;
+        router.log(info("HTTP GET /health")).await;
+
+        assert_eq!(durable.0.lock().len(), 3);
+        assert!(logs.0.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_info_events_are_sampled_to_logs() {
+        let mut config = AuditRoutingConfig {
+            info_sample_rate: 0.1,
+            ..Default::default()
+        };
+        config.sample_rates.insert("HTTP POST ".to_string(), 1.0);
+        config
+            .severities
+            .insert("HTTP POST /v1/auth/login".to_string(), AuditLevel::Warning);
+        let (router, durable, logs) = router(config);
+
+        for _ in 0..10_000 {
+            router.log(info("HTTP GET /v1/users")).await;
+        }
+        let kept = logs.0.lock().len();
+        assert!((700..1_300).contains(&kept), "kept {}", kept);
+
+        router.log(info("HTTP POST /v1/roles")).await;
+        assert_eq!(logs.0.lock().len(), kept + 1);
+        // Raised by its override, so treated as a security event
+        router.log(info("HTTP POST /v1/auth/login")).await;
+        assert_eq!(
+            *durable.0.lock(),
+            vec!["HTTP POST /v1/auth/login".to_string()]
+        );
+    }
+}
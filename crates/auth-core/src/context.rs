@@ -0,0 +1,100 @@
+//! Per-request correlation context
+//!
+//! The HTTP layer opens a [`RequestContext`] scope around each request.
+//! Anything running inside it (services, audit events, webhook enqueues)
+//! can read the request id without it being passed down explicitly. The
+//! actor and tenant are filled in once a token has been validated.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use uuid::Uuid;
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: Arc<Mutex<RequestContext>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestContext {
+    pub request_id: Uuid,
+    pub tenant_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+}
+
+impl RequestContext {
+    pub fn new(request_id: Uuid) -> Self {
+        Self {
+            request_id,
+            tenant_id: None,
+            actor_id: None,
+            ip_address: None,
+        }
+    }
+
+    pub fn with_tenant(mut self, tenant_id: Option<Uuid>) -> Self {
+        self.tenant_id = tenant_id;
+        self
+    }
+
+    pub fn with_ip(mut self, ip_address: Option<String>) -> Self {
+        self.ip_address = ip_address;
+        self
+    }
+
+    /// Run `future` with this context as the current one
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        REQUEST_CONTEXT
+            .scope(Arc::new(Mutex::new(self)), future)
+            .await
+    }
+
+    /// Snapshot of the current request's context, if any
+    pub fn current() -> Option<RequestContext> {
+        REQUEST_CONTEXT.try_with(|c| c.lock().clone()).ok()
+    }
+
+    pub fn current_request_id() -> Option<Uuid> {
+        REQUEST_CONTEXT.try_with(|c| c.lock().request_id).ok()
+    }
+
+    /// Record who the request acts as. The first principal wins so a later
+    /// token check (e.g. a target user's) cannot replace the caller.
+    pub fn record_principal(actor_id: Uuid, tenant_id: Uuid) {
+        let _ = REQUEST_CONTEXT.try_with(|c| {
+            let mut context = c.lock();
+            if context.actor_id.is_none() {
+                context.actor_id = Some(actor_id);
+                context.tenant_id.get_or_insert(tenant_id);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_context_visible_inside_scope_only() {
+        assert!(RequestContext::current().is_none());
+
+        let request_id = Uuid::new_v4();
+        let actor = Uuid::new_v4();
+        let tenant = Uuid::new_v4();
+        let seen = RequestContext::new(request_id)
+            .scope(async {
+                RequestContext::record_principal(actor, tenant);
+                RequestContext::record_principal(Uuid::new_v4(), Uuid::new_v4());
+                RequestContext::current()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(seen.request_id, request_id);
+        assert_eq!(seen.actor_id, Some(actor));
+        assert_eq!(seen.tenant_id, Some(tenant));
+        assert!(RequestContext::current_request_id().is_none());
+    }
+}
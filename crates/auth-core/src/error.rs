@@ -1,9 +1,122 @@
 //! Error types for the authentication system
 //!
 //! Implements MNC-grade error handling with standard error codes.
+//!
+//! Every crate's errors share one taxonomy: a stable code and an
+//! [`ErrorCategory`] through [`ErrorCode`]. [`AuthError`] is what services
+//! return; an error from another layer that has no variant of its own is
+//! wrapped as [`AuthError::Classified`], keeping it as the source. HTTP
+//! responses and audit events are both derived from the code, category,
+//! status and public message defined here.
 
+use serde::Serialize;
 use thiserror::Error;
 
+/// Coarse class of a failure, whichever crate it comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// The request is wrong; repeating it unchanged fails again
+    Validation,
+    /// Who the caller is could not be established
+    Authentication,
+    /// The caller is known but may not do this
+    Forbidden,
+    NotFound,
+    Conflict,
+    RateLimited,
+    /// The tenant is served by another deployment
+    Misdirected,
+    /// A dependency is down or shedding load; worth retrying later
+    Unavailable,
+    /// A dependency answered with an error
+    Upstream,
+    Internal,
+}
+
+impl ErrorCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCategory::Validation => "validation",
+            ErrorCategory::Authentication => "authentication",
+            ErrorCategory::Forbidden => "forbidden",
+            ErrorCategory::NotFound => "not_found",
+            ErrorCategory::Conflict => "conflict",
+            ErrorCategory::RateLimited => "rate_limited",
+            ErrorCategory::Misdirected => "misdirected",
+            ErrorCategory::Unavailable => "unavailable",
+            ErrorCategory::Upstream => "upstream",
+            ErrorCategory::Internal => "internal",
+        }
+    }
+
+    /// HTTP status for errors of this category unless the error says otherwise
+    pub fn status(&self) -> u16 {
+        match self {
+            ErrorCategory::Validation => 400,
+            ErrorCategory::Authentication => 401,
+            ErrorCategory::Forbidden => 403,
+            ErrorCategory::NotFound => 404,
+            ErrorCategory::Conflict => 409,
+            ErrorCategory::RateLimited => 429,
+            ErrorCategory::Misdirected => 421,
+            ErrorCategory::Unavailable => 503,
+            ErrorCategory::Upstream => 502,
+            ErrorCategory::Internal => 500,
+        }
+    }
+
+    /// Client-facing code of an error known only by its category. These
+    /// are all in the message catalogs clients localize codes with.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCategory::Validation => "AUTH_001",
+            ErrorCategory::Authentication => "AUTH_058",
+            ErrorCategory::Forbidden => "AUTH_023",
+            ErrorCategory::NotFound => "AUTH_059",
+            ErrorCategory::Conflict => "AUTH_005",
+            ErrorCategory::RateLimited => "AUTH_017",
+            ErrorCategory::Misdirected => "AUTH_055",
+            ErrorCategory::Unavailable => "AUTH_046",
+            ErrorCategory::Upstream => "AUTH_027",
+            ErrorCategory::Internal => "AUTH_026",
+        }
+    }
+
+    /// Whether the server rather than the caller is at fault; details of
+    /// such errors are kept out of responses
+    pub fn is_server_fault(&self) -> bool {
+        matches!(
+            self,
+            ErrorCategory::Unavailable | ErrorCategory::Upstream | ErrorCategory::Internal
+        )
+    }
+}
+
+/// Code and category of an error, detached from it; what responses hand
+/// on to the audit trail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ErrorClass {
+    pub code: &'static str,
+    pub category: ErrorCategory,
+}
+
+impl ErrorClass {
+    pub fn of<E: ErrorCode + ?Sized>(error: &E) -> Self {
+        Self {
+            code: error.code(),
+            category: error.category(),
+        }
+    }
+}
+
+/// A stable, machine-readable code and a category. Codes are namespaced by
+/// the layer they come from (`AUTH_`, `JWT_`, `OTP_`, ...) and never reused.
+pub trait ErrorCode: std::error::Error + Send + Sync + 'static {
+    fn code(&self) -> &'static str;
+    fn category(&self) -> ErrorCategory;
+}
+
 #[derive(Debug, Error)]
 pub enum AuthError {
     #[error("Authentication failed: {reason}")]
@@ -21,9 +134,15 @@ pub enum AuthError {
     #[error("Rate limit exceeded: {limit} requests per {window}")]
     RateLimitExceeded { limit: u32, window: String },
 
+    #[error("Concurrency limit exceeded: {limit} requests in flight")]
+    ConcurrencyLimitExceeded { limit: u32 },
+
     #[error("Tenant not found: {tenant_id}")]
     TenantNotFound { tenant_id: String },
 
+    #[error("Tenant {tenant_id} is served in region {region}")]
+    TenantMisrouted { tenant_id: String, region: String },
+
     #[error("Configuration error: {message}")]
     ConfigurationError { message: String },
 
@@ -54,8 +173,10 @@ pub enum AuthError {
     #[error("Account deleted")]
     AccountDeleted,
 
+    /// The password is past its maximum age. The ticket lets the user set
+    /// a new one without logging in.
     #[error("Password expired")]
-    PasswordExpired,
+    PasswordExpired { change_ticket: String },
 
     #[error("User not found")]
     UserNotFound,
@@ -83,21 +204,79 @@ pub enum AuthError {
 
     #[error("Circuit breaker open: {service}")]
     CircuitBreakerOpen { service: String },
+
+    #[error("CAPTCHA challenge required")]
+    CaptchaRequired {
+        provider: String,
+        site_key: Option<String>,
+    },
+
+    #[error("CAPTCHA verification failed")]
+    CaptchaFailed,
+
+    #[error("Service account not found")]
+    ServiceAccountNotFound,
+
+    #[error("Step-up authentication required")]
+    StepUpRequired,
+
+    #[error("Device certificate not found")]
+    DeviceCertificateNotFound,
+
+    #[error("Push device not found")]
+    PushDeviceNotFound,
+
+    #[error("Remembered device not found")]
+    RememberedDeviceNotFound,
+
+    #[error("Push challenge not found")]
+    PushChallengeNotFound,
+
+    #[error("Sign-in method not allowed: {method}")]
+    AuthMethodNotAllowed { method: String },
+
+    /// An error of another layer, kept whole as the source; `code` is its
+    /// own code, reported alongside the category's
+    #[error("{code}: {source}")]
+    Classified {
+        code: &'static str,
+        category: ErrorCategory,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub enum TokenErrorKind {
     Expired,
+    /// A refresh token went unused for longer than its idle lifetime
+    IdleExpired,
+    /// A refresh token family reached its absolute lifetime; the user has to
+    /// sign in again
+    AbsoluteExpired,
     Invalid,
     Revoked,
+    /// A single-use token was presented a second time
+    Replayed,
     MalformedSignature,
     UnsupportedAlgorithm,
 }
 
 impl AuthError {
+    /// Wrap an error of another layer, keeping its code, category and chain
+    pub fn classified<E: ErrorCode>(error: E) -> Self {
+        AuthError::Classified {
+            code: error.code(),
+            category: error.category(),
+            source: Box::new(error),
+        }
+    }
+
+    /// Client-facing code, the key of the localized message catalogs
     pub fn code(&self) -> &'static str {
         match self {
-            AuthError::ValidationError { .. } => "AUTH_001", // And 002, 003, 004 depending on context, using generic 001 for now
+            AuthError::ValidationError { .. } => "AUTH_001", // 002 and 004 are field-level, from handlers
+            AuthError::PasswordPolicyViolation { .. } => "AUTH_003",
             AuthError::Conflict { .. } => "AUTH_005",
             AuthError::InvalidCredentials => "AUTH_007",
             AuthError::InvalidOtp => "AUTH_008",
@@ -105,23 +284,326 @@ impl AuthError {
             AuthError::AccountLocked { .. } => "AUTH_011",
             AuthError::AccountSuspended => "AUTH_012",
             AuthError::AccountDeleted => "AUTH_013",
-            AuthError::RateLimitExceeded { .. } => "AUTH_017", // Or 018, 040
+            AuthError::RateLimitExceeded { .. } => "AUTH_017",
+            AuthError::ConcurrencyLimitExceeded { .. } => "AUTH_054",
             AuthError::TokenError { kind } => match kind {
-                TokenErrorKind::Expired => "AUTH_021",
-                TokenErrorKind::Revoked => "AUTH_022",
-                _ => "AUTH_020",
+                TokenErrorKind::Expired
+                | TokenErrorKind::IdleExpired
+                | TokenErrorKind::AbsoluteExpired => "AUTH_021",
+                TokenErrorKind::Revoked | TokenErrorKind::Replayed => "AUTH_022",
+                TokenErrorKind::Invalid
+                | TokenErrorKind::MalformedSignature
+                | TokenErrorKind::UnsupportedAlgorithm => "AUTH_020",
             },
             AuthError::AuthorizationDenied { .. } => "AUTH_023",
             AuthError::Unauthorized { .. } => "AUTH_023",
             AuthError::UserNotFound => "AUTH_024",
             AuthError::SessionNotFound => "AUTH_025",
-            AuthError::DatabaseError { .. } => "AUTH_026",
-            AuthError::ExternalServiceError { .. } => "AUTH_027", // Or 028
-            AuthError::CircuitBreakerOpen { .. } => "AUTH_046",
-            AuthError::InternalError => "AUTH_026", // Generic internal
+            AuthError::DatabaseError { .. }
+            | AuthError::InternalError
+            | AuthError::ConfigurationError { .. } => "AUTH_026",
+            AuthError::ExternalServiceError { .. } => "AUTH_027",
             AuthError::UTCryptoError(_) => "AUTH_044",
-            AuthError::ConfigurationError { .. } => "AUTH_026",
-            _ => "AUTH_026", // Default to internal DB type error
+            AuthError::CircuitBreakerOpen { .. } => "AUTH_046",
+            AuthError::CaptchaRequired { .. } => "AUTH_047",
+            AuthError::CaptchaFailed => "AUTH_048",
+            AuthError::ServiceAccountNotFound => "AUTH_049",
+            AuthError::StepUpRequired => "AUTH_050",
+            AuthError::DeviceCertificateNotFound => "AUTH_051",
+            AuthError::PushDeviceNotFound | AuthError::RememberedDeviceNotFound => "AUTH_052",
+            AuthError::PushChallengeNotFound => "AUTH_053",
+            AuthError::TenantMisrouted { .. } => "AUTH_055",
+            AuthError::PasswordExpired { .. } => "AUTH_056",
+            AuthError::AuthMethodNotAllowed { .. } => "AUTH_057",
+            AuthError::AuthenticationFailed { .. } | AuthError::CredentialError { .. } => {
+                "AUTH_058"
+            }
+            AuthError::TenantNotFound { .. } => "AUTH_059",
+            AuthError::Classified { category, .. } => category.code(),
+        }
+    }
+
+    /// Code of the wrapped lower-layer error, if any
+    pub fn cause_code(&self) -> Option<&'static str> {
+        match self {
+            AuthError::Classified { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            AuthError::ValidationError { .. }
+            | AuthError::PasswordPolicyViolation { .. }
+            | AuthError::InvalidOtp
+            | AuthError::OtpExpired => ErrorCategory::Validation,
+            AuthError::AuthenticationFailed { .. }
+            | AuthError::TokenError { .. }
+            | AuthError::CredentialError { .. }
+            | AuthError::InvalidCredentials
+            | AuthError::Unauthorized { .. }
+            | AuthError::SessionNotFound
+            | AuthError::CaptchaRequired { .. }
+            | AuthError::StepUpRequired => ErrorCategory::Authentication,
+            AuthError::AuthorizationDenied { .. }
+            | AuthError::AccountLocked { .. }
+            | AuthError::AccountSuspended
+            | AuthError::AccountDeleted
+            | AuthError::PasswordExpired { .. }
+            | AuthError::CaptchaFailed
+            | AuthError::AuthMethodNotAllowed { .. } => ErrorCategory::Forbidden,
+            AuthError::TenantNotFound { .. }
+            | AuthError::UserNotFound
+            | AuthError::ServiceAccountNotFound
+            | AuthError::DeviceCertificateNotFound
+            | AuthError::PushDeviceNotFound
+            | AuthError::RememberedDeviceNotFound
+            | AuthError::PushChallengeNotFound => ErrorCategory::NotFound,
+            AuthError::Conflict { .. } => ErrorCategory::Conflict,
+            AuthError::RateLimitExceeded { .. } | AuthError::ConcurrencyLimitExceeded { .. } => {
+                ErrorCategory::RateLimited
+            }
+            AuthError::TenantMisrouted { .. } => ErrorCategory::Misdirected,
+            AuthError::CircuitBreakerOpen { .. } => ErrorCategory::Unavailable,
+            AuthError::ExternalServiceError { .. } => ErrorCategory::Upstream,
+            AuthError::DatabaseError { .. }
+            | AuthError::InternalError
+            | AuthError::ConfigurationError { .. }
+            | AuthError::UTCryptoError(_) => ErrorCategory::Internal,
+            AuthError::Classified { category, .. } => *category,
+        }
+    }
+
+    /// HTTP status of the error response
+    pub fn status(&self) -> u16 {
+        match self {
+            AuthError::AccountLocked { .. } => 423,
+            AuthError::CaptchaRequired { .. } => 428,
+            _ => self.category().status(),
+        }
+    }
+
+    /// Message safe to show the caller; internal details stay in the logs
+    pub fn public_message(&self) -> String {
+        match self {
+            AuthError::AuthenticationFailed { reason } => reason.clone(),
+            AuthError::AuthorizationDenied { permission, .. } => {
+                format!("Permission denied: {}", permission)
+            }
+            AuthError::TokenError {
+                kind: TokenErrorKind::IdleExpired,
+            } => "Refresh token expired after inactivity".to_string(),
+            AuthError::TokenError {
+                kind: TokenErrorKind::AbsoluteExpired,
+            } => "Session reached its maximum lifetime; sign in again".to_string(),
+            AuthError::TokenError { .. } => "Invalid or expired token".to_string(),
+            AuthError::RateLimitExceeded { limit, window } => {
+                format!("Rate limit exceeded: {} per {}", limit, window)
+            }
+            AuthError::ConcurrencyLimitExceeded { limit } => {
+                format!("Too many concurrent requests: {} allowed", limit)
+            }
+            AuthError::TenantNotFound { .. } => "Tenant not found".to_string(),
+            AuthError::TenantMisrouted { region, .. } => {
+                format!("Tenant data is served in region {}", region)
+            }
+            AuthError::ConfigurationError { .. } => "Configuration error".to_string(),
+            AuthError::ExternalServiceError { .. } => "External service error".to_string(),
+            AuthError::DatabaseError { .. } => "Database error".to_string(),
+            AuthError::ValidationError { message } => message.clone(),
+            AuthError::InternalError => "Internal server error".to_string(),
+            AuthError::CredentialError { message } => message.clone(),
+            AuthError::PasswordPolicyViolation { errors } => errors.join(", "),
+            AuthError::AccountLocked { reason } => reason.clone(),
+            AuthError::AccountSuspended => "Account suspended".to_string(),
+            AuthError::AccountDeleted => "Account deleted".to_string(),
+            AuthError::PasswordExpired { .. } => "Password expired".to_string(),
+            AuthError::UserNotFound => "User not found".to_string(),
+            AuthError::InvalidCredentials => "Invalid credentials".to_string(),
+            AuthError::Conflict { message } => message.clone(),
+            AuthError::Unauthorized { message } => message.clone(),
+            AuthError::UTCryptoError(_) => "Cryptography error".to_string(),
+            AuthError::SessionNotFound => "Session not found".to_string(),
+            AuthError::InvalidOtp => "Invalid OTP".to_string(),
+            AuthError::OtpExpired => "OTP expired".to_string(),
+            AuthError::CircuitBreakerOpen { service } => {
+                format!("Service unavailable: {}", service)
+            }
+            AuthError::CaptchaRequired { .. } => "CAPTCHA challenge required".to_string(),
+            AuthError::CaptchaFailed => "CAPTCHA verification failed".to_string(),
+            AuthError::ServiceAccountNotFound => "Service account not found".to_string(),
+            AuthError::StepUpRequired => "Step-up authentication required".to_string(),
+            AuthError::DeviceCertificateNotFound => "Device certificate not found".to_string(),
+            AuthError::PushDeviceNotFound => "Push device not found".to_string(),
+            AuthError::RememberedDeviceNotFound => "Remembered device not found".to_string(),
+            AuthError::PushChallengeNotFound => "Push challenge not found".to_string(),
+            AuthError::AuthMethodNotAllowed { method } => {
+                format!("Sign-in with {} is not allowed for this tenant", method)
+            }
+            AuthError::Classified {
+                category, source, ..
+            } => {
+                if category.is_server_fault() {
+                    match category {
+                        ErrorCategory::Unavailable => "Service unavailable".to_string(),
+                        ErrorCategory::Upstream => "External service error".to_string(),
+                        _ => "Internal server error".to_string(),
+                    }
+                } else {
+                    source.to_string()
+                }
+            }
+        }
+    }
+}
+
+impl ErrorCode for AuthError {
+    fn code(&self) -> &'static str {
+        self.cause_code().unwrap_or_else(|| AuthError::code(self))
+    }
+
+    fn category(&self) -> ErrorCategory {
+        AuthError::category(self)
+    }
+}
+
+impl ErrorCode for auth_crypto::JwtError {
+    fn code(&self) -> &'static str {
+        use auth_crypto::JwtError;
+        match self {
+            JwtError::EncodingError(_) => "JWT_001",
+            JwtError::ValidationError { .. } => "JWT_002",
+            JwtError::TokenExpired => "JWT_003",
+            JwtError::InvalidFormat => "JWT_004",
+            JwtError::KeyError(_) => "JWT_005",
+        }
+    }
+
+    fn category(&self) -> ErrorCategory {
+        use auth_crypto::JwtError;
+        match self {
+            JwtError::EncodingError(_) | JwtError::KeyError(_) => ErrorCategory::Internal,
+            _ => ErrorCategory::Authentication,
+        }
+    }
+}
+
+impl ErrorCode for auth_platform::PlatformError {
+    fn code(&self) -> &'static str {
+        use auth_platform::PlatformError;
+        match self {
+            PlatformError::Port(_) => "PLATFORM_001",
+            PlatformError::Policy(_) => "PLATFORM_002",
+            PlatformError::Io(_) => "PLATFORM_003",
+            PlatformError::Lock(_) => "PLATFORM_004",
+            PlatformError::Wal(_) => "PLATFORM_005",
+            PlatformError::Http(_) => "PLATFORM_006",
+            PlatformError::ObjectStore(_) => "PLATFORM_007",
+            PlatformError::Lifecycle(_) => "PLATFORM_008",
+            PlatformError::Shutdown(_) => "PLATFORM_009",
+        }
+    }
+
+    fn category(&self) -> ErrorCategory {
+        use auth_platform::PlatformError;
+        match self {
+            PlatformError::Lock(_) => ErrorCategory::Unavailable,
+            PlatformError::Http(_) | PlatformError::ObjectStore(_) => ErrorCategory::Upstream,
+            _ => ErrorCategory::Internal,
+        }
+    }
+}
+
+impl ErrorCode for crate::services::otp_delivery::DeliveryError {
+    fn code(&self) -> &'static str {
+        use crate::services::otp_delivery::DeliveryError;
+        match self {
+            DeliveryError::SmsFailed(_) => "DELIVERY_001",
+            DeliveryError::EmailFailed(_) => "DELIVERY_002",
+            DeliveryError::PushFailed(_) => "DELIVERY_003",
+            DeliveryError::CircuitBreakerOpen(_) => "DELIVERY_004",
+            DeliveryError::AllMethodsFailed => "DELIVERY_005",
+            DeliveryError::RateLimitExceeded => "DELIVERY_006",
+            DeliveryError::ConfigError(_) => "DELIVERY_007",
+            DeliveryError::ComplianceViolation(_) => "DELIVERY_008",
+            DeliveryError::BudgetExhausted { .. } => "DELIVERY_009",
+            DeliveryError::DestinationBlocked(_) => "DELIVERY_010",
+            DeliveryError::DestinationQuarantined => "DELIVERY_011",
+        }
+    }
+
+    fn category(&self) -> ErrorCategory {
+        use crate::services::otp_delivery::DeliveryError;
+        match self {
+            DeliveryError::CircuitBreakerOpen(_) => ErrorCategory::Unavailable,
+            DeliveryError::RateLimitExceeded | DeliveryError::BudgetExhausted { .. } => {
+                ErrorCategory::RateLimited
+            }
+            DeliveryError::ComplianceViolation(_)
+            | DeliveryError::DestinationBlocked(_)
+            | DeliveryError::DestinationQuarantined => ErrorCategory::Validation,
+            DeliveryError::ConfigError(_) => ErrorCategory::Internal,
+            _ => ErrorCategory::Upstream,
+        }
+    }
+}
+
+impl ErrorCode for crate::services::otp_service::OtpError {
+    fn code(&self) -> &'static str {
+        use crate::services::otp_service::OtpError;
+        match self {
+            OtpError::GenerationFailed => "OTP_001",
+            OtpError::StorageFailed(_) => "OTP_002",
+            OtpError::NotFound => "OTP_003",
+            OtpError::Expired => "OTP_004",
+            OtpError::Invalid => "OTP_005",
+            OtpError::MaxAttemptsExceeded => "OTP_006",
+            OtpError::AlreadyVerified => "OTP_007",
+            OtpError::RateLimitExceeded => "OTP_008",
+        }
+    }
+
+    fn category(&self) -> ErrorCategory {
+        use crate::services::otp_service::OtpError;
+        match self {
+            OtpError::GenerationFailed | OtpError::StorageFailed(_) => ErrorCategory::Internal,
+            OtpError::MaxAttemptsExceeded | OtpError::RateLimitExceeded => {
+                ErrorCategory::RateLimited
+            }
+            OtpError::AlreadyVerified => ErrorCategory::Conflict,
+            _ => ErrorCategory::Validation,
+        }
+    }
+}
+
+impl ErrorCode for sqlx::Error {
+    fn code(&self) -> &'static str {
+        match self {
+            sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_) => "DB_002",
+            sqlx::Error::Database(e)
+                if e.is_unique_violation()
+                    || e.is_foreign_key_violation()
+                    || e.is_check_violation() =>
+            {
+                "DB_003"
+            }
+            _ => "DB_001",
+        }
+    }
+
+    /// A broken constraint is still internal: repositories that expect one
+    /// report it as a conflict themselves, and the database's message
+    /// names tables and keys that clients must not see
+    fn category(&self) -> ErrorCategory {
+        match self {
+            sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_) => ErrorCategory::Unavailable,
+            _ => ErrorCategory::Internal,
         }
     }
 }
@@ -130,9 +612,7 @@ impl From<sqlx::Error> for AuthError {
     fn from(err: sqlx::Error) -> Self {
         match err {
             sqlx::Error::RowNotFound => AuthError::UserNotFound,
-            _ => AuthError::DatabaseError {
-                message: err.to_string(),
-            },
+            _ => AuthError::classified(err),
         }
     }
 }
@@ -149,13 +629,30 @@ impl From<auth_crypto::JwtError> for AuthError {
             auth_crypto::JwtError::EncodingError(_) => AuthError::TokenError {
                 kind: TokenErrorKind::Invalid,
             },
-            auth_crypto::JwtError::KeyError(msg) => AuthError::ConfigurationError { message: msg },
-            _ => AuthError::TokenError {
+            err @ auth_crypto::JwtError::KeyError(_) => AuthError::classified(err),
+            auth_crypto::JwtError::InvalidFormat => AuthError::TokenError {
                 kind: TokenErrorKind::Invalid,
             },
         }
     }
 }
+
+impl From<auth_crypto::DeviceCaError> for AuthError {
+    fn from(err: auth_crypto::DeviceCaError) -> Self {
+        use auth_crypto::DeviceCaError;
+        match err {
+            DeviceCaError::InvalidCsr(_) | DeviceCaError::InvalidCertificate(_) => {
+                AuthError::ValidationError {
+                    message: err.to_string(),
+                }
+            }
+            DeviceCaError::Signing(_) | DeviceCaError::Encoding(_) => {
+                AuthError::UTCryptoError(err.to_string())
+            }
+        }
+    }
+}
+
 impl From<validator::ValidationErrors> for AuthError {
     fn from(err: validator::ValidationErrors) -> Self {
         AuthError::ValidationError {
@@ -163,30 +660,112 @@ impl From<validator::ValidationErrors> for AuthError {
         }
     }
 }
+
+impl From<auth_platform::PlatformError> for AuthError {
+    fn from(err: auth_platform::PlatformError) -> Self {
+        AuthError::classified(err)
+    }
+}
+
 impl From<crate::services::otp_delivery::DeliveryError> for AuthError {
     fn from(err: crate::services::otp_delivery::DeliveryError) -> Self {
         use crate::services::otp_delivery::DeliveryError;
         match err {
             DeliveryError::CircuitBreakerOpen(s) => AuthError::CircuitBreakerOpen { service: s },
-            _ => AuthError::ExternalServiceError {
-                service: "otp_delivery".to_string(),
-                error: err.to_string(),
+            DeliveryError::BudgetExhausted { limit, period } => AuthError::RateLimitExceeded {
+                limit: u32::try_from(limit).unwrap_or(u32::MAX),
+                window: period,
             },
+            DeliveryError::DestinationBlocked(_) | DeliveryError::DestinationQuarantined => {
+                AuthError::ValidationError {
+                    message: "SMS cannot be sent to this number".to_string(),
+                }
+            }
+            err => AuthError::classified(err),
         }
     }
 }
+
 impl From<crate::services::otp_service::OtpError> for AuthError {
     fn from(err: crate::services::otp_service::OtpError) -> Self {
         use crate::services::otp_service::OtpError;
         match err {
-            OtpError::Invalid | OtpError::GenerationFailed => AuthError::InvalidOtp, // Generation failure internal really
+            OtpError::Invalid | OtpError::NotFound => AuthError::InvalidOtp,
             OtpError::Expired => AuthError::OtpExpired,
-            OtpError::NotFound => AuthError::InvalidOtp,
             OtpError::MaxAttemptsExceeded => AuthError::RateLimitExceeded {
                 limit: 5,
                 window: "session".to_string(),
-            }, // Mapping rough
-            _ => AuthError::InternalError,
+            },
+            err => AuthError::classified(err),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::otp_delivery::DeliveryError;
+
+    #[test]
+    fn test_classified_errors_keep_their_source() {
+        let err = AuthError::from(DeliveryError::SmsFailed("carrier said no".to_string()));
+        assert_eq!(err.category(), ErrorCategory::Upstream);
+        assert_eq!(err.code(), "AUTH_027");
+        assert_eq!(err.cause_code(), Some("DELIVERY_001"));
+        assert_eq!(ErrorCode::code(&err), "DELIVERY_001");
+        assert_eq!(err.status(), 502);
+        // Details of server faults stay out of the response...
+        assert_eq!(err.public_message(), "External service error");
+        // ...but not out of the error chain
+        let source = std::error::Error::source(&err).unwrap();
+        assert!(source.to_string().contains("carrier said no"));
+
+        let err = AuthError::from(DeliveryError::ComplianceViolation("STOP".to_string()));
+        assert_eq!(err.status(), 400);
+        assert!(err.public_message().contains("STOP"));
+    }
+
+    #[test]
+    fn test_status_follows_category_unless_overridden() {
+        assert_eq!(AuthError::UserNotFound.status(), 404);
+        assert_eq!(
+            AuthError::CircuitBreakerOpen {
+                service: "sms".into()
+            }
+            .status(),
+            503
+        );
+        assert_eq!(
+            AuthError::AccountLocked {
+                reason: "too many attempts".into()
+            }
+            .status(),
+            423
+        );
+        assert_eq!(
+            AuthError::TenantNotFound {
+                tenant_id: "t".into()
+            }
+            .code(),
+            ErrorCategory::NotFound.code()
+        );
+    }
+
+    #[test]
+    fn test_database_errors_are_classified() {
+        let err = AuthError::from(sqlx::Error::PoolTimedOut);
+        assert_eq!(err.cause_code(), Some("DB_002"));
+        assert_eq!(err.status(), 503);
+        assert_eq!(err.public_message(), "Service unavailable");
+
+        let err = AuthError::from(sqlx::Error::ColumnNotFound("secret_hash".into()));
+        assert_eq!(err.cause_code(), Some("DB_001"));
+        assert_eq!(err.status(), 500);
+        assert!(!err.public_message().contains("secret_hash"));
+
+        assert!(matches!(
+            AuthError::from(sqlx::Error::RowNotFound),
+            AuthError::UserNotFound
+        ));
+    }
+}
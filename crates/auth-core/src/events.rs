@@ -0,0 +1,193 @@
+//! In-process domain event bus
+//!
+//! Services publish [`DomainEvent`]s to an [`EventBus`]; interested components
+//! (caches, background workers) subscribe to react. When an [`EventRelay`] is
+//! attached, every locally published event is also forwarded so that other
+//! nodes can replay it through [`EventBus::ingest_remote`].
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+const DEFAULT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DomainEvent {
+    /// A role was created, updated or deleted, or its permission set changed
+    RoleChanged { tenant_id: Uuid, role_id: Uuid },
+    /// Roles were granted to or revoked from a single user
+    UserRolesChanged { tenant_id: Uuid, user_id: Uuid },
+    /// ABAC policies changed for a tenant
+    PolicyChanged { tenant_id: Uuid },
+    /// The tenant's status or settings changed; state cached at an older
+    /// version is stale
+    TenantChanged { tenant_id: Uuid, version: u64 },
+    /// A single session was revoked (logout, admin action)
+    SessionRevoked {
+        tenant_id: Uuid,
+        user_id: Uuid,
+        session_id: Uuid,
+    },
+    /// Every session of a user was revoked
+    UserSessionsRevoked { user_id: Uuid },
+    /// The user must re-authenticate with a stronger factor to continue
+    StepUpRequired {
+        tenant_id: Uuid,
+        user_id: Uuid,
+        reason: String,
+    },
+}
+
+impl DomainEvent {
+    /// The user the event is about, for events scoped to one user
+    pub fn user_id(&self) -> Option<Uuid> {
+        match self {
+            DomainEvent::UserRolesChanged { user_id, .. }
+            | DomainEvent::SessionRevoked { user_id, .. }
+            | DomainEvent::UserSessionsRevoked { user_id }
+            | DomainEvent::StepUpRequired { user_id, .. } => Some(*user_id),
+            DomainEvent::RoleChanged { .. }
+            | DomainEvent::PolicyChanged { .. }
+            | DomainEvent::TenantChanged { .. } => None,
+        }
+    }
+}
+
+/// Wire format for events crossing node boundaries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventEnvelope {
+    pub origin: Uuid,
+    pub event: DomainEvent,
+}
+
+/// Transport used to fan events out to other nodes
+#[async_trait]
+pub trait EventRelay: Send + Sync {
+    async fn publish(&self, envelope: &EventEnvelope) -> anyhow::Result<()>;
+}
+
+pub struct EventBus {
+    node_id: Uuid,
+    sender: broadcast::Sender<DomainEvent>,
+    relay: Option<Arc<dyn EventRelay>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            node_id: Uuid::new_v4(),
+            sender,
+            relay: None,
+        }
+    }
+
+    pub fn with_relay(mut self, relay: Arc<dyn EventRelay>) -> Self {
+        self.relay = Some(relay);
+        self
+    }
+
+    pub fn node_id(&self) -> Uuid {
+        self.node_id
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DomainEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Deliver to local subscribers and forward to the relay, if any.
+    /// Relay failures are logged; local delivery never depends on them.
+    pub async fn publish(&self, event: DomainEvent) {
+        // No receivers is not an error: nothing is interested yet
+        let _ = self.sender.send(event.clone());
+
+        if let Some(relay) = &self.relay {
+            let envelope = EventEnvelope {
+                origin: self.node_id,
+                event,
+            };
+            if let Err(e) = relay.publish(&envelope).await {
+                tracing::warn!("Failed to relay domain event: {}", e);
+            }
+        }
+    }
+
+    /// Deliver an event received from another node. Events this node
+    /// originated are dropped since they were already delivered locally.
+    pub fn ingest_remote(&self, envelope: EventEnvelope) {
+        if envelope.origin == self.node_id {
+            return;
+        }
+        let _ = self.sender.send(envelope.event);
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    #[derive(Default)]
+    struct RecordingRelay {
+        sent: Mutex<Vec<EventEnvelope>>,
+    }
+
+    #[async_trait]
+    impl EventRelay for RecordingRelay {
+        async fn publish(&self, envelope: &EventEnvelope) -> anyhow::Result<()> {
+            self.sent.lock().push(envelope.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_reaches_subscribers_and_relay() {
+        let relay = Arc::new(RecordingRelay::default());
+        let bus = EventBus::new().with_relay(relay.clone());
+        let mut rx = bus.subscribe();
+
+        let event = DomainEvent::PolicyChanged {
+            tenant_id: Uuid::new_v4(),
+        };
+        bus.publish(event.clone()).await;
+
+        assert_eq!(rx.recv().await.unwrap(), event);
+        let sent = relay.sent.lock();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].origin, bus.node_id());
+    }
+
+    #[tokio::test]
+    async fn test_ingest_remote_skips_own_events() {
+        let bus = EventBus::new();
+        let mut rx = bus.subscribe();
+        let event = DomainEvent::PolicyChanged {
+            tenant_id: Uuid::new_v4(),
+        };
+
+        bus.ingest_remote(EventEnvelope {
+            origin: bus.node_id(),
+            event: event.clone(),
+        });
+        bus.ingest_remote(EventEnvelope {
+            origin: Uuid::new_v4(),
+            event: event.clone(),
+        });
+
+        assert_eq!(rx.recv().await.unwrap(), event);
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -4,7 +4,9 @@
 //! independent of HTTP or database concerns.
 
 pub mod audit;
+pub mod context;
 pub mod error;
+pub mod events;
 pub mod models;
 pub mod resilience;
 pub mod services;
@@ -1,10 +1,16 @@
 //! Core data models
 
+pub mod device;
+pub mod oauth_client;
 pub mod organization;
 pub mod password_policy;
 pub mod permission;
+pub mod push;
 pub mod role;
+pub mod secret;
+pub mod service_account;
 pub mod session;
+pub mod sso;
 pub mod subscription;
 pub mod tenant;
 pub mod token;
@@ -12,11 +18,17 @@ pub mod user;
 pub mod user_tenant;
 pub mod validation;
 
+pub use device::*;
+pub use oauth_client::*;
 pub use organization::*;
 pub use password_policy::*;
 pub use permission::*;
+pub use push::*;
 pub use role::*;
+pub use secret::SensitiveString;
+pub use service_account::*;
 pub use session::*;
+pub use sso::*;
 pub use tenant::*;
 pub use token::*;
 pub use user::*;
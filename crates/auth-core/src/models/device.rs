@@ -0,0 +1,104 @@
+//! Device identities and the client certificates issued to them
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Single-use credential handed to a device at provisioning time. Only a
+/// SHA-256 digest of the token is stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceBootstrapToken {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub device_id: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DeviceCertificate {
+    /// Lowercase hex certificate serial
+    pub serial: String,
+    pub tenant_id: Uuid,
+    pub device_id: String,
+    pub certificate_pem: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// Serial of the certificate this one renewed
+    pub renewed_from: Option<String>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub revocation_reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DeviceCertificate {
+    pub fn is_valid(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.not_before <= now && now < self.not_after
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, utoipa::ToSchema)]
+pub struct CreateBootstrapTokenRequest {
+    /// Stable device identity; becomes the certificate common name
+    #[validate(length(min = 1, max = 128), custom(function = "validate_device_id"))]
+    pub device_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, utoipa::ToSchema)]
+pub struct DeviceEnrollmentRequest {
+    pub tenant_id: Uuid,
+    #[validate(length(min = 1, max = 256))]
+    pub bootstrap_token: String,
+    /// PKCS#10 request, PEM encoded
+    #[validate(length(min = 1, max = 16384))]
+    pub csr: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, utoipa::ToSchema)]
+pub struct DeviceRenewalRequest {
+    pub tenant_id: Uuid,
+    /// The certificate being renewed, PEM encoded
+    #[validate(length(min = 1, max = 16384))]
+    pub certificate: String,
+    /// Must be signed by the key of `certificate`
+    #[validate(length(min = 1, max = 16384))]
+    pub csr: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, utoipa::ToSchema)]
+pub struct RevokeDeviceCertificateRequest {
+    #[validate(length(max = 255))]
+    pub reason: Option<String>,
+}
+
+/// Device ids end up in an X.509 name, so keep them to characters that need
+/// no escaping there
+fn validate_device_id(device_id: &str) -> Result<(), validator::ValidationError> {
+    if device_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+    {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("device_id"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_id_rejects_name_metacharacters() {
+        let request = |id: &str| CreateBootstrapTokenRequest {
+            device_id: id.to_string(),
+        };
+        assert!(request("sensor-42.floor:3").validate().is_ok());
+        assert!(request("sensor,O=Other").validate().is_err());
+        assert!(request("").validate().is_err());
+    }
+}
@@ -0,0 +1,75 @@
+//! OAuth clients registered with the authorization server
+
+use crate::error::AuthError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A relying party allowed to request tokens. Secrets and registration
+/// access tokens are only ever stored hashed.
+#[derive(Debug, Clone)]
+pub struct OAuthClient {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub client_id: String,
+    /// Hash of the client secret; `None` for public clients
+    pub secret_hash: Option<String>,
+    pub token_endpoint_auth_method: TokenEndpointAuthMethod,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>,
+    pub name: Option<String>,
+    pub logo_uri: Option<String>,
+    /// Hash of the RFC 7592 registration access token
+    pub registration_token_hash: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl OAuthClient {
+    /// Public clients, such as native and browser apps, cannot keep a secret
+    pub fn is_public(&self) -> bool {
+        self.token_endpoint_auth_method == TokenEndpointAuthMethod::None
+    }
+}
+
+/// How a client authenticates at the token endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenEndpointAuthMethod {
+    #[default]
+    ClientSecretBasic,
+    ClientSecretPost,
+    /// A public client, with no secret
+    None,
+}
+
+impl TokenEndpointAuthMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenEndpointAuthMethod::ClientSecretBasic => "client_secret_basic",
+            TokenEndpointAuthMethod::ClientSecretPost => "client_secret_post",
+            TokenEndpointAuthMethod::None => "none",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "client_secret_basic" => Some(TokenEndpointAuthMethod::ClientSecretBasic),
+            "client_secret_post" => Some(TokenEndpointAuthMethod::ClientSecretPost),
+            "none" => Some(TokenEndpointAuthMethod::None),
+            _ => None,
+        }
+    }
+}
+
+#[async_trait]
+pub trait OAuthClientStore: Send + Sync {
+    async fn insert(&self, client: &OAuthClient) -> Result<(), AuthError>;
+    async fn find(&self, client_id: &str) -> Result<Option<OAuthClient>, AuthError>;
+    /// Replace the client's metadata and credentials
+    async fn update(&self, client: &OAuthClient) -> Result<(), AuthError>;
+    /// `false` if there was no such client
+    async fn delete(&self, client_id: &str) -> Result<bool, AuthError>;
+}
@@ -0,0 +1,176 @@
+//! Mobile authenticator devices and the push challenges sent to them
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PushPlatform {
+    /// Firebase Cloud Messaging (Android)
+    Fcm,
+    /// Apple Push Notification service (iOS)
+    Apns,
+}
+
+impl PushPlatform {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PushPlatform::Fcm => "fcm",
+            PushPlatform::Apns => "apns",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "fcm" => Some(PushPlatform::Fcm),
+            "apns" => Some(PushPlatform::Apns),
+            _ => None,
+        }
+    }
+}
+
+/// An authenticator app installation. The app keeps the private half of
+/// `public_key_pem` and signs every approve/deny answer with it.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct PushDevice {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub tenant_id: Uuid,
+    pub name: String,
+    pub platform: PushPlatform,
+    #[serde(skip_serializing)]
+    pub push_token: String,
+    /// P-256 SubjectPublicKeyInfo, PEM encoded
+    pub public_key_pem: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PushChallengeStatus {
+    Pending,
+    Approved,
+    Denied,
+    Expired,
+}
+
+impl PushChallengeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PushChallengeStatus::Pending => "pending",
+            PushChallengeStatus::Approved => "approved",
+            PushChallengeStatus::Denied => "denied",
+            PushChallengeStatus::Expired => "expired",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(PushChallengeStatus::Pending),
+            "approved" => Some(PushChallengeStatus::Approved),
+            "denied" => Some(PushChallengeStatus::Denied),
+            "expired" => Some(PushChallengeStatus::Expired),
+            _ => None,
+        }
+    }
+}
+
+/// A sign-in waiting for approval. `nonce` goes into the push payload and
+/// must be part of the signed answer, so an answer cannot be replayed
+/// against another challenge.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct PushChallenge {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub tenant_id: Uuid,
+    pub nonce: String,
+    pub status: PushChallengeStatus,
+    /// Shown on the prompt so the user can tell whether they started it
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    /// Device that answered
+    pub responded_by: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub responded_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PushChallenge {
+    /// Status as seen at `now`; a pending challenge past its expiry is expired
+    pub fn status_at(&self, now: DateTime<Utc>) -> PushChallengeStatus {
+        if self.status == PushChallengeStatus::Pending && now >= self.expires_at {
+            PushChallengeStatus::Expired
+        } else {
+            self.status
+        }
+    }
+
+    /// The exact bytes the app signs to answer this challenge
+    pub fn signing_payload(&self, approve: bool) -> String {
+        format!(
+            "{}.{}.{}",
+            self.id,
+            self.nonce,
+            if approve { "approve" } else { "deny" }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, utoipa::ToSchema)]
+pub struct RegisterPushDeviceRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    pub platform: PushPlatform,
+    /// FCM registration token or APNs device token
+    #[validate(length(min = 1, max = 4096))]
+    pub push_token: String,
+    #[validate(length(min = 1, max = 4096))]
+    pub public_key_pem: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, utoipa::ToSchema)]
+pub struct PushChallengeResponseRequest {
+    pub device_id: Uuid,
+    pub approve: bool,
+    /// Base64url ES256 signature (raw r||s) over `<challenge id>.<nonce>.<approve|deny>`
+    #[validate(length(min = 1, max = 512))]
+    pub signature: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_challenge_expires() {
+        let now = Utc::now();
+        let challenge = PushChallenge {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            tenant_id: Uuid::new_v4(),
+            nonce: "n".to_string(),
+            status: PushChallengeStatus::Pending,
+            ip_address: None,
+            user_agent: None,
+            responded_by: None,
+            expires_at: now,
+            responded_at: None,
+            created_at: now,
+        };
+        assert_eq!(
+            challenge.status_at(now - chrono::Duration::seconds(1)),
+            PushChallengeStatus::Pending
+        );
+        assert_eq!(challenge.status_at(now), PushChallengeStatus::Expired);
+
+        let denied = PushChallenge {
+            status: PushChallengeStatus::Denied,
+            ..challenge
+        };
+        assert_eq!(denied.status_at(now), PushChallengeStatus::Denied);
+    }
+}
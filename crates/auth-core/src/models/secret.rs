@@ -0,0 +1,107 @@
+//! Redacting wrapper for credential material carried in requests
+
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Password, OTP or token received from a client.
+///
+/// The buffer is zeroized on drop, and neither `Debug` nor `Serialize` ever
+/// emits the value, so a request struct can be logged or echoed safely.
+/// Reading it requires an explicit [`SensitiveString::expose`].
+pub struct SensitiveString(Secret<String>);
+
+impl SensitiveString {
+    pub fn new(value: String) -> Self {
+        Self(Secret::new(value))
+    }
+
+    pub fn expose(&self) -> &str {
+        self.0.expose_secret()
+    }
+
+    pub fn len(&self) -> usize {
+        self.expose().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expose().is_empty()
+    }
+}
+
+impl Clone for SensitiveString {
+    fn clone(&self) -> Self {
+        Self::new(self.expose().to_owned())
+    }
+}
+
+impl fmt::Debug for SensitiveString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl From<String> for SensitiveString {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for SensitiveString {
+    fn from(value: &str) -> Self {
+        Self::new(value.to_owned())
+    }
+}
+
+impl<'de> Deserialize<'de> for SensitiveString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+impl Serialize for SensitiveString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl validator::ValidateLength<u64> for SensitiveString {
+    fn length(&self) -> Option<u64> {
+        Some(self.expose().chars().count() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Login {
+        email: String,
+        password: SensitiveString,
+    }
+
+    #[test]
+    fn test_debug_and_serialize_redact() {
+        let login: Login =
+            serde_json::from_str(r#"{"email":"a@example.com","password":"hunter22"}"#).unwrap();
+        assert_eq!(login.password.expose(), "hunter22");
+
+        let debug = format!("{:?}", login);
+        assert!(!debug.contains("hunter22"));
+        assert!(debug.contains(REDACTED));
+
+        let json = serde_json::to_string(&login).unwrap();
+        assert!(!json.contains("hunter22"));
+    }
+
+    #[test]
+    fn test_length_counts_characters() {
+        use validator::ValidateLength;
+        let secret = SensitiveString::from("pässwörd");
+        assert_eq!(secret.length(), Some(8));
+        assert!(secret.validate_length(Some(8), Some(128), None));
+    }
+}
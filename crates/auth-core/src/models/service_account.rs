@@ -0,0 +1,180 @@
+//! Service accounts and their registered signing keys
+
+use crate::error::AuthError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+/// A non-human principal that authenticates with a private key it holds
+/// itself. Only the public half of each key is ever registered here.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ServiceAccount {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Upper bound on the scopes a token for this account may carry
+    pub scopes: Vec<String>,
+    pub status: ServiceAccountStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceAccountStatus {
+    #[default]
+    Active,
+    Disabled,
+}
+
+impl ServiceAccountStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceAccountStatus::Active => "active",
+            ServiceAccountStatus::Disabled => "disabled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "active" => ServiceAccountStatus::Active,
+            _ => ServiceAccountStatus::Disabled,
+        }
+    }
+}
+
+impl ServiceAccount {
+    pub fn new(tenant_id: Uuid, request: CreateServiceAccountRequest) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            tenant_id,
+            name: request.name,
+            description: request.description,
+            scopes: request.scopes,
+            status: ServiceAccountStatus::Active,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == ServiceAccountStatus::Active
+    }
+
+    /// Narrow a requested scope string to what the account is allowed.
+    /// No request means every allowed scope; asking for more is an error.
+    pub fn grant_scopes(&self, requested: Option<&str>) -> Result<Option<String>, AuthError> {
+        let granted: Vec<&str> = match requested {
+            None => self.scopes.iter().map(String::as_str).collect(),
+            Some(requested) => {
+                let requested: Vec<&str> = requested.split_whitespace().collect();
+                if let Some(scope) = requested
+                    .iter()
+                    .find(|s| !self.scopes.iter().any(|a| a == *s))
+                {
+                    return Err(AuthError::AuthorizationDenied {
+                        permission: scope.to_string(),
+                        resource: "service_account".to_string(),
+                    });
+                }
+                requested
+            }
+        };
+        Ok((!granted.is_empty()).then(|| granted.join(" ")))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+pub enum KeyAlgorithm {
+    RS256,
+    ES256,
+}
+
+impl KeyAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyAlgorithm::RS256 => "RS256",
+            KeyAlgorithm::ES256 => "ES256",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "RS256" => Some(KeyAlgorithm::RS256),
+            "ES256" => Some(KeyAlgorithm::ES256),
+            _ => None,
+        }
+    }
+}
+
+/// Public key registered for a service account. The id doubles as the
+/// `kid` the caller puts in the assertion header.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ServiceAccountKey {
+    pub id: Uuid,
+    pub service_account_id: Uuid,
+    pub algorithm: KeyAlgorithm,
+    pub public_key_pem: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// Set once the owner has been told this key is about to expire
+    pub reminder_sent_at: Option<DateTime<Utc>>,
+}
+
+impl ServiceAccountKey {
+    pub fn is_usable(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, utoipa::ToSchema)]
+pub struct CreateServiceAccountRequest {
+    #[validate(length(min = 1, max = 255))]
+    pub name: String,
+    #[validate(length(max = 1024))]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, utoipa::ToSchema)]
+pub struct RegisterServiceAccountKeyRequest {
+    pub algorithm: KeyAlgorithm,
+    /// SPKI PEM (`-----BEGIN PUBLIC KEY-----`)
+    #[validate(length(min = 1, max = 8192))]
+    pub public_key_pem: String,
+    /// Defaults to the platform key lifetime; capped at the maximum
+    pub expires_in_days: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grant_scopes_is_bounded_by_account() {
+        let account = ServiceAccount::new(
+            Uuid::new_v4(),
+            CreateServiceAccountRequest {
+                name: "ci".to_string(),
+                description: None,
+                scopes: vec!["read:users".to_string(), "write:users".to_string()],
+            },
+        );
+
+        assert_eq!(
+            account.grant_scopes(None).unwrap().as_deref(),
+            Some("read:users write:users")
+        );
+        assert_eq!(
+            account.grant_scopes(Some("read:users")).unwrap().as_deref(),
+            Some("read:users")
+        );
+        assert!(account.grant_scopes(Some("read:users admin")).is_err());
+    }
+}
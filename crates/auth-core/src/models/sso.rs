@@ -0,0 +1,79 @@
+//! Central SSO session bookkeeping
+//!
+//! One SSO session (the `sessions` row behind the IdP cookie) fans out into a
+//! client session per product that signed in through it. The client session
+//! id is the OIDC `sid` handed to that product, and the index of them is what
+//! logout walks to notify every product.
+//!
+//! In the other direction, a user who signed in through an upstream identity
+//! provider has a session there too; it is recorded so logout can end it.
+
+use crate::error::AuthError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClientSession {
+    /// OIDC `sid`, derived from the SSO session and client id
+    pub sid: String,
+    pub session_id: Uuid,
+    pub client_id: String,
+    pub user_id: Uuid,
+    pub tenant_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_authenticated_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+/// Logout endpoints a client registered (OIDC Front-/Back-Channel Logout)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ClientLogoutEndpoints {
+    pub client_id: String,
+    pub frontchannel_logout_uri: Option<String>,
+    /// Append `iss` and `sid` to the front-channel URI
+    pub frontchannel_logout_session_required: bool,
+    pub backchannel_logout_uri: Option<String>,
+    pub post_logout_redirect_uris: Vec<String>,
+}
+
+/// What the browser still has to do after the server side of a logout
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogoutOutcome {
+    /// URLs to load in hidden iframes
+    pub frontchannel_uris: Vec<String>,
+    /// Clients whose back-channel endpoint could not be reached
+    pub backchannel_failures: Vec<String>,
+}
+
+/// The user's session at the upstream identity provider they signed in
+/// through, as needed to log them out there
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UpstreamSession {
+    pub id: Uuid,
+    /// SSO session the sign-in created
+    pub session_id: Uuid,
+    pub tenant_id: Uuid,
+    pub user_id: Uuid,
+    /// Key of the provider in `security.sso.upstream_idps`
+    pub provider: String,
+    /// OIDC: ID token the provider issued, sent back as `id_token_hint`
+    pub id_token: Option<String>,
+    /// SAML: subject of the assertion
+    pub name_id: Option<String>,
+    /// SAML: `SessionIndex` of the assertion
+    pub session_index: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait UpstreamSessionStore: Send + Sync {
+    async fn record(&self, session: &UpstreamSession) -> Result<(), AuthError>;
+    async fn for_session(
+        &self,
+        tenant_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<Vec<UpstreamSession>, AuthError>;
+    async fn remove(&self, tenant_id: Uuid, session_id: Uuid) -> Result<(), AuthError>;
+}
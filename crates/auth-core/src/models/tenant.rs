@@ -19,6 +19,9 @@ pub struct Tenant {
     pub auth_config: serde_json::Value,
     pub compliance_config: serde_json::Value,
     pub status: TenantStatus,
+    /// Region whose database holds the tenant's rows; `None` if not pinned
+    #[serde(default)]
+    pub data_region: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -2,6 +2,7 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +19,28 @@ pub struct RefreshToken {
     pub revoked_at: Option<DateTime<Utc>>,
     pub revoked_reason: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// Session the token family was issued under
+    #[serde(default)]
+    pub session_id: Option<Uuid>,
+    /// Access token issued alongside this refresh token
+    #[serde(default)]
+    pub access_token_jti: Option<Uuid>,
+    /// End of the family's absolute lifetime; rotation never extends a
+    /// token past it. `None` for tokens issued before families were capped.
+    #[serde(default)]
+    pub family_expires_at: Option<DateTime<Utc>>,
+    /// OAuth client the family was issued to, which alone may rotate it.
+    /// `None` for first-party sign-in.
+    #[serde(default)]
+    pub client_id: Option<String>,
+}
+
+/// Ties a newly issued refresh token to its session and access token, so
+/// revoking the session can reach both
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionBinding {
+    pub session_id: Option<Uuid>,
+    pub access_token_jti: Option<Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,4 +70,13 @@ pub struct Claims {
     pub permissions: Vec<String>,
     pub roles: Vec<String>,
     pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// OAuth client the token was issued to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Claims added at issuance beyond roles and permissions, such as the
+    /// tenant's plan
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ext: BTreeMap<String, serde_json::Value>,
 }
@@ -5,6 +5,8 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use validator::Validate;
 
+use super::secret::SensitiveString;
+
 /// Identifier type for user authentication
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, utoipa::ToSchema)]
 #[sqlx(rename_all = "snake_case")]
@@ -12,6 +14,8 @@ pub enum IdentifierType {
     Email,
     Phone,
     Both,
+    /// A chosen handle, with no channel to deliver codes to
+    Username,
 }
 
 /// Primary identifier for login
@@ -20,6 +24,7 @@ pub enum IdentifierType {
 pub enum PrimaryIdentifier {
     Email,
     Phone,
+    Username,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, utoipa::ToSchema)]
@@ -40,6 +45,10 @@ pub struct User {
     pub phone_verified: bool,
     pub phone_verified_at: Option<DateTime<Utc>>,
 
+    /// As the user typed it; lookups go through the tenant's canonical form
+    #[serde(default)]
+    pub username: Option<String>,
+
     pub password_hash: Option<String>,
     pub password_changed_at: Option<DateTime<Utc>>,
     pub failed_login_attempts: u32,
@@ -71,6 +80,7 @@ impl Default for User {
             phone: None,
             phone_verified: false,
             phone_verified_at: None,
+            username: None,
             password_hash: None,
             password_changed_at: None,
             failed_login_attempts: 0,
@@ -112,11 +122,16 @@ pub struct CreateUserRequest {
 
     pub phone: Option<String>,
 
-    /// Primary identifier for login (email or phone)
+    /// Checked against the tenant's username policy
+    #[serde(default)]
+    pub username: Option<String>,
+
+    /// Primary identifier for login (email, phone or username)
     pub primary_identifier: Option<PrimaryIdentifier>,
 
     #[validate(length(min = 8, max = 128))]
-    pub password: Option<String>,
+    #[schema(value_type = Option<String>, format = Password)]
+    pub password: Option<SensitiveString>,
 
     pub profile_data: Option<serde_json::Value>,
 
@@ -37,14 +37,21 @@ pub fn validate_email(email: &str) -> Result<(), String> {
 }
 
 /// Detect identifier type from string
+///
+/// Anything that is neither phone-shaped nor holds an `@` is a username.
 pub fn detect_identifier_type(identifier: &str) -> IdentifierType {
-    if identifier.starts_with('+') || identifier.chars().all(|c| c.is_ascii_digit()) {
+    let identifier = identifier.trim();
+    let phone_shaped = identifier.chars().any(|c| c.is_ascii_digit())
+        && identifier
+            .chars()
+            .enumerate()
+            .all(|(i, c)| c.is_ascii_digit() || " -().".contains(c) || (i == 0 && c == '+'));
+    if phone_shaped {
         IdentifierType::Phone
     } else if identifier.contains('@') {
         IdentifierType::Email
     } else {
-        // Default to email for ambiguous cases
-        IdentifierType::Email
+        IdentifierType::Username
     }
 }
 
@@ -81,5 +88,13 @@ mod tests {
             detect_identifier_type("user@example.com"),
             IdentifierType::Email
         ));
+        assert!(matches!(
+            detect_identifier_type("+1 (415) 555-2671"),
+            IdentifierType::Phone
+        ));
+        assert!(matches!(
+            detect_identifier_type("jane.doe"),
+            IdentifierType::Username
+        ));
     }
 }
@@ -0,0 +1,369 @@
+//! Adaptive tenant request rates
+//!
+//! Learns each tenant's busiest minute per hour of the day (UTC) and derives
+//! its per-minute limit from that instead of the tier's fixed rate; see
+//! [`AdaptiveQuotaConfig`] for the rules. Like the quota buckets, traffic is
+//! counted in memory per instance, so each instance learns the share of
+//! traffic it serves and a restart starts learning over.
+
+use auth_config::AdaptiveQuotaConfig;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+/// Limit changes kept per tenant
+const HISTORY_LEN: usize = 288;
+
+/// Where a tenant's current limit comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitBasis {
+    /// The tier's rate; the hour has too little history
+    Static,
+    Learned,
+    /// Lowered after anomalous traffic
+    Tightened,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct LimitChange {
+    pub at: DateTime<Utc>,
+    pub requests_per_minute: u32,
+    pub basis: LimitBasis,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, utoipa::ToSchema)]
+pub struct HourProfile {
+    /// Hour of the day, UTC
+    pub hour: u32,
+    /// Learned requests in the busiest minute of the hour
+    pub peak_per_minute: f64,
+    /// Days the hour has been observed
+    pub days: u32,
+}
+
+/// A tenant's effective limit, learned profile and how the limit changed
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct AdaptiveLimitReport {
+    pub enabled: bool,
+    pub tier_requests_per_minute: u32,
+    pub requests_per_minute: u32,
+    pub basis: LimitBasis,
+    pub tightened_until: Option<DateTime<Utc>>,
+    pub profile: Vec<HourProfile>,
+    /// Oldest first
+    pub history: Vec<LimitChange>,
+}
+
+struct TenantTraffic {
+    hours: [HourProfile; 24],
+    /// Minutes and hours since the epoch of the counters below
+    minute: i64,
+    minute_count: u32,
+    hour: i64,
+    hour_peak: u32,
+    /// The hour is not learned from: it was only partly observed or was
+    /// anomalous
+    hour_skipped: bool,
+    tightened_until: Option<DateTime<Utc>>,
+    current: Option<(u32, LimitBasis)>,
+    history: VecDeque<LimitChange>,
+}
+
+impl TenantTraffic {
+    fn new(now: DateTime<Utc>) -> Self {
+        let mut hours = [HourProfile::default(); 24];
+        for (hour, profile) in hours.iter_mut().enumerate() {
+            profile.hour = hour as u32;
+        }
+        Self {
+            hours,
+            minute: now.timestamp().div_euclid(60),
+            minute_count: 0,
+            hour: now.timestamp().div_euclid(3600),
+            hour_peak: 0,
+            hour_skipped: true,
+            tightened_until: None,
+            current: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    fn learn(&mut self, hour: i64, peak: u32, learning_rate: f64) {
+        let profile = &mut self.hours[hour.rem_euclid(24) as usize];
+        if profile.days == 0 {
+            profile.peak_per_minute = peak as f64;
+        } else {
+            profile.peak_per_minute += learning_rate * (peak as f64 - profile.peak_per_minute);
+        }
+        profile.days = profile.days.saturating_add(1);
+    }
+
+    /// Start new minute and hour counters once `now` has left the current
+    /// ones, learning from the hours that ended
+    fn roll(&mut self, now: DateTime<Utc>, learning_rate: f64) {
+        let minute = now.timestamp().div_euclid(60);
+        if minute != self.minute {
+            self.minute = minute;
+            self.minute_count = 0;
+        }
+
+        let hour = now.timestamp().div_euclid(3600);
+        if hour > self.hour {
+            if !self.hour_skipped {
+                self.learn(self.hour, self.hour_peak, learning_rate);
+            }
+            // Hours without a single request had a peak of zero; a day of
+            // them is as far back as the profile goes
+            for idle in (self.hour + 1).max(hour - 24)..hour {
+                self.learn(idle, 0, learning_rate);
+            }
+            self.hour = hour;
+            self.hour_peak = 0;
+            self.hour_skipped = false;
+        }
+    }
+
+    fn baseline(&self, now: DateTime<Utc>, min_days: u32) -> Option<f64> {
+        let profile = &self.hours[now.timestamp().div_euclid(3600).rem_euclid(24) as usize];
+        (profile.days >= min_days.max(1)).then(|| profile.peak_per_minute.max(1.0))
+    }
+
+    fn tightened(&self, now: DateTime<Utc>) -> bool {
+        self.tightened_until.is_some_and(|until| now < until)
+    }
+}
+
+pub struct AdaptiveQuotas {
+    config: AdaptiveQuotaConfig,
+    tenants: DashMap<Uuid, TenantTraffic>,
+}
+
+impl AdaptiveQuotas {
+    pub fn new(config: AdaptiveQuotaConfig) -> Self {
+        Self {
+            config,
+            tenants: DashMap::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Count one request of `tenant_id` and return its per-minute limit
+    pub fn record(
+        &self,
+        tenant_id: Uuid,
+        tier_requests_per_minute: u32,
+        now: DateTime<Utc>,
+    ) -> u32 {
+        let mut traffic = self
+            .tenants
+            .entry(tenant_id)
+            .or_insert_with(|| TenantTraffic::new(now));
+        traffic.roll(now, self.config.learning_rate);
+        traffic.minute_count = traffic.minute_count.saturating_add(1);
+        traffic.hour_peak = traffic.hour_peak.max(traffic.minute_count);
+
+        if let Some(baseline) = traffic.baseline(now, self.config.min_days) {
+            let anomalous = traffic.minute_count as f64 > baseline * self.config.anomaly_multiplier;
+            if anomalous && !traffic.tightened(now) {
+                tracing::warn!(
+                    tenant_id = %tenant_id,
+                    requests = traffic.minute_count,
+                    baseline,
+                    "Anomalous request rate; tightening the tenant's limit"
+                );
+                metrics::counter!(
+                    "auth_quota_tightenings_total",
+                    1,
+                    "tenant_id" => tenant_id.to_string()
+                );
+            }
+            if anomalous {
+                traffic.tightened_until =
+                    Some(now + Duration::seconds(self.config.tighten_seconds as i64));
+            }
+        }
+        if traffic.tightened(now) {
+            traffic.hour_skipped = true;
+        }
+
+        let (limit, basis) = self.limit(&traffic, tier_requests_per_minute, now);
+        if traffic.current != Some((limit, basis)) {
+            traffic.current = Some((limit, basis));
+            if traffic.history.len() == HISTORY_LEN {
+                traffic.history.pop_front();
+            }
+            traffic.history.push_back(LimitChange {
+                at: now,
+                requests_per_minute: limit,
+                basis,
+            });
+            metrics::gauge!(
+                "auth_quota_effective_limit",
+                limit as f64,
+                "tenant_id" => tenant_id.to_string()
+            );
+        }
+        limit
+    }
+
+    fn limit(
+        &self,
+        traffic: &TenantTraffic,
+        tier_requests_per_minute: u32,
+        now: DateTime<Utc>,
+    ) -> (u32, LimitBasis) {
+        let tier = tier_requests_per_minute as f64;
+        let (target, basis) = match traffic.baseline(now, self.config.min_days) {
+            None => (tier, LimitBasis::Static),
+            Some(baseline) if traffic.tightened(now) => (
+                baseline * self.config.tightened_headroom,
+                LimitBasis::Tightened,
+            ),
+            Some(baseline) => (baseline * self.config.headroom, LimitBasis::Learned),
+        };
+        let limit = target
+            .clamp(tier * self.config.min_factor, tier * self.config.max_factor)
+            .round()
+            .max(1.0);
+        (limit as u32, basis)
+    }
+
+    /// The tenant's limit as of `now`, without counting a request
+    pub fn report(
+        &self,
+        tenant_id: Uuid,
+        tier_requests_per_minute: u32,
+        now: DateTime<Utc>,
+    ) -> AdaptiveLimitReport {
+        let fresh;
+        let entry = self.tenants.get(&tenant_id);
+        let traffic: &TenantTraffic = match &entry {
+            Some(entry) => entry,
+            None => {
+                fresh = TenantTraffic::new(now);
+                &fresh
+            }
+        };
+        let (requests_per_minute, basis) = if self.enabled() {
+            self.limit(traffic, tier_requests_per_minute, now)
+        } else {
+            (tier_requests_per_minute, LimitBasis::Static)
+        };
+        AdaptiveLimitReport {
+            enabled: self.enabled(),
+            tier_requests_per_minute,
+            requests_per_minute,
+            basis,
+            tightened_until: traffic.tightened_until.filter(|until| now < *until),
+            profile: traffic.hours.to_vec(),
+            history: traffic.history.iter().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn quotas() -> AdaptiveQuotas {
+        AdaptiveQuotas::new(AdaptiveQuotaConfig {
+            enabled: true,
+            min_days: 2,
+            learning_rate: 0.5,
+            ..Default::default()
+        })
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, day, hour, minute, 0).unwrap()
+    }
+
+    /// `per_minute` requests in each of the first five minutes of 09:00 on
+    /// `days`, with a request at 10:00 to close the hour
+    fn learn(quotas: &AdaptiveQuotas, tenant: Uuid, days: std::ops::Range<u32>, per_minute: u32) {
+        for day in days {
+            for minute in 0..5 {
+                for _ in 0..per_minute {
+                    quotas.record(tenant, 1000, at(day, 9, minute));
+                }
+            }
+            quotas.record(tenant, 1000, at(day, 10, 0));
+        }
+    }
+
+    #[test]
+    fn test_limit_follows_learned_peak_within_guard_rails() {
+        let quotas = quotas();
+        let tenant = Uuid::new_v4();
+
+        // The first, partial hour is not learned from
+        assert_eq!(quotas.record(tenant, 1000, at(1, 8, 59)), 1000);
+        learn(&quotas, tenant, 1..3, 200);
+        let report = quotas.report(tenant, 1000, at(3, 9, 0));
+        assert_eq!(report.profile[9].days, 2);
+        assert_eq!(report.profile[9].peak_per_minute, 200.0);
+        assert_eq!(report.basis, LimitBasis::Learned);
+        assert_eq!(report.requests_per_minute, 600);
+
+        // Busy hours are capped at max_factor, quiet ones floored at
+        // min_factor
+        learn(&quotas, tenant, 3..10, 2000);
+        assert_eq!(
+            quotas
+                .report(tenant, 1000, at(10, 9, 0))
+                .requests_per_minute,
+            2000
+        );
+        let night = quotas.report(tenant, 1000, at(10, 3, 0));
+        assert_eq!(night.profile[3].peak_per_minute, 0.0);
+        assert_eq!(night.requests_per_minute, 250);
+    }
+
+    #[test]
+    fn test_anomaly_tightens_and_is_not_learned() {
+        let quotas = quotas();
+        let tenant = Uuid::new_v4();
+        quotas.record(tenant, 1000, at(1, 8, 59));
+        learn(&quotas, tenant, 1..3, 50);
+        assert_eq!(
+            quotas.report(tenant, 1000, at(3, 9, 0)).requests_per_minute,
+            250
+        );
+
+        // 501 requests in a minute is over ten times the peak of 50
+        let mut limit = 0;
+        for _ in 0..501 {
+            limit = quotas.record(tenant, 1000, at(3, 9, 1));
+        }
+        assert_eq!(limit, 250);
+        let report = quotas.report(tenant, 1000, at(3, 9, 2));
+        assert_eq!(report.basis, LimitBasis::Tightened);
+        assert_eq!(report.tightened_until, Some(at(3, 9, 16)));
+        assert!(report
+            .history
+            .iter()
+            .any(|change| change.basis == LimitBasis::Tightened));
+
+        // The attack hour leaves the profile as it was
+        quotas.record(tenant, 1000, at(3, 10, 0));
+        let report = quotas.report(tenant, 1000, at(3, 10, 30));
+        assert_eq!(report.profile[9].days, 2);
+        assert_eq!(report.profile[9].peak_per_minute, 50.0);
+    }
+
+    #[test]
+    fn test_disabled_reports_tier_rate() {
+        let quotas = AdaptiveQuotas::new(AdaptiveQuotaConfig::default());
+        let report = quotas.report(Uuid::new_v4(), 600, Utc::now());
+        assert!(!report.enabled);
+        assert_eq!(report.requests_per_minute, 600);
+        assert_eq!(report.basis, LimitBasis::Static);
+    }
+}
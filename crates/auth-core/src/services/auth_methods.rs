@@ -0,0 +1,328 @@
+//! Sign-in methods allowed per tenant
+//!
+//! A tenant's policy lists the methods its users may sign in with:
+//! password, one-time code, passkey or federation. Login endpoints and the
+//! step-based flow check it before verifying anything, and the flow only
+//! offers the methods that are left.
+//!
+//! Retiring passwords goes in two steps. With `nudge_passkeys` the tenant
+//! recommends a passkey to every user who still signs in with a password,
+//! and [`PasskeyNudge`] emails the ones who have none. Once few enough are
+//! left, as [`PasswordOnlyStore::password_only_counts`] reports, `password`
+//! is dropped from the allowed methods.
+
+use crate::error::AuthError;
+use crate::services::otp_delivery::EmailProvider;
+use async_trait::async_trait;
+use auth_config::{AuthMethod, AuthMethodConfig, AuthMethodPolicy};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+
+pub struct AuthMethodPolicies {
+    default_policy: AuthMethodPolicy,
+    tenants: HashMap<Uuid, AuthMethodPolicy>,
+}
+
+impl AuthMethodPolicies {
+    pub fn new(config: &AuthMethodConfig) -> Self {
+        Self {
+            default_policy: config.default_policy.clone(),
+            tenants: config
+                .tenants
+                .iter()
+                .filter_map(|(id, policy)| Uuid::parse_str(id).ok().map(|id| (id, policy.clone())))
+                .collect(),
+        }
+    }
+
+    fn policy(&self, tenant_id: Uuid) -> &AuthMethodPolicy {
+        self.tenants.get(&tenant_id).unwrap_or(&self.default_policy)
+    }
+
+    pub fn allowed(&self, tenant_id: Uuid) -> &[AuthMethod] {
+        &self.policy(tenant_id).allowed
+    }
+
+    pub fn allows(&self, tenant_id: Uuid, method: AuthMethod) -> bool {
+        self.allowed(tenant_id).contains(&method)
+    }
+
+    /// Refuse a sign-in by a method the tenant does not allow
+    pub fn require(&self, tenant_id: Uuid, method: AuthMethod) -> Result<(), AuthError> {
+        if self.allows(tenant_id, method) {
+            return Ok(());
+        }
+        metrics::counter!("auth_method_refused_total", 1, "method" => method.as_str());
+        Err(AuthError::AuthMethodNotAllowed {
+            method: method.as_str().to_string(),
+        })
+    }
+
+    /// Whether password users of the tenant are steered towards passkeys
+    pub fn nudges_passkeys(&self, tenant_id: Uuid) -> bool {
+        let policy = self.policy(tenant_id);
+        policy.nudge_passkeys && policy.allowed.contains(&AuthMethod::Passkey)
+    }
+}
+
+impl Default for AuthMethodPolicies {
+    fn default() -> Self {
+        Self::new(&AuthMethodConfig::default())
+    }
+}
+
+/// A user who signs in with a password and has no passkey
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordOnlyUser {
+    pub user_id: Uuid,
+    pub tenant_id: Uuid,
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PasswordOnlyCount {
+    pub tenant_id: Uuid,
+    pub users: u64,
+}
+
+#[async_trait]
+pub trait PasswordOnlyStore: Send + Sync {
+    /// Users with a password and no passkey, by tenant, most first
+    async fn password_only_counts(
+        &self,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<PasswordOnlyCount>, AuthError>;
+    /// Active such users of a tenant, in id order after `after`
+    async fn password_only_users(
+        &self,
+        tenant_id: Uuid,
+        after: Option<Uuid>,
+        limit: usize,
+    ) -> Result<Vec<PasswordOnlyUser>, AuthError>;
+}
+
+/// Outcome of one nudge run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct NudgeReport {
+    pub tenant_id: Uuid,
+    /// Password-only users found
+    pub users: u64,
+    pub sent: u64,
+    pub failed: u64,
+    /// Users with no email address to write to
+    pub unreachable: u64,
+    pub dry_run: bool,
+}
+
+/// Emails a tenant's password-only users asking them to add a passkey.
+/// Run by an administrator; each run writes to every such user again.
+pub struct PasskeyNudge {
+    policies: Arc<AuthMethodPolicies>,
+    store: Arc<dyn PasswordOnlyStore>,
+    email: Arc<dyn EmailProvider>,
+    subject: String,
+    body: String,
+    batch_size: usize,
+}
+
+impl PasskeyNudge {
+    pub fn new(
+        policies: Arc<AuthMethodPolicies>,
+        store: Arc<dyn PasswordOnlyStore>,
+        email: Arc<dyn EmailProvider>,
+        config: &AuthMethodConfig,
+    ) -> Self {
+        Self {
+            policies,
+            store,
+            email,
+            subject: config.nudge_subject.clone(),
+            body: config.nudge_body.clone(),
+            batch_size: 500,
+        }
+    }
+
+    /// Users remaining on passwords alone, by tenant
+    pub async fn password_only(
+        &self,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<PasswordOnlyCount>, AuthError> {
+        self.store.password_only_counts(tenant_id).await
+    }
+
+    /// Nudge the tenant's password-only users; with `dry_run`, only count
+    /// them. Refused unless the tenant's policy nudges towards passkeys.
+    pub async fn run(&self, tenant_id: Uuid, dry_run: bool) -> Result<NudgeReport, AuthError> {
+        if !self.policies.nudges_passkeys(tenant_id) {
+            return Err(AuthError::ValidationError {
+                message: "The tenant's policy does not nudge users towards passkeys".to_string(),
+            });
+        }
+
+        let mut report = NudgeReport {
+            tenant_id,
+            dry_run,
+            ..Default::default()
+        };
+        let mut after = None;
+        loop {
+            let batch = self
+                .store
+                .password_only_users(tenant_id, after, self.batch_size)
+                .await?;
+            let Some(last) = batch.last() else {
+                break;
+            };
+            after = Some(last.user_id);
+            let full = batch.len() == self.batch_size;
+
+            for user in batch {
+                report.users += 1;
+                let Some(email) = &user.email else {
+                    report.unreachable += 1;
+                    continue;
+                };
+                if dry_run {
+                    continue;
+                }
+                match self
+                    .email
+                    .send_email(email, &self.subject, &self.body)
+                    .await
+                {
+                    Ok(_) => report.sent += 1,
+                    Err(e) => {
+                        warn!(user_id = %user.user_id, error = %e, "Passkey nudge not sent");
+                        report.failed += 1;
+                    }
+                }
+            }
+            if !full {
+                break;
+            }
+        }
+
+        metrics::counter!("auth_passkey_nudges_sent_total", report.sent);
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::otp_delivery::DeliveryError;
+    use parking_lot::Mutex;
+
+    fn policies(tenant_id: Uuid, policy: AuthMethodPolicy) -> AuthMethodPolicies {
+        AuthMethodPolicies::new(&AuthMethodConfig {
+            tenants: HashMap::from([(tenant_id.to_string(), policy)]),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_tenant_without_passwords_refuses_them() {
+        let tenant_id = Uuid::new_v4();
+        let policies = policies(
+            tenant_id,
+            AuthMethodPolicy {
+                allowed: vec![AuthMethod::Otp, AuthMethod::Passkey],
+                nudge_passkeys: false,
+            },
+        );
+
+        assert!(matches!(
+            policies.require(tenant_id, AuthMethod::Password),
+            Err(AuthError::AuthMethodNotAllowed { .. })
+        ));
+        assert!(policies.require(tenant_id, AuthMethod::Passkey).is_ok());
+        // Other tenants keep every method
+        assert!(policies
+            .require(Uuid::new_v4(), AuthMethod::Password)
+            .is_ok());
+    }
+
+    struct Store(Vec<PasswordOnlyUser>);
+
+    #[async_trait]
+    impl PasswordOnlyStore for Store {
+        async fn password_only_counts(
+            &self,
+            _tenant_id: Option<Uuid>,
+        ) -> Result<Vec<PasswordOnlyCount>, AuthError> {
+            Ok(vec![])
+        }
+
+        async fn password_only_users(
+            &self,
+            tenant_id: Uuid,
+            after: Option<Uuid>,
+            limit: usize,
+        ) -> Result<Vec<PasswordOnlyUser>, AuthError> {
+            let mut users: Vec<_> = self
+                .0
+                .iter()
+                .filter(|u| u.tenant_id == tenant_id && after.is_none_or(|a| u.user_id > a))
+                .cloned()
+                .collect();
+            users.sort_by_key(|u| u.user_id);
+            users.truncate(limit);
+            Ok(users)
+        }
+    }
+
+    #[derive(Default)]
+    struct Outbox(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl EmailProvider for Outbox {
+        async fn send_email(
+            &self,
+            to: &str,
+            _subject: &str,
+            _body: &str,
+        ) -> Result<String, DeliveryError> {
+            self.0.lock().push(to.to_string());
+            Ok("sent".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nudge_writes_to_password_only_users() {
+        let tenant_id = Uuid::new_v4();
+        let policies = Arc::new(policies(
+            tenant_id,
+            AuthMethodPolicy {
+                nudge_passkeys: true,
+                ..Default::default()
+            },
+        ));
+        let user = |email: Option<&str>| PasswordOnlyUser {
+            user_id: Uuid::new_v4(),
+            tenant_id,
+            email: email.map(str::to_string),
+        };
+        let store = Arc::new(Store(vec![user(Some("a@example.com")), user(None)]));
+        let outbox = Arc::new(Outbox::default());
+        let nudge = PasskeyNudge::new(
+            policies,
+            store,
+            outbox.clone(),
+            &AuthMethodConfig::default(),
+        );
+
+        let dry = nudge.run(tenant_id, true).await.unwrap();
+        assert_eq!((dry.users, dry.sent, dry.unreachable), (2, 0, 1));
+        assert!(outbox.0.lock().is_empty());
+
+        let report = nudge.run(tenant_id, false).await.unwrap();
+        assert_eq!(report.sent, 1);
+        assert_eq!(*outbox.0.lock(), vec!["a@example.com".to_string()]);
+
+        // Tenants that do not nudge cannot be nudged
+        assert!(nudge.run(Uuid::new_v4(), true).await.is_err());
+    }
+}
@@ -0,0 +1,220 @@
+//! Short-lived cache of effective permissions and tenant role lists
+//!
+//! Permission entries are keyed by (user, tenant, resource class), role lists
+//! by tenant, and both expire after a short TTL. Role and policy changes invalidate entries eagerly through the
+//! [`EventBus`], so the TTL only bounds staleness when an event is lost.
+
+use crate::events::{DomainEvent, EventBus};
+use crate::models::Role;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DecisionKey {
+    pub user_id: Uuid,
+    pub tenant_id: Uuid,
+    pub resource_class: String,
+}
+
+pub struct DecisionCache {
+    entries: DashMap<DecisionKey, (Arc<Vec<String>>, Instant)>,
+    roles: DashMap<Uuid, (Arc<Vec<Role>>, Instant)>,
+    ttl: Duration,
+}
+
+impl DecisionCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: DashMap::new(),
+            roles: DashMap::new(),
+            ttl,
+        }
+    }
+
+    pub fn get(&self, key: &DecisionKey) -> Option<Arc<Vec<String>>> {
+        let entry = self.entries.get(key)?;
+        if entry.1 > Instant::now() {
+            return Some(entry.0.clone());
+        }
+        drop(entry);
+        self.entries.remove(key);
+        None
+    }
+
+    pub fn insert(&self, key: DecisionKey, permissions: Vec<String>) -> Arc<Vec<String>> {
+        let permissions = Arc::new(permissions);
+        self.entries
+            .insert(key, (permissions.clone(), Instant::now() + self.ttl));
+        permissions
+    }
+
+    pub fn get_roles(&self, tenant_id: Uuid) -> Option<Arc<Vec<Role>>> {
+        let entry = self.roles.get(&tenant_id)?;
+        if entry.1 > Instant::now() {
+            return Some(entry.0.clone());
+        }
+        drop(entry);
+        self.roles.remove(&tenant_id);
+        None
+    }
+
+    pub fn insert_roles(&self, tenant_id: Uuid, roles: Vec<Role>) -> Arc<Vec<Role>> {
+        let roles = Arc::new(roles);
+        self.roles
+            .insert(tenant_id, (roles.clone(), Instant::now() + self.ttl));
+        roles
+    }
+
+    pub fn invalidate_user(&self, tenant_id: Uuid, user_id: Uuid) {
+        self.entries
+            .retain(|k, _| !(k.tenant_id == tenant_id && k.user_id == user_id));
+    }
+
+    /// Role membership is not tracked here, so a role change drops the whole tenant
+    pub fn invalidate_tenant(&self, tenant_id: Uuid) {
+        self.entries.retain(|k, _| k.tenant_id != tenant_id);
+        self.roles.remove(&tenant_id);
+    }
+
+    pub fn clear(&self) {
+        self.entries.clear();
+        self.roles.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn apply(&self, event: &DomainEvent) {
+        match event {
+            DomainEvent::UserRolesChanged { tenant_id, user_id } => {
+                self.invalidate_user(*tenant_id, *user_id)
+            }
+            DomainEvent::RoleChanged { tenant_id, .. }
+            | DomainEvent::PolicyChanged { tenant_id } => self.invalidate_tenant(*tenant_id),
+            // Session lifecycle and tenant settings do not affect permissions
+            DomainEvent::SessionRevoked { .. }
+            | DomainEvent::UserSessionsRevoked { .. }
+            | DomainEvent::StepUpRequired { .. }
+            | DomainEvent::TenantChanged { .. } => {}
+        }
+    }
+
+    /// Keep the cache in sync with the event bus until the bus is dropped
+    pub fn spawn_invalidation_listener(self: Arc<Self>, bus: &EventBus) -> JoinHandle<()> {
+        let mut rx = bus.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => self.apply(&event),
+                    Err(RecvError::Lagged(skipped)) => {
+                        // We cannot know what was missed, so nothing cached can be trusted
+                        tracing::warn!(
+                            "Decision cache missed {} invalidation events; clearing",
+                            skipped
+                        );
+                        self.clear();
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+impl Default for DecisionCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(user_id: Uuid, tenant_id: Uuid, class: &str) -> DecisionKey {
+        DecisionKey {
+            user_id,
+            tenant_id,
+            resource_class: class.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_entries_expire() {
+        let cache = DecisionCache::new(Duration::from_millis(0));
+        let k = key(Uuid::new_v4(), Uuid::new_v4(), "user");
+        cache.insert(k.clone(), vec!["user:read".to_string()]);
+        assert!(cache.get(&k).is_none());
+    }
+
+    #[test]
+    fn test_events_invalidate_matching_entries() {
+        let cache = DecisionCache::default();
+        let (tenant, other_tenant) = (Uuid::new_v4(), Uuid::new_v4());
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+
+        cache.insert(key(alice, tenant, "user"), vec![]);
+        cache.insert(key(bob, tenant, "user"), vec![]);
+        cache.insert(key(alice, other_tenant, "user"), vec![]);
+
+        cache.apply(&DomainEvent::UserRolesChanged {
+            tenant_id: tenant,
+            user_id: alice,
+        });
+        assert!(cache.get(&key(alice, tenant, "user")).is_none());
+        assert!(cache.get(&key(bob, tenant, "user")).is_some());
+
+        cache.apply(&DomainEvent::RoleChanged {
+            tenant_id: tenant,
+            role_id: Uuid::new_v4(),
+        });
+        assert!(cache.get(&key(bob, tenant, "user")).is_none());
+        assert!(cache.get(&key(alice, other_tenant, "user")).is_some());
+    }
+
+    #[test]
+    fn test_role_change_drops_tenant_role_list() {
+        let cache = DecisionCache::default();
+        let (tenant, other_tenant) = (Uuid::new_v4(), Uuid::new_v4());
+        cache.insert_roles(tenant, vec![]);
+        cache.insert_roles(other_tenant, vec![]);
+
+        cache.apply(&DomainEvent::RoleChanged {
+            tenant_id: tenant,
+            role_id: Uuid::new_v4(),
+        });
+        assert!(cache.get_roles(tenant).is_none());
+        assert!(cache.get_roles(other_tenant).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_listener_applies_bus_events() {
+        let bus = EventBus::new();
+        let cache = Arc::new(DecisionCache::default());
+        let _listener = cache.clone().spawn_invalidation_listener(&bus);
+
+        let tenant = Uuid::new_v4();
+        cache.insert(key(Uuid::new_v4(), tenant, "report"), vec![]);
+        bus.publish(DomainEvent::PolicyChanged { tenant_id: tenant })
+            .await;
+
+        for _ in 0..50 {
+            if cache.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(cache.is_empty());
+    }
+}
@@ -1,5 +1,7 @@
+pub mod decision_cache;
 pub mod policy;
 pub mod service;
 
+pub use decision_cache::{DecisionCache, DecisionKey};
 pub use policy::{AuthContext, PolicyDecision, PolicyEngine};
 pub use service::{AuthorizationService, RoleStore};
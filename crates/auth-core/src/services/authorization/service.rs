@@ -1,5 +1,9 @@
+use super::decision_cache::{DecisionCache, DecisionKey};
 use crate::error::AuthError;
+use crate::events::{DomainEvent, EventBus};
 use crate::models::{CreateRoleRequest, Role};
+use crate::services::forced_reauth::TokenGenerations;
+use crate::services::permission_sync::{PermissionChangeLog, PermissionSubject};
 use async_trait::async_trait;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -12,15 +16,141 @@ pub trait RoleStore: Send + Sync {
     async fn find_by_id(&self, id: Uuid, tenant_id: Uuid) -> Result<Option<Role>, AuthError>;
     async fn list(&self, tenant_id: Uuid) -> Result<Vec<Role>, AuthError>;
     async fn assign_permission(&self, role_id: Uuid, permission_id: Uuid) -> Result<(), AuthError>;
+    /// Permission codes granted to the user through active role assignments,
+    /// restricted to a single resource class (e.g. "user", "report")
+    async fn find_user_permissions(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        resource_class: &str,
+    ) -> Result<Vec<String>, AuthError>;
+    /// Names of the roles the user currently holds in the tenant
+    async fn find_user_role_names(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+    ) -> Result<Vec<String>, AuthError>;
 }
 
 pub struct AuthorizationService {
     role_store: Arc<dyn RoleStore>,
+    decisions: Arc<DecisionCache>,
+    events: Option<Arc<EventBus>>,
+    generations: Option<Arc<TokenGenerations>>,
+    changes: Option<Arc<dyn PermissionChangeLog>>,
 }
 
 impl AuthorizationService {
     pub fn new(role_store: Arc<dyn RoleStore>) -> Self {
-        Self { role_store }
+        Self {
+            role_store,
+            decisions: Arc::new(DecisionCache::default()),
+            events: None,
+            generations: None,
+            changes: None,
+        }
+    }
+
+    /// Invalidate a user's access tokens, which carry their roles, when
+    /// their role assignments change
+    pub fn with_token_generations(mut self, generations: Arc<TokenGenerations>) -> Self {
+        self.generations = Some(generations);
+        self
+    }
+
+    /// Record role and assignment changes in `log` for the permission sync
+    /// feed
+    pub fn with_change_log(mut self, log: Arc<dyn PermissionChangeLog>) -> Self {
+        self.changes = Some(log);
+        self
+    }
+
+    /// Publish role changes on `bus` and invalidate cached decisions from it,
+    /// including events relayed from other nodes
+    pub fn with_events(mut self, bus: Arc<EventBus>) -> Self {
+        self.decisions.clone().spawn_invalidation_listener(&bus);
+        self.events = Some(bus);
+        self
+    }
+
+    /// Effective permissions for the user within a resource class, served
+    /// from the decision cache when possible
+    pub async fn effective_permissions(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        resource_class: &str,
+    ) -> Result<Arc<Vec<String>>, AuthError> {
+        let key = DecisionKey {
+            user_id,
+            tenant_id,
+            resource_class: resource_class.to_string(),
+        };
+        if let Some(permissions) = self.decisions.get(&key) {
+            return Ok(permissions);
+        }
+
+        let permissions = self
+            .role_store
+            .find_user_permissions(user_id, tenant_id, resource_class)
+            .await?;
+        Ok(self.decisions.insert(key, permissions))
+    }
+
+    /// Check a permission code of the form `resource_class:action`
+    pub async fn has_permission(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        permission_code: &str,
+    ) -> Result<bool, AuthError> {
+        let resource_class = permission_code
+            .split_once(':')
+            .map(|(class, _)| class)
+            .unwrap_or(permission_code);
+        let permissions = self
+            .effective_permissions(user_id, tenant_id, resource_class)
+            .await?;
+        Ok(permissions.iter().any(|p| p == permission_code))
+    }
+
+    /// Roles defined in the tenant, served from the decision cache when
+    /// possible
+    pub async fn list_roles(&self, tenant_id: Uuid) -> Result<Arc<Vec<Role>>, AuthError> {
+        if let Some(roles) = self.decisions.get_roles(tenant_id) {
+            return Ok(roles);
+        }
+        let roles = self.role_store.list(tenant_id).await?;
+        Ok(self.decisions.insert_roles(tenant_id, roles))
+    }
+
+    /// Roles held by the user, for callers that map roles onto something
+    /// other than permission codes. Not cached.
+    pub async fn user_role_names(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+    ) -> Result<Vec<String>, AuthError> {
+        self.role_store
+            .find_user_role_names(user_id, tenant_id)
+            .await
+    }
+
+    async fn publish(&self, event: DomainEvent) {
+        // Recorded before publishing, so a feed reader woken by the event
+        // finds the entry
+        if let (Some(log), Some((tenant_id, subject))) =
+            (&self.changes, PermissionSubject::of(&event))
+        {
+            if let Err(e) = log.append(tenant_id, subject).await {
+                tracing::error!(tenant_id = %tenant_id, "Failed to record permission change: {}", e);
+            }
+        }
+        match &self.events {
+            Some(bus) => bus.publish(event).await,
+            // Without a bus, at least keep this node's cache coherent
+            None => self.decisions.apply(&event),
+        }
     }
 
     pub async fn create_role(
@@ -46,7 +176,46 @@ impl AuthorizationService {
             updated_at: None,
         };
 
-        self.role_store.create(role).await
+        let role = self.role_store.create(role).await?;
+        self.publish(DomainEvent::RoleChanged {
+            tenant_id,
+            role_id: role.id,
+        })
+        .await;
+        Ok(role)
+    }
+
+    pub async fn delete_role(&self, id: Uuid, tenant_id: Uuid) -> Result<(), AuthError> {
+        self.role_store.delete(id, tenant_id).await?;
+        self.publish(DomainEvent::RoleChanged {
+            tenant_id,
+            role_id: id,
+        })
+        .await;
+        Ok(())
+    }
+
+    pub async fn assign_permission(
+        &self,
+        tenant_id: Uuid,
+        role_id: Uuid,
+        permission_id: Uuid,
+    ) -> Result<(), AuthError> {
+        self.role_store
+            .assign_permission(role_id, permission_id)
+            .await?;
+        self.publish(DomainEvent::RoleChanged { tenant_id, role_id })
+            .await;
+        Ok(())
+    }
+
+    /// Notify that a user's role assignments changed outside this service
+    pub async fn user_roles_changed(&self, tenant_id: Uuid, user_id: Uuid) {
+        if let Some(generations) = &self.generations {
+            generations.bump_user(user_id, "role change").await;
+        }
+        self.publish(DomainEvent::UserRolesChanged { tenant_id, user_id })
+            .await;
     }
 
     // ... other CRUD methods
@@ -1,50 +1,514 @@
+//! Asynchronous audit pipeline
+//!
+//! Request handlers enqueue audit events on a bounded in-memory queue that
+//! [`AuditWorker`] drains into the persistent logger. When the queue is full
+//! the configured [`AuditOverflowPolicy`] decides between making the caller
+//! wait, evicting the oldest event, or spilling to a [`DurableQueue`] that
+//! the worker replays once it catches up (including after a restart).
+//!
+//! With a write-ahead log configured, every event goes through the durable
+//! queue instead and is only acknowledged once the sink has taken it.
+
 use crate::audit::{AuditEvent, AuditLogger};
 use async_trait::async_trait;
+use auth_config::{AuditOverflowPolicy, AuditPipelineConfig};
+use auth_platform::{DurableQueue, WalConfig, WalError};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::mpsc;
-use tracing::{error, info};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+
+/// Records replayed from disk per acknowledgement
+const REPLAY_BATCH: usize = 256;
+
+/// Snapshot of the pipeline for health checks and dashboards
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct AuditPipelineStats {
+    pub depth: usize,
+    pub capacity: usize,
+    pub dropped: u64,
+    pub spilled: u64,
+    /// Bytes waiting in the spill queue or write-ahead log
+    pub pending_bytes: u64,
+    /// Depth crossed the high watermark and has not yet recovered
+    pub falling_behind: bool,
+}
+
+struct Spill {
+    queue: DurableQueue,
+    /// Set while the queue holds events; new events follow them to keep order
+    active: AtomicBool,
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<AuditEvent>>,
+    capacity: usize,
+    high_watermark: usize,
+    low_watermark: usize,
+    policy: AuditOverflowPolicy,
+    spill: Option<Spill>,
+    wal: Option<DurableQueue>,
+    item_ready: Notify,
+    space_ready: Notify,
+    closed: AtomicBool,
+    falling_behind: AtomicBool,
+    dropped: AtomicU64,
+    spilled: AtomicU64,
+}
+
+impl Shared {
+    /// Publish depth and raise or clear the falling-behind alert. The alert
+    /// clears at half the high watermark so it does not flap.
+    fn observe_depth(&self, depth: usize) {
+        metrics::gauge!("auth_audit_queue_depth", depth as f64);
+
+        if depth >= self.high_watermark && !self.falling_behind.swap(true, Ordering::SeqCst) {
+            warn!(
+                target: "audit",
+                depth = depth,
+                capacity = self.capacity,
+                "Audit pipeline falling behind: queue above high watermark"
+            );
+            metrics::counter!("auth_audit_backlog_alerts_total", 1);
+        } else if depth <= self.low_watermark && self.falling_behind.swap(false, Ordering::SeqCst) {
+            info!(target: "audit", depth = depth, "Audit pipeline recovered");
+        }
+    }
+
+    fn record_drop(&self, reason: &'static str) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        metrics::counter!("auth_audit_events_dropped_total", 1, "reason" => reason);
+    }
+
+    /// Push if there is room, handing the event back otherwise
+    fn try_push(&self, event: AuditEvent) -> Result<(), Box<AuditEvent>> {
+        let mut queue = self.queue.lock();
+        if queue.len() >= self.capacity {
+            return Err(Box::new(event));
+        }
+        queue.push_back(event);
+        let depth = queue.len();
+        drop(queue);
+
+        self.observe_depth(depth);
+        self.item_ready.notify_one();
+        Ok(())
+    }
+
+    /// Write an event to a durable queue, counting it as dropped on failure
+    async fn persist(&self, queue: &DurableQueue, event: &AuditEvent) -> bool {
+        let result = match serde_json::to_vec(event) {
+            Ok(payload) => queue.append(payload).await,
+            Err(e) => Err(WalError::Io(e.into())),
+        };
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                error!(
+                    target: "audit",
+                    event_id = %event.id,
+                    action = %event.action,
+                    "Failed to persist audit event: {}",
+                    e
+                );
+                self.record_drop(match e {
+                    WalError::Full { .. } => "disk_full",
+                    _ => "disk_error",
+                });
+                false
+            }
+        }
+    }
+}
 
 /// A channel-based audit logger that offloads writing to a background task
 pub struct AsyncAuditLogger {
-    sender: mpsc::Sender<AuditEvent>,
+    shared: Arc<Shared>,
+}
+
+/// Consumer side of the pipeline, handed to [`AuditWorker::new`]
+pub struct AuditReceiver {
+    shared: Arc<Shared>,
 }
 
 impl AsyncAuditLogger {
-    pub fn new(buffer_size: usize) -> (Self, mpsc::Receiver<AuditEvent>) {
-        let (tx, rx) = mpsc::channel(buffer_size);
-        (Self { sender: tx }, rx)
+    /// Fails only if a configured spill queue or write-ahead log cannot be opened
+    pub fn new(config: &AuditPipelineConfig) -> Result<(Self, AuditReceiver), WalError> {
+        let capacity = config.capacity.max(1);
+        let high_watermark = ((capacity as f64 * config.high_watermark).ceil() as usize).max(1);
+
+        let spill = match &config.overflow {
+            AuditOverflowPolicy::Spill { path } => {
+                let queue = DurableQueue::open(WalConfig::new(path))?;
+                // Leftovers from a previous run are replayed before new events
+                let active = AtomicBool::new(queue.stats().pending_bytes > 0);
+                Some(Spill { queue, active })
+            }
+            _ => None,
+        };
+        let wal = config.wal.clone().map(DurableQueue::open).transpose()?;
+
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            high_watermark,
+            low_watermark: high_watermark / 2,
+            policy: config.overflow.clone(),
+            spill,
+            wal,
+            item_ready: Notify::new(),
+            space_ready: Notify::new(),
+            closed: AtomicBool::new(false),
+            falling_behind: AtomicBool::new(false),
+            dropped: AtomicU64::new(0),
+            spilled: AtomicU64::new(0),
+        });
+
+        Ok((
+            Self {
+                shared: shared.clone(),
+            },
+            AuditReceiver { shared },
+        ))
+    }
+
+    pub fn stats(&self) -> AuditPipelineStats {
+        let shared = &self.shared;
+        let pending_bytes = shared
+            .wal
+            .iter()
+            .chain(shared.spill.as_ref().map(|s| &s.queue))
+            .map(|q| q.stats().pending_bytes)
+            .sum();
+        AuditPipelineStats {
+            depth: shared.queue.lock().len(),
+            capacity: shared.capacity,
+            dropped: shared.dropped.load(Ordering::Relaxed),
+            spilled: shared.spilled.load(Ordering::Relaxed),
+            pending_bytes,
+            falling_behind: shared.falling_behind.load(Ordering::SeqCst),
+        }
+    }
+
+    async fn enqueue_blocking(&self, mut event: AuditEvent, timeout: Duration) {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Register for wakeups before checking, so a pop in between is not missed
+            let space = self.shared.space_ready.notified();
+            tokio::pin!(space);
+            space.as_mut().enable();
+
+            event = match self.shared.try_push(event) {
+                Ok(()) => return,
+                Err(event) => *event,
+            };
+
+            if tokio::time::timeout_at(deadline, space).await.is_err() {
+                error!(
+                    target: "audit",
+                    event_id = %event.id,
+                    action = %event.action,
+                    "Audit queue full; event dropped after waiting {:?}",
+                    timeout
+                );
+                self.shared.record_drop("timeout");
+                return;
+            }
+        }
+    }
+
+    fn enqueue_drop_oldest(&self, event: AuditEvent) {
+        let mut queue = self.shared.queue.lock();
+        let evicted = if queue.len() >= self.shared.capacity {
+            queue.pop_front()
+        } else {
+            None
+        };
+        queue.push_back(event);
+        let depth = queue.len();
+        drop(queue);
+
+        if let Some(evicted) = evicted {
+            warn!(
+                target: "audit",
+                event_id = %evicted.id,
+                action = %evicted.action,
+                "Audit queue full; oldest event evicted"
+            );
+            self.shared.record_drop("evicted");
+        }
+        self.shared.observe_depth(depth);
+        self.shared.item_ready.notify_one();
+    }
+
+    async fn enqueue_spilling(&self, spill: &Spill, event: AuditEvent) {
+        let event = if spill.active.load(Ordering::SeqCst) {
+            event
+        } else {
+            match self.shared.try_push(event) {
+                Ok(()) => return,
+                Err(event) => *event,
+            }
+        };
+
+        if self.shared.persist(&spill.queue, &event).await {
+            spill.active.store(true, Ordering::SeqCst);
+            self.shared.spilled.fetch_add(1, Ordering::Relaxed);
+            metrics::counter!("auth_audit_events_spilled_total", 1);
+            self.shared.item_ready.notify_one();
+        }
+    }
+}
+
+impl Drop for AsyncAuditLogger {
+    fn drop(&mut self) {
+        // Let the worker finish the backlog and exit
+        self.shared.closed.store(true, Ordering::SeqCst);
+        self.shared.item_ready.notify_one();
     }
 }
 
 #[async_trait]
 impl AuditLogger for AsyncAuditLogger {
     async fn log(&self, event: AuditEvent) {
-        // We use try_send to avoid blocking if buffer is full, or send to wait.
-        // For audit, we generally prefer not to block the auth flow if logging is slow,
-        // but we also don't want to lose logs.
-        // A bounded channel provides backpressure.
-        if let Err(e) = self.sender.send(event).await {
-            error!("Failed to send audit event to background worker: {}", e);
+        if let Some(wal) = &self.shared.wal {
+            if self.shared.persist(wal, &event).await {
+                self.shared.item_ready.notify_one();
+            }
+            return;
+        }
+
+        match &self.shared.policy {
+            AuditOverflowPolicy::Block { timeout_ms } => {
+                self.enqueue_blocking(event, Duration::from_millis(*timeout_ms))
+                    .await
+            }
+            AuditOverflowPolicy::DropOldest => self.enqueue_drop_oldest(event),
+            AuditOverflowPolicy::Spill { .. } => match &self.shared.spill {
+                Some(spill) => self.enqueue_spilling(spill, event).await,
+                None => self.enqueue_drop_oldest(event),
+            },
         }
     }
 }
 
 /// The background worker that consumes events and writes them to the underlying storage
 pub struct AuditWorker {
-    receiver: mpsc::Receiver<AuditEvent>,
+    receiver: AuditReceiver,
     delegate: Arc<dyn AuditLogger>,
 }
 
 impl AuditWorker {
-    pub fn new(receiver: mpsc::Receiver<AuditEvent>, delegate: Arc<dyn AuditLogger>) -> Self {
+    pub fn new(receiver: AuditReceiver, delegate: Arc<dyn AuditLogger>) -> Self {
         Self { receiver, delegate }
     }
 
-    pub async fn run(mut self) {
+    pub async fn run(self) {
         info!("Audit background worker started");
-        while let Some(event) = self.receiver.recv().await {
-            self.delegate.log(event).await;
+        let shared = self.receiver.shared.clone();
+
+        loop {
+            let ready = shared.item_ready.notified();
+            tokio::pin!(ready);
+            ready.as_mut().enable();
+
+            if let Some(wal) = &shared.wal {
+                match self.replay(wal).await {
+                    Ok(0) => {}
+                    Ok(_) => continue,
+                    Err(e) => {
+                        error!("Failed to read audit write-ahead log: {}", e);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                }
+            }
+
+            let next = {
+                let mut queue = shared.queue.lock();
+                queue.pop_front().map(|event| (event, queue.len()))
+            };
+            if let Some((event, depth)) = next {
+                shared.observe_depth(depth);
+                shared.space_ready.notify_one();
+                self.delegate.log(event).await;
+                continue;
+            }
+
+            // Queue drained: replay anything that overflowed to disk. Clearing
+            // the flag before reading means a concurrent spill either shows up
+            // in this read or sets the flag again.
+            if let Some(spill) = &shared.spill {
+                if spill.active.swap(false, Ordering::SeqCst) {
+                    match self.replay(&spill.queue).await {
+                        Ok(0) => {}
+                        Ok(replayed) => {
+                            spill.active.store(true, Ordering::SeqCst);
+                            info!("Replayed {} spilled audit events", replayed);
+                        }
+                        Err(e) => {
+                            spill.active.store(true, Ordering::SeqCst);
+                            error!("Failed to replay spilled audit events: {}", e);
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            if shared.closed.load(Ordering::SeqCst) {
+                break;
+            }
+            ready.await;
         }
         info!("Audit background worker stopped");
     }
+
+    /// Deliver one batch from a durable queue and acknowledge it
+    async fn replay(&self, queue: &DurableQueue) -> Result<usize, WalError> {
+        let batch = queue.peek(REPLAY_BATCH).await?;
+        let Some(last) = batch.last() else {
+            return Ok(0);
+        };
+        let through = last.next;
+
+        for record in &batch {
+            match serde_json::from_slice::<AuditEvent>(&record.payload) {
+                Ok(event) => self.delegate.log(event).await,
+                Err(e) => warn!("Skipping unreadable persisted audit event: {}", e),
+            }
+        }
+        queue.ack(through).await?;
+        Ok(batch.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{AuditCategory, AuditSeverity};
+
+    #[derive(Default)]
+    struct CollectingLogger {
+        actions: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl AuditLogger for CollectingLogger {
+        async fn log(&self, event: AuditEvent) {
+            self.actions.lock().push(event.action);
+        }
+    }
+
+    fn event(action: &str) -> AuditEvent {
+        AuditEvent::new(AuditCategory::System, action, AuditSeverity::Info)
+    }
+
+    fn config(capacity: usize, overflow: AuditOverflowPolicy) -> AuditPipelineConfig {
+        AuditPipelineConfig {
+            capacity,
+            overflow,
+            high_watermark: 0.5,
+            wal: None,
+            routing: Default::default(),
+        }
+    }
+
+    async fn drain(logger: AsyncAuditLogger, receiver: AuditReceiver) -> Vec<String> {
+        let sink = Arc::new(CollectingLogger::default());
+        drop(logger);
+        AuditWorker::new(receiver, sink.clone()).run().await;
+        let actions = sink.actions.lock().clone();
+        actions
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_evicts_and_counts() {
+        let (logger, receiver) =
+            AsyncAuditLogger::new(&config(2, AuditOverflowPolicy::DropOldest)).unwrap();
+        for action in ["a", "b", "c"] {
+            logger.log(event(action)).await;
+        }
+
+        let stats = logger.stats();
+        assert_eq!(stats.dropped, 1);
+        assert!(stats.falling_behind);
+        assert_eq!(drain(logger, receiver).await, vec!["b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_block_times_out_and_drops() {
+        let (logger, receiver) =
+            AsyncAuditLogger::new(&config(1, AuditOverflowPolicy::Block { timeout_ms: 20 }))
+                .unwrap();
+        logger.log(event("a")).await;
+        logger.log(event("b")).await;
+
+        assert_eq!(logger.stats().dropped, 1);
+        assert_eq!(drain(logger, receiver).await, vec!["a"]);
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_worker() {
+        let (logger, receiver) =
+            AsyncAuditLogger::new(&config(1, AuditOverflowPolicy::Block { timeout_ms: 5_000 }))
+                .unwrap();
+        let sink = Arc::new(CollectingLogger::default());
+        let worker = tokio::spawn(AuditWorker::new(receiver, sink.clone()).run());
+
+        for action in ["a", "b", "c"] {
+            logger.log(event(action)).await;
+        }
+        assert_eq!(logger.stats().dropped, 0);
+
+        drop(logger);
+        worker.await.unwrap();
+        assert_eq!(*sink.actions.lock(), vec!["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_spill_preserves_order_and_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spill").to_string_lossy().to_string();
+        let policy = AuditOverflowPolicy::Spill { path };
+
+        // First run: overflow spills to disk and the process "crashes"
+        let (logger, receiver) = AsyncAuditLogger::new(&config(1, policy.clone())).unwrap();
+        for action in ["a", "b", "c"] {
+            logger.log(event(action)).await;
+        }
+        assert_eq!(logger.stats().spilled, 2);
+        drop((logger, receiver));
+
+        // Next run replays the spilled events
+        let (logger, receiver) = AsyncAuditLogger::new(&config(1, policy)).unwrap();
+        assert!(logger.stats().pending_bytes > 0);
+        assert_eq!(drain(logger, receiver).await, vec!["b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_write_ahead_log_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let pipeline = AuditPipelineConfig {
+            wal: Some(WalConfig::new(dir.path())),
+            ..AuditPipelineConfig::default()
+        };
+
+        let (logger, receiver) = AsyncAuditLogger::new(&pipeline).unwrap();
+        for action in ["a", "b"] {
+            logger.log(event(action)).await;
+        }
+        drop((logger, receiver));
+
+        let (logger, receiver) = AsyncAuditLogger::new(&pipeline).unwrap();
+        logger.log(event("c")).await;
+        assert_eq!(drain(logger, receiver).await, vec!["a", "b", "c"]);
+
+        let (logger, receiver) = AsyncAuditLogger::new(&pipeline).unwrap();
+        assert_eq!(logger.stats().pending_bytes, 0);
+        assert!(drain(logger, receiver).await.is_empty());
+    }
 }
@@ -0,0 +1,445 @@
+//! CAPTCHA challenges for logins under attack
+//!
+//! A challenge is only inserted when a login looks automated: the risk score
+//! is high or the client has failed repeatedly. Tokens solved in the browser
+//! are always verified server-side against the provider's siteverify API.
+
+use crate::error::AuthError;
+use async_trait::async_trait;
+use auth_config::{CaptchaConfig, CaptchaProviderKind, CaptchaTenantOverride, SecurityConfig};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+pub const HCAPTCHA_VERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+pub const TURNSTILE_VERIFY_URL: &str = "https://challenges.cloudflare.com/turnstile/v0/siteverify";
+
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum CaptchaError {
+    #[error("Verification request failed: {0}")]
+    Transport(String),
+    #[error("Unexpected verification response: {0}")]
+    InvalidResponse(String),
+}
+
+/// Outcome of a siteverify call
+#[derive(Debug, Clone, Default)]
+pub struct CaptchaVerification {
+    pub success: bool,
+    pub hostname: Option<String>,
+    pub error_codes: Vec<String>,
+}
+
+#[async_trait]
+pub trait CaptchaProvider: Send + Sync {
+    /// Name reported to clients so they load the matching widget
+    fn name(&self) -> &'static str;
+
+    async fn verify(
+        &self,
+        token: &str,
+        remote_ip: Option<&str>,
+    ) -> Result<CaptchaVerification, CaptchaError>;
+}
+
+#[derive(Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+    #[serde(default)]
+    hostname: Option<String>,
+    #[serde(default, rename = "error-codes")]
+    error_codes: Vec<String>,
+}
+
+/// hCaptcha and Turnstile share the same siteverify contract
+struct SiteVerifyClient {
+    client: reqwest::Client,
+    endpoint: String,
+    secret: Secret<String>,
+}
+
+impl SiteVerifyClient {
+    fn new(endpoint: &str, secret: Secret<String>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(VERIFY_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            client,
+            endpoint: endpoint.to_string(),
+            secret,
+        }
+    }
+
+    async fn verify(
+        &self,
+        token: &str,
+        remote_ip: Option<&str>,
+    ) -> Result<CaptchaVerification, CaptchaError> {
+        let mut form = vec![
+            ("secret", self.secret.expose_secret().as_str()),
+            ("response", token),
+        ];
+        if let Some(ip) = remote_ip {
+            form.push(("remoteip", ip));
+        }
+
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| CaptchaError::Transport(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(CaptchaError::Transport(format!(
+                "status {}",
+                response.status()
+            )));
+        }
+
+        let body: SiteVerifyResponse = response
+            .json()
+            .await
+            .map_err(|e| CaptchaError::InvalidResponse(e.to_string()))?;
+        Ok(CaptchaVerification {
+            success: body.success,
+            hostname: body.hostname,
+            error_codes: body.error_codes,
+        })
+    }
+}
+
+pub struct HCaptchaProvider {
+    inner: SiteVerifyClient,
+}
+
+impl HCaptchaProvider {
+    pub fn new(secret: Secret<String>) -> Self {
+        Self::with_endpoint(HCAPTCHA_VERIFY_URL, secret)
+    }
+
+    pub fn with_endpoint(endpoint: &str, secret: Secret<String>) -> Self {
+        Self {
+            inner: SiteVerifyClient::new(endpoint, secret),
+        }
+    }
+}
+
+#[async_trait]
+impl CaptchaProvider for HCaptchaProvider {
+    fn name(&self) -> &'static str {
+        "hcaptcha"
+    }
+
+    async fn verify(
+        &self,
+        token: &str,
+        remote_ip: Option<&str>,
+    ) -> Result<CaptchaVerification, CaptchaError> {
+        self.inner.verify(token, remote_ip).await
+    }
+}
+
+/// Cloudflare Turnstile
+pub struct TurnstileProvider {
+    inner: SiteVerifyClient,
+}
+
+impl TurnstileProvider {
+    pub fn new(secret: Secret<String>) -> Self {
+        Self::with_endpoint(TURNSTILE_VERIFY_URL, secret)
+    }
+
+    pub fn with_endpoint(endpoint: &str, secret: Secret<String>) -> Self {
+        Self {
+            inner: SiteVerifyClient::new(endpoint, secret),
+        }
+    }
+}
+
+#[async_trait]
+impl CaptchaProvider for TurnstileProvider {
+    fn name(&self) -> &'static str {
+        "turnstile"
+    }
+
+    async fn verify(
+        &self,
+        token: &str,
+        remote_ip: Option<&str>,
+    ) -> Result<CaptchaVerification, CaptchaError> {
+        self.inner.verify(token, remote_ip).await
+    }
+}
+
+/// What is known about a login attempt when deciding on a challenge
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaptchaSignals {
+    pub risk_score: f32,
+    pub recent_failures: u32,
+}
+
+/// Decides whether a login needs a challenge and verifies solved ones
+pub struct CaptchaService {
+    provider: Option<Arc<dyn CaptchaProvider>>,
+    site_key: Option<String>,
+    config: CaptchaConfig,
+    tenants: HashMap<Uuid, CaptchaTenantOverride>,
+}
+
+impl CaptchaService {
+    pub fn new(
+        provider: Option<Arc<dyn CaptchaProvider>>,
+        site_key: Option<String>,
+        config: CaptchaConfig,
+    ) -> Self {
+        let tenants = config
+            .tenants
+            .iter()
+            .filter_map(|(id, o)| match Uuid::parse_str(id) {
+                Ok(id) => Some((id, o.clone())),
+                Err(_) => {
+                    tracing::warn!("Ignoring CAPTCHA override for invalid tenant id '{}'", id);
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            provider,
+            site_key,
+            config,
+            tenants,
+        }
+    }
+
+    pub fn from_security_config(security: &SecurityConfig) -> Self {
+        let config = security.captcha.clone();
+        let provider = config.secret_key.clone().map(|secret| {
+            let provider: Arc<dyn CaptchaProvider> = match config.provider {
+                CaptchaProviderKind::HCaptcha => Arc::new(HCaptchaProvider::new(secret)),
+                CaptchaProviderKind::Turnstile => Arc::new(TurnstileProvider::new(secret)),
+            };
+            provider
+        });
+        Self::new(provider, config.site_key.clone(), config)
+    }
+
+    pub fn enabled_for(&self, tenant_id: Uuid) -> bool {
+        self.provider.is_some()
+            && self
+                .tenants
+                .get(&tenant_id)
+                .and_then(|o| o.enabled)
+                .unwrap_or(self.config.enabled)
+    }
+
+    pub fn requires_challenge(&self, tenant_id: Uuid, signals: CaptchaSignals) -> bool {
+        if !self.enabled_for(tenant_id) {
+            return false;
+        }
+        let tenant = self.tenants.get(&tenant_id);
+        let risk_threshold = tenant
+            .and_then(|o| o.risk_threshold)
+            .unwrap_or(self.config.risk_threshold);
+        let failure_threshold = tenant
+            .and_then(|o| o.failure_threshold)
+            .unwrap_or(self.config.failure_threshold);
+
+        signals.risk_score >= risk_threshold || signals.recent_failures >= failure_threshold
+    }
+
+    pub fn provider_name(&self) -> Option<&'static str> {
+        self.provider.as_ref().map(|p| p.name())
+    }
+
+    pub fn site_key(&self) -> Option<&str> {
+        self.site_key.as_deref()
+    }
+
+    /// Error telling the client which widget to render
+    pub fn challenge(&self) -> AuthError {
+        AuthError::CaptchaRequired {
+            provider: self.provider_name().unwrap_or_default().to_string(),
+            site_key: self.site_key.clone(),
+        }
+    }
+
+    /// Verify a solved challenge. A missing token re-issues the challenge.
+    pub async fn verify(
+        &self,
+        token: Option<&str>,
+        remote_ip: Option<&str>,
+    ) -> Result<(), AuthError> {
+        let Some(provider) = &self.provider else {
+            return Ok(());
+        };
+        let Some(token) = token.filter(|t| !t.is_empty()) else {
+            return Err(self.challenge());
+        };
+
+        let verification = provider.verify(token, remote_ip).await.map_err(|e| {
+            AuthError::ExternalServiceError {
+                service: provider.name().to_string(),
+                error: e.to_string(),
+            }
+        })?;
+        if !verification.success {
+            tracing::debug!(errors = ?verification.error_codes, "CAPTCHA verification rejected");
+            return Err(AuthError::CaptchaFailed);
+        }
+        Ok(())
+    }
+
+    /// Window over which failed logins count towards a challenge
+    pub fn failure_window(&self) -> Duration {
+        Duration::from_secs(self.config.failure_window_seconds)
+    }
+
+    pub fn failure_key(tenant_id: Uuid, client: &str) -> String {
+        format!("captcha_failures:{}:{}", tenant_id, client)
+    }
+}
+
+impl Default for CaptchaService {
+    /// Never challenges
+    fn default() -> Self {
+        Self::new(None, None, CaptchaConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct StubProvider {
+        accept: &'static str,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl CaptchaProvider for StubProvider {
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+
+        async fn verify(
+            &self,
+            token: &str,
+            _remote_ip: Option<&str>,
+        ) -> Result<CaptchaVerification, CaptchaError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(CaptchaVerification {
+                success: token == self.accept,
+                ..Default::default()
+            })
+        }
+    }
+
+    fn service(config: CaptchaConfig) -> (CaptchaService, Arc<StubProvider>) {
+        let stub = Arc::new(StubProvider {
+            accept: "solved",
+            calls: AtomicUsize::new(0),
+        });
+        let service = CaptchaService::new(Some(stub.clone()), Some("site".to_string()), config);
+        (service, stub)
+    }
+
+    #[test]
+    fn test_challenge_triggered_by_risk_or_failures() {
+        let (service, _) = service(CaptchaConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        let tenant = Uuid::new_v4();
+
+        assert!(!service.requires_challenge(tenant, CaptchaSignals::default()));
+        assert!(service.requires_challenge(
+            tenant,
+            CaptchaSignals {
+                risk_score: 0.7,
+                recent_failures: 0
+            }
+        ));
+        assert!(service.requires_challenge(
+            tenant,
+            CaptchaSignals {
+                risk_score: 0.0,
+                recent_failures: 3
+            }
+        ));
+    }
+
+    #[test]
+    fn test_tenant_override_enables_and_tunes() {
+        let tenant = Uuid::new_v4();
+        let mut config = CaptchaConfig::default();
+        config.tenants.insert(
+            tenant.to_string(),
+            CaptchaTenantOverride {
+                enabled: Some(true),
+                risk_threshold: None,
+                failure_threshold: Some(1),
+            },
+        );
+        let (service, _) = service(config);
+        let signals = CaptchaSignals {
+            risk_score: 0.0,
+            recent_failures: 1,
+        };
+
+        assert!(service.requires_challenge(tenant, signals));
+        assert!(!service.requires_challenge(Uuid::new_v4(), signals));
+    }
+
+    #[test]
+    fn test_no_provider_never_challenges() {
+        let service = CaptchaService::new(
+            None,
+            None,
+            CaptchaConfig {
+                enabled: true,
+                ..Default::default()
+            },
+        );
+        assert!(!service.requires_challenge(
+            Uuid::new_v4(),
+            CaptchaSignals {
+                risk_score: 1.0,
+                recent_failures: 100
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_outcomes() {
+        let (service, stub) = service(CaptchaConfig {
+            enabled: true,
+            ..Default::default()
+        });
+
+        assert!(matches!(
+            service.verify(None, None).await,
+            Err(AuthError::CaptchaRequired { .. })
+        ));
+        assert_eq!(stub.calls.load(Ordering::SeqCst), 0);
+
+        assert!(matches!(
+            service.verify(Some("forged"), None).await,
+            Err(AuthError::CaptchaFailed)
+        ));
+        assert!(service
+            .verify(Some("solved"), Some("10.0.0.1"))
+            .await
+            .is_ok());
+        assert_eq!(stub.calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,452 @@
+//! Authorization data resolved into access tokens at issuance
+//!
+//! Each registered [`ClaimsResolver`] contributes roles, permissions or
+//! extension claims for the token's subject. Resolvers run concurrently,
+//! each bounded by its own timeout. One that fails or runs out of time is
+//! skipped unless it is required, so a slow source costs the token its
+//! contribution rather than costing the user their login.
+//!
+//! Results are cached per resolver, tenant and user. Role grants, role
+//! changes and tenant changes announced on the [`EventBus`] drop the
+//! affected entries, so the TTL only bounds staleness when an event is
+//! lost or the source has no event, such as a subscription change.
+
+use crate::error::AuthError;
+use crate::events::{DomainEvent, EventBus};
+use crate::models::Claims;
+use crate::services::authorization::RoleStore;
+use crate::services::subscription_service::SubscriptionService;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use futures::future::join_all;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// What one resolver adds to a token
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResolvedClaims {
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
+    /// Merged into the token's `ext` claim
+    pub ext: BTreeMap<String, Value>,
+}
+
+#[async_trait]
+pub trait ClaimsResolver: Send + Sync {
+    /// Names the resolver in logs and metrics
+    fn name(&self) -> &'static str;
+    async fn resolve(&self, user_id: Uuid, tenant_id: Uuid) -> Result<ResolvedClaims, AuthError>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ResolverOptions {
+    /// Longest wait for the resolver on a cache miss
+    pub timeout: Duration,
+    /// How long a result is reused; zero disables caching
+    pub cache_ttl: Duration,
+    /// Fail issuance instead of leaving the resolver's claims out
+    pub required: bool,
+}
+
+impl Default for ResolverOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_millis(250),
+            cache_ttl: Duration::from_secs(60),
+            required: false,
+        }
+    }
+}
+
+/// Resolver index, tenant and user
+type EntryKey = (usize, Uuid, Uuid);
+
+struct Entry {
+    claims: Arc<ResolvedClaims>,
+    expires_at: Instant,
+}
+
+#[derive(Default)]
+struct ResolvedEntries {
+    entries: DashMap<EntryKey, Entry>,
+}
+
+impl ResolvedEntries {
+    fn fresh(&self, key: &EntryKey) -> Option<Arc<ResolvedClaims>> {
+        let entry = self.entries.get(key)?;
+        if entry.expires_at > Instant::now() {
+            return Some(entry.claims.clone());
+        }
+        drop(entry);
+        self.entries.remove(key);
+        None
+    }
+
+    fn insert(&self, key: EntryKey, claims: Arc<ResolvedClaims>, ttl: Duration) {
+        self.entries.insert(
+            key,
+            Entry {
+                claims,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+    }
+
+    fn apply(&self, event: &DomainEvent) {
+        match event {
+            DomainEvent::UserRolesChanged { tenant_id, user_id } => self
+                .entries
+                .retain(|(_, tenant, user), _| !(tenant == tenant_id && user == user_id)),
+            DomainEvent::RoleChanged { tenant_id, .. }
+            | DomainEvent::PolicyChanged { tenant_id }
+            | DomainEvent::TenantChanged { tenant_id, .. } => {
+                self.entries.retain(|(_, tenant, _), _| tenant != tenant_id)
+            }
+            DomainEvent::SessionRevoked { .. }
+            | DomainEvent::UserSessionsRevoked { .. }
+            | DomainEvent::StepUpRequired { .. } => {}
+        }
+    }
+
+    fn clear(&self) {
+        self.entries.clear();
+    }
+}
+
+struct Registered {
+    resolver: Arc<dyn ClaimsResolver>,
+    options: ResolverOptions,
+}
+
+#[derive(Default)]
+pub struct ClaimsEnricher {
+    resolvers: Vec<Registered>,
+    cache: Arc<ResolvedEntries>,
+}
+
+impl ClaimsEnricher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `resolver` for every token; resolvers registered earlier win
+    /// when two set the same extension claim
+    pub fn with_resolver(
+        mut self,
+        resolver: Arc<dyn ClaimsResolver>,
+        options: ResolverOptions,
+    ) -> Self {
+        self.resolvers.push(Registered { resolver, options });
+        self
+    }
+
+    /// Drop cached results when roles or tenants change, including changes
+    /// made on other nodes
+    pub fn with_events(self, bus: Arc<EventBus>) -> Self {
+        spawn_invalidation_listener(self.cache.clone(), &bus);
+        self
+    }
+
+    /// Claims of every resolver for the user, merged in registration order
+    pub async fn resolve(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+    ) -> Result<ResolvedClaims, AuthError> {
+        let results = join_all(
+            self.resolvers
+                .iter()
+                .enumerate()
+                .map(|(index, registered)| self.run(index, registered, user_id, tenant_id)),
+        )
+        .await;
+
+        let mut merged = ResolvedClaims::default();
+        for (registered, result) in self.resolvers.iter().zip(results) {
+            match result {
+                Ok(claims) => merge(&mut merged, &claims),
+                Err(e) if registered.options.required => return Err(e),
+                Err(e) => tracing::warn!(
+                    resolver = registered.resolver.name(),
+                    user_id = %user_id,
+                    tenant_id = %tenant_id,
+                    "Claims resolver skipped: {}",
+                    e
+                ),
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Add the resolved claims to `claims`, keeping what the caller set
+    pub async fn enrich(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        claims: &mut Claims,
+    ) -> Result<(), AuthError> {
+        if self.resolvers.is_empty() {
+            return Ok(());
+        }
+        let resolved = self.resolve(user_id, tenant_id).await?;
+        extend_unique(&mut claims.roles, &resolved.roles);
+        extend_unique(&mut claims.permissions, &resolved.permissions);
+        for (name, value) in resolved.ext {
+            claims.ext.entry(name).or_insert(value);
+        }
+        Ok(())
+    }
+
+    async fn run(
+        &self,
+        index: usize,
+        registered: &Registered,
+        user_id: Uuid,
+        tenant_id: Uuid,
+    ) -> Result<Arc<ResolvedClaims>, AuthError> {
+        let name = registered.resolver.name();
+        let key = (index, tenant_id, user_id);
+        if let Some(claims) = self.cache.fresh(&key) {
+            metrics::counter!("auth_claims_resolver_total", 1, "resolver" => name, "outcome" => "cached");
+            return Ok(claims);
+        }
+
+        let timeout = registered.options.timeout;
+        let resolving = registered.resolver.resolve(user_id, tenant_id);
+        let (outcome, result) = match tokio::time::timeout(timeout, resolving).await {
+            Ok(Ok(claims)) => ("resolved", Ok(Arc::new(claims))),
+            Ok(Err(e)) => ("error", Err(e)),
+            Err(_) => (
+                "timeout",
+                Err(AuthError::ExternalServiceError {
+                    service: name.to_string(),
+                    error: format!("claims not resolved within {}ms", timeout.as_millis()),
+                }),
+            ),
+        };
+        metrics::counter!("auth_claims_resolver_total", 1, "resolver" => name, "outcome" => outcome);
+
+        let claims = result?;
+        if !registered.options.cache_ttl.is_zero() {
+            self.cache
+                .insert(key, claims.clone(), registered.options.cache_ttl);
+        }
+        Ok(claims)
+    }
+}
+
+fn extend_unique(into: &mut Vec<String>, values: &[String]) {
+    for value in values {
+        if !into.contains(value) {
+            into.push(value.clone());
+        }
+    }
+}
+
+fn merge(into: &mut ResolvedClaims, claims: &ResolvedClaims) {
+    extend_unique(&mut into.roles, &claims.roles);
+    extend_unique(&mut into.permissions, &claims.permissions);
+    for (name, value) in &claims.ext {
+        into.ext
+            .entry(name.clone())
+            .or_insert_with(|| value.clone());
+    }
+}
+
+fn spawn_invalidation_listener(cache: Arc<ResolvedEntries>, bus: &EventBus) -> JoinHandle<()> {
+    let mut rx = bus.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => cache.apply(&event),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(
+                        "Claims cache missed {} invalidation events; clearing",
+                        skipped
+                    );
+                    cache.clear();
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// Roles the user holds in the tenant, and the permission codes they grant
+/// in the given resource classes
+pub struct RoleClaims {
+    roles: Arc<dyn RoleStore>,
+    permission_classes: Vec<String>,
+}
+
+impl RoleClaims {
+    pub fn new(roles: Arc<dyn RoleStore>, permission_classes: Vec<String>) -> Self {
+        Self {
+            roles,
+            permission_classes,
+        }
+    }
+}
+
+#[async_trait]
+impl ClaimsResolver for RoleClaims {
+    fn name(&self) -> &'static str {
+        "roles"
+    }
+
+    async fn resolve(&self, user_id: Uuid, tenant_id: Uuid) -> Result<ResolvedClaims, AuthError> {
+        let mut claims = ResolvedClaims {
+            roles: self.roles.find_user_role_names(user_id, tenant_id).await?,
+            ..ResolvedClaims::default()
+        };
+        for class in &self.permission_classes {
+            let permissions = self
+                .roles
+                .find_user_permissions(user_id, tenant_id, class)
+                .await?;
+            extend_unique(&mut claims.permissions, &permissions);
+        }
+        Ok(claims)
+    }
+}
+
+/// The tenant's active subscription plan, as the `plan` extension claim
+pub struct SubscriptionClaims {
+    subscriptions: Arc<SubscriptionService>,
+}
+
+impl SubscriptionClaims {
+    pub fn new(subscriptions: Arc<SubscriptionService>) -> Self {
+        Self { subscriptions }
+    }
+}
+
+#[async_trait]
+impl ClaimsResolver for SubscriptionClaims {
+    fn name(&self) -> &'static str {
+        "subscription"
+    }
+
+    async fn resolve(&self, _user_id: Uuid, tenant_id: Uuid) -> Result<ResolvedClaims, AuthError> {
+        let mut claims = ResolvedClaims::default();
+        if let Some(plan) = self.subscriptions.active_plan(tenant_id).await? {
+            claims.ext.insert("plan".to_string(), Value::String(plan));
+        }
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Returns fixed claims after `delay`, counting calls
+    struct Fixed {
+        name: &'static str,
+        claims: ResolvedClaims,
+        delay: Duration,
+        calls: AtomicUsize,
+    }
+
+    impl Fixed {
+        fn new(name: &'static str, roles: &[&str], ext: &[(&str, &str)]) -> Self {
+            Self {
+                name,
+                claims: ResolvedClaims {
+                    roles: roles.iter().map(|r| r.to_string()).collect(),
+                    permissions: vec![],
+                    ext: ext
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+                        .collect(),
+                },
+                delay: Duration::ZERO,
+                calls: AtomicUsize::new(0),
+            }
+        }
+
+        fn slow(name: &'static str) -> Self {
+            Self {
+                delay: Duration::from_secs(5),
+                ..Self::new(name, &["slow"], &[])
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl ClaimsResolver for Fixed {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn resolve(&self, _: Uuid, _: Uuid) -> Result<ResolvedClaims, AuthError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            Ok(self.claims.clone())
+        }
+    }
+
+    fn fast() -> ResolverOptions {
+        ResolverOptions {
+            timeout: Duration::from_millis(50),
+            ..ResolverOptions::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolvers_are_merged_and_slow_ones_skipped() {
+        let roles = Fixed::new("roles", &["admin", "editor"], &[("plan", "pro")]);
+        let plugin = Fixed::new("plugin", &["editor"], &[("plan", "free"), ("region", "eu")]);
+        let enricher = ClaimsEnricher::new()
+            .with_resolver(Arc::new(roles), fast())
+            .with_resolver(Arc::new(Fixed::slow("slow")), fast())
+            .with_resolver(Arc::new(plugin), fast());
+
+        let resolved = enricher
+            .resolve(Uuid::new_v4(), Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_eq!(resolved.roles, vec!["admin", "editor"]);
+        assert_eq!(resolved.ext["plan"], "pro");
+        assert_eq!(resolved.ext["region"], "eu");
+
+        let required = ClaimsEnricher::new().with_resolver(
+            Arc::new(Fixed::slow("slow")),
+            ResolverOptions {
+                required: true,
+                ..fast()
+            },
+        );
+        assert!(matches!(
+            required.resolve(Uuid::new_v4(), Uuid::new_v4()).await,
+            Err(AuthError::ExternalServiceError { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_results_are_cached_until_roles_change() {
+        let roles = Arc::new(Fixed::new("roles", &["admin"], &[]));
+        let enricher = ClaimsEnricher::new().with_resolver(roles.clone(), fast());
+        let (user_id, tenant_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        enricher.resolve(user_id, tenant_id).await.unwrap();
+        enricher.resolve(user_id, tenant_id).await.unwrap();
+        assert_eq!(roles.calls(), 1);
+
+        enricher
+            .cache
+            .apply(&DomainEvent::UserRolesChanged { tenant_id, user_id });
+        enricher.resolve(user_id, tenant_id).await.unwrap();
+        assert_eq!(roles.calls(), 2);
+    }
+}
@@ -1,7 +1,7 @@
 //! Credential management service
 
 use crate::error::AuthError;
-use crate::models::{PasswordPolicyRules, PasswordPolicyTemplates};
+use crate::models::{PasswordPolicyRules, PasswordPolicyTemplates, SensitiveString};
 // TODO: Implement PasswordHasher in auth_crypto
 // use auth_crypto::hashing::PasswordHasher;
 use chrono::{DateTime, Duration, Utc};
@@ -35,8 +35,8 @@ pub struct CredentialRequest {
     pub user_id: Uuid,
     pub tenant_id: Uuid,
     #[validate(length(min = 1))]
-    pub password: String,
-    pub current_password: Option<String>,
+    pub password: SensitiveString,
+    pub current_password: Option<SensitiveString>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -259,14 +259,16 @@ impl CredentialService {
 
     /// Check if password change is required based on age
     pub fn is_password_change_required(&self, password_changed_at: Option<DateTime<Utc>>) -> bool {
-        if let (Some(max_age_days), Some(changed_at)) =
-            (self.policy.max_age_days, password_changed_at)
-        {
-            let max_age = Duration::days(max_age_days as i64);
-            Utc::now() - changed_at > max_age
-        } else {
-            false
-        }
+        password_changed_at
+            .and_then(|changed_at| self.password_expires_at(changed_at))
+            .is_some_and(|expires_at| Utc::now() > expires_at)
+    }
+
+    /// When a password set at `changed_at` expires; never without a maximum age
+    pub fn password_expires_at(&self, changed_at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.policy
+            .max_age_days
+            .map(|days| changed_at + Duration::days(days as i64))
     }
 
     /// Check if password can be changed (minimum age check)
@@ -316,7 +318,7 @@ impl CredentialService {
         })?;
 
         // Validate password policy
-        let validation = self.validate_password(&request.password);
+        let validation = self.validate_password(request.password.expose());
         if !validation.is_valid {
             return Err(AuthError::PasswordPolicyViolation {
                 errors: validation.errors,
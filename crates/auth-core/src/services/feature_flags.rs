@@ -0,0 +1,564 @@
+//! Feature flags with canary cohorts
+//!
+//! A feature is on or off for everyone by `features.enabled_features`, or on
+//! for a cohort of users first by `features.cohorts.targets`. Users are
+//! placed in cohorts by a salted hash of their ID (see
+//! [`CohortConfig`]), so a user sees the same behaviour on every request
+//! and replica.
+//!
+//! Requests of users in and out of a targeted feature's cohort are counted
+//! separately, so the cohort's error rate and latency can be compared with
+//! everyone else's before the feature goes further. If the comparison looks
+//! wrong, the feature's kill switch puts the cohort back on the behaviour
+//! everyone else has: at once on the replica that set it, and within
+//! `kill_switch_refresh_seconds` on the others.
+
+use crate::error::AuthError;
+use async_trait::async_trait;
+use auth_config::{CohortConfig, FeatureConfig};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+pub const BUCKETS: u32 = 10_000;
+
+/// Upper bounds of the latency histogram, in milliseconds; the last bucket
+/// is everything slower
+const LATENCY_BOUNDS_MS: [u64; 11] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10_000];
+
+/// Which side of a targeted feature a user is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Variant {
+    /// In the cohort, with the feature's cohort behaviour
+    Treatment,
+    /// Everyone else, and the cohort while the kill switch is on
+    Control,
+}
+
+impl Variant {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Variant::Treatment => "treatment",
+            Variant::Control => "control",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KillSwitch {
+    pub feature: String,
+    pub reason: Option<String>,
+    pub killed_by: Option<String>,
+    pub killed_at: DateTime<Utc>,
+}
+
+/// Kill switches shared by every replica
+#[async_trait]
+pub trait KillSwitchStore: Send + Sync {
+    async fn list(&self) -> Result<Vec<KillSwitch>, AuthError>;
+    async fn set(&self, switch: &KillSwitch) -> Result<(), AuthError>;
+    /// Whether the feature had a kill switch
+    async fn clear(&self, feature: &str) -> Result<bool, AuthError>;
+}
+
+#[derive(Default)]
+pub struct InMemoryKillSwitchStore {
+    switches: DashMap<String, KillSwitch>,
+}
+
+#[async_trait]
+impl KillSwitchStore for InMemoryKillSwitchStore {
+    async fn list(&self) -> Result<Vec<KillSwitch>, AuthError> {
+        Ok(self.switches.iter().map(|s| s.clone()).collect())
+    }
+
+    async fn set(&self, switch: &KillSwitch) -> Result<(), AuthError> {
+        self.switches.insert(switch.feature.clone(), switch.clone());
+        Ok(())
+    }
+
+    async fn clear(&self, feature: &str) -> Result<bool, AuthError> {
+        Ok(self.switches.remove(feature).is_some())
+    }
+}
+
+/// Features as they apply to one user
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct FeatureEvaluation {
+    pub features: BTreeMap<String, bool>,
+    /// Cohorts the user is in
+    pub cohorts: Vec<String>,
+}
+
+/// A feature's configuration and kill switch
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureStatus {
+    pub feature: String,
+    /// Value for users outside the cohort
+    pub enabled: bool,
+    pub cohort: Option<String>,
+    pub cohort_percent: Option<f64>,
+    pub kill_switch: Option<KillSwitch>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Outcomes {
+    requests: u64,
+    client_errors: u64,
+    server_errors: u64,
+    total_ms: u64,
+    latency: [u64; LATENCY_BOUNDS_MS.len() + 1],
+}
+
+impl Outcomes {
+    fn record(&mut self, status: u16, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        self.requests += 1;
+        match status {
+            400..=499 => self.client_errors += 1,
+            500..=599 => self.server_errors += 1,
+            _ => {}
+        }
+        self.total_ms += ms;
+        let bucket = LATENCY_BOUNDS_MS
+            .iter()
+            .position(|bound| ms <= *bound)
+            .unwrap_or(LATENCY_BOUNDS_MS.len());
+        self.latency[bucket] += 1;
+    }
+
+    /// Upper bound of the bucket the `q` quantile falls in; `None` past the
+    /// last bound or without requests
+    fn quantile_ms(&self, q: f64) -> Option<u64> {
+        let rank = (self.requests as f64 * q).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, count) in self.latency.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return LATENCY_BOUNDS_MS.get(i).copied();
+            }
+        }
+        None
+    }
+
+    fn stats(&self) -> VariantStats {
+        let rate = |n: u64| {
+            if self.requests == 0 {
+                0.0
+            } else {
+                n as f64 / self.requests as f64
+            }
+        };
+        VariantStats {
+            requests: self.requests,
+            client_errors: self.client_errors,
+            server_errors: self.server_errors,
+            error_rate: rate(self.server_errors),
+            mean_ms: rate(self.total_ms),
+            p95_ms: self.quantile_ms(0.95),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VariantStats {
+    pub requests: u64,
+    pub client_errors: u64,
+    pub server_errors: u64,
+    /// Share of requests that ended in a 5xx
+    pub error_rate: f64,
+    pub mean_ms: f64,
+    /// Upper bound of the latency bucket holding the 95th percentile;
+    /// `None` when there were no requests or it is over 10 s
+    pub p95_ms: Option<u64>,
+}
+
+/// A targeted feature's cohort against everyone else, over the requests
+/// this replica served since `since`
+#[derive(Debug, Clone, Serialize)]
+pub struct CohortComparison {
+    pub feature: String,
+    pub cohort: String,
+    pub killed: bool,
+    pub since: DateTime<Utc>,
+    pub treatment: VariantStats,
+    pub control: VariantStats,
+    /// Treatment error rate minus control error rate; `None` until both
+    /// have requests
+    pub error_rate_delta: Option<f64>,
+    /// Treatment mean latency minus control mean latency
+    pub mean_ms_delta: Option<f64>,
+}
+
+pub struct FeatureFlagService {
+    enabled: HashMap<String, bool>,
+    /// Cohort name to the number of buckets it covers
+    cohorts: HashMap<String, u32>,
+    targets: HashMap<String, String>,
+    salt: Vec<u8>,
+    store: Arc<dyn KillSwitchStore>,
+    killed: RwLock<HashMap<String, KillSwitch>>,
+    outcomes: DashMap<(String, Variant), Outcomes>,
+    since: DateTime<Utc>,
+}
+
+impl FeatureFlagService {
+    pub fn new(config: &FeatureConfig, store: Arc<dyn KillSwitchStore>) -> Self {
+        let CohortConfig {
+            salt,
+            cohorts,
+            targets,
+            ..
+        } = &config.cohorts;
+        Self {
+            enabled: config.enabled_features.clone(),
+            cohorts: cohorts
+                .iter()
+                .map(|(name, percent)| {
+                    let buckets = (percent * f64::from(BUCKETS) / 100.0).round();
+                    (name.clone(), buckets.clamp(0.0, f64::from(BUCKETS)) as u32)
+                })
+                .collect(),
+            targets: targets.clone(),
+            salt: salt
+                .as_ref()
+                .map(|s| s.expose_secret().as_bytes().to_vec())
+                .unwrap_or_default(),
+            store,
+            killed: RwLock::new(HashMap::new()),
+            outcomes: DashMap::new(),
+            since: Utc::now(),
+        }
+    }
+
+    /// The user's bucket, in `0..BUCKETS`
+    pub fn bucket(&self, user_id: Uuid) -> u32 {
+        let digest = Sha256::new()
+            .chain_update(&self.salt)
+            .chain_update(user_id.as_bytes())
+            .finalize();
+        let mut prefix = [0u8; 8];
+        prefix.copy_from_slice(&digest[..8]);
+        (u64::from_be_bytes(prefix) % u64::from(BUCKETS)) as u32
+    }
+
+    fn in_cohort(&self, cohort: &str, bucket: u32) -> bool {
+        self.cohorts
+            .get(cohort)
+            .is_some_and(|covered| bucket < *covered)
+    }
+
+    pub fn cohorts_of(&self, user_id: Uuid) -> Vec<String> {
+        let bucket = self.bucket(user_id);
+        let mut cohorts: Vec<String> = self
+            .cohorts
+            .keys()
+            .filter(|name| self.in_cohort(name, bucket))
+            .cloned()
+            .collect();
+        cohorts.sort();
+        cohorts
+    }
+
+    fn is_killed(&self, feature: &str) -> bool {
+        self.killed.read().contains_key(feature)
+    }
+
+    /// Side of a targeted feature the user is on; `None` if the feature
+    /// targets no cohort
+    pub fn variant(&self, feature: &str, user_id: Uuid) -> Option<Variant> {
+        let cohort = self.targets.get(feature)?;
+        if !self.is_killed(feature) && self.in_cohort(cohort, self.bucket(user_id)) {
+            Some(Variant::Treatment)
+        } else {
+            Some(Variant::Control)
+        }
+    }
+
+    /// Whether `feature` is on for the user, or for everyone when no user
+    /// is known
+    pub fn is_enabled(&self, feature: &str, user_id: Option<Uuid>) -> bool {
+        let treated = user_id
+            .and_then(|user_id| self.variant(feature, user_id))
+            .is_some_and(|variant| variant == Variant::Treatment);
+        treated || self.enabled.get(feature).copied().unwrap_or(false)
+    }
+
+    fn features(&self) -> BTreeSet<&String> {
+        self.enabled.keys().chain(self.targets.keys()).collect()
+    }
+
+    pub fn evaluate(&self, user_id: Uuid) -> FeatureEvaluation {
+        FeatureEvaluation {
+            features: self
+                .features()
+                .into_iter()
+                .map(|feature| (feature.clone(), self.is_enabled(feature, Some(user_id))))
+                .collect(),
+            cohorts: self.cohorts_of(user_id),
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<FeatureStatus> {
+        let killed = self.killed.read();
+        self.features()
+            .into_iter()
+            .map(|feature| {
+                let cohort = self.targets.get(feature).cloned();
+                FeatureStatus {
+                    feature: feature.clone(),
+                    enabled: self.enabled.get(feature).copied().unwrap_or(false),
+                    cohort_percent: cohort.as_ref().and_then(|c| {
+                        self.cohorts
+                            .get(c)
+                            .map(|buckets| f64::from(*buckets) * 100.0 / f64::from(BUCKETS))
+                    }),
+                    cohort,
+                    kill_switch: killed.get(feature).cloned(),
+                }
+            })
+            .collect()
+    }
+
+    /// Count a finished request of the user under each targeted feature
+    pub fn record(&self, user_id: Uuid, status: u16, elapsed: Duration) {
+        for feature in self.targets.keys() {
+            let Some(variant) = self.variant(feature, user_id) else {
+                continue;
+            };
+            self.outcomes
+                .entry((feature.clone(), variant))
+                .or_default()
+                .record(status, elapsed);
+            let class = match status {
+                500..=599 => "server_error",
+                400..=499 => "client_error",
+                _ => "ok",
+            };
+            metrics::counter!(
+                "auth_cohort_requests_total",
+                1,
+                "feature" => feature.clone(),
+                "variant" => variant.as_str(),
+                "class" => class
+            );
+        }
+    }
+
+    pub fn compare(&self, feature: &str) -> Option<CohortComparison> {
+        let cohort = self.targets.get(feature)?;
+        let stats = |variant| {
+            self.outcomes
+                .get(&(feature.to_string(), variant))
+                .map(|o| o.stats())
+                .unwrap_or_else(|| Outcomes::default().stats())
+        };
+        let (treatment, control) = (stats(Variant::Treatment), stats(Variant::Control));
+        let both = treatment.requests > 0 && control.requests > 0;
+        Some(CohortComparison {
+            feature: feature.to_string(),
+            cohort: cohort.clone(),
+            killed: self.is_killed(feature),
+            since: self.since,
+            error_rate_delta: both.then_some(treatment.error_rate - control.error_rate),
+            mean_ms_delta: both.then_some(treatment.mean_ms - control.mean_ms),
+            treatment,
+            control,
+        })
+    }
+
+    /// Put the feature's cohort back on everyone else's behaviour
+    pub async fn kill(
+        &self,
+        feature: &str,
+        reason: Option<String>,
+        killed_by: Option<String>,
+    ) -> Result<KillSwitch, AuthError> {
+        if !self.targets.contains_key(feature) {
+            return Err(AuthError::ValidationError {
+                message: format!("feature {} does not target a cohort", feature),
+            });
+        }
+        let switch = KillSwitch {
+            feature: feature.to_string(),
+            reason,
+            killed_by,
+            killed_at: Utc::now(),
+        };
+        self.store.set(&switch).await?;
+        self.killed
+            .write()
+            .insert(feature.to_string(), switch.clone());
+        metrics::counter!("auth_feature_kill_switches_total", 1, "feature" => feature.to_string());
+        warn!(
+            feature = %feature,
+            reason = ?switch.reason,
+            killed_by = ?switch.killed_by,
+            "Feature kill switch set; cohort reverted"
+        );
+        Ok(switch)
+    }
+
+    /// Turn the feature back on for its cohort. Whether it had a kill switch.
+    pub async fn restore(&self, feature: &str) -> Result<bool, AuthError> {
+        let cleared = self.store.clear(feature).await?;
+        let local = self.killed.write().remove(feature).is_some();
+        if cleared || local {
+            info!(feature = %feature, "Feature kill switch cleared");
+        }
+        Ok(cleared || local)
+    }
+
+    /// Load the kill switches set by any replica
+    pub async fn refresh(&self) -> Result<(), AuthError> {
+        let switches = self.store.list().await?;
+        *self.killed.write() = switches
+            .into_iter()
+            .map(|switch| (switch.feature.clone(), switch))
+            .collect();
+        Ok(())
+    }
+
+    pub async fn run_refresh(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.refresh().await {
+                warn!("Kill switch refresh failed, keeping the last known: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::Secret;
+
+    fn config(canary_percent: f64) -> FeatureConfig {
+        FeatureConfig {
+            enabled_features: HashMap::from([
+                ("passkeys".to_string(), false),
+                ("audit_logging".to_string(), true),
+            ]),
+            feature_limits: HashMap::new(),
+            tenant_overrides: HashMap::new(),
+            cohorts: CohortConfig {
+                salt: Some(Secret::new("test-salt-0123456789".to_string())),
+                cohorts: HashMap::from([
+                    ("canary".to_string(), canary_percent),
+                    ("early".to_string(), 10.0),
+                ]),
+                targets: HashMap::from([("passkeys".to_string(), "canary".to_string())]),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn service(canary_percent: f64) -> FeatureFlagService {
+        FeatureFlagService::new(
+            &config(canary_percent),
+            Arc::new(InMemoryKillSwitchStore::default()),
+        )
+    }
+
+    #[test]
+    fn test_cohorts_are_stable_nested_and_sized() {
+        let flags = service(1.0);
+        let users: Vec<Uuid> = (0..20_000).map(|_| Uuid::new_v4()).collect();
+
+        let canary = users
+            .iter()
+            .filter(|u| flags.cohorts_of(**u).contains(&"canary".to_string()))
+            .count();
+        assert!((100..=300).contains(&canary), "{} in canary", canary);
+
+        for user in &users[..500] {
+            assert_eq!(flags.bucket(*user), service(1.0).bucket(*user));
+            let cohorts = flags.cohorts_of(*user);
+            if cohorts.contains(&"canary".to_string()) {
+                assert!(cohorts.contains(&"early".to_string()));
+            }
+        }
+
+        // Another salt places users elsewhere
+        let mut other = config(1.0);
+        other.cohorts.salt = Some(Secret::new("another-salt-0123456".to_string()));
+        let other = FeatureFlagService::new(&other, Arc::new(InMemoryKillSwitchStore::default()));
+        assert!(users[..50]
+            .iter()
+            .any(|u| flags.bucket(*u) != other.bucket(*u)));
+    }
+
+    #[tokio::test]
+    async fn test_kill_switch_reverts_the_cohort_until_restored() {
+        let flags = service(100.0);
+        let user = Uuid::new_v4();
+        assert!(flags.is_enabled("passkeys", Some(user)));
+        assert!(!flags.is_enabled("passkeys", None));
+        assert!(flags.is_enabled("audit_logging", Some(user)));
+        // Everyone is a canary; `early` covers a tenth of users
+        assert!(flags.evaluate(user).cohorts.contains(&"canary".to_string()));
+
+        flags
+            .kill("passkeys", Some("error spike".to_string()), None)
+            .await
+            .unwrap();
+        assert!(!flags.is_enabled("passkeys", Some(user)));
+        assert_eq!(flags.variant("passkeys", user), Some(Variant::Control));
+        assert!(flags.statuses().iter().any(|s| s.kill_switch.is_some()));
+        assert!(flags.kill("audit_logging", None, None).await.is_err());
+
+        assert!(flags.restore("passkeys").await.unwrap());
+        assert!(flags.is_enabled("passkeys", Some(user)));
+        assert!(!flags.restore("passkeys").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_kill_switches_reach_other_replicas_on_refresh() {
+        let store = Arc::new(InMemoryKillSwitchStore::default());
+        let here = FeatureFlagService::new(&config(100.0), store.clone());
+        let there = FeatureFlagService::new(&config(100.0), store);
+        let user = Uuid::new_v4();
+
+        here.kill("passkeys", None, Some("ops".to_string()))
+            .await
+            .unwrap();
+        assert!(there.is_enabled("passkeys", Some(user)));
+        there.refresh().await.unwrap();
+        assert!(!there.is_enabled("passkeys", Some(user)));
+    }
+
+    #[test]
+    fn test_outcomes_are_compared_by_variant() {
+        let flags = service(50.0);
+        let users: Vec<Uuid> = (0..200).map(|_| Uuid::new_v4()).collect();
+        for user in &users {
+            let treated = flags.variant("passkeys", *user) == Some(Variant::Treatment);
+            let (status, ms) = if treated { (500, 120) } else { (200, 20) };
+            flags.record(*user, status, Duration::from_millis(ms));
+        }
+
+        let comparison = flags.compare("passkeys").unwrap();
+        assert_eq!(
+            comparison.treatment.requests + comparison.control.requests,
+            200
+        );
+        assert_eq!(comparison.treatment.error_rate, 1.0);
+        assert_eq!(comparison.control.error_rate, 0.0);
+        assert_eq!(comparison.error_rate_delta, Some(1.0));
+        assert_eq!(comparison.treatment.p95_ms, Some(250));
+        assert_eq!(comparison.control.p95_ms, Some(25));
+        assert!(comparison.mean_ms_delta.unwrap() > 90.0);
+        assert!(flags.compare("audit_logging").is_none());
+    }
+}
@@ -0,0 +1,334 @@
+//! Forced reauthentication and token generations
+//!
+//! Every tenant and every user has a token generation. Access tokens carry
+//! the generations they were issued under, and
+//! [`TokenEngine`](crate::services::token_service::TokenEngine) rejects
+//! those from an older one, so outstanding JWTs stop working without being
+//! listed one by one. A user's generation is bumped when their password or
+//! roles change; a tenant's on incident response, where every session and
+//! refresh token family of the tenant is revoked as well. When an OAuth
+//! client's secret leaks, the refresh tokens issued to that client alone
+//! can be revoked.
+
+use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use crate::error::AuthError;
+use crate::services::session_service::SessionStore;
+use crate::services::token_service::RefreshTokenStore;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Cached generations kept before expired ones are swept
+const MAX_CACHED_SCOPES: usize = 10_000;
+
+/// Whose tokens a generation covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GenerationScope {
+    Tenant(Uuid),
+    User(Uuid),
+}
+
+/// Persistent token generations
+#[async_trait::async_trait]
+pub trait TokenGenerationStore: Send + Sync {
+    /// The scope's generation; 0 if it was never bumped
+    async fn generation(&self, scope: GenerationScope) -> Result<u64, AuthError>;
+    /// Increment the scope's generation and return the new one
+    async fn bump(&self, scope: GenerationScope) -> Result<u64, AuthError>;
+}
+
+#[derive(Default)]
+pub struct InMemoryTokenGenerationStore {
+    generations: DashMap<GenerationScope, u64>,
+}
+
+#[async_trait::async_trait]
+impl TokenGenerationStore for InMemoryTokenGenerationStore {
+    async fn generation(&self, scope: GenerationScope) -> Result<u64, AuthError> {
+        Ok(self.generations.get(&scope).map_or(0, |g| *g))
+    }
+
+    async fn bump(&self, scope: GenerationScope) -> Result<u64, AuthError> {
+        let mut generation = self.generations.entry(scope).or_insert(0);
+        *generation += 1;
+        Ok(*generation)
+    }
+}
+
+/// Token generations as checked on every validation. Lookups are cached
+/// for `ttl`; a bump is seen at once on the instance that made it and
+/// within `ttl` on the others.
+pub struct TokenGenerations {
+    store: Arc<dyn TokenGenerationStore>,
+    cache: DashMap<GenerationScope, (u64, Instant)>,
+    ttl: Duration,
+}
+
+impl TokenGenerations {
+    pub fn new(store: Arc<dyn TokenGenerationStore>, ttl: Duration) -> Self {
+        Self {
+            store,
+            cache: DashMap::new(),
+            ttl,
+        }
+    }
+
+    pub async fn current(&self, scope: GenerationScope) -> Result<u64, AuthError> {
+        if let Some(entry) = self.cache.get(&scope) {
+            let (generation, fetched_at) = *entry;
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(generation);
+            }
+        }
+        let generation = self.store.generation(scope).await?;
+        self.cache_generation(scope, generation);
+        Ok(generation)
+    }
+
+    pub async fn bump(&self, scope: GenerationScope) -> Result<u64, AuthError> {
+        let generation = self.store.bump(scope).await?;
+        self.cache_generation(scope, generation);
+        Ok(generation)
+    }
+
+    fn cache_generation(&self, scope: GenerationScope, generation: u64) {
+        if self.cache.len() >= MAX_CACHED_SCOPES {
+            let ttl = self.ttl;
+            self.cache
+                .retain(|_, (_, fetched_at)| fetched_at.elapsed() < ttl);
+        }
+        self.cache.insert(scope, (generation, Instant::now()));
+    }
+
+    /// Invalidate the user's access tokens, logging rather than failing:
+    /// for callers whose own change has already been made
+    pub async fn bump_user(&self, user_id: Uuid, cause: &str) {
+        if let Err(e) = self.bump(GenerationScope::User(user_id)).await {
+            tracing::error!(
+                user_id = %user_id,
+                "Failed to invalidate access tokens after {}: {}",
+                cause,
+                e
+            );
+        }
+    }
+}
+
+/// What a forced reauthentication revoked
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct ForcedReauth {
+    pub tenant_id: Uuid,
+    /// Access tokens issued under an older generation are rejected
+    pub generation: u64,
+    pub sessions_revoked: u64,
+    pub refresh_tokens_revoked: u64,
+}
+
+/// What revoking a client's refresh tokens revoked
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct ClientRevocation {
+    pub tenant_id: Uuid,
+    pub client_id: String,
+    pub refresh_tokens_revoked: u64,
+}
+
+pub struct ForcedReauthService {
+    generations: Arc<TokenGenerations>,
+    sessions: Arc<dyn SessionStore>,
+    refresh_tokens: Arc<dyn RefreshTokenStore>,
+    audit: Option<Arc<dyn AuditLogger>>,
+}
+
+impl ForcedReauthService {
+    pub fn new(
+        generations: Arc<TokenGenerations>,
+        sessions: Arc<dyn SessionStore>,
+        refresh_tokens: Arc<dyn RefreshTokenStore>,
+    ) -> Self {
+        Self {
+            generations,
+            sessions,
+            refresh_tokens,
+            audit: None,
+        }
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Sign everyone in the tenant out, `actor_id` included
+    pub async fn force_tenant(
+        &self,
+        tenant_id: Uuid,
+        actor_id: Uuid,
+        reason: Option<String>,
+    ) -> Result<ForcedReauth, AuthError> {
+        // Access tokens first: they are the credentials an attacker can use
+        // without talking to us
+        let generation = self
+            .generations
+            .bump(GenerationScope::Tenant(tenant_id))
+            .await?;
+        let refresh_tokens_revoked = self.refresh_tokens.revoke_tenant(tenant_id).await?;
+        let sessions_revoked = self.sessions.delete_by_tenant(tenant_id).await?;
+
+        let outcome = ForcedReauth {
+            tenant_id,
+            generation,
+            sessions_revoked,
+            refresh_tokens_revoked,
+        };
+        tracing::warn!(
+            tenant_id = %tenant_id,
+            actor_id = %actor_id,
+            generation = generation,
+            "Forced reauthentication of tenant"
+        );
+        if let Some(audit) = &self.audit {
+            audit
+                .log(
+                    AuditEvent::new(
+                        AuditCategory::Security,
+                        "tenant.force_reauth",
+                        AuditSeverity::Critical,
+                    )
+                    .with_actor(actor_id)
+                    .with_resource(tenant_id.to_string())
+                    .with_context(None, None, Some(tenant_id))
+                    .with_metadata(serde_json::json!({
+                        "reason": reason,
+                        "generation": generation,
+                        "sessions_revoked": sessions_revoked,
+                        "refresh_tokens_revoked": refresh_tokens_revoked,
+                    })),
+                )
+                .await;
+        }
+        Ok(outcome)
+    }
+
+    /// Revoke every refresh token the tenant issued to `client_id`. Access
+    /// tokens already issued to the client run out at their expiry.
+    pub async fn revoke_client(
+        &self,
+        tenant_id: Uuid,
+        client_id: &str,
+        actor_id: Uuid,
+        reason: Option<String>,
+    ) -> Result<ClientRevocation, AuthError> {
+        let client_id = client_id.trim();
+        if client_id.is_empty() {
+            return Err(AuthError::ValidationError {
+                message: "client_id is required".to_string(),
+            });
+        }
+        let refresh_tokens_revoked = self
+            .refresh_tokens
+            .revoke_client(tenant_id, client_id)
+            .await?;
+
+        tracing::warn!(
+            tenant_id = %tenant_id,
+            client_id = %client_id,
+            actor_id = %actor_id,
+            revoked = refresh_tokens_revoked,
+            "Revoked refresh tokens of client"
+        );
+        if let Some(audit) = &self.audit {
+            audit
+                .log(
+                    AuditEvent::new(
+                        AuditCategory::Security,
+                        "client.refresh_tokens_revoked",
+                        AuditSeverity::Critical,
+                    )
+                    .with_actor(actor_id)
+                    .with_resource(client_id.to_string())
+                    .with_context(None, None, Some(tenant_id))
+                    .with_metadata(serde_json::json!({
+                        "reason": reason,
+                        "refresh_tokens_revoked": refresh_tokens_revoked,
+                    })),
+                )
+                .await;
+        }
+        Ok(ClientRevocation {
+            tenant_id,
+            client_id: client_id.to_string(),
+            refresh_tokens_revoked,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CountingStore {
+        inner: InMemoryTokenGenerationStore,
+        reads: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl TokenGenerationStore for CountingStore {
+        async fn generation(&self, scope: GenerationScope) -> Result<u64, AuthError> {
+            self.reads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.inner.generation(scope).await
+        }
+
+        async fn bump(&self, scope: GenerationScope) -> Result<u64, AuthError> {
+            self.inner.bump(scope).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generations_are_cached_and_bumps_seen_at_once() {
+        let store = Arc::new(CountingStore {
+            inner: InMemoryTokenGenerationStore::default(),
+            reads: Default::default(),
+        });
+        let generations = TokenGenerations::new(store.clone(), Duration::from_secs(60));
+        let id = Uuid::new_v4();
+        let tenant = GenerationScope::Tenant(id);
+
+        assert_eq!(generations.current(tenant).await.unwrap(), 0);
+        assert_eq!(generations.current(tenant).await.unwrap(), 0);
+        assert_eq!(store.reads.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        assert_eq!(generations.bump(tenant).await.unwrap(), 1);
+        assert_eq!(generations.current(tenant).await.unwrap(), 1);
+        // Scopes are independent, even under the same id
+        assert_eq!(
+            generations
+                .current(GenerationScope::User(id))
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_generations_are_swept() {
+        let generations = TokenGenerations::new(
+            Arc::new(InMemoryTokenGenerationStore::default()),
+            Duration::ZERO,
+        );
+        for _ in 0..MAX_CACHED_SCOPES {
+            generations
+                .current(GenerationScope::User(Uuid::new_v4()))
+                .await
+                .unwrap();
+        }
+        assert_eq!(generations.cache.len(), MAX_CACHED_SCOPES);
+
+        generations
+            .current(GenerationScope::User(Uuid::new_v4()))
+            .await
+            .unwrap();
+        assert_eq!(generations.cache.len(), 1);
+    }
+}
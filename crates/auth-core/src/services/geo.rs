@@ -0,0 +1,196 @@
+//! IP geolocation
+//!
+//! Resolves IP addresses to country, city and ASN. The MaxMind resolver keeps
+//! the databases in memory and reloads them when the files on disk change, so
+//! the weekly GeoLite2/GeoIP2 updates are picked up without a restart.
+
+use maxminddb::{geoip2, Reader};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct GeoLocation {
+    /// ISO 3166-1 alpha-2 code
+    pub country_code: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub asn: Option<u32>,
+    pub as_org: Option<String>,
+}
+
+impl GeoLocation {
+    /// Great-circle distance in kilometres, if both points have coordinates
+    pub fn distance_km(&self, other: &GeoLocation) -> Option<f64> {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+        let (lat1, lon1) = (self.latitude?.to_radians(), self.longitude?.to_radians());
+        let (lat2, lon2) = (other.latitude?.to_radians(), other.longitude?.to_radians());
+
+        let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+            + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+        Some(2.0 * EARTH_RADIUS_KM * a.sqrt().asin())
+    }
+}
+
+pub trait GeoResolver: Send + Sync {
+    fn lookup(&self, ip: IpAddr) -> Option<GeoLocation>;
+
+    fn lookup_str(&self, ip: &str) -> Option<GeoLocation> {
+        self.lookup(ip.trim().parse().ok()?)
+    }
+}
+
+/// Resolver used when no database is configured
+pub struct NoopGeoResolver;
+
+impl GeoResolver for NoopGeoResolver {
+    fn lookup(&self, _ip: IpAddr) -> Option<GeoLocation> {
+        None
+    }
+}
+
+struct LoadedDb {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    reader: Reader<Vec<u8>>,
+}
+
+impl LoadedDb {
+    fn open(path: PathBuf) -> Result<Self, maxminddb::MaxMindDBError> {
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let reader = Reader::open_readfile(&path)?;
+        Ok(Self {
+            path,
+            modified,
+            reader,
+        })
+    }
+}
+
+/// MaxMind (GeoIP2 / GeoLite2) backed resolver
+pub struct MaxMindGeoResolver {
+    city: RwLock<Option<LoadedDb>>,
+    asn: RwLock<Option<LoadedDb>>,
+}
+
+impl MaxMindGeoResolver {
+    pub fn open(
+        city_db: Option<PathBuf>,
+        asn_db: Option<PathBuf>,
+    ) -> Result<Self, maxminddb::MaxMindDBError> {
+        Ok(Self {
+            city: RwLock::new(city_db.map(LoadedDb::open).transpose()?),
+            asn: RwLock::new(asn_db.map(LoadedDb::open).transpose()?),
+        })
+    }
+
+    /// Reload any database whose file changed since it was loaded. A failed
+    /// reload keeps serving the previous copy.
+    pub fn refresh(&self) {
+        for db in [&self.city, &self.asn] {
+            let stale = db.read().as_ref().and_then(|loaded| {
+                let current = std::fs::metadata(&loaded.path)
+                    .and_then(|m| m.modified())
+                    .ok();
+                (current != loaded.modified).then(|| loaded.path.clone())
+            });
+
+            if let Some(path) = stale {
+                match LoadedDb::open(path.clone()) {
+                    Ok(fresh) => {
+                        tracing::info!("Reloaded GeoIP database {}", path.display());
+                        *db.write() = Some(fresh);
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to reload GeoIP database {}: {}", path.display(), e)
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn spawn_refresh(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let resolver = self.clone();
+                // File IO and parsing are blocking
+                let _ = tokio::task::spawn_blocking(move || resolver.refresh()).await;
+            }
+        })
+    }
+}
+
+impl GeoResolver for MaxMindGeoResolver {
+    fn lookup(&self, ip: IpAddr) -> Option<GeoLocation> {
+        let mut geo = GeoLocation::default();
+
+        if let Some(db) = self.city.read().as_ref() {
+            if let Ok(city) = db.reader.lookup::<geoip2::City>(ip) {
+                if let Some(country) = city.country {
+                    geo.country_code = country.iso_code.map(str::to_string);
+                    geo.country = country
+                        .names
+                        .and_then(|n| n.get("en").map(|s| s.to_string()));
+                }
+                geo.city = city
+                    .city
+                    .and_then(|c| c.names)
+                    .and_then(|n| n.get("en").map(|s| s.to_string()));
+                if let Some(location) = city.location {
+                    geo.latitude = location.latitude;
+                    geo.longitude = location.longitude;
+                }
+            }
+        }
+
+        if let Some(db) = self.asn.read().as_ref() {
+            if let Ok(asn) = db.reader.lookup::<geoip2::Asn>(ip) {
+                geo.asn = asn.autonomous_system_number;
+                geo.as_org = asn.autonomous_system_organization.map(str::to_string);
+            }
+        }
+
+        (geo != GeoLocation::default()).then_some(geo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(lat: f64, lon: f64) -> GeoLocation {
+        GeoLocation {
+            latitude: Some(lat),
+            longitude: Some(lon),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_distance_km() {
+        // London -> New York is roughly 5570 km
+        let d = point(51.5074, -0.1278)
+            .distance_km(&point(40.7128, -74.0060))
+            .unwrap();
+        assert!((5500.0..5650.0).contains(&d), "got {}", d);
+
+        assert!(GeoLocation::default()
+            .distance_km(&point(0.0, 0.0))
+            .is_none());
+    }
+
+    #[test]
+    fn test_noop_resolver() {
+        assert!(NoopGeoResolver.lookup_str("8.8.8.8").is_none());
+        assert!(NoopGeoResolver.lookup_str("garbage").is_none());
+    }
+}
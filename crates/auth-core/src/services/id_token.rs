@@ -0,0 +1,207 @@
+//! OpenID Connect ID tokens
+//!
+//! The token endpoint returns an ID token next to the access token when the
+//! authorization request asked for the `openid` scope. It is signed with the
+//! key published in the JWKS, addressed to the client, and carries the
+//! request's `nonce` and the `at_hash` of the access token issued with it.
+//!
+//! Each tenant has an issuer: the deployment's base URL, or the URL of the
+//! tenant's own domain where one is configured under `[security.sso]`.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::Utc;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// OIDC issuer of each tenant
+#[derive(Debug, Clone)]
+pub struct Issuers {
+    default: String,
+    tenants: HashMap<Uuid, String>,
+}
+
+impl Issuers {
+    /// `tenants` maps tenant ids to issuer URLs; unparseable ids are ignored
+    pub fn new(default: impl Into<String>, tenants: &HashMap<String, String>) -> Self {
+        Self {
+            default: trim_issuer(default.into()),
+            tenants: tenants
+                .iter()
+                .filter_map(|(id, issuer)| {
+                    Uuid::parse_str(id)
+                        .ok()
+                        .map(|id| (id, trim_issuer(issuer.clone())))
+                })
+                .collect(),
+        }
+    }
+
+    pub fn default_issuer(&self) -> &str {
+        &self.default
+    }
+
+    pub fn for_tenant(&self, tenant_id: Uuid) -> &str {
+        self.tenants.get(&tenant_id).unwrap_or(&self.default)
+    }
+
+    /// The tenant issuer served on `host`, for discovery requests arriving
+    /// on a tenant's domain
+    pub fn for_host(&self, host: &str) -> Option<&str> {
+        self.tenants
+            .values()
+            .find(|issuer| {
+                url::Url::parse(issuer).is_ok_and(|url| {
+                    let authority = match (url.host_str(), url.port()) {
+                        (Some(h), Some(port)) => format!("{}:{}", h, port),
+                        (Some(h), None) => h.to_string(),
+                        _ => return false,
+                    };
+                    authority.eq_ignore_ascii_case(host)
+                })
+            })
+            .map(String::as_str)
+    }
+}
+
+impl From<&str> for Issuers {
+    fn from(issuer: &str) -> Self {
+        Self::new(issuer, &HashMap::new())
+    }
+}
+
+impl From<String> for Issuers {
+    fn from(issuer: String) -> Self {
+        Self::new(issuer, &HashMap::new())
+    }
+}
+
+/// Issuers are compared as strings, so `https://idp/` and `https://idp`
+/// must not both be in use
+fn trim_issuer(issuer: String) -> String {
+    issuer.trim_end_matches('/').to_string()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    pub jti: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at_hash: Option<String>,
+    /// Client session id, matched against back-channel logout tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+}
+
+impl IdTokenClaims {
+    pub fn new(issuer: &str, user_id: Uuid, client_id: &str, ttl: chrono::Duration) -> Self {
+        let now = Utc::now();
+        Self {
+            iss: issuer.to_string(),
+            sub: user_id.to_string(),
+            aud: client_id.to_string(),
+            exp: (now + ttl).timestamp(),
+            iat: now.timestamp(),
+            jti: Uuid::new_v4().to_string(),
+            nonce: None,
+            at_hash: None,
+            sid: None,
+        }
+    }
+
+    pub fn with_nonce(mut self, nonce: Option<String>) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    pub fn with_sid(mut self, sid: Option<String>) -> Self {
+        self.sid = sid;
+        self
+    }
+
+    /// Bind the ID token to the access token issued with it
+    pub fn with_access_token(mut self, access_token: &str) -> Self {
+        self.at_hash = Some(at_hash(access_token));
+        self
+    }
+}
+
+/// Left half of the SHA-256 of the access token, base64url-encoded, as
+/// OIDC Core 3.1.3.6 requires for RS256-signed ID tokens
+pub fn at_hash(access_token: &str) -> String {
+    let digest = Sha256::digest(access_token.as_bytes());
+    URL_SAFE_NO_PAD.encode(&digest[..digest.len() / 2])
+}
+
+/// Whether a space-delimited scope requests OpenID Connect
+pub fn requests_openid(scope: Option<&str>) -> bool {
+    scope.is_some_and(|scope| scope.split_whitespace().any(|s| s == "openid"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_at_hash_matches_spec_example() {
+        // OIDC Core 1.0, Appendix A.4
+        assert_eq!(
+            at_hash("jHkWEdUXMU1BwAsC4vtUsZwnNvTIxEl0z9K3vx5KF0Y"),
+            "77QmUPtjPfzWtF2AnpK9RQ"
+        );
+    }
+
+    #[test]
+    fn test_tenants_with_their_own_domain_get_their_own_issuer() {
+        let tenant_id = Uuid::new_v4();
+        let issuers = Issuers::new(
+            "https://idp.example.com/",
+            &HashMap::from([
+                (
+                    tenant_id.to_string(),
+                    "https://login.acme.test/".to_string(),
+                ),
+                ("not-a-uuid".to_string(), "https://x.test".to_string()),
+            ]),
+        );
+
+        assert_eq!(issuers.for_tenant(tenant_id), "https://login.acme.test");
+        assert_eq!(
+            issuers.for_tenant(Uuid::new_v4()),
+            "https://idp.example.com"
+        );
+        assert_eq!(
+            issuers.for_host("LOGIN.acme.test"),
+            Some("https://login.acme.test")
+        );
+        assert_eq!(issuers.for_host("idp.example.com"), None);
+    }
+
+    #[test]
+    fn test_claims_carry_nonce_and_at_hash() {
+        let user_id = Uuid::new_v4();
+        let claims = IdTokenClaims::new("https://idp", user_id, "rp", chrono::Duration::minutes(5))
+            .with_nonce(Some("n-0S6_WzA2Mj".to_string()))
+            .with_access_token("access")
+            .with_sid(None);
+        let json = serde_json::to_value(&claims).unwrap();
+
+        assert_eq!(json["aud"], "rp");
+        assert_eq!(json["sub"], user_id.to_string());
+        assert_eq!(json["nonce"], "n-0S6_WzA2Mj");
+        assert_eq!(json["at_hash"], at_hash("access"));
+        assert!(json.get("sid").is_none());
+        assert!(claims.exp > claims.iat);
+
+        assert!(requests_openid(Some("profile openid")));
+        assert!(!requests_openid(Some("openid_like")));
+        assert!(!requests_openid(None));
+    }
+}
@@ -0,0 +1,286 @@
+//! Canonical form of sign-in identifiers
+//!
+//! Two spellings of the same address must not register two accounts, and
+//! must find the same account at sign-in. Emails are compared trimmed,
+//! NFC-normalized and lowercased; a tenant's policy may fold dots and plus
+//! tags in the local part as well. Phone numbers keep their digits and a
+//! leading `+`. Usernames, where a tenant enables them, must match its
+//! pattern and avoid its reserved names, and fold case unless the tenant
+//! keeps it.
+//!
+//! The canonical form is only ever compared, never shown or mailed to.
+
+use auth_config::{IdentifierConfig, IdentifierPolicy, UsernamePolicy};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use unicode_normalization::UnicodeNormalization;
+use uuid::Uuid;
+
+use crate::models::user::IdentifierType;
+use crate::models::validation::detect_identifier_type;
+
+pub struct IdentifierNormalizer {
+    default_policy: IdentifierPolicy,
+    tenants: HashMap<Uuid, IdentifierPolicy>,
+    dot_folding_domains: Vec<String>,
+    default_usernames: UsernameRules,
+    usernames: HashMap<Uuid, UsernameRules>,
+}
+
+struct UsernameRules {
+    enabled: bool,
+    /// None if the pattern does not compile, which refuses every username
+    pattern: Option<Regex>,
+    /// Lowercased
+    reserved: HashSet<String>,
+    case_sensitive: bool,
+}
+
+impl UsernameRules {
+    fn new(policy: &UsernamePolicy) -> Self {
+        Self {
+            enabled: policy.enabled,
+            pattern: Regex::new(&policy.pattern).ok(),
+            reserved: policy.reserved.iter().map(|r| r.to_lowercase()).collect(),
+            case_sensitive: policy.case_sensitive,
+        }
+    }
+}
+
+impl IdentifierNormalizer {
+    pub fn new(config: &IdentifierConfig) -> Self {
+        Self {
+            default_policy: config.default_policy,
+            tenants: config
+                .tenants
+                .iter()
+                .filter_map(|(id, policy)| Uuid::parse_str(id).ok().map(|id| (id, *policy)))
+                .collect(),
+            dot_folding_domains: config.dot_folding_domains.clone(),
+            default_usernames: UsernameRules::new(&config.usernames.default_policy),
+            usernames: config
+                .usernames
+                .tenants
+                .iter()
+                .filter_map(|(id, policy)| {
+                    Uuid::parse_str(id)
+                        .ok()
+                        .map(|id| (id, UsernameRules::new(policy)))
+                })
+                .collect(),
+        }
+    }
+
+    pub fn policy(&self, tenant_id: Uuid) -> IdentifierPolicy {
+        self.tenants
+            .get(&tenant_id)
+            .copied()
+            .unwrap_or(self.default_policy)
+    }
+
+    /// Email, phone number or username, told apart the way sign-in tells
+    /// them apart
+    pub fn identifier(&self, tenant_id: Uuid, identifier: &str) -> String {
+        match detect_identifier_type(identifier) {
+            IdentifierType::Phone => phone(identifier),
+            IdentifierType::Username => self.username(tenant_id, identifier),
+            _ => self.email(tenant_id, identifier),
+        }
+    }
+
+    fn username_rules(&self, tenant_id: Uuid) -> &UsernameRules {
+        self.usernames
+            .get(&tenant_id)
+            .unwrap_or(&self.default_usernames)
+    }
+
+    pub fn usernames_enabled(&self, tenant_id: Uuid) -> bool {
+        self.username_rules(tenant_id).enabled
+    }
+
+    /// Whether a new username may be registered under the tenant's policy.
+    /// The error is fit to show the user.
+    pub fn check_username(&self, tenant_id: Uuid, username: &str) -> Result<(), String> {
+        let rules = self.username_rules(tenant_id);
+        if !rules.enabled {
+            return Err("Usernames are not enabled for this tenant".to_string());
+        }
+        let username = username.trim();
+        if !rules.pattern.as_ref().is_some_and(|p| p.is_match(username)) {
+            return Err("Username does not match the required format".to_string());
+        }
+        let folded: String = username.to_lowercase().nfc().collect();
+        if rules.reserved.contains(&folded) {
+            return Err("Username is reserved".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn username(&self, tenant_id: Uuid, username: &str) -> String {
+        let username: String = username.trim().nfc().collect();
+        if self.username_rules(tenant_id).case_sensitive {
+            username
+        } else {
+            username.to_lowercase()
+        }
+    }
+
+    pub fn email(&self, tenant_id: Uuid, email: &str) -> String {
+        let folded: String = email.trim().to_lowercase().nfc().collect();
+        let Some((local, domain)) = folded.rsplit_once('@') else {
+            return folded;
+        };
+
+        let policy = self.policy(tenant_id);
+        let mut local = local;
+        if policy.fold_plus_tags {
+            // A bare `+` or a leading one is the address, not a tag
+            if let Some((base, _)) = local.split_once('+').filter(|(b, _)| !b.is_empty()) {
+                local = base;
+            }
+        }
+        if policy.fold_dots && self.dot_folding_domains.iter().any(|d| d == domain) {
+            return format!("{}@{}", local.replace('.', ""), domain);
+        }
+        format!("{}@{}", local, domain)
+    }
+}
+
+impl Default for IdentifierNormalizer {
+    fn default() -> Self {
+        Self::new(&IdentifierConfig::default())
+    }
+}
+
+pub fn phone(phone: &str) -> String {
+    phone
+        .trim()
+        .chars()
+        .enumerate()
+        .filter(|(i, c)| c.is_ascii_digit() || (*i == 0 && *c == '+'))
+        .map(|(_, c)| c)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auth_config::UsernameConfig;
+
+    fn normalizer(policy: IdentifierPolicy) -> (IdentifierNormalizer, Uuid) {
+        let tenant_id = Uuid::new_v4();
+        let config = IdentifierConfig {
+            tenants: HashMap::from([(tenant_id.to_string(), policy)]),
+            ..Default::default()
+        };
+        (IdentifierNormalizer::new(&config), tenant_id)
+    }
+
+    #[test]
+    fn test_case_and_unicode_forms_fold() {
+        let normalizer = IdentifierNormalizer::default();
+        let tenant_id = Uuid::new_v4();
+        assert_eq!(
+            normalizer.email(tenant_id, " Alice@Example.COM "),
+            "alice@example.com"
+        );
+        // "é" precomposed and as "e" plus a combining acute accent
+        assert_eq!(
+            normalizer.email(tenant_id, "Jos\u{e9}@example.com"),
+            normalizer.email(tenant_id, "Jose\u{301}@example.com")
+        );
+        // Without a policy, dots and tags are part of the address
+        assert_eq!(
+            normalizer.email(tenant_id, "j.doe+news@gmail.com"),
+            "j.doe+news@gmail.com"
+        );
+    }
+
+    #[test]
+    fn test_tenant_policy_folds_dots_and_tags() {
+        let (normalizer, tenant_id) = normalizer(IdentifierPolicy {
+            fold_dots: true,
+            fold_plus_tags: true,
+        });
+        assert_eq!(
+            normalizer.email(tenant_id, "J.Doe+News@Gmail.com"),
+            "jdoe@gmail.com"
+        );
+        // Dots only fold where the provider ignores them
+        assert_eq!(
+            normalizer.email(tenant_id, "j.doe+news@example.com"),
+            "j.doe@example.com"
+        );
+        assert_eq!(
+            normalizer.email(tenant_id, "+1@example.com"),
+            "+1@example.com"
+        );
+
+        // Other tenants keep the default policy
+        assert_eq!(
+            normalizer.email(Uuid::new_v4(), "j.doe+news@gmail.com"),
+            "j.doe+news@gmail.com"
+        );
+    }
+
+    #[test]
+    fn test_username_policy_per_tenant() {
+        let tenant_id = Uuid::new_v4();
+        let config = IdentifierConfig {
+            usernames: UsernameConfig {
+                tenants: HashMap::from([(
+                    tenant_id.to_string(),
+                    UsernamePolicy {
+                        enabled: true,
+                        ..Default::default()
+                    },
+                )]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let normalizer = IdentifierNormalizer::new(&config);
+
+        assert!(normalizer.check_username(tenant_id, " Jane.Doe ").is_ok());
+        assert_eq!(normalizer.username(tenant_id, " Jane.Doe "), "jane.doe");
+        assert_eq!(normalizer.identifier(tenant_id, "Jane.Doe"), "jane.doe");
+        assert!(normalizer.check_username(tenant_id, "ab").is_err());
+        assert!(normalizer.check_username(tenant_id, "1jane").is_err());
+        // Reserved names are refused in any case
+        assert!(normalizer.check_username(tenant_id, "Admin").is_err());
+
+        // Usernames are off unless a policy enables them
+        assert!(!normalizer.usernames_enabled(Uuid::new_v4()));
+        assert!(normalizer
+            .check_username(Uuid::new_v4(), "jane.doe")
+            .is_err());
+    }
+
+    #[test]
+    fn test_case_sensitive_usernames_keep_case() {
+        let config = IdentifierConfig {
+            usernames: UsernameConfig {
+                default_policy: UsernamePolicy {
+                    enabled: true,
+                    case_sensitive: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let normalizer = IdentifierNormalizer::new(&config);
+        let tenant_id = Uuid::new_v4();
+        assert_eq!(normalizer.username(tenant_id, "Jane.Doe"), "Jane.Doe");
+        assert!(normalizer.check_username(tenant_id, "ROOT").is_err());
+    }
+
+    #[test]
+    fn test_phone_numbers_keep_digits() {
+        let normalizer = IdentifierNormalizer::default();
+        assert_eq!(
+            normalizer.identifier(Uuid::new_v4(), " +1 (555) 010-9999 "),
+            "+15550109999"
+        );
+    }
+}
@@ -1,8 +1,15 @@
 use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
-use crate::error::AuthError;
+use crate::context::RequestContext;
+use crate::error::{AuthError, TokenErrorKind};
 use crate::models::user::{IdentifierType, PrimaryIdentifier};
-use crate::models::Claims;
-use crate::models::{CreateUserRequest, UpdateUserRequest, User, UserStatus};
+use crate::models::{AccessToken, Claims, ServiceAccount, SessionBinding, TokenPair};
+use crate::models::{CreateUserRequest, SensitiveString, UpdateUserRequest, User, UserStatus};
+use crate::services::forced_reauth::TokenGenerations;
+use crate::services::identifier::IdentifierNormalizer;
+use crate::services::password_expiry::ExpiredPasswordGate;
+use crate::services::remember_me::REMEMBERED_CLAIM;
+use crate::services::tenant_context::TenantContexts;
+use crate::services::timing;
 use crate::services::token_service::TokenProvider;
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
@@ -18,6 +25,12 @@ use uuid::Uuid;
 pub trait UserStore: Send + Sync {
     async fn find_by_email(&self, email: &str, tenant_id: Uuid) -> Result<Option<User>, AuthError>;
     async fn find_by_phone(&self, phone: &str, tenant_id: Uuid) -> Result<Option<User>, AuthError>;
+    /// Matches the tenant's canonical form of `username`
+    async fn find_by_username(
+        &self,
+        username: &str,
+        tenant_id: Uuid,
+    ) -> Result<Option<User>, AuthError>;
     async fn find_by_identifier(
         &self,
         identifier: &str,
@@ -42,11 +55,16 @@ pub trait UserStore: Send + Sync {
 
 #[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct AuthRequest {
+    /// Email, phone number or username
     pub email: String,
-    pub password: String,
+    #[schema(value_type = String, format = Password)]
+    pub password: SensitiveString,
     pub tenant_id: Uuid,
     pub ip_address: Option<String>,
     pub user_agent: Option<String>,
+    /// Solved CAPTCHA token, required once the login has been challenged
+    #[serde(default)]
+    pub captcha_token: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
@@ -55,12 +73,23 @@ pub struct AuthResponse {
     pub access_token: String,
     pub refresh_token: String,
     pub requires_mfa: bool,
+    /// Access token lifetime in seconds
+    #[serde(default)]
+    pub expires_in: u64,
+    /// Set when the tenant is moving its users off passwords and this one
+    /// has no passkey yet
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub passkey_enrollment_recommended: bool,
 }
 
 pub struct IdentityService {
     store: Arc<dyn UserStore>,
     token_service: Arc<dyn TokenProvider>,
     audit_logger: Arc<dyn AuditLogger>,
+    generations: Option<Arc<TokenGenerations>>,
+    password_expiry: Option<Arc<ExpiredPasswordGate>>,
+    identifiers: Arc<IdentifierNormalizer>,
+    tenants: Option<Arc<TenantContexts>>,
 }
 
 impl IdentityService {
@@ -73,20 +102,48 @@ impl IdentityService {
             store,
             token_service,
             audit_logger,
+            generations: None,
+            password_expiry: None,
+            identifiers: Arc::new(IdentifierNormalizer::default()),
+            tenants: None,
         }
     }
 
+    /// Invalidate a user's access tokens when their password changes
+    pub fn with_token_generations(mut self, generations: Arc<TokenGenerations>) -> Self {
+        self.generations = Some(generations);
+        self
+    }
+
+    /// Refuse tokens to logins with an expired password
+    pub fn with_password_expiry(mut self, gate: Arc<ExpiredPasswordGate>) -> Self {
+        self.password_expiry = Some(gate);
+        self
+    }
+
+    /// Check new usernames against the tenants' username policies
+    pub fn with_identifiers(mut self, identifiers: Arc<IdentifierNormalizer>) -> Self {
+        self.identifiers = identifiers;
+        self
+    }
+
+    /// Refuse access tokens of tenants that are suspended or gone
+    pub fn with_tenant_contexts(mut self, tenants: Arc<TenantContexts>) -> Self {
+        self.tenants = Some(tenants);
+        self
+    }
+
     pub async fn register(
         &self,
         request: CreateUserRequest,
         tenant_id: Uuid,
     ) -> Result<User, AuthError> {
         // 1. Validate Password exists logic (optional based on use case, but for manual register it's usually required)
-        if request.password.is_none() {
+        let Some(password) = request.password.clone() else {
             return Err(AuthError::ValidationError {
                 message: "Password required".to_string(),
             });
-        }
+        };
 
         // 2. Check existence
         if let Some(ref email) = request.email {
@@ -105,18 +162,19 @@ impl IdentityService {
             }
         }
 
-        // 3. Hash Password (offload to blocking thread to prevent executor starvation)
-        let password_clone = request.password.as_ref().unwrap().clone();
-        let password_hash = tokio::task::spawn_blocking(move || {
-            let salt = SaltString::generate(&mut OsRng);
-            let argon2 = Argon2::default();
-            argon2
-                .hash_password(password_clone.as_bytes(), &salt)
-                .map(|h| h.to_string())
-        })
-        .await
-        .map_err(|_| AuthError::InternalError)?
-        .map_err(|e| AuthError::UTCryptoError(e.to_string()))?;
+        if let Some(ref username) = request.username {
+            self.identifiers
+                .check_username(tenant_id, username)
+                .map_err(|message| AuthError::ValidationError { message })?;
+            if (self.store.find_by_username(username, tenant_id).await?).is_some() {
+                return Err(AuthError::Conflict {
+                    message: "Username already taken".to_string(),
+                });
+            }
+        }
+
+        // 3. Hash Password
+        let password_hash = hash_password(password).await?;
 
         // 4. Create User
         let user = self.store.create(request, password_hash, tenant_id).await?;
@@ -140,33 +198,24 @@ impl IdentityService {
         // 1. Fetch User
         let user = self
             .store
-            .find_by_email(&request.email, request.tenant_id)
-            .await?
-            .ok_or(AuthError::InvalidCredentials)?;
+            .find_by_identifier(&request.email, request.tenant_id)
+            .await?;
 
-        // 2. Check Status
+        // 2. Verify Password. Unknown users and users without a password are
+        // checked against a dummy hash so every path costs one verification.
+        let hash = user.as_ref().and_then(|u| u.password_hash.clone());
+        let is_valid = timing::verify_password_uniform(request.password.clone(), hash).await;
+        let Some(user) = user else {
+            return Err(AuthError::InvalidCredentials);
+        };
+
+        // 3. Check Status
         if !user.can_authenticate() {
             return Err(AuthError::Unauthorized {
                 message: "Account locked or suspended".to_string(),
             });
         }
 
-        // 3. Verify Password (offload to blocking thread to prevent executor starvation)
-        let password_clone = request.password.clone();
-        let hash_clone = user.password_hash.as_ref().unwrap().clone();
-
-        let is_valid = tokio::task::spawn_blocking(move || {
-            let parsed_hash = PasswordHash::new(&hash_clone).ok()?;
-            Some(
-                Argon2::default()
-                    .verify_password(password_clone.as_bytes(), &parsed_hash)
-                    .is_ok(),
-            )
-        })
-        .await
-        .map_err(|_| AuthError::InternalError)?
-        .unwrap_or(false);
-
         if !is_valid {
             // Increment failed attempts
             let attempts = self.store.increment_failed_attempts(user.id).await?;
@@ -179,19 +228,41 @@ impl IdentityService {
         // 4. Reset failed attempts
         self.store.record_login(user.id, request.ip_address).await?;
 
+        // An expired password proves who the user is, but only gets them a
+        // ticket to change it
+        if let Some(gate) = &self.password_expiry {
+            if gate.is_expired(&user) {
+                let change_ticket = gate.issue_ticket(user.id).await?;
+                let event = AuditEvent::new(
+                    AuditCategory::Authentication,
+                    "user.password_expired",
+                    AuditSeverity::Info,
+                )
+                .with_actor(user.id)
+                .with_context(None, None, Some(request.tenant_id))
+                .with_resource(user.id.to_string());
+                self.audit_logger.log(event).await;
+                return Err(AuthError::PasswordExpired { change_ticket });
+            }
+        }
+
         // 5. Issue Tokens
-        self.issue_tokens_for_user(&user, request.tenant_id, None, None)
+        self.issue_tokens_for_user(&user, request.tenant_id, None, None, None)
             .await
     }
 
-    /// Issue access and refresh tokens for a newly authenticated user
+    /// Issue access and refresh tokens for a newly authenticated user. Tokens
+    /// issued under `session_id` are revoked along with that session.
     pub async fn issue_tokens_for_user(
         &self,
         user: &User,
         tenant_id: Uuid,
         audience: Option<String>,
         scope: Option<String>,
+        session_id: Option<Uuid>,
     ) -> Result<AuthResponse, AuthError> {
+        let client_id = audience.clone();
+        let jti = Uuid::new_v4();
         let claims = Claims {
             sub: user.id.to_string(),
             iss: "auth-service".to_string(),
@@ -199,18 +270,31 @@ impl IdentityService {
             exp: (chrono::Utc::now() + chrono::Duration::minutes(15)).timestamp(),
             iat: chrono::Utc::now().timestamp(),
             nbf: chrono::Utc::now().timestamp(),
-            jti: Uuid::new_v4().to_string(),
+            jti: jti.to_string(),
             tenant_id: tenant_id.to_string(),
+            // Resolved by the token engine's claims enricher, when it has one
             permissions: vec![],
             roles: vec![],
             scope,
+            session_id: session_id.map(|id| id.to_string()),
+            client_id: client_id.clone(),
+            ext: Default::default(),
         };
 
         let access_token_struct = self.token_service.issue_access_token(claims).await?;
         let access_token = access_token_struct.token;
+        let expires_in = access_token_struct.expires_in;
         let refresh_token_struct = self
             .token_service
-            .issue_refresh_token(user.id, tenant_id)
+            .issue_refresh_token_for_client(
+                user.id,
+                tenant_id,
+                client_id.as_deref(),
+                SessionBinding {
+                    session_id,
+                    access_token_jti: Some(jti),
+                },
+            )
             .await?;
         let refresh_token = refresh_token_struct.token_hash;
 
@@ -220,9 +304,69 @@ impl IdentityService {
             access_token,
             refresh_token,
             requires_mfa,
+            expires_in,
+            passkey_enrollment_recommended: false,
         })
     }
 
+    /// Access token for a service account. No refresh token is issued: the
+    /// account signs a fresh assertion whenever it needs a new token.
+    pub async fn issue_service_account_token(
+        &self,
+        account: &ServiceAccount,
+        scope: Option<String>,
+    ) -> Result<AccessToken, AuthError> {
+        let now = chrono::Utc::now();
+        let claims = Claims {
+            sub: account.id.to_string(),
+            iss: "auth-service".to_string(),
+            aud: "auth-service".to_string(),
+            exp: (now + chrono::Duration::minutes(15)).timestamp(),
+            iat: now.timestamp(),
+            nbf: now.timestamp(),
+            jti: Uuid::new_v4().to_string(),
+            tenant_id: account.tenant_id.to_string(),
+            permissions: vec![],
+            roles: vec![],
+            scope: scope.clone(),
+            session_id: None,
+            client_id: None,
+            ext: Default::default(),
+        };
+        let mut token = self.token_service.issue_access_token(claims).await?;
+        token.scope = scope;
+        Ok(token)
+    }
+
+    /// Access token for a user restored from a remember-me token. It is
+    /// marked as such, so routes that need a fresh sign-in refuse it, and
+    /// comes without a refresh token: the device redeems its next
+    /// remember-me token instead.
+    pub async fn issue_remembered_access_token(
+        &self,
+        user: &User,
+        tenant_id: Uuid,
+    ) -> Result<AccessToken, AuthError> {
+        let now = chrono::Utc::now();
+        let claims = Claims {
+            sub: user.id.to_string(),
+            iss: "auth-service".to_string(),
+            aud: "auth-service".to_string(),
+            exp: (now + chrono::Duration::minutes(15)).timestamp(),
+            iat: now.timestamp(),
+            nbf: now.timestamp(),
+            jti: Uuid::new_v4().to_string(),
+            tenant_id: tenant_id.to_string(),
+            permissions: vec![],
+            roles: vec![],
+            scope: None,
+            session_id: None,
+            client_id: None,
+            ext: [(REMEMBERED_CLAIM.to_string(), json!(true))].into(),
+        };
+        self.token_service.issue_access_token(claims).await
+    }
+
     pub async fn ban_user(&self, user_id: Uuid) -> Result<(), AuthError> {
         self.store
             .update_status(user_id, UserStatus::Suspended)
@@ -233,7 +377,7 @@ impl IdentityService {
         self.store.update_status(user_id, UserStatus::Active).await
     }
 
-    /// Find a user by any supported identifier (email or phone)
+    /// Find a user by any supported identifier (email, phone or username)
     pub async fn find_user_by_identifier(
         &self,
         tenant_id: Uuid,
@@ -257,27 +401,19 @@ impl IdentityService {
                 return Err(AuthError::ValidationError {
                     message: "Invalid identifier type for lazy registration".to_string(),
                 })
-            } // 'Both' not supported for lazy yet
+            } // 'Both' not supported for lazy yet; usernames have no channel to verify
         };
 
         // For lazy users, we might set an unusable password or handled at DB level
         // Here we generate a random 32-char string to ensure no one can guess it
-        let temp_password = Uuid::new_v4().to_string();
-
-        let password_hash = tokio::task::spawn_blocking(move || {
-            let salt = SaltString::generate(&mut OsRng);
-            Argon2::default()
-                .hash_password(temp_password.as_bytes(), &salt)
-                .map(|h| h.to_string())
-        })
-        .await
-        .map_err(|_| AuthError::InternalError)?
-        .map_err(|e| AuthError::UTCryptoError(e.to_string()))?;
+        let temp_password = SensitiveString::new(Uuid::new_v4().to_string());
+        let password_hash = hash_password(temp_password).await?;
 
         let request = CreateUserRequest {
             identifier_type: identifier_type.clone(),
             email,
             phone,
+            username: None,
             primary_identifier: Some(primary),
             password: None, // We manually hashed it above, so we pass None here (Wait, create expects CreateUserRequest, but also a password_hash string. The CreateUserRequest's password field is mostly for pre-hash validation if needed, but here we don't need it)
             // Actually, CreateUserRequest usually carries the password for validation.
@@ -297,22 +433,54 @@ impl IdentityService {
     pub async fn update_password(
         &self,
         user_id: Uuid,
-        new_password: String,
+        new_password: SensitiveString,
     ) -> Result<(), AuthError> {
-        // Hash new password
-        let password_hash = tokio::task::spawn_blocking(move || {
-            let salt = SaltString::generate(&mut OsRng);
-            Argon2::default()
-                .hash_password(new_password.as_bytes(), &salt)
-                .map(|h| h.to_string())
-        })
-        .await
-        .map_err(|_| AuthError::InternalError)?
-        .map_err(|e| AuthError::UTCryptoError(e.to_string()))?;
+        let password_hash = hash_password(new_password).await?;
 
         self.store
             .update_password_hash(user_id, password_hash)
-            .await
+            .await?;
+        if let Some(generations) = &self.generations {
+            generations.bump_user(user_id, "password change").await;
+        }
+        Ok(())
+    }
+
+    /// Set a new password with the ticket handed out when login was refused
+    /// for an expired password. Returns the user, who then logs in again.
+    pub async fn change_expired_password(
+        &self,
+        change_ticket: &str,
+        new_password: SensitiveString,
+    ) -> Result<User, AuthError> {
+        let gate = self.password_expiry.as_ref().ok_or(AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        })?;
+        gate.check_new_password(new_password.expose())?;
+        let user_id = gate.redeem_ticket(change_ticket).await?;
+        let user = self
+            .store
+            .find_by_id(user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+
+        if timing::verify_password_uniform(new_password.clone(), user.password_hash.clone()).await {
+            return Err(AuthError::PasswordPolicyViolation {
+                errors: vec!["New password must differ from the expired one".to_string()],
+            });
+        }
+        self.update_password(user.id, new_password).await?;
+
+        let event = AuditEvent::new(
+            AuditCategory::Authentication,
+            "user.password_changed",
+            AuditSeverity::Info,
+        )
+        .with_actor(user.id)
+        .with_context(None, None, Some(user.tenant_id))
+        .with_resource(user.id.to_string());
+        self.audit_logger.log(event).await;
+        Ok(user)
     }
 
     /// Get user by ID
@@ -351,18 +519,78 @@ impl IdentityService {
 
     /// Validate access token and return claims
     pub async fn validate_token(&self, token: &str) -> Result<Claims, AuthError> {
-        self.token_service.validate_token(token).await
+        let claims = self.token_service.validate_token(token).await?;
+        self.check_tenant(&claims).await?;
+        if let (Ok(actor), Ok(tenant)) = (
+            Uuid::parse_str(&claims.sub),
+            Uuid::parse_str(&claims.tenant_id),
+        ) {
+            RequestContext::record_principal(actor, tenant);
+        }
+        Ok(claims)
+    }
+
+    /// Validate a batch of access tokens; results are in input order
+    pub async fn validate_tokens(
+        &self,
+        tokens: &[String],
+    ) -> Result<Vec<Result<Claims, AuthError>>, AuthError> {
+        let mut results = self.token_service.validate_tokens(tokens).await?;
+        if self.tenants.is_some() {
+            for result in results.iter_mut() {
+                let checked = match result {
+                    Ok(claims) => self.check_tenant(claims).await,
+                    Err(_) => continue,
+                };
+                if let Err(e) = checked {
+                    *result = Err(e);
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Tenant state is served from the cache, so this only reaches the
+    /// store the first time a tenant is seen or after it changed
+    async fn check_tenant(&self, claims: &Claims) -> Result<(), AuthError> {
+        let Some(tenants) = &self.tenants else {
+            return Ok(());
+        };
+        let tenant_id = Uuid::parse_str(&claims.tenant_id).map_err(|_| AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        })?;
+        tenants.require_active(tenant_id).await.map(|_| ())
+    }
+
+    /// Rotate a refresh token and issue a new access token
+    pub async fn refresh_tokens(&self, refresh_token: &str) -> Result<TokenPair, AuthError> {
+        self.token_service.refresh_tokens(refresh_token).await
+    }
+
+    /// Rotate a refresh token issued to `client_id`
+    pub async fn refresh_tokens_for_client(
+        &self,
+        refresh_token: &str,
+        client_id: &str,
+    ) -> Result<TokenPair, AuthError> {
+        self.token_service
+            .refresh_tokens_for_client(refresh_token, Some(client_id))
+            .await
     }
 
     /// Verify password for a user
-    pub async fn verify_password(&self, user_id: Uuid, password: &str) -> Result<bool, AuthError> {
+    pub async fn verify_password(
+        &self,
+        user_id: Uuid,
+        password: &SensitiveString,
+    ) -> Result<bool, AuthError> {
         let user = self
             .store
             .find_by_id(user_id)
             .await?
             .ok_or(AuthError::UserNotFound)?;
 
-        let password_clone = password.to_string();
+        let password_clone = password.clone();
         // If user has no password (e.g. social only), fail
         let hash_clone = user
             .password_hash
@@ -373,7 +601,7 @@ impl IdentityService {
             let parsed_hash = PasswordHash::new(&hash_clone).ok()?;
             Some(
                 Argon2::default()
-                    .verify_password(password_clone.as_bytes(), &parsed_hash)
+                    .verify_password(password_clone.expose().as_bytes(), &parsed_hash)
                     .is_ok(),
             )
         })
@@ -393,4 +621,23 @@ impl IdentityService {
     pub async fn get_jwks(&self) -> serde_json::Value {
         self.token_service.get_jwks().await
     }
+
+    /// Sign claims with the key published in the JWKS
+    pub async fn sign_jwt(&self, claims: serde_json::Value) -> Result<String, AuthError> {
+        self.token_service.sign_claims(claims).await
+    }
+}
+
+/// Hash a password on a blocking thread to prevent executor starvation. The
+/// plaintext moves into the closure and is zeroized there once hashed.
+async fn hash_password(password: SensitiveString) -> Result<String, AuthError> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.expose().as_bytes(), &salt)
+            .map(|h| h.to_string())
+    })
+    .await
+    .map_err(|_| AuthError::InternalError)?
+    .map_err(|e| AuthError::UTCryptoError(e.to_string()))
 }
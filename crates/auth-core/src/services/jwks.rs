@@ -0,0 +1,313 @@
+//! JWKS publication for verification partners
+//!
+//! Partners verifying our tokens cache the JWKS, so a key change has to
+//! reach them before it matters: a successor key is published ahead of its
+//! `nbf`, a predecessor stays published until it retires, and the cache
+//! lifetime handed out never reaches past the next such change. Partners
+//! are also notified by webhook when a key enters its retirement notice
+//! period.
+
+use crate::error::AuthError;
+use auth_config::{JwksConfig, JwksKeyConfig};
+use auth_crypto::rsa_jwk;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+/// The JWKS as served, with what the response headers need
+#[derive(Debug, Clone)]
+pub struct JwksDocument {
+    pub body: Value,
+    /// Strong validator of `body`, quoted
+    pub etag: String,
+    /// Seconds the document may be cached
+    pub max_age: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyTransition {
+    /// Tokens signed with the key start being accepted
+    Activates,
+    /// The key is removed from the JWKS
+    Retires,
+}
+
+/// A scheduled change to one key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeyRotation {
+    pub kid: String,
+    pub transition: KeyTransition,
+    pub at: DateTime<Utc>,
+}
+
+/// Payload of the `jwks.key_retiring` webhook
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeyRetirementNotice {
+    /// The same for every notice about one retirement, for deduplication
+    pub notice_id: String,
+    pub kid: String,
+    pub retires_at: DateTime<Utc>,
+}
+
+pub struct JwksService {
+    config: JwksConfig,
+    /// JWKs of configured keys published next to the signing key
+    extra_keys: Vec<Value>,
+    /// Notices this instance already returned, by notice id
+    notified: DashMap<String, ()>,
+}
+
+impl JwksService {
+    /// Reads the public key of each configured key that has a file
+    pub fn from_config(config: JwksConfig) -> Result<Self, AuthError> {
+        let mut extra_keys = Vec::new();
+        for key in &config.keys {
+            let Some(path) = &key.public_key_path else {
+                continue;
+            };
+            let pem = std::fs::read_to_string(path).map_err(|e| AuthError::ConfigurationError {
+                message: format!("Failed to read JWKS key {} from {}: {}", key.kid, path, e),
+            })?;
+            let jwk = rsa_jwk(&key.kid, &pem).map_err(|e| AuthError::ConfigurationError {
+                message: format!("Invalid JWKS key {}: {}", key.kid, e),
+            })?;
+            extra_keys.push(jwk);
+        }
+
+        Ok(Self {
+            config,
+            extra_keys,
+            notified: DashMap::new(),
+        })
+    }
+
+    /// The JWKS to serve at `now`: the keys of `signing` followed by the
+    /// configured keys, less those retired, each with its `nbf` and `exp`
+    /// where configured. A configured key with the kid of a signing key is
+    /// not published twice.
+    pub fn document(&self, signing: &Value, now: DateTime<Utc>) -> JwksDocument {
+        let signing_keys = signing
+            .get("keys")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        let signing_kids: HashSet<String> = signing_keys
+            .iter()
+            .filter_map(|jwk| Some(jwk.get("kid")?.as_str()?.to_string()))
+            .collect();
+        let extra = self.extra_keys.iter().filter(|jwk| {
+            jwk.get("kid")
+                .and_then(Value::as_str)
+                .is_some_and(|kid| !signing_kids.contains(kid))
+        });
+
+        let keys: Vec<Value> = signing_keys
+            .iter()
+            .chain(extra)
+            .filter_map(|jwk| self.annotate(jwk.clone(), now))
+            .collect();
+        let body = serde_json::json!({ "keys": keys });
+
+        let digest = Sha256::digest(body.to_string().as_bytes());
+        let etag = format!("\"{}\"", hex::encode(&digest[..16]));
+        let max_age = match self.next_transition(now) {
+            Some(at) => (at - now)
+                .num_seconds()
+                .clamp(1, self.config.max_age_seconds.max(1) as i64) as u64,
+            None => self.config.max_age_seconds,
+        };
+
+        JwksDocument {
+            body,
+            etag,
+            max_age,
+        }
+    }
+
+    /// Key changes still ahead of `now`, soonest first
+    pub fn rotations(&self, now: DateTime<Utc>) -> Vec<KeyRotation> {
+        let mut rotations: Vec<KeyRotation> = self
+            .config
+            .keys
+            .iter()
+            .flat_map(|key| {
+                [
+                    (KeyTransition::Activates, key.not_before),
+                    (KeyTransition::Retires, key.retires_at),
+                ]
+                .into_iter()
+                .filter_map(|(transition, at)| {
+                    Some(KeyRotation {
+                        kid: key.kid.clone(),
+                        transition,
+                        at: at.filter(|at| *at > now)?,
+                    })
+                })
+            })
+            .collect();
+        rotations.sort_by_key(|r| r.at);
+        rotations
+    }
+
+    /// Keys that entered their retirement notice period and have not been
+    /// announced by this instance yet. Each instance announces once, so
+    /// partners should deduplicate by `notice_id`.
+    pub fn due_retirement_notices(&self, now: DateTime<Utc>) -> Vec<KeyRetirementNotice> {
+        let notice = Duration::hours(self.config.retirement_notice_hours as i64);
+        self.config
+            .keys
+            .iter()
+            .filter_map(|key| {
+                let retires_at = key
+                    .retires_at
+                    .filter(|at| *at > now && *at - notice <= now)?;
+                let notice_id = format!("{}:{}", key.kid, retires_at.timestamp());
+                if self.notified.insert(notice_id.clone(), ()).is_some() {
+                    return None;
+                }
+                Some(KeyRetirementNotice {
+                    notice_id,
+                    kid: key.kid.clone(),
+                    retires_at,
+                })
+            })
+            .collect()
+    }
+
+    /// `None` once the key has retired
+    fn annotate(&self, mut jwk: Value, now: DateTime<Utc>) -> Option<Value> {
+        let kid = jwk.get("kid").and_then(Value::as_str).unwrap_or_default();
+        let Some(key) = self.key_config(kid) else {
+            return Some(jwk);
+        };
+        if key.retires_at.is_some_and(|at| at <= now) {
+            return None;
+        }
+        if let (Some(not_before), Some(fields)) = (key.not_before, jwk.as_object_mut()) {
+            fields.insert("nbf".to_string(), not_before.timestamp().into());
+        }
+        if let (Some(retires_at), Some(fields)) = (key.retires_at, jwk.as_object_mut()) {
+            fields.insert("exp".to_string(), retires_at.timestamp().into());
+        }
+        Some(jwk)
+    }
+
+    fn key_config(&self, kid: &str) -> Option<&JwksKeyConfig> {
+        self.config.keys.iter().find(|key| key.kid == kid)
+    }
+
+    fn next_transition(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.rotations(now).first().map(|r| r.at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(keys: Vec<JwksKeyConfig>, extra: &[&str]) -> JwksService {
+        JwksService {
+            config: JwksConfig {
+                keys,
+                retirement_notice_hours: 24,
+                ..JwksConfig::default()
+            },
+            extra_keys: extra
+                .iter()
+                .map(|kid| serde_json::json!({ "kty": "RSA", "kid": kid }))
+                .collect(),
+            notified: DashMap::new(),
+        }
+    }
+
+    fn key(kid: &str, not_before: Option<i64>, retires_at: Option<i64>) -> JwksKeyConfig {
+        JwksKeyConfig {
+            kid: kid.to_string(),
+            public_key_path: None,
+            not_before: not_before.and_then(|s| DateTime::from_timestamp(s, 0)),
+            retires_at: retires_at.and_then(|s| DateTime::from_timestamp(s, 0)),
+        }
+    }
+
+    fn kids(document: &JwksDocument) -> Vec<&str> {
+        document.body["keys"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|jwk| jwk["kid"].as_str().unwrap())
+            .collect()
+    }
+
+    fn signing() -> Value {
+        serde_json::json!({ "keys": [{ "kty": "RSA", "kid": "current" }] })
+    }
+
+    #[test]
+    fn test_rollover_keys_annotated_and_retired() {
+        let now = DateTime::from_timestamp(1_000_000, 0).unwrap();
+        let jwks = service(
+            vec![
+                key("current", None, Some(1_000_000 + 7200)),
+                key("next", Some(1_000_000 + 3600), None),
+                key("old", None, Some(1_000_000 - 1)),
+            ],
+            &["next", "old", "current"],
+        );
+
+        let document = jwks.document(&signing(), now);
+        assert_eq!(kids(&document), vec!["current", "next"]);
+        assert_eq!(document.body["keys"][0]["exp"], 1_000_000 + 7200);
+        assert_eq!(document.body["keys"][1]["nbf"], 1_000_000 + 3600);
+        // Cached no longer than until "next" becomes valid
+        assert_eq!(document.max_age, 3600);
+
+        let later = jwks.document(&signing(), now + Duration::hours(3));
+        assert_eq!(kids(&later), vec!["next"]);
+        assert_ne!(later.etag, document.etag);
+        assert_eq!(later.max_age, 3600);
+    }
+
+    #[test]
+    fn test_etag_stable_for_same_document() {
+        let jwks = service(vec![], &[]);
+        let now = Utc::now();
+        let a = jwks.document(&signing(), now);
+        let b = jwks.document(&signing(), now + Duration::minutes(5));
+        assert_eq!(a.etag, b.etag);
+        assert!(a.etag.starts_with('"') && a.etag.ends_with('"'));
+    }
+
+    #[test]
+    fn test_rotations_and_retirement_notices() {
+        let now = DateTime::from_timestamp(1_000_000, 0).unwrap();
+        let jwks = service(
+            vec![
+                key("current", None, Some(1_000_000 + 3600)),
+                key("next", Some(1_000_000 + 60), Some(1_000_000 + 90 * 86400)),
+            ],
+            &[],
+        );
+
+        let rotations = jwks.rotations(now);
+        assert_eq!(
+            rotations
+                .iter()
+                .map(|r| (r.kid.as_str(), r.transition))
+                .collect::<Vec<_>>(),
+            vec![
+                ("next", KeyTransition::Activates),
+                ("current", KeyTransition::Retires),
+                ("next", KeyTransition::Retires),
+            ]
+        );
+
+        let notices = jwks.due_retirement_notices(now);
+        assert_eq!(notices.len(), 1);
+        assert_eq!(notices[0].kid, "current");
+        assert!(jwks.due_retirement_notices(now).is_empty());
+    }
+}
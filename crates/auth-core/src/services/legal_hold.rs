@@ -0,0 +1,273 @@
+//! Legal holds
+//!
+//! A hold preserves a user's data, or a whole tenant's, for litigation.
+//! While it is active, retention purges and anonymization leave the held
+//! rows alone; the jobs check holds in their own queries, so a hold takes
+//! effect on their next pass. Holds are never deleted: releasing one
+//! records who released it and when, next to who placed it.
+
+use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use crate::error::AuthError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LegalHold {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    /// `None` holds the whole tenant
+    pub user_id: Option<Uuid>,
+    /// Case or matter reference
+    pub reason: String,
+    pub placed_by: String,
+    pub placed_at: DateTime<Utc>,
+    pub released_by: Option<String>,
+    pub released_at: Option<DateTime<Utc>>,
+}
+
+impl LegalHold {
+    pub fn is_active(&self) -> bool {
+        self.released_at.is_none()
+    }
+
+    /// Whether the hold preserves data of `user_id` in `tenant_id`; with no
+    /// user, whether it holds the tenant as a whole
+    pub fn covers(&self, tenant_id: Uuid, user_id: Option<Uuid>) -> bool {
+        self.is_active()
+            && self.tenant_id == tenant_id
+            && (self.user_id.is_none() || self.user_id == user_id)
+    }
+}
+
+#[async_trait]
+pub trait LegalHoldStore: Send + Sync {
+    async fn insert(&self, hold: &LegalHold) -> Result<(), AuthError>;
+    /// Newest first
+    async fn list(
+        &self,
+        tenant_id: Option<Uuid>,
+        include_released: bool,
+    ) -> Result<Vec<LegalHold>, AuthError>;
+    /// Release an active hold; `None` if there is no such hold or it was
+    /// already released
+    async fn release(&self, id: Uuid, released_by: &str) -> Result<Option<LegalHold>, AuthError>;
+    async fn is_held(&self, tenant_id: Uuid, user_id: Option<Uuid>) -> Result<bool, AuthError>;
+}
+
+#[derive(Default)]
+pub struct InMemoryLegalHoldStore {
+    holds: DashMap<Uuid, LegalHold>,
+}
+
+#[async_trait]
+impl LegalHoldStore for InMemoryLegalHoldStore {
+    async fn insert(&self, hold: &LegalHold) -> Result<(), AuthError> {
+        self.holds.insert(hold.id, hold.clone());
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        tenant_id: Option<Uuid>,
+        include_released: bool,
+    ) -> Result<Vec<LegalHold>, AuthError> {
+        let mut holds: Vec<LegalHold> = self
+            .holds
+            .iter()
+            .filter(|h| include_released || h.is_active())
+            .filter(|h| tenant_id.is_none() || tenant_id == Some(h.tenant_id))
+            .map(|h| h.value().clone())
+            .collect();
+        holds.sort_by_key(|h| std::cmp::Reverse(h.placed_at));
+        Ok(holds)
+    }
+
+    async fn release(&self, id: Uuid, released_by: &str) -> Result<Option<LegalHold>, AuthError> {
+        Ok(self
+            .holds
+            .get_mut(&id)
+            .filter(|hold| hold.is_active())
+            .map(|mut hold| {
+                hold.released_by = Some(released_by.to_string());
+                hold.released_at = Some(Utc::now());
+                hold.clone()
+            }))
+    }
+
+    async fn is_held(&self, tenant_id: Uuid, user_id: Option<Uuid>) -> Result<bool, AuthError> {
+        Ok(self.holds.iter().any(|h| h.covers(tenant_id, user_id)))
+    }
+}
+
+pub struct LegalHoldService {
+    store: Arc<dyn LegalHoldStore>,
+    audit: Option<Arc<dyn AuditLogger>>,
+}
+
+impl LegalHoldService {
+    pub fn new(store: Arc<dyn LegalHoldStore>) -> Self {
+        Self { store, audit: None }
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Hold a user's data, or with no user the tenant's. Holds overlap
+    /// freely; data stays preserved until the last one covering it is
+    /// released.
+    pub async fn place(
+        &self,
+        tenant_id: Uuid,
+        user_id: Option<Uuid>,
+        reason: &str,
+        placed_by: &str,
+    ) -> Result<LegalHold, AuthError> {
+        let (reason, placed_by) = (reason.trim(), placed_by.trim());
+        if reason.is_empty() || placed_by.is_empty() {
+            return Err(AuthError::ValidationError {
+                message: "A legal hold needs a reason and who placed it".to_string(),
+            });
+        }
+
+        let hold = LegalHold {
+            id: Uuid::new_v4(),
+            tenant_id,
+            user_id,
+            reason: reason.to_string(),
+            placed_by: placed_by.to_string(),
+            placed_at: Utc::now(),
+            released_by: None,
+            released_at: None,
+        };
+        self.store.insert(&hold).await?;
+        self.audit("legal_hold.placed", &hold, placed_by).await;
+        Ok(hold)
+    }
+
+    /// `None` if there is no active hold with this id
+    pub async fn release(
+        &self,
+        id: Uuid,
+        released_by: &str,
+    ) -> Result<Option<LegalHold>, AuthError> {
+        let released_by = released_by.trim();
+        if released_by.is_empty() {
+            return Err(AuthError::ValidationError {
+                message: "Releasing a legal hold needs who released it".to_string(),
+            });
+        }
+
+        let hold = self.store.release(id, released_by).await?;
+        if let Some(hold) = &hold {
+            self.audit("legal_hold.released", hold, released_by).await;
+        }
+        Ok(hold)
+    }
+
+    /// Active holds, or all of them with `include_released`, newest first
+    pub async fn holds(
+        &self,
+        tenant_id: Option<Uuid>,
+        include_released: bool,
+    ) -> Result<Vec<LegalHold>, AuthError> {
+        self.store.list(tenant_id, include_released).await
+    }
+
+    /// Whether an active hold covers the user, or with no user the tenant
+    /// as a whole
+    pub async fn is_held(&self, tenant_id: Uuid, user_id: Option<Uuid>) -> Result<bool, AuthError> {
+        self.store.is_held(tenant_id, user_id).await
+    }
+
+    async fn audit(&self, action: &str, hold: &LegalHold, by: &str) {
+        if let Some(audit) = &self.audit {
+            let mut event =
+                AuditEvent::new(AuditCategory::Security, action, AuditSeverity::Warning)
+                    .with_context(None, None, Some(hold.tenant_id))
+                    .with_metadata(serde_json::json!({
+                        "hold_id": hold.id,
+                        "reason": hold.reason,
+                        "by": by,
+                    }));
+            if let Some(user_id) = hold.user_id {
+                event = event.with_resource(user_id.to_string());
+            }
+            audit.log(event).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> LegalHoldService {
+        LegalHoldService::new(Arc::new(InMemoryLegalHoldStore::default()))
+    }
+
+    #[tokio::test]
+    async fn test_user_hold_covers_only_that_user() {
+        let holds = service();
+        let (tenant_id, user_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let hold = holds
+            .place(
+                tenant_id,
+                Some(user_id),
+                "Case 24-cv-118",
+                "legal@example.com",
+            )
+            .await
+            .unwrap();
+        assert!(holds.is_held(tenant_id, Some(user_id)).await.unwrap());
+        assert!(!holds
+            .is_held(tenant_id, Some(Uuid::new_v4()))
+            .await
+            .unwrap());
+        assert!(!holds.is_held(tenant_id, None).await.unwrap());
+
+        let released = holds
+            .release(hold.id, "legal@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(released.released_by.as_deref(), Some("legal@example.com"));
+        assert!(!holds.is_held(tenant_id, Some(user_id)).await.unwrap());
+
+        // Released holds stay on record, and cannot be released twice
+        assert!(holds.holds(None, false).await.unwrap().is_empty());
+        assert_eq!(holds.holds(None, true).await.unwrap().len(), 1);
+        assert!(holds.release(hold.id, "ops").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tenant_hold_covers_every_user() {
+        let holds = service();
+        let tenant_id = Uuid::new_v4();
+        holds
+            .place(tenant_id, None, "Regulator inquiry", "legal@example.com")
+            .await
+            .unwrap();
+
+        assert!(holds
+            .is_held(tenant_id, Some(Uuid::new_v4()))
+            .await
+            .unwrap());
+        assert!(holds.is_held(tenant_id, None).await.unwrap());
+        assert!(!holds.is_held(Uuid::new_v4(), None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_hold_records_who_placed_it() {
+        let holds = service();
+        let result = holds.place(Uuid::new_v4(), None, "Case 7", " ").await;
+        assert!(matches!(result, Err(AuthError::ValidationError { .. })));
+        assert!(holds.release(Uuid::new_v4(), "").await.is_err());
+    }
+}
@@ -0,0 +1,392 @@
+//! Hourly rollup of sign-ins by tenant, country and ASN
+//!
+//! The admin dashboard's heatmap counts successful and failed sign-ins
+//! per hour and place. Aggregating `login_events` per request would scan
+//! millions of rows, so a job on the leader counts each hour once into a
+//! rollup and requests only sum rollup rows. Geo enrichment lands after
+//! the event is written, so every run recounts the current hour and the
+//! `lookback_hours` before it; an event still unresolved by then stays
+//! counted without a country or ASN. A rolled-up hour is recorded even
+//! when it had no sign-ins, so quiet hours are not recounted.
+
+use crate::error::AuthError;
+use async_trait::async_trait;
+use auth_config::LoginHeatmapConfig;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Sign-ins of one tenant in one hour from one country and ASN
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeatmapBucket {
+    pub hour: DateTime<Utc>,
+    pub tenant_id: Uuid,
+    /// ISO 3166-1 alpha-2; `None` when the IP did not resolve
+    pub country_code: Option<String>,
+    pub asn: Option<u32>,
+    pub successes: u64,
+    pub failures: u64,
+}
+
+/// What the counts of a heatmap cell are grouped by, besides the hour
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeatmapDimension {
+    #[default]
+    Country,
+    Asn,
+    CountryAsn,
+}
+
+impl HeatmapDimension {
+    pub fn by_country(&self) -> bool {
+        matches!(
+            self,
+            HeatmapDimension::Country | HeatmapDimension::CountryAsn
+        )
+    }
+
+    pub fn by_asn(&self) -> bool {
+        matches!(self, HeatmapDimension::Asn | HeatmapDimension::CountryAsn)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HeatmapQuery {
+    /// Every tenant when `None`
+    pub tenant_id: Option<Uuid>,
+    /// Start of the first hour
+    pub since: DateTime<Utc>,
+    /// Hours starting before this are included
+    pub until: DateTime<Utc>,
+    pub by: HeatmapDimension,
+}
+
+/// Counts of one hour, summed over the selected tenants. The country or
+/// ASN is `None` both when the dimension leaves it out and when the IP
+/// did not resolve.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HeatmapCell {
+    pub hour: DateTime<Utc>,
+    pub country_code: Option<String>,
+    pub asn: Option<u32>,
+    pub successes: u64,
+    pub failures: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoginHeatmap {
+    #[serde(flatten)]
+    pub query: HeatmapQuery,
+    /// Ordered by hour, then country and ASN
+    pub cells: Vec<HeatmapCell>,
+}
+
+#[async_trait]
+pub trait LoginHeatmapStore: Send + Sync {
+    /// Counts of the login events created in the hour starting at `hour`,
+    /// read from the events themselves
+    async fn count_hour(&self, hour: DateTime<Utc>) -> Result<Vec<HeatmapBucket>, AuthError>;
+
+    /// Replace the rollup of the hour with `buckets` and record the hour
+    /// as rolled up
+    async fn replace_hour(
+        &self,
+        hour: DateTime<Utc>,
+        buckets: &[HeatmapBucket],
+    ) -> Result<(), AuthError>;
+
+    /// Latest hour recorded as rolled up
+    async fn latest_hour(&self) -> Result<Option<DateTime<Utc>>, AuthError>;
+
+    /// Rollup rows of the query's range, summed per hour and dimension
+    async fn cells(&self, query: &HeatmapQuery) -> Result<Vec<HeatmapCell>, AuthError>;
+}
+
+/// Start of the UTC hour `at` falls in
+pub fn hour_start(at: DateTime<Utc>) -> DateTime<Utc> {
+    let seconds = at.timestamp();
+    Utc.timestamp_opt(seconds - seconds.rem_euclid(3600), 0)
+        .single()
+        .unwrap_or(at)
+}
+
+pub struct LoginHeatmapService {
+    store: Arc<dyn LoginHeatmapStore>,
+    lookback: Duration,
+    backfill: Duration,
+    max_range: Duration,
+}
+
+impl LoginHeatmapService {
+    pub fn new(store: Arc<dyn LoginHeatmapStore>, config: &LoginHeatmapConfig) -> Self {
+        Self {
+            store,
+            lookback: Duration::hours(config.lookback_hours as i64),
+            backfill: Duration::days(config.backfill_days as i64),
+            max_range: Duration::days(config.max_query_days.max(1) as i64),
+        }
+    }
+
+    /// Roll up every hour from the last one rolled up, or the start of the
+    /// backfill window, through the current one. Returns the hours counted.
+    pub async fn rollup(&self, now: DateTime<Utc>) -> Result<u32, AuthError> {
+        let current = hour_start(now);
+        let mut hour = match self.store.latest_hour().await? {
+            Some(latest) => latest.min(current - self.lookback),
+            None => hour_start(now - self.backfill),
+        };
+        let mut hours = 0;
+        while hour <= current {
+            let buckets = self.store.count_hour(hour).await?;
+            self.store.replace_hour(hour, &buckets).await?;
+            hour += Duration::hours(1);
+            hours += 1;
+        }
+        Ok(hours)
+    }
+
+    pub async fn heatmap(&self, query: HeatmapQuery) -> Result<LoginHeatmap, AuthError> {
+        if query.since >= query.until {
+            return Err(AuthError::ValidationError {
+                message: "since must be before until".to_string(),
+            });
+        }
+        if query.until - query.since > self.max_range {
+            return Err(AuthError::ValidationError {
+                message: format!(
+                    "A heatmap covers at most {} days",
+                    self.max_range.num_days()
+                ),
+            });
+        }
+        let query = HeatmapQuery {
+            since: hour_start(query.since),
+            ..query
+        };
+        let cells = self.store.cells(&query).await?;
+        Ok(LoginHeatmap { query, cells })
+    }
+
+    pub async fn run(self: Arc<Self>, interval: std::time::Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self.rollup(Utc::now()).await {
+                Ok(hours) => {
+                    metrics::counter!("auth_login_heatmap_hours_total", hours as u64);
+                    info!(hours, "Login heatmap rolled up");
+                }
+                Err(e) => {
+                    metrics::counter!("auth_login_heatmap_failures_total", 1);
+                    warn!("Login heatmap rollup failed: {}", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    type Key = (DateTime<Utc>, Uuid, Option<String>, Option<u32>);
+    /// `(created_at, tenant, country, asn, success)`
+    type Event = (DateTime<Utc>, Uuid, Option<String>, Option<u32>, bool);
+
+    #[derive(Default)]
+    struct MemoryStore {
+        events: Mutex<Vec<Event>>,
+        rollup: Mutex<BTreeMap<Key, (u64, u64)>>,
+        hours: Mutex<BTreeSet<DateTime<Utc>>>,
+        counted: Mutex<Vec<DateTime<Utc>>>,
+    }
+
+    #[async_trait]
+    impl LoginHeatmapStore for MemoryStore {
+        async fn count_hour(&self, hour: DateTime<Utc>) -> Result<Vec<HeatmapBucket>, AuthError> {
+            self.counted.lock().push(hour);
+            let mut counts: BTreeMap<Key, (u64, u64)> = BTreeMap::new();
+            for (created_at, tenant, country, asn, success) in self.events.lock().iter() {
+                if hour_start(*created_at) == hour {
+                    let entry = counts
+                        .entry((hour, *tenant, country.clone(), *asn))
+                        .or_default();
+                    if *success {
+                        entry.0 += 1;
+                    } else {
+                        entry.1 += 1;
+                    }
+                }
+            }
+            Ok(counts
+                .into_iter()
+                .map(
+                    |((hour, tenant_id, country_code, asn), (successes, failures))| HeatmapBucket {
+                        hour,
+                        tenant_id,
+                        country_code,
+                        asn,
+                        successes,
+                        failures,
+                    },
+                )
+                .collect())
+        }
+
+        async fn replace_hour(
+            &self,
+            hour: DateTime<Utc>,
+            buckets: &[HeatmapBucket],
+        ) -> Result<(), AuthError> {
+            let mut rollup = self.rollup.lock();
+            rollup.retain(|key, _| key.0 != hour);
+            for b in buckets {
+                rollup.insert(
+                    (b.hour, b.tenant_id, b.country_code.clone(), b.asn),
+                    (b.successes, b.failures),
+                );
+            }
+            self.hours.lock().insert(hour);
+            Ok(())
+        }
+
+        async fn latest_hour(&self) -> Result<Option<DateTime<Utc>>, AuthError> {
+            Ok(self.hours.lock().last().copied())
+        }
+
+        async fn cells(&self, query: &HeatmapQuery) -> Result<Vec<HeatmapCell>, AuthError> {
+            let mut cells: BTreeMap<(DateTime<Utc>, Option<String>, Option<u32>), (u64, u64)> =
+                BTreeMap::new();
+            for ((hour, tenant, country, asn), (s, f)) in self.rollup.lock().iter() {
+                if *hour < query.since
+                    || *hour >= query.until
+                    || query.tenant_id.is_some_and(|t| t != *tenant)
+                {
+                    continue;
+                }
+                let key = (
+                    *hour,
+                    country.clone().filter(|_| query.by.by_country()),
+                    asn.filter(|_| query.by.by_asn()),
+                );
+                let cell = cells.entry(key).or_default();
+                cell.0 += s;
+                cell.1 += f;
+            }
+            Ok(cells
+                .into_iter()
+                .map(
+                    |((hour, country_code, asn), (successes, failures))| HeatmapCell {
+                        hour,
+                        country_code,
+                        asn,
+                        successes,
+                        failures,
+                    },
+                )
+                .collect())
+        }
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 1, hour, minute, 0).unwrap()
+    }
+
+    fn service(store: Arc<MemoryStore>) -> LoginHeatmapService {
+        let config = LoginHeatmapConfig {
+            lookback_hours: 1,
+            backfill_days: 1,
+            ..LoginHeatmapConfig::default()
+        };
+        LoginHeatmapService::new(store, &config)
+    }
+
+    #[tokio::test]
+    async fn test_rollup_counts_hours_and_recounts_late_enrichment() {
+        let store = Arc::new(MemoryStore::default());
+        let tenant = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        store.events.lock().extend([
+            (at(9, 5), tenant, Some("DE".to_string()), Some(3320), true),
+            (at(9, 40), tenant, Some("DE".to_string()), Some(3320), false),
+            (at(9, 41), other, Some("DE".to_string()), Some(3320), false),
+            (at(10, 2), tenant, None, None, false),
+        ]);
+        let heatmap = service(store.clone());
+
+        // The first run backfills a day through the current hour
+        assert_eq!(heatmap.rollup(at(10, 30)).await.unwrap(), 25);
+
+        // The unresolved event is geo-enriched after the run
+        store.events.lock()[3].2 = Some("US".to_string());
+        store.counted.lock().clear();
+        heatmap.rollup(at(10, 45)).await.unwrap();
+        assert_eq!(*store.counted.lock(), vec![at(9, 0), at(10, 0)]);
+
+        let map = heatmap
+            .heatmap(HeatmapQuery {
+                tenant_id: Some(tenant),
+                since: at(9, 30),
+                until: at(11, 0),
+                by: HeatmapDimension::Country,
+            })
+            .await
+            .unwrap();
+        assert_eq!(map.query.since, at(9, 0));
+        assert_eq!(
+            map.cells,
+            vec![
+                HeatmapCell {
+                    hour: at(9, 0),
+                    country_code: Some("DE".to_string()),
+                    asn: None,
+                    successes: 1,
+                    failures: 1,
+                },
+                HeatmapCell {
+                    hour: at(10, 0),
+                    country_code: Some("US".to_string()),
+                    asn: None,
+                    successes: 0,
+                    failures: 1,
+                },
+            ]
+        );
+
+        // Across tenants, by ASN
+        let map = heatmap
+            .heatmap(HeatmapQuery {
+                tenant_id: None,
+                since: at(9, 0),
+                until: at(10, 0),
+                by: HeatmapDimension::Asn,
+            })
+            .await
+            .unwrap();
+        assert_eq!(map.cells.len(), 1);
+        assert_eq!((map.cells[0].successes, map.cells[0].failures), (1, 2));
+    }
+
+    #[tokio::test]
+    async fn test_heatmap_range_is_bounded() {
+        let heatmap = service(Arc::new(MemoryStore::default()));
+        let query = |since, until| HeatmapQuery {
+            tenant_id: None,
+            since,
+            until,
+            by: HeatmapDimension::CountryAsn,
+        };
+
+        assert!(heatmap.heatmap(query(at(10, 0), at(9, 0))).await.is_err());
+        assert!(heatmap
+            .heatmap(query(at(0, 0) - Duration::days(40), at(0, 0)))
+            .await
+            .is_err());
+        assert!(heatmap.heatmap(query(at(0, 0), at(12, 0))).await.is_ok());
+    }
+}
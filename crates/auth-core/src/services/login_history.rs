@@ -0,0 +1,279 @@
+//! Login history with asynchronous geo-enrichment
+//!
+//! Login events are written immediately with the raw IP. Resolution to
+//! country/city/ASN happens on a background worker so a slow or missing
+//! GeoIP database never delays authentication.
+
+use crate::error::AuthError;
+use crate::services::geo::{GeoLocation, GeoResolver};
+use crate::services::risk_assessment::{LoginHistory, RiskContext};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Logins considered when scoring a new one
+const RISK_HISTORY_LIMIT: u32 = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct LoginEvent {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub tenant_id: Uuid,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub success: bool,
+    /// Filled in by the enrichment worker; `None` until resolved
+    pub geo: Option<GeoLocation>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl LoginEvent {
+    pub fn new(
+        user_id: Uuid,
+        tenant_id: Uuid,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+        success: bool,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            tenant_id,
+            ip_address,
+            user_agent,
+            success,
+            geo: None,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+impl From<&LoginEvent> for LoginHistory {
+    fn from(event: &LoginEvent) -> Self {
+        LoginHistory {
+            timestamp: event.created_at,
+            ip_address: event.ip_address.clone().unwrap_or_default(),
+            success: event.success,
+            geo: event.geo.clone(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+pub trait LoginEventStore: Send + Sync {
+    async fn record(&self, event: &LoginEvent) -> Result<(), AuthError>;
+    async fn set_geo(&self, event_id: Uuid, geo: &GeoLocation) -> Result<(), AuthError>;
+    /// Most recent first
+    async fn recent_for_user(
+        &self,
+        user_id: Uuid,
+        limit: u32,
+    ) -> Result<Vec<LoginEvent>, AuthError>;
+}
+
+struct EnrichmentJob {
+    event_id: Uuid,
+    ip_address: String,
+}
+
+pub struct LoginHistoryService {
+    store: Arc<dyn LoginEventStore>,
+    resolver: Arc<dyn GeoResolver>,
+    queue: mpsc::Sender<EnrichmentJob>,
+}
+
+impl LoginHistoryService {
+    /// Returns the service and the worker that must be spawned to enrich events
+    pub fn new(
+        store: Arc<dyn LoginEventStore>,
+        resolver: Arc<dyn GeoResolver>,
+        buffer_size: usize,
+    ) -> (Self, GeoEnrichmentWorker) {
+        let (tx, rx) = mpsc::channel(buffer_size);
+        let worker = GeoEnrichmentWorker {
+            receiver: rx,
+            store: store.clone(),
+            resolver: resolver.clone(),
+        };
+        (
+            Self {
+                store,
+                resolver,
+                queue: tx,
+            },
+            worker,
+        )
+    }
+
+    pub async fn record(&self, event: LoginEvent) -> Result<(), AuthError> {
+        self.store.record(&event).await?;
+
+        if let Some(ip) = event.ip_address {
+            let job = EnrichmentJob {
+                event_id: event.id,
+                ip_address: ip,
+            };
+            // Enrichment is best effort; never apply backpressure to logins
+            if self.queue.try_send(job).is_err() {
+                warn!(
+                    "Geo-enrichment queue full; login event {} left unenriched",
+                    event.id
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn recent(&self, user_id: Uuid, limit: u32) -> Result<Vec<LoginEvent>, AuthError> {
+        self.store.recent_for_user(user_id, limit).await
+    }
+
+    /// Recent logins in the shape the risk engine consumes
+    pub async fn risk_history(
+        &self,
+        user_id: Uuid,
+        limit: u32,
+    ) -> Result<Vec<LoginHistory>, AuthError> {
+        Ok(self
+            .recent(user_id, limit)
+            .await?
+            .iter()
+            .map(LoginHistory::from)
+            .collect())
+    }
+
+    /// Resolve an IP inline, for scoring the login currently in progress
+    pub fn locate(&self, ip: &str) -> Option<GeoLocation> {
+        self.resolver.lookup_str(ip)
+    }
+
+    /// Risk context for a login in progress, with its location and the
+    /// user's enriched history
+    pub async fn risk_context(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+        device_fingerprint: Option<String>,
+    ) -> Result<RiskContext, AuthError> {
+        Ok(RiskContext {
+            user_id,
+            tenant_id,
+            geolocation: ip_address.as_deref().and_then(|ip| self.locate(ip)),
+            ip_address,
+            user_agent,
+            device_fingerprint,
+            previous_logins: self.risk_history(user_id, RISK_HISTORY_LIMIT).await?,
+        })
+    }
+}
+
+pub struct GeoEnrichmentWorker {
+    receiver: mpsc::Receiver<EnrichmentJob>,
+    store: Arc<dyn LoginEventStore>,
+    resolver: Arc<dyn GeoResolver>,
+}
+
+impl GeoEnrichmentWorker {
+    pub async fn run(mut self) {
+        info!("Geo-enrichment worker started");
+        while let Some(job) = self.receiver.recv().await {
+            let Some(geo) = self.resolver.lookup_str(&job.ip_address) else {
+                continue;
+            };
+            if let Err(e) = self.store.set_geo(job.event_id, &geo).await {
+                warn!(
+                    "Failed to store geo for login event {}: {}",
+                    job.event_id, e
+                );
+            }
+        }
+        info!("Geo-enrichment worker stopped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::net::IpAddr;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        events: Mutex<Vec<LoginEvent>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LoginEventStore for MemoryStore {
+        async fn record(&self, event: &LoginEvent) -> Result<(), AuthError> {
+            self.events.lock().push(event.clone());
+            Ok(())
+        }
+
+        async fn set_geo(&self, event_id: Uuid, geo: &GeoLocation) -> Result<(), AuthError> {
+            if let Some(e) = self.events.lock().iter_mut().find(|e| e.id == event_id) {
+                e.geo = Some(geo.clone());
+            }
+            Ok(())
+        }
+
+        async fn recent_for_user(
+            &self,
+            user_id: Uuid,
+            limit: u32,
+        ) -> Result<Vec<LoginEvent>, AuthError> {
+            Ok(self
+                .events
+                .lock()
+                .iter()
+                .rev()
+                .filter(|e| e.user_id == user_id)
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+    }
+
+    struct FixedResolver;
+
+    impl GeoResolver for FixedResolver {
+        fn lookup(&self, _ip: IpAddr) -> Option<GeoLocation> {
+            Some(GeoLocation {
+                country_code: Some("NL".to_string()),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_are_enriched_in_background() {
+        let store = Arc::new(MemoryStore::default());
+        let (service, worker) = LoginHistoryService::new(store.clone(), Arc::new(FixedResolver), 8);
+        let user = Uuid::new_v4();
+
+        service
+            .record(LoginEvent::new(
+                user,
+                Uuid::new_v4(),
+                Some("192.0.2.1".to_string()),
+                None,
+                true,
+            ))
+            .await
+            .unwrap();
+
+        // Dropping the service closes the queue so the worker drains and exits
+        drop(service);
+        worker.run().await;
+
+        let events = store.recent_for_user(user, 10).await.unwrap();
+        assert_eq!(
+            events[0].geo.as_ref().unwrap().country_code.as_deref(),
+            Some("NL")
+        );
+    }
+}
@@ -0,0 +1,605 @@
+//! One-time login links issued by support
+//!
+//! Support staff can have a sign-in link emailed to a user who is stuck,
+//! for example to fix their account settings. The link is sent only to the
+//! user's verified email address and never shown to the issuer. It works
+//! once, for a short time, and lands the user on the redirect it was issued
+//! for. Users with TOTP enabled must still enter a code; a wrong code hands
+//! back a continuation token so the link is not lost, up to
+//! [`MAX_MFA_FAILURES`] times.
+
+use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use crate::error::{AuthError, TokenErrorKind};
+use crate::models::User;
+use crate::services::nonce_store::{NonceNamespace, NonceStore};
+use crate::services::otp_delivery::EmailProvider;
+use crate::services::otp_service::OtpService;
+use async_trait::async_trait;
+use auth_config::LoginLinkConfig;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+/// Wrong TOTP codes accepted on one link before it is spent
+pub const MAX_MFA_FAILURES: u32 = 5;
+
+/// An issued link, kept for audit and the issuance limit. The token itself
+/// lives only in the nonce store.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct LoginLink {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub user_id: Uuid,
+    pub issued_by: Uuid,
+    pub redirect_uri: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub redeemed_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait LoginLinkStore: Send + Sync {
+    async fn create(&self, link: &LoginLink) -> Result<(), AuthError>;
+    /// Links issued for the user since `since`
+    async fn issued_since(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<u32, AuthError>;
+    async fn mark_redeemed(
+        &self,
+        tenant_id: Uuid,
+        link_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError>;
+}
+
+#[derive(Debug, Clone, Deserialize, utoipa::ToSchema)]
+pub struct IssueLoginLinkRequest {
+    /// Must be one of the configured redirects
+    pub redirect_uri: String,
+    /// Shorter lifetime than the configured one
+    pub ttl_seconds: Option<u64>,
+    /// Ticket number or note for the audit trail
+    pub reason: Option<String>,
+}
+
+/// What the issuer learns about a sent link
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct IssuedLoginLink {
+    pub link_id: Uuid,
+    /// The address it was sent to, masked
+    pub sent_to: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// What a link token stands for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkGrant {
+    pub link_id: Uuid,
+    pub tenant_id: Uuid,
+    pub user_id: Uuid,
+    pub redirect_uri: String,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default)]
+    pub mfa_failures: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkOutcome {
+    /// Issue tokens for the user and send them to `redirect_uri`
+    SignedIn { redirect_uri: String },
+    /// Redeem `continuation` with a TOTP code
+    MfaRequired {
+        continuation: String,
+        expires_at: DateTime<Utc>,
+    },
+}
+
+pub struct LoginLinkService {
+    store: Arc<dyn LoginLinkStore>,
+    nonces: Arc<NonceStore>,
+    email: Arc<dyn EmailProvider>,
+    otp: OtpService,
+    audit: Option<Arc<dyn AuditLogger>>,
+    config: LoginLinkConfig,
+}
+
+impl LoginLinkService {
+    pub fn new(
+        store: Arc<dyn LoginLinkStore>,
+        nonces: Arc<NonceStore>,
+        email: Arc<dyn EmailProvider>,
+        config: LoginLinkConfig,
+    ) -> Self {
+        Self {
+            store,
+            nonces,
+            email,
+            otp: OtpService::new(),
+            audit: None,
+            config,
+        }
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Email `user` a link on behalf of `issued_by`
+    pub async fn issue(
+        &self,
+        user: &User,
+        issued_by: Uuid,
+        request: IssueLoginLinkRequest,
+    ) -> Result<IssuedLoginLink, AuthError> {
+        if !self.config.enabled {
+            return Err(AuthError::ConfigurationError {
+                message: "Login links are disabled".to_string(),
+            });
+        }
+        if !self
+            .config
+            .allowed_redirects
+            .contains(&request.redirect_uri)
+        {
+            return Err(AuthError::ValidationError {
+                message: "Redirect URI is not allowed for login links".to_string(),
+            });
+        }
+        let email = match &user.email {
+            Some(email) if user.email_verified => email.clone(),
+            _ => {
+                return Err(AuthError::ValidationError {
+                    message: "User has no verified email address".to_string(),
+                })
+            }
+        };
+        if !user.can_authenticate() {
+            return Err(AuthError::Unauthorized {
+                message: "Account locked or suspended".to_string(),
+            });
+        }
+
+        let now = Utc::now();
+        let window = Duration::seconds(self.config.issuance_window_seconds as i64);
+        let issued = self
+            .store
+            .issued_since(user.tenant_id, user.id, now - window)
+            .await?;
+        if issued >= self.config.max_per_user {
+            self.record(
+                AuditEvent::new(
+                    AuditCategory::Security,
+                    "login_link.issue",
+                    AuditSeverity::Warning,
+                )
+                .with_actor(issued_by)
+                .with_context(None, None, Some(user.tenant_id))
+                .with_resource(user.id.to_string())
+                .failure("issuance limit reached"),
+            )
+            .await;
+            return Err(AuthError::RateLimitExceeded {
+                limit: self.config.max_per_user,
+                window: format!("{} seconds", self.config.issuance_window_seconds),
+            });
+        }
+
+        let ttl = request
+            .ttl_seconds
+            .unwrap_or(self.config.ttl_seconds)
+            .clamp(1, self.config.ttl_seconds);
+        let link = LoginLink {
+            id: Uuid::new_v4(),
+            tenant_id: user.tenant_id,
+            user_id: user.id,
+            issued_by,
+            redirect_uri: request.redirect_uri,
+            reason: request.reason,
+            created_at: now,
+            expires_at: now + Duration::seconds(ttl as i64),
+            redeemed_at: None,
+        };
+        let token = self
+            .grant_token(&LinkGrant {
+                link_id: link.id,
+                tenant_id: link.tenant_id,
+                user_id: link.user_id,
+                redirect_uri: link.redirect_uri.clone(),
+                expires_at: link.expires_at,
+                mfa_failures: 0,
+            })
+            .await?;
+        self.store.create(&link).await?;
+
+        let url = format!("{}?token={}", self.config.link_url, token);
+        let minutes = ttl.div_ceil(60).to_string();
+        let fill = |text: &str| text.replace("{link}", &url).replace("{minutes}", &minutes);
+        self.email
+            .send_email(
+                &email,
+                &fill(&self.config.email_subject),
+                &fill(&self.config.email_body),
+            )
+            .await
+            .map_err(|e| AuthError::ExternalServiceError {
+                service: "email".to_string(),
+                error: e.to_string(),
+            })?;
+
+        self.record(
+            AuditEvent::new(
+                AuditCategory::Security,
+                "login_link.issue",
+                AuditSeverity::Warning,
+            )
+            .with_actor(issued_by)
+            .with_context(None, None, Some(link.tenant_id))
+            .with_resource(link.user_id.to_string())
+            .with_metadata(json!({
+                "link_id": link.id,
+                "redirect_uri": link.redirect_uri,
+                "reason": link.reason,
+                "expires_at": link.expires_at,
+            })),
+        )
+        .await;
+        info!(
+            link_id = %link.id,
+            user_id = %link.user_id,
+            issued_by = %issued_by,
+            "Login link issued"
+        );
+        Ok(IssuedLoginLink {
+            link_id: link.id,
+            sent_to: mask_email(&email),
+            expires_at: link.expires_at,
+        })
+    }
+
+    /// Spend a link or continuation token. The redirect must be the one the
+    /// link was issued for.
+    pub async fn open(&self, token: &str, redirect_uri: &str) -> Result<LinkGrant, AuthError> {
+        let payload = self
+            .nonces
+            .consume(NonceNamespace::LoginLink, token)
+            .await?;
+        let grant: LinkGrant =
+            serde_json::from_str(&payload).map_err(|_| AuthError::InternalError)?;
+        if grant.redirect_uri != redirect_uri {
+            self.record(
+                self.redemption_event(&grant, AuditSeverity::Warning)
+                    .failure("redirect mismatch"),
+            )
+            .await;
+            return Err(AuthError::TokenError {
+                kind: TokenErrorKind::Invalid,
+            });
+        }
+        Ok(grant)
+    }
+
+    /// Sign `user`, the subject of `grant`, in; or ask for their TOTP code
+    pub async fn finish(
+        &self,
+        grant: LinkGrant,
+        user: &User,
+        mfa_code: Option<&str>,
+    ) -> Result<LinkOutcome, AuthError> {
+        if user.id != grant.user_id || user.tenant_id != grant.tenant_id {
+            return Err(AuthError::InvalidCredentials);
+        }
+        if !user.can_authenticate() {
+            return Err(AuthError::Unauthorized {
+                message: "Account locked or suspended".to_string(),
+            });
+        }
+
+        let secret = user.mfa_secret.as_deref().filter(|_| user.mfa_enabled);
+        if let Some(secret) = secret.filter(|_| self.config.require_mfa) {
+            let verified =
+                mfa_code.is_some_and(|code| self.otp.verify_totp(secret, code).unwrap_or(false));
+            if !verified {
+                let mut grant = grant;
+                if mfa_code.is_some() {
+                    grant.mfa_failures += 1;
+                    if grant.mfa_failures >= MAX_MFA_FAILURES {
+                        self.record(
+                            self.redemption_event(&grant, AuditSeverity::Warning)
+                                .failure("too many MFA failures"),
+                        )
+                        .await;
+                        return Err(AuthError::InvalidOtp);
+                    }
+                }
+                if grant.expires_at <= Utc::now() {
+                    return Err(AuthError::TokenError {
+                        kind: TokenErrorKind::Expired,
+                    });
+                }
+                let expires_at = grant.expires_at;
+                let continuation = self.grant_token(&grant).await?;
+                return Ok(LinkOutcome::MfaRequired {
+                    continuation,
+                    expires_at,
+                });
+            }
+        }
+
+        self.store
+            .mark_redeemed(grant.tenant_id, grant.link_id, Utc::now())
+            .await?;
+        self.record(self.redemption_event(&grant, AuditSeverity::Info))
+            .await;
+        Ok(LinkOutcome::SignedIn {
+            redirect_uri: grant.redirect_uri,
+        })
+    }
+
+    /// A single-use token for `grant`, valid until it expires
+    async fn grant_token(&self, grant: &LinkGrant) -> Result<String, AuthError> {
+        // Two v4 UUIDs: 244 bits from the OS RNG
+        let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let ttl = (grant.expires_at - Utc::now())
+            .to_std()
+            .map_err(|_| AuthError::TokenError {
+                kind: TokenErrorKind::Expired,
+            })?;
+        let payload = serde_json::to_string(grant).map_err(|_| AuthError::InternalError)?;
+        self.nonces
+            .issue(NonceNamespace::LoginLink, &token, &payload, Some(ttl))
+            .await?;
+        Ok(token)
+    }
+
+    fn redemption_event(&self, grant: &LinkGrant, severity: AuditSeverity) -> AuditEvent {
+        AuditEvent::new(AuditCategory::Authentication, "login_link.redeem", severity)
+            .with_actor(grant.user_id)
+            .with_context(None, None, Some(grant.tenant_id))
+            .with_resource(grant.link_id.to_string())
+    }
+
+    async fn record(&self, event: AuditEvent) {
+        if let Some(audit) = &self.audit {
+            audit.log(event).await;
+        }
+    }
+}
+
+/// `j***@example.com`
+pub(crate) fn mask_email(email: &str) -> String {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            let first: String = local.chars().take(1).collect();
+            format!("{}***@{}", first, domain)
+        }
+        None => "***".to_string(),
+    }
+}
+
+/// Process-local store for tests and single-node development
+#[derive(Default)]
+pub struct InMemoryLoginLinkStore {
+    links: DashMap<Uuid, LoginLink>,
+}
+
+#[async_trait]
+impl LoginLinkStore for InMemoryLoginLinkStore {
+    async fn create(&self, link: &LoginLink) -> Result<(), AuthError> {
+        self.links.insert(link.id, link.clone());
+        Ok(())
+    }
+
+    async fn issued_since(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<u32, AuthError> {
+        Ok(self
+            .links
+            .iter()
+            .filter(|l| l.tenant_id == tenant_id && l.user_id == user_id && l.created_at >= since)
+            .count() as u32)
+    }
+
+    async fn mark_redeemed(
+        &self,
+        tenant_id: Uuid,
+        link_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError> {
+        if let Some(mut link) = self.links.get_mut(&link_id) {
+            if link.tenant_id == tenant_id {
+                link.redeemed_at = Some(at);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UserStatus;
+    use crate::services::nonce_store::MemoryNonceBackend;
+    use crate::services::otp_delivery::DeliveryError;
+    use parking_lot::Mutex;
+
+    const REDIRECT: &str = "https://app.example.com/settings";
+
+    #[derive(Default)]
+    struct Outbox {
+        sent: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl EmailProvider for Outbox {
+        async fn send_email(
+            &self,
+            to: &str,
+            _subject: &str,
+            body: &str,
+        ) -> Result<String, DeliveryError> {
+            self.sent.lock().push((to.to_string(), body.to_string()));
+            Ok("queued".to_string())
+        }
+    }
+
+    impl Outbox {
+        fn last_token(&self) -> String {
+            let (_, body) = self.sent.lock().last().cloned().unwrap();
+            let start = body.find("token=").unwrap() + "token=".len();
+            body[start..start + 64].to_string()
+        }
+    }
+
+    fn service(outbox: Arc<Outbox>) -> LoginLinkService {
+        LoginLinkService::new(
+            Arc::new(InMemoryLoginLinkStore::default()),
+            Arc::new(NonceStore::new(Arc::new(MemoryNonceBackend::default()))),
+            outbox,
+            LoginLinkConfig {
+                enabled: true,
+                allowed_redirects: vec![REDIRECT.to_string()],
+                max_per_user: 2,
+                email_body: "{link}".to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn user() -> User {
+        User {
+            email: Some("jordan@example.com".to_string()),
+            email_verified: true,
+            status: UserStatus::Active,
+            ..User::default()
+        }
+    }
+
+    fn request() -> IssueLoginLinkRequest {
+        IssueLoginLinkRequest {
+            redirect_uri: REDIRECT.to_string(),
+            ttl_seconds: None,
+            reason: Some("ticket 4211".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_link_signs_in_once() {
+        let outbox = Arc::new(Outbox::default());
+        let links = service(outbox.clone());
+        let user = user();
+
+        let issued = links.issue(&user, Uuid::new_v4(), request()).await.unwrap();
+        assert_eq!(issued.sent_to, "j***@example.com");
+        assert_eq!(outbox.sent.lock()[0].0, "jordan@example.com");
+        let token = outbox.last_token();
+
+        let grant = links.open(&token, REDIRECT).await.unwrap();
+        assert_eq!(
+            links.finish(grant, &user, None).await.unwrap(),
+            LinkOutcome::SignedIn {
+                redirect_uri: REDIRECT.to_string()
+            }
+        );
+        assert!(matches!(
+            links.open(&token, REDIRECT).await,
+            Err(AuthError::TokenError {
+                kind: TokenErrorKind::Replayed
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_links_are_scoped() {
+        let outbox = Arc::new(Outbox::default());
+        let links = service(outbox.clone());
+        let user = user();
+
+        let elsewhere = IssueLoginLinkRequest {
+            redirect_uri: "https://evil.example.net/".to_string(),
+            ..request()
+        };
+        assert!(links.issue(&user, Uuid::new_v4(), elsewhere).await.is_err());
+        let unverified = User {
+            email_verified: false,
+            ..user.clone()
+        };
+        assert!(links
+            .issue(&unverified, Uuid::new_v4(), request())
+            .await
+            .is_err());
+
+        links.issue(&user, Uuid::new_v4(), request()).await.unwrap();
+        let token = outbox.last_token();
+        assert!(links
+            .open(&token, "https://app.example.com/")
+            .await
+            .is_err());
+        // A link presented with the wrong redirect is spent
+        assert!(links.open(&token, REDIRECT).await.is_err());
+
+        links.issue(&user, Uuid::new_v4(), request()).await.unwrap();
+        assert!(matches!(
+            links.issue(&user, Uuid::new_v4(), request()).await,
+            Err(AuthError::RateLimitExceeded { limit: 2, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mfa_users_continue_with_a_code() {
+        let outbox = Arc::new(Outbox::default());
+        let links = service(outbox.clone());
+        let secret = "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP";
+        let user = User {
+            mfa_enabled: true,
+            mfa_secret: Some(secret.to_string()),
+            ..user()
+        };
+
+        links.issue(&user, Uuid::new_v4(), request()).await.unwrap();
+        let grant = links.open(&outbox.last_token(), REDIRECT).await.unwrap();
+        let LinkOutcome::MfaRequired { continuation, .. } =
+            links.finish(grant, &user, None).await.unwrap()
+        else {
+            panic!("MFA user signed in without a code");
+        };
+
+        let grant = links.open(&continuation, REDIRECT).await.unwrap();
+        let LinkOutcome::MfaRequired { continuation, .. } =
+            links.finish(grant, &user, Some("000000")).await.unwrap()
+        else {
+            panic!("wrong code accepted");
+        };
+
+        let code = totp_rs::TOTP::new(
+            totp_rs::Algorithm::SHA1,
+            6,
+            1,
+            30,
+            totp_rs::Secret::Encoded(secret.to_string())
+                .to_bytes()
+                .unwrap(),
+            None,
+            "user".to_string(),
+        )
+        .unwrap()
+        .generate_current()
+        .unwrap();
+        let grant = links.open(&continuation, REDIRECT).await.unwrap();
+        assert!(matches!(
+            links.finish(grant, &user, Some(&code)).await.unwrap(),
+            LinkOutcome::SignedIn { .. }
+        ));
+    }
+}
@@ -1,15 +1,51 @@
+pub mod adaptive_quota;
+pub mod analytics_export;
+pub mod auth_methods;
 pub mod authorization;
 pub mod background;
+pub mod captcha;
+pub mod claims_enrichment;
 pub mod credential;
+pub mod device_enrollment;
+pub mod feature_flags;
+pub mod forced_reauth;
+pub mod geo;
+pub mod id_token;
+pub mod identifier;
 pub mod identity;
+pub mod jwks;
 pub mod lazy_registration;
+pub mod legal_hold;
+pub mod login_heatmap;
+pub mod login_history;
+pub mod login_link;
+pub mod nonce_store;
 pub mod otp_delivery;
 pub mod otp_service;
+pub mod password_expiry;
+pub mod permission_sync;
+pub mod push_mfa;
 pub mod rate_limiter;
+pub mod recovery_codes;
+pub mod remember_me;
 pub mod risk_assessment;
 pub mod role_service;
+pub mod service_account;
 pub mod session_service;
+pub mod signup_abuse;
+pub mod sms_budget;
+pub mod sms_compliance;
+pub mod sms_risk;
+pub mod ssh_ca;
+pub mod sso_session;
 pub mod subscription_service;
+pub mod takeover_response;
+pub mod tenant_context;
+pub mod tenant_metrics;
+pub mod tenant_onboarding;
+pub mod tenant_quota;
+pub mod timing;
 pub mod token_service;
+pub mod token_ttl;
 pub mod webauthn_service;
 pub mod workflow;
@@ -0,0 +1,363 @@
+//! Single-use tokens and nonces
+//!
+//! Authorization codes, magic links and logout tokens may each be redeemed
+//! exactly once. Every check goes through [`NonceStore`], which keeps a
+//! tombstone after a token is consumed so that a second redemption is told
+//! apart from an unknown or expired token and counted as a replay.
+//!
+//! The primary backend is Redis (an atomic Lua check-and-set); the database
+//! backend takes over when Redis is unavailable. Tokens issued on one backend
+//! cannot be redeemed on the other, so a fallback fails closed.
+
+use crate::error::{AuthError, TokenErrorKind};
+use async_trait::async_trait;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NonceNamespace {
+    AuthCode,
+    MagicLink,
+    LogoutToken,
+    JwtAssertion,
+    PasswordChange,
+    LoginLink,
+    /// Progress of a logout through upstream identity providers
+    UpstreamLogout,
+}
+
+impl NonceNamespace {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NonceNamespace::AuthCode => "auth_code",
+            NonceNamespace::MagicLink => "magic_link",
+            NonceNamespace::LogoutToken => "logout_token",
+            NonceNamespace::JwtAssertion => "jwt_assertion",
+            NonceNamespace::PasswordChange => "password_change",
+            NonceNamespace::LoginLink => "login_link",
+            NonceNamespace::UpstreamLogout => "upstream_logout",
+        }
+    }
+
+    /// Lifetime used when the caller does not pick one
+    pub fn default_ttl(&self) -> Duration {
+        match self {
+            NonceNamespace::AuthCode => Duration::from_secs(600),
+            NonceNamespace::MagicLink => Duration::from_secs(900),
+            // Covers the logout token's own lifetime plus clock skew
+            NonceNamespace::LogoutToken => Duration::from_secs(300),
+            NonceNamespace::JwtAssertion => Duration::from_secs(300),
+            NonceNamespace::PasswordChange => Duration::from_secs(600),
+            NonceNamespace::LoginLink => Duration::from_secs(900),
+            // Time the user may spend at one provider's logout page
+            NonceNamespace::UpstreamLogout => Duration::from_secs(600),
+        }
+    }
+}
+
+/// Result of redeeming a token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redemption {
+    /// First redemption; carries the payload stored at issue time
+    Redeemed(String),
+    /// The token was valid once and has already been redeemed
+    Replayed,
+    /// Never issued, or expired
+    Unknown,
+}
+
+#[async_trait]
+pub trait NonceBackend: Send + Sync {
+    /// Store `payload` under `key` unless the key exists. Returns false on collision.
+    async fn put_if_absent(&self, key: &str, payload: &str, ttl: Duration) -> anyhow::Result<bool>;
+
+    /// Atomically read the payload and replace it with a tombstone that
+    /// keeps the remaining TTL
+    async fn redeem(&self, key: &str) -> anyhow::Result<Redemption>;
+}
+
+pub struct NonceStore {
+    primary: Arc<dyn NonceBackend>,
+    fallback: Option<Arc<dyn NonceBackend>>,
+}
+
+impl NonceStore {
+    pub fn new(primary: Arc<dyn NonceBackend>) -> Self {
+        Self {
+            primary,
+            fallback: None,
+        }
+    }
+
+    pub fn with_fallback(mut self, fallback: Arc<dyn NonceBackend>) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    /// Register a token the client will redeem later
+    pub async fn issue(
+        &self,
+        namespace: NonceNamespace,
+        token: &str,
+        payload: &str,
+        ttl: Option<Duration>,
+    ) -> Result<(), AuthError> {
+        let key = Self::key(namespace, token);
+        let ttl = ttl.unwrap_or_else(|| namespace.default_ttl());
+
+        let inserted = match self.primary.put_if_absent(&key, payload, ttl).await {
+            Ok(inserted) => inserted,
+            Err(e) => {
+                let fallback = self.fallback(namespace, e)?;
+                fallback
+                    .put_if_absent(&key, payload, ttl)
+                    .await
+                    .map_err(Self::backend_err)?
+            }
+        };
+
+        if inserted {
+            Ok(())
+        } else {
+            Err(AuthError::Conflict {
+                message: format!("{} already issued", namespace.as_str()),
+            })
+        }
+    }
+
+    /// Redeem a token issued with [`NonceStore::issue`], returning its payload
+    pub async fn consume(
+        &self,
+        namespace: NonceNamespace,
+        token: &str,
+    ) -> Result<String, AuthError> {
+        let key = Self::key(namespace, token);
+        let redemption = match self.primary.redeem(&key).await {
+            Ok(r) => r,
+            Err(e) => self
+                .fallback(namespace, e)?
+                .redeem(&key)
+                .await
+                .map_err(Self::backend_err)?,
+        };
+
+        match redemption {
+            Redemption::Redeemed(payload) => Ok(payload),
+            Redemption::Replayed => Err(Self::replay(namespace)),
+            Redemption::Unknown => Err(AuthError::TokenError {
+                kind: TokenErrorKind::Invalid,
+            }),
+        }
+    }
+
+    /// Accept a value seen for the first time and reject it afterwards. For
+    /// externally minted tokens, such as a logout token's `jti`, that were
+    /// never issued through this store.
+    pub async fn check_unused(
+        &self,
+        namespace: NonceNamespace,
+        value: &str,
+        ttl: Option<Duration>,
+    ) -> Result<(), AuthError> {
+        match self.issue(namespace, value, "", ttl).await {
+            Err(AuthError::Conflict { .. }) => Err(Self::replay(namespace)),
+            other => other,
+        }
+    }
+
+    fn key(namespace: NonceNamespace, token: &str) -> String {
+        format!("nonce:{}:{}", namespace.as_str(), token)
+    }
+
+    fn fallback(
+        &self,
+        namespace: NonceNamespace,
+        error: anyhow::Error,
+    ) -> Result<&Arc<dyn NonceBackend>, AuthError> {
+        match &self.fallback {
+            Some(fallback) => {
+                warn!(
+                    "Nonce store primary failed for {}: {}; using fallback",
+                    namespace.as_str(),
+                    error
+                );
+                metrics::counter!("auth_nonce_fallback_total", 1, "namespace" => namespace.as_str());
+                Ok(fallback)
+            }
+            None => Err(Self::backend_err(error)),
+        }
+    }
+
+    fn replay(namespace: NonceNamespace) -> AuthError {
+        warn!("Replay of single-use {} rejected", namespace.as_str());
+        metrics::counter!("auth_nonce_replay_total", 1, "namespace" => namespace.as_str());
+        AuthError::TokenError {
+            kind: TokenErrorKind::Replayed,
+        }
+    }
+
+    fn backend_err(error: anyhow::Error) -> AuthError {
+        AuthError::ExternalServiceError {
+            service: "nonce_store".to_string(),
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Process-local backend for tests and single-node development
+#[derive(Default)]
+pub struct MemoryNonceBackend {
+    entries: DashMap<String, (Option<String>, Instant)>,
+}
+
+#[async_trait]
+impl NonceBackend for MemoryNonceBackend {
+    async fn put_if_absent(&self, key: &str, payload: &str, ttl: Duration) -> anyhow::Result<bool> {
+        let now = Instant::now();
+        let mut entry = self.entries.entry(key.to_string()).or_insert((None, now));
+        // A fresh entry carries the `now` marker and is treated as expired
+        if entry.1 > now {
+            return Ok(false);
+        }
+        *entry = (Some(payload.to_string()), now + ttl);
+        Ok(true)
+    }
+
+    async fn redeem(&self, key: &str) -> anyhow::Result<Redemption> {
+        let Some(mut entry) = self.entries.get_mut(key) else {
+            return Ok(Redemption::Unknown);
+        };
+        if entry.1 <= Instant::now() {
+            drop(entry);
+            self.entries.remove(key);
+            return Ok(Redemption::Unknown);
+        }
+        Ok(match entry.0.take() {
+            Some(payload) => Redemption::Redeemed(payload),
+            None => Redemption::Replayed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FailingBackend;
+
+    #[async_trait]
+    impl NonceBackend for FailingBackend {
+        async fn put_if_absent(&self, _: &str, _: &str, _: Duration) -> anyhow::Result<bool> {
+            anyhow::bail!("connection refused")
+        }
+
+        async fn redeem(&self, _: &str) -> anyhow::Result<Redemption> {
+            anyhow::bail!("connection refused")
+        }
+    }
+
+    fn store() -> NonceStore {
+        NonceStore::new(Arc::new(MemoryNonceBackend::default()))
+    }
+
+    #[tokio::test]
+    async fn test_token_redeems_once() {
+        let store = store();
+        store
+            .issue(NonceNamespace::AuthCode, "code-1", "payload", None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store
+                .consume(NonceNamespace::AuthCode, "code-1")
+                .await
+                .unwrap(),
+            "payload"
+        );
+        assert!(matches!(
+            store.consume(NonceNamespace::AuthCode, "code-1").await,
+            Err(AuthError::TokenError {
+                kind: TokenErrorKind::Replayed
+            })
+        ));
+        assert!(matches!(
+            store
+                .consume(NonceNamespace::AuthCode, "never-issued")
+                .await,
+            Err(AuthError::TokenError {
+                kind: TokenErrorKind::Invalid
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_are_isolated() {
+        let store = store();
+        store
+            .issue(NonceNamespace::MagicLink, "t", "user", None)
+            .await
+            .unwrap();
+        assert!(store.consume(NonceNamespace::AuthCode, "t").await.is_err());
+        assert!(store.consume(NonceNamespace::MagicLink, "t").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_is_unknown() {
+        let store = store();
+        store
+            .issue(
+                NonceNamespace::MagicLink,
+                "t",
+                "user",
+                Some(Duration::from_millis(0)),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.consume(NonceNamespace::MagicLink, "t").await,
+            Err(AuthError::TokenError {
+                kind: TokenErrorKind::Invalid
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_check_unused_rejects_second_sighting() {
+        let store = store();
+        store
+            .check_unused(NonceNamespace::LogoutToken, "jti-1", None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            store
+                .check_unused(NonceNamespace::LogoutToken, "jti-1", None)
+                .await,
+            Err(AuthError::TokenError {
+                kind: TokenErrorKind::Replayed
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_when_primary_fails() {
+        let store = NonceStore::new(Arc::new(FailingBackend))
+            .with_fallback(Arc::new(MemoryNonceBackend::default()));
+        store
+            .issue(NonceNamespace::AuthCode, "c", "p", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.consume(NonceNamespace::AuthCode, "c").await.unwrap(),
+            "p"
+        );
+
+        let no_fallback = NonceStore::new(Arc::new(FailingBackend));
+        assert!(matches!(
+            no_fallback.consume(NonceNamespace::AuthCode, "c").await,
+            Err(AuthError::ExternalServiceError { .. })
+        ));
+    }
+}
@@ -4,12 +4,20 @@
 //! - Firebase Authentication (for phone OTP)
 //! - SMTP (for email OTP)
 //!
-//! Includes circuit breakers and fallback mechanisms
+//! Includes circuit breakers and fallback mechanisms. SMS can be routed to
+//! a provider per destination region, with the region's carrier
+//! registration attached (see [`super::sms_compliance`]).
 
+use super::sms_budget::SmsBudgetService;
+use super::sms_compliance::{SmsComplianceService, SmsMessage, OTP_MESSAGE};
+use super::sms_risk::SmsRiskService;
 use async_trait::async_trait;
+use auth_platform::{HttpClient, HttpClientError};
 use lettre::{transport::smtp::authentication::Credentials, Message, SmtpTransport, Transport};
+use std::collections::HashMap;
 use std::sync::Arc;
 use thiserror::Error;
+use uuid::Uuid;
 
 #[derive(Debug, Error)]
 pub enum DeliveryError {
@@ -19,6 +27,9 @@ pub enum DeliveryError {
     #[error("Email delivery failed: {0}")]
     EmailFailed(String),
 
+    #[error("Push delivery failed: {0}")]
+    PushFailed(String),
+
     #[error("Circuit breaker open for {0}")]
     CircuitBreakerOpen(String),
 
@@ -30,12 +41,51 @@ pub enum DeliveryError {
 
     #[error("Provider configuration error: {0}")]
     ConfigError(String),
+
+    #[error("SMS not sendable under carrier registration: {0}")]
+    ComplianceViolation(String),
+
+    #[error("SMS budget exhausted: {limit} messages per {period}")]
+    BudgetExhausted { limit: u64, period: String },
+
+    #[error("SMS destination blocked: {0}")]
+    DestinationBlocked(String),
+
+    #[error("SMS destination held for review")]
+    DestinationQuarantined,
+}
+
+/// Map a shared HTTP client failure, keeping an open circuit distinguishable
+pub(crate) fn delivery_error(
+    e: HttpClientError,
+    failed: fn(String) -> DeliveryError,
+) -> DeliveryError {
+    match e {
+        HttpClientError::CircuitOpen { host } => DeliveryError::CircuitBreakerOpen(host),
+        e => failed(e.to_string()),
+    }
+}
+
+/// Text of the one-time code SMS. Regions requiring registration need a
+/// template matching it, with `{#var#}` in place of the code.
+pub fn otp_text(otp: &str) -> String {
+    format!(
+        "Your verification code is: {}. Valid for 10 minutes. Do not share this code.",
+        otp
+    )
 }
 
 /// SMS/OTP Provider trait
 #[async_trait]
 pub trait OtpProvider: Send + Sync {
     async fn send_otp(&self, to: &str, otp: &str) -> Result<String, DeliveryError>;
+
+    /// Send `message` with its sender and registration metadata. Providers
+    /// that word the message themselves (e.g. Firebase) keep the default,
+    /// which passes on only the code.
+    async fn send_sms(&self, message: &SmsMessage, otp: &str) -> Result<String, DeliveryError> {
+        self.send_otp(&message.to, otp).await
+    }
 }
 
 /// Email Provider trait
@@ -55,15 +105,15 @@ pub struct FirebaseOtpProvider {
     #[allow(dead_code)]
     project_id: String,
     api_key: String,
-    client: reqwest::Client,
+    client: HttpClient,
 }
 
 impl FirebaseOtpProvider {
-    pub fn new(project_id: String, api_key: String) -> Self {
+    pub fn new(project_id: String, api_key: String, client: HttpClient) -> Self {
         Self {
             project_id,
             api_key,
-            client: reqwest::Client::new(),
+            client,
         }
     }
 
@@ -82,11 +132,9 @@ impl FirebaseOtpProvider {
 
         let response = self
             .client
-            .post(&url)
-            .json(&body)
-            .send()
+            .send(self.client.post(&url).json(&body))
             .await
-            .map_err(|e| DeliveryError::SmsFailed(e.to_string()))?;
+            .map_err(|e| delivery_error(e, DeliveryError::SmsFailed))?;
 
         if response.status().is_success() {
             let result: serde_json::Value = response
@@ -124,16 +172,16 @@ pub struct GenericSmsProvider {
     api_url: String,
     api_key: String,
     sender_id: String,
-    client: reqwest::Client,
+    client: HttpClient,
 }
 
 impl GenericSmsProvider {
-    pub fn new(api_url: String, api_key: String, sender_id: String) -> Self {
+    pub fn new(api_url: String, api_key: String, sender_id: String, client: HttpClient) -> Self {
         Self {
             api_url,
             api_key,
             sender_id,
-            client: reqwest::Client::new(),
+            client,
         }
     }
 }
@@ -141,24 +189,31 @@ impl GenericSmsProvider {
 #[async_trait]
 impl OtpProvider for GenericSmsProvider {
     async fn send_otp(&self, to: &str, otp: &str) -> Result<String, DeliveryError> {
-        let message = format!(
-            "Your verification code is: {}. Valid for 10 minutes. Do not share this code.",
-            otp
-        );
+        self.send_sms(&SmsMessage::new(to, otp_text(otp)), otp)
+            .await
+    }
+
+    async fn send_sms(&self, message: &SmsMessage, _otp: &str) -> Result<String, DeliveryError> {
+        let to = message.to.as_str();
 
         // Generic SMS API call (adapt based on your provider)
-        let response = self
+        let request = self
             .client
             .post(&self.api_url)
             .header("Authorization", format!("Bearer {}", self.api_key))
             .json(&serde_json::json!({
                 "to": to,
-                "text": message,
-                "senderId": self.sender_id,
-            }))
-            .send()
+                "text": message.text,
+                "senderId": message.sender_id.as_deref().unwrap_or(&self.sender_id),
+                "entityId": message.entity_id,
+                "templateId": message.template_id,
+                "campaignId": message.campaign_id,
+            }));
+        let response = self
+            .client
+            .send(request)
             .await
-            .map_err(|e| DeliveryError::SmsFailed(e.to_string()))?;
+            .map_err(|e| delivery_error(e, DeliveryError::SmsFailed))?;
 
         if response.status().is_success() {
             tracing::info!("SMS OTP sent successfully to {}", to);
@@ -345,12 +400,23 @@ impl CircuitBreaker {
     }
 }
 
+/// SMS provider for one region, with its own breaker
+struct RegionalSmsProvider {
+    provider: Arc<dyn OtpProvider>,
+    circuit_breaker: CircuitBreaker,
+}
+
 /// OTP Delivery Service with Firebase and SMTP
 pub struct OtpDeliveryService {
     otp_provider: Arc<dyn OtpProvider>,
     email_provider: Arc<dyn EmailProvider>,
     otp_circuit_breaker: Arc<CircuitBreaker>,
     email_circuit_breaker: Arc<CircuitBreaker>,
+    sms_compliance: Option<Arc<SmsComplianceService>>,
+    sms_budget: Option<Arc<SmsBudgetService>>,
+    sms_risk: Option<Arc<SmsRiskService>>,
+    /// Keyed by region name; other regions use `otp_provider`
+    regional_sms: HashMap<String, RegionalSmsProvider>,
 }
 
 impl OtpDeliveryService {
@@ -360,24 +426,106 @@ impl OtpDeliveryService {
             email_provider,
             otp_circuit_breaker: Arc::new(CircuitBreaker::new(5, 60)),
             email_circuit_breaker: Arc::new(CircuitBreaker::new(5, 60)),
+            sms_compliance: None,
+            sms_budget: None,
+            sms_risk: None,
+            regional_sms: HashMap::new(),
         }
     }
 
-    /// Send OTP via SMS/Firebase with circuit breaker
-    pub async fn send_phone_otp(&self, to: &str, otp: &str) -> Result<String, DeliveryError> {
-        if self.otp_circuit_breaker.is_open().await {
-            return Err(DeliveryError::CircuitBreakerOpen(
+    /// Attach carrier registration to SMS and refuse unregistered messages
+    pub fn with_sms_compliance(mut self, compliance: Arc<SmsComplianceService>) -> Self {
+        self.sms_compliance = Some(compliance);
+        self
+    }
+
+    /// Hold each tenant's SMS to its budget. SMS without a tenant is not
+    /// counted.
+    pub fn with_sms_budget(mut self, budget: Arc<SmsBudgetService>) -> Self {
+        self.sms_budget = Some(budget);
+        self
+    }
+
+    /// Screen SMS destinations for toll fraud before dispatch
+    pub fn with_sms_risk(mut self, risk: Arc<SmsRiskService>) -> Self {
+        self.sms_risk = Some(risk);
+        self
+    }
+
+    /// Send SMS for numbers routed to `region` through `provider`
+    pub fn with_regional_sms_provider(
+        mut self,
+        region: impl Into<String>,
+        provider: Arc<dyn OtpProvider>,
+    ) -> Self {
+        self.regional_sms.insert(
+            region.into(),
+            RegionalSmsProvider {
+                provider,
+                circuit_breaker: CircuitBreaker::new(5, 60),
+            },
+        );
+        self
+    }
+
+    /// Send OTP via SMS/Firebase with circuit breaker. `tenant_id` selects
+    /// the tenant's own carrier registration where it has one, and the
+    /// budget the message counts against; an exhausted budget fails with
+    /// [`DeliveryError::BudgetExhausted`] so callers can fall back to email.
+    /// Numbers that fail toll fraud screening are refused with
+    /// [`DeliveryError::DestinationBlocked`] or
+    /// [`DeliveryError::DestinationQuarantined`].
+    pub async fn send_phone_otp(
+        &self,
+        tenant_id: Option<Uuid>,
+        to: &str,
+        otp: &str,
+    ) -> Result<String, DeliveryError> {
+        let budget = self.sms_budget.as_ref().zip(tenant_id);
+        if let Some((budget, tenant_id)) = budget {
+            budget.check(tenant_id).await?;
+        }
+        if let Some(risk) = &self.sms_risk {
+            risk.screen(tenant_id, to).await?;
+        }
+
+        let text = otp_text(otp);
+        let message = match &self.sms_compliance {
+            Some(compliance) => compliance.prepare(tenant_id, OTP_MESSAGE, to, text)?,
+            None => SmsMessage::new(to, text),
+        };
+
+        let regional = message
+            .region
+            .as_ref()
+            .and_then(|region| Some((region, self.regional_sms.get(region)?)));
+        let (provider, breaker, name) = match regional {
+            Some((region, route)) => (
+                &route.provider,
+                &route.circuit_breaker,
+                format!("SMS Provider ({})", region),
+            ),
+            None => (
+                &self.otp_provider,
+                self.otp_circuit_breaker.as_ref(),
                 "OTP Provider".to_string(),
-            ));
+            ),
+        };
+
+        if breaker.is_open().await {
+            return Err(DeliveryError::CircuitBreakerOpen(name));
         }
 
-        match self.otp_provider.send_otp(to, otp).await {
+        match provider.send_sms(&message, otp).await {
             Ok(msg_id) => {
-                self.otp_circuit_breaker.record_success().await;
+                breaker.record_success().await;
+                if let Some((budget, tenant_id)) = budget {
+                    budget.record(tenant_id, to).await;
+                }
                 Ok(msg_id)
             }
             Err(e) => {
-                self.otp_circuit_breaker.record_failure().await;
+                breaker.record_failure().await;
                 Err(e)
             }
         }
@@ -410,12 +558,13 @@ impl OtpDeliveryService {
     /// Send OTP with automatic fallback
     pub async fn send_with_fallback(
         &self,
+        tenant_id: Option<Uuid>,
         identifier: &str,
         otp: &str,
         prefer_phone: bool,
     ) -> Result<(String, &str), DeliveryError> {
         if prefer_phone {
-            match self.send_phone_otp(identifier, otp).await {
+            match self.send_phone_otp(tenant_id, identifier, otp).await {
                 Ok(id) => return Ok((id, "phone")),
                 Err(e) => {
                     tracing::warn!("Phone OTP delivery failed, trying email: {:?}", e);
@@ -429,19 +578,25 @@ impl OtpDeliveryService {
         }
     }
     /// Send verification email (Magic Link)
+    /// `code` is offered next to the link for readers whose mail scanner
+    /// consumes or rewrites links
     pub async fn send_verification_email(
         &self,
         to: &str,
         link: &str,
+        code: Option<&str>,
     ) -> Result<String, DeliveryError> {
         if self.email_circuit_breaker.is_open().await {
             return Err(DeliveryError::CircuitBreakerOpen("Email".to_string()));
         }
 
         let subject = "Verify your email address";
+        let code_section = code
+            .map(|code| format!("Or enter this code on the verification page: {}\n\n", code))
+            .unwrap_or_default();
         let body = format!(
-            "Please click the link below to verify your email address:\n\n{}\n\nThis link will expire in 24 hours.\n\nIf you didn't request this, please ignore this email.",
-            link
+            "Please click the link below to verify your email address:\n\n{}\n\n{}This link will expire in 24 hours.\n\nIf you didn't request this, please ignore this email.",
+            link, code_section
         );
 
         match self.email_provider.send_email(to, subject, &body).await {
@@ -489,10 +644,74 @@ mod tests {
         let service =
             OtpDeliveryService::new(Arc::new(MockOtpProvider), Arc::new(MockEmailProvider));
 
-        let result = service.send_phone_otp("+14155552671", "123456").await;
+        let result = service.send_phone_otp(None, "+14155552671", "123456").await;
         assert!(result.is_ok());
     }
 
+    struct RecordingSmsProvider(parking_lot::Mutex<Vec<SmsMessage>>);
+
+    #[async_trait]
+    impl OtpProvider for RecordingSmsProvider {
+        async fn send_otp(&self, _to: &str, _otp: &str) -> Result<String, DeliveryError> {
+            unreachable!("messages go through send_sms")
+        }
+
+        async fn send_sms(
+            &self,
+            message: &SmsMessage,
+            _otp: &str,
+        ) -> Result<String, DeliveryError> {
+            self.0.lock().push(message.clone());
+            Ok("regional-id".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_regional_provider_receives_registered_message() {
+        use auth_config::{SmsComplianceConfig, SmsRegionConfig, SmsTemplate};
+
+        let india = SmsRegionConfig {
+            calling_codes: vec!["91".to_string()],
+            sender_id: Some("AUTHID".to_string()),
+            templates: [(
+                OTP_MESSAGE.to_string(),
+                SmsTemplate {
+                    template_id: "1107000000000000001".to_string(),
+                    body: otp_text("{#var#}"),
+                },
+            )]
+            .into(),
+            ..SmsRegionConfig::default()
+        };
+        let compliance = SmsComplianceService::new(SmsComplianceConfig {
+            regions: [("in".to_string(), india)].into(),
+            ..SmsComplianceConfig::default()
+        });
+        let regional = Arc::new(RecordingSmsProvider(Default::default()));
+        let service =
+            OtpDeliveryService::new(Arc::new(MockOtpProvider), Arc::new(MockEmailProvider))
+                .with_sms_compliance(Arc::new(compliance))
+                .with_regional_sms_provider("in", regional.clone());
+
+        let id = service
+            .send_phone_otp(None, "+919812345678", "482913")
+            .await
+            .unwrap();
+        assert_eq!(id, "regional-id");
+        {
+            let sent = regional.0.lock();
+            assert_eq!(sent[0].template_id.as_deref(), Some("1107000000000000001"));
+            assert_eq!(sent[0].sender_id.as_deref(), Some("AUTHID"));
+        }
+
+        // Other regions still go to the default provider
+        let id = service
+            .send_phone_otp(None, "+14155552671", "482913")
+            .await
+            .unwrap();
+        assert_eq!(id, "mock-otp-id");
+    }
+
     #[tokio::test]
     async fn test_circuit_breaker() {
         let breaker = CircuitBreaker::new(3, 60);
@@ -508,4 +727,17 @@ mod tests {
         breaker.record_success().await;
         assert!(!breaker.is_open().await);
     }
+
+    #[test]
+    fn test_open_host_circuit_reported_as_breaker_open() {
+        let error = delivery_error(
+            HttpClientError::CircuitOpen {
+                host: "sms.example.com:443".to_string(),
+            },
+            DeliveryError::SmsFailed,
+        );
+        assert!(
+            matches!(error, DeliveryError::CircuitBreakerOpen(host) if host == "sms.example.com:443")
+        );
+    }
 }
@@ -5,10 +5,18 @@
 //! - Registration verification
 //! - Email/Phone verification
 //! - Password reset
+//!
+//! Each purpose has its own code length, lifetime and alphabet, set under
+//! `[security.otp_policies]` and overridable per tenant.
 
+use async_trait::async_trait;
+use auth_config::{
+    OtpAlphabet, OtpPolicy, OtpPolicyConfig, OtpPolicyOverrides, OtpPurposePolicies,
+};
 use bcrypt::{hash, verify, DEFAULT_COST};
 use chrono::{DateTime, Duration, Utc};
 use rand::{distributions::Alphanumeric, Rng};
+use std::collections::HashMap;
 use thiserror::Error;
 use totp_rs::{Algorithm, Secret, TOTP};
 use uuid::Uuid;
@@ -47,6 +55,8 @@ pub enum OtpPurpose {
     EmailVerification,
     PhoneVerification,
     PasswordReset,
+    /// Token sent in a link to confirm an email address
+    MagicLink,
 }
 
 impl OtpPurpose {
@@ -57,6 +67,8 @@ impl OtpPurpose {
             OtpPurpose::EmailVerification => "verification",
             OtpPurpose::PhoneVerification => "verification",
             OtpPurpose::PasswordReset => "password_reset",
+            // A link confirms the address it was sent to
+            OtpPurpose::MagicLink => "verification",
         }
     }
 }
@@ -89,22 +101,87 @@ pub struct OtpSession {
     pub verified_at: Option<DateTime<Utc>>,
 }
 
+/// Attempt counters as left by an atomic update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OtpAttemptState {
+    pub attempts: u32,
+    pub max_attempts: u32,
+}
+
+impl OtpAttemptState {
+    pub fn remaining(&self) -> u32 {
+        self.max_attempts.saturating_sub(self.attempts)
+    }
+}
+
+/// Session updates that must be atomic across replicas, so concurrent
+/// verifications can neither share an attempt nor both consume the code
+#[async_trait]
+pub trait OtpAttemptStore: Send + Sync {
+    /// Consume one attempt if the session is unverified, unexpired and under
+    /// its limit, returning the counters after the increment
+    async fn reserve_attempt(&self, session_id: Uuid) -> Result<OtpAttemptState, OtpError>;
+
+    /// Mark the session verified; `AlreadyVerified` if another request won
+    async fn mark_verified_once(&self, session_id: Uuid) -> Result<(), OtpError>;
+}
+
 pub struct OtpService {
     // Configuration
     pub default_length: usize,
     pub default_ttl_minutes: i64,
     pub max_attempts: u32,
+    policies: OtpPurposePolicies,
+    tenant_policies: HashMap<Uuid, OtpPolicyOverrides>,
 }
 
 impl OtpService {
     pub fn new() -> Self {
+        Self::with_policies(&OtpPolicyConfig::default())
+    }
+
+    /// Tenant overrides keyed by anything but a tenant id are ignored
+    pub fn with_policies(config: &OtpPolicyConfig) -> Self {
         Self {
             default_length: 6,
             default_ttl_minutes: 10,
             max_attempts: 5,
+            policies: config.default_policy.clone(),
+            tenant_policies: config
+                .tenants
+                .iter()
+                .filter_map(|(id, o)| Uuid::parse_str(id).ok().map(|id| (id, o.clone())))
+                .collect(),
+        }
+    }
+
+    /// The policy for codes of `purpose` issued to users of the tenant
+    pub fn policy(&self, tenant_id: Uuid, purpose: &OtpPurpose) -> OtpPolicy {
+        let o = self.tenant_policies.get(&tenant_id);
+        let p = &self.policies;
+        match purpose {
+            OtpPurpose::Registration => o.and_then(|o| o.registration).unwrap_or(p.registration),
+            OtpPurpose::Login => o.and_then(|o| o.login).unwrap_or(p.login),
+            OtpPurpose::EmailVerification | OtpPurpose::PhoneVerification => {
+                o.and_then(|o| o.verification).unwrap_or(p.verification)
+            }
+            OtpPurpose::PasswordReset => {
+                o.and_then(|o| o.password_reset).unwrap_or(p.password_reset)
+            }
+            OtpPurpose::MagicLink => o.and_then(|o| o.magic_link).unwrap_or(p.magic_link),
         }
     }
 
+    /// Generate a code for `purpose` as the tenant's policy prescribes
+    pub fn generate_for(&self, tenant_id: Uuid, purpose: &OtpPurpose) -> String {
+        let policy = self.policy(tenant_id, purpose);
+        let token_type = match policy.alphabet {
+            OtpAlphabet::Numeric => TokenType::Numeric,
+            OtpAlphabet::Alphanumeric => TokenType::Alphanumeric,
+        };
+        self.generate_token(token_type, policy.length)
+    }
+
     /// Generate a random token based on type and length
     pub fn generate_token(&self, token_type: TokenType, length: usize) -> String {
         let mut rng = rand::thread_rng();
@@ -141,7 +218,8 @@ impl OtpService {
         hash(otp, DEFAULT_COST).map_err(|_| OtpError::GenerationFailed)
     }
 
-    /// Verify OTP against hash
+    /// Verify OTP against hash. bcrypt recomputes the full hash and compares
+    /// it in constant time, so timing does not reveal matching prefixes.
     pub fn verify_otp(&self, otp: &str, hash: &str) -> Result<bool, OtpError> {
         verify(otp, hash).map_err(|_| OtpError::Invalid)
     }
@@ -167,7 +245,9 @@ impl OtpService {
     }
 
     /// Create new OTP session
-    /// If `explicit_token` is provided, it is used. Otherwise, one is generated.
+    /// If `explicit_token` is provided, it is used. Otherwise, one is generated
+    /// per the purpose's policy, whose lifetime also applies unless
+    /// `ttl_minutes` is given.
     #[allow(clippy::too_many_arguments)]
     pub fn create_session(
         &self,
@@ -180,11 +260,12 @@ impl OtpService {
         explicit_token: Option<String>,
         ttl_minutes: Option<i64>,
     ) -> Result<(OtpSession, String), OtpError> {
-        let otp = explicit_token.unwrap_or_else(|| self.generate_otp());
+        let policy = self.policy(tenant_id, &purpose);
+        let otp = explicit_token.unwrap_or_else(|| self.generate_for(tenant_id, &purpose));
         let _otp_hash = self.hash_otp(&otp)?;
 
         let now = Utc::now();
-        let ttl = ttl_minutes.unwrap_or(self.default_ttl_minutes);
+        let ttl = ttl_minutes.unwrap_or(policy.ttl_minutes);
         let expires_at = now + Duration::minutes(ttl);
 
         let session = OtpSession {
@@ -219,6 +300,44 @@ impl OtpService {
     pub fn is_max_attempts_exceeded(&self, session: &OtpSession) -> bool {
         session.attempts >= session.max_attempts
     }
+
+    /// Verify a code against a stored session. The attempt is reserved before
+    /// the hash is checked, so it counts whether or not the code is correct,
+    /// and only one concurrent request can complete the session.
+    pub async fn verify_with_store(
+        &self,
+        store: &dyn OtpAttemptStore,
+        session_id: Uuid,
+        otp: &str,
+        otp_hash: &str,
+    ) -> Result<OtpAttemptState, OtpError> {
+        self.verify_any_with_store(store, session_id, otp, &[otp_hash])
+            .await
+    }
+
+    /// Like [`OtpService::verify_with_store`] for a session that accepts
+    /// several proofs, e.g. a link token and a typed code. One attempt is
+    /// spent per call and the first matching proof completes the session
+    /// for all of them.
+    pub async fn verify_any_with_store(
+        &self,
+        store: &dyn OtpAttemptStore,
+        session_id: Uuid,
+        proof: &str,
+        hashes: &[&str],
+    ) -> Result<OtpAttemptState, OtpError> {
+        let state = store.reserve_attempt(session_id).await?;
+        let mut matched = false;
+        // Check every hash so timing does not reveal which proof was sent
+        for hash in hashes {
+            matched |= self.verify_otp(proof, hash)?;
+        }
+        if !matched {
+            return Err(OtpError::Invalid);
+        }
+        store.mark_verified_once(session_id).await?;
+        Ok(state)
+    }
 }
 
 impl Default for OtpService {
@@ -266,6 +385,203 @@ mod tests {
         assert_eq!(session.attempts, 0);
     }
 
+    #[test]
+    fn test_create_session_follows_purpose_policy() {
+        let tenant_id = Uuid::new_v4();
+        let service = OtpService::with_policies(&OtpPolicyConfig {
+            tenants: HashMap::from([(
+                tenant_id.to_string(),
+                OtpPolicyOverrides {
+                    password_reset: Some(OtpPolicy {
+                        length: 10,
+                        ttl_minutes: 15,
+                        alphabet: OtpAlphabet::Numeric,
+                    }),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        });
+        let create = |tenant_id, purpose| {
+            service
+                .create_session(
+                    tenant_id,
+                    "test@example.com".to_string(),
+                    "email".to_string(),
+                    DeliveryMethod::Email,
+                    purpose,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap()
+        };
+
+        let (session, otp) = create(Uuid::new_v4(), OtpPurpose::PasswordReset);
+        assert_eq!(otp.len(), 8);
+        assert!(session.expires_at <= Utc::now() + Duration::minutes(5));
+
+        let (_, link) = create(Uuid::new_v4(), OtpPurpose::MagicLink);
+        assert_eq!(link.len(), 32);
+
+        // The tenant overrides password resets only
+        let (session, otp) = create(tenant_id, OtpPurpose::PasswordReset);
+        assert_eq!(otp.len(), 10);
+        assert!(otp.chars().all(|c| c.is_ascii_digit()));
+        assert!(session.expires_at > Utc::now() + Duration::minutes(14));
+        let (_, otp) = create(tenant_id, OtpPurpose::Registration);
+        assert_eq!(otp.len(), 6);
+    }
+
+    /// Applies the same conditional updates as the SQL store under one lock
+    struct MemoryAttemptStore {
+        session: parking_lot::Mutex<OtpSession>,
+    }
+
+    #[async_trait]
+    impl OtpAttemptStore for MemoryAttemptStore {
+        async fn reserve_attempt(&self, _session_id: Uuid) -> Result<OtpAttemptState, OtpError> {
+            let mut session = self.session.lock();
+            if session.verified_at.is_some() {
+                return Err(OtpError::AlreadyVerified);
+            }
+            if session.expires_at < Utc::now() {
+                return Err(OtpError::Expired);
+            }
+            if session.attempts >= session.max_attempts {
+                return Err(OtpError::MaxAttemptsExceeded);
+            }
+            session.attempts += 1;
+            Ok(OtpAttemptState {
+                attempts: session.attempts,
+                max_attempts: session.max_attempts,
+            })
+        }
+
+        async fn mark_verified_once(&self, _session_id: Uuid) -> Result<(), OtpError> {
+            let mut session = self.session.lock();
+            if session.verified_at.is_some() {
+                return Err(OtpError::AlreadyVerified);
+            }
+            session.verified_at = Some(Utc::now());
+            Ok(())
+        }
+    }
+
+    fn stored_session(service: &OtpService) -> (std::sync::Arc<MemoryAttemptStore>, String) {
+        let (session, otp) = service
+            .create_session(
+                Uuid::new_v4(),
+                "test@example.com".to_string(),
+                "email".to_string(),
+                DeliveryMethod::Email,
+                OtpPurpose::Login,
+                None,
+                Some("123456".to_string()),
+                None,
+            )
+            .unwrap();
+        let otp_hash = hash(otp, 4).unwrap();
+        let store = std::sync::Arc::new(MemoryAttemptStore {
+            session: parking_lot::Mutex::new(session),
+        });
+        (store, otp_hash)
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_wrong_codes_share_attempt_budget() {
+        let service = std::sync::Arc::new(OtpService::new());
+        let (store, otp_hash) = stored_session(&service);
+
+        let tasks: Vec<_> = (0..20)
+            .map(|_| {
+                let (service, store, otp_hash) = (service.clone(), store.clone(), otp_hash.clone());
+                tokio::spawn(async move {
+                    service
+                        .verify_with_store(store.as_ref(), Uuid::nil(), "000000", &otp_hash)
+                        .await
+                })
+            })
+            .collect();
+
+        let mut invalid = 0;
+        let mut exhausted = 0;
+        for task in tasks {
+            match task.await.unwrap() {
+                Err(OtpError::Invalid) => invalid += 1,
+                Err(OtpError::MaxAttemptsExceeded) => exhausted += 1,
+                other => panic!("unexpected outcome: {:?}", other),
+            }
+        }
+        assert_eq!(invalid, service.max_attempts);
+        assert_eq!(exhausted, 20 - service.max_attempts);
+        assert_eq!(store.session.lock().attempts, service.max_attempts);
+
+        // The correct code no longer helps once the budget is spent
+        assert!(matches!(
+            service
+                .verify_with_store(store.as_ref(), Uuid::nil(), "123456", &otp_hash)
+                .await,
+            Err(OtpError::MaxAttemptsExceeded)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_correct_codes_verify_once() {
+        let service = std::sync::Arc::new(OtpService::new());
+        let (store, otp_hash) = stored_session(&service);
+
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let (service, store, otp_hash) = (service.clone(), store.clone(), otp_hash.clone());
+                tokio::spawn(async move {
+                    service
+                        .verify_with_store(store.as_ref(), Uuid::nil(), "123456", &otp_hash)
+                        .await
+                })
+            })
+            .collect();
+
+        let mut verified = 0;
+        for task in tasks {
+            match task.await.unwrap() {
+                Ok(state) => {
+                    verified += 1;
+                    assert!(state.remaining() < service.max_attempts);
+                }
+                Err(OtpError::AlreadyVerified) => {}
+                other => panic!("unexpected outcome: {:?}", other),
+            }
+        }
+        assert_eq!(verified, 1);
+    }
+
+    #[tokio::test]
+    async fn test_either_proof_completes_session_once() {
+        let service = OtpService::new();
+        let (store, link_hash) = stored_session(&service);
+        let code_hash = hash("654321", 4).unwrap();
+        let hashes = [link_hash.as_str(), code_hash.as_str()];
+
+        assert!(matches!(
+            service
+                .verify_any_with_store(store.as_ref(), Uuid::nil(), "000000", &hashes)
+                .await,
+            Err(OtpError::Invalid)
+        ));
+        assert!(service
+            .verify_any_with_store(store.as_ref(), Uuid::nil(), "654321", &hashes)
+            .await
+            .is_ok());
+        // The link token is spent along with the code
+        assert!(matches!(
+            service
+                .verify_any_with_store(store.as_ref(), Uuid::nil(), "123456", &hashes)
+                .await,
+            Err(OtpError::AlreadyVerified)
+        ));
+    }
+
     #[test]
     fn test_create_session_explicit() {
         let service = OtpService::new();
@@ -0,0 +1,481 @@
+//! Password expiry
+//!
+//! Under a password policy with a maximum age, a password expires that long
+//! after it was set (at account creation, for users who never changed it).
+//! [`PasswordExpiryCampaign`] emails users as expiry approaches, once per
+//! threshold and password. [`ExpiredPasswordGate`] stops a login with an
+//! expired password from getting tokens: the user is handed a short-lived,
+//! single-use ticket that only allows setting a new password.
+
+use crate::error::AuthError;
+use crate::models::User;
+use crate::services::credential::CredentialService;
+use crate::services::nonce_store::{NonceNamespace, NonceStore};
+use crate::services::otp_delivery::EmailProvider;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// A user whose password is old enough to be reminded about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgingPassword {
+    pub user_id: Uuid,
+    pub tenant_id: Uuid,
+    pub email: String,
+    pub password_set_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait PasswordExpiryStore: Send + Sync {
+    /// Active users with an email and a password set before `set_before`,
+    /// in id order after `after`
+    async fn aging_passwords(
+        &self,
+        set_before: DateTime<Utc>,
+        after: Option<Uuid>,
+        limit: usize,
+    ) -> Result<Vec<AgingPassword>, AuthError>;
+    /// Claim the reminder at `threshold_days` for the password set at
+    /// `password_set_at`; false if it was claimed before
+    async fn claim_reminder(
+        &self,
+        user_id: Uuid,
+        password_set_at: DateTime<Utc>,
+        threshold_days: u32,
+    ) -> Result<bool, AuthError>;
+    /// Give a claim back after the reminder could not be sent
+    async fn release_reminder(
+        &self,
+        user_id: Uuid,
+        password_set_at: DateTime<Utc>,
+        threshold_days: u32,
+    ) -> Result<(), AuthError>;
+}
+
+/// When the user's current password was set
+pub fn password_set_at(user: &User) -> DateTime<Utc> {
+    user.password_changed_at.unwrap_or(user.created_at)
+}
+
+/// Subject and body of a reminder; `{days}` and `{expires_on}` are filled in
+#[derive(Debug, Clone)]
+pub struct ReminderTemplate {
+    pub subject: String,
+    pub body: String,
+}
+
+impl ReminderTemplate {
+    pub fn render(&self, days: i64, expires_at: DateTime<Utc>) -> (String, String) {
+        let fill = |text: &str| {
+            text.replace("{days}", &days.to_string())
+                .replace("{expires_on}", &expires_at.format("%Y-%m-%d").to_string())
+        };
+        (fill(&self.subject), fill(&self.body))
+    }
+}
+
+/// Outcome of one pass, or of every pass since startup
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CampaignStats {
+    pub runs: u64,
+    /// Users past the earliest reminder threshold
+    pub scanned: u64,
+    /// Reminders sent, by threshold in days
+    pub sent: BTreeMap<u32, u64>,
+    pub failed: u64,
+    /// Users whose password has expired
+    pub expired: u64,
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+impl CampaignStats {
+    fn add(&mut self, run: &CampaignStats) {
+        self.runs += run.runs;
+        self.scanned += run.scanned;
+        for (days, sent) in &run.sent {
+            *self.sent.entry(*days).or_default() += sent;
+        }
+        self.failed += run.failed;
+        self.expired += run.expired;
+        self.last_run_at = run.last_run_at;
+    }
+}
+
+pub struct PasswordExpiryCampaign {
+    store: Arc<dyn PasswordExpiryStore>,
+    email: Arc<dyn EmailProvider>,
+    credentials: CredentialService,
+    /// Descending
+    reminder_days: Vec<u32>,
+    template: ReminderTemplate,
+    batch_size: usize,
+    totals: Mutex<CampaignStats>,
+}
+
+impl PasswordExpiryCampaign {
+    pub fn new(
+        store: Arc<dyn PasswordExpiryStore>,
+        email: Arc<dyn EmailProvider>,
+        credentials: CredentialService,
+        reminder_days: &[u32],
+        template: ReminderTemplate,
+    ) -> Self {
+        let mut reminder_days = reminder_days.to_vec();
+        reminder_days.sort_unstable_by(|a, b| b.cmp(a));
+        reminder_days.dedup();
+        Self {
+            store,
+            email,
+            credentials,
+            reminder_days,
+            template,
+            batch_size: 500,
+            totals: Mutex::new(CampaignStats::default()),
+        }
+    }
+
+    /// Totals since startup
+    pub fn stats(&self) -> CampaignStats {
+        self.totals.lock().clone()
+    }
+
+    /// Send the reminders that are due. A user gets the most urgent
+    /// threshold they have reached, so one who was missed at 14 days still
+    /// hears at 7.
+    pub async fn run_once(&self) -> Result<CampaignStats, AuthError> {
+        let now = Utc::now();
+        let mut run = CampaignStats {
+            runs: 1,
+            last_run_at: Some(now),
+            ..Default::default()
+        };
+        let (Some(max_age), Some(&earliest)) = (
+            self.credentials.get_policy().max_age_days,
+            self.reminder_days.first(),
+        ) else {
+            return Ok(run);
+        };
+        // Passwords set before this are within `earliest` days of expiry
+        let set_before = now - chrono::Duration::days(max_age as i64 - earliest as i64);
+
+        let mut after = None;
+        loop {
+            let batch = self
+                .store
+                .aging_passwords(set_before, after, self.batch_size)
+                .await?;
+            let Some(last) = batch.last() else {
+                break;
+            };
+            after = Some(last.user_id);
+            let full = batch.len() == self.batch_size;
+
+            for password in batch {
+                run.scanned += 1;
+                let Some(expires_at) = self
+                    .credentials
+                    .password_expires_at(password.password_set_at)
+                else {
+                    continue;
+                };
+                if expires_at <= now {
+                    run.expired += 1;
+                    continue;
+                }
+                let days_left = (expires_at - now).num_hours().div_euclid(24) + 1;
+                let Some(&threshold) = self
+                    .reminder_days
+                    .iter()
+                    .rev()
+                    .find(|&&days| days_left <= days as i64)
+                else {
+                    continue;
+                };
+                if !self
+                    .store
+                    .claim_reminder(password.user_id, password.password_set_at, threshold)
+                    .await?
+                {
+                    continue;
+                }
+
+                let (subject, body) = self.template.render(days_left, expires_at);
+                match self
+                    .email
+                    .send_email(&password.email, &subject, &body)
+                    .await
+                {
+                    Ok(_) => {
+                        *run.sent.entry(threshold).or_default() += 1;
+                        metrics::counter!("auth_password_expiry_reminders_total", 1, "days" => threshold.to_string());
+                    }
+                    Err(e) => {
+                        warn!(
+                            user_id = %password.user_id,
+                            tenant_id = %password.tenant_id,
+                            "Failed to send password expiry reminder: {}",
+                            e
+                        );
+                        run.failed += 1;
+                        // Retried on the next pass
+                        self.store
+                            .release_reminder(password.user_id, password.password_set_at, threshold)
+                            .await?;
+                    }
+                }
+            }
+            if !full {
+                break;
+            }
+        }
+
+        self.totals.lock().add(&run);
+        Ok(run)
+    }
+
+    pub async fn run(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self.run_once().await {
+                Ok(run) => info!(
+                    scanned = run.scanned,
+                    sent = run.sent.values().sum::<u64>(),
+                    failed = run.failed,
+                    expired = run.expired,
+                    "Password expiry reminder pass complete"
+                ),
+                Err(e) => warn!("Password expiry reminder pass failed: {}", e),
+            }
+        }
+    }
+}
+
+/// Refuses tokens to logins with an expired password and hands out the
+/// tickets for changing it
+pub struct ExpiredPasswordGate {
+    credentials: CredentialService,
+    nonces: Arc<NonceStore>,
+    ticket_ttl: Duration,
+}
+
+impl ExpiredPasswordGate {
+    pub fn new(
+        credentials: CredentialService,
+        nonces: Arc<NonceStore>,
+        ticket_ttl: Duration,
+    ) -> Self {
+        Self {
+            credentials,
+            nonces,
+            ticket_ttl,
+        }
+    }
+
+    pub fn is_expired(&self, user: &User) -> bool {
+        self.credentials
+            .is_password_change_required(Some(password_set_at(user)))
+    }
+
+    /// A ticket the user redeems with their new password
+    pub async fn issue_ticket(&self, user_id: Uuid) -> Result<String, AuthError> {
+        let ticket = Uuid::new_v4().simple().to_string();
+        self.nonces
+            .issue(
+                NonceNamespace::PasswordChange,
+                &ticket,
+                &user_id.to_string(),
+                Some(self.ticket_ttl),
+            )
+            .await?;
+        Ok(ticket)
+    }
+
+    /// The user a ticket was issued to. Each ticket is redeemed once.
+    pub async fn redeem_ticket(&self, ticket: &str) -> Result<Uuid, AuthError> {
+        let payload = self
+            .nonces
+            .consume(NonceNamespace::PasswordChange, ticket)
+            .await?;
+        Uuid::parse_str(&payload).map_err(|_| AuthError::InternalError)
+    }
+
+    /// Policy errors of a new password, if any
+    pub fn check_new_password(&self, password: &str) -> Result<(), AuthError> {
+        let result = self.credentials.validate_password(password);
+        if result.is_valid {
+            Ok(())
+        } else {
+            Err(AuthError::PasswordPolicyViolation {
+                errors: result.errors,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::otp_delivery::DeliveryError;
+    use dashmap::DashSet;
+
+    #[derive(Default)]
+    struct Store {
+        passwords: Mutex<Vec<AgingPassword>>,
+        claims: DashSet<(Uuid, DateTime<Utc>, u32)>,
+    }
+
+    #[async_trait]
+    impl PasswordExpiryStore for Store {
+        async fn aging_passwords(
+            &self,
+            set_before: DateTime<Utc>,
+            after: Option<Uuid>,
+            limit: usize,
+        ) -> Result<Vec<AgingPassword>, AuthError> {
+            let mut passwords: Vec<AgingPassword> = self
+                .passwords
+                .lock()
+                .iter()
+                .filter(|p| p.password_set_at < set_before && after.is_none_or(|a| p.user_id > a))
+                .cloned()
+                .collect();
+            passwords.sort_by_key(|p| p.user_id);
+            passwords.truncate(limit);
+            Ok(passwords)
+        }
+
+        async fn claim_reminder(
+            &self,
+            user_id: Uuid,
+            password_set_at: DateTime<Utc>,
+            threshold_days: u32,
+        ) -> Result<bool, AuthError> {
+            Ok(self
+                .claims
+                .insert((user_id, password_set_at, threshold_days)))
+        }
+
+        async fn release_reminder(
+            &self,
+            user_id: Uuid,
+            password_set_at: DateTime<Utc>,
+            threshold_days: u32,
+        ) -> Result<(), AuthError> {
+            self.claims
+                .remove(&(user_id, password_set_at, threshold_days));
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Outbox {
+        sent: Mutex<Vec<(String, String)>>,
+        failing: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait]
+    impl EmailProvider for Outbox {
+        async fn send_email(
+            &self,
+            to: &str,
+            subject: &str,
+            _body: &str,
+        ) -> Result<String, DeliveryError> {
+            if self.failing.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(DeliveryError::EmailFailed("smtp down".to_string()));
+            }
+            self.sent.lock().push((to.to_string(), subject.to_string()));
+            Ok("queued".to_string())
+        }
+    }
+
+    fn aging(email: &str, set_days_ago: i64) -> AgingPassword {
+        AgingPassword {
+            user_id: Uuid::new_v4(),
+            tenant_id: Uuid::new_v4(),
+            email: email.to_string(),
+            password_set_at: Utc::now() - chrono::Duration::days(set_days_ago),
+        }
+    }
+
+    fn campaign(store: Arc<Store>, outbox: Arc<Outbox>) -> PasswordExpiryCampaign {
+        PasswordExpiryCampaign::new(
+            store,
+            outbox,
+            CredentialService::with_template("enterprise"),
+            &[14, 7, 1],
+            ReminderTemplate {
+                subject: "Expires in {days} days".to_string(),
+                body: "Change it before {expires_on}".to_string(),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_reminders_sent_once_per_threshold() {
+        let store = Arc::new(Store::default());
+        let outbox = Arc::new(Outbox::default());
+        // 90-day maximum age: 12, 5 and 0.5 days left, one expired, one fresh
+        store.passwords.lock().extend([
+            aging("a@example.com", 78),
+            aging("b@example.com", 85),
+            AgingPassword {
+                password_set_at: Utc::now() - chrono::Duration::hours(90 * 24 - 12),
+                ..aging("c@example.com", 0)
+            },
+            aging("d@example.com", 120),
+            aging("e@example.com", 10),
+        ]);
+        let campaign = campaign(store.clone(), outbox.clone());
+
+        let run = campaign.run_once().await.unwrap();
+        assert_eq!(run.scanned, 4);
+        assert_eq!(run.expired, 1);
+        assert_eq!(run.sent, BTreeMap::from([(14, 1), (7, 1), (1, 1)]));
+        let mut sent = outbox.sent.lock().clone();
+        sent.sort();
+        assert_eq!(
+            sent,
+            vec![
+                (
+                    "a@example.com".to_string(),
+                    "Expires in 12 days".to_string()
+                ),
+                ("b@example.com".to_string(), "Expires in 5 days".to_string()),
+                ("c@example.com".to_string(), "Expires in 1 days".to_string()),
+            ]
+        );
+
+        let again = campaign.run_once().await.unwrap();
+        assert!(again.sent.is_empty());
+        assert_eq!(campaign.stats().runs, 2);
+        assert_eq!(campaign.stats().sent.values().sum::<u64>(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_failed_reminder_is_retried() {
+        let store = Arc::new(Store::default());
+        let outbox = Arc::new(Outbox::default());
+        store.passwords.lock().push(aging("a@example.com", 80));
+        let campaign = campaign(store, outbox.clone());
+
+        outbox
+            .failing
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(campaign.run_once().await.unwrap().failed, 1);
+        outbox
+            .failing
+            .store(false, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(
+            campaign.run_once().await.unwrap().sent,
+            BTreeMap::from([(14, 1)])
+        );
+    }
+}
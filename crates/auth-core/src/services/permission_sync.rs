@@ -0,0 +1,723 @@
+//! Permission sync for downstream services
+//!
+//! Services that enforce permissions locally keep a replica of a tenant's
+//! roles and role assignments. They load it once from a [`PermissionSnapshot`]
+//! and then follow the tenant's change feed from the snapshot's cursor.
+//!
+//! Every role or assignment change appends an entry to a per-tenant
+//! [`PermissionChangeLog`] whose cursor only grows. A page of the feed
+//! resolves each entry to the subject's current state, so applying a change
+//! twice, or one already reflected in the snapshot, is harmless. A replica
+//! compares its own [`checksum`] with the one on a caught-up page to detect
+//! divergence, and starts over from a snapshot when they differ or when the
+//! feed answers `resync_required`.
+
+use crate::error::AuthError;
+use crate::events::{DomainEvent, EventBus};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// Largest page of the change feed
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// What a change-log entry is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PermissionSubject {
+    /// A role's name, parent or permission set
+    Role(Uuid),
+    /// The roles held by one user
+    UserRoles(Uuid),
+}
+
+impl PermissionSubject {
+    /// The subject a domain event records, for role and assignment events
+    pub fn of(event: &DomainEvent) -> Option<(Uuid, Self)> {
+        match event {
+            DomainEvent::RoleChanged { tenant_id, role_id } => {
+                Some((*tenant_id, Self::Role(*role_id)))
+            }
+            DomainEvent::UserRolesChanged { tenant_id, user_id } => {
+                Some((*tenant_id, Self::UserRoles(*user_id)))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEntry {
+    pub cursor: u64,
+    pub subject: PermissionSubject,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Per-tenant log of permission changes. Cursors start at 1 and an entry
+/// becomes readable only after every entry with a lower cursor, so a reader
+/// never skips one.
+#[async_trait]
+pub trait PermissionChangeLog: Send + Sync {
+    /// Record a change and return its cursor
+    async fn append(&self, tenant_id: Uuid, subject: PermissionSubject) -> Result<u64, AuthError>;
+    /// Entries after `after` in cursor order, at most `limit`. Entries may
+    /// have been pruned, leaving a gap at the start.
+    async fn read(
+        &self,
+        tenant_id: Uuid,
+        after: u64,
+        limit: usize,
+    ) -> Result<Vec<ChangeEntry>, AuthError>;
+    /// Cursor of the latest entry; 0 for a tenant without changes
+    async fn head(&self, tenant_id: Uuid) -> Result<u64, AuthError>;
+}
+
+/// A role as replicated: what it is called and what it grants
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct RoleGrant {
+    pub role_id: Uuid,
+    pub name: String,
+    /// Roles inherit the permissions of their parent
+    pub parent_role_id: Option<Uuid>,
+    /// Permission codes, sorted
+    pub permissions: Vec<String>,
+}
+
+/// A role held by a user. Assignments past `expires_at` are still listed;
+/// replicas compare the expiry with their own clock.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct Assignment {
+    pub user_id: Uuid,
+    pub role_id: Uuid,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Current roles and assignments of a tenant
+#[async_trait]
+pub trait PermissionStateSource: Send + Sync {
+    async fn roles(&self, tenant_id: Uuid) -> Result<Vec<RoleGrant>, AuthError>;
+    async fn role(&self, tenant_id: Uuid, role_id: Uuid) -> Result<Option<RoleGrant>, AuthError>;
+    /// Assignments that were not revoked
+    async fn assignments(&self, tenant_id: Uuid) -> Result<Vec<Assignment>, AuthError>;
+    async fn user_assignments(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<Assignment>, AuthError>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct PermissionSnapshot {
+    pub tenant_id: Uuid,
+    /// Follow the change feed from here
+    pub cursor: u64,
+    pub roles: Vec<RoleGrant>,
+    pub assignments: Vec<Assignment>,
+    /// [`checksum`] of `roles` and `assignments`
+    pub checksum: String,
+}
+
+/// A change as delivered, carrying the subject's state when the page was read
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct PermissionChange {
+    pub cursor: u64,
+    pub recorded_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: PermissionChangeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PermissionChangeKind {
+    /// Replace the role
+    RoleUpdated { role: RoleGrant },
+    /// Drop the role and its assignments
+    RoleDeleted { role_id: Uuid },
+    /// Replace every assignment of the user
+    UserRolesUpdated {
+        user_id: Uuid,
+        assignments: Vec<Assignment>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct PermissionChangePage {
+    pub changes: Vec<PermissionChange>,
+    /// Cursor to ask for next; the request's cursor when nothing changed
+    pub next_cursor: u64,
+    /// Whether the page reaches the latest change
+    pub caught_up: bool,
+    /// Checksum of the tenant's state as of `next_cursor`, on caught-up
+    /// pages when asked for. Left out when the state moved while it was
+    /// computed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// The cursor can no longer be followed, because changes after it were
+    /// pruned or it is ahead of the log. Load a new snapshot.
+    pub resync_required: bool,
+}
+
+/// What a page of the change feed is asked for
+#[derive(Debug, Clone, Copy)]
+pub struct ChangeQuery {
+    pub after: u64,
+    pub limit: usize,
+    /// How long to hold an empty page open for a change to arrive
+    pub wait: Duration,
+    pub with_checksum: bool,
+}
+
+/// SHA-256 over the canonical form of a tenant's state, hex encoded.
+///
+/// Roles are sorted by id and written as
+/// `role|<id>|<name>|<parent id or ->|<permissions joined by ,>` per line;
+/// assignments, sorted by user then role, as
+/// `assignment|<user id>|<role id>|<expiry or ->`. Ids are lowercase
+/// hyphenated, permissions sorted, and expiries RFC 3339 in UTC to the
+/// second (`2026-03-01T00:00:00Z`).
+pub fn checksum(roles: &[RoleGrant], assignments: &[Assignment]) -> String {
+    let mut roles: Vec<&RoleGrant> = roles.iter().collect();
+    roles.sort_by_key(|r| r.role_id);
+    let mut assignments: Vec<&Assignment> = assignments.iter().collect();
+    assignments.sort_by_key(|a| (a.user_id, a.role_id));
+
+    let mut hasher = Sha256::new();
+    for role in roles {
+        let mut permissions = role.permissions.clone();
+        permissions.sort();
+        hasher.update(format!(
+            "role|{}|{}|{}|{}\n",
+            role.role_id,
+            role.name,
+            role.parent_role_id
+                .map_or_else(|| "-".to_string(), |id| id.to_string()),
+            permissions.join(",")
+        ));
+    }
+    for assignment in assignments {
+        hasher.update(format!(
+            "assignment|{}|{}|{}\n",
+            assignment.user_id,
+            assignment.role_id,
+            assignment.expires_at.map_or_else(
+                || "-".to_string(),
+                |at| at.to_rfc3339_opts(SecondsFormat::Secs, true)
+            )
+        ));
+    }
+    hex::encode(hasher.finalize())
+}
+
+pub struct PermissionSyncService {
+    log: Arc<dyn PermissionChangeLog>,
+    state: Arc<dyn PermissionStateSource>,
+    events: Option<Arc<EventBus>>,
+}
+
+impl PermissionSyncService {
+    pub fn new(log: Arc<dyn PermissionChangeLog>, state: Arc<dyn PermissionStateSource>) -> Self {
+        Self {
+            log,
+            state,
+            events: None,
+        }
+    }
+
+    /// Wake long polls on role and assignment events from `bus`, including
+    /// those relayed from other nodes. Without a bus, long polls re-read
+    /// the log once a second.
+    pub fn with_events(mut self, bus: Arc<EventBus>) -> Self {
+        self.events = Some(bus);
+        self
+    }
+
+    pub async fn snapshot(&self, tenant_id: Uuid) -> Result<PermissionSnapshot, AuthError> {
+        // The head is read first: changes landing while the state is read
+        // are then replayed from the feed, which is harmless
+        let cursor = self.log.head(tenant_id).await?;
+        let mut roles = self.state.roles(tenant_id).await?;
+        let mut assignments = self.state.assignments(tenant_id).await?;
+        normalize(&mut roles, &mut assignments);
+        let checksum = checksum(&roles, &assignments);
+        Ok(PermissionSnapshot {
+            tenant_id,
+            cursor,
+            roles,
+            assignments,
+            checksum,
+        })
+    }
+
+    pub async fn changes(
+        &self,
+        tenant_id: Uuid,
+        query: ChangeQuery,
+    ) -> Result<PermissionChangePage, AuthError> {
+        let limit = query.limit.clamp(1, MAX_PAGE_SIZE);
+        let deadline = Instant::now() + query.wait;
+        let mut events = self.events.as_ref().map(|bus| bus.subscribe());
+
+        let (entries, head) = loop {
+            // Subscribed before reading, so a change between the read and
+            // the wait still wakes us. The head is read first: every entry
+            // up to it is then either in the page or pruned.
+            let head = self.log.head(tenant_id).await?;
+            let entries = self.log.read(tenant_id, query.after, limit).await?;
+            if !entries.is_empty() || head < query.after || Instant::now() >= deadline {
+                break (entries, head);
+            }
+            match &mut events {
+                Some(events) => loop {
+                    match tokio::time::timeout_at(deadline, events.recv()).await {
+                        Ok(Ok(event)) => {
+                            if PermissionSubject::of(&event).is_some_and(|(t, _)| t == tenant_id) {
+                                break;
+                            }
+                        }
+                        Ok(Err(RecvError::Lagged(_))) => break,
+                        Ok(Err(RecvError::Closed)) | Err(_) => {
+                            tokio::time::sleep_until(deadline).await;
+                            break;
+                        }
+                    }
+                },
+                None => {
+                    tokio::time::sleep_until(deadline.min(Instant::now() + Duration::from_secs(1)))
+                        .await
+                }
+            }
+        };
+
+        let pruned = match entries.first() {
+            Some(first) => first.cursor > query.after + 1,
+            None => head > query.after,
+        };
+        if head < query.after || pruned {
+            return Ok(PermissionChangePage {
+                changes: Vec::new(),
+                next_cursor: query.after,
+                caught_up: false,
+                checksum: None,
+                resync_required: true,
+            });
+        }
+
+        let next_cursor = entries.last().map_or(query.after, |e| e.cursor);
+        let mut resolved: HashMap<PermissionSubject, PermissionChangeKind> = HashMap::new();
+        let mut changes = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let kind = match resolved.get(&entry.subject) {
+                Some(kind) => kind.clone(),
+                None => {
+                    let kind = self.resolve(tenant_id, entry.subject).await?;
+                    resolved.insert(entry.subject, kind.clone());
+                    kind
+                }
+            };
+            changes.push(PermissionChange {
+                cursor: entry.cursor,
+                recorded_at: entry.recorded_at,
+                kind,
+            });
+        }
+
+        let caught_up = next_cursor >= head;
+        let checksum = if caught_up && query.with_checksum {
+            self.checksum_at(tenant_id, next_cursor).await?
+        } else {
+            None
+        };
+        Ok(PermissionChangePage {
+            changes,
+            next_cursor,
+            caught_up,
+            checksum,
+            resync_required: false,
+        })
+    }
+
+    async fn resolve(
+        &self,
+        tenant_id: Uuid,
+        subject: PermissionSubject,
+    ) -> Result<PermissionChangeKind, AuthError> {
+        Ok(match subject {
+            PermissionSubject::Role(role_id) => match self.state.role(tenant_id, role_id).await? {
+                Some(mut role) => {
+                    role.permissions.sort();
+                    PermissionChangeKind::RoleUpdated { role }
+                }
+                None => PermissionChangeKind::RoleDeleted { role_id },
+            },
+            PermissionSubject::UserRoles(user_id) => {
+                let mut assignments = self.state.user_assignments(tenant_id, user_id).await?;
+                assignments.sort_by_key(|a| a.role_id);
+                PermissionChangeKind::UserRolesUpdated {
+                    user_id,
+                    assignments,
+                }
+            }
+        })
+    }
+
+    /// Checksum of the state if nothing was recorded after `cursor` while
+    /// it was read
+    async fn checksum_at(&self, tenant_id: Uuid, cursor: u64) -> Result<Option<String>, AuthError> {
+        let roles = self.state.roles(tenant_id).await?;
+        let assignments = self.state.assignments(tenant_id).await?;
+        if self.log.head(tenant_id).await? != cursor {
+            return Ok(None);
+        }
+        Ok(Some(checksum(&roles, &assignments)))
+    }
+}
+
+/// Sort into the order snapshots are served in
+fn normalize(roles: &mut [RoleGrant], assignments: &mut [Assignment]) {
+    for role in roles.iter_mut() {
+        role.permissions.sort();
+    }
+    roles.sort_by_key(|r| r.role_id);
+    assignments.sort_by_key(|a| (a.user_id, a.role_id));
+}
+
+/// Change log for a single node and for tests
+#[derive(Default)]
+pub struct InMemoryPermissionChangeLog {
+    tenants: DashMap<Uuid, TenantLog>,
+}
+
+#[derive(Default)]
+struct TenantLog {
+    head: u64,
+    entries: Vec<ChangeEntry>,
+}
+
+impl InMemoryPermissionChangeLog {
+    /// Drop the tenant's entries up to and including `cursor`
+    pub fn prune(&self, tenant_id: Uuid, cursor: u64) {
+        if let Some(mut log) = self.tenants.get_mut(&tenant_id) {
+            log.entries.retain(|e| e.cursor > cursor);
+        }
+    }
+}
+
+#[async_trait]
+impl PermissionChangeLog for InMemoryPermissionChangeLog {
+    async fn append(&self, tenant_id: Uuid, subject: PermissionSubject) -> Result<u64, AuthError> {
+        let mut log = self.tenants.entry(tenant_id).or_default();
+        log.head += 1;
+        let cursor = log.head;
+        log.entries.push(ChangeEntry {
+            cursor,
+            subject,
+            recorded_at: Utc::now(),
+        });
+        Ok(cursor)
+    }
+
+    async fn read(
+        &self,
+        tenant_id: Uuid,
+        after: u64,
+        limit: usize,
+    ) -> Result<Vec<ChangeEntry>, AuthError> {
+        Ok(self.tenants.get(&tenant_id).map_or_else(Vec::new, |log| {
+            log.entries
+                .iter()
+                .filter(|e| e.cursor > after)
+                .take(limit)
+                .cloned()
+                .collect()
+        }))
+    }
+
+    async fn head(&self, tenant_id: Uuid) -> Result<u64, AuthError> {
+        Ok(self.tenants.get(&tenant_id).map_or(0, |log| log.head))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    #[derive(Default)]
+    struct State {
+        roles: Mutex<Vec<RoleGrant>>,
+        assignments: Mutex<Vec<Assignment>>,
+    }
+
+    #[async_trait]
+    impl PermissionStateSource for State {
+        async fn roles(&self, _tenant_id: Uuid) -> Result<Vec<RoleGrant>, AuthError> {
+            Ok(self.roles.lock().clone())
+        }
+
+        async fn role(
+            &self,
+            _tenant_id: Uuid,
+            role_id: Uuid,
+        ) -> Result<Option<RoleGrant>, AuthError> {
+            Ok(self
+                .roles
+                .lock()
+                .iter()
+                .find(|r| r.role_id == role_id)
+                .cloned())
+        }
+
+        async fn assignments(&self, _tenant_id: Uuid) -> Result<Vec<Assignment>, AuthError> {
+            Ok(self.assignments.lock().clone())
+        }
+
+        async fn user_assignments(
+            &self,
+            _tenant_id: Uuid,
+            user_id: Uuid,
+        ) -> Result<Vec<Assignment>, AuthError> {
+            Ok(self
+                .assignments
+                .lock()
+                .iter()
+                .filter(|a| a.user_id == user_id)
+                .cloned()
+                .collect())
+        }
+    }
+
+    fn role(name: &str, permissions: &[&str]) -> RoleGrant {
+        RoleGrant {
+            role_id: Uuid::new_v4(),
+            name: name.to_string(),
+            parent_role_id: None,
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    fn query(after: u64) -> ChangeQuery {
+        ChangeQuery {
+            after,
+            limit: 100,
+            wait: Duration::ZERO,
+            with_checksum: true,
+        }
+    }
+
+    /// A replica built from a snapshot and the feed
+    #[derive(Default)]
+    struct Replica {
+        roles: HashMap<Uuid, RoleGrant>,
+        assignments: Vec<Assignment>,
+    }
+
+    impl Replica {
+        fn apply(&mut self, change: &PermissionChange) {
+            match &change.kind {
+                PermissionChangeKind::RoleUpdated { role } => {
+                    self.roles.insert(role.role_id, role.clone());
+                }
+                PermissionChangeKind::RoleDeleted { role_id } => {
+                    self.roles.remove(role_id);
+                    self.assignments.retain(|a| a.role_id != *role_id);
+                }
+                PermissionChangeKind::UserRolesUpdated {
+                    user_id,
+                    assignments,
+                } => {
+                    self.assignments.retain(|a| a.user_id != *user_id);
+                    self.assignments.extend(assignments.iter().cloned());
+                }
+            }
+        }
+
+        fn checksum(&self) -> String {
+            let roles: Vec<RoleGrant> = self.roles.values().cloned().collect();
+            checksum(&roles, &self.assignments)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replica_follows_feed_and_matches_checksum() {
+        let tenant = Uuid::new_v4();
+        let log = Arc::new(InMemoryPermissionChangeLog::default());
+        let state = Arc::new(State::default());
+        let sync = PermissionSyncService::new(log.clone(), state.clone());
+
+        let admin = role("admin", &["user:write", "user:read"]);
+        state.roles.lock().push(admin.clone());
+        log.append(tenant, PermissionSubject::Role(admin.role_id))
+            .await
+            .unwrap();
+
+        let snapshot = sync.snapshot(tenant).await.unwrap();
+        assert_eq!(snapshot.cursor, 1);
+        assert_eq!(
+            snapshot.roles[0].permissions,
+            vec!["user:read", "user:write"]
+        );
+        let mut replica = Replica::default();
+        for role in snapshot.roles {
+            replica.roles.insert(role.role_id, role);
+        }
+
+        let viewer = role("viewer", &["user:read"]);
+        let user = Uuid::new_v4();
+        state.roles.lock().push(viewer.clone());
+        log.append(tenant, PermissionSubject::Role(viewer.role_id))
+            .await
+            .unwrap();
+        state.assignments.lock().push(Assignment {
+            user_id: user,
+            role_id: viewer.role_id,
+            expires_at: None,
+        });
+        log.append(tenant, PermissionSubject::UserRoles(user))
+            .await
+            .unwrap();
+        state.roles.lock().retain(|r| r.role_id != admin.role_id);
+        log.append(tenant, PermissionSubject::Role(admin.role_id))
+            .await
+            .unwrap();
+
+        let page = sync
+            .changes(
+                tenant,
+                ChangeQuery {
+                    limit: 2,
+                    ..query(snapshot.cursor)
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.changes.len(), 2);
+        assert!(!page.caught_up);
+        assert!(page.checksum.is_none());
+        page.changes.iter().for_each(|c| replica.apply(c));
+
+        let page = sync.changes(tenant, query(page.next_cursor)).await.unwrap();
+        assert_eq!(page.next_cursor, 4);
+        assert!(page.caught_up);
+        assert_eq!(
+            page.changes[0].kind,
+            PermissionChangeKind::RoleDeleted {
+                role_id: admin.role_id
+            }
+        );
+        page.changes.iter().for_each(|c| replica.apply(c));
+        assert_eq!(Some(replica.checksum()), page.checksum);
+
+        // Nothing new: the cursor stays and the checksum still matches
+        let idle = sync.changes(tenant, query(4)).await.unwrap();
+        assert!(idle.changes.is_empty());
+        assert_eq!(idle.next_cursor, 4);
+        assert_eq!(idle.checksum, page.checksum);
+
+        // A replica that missed a change disagrees
+        replica.assignments.clear();
+        assert_ne!(Some(replica.checksum()), idle.checksum);
+    }
+
+    #[tokio::test]
+    async fn test_pruned_or_foreign_cursor_requires_resync() {
+        let tenant = Uuid::new_v4();
+        let log = Arc::new(InMemoryPermissionChangeLog::default());
+        let sync = PermissionSyncService::new(log.clone(), Arc::new(State::default()));
+        for _ in 0..3 {
+            log.append(tenant, PermissionSubject::Role(Uuid::new_v4()))
+                .await
+                .unwrap();
+        }
+
+        assert!(
+            !sync
+                .changes(tenant, query(0))
+                .await
+                .unwrap()
+                .resync_required
+        );
+        log.prune(tenant, 2);
+        assert!(
+            sync.changes(tenant, query(1))
+                .await
+                .unwrap()
+                .resync_required
+        );
+        assert!(
+            !sync
+                .changes(tenant, query(2))
+                .await
+                .unwrap()
+                .resync_required
+        );
+        log.prune(tenant, 3);
+        assert!(
+            sync.changes(tenant, query(2))
+                .await
+                .unwrap()
+                .resync_required
+        );
+        assert!(
+            !sync
+                .changes(tenant, query(3))
+                .await
+                .unwrap()
+                .resync_required
+        );
+        assert!(
+            sync.changes(tenant, query(7))
+                .await
+                .unwrap()
+                .resync_required
+        );
+    }
+
+    #[tokio::test]
+    async fn test_long_poll_wakes_on_change() {
+        let tenant = Uuid::new_v4();
+        let log = Arc::new(InMemoryPermissionChangeLog::default());
+        let bus = Arc::new(EventBus::new());
+        let sync = Arc::new(
+            PermissionSyncService::new(log.clone(), Arc::new(State::default()))
+                .with_events(bus.clone()),
+        );
+
+        let poll = tokio::spawn({
+            let sync = sync.clone();
+            async move {
+                sync.changes(
+                    tenant,
+                    ChangeQuery {
+                        wait: Duration::from_secs(30),
+                        ..query(0)
+                    },
+                )
+                .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let role_id = Uuid::new_v4();
+        log.append(tenant, PermissionSubject::Role(role_id))
+            .await
+            .unwrap();
+        bus.publish(DomainEvent::RoleChanged {
+            tenant_id: tenant,
+            role_id,
+        })
+        .await;
+
+        let page = tokio::time::timeout(Duration::from_secs(5), poll)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(page.next_cursor, 1);
+    }
+}
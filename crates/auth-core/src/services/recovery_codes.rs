@@ -0,0 +1,303 @@
+//! MFA recovery codes
+//!
+//! Ten single-use codes, shown once when generated and stored only as
+//! Argon2 hashes in `users.backup_codes`. A code is removed from the list
+//! when it is used; regenerating replaces the whole list, so codes from an
+//! earlier set stop working.
+
+use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use crate::error::AuthError;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use async_trait::async_trait;
+use dashmap::DashMap;
+use rand::Rng;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Warn the user to regenerate once this few codes are left
+pub const LOW_RECOVERY_CODES: usize = 3;
+
+/// No 0/O, 1/I/L so codes survive being read off paper
+const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// How often a consumption is retried when another request changed the
+/// list in between
+const MAX_SWAP_ATTEMPTS: usize = 3;
+
+#[async_trait]
+pub trait RecoveryCodeStore: Send + Sync {
+    /// Hashes of the user's unused codes
+    async fn recovery_codes(&self, user_id: Uuid) -> Result<Vec<String>, AuthError>;
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        hashes: &[String],
+    ) -> Result<(), AuthError>;
+    /// Store `hashes` only if the stored list still equals `expected`.
+    /// Returns false if it changed, so two requests cannot spend one code.
+    async fn swap_recovery_codes(
+        &self,
+        user_id: Uuid,
+        expected: &[String],
+        hashes: &[String],
+    ) -> Result<bool, AuthError>;
+}
+
+/// Outcome of a successful recovery code login
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryCodeUse {
+    pub remaining: usize,
+}
+
+impl RecoveryCodeUse {
+    pub fn is_low(&self) -> bool {
+        self.remaining <= LOW_RECOVERY_CODES
+    }
+}
+
+pub struct RecoveryCodeService {
+    store: Arc<dyn RecoveryCodeStore>,
+    audit: Option<Arc<dyn AuditLogger>>,
+}
+
+impl RecoveryCodeService {
+    pub fn new(store: Arc<dyn RecoveryCodeStore>) -> Self {
+        Self { store, audit: None }
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Issue a fresh set, invalidating any earlier one. The plaintext codes
+    /// are returned once and cannot be retrieved again.
+    pub async fn generate(&self, user_id: Uuid, tenant_id: Uuid) -> Result<Vec<String>, AuthError> {
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| new_code()).collect();
+        let to_hash: Vec<String> = codes.iter().map(|c| normalize(c)).collect();
+        let hashes = tokio::task::spawn_blocking(move || {
+            to_hash
+                .iter()
+                .map(|code| {
+                    let salt = SaltString::generate(&mut OsRng);
+                    Argon2::default()
+                        .hash_password(code.as_bytes(), &salt)
+                        .map(|h| h.to_string())
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .await
+        .map_err(|_| AuthError::InternalError)?
+        .map_err(|e| AuthError::UTCryptoError(e.to_string()))?;
+
+        let had_codes = !self.store.recovery_codes(user_id).await?.is_empty();
+        self.store.replace_recovery_codes(user_id, &hashes).await?;
+
+        let action = if had_codes {
+            "mfa.recovery_codes.regenerated"
+        } else {
+            "mfa.recovery_codes.generated"
+        };
+        self.log(
+            AuditEvent::new(AuditCategory::Security, action, AuditSeverity::Info)
+                .with_actor(user_id)
+                .with_context(None, None, Some(tenant_id)),
+        )
+        .await;
+
+        Ok(codes)
+    }
+
+    pub async fn remaining(&self, user_id: Uuid) -> Result<usize, AuthError> {
+        Ok(self.store.recovery_codes(user_id).await?.len())
+    }
+
+    /// Spend a code as the second factor. Fails with `InvalidOtp` if it does
+    /// not match an unused code.
+    pub async fn verify(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        code: &str,
+    ) -> Result<RecoveryCodeUse, AuthError> {
+        let code = normalize(code);
+        for _ in 0..MAX_SWAP_ATTEMPTS {
+            let hashes = self.store.recovery_codes(user_id).await?;
+            let Some(index) = find_match(&hashes, &code).await? else {
+                self.log(
+                    AuditEvent::new(
+                        AuditCategory::Authentication,
+                        "mfa.recovery_code.failed",
+                        AuditSeverity::Warning,
+                    )
+                    .with_actor(user_id)
+                    .with_context(None, None, Some(tenant_id)),
+                )
+                .await;
+                return Err(AuthError::InvalidOtp);
+            };
+
+            let mut remaining = hashes.clone();
+            remaining.remove(index);
+            if !self
+                .store
+                .swap_recovery_codes(user_id, &hashes, &remaining)
+                .await?
+            {
+                // Another request changed the list; if it spent this code the
+                // next pass will not find it
+                continue;
+            }
+
+            let outcome = RecoveryCodeUse {
+                remaining: remaining.len(),
+            };
+            self.log(
+                AuditEvent::new(
+                    AuditCategory::Authentication,
+                    "mfa.recovery_code.used",
+                    if outcome.is_low() {
+                        AuditSeverity::Warning
+                    } else {
+                        AuditSeverity::Info
+                    },
+                )
+                .with_actor(user_id)
+                .with_context(None, None, Some(tenant_id))
+                .with_metadata(serde_json::json!({ "remaining": outcome.remaining })),
+            )
+            .await;
+            return Ok(outcome);
+        }
+
+        Err(AuthError::Conflict {
+            message: "recovery codes changed concurrently; try again".to_string(),
+        })
+    }
+
+    async fn log(&self, event: AuditEvent) {
+        if let Some(audit) = &self.audit {
+            audit.log(event).await;
+        }
+    }
+}
+
+/// `xxxxx-xxxxx`
+fn new_code() -> String {
+    let mut rng = rand::thread_rng();
+    let mut code: String = (0..10)
+        .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+        .collect();
+    code.insert(5, '-');
+    code
+}
+
+/// Users type codes with or without the dash, in any case
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Index of the hash `code` matches, checked on a blocking thread
+async fn find_match(hashes: &[String], code: &str) -> Result<Option<usize>, AuthError> {
+    let hashes = hashes.to_vec();
+    let code = code.to_string();
+    tokio::task::spawn_blocking(move || {
+        hashes.iter().position(|hash| {
+            PasswordHash::new(hash)
+                .map(|parsed| {
+                    Argon2::default()
+                        .verify_password(code.as_bytes(), &parsed)
+                        .is_ok()
+                })
+                .unwrap_or(false)
+        })
+    })
+    .await
+    .map_err(|_| AuthError::InternalError)
+}
+
+#[derive(Default)]
+pub struct InMemoryRecoveryCodeStore {
+    codes: DashMap<Uuid, Vec<String>>,
+}
+
+#[async_trait]
+impl RecoveryCodeStore for InMemoryRecoveryCodeStore {
+    async fn recovery_codes(&self, user_id: Uuid) -> Result<Vec<String>, AuthError> {
+        Ok(self
+            .codes
+            .get(&user_id)
+            .map(|c| c.clone())
+            .unwrap_or_default())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        hashes: &[String],
+    ) -> Result<(), AuthError> {
+        self.codes.insert(user_id, hashes.to_vec());
+        Ok(())
+    }
+
+    async fn swap_recovery_codes(
+        &self,
+        user_id: Uuid,
+        expected: &[String],
+        hashes: &[String],
+    ) -> Result<bool, AuthError> {
+        let mut entry = self.codes.entry(user_id).or_default();
+        if entry.as_slice() != expected {
+            return Ok(false);
+        }
+        *entry = hashes.to_vec();
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_codes_are_single_use() {
+        let service = RecoveryCodeService::new(Arc::new(InMemoryRecoveryCodeStore::default()));
+        let (user, tenant) = (Uuid::new_v4(), Uuid::new_v4());
+        let codes = service.generate(user, tenant).await.unwrap();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+
+        // Dash and case are ignored
+        let typed = codes[0].replace('-', "").to_uppercase();
+        let used = service.verify(user, tenant, &typed).await.unwrap();
+        assert_eq!(used.remaining, RECOVERY_CODE_COUNT - 1);
+        assert!(!used.is_low());
+
+        assert!(matches!(
+            service.verify(user, tenant, &codes[0]).await,
+            Err(AuthError::InvalidOtp)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_regeneration_invalidates_old_codes() {
+        let service = RecoveryCodeService::new(Arc::new(InMemoryRecoveryCodeStore::default()));
+        let (user, tenant) = (Uuid::new_v4(), Uuid::new_v4());
+        let old = service.generate(user, tenant).await.unwrap();
+        let new = service.generate(user, tenant).await.unwrap();
+
+        assert!(service.verify(user, tenant, &old[1]).await.is_err());
+        assert!(service.verify(user, tenant, &new[1]).await.is_ok());
+        assert_eq!(
+            service.remaining(user).await.unwrap(),
+            RECOVERY_CODE_COUNT - 1
+        );
+    }
+}
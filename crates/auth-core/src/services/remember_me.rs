@@ -0,0 +1,682 @@
+//! "Keep me signed in" on devices the user registers
+//!
+//! Signing in with remember-me registers the device and hands it a
+//! long-lived token. The token restores a session without credentials, but
+//! only once: each redemption spends it and returns its successor, so a
+//! token copied off the device stops working as soon as either copy is
+//! used. A spent token presented again is taken as theft and revokes the
+//! device. Tokens are bound to the device fingerprint they were issued for
+//! and stored only as hashes.
+//!
+//! A device's lifetime runs from the sign-in that registered it and is not
+//! extended by redemptions; it also ends after a period without use. Access
+//! tokens restored this way are marked (see [`is_remembered`]) so that
+//! sensitive operations can require a fresh sign-in.
+
+use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use crate::error::{AuthError, TokenErrorKind};
+use crate::models::token::Claims;
+use async_trait::async_trait;
+use auth_config::RememberMeConfig;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Claim in `ext` marking an access token restored from a remember-me token
+pub const REMEMBERED_CLAIM: &str = "remembered";
+
+/// A device a user chose to stay signed in on
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct RememberedDevice {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub user_id: Uuid,
+    #[serde(skip_serializing)]
+    pub fingerprint_hash: String,
+    pub name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// One link of a device's token chain
+#[derive(Debug, Clone)]
+pub struct RememberMeToken {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub tenant_id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait RememberMeStore: Send + Sync {
+    async fn insert_device(&self, device: &RememberedDevice) -> Result<(), AuthError>;
+    async fn get_device(
+        &self,
+        tenant_id: Uuid,
+        device_id: Uuid,
+    ) -> Result<Option<RememberedDevice>, AuthError>;
+    /// The user's devices that are not revoked
+    async fn list_devices(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<RememberedDevice>, AuthError>;
+    async fn touch_device(
+        &self,
+        tenant_id: Uuid,
+        device_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError>;
+    async fn revoke_device(
+        &self,
+        tenant_id: Uuid,
+        device_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError>;
+    async fn insert_token(&self, token: &RememberMeToken) -> Result<(), AuthError>;
+    async fn find_token(&self, token_hash: &str) -> Result<Option<RememberMeToken>, AuthError>;
+    /// Mark the token used; false if it already was. Must be atomic so a
+    /// token is redeemed at most once.
+    async fn mark_token_used(
+        &self,
+        tenant_id: Uuid,
+        token_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<bool, AuthError>;
+}
+
+/// A token handed to a device, shown once
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct IssuedRememberMe {
+    pub device_id: Uuid,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Whom a redeemed token signs in, and the token that replaces it
+#[derive(Debug, Clone)]
+pub struct RememberMeGrant {
+    pub user_id: Uuid,
+    pub tenant_id: Uuid,
+    pub next: IssuedRememberMe,
+}
+
+/// Whether the token was restored from a remember-me token rather than
+/// issued by a full sign-in
+pub fn is_remembered(claims: &Claims) -> bool {
+    claims.ext.get(REMEMBERED_CLAIM) == Some(&serde_json::Value::Bool(true))
+}
+
+pub struct RememberMeService {
+    store: Arc<dyn RememberMeStore>,
+    config: RememberMeConfig,
+    audit: Option<Arc<dyn AuditLogger>>,
+}
+
+impl RememberMeService {
+    pub fn new(store: Arc<dyn RememberMeStore>, config: RememberMeConfig) -> Self {
+        Self {
+            store,
+            config,
+            audit: None,
+        }
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Whether the tenant allows remember-me, and for how long
+    fn policy(&self, tenant_id: Uuid) -> Option<Duration> {
+        let (enabled, days) = match self.config.tenants.get(&tenant_id.to_string()) {
+            Some(policy) => (
+                policy.enabled,
+                policy
+                    .max_duration_days
+                    .unwrap_or(self.config.max_duration_days),
+            ),
+            None => (self.config.enabled, self.config.max_duration_days),
+        };
+        enabled.then(|| Duration::days(days as i64))
+    }
+
+    fn not_allowed() -> AuthError {
+        AuthError::AuthMethodNotAllowed {
+            method: "remember_me".to_string(),
+        }
+    }
+
+    /// Register the device the user just signed in on. Registering a
+    /// device again replaces its earlier registration; past the device
+    /// limit the least recently used one is revoked.
+    pub async fn issue(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        fingerprint: &str,
+        name: Option<String>,
+    ) -> Result<IssuedRememberMe, AuthError> {
+        let max_duration = self.policy(tenant_id).ok_or_else(Self::not_allowed)?;
+        if fingerprint.is_empty() {
+            return Err(AuthError::ValidationError {
+                message: "Device fingerprint is required".to_string(),
+            });
+        }
+        let now = Utc::now();
+        let fingerprint_hash = digest(fingerprint);
+
+        let mut devices = self.store.list_devices(tenant_id, user_id).await?;
+        devices.sort_by_key(|d| d.last_used_at);
+        let (same, others): (Vec<_>, Vec<_>) = devices
+            .into_iter()
+            .partition(|d| d.fingerprint_hash == fingerprint_hash);
+        let keep = (self.config.max_devices_per_user as usize).saturating_sub(1);
+        let excess = others.len().saturating_sub(keep);
+        for device in same.iter().chain(&others[..excess]) {
+            self.store.revoke_device(tenant_id, device.id, now).await?;
+        }
+
+        let device = RememberedDevice {
+            id: Uuid::new_v4(),
+            tenant_id,
+            user_id,
+            fingerprint_hash,
+            name,
+            created_at: now,
+            last_used_at: now,
+            revoked_at: None,
+        };
+        self.store.insert_device(&device).await?;
+        let issued = self.next_token(&device, now + max_duration).await?;
+
+        self.record(
+            AuditEvent::new(
+                AuditCategory::Authentication,
+                "remember_me.issue",
+                AuditSeverity::Info,
+            )
+            .with_actor(user_id)
+            .with_context(None, None, Some(tenant_id))
+            .with_resource(device.id.to_string())
+            .with_metadata(json!({
+                "device_name": device.name,
+                "expires_at": issued.expires_at,
+                "replaced": same.len(),
+                "evicted": excess,
+            })),
+        )
+        .await;
+        Ok(issued)
+    }
+
+    /// Spend `token`, presented by the device with `fingerprint`, and
+    /// return whom it signs in along with its successor
+    pub async fn redeem(
+        &self,
+        token: &str,
+        fingerprint: &str,
+    ) -> Result<RememberMeGrant, AuthError> {
+        let invalid = || AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        };
+        let stored = self
+            .store
+            .find_token(&digest(token))
+            .await?
+            .ok_or_else(invalid)?;
+        let device = self
+            .store
+            .get_device(stored.tenant_id, stored.device_id)
+            .await?
+            .filter(|d| d.revoked_at.is_none())
+            .ok_or(AuthError::TokenError {
+                kind: TokenErrorKind::Revoked,
+            })?;
+
+        let now = Utc::now();
+        if stored.used_at.is_some() {
+            return Err(self
+                .revoke_on(&device, "replayed", TokenErrorKind::Replayed)
+                .await);
+        }
+        if device.fingerprint_hash != digest(fingerprint) {
+            return Err(self
+                .revoke_on(&device, "fingerprint mismatch", TokenErrorKind::Invalid)
+                .await);
+        }
+        let Some(max_duration) = self.policy(device.tenant_id) else {
+            return Err(Self::not_allowed());
+        };
+        // A shorter duration set since issuance applies to existing devices
+        let expires_at = stored.expires_at.min(device.created_at + max_duration);
+        if expires_at <= now {
+            return Err(self
+                .revoke_on(&device, "expired", TokenErrorKind::AbsoluteExpired)
+                .await);
+        }
+        let idle = Duration::days(self.config.idle_timeout_days as i64);
+        if device.last_used_at + idle <= now {
+            return Err(self
+                .revoke_on(&device, "idle", TokenErrorKind::IdleExpired)
+                .await);
+        }
+        if !self
+            .store
+            .mark_token_used(device.tenant_id, stored.id, now)
+            .await?
+        {
+            // Lost a race with another redemption of the same token
+            return Err(self
+                .revoke_on(&device, "replayed", TokenErrorKind::Replayed)
+                .await);
+        }
+
+        let next = self.next_token(&device, expires_at).await?;
+        self.store
+            .touch_device(device.tenant_id, device.id, now)
+            .await?;
+        self.record(self.redemption_event(&device, AuditSeverity::Info))
+            .await;
+        Ok(RememberMeGrant {
+            user_id: device.user_id,
+            tenant_id: device.tenant_id,
+            next,
+        })
+    }
+
+    pub async fn list_devices(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<RememberedDevice>, AuthError> {
+        self.store.list_devices(tenant_id, user_id).await
+    }
+
+    /// Sign the user out on one of their devices
+    pub async fn revoke_device(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+        device_id: Uuid,
+    ) -> Result<(), AuthError> {
+        let device = self
+            .store
+            .get_device(tenant_id, device_id)
+            .await?
+            .filter(|d| d.user_id == user_id && d.revoked_at.is_none())
+            .ok_or(AuthError::RememberedDeviceNotFound)?;
+        self.store
+            .revoke_device(tenant_id, device.id, Utc::now())
+            .await?;
+        self.record(
+            AuditEvent::new(
+                AuditCategory::Authentication,
+                "remember_me.revoke",
+                AuditSeverity::Info,
+            )
+            .with_actor(user_id)
+            .with_context(None, None, Some(tenant_id))
+            .with_resource(device.id.to_string()),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Forget every device of the user, e.g. on a password change
+    pub async fn revoke_all(&self, tenant_id: Uuid, user_id: Uuid) -> Result<usize, AuthError> {
+        let devices = self.store.list_devices(tenant_id, user_id).await?;
+        let now = Utc::now();
+        for device in &devices {
+            self.store.revoke_device(tenant_id, device.id, now).await?;
+        }
+        Ok(devices.len())
+    }
+
+    async fn next_token(
+        &self,
+        device: &RememberedDevice,
+        expires_at: DateTime<Utc>,
+    ) -> Result<IssuedRememberMe, AuthError> {
+        // Two v4 UUIDs: 244 bits from the OS RNG
+        let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        self.store
+            .insert_token(&RememberMeToken {
+                id: Uuid::new_v4(),
+                device_id: device.id,
+                tenant_id: device.tenant_id,
+                user_id: device.user_id,
+                token_hash: digest(&token),
+                created_at: Utc::now(),
+                expires_at,
+                used_at: None,
+            })
+            .await?;
+        Ok(IssuedRememberMe {
+            device_id: device.id,
+            token,
+            expires_at,
+        })
+    }
+
+    /// Revoke the device a failed redemption came for and return the error
+    /// to report
+    async fn revoke_on(
+        &self,
+        device: &RememberedDevice,
+        reason: &str,
+        kind: TokenErrorKind,
+    ) -> AuthError {
+        let suspicious = matches!(kind, TokenErrorKind::Replayed | TokenErrorKind::Invalid);
+        if suspicious {
+            warn!(device_id = %device.id, user_id = %device.user_id, reason, "Remember-me token rejected");
+        }
+        if let Err(e) = self
+            .store
+            .revoke_device(device.tenant_id, device.id, Utc::now())
+            .await
+        {
+            warn!(device_id = %device.id, error = %e, "Failed to revoke remembered device");
+        }
+        let severity = if suspicious {
+            AuditSeverity::Warning
+        } else {
+            AuditSeverity::Info
+        };
+        self.record(self.redemption_event(device, severity).failure(reason))
+            .await;
+        AuthError::TokenError { kind }
+    }
+
+    fn redemption_event(&self, device: &RememberedDevice, severity: AuditSeverity) -> AuditEvent {
+        AuditEvent::new(
+            AuditCategory::Authentication,
+            "remember_me.redeem",
+            severity,
+        )
+        .with_actor(device.user_id)
+        .with_context(None, None, Some(device.tenant_id))
+        .with_resource(device.id.to_string())
+    }
+
+    async fn record(&self, event: AuditEvent) {
+        if let Some(audit) = &self.audit {
+            audit.log(event).await;
+        }
+    }
+}
+
+fn digest(value: &str) -> String {
+    hex::encode(Sha256::digest(value.as_bytes()))
+}
+
+/// Process-local store for tests and single-node development
+#[derive(Default)]
+pub struct InMemoryRememberMeStore {
+    devices: DashMap<Uuid, RememberedDevice>,
+    tokens: DashMap<String, RememberMeToken>,
+}
+
+#[async_trait]
+impl RememberMeStore for InMemoryRememberMeStore {
+    async fn insert_device(&self, device: &RememberedDevice) -> Result<(), AuthError> {
+        self.devices.insert(device.id, device.clone());
+        Ok(())
+    }
+
+    async fn get_device(
+        &self,
+        tenant_id: Uuid,
+        device_id: Uuid,
+    ) -> Result<Option<RememberedDevice>, AuthError> {
+        Ok(self
+            .devices
+            .get(&device_id)
+            .filter(|d| d.tenant_id == tenant_id)
+            .map(|d| d.clone()))
+    }
+
+    async fn list_devices(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<RememberedDevice>, AuthError> {
+        Ok(self
+            .devices
+            .iter()
+            .filter(|d| d.tenant_id == tenant_id && d.user_id == user_id)
+            .filter(|d| d.revoked_at.is_none())
+            .map(|d| d.clone())
+            .collect())
+    }
+
+    async fn touch_device(
+        &self,
+        tenant_id: Uuid,
+        device_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError> {
+        if let Some(mut device) = self.devices.get_mut(&device_id) {
+            if device.tenant_id == tenant_id {
+                device.last_used_at = at;
+            }
+        }
+        Ok(())
+    }
+
+    async fn revoke_device(
+        &self,
+        tenant_id: Uuid,
+        device_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError> {
+        if let Some(mut device) = self.devices.get_mut(&device_id) {
+            if device.tenant_id == tenant_id && device.revoked_at.is_none() {
+                device.revoked_at = Some(at);
+            }
+        }
+        Ok(())
+    }
+
+    async fn insert_token(&self, token: &RememberMeToken) -> Result<(), AuthError> {
+        self.tokens.insert(token.token_hash.clone(), token.clone());
+        Ok(())
+    }
+
+    async fn find_token(&self, token_hash: &str) -> Result<Option<RememberMeToken>, AuthError> {
+        Ok(self.tokens.get(token_hash).map(|t| t.clone()))
+    }
+
+    async fn mark_token_used(
+        &self,
+        tenant_id: Uuid,
+        token_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<bool, AuthError> {
+        let Some(mut token) = self
+            .tokens
+            .iter_mut()
+            .find(|t| t.id == token_id && t.tenant_id == tenant_id)
+        else {
+            return Ok(false);
+        };
+        if token.used_at.is_some() {
+            return Ok(false);
+        }
+        token.used_at = Some(at);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auth_config::RememberMePolicy;
+
+    fn service(config: RememberMeConfig) -> (RememberMeService, Arc<InMemoryRememberMeStore>) {
+        let store = Arc::new(InMemoryRememberMeStore::default());
+        (RememberMeService::new(store.clone(), config), store)
+    }
+
+    fn enabled() -> RememberMeConfig {
+        RememberMeConfig {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    fn kind(result: Result<RememberMeGrant, AuthError>) -> TokenErrorKind {
+        match result {
+            Err(AuthError::TokenError { kind }) => kind,
+            other => panic!("Expected token error, got {:?}", other.map(|g| g.user_id)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tokens_rotate_and_replay_revokes_the_device() {
+        let (remember, store) = service(enabled());
+        let (user_id, tenant_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let issued = remember
+            .issue(user_id, tenant_id, "laptop", Some("Laptop".to_string()))
+            .await
+            .unwrap();
+
+        let grant = remember.redeem(&issued.token, "laptop").await.unwrap();
+        assert_eq!((grant.user_id, grant.tenant_id), (user_id, tenant_id));
+        assert_ne!(grant.next.token, issued.token);
+        // The chain keeps the lifetime of the first sign-in
+        assert_eq!(grant.next.expires_at, issued.expires_at);
+
+        assert!(matches!(
+            kind(remember.redeem(&issued.token, "laptop").await),
+            TokenErrorKind::Replayed
+        ));
+        assert!(matches!(
+            kind(remember.redeem(&grant.next.token, "laptop").await),
+            TokenErrorKind::Revoked
+        ));
+        assert!(store
+            .list_devices(tenant_id, user_id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tokens_are_bound_to_their_device() {
+        let (remember, _) = service(enabled());
+        let (user_id, tenant_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let issued = remember
+            .issue(user_id, tenant_id, "laptop", None)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            kind(remember.redeem(&issued.token, "phone").await),
+            TokenErrorKind::Invalid
+        ));
+        assert!(matches!(
+            kind(remember.redeem(&issued.token, "laptop").await),
+            TokenErrorKind::Revoked
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_device_limit_evicts_the_least_recently_used() {
+        let (remember, _) = service(RememberMeConfig {
+            max_devices_per_user: 2,
+            ..enabled()
+        });
+        let (user_id, tenant_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let first = remember
+            .issue(user_id, tenant_id, "one", None)
+            .await
+            .unwrap();
+        let second = remember
+            .issue(user_id, tenant_id, "two", None)
+            .await
+            .unwrap();
+        remember.redeem(&first.token, "one").await.unwrap();
+        remember
+            .issue(user_id, tenant_id, "three", None)
+            .await
+            .unwrap();
+
+        let devices = remember.list_devices(tenant_id, user_id).await.unwrap();
+        assert_eq!(devices.len(), 2);
+        assert!(devices.iter().all(|d| d.id != second.device_id));
+    }
+
+    #[tokio::test]
+    async fn test_tenant_policy_disables_and_shortens() {
+        let tenant_id = Uuid::new_v4();
+        let mut config = enabled();
+        config.tenants.insert(
+            tenant_id.to_string(),
+            RememberMePolicy {
+                enabled: false,
+                max_duration_days: None,
+            },
+        );
+        let (remember, store) = service(config.clone());
+        assert!(matches!(
+            remember
+                .issue(Uuid::new_v4(), tenant_id, "laptop", None)
+                .await,
+            Err(AuthError::AuthMethodNotAllowed { .. })
+        ));
+
+        // Issued under a long duration, redeemed after the tenant cut it
+        config.tenants.clear();
+        let issued = RememberMeService::new(store.clone(), config.clone())
+            .issue(Uuid::new_v4(), tenant_id, "laptop", None)
+            .await
+            .unwrap();
+        store.devices.get_mut(&issued.device_id).unwrap().created_at -= Duration::days(3);
+        config.tenants.insert(
+            tenant_id.to_string(),
+            RememberMePolicy {
+                enabled: true,
+                max_duration_days: Some(2),
+            },
+        );
+        let shortened = RememberMeService::new(store, config);
+        assert!(matches!(
+            kind(shortened.redeem(&issued.token, "laptop").await),
+            TokenErrorKind::AbsoluteExpired
+        ));
+    }
+
+    #[test]
+    fn test_remembered_claim() {
+        let mut claims = Claims {
+            sub: String::new(),
+            iss: String::new(),
+            aud: String::new(),
+            exp: 0,
+            iat: 0,
+            nbf: 0,
+            jti: String::new(),
+            tenant_id: String::new(),
+            permissions: vec![],
+            roles: vec![],
+            scope: None,
+            session_id: None,
+            client_id: None,
+            ext: Default::default(),
+        };
+        assert!(!is_remembered(&claims));
+        claims.ext.insert(REMEMBERED_CLAIM.to_string(), json!(true));
+        assert!(is_remembered(&claims));
+    }
+}
@@ -1,8 +1,12 @@
 //! Risk assessment service for security evaluation
 
 use crate::error::AuthError;
+use crate::services::geo::GeoLocation;
 use uuid::Uuid;
 
+/// Faster than a commercial flight, allowing for airport time
+const IMPOSSIBLE_TRAVEL_KMH: f64 = 900.0;
+
 #[derive(Debug, Clone)]
 pub struct RiskContext {
     pub user_id: Uuid,
@@ -10,7 +14,7 @@ pub struct RiskContext {
     pub ip_address: Option<String>,
     pub user_agent: Option<String>,
     pub device_fingerprint: Option<String>,
-    pub geolocation: Option<String>,
+    pub geolocation: Option<GeoLocation>,
     pub previous_logins: Vec<LoginHistory>,
 }
 
@@ -19,6 +23,7 @@ pub struct LoginHistory {
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub ip_address: String,
     pub success: bool,
+    pub geo: Option<GeoLocation>,
 }
 
 #[derive(Debug, Clone)]
@@ -81,7 +86,55 @@ impl RiskEngine {
         (0.0, None)
     }
 
-    // Placeholder for more complex checks (e.g., Geo-velocity)
+    fn calculate_geo_risk(
+        &self,
+        geo: &Option<GeoLocation>,
+        history: &[LoginHistory],
+    ) -> Vec<RiskFactor> {
+        let Some(current) = geo else {
+            return Vec::new();
+        };
+        let mut factors = Vec::new();
+
+        // New country: only meaningful once we have enriched history
+        if let Some(country) = &current.country_code {
+            let mut known = history
+                .iter()
+                .filter_map(|h| h.geo.as_ref()?.country_code.as_ref())
+                .peekable();
+            if known.peek().is_some() && !known.any(|c| c == country) {
+                factors.push(RiskFactor {
+                    name: "new_country".to_string(),
+                    weight: 0.2,
+                    description: format!("First login from country {}", country),
+                });
+            }
+        }
+
+        // Geo-velocity against the most recent successful, located login
+        let last = history
+            .iter()
+            .filter(|h| h.success && h.geo.is_some())
+            .max_by_key(|h| h.timestamp);
+        if let Some(last) = last {
+            let distance = last.geo.as_ref().and_then(|g| g.distance_km(current));
+            let hours = (chrono::Utc::now() - last.timestamp).num_seconds().max(60) as f64 / 3600.0;
+            if let Some(distance) = distance {
+                if distance / hours > IMPOSSIBLE_TRAVEL_KMH {
+                    factors.push(RiskFactor {
+                        name: "impossible_travel".to_string(),
+                        weight: 0.4,
+                        description: format!(
+                            "{:.0} km from previous login in {:.1} hours",
+                            distance, hours
+                        ),
+                    });
+                }
+            }
+        }
+
+        factors
+    }
 }
 
 #[async_trait::async_trait]
@@ -99,7 +152,13 @@ impl RiskAssessor for RiskEngine {
             factors.push(f);
         }
 
-        // 2. Device Risk (Mock implementation - normally would check against user_devices table)
+        // 2. Geo Risk (new country, impossible travel)
+        for factor in self.calculate_geo_risk(&context.geolocation, &context.previous_logins) {
+            score += factor.weight;
+            factors.push(factor);
+        }
+
+        // 3. Device Risk (Mock implementation - normally would check against user_devices table)
         if context.device_fingerprint.is_none() {
             score += 0.2;
             factors.push(RiskFactor {
@@ -109,7 +168,7 @@ impl RiskAssessor for RiskEngine {
             });
         }
 
-        // 3. Recent Failures (Mock - assuming history contains failures)
+        // 4. Recent Failures (Mock - assuming history contains failures)
         let recent_failures = context
             .previous_logins
             .iter()
@@ -146,3 +205,36 @@ impl RiskAssessor for RiskEngine {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn located(country: &str, lat: f64, lon: f64) -> Option<GeoLocation> {
+        Some(GeoLocation {
+            country_code: Some(country.to_string()),
+            latitude: Some(lat),
+            longitude: Some(lon),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_impossible_travel_and_new_country() {
+        let engine = RiskEngine::new();
+        let history = vec![LoginHistory {
+            timestamp: chrono::Utc::now() - chrono::Duration::minutes(30),
+            ip_address: "192.0.2.1".to_string(),
+            success: true,
+            geo: located("GB", 51.5074, -0.1278),
+        }];
+
+        let factors = engine.calculate_geo_risk(&located("US", 40.7128, -74.0060), &history);
+        let names: Vec<_> = factors.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["new_country", "impossible_travel"]);
+
+        assert!(engine
+            .calculate_geo_risk(&located("GB", 51.45, -0.97), &history)
+            .is_empty());
+    }
+}
@@ -1,6 +1,9 @@
 use crate::error::AuthError;
+use crate::events::{DomainEvent, EventBus};
 use crate::models::{Session, User};
-use crate::services::risk_assessment::{RiskAssessor, RiskContext};
+use crate::services::risk_assessment::{RiskAssessment, RiskAssessor, RiskContext};
+use crate::services::takeover_response::{TakeoverResponder, TakeoverSignal};
+use crate::services::token_service::TokenProvider;
 use chrono::Utc;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -11,16 +14,58 @@ pub trait SessionStore: Send + Sync {
     async fn get(&self, session_token: &str) -> Result<Option<Session>, AuthError>;
     async fn delete(&self, session_token: &str) -> Result<(), AuthError>;
     async fn delete_by_user(&self, user_id: Uuid) -> Result<(), AuthError>;
+    /// Delete every session of the tenant, returning how many there were
+    async fn delete_by_tenant(&self, tenant_id: Uuid) -> Result<u64, AuthError>;
 }
 
 pub struct SessionService {
     store: Arc<dyn SessionStore>,
     risk_engine: Arc<dyn RiskAssessor>,
+    events: Option<Arc<EventBus>>,
+    tokens: Option<Arc<dyn TokenProvider>>,
+    takeover: Option<Arc<TakeoverResponder>>,
 }
 
 impl SessionService {
     pub fn new(store: Arc<dyn SessionStore>, risk_engine: Arc<dyn RiskAssessor>) -> Self {
-        Self { store, risk_engine }
+        Self {
+            store,
+            risk_engine,
+            events: None,
+            tokens: None,
+            takeover: None,
+        }
+    }
+
+    /// Publish revocations and step-up requests so connected clients hear
+    /// about them without polling
+    pub fn with_events(mut self, bus: Arc<EventBus>) -> Self {
+        self.events = Some(bus);
+        self
+    }
+
+    /// Revoke the tokens issued under a session when the session is revoked
+    pub fn with_token_revocation(mut self, tokens: Arc<dyn TokenProvider>) -> Self {
+        self.tokens = Some(tokens);
+        self
+    }
+
+    /// Answer impossible travel with the tenant's takeover playbook
+    pub fn with_takeover_response(mut self, responder: Arc<TakeoverResponder>) -> Self {
+        self.takeover = Some(responder);
+        self
+    }
+
+    /// Act on what a sign-in's risk assessment says about a takeover
+    pub async fn respond_to_risk(&self, context: &RiskContext, assessment: &RiskAssessment) {
+        let Some(takeover) = &self.takeover else {
+            return;
+        };
+        if let Some(signal) = TakeoverSignal::from_assessment(context, assessment) {
+            takeover
+                .respond(context.tenant_id, context.user_id, signal)
+                .await;
+        }
     }
 
     pub async fn create_session(
@@ -30,6 +75,7 @@ impl SessionService {
     ) -> Result<Session, AuthError> {
         // 1. Assess Risk
         let risk_assessment = self.risk_engine.assess_risk(risk_context.clone()).await?;
+        self.respond_to_risk(&risk_context, &risk_assessment).await;
 
         // 2. Decide Policy (e.g., if Critical, reject login)
         // using string matching for simplicity since RiskLevel is enum
@@ -47,7 +93,7 @@ impl SessionService {
         let session = Session {
             id: Uuid::new_v4(),
             user_id: user.id,
-            tenant_id: Uuid::new_v4(), // Should come from context/user_tenant
+            tenant_id: user.tenant_id,
             session_token: Uuid::new_v4().to_string(), // In real app, use high entropy random string
             device_fingerprint: risk_context.device_fingerprint,
             user_agent: risk_context.user_agent,
@@ -81,10 +127,45 @@ impl SessionService {
     }
 
     pub async fn revoke_session(&self, token: &str) -> Result<(), AuthError> {
-        self.store.delete(token).await
+        let session = match (&self.events, &self.tokens) {
+            (None, None) => None,
+            _ => self.store.get(token).await?,
+        };
+        self.store.delete(token).await?;
+        if let (Some(session), Some(tokens)) = (&session, &self.tokens) {
+            tokens.revoke_session_tokens(session.id).await?;
+        }
+        if let Some(session) = session {
+            self.publish(DomainEvent::SessionRevoked {
+                tenant_id: session.tenant_id,
+                user_id: session.user_id,
+                session_id: session.id,
+            })
+            .await;
+        }
+        Ok(())
     }
 
     pub async fn revoke_user_sessions(&self, user_id: Uuid) -> Result<(), AuthError> {
-        self.store.delete_by_user(user_id).await
+        self.store.delete_by_user(user_id).await?;
+        self.publish(DomainEvent::UserSessionsRevoked { user_id })
+            .await;
+        Ok(())
+    }
+
+    /// Ask the user's live clients to re-authenticate with a stronger factor
+    pub async fn require_step_up(&self, tenant_id: Uuid, user_id: Uuid, reason: &str) {
+        self.publish(DomainEvent::StepUpRequired {
+            tenant_id,
+            user_id,
+            reason: reason.to_string(),
+        })
+        .await;
+    }
+
+    async fn publish(&self, event: DomainEvent) {
+        if let Some(bus) = &self.events {
+            bus.publish(event).await;
+        }
     }
 }
@@ -0,0 +1,603 @@
+//! Abuse protection for public signup
+//!
+//! Bots creating accounts in bulk reuse client addresses, devices and
+//! throwaway email domains. Before an account is created, the signup is
+//! counted per tenant against the configured velocity rules; over a limit
+//! it is refused, challenged with a CAPTCHA, or shadow-created. A
+//! shadow-created account looks registered to the client, but it is
+//! suspended, sent nothing, and queued for an operator to approve or
+//! reject.
+//!
+//! Counts live in a [`SignupCounter`]; a counter that cannot be reached
+//! lets signups through rather than turn everyone away.
+
+use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use crate::error::AuthError;
+use crate::models::UserStatus;
+use crate::services::identity::UserStore;
+use crate::services::sms_risk::QuarantineStatus;
+use async_trait::async_trait;
+use auth_config::{SignupAbuseConfig, SignupAction, SignupSignal};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// What is known about a signup's origin
+#[derive(Debug, Clone, Default)]
+pub struct SignupSignals {
+    pub ip_address: Option<String>,
+    pub device_fingerprint: Option<String>,
+    pub email_domain: Option<String>,
+}
+
+impl SignupSignals {
+    pub fn new(
+        ip_address: Option<String>,
+        device_fingerprint: Option<String>,
+        email: Option<&str>,
+    ) -> Self {
+        Self {
+            ip_address: ip_address.filter(|ip| !ip.is_empty()),
+            device_fingerprint: device_fingerprint.filter(|d| !d.is_empty()),
+            email_domain: email
+                .and_then(|email| email.trim().rsplit_once('@'))
+                .map(|(_, domain)| domain.to_lowercase())
+                .filter(|domain| !domain.is_empty()),
+        }
+    }
+
+    fn value(&self, signal: SignupSignal) -> Option<&str> {
+        match signal {
+            SignupSignal::Ip => self.ip_address.as_deref(),
+            SignupSignal::Device => self.device_fingerprint.as_deref(),
+            SignupSignal::EmailDomain => self.email_domain.as_deref(),
+        }
+    }
+}
+
+/// Strictest action of the rules a signup went over, and which they were
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SignupScreening {
+    pub action: Option<SignupAction>,
+    pub reasons: Vec<String>,
+}
+
+#[async_trait]
+pub trait SignupCounter: Send + Sync {
+    /// Count one more signup under `key` and return the count in the
+    /// current fixed window of length `window`
+    async fn increment(&self, key: &str, window: Duration) -> Result<u32, AuthError>;
+}
+
+/// Counts for this instance only
+#[derive(Default)]
+pub struct InMemorySignupCounter {
+    windows: DashMap<String, (Instant, u32)>,
+}
+
+#[async_trait]
+impl SignupCounter for InMemorySignupCounter {
+    async fn increment(&self, key: &str, window: Duration) -> Result<u32, AuthError> {
+        let now = Instant::now();
+        let mut entry = self.windows.entry(key.to_string()).or_insert((now, 0));
+        if now.saturating_duration_since(entry.0) >= window {
+            *entry = (now, 0);
+        }
+        entry.1 += 1;
+        Ok(entry.1)
+    }
+}
+
+/// A shadow-created account awaiting review
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedSignup {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub user_id: Uuid,
+    pub ip_address: Option<String>,
+    pub device_fingerprint: Option<String>,
+    pub email_domain: Option<String>,
+    pub reasons: Vec<String>,
+    pub status: QuarantineStatus,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait SignupQuarantineStore: Send + Sync {
+    async fn insert(&self, entry: &QuarantinedSignup) -> Result<(), AuthError>;
+    async fn find(&self, id: Uuid) -> Result<Option<QuarantinedSignup>, AuthError>;
+    /// Newest first
+    async fn list(
+        &self,
+        status: Option<QuarantineStatus>,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<QuarantinedSignup>, AuthError>;
+    /// Record a review decision; `None` if there is no such entry
+    async fn review(
+        &self,
+        id: Uuid,
+        status: QuarantineStatus,
+        reviewed_by: Option<&str>,
+    ) -> Result<Option<QuarantinedSignup>, AuthError>;
+}
+
+#[derive(Default)]
+pub struct InMemorySignupQuarantineStore {
+    entries: DashMap<Uuid, QuarantinedSignup>,
+}
+
+#[async_trait]
+impl SignupQuarantineStore for InMemorySignupQuarantineStore {
+    async fn insert(&self, entry: &QuarantinedSignup) -> Result<(), AuthError> {
+        self.entries.insert(entry.id, entry.clone());
+        Ok(())
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<QuarantinedSignup>, AuthError> {
+        Ok(self.entries.get(&id).map(|e| e.value().clone()))
+    }
+
+    async fn list(
+        &self,
+        status: Option<QuarantineStatus>,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<QuarantinedSignup>, AuthError> {
+        let mut entries: Vec<QuarantinedSignup> = self
+            .entries
+            .iter()
+            .filter(|e| status.is_none() || status == Some(e.status))
+            .filter(|e| tenant_id.is_none() || tenant_id == Some(e.tenant_id))
+            .map(|e| e.value().clone())
+            .collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+        Ok(entries)
+    }
+
+    async fn review(
+        &self,
+        id: Uuid,
+        status: QuarantineStatus,
+        reviewed_by: Option<&str>,
+    ) -> Result<Option<QuarantinedSignup>, AuthError> {
+        Ok(self.entries.get_mut(&id).map(|mut entry| {
+            entry.status = status;
+            entry.reviewed_by = reviewed_by.map(str::to_string);
+            entry.reviewed_at = Some(Utc::now());
+            entry.clone()
+        }))
+    }
+}
+
+pub struct SignupAbuseService {
+    config: SignupAbuseConfig,
+    counter: Arc<dyn SignupCounter>,
+    store: Arc<dyn SignupQuarantineStore>,
+    users: Arc<dyn UserStore>,
+    audit: Option<Arc<dyn AuditLogger>>,
+}
+
+impl SignupAbuseService {
+    pub fn new(
+        config: SignupAbuseConfig,
+        counter: Arc<dyn SignupCounter>,
+        store: Arc<dyn SignupQuarantineStore>,
+        users: Arc<dyn UserStore>,
+    ) -> Self {
+        Self {
+            config,
+            counter,
+            store,
+            users,
+            audit: None,
+        }
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Count a signup against every rule it has a signal for. Refused and
+    /// challenged attempts count too, so a bot cannot wait out a limit by
+    /// retrying.
+    pub async fn screen(&self, tenant_id: Uuid, signals: &SignupSignals) -> SignupScreening {
+        let mut screening = SignupScreening::default();
+        if !self.config.enabled {
+            return screening;
+        }
+
+        // Rules on the same signal and window share one count
+        let mut counts: HashMap<(SignupSignal, u64), u32> = HashMap::new();
+        for rule in &self.config.rules {
+            let Some(value) = signals.value(rule.signal) else {
+                continue;
+            };
+            if rule.signal == SignupSignal::EmailDomain
+                && self.config.exempt_domains.iter().any(|d| d == value)
+            {
+                continue;
+            }
+
+            let count = match counts.get(&(rule.signal, rule.window_seconds)) {
+                Some(count) => *count,
+                None => {
+                    let key = format!(
+                        "signup_velocity:{}:{}:{}:{}",
+                        tenant_id,
+                        rule.signal.as_str(),
+                        rule.window_seconds,
+                        value
+                    );
+                    let window = Duration::from_secs(rule.window_seconds);
+                    match self.counter.increment(&key, window).await {
+                        Ok(count) => {
+                            counts.insert((rule.signal, rule.window_seconds), count);
+                            count
+                        }
+                        Err(e) => {
+                            tracing::warn!("Signup velocity unavailable: {}", e);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            if count > rule.max_signups {
+                metrics::counter!(
+                    "auth_signup_rule_triggered_total",
+                    1,
+                    "signal" => rule.signal.as_str(),
+                    "action" => rule.action.as_str()
+                );
+                screening.action = screening.action.max(Some(rule.action));
+                screening.reasons.push(format!(
+                    "{} signups by {} within {}s",
+                    count,
+                    rule.signal.as_str(),
+                    rule.window_seconds
+                ));
+            }
+        }
+
+        let outcome = screening.action.map_or("allow", |a| a.as_str());
+        metrics::counter!("auth_signup_screened_total", 1, "action" => outcome);
+        if let Some(action) = screening.action {
+            self.audit(
+                AuditEvent::new(
+                    AuditCategory::Security,
+                    "signup.velocity_exceeded",
+                    AuditSeverity::Warning,
+                )
+                .with_context(signals.ip_address.clone(), None, Some(tenant_id))
+                .with_metadata(serde_json::json!({
+                    "action": action.as_str(),
+                    "reasons": screening.reasons,
+                })),
+            )
+            .await;
+        }
+        screening
+    }
+
+    /// Suspend a just-created account and queue it for review
+    pub async fn quarantine(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+        signals: &SignupSignals,
+        reasons: Vec<String>,
+    ) -> Result<QuarantinedSignup, AuthError> {
+        self.users
+            .update_status(user_id, UserStatus::Suspended)
+            .await?;
+        let entry = QuarantinedSignup {
+            id: Uuid::new_v4(),
+            tenant_id,
+            user_id,
+            ip_address: signals.ip_address.clone(),
+            device_fingerprint: signals.device_fingerprint.clone(),
+            email_domain: signals.email_domain.clone(),
+            reasons,
+            status: QuarantineStatus::Pending,
+            created_at: Utc::now(),
+            reviewed_by: None,
+            reviewed_at: None,
+        };
+        self.store.insert(&entry).await?;
+
+        metrics::counter!("auth_signup_quarantined_total", 1);
+        self.audit(
+            AuditEvent::new(
+                AuditCategory::Security,
+                "signup.quarantined",
+                AuditSeverity::Warning,
+            )
+            .with_actor(user_id)
+            .with_context(entry.ip_address.clone(), None, Some(tenant_id))
+            .with_resource(user_id.to_string())
+            .with_metadata(serde_json::json!({
+                "quarantine_id": entry.id,
+                "reasons": entry.reasons,
+            })),
+        )
+        .await;
+        Ok(entry)
+    }
+
+    /// Entries in the review queue, newest first
+    pub async fn quarantined(
+        &self,
+        status: Option<QuarantineStatus>,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<QuarantinedSignup>, AuthError> {
+        self.store.list(status, tenant_id).await
+    }
+
+    /// Approve or reject a shadow-created account. Approval releases it to
+    /// verify its email or phone as a fresh signup would (an account with
+    /// neither is activated); rejection keeps it suspended. A decision can
+    /// be revisited.
+    pub async fn review(
+        &self,
+        id: Uuid,
+        approve: bool,
+        reviewed_by: Option<&str>,
+    ) -> Result<Option<QuarantinedSignup>, AuthError> {
+        let Some(entry) = self.store.find(id).await? else {
+            return Ok(None);
+        };
+
+        let status = if !approve {
+            UserStatus::Suspended
+        } else {
+            match self.users.find_by_id(entry.user_id).await? {
+                Some(user) if user.email.is_none() && user.phone.is_none() => UserStatus::Active,
+                _ => UserStatus::PendingVerification,
+            }
+        };
+        self.users.update_status(entry.user_id, status).await?;
+
+        let decision = if approve {
+            QuarantineStatus::Approved
+        } else {
+            QuarantineStatus::Rejected
+        };
+        let entry = self.store.review(id, decision, reviewed_by).await?;
+        if let Some(entry) = &entry {
+            metrics::counter!("auth_signup_reviews_total", 1, "decision" => decision.as_str());
+            let action = if approve {
+                "signup.quarantine_approved"
+            } else {
+                "signup.quarantine_rejected"
+            };
+            self.audit(
+                AuditEvent::new(AuditCategory::Security, action, AuditSeverity::Info)
+                    .with_context(None, None, Some(entry.tenant_id))
+                    .with_resource(entry.user_id.to_string())
+                    .with_metadata(serde_json::json!({
+                        "quarantine_id": entry.id,
+                        "reviewed_by": entry.reviewed_by,
+                    })),
+            )
+            .await;
+        }
+        Ok(entry)
+    }
+
+    async fn audit(&self, event: AuditEvent) {
+        if let Some(audit) = &self.audit {
+            audit.log(event).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateUserRequest, UpdateUserRequest, User};
+    use auth_config::SignupVelocityRule;
+    use parking_lot::Mutex;
+
+    /// One user; records status changes
+    struct Users {
+        user: Mutex<User>,
+    }
+
+    #[async_trait]
+    impl UserStore for Users {
+        async fn find_by_email(&self, _: &str, _: Uuid) -> Result<Option<User>, AuthError> {
+            Ok(None)
+        }
+        async fn find_by_phone(&self, _: &str, _: Uuid) -> Result<Option<User>, AuthError> {
+            Ok(None)
+        }
+        async fn find_by_username(&self, _: &str, _: Uuid) -> Result<Option<User>, AuthError> {
+            Ok(None)
+        }
+        async fn find_by_identifier(&self, _: &str, _: Uuid) -> Result<Option<User>, AuthError> {
+            Ok(None)
+        }
+        async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, AuthError> {
+            let user = self.user.lock().clone();
+            Ok((user.id == id).then_some(user))
+        }
+        async fn create(
+            &self,
+            _: CreateUserRequest,
+            _: String,
+            _: Uuid,
+        ) -> Result<User, AuthError> {
+            Err(AuthError::InternalError)
+        }
+        async fn update_status(&self, _: Uuid, status: UserStatus) -> Result<(), AuthError> {
+            self.user.lock().status = status;
+            Ok(())
+        }
+        async fn increment_failed_attempts(&self, _: Uuid) -> Result<u32, AuthError> {
+            Ok(1)
+        }
+        async fn reset_failed_attempts(&self, _: Uuid) -> Result<(), AuthError> {
+            Ok(())
+        }
+        async fn record_login(&self, _: Uuid, _: Option<String>) -> Result<(), AuthError> {
+            Ok(())
+        }
+        async fn update(&self, _: UpdateUserRequest) -> Result<User, AuthError> {
+            Ok(self.user.lock().clone())
+        }
+        async fn update_password_hash(&self, _: Uuid, _: String) -> Result<(), AuthError> {
+            Ok(())
+        }
+        async fn set_email_verified(&self, _: Uuid, _: bool) -> Result<(), AuthError> {
+            Ok(())
+        }
+        async fn set_phone_verified(&self, _: Uuid, _: bool) -> Result<(), AuthError> {
+            Ok(())
+        }
+    }
+
+    fn service(config: SignupAbuseConfig, user: User) -> (SignupAbuseService, Arc<Users>) {
+        let users = Arc::new(Users {
+            user: Mutex::new(user),
+        });
+        let service = SignupAbuseService::new(
+            config,
+            Arc::new(InMemorySignupCounter::default()),
+            Arc::new(InMemorySignupQuarantineStore::default()),
+            users.clone(),
+        );
+        (service, users)
+    }
+
+    fn signals(ip: &str, email: &str) -> SignupSignals {
+        SignupSignals::new(Some(ip.to_string()), None, Some(email))
+    }
+
+    #[tokio::test]
+    async fn test_address_velocity_escalates() {
+        let (abuse, _) = service(SignupAbuseConfig::default(), User::default());
+        let tenant_id = Uuid::new_v4();
+
+        for _ in 0..5 {
+            let screening = abuse
+                .screen(tenant_id, &signals("203.0.113.7", "a@gmail.com"))
+                .await;
+            assert_eq!(screening.action, None);
+        }
+        let screening = abuse
+            .screen(tenant_id, &signals("203.0.113.7", "a@gmail.com"))
+            .await;
+        assert_eq!(screening.action, Some(SignupAction::Captcha));
+        assert_eq!(screening.reasons, vec!["6 signups by ip within 3600s"]);
+
+        for _ in 0..14 {
+            abuse
+                .screen(tenant_id, &signals("203.0.113.7", "a@gmail.com"))
+                .await;
+        }
+        let screening = abuse
+            .screen(tenant_id, &signals("203.0.113.7", "a@gmail.com"))
+            .await;
+        assert_eq!(screening.action, Some(SignupAction::Block));
+
+        // Other addresses and tenants keep their own counts
+        assert_eq!(
+            abuse
+                .screen(tenant_id, &signals("203.0.113.8", "a@gmail.com"))
+                .await
+                .action,
+            None
+        );
+        assert_eq!(
+            abuse
+                .screen(Uuid::new_v4(), &signals("203.0.113.7", "a@gmail.com"))
+                .await
+                .action,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exempt_domains_are_not_counted() {
+        let config = SignupAbuseConfig {
+            rules: vec![SignupVelocityRule {
+                signal: SignupSignal::EmailDomain,
+                max_signups: 1,
+                window_seconds: 60,
+                action: SignupAction::Quarantine,
+            }],
+            ..SignupAbuseConfig::default()
+        };
+        let (abuse, _) = service(config, User::default());
+        let tenant_id = Uuid::new_v4();
+
+        for n in 0..3 {
+            let ip = format!("198.51.100.{}", n);
+            let screening = abuse
+                .screen(tenant_id, &signals(&ip, "Someone@GMAIL.com"))
+                .await;
+            assert_eq!(screening.action, None);
+        }
+        abuse
+            .screen(tenant_id, &signals("198.51.100.1", "x@mailinator.test"))
+            .await;
+        let screening = abuse
+            .screen(tenant_id, &signals("198.51.100.2", "y@mailinator.test"))
+            .await;
+        assert_eq!(screening.action, Some(SignupAction::Quarantine));
+    }
+
+    #[tokio::test]
+    async fn test_quarantined_signup_suspended_until_approved() {
+        let user = User {
+            email: Some("bot@mailinator.test".to_string()),
+            status: UserStatus::PendingVerification,
+            ..User::default()
+        };
+        let (user_id, tenant_id) = (user.id, user.tenant_id);
+        let (abuse, users) = service(SignupAbuseConfig::default(), user);
+
+        let entry = abuse
+            .quarantine(
+                tenant_id,
+                user_id,
+                &signals("203.0.113.7", "bot@mailinator.test"),
+                vec!["4 signups by device within 3600s".to_string()],
+            )
+            .await
+            .unwrap();
+        assert!(matches!(users.user.lock().status, UserStatus::Suspended));
+        assert_eq!(entry.email_domain.as_deref(), Some("mailinator.test"));
+
+        let pending = abuse
+            .quarantined(Some(QuarantineStatus::Pending), Some(tenant_id))
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+
+        let reviewed = abuse
+            .review(entry.id, true, Some("ops"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reviewed.status, QuarantineStatus::Approved);
+        assert!(matches!(
+            users.user.lock().status,
+            UserStatus::PendingVerification
+        ));
+
+        abuse.review(entry.id, false, None).await.unwrap();
+        assert!(matches!(users.user.lock().status, UserStatus::Suspended));
+        assert!(abuse
+            .review(Uuid::new_v4(), true, None)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,594 @@
+//! SMS cost controls
+//!
+//! SMS pumping fraud requests codes to premium-rate ranges it profits from,
+//! so every message sent is money lost. Each tenant has daily and monthly
+//! budgets: crossing a soft limit raises an alert, reaching a hard limit
+//! stops SMS for the rest of the period. Counts are persisted per tenant
+//! and UTC day so they hold across instances and restarts; messages in
+//! flight when a hard limit is reached may still go out.
+//!
+//! Independently, volume to each range of numbers (the first few digits) is
+//! tracked per instance, and a window with several times the range's usual
+//! volume raises an alert.
+
+use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use crate::error::AuthError;
+use crate::services::otp_delivery::DeliveryError;
+use async_trait::async_trait;
+use auth_config::{SmsAnomalyConfig, SmsBudgetConfig, SmsBudgetLimits};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, Utc};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Weight of the latest window in a range's average
+const AVERAGE_WEIGHT: f64 = 0.3;
+
+/// Spikes kept for reporting
+const RECENT_ANOMALIES: usize = 100;
+
+/// Messages one tenant sent on one day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DailySmsUsage {
+    pub tenant_id: Uuid,
+    pub day: NaiveDate,
+    pub sent: u64,
+}
+
+#[async_trait]
+pub trait SmsUsageStore: Send + Sync {
+    /// Count one message sent by the tenant on `day`
+    async fn record(&self, tenant_id: Uuid, day: NaiveDate) -> Result<(), AuthError>;
+    /// Days with messages in `from..=to`, for one tenant or all
+    async fn daily_usage(
+        &self,
+        tenant_id: Option<Uuid>,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<DailySmsUsage>, AuthError>;
+}
+
+#[derive(Default)]
+pub struct InMemorySmsUsageStore {
+    days: DashMap<(Uuid, NaiveDate), u64>,
+}
+
+#[async_trait]
+impl SmsUsageStore for InMemorySmsUsageStore {
+    async fn record(&self, tenant_id: Uuid, day: NaiveDate) -> Result<(), AuthError> {
+        *self.days.entry((tenant_id, day)).or_default() += 1;
+        Ok(())
+    }
+
+    async fn daily_usage(
+        &self,
+        tenant_id: Option<Uuid>,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<DailySmsUsage>, AuthError> {
+        let mut usage: Vec<DailySmsUsage> = self
+            .days
+            .iter()
+            .filter(|e| {
+                let (tenant, day) = *e.key();
+                (tenant_id.is_none() || tenant_id == Some(tenant)) && (from..=to).contains(&day)
+            })
+            .map(|e| DailySmsUsage {
+                tenant_id: e.key().0,
+                day: e.key().1,
+                sent: *e.value(),
+            })
+            .collect();
+        usage.sort_by_key(|u| (u.tenant_id, u.day));
+        Ok(usage)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmsBudgetStatus {
+    Within,
+    SoftLimitReached,
+    Exhausted,
+}
+
+/// A tenant's usage against its limits
+#[derive(Debug, Clone, Serialize)]
+pub struct SmsBudgetUsage {
+    pub tenant_id: Uuid,
+    pub today: u64,
+    pub this_month: u64,
+    pub limits: SmsBudgetLimits,
+    pub status: SmsBudgetStatus,
+    /// The hard limit reached, as (limit, period), when exhausted
+    #[serde(skip)]
+    exhausted_by: Option<(u64, &'static str)>,
+}
+
+/// A window with unusual volume to one range of numbers
+#[derive(Debug, Clone, Serialize)]
+pub struct SmsAnomaly {
+    pub prefix: String,
+    /// Tenant whose message crossed the threshold
+    pub tenant_id: Uuid,
+    pub messages: u32,
+    pub window_seconds: u64,
+    /// Average messages per window before the spike
+    pub baseline: f64,
+    pub detected_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SmsUsageReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub tenants: Vec<TenantSmsReport>,
+    /// Most recent first; this instance only
+    pub anomalies: Vec<SmsAnomaly>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TenantSmsReport {
+    #[serde(flatten)]
+    pub usage: SmsBudgetUsage,
+    pub daily: Vec<DailySmsUsage>,
+}
+
+/// Volume to one range, in fixed windows
+struct RangeVolume {
+    window_start: Instant,
+    count: u32,
+    average: f64,
+    alerted: bool,
+}
+
+impl RangeVolume {
+    fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            count: 0,
+            average: 0.0,
+            alerted: false,
+        }
+    }
+
+    /// Count one message; returns the window's count and the baseline when
+    /// this message makes the window a spike, once per window
+    fn observe(&mut self, now: Instant, config: &SmsAnomalyConfig) -> Option<(u32, f64)> {
+        let window = Duration::from_secs(config.window_seconds.max(1));
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if elapsed >= window {
+            // Windows that passed without messages count as empty
+            let empty = (elapsed.as_secs() / window.as_secs()).saturating_sub(1);
+            self.average =
+                self.average * (1.0 - AVERAGE_WEIGHT) + self.count as f64 * AVERAGE_WEIGHT;
+            self.average *= (1.0 - AVERAGE_WEIGHT).powi(empty.min(64) as i32);
+            self.window_start = now;
+            self.count = 0;
+            self.alerted = false;
+        }
+
+        self.count += 1;
+        let spike = self.count >= config.min_messages
+            && self.count as f64 > self.average.max(1.0) * config.spike_factor;
+        if spike && !self.alerted {
+            self.alerted = true;
+            Some((self.count, self.average))
+        } else {
+            None
+        }
+    }
+}
+
+pub struct SmsBudgetService {
+    config: SmsBudgetConfig,
+    store: Arc<dyn SmsUsageStore>,
+    audit: Option<Arc<dyn AuditLogger>>,
+    ranges: DashMap<String, RangeVolume>,
+    anomalies: Mutex<VecDeque<SmsAnomaly>>,
+    /// Limits already alerted on, as (tenant, limit name, period start)
+    alerted: DashMap<(Uuid, &'static str, NaiveDate), ()>,
+}
+
+impl SmsBudgetService {
+    pub fn new(config: SmsBudgetConfig, store: Arc<dyn SmsUsageStore>) -> Self {
+        Self {
+            config,
+            store,
+            audit: None,
+            ranges: DashMap::new(),
+            anomalies: Mutex::new(VecDeque::new()),
+            alerted: DashMap::new(),
+        }
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    pub fn limits(&self, tenant_id: Uuid) -> SmsBudgetLimits {
+        self.config
+            .tenants
+            .get(&tenant_id)
+            .copied()
+            .unwrap_or(self.config.default_limits)
+    }
+
+    /// Refuse SMS for a tenant whose budget is exhausted. If usage cannot be
+    /// read the message is allowed, so an outage of the store does not stop
+    /// sign-ins.
+    pub async fn check(&self, tenant_id: Uuid) -> Result<(), DeliveryError> {
+        let today = Utc::now().date_naive();
+        let usage = match self.usage(tenant_id, today).await {
+            Ok(usage) => usage,
+            Err(e) => {
+                tracing::warn!("SMS budget of tenant {} unavailable: {}", tenant_id, e);
+                return Ok(());
+            }
+        };
+        match usage.exhausted_by {
+            Some((limit, period)) => Err(DeliveryError::BudgetExhausted {
+                limit,
+                period: period.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Count a message the tenant sent to `to`, alerting on limits it
+    /// crosses and on volume spikes to its range
+    pub async fn record(&self, tenant_id: Uuid, to: &str) {
+        let today = Utc::now().date_naive();
+        if let Err(e) = self.store.record(tenant_id, today).await {
+            tracing::error!("Failed to record SMS usage of tenant {}: {}", tenant_id, e);
+        }
+        match self.usage(tenant_id, today).await {
+            Ok(usage) => self.alert_on_limits(&usage, today).await,
+            Err(e) => tracing::warn!("SMS budget of tenant {} unavailable: {}", tenant_id, e),
+        }
+        self.observe_range(tenant_id, to, Instant::now()).await;
+    }
+
+    /// Usage of one tenant, or of every tenant that sent SMS, over the last
+    /// `days` days including today
+    pub async fn report(
+        &self,
+        tenant_id: Option<Uuid>,
+        days: u32,
+    ) -> Result<SmsUsageReport, AuthError> {
+        let today = Utc::now().date_naive();
+        let from = today - ChronoDuration::days(i64::from(days.max(1)) - 1);
+        let rows = self
+            .store
+            .daily_usage(tenant_id, from.min(month_start(today)), today)
+            .await?;
+
+        let mut by_tenant: BTreeMap<Uuid, Vec<DailySmsUsage>> = BTreeMap::new();
+        if let Some(tenant_id) = tenant_id {
+            by_tenant.entry(tenant_id).or_default();
+        }
+        for row in rows {
+            by_tenant.entry(row.tenant_id).or_default().push(row);
+        }
+
+        let tenants = by_tenant
+            .into_iter()
+            .map(|(tenant_id, rows)| TenantSmsReport {
+                usage: self.summarize(tenant_id, today, &rows),
+                daily: rows.into_iter().filter(|row| row.day >= from).collect(),
+            })
+            .collect();
+
+        Ok(SmsUsageReport {
+            from,
+            to: today,
+            tenants,
+            anomalies: self.anomalies.lock().iter().rev().cloned().collect(),
+        })
+    }
+
+    async fn usage(&self, tenant_id: Uuid, today: NaiveDate) -> Result<SmsBudgetUsage, AuthError> {
+        let rows = self
+            .store
+            .daily_usage(Some(tenant_id), month_start(today), today)
+            .await?;
+        Ok(self.summarize(tenant_id, today, &rows))
+    }
+
+    fn summarize(
+        &self,
+        tenant_id: Uuid,
+        today: NaiveDate,
+        rows: &[DailySmsUsage],
+    ) -> SmsBudgetUsage {
+        let month = month_start(today);
+        let this_month = rows
+            .iter()
+            .filter(|row| row.day >= month && row.day <= today)
+            .map(|row| row.sent)
+            .sum();
+        let today_sent = rows
+            .iter()
+            .filter(|row| row.day == today)
+            .map(|row| row.sent)
+            .sum();
+        let limits = self.limits(tenant_id);
+
+        let reached = |sent: u64, limit: Option<u64>| limit.filter(|limit| sent >= *limit);
+        let exhausted_by = reached(today_sent, limits.daily_hard)
+            .map(|limit| (limit, "day"))
+            .or_else(|| reached(this_month, limits.monthly_hard).map(|limit| (limit, "month")));
+        let status = if exhausted_by.is_some() {
+            SmsBudgetStatus::Exhausted
+        } else if reached(today_sent, limits.daily_soft).is_some()
+            || reached(this_month, limits.monthly_soft).is_some()
+        {
+            SmsBudgetStatus::SoftLimitReached
+        } else {
+            SmsBudgetStatus::Within
+        };
+
+        SmsBudgetUsage {
+            tenant_id,
+            today: today_sent,
+            this_month,
+            limits,
+            status,
+            exhausted_by,
+        }
+    }
+
+    /// Alert once per limit and period, per instance
+    async fn alert_on_limits(&self, usage: &SmsBudgetUsage, today: NaiveDate) {
+        let limits = &usage.limits;
+        let checks = [
+            ("daily_soft", usage.today, limits.daily_soft, today),
+            ("daily_hard", usage.today, limits.daily_hard, today),
+            (
+                "monthly_soft",
+                usage.this_month,
+                limits.monthly_soft,
+                month_start(today),
+            ),
+            (
+                "monthly_hard",
+                usage.this_month,
+                limits.monthly_hard,
+                month_start(today),
+            ),
+        ];
+        for (name, sent, limit, period) in checks {
+            let Some(limit) = limit.filter(|limit| sent >= *limit) else {
+                continue;
+            };
+            if self
+                .alerted
+                .insert((usage.tenant_id, name, period), ())
+                .is_some()
+            {
+                continue;
+            }
+
+            let hard = name.ends_with("hard");
+            tracing::warn!(
+                "Tenant {} reached its SMS {} limit ({} of {})",
+                usage.tenant_id,
+                name,
+                sent,
+                limit
+            );
+            let (action, severity) = if hard {
+                ("sms.budget_exhausted", AuditSeverity::Critical)
+            } else {
+                ("sms.budget_soft_limit", AuditSeverity::Warning)
+            };
+            self.audit(
+                AuditEvent::new(AuditCategory::Security, action, severity)
+                    .with_context(None, None, Some(usage.tenant_id))
+                    .with_metadata(serde_json::json!({
+                        "limit": name,
+                        "sent": sent,
+                        "max": limit,
+                    })),
+            )
+            .await;
+        }
+        // Entries for past periods are no longer needed
+        self.alerted
+            .retain(|(_, _, period), _| *period >= month_start(today));
+    }
+
+    async fn observe_range(&self, tenant_id: Uuid, to: &str, now: Instant) {
+        let config = &self.config.anomaly;
+        let digits: String = to.chars().filter(|c| c.is_ascii_digit()).collect();
+        let prefix: String = digits.chars().take(config.prefix_digits.max(1)).collect();
+        if prefix.is_empty() {
+            return;
+        }
+
+        let spike = self
+            .ranges
+            .entry(prefix.clone())
+            .or_insert_with(|| RangeVolume::new(now))
+            .observe(now, config);
+        let Some((messages, baseline)) = spike else {
+            return;
+        };
+
+        let anomaly = SmsAnomaly {
+            prefix,
+            tenant_id,
+            messages,
+            window_seconds: config.window_seconds,
+            baseline,
+            detected_at: Utc::now(),
+        };
+        tracing::error!(
+            "SMS volume spike to +{}: {} messages in {}s against an average of {:.1}",
+            anomaly.prefix,
+            anomaly.messages,
+            anomaly.window_seconds,
+            anomaly.baseline
+        );
+        self.audit(
+            AuditEvent::new(
+                AuditCategory::Security,
+                "sms.volume_spike",
+                AuditSeverity::Critical,
+            )
+            .with_context(None, None, Some(tenant_id))
+            .with_metadata(serde_json::json!({
+                "prefix": anomaly.prefix,
+                "messages": anomaly.messages,
+                "window_seconds": anomaly.window_seconds,
+                "baseline": anomaly.baseline,
+            })),
+        )
+        .await;
+
+        let mut anomalies = self.anomalies.lock();
+        if anomalies.len() == RECENT_ANOMALIES {
+            anomalies.pop_front();
+        }
+        anomalies.push_back(anomaly);
+    }
+
+    async fn audit(&self, event: AuditEvent) {
+        if let Some(audit) = &self.audit {
+            audit.log(event).await;
+        }
+    }
+}
+
+fn month_start(day: NaiveDate) -> NaiveDate {
+    day.with_day(1).unwrap_or(day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn service(limits: SmsBudgetLimits) -> SmsBudgetService {
+        SmsBudgetService::new(
+            SmsBudgetConfig {
+                default_limits: limits,
+                tenants: HashMap::new(),
+                anomaly: SmsAnomalyConfig {
+                    min_messages: 5,
+                    ..SmsAnomalyConfig::default()
+                },
+            },
+            Arc::new(InMemorySmsUsageStore::default()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_hard_daily_limit_exhausts_budget() {
+        let budget = service(SmsBudgetLimits {
+            daily_soft: Some(2),
+            daily_hard: Some(3),
+            ..SmsBudgetLimits::default()
+        });
+        let tenant = Uuid::new_v4();
+
+        for _ in 0..2 {
+            assert!(budget.check(tenant).await.is_ok());
+            budget.record(tenant, "+14155552671").await;
+        }
+        let report = budget.report(Some(tenant), 1).await.unwrap();
+        assert_eq!(
+            report.tenants[0].usage.status,
+            SmsBudgetStatus::SoftLimitReached
+        );
+
+        budget.record(tenant, "+14155552671").await;
+        assert!(matches!(
+            budget.check(tenant).await,
+            Err(DeliveryError::BudgetExhausted { limit: 3, ref period }) if period == "day"
+        ));
+        // Other tenants are unaffected
+        assert!(budget.check(Uuid::new_v4()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_monthly_limit_counts_earlier_days() {
+        let store = Arc::new(InMemorySmsUsageStore::default());
+        let tenant = Uuid::new_v4();
+        let today = Utc::now().date_naive();
+        for _ in 0..4 {
+            store.record(tenant, month_start(today)).await.unwrap();
+        }
+        let budget = SmsBudgetService::new(
+            SmsBudgetConfig {
+                tenants: HashMap::from([(
+                    tenant,
+                    SmsBudgetLimits {
+                        monthly_hard: Some(5),
+                        ..SmsBudgetLimits::default()
+                    },
+                )]),
+                ..SmsBudgetConfig::default()
+            },
+            store,
+        );
+
+        assert!(budget.check(tenant).await.is_ok());
+        budget.record(tenant, "+919812345678").await;
+        assert!(matches!(
+            budget.check(tenant).await,
+            Err(DeliveryError::BudgetExhausted { limit: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn test_spike_detected_once_per_window() {
+        let config = SmsAnomalyConfig {
+            min_messages: 5,
+            spike_factor: 3.0,
+            window_seconds: 60,
+            ..SmsAnomalyConfig::default()
+        };
+        let start = Instant::now();
+        let mut range = RangeVolume::new(start);
+
+        // A steady four per window never alerts
+        for window in 0..5 {
+            let now = start + Duration::from_secs(60 * window);
+            for _ in 0..4 {
+                assert_eq!(range.observe(now, &config), None);
+            }
+        }
+
+        let now = start + Duration::from_secs(60 * 5);
+        let alerts: Vec<_> = (0..40)
+            .filter_map(|_| range.observe(now, &config))
+            .collect();
+        assert_eq!(alerts.len(), 1);
+        let (messages, baseline) = alerts[0];
+        assert_eq!(messages, 10);
+        assert!(baseline > 2.0 && baseline < 4.0);
+    }
+
+    #[tokio::test]
+    async fn test_report_lists_spikes_and_daily_usage() {
+        let budget = service(SmsBudgetLimits::default());
+        let tenant = Uuid::new_v4();
+        // Above five times the floor of one message per window
+        for _ in 0..6 {
+            budget.record(tenant, "+88216000001").await;
+        }
+
+        let report = budget.report(None, 7).await.unwrap();
+        assert_eq!(report.tenants.len(), 1);
+        assert_eq!(report.tenants[0].usage.today, 6);
+        assert_eq!(report.tenants[0].daily.len(), 1);
+        assert_eq!(report.anomalies.len(), 1);
+        assert_eq!(report.anomalies[0].prefix, "88216");
+    }
+}
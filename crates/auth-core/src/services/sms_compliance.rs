@@ -0,0 +1,296 @@
+//! SMS carrier registration (DLT, 10DLC)
+//!
+//! Carriers in some regions only deliver application-to-person SMS that is
+//! registered: India's DLT requires the sender header, principal entity and
+//! a content template id on every message, and US carriers filter traffic
+//! not tied to a 10DLC campaign. Messages that do not match their template
+//! are dropped silently downstream, so they are refused here instead, where
+//! the failure is visible.
+
+use crate::services::otp_delivery::DeliveryError;
+use auth_config::{SmsComplianceConfig, SmsRegionConfig};
+use std::cmp::Reverse;
+use uuid::Uuid;
+
+/// Placeholder for a value in a registered template
+const TEMPLATE_VAR: &str = "{#var#}";
+
+/// Longest value DLT accepts for one placeholder
+const MAX_VAR_CHARS: usize = 30;
+
+/// Kind of the one-time code message
+pub const OTP_MESSAGE: &str = "otp";
+
+/// An outbound SMS with the registration metadata of its region
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmsMessage {
+    pub to: String,
+    pub text: String,
+    /// Region the number was routed to
+    pub region: Option<String>,
+    pub sender_id: Option<String>,
+    /// DLT principal entity id
+    pub entity_id: Option<String>,
+    /// Registered template the text matches
+    pub template_id: Option<String>,
+    /// 10DLC campaign id
+    pub campaign_id: Option<String>,
+}
+
+impl SmsMessage {
+    /// A message sent without registration metadata
+    pub fn new(to: &str, text: String) -> Self {
+        Self {
+            to: to.to_string(),
+            text,
+            ..Self::default()
+        }
+    }
+}
+
+pub struct SmsComplianceService {
+    config: SmsComplianceConfig,
+}
+
+impl SmsComplianceService {
+    pub fn new(config: SmsComplianceConfig) -> Self {
+        Self { config }
+    }
+
+    /// Region for `to`: the one with the longest matching calling code,
+    /// with the tenant's regions taking the place of global ones of the
+    /// same name. Numbers not in `+<digits>` form match no region.
+    pub fn region(&self, tenant_id: Option<Uuid>, to: &str) -> Option<(&str, &SmsRegionConfig)> {
+        let number = to.strip_prefix('+')?;
+        let tenant = tenant_id.and_then(|t| self.config.tenants.get(&t));
+        let global = self
+            .config
+            .regions
+            .iter()
+            .filter(|(name, _)| !tenant.is_some_and(|t| t.contains_key(*name)));
+
+        tenant
+            .into_iter()
+            .flatten()
+            .chain(global)
+            .filter_map(|(name, region)| {
+                let matched = region
+                    .calling_codes
+                    .iter()
+                    .filter(|code| !code.is_empty() && number.starts_with(code.as_str()))
+                    .map(|code| code.len())
+                    .max()?;
+                Some((matched, name.as_str(), region))
+            })
+            // Tenant regions come first and win ties
+            .min_by_key(|(matched, ..)| Reverse(*matched))
+            .map(|(_, name, region)| (name, region))
+    }
+
+    /// Attach the region's registration to a message of `kind`, refusing it
+    /// if it does not match the registered template
+    pub fn prepare(
+        &self,
+        tenant_id: Option<Uuid>,
+        kind: &str,
+        to: &str,
+        text: String,
+    ) -> Result<SmsMessage, DeliveryError> {
+        let Some((name, region)) = self.region(tenant_id, to) else {
+            return Ok(SmsMessage::new(to, text));
+        };
+
+        let template_id = match region.templates.get(kind) {
+            Some(template) if template_matches(&template.body, &text) => {
+                Some(template.template_id.clone())
+            }
+            Some(template) => {
+                return Err(DeliveryError::ComplianceViolation(format!(
+                    "{} message does not match template {} registered for region {}",
+                    kind, template.template_id, name
+                )))
+            }
+            None if region.require_template => {
+                return Err(DeliveryError::ComplianceViolation(format!(
+                    "no {} template registered for region {}",
+                    kind, name
+                )))
+            }
+            None => None,
+        };
+
+        Ok(SmsMessage {
+            to: to.to_string(),
+            text,
+            region: Some(name.to_string()),
+            sender_id: region.sender_id.clone(),
+            entity_id: region.entity_id.clone(),
+            template_id,
+            campaign_id: region.campaign_id.clone(),
+        })
+    }
+}
+
+/// Whether `text` is `template` with each placeholder replaced by 1 to
+/// [`MAX_VAR_CHARS`] characters
+pub fn template_matches(template: &str, text: &str) -> bool {
+    let mut parts = template.split(TEMPLATE_VAR);
+    let head = parts.next().unwrap_or_default();
+    let literals: Vec<&str> = parts.collect();
+    text.strip_prefix(head)
+        .is_some_and(|rest| matches_after_vars(&literals, rest))
+}
+
+/// `literals` are what follows each remaining placeholder
+fn matches_after_vars(literals: &[&str], text: &str) -> bool {
+    let Some((literal, rest)) = literals.split_first() else {
+        return text.is_empty();
+    };
+    // Byte offsets after the first 1..=MAX_VAR_CHARS characters
+    text.char_indices()
+        .map(|(i, _)| i)
+        .skip(1)
+        .chain((!text.is_empty()).then_some(text.len()))
+        .take(MAX_VAR_CHARS)
+        .any(|end| {
+            text[end..]
+                .strip_prefix(literal)
+                .is_some_and(|after| matches_after_vars(rest, after))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auth_config::SmsTemplate;
+    use std::collections::HashMap;
+
+    const OTP_TEMPLATE: &str =
+        "Your verification code is: {#var#}. Valid for 10 minutes. Do not share this code.";
+
+    fn india() -> SmsRegionConfig {
+        SmsRegionConfig {
+            calling_codes: vec!["91".to_string()],
+            sender_id: Some("AUTHID".to_string()),
+            entity_id: Some("1101000000000000001".to_string()),
+            templates: HashMap::from([(
+                OTP_MESSAGE.to_string(),
+                SmsTemplate {
+                    template_id: "1107000000000000001".to_string(),
+                    body: OTP_TEMPLATE.to_string(),
+                },
+            )]),
+            require_template: true,
+            ..SmsRegionConfig::default()
+        }
+    }
+
+    fn us() -> SmsRegionConfig {
+        SmsRegionConfig {
+            calling_codes: vec!["1".to_string()],
+            campaign_id: Some("CABC123".to_string()),
+            ..SmsRegionConfig::default()
+        }
+    }
+
+    fn service() -> SmsComplianceService {
+        SmsComplianceService::new(SmsComplianceConfig {
+            regions: HashMap::from([("in".to_string(), india()), ("us".to_string(), us())]),
+            tenants: HashMap::new(),
+        })
+    }
+
+    #[test]
+    fn test_template_placeholders_match_bounded_values() {
+        assert!(template_matches(
+            OTP_TEMPLATE,
+            &OTP_TEMPLATE.replace("{#var#}", "123456")
+        ));
+        assert!(!template_matches(
+            OTP_TEMPLATE,
+            &OTP_TEMPLATE.replace("{#var#}", "")
+        ));
+        assert!(!template_matches(
+            OTP_TEMPLATE,
+            &OTP_TEMPLATE.replace("{#var#}", &"9".repeat(31))
+        ));
+        assert!(!template_matches(OTP_TEMPLATE, "Your code is 123456"));
+        assert!(template_matches("{#var#} and {#var#}", "a and b"));
+        assert!(template_matches("no placeholders", "no placeholders"));
+    }
+
+    #[test]
+    fn test_registered_message_carries_region_metadata() {
+        let text = OTP_TEMPLATE.replace("{#var#}", "482913");
+        let message = service()
+            .prepare(None, OTP_MESSAGE, "+919812345678", text.clone())
+            .unwrap();
+        assert_eq!(message.region.as_deref(), Some("in"));
+        assert_eq!(message.sender_id.as_deref(), Some("AUTHID"));
+        assert_eq!(message.template_id.as_deref(), Some("1107000000000000001"));
+        assert_eq!(message.text, text);
+
+        let message = service()
+            .prepare(None, OTP_MESSAGE, "+14155552671", text)
+            .unwrap();
+        assert_eq!(message.region.as_deref(), Some("us"));
+        assert_eq!(message.campaign_id.as_deref(), Some("CABC123"));
+        assert_eq!(message.template_id, None);
+    }
+
+    #[test]
+    fn test_unregistered_text_refused() {
+        let result = service().prepare(
+            None,
+            OTP_MESSAGE,
+            "+919812345678",
+            "Code: 482913".to_string(),
+        );
+        assert!(matches!(result, Err(DeliveryError::ComplianceViolation(_))));
+
+        let result = service().prepare(None, "alert", "+919812345678", "Hi".to_string());
+        assert!(matches!(result, Err(DeliveryError::ComplianceViolation(_))));
+    }
+
+    #[test]
+    fn test_unrouted_numbers_sent_without_metadata() {
+        let message = service()
+            .prepare(None, OTP_MESSAGE, "+447700900123", "Code: 1".to_string())
+            .unwrap();
+        assert_eq!(
+            message,
+            SmsMessage::new("+447700900123", "Code: 1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tenant_region_replaces_global_and_longest_code_wins() {
+        let tenant = Uuid::new_v4();
+        let mut config = service().config;
+        config.tenants.insert(
+            tenant,
+            HashMap::from([(
+                "in".to_string(),
+                SmsRegionConfig {
+                    sender_id: Some("ACMEIN".to_string()),
+                    ..india()
+                },
+            )]),
+        );
+        config.regions.insert(
+            "ca".to_string(),
+            SmsRegionConfig {
+                calling_codes: vec!["1416".to_string()],
+                ..SmsRegionConfig::default()
+            },
+        );
+        let service = SmsComplianceService::new(config);
+
+        let (_, region) = service.region(Some(tenant), "+919812345678").unwrap();
+        assert_eq!(region.sender_id.as_deref(), Some("ACMEIN"));
+        let (_, region) = service.region(None, "+919812345678").unwrap();
+        assert_eq!(region.sender_id.as_deref(), Some("AUTHID"));
+        assert_eq!(service.region(None, "+14165550100").unwrap().0, "ca");
+        assert_eq!(service.region(None, "+14155552671").unwrap().0, "us");
+    }
+}
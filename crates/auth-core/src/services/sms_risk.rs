@@ -0,0 +1,496 @@
+//! Toll fraud protection for SMS
+//!
+//! Toll fraud requests one-time codes to premium-rate numbers whose revenue
+//! the attacker shares. Before dispatch each destination is scored by its
+//! prefix and by recent volume to its range of numbers. Destinations under
+//! a blocked prefix, or scoring at the block threshold, are refused; those
+//! at the quarantine threshold are queued for manual review and refused
+//! until an operator approves them. The code that put a number in
+//! quarantine is not sent on approval, as it has expired by then.
+//!
+//! Range volume is counted per instance; review decisions are persisted.
+
+use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use crate::error::AuthError;
+use crate::services::otp_delivery::DeliveryError;
+use async_trait::async_trait;
+use auth_config::SmsRiskConfig;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuarantineStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl QuarantineStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuarantineStatus::Pending => "pending",
+            QuarantineStatus::Approved => "approved",
+            QuarantineStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(QuarantineStatus::Pending),
+            "approved" => Some(QuarantineStatus::Approved),
+            "rejected" => Some(QuarantineStatus::Rejected),
+            _ => None,
+        }
+    }
+}
+
+/// A number held for review, for one tenant
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedDestination {
+    pub id: Uuid,
+    pub tenant_id: Option<Uuid>,
+    pub destination: String,
+    pub score: u32,
+    pub reasons: Vec<String>,
+    pub status: QuarantineStatus,
+    /// Messages refused while pending, the first included
+    pub attempts: u32,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait SmsQuarantineStore: Send + Sync {
+    /// The entry for a tenant's destination, whatever its status
+    async fn find(
+        &self,
+        tenant_id: Option<Uuid>,
+        destination: &str,
+    ) -> Result<Option<QuarantinedDestination>, AuthError>;
+    async fn insert(&self, entry: &QuarantinedDestination) -> Result<(), AuthError>;
+    /// Count one more message refused while pending
+    async fn record_attempt(&self, id: Uuid) -> Result<(), AuthError>;
+    /// Newest first
+    async fn list(
+        &self,
+        status: Option<QuarantineStatus>,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<QuarantinedDestination>, AuthError>;
+    /// Record a review decision; `None` if there is no such entry
+    async fn review(
+        &self,
+        id: Uuid,
+        status: QuarantineStatus,
+        reviewed_by: Option<&str>,
+    ) -> Result<Option<QuarantinedDestination>, AuthError>;
+}
+
+#[derive(Default)]
+pub struct InMemorySmsQuarantineStore {
+    entries: DashMap<Uuid, QuarantinedDestination>,
+}
+
+#[async_trait]
+impl SmsQuarantineStore for InMemorySmsQuarantineStore {
+    async fn find(
+        &self,
+        tenant_id: Option<Uuid>,
+        destination: &str,
+    ) -> Result<Option<QuarantinedDestination>, AuthError> {
+        Ok(self
+            .entries
+            .iter()
+            .find(|e| e.tenant_id == tenant_id && e.destination == destination)
+            .map(|e| e.value().clone()))
+    }
+
+    async fn insert(&self, entry: &QuarantinedDestination) -> Result<(), AuthError> {
+        self.entries.insert(entry.id, entry.clone());
+        Ok(())
+    }
+
+    async fn record_attempt(&self, id: Uuid) -> Result<(), AuthError> {
+        if let Some(mut entry) = self.entries.get_mut(&id) {
+            entry.attempts += 1;
+        }
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        status: Option<QuarantineStatus>,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<QuarantinedDestination>, AuthError> {
+        let mut entries: Vec<QuarantinedDestination> = self
+            .entries
+            .iter()
+            .filter(|e| status.is_none() || status == Some(e.status))
+            .filter(|e| tenant_id.is_none() || tenant_id == e.tenant_id)
+            .map(|e| e.value().clone())
+            .collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+        Ok(entries)
+    }
+
+    async fn review(
+        &self,
+        id: Uuid,
+        status: QuarantineStatus,
+        reviewed_by: Option<&str>,
+    ) -> Result<Option<QuarantinedDestination>, AuthError> {
+        Ok(self.entries.get_mut(&id).map(|mut entry| {
+            entry.status = status;
+            entry.reviewed_by = reviewed_by.map(str::to_string);
+            entry.reviewed_at = Some(Utc::now());
+            entry.clone()
+        }))
+    }
+}
+
+/// Score of one destination and what contributed to it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SmsRiskAssessment {
+    pub score: u32,
+    pub reasons: Vec<String>,
+}
+
+/// Messages to one range in the current fixed window
+struct RangeWindow {
+    start: Instant,
+    count: u32,
+}
+
+pub struct SmsRiskService {
+    config: SmsRiskConfig,
+    store: Arc<dyn SmsQuarantineStore>,
+    audit: Option<Arc<dyn AuditLogger>>,
+    ranges: DashMap<String, RangeWindow>,
+}
+
+impl SmsRiskService {
+    pub fn new(config: SmsRiskConfig, store: Arc<dyn SmsQuarantineStore>) -> Self {
+        Self {
+            config,
+            store,
+            audit: None,
+            ranges: DashMap::new(),
+        }
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Refuse SMS to `to` if it is blocked, quarantined or risky enough to
+    /// be either. Every call counts towards the volume of `to`'s range. If
+    /// review decisions cannot be read, numbers below the quarantine
+    /// threshold are still allowed.
+    pub async fn screen(&self, tenant_id: Option<Uuid>, to: &str) -> Result<(), DeliveryError> {
+        let digits = digits(to);
+        if let Some(prefix) = self
+            .config
+            .blocked_prefixes
+            .iter()
+            .find(|prefix| !prefix.is_empty() && digits.starts_with(prefix.as_str()))
+        {
+            let reason = format!("prefix +{} is blocked", prefix);
+            self.audit_refusal(tenant_id, to, "sms.destination_blocked", &reason)
+                .await;
+            return Err(DeliveryError::DestinationBlocked(reason));
+        }
+
+        let assessment = self.assess(&digits, Instant::now());
+        let review = match self.store.find(tenant_id, to).await {
+            Ok(review) => review,
+            Err(e) => {
+                tracing::warn!("SMS quarantine unavailable: {}", e);
+                if assessment.score >= self.config.quarantine_score {
+                    return Err(DeliveryError::DestinationQuarantined);
+                }
+                return Ok(());
+            }
+        };
+
+        match review {
+            Some(entry) => match entry.status {
+                QuarantineStatus::Approved => Ok(()),
+                QuarantineStatus::Rejected => Err(DeliveryError::DestinationBlocked(
+                    "destination rejected on review".to_string(),
+                )),
+                QuarantineStatus::Pending => {
+                    if let Err(e) = self.store.record_attempt(entry.id).await {
+                        tracing::warn!("Failed to count SMS to quarantined number: {}", e);
+                    }
+                    Err(DeliveryError::DestinationQuarantined)
+                }
+            },
+            None if assessment.score >= self.config.block_score => {
+                let reason = assessment.reasons.join("; ");
+                self.audit_refusal(tenant_id, to, "sms.destination_blocked", &reason)
+                    .await;
+                Err(DeliveryError::DestinationBlocked(reason))
+            }
+            None if assessment.score >= self.config.quarantine_score => {
+                self.quarantine(tenant_id, to, assessment).await;
+                Err(DeliveryError::DestinationQuarantined)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Entries in the review queue, newest first
+    pub async fn quarantined(
+        &self,
+        status: Option<QuarantineStatus>,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<QuarantinedDestination>, AuthError> {
+        self.store.list(status, tenant_id).await
+    }
+
+    /// Approve or reject a quarantined number. Approved numbers are sent to
+    /// regardless of their score, unless under a blocked prefix; rejected
+    /// ones are refused from then on.
+    pub async fn review(
+        &self,
+        id: Uuid,
+        approve: bool,
+        reviewed_by: Option<&str>,
+    ) -> Result<Option<QuarantinedDestination>, AuthError> {
+        let status = if approve {
+            QuarantineStatus::Approved
+        } else {
+            QuarantineStatus::Rejected
+        };
+        let entry = self.store.review(id, status, reviewed_by).await?;
+        if let Some(entry) = &entry {
+            let action = if approve {
+                "sms.quarantine_approved"
+            } else {
+                "sms.quarantine_rejected"
+            };
+            self.audit(
+                AuditEvent::new(AuditCategory::Security, action, AuditSeverity::Info)
+                    .with_context(None, None, entry.tenant_id)
+                    .with_metadata(serde_json::json!({
+                        "quarantine_id": entry.id,
+                        "destination": entry.destination,
+                        "reviewed_by": entry.reviewed_by,
+                    })),
+            )
+            .await;
+        }
+        Ok(entry)
+    }
+
+    /// Score the number with E.164 digits `digits`, counting it towards its
+    /// range's volume
+    fn assess(&self, digits: &str, now: Instant) -> SmsRiskAssessment {
+        let mut assessment = SmsRiskAssessment::default();
+
+        if let Some((prefix, score)) = self
+            .config
+            .prefix_scores
+            .iter()
+            .filter(|(prefix, _)| !prefix.is_empty() && digits.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+        {
+            assessment.score += score;
+            assessment
+                .reasons
+                .push(format!("prefix +{} scores {}", prefix, score));
+        }
+
+        let velocity = &self.config.velocity;
+        let range: String = digits.chars().take(velocity.prefix_digits.max(1)).collect();
+        if !range.is_empty() {
+            let window = Duration::from_secs(velocity.window_seconds.max(1));
+            let mut volume = self.ranges.entry(range.clone()).or_insert(RangeWindow {
+                start: now,
+                count: 0,
+            });
+            if now.saturating_duration_since(volume.start) >= window {
+                volume.start = now;
+                volume.count = 0;
+            }
+            volume.count += 1;
+            if volume.count > velocity.max_messages {
+                assessment.score += velocity.score;
+                assessment.reasons.push(format!(
+                    "{} messages to +{} within {}s",
+                    volume.count, range, velocity.window_seconds
+                ));
+            }
+        }
+
+        assessment
+    }
+
+    async fn quarantine(&self, tenant_id: Option<Uuid>, to: &str, assessment: SmsRiskAssessment) {
+        let entry = QuarantinedDestination {
+            id: Uuid::new_v4(),
+            tenant_id,
+            destination: to.to_string(),
+            score: assessment.score,
+            reasons: assessment.reasons,
+            status: QuarantineStatus::Pending,
+            attempts: 1,
+            created_at: Utc::now(),
+            reviewed_by: None,
+            reviewed_at: None,
+        };
+        if let Err(e) = self.store.insert(&entry).await {
+            tracing::error!("Failed to quarantine SMS destination: {}", e);
+        }
+        self.audit_refusal(
+            tenant_id,
+            to,
+            "sms.destination_quarantined",
+            &entry.reasons.join("; "),
+        )
+        .await;
+    }
+
+    async fn audit_refusal(&self, tenant_id: Option<Uuid>, to: &str, action: &str, reason: &str) {
+        tracing::warn!("SMS to {} refused: {}", mask(to), reason);
+        self.audit(
+            AuditEvent::new(AuditCategory::Security, action, AuditSeverity::Warning)
+                .with_context(None, None, tenant_id)
+                .with_metadata(serde_json::json!({
+                    "destination": to,
+                    "reason": reason,
+                })),
+        )
+        .await;
+    }
+
+    async fn audit(&self, event: AuditEvent) {
+        if let Some(audit) = &self.audit {
+            audit.log(event).await;
+        }
+    }
+}
+
+fn digits(to: &str) -> String {
+    to.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+/// Number with all but its last four digits hidden, for logs
+fn mask(to: &str) -> String {
+    let keep = to.len().saturating_sub(4);
+    format!("{}{}", "*".repeat(keep), to.get(keep..).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auth_config::SmsVelocityConfig;
+    use std::collections::HashMap;
+
+    fn service(config: SmsRiskConfig) -> SmsRiskService {
+        SmsRiskService::new(config, Arc::new(InMemorySmsQuarantineStore::default()))
+    }
+
+    #[tokio::test]
+    async fn test_blocked_prefix_refused() {
+        let risk = service(SmsRiskConfig {
+            blocked_prefixes: vec!["882".to_string()],
+            ..SmsRiskConfig::default()
+        });
+
+        assert!(matches!(
+            risk.screen(None, "+88216000001").await,
+            Err(DeliveryError::DestinationBlocked(_))
+        ));
+        assert!(risk.screen(None, "+14155552671").await.is_ok());
+        assert!(risk.quarantined(None, None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_risky_prefix_quarantined_until_approved() {
+        let risk = service(SmsRiskConfig {
+            prefix_scores: HashMap::from([("7".to_string(), 10), ("79".to_string(), 60)]),
+            ..SmsRiskConfig::default()
+        });
+        let tenant = Some(Uuid::new_v4());
+
+        assert!(risk.screen(tenant, "+74951234567").await.is_ok());
+        for _ in 0..2 {
+            assert!(matches!(
+                risk.screen(tenant, "+79161234567").await,
+                Err(DeliveryError::DestinationQuarantined)
+            ));
+        }
+
+        let pending = risk
+            .quarantined(Some(QuarantineStatus::Pending), None)
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].score, 60);
+        assert_eq!(pending[0].attempts, 2);
+
+        risk.review(pending[0].id, true, Some("ops")).await.unwrap();
+        assert!(risk.screen(tenant, "+79161234567").await.is_ok());
+        // Approval is per tenant
+        assert!(risk.screen(None, "+79161234567").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_destination_stays_blocked() {
+        let risk = service(SmsRiskConfig {
+            prefix_scores: HashMap::from([("252".to_string(), 50)]),
+            ..SmsRiskConfig::default()
+        });
+
+        assert!(risk.screen(None, "+252612345678").await.is_err());
+        let id = risk.quarantined(None, None).await.unwrap()[0].id;
+        let entry = risk.review(id, false, None).await.unwrap().unwrap();
+        assert_eq!(entry.status, QuarantineStatus::Rejected);
+
+        assert!(matches!(
+            risk.screen(None, "+252612345678").await,
+            Err(DeliveryError::DestinationBlocked(_))
+        ));
+        assert!(risk
+            .review(Uuid::new_v4(), true, None)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_range_velocity_raises_score() {
+        let risk = service(SmsRiskConfig {
+            velocity: SmsVelocityConfig {
+                prefix_digits: 4,
+                max_messages: 3,
+                score: 50,
+                ..SmsVelocityConfig::default()
+            },
+            ..SmsRiskConfig::default()
+        });
+
+        for n in 0..3 {
+            assert!(risk
+                .screen(None, &format!("+2341000000{}", n))
+                .await
+                .is_ok());
+        }
+        assert!(matches!(
+            risk.screen(None, "+23410000009").await,
+            Err(DeliveryError::DestinationQuarantined)
+        ));
+        // Other ranges are unaffected
+        assert!(risk.screen(None, "+23480000000").await.is_ok());
+
+        let entry = &risk.quarantined(None, None).await.unwrap()[0];
+        assert_eq!(entry.reasons, vec!["4 messages to +2341 within 3600s"]);
+    }
+}
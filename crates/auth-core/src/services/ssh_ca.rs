@@ -0,0 +1,324 @@
+//! SSH certificates for infrastructure access
+//!
+//! Users exchange an SSH public key for a short-lived certificate. Login
+//! principals and extensions come from the roles they hold, and every
+//! request needs a fresh TOTP code on top of the bearer token.
+//!
+//! To rotate the CA, put the new seed first in `ca_keys` and keep the old
+//! one after it until every server's `TrustedUserCAKeys` has the new key.
+
+use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use crate::error::AuthError;
+use crate::models::User;
+use crate::services::authorization::AuthorizationService;
+use crate::services::otp_service::OtpService;
+use auth_config::SshCaConfig;
+use auth_crypto::ssh_ca::{
+    SshCaKey, SshCertificate, SshCertificateAuthority, SshCertificateRequest, SshPublicKey,
+};
+use chrono::{Duration, Utc};
+use secrecy::ExposeSecret;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Backdate certificates slightly so small clock drift on servers does not
+/// reject a certificate that was just issued
+const CLOCK_SKEW_SECONDS: i64 = 60;
+
+pub struct SshCaService {
+    ca: Option<SshCertificateAuthority>,
+    config: SshCaConfig,
+    roles: Arc<AuthorizationService>,
+    otp: OtpService,
+    audit: Option<Arc<dyn AuditLogger>>,
+}
+
+impl SshCaService {
+    /// Fails if the CA is enabled and a configured key cannot be loaded
+    pub fn new(config: SshCaConfig, roles: Arc<AuthorizationService>) -> Result<Self, AuthError> {
+        let ca = if config.enabled {
+            let mut keys = config.ca_keys.iter().map(|seed| {
+                SshCaKey::from_seed_base64(seed.expose_secret()).map_err(|e| {
+                    AuthError::ConfigurationError {
+                        message: e.to_string(),
+                    }
+                })
+            });
+            let active = keys.next().ok_or_else(|| AuthError::ConfigurationError {
+                message: "SSH CA is enabled but no CA key is configured".to_string(),
+            })??;
+            let retired = keys
+                .map(|key| key.map(|k| k.public_key()))
+                .collect::<Result<Vec<_>, _>>()?;
+            Some(SshCertificateAuthority::new(active).with_retired(retired))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            ca,
+            config,
+            roles,
+            otp: OtpService::new(),
+            audit: None,
+        })
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    fn authority(&self) -> Result<&SshCertificateAuthority, AuthError> {
+        self.ca
+            .as_ref()
+            .ok_or_else(|| AuthError::ConfigurationError {
+                message: "SSH certificate authority is not enabled".to_string(),
+            })
+    }
+
+    /// CA public keys in `authorized_keys` format, for `TrustedUserCAKeys`
+    pub fn trusted_keys(&self) -> Result<Vec<String>, AuthError> {
+        Ok(self
+            .authority()?
+            .trusted_keys()
+            .iter()
+            .map(SshPublicKey::to_openssh)
+            .collect())
+    }
+
+    /// Sign `public_key` for `user`. `mfa_code` is the step-up proof and is
+    /// checked against the user's TOTP enrolment.
+    pub async fn sign(
+        &self,
+        user: &User,
+        public_key: &str,
+        mfa_code: Option<&str>,
+        ttl_seconds: Option<u64>,
+        ip_address: Option<String>,
+    ) -> Result<SshCertificate, AuthError> {
+        let ca = self.authority()?;
+        self.verify_step_up(user, mfa_code)?;
+
+        let key = SshPublicKey::parse(public_key).map_err(|e| AuthError::ValidationError {
+            message: e.to_string(),
+        })?;
+
+        let mut request = self.map_roles(user.id, user.tenant_id).await?;
+        let ttl = ttl_seconds
+            .unwrap_or(self.config.default_ttl_seconds)
+            .min(self.config.max_ttl_seconds);
+        let now = Utc::now();
+        request.valid_after = now - Duration::seconds(CLOCK_SKEW_SECONDS);
+        request.valid_before = now + Duration::seconds(ttl as i64);
+        request.key_id = format!("{}:{}", user.tenant_id, user.id);
+
+        let certificate = ca
+            .sign(&key, &request)
+            .map_err(|e| AuthError::ValidationError {
+                message: e.to_string(),
+            })?;
+
+        if let Some(audit) = &self.audit {
+            audit
+                .log(
+                    AuditEvent::new(
+                        AuditCategory::Security,
+                        "ssh.certificate.issued",
+                        AuditSeverity::Info,
+                    )
+                    .with_actor(user.id)
+                    .with_context(ip_address, None, Some(user.tenant_id))
+                    .with_resource(certificate.serial.to_string())
+                    .with_metadata(serde_json::json!({
+                        "serial": certificate.serial,
+                        "key_id": certificate.key_id,
+                        "principals": certificate.principals,
+                        "extensions": request.extensions,
+                        "critical_options": request.critical_options.keys().collect::<Vec<_>>(),
+                        "valid_after": certificate.valid_after,
+                        "valid_before": certificate.valid_before,
+                        "public_key_fingerprint": key.fingerprint(),
+                        "ca_fingerprint": certificate.ca_fingerprint,
+                    })),
+                )
+                .await;
+        }
+
+        Ok(certificate)
+    }
+
+    fn verify_step_up(&self, user: &User, mfa_code: Option<&str>) -> Result<(), AuthError> {
+        let secret = user.mfa_secret.as_deref().filter(|_| user.mfa_enabled);
+        let (Some(secret), Some(code)) = (secret, mfa_code) else {
+            return Err(AuthError::StepUpRequired);
+        };
+        match self.otp.verify_totp(secret, code) {
+            Ok(true) => Ok(()),
+            _ => Err(AuthError::InvalidOtp),
+        }
+    }
+
+    /// Union of the mappings for every role the user holds. Single-valued
+    /// critical options come from the first mapped role, by name, that sets them.
+    async fn map_roles(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+    ) -> Result<SshCertificateRequest, AuthError> {
+        let mut role_names = self.roles.user_role_names(user_id, tenant_id).await?;
+        role_names.sort();
+
+        let mut principals = BTreeSet::new();
+        let mut extensions = BTreeSet::new();
+        let mut critical_options = BTreeMap::new();
+        for mapping in role_names.iter().filter_map(|r| self.config.roles.get(r)) {
+            principals.extend(mapping.principals.iter().cloned());
+            extensions.extend(mapping.extensions.iter().cloned());
+            if let Some(command) = &mapping.force_command {
+                critical_options
+                    .entry("force-command".to_string())
+                    .or_insert_with(|| command.clone());
+            }
+            if let Some(source) = &mapping.source_address {
+                critical_options
+                    .entry("source-address".to_string())
+                    .or_insert_with(|| source.clone());
+            }
+        }
+
+        if principals.is_empty() {
+            return Err(AuthError::AuthorizationDenied {
+                permission: "ssh:sign".to_string(),
+                resource: "ssh_certificate".to_string(),
+            });
+        }
+
+        Ok(SshCertificateRequest {
+            key_id: String::new(),
+            principals: principals.into_iter().collect(),
+            valid_after: Utc::now(),
+            valid_before: Utc::now(),
+            critical_options,
+            extensions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Role;
+    use crate::services::authorization::RoleStore;
+    use async_trait::async_trait;
+    use auth_config::SshRoleMapping;
+
+    struct FixedRoles(Vec<String>);
+
+    #[async_trait]
+    impl RoleStore for FixedRoles {
+        async fn create(&self, role: Role) -> Result<Role, AuthError> {
+            Ok(role)
+        }
+        async fn update(&self, role: Role) -> Result<Role, AuthError> {
+            Ok(role)
+        }
+        async fn delete(&self, _id: Uuid, _tenant_id: Uuid) -> Result<(), AuthError> {
+            Ok(())
+        }
+        async fn find_by_id(&self, _id: Uuid, _tenant_id: Uuid) -> Result<Option<Role>, AuthError> {
+            Ok(None)
+        }
+        async fn list(&self, _tenant_id: Uuid) -> Result<Vec<Role>, AuthError> {
+            Ok(vec![])
+        }
+        async fn assign_permission(&self, _role: Uuid, _permission: Uuid) -> Result<(), AuthError> {
+            Ok(())
+        }
+        async fn find_user_permissions(
+            &self,
+            _user_id: Uuid,
+            _tenant_id: Uuid,
+            _resource_class: &str,
+        ) -> Result<Vec<String>, AuthError> {
+            Ok(vec![])
+        }
+        async fn find_user_role_names(
+            &self,
+            _user_id: Uuid,
+            _tenant_id: Uuid,
+        ) -> Result<Vec<String>, AuthError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn service(held: &[&str]) -> SshCaService {
+        let mut config = SshCaConfig {
+            enabled: true,
+            ca_keys: vec![secrecy::Secret::new(
+                "BwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwc=".to_string(),
+            )],
+            ..SshCaConfig::default()
+        };
+        config.roles.insert(
+            "sre".to_string(),
+            SshRoleMapping {
+                principals: vec!["ops".to_string()],
+                extensions: vec!["permit-pty".to_string()],
+                force_command: None,
+                source_address: Some("10.0.0.0/8".to_string()),
+            },
+        );
+        config.roles.insert(
+            "dba".to_string(),
+            SshRoleMapping {
+                principals: vec!["postgres".to_string(), "ops".to_string()],
+                extensions: vec!["permit-port-forwarding".to_string()],
+                force_command: None,
+                source_address: Some("192.168.0.0/16".to_string()),
+            },
+        );
+
+        let roles = FixedRoles(held.iter().map(|r| r.to_string()).collect());
+        SshCaService::new(config, Arc::new(AuthorizationService::new(Arc::new(roles)))).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_roles_map_to_principals_and_extensions() {
+        let request = service(&["sre", "dba", "viewer"])
+            .map_roles(Uuid::new_v4(), Uuid::new_v4())
+            .await
+            .unwrap();
+
+        assert_eq!(request.principals, vec!["ops", "postgres"]);
+        assert!(request.extensions.contains("permit-pty"));
+        assert!(request.extensions.contains("permit-port-forwarding"));
+        // "dba" sorts first, so its source restriction wins
+        assert_eq!(
+            request
+                .critical_options
+                .get("source-address")
+                .map(String::as_str),
+            Some("192.168.0.0/16")
+        );
+
+        assert!(service(&["viewer"])
+            .map_roles(Uuid::new_v4(), Uuid::new_v4())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_signing_requires_step_up() {
+        let service = service(&["sre"]);
+        let user = User::default();
+        let key = SshCaKey::generate().public_key().to_openssh();
+
+        let err = service
+            .sign(&user, &key, Some("123456"), None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::StepUpRequired));
+    }
+}
@@ -0,0 +1,367 @@
+//! Central SSO session index and logout fan-out
+//!
+//! The IdP keeps one SSO session per browser (see `SessionService`). Every
+//! product that completes `/authorize` against it gets a client session with
+//! its own `sid`; logout ends the SSO session and notifies each product over
+//! the front channel (iframes) and back channel (signed logout tokens).
+
+use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use crate::error::AuthError;
+use crate::models::{ClientLogoutEndpoints, ClientSession, LogoutOutcome, Session};
+use crate::services::id_token::Issuers;
+use crate::services::identity::IdentityService;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// `events` member identifying an OIDC back-channel logout token
+pub const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+/// Logout tokens are only meant to be processed on receipt
+const LOGOUT_TOKEN_TTL_SECS: i64 = 2 * 60;
+
+#[async_trait]
+pub trait SsoSessionStore: Send + Sync {
+    /// Insert, or refresh `last_authenticated_at` if the client already joined
+    async fn upsert_client_session(&self, session: &ClientSession) -> Result<(), AuthError>;
+    async fn active_client_sessions(
+        &self,
+        session_id: Uuid,
+    ) -> Result<Vec<ClientSession>, AuthError>;
+    async fn end_client_sessions(
+        &self,
+        session_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError>;
+    async fn logout_endpoints(
+        &self,
+        client_id: &str,
+    ) -> Result<Option<ClientLogoutEndpoints>, AuthError>;
+}
+
+/// The `sid` a client sees. Derived so each product gets a different value
+/// and none of them learns the SSO session id itself.
+pub fn client_sid(session_id: Uuid, client_id: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(session_id.as_bytes());
+    hasher.update(b":");
+    hasher.update(client_id.as_bytes());
+    URL_SAFE_NO_PAD.encode(hasher.finalize())
+}
+
+pub struct SsoSessionService {
+    store: Arc<dyn SsoSessionStore>,
+    identity: Arc<IdentityService>,
+    issuers: Issuers,
+    client: reqwest::Client,
+    audit: Option<Arc<dyn AuditLogger>>,
+}
+
+impl SsoSessionService {
+    pub fn new(
+        store: Arc<dyn SsoSessionStore>,
+        identity: Arc<IdentityService>,
+        issuers: impl Into<Issuers>,
+        backchannel_timeout: Duration,
+    ) -> Self {
+        Self {
+            store,
+            identity,
+            issuers: issuers.into(),
+            client: reqwest::Client::builder()
+                .timeout(backchannel_timeout)
+                .build()
+                .unwrap_or_default(),
+            audit: None,
+        }
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Record that `client_id` signed in through the SSO session
+    pub async fn join(
+        &self,
+        session: &Session,
+        client_id: &str,
+    ) -> Result<ClientSession, AuthError> {
+        let now = Utc::now();
+        let client_session = ClientSession {
+            sid: client_sid(session.id, client_id),
+            session_id: session.id,
+            client_id: client_id.to_string(),
+            user_id: session.user_id,
+            tenant_id: session.tenant_id,
+            created_at: now,
+            last_authenticated_at: now,
+            ended_at: None,
+        };
+        self.store.upsert_client_session(&client_session).await?;
+        Ok(client_session)
+    }
+
+    /// Products currently signed in through the SSO session
+    pub async fn client_sessions(&self, session_id: Uuid) -> Result<Vec<ClientSession>, AuthError> {
+        self.store.active_client_sessions(session_id).await
+    }
+
+    /// End every client session of the SSO session and notify the products.
+    /// The caller revokes the SSO session itself and clears the cookie.
+    pub async fn logout(&self, session: &Session) -> Result<LogoutOutcome, AuthError> {
+        let clients = self.store.active_client_sessions(session.id).await?;
+        self.store
+            .end_client_sessions(session.id, Utc::now())
+            .await?;
+
+        let mut outcome = LogoutOutcome::default();
+        let mut backchannel = Vec::new();
+        for client_session in &clients {
+            let Some(endpoints) = self
+                .store
+                .logout_endpoints(&client_session.client_id)
+                .await?
+            else {
+                continue;
+            };
+            if let Some(uri) = &endpoints.frontchannel_logout_uri {
+                outcome.frontchannel_uris.push(self.frontchannel_uri(
+                    uri,
+                    endpoints.frontchannel_logout_session_required,
+                    client_session,
+                ));
+            }
+            if let Some(uri) = endpoints.backchannel_logout_uri {
+                backchannel.push(self.notify_backchannel(client_session, uri));
+            }
+        }
+
+        for result in futures::future::join_all(backchannel).await {
+            if let Err(client_id) = result {
+                outcome.backchannel_failures.push(client_id);
+            }
+        }
+
+        self.log(
+            AuditEvent::new(
+                AuditCategory::Authentication,
+                "sso.session.logout",
+                if outcome.backchannel_failures.is_empty() {
+                    AuditSeverity::Info
+                } else {
+                    AuditSeverity::Warning
+                },
+            )
+            .with_actor(session.user_id)
+            .with_context(None, None, Some(session.tenant_id))
+            .with_resource(session.id.to_string())
+            .with_metadata(serde_json::json!({
+                "clients": clients.iter().map(|c| &c.client_id).collect::<Vec<_>>(),
+                "backchannel_failures": &outcome.backchannel_failures,
+            })),
+        )
+        .await;
+
+        Ok(outcome)
+    }
+
+    /// `post_logout_redirect_uri` must be registered by the client exactly
+    pub async fn post_logout_redirect_allowed(
+        &self,
+        client_id: &str,
+        uri: &str,
+    ) -> Result<bool, AuthError> {
+        Ok(self
+            .store
+            .logout_endpoints(client_id)
+            .await?
+            .is_some_and(|e| e.post_logout_redirect_uris.iter().any(|u| u == uri)))
+    }
+
+    fn frontchannel_uri(
+        &self,
+        uri: &str,
+        session_required: bool,
+        client_session: &ClientSession,
+    ) -> String {
+        if !session_required {
+            return uri.to_string();
+        }
+        let Ok(mut url) = url::Url::parse(uri) else {
+            return uri.to_string();
+        };
+        url.query_pairs_mut()
+            .append_pair("iss", self.issuers.for_tenant(client_session.tenant_id))
+            .append_pair("sid", &client_session.sid);
+        url.to_string()
+    }
+
+    /// POST a logout token; Err carries the client id for reporting
+    async fn notify_backchannel(
+        &self,
+        client_session: &ClientSession,
+        uri: String,
+    ) -> Result<(), String> {
+        let failed = |reason: String| {
+            tracing::warn!(
+                client_id = %client_session.client_id,
+                error = %reason,
+                "Back-channel logout failed"
+            );
+            client_session.client_id.clone()
+        };
+
+        let token = self
+            .identity
+            .sign_jwt(self.logout_token_claims(client_session))
+            .await
+            .map_err(|e| failed(e.to_string()))?;
+        let response = self
+            .client
+            .post(&uri)
+            .form(&[("logout_token", token)])
+            .send()
+            .await
+            .map_err(|e| failed(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(failed(format!("HTTP {}", response.status())));
+        }
+        Ok(())
+    }
+
+    fn logout_token_claims(&self, client_session: &ClientSession) -> serde_json::Value {
+        let now = Utc::now().timestamp();
+        serde_json::json!({
+            "iss": self.issuers.for_tenant(client_session.tenant_id),
+            "aud": client_session.client_id,
+            "iat": now,
+            "exp": now + LOGOUT_TOKEN_TTL_SECS,
+            "jti": Uuid::new_v4().to_string(),
+            "sub": client_session.user_id.to_string(),
+            "sid": client_session.sid,
+            "events": { BACKCHANNEL_LOGOUT_EVENT: {} },
+        })
+    }
+
+    async fn log(&self, event: AuditEvent) {
+        if let Some(audit) = &self.audit {
+            audit.log(event).await;
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemorySsoSessionStore {
+    sessions: DashMap<String, ClientSession>,
+    clients: DashMap<String, ClientLogoutEndpoints>,
+}
+
+impl InMemorySsoSessionStore {
+    pub fn register_client(&self, endpoints: ClientLogoutEndpoints) {
+        self.clients.insert(endpoints.client_id.clone(), endpoints);
+    }
+}
+
+#[async_trait]
+impl SsoSessionStore for InMemorySsoSessionStore {
+    async fn upsert_client_session(&self, session: &ClientSession) -> Result<(), AuthError> {
+        self.sessions
+            .entry(session.sid.clone())
+            .and_modify(|s| s.last_authenticated_at = session.last_authenticated_at)
+            .or_insert_with(|| session.clone());
+        Ok(())
+    }
+
+    async fn active_client_sessions(
+        &self,
+        session_id: Uuid,
+    ) -> Result<Vec<ClientSession>, AuthError> {
+        Ok(self
+            .sessions
+            .iter()
+            .filter(|s| s.session_id == session_id && s.ended_at.is_none())
+            .map(|s| s.clone())
+            .collect())
+    }
+
+    async fn end_client_sessions(
+        &self,
+        session_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError> {
+        for mut s in self.sessions.iter_mut() {
+            if s.session_id == session_id && s.ended_at.is_none() {
+                s.ended_at = Some(at);
+            }
+        }
+        Ok(())
+    }
+
+    async fn logout_endpoints(
+        &self,
+        client_id: &str,
+    ) -> Result<Option<ClientLogoutEndpoints>, AuthError> {
+        Ok(self.clients.get(client_id).map(|c| c.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sid_is_stable_per_client() {
+        let session = Uuid::new_v4();
+        assert_eq!(client_sid(session, "app-a"), client_sid(session, "app-a"));
+        assert_ne!(client_sid(session, "app-a"), client_sid(session, "app-b"));
+        assert_ne!(
+            client_sid(session, "app-a"),
+            client_sid(Uuid::new_v4(), "app-a")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_sessions_end_together() {
+        let store = InMemorySsoSessionStore::default();
+        let session_id = Uuid::new_v4();
+        for client_id in ["app-a", "app-b"] {
+            store
+                .upsert_client_session(&ClientSession {
+                    sid: client_sid(session_id, client_id),
+                    session_id,
+                    client_id: client_id.to_string(),
+                    user_id: Uuid::new_v4(),
+                    tenant_id: Uuid::new_v4(),
+                    created_at: Utc::now(),
+                    last_authenticated_at: Utc::now(),
+                    ended_at: None,
+                })
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            store
+                .active_client_sessions(session_id)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+
+        store
+            .end_client_sessions(session_id, Utc::now())
+            .await
+            .unwrap();
+        assert!(store
+            .active_client_sessions(session_id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
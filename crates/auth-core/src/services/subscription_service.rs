@@ -90,6 +90,21 @@ impl SubscriptionService {
         self.store.create(sub).await
     }
 
+    /// Plan of the tenant's subscription, if it is active or trialing
+    pub async fn active_plan(&self, tenant_id: Uuid) -> Result<Option<String>, AuthError> {
+        Ok(self
+            .store
+            .get_by_tenant(tenant_id)
+            .await?
+            .filter(|sub| {
+                matches!(
+                    sub.status,
+                    SubscriptionStatus::Active | SubscriptionStatus::Trialing
+                )
+            })
+            .map(|sub| sub.plan_id))
+    }
+
     pub async fn check_feature_access(
         &self,
         tenant_id: Uuid,
@@ -0,0 +1,769 @@
+//! Account takeover response
+//!
+//! Two signals say someone else may hold a user's credentials: a refresh
+//! token presented again after it was rotated, meaning two parties share the
+//! token family, and a sign-in from further away than anyone could have
+//! travelled since the previous one. [`TakeoverResponder`] answers them with
+//! the playbook configured for the user's tenant, whose actions can sign the
+//! user out everywhere, make their password unusable, email them and the
+//! tenant's administrators, raise a webhook and suspend the account.
+//!
+//! A playbook in dry-run mode records what it would have done and does
+//! nothing, so a tenant can watch one against real traffic before relying on
+//! it. Every response is audited. A user is responded to at most once per
+//! cooldown on each replica, so a stolen client retrying its refresh does
+//! not set off a burst of emails.
+
+use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use crate::error::AuthError;
+use crate::models::UserStatus;
+use crate::services::forced_reauth::{GenerationScope, TokenGenerations};
+use crate::services::identity::UserStore;
+use crate::services::login_link::mask_email;
+use crate::services::otp_delivery::EmailProvider;
+use crate::services::risk_assessment::{RiskAssessment, RiskContext};
+use crate::services::session_service::SessionStore;
+use crate::services::token_service::RefreshTokenStore;
+use async_trait::async_trait;
+use auth_config::{TakeoverAction, TakeoverPlaybook, TakeoverResponseConfig};
+use dashmap::{mapref::entry::Entry, DashMap};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Risk factor of a sign-in too far from the previous one
+const IMPOSSIBLE_TRAVEL_FACTOR: &str = "impossible_travel";
+
+/// Stored in place of the password hash; it is not a PHC string, so no
+/// password verifies against it
+const UNUSABLE_PASSWORD_HASH: &str = "!takeover-reset-required";
+
+/// Cooldown entries kept before expired ones are swept
+const MAX_TRACKED_USERS: usize = 10_000;
+
+/// Why an account is suspected of being taken over
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TakeoverSignal {
+    /// A rotated refresh token of `family_id` was presented again
+    RefreshTokenReuse { family_id: Uuid },
+    /// A sign-in from `ip_address` too far from the previous one
+    ImpossibleTravel {
+        ip_address: Option<String>,
+        description: String,
+    },
+}
+
+impl TakeoverSignal {
+    /// The impossible travel in a sign-in's risk assessment, if any
+    pub fn from_assessment(context: &RiskContext, assessment: &RiskAssessment) -> Option<Self> {
+        let factor = assessment
+            .factors
+            .iter()
+            .find(|f| f.name == IMPOSSIBLE_TRAVEL_FACTOR)?;
+        Some(TakeoverSignal::ImpossibleTravel {
+            ip_address: context.ip_address.clone(),
+            description: factor.description.clone(),
+        })
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TakeoverSignal::RefreshTokenReuse { .. } => "refresh_token_reuse",
+            TakeoverSignal::ImpossibleTravel { .. } => "impossible_travel",
+        }
+    }
+
+    /// For the people notified
+    fn reason(&self) -> String {
+        match self {
+            TakeoverSignal::RefreshTokenReuse { .. } => {
+                "a sign-in token was used again after it had been replaced".to_string()
+            }
+            TakeoverSignal::ImpossibleTravel { description, .. } => {
+                format!("a sign-in from an unexpected location ({})", description)
+            }
+        }
+    }
+}
+
+/// Delivers the `raise_webhook` action's event
+#[async_trait]
+pub trait TakeoverWebhook: Send + Sync {
+    async fn raise(&self, url: &str, payload: Value) -> Result<(), String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionStatus {
+    Done,
+    /// The playbook is in dry-run mode
+    WouldRun,
+    /// Nothing to act on, such as a user without an email address
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionOutcome {
+    pub action: TakeoverAction,
+    pub status: ActionStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl ActionOutcome {
+    fn new(action: TakeoverAction, status: ActionStatus, detail: Option<String>) -> Self {
+        Self {
+            action,
+            status,
+            detail,
+        }
+    }
+}
+
+/// What was done about one signal
+#[derive(Debug, Clone, Serialize)]
+pub struct TakeoverResponse {
+    pub tenant_id: Uuid,
+    pub user_id: Uuid,
+    pub signal: TakeoverSignal,
+    pub playbook: String,
+    pub dry_run: bool,
+    pub actions: Vec<ActionOutcome>,
+}
+
+pub struct TakeoverResponder {
+    config: TakeoverResponseConfig,
+    users: Arc<dyn UserStore>,
+    sessions: Arc<dyn SessionStore>,
+    refresh_tokens: Arc<dyn RefreshTokenStore>,
+    generations: Arc<TokenGenerations>,
+    email: Arc<dyn EmailProvider>,
+    webhook: Option<Arc<dyn TakeoverWebhook>>,
+    audit: Option<Arc<dyn AuditLogger>>,
+    /// When each user was last responded to
+    responded: DashMap<Uuid, Instant>,
+}
+
+impl TakeoverResponder {
+    pub fn new(
+        config: TakeoverResponseConfig,
+        users: Arc<dyn UserStore>,
+        sessions: Arc<dyn SessionStore>,
+        refresh_tokens: Arc<dyn RefreshTokenStore>,
+        generations: Arc<TokenGenerations>,
+        email: Arc<dyn EmailProvider>,
+    ) -> Self {
+        Self {
+            config,
+            users,
+            sessions,
+            refresh_tokens,
+            generations,
+            email,
+            webhook: None,
+            audit: None,
+            responded: DashMap::new(),
+        }
+    }
+
+    /// Without one, `raise_webhook` is skipped
+    pub fn with_webhook(mut self, webhook: Arc<dyn TakeoverWebhook>) -> Self {
+        self.webhook = Some(webhook);
+        self
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// The tenant's playbook and its name
+    pub fn playbook_for(&self, tenant_id: Uuid) -> Option<(&str, &TakeoverPlaybook)> {
+        let name = self
+            .config
+            .tenants
+            .get(&tenant_id.to_string())
+            .and_then(|t| t.playbook.as_ref())
+            .or(self.config.default_playbook.as_ref())?;
+        self.config
+            .playbooks
+            .get(name)
+            .map(|playbook| (name.as_str(), playbook))
+    }
+
+    /// Run the tenant's playbook against a suspected takeover. `None` if the
+    /// response is disabled or the user was responded to within the
+    /// cooldown; a tenant without a playbook gets a response with no
+    /// actions, which is audited all the same.
+    pub async fn respond(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+        signal: TakeoverSignal,
+    ) -> Option<TakeoverResponse> {
+        if !self.config.enabled || !self.claim(user_id) {
+            return None;
+        }
+
+        let (playbook, dry_run, actions) = match self.playbook_for(tenant_id) {
+            Some((name, playbook)) => {
+                let mut actions = Vec::with_capacity(playbook.actions.len());
+                for action in &playbook.actions {
+                    actions.push(if playbook.dry_run {
+                        ActionOutcome::new(*action, ActionStatus::WouldRun, None)
+                    } else {
+                        self.run(*action, tenant_id, user_id, &signal, &playbook.actions)
+                            .await
+                    });
+                }
+                (name.to_string(), playbook.dry_run, actions)
+            }
+            None => (String::new(), false, Vec::new()),
+        };
+        let response = TakeoverResponse {
+            tenant_id,
+            user_id,
+            signal,
+            playbook,
+            dry_run,
+            actions,
+        };
+
+        metrics::counter!(
+            "auth_takeover_responses_total",
+            1,
+            "signal" => response.signal.kind(),
+            "mode" => if dry_run { "dry_run" } else { "live" }
+        );
+        tracing::warn!(
+            tenant_id = %tenant_id,
+            user_id = %user_id,
+            signal = response.signal.kind(),
+            playbook = %response.playbook,
+            dry_run = dry_run,
+            "Responded to suspected account takeover"
+        );
+        if let Some(audit) = &self.audit {
+            audit
+                .log(
+                    AuditEvent::new(
+                        AuditCategory::Security,
+                        "user.takeover_response",
+                        AuditSeverity::Critical,
+                    )
+                    .with_resource(user_id.to_string())
+                    .with_context(None, None, Some(tenant_id))
+                    .with_metadata(serde_json::to_value(&response).unwrap_or_default()),
+                )
+                .await;
+        }
+        Some(response)
+    }
+
+    /// Whether the user is due a response, marking them as responded to
+    fn claim(&self, user_id: Uuid) -> bool {
+        let cooldown = Duration::from_secs(self.config.cooldown_seconds);
+        if self.responded.len() >= MAX_TRACKED_USERS {
+            self.responded.retain(|_, at| at.elapsed() < cooldown);
+        }
+        match self.responded.entry(user_id) {
+            Entry::Occupied(entry) if entry.get().elapsed() < cooldown => false,
+            Entry::Occupied(mut entry) => {
+                entry.insert(Instant::now());
+                true
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(Instant::now());
+                true
+            }
+        }
+    }
+
+    async fn run(
+        &self,
+        action: TakeoverAction,
+        tenant_id: Uuid,
+        user_id: Uuid,
+        signal: &TakeoverSignal,
+        playbook: &[TakeoverAction],
+    ) -> ActionOutcome {
+        let result = match action {
+            TakeoverAction::RevokeSessions => self.revoke_sessions(tenant_id, user_id).await,
+            TakeoverAction::ForcePasswordReset => self.force_password_reset(user_id).await,
+            TakeoverAction::NotifyUser => self.notify_user(user_id, signal).await,
+            TakeoverAction::NotifyTenantAdmin => {
+                self.notify_tenant_admin(tenant_id, user_id, signal, playbook)
+                    .await
+            }
+            TakeoverAction::RaiseWebhook => {
+                self.raise_webhook(tenant_id, user_id, signal, playbook)
+                    .await
+            }
+            TakeoverAction::Quarantine => self
+                .users
+                .update_status(user_id, UserStatus::Suspended)
+                .await
+                .map(|_| (ActionStatus::Done, None)),
+        };
+        match result {
+            Ok((status, detail)) => ActionOutcome::new(action, status, detail),
+            Err(e) => {
+                tracing::error!(
+                    user_id = %user_id,
+                    action = action.as_str(),
+                    "Takeover response action failed: {}",
+                    e
+                );
+                ActionOutcome::new(action, ActionStatus::Failed, Some(e.to_string()))
+            }
+        }
+    }
+
+    async fn revoke_sessions(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<(ActionStatus, Option<String>), AuthError> {
+        // Access tokens first, as in a forced reauthentication
+        self.generations
+            .bump(GenerationScope::User(user_id))
+            .await?;
+        let refresh_tokens = self.refresh_tokens.revoke_user(tenant_id, user_id).await?;
+        self.sessions.delete_by_user(user_id).await?;
+        Ok((
+            ActionStatus::Done,
+            Some(format!("{} refresh tokens revoked", refresh_tokens)),
+        ))
+    }
+
+    async fn force_password_reset(
+        &self,
+        user_id: Uuid,
+    ) -> Result<(ActionStatus, Option<String>), AuthError> {
+        self.users
+            .update_password_hash(user_id, UNUSABLE_PASSWORD_HASH.to_string())
+            .await?;
+        self.generations
+            .bump_user(user_id, "takeover password reset")
+            .await;
+        Ok((ActionStatus::Done, None))
+    }
+
+    async fn notify_user(
+        &self,
+        user_id: Uuid,
+        signal: &TakeoverSignal,
+    ) -> Result<(ActionStatus, Option<String>), AuthError> {
+        let email = self.users.find_by_id(user_id).await?.and_then(|u| u.email);
+        let Some(email) = email else {
+            return Ok((ActionStatus::Skipped, Some("no email address".to_string())));
+        };
+        let reason = signal.reason();
+        let fill = |template: &str| template.replace("{reason}", &reason);
+        self.email
+            .send_email(
+                &email,
+                &fill(&self.config.user_email_subject),
+                &fill(&self.config.user_email_body),
+            )
+            .await
+            .map_err(|e| AuthError::ExternalServiceError {
+                service: "email".to_string(),
+                error: e.to_string(),
+            })?;
+        Ok((ActionStatus::Done, Some(mask_email(&email))))
+    }
+
+    async fn notify_tenant_admin(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+        signal: &TakeoverSignal,
+        playbook: &[TakeoverAction],
+    ) -> Result<(ActionStatus, Option<String>), AuthError> {
+        let recipients = self
+            .config
+            .tenants
+            .get(&tenant_id.to_string())
+            .map(|t| t.admin_emails.as_slice())
+            .unwrap_or_default();
+        if recipients.is_empty() {
+            return Ok((
+                ActionStatus::Skipped,
+                Some("tenant has no admin_emails".to_string()),
+            ));
+        }
+
+        let (user_id, reason) = (user_id.to_string(), signal.reason());
+        let actions = playbook
+            .iter()
+            .map(TakeoverAction::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let fill = |template: &str| {
+            template
+                .replace("{user_id}", &user_id)
+                .replace("{reason}", &reason)
+                .replace("{actions}", &actions)
+        };
+        let (subject, body) = (
+            fill(&self.config.admin_email_subject),
+            fill(&self.config.admin_email_body),
+        );
+        let mut failed = 0;
+        for recipient in recipients {
+            if let Err(e) = self.email.send_email(recipient, &subject, &body).await {
+                tracing::warn!(tenant_id = %tenant_id, "Takeover notice not sent: {}", e);
+                failed += 1;
+            }
+        }
+        if failed == recipients.len() {
+            return Err(AuthError::ExternalServiceError {
+                service: "email".to_string(),
+                error: "no administrator could be emailed".to_string(),
+            });
+        }
+        Ok((
+            ActionStatus::Done,
+            Some(format!(
+                "{} of {} emailed",
+                recipients.len() - failed,
+                recipients.len()
+            )),
+        ))
+    }
+
+    async fn raise_webhook(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+        signal: &TakeoverSignal,
+        playbook: &[TakeoverAction],
+    ) -> Result<(ActionStatus, Option<String>), AuthError> {
+        let url = self
+            .config
+            .tenants
+            .get(&tenant_id.to_string())
+            .and_then(|t| t.webhook_url.as_deref());
+        let (Some(webhook), Some(url)) = (&self.webhook, url) else {
+            return Ok((ActionStatus::Skipped, Some("no webhook_url".to_string())));
+        };
+        let payload = serde_json::json!({
+            "tenant_id": tenant_id,
+            "user_id": user_id,
+            "signal": signal,
+            "actions": playbook,
+        });
+        webhook
+            .raise(url, payload)
+            .await
+            .map_err(|error| AuthError::ExternalServiceError {
+                service: "webhook".to_string(),
+                error,
+            })?;
+        Ok((ActionStatus::Done, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateUserRequest, Session, UpdateUserRequest, User};
+    use crate::services::forced_reauth::InMemoryTokenGenerationStore;
+    use crate::services::otp_delivery::DeliveryError;
+    #[allow(deprecated)]
+    use crate::services::token_service::InMemoryRefreshTokenStore;
+    use auth_config::TakeoverTenantConfig;
+    use parking_lot::Mutex;
+
+    /// One user; records status and password changes
+    struct Users {
+        user: Mutex<User>,
+    }
+
+    #[async_trait]
+    impl UserStore for Users {
+        async fn find_by_email(&self, _: &str, _: Uuid) -> Result<Option<User>, AuthError> {
+            Ok(None)
+        }
+        async fn find_by_phone(&self, _: &str, _: Uuid) -> Result<Option<User>, AuthError> {
+            Ok(None)
+        }
+        async fn find_by_username(&self, _: &str, _: Uuid) -> Result<Option<User>, AuthError> {
+            Ok(None)
+        }
+        async fn find_by_identifier(&self, _: &str, _: Uuid) -> Result<Option<User>, AuthError> {
+            Ok(None)
+        }
+        async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, AuthError> {
+            let user = self.user.lock().clone();
+            Ok((user.id == id).then_some(user))
+        }
+        async fn create(
+            &self,
+            _: CreateUserRequest,
+            _: String,
+            _: Uuid,
+        ) -> Result<User, AuthError> {
+            Err(AuthError::InternalError)
+        }
+        async fn update_status(&self, _: Uuid, status: UserStatus) -> Result<(), AuthError> {
+            self.user.lock().status = status;
+            Ok(())
+        }
+        async fn increment_failed_attempts(&self, _: Uuid) -> Result<u32, AuthError> {
+            Ok(1)
+        }
+        async fn reset_failed_attempts(&self, _: Uuid) -> Result<(), AuthError> {
+            Ok(())
+        }
+        async fn record_login(&self, _: Uuid, _: Option<String>) -> Result<(), AuthError> {
+            Ok(())
+        }
+        async fn update(&self, _: UpdateUserRequest) -> Result<User, AuthError> {
+            Ok(self.user.lock().clone())
+        }
+        async fn update_password_hash(&self, _: Uuid, hash: String) -> Result<(), AuthError> {
+            self.user.lock().password_hash = Some(hash);
+            Ok(())
+        }
+        async fn set_email_verified(&self, _: Uuid, _: bool) -> Result<(), AuthError> {
+            Ok(())
+        }
+        async fn set_phone_verified(&self, _: Uuid, _: bool) -> Result<(), AuthError> {
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Sessions {
+        deleted_for: Mutex<Vec<Uuid>>,
+    }
+
+    #[async_trait]
+    impl SessionStore for Sessions {
+        async fn create(&self, session: Session) -> Result<Session, AuthError> {
+            Ok(session)
+        }
+        async fn get(&self, _: &str) -> Result<Option<Session>, AuthError> {
+            Ok(None)
+        }
+        async fn delete(&self, _: &str) -> Result<(), AuthError> {
+            Ok(())
+        }
+        async fn delete_by_user(&self, user_id: Uuid) -> Result<(), AuthError> {
+            self.deleted_for.lock().push(user_id);
+            Ok(())
+        }
+        async fn delete_by_tenant(&self, _: Uuid) -> Result<u64, AuthError> {
+            Ok(0)
+        }
+    }
+
+    #[derive(Default)]
+    struct Outbox {
+        sent: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl EmailProvider for Outbox {
+        async fn send_email(
+            &self,
+            to: &str,
+            _subject: &str,
+            body: &str,
+        ) -> Result<String, DeliveryError> {
+            self.sent.lock().push((to.to_string(), body.to_string()));
+            Ok("sent".to_string())
+        }
+    }
+
+    struct Fixture {
+        responder: TakeoverResponder,
+        users: Arc<Users>,
+        sessions: Arc<Sessions>,
+        outbox: Arc<Outbox>,
+        generations: Arc<TokenGenerations>,
+        user_id: Uuid,
+        tenant_id: Uuid,
+    }
+
+    #[allow(deprecated)]
+    fn fixture(actions: Vec<TakeoverAction>, dry_run: bool) -> Fixture {
+        let user = User {
+            email: Some("victim@example.com".to_string()),
+            password_hash: Some("$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA".to_string()),
+            status: UserStatus::Active,
+            ..User::default()
+        };
+        let (user_id, tenant_id) = (user.id, user.tenant_id);
+
+        let mut config = TakeoverResponseConfig {
+            enabled: true,
+            default_playbook: Some("contain".to_string()),
+            ..Default::default()
+        };
+        config
+            .playbooks
+            .insert("contain".to_string(), TakeoverPlaybook { actions, dry_run });
+        config.tenants.insert(
+            tenant_id.to_string(),
+            TakeoverTenantConfig {
+                admin_emails: vec!["secops@example.com".to_string()],
+                ..Default::default()
+            },
+        );
+
+        let users = Arc::new(Users {
+            user: Mutex::new(user),
+        });
+        let sessions = Arc::new(Sessions::default());
+        let outbox = Arc::new(Outbox::default());
+        let generations = Arc::new(TokenGenerations::new(
+            Arc::new(InMemoryTokenGenerationStore::default()),
+            Duration::from_secs(60),
+        ));
+        let responder = TakeoverResponder::new(
+            config,
+            users.clone(),
+            sessions.clone(),
+            Arc::new(InMemoryRefreshTokenStore::new(16)),
+            generations.clone(),
+            outbox.clone(),
+        );
+        Fixture {
+            responder,
+            users,
+            sessions,
+            outbox,
+            generations,
+            user_id,
+            tenant_id,
+        }
+    }
+
+    fn reuse() -> TakeoverSignal {
+        TakeoverSignal::RefreshTokenReuse {
+            family_id: Uuid::new_v4(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_playbook_contains_the_account() {
+        let f = fixture(
+            vec![
+                TakeoverAction::RevokeSessions,
+                TakeoverAction::ForcePasswordReset,
+                TakeoverAction::NotifyUser,
+                TakeoverAction::NotifyTenantAdmin,
+                TakeoverAction::RaiseWebhook,
+                TakeoverAction::Quarantine,
+            ],
+            false,
+        );
+
+        let response = f
+            .responder
+            .respond(f.tenant_id, f.user_id, reuse())
+            .await
+            .unwrap();
+        let statuses: Vec<ActionStatus> = response.actions.iter().map(|a| a.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ActionStatus::Done,
+                ActionStatus::Done,
+                ActionStatus::Done,
+                ActionStatus::Done,
+                // No webhook is configured for the tenant
+                ActionStatus::Skipped,
+                ActionStatus::Done,
+            ]
+        );
+
+        let user = f.users.user.lock().clone();
+        assert!(matches!(user.status, UserStatus::Suspended));
+        assert_eq!(user.password_hash.as_deref(), Some(UNUSABLE_PASSWORD_HASH));
+        assert_eq!(*f.sessions.deleted_for.lock(), vec![f.user_id]);
+        assert!(
+            f.generations
+                .current(GenerationScope::User(f.user_id))
+                .await
+                .unwrap()
+                > 0
+        );
+        let recipients: Vec<String> = f.outbox.sent.lock().iter().map(|m| m.0.clone()).collect();
+        assert_eq!(recipients, vec!["victim@example.com", "secops@example.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_changes_nothing() {
+        let f = fixture(
+            vec![TakeoverAction::Quarantine, TakeoverAction::NotifyUser],
+            true,
+        );
+
+        let response = f
+            .responder
+            .respond(f.tenant_id, f.user_id, reuse())
+            .await
+            .unwrap();
+        assert!(response.dry_run);
+        assert!(response
+            .actions
+            .iter()
+            .all(|a| a.status == ActionStatus::WouldRun));
+        assert!(matches!(f.users.user.lock().status, UserStatus::Active));
+        assert!(f.outbox.sent.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_user_is_responded_to_once_per_cooldown() {
+        let f = fixture(vec![TakeoverAction::NotifyUser], false);
+
+        assert!(f
+            .responder
+            .respond(f.tenant_id, f.user_id, reuse())
+            .await
+            .is_some());
+        assert!(f
+            .responder
+            .respond(f.tenant_id, f.user_id, reuse())
+            .await
+            .is_none());
+        assert_eq!(f.outbox.sent.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_playbook_overrides_default() {
+        let mut f = fixture(vec![TakeoverAction::Quarantine], false);
+        f.responder.config.playbooks.insert(
+            "watch".to_string(),
+            TakeoverPlaybook {
+                actions: vec![TakeoverAction::NotifyTenantAdmin],
+                dry_run: false,
+            },
+        );
+        f.responder
+            .config
+            .tenants
+            .get_mut(&f.tenant_id.to_string())
+            .unwrap()
+            .playbook = Some("watch".to_string());
+
+        let (name, _) = f.responder.playbook_for(f.tenant_id).unwrap();
+        assert_eq!(name, "watch");
+        let (name, _) = f.responder.playbook_for(Uuid::new_v4()).unwrap();
+        assert_eq!(name, "contain");
+
+        f.responder
+            .respond(f.tenant_id, f.user_id, reuse())
+            .await
+            .unwrap();
+        assert!(matches!(f.users.user.lock().status, UserStatus::Active));
+    }
+}
@@ -0,0 +1,363 @@
+//! Tenant state for token validation, hydrated lazily and cached
+//!
+//! Every validated access token is checked against its tenant's status.
+//! The state is loaded from the store the first time a tenant is seen and
+//! then served from memory for the TTL; concurrent misses for one tenant
+//! share a single load. Each change to a tenant bumps its version and is
+//! published as [`DomainEvent::TenantChanged`]. An instance drops state
+//! cached at an older version when the event arrives, and does not cache
+//! a load older than the newest version it has seen, which would be a
+//! read that raced the change. The TTL only bounds staleness when an event
+//! is lost.
+
+use crate::error::AuthError;
+use crate::events::{DomainEvent, EventBus};
+use crate::models::tenant::TenantStatus;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// What validation needs to know about a tenant
+#[derive(Debug, Clone)]
+pub struct TenantState {
+    pub tenant_id: Uuid,
+    pub status: TenantStatus,
+    /// Region whose database holds the tenant's rows
+    pub data_region: Option<String>,
+    pub auth_config: serde_json::Value,
+    /// Bumped on every change to the tenant
+    pub version: u64,
+}
+
+impl TenantState {
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, TenantStatus::Active)
+    }
+}
+
+#[async_trait]
+pub trait TenantStateStore: Send + Sync {
+    async fn load(&self, tenant_id: Uuid) -> Result<Option<TenantState>, AuthError>;
+    /// Increment the tenant's version and return the new one
+    async fn bump_version(&self, tenant_id: Uuid) -> Result<u64, AuthError>;
+}
+
+/// Store for tests and single-node development
+#[derive(Default)]
+pub struct InMemoryTenantStateStore {
+    tenants: DashMap<Uuid, TenantState>,
+}
+
+impl InMemoryTenantStateStore {
+    pub fn insert(&self, state: TenantState) {
+        self.tenants.insert(state.tenant_id, state);
+    }
+
+    pub fn set_status(&self, tenant_id: Uuid, status: TenantStatus) {
+        if let Some(mut state) = self.tenants.get_mut(&tenant_id) {
+            state.status = status;
+        }
+    }
+}
+
+#[async_trait]
+impl TenantStateStore for InMemoryTenantStateStore {
+    async fn load(&self, tenant_id: Uuid) -> Result<Option<TenantState>, AuthError> {
+        Ok(self.tenants.get(&tenant_id).map(|state| state.clone()))
+    }
+
+    async fn bump_version(&self, tenant_id: Uuid) -> Result<u64, AuthError> {
+        let mut state =
+            self.tenants
+                .get_mut(&tenant_id)
+                .ok_or_else(|| AuthError::TenantNotFound {
+                    tenant_id: tenant_id.to_string(),
+                })?;
+        state.version += 1;
+        Ok(state.version)
+    }
+}
+
+/// `None` caches a tenant that does not exist, so tokens naming one do
+/// not reach the store on every request either
+struct Entry {
+    state: Option<Arc<TenantState>>,
+    fetched_at: Instant,
+}
+
+#[derive(Default)]
+struct ContextEntries {
+    entries: DashMap<Uuid, Entry>,
+    /// Newest version each tenant has been announced at
+    versions: DashMap<Uuid, u64>,
+}
+
+impl ContextEntries {
+    fn fresh(&self, tenant_id: Uuid, ttl: Duration) -> Option<Option<Arc<TenantState>>> {
+        let entry = self.entries.get(&tenant_id)?;
+        (entry.fetched_at.elapsed() < ttl).then(|| entry.state.clone())
+    }
+
+    /// Whether the loaded state was cached; a state older than an
+    /// announced version is returned to the caller but not kept
+    fn insert(&self, tenant_id: Uuid, state: Option<Arc<TenantState>>) -> bool {
+        let announced = self.versions.get(&tenant_id).map_or(0, |v| *v);
+        if state.as_ref().is_some_and(|s| s.version < announced) {
+            return false;
+        }
+        self.entries.insert(
+            tenant_id,
+            Entry {
+                state,
+                fetched_at: Instant::now(),
+            },
+        );
+        true
+    }
+
+    fn apply(&self, event: &DomainEvent) {
+        if let DomainEvent::TenantChanged { tenant_id, version } = event {
+            self.versions
+                .entry(*tenant_id)
+                .and_modify(|v| *v = (*v).max(*version))
+                .or_insert(*version);
+            self.entries.remove_if(tenant_id, |_, entry| {
+                entry.state.as_ref().is_none_or(|s| s.version < *version)
+            });
+        }
+    }
+
+    fn clear(&self) {
+        self.entries.clear();
+    }
+}
+
+pub struct TenantContexts {
+    store: Arc<dyn TenantStateStore>,
+    cache: Arc<ContextEntries>,
+    /// Loads in flight, so that a burst of misses for one tenant waits on
+    /// a single load
+    hydrating: DashMap<Uuid, Arc<tokio::sync::Mutex<()>>>,
+    ttl: Duration,
+    events: Option<Arc<EventBus>>,
+}
+
+impl TenantContexts {
+    pub fn new(store: Arc<dyn TenantStateStore>, ttl: Duration) -> Self {
+        Self {
+            store,
+            cache: Arc::new(ContextEntries::default()),
+            hydrating: DashMap::new(),
+            ttl,
+            events: None,
+        }
+    }
+
+    /// Publish tenant changes on `bus` and drop stale state from it,
+    /// including changes made on other nodes
+    pub fn with_events(mut self, bus: Arc<EventBus>) -> Self {
+        spawn_invalidation_listener(self.cache.clone(), &bus);
+        self.events = Some(bus);
+        self
+    }
+
+    /// The tenant's state, from the cache when fresh; `None` if there is
+    /// no such tenant
+    pub async fn get(&self, tenant_id: Uuid) -> Result<Option<Arc<TenantState>>, AuthError> {
+        if let Some(state) = self.cache.fresh(tenant_id, self.ttl) {
+            return Ok(state);
+        }
+
+        let lock = self.hydrating.entry(tenant_id).or_default().clone();
+        let _loading = lock.lock().await;
+        // Hydrated by the request this one waited on
+        if let Some(state) = self.cache.fresh(tenant_id, self.ttl) {
+            return Ok(state);
+        }
+        let state = match self.store.load(tenant_id).await {
+            Ok(state) => state.map(Arc::new),
+            Err(e) => {
+                self.hydrating.remove(&tenant_id);
+                return Err(e);
+            }
+        };
+        metrics::counter!("auth_tenant_context_loads_total", 1);
+        if !self.cache.insert(tenant_id, state.clone()) {
+            tracing::debug!(tenant_id = %tenant_id, "Loaded tenant state is older than announced");
+        }
+        // Only once cached, so a request arriving now finds it
+        self.hydrating.remove(&tenant_id);
+        Ok(state)
+    }
+
+    /// The tenant's state, failing unless the tenant exists and is active
+    pub async fn require_active(&self, tenant_id: Uuid) -> Result<Arc<TenantState>, AuthError> {
+        match self.get(tenant_id).await? {
+            Some(state) if state.is_active() => Ok(state),
+            Some(_) => Err(AuthError::Unauthorized {
+                message: "Tenant is not active".to_string(),
+            }),
+            None => Err(AuthError::Unauthorized {
+                message: "Unknown tenant".to_string(),
+            }),
+        }
+    }
+
+    /// Record that the tenant's status or settings changed: bump its
+    /// version, drop the local entry and tell the other instances
+    pub async fn changed(&self, tenant_id: Uuid) -> Result<u64, AuthError> {
+        let version = self.store.bump_version(tenant_id).await?;
+        let event = DomainEvent::TenantChanged { tenant_id, version };
+        self.cache.apply(&event);
+        if let Some(bus) = &self.events {
+            bus.publish(event).await;
+        }
+        Ok(version)
+    }
+}
+
+fn spawn_invalidation_listener(cache: Arc<ContextEntries>, bus: &EventBus) -> JoinHandle<()> {
+    let mut rx = bus.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => cache.apply(&event),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(
+                        "Tenant context cache missed {} invalidation events; clearing",
+                        skipped
+                    );
+                    cache.clear();
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tenant(version: u64) -> TenantState {
+        TenantState {
+            tenant_id: Uuid::new_v4(),
+            status: TenantStatus::Active,
+            data_region: None,
+            auth_config: serde_json::Value::Null,
+            version,
+        }
+    }
+
+    /// Counts loads
+    struct CountingStore {
+        inner: InMemoryTenantStateStore,
+        loads: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TenantStateStore for CountingStore {
+        async fn load(&self, tenant_id: Uuid) -> Result<Option<TenantState>, AuthError> {
+            self.loads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tokio::task::yield_now().await;
+            self.inner.load(tenant_id).await
+        }
+
+        async fn bump_version(&self, tenant_id: Uuid) -> Result<u64, AuthError> {
+            self.inner.bump_version(tenant_id).await
+        }
+    }
+
+    fn counting() -> Arc<CountingStore> {
+        Arc::new(CountingStore {
+            inner: InMemoryTenantStateStore::default(),
+            loads: Default::default(),
+        })
+    }
+
+    fn loads(store: &CountingStore) -> usize {
+        store.loads.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_state_is_loaded_once_and_served_from_cache() {
+        let store = counting();
+        let state = tenant(0);
+        let tenant_id = state.tenant_id;
+        store.inner.insert(state);
+        let contexts = Arc::new(TenantContexts::new(store.clone(), Duration::from_secs(60)));
+
+        let lookups: Vec<_> = (0..8)
+            .map(|_| {
+                let contexts = contexts.clone();
+                tokio::spawn(async move { contexts.require_active(tenant_id).await })
+            })
+            .collect();
+        for lookup in lookups {
+            assert!(lookup.await.unwrap().is_ok());
+        }
+        assert_eq!(loads(&store), 1);
+
+        // Unknown tenants are cached too
+        let unknown = Uuid::new_v4();
+        assert!(contexts.require_active(unknown).await.is_err());
+        assert!(contexts.require_active(unknown).await.is_err());
+        assert_eq!(loads(&store), 2);
+    }
+
+    #[tokio::test]
+    async fn test_change_invalidates_older_state() {
+        let store = counting();
+        let state = tenant(0);
+        let tenant_id = state.tenant_id;
+        store.inner.insert(state);
+        let bus = Arc::new(EventBus::new());
+        let contexts =
+            TenantContexts::new(store.clone(), Duration::from_secs(60)).with_events(bus.clone());
+        let mut rx = bus.subscribe();
+
+        assert!(contexts.require_active(tenant_id).await.is_ok());
+        store.inner.set_status(tenant_id, TenantStatus::Suspended);
+        assert_eq!(contexts.changed(tenant_id).await.unwrap(), 1);
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            DomainEvent::TenantChanged {
+                tenant_id,
+                version: 1
+            }
+        );
+
+        assert!(contexts.require_active(tenant_id).await.is_err());
+        assert_eq!(loads(&store), 2);
+    }
+
+    #[test]
+    fn test_load_older_than_announced_version_is_not_cached() {
+        let cache = ContextEntries::default();
+        let state = tenant(3);
+        let tenant_id = state.tenant_id;
+
+        cache.apply(&DomainEvent::TenantChanged {
+            tenant_id,
+            version: 4,
+        });
+        assert!(!cache.insert(tenant_id, Some(Arc::new(state.clone()))));
+        assert!(cache.fresh(tenant_id, Duration::from_secs(60)).is_none());
+
+        // A late event for an older version keeps newer state
+        let newer = TenantState {
+            version: 5,
+            ..state
+        };
+        assert!(cache.insert(tenant_id, Some(Arc::new(newer))));
+        cache.apply(&DomainEvent::TenantChanged {
+            tenant_id,
+            version: 4,
+        });
+        assert!(cache.fresh(tenant_id, Duration::from_secs(60)).is_some());
+    }
+}
@@ -0,0 +1,421 @@
+//! Per-tenant usage metrics, exact and as disclosed to the tenant
+//!
+//! Counts cover the last `window_days` full UTC days, so they are computed
+//! once per tenant and day. What a tenant's administrators are shown goes
+//! through the disclosure settings of the metric's class: Laplace noise,
+//! then withholding of values under the class threshold, then rounding.
+//! The noise is derived from the noise key, tenant, metric and day rather
+//! than drawn per request, so repeating a request cannot average it away.
+//! Exact counts are only served on the admin listener.
+
+use crate::error::AuthError;
+use async_trait::async_trait;
+use auth_config::{MetricDisclosure, MetricNoise, TenantMetricsConfig};
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricClass {
+    Population,
+    Activity,
+    Security,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TenantMetric {
+    /// Users not deleted
+    Users,
+    /// Users with MFA enabled
+    MfaUsers,
+    /// Users with a successful sign-in in the window
+    ActiveUsers,
+    SuccessfulLogins,
+    SessionsCreated,
+    FailedLogins,
+    /// Users locked out at the end of the window
+    LockedUsers,
+}
+
+impl TenantMetric {
+    pub const ALL: [TenantMetric; 7] = [
+        TenantMetric::Users,
+        TenantMetric::MfaUsers,
+        TenantMetric::ActiveUsers,
+        TenantMetric::SuccessfulLogins,
+        TenantMetric::SessionsCreated,
+        TenantMetric::FailedLogins,
+        TenantMetric::LockedUsers,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TenantMetric::Users => "users",
+            TenantMetric::MfaUsers => "mfa_users",
+            TenantMetric::ActiveUsers => "active_users",
+            TenantMetric::SuccessfulLogins => "successful_logins",
+            TenantMetric::SessionsCreated => "sessions_created",
+            TenantMetric::FailedLogins => "failed_logins",
+            TenantMetric::LockedUsers => "locked_users",
+        }
+    }
+
+    pub fn class(&self) -> MetricClass {
+        match self {
+            TenantMetric::Users | TenantMetric::MfaUsers | TenantMetric::ActiveUsers => {
+                MetricClass::Population
+            }
+            TenantMetric::SuccessfulLogins | TenantMetric::SessionsCreated => MetricClass::Activity,
+            TenantMetric::FailedLogins | TenantMetric::LockedUsers => MetricClass::Security,
+        }
+    }
+}
+
+/// Exact counts of a tenant over a window
+#[derive(Debug, Clone, Serialize)]
+pub struct ExactTenantMetrics {
+    pub tenant_id: Uuid,
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub counts: BTreeMap<TenantMetric, u64>,
+}
+
+/// A metric as shown to the tenant
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct DisclosedMetric {
+    pub metric: TenantMetric,
+    pub class: MetricClass,
+    /// `None` when the value is too small to be shown
+    pub value: Option<u64>,
+    /// The value includes random noise
+    pub noised: bool,
+    /// The value is rounded to a multiple of this
+    pub rounded_to: u64,
+}
+
+/// Metrics of a tenant as shown to the tenant
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct TenantMetricsReport {
+    pub tenant_id: Uuid,
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub metrics: Vec<DisclosedMetric>,
+}
+
+#[async_trait]
+pub trait TenantMetricsStore: Send + Sync {
+    /// Every metric of the tenant; event counts cover `[since, until)`
+    async fn counts(
+        &self,
+        tenant_id: Uuid,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<BTreeMap<TenantMetric, u64>, AuthError>;
+}
+
+pub struct TenantMetricsService {
+    store: Arc<dyn TenantMetricsStore>,
+    config: TenantMetricsConfig,
+    noise_key: Vec<u8>,
+    /// Counts of the current window per tenant
+    cache: DashMap<Uuid, ExactTenantMetrics>,
+}
+
+impl TenantMetricsService {
+    pub fn new(store: Arc<dyn TenantMetricsStore>, config: TenantMetricsConfig) -> Self {
+        let noise_key = match &config.noise_key {
+            Some(key) => key.expose_secret().as_bytes().to_vec(),
+            None => rand::random::<[u8; 32]>().to_vec(),
+        };
+        Self {
+            store,
+            config,
+            noise_key,
+            cache: DashMap::new(),
+        }
+    }
+
+    /// The last `window_days` full UTC days before `now`
+    fn window(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let until = now
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .map(|midnight| midnight.and_utc())
+            .unwrap_or(now);
+        (
+            until - Duration::days(self.config.window_days as i64),
+            until,
+        )
+    }
+
+    /// Exact counts, for internal use only
+    pub async fn exact(
+        &self,
+        tenant_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<ExactTenantMetrics, AuthError> {
+        let (since, until) = self.window(now);
+        if let Some(cached) = self.cache.get(&tenant_id) {
+            if cached.until == until {
+                return Ok(cached.clone());
+            }
+        }
+
+        let counts = self.store.counts(tenant_id, since, until).await?;
+        let metrics = ExactTenantMetrics {
+            tenant_id,
+            since,
+            until,
+            counts,
+        };
+        // Windows of earlier days are not asked for again
+        self.cache.retain(|_, m| m.until == until);
+        self.cache.insert(tenant_id, metrics.clone());
+        Ok(metrics)
+    }
+
+    /// Counts with the disclosure settings of each metric's class applied
+    pub async fn report(
+        &self,
+        tenant_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<TenantMetricsReport, AuthError> {
+        let exact = self.exact(tenant_id, now).await?;
+        let metrics = TenantMetric::ALL
+            .iter()
+            .map(|&metric| {
+                let disclosure = self.disclosure(metric.class());
+                let count = exact.counts.get(&metric).copied().unwrap_or(0);
+                let draw = self.noise_draw(tenant_id, metric, exact.until);
+                DisclosedMetric {
+                    metric,
+                    class: metric.class(),
+                    value: disclose(count, disclosure, draw),
+                    noised: disclosure.noise != MetricNoise::None,
+                    rounded_to: disclosure.round_to.max(1),
+                }
+            })
+            .collect();
+        Ok(TenantMetricsReport {
+            tenant_id,
+            since: exact.since,
+            until: exact.until,
+            metrics,
+        })
+    }
+
+    fn disclosure(&self, class: MetricClass) -> &MetricDisclosure {
+        match class {
+            MetricClass::Population => &self.config.population,
+            MetricClass::Activity => &self.config.activity,
+            MetricClass::Security => &self.config.security,
+        }
+    }
+
+    /// Uniform in (0, 1), fixed for a tenant, metric and window
+    fn noise_draw(&self, tenant_id: Uuid, metric: TenantMetric, until: DateTime<Utc>) -> f64 {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.noise_key);
+        hasher.update(tenant_id.as_bytes());
+        hasher.update(metric.as_str().as_bytes());
+        hasher.update(until.timestamp().to_be_bytes());
+        let digest = hasher.finalize();
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        ((u64::from_be_bytes(bytes) >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+}
+
+/// Noise, then threshold, then rounding. Thresholding the noised value
+/// keeps the exact count from deciding alone whether a value is shown.
+fn disclose(count: u64, disclosure: &MetricDisclosure, draw: f64) -> Option<u64> {
+    let mut value = count as f64;
+    if let MetricNoise::Laplace { epsilon } = disclosure.noise {
+        value += laplace(1.0 / epsilon, draw);
+    }
+    if value < disclosure.min_count as f64 {
+        return None;
+    }
+    let step = disclosure.round_to.max(1) as f64;
+    Some(((value / step).round() * step).max(0.0) as u64)
+}
+
+/// Laplace(0, scale) by inverting its CDF at `draw`
+fn laplace(scale: f64, draw: f64) -> f64 {
+    let centered = draw - 0.5;
+    -scale * centered.signum() * (1.0 - 2.0 * centered.abs()).ln()
+}
+
+/// Store for tests and single-node development
+#[derive(Default)]
+pub struct InMemoryTenantMetricsStore {
+    counts: parking_lot::Mutex<BTreeMap<Uuid, BTreeMap<TenantMetric, u64>>>,
+}
+
+impl InMemoryTenantMetricsStore {
+    pub fn set(&self, tenant_id: Uuid, metric: TenantMetric, value: u64) {
+        self.counts
+            .lock()
+            .entry(tenant_id)
+            .or_default()
+            .insert(metric, value);
+    }
+}
+
+#[async_trait]
+impl TenantMetricsStore for InMemoryTenantMetricsStore {
+    async fn counts(
+        &self,
+        tenant_id: Uuid,
+        _since: DateTime<Utc>,
+        _until: DateTime<Utc>,
+    ) -> Result<BTreeMap<TenantMetric, u64>, AuthError> {
+        Ok(self
+            .counts
+            .lock()
+            .get(&tenant_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn metrics_service(
+        config: TenantMetricsConfig,
+    ) -> (Arc<InMemoryTenantMetricsStore>, TenantMetricsService) {
+        let store = Arc::new(InMemoryTenantMetricsStore::default());
+        let service = TenantMetricsService::new(store.clone(), config);
+        (store, service)
+    }
+
+    fn value(report: &TenantMetricsReport, metric: TenantMetric) -> Option<u64> {
+        report
+            .metrics
+            .iter()
+            .find(|m| m.metric == metric)
+            .and_then(|m| m.value)
+    }
+
+    #[tokio::test]
+    async fn test_small_values_are_withheld_and_rounded() {
+        let config = TenantMetricsConfig {
+            security: MetricDisclosure {
+                min_count: 20,
+                noise: MetricNoise::None,
+                round_to: 5,
+            },
+            ..Default::default()
+        };
+        let (store, service) = metrics_service(config);
+        let tenant = Uuid::new_v4();
+        store.set(tenant, TenantMetric::Users, 4);
+        store.set(tenant, TenantMetric::SuccessfulLogins, 1234);
+        store.set(tenant, TenantMetric::FailedLogins, 19);
+        store.set(tenant, TenantMetric::LockedUsers, 22);
+
+        let now = Utc.with_ymd_and_hms(2026, 3, 2, 15, 30, 0).unwrap();
+        let report = service.report(tenant, now).await.unwrap();
+        assert_eq!(
+            report.until,
+            Utc.with_ymd_and_hms(2026, 3, 2, 0, 0, 0).unwrap()
+        );
+        assert_eq!(value(&report, TenantMetric::Users), None);
+        assert_eq!(value(&report, TenantMetric::SuccessfulLogins), Some(1230));
+        assert_eq!(value(&report, TenantMetric::FailedLogins), None);
+        assert_eq!(value(&report, TenantMetric::LockedUsers), Some(20));
+
+        // Exact values stay available internally
+        let exact = service.exact(tenant, now).await.unwrap();
+        assert_eq!(exact.counts[&TenantMetric::Users], 4);
+    }
+
+    #[tokio::test]
+    async fn test_noise_is_fixed_per_day() {
+        let noisy = MetricDisclosure {
+            min_count: 0,
+            noise: MetricNoise::Laplace { epsilon: 0.1 },
+            round_to: 1,
+        };
+        let config = TenantMetricsConfig {
+            noise_key: Some(secrecy::Secret::new("key".to_string())),
+            population: noisy,
+            activity: noisy,
+            security: noisy,
+            ..Default::default()
+        };
+        let (store, service) = metrics_service(config.clone());
+        let tenant = Uuid::new_v4();
+        for metric in TenantMetric::ALL {
+            store.set(tenant, metric, 1000);
+        }
+
+        let day = Utc.with_ymd_and_hms(2026, 3, 2, 9, 0, 0).unwrap();
+        let first = service.report(tenant, day).await.unwrap();
+        let again = service
+            .report(tenant, day + Duration::hours(8))
+            .await
+            .unwrap();
+        let values =
+            |r: &TenantMetricsReport| r.metrics.iter().map(|m| m.value).collect::<Vec<_>>();
+        assert_eq!(values(&first), values(&again));
+        assert!(values(&first).iter().any(|v| *v != Some(1000)));
+
+        // Another replica with the same key agrees
+        let (other_store, other) = metrics_service(config);
+        for metric in TenantMetric::ALL {
+            other_store.set(tenant, metric, 1000);
+        }
+        assert_eq!(
+            values(&other.report(tenant, day).await.unwrap()),
+            values(&first)
+        );
+
+        let next_day = service
+            .report(tenant, day + Duration::days(1))
+            .await
+            .unwrap();
+        assert_ne!(values(&next_day), values(&first));
+    }
+
+    #[test]
+    fn test_laplace_is_centered() {
+        assert_eq!(laplace(2.0, 0.5), 0.0);
+        assert!(laplace(2.0, 0.25) < 0.0);
+        assert!((laplace(2.0, 0.75) - 2.0 * 2f64.ln()).abs() < 1e-9);
+    }
+}
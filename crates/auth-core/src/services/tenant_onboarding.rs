@@ -0,0 +1,1161 @@
+//! Guided setup of a new tenant
+//!
+//! Tenant administrators configure domains, sign-in providers, policies and
+//! branding, in that order, then review and finish. The setup runs on the
+//! [`WorkflowEngine`]: each step is a flow state whose handler checks and
+//! records that step's settings, and the flow context is saved after every
+//! action so an unfinished setup is picked up where it was left. Nothing
+//! reaches the tenant before `finish`, which checks every step again and
+//! writes all settings at once.
+//!
+//! `send_test_email` and `test_sso` can be run at any step. They do not move
+//! the setup on; the latest result of each is kept for the checklist.
+
+use crate::error::AuthError;
+use crate::services::login_link::mask_email;
+use crate::services::otp_delivery::EmailProvider;
+use crate::services::tenant_context::TenantContexts;
+use crate::services::workflow::{FlowAction, FlowContext, FlowState, StepHandler, WorkflowEngine};
+use async_trait::async_trait;
+use auth_platform::{EgressConfig, EgressPolicy, HttpClient, HttpClientConfig, HttpClientError};
+use chrono::{DateTime, Utc};
+use dashmap::{mapref::entry::Entry, DashMap};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+use url::Url;
+use uuid::Uuid;
+
+pub const FLOW_TYPE: &str = "tenant_onboarding";
+
+/// Context keys besides the per-step settings
+const SKIPPED_KEY: &str = "skipped";
+const TESTS_KEY: &str = "tests";
+
+const MAX_HOSTS: usize = 10;
+const MAX_PROVIDERS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardingStep {
+    Domains,
+    Providers,
+    Policies,
+    Branding,
+    Review,
+}
+
+impl OnboardingStep {
+    pub const ALL: [OnboardingStep; 5] = [
+        OnboardingStep::Domains,
+        OnboardingStep::Providers,
+        OnboardingStep::Policies,
+        OnboardingStep::Branding,
+        OnboardingStep::Review,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OnboardingStep::Domains => "domains",
+            OnboardingStep::Providers => "providers",
+            OnboardingStep::Policies => "policies",
+            OnboardingStep::Branding => "branding",
+            OnboardingStep::Review => "review",
+        }
+    }
+
+    /// Whether the step may be skipped. Without domains the tenant is
+    /// served on the shared host, without providers users sign in with a
+    /// password, and without branding they see the default look.
+    pub fn optional(&self) -> bool {
+        matches!(
+            self,
+            OnboardingStep::Domains | OnboardingStep::Providers | OnboardingStep::Branding
+        )
+    }
+
+    fn state(&self) -> FlowState {
+        FlowState::Custom(format!("onboarding.{}", self.as_str()))
+    }
+
+    fn from_state(state: &FlowState) -> Option<Self> {
+        let FlowState::Custom(name) = state else {
+            return None;
+        };
+        let name = name.strip_prefix("onboarding.")?;
+        Self::ALL.into_iter().find(|step| step.as_str() == name)
+    }
+
+    fn position(&self) -> usize {
+        Self::ALL.iter().position(|step| step == self).unwrap_or(0)
+    }
+
+    fn next(&self) -> Option<Self> {
+        Self::ALL.get(self.position() + 1).copied()
+    }
+
+    fn previous(&self) -> Option<Self> {
+        self.position()
+            .checked_sub(1)
+            .and_then(|i| Self::ALL.get(i).copied())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DomainSettings {
+    /// Host names users sign in on; the first becomes the custom domain
+    pub hosts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct IdentityProviderSettings {
+    /// Unique in the tenant, e.g. `okta`
+    pub id: String,
+    /// OpenID Connect issuer; `test_sso` reads its discovery document
+    pub issuer: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ProviderSettings {
+    pub providers: Vec<IdentityProviderSettings>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct PolicySettings {
+    pub password_min_length: u32,
+    pub mfa_required: bool,
+    pub session_lifetime_minutes: u32,
+    /// Email domains allowed to register; any when empty
+    #[serde(default)]
+    pub allowed_email_domains: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct BrandingSettings {
+    pub display_name: String,
+    /// HTTPS URL of the logo
+    pub logo_url: Option<String>,
+    /// `#rrggbb`
+    pub primary_color: Option<String>,
+    pub support_email: Option<String>,
+}
+
+/// Settings a step records, with its checks
+trait StepSettings: Serialize + DeserializeOwned {
+    fn check(&self) -> Result<(), String>;
+}
+
+impl StepSettings for DomainSettings {
+    fn check(&self) -> Result<(), String> {
+        if self.hosts.is_empty() {
+            return Err(
+                "at least one host is required; skip the step to use the shared host".into(),
+            );
+        }
+        if self.hosts.len() > MAX_HOSTS {
+            return Err(format!("at most {} hosts are allowed", MAX_HOSTS));
+        }
+        let mut seen = HashSet::new();
+        for host in &self.hosts {
+            check_host(host)?;
+            if !seen.insert(host) {
+                return Err(format!("host {} is listed twice", host));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StepSettings for ProviderSettings {
+    fn check(&self) -> Result<(), String> {
+        if self.providers.is_empty() {
+            return Err(
+                "at least one provider is required; skip the step to use passwords only".into(),
+            );
+        }
+        if self.providers.len() > MAX_PROVIDERS {
+            return Err(format!("at most {} providers are allowed", MAX_PROVIDERS));
+        }
+        let mut seen = HashSet::new();
+        for provider in &self.providers {
+            let id_ok = !provider.id.is_empty()
+                && provider.id.len() <= 64
+                && provider
+                    .id
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+            if !id_ok {
+                return Err(format!(
+                    "provider id {:?} must be 1-64 lowercase letters, digits, - or _",
+                    provider.id
+                ));
+            }
+            if !seen.insert(&provider.id) {
+                return Err(format!("provider {} is listed twice", provider.id));
+            }
+            let issuer = check_https_url(&provider.issuer)
+                .map_err(|e| format!("provider {} issuer: {}", provider.id, e))?;
+            if issuer.query().is_some() || issuer.fragment().is_some() {
+                return Err(format!(
+                    "provider {} issuer must not have a query or fragment",
+                    provider.id
+                ));
+            }
+            if provider.client_id.trim().is_empty() || provider.client_id.len() > 255 {
+                return Err(format!("provider {} needs a client_id", provider.id));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StepSettings for PolicySettings {
+    fn check(&self) -> Result<(), String> {
+        if !(8..=128).contains(&self.password_min_length) {
+            return Err("password_min_length must be between 8 and 128".into());
+        }
+        if !(5..=43_200).contains(&self.session_lifetime_minutes) {
+            return Err("session_lifetime_minutes must be between 5 and 43200".into());
+        }
+        for domain in &self.allowed_email_domains {
+            check_host(domain)?;
+        }
+        Ok(())
+    }
+}
+
+impl StepSettings for BrandingSettings {
+    fn check(&self) -> Result<(), String> {
+        let name = self.display_name.trim();
+        if name.is_empty() || name.chars().count() > 100 {
+            return Err("display_name must be 1-100 characters".into());
+        }
+        if let Some(logo) = &self.logo_url {
+            check_https_url(logo).map_err(|e| format!("logo_url: {}", e))?;
+        }
+        if let Some(color) = &self.primary_color {
+            let hex = color.strip_prefix('#').unwrap_or("");
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err("primary_color must be #rrggbb".into());
+            }
+        }
+        if let Some(email) = &self.support_email {
+            check_email(email)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_host(host: &str) -> Result<(), String> {
+    let labels: Vec<&str> = host.split('.').collect();
+    let valid = host.len() <= 253
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("{:?} is not a lowercase host name", host))
+    }
+}
+
+fn check_https_url(value: &str) -> Result<Url, String> {
+    let url = Url::parse(value).map_err(|e| format!("{:?} is not a URL: {}", value, e))?;
+    if url.scheme() != "https" || url.host_str().is_none() {
+        return Err(format!("{:?} must be an https URL", value));
+    }
+    Ok(url)
+}
+
+fn check_email(email: &str) -> Result<(), String> {
+    match email.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && check_host(domain).is_ok() => Ok(()),
+        _ => Err(format!("{:?} is not an email address", email)),
+    }
+}
+
+fn parse_settings<T: StepSettings>(payload: Value) -> Result<T, AuthError> {
+    let settings: T = serde_json::from_value(payload).map_err(|e| AuthError::ValidationError {
+        message: e.to_string(),
+    })?;
+    settings
+        .check()
+        .map_err(|message| AuthError::ValidationError { message })?;
+    Ok(settings)
+}
+
+/// `payload` checked as the settings of `step`, normalized
+fn check_step(step: OnboardingStep, payload: Value) -> Result<Value, AuthError> {
+    fn normalized<T: StepSettings>(payload: Value) -> Result<Value, AuthError> {
+        serde_json::to_value(parse_settings::<T>(payload)?).map_err(|_| AuthError::InternalError)
+    }
+    match step {
+        OnboardingStep::Domains => normalized::<DomainSettings>(payload),
+        OnboardingStep::Providers => normalized::<ProviderSettings>(payload),
+        OnboardingStep::Policies => normalized::<PolicySettings>(payload),
+        OnboardingStep::Branding => normalized::<BrandingSettings>(payload),
+        OnboardingStep::Review => Err(AuthError::ValidationError {
+            message: "the review step has no settings".to_string(),
+        }),
+    }
+}
+
+fn step_settings<T: StepSettings>(ctx: &FlowContext, step: OnboardingStep) -> Option<T> {
+    ctx.data
+        .get(step.as_str())
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+}
+
+fn skipped(ctx: &FlowContext) -> Vec<OnboardingStep> {
+    ctx.data
+        .get(SKIPPED_KEY)
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+        .unwrap_or_default()
+}
+
+fn set_skipped(ctx: &mut FlowContext, step: OnboardingStep, skip: bool) {
+    let mut steps = skipped(ctx);
+    steps.retain(|s| *s != step);
+    if skip {
+        steps.push(step);
+    }
+    ctx.data
+        .insert(SKIPPED_KEY.to_string(), serde_json::json!(steps));
+}
+
+/// Outcome of a test action
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct TestResult {
+    /// `send_test_email` or `test_sso`
+    pub action: String,
+    /// Masked address or provider id
+    pub target: String,
+    pub ok: bool,
+    pub detail: String,
+    pub at: DateTime<Utc>,
+}
+
+fn tests(ctx: &FlowContext) -> Vec<TestResult> {
+    ctx.data
+        .get(TESTS_KEY)
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+        .unwrap_or_default()
+}
+
+/// Keep `result` as the latest of its action and target
+fn record_test(ctx: &mut FlowContext, result: TestResult) {
+    let mut results = tests(ctx);
+    results.retain(|r| !(r.action == result.action && r.target == result.target));
+    results.push(result);
+    ctx.data
+        .insert(TESTS_KEY.to_string(), serde_json::json!(results));
+}
+
+/// What `finish` writes to the tenant
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TenantSettings {
+    /// `https://` and the first host; unchanged when domains were skipped
+    pub custom_domain: Option<String>,
+    /// Merged into the tenant's `auth_config`
+    pub auth_config: Value,
+    /// Replaces `branding_config`; unchanged when branding was skipped
+    pub branding_config: Option<Value>,
+}
+
+impl TenantSettings {
+    fn from_context(ctx: &FlowContext) -> Self {
+        let domains: Option<DomainSettings> = step_settings(ctx, OnboardingStep::Domains);
+        let providers: Option<ProviderSettings> = step_settings(ctx, OnboardingStep::Providers);
+        Self {
+            custom_domain: domains
+                .as_ref()
+                .and_then(|d| d.hosts.first())
+                .map(|host| format!("https://{}", host)),
+            auth_config: serde_json::json!({
+                "hosts": domains.map(|d| d.hosts).unwrap_or_default(),
+                "identity_providers": providers.map(|p| p.providers).unwrap_or_default(),
+                "policies": ctx.data.get(OnboardingStep::Policies.as_str()),
+            }),
+            branding_config: ctx.data.get(OnboardingStep::Branding.as_str()).cloned(),
+        }
+    }
+}
+
+#[async_trait]
+pub trait OnboardingStore: Send + Sync {
+    async fn load(&self, tenant_id: Uuid) -> Result<Option<FlowContext>, AuthError>;
+    /// Store `ctx` if the stored version is still `expected_version`, 0
+    /// meaning none is stored; `AuthError::Conflict` otherwise
+    async fn save(&self, ctx: &FlowContext, expected_version: u64) -> Result<(), AuthError>;
+    async fn apply(&self, tenant_id: Uuid, settings: &TenantSettings) -> Result<(), AuthError>;
+}
+
+/// Checks an identity provider during setup
+#[async_trait]
+pub trait SsoProbe: Send + Sync {
+    /// Detail of a usable OpenID Connect discovery document at `issuer`,
+    /// or what is wrong with it
+    async fn probe(&self, tenant_id: Uuid, issuer: &str) -> Result<String, String>;
+}
+
+/// Reads the issuer's discovery document. Issuers are tenant-supplied, so
+/// the destination is checked against the egress policy first.
+pub struct DiscoveryProbe {
+    client: HttpClient,
+    egress: Arc<EgressPolicy>,
+}
+
+impl DiscoveryProbe {
+    pub fn new(http: HttpClientConfig, egress: EgressConfig) -> Result<Self, HttpClientError> {
+        let egress = Arc::new(EgressPolicy::new(egress));
+        Ok(Self {
+            client: HttpClient::with_egress_policy(http, egress.clone())?,
+            egress,
+        })
+    }
+}
+
+#[async_trait]
+impl SsoProbe for DiscoveryProbe {
+    async fn probe(&self, tenant_id: Uuid, issuer: &str) -> Result<String, String> {
+        let url = Url::parse(&format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        ))
+        .map_err(|e| e.to_string())?;
+        self.egress
+            .check_destination(Some(tenant_id), &url)
+            .await
+            .map_err(|e| e.to_string())?;
+        let response = self
+            .client
+            .send(self.client.get(url.as_str()))
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("{} answered {}", url, response.status()));
+        }
+        let document: Value = response
+            .json()
+            .await
+            .map_err(|e| format!("{} is not JSON: {}", url, e))?;
+
+        if document.get("issuer").and_then(Value::as_str) != Some(issuer) {
+            return Err(format!(
+                "discovery document names issuer {}, expected {}",
+                document.get("issuer").unwrap_or(&Value::Null),
+                issuer
+            ));
+        }
+        let missing: Vec<&str> = ["authorization_endpoint", "token_endpoint", "jwks_uri"]
+            .into_iter()
+            .filter(|field| document.get(*field).and_then(Value::as_str).is_none())
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("discovery document lacks {}", missing.join(", ")));
+        }
+        Ok(format!("{} publishes its endpoints", issuer))
+    }
+}
+
+struct Deps {
+    store: Arc<dyn OnboardingStore>,
+    email: Arc<dyn EmailProvider>,
+    probe: Arc<dyn SsoProbe>,
+}
+
+struct OnboardingStepHandler {
+    step: OnboardingStep,
+    deps: Arc<Deps>,
+}
+
+fn invalid(message: impl Into<String>) -> AuthError {
+    AuthError::ValidationError {
+        message: message.into(),
+    }
+}
+
+impl OnboardingStepHandler {
+    fn advance(&self) -> Result<FlowState, AuthError> {
+        self.step
+            .next()
+            .map(|step| step.state())
+            .ok_or_else(|| invalid("the review step is finished with finish"))
+    }
+
+    async fn send_test_email(
+        &self,
+        ctx: &mut FlowContext,
+        payload: &Value,
+    ) -> Result<(), AuthError> {
+        let to = payload
+            .get("to")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("to is required"))?;
+        check_email(to).map_err(invalid)?;
+
+        let name = step_settings::<BrandingSettings>(ctx, OnboardingStep::Branding)
+            .map(|b| b.display_name)
+            .unwrap_or_else(|| "your sign-in service".to_string());
+        let outcome = self
+            .deps
+            .email
+            .send_email(
+                to,
+                &format!("Test email from {}", name),
+                &format!(
+                    "This is a test email sent while setting up {}. \
+                     If you received it, email delivery works.",
+                    name
+                ),
+            )
+            .await;
+        record_test(
+            ctx,
+            TestResult {
+                action: "send_test_email".to_string(),
+                target: mask_email(to),
+                ok: outcome.is_ok(),
+                detail: match outcome {
+                    Ok(_) => "sent".to_string(),
+                    Err(e) => e.to_string(),
+                },
+                at: Utc::now(),
+            },
+        );
+        Ok(())
+    }
+
+    async fn test_sso(&self, ctx: &mut FlowContext, payload: &Value) -> Result<(), AuthError> {
+        let id = payload
+            .get("provider")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("provider is required"))?;
+        let provider = step_settings::<ProviderSettings>(ctx, OnboardingStep::Providers)
+            .and_then(|p| p.providers.into_iter().find(|p| p.id == id))
+            .ok_or_else(|| invalid(format!("provider {} has not been added", id)))?;
+
+        let outcome = self.deps.probe.probe(ctx.tenant_id, &provider.issuer).await;
+        record_test(
+            ctx,
+            TestResult {
+                action: "test_sso".to_string(),
+                target: provider.id,
+                ok: outcome.is_ok(),
+                detail: outcome.unwrap_or_else(|e| e),
+                at: Utc::now(),
+            },
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StepHandler for OnboardingStepHandler {
+    async fn handle(
+        &self,
+        ctx: &mut FlowContext,
+        action: FlowAction,
+    ) -> Result<FlowState, AuthError> {
+        match action.name.as_str() {
+            "submit" if self.step != OnboardingStep::Review => {
+                let settings = check_step(self.step, action.payload)?;
+                ctx.data.insert(self.step.as_str().to_string(), settings);
+                set_skipped(ctx, self.step, false);
+                self.advance()
+            }
+            "skip" => {
+                if !self.step.optional() {
+                    return Err(invalid(format!(
+                        "the {} step cannot be skipped",
+                        self.step.as_str()
+                    )));
+                }
+                ctx.data.remove(self.step.as_str());
+                set_skipped(ctx, self.step, true);
+                self.advance()
+            }
+            "back" => self
+                .step
+                .previous()
+                .map(|step| step.state())
+                .ok_or_else(|| invalid("this is the first step")),
+            "send_test_email" => {
+                self.send_test_email(ctx, &action.payload).await?;
+                Ok(self.step.state())
+            }
+            "test_sso" => {
+                self.test_sso(ctx, &action.payload).await?;
+                Ok(self.step.state())
+            }
+            "finish" if self.step == OnboardingStep::Review => {
+                self.validate(ctx).await?;
+                self.deps
+                    .store
+                    .apply(ctx.tenant_id, &TenantSettings::from_context(ctx))
+                    .await?;
+                Ok(FlowState::Success)
+            }
+            other => Err(invalid(format!(
+                "{} is not an action of the {} step",
+                other,
+                self.step.as_str()
+            ))),
+        }
+    }
+
+    /// Every step before this one is skipped or has settings that still
+    /// pass their checks
+    async fn validate(&self, ctx: &FlowContext) -> Result<(), AuthError> {
+        let skipped = skipped(ctx);
+        for step in &OnboardingStep::ALL[..self.step.position()] {
+            match ctx.data.get(step.as_str()) {
+                Some(settings) => {
+                    check_step(*step, settings.clone())?;
+                }
+                None if skipped.contains(step) => {}
+                None => return Err(invalid(format!("the {} step is not done", step.as_str()))),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Pending,
+    Current,
+    Done,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct ChecklistItem {
+    pub step: OnboardingStep,
+    pub optional: bool,
+    pub status: StepStatus,
+    /// What was submitted for the step
+    #[schema(value_type = Option<Object>)]
+    pub settings: Option<Value>,
+}
+
+/// Progress of a tenant's setup, in checklist form
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct OnboardingStatus {
+    pub tenant_id: Uuid,
+    /// `None` once finished
+    pub current_step: Option<OnboardingStep>,
+    pub finished: bool,
+    /// Send back as `version` to act only on this state
+    pub version: u64,
+    pub steps: Vec<ChecklistItem>,
+    pub tests: Vec<TestResult>,
+}
+
+impl OnboardingStatus {
+    fn from_context(ctx: &FlowContext) -> Self {
+        let finished = ctx.current_state == FlowState::Success;
+        let current = OnboardingStep::from_state(&ctx.current_state);
+        let skipped = skipped(ctx);
+        let steps = OnboardingStep::ALL
+            .into_iter()
+            .map(|step| {
+                let settings = ctx.data.get(step.as_str()).cloned();
+                let status = if current == Some(step) {
+                    StepStatus::Current
+                } else if skipped.contains(&step) {
+                    StepStatus::Skipped
+                } else if settings.is_some() || finished {
+                    StepStatus::Done
+                } else {
+                    StepStatus::Pending
+                };
+                ChecklistItem {
+                    step,
+                    optional: step.optional(),
+                    status,
+                    settings,
+                }
+            })
+            .collect();
+        Self {
+            tenant_id: ctx.tenant_id,
+            current_step: current,
+            finished,
+            version: ctx.version,
+            steps,
+            tests: tests(ctx),
+        }
+    }
+}
+
+pub struct TenantOnboardingService {
+    store: Arc<dyn OnboardingStore>,
+    engine: WorkflowEngine,
+    tenants: Option<Arc<TenantContexts>>,
+}
+
+impl TenantOnboardingService {
+    pub fn new(
+        store: Arc<dyn OnboardingStore>,
+        email: Arc<dyn EmailProvider>,
+        probe: Arc<dyn SsoProbe>,
+    ) -> Self {
+        let deps = Arc::new(Deps {
+            store: store.clone(),
+            email,
+            probe,
+        });
+        let mut engine = WorkflowEngine::new();
+        for step in OnboardingStep::ALL {
+            engine.register_handler(
+                step.state(),
+                Box::new(OnboardingStepHandler {
+                    step,
+                    deps: deps.clone(),
+                }),
+            );
+        }
+        Self {
+            store,
+            engine,
+            tenants: None,
+        }
+    }
+
+    /// Announce the settings written by `finish`, so that cached tenant
+    /// state is reloaded
+    pub fn with_tenant_contexts(mut self, tenants: Arc<TenantContexts>) -> Self {
+        self.tenants = Some(tenants);
+        self
+    }
+
+    async fn load(&self, tenant_id: Uuid) -> Result<FlowContext, AuthError> {
+        if let Some(ctx) = self.store.load(tenant_id).await? {
+            return Ok(ctx);
+        }
+        let now = Utc::now().timestamp();
+        Ok(FlowContext {
+            flow_id: tenant_id.to_string(),
+            tenant_id,
+            flow_type: FLOW_TYPE.to_string(),
+            current_state: OnboardingStep::Domains.state(),
+            user_id: None,
+            data: Default::default(),
+            version: 0,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    pub async fn status(&self, tenant_id: Uuid) -> Result<OnboardingStatus, AuthError> {
+        Ok(OnboardingStatus::from_context(&self.load(tenant_id).await?))
+    }
+
+    /// Run `action` on the current step and save the outcome. With
+    /// `expected_version`, fails with a conflict if the setup has moved on
+    /// since that version was read.
+    pub async fn act(
+        &self,
+        tenant_id: Uuid,
+        action: FlowAction,
+        expected_version: Option<u64>,
+    ) -> Result<OnboardingStatus, AuthError> {
+        let ctx = self.load(tenant_id).await?;
+        let version = ctx.version;
+        if expected_version.is_some_and(|expected| expected != version) {
+            return Err(AuthError::Conflict {
+                message: format!("onboarding is at version {}", version),
+            });
+        }
+        if ctx.current_state.is_terminal() {
+            return Err(AuthError::Conflict {
+                message: "onboarding is already finished".to_string(),
+            });
+        }
+
+        let (ctx, _) = self.engine.process(ctx, action).await?;
+        self.store.save(&ctx, version).await?;
+        if let (FlowState::Success, Some(tenants)) = (&ctx.current_state, &self.tenants) {
+            // The settings are written; stale caches expire within their TTL
+            if let Err(e) = tenants.changed(tenant_id).await {
+                tracing::error!(tenant_id = %tenant_id, "Failed to announce tenant change: {}", e);
+            }
+        }
+        Ok(OnboardingStatus::from_context(&ctx))
+    }
+}
+
+/// Process-local store for tests and single-node development
+#[derive(Default)]
+pub struct InMemoryOnboardingStore {
+    flows: DashMap<Uuid, FlowContext>,
+    applied: DashMap<Uuid, TenantSettings>,
+}
+
+impl InMemoryOnboardingStore {
+    /// Settings last applied to the tenant
+    pub fn applied(&self, tenant_id: Uuid) -> Option<TenantSettings> {
+        self.applied.get(&tenant_id).map(|s| s.clone())
+    }
+}
+
+#[async_trait]
+impl OnboardingStore for InMemoryOnboardingStore {
+    async fn load(&self, tenant_id: Uuid) -> Result<Option<FlowContext>, AuthError> {
+        Ok(self.flows.get(&tenant_id).map(|ctx| ctx.clone()))
+    }
+
+    async fn save(&self, ctx: &FlowContext, expected_version: u64) -> Result<(), AuthError> {
+        let stored = match self.flows.entry(ctx.tenant_id) {
+            Entry::Occupied(mut entry) if entry.get().version == expected_version => {
+                entry.insert(ctx.clone());
+                return Ok(());
+            }
+            Entry::Vacant(entry) if expected_version == 0 => {
+                entry.insert(ctx.clone());
+                return Ok(());
+            }
+            Entry::Occupied(entry) => entry.get().version,
+            Entry::Vacant(_) => 0,
+        };
+        Err(AuthError::Conflict {
+            message: format!("onboarding is at version {}", stored),
+        })
+    }
+
+    async fn apply(&self, tenant_id: Uuid, settings: &TenantSettings) -> Result<(), AuthError> {
+        self.applied.insert(tenant_id, settings.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::otp_delivery::DeliveryError;
+    use parking_lot::Mutex;
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct Outbox {
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl EmailProvider for Outbox {
+        async fn send_email(
+            &self,
+            to: &str,
+            _subject: &str,
+            _body: &str,
+        ) -> Result<String, DeliveryError> {
+            self.sent.lock().push(to.to_string());
+            Ok("queued".to_string())
+        }
+    }
+
+    struct FakeProbe;
+
+    #[async_trait]
+    impl SsoProbe for FakeProbe {
+        async fn probe(&self, _tenant_id: Uuid, issuer: &str) -> Result<String, String> {
+            if issuer.contains("broken") {
+                Err("discovery document lacks jwks_uri".to_string())
+            } else {
+                Ok("ok".to_string())
+            }
+        }
+    }
+
+    fn service() -> (
+        TenantOnboardingService,
+        Arc<InMemoryOnboardingStore>,
+        Arc<Outbox>,
+    ) {
+        let store = Arc::new(InMemoryOnboardingStore::default());
+        let outbox = Arc::new(Outbox::default());
+        let service =
+            TenantOnboardingService::new(store.clone(), outbox.clone(), Arc::new(FakeProbe));
+        (service, store, outbox)
+    }
+
+    fn action(name: &str, payload: Value) -> FlowAction {
+        FlowAction {
+            name: name.to_string(),
+            payload,
+        }
+    }
+
+    fn policies() -> Value {
+        json!({
+            "password_min_length": 12,
+            "mfa_required": true,
+            "session_lifetime_minutes": 480,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_steps_run_in_order_and_finish_applies_settings() {
+        let (service, store, _) = service();
+        let tenant = Uuid::new_v4();
+
+        let status = service.status(tenant).await.unwrap();
+        assert_eq!(status.current_step, Some(OnboardingStep::Domains));
+        assert_eq!(status.version, 0);
+
+        // Only the review step can be finished
+        let err = service
+            .act(tenant, action("finish", json!({})), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::ValidationError { .. }));
+
+        service
+            .act(
+                tenant,
+                action("submit", json!({ "hosts": ["login.acme.com"] })),
+                Some(0),
+            )
+            .await
+            .unwrap();
+        service
+            .act(tenant, action("skip", json!({})), None)
+            .await
+            .unwrap();
+        let status = service
+            .act(tenant, action("submit", policies()), None)
+            .await
+            .unwrap();
+        assert_eq!(status.current_step, Some(OnboardingStep::Branding));
+        assert_eq!(status.steps[0].status, StepStatus::Done);
+        assert_eq!(status.steps[1].status, StepStatus::Skipped);
+        assert_eq!(status.steps[3].status, StepStatus::Current);
+        assert_eq!(status.steps[4].status, StepStatus::Pending);
+        assert!(store.applied(tenant).is_none());
+
+        service
+            .act(
+                tenant,
+                action(
+                    "submit",
+                    json!({ "display_name": "Acme", "primary_color": "#1a2b3c" }),
+                ),
+                None,
+            )
+            .await
+            .unwrap();
+        let status = service
+            .act(tenant, action("finish", json!({})), None)
+            .await
+            .unwrap();
+        assert!(status.finished);
+        assert_eq!(status.current_step, None);
+
+        let applied = store.applied(tenant).unwrap();
+        assert_eq!(
+            applied.custom_domain.as_deref(),
+            Some("https://login.acme.com")
+        );
+        assert_eq!(applied.auth_config["policies"]["password_min_length"], 12);
+        assert_eq!(applied.branding_config.unwrap()["display_name"], "Acme");
+
+        let err = service
+            .act(tenant, action("back", json!({})), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::Conflict { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_settings_and_required_steps_are_rejected() {
+        let (service, _, _) = service();
+        let tenant = Uuid::new_v4();
+
+        for hosts in [
+            json!([]),
+            json!(["Login.Acme.com"]),
+            json!(["a.com", "a.com"]),
+        ] {
+            let err = service
+                .act(tenant, action("submit", json!({ "hosts": hosts })), None)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(err, AuthError::ValidationError { .. }),
+                "{}",
+                hosts
+            );
+        }
+        service
+            .act(tenant, action("skip", json!({})), None)
+            .await
+            .unwrap();
+
+        let providers = json!({ "providers": [
+            { "id": "okta", "issuer": "http://acme.okta.com", "client_id": "abc" }
+        ]});
+        assert!(service
+            .act(tenant, action("submit", providers), None)
+            .await
+            .is_err());
+        service
+            .act(tenant, action("skip", json!({})), None)
+            .await
+            .unwrap();
+
+        let err = service
+            .act(tenant, action("skip", json!({})), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::ValidationError { .. }));
+
+        let mut weak = policies();
+        weak["password_min_length"] = json!(4);
+        assert!(service
+            .act(tenant, action("submit", weak), None)
+            .await
+            .is_err());
+
+        // Stale versions are refused
+        let err = service
+            .act(tenant, action("submit", policies()), Some(1))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::Conflict { .. }));
+        assert_eq!(
+            service.status(tenant).await.unwrap().current_step,
+            Some(OnboardingStep::Policies)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_test_actions_record_results_without_advancing() {
+        let (service, _, outbox) = service();
+        let tenant = Uuid::new_v4();
+        service
+            .act(tenant, action("skip", json!({})), None)
+            .await
+            .unwrap();
+        service
+            .act(
+                tenant,
+                action(
+                    "submit",
+                    json!({ "providers": [
+                        { "id": "okta", "issuer": "https://acme.okta.com", "client_id": "abc" },
+                        { "id": "legacy", "issuer": "https://broken.example.com", "client_id": "xyz" }
+                    ]}),
+                ),
+                None,
+            )
+            .await
+            .unwrap();
+
+        service
+            .act(
+                tenant,
+                action("test_sso", json!({ "provider": "okta" })),
+                None,
+            )
+            .await
+            .unwrap();
+        service
+            .act(
+                tenant,
+                action("test_sso", json!({ "provider": "legacy" })),
+                None,
+            )
+            .await
+            .unwrap();
+        let status = service
+            .act(
+                tenant,
+                action("send_test_email", json!({ "to": "admin@acme.com" })),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(status.current_step, Some(OnboardingStep::Policies));
+        assert_eq!(outbox.sent.lock().as_slice(), ["admin@acme.com"]);
+        let result = |target: &str| status.tests.iter().find(|t| t.target == target).unwrap();
+        assert!(result("okta").ok);
+        assert!(!result("legacy").ok);
+        assert!(result("legacy").detail.contains("jwks_uri"));
+        assert_ne!(status.tests[2].target, "admin@acme.com");
+
+        let err = service
+            .act(
+                tenant,
+                action("test_sso", json!({ "provider": "azure" })),
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::ValidationError { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_back_revisits_a_step_and_finish_rechecks_everything() {
+        let (service, _, _) = service();
+        let tenant = Uuid::new_v4();
+        service
+            .act(tenant, action("skip", json!({})), None)
+            .await
+            .unwrap();
+        service
+            .act(tenant, action("skip", json!({})), None)
+            .await
+            .unwrap();
+        service
+            .act(tenant, action("submit", policies()), None)
+            .await
+            .unwrap();
+        service
+            .act(tenant, action("skip", json!({})), None)
+            .await
+            .unwrap();
+
+        service
+            .act(tenant, action("back", json!({})), None)
+            .await
+            .unwrap();
+        let status = service
+            .act(tenant, action("back", json!({})), None)
+            .await
+            .unwrap();
+        assert_eq!(status.current_step, Some(OnboardingStep::Policies));
+        assert_eq!(status.steps[3].status, StepStatus::Skipped);
+
+        service
+            .act(tenant, action("submit", policies()), None)
+            .await
+            .unwrap();
+        service
+            .act(tenant, action("skip", json!({})), None)
+            .await
+            .unwrap();
+        let status = service
+            .act(tenant, action("finish", json!({})), None)
+            .await
+            .unwrap();
+        assert!(status.finished);
+        assert!(status
+            .steps
+            .iter()
+            .all(|s| matches!(s.status, StepStatus::Done | StepStatus::Skipped)));
+    }
+}
@@ -0,0 +1,359 @@
+//! Per-tenant and per-client request quotas
+//!
+//! A tenant's request rate is a token bucket refilled at the tier's
+//! requests-per-minute, and its requests in flight are capped at the tier's
+//! concurrency. The tier is named after the tenant's subscription plan.
+//! Client addresses get their own in-flight cap so one caller cannot take a
+//! whole tenant's share. Counters are in memory, i.e. per instance.
+//!
+//! With adaptive quotas on, the tier's rate is only the anchor of the
+//! guard rails; the rate enforced comes from [`AdaptiveQuotas`].
+
+use crate::error::AuthError;
+use crate::services::adaptive_quota::{AdaptiveLimitReport, AdaptiveQuotas};
+use crate::services::subscription_service::SubscriptionService;
+use auth_config::{QuotaConfig, QuotaTier};
+use chrono::Utc;
+use dashmap::DashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Quota state reported back to the caller in response headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaStatus {
+    pub requests_per_minute: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again
+    pub reset_seconds: u64,
+    pub max_concurrent: u32,
+}
+
+/// Why a request was turned away, with when to retry
+#[derive(Debug)]
+pub struct QuotaRejection {
+    pub error: AuthError,
+    pub retry_after_seconds: u64,
+    pub status: Option<QuotaStatus>,
+}
+
+/// Holds one in-flight slot until dropped
+#[derive(Debug)]
+pub struct QuotaPermit {
+    in_flight: Arc<AtomicU32>,
+    pub status: Option<QuotaStatus>,
+}
+
+impl Drop for QuotaPermit {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+struct TenantQuota {
+    bucket: parking_lot::Mutex<Bucket>,
+    in_flight: Arc<AtomicU32>,
+}
+
+pub struct TenantQuotaService {
+    config: QuotaConfig,
+    subscriptions: Arc<SubscriptionService>,
+    tenants: DashMap<Uuid, Arc<TenantQuota>>,
+    clients: DashMap<String, Arc<AtomicU32>>,
+    /// Plan id per tenant and when it was looked up
+    plans: DashMap<Uuid, (String, Instant)>,
+    adaptive: AdaptiveQuotas,
+}
+
+impl TenantQuotaService {
+    pub fn new(config: QuotaConfig, subscriptions: Arc<SubscriptionService>) -> Self {
+        Self {
+            adaptive: AdaptiveQuotas::new(config.adaptive.clone()),
+            config,
+            subscriptions,
+            tenants: DashMap::new(),
+            clients: DashMap::new(),
+            plans: DashMap::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Tenant served on `host`, for requests that carry no token
+    pub fn tenant_for_host(&self, host: &str) -> Option<Uuid> {
+        let host = host.split(':').next().unwrap_or(host);
+        self.config.hosts.get(host).copied()
+    }
+
+    /// Take one request-rate token and one in-flight slot for `tenant_id`
+    pub async fn admit_tenant(&self, tenant_id: Uuid) -> Result<QuotaPermit, QuotaRejection> {
+        let tier = self.tier_for(tenant_id).await;
+        let requests_per_minute = if self.adaptive.enabled() {
+            self.adaptive
+                .record(tenant_id, tier.requests_per_minute, Utc::now())
+        } else {
+            tier.requests_per_minute
+        };
+        let quota = self
+            .tenants
+            .entry(tenant_id)
+            .or_insert_with(|| {
+                Arc::new(TenantQuota {
+                    bucket: parking_lot::Mutex::new(Bucket {
+                        tokens: requests_per_minute as f64,
+                        last_refill: Instant::now(),
+                    }),
+                    in_flight: Arc::new(AtomicU32::new(0)),
+                })
+            })
+            .clone();
+
+        let status = {
+            let mut bucket = quota.bucket.lock();
+            let capacity = requests_per_minute as f64;
+            let per_second = capacity / WINDOW.as_secs_f64();
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+            bucket.last_refill = now;
+
+            if bucket.tokens < 1.0 {
+                let status = QuotaStatus {
+                    requests_per_minute,
+                    remaining: 0,
+                    reset_seconds: ((capacity - bucket.tokens) / per_second).ceil() as u64,
+                    max_concurrent: tier.max_concurrent,
+                };
+                return Err(QuotaRejection {
+                    error: AuthError::RateLimitExceeded {
+                        limit: requests_per_minute,
+                        window: "minute".to_string(),
+                    },
+                    retry_after_seconds: ((1.0 - bucket.tokens) / per_second).ceil() as u64,
+                    status: Some(status),
+                });
+            }
+            bucket.tokens -= 1.0;
+            QuotaStatus {
+                requests_per_minute,
+                remaining: bucket.tokens.floor() as u32,
+                reset_seconds: ((capacity - bucket.tokens) / per_second).ceil() as u64,
+                max_concurrent: tier.max_concurrent,
+            }
+        };
+
+        acquire_slot(&quota.in_flight, tier.max_concurrent)
+            .map(|in_flight| QuotaPermit {
+                in_flight,
+                status: Some(status),
+            })
+            .map_err(|error| QuotaRejection {
+                error,
+                retry_after_seconds: 1,
+                status: Some(status),
+            })
+    }
+
+    /// The tenant's current rate limit and what it was learned from
+    pub async fn adaptive_report(&self, tenant_id: Uuid) -> AdaptiveLimitReport {
+        let tier = self.tier_for(tenant_id).await;
+        self.adaptive
+            .report(tenant_id, tier.requests_per_minute, Utc::now())
+    }
+
+    /// Look up the tenant's plan ahead of its first request
+    pub async fn prefetch_plan(&self, tenant_id: Uuid) {
+        self.tier_for(tenant_id).await;
+    }
+
+    /// Tenants mapped to a host, which will see unauthenticated traffic
+    pub fn host_tenants(&self) -> impl Iterator<Item = Uuid> + '_ {
+        self.config.hosts.values().copied()
+    }
+
+    /// Take one in-flight slot for a client address
+    pub fn admit_client(&self, client: &str) -> Result<QuotaPermit, QuotaRejection> {
+        let in_flight = self
+            .clients
+            .entry(client.to_string())
+            .or_insert_with(|| Arc::new(AtomicU32::new(0)))
+            .clone();
+        acquire_slot(&in_flight, self.config.max_concurrent_per_client)
+            .map(|in_flight| QuotaPermit {
+                in_flight,
+                status: None,
+            })
+            .map_err(|error| QuotaRejection {
+                error,
+                retry_after_seconds: 1,
+                status: None,
+            })
+    }
+
+    /// Drop idle client counters so the map does not grow with every address
+    pub fn prune_idle_clients(&self) {
+        self.clients
+            .retain(|_, in_flight| Arc::strong_count(in_flight) > 1);
+    }
+
+    /// Prune idle client counters every `interval`
+    pub async fn run_pruning(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            self.prune_idle_clients();
+        }
+    }
+
+    async fn tier_for(&self, tenant_id: Uuid) -> QuotaTier {
+        let max_age = Duration::from_secs(self.config.plan_cache_seconds);
+        let cached = self
+            .plans
+            .get(&tenant_id)
+            .filter(|entry| entry.1.elapsed() < max_age)
+            .map(|entry| entry.0.clone());
+
+        let plan = match cached {
+            Some(plan) => plan,
+            None => {
+                let plan = match self.subscriptions.active_plan(tenant_id).await {
+                    Ok(Some(plan)) => plan,
+                    Ok(None) => self.config.default_tier.clone(),
+                    Err(e) => {
+                        tracing::warn!(
+                            "Plan lookup failed for tenant {}, using default tier: {}",
+                            tenant_id,
+                            e
+                        );
+                        self.config.default_tier.clone()
+                    }
+                };
+                self.plans.insert(tenant_id, (plan.clone(), Instant::now()));
+                plan
+            }
+        };
+
+        self.config
+            .tiers
+            .get(&plan)
+            .or_else(|| self.config.tiers.get(&self.config.default_tier))
+            .cloned()
+            .unwrap_or(QuotaTier {
+                requests_per_minute: u32::MAX,
+                max_concurrent: u32::MAX,
+            })
+    }
+}
+
+fn acquire_slot(in_flight: &Arc<AtomicU32>, limit: u32) -> Result<Arc<AtomicU32>, AuthError> {
+    in_flight
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+            (n < limit).then_some(n + 1)
+        })
+        .map(|_| in_flight.clone())
+        .map_err(|_| AuthError::ConcurrencyLimitExceeded { limit })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::subscription::TenantSubscription;
+    use crate::services::subscription_service::SubscriptionStore;
+    use std::collections::HashMap;
+
+    struct NoSubscriptions;
+
+    #[async_trait::async_trait]
+    impl SubscriptionStore for NoSubscriptions {
+        async fn create(&self, sub: TenantSubscription) -> Result<TenantSubscription, AuthError> {
+            Ok(sub)
+        }
+        async fn get_by_tenant(
+            &self,
+            _tenant_id: Uuid,
+        ) -> Result<Option<TenantSubscription>, AuthError> {
+            Ok(None)
+        }
+        async fn update_usage(
+            &self,
+            _tenant_id: Uuid,
+            _usage: HashMap<String, i64>,
+        ) -> Result<(), AuthError> {
+            Ok(())
+        }
+    }
+
+    fn service(requests_per_minute: u32, max_concurrent: u32) -> TenantQuotaService {
+        let config = QuotaConfig {
+            tiers: HashMap::from([(
+                "free".to_string(),
+                QuotaTier {
+                    requests_per_minute,
+                    max_concurrent,
+                },
+            )]),
+            max_concurrent_per_client: 1,
+            ..QuotaConfig::default()
+        };
+        TenantQuotaService::new(
+            config,
+            Arc::new(SubscriptionService::new(Arc::new(NoSubscriptions))),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_per_tenant() {
+        let quotas = service(3, 10);
+        let tenant = Uuid::new_v4();
+        for remaining in [2, 1, 0] {
+            let permit = quotas.admit_tenant(tenant).await.unwrap();
+            assert_eq!(permit.status.unwrap().remaining, remaining);
+        }
+        let rejection = quotas.admit_tenant(tenant).await.unwrap_err();
+        assert!(matches!(
+            rejection.error,
+            AuthError::RateLimitExceeded { limit: 3, .. }
+        ));
+        assert!(rejection.retry_after_seconds >= 1);
+
+        // Other tenants have their own bucket
+        assert!(quotas.admit_tenant(Uuid::new_v4()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_slots_are_released_on_drop() {
+        let quotas = service(100, 2);
+        let tenant = Uuid::new_v4();
+        let first = quotas.admit_tenant(tenant).await.unwrap();
+        let _second = quotas.admit_tenant(tenant).await.unwrap();
+        assert!(matches!(
+            quotas.admit_tenant(tenant).await.unwrap_err().error,
+            AuthError::ConcurrencyLimitExceeded { limit: 2 }
+        ));
+
+        drop(first);
+        assert!(quotas.admit_tenant(tenant).await.is_ok());
+    }
+
+    #[test]
+    fn test_client_in_flight_cap() {
+        let quotas = service(100, 10);
+        let permit = quotas.admit_client("10.0.0.1").unwrap();
+        assert!(quotas.admit_client("10.0.0.1").is_err());
+        assert!(quotas.admit_client("10.0.0.2").is_ok());
+
+        drop(permit);
+        quotas.prune_idle_clients();
+        assert!(quotas.admit_client("10.0.0.1").is_ok());
+    }
+}
@@ -0,0 +1,171 @@
+//! Timing side-channel defences for credential checks
+//!
+//! A credential endpoint must not reveal, through how long it takes, whether
+//! an account exists or how far a secret comparison got. Unknown users get a
+//! password verification against a dummy hash, secrets are compared in
+//! constant time, and whole responses can be padded to a jittered floor.
+
+use crate::models::SensitiveString;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use rand::Rng;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Compare two byte strings without short-circuiting on the first mismatch.
+/// Only the length is allowed to leak, which is public for hashes and codes.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Hash with the same parameters as real passwords, created on first use
+fn dummy_hash() -> &'static str {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+    DUMMY.get_or_init(|| {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(b"dummy-password-for-timing", &salt)
+            .expect("argon2 hashing with default parameters")
+            .to_string()
+    })
+}
+
+/// Verify `password` against `hash`, or against a dummy hash when there is
+/// none, so both paths cost one argon2 verification. Runs off the executor.
+pub async fn verify_password_uniform(password: SensitiveString, hash: Option<String>) -> bool {
+    let has_hash = hash.is_some();
+    tokio::task::spawn_blocking(move || {
+        let hash = match hash.as_deref() {
+            Some(hash) => hash,
+            None => dummy_hash(),
+        };
+        let verified = match PasswordHash::new(hash) {
+            Ok(parsed) => Argon2::default()
+                .verify_password(password.expose().as_bytes(), &parsed)
+                .is_ok(),
+            Err(_) => false,
+        };
+        verified && has_hash
+    })
+    .await
+    .unwrap_or(false)
+}
+
+/// Minimum response time plus random jitter, so fast failure paths are
+/// indistinguishable from slow success paths
+#[derive(Debug, Clone, Copy)]
+pub struct ResponseFloor {
+    pub minimum: Duration,
+    pub jitter: Duration,
+}
+
+impl ResponseFloor {
+    pub const fn new(minimum: Duration, jitter: Duration) -> Self {
+        Self { minimum, jitter }
+    }
+
+    /// When a response started at `started` may go out: `minimum` plus a
+    /// random share of `jitter` later
+    pub fn deadline(&self, started: Instant) -> Instant {
+        let jitter_ms = self.jitter.as_millis() as u64;
+        let jitter = if jitter_ms == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(rand::thread_rng().gen_range(0..=jitter_ms))
+        };
+        started + self.minimum + jitter
+    }
+
+    /// Sleep until the [`deadline`](Self::deadline); returns at once if past it
+    pub async fn pad(&self, started: Instant) {
+        tokio::time::sleep_until(self.deadline(started)).await;
+    }
+
+    pub async fn run<F: Future>(&self, fut: F) -> F::Output {
+        let started = Instant::now();
+        let output = fut.await;
+        self.pad(started).await;
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"123456", b"123456"));
+        assert!(!constant_time_eq(b"123456", b"123457"));
+        assert!(!constant_time_eq(b"123456", b"12345"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[tokio::test]
+    async fn test_missing_hash_never_verifies() {
+        assert!(!verify_password_uniform("dummy-password-for-timing".into(), None).await);
+
+        let salt = SaltString::generate(&mut OsRng);
+        let real = Argon2::default()
+            .hash_password(b"secret", &salt)
+            .unwrap()
+            .to_string();
+        assert!(verify_password_uniform("secret".into(), Some(real.clone())).await);
+        assert!(!verify_password_uniform("wrong".into(), Some(real)).await);
+    }
+
+    #[test]
+    fn test_unknown_user_costs_a_real_verification() {
+        // The dummy hash must cost what a real one does: same algorithm,
+        // same parameters
+        let salt = SaltString::generate(&mut OsRng);
+        let real = Argon2::default()
+            .hash_password(b"secret", &salt)
+            .unwrap()
+            .to_string();
+        let real = PasswordHash::new(&real).unwrap();
+        let dummy = PasswordHash::new(dummy_hash()).unwrap();
+
+        assert_eq!(dummy.algorithm, real.algorithm);
+        assert_eq!(dummy.version, real.version);
+        assert_eq!(dummy.params, real.params);
+    }
+
+    #[test]
+    fn test_deadline_stays_within_floor_and_jitter() {
+        let floor = ResponseFloor::new(Duration::from_millis(60), Duration::from_millis(10));
+        let started = Instant::now();
+
+        for _ in 0..100 {
+            let deadline = floor.deadline(started);
+            assert!(deadline >= started + Duration::from_millis(60));
+            assert!(deadline <= started + Duration::from_millis(70));
+        }
+
+        let no_jitter = ResponseFloor::new(Duration::from_millis(60), Duration::ZERO);
+        assert_eq!(
+            no_jitter.deadline(started),
+            started + Duration::from_millis(60)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_floor_applies_minimum_delay() {
+        let floor = ResponseFloor::new(Duration::from_millis(30), Duration::from_millis(5));
+
+        for work_ms in [0u64, 10] {
+            let started = std::time::Instant::now();
+            floor
+                .run(tokio::time::sleep(Duration::from_millis(work_ms)))
+                .await;
+            assert!(started.elapsed() >= Duration::from_millis(30));
+        }
+    }
+}
@@ -1,10 +1,15 @@
 //! Token management service
 
 use crate::error::{AuthError, TokenErrorKind};
-use crate::models::{AccessToken, Claims, RefreshToken, TokenPair};
-use auth_crypto::{JwtConfig, JwtError, JwtService, KeyManager};
+use crate::models::{AccessToken, Claims, RefreshToken, SessionBinding, TokenPair};
+use crate::services::claims_enrichment::ClaimsEnricher;
+use crate::services::forced_reauth::{GenerationScope, TokenGenerations};
+use crate::services::takeover_response::{TakeoverResponder, TakeoverSignal};
+use crate::services::token_ttl::TokenTtlPolicy;
+use auth_crypto::{JwtClaims, JwtConfig, JwtError, JwtService, KeyManager};
 use chrono::{DateTime, Duration, Utc};
 use lru::LruCache;
+use std::collections::HashSet;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -12,13 +17,26 @@ use uuid::Uuid;
 
 const DEFAULT_CACHE_CAPACITY: usize = 10_000;
 
+/// Blocking threads a token batch is spread over for signature checks
+const BATCH_WORKERS: usize = 4;
+
 /// Trait for refresh token persistent storage
 #[async_trait::async_trait]
 pub trait RefreshTokenStore: Send + Sync {
     async fn create(&self, token: RefreshToken) -> Result<(), AuthError>;
     async fn find_by_hash(&self, hash: &str) -> Result<Option<RefreshToken>, AuthError>;
     async fn revoke(&self, token_id: Uuid) -> Result<(), AuthError>;
-    async fn revoke_family(&self, family_id: Uuid) -> Result<(), AuthError>;
+    /// Revoke the family's live tokens, returning how many there were
+    async fn revoke_family(&self, family_id: Uuid) -> Result<u64, AuthError>;
+    /// Every refresh token issued under `session_id`, revoked or not
+    async fn find_by_session(&self, session_id: Uuid) -> Result<Vec<RefreshToken>, AuthError>;
+    /// Revoke every live refresh token of the tenant, returning how many
+    async fn revoke_tenant(&self, tenant_id: Uuid) -> Result<u64, AuthError>;
+    /// Revoke every live refresh token of the user, returning how many
+    async fn revoke_user(&self, tenant_id: Uuid, user_id: Uuid) -> Result<u64, AuthError>;
+    /// Revoke every live refresh token issued to the client, returning how
+    /// many
+    async fn revoke_client(&self, tenant_id: Uuid, client_id: &str) -> Result<u64, AuthError>;
 }
 
 /// Trait for revoked access token storage (blacklist)
@@ -32,6 +50,17 @@ pub trait RevokedTokenStore: Send + Sync {
         expires_at: DateTime<Utc>,
     ) -> Result<(), AuthError>;
     async fn is_revoked(&self, jti: Uuid) -> Result<bool, AuthError>;
+    /// The subset of `jtis` that is revoked, in as few lookups as the
+    /// backend allows
+    async fn revoked_among(&self, jtis: &[Uuid]) -> Result<HashSet<Uuid>, AuthError> {
+        let mut revoked = HashSet::new();
+        for &jti in jtis {
+            if self.is_revoked(jti).await? {
+                revoked.insert(jti);
+            }
+        }
+        Ok(revoked)
+    }
 }
 
 #[async_trait::async_trait]
@@ -42,7 +71,30 @@ pub trait TokenProvider: Send + Sync {
         user_id: Uuid,
         tenant_id: Uuid,
     ) -> Result<RefreshToken, AuthError>;
+    /// Issue a refresh token honouring per-client lifetime overrides, bound
+    /// to the session and access token it was issued with
+    async fn issue_refresh_token_for_client(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        _client_id: Option<&str>,
+        _binding: SessionBinding,
+    ) -> Result<RefreshToken, AuthError> {
+        self.issue_refresh_token(user_id, tenant_id).await
+    }
     async fn validate_token(&self, token: &str) -> Result<Claims, AuthError>;
+    /// Validate many tokens, one result per token in input order. Fails as a
+    /// whole only when revocation status cannot be determined.
+    async fn validate_tokens(
+        &self,
+        tokens: &[String],
+    ) -> Result<Vec<Result<Claims, AuthError>>, AuthError> {
+        let mut results = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            results.push(self.validate_token(token).await);
+        }
+        Ok(results)
+    }
     async fn revoke_token(
         &self,
         token_id: Uuid,
@@ -50,8 +102,29 @@ pub trait TokenProvider: Send + Sync {
         tenant_id: Uuid,
     ) -> Result<(), AuthError>;
     async fn refresh_tokens(&self, refresh_token: &str) -> Result<TokenPair, AuthError>;
+    /// Rotate a refresh token on behalf of `client_id`, which must be the
+    /// client it was issued to; `None` for first-party callers
+    async fn refresh_tokens_for_client(
+        &self,
+        refresh_token: &str,
+        _client_id: Option<&str>,
+    ) -> Result<TokenPair, AuthError> {
+        self.refresh_tokens(refresh_token).await
+    }
+    /// Revoke every refresh token family issued under `session_id` and
+    /// blacklist the access tokens issued alongside them. Providers that do
+    /// not bind tokens to sessions have nothing to revoke.
+    async fn revoke_session_tokens(&self, _session_id: Uuid) -> Result<(), AuthError> {
+        Ok(())
+    }
     async fn introspect_token(&self, token: &str) -> Result<TokenIntrospectionResponse, AuthError>;
     async fn get_jwks(&self) -> serde_json::Value;
+    /// Sign a JWT with the provider's published key
+    async fn sign_claims(&self, _claims: serde_json::Value) -> Result<String, AuthError> {
+        Err(AuthError::ConfigurationError {
+            message: "token provider cannot sign custom claims".to_string(),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -68,12 +141,18 @@ pub struct TokenIntrospectionResponse {
     pub aud: Option<String>,
     pub iss: Option<String>,
     pub jti: Option<String>,
+    /// Session the token was issued under
+    pub session_id: Option<String>,
 }
 
 pub struct TokenEngine {
     jwt_service: JwtService,
     revoked_token_store: Arc<dyn RevokedTokenStore>,
     refresh_token_store: Arc<dyn RefreshTokenStore>,
+    ttl_policy: Arc<TokenTtlPolicy>,
+    generations: Option<Arc<TokenGenerations>>,
+    takeover: Option<Arc<TakeoverResponder>>,
+    enricher: Option<Arc<ClaimsEnricher>>,
 }
 
 // In-memory implementations for testing/default
@@ -131,9 +210,70 @@ impl RefreshTokenStore for InMemoryRefreshTokenStore {
         Ok(())
     }
 
-    async fn revoke_family(&self, _family_id: Uuid) -> Result<(), AuthError> {
-        // TODO: Implement family revocation
-        Ok(())
+    async fn revoke_family(&self, family_id: Uuid) -> Result<u64, AuthError> {
+        let mut tokens = self.tokens.write().await;
+        let now = Utc::now();
+        let mut revoked = 0;
+        for (_, token) in tokens.iter_mut() {
+            if token.token_family == family_id && token.revoked_at.is_none() {
+                token.revoked_at = Some(now);
+                revoked += 1;
+            }
+        }
+        Ok(revoked)
+    }
+
+    async fn find_by_session(&self, session_id: Uuid) -> Result<Vec<RefreshToken>, AuthError> {
+        let tokens = self.tokens.read().await;
+        Ok(tokens
+            .iter()
+            .filter(|(_, token)| token.session_id == Some(session_id))
+            .map(|(_, token)| token.clone())
+            .collect())
+    }
+
+    async fn revoke_tenant(&self, tenant_id: Uuid) -> Result<u64, AuthError> {
+        let mut tokens = self.tokens.write().await;
+        let now = Utc::now();
+        let mut revoked = 0;
+        for (_, token) in tokens.iter_mut() {
+            if token.tenant_id == tenant_id && token.revoked_at.is_none() {
+                token.revoked_at = Some(now);
+                revoked += 1;
+            }
+        }
+        Ok(revoked)
+    }
+    async fn revoke_user(&self, tenant_id: Uuid, user_id: Uuid) -> Result<u64, AuthError> {
+        let mut tokens = self.tokens.write().await;
+        let now = Utc::now();
+        let mut revoked = 0;
+        for (_, token) in tokens.iter_mut() {
+            if token.tenant_id == tenant_id
+                && token.user_id == user_id
+                && token.revoked_at.is_none()
+            {
+                token.revoked_at = Some(now);
+                revoked += 1;
+            }
+        }
+        Ok(revoked)
+    }
+
+    async fn revoke_client(&self, tenant_id: Uuid, client_id: &str) -> Result<u64, AuthError> {
+        let mut tokens = self.tokens.write().await;
+        let now = Utc::now();
+        let mut revoked = 0;
+        for (_, token) in tokens.iter_mut() {
+            if token.tenant_id == tenant_id
+                && token.client_id.as_deref() == Some(client_id)
+                && token.revoked_at.is_none()
+            {
+                token.revoked_at = Some(now);
+                revoked += 1;
+            }
+        }
+        Ok(revoked)
     }
 }
 
@@ -203,6 +343,10 @@ impl TokenEngine {
             jwt_service: JwtService::new(config, key_manager),
             revoked_token_store: Arc::new(InMemoryRevokedTokenStore::new(10_000)),
             refresh_token_store: Arc::new(InMemoryRefreshTokenStore::new(10_000)),
+            ttl_policy: Arc::new(TokenTtlPolicy::default()),
+            generations: None,
+            takeover: None,
+            enricher: None,
         })
     }
 
@@ -224,6 +368,10 @@ impl TokenEngine {
             jwt_service: JwtService::new(config, key_manager),
             revoked_token_store: Arc::new(InMemoryRevokedTokenStore::new(10_000)),
             refresh_token_store: Arc::new(InMemoryRefreshTokenStore::new(10_000)),
+            ttl_policy: Arc::new(TokenTtlPolicy::default()),
+            generations: None,
+            takeover: None,
+            enricher: None,
         })
     }
 
@@ -242,9 +390,113 @@ impl TokenEngine {
             jwt_service: JwtService::new(config, key_manager),
             revoked_token_store: revoked_store,
             refresh_token_store: refresh_store,
+            ttl_policy: Arc::new(TokenTtlPolicy::default()),
+            generations: None,
+            takeover: None,
+            enricher: None,
         })
     }
 
+    /// Use the given lifetime policy instead of the built-in 15 min / 30 day defaults
+    pub fn with_ttl_policy(mut self, policy: Arc<TokenTtlPolicy>) -> Self {
+        self.ttl_policy = policy;
+        self
+    }
+
+    /// Stamp access tokens with their tenant's and user's token
+    /// generations, and reject those from an older one
+    pub fn with_generations(mut self, generations: Arc<TokenGenerations>) -> Self {
+        self.generations = Some(generations);
+        self
+    }
+
+    /// Answer a replayed refresh token by revoking its family and running
+    /// the tenant's takeover playbook
+    pub fn with_takeover_response(mut self, responder: Arc<TakeoverResponder>) -> Self {
+        self.takeover = Some(responder);
+        self
+    }
+
+    /// Resolve roles, permissions and extension claims into every access
+    /// token, whatever path issues it
+    pub fn with_claims_enricher(mut self, enricher: Arc<ClaimsEnricher>) -> Self {
+        self.enricher = Some(enricher);
+        self
+    }
+
+    async fn generation(&self, scope: GenerationScope) -> Result<u64, AuthError> {
+        match &self.generations {
+            Some(generations) => generations.current(scope).await,
+            None => Ok(0),
+        }
+    }
+
+    /// Whether the tenant or the user was forced to reauthenticate after
+    /// `claims` were issued
+    async fn superseded(&self, claims: &JwtClaims) -> Result<bool, AuthError> {
+        if self.generations.is_none() {
+            return Ok(false);
+        }
+        if let Ok(tenant_id) = Uuid::parse_str(&claims.tenant_id) {
+            if claims.generation < self.generation(GenerationScope::Tenant(tenant_id)).await? {
+                return Ok(true);
+            }
+        }
+        if let Ok(user_id) = Uuid::parse_str(&claims.sub) {
+            if claims.user_generation < self.generation(GenerationScope::User(user_id)).await? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Store a refresh token valid for its idle lifetime, but not past
+    /// `family_expires_at`. A new family (`None`) is capped at its absolute
+    /// lifetime from now.
+    async fn store_refresh_token(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        client_id: Option<&str>,
+        token_family: Uuid,
+        family_expires_at: Option<DateTime<Utc>>,
+        binding: SessionBinding,
+    ) -> Result<RefreshToken, AuthError> {
+        let token_id = Uuid::new_v4();
+        let now = Utc::now();
+        let lifetimes = self.ttl_policy.resolve(tenant_id, client_id);
+        let family_expires_at = family_expires_at.unwrap_or(now + lifetimes.refresh_absolute_ttl);
+        let expires_at = (now + lifetimes.refresh_ttl).min(family_expires_at);
+
+        // Generate a secure random token
+        let token_hash = format!("rt_{}", Uuid::new_v4());
+
+        let refresh_token = RefreshToken {
+            id: token_id,
+            user_id,
+            tenant_id,
+            token_family,
+            token_hash: token_hash.clone(),
+            device_fingerprint: None,
+            user_agent: None,
+            ip_address: None,
+            expires_at,
+            revoked_at: None,
+            revoked_reason: None,
+            created_at: now,
+            session_id: binding.session_id,
+            access_token_jti: binding.access_token_jti,
+            family_expires_at: Some(family_expires_at),
+            client_id: client_id.map(str::to_string),
+        };
+
+        self.refresh_token_store
+            .create(refresh_token.clone())
+            .await?;
+
+        Ok(refresh_token)
+    }
+
     /// Clean up expired tokens (No-op in trait-based implementation as DB handles it)
     #[allow(dead_code)]
     async fn cleanup_expired_tokens(&self) {}
@@ -257,7 +509,7 @@ impl TokenEngine {
 
 #[async_trait::async_trait]
 impl TokenProvider for TokenEngine {
-    async fn issue_access_token(&self, claims: Claims) -> Result<AccessToken, AuthError> {
+    async fn issue_access_token(&self, mut claims: Claims) -> Result<AccessToken, AuthError> {
         let user_id = Uuid::parse_str(&claims.sub).map_err(|_| AuthError::TokenError {
             kind: TokenErrorKind::Invalid,
         })?;
@@ -266,15 +518,43 @@ impl TokenProvider for TokenEngine {
             kind: TokenErrorKind::Invalid,
         })?;
 
+        if let Some(enricher) = &self.enricher {
+            enricher.enrich(user_id, tenant_id, &mut claims).await?;
+        }
+
+        // The audience is the OAuth client the token was issued to
+        let lifetimes = self.ttl_policy.resolve(tenant_id, Some(&claims.aud));
+        let generation = self.generation(GenerationScope::Tenant(tenant_id)).await?;
+        let user_generation = self.generation(GenerationScope::User(user_id)).await?;
+
+        // Keep the caller's jti so the token can be revoked through whatever
+        // it was recorded against
+        let jti = match Uuid::parse_str(&claims.jti) {
+            Ok(_) => claims.jti,
+            Err(_) => Uuid::new_v4().to_string(),
+        };
+        let jwt_claims = JwtClaims {
+            sub: user_id.to_string(),
+            iss: String::new(),
+            aud: String::new(),
+            exp: 0,
+            iat: 0,
+            nbf: 0,
+            jti,
+            tenant_id: tenant_id.to_string(),
+            permissions: claims.permissions,
+            roles: claims.roles,
+            scope: claims.scope,
+            session_id: claims.session_id,
+            client_id: claims.client_id,
+            ext: claims.ext,
+            generation,
+            user_generation,
+        };
+
         let token = self
             .jwt_service
-            .generate_access_token(
-                user_id,
-                tenant_id,
-                claims.permissions,
-                claims.roles,
-                claims.scope.clone(),
-            )
+            .sign_access_token(jwt_claims, lifetimes.access_ttl)
             .await
             .map_err(|e| match e {
                 JwtError::EncodingError(_) => AuthError::TokenError {
@@ -289,7 +569,7 @@ impl TokenProvider for TokenEngine {
         Ok(AccessToken {
             token,
             token_type: "Bearer".to_string(),
-            expires_in: 900,
+            expires_in: lifetimes.access_ttl.num_seconds().max(0) as u64,
             scope: None,
         })
     }
@@ -299,34 +579,19 @@ impl TokenProvider for TokenEngine {
         user_id: Uuid,
         tenant_id: Uuid,
     ) -> Result<RefreshToken, AuthError> {
-        let token_id = Uuid::new_v4();
-        let token_family = Uuid::new_v4();
-        let now = Utc::now();
-        let expires_at = now + Duration::days(30);
-
-        // Generate a secure random token
-        let token_hash = format!("rt_{}", Uuid::new_v4());
-
-        let refresh_token = RefreshToken {
-            id: token_id,
-            user_id,
-            tenant_id,
-            token_family,
-            token_hash: token_hash.clone(),
-            device_fingerprint: None,
-            user_agent: None,
-            ip_address: None,
-            expires_at,
-            revoked_at: None,
-            revoked_reason: None,
-            created_at: now,
-        };
-
-        self.refresh_token_store
-            .create(refresh_token.clone())
-            .await?;
+        self.issue_refresh_token_for_client(user_id, tenant_id, None, SessionBinding::default())
+            .await
+    }
 
-        Ok(refresh_token)
+    async fn issue_refresh_token_for_client(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        client_id: Option<&str>,
+        binding: SessionBinding,
+    ) -> Result<RefreshToken, AuthError> {
+        self.store_refresh_token(user_id, tenant_id, client_id, Uuid::new_v4(), None, binding)
+            .await
     }
 
     async fn validate_token(&self, token: &str) -> Result<Claims, AuthError> {
@@ -334,17 +599,7 @@ impl TokenProvider for TokenEngine {
             .jwt_service
             .validate_token(token)
             .await
-            .map_err(|e| match e {
-                JwtError::TokenExpired => AuthError::TokenError {
-                    kind: TokenErrorKind::Expired,
-                },
-                JwtError::ValidationError { .. } => AuthError::TokenError {
-                    kind: TokenErrorKind::Invalid,
-                },
-                _ => AuthError::TokenError {
-                    kind: TokenErrorKind::Invalid,
-                },
-            })?;
+            .map_err(token_error)?;
 
         // Check blacklist using JTI
         if let Ok(jti) = Uuid::parse_str(&jwt_claims.jti) {
@@ -354,20 +609,77 @@ impl TokenProvider for TokenEngine {
                 });
             }
         }
+        if self.superseded(&jwt_claims).await? {
+            return Err(AuthError::TokenError {
+                kind: TokenErrorKind::Revoked,
+            });
+        }
 
-        Ok(Claims {
-            sub: jwt_claims.sub,
-            iss: jwt_claims.iss,
-            aud: jwt_claims.aud,
-            exp: jwt_claims.exp,
-            iat: jwt_claims.iat,
-            nbf: jwt_claims.nbf,
-            jti: jwt_claims.jti,
-            tenant_id: jwt_claims.tenant_id,
-            permissions: jwt_claims.permissions,
-            roles: jwt_claims.roles,
-            scope: jwt_claims.scope,
-        })
+        Ok(claims_from(jwt_claims))
+    }
+
+    async fn validate_tokens(
+        &self,
+        tokens: &[String],
+    ) -> Result<Vec<Result<Claims, AuthError>>, AuthError> {
+        if tokens.is_empty() {
+            return Ok(Vec::new());
+        }
+        let validator = self.jwt_service.batch_validator().await.map_err(|e| {
+            AuthError::ConfigurationError {
+                message: format!("Failed to load verification key: {}", e),
+            }
+        })?;
+
+        // Signature checks are CPU-bound, so the batch is split across
+        // blocking threads rather than interleaved on this task
+        let chunk_size = tokens.len().div_ceil(BATCH_WORKERS);
+        let workers: Vec<_> = tokens
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let validator = validator.clone();
+                let chunk = chunk.to_vec();
+                tokio::task::spawn_blocking(move || {
+                    chunk
+                        .iter()
+                        .map(|token| validator.validate(token))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut decoded = Vec::with_capacity(tokens.len());
+        for worker in workers {
+            decoded.extend(worker.await.map_err(|_| AuthError::InternalError)?);
+        }
+
+        // One revocation lookup for the whole batch
+        let jtis: Vec<Uuid> = decoded
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .filter_map(|c| Uuid::parse_str(&c.jti).ok())
+            .collect();
+        let revoked = self.revoked_token_store.revoked_among(&jtis).await?;
+
+        let mut results = Vec::with_capacity(decoded.len());
+        for result in decoded {
+            let jwt_claims = match result {
+                Ok(jwt_claims) => jwt_claims,
+                Err(e) => {
+                    results.push(Err(token_error(e)));
+                    continue;
+                }
+            };
+            if Uuid::parse_str(&jwt_claims.jti).is_ok_and(|jti| revoked.contains(&jti))
+                || self.superseded(&jwt_claims).await?
+            {
+                results.push(Err(AuthError::TokenError {
+                    kind: TokenErrorKind::Revoked,
+                }));
+                continue;
+            }
+            results.push(Ok(claims_from(jwt_claims)));
+        }
+        Ok(results)
     }
 
     async fn revoke_token(
@@ -386,6 +698,15 @@ impl TokenProvider for TokenEngine {
     }
 
     async fn refresh_tokens(&self, refresh_token_hash: &str) -> Result<TokenPair, AuthError> {
+        self.refresh_tokens_for_client(refresh_token_hash, None)
+            .await
+    }
+
+    async fn refresh_tokens_for_client(
+        &self,
+        refresh_token_hash: &str,
+        client_id: Option<&str>,
+    ) -> Result<TokenPair, AuthError> {
         let token_data = self
             .refresh_token_store
             .find_by_hash(refresh_token_hash)
@@ -394,38 +715,106 @@ impl TokenProvider for TokenEngine {
                 kind: TokenErrorKind::Invalid,
             })?;
 
-        if token_data.expires_at < Utc::now() {
+        // Checked before anything else, so that a client holding another's
+        // token learns nothing about it and cannot revoke its family
+        if token_data.client_id.as_deref() != client_id {
+            tracing::warn!(
+                token_client = ?token_data.client_id,
+                presented_by = ?client_id,
+                "Refresh token presented by a client it was not issued to"
+            );
+            metrics::counter!("auth_refresh_client_mismatch_total", 1);
+            return Err(AuthError::TokenError {
+                kind: TokenErrorKind::Invalid,
+            });
+        }
+
+        // Each rotation extends the idle lifetime, up to the family's cap.
+        // Families from before the cap count it from their latest token.
+        let now = Utc::now();
+        let family_expires_at = token_data.family_expires_at.unwrap_or_else(|| {
+            token_data.created_at
+                + self
+                    .ttl_policy
+                    .resolve(token_data.tenant_id, client_id)
+                    .refresh_absolute_ttl
+        });
+        if family_expires_at <= now {
             return Err(AuthError::TokenError {
-                kind: TokenErrorKind::Expired,
+                kind: TokenErrorKind::AbsoluteExpired,
+            });
+        }
+        if token_data.expires_at < now {
+            return Err(AuthError::TokenError {
+                kind: TokenErrorKind::IdleExpired,
             });
         }
 
         if token_data.revoked_at.is_some() {
+            // With takeover response on, a revoked token whose family is
+            // still live was rotated and is being replayed: the family is
+            // held by two parties, and is no longer trusted in either's hands
+            if let Some(takeover) = &self.takeover {
+                let live = self
+                    .refresh_token_store
+                    .revoke_family(token_data.token_family)
+                    .await?;
+                if live > 0 {
+                    tracing::warn!(
+                        user_id = %token_data.user_id,
+                        family_id = %token_data.token_family,
+                        "Rotated refresh token presented again; family revoked"
+                    );
+                    takeover
+                        .respond(
+                            token_data.tenant_id,
+                            token_data.user_id,
+                            TakeoverSignal::RefreshTokenReuse {
+                                family_id: token_data.token_family,
+                            },
+                        )
+                        .await;
+                }
+            }
             return Err(AuthError::TokenError {
                 kind: TokenErrorKind::Revoked,
             });
         }
 
-        // Rotate
+        // Rotate within the same family and session
         self.refresh_token_store.revoke(token_data.id).await?;
 
+        let jti = Uuid::new_v4();
         let claims = Claims {
             sub: token_data.user_id.to_string(),
             iss: "auth-platform".to_string(),
-            aud: "auth-platform".to_string(),
+            aud: client_id.unwrap_or("auth-platform").to_string(),
             exp: (Utc::now() + Duration::minutes(15)).timestamp(),
             iat: Utc::now().timestamp(),
             nbf: Utc::now().timestamp(),
-            jti: Uuid::new_v4().to_string(),
+            jti: jti.to_string(),
             tenant_id: token_data.tenant_id.to_string(),
             permissions: vec![],
             roles: vec![],
             scope: None,
+            session_id: token_data.session_id.map(|id| id.to_string()),
+            client_id: token_data.client_id.clone(),
+            ext: Default::default(),
         };
 
         let access_token = self.issue_access_token(claims).await?;
         let new_refresh_token = self
-            .issue_refresh_token(token_data.user_id, token_data.tenant_id)
+            .store_refresh_token(
+                token_data.user_id,
+                token_data.tenant_id,
+                client_id,
+                token_data.token_family,
+                Some(family_expires_at),
+                SessionBinding {
+                    session_id: token_data.session_id,
+                    access_token_jti: Some(jti),
+                },
+            )
             .await?;
 
         Ok(TokenPair {
@@ -434,6 +823,27 @@ impl TokenProvider for TokenEngine {
         })
     }
 
+    async fn revoke_session_tokens(&self, session_id: Uuid) -> Result<(), AuthError> {
+        let tokens = self.refresh_token_store.find_by_session(session_id).await?;
+        let expiry = Utc::now() + Duration::hours(24);
+        let mut families = HashSet::new();
+        for token in tokens {
+            // Rotated tokens are already revoked, but the access tokens
+            // issued with them may still be live
+            if let Some(jti) = token.access_token_jti {
+                self.revoked_token_store
+                    .add_to_blacklist(jti, token.user_id, token.tenant_id, expiry)
+                    .await?;
+            }
+            if families.insert(token.token_family) {
+                self.refresh_token_store
+                    .revoke_family(token.token_family)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
     async fn introspect_token(&self, token: &str) -> Result<TokenIntrospectionResponse, AuthError> {
         let claims_result = self.jwt_service.extract_claims_unsafe(token);
 
@@ -453,6 +863,7 @@ impl TokenProvider for TokenEngine {
                     aud: None,
                     iss: None,
                     jti: None,
+                    session_id: None,
                 })
             }
         };
@@ -468,6 +879,9 @@ impl TokenProvider for TokenEngine {
                 is_revoked = true;
             }
         }
+        if self.superseded(&claims).await.unwrap_or(false) {
+            is_revoked = true;
+        }
 
         let is_expired = self.jwt_service.is_token_expired(&claims);
         let active = !is_revoked && !is_expired;
@@ -475,7 +889,7 @@ impl TokenProvider for TokenEngine {
         Ok(TokenIntrospectionResponse {
             active,
             scope: None,
-            client_id: None,
+            client_id: claims.client_id,
             username: Some(claims.sub.clone()),
             token_type: Some("Bearer".to_string()),
             exp: Some(claims.exp),
@@ -485,10 +899,48 @@ impl TokenProvider for TokenEngine {
             aud: Some(claims.aud),
             iss: Some(claims.iss),
             jti: Some(claims.jti),
+            session_id: claims.session_id,
         })
     }
 
     async fn get_jwks(&self) -> serde_json::Value {
         self.jwt_service.get_jwk_set()
     }
+
+    async fn sign_claims(&self, claims: serde_json::Value) -> Result<String, AuthError> {
+        self.jwt_service
+            .sign_claims(&claims)
+            .await
+            .map_err(|e| AuthError::UTCryptoError(e.to_string()))
+    }
+}
+
+fn token_error(e: JwtError) -> AuthError {
+    match e {
+        JwtError::TokenExpired => AuthError::TokenError {
+            kind: TokenErrorKind::Expired,
+        },
+        _ => AuthError::TokenError {
+            kind: TokenErrorKind::Invalid,
+        },
+    }
+}
+
+fn claims_from(jwt_claims: JwtClaims) -> Claims {
+    Claims {
+        sub: jwt_claims.sub,
+        iss: jwt_claims.iss,
+        aud: jwt_claims.aud,
+        exp: jwt_claims.exp,
+        iat: jwt_claims.iat,
+        nbf: jwt_claims.nbf,
+        jti: jwt_claims.jti,
+        tenant_id: jwt_claims.tenant_id,
+        permissions: jwt_claims.permissions,
+        roles: jwt_claims.roles,
+        scope: jwt_claims.scope,
+        session_id: jwt_claims.session_id,
+        client_id: jwt_claims.client_id,
+        ext: jwt_claims.ext,
+    }
 }
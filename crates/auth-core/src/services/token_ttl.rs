@@ -0,0 +1,204 @@
+//! Token lifetime resolution
+//!
+//! Lifetimes resolve per field in the order client override, tenant override,
+//! platform default, and are always clamped to the platform min/max bounds.
+//! A refresh token family's absolute lifetime is never shorter than the idle
+//! lifetime of its tokens.
+
+use auth_config::{SecurityConfig, TokenTtlConfig, TokenTtlOverride};
+use chrono::Duration;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenLifetimes {
+    pub access_ttl: Duration,
+    /// Idle lifetime: how long a refresh token stays valid unused
+    pub refresh_ttl: Duration,
+    /// How long a refresh token family can be rotated, from its first token
+    pub refresh_absolute_ttl: Duration,
+}
+
+pub struct TokenTtlPolicy {
+    default_access_secs: u64,
+    default_refresh_secs: u64,
+    bounds: TokenTtlConfig,
+    tenants: HashMap<Uuid, TokenTtlOverride>,
+}
+
+impl TokenTtlPolicy {
+    pub fn new(
+        default_access_secs: u64,
+        default_refresh_secs: u64,
+        config: TokenTtlConfig,
+    ) -> Self {
+        let tenants = config
+            .tenants
+            .iter()
+            .filter_map(|(id, o)| match Uuid::parse_str(id) {
+                Ok(id) => Some((id, o.clone())),
+                Err(_) => {
+                    tracing::warn!("Ignoring token TTL override for invalid tenant id '{}'", id);
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            default_access_secs,
+            default_refresh_secs,
+            bounds: config,
+            tenants,
+        }
+    }
+
+    pub fn from_security_config(security: &SecurityConfig) -> Self {
+        Self::new(
+            u64::from(security.jwt_expiry_minutes) * 60,
+            u64::from(security.refresh_token_expiry_days) * 24 * 60 * 60,
+            security.token_ttl.clone(),
+        )
+    }
+
+    /// Platform-wide lifetimes, ignoring any overrides
+    pub fn defaults(&self) -> TokenLifetimes {
+        self.lifetimes(
+            self.default_access_secs,
+            self.default_refresh_secs,
+            self.bounds.refresh_absolute_ttl_seconds,
+        )
+    }
+
+    pub fn resolve(&self, tenant_id: Uuid, client_id: Option<&str>) -> TokenLifetimes {
+        let client = client_id.and_then(|c| self.bounds.clients.get(c));
+        let tenant = self.tenants.get(&tenant_id);
+
+        let access = client
+            .and_then(|o| o.access_ttl_seconds)
+            .or_else(|| tenant.and_then(|o| o.access_ttl_seconds))
+            .unwrap_or(self.default_access_secs);
+        let refresh = client
+            .and_then(|o| o.refresh_ttl_seconds)
+            .or_else(|| tenant.and_then(|o| o.refresh_ttl_seconds))
+            .unwrap_or(self.default_refresh_secs);
+        let absolute = client
+            .and_then(|o| o.refresh_absolute_ttl_seconds)
+            .or_else(|| tenant.and_then(|o| o.refresh_absolute_ttl_seconds))
+            .unwrap_or(self.bounds.refresh_absolute_ttl_seconds);
+
+        self.lifetimes(access, refresh, absolute)
+    }
+
+    fn lifetimes(&self, access_secs: u64, refresh_secs: u64, absolute_secs: u64) -> TokenLifetimes {
+        let b = &self.bounds;
+        // Guard the upper bound so a misconfigured range cannot make clamp panic
+        let access = access_secs.clamp(
+            b.min_access_ttl_seconds,
+            b.max_access_ttl_seconds.max(b.min_access_ttl_seconds),
+        );
+        let max_refresh = b.max_refresh_ttl_seconds.max(b.min_refresh_ttl_seconds);
+        let refresh = refresh_secs.clamp(b.min_refresh_ttl_seconds, max_refresh);
+        let absolute = absolute_secs.clamp(refresh, max_refresh);
+        TokenLifetimes {
+            access_ttl: Duration::seconds(access as i64),
+            refresh_ttl: Duration::seconds(refresh as i64),
+            refresh_absolute_ttl: Duration::seconds(absolute as i64),
+        }
+    }
+}
+
+impl Default for TokenTtlPolicy {
+    /// 15 minute access tokens and 30 day refresh tokens
+    fn default() -> Self {
+        Self::new(15 * 60, 30 * 24 * 60 * 60, TokenTtlConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> (TokenTtlPolicy, Uuid) {
+        let tenant = Uuid::new_v4();
+        let mut config = TokenTtlConfig::default();
+        config.tenants.insert(
+            tenant.to_string(),
+            TokenTtlOverride {
+                access_ttl_seconds: Some(600),
+                refresh_ttl_seconds: Some(7 * 24 * 3600),
+                refresh_absolute_ttl_seconds: Some(14 * 24 * 3600),
+            },
+        );
+        config.clients.insert(
+            "mobile".to_string(),
+            TokenTtlOverride {
+                access_ttl_seconds: Some(300),
+                refresh_ttl_seconds: None,
+                refresh_absolute_ttl_seconds: None,
+            },
+        );
+        config.clients.insert(
+            "greedy".to_string(),
+            TokenTtlOverride {
+                access_ttl_seconds: Some(24 * 3600),
+                refresh_ttl_seconds: Some(10),
+                refresh_absolute_ttl_seconds: Some(365 * 24 * 3600),
+            },
+        );
+        (TokenTtlPolicy::new(900, 30 * 24 * 3600, config), tenant)
+    }
+
+    #[test]
+    fn test_client_overrides_tenant_overrides_default() {
+        let (policy, tenant) = policy();
+
+        let defaults = policy.resolve(Uuid::new_v4(), None);
+        assert_eq!(defaults.access_ttl, Duration::seconds(900));
+
+        let tenant_only = policy.resolve(tenant, None);
+        assert_eq!(tenant_only.access_ttl, Duration::seconds(600));
+        assert_eq!(tenant_only.refresh_ttl, Duration::days(7));
+
+        let client = policy.resolve(tenant, Some("mobile"));
+        assert_eq!(client.access_ttl, Duration::seconds(300));
+        // Refresh falls back to the tenant override
+        assert_eq!(client.refresh_ttl, Duration::days(7));
+    }
+
+    #[test]
+    fn test_overrides_are_clamped_to_bounds() {
+        let (policy, tenant) = policy();
+        let lifetimes = policy.resolve(tenant, Some("greedy"));
+        assert_eq!(lifetimes.access_ttl, Duration::seconds(3600));
+        assert_eq!(lifetimes.refresh_ttl, Duration::seconds(3600));
+        assert_eq!(lifetimes.refresh_absolute_ttl, Duration::days(90));
+    }
+
+    #[test]
+    fn test_absolute_lifetime_is_never_shorter_than_idle() {
+        let (policy, tenant) = policy();
+        assert_eq!(
+            policy.resolve(tenant, None).refresh_absolute_ttl,
+            Duration::days(14)
+        );
+        assert_eq!(policy.defaults().refresh_absolute_ttl, Duration::days(90));
+
+        let mut config = TokenTtlConfig {
+            refresh_absolute_ttl_seconds: 24 * 3600,
+            ..Default::default()
+        };
+        config.tenants.insert(
+            tenant.to_string(),
+            TokenTtlOverride {
+                refresh_absolute_ttl_seconds: Some(60),
+                ..Default::default()
+            },
+        );
+        let policy = TokenTtlPolicy::new(900, 7 * 24 * 3600, config);
+        assert_eq!(
+            policy.resolve(tenant, None).refresh_absolute_ttl,
+            Duration::days(7)
+        );
+        assert_eq!(policy.defaults().refresh_absolute_ttl, Duration::days(7));
+    }
+}
@@ -1,63 +1,294 @@
+//! Passkeys (WebAuthn)
+//!
+//! Credentials are registered as discoverable (resident) keys, so sign-in can
+//! start without an identifier: the authenticator returns the user handle and
+//! the browser can offer passkeys from the username field's autofill
+//! (conditional mediation).
+
+use crate::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use crate::error::AuthError;
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use auth_config::WebauthnConfig;
+use base64::Engine as _;
+use dashmap::DashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
+use webauthn_rs::prelude::{CredentialID, DiscoverableKey, Url, Webauthn, WebauthnBuilder};
+use webauthn_rs_proto::{Mediation, ResidentKeyRequirement};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Passkey;
-
-impl Passkey {
-    pub fn cred_id(&self) -> Uuid {
-        Uuid::nil()
-    }
-}
-
-// Dummy types for return signature
-#[derive(Debug, Clone)]
-pub struct CreationChallengeResponse;
-#[derive(Debug, Clone)]
-pub struct RegisterPublicKeyCredentialCreationOptions;
-#[derive(Debug, Clone)]
-pub struct PasskeyRegistration;
-#[derive(Debug, Clone)]
-pub struct RegisterPublicKeyCredential;
+// Ceremony types cross the API boundary; re-exported so callers need no
+// direct webauthn-rs dependency
+pub use webauthn_rs::prelude::{
+    CreationChallengeResponse, DiscoverableAuthentication, Passkey, PasskeyRegistration,
+    PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse,
+};
 
 #[async_trait]
 pub trait WebauthnStore: Send + Sync {
-    async fn save_passkey(&self, user_id: Uuid, passkey: &Passkey) -> anyhow::Result<()>;
+    async fn save_passkey(&self, user_id: Uuid, passkey: &Passkey) -> Result<(), AuthError>;
+    async fn passkeys(&self, user_id: Uuid) -> Result<Vec<Passkey>, AuthError>;
+    /// Persist a passkey whose signature counter or backup state changed
+    async fn update_passkey(&self, user_id: Uuid, passkey: &Passkey) -> Result<(), AuthError>;
+}
+
+/// Stable key for a credential, as stored in `passkeys.id`
+pub fn credential_key(cred_id: &CredentialID) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(cred_id.as_ref())
 }
 
 pub struct WebauthnService {
+    webauthn: Webauthn,
+    ceremony_ttl: Duration,
     store: Arc<dyn WebauthnStore>,
+    audit: Option<Arc<dyn AuditLogger>>,
 }
 
 impl WebauthnService {
-    pub fn new(store: Arc<dyn WebauthnStore>, _rp_origin: &str, _rp_id: &str) -> Self {
-        Self { store }
+    pub fn new(store: Arc<dyn WebauthnStore>, config: &WebauthnConfig) -> Result<Self, AuthError> {
+        let origin = Url::parse(&config.rp_origin).map_err(|e| AuthError::ConfigurationError {
+            message: format!("Invalid WebAuthn origin: {}", e),
+        })?;
+        let webauthn = WebauthnBuilder::new(&config.rp_id, &origin)
+            .and_then(|builder| builder.rp_name(&config.rp_name).build())
+            .map_err(|e| AuthError::ConfigurationError {
+                message: format!("Invalid WebAuthn relying party: {}", e),
+            })?;
+
+        Ok(Self {
+            webauthn,
+            ceremony_ttl: Duration::from_secs(config.ceremony_ttl_seconds),
+            store,
+            audit: None,
+        })
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
     }
 
+    /// How long ceremony state should be kept while the browser answers
+    pub fn ceremony_ttl(&self) -> Duration {
+        self.ceremony_ttl
+    }
+
+    /// Options for creating a discoverable passkey. The returned registration
+    /// state must be kept server-side (or sealed) until `finish_registration`.
     pub async fn start_registration(
         &self,
-        _user_id: Uuid,
-        _username: &str,
-    ) -> anyhow::Result<(
-        CreationChallengeResponse,
-        RegisterPublicKeyCredentialCreationOptions,
-    )> {
-        Ok((
-            CreationChallengeResponse,
-            RegisterPublicKeyCredentialCreationOptions,
-        ))
+        user_id: Uuid,
+        username: &str,
+        display_name: &str,
+    ) -> Result<(CreationChallengeResponse, PasskeyRegistration), AuthError> {
+        let existing: Vec<CredentialID> = self
+            .store
+            .passkeys(user_id)
+            .await?
+            .iter()
+            .map(|p| p.cred_id().clone())
+            .collect();
+
+        let (mut options, registration) = self
+            .webauthn
+            .start_passkey_registration(
+                user_id,
+                username,
+                display_name,
+                (!existing.is_empty()).then_some(existing),
+            )
+            .map_err(|e| AuthError::ValidationError {
+                message: format!("Cannot start passkey registration: {}", e),
+            })?;
+
+        // Usernameless sign-in only works if the authenticator stores the
+        // credential together with the user handle
+        if let Some(selection) = options.public_key.authenticator_selection.as_mut() {
+            selection.require_resident_key = true;
+            selection.resident_key = Some(ResidentKeyRequirement::Required);
+        }
+
+        Ok((options, registration))
     }
 
     pub async fn finish_registration(
         &self,
         user_id: Uuid,
-        _challenge: &PasskeyRegistration,
-        _response: &RegisterPublicKeyCredential,
-    ) -> anyhow::Result<()> {
-        let passkey = Passkey;
+        tenant_id: Uuid,
+        registration: &PasskeyRegistration,
+        response: &RegisterPublicKeyCredential,
+    ) -> Result<Passkey, AuthError> {
+        let passkey = self
+            .webauthn
+            .finish_passkey_registration(response, registration)
+            .map_err(|e| AuthError::ValidationError {
+                message: format!("Passkey registration failed: {}", e),
+            })?;
         self.store.save_passkey(user_id, &passkey).await?;
+
+        self.log(
+            AuditEvent::new(
+                AuditCategory::Security,
+                "webauthn.passkey.registered",
+                AuditSeverity::Info,
+            )
+            .with_actor(user_id)
+            .with_context(None, None, Some(tenant_id))
+            .with_resource(credential_key(passkey.cred_id())),
+        )
+        .await;
+
+        Ok(passkey)
+    }
+
+    /// Challenge for a sign-in that does not know the user yet. The options
+    /// request conditional mediation so browsers can offer passkeys in autofill.
+    pub fn start_authentication(
+        &self,
+    ) -> Result<(RequestChallengeResponse, DiscoverableAuthentication), AuthError> {
+        let (mut options, authentication) = self
+            .webauthn
+            .start_discoverable_authentication()
+            .map_err(|e| AuthError::ValidationError {
+                message: format!("Cannot start passkey sign-in: {}", e),
+            })?;
+        options.mediation = Some(Mediation::Conditional);
+        Ok((options, authentication))
+    }
+
+    /// Verify the assertion and return the user it belongs to
+    pub async fn finish_authentication(
+        &self,
+        authentication: DiscoverableAuthentication,
+        response: &PublicKeyCredential,
+    ) -> Result<Uuid, AuthError> {
+        let (user_id, _) = self
+            .webauthn
+            .identify_discoverable_authentication(response)
+            .map_err(|_| AuthError::InvalidCredentials)?;
+
+        let mut passkeys = self.store.passkeys(user_id).await?;
+        let keys: Vec<DiscoverableKey> = passkeys.iter().map(DiscoverableKey::from).collect();
+        let result =
+            match self
+                .webauthn
+                .finish_discoverable_authentication(response, authentication, &keys)
+            {
+                Ok(result) => result,
+                Err(e) => {
+                    self.log(
+                        AuditEvent::new(
+                            AuditCategory::Authentication,
+                            "webauthn.passkey.failed",
+                            AuditSeverity::Warning,
+                        )
+                        .with_actor(user_id)
+                        .with_metadata(serde_json::json!({ "reason": e.to_string() })),
+                    )
+                    .await;
+                    return Err(AuthError::InvalidCredentials);
+                }
+            };
+
+        for passkey in passkeys.iter_mut() {
+            if passkey.update_credential(&result) == Some(true) {
+                self.store.update_passkey(user_id, passkey).await?;
+            }
+        }
+
+        self.log(
+            AuditEvent::new(
+                AuditCategory::Authentication,
+                "webauthn.passkey.used",
+                AuditSeverity::Info,
+            )
+            .with_actor(user_id)
+            .with_resource(credential_key(result.cred_id())),
+        )
+        .await;
+
+        Ok(user_id)
+    }
+
+    pub async fn has_passkeys(&self, user_id: Uuid) -> Result<bool, AuthError> {
+        Ok(!self.store.passkeys(user_id).await?.is_empty())
+    }
+
+    async fn log(&self, event: AuditEvent) {
+        if let Some(audit) = &self.audit {
+            audit.log(event).await;
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryWebauthnStore {
+    passkeys: DashMap<Uuid, Vec<Passkey>>,
+}
+
+#[async_trait]
+impl WebauthnStore for InMemoryWebauthnStore {
+    async fn save_passkey(&self, user_id: Uuid, passkey: &Passkey) -> Result<(), AuthError> {
+        self.passkeys
+            .entry(user_id)
+            .or_default()
+            .push(passkey.clone());
         Ok(())
     }
+
+    async fn passkeys(&self, user_id: Uuid) -> Result<Vec<Passkey>, AuthError> {
+        Ok(self
+            .passkeys
+            .get(&user_id)
+            .map(|p| p.clone())
+            .unwrap_or_default())
+    }
+
+    async fn update_passkey(&self, user_id: Uuid, passkey: &Passkey) -> Result<(), AuthError> {
+        if let Some(mut stored) = self.passkeys.get_mut(&user_id) {
+            if let Some(existing) = stored.iter_mut().find(|p| p.cred_id() == passkey.cred_id()) {
+                *existing = passkey.clone();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_registration_requires_resident_key() {
+        let service = WebauthnService::new(
+            Arc::new(InMemoryWebauthnStore::default()),
+            &WebauthnConfig::default(),
+        )
+        .unwrap();
+        let (options, _) = service
+            .start_registration(Uuid::new_v4(), "alice", "Alice")
+            .await
+            .unwrap();
+
+        let selection = options.public_key.authenticator_selection.unwrap();
+        assert!(selection.require_resident_key);
+        assert_eq!(
+            selection.resident_key,
+            Some(ResidentKeyRequirement::Required)
+        );
+    }
+
+    #[test]
+    fn test_sign_in_options_use_conditional_mediation() {
+        let service = WebauthnService::new(
+            Arc::new(InMemoryWebauthnStore::default()),
+            &WebauthnConfig::default(),
+        )
+        .unwrap();
+        let (options, _) = service.start_authentication().unwrap();
+
+        assert!(matches!(options.mediation, Some(Mediation::Conditional)));
+        // No allow-list: the authenticator picks the account
+        assert!(options.public_key.allow_credentials.is_empty());
+    }
 }
@@ -9,6 +9,8 @@ pub enum FlowState {
     Start,
     Identify,
     Authenticate,
+    /// Login looked automated; a solved CAPTCHA is needed to continue
+    CaptchaRequired,
     MfaRequired,
     ConsentRequired,
     ProfileRequired,
@@ -19,6 +21,13 @@ pub enum FlowState {
     Custom(String),
 }
 
+impl FlowState {
+    /// Whether the flow has finished and must not be advanced again
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, FlowState::Success | FlowState::Failed)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FlowContext {
     pub flow_id: String,
@@ -115,6 +124,9 @@ impl WorkflowEngine {
             FlowState::Authenticate => {
                 hints.insert("view".to_string(), "login_password".into());
             }
+            FlowState::CaptchaRequired => {
+                hints.insert("view".to_string(), "captcha_challenge".into());
+            }
             FlowState::MfaRequired => {
                 hints.insert("view".to_string(), "mfa_challenge".into());
             }
@@ -1,5 +1,7 @@
 pub mod engine;
 pub mod rules;
+pub mod sealed_state;
 
 pub use engine::{FlowAction, FlowContext, FlowResult, FlowState, StepHandler, WorkflowEngine};
 pub use rules::{Rule, RuleEngine};
+pub use sealed_state::FlowStateSealer;
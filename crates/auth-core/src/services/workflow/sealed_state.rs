@@ -0,0 +1,134 @@
+//! Stateless flow state
+//!
+//! Instead of keeping a [`FlowContext`] in the cache, the context can be
+//! handed to the client as an opaque token. The token is AES-256-GCM sealed,
+//! so it is both confidential and tamper-evident, and it carries its own
+//! expiry. Replay of terminal transitions is guarded by the caller using
+//! [`FlowStateSealer::consumed_key`].
+
+use super::engine::FlowContext;
+use crate::error::{AuthError, TokenErrorKind};
+use auth_crypto::SymmetricCipher;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+const FLOW_STATE_AAD: &[u8] = b"auth_flow_state";
+
+#[derive(Serialize, Deserialize)]
+struct SealedEnvelope {
+    ctx: FlowContext,
+    exp: i64,
+}
+
+pub struct FlowStateSealer {
+    cipher: SymmetricCipher,
+    ttl_seconds: i64,
+}
+
+impl FlowStateSealer {
+    pub fn new(cipher: SymmetricCipher, ttl_seconds: i64) -> Self {
+        Self {
+            cipher,
+            ttl_seconds,
+        }
+    }
+
+    /// Seal the context into a token valid for the configured TTL
+    pub fn seal(&self, ctx: &FlowContext) -> Result<String, AuthError> {
+        let envelope = SealedEnvelope {
+            ctx: ctx.clone(),
+            exp: Utc::now().timestamp() + self.ttl_seconds,
+        };
+        let plaintext = serde_json::to_vec(&envelope).map_err(|_| AuthError::InternalError)?;
+        self.cipher
+            .seal_to_string(&plaintext, FLOW_STATE_AAD)
+            .map_err(|_| AuthError::InternalError)
+    }
+
+    /// Open a token, rejecting tampered or expired ones.
+    /// Returns the context and the token's expiry timestamp.
+    pub fn open(&self, token: &str) -> Result<(FlowContext, i64), AuthError> {
+        let plaintext = self
+            .cipher
+            .open_from_string(token, FLOW_STATE_AAD)
+            .map_err(|_| AuthError::TokenError {
+                kind: TokenErrorKind::Invalid,
+            })?;
+        let envelope: SealedEnvelope =
+            serde_json::from_slice(&plaintext).map_err(|_| AuthError::TokenError {
+                kind: TokenErrorKind::Invalid,
+            })?;
+
+        if envelope.exp <= Utc::now().timestamp() {
+            return Err(AuthError::TokenError {
+                kind: TokenErrorKind::Expired,
+            });
+        }
+
+        Ok((envelope.ctx, envelope.exp))
+    }
+
+    pub fn ttl_seconds(&self) -> i64 {
+        self.ttl_seconds
+    }
+
+    /// Cache key marking a flow as finished; any further token for the flow is a replay
+    pub fn consumed_key(flow_id: &str) -> String {
+        format!("auth_flow_consumed:{}", flow_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::workflow::FlowState;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn sealer(ttl: i64) -> FlowStateSealer {
+        let cipher = SymmetricCipher::from_base64(&SymmetricCipher::generate_key()).unwrap();
+        FlowStateSealer::new(cipher, ttl)
+    }
+
+    fn context() -> FlowContext {
+        FlowContext {
+            flow_id: Uuid::new_v4().to_string(),
+            tenant_id: Uuid::new_v4(),
+            flow_type: "login".to_string(),
+            current_state: FlowState::Identify,
+            user_id: None,
+            data: HashMap::new(),
+            version: 1,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open_roundtrip() {
+        let sealer = sealer(900);
+        let ctx = context();
+        let token = sealer.seal(&ctx).unwrap();
+        let (opened, _) = sealer.open(&token).unwrap();
+        assert_eq!(opened.flow_id, ctx.flow_id);
+        assert_eq!(opened.current_state, FlowState::Identify);
+    }
+
+    #[test]
+    fn test_expired_token_rejected() {
+        let sealer = sealer(-1);
+        let token = sealer.seal(&context()).unwrap();
+        assert!(matches!(
+            sealer.open(&token),
+            Err(AuthError::TokenError {
+                kind: TokenErrorKind::Expired
+            })
+        ));
+    }
+
+    #[test]
+    fn test_token_from_other_key_rejected() {
+        let token = sealer(900).seal(&context()).unwrap();
+        assert!(sealer(900).open(&token).is_err());
+    }
+}
@@ -3,9 +3,17 @@
 //!
 //! Requirements Covered: 3.4, 7.1
 
-use auth_core::models::Claims;
-use auth_core::services::token_service::{TokenEngine, TokenProvider};
+use auth_core::error::{AuthError, TokenErrorKind};
+use auth_core::models::{Claims, RefreshToken, SessionBinding};
+use auth_core::services::forced_reauth::{
+    GenerationScope, InMemoryTokenGenerationStore, TokenGenerations,
+};
+use auth_core::services::token_service::{
+    InMemoryRefreshTokenStore, InMemoryRevokedTokenStore, RefreshTokenStore, TokenEngine,
+    TokenProvider,
+};
 use chrono::{Duration, Utc};
+use std::sync::Arc;
 use uuid::Uuid;
 
 #[tokio::test]
@@ -101,6 +109,81 @@ async fn test_expired_refresh_token_rejected() {
     assert!(refresh_token.expires_at > Utc::now());
 }
 
+#[tokio::test]
+#[allow(deprecated)]
+async fn test_refresh_slides_until_the_family_expires() {
+    /// Test: Rotation extends the idle lifetime up to the absolute cap
+    ///
+    /// Scenario:
+    /// 1. Rotate a token whose family ends before its idle lifetime would
+    /// 2. Verify the new token expires with the family
+    /// 3. Verify idle-expired and absolute-expired tokens fail distinctly
+    let store = Arc::new(InMemoryRefreshTokenStore::new(100));
+    let engine =
+        TokenEngine::new_with_stores(Arc::new(InMemoryRevokedTokenStore::new(100)), store.clone())
+            .await
+            .unwrap();
+    let now = Utc::now();
+    let token = |hash: &str, expires_at, family_expires_at| RefreshToken {
+        id: Uuid::new_v4(),
+        user_id: Uuid::new_v4(),
+        tenant_id: Uuid::new_v4(),
+        token_family: Uuid::new_v4(),
+        token_hash: hash.to_string(),
+        device_fingerprint: None,
+        user_agent: None,
+        ip_address: None,
+        expires_at,
+        revoked_at: None,
+        revoked_reason: None,
+        created_at: now - Duration::days(1),
+        session_id: None,
+        access_token_jti: None,
+        family_expires_at,
+        client_id: None,
+    };
+
+    let cap = now + Duration::days(2);
+    store
+        .create(token("near_cap", now + Duration::hours(1), Some(cap)))
+        .await
+        .unwrap();
+    let pair = engine.refresh_tokens("near_cap").await.unwrap();
+    let rotated = store
+        .find_by_hash(&pair.refresh_token)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(rotated.family_expires_at, Some(cap));
+    assert_eq!(rotated.expires_at, cap);
+
+    store
+        .create(token("idle", now - Duration::minutes(1), Some(cap)))
+        .await
+        .unwrap();
+    assert!(matches!(
+        engine.refresh_tokens("idle").await,
+        Err(AuthError::TokenError {
+            kind: TokenErrorKind::IdleExpired
+        })
+    ));
+
+    store
+        .create(token(
+            "capped",
+            now + Duration::hours(1),
+            Some(now - Duration::minutes(1)),
+        ))
+        .await
+        .unwrap();
+    assert!(matches!(
+        engine.refresh_tokens("capped").await,
+        Err(AuthError::TokenError {
+            kind: TokenErrorKind::AbsoluteExpired
+        })
+    ));
+}
+
 #[tokio::test]
 async fn test_device_context_tracking() {
     /// Test: Device fingerprint and context are tracked
@@ -208,6 +291,9 @@ async fn test_token_introspection() {
         permissions: vec!["read:users".to_string()],
         roles: vec!["admin".to_string()],
         scope: Some("openid profile".to_string()),
+        session_id: None,
+        client_id: None,
+        ext: Default::default(),
     };
 
     let access_token = engine.issue_access_token(claims.clone()).await.unwrap();
@@ -243,6 +329,9 @@ async fn test_revoked_token_introspection_shows_inactive() {
         permissions: vec![],
         roles: vec![],
         scope: None,
+        session_id: None,
+        client_id: None,
+        ext: Default::default(),
     };
 
     let token_jti = Uuid::parse_str(&claims.jti).unwrap();
@@ -292,3 +381,335 @@ async fn test_multiple_concurrent_refreshes() {
         "Second refresh with same token should fail"
     );
 }
+
+#[tokio::test]
+async fn test_batch_validation_keeps_order_and_flags_revoked() {
+    /// Test: Batch validation reports each token in input order
+    ///
+    /// Scenario:
+    /// 1. Issue several tokens and revoke one
+    /// 2. Validate them in a batch with a malformed token in between
+    /// 3. Verify each result lines up with its token
+    let engine = TokenEngine::new().await.unwrap();
+    let tenant_id = Uuid::new_v4();
+
+    let mut issued = Vec::new();
+    for _ in 0..9 {
+        let claims = Claims {
+            sub: Uuid::new_v4().to_string(),
+            iss: "auth-platform".to_string(),
+            aud: "auth-platform".to_string(),
+            exp: (Utc::now() + Duration::minutes(15)).timestamp(),
+            iat: Utc::now().timestamp(),
+            nbf: Utc::now().timestamp(),
+            jti: Uuid::new_v4().to_string(),
+            tenant_id: tenant_id.to_string(),
+            permissions: vec![],
+            roles: vec![],
+            scope: None,
+            session_id: None,
+            client_id: None,
+            ext: Default::default(),
+        };
+        let token = engine.issue_access_token(claims.clone()).await.unwrap();
+        issued.push((claims, token.token));
+    }
+
+    let revoked = &issued[4].0;
+    engine
+        .revoke_token(
+            Uuid::parse_str(&revoked.jti).unwrap(),
+            Uuid::parse_str(&revoked.sub).unwrap(),
+            tenant_id,
+        )
+        .await
+        .unwrap();
+
+    let mut batch: Vec<String> = issued.iter().map(|(_, t)| t.clone()).collect();
+    batch.insert(2, "not-a-jwt".to_string());
+
+    let results = engine.validate_tokens(&batch).await.unwrap();
+    assert_eq!(results.len(), batch.len());
+    assert!(results[2].is_err(), "Malformed token should be rejected");
+    assert!(results[5].is_err(), "Revoked token should be rejected");
+
+    let valid: Vec<_> = results
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 2 && *i != 5)
+        .map(|(_, r)| r.as_ref().unwrap().sub.clone())
+        .collect();
+    let expected: Vec<_> = issued
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 4)
+        .map(|(_, (c, _))| c.sub.clone())
+        .collect();
+    assert_eq!(valid, expected);
+}
+
+#[tokio::test]
+async fn test_session_revocation_cascades_to_its_tokens() {
+    /// Test: Revoking a session revokes the tokens issued under it
+    ///
+    /// Scenario:
+    /// 1. Issue a token pair bound to a session and rotate it once
+    /// 2. Revoke the session's tokens
+    /// 3. Verify both access tokens and the refresh family are revoked,
+    ///    while tokens outside the session keep working
+    let engine = TokenEngine::new().await.unwrap();
+    let user_id = Uuid::new_v4();
+    let tenant_id = Uuid::new_v4();
+    let session_id = Uuid::new_v4();
+    let jti = Uuid::new_v4();
+
+    let claims = Claims {
+        sub: user_id.to_string(),
+        iss: "auth-platform".to_string(),
+        aud: "auth-platform".to_string(),
+        exp: (Utc::now() + Duration::minutes(15)).timestamp(),
+        iat: Utc::now().timestamp(),
+        nbf: Utc::now().timestamp(),
+        jti: jti.to_string(),
+        tenant_id: tenant_id.to_string(),
+        permissions: vec![],
+        roles: vec![],
+        scope: None,
+        session_id: Some(session_id.to_string()),
+        client_id: None,
+        ext: Default::default(),
+    };
+    let access_token = engine.issue_access_token(claims).await.unwrap();
+    let refresh_token = engine
+        .issue_refresh_token_for_client(
+            user_id,
+            tenant_id,
+            None,
+            SessionBinding {
+                session_id: Some(session_id),
+                access_token_jti: Some(jti),
+            },
+        )
+        .await
+        .unwrap();
+    let unrelated = engine
+        .issue_refresh_token(user_id, tenant_id)
+        .await
+        .unwrap();
+
+    let introspection = engine.introspect_token(&access_token.token).await.unwrap();
+    assert_eq!(introspection.session_id, Some(session_id.to_string()));
+
+    // Rotation stays within the session
+    let rotated = engine
+        .refresh_tokens(&refresh_token.token_hash)
+        .await
+        .unwrap();
+    let rotated_claims = engine
+        .validate_token(&rotated.access_token.token)
+        .await
+        .unwrap();
+    assert_eq!(rotated_claims.session_id, Some(session_id.to_string()));
+
+    engine.revoke_session_tokens(session_id).await.unwrap();
+
+    for token in [&access_token.token, &rotated.access_token.token] {
+        assert!(matches!(
+            engine.validate_token(token).await,
+            Err(AuthError::TokenError {
+                kind: TokenErrorKind::Revoked
+            })
+        ));
+    }
+    assert!(
+        engine.refresh_tokens(&rotated.refresh_token).await.is_err(),
+        "Refresh family should be revoked with the session"
+    );
+    assert!(
+        engine.refresh_tokens(&unrelated.token_hash).await.is_ok(),
+        "Tokens outside the session should be unaffected"
+    );
+}
+
+#[tokio::test]
+async fn test_generation_bump_rejects_outstanding_access_tokens() {
+    /// Test: Forced reauthentication kills a tenant's access tokens
+    ///
+    /// Scenario:
+    /// 1. Issue access tokens in two tenants
+    /// 2. Bump the first tenant's token generation
+    /// 3. Verify its token is rejected, the other tenant's still works and
+    ///    tokens issued afterwards validate
+    let generations = Arc::new(TokenGenerations::new(
+        Arc::new(InMemoryTokenGenerationStore::default()),
+        std::time::Duration::from_secs(60),
+    ));
+    let engine = TokenEngine::new()
+        .await
+        .unwrap()
+        .with_generations(generations.clone());
+    let tenant_id = Uuid::new_v4();
+    let other_tenant = Uuid::new_v4();
+    let claims = |tenant_id: Uuid| Claims {
+        sub: Uuid::new_v4().to_string(),
+        iss: "auth-platform".to_string(),
+        aud: "auth-platform".to_string(),
+        exp: (Utc::now() + Duration::minutes(15)).timestamp(),
+        iat: Utc::now().timestamp(),
+        nbf: Utc::now().timestamp(),
+        jti: Uuid::new_v4().to_string(),
+        tenant_id: tenant_id.to_string(),
+        permissions: vec![],
+        roles: vec![],
+        scope: None,
+        session_id: None,
+        client_id: None,
+        ext: Default::default(),
+    };
+
+    let before = engine.issue_access_token(claims(tenant_id)).await.unwrap();
+    let other = engine
+        .issue_access_token(claims(other_tenant))
+        .await
+        .unwrap();
+    assert!(engine.validate_token(&before.token).await.is_ok());
+
+    generations
+        .bump(GenerationScope::Tenant(tenant_id))
+        .await
+        .unwrap();
+    assert!(matches!(
+        engine.validate_token(&before.token).await,
+        Err(AuthError::TokenError {
+            kind: TokenErrorKind::Revoked
+        })
+    ));
+    assert!(!engine.introspect_token(&before.token).await.unwrap().active);
+    assert!(engine.validate_token(&other.token).await.is_ok());
+
+    let after = engine.issue_access_token(claims(tenant_id)).await.unwrap();
+    let results = engine
+        .validate_tokens(&[before.token, after.token])
+        .await
+        .unwrap();
+    assert!(results[0].is_err());
+    assert!(results[1].is_ok());
+}
+
+#[tokio::test]
+async fn test_user_generation_bump_rejects_only_that_users_tokens() {
+    /// Test: A password or role change kills the user's access tokens
+    ///
+    /// Scenario:
+    /// 1. Issue access tokens to two users of one tenant
+    /// 2. Bump the first user's token generation
+    /// 3. Verify only the first user's token is rejected
+    let generations = Arc::new(TokenGenerations::new(
+        Arc::new(InMemoryTokenGenerationStore::default()),
+        std::time::Duration::from_secs(60),
+    ));
+    let engine = TokenEngine::new()
+        .await
+        .unwrap()
+        .with_generations(generations.clone());
+    let tenant_id = Uuid::new_v4();
+    let (user_id, other_user) = (Uuid::new_v4(), Uuid::new_v4());
+    let claims = |user_id: Uuid| Claims {
+        sub: user_id.to_string(),
+        iss: "auth-platform".to_string(),
+        aud: "auth-platform".to_string(),
+        exp: (Utc::now() + Duration::minutes(15)).timestamp(),
+        iat: Utc::now().timestamp(),
+        nbf: Utc::now().timestamp(),
+        jti: Uuid::new_v4().to_string(),
+        tenant_id: tenant_id.to_string(),
+        permissions: vec![],
+        roles: vec![],
+        scope: None,
+        session_id: None,
+        client_id: None,
+        ext: Default::default(),
+    };
+
+    let token = engine.issue_access_token(claims(user_id)).await.unwrap();
+    let other = engine.issue_access_token(claims(other_user)).await.unwrap();
+
+    generations.bump_user(user_id, "password change").await;
+    assert!(engine.validate_token(&token.token).await.is_err());
+    assert!(engine.validate_token(&other.token).await.is_ok());
+
+    let reissued = engine.issue_access_token(claims(user_id)).await.unwrap();
+    assert!(engine.validate_token(&reissued.token).await.is_ok());
+}
+
+#[tokio::test]
+#[allow(deprecated)]
+async fn test_refresh_tokens_are_bound_to_their_client() {
+    /// Test: Only the client a refresh token was issued to can rotate it
+    ///
+    /// Scenario:
+    /// 1. Issue a refresh token to one client
+    /// 2. Verify another client and first-party callers are refused,
+    ///    without harming the family
+    /// 3. Rotate it as the right client; the new tokens keep the client
+    /// 4. Revoke everything issued to the client
+    let store = Arc::new(InMemoryRefreshTokenStore::new(100));
+    let engine =
+        TokenEngine::new_with_stores(Arc::new(InMemoryRevokedTokenStore::new(100)), store.clone())
+            .await
+            .unwrap();
+    let (user_id, tenant_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let issued = engine
+        .issue_refresh_token_for_client(
+            user_id,
+            tenant_id,
+            Some("billing-app"),
+            SessionBinding::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(issued.client_id.as_deref(), Some("billing-app"));
+
+    for client_id in [Some("reports-app"), None] {
+        assert!(matches!(
+            engine
+                .refresh_tokens_for_client(&issued.token_hash, client_id)
+                .await,
+            Err(AuthError::TokenError {
+                kind: TokenErrorKind::Invalid
+            })
+        ));
+    }
+
+    let rotated = engine
+        .refresh_tokens_for_client(&issued.token_hash, Some("billing-app"))
+        .await
+        .unwrap();
+    let introspection = engine
+        .introspect_token(&rotated.access_token.token)
+        .await
+        .unwrap();
+    assert_eq!(introspection.client_id.as_deref(), Some("billing-app"));
+
+    let other_client = engine
+        .issue_refresh_token_for_client(
+            user_id,
+            tenant_id,
+            Some("reports-app"),
+            SessionBinding::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        store.revoke_client(tenant_id, "billing-app").await.unwrap(),
+        1
+    );
+    assert!(engine
+        .refresh_tokens_for_client(&rotated.refresh_token, Some("billing-app"))
+        .await
+        .is_err());
+    assert!(engine
+        .refresh_tokens_for_client(&other_client.token_hash, Some("reports-app"))
+        .await
+        .is_ok());
+}
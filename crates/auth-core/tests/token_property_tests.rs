@@ -51,6 +51,9 @@ fn claims_strategy() -> impl Strategy<Value = Claims> {
                 permissions,
                 roles,
                 scope: None,
+                session_id: None,
+                client_id: None,
+                ext: Default::default(),
             }
         })
 }
@@ -158,6 +161,9 @@ proptest! {
                 permissions: vec![],
                 roles: vec![],
                 scope: None,
+                session_id: None,
+                client_id: None,
+                ext: Default::default(),
             };
 
             let access_token = engine.issue_access_token(claims).await.unwrap();
@@ -285,6 +291,9 @@ async fn test_token_signature_tampering_detection() {
         permissions: vec!["read:users".to_string()],
         roles: vec!["admin".to_string()],
         scope: None,
+        session_id: None,
+        client_id: None,
+        ext: Default::default(),
     };
 
     let access_token = engine.issue_access_token(claims).await.unwrap();
@@ -376,6 +385,9 @@ async fn test_revocation_consistency() {
         permissions: vec![],
         roles: vec![],
         scope: None,
+        session_id: None,
+        client_id: None,
+        ext: Default::default(),
     };
 
     let token_jti = Uuid::parse_str(&claims.jti).unwrap();
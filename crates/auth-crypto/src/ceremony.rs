@@ -0,0 +1,468 @@
+//! Signing key ceremony and escrow
+//!
+//! A ceremony generates an RSA signing key, splits an escrow copy of it
+//! between custodians with Shamir secret sharing, and produces a record of
+//! the public key, its fingerprint and who holds which share. Any
+//! `threshold` custodians can later recover the key and import it into a
+//! [`KeyProvider`](crate::KeyProvider); the record is also what the live
+//! JWKS is checked against.
+
+use crate::keys::rsa_jwk;
+use crate::kms::{KeyProvider, SoftKeyProvider};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use rand::{rngs::OsRng, RngCore};
+use rsa::pkcs8::{
+    DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding,
+};
+use rsa::{Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+const SHARE_PREFIX: &str = "uac-share-v1";
+
+#[derive(Debug, Error)]
+pub enum CeremonyError {
+    #[error("Invalid ceremony parameters: {0}")]
+    InvalidParameters(String),
+    #[error("Invalid escrow share: {0}")]
+    InvalidShare(String),
+    #[error("{needed} shares are needed to recover the key, {got} were given")]
+    NotEnoughShares { needed: u8, got: usize },
+    #[error("Key does not match the ceremony record: {0}")]
+    Mismatch(String),
+    #[error("Key operation failed: {0}")]
+    Key(String),
+}
+
+fn key_err(e: impl std::fmt::Display) -> CeremonyError {
+    CeremonyError::Key(e.to_string())
+}
+
+// ============================================================================
+// Shamir secret sharing over GF(2^8)
+// ============================================================================
+
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            // x^8 + x^4 + x^3 + x + 1
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+fn gf_inv(a: u8) -> u8 {
+    // a^254 = a^-1 in GF(2^8)
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exponent = 254u8;
+    while exponent != 0 {
+        if exponent & 1 != 0 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// One custodian's part of a split secret
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscrowShare {
+    /// Evaluation point, 1 to 255
+    pub index: u8,
+    /// Shares needed to recover the secret
+    pub threshold: u8,
+    pub data: Vec<u8>,
+}
+
+impl EscrowShare {
+    /// Text form handed to a custodian: `uac-share-v1:<threshold>:<index>:<data>`
+    pub fn encode(&self) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            SHARE_PREFIX,
+            self.threshold,
+            self.index,
+            URL_SAFE_NO_PAD.encode(&self.data)
+        )
+    }
+
+    pub fn decode(text: &str) -> Result<Self, CeremonyError> {
+        let invalid = |reason: &str| CeremonyError::InvalidShare(reason.to_string());
+        let mut parts = text.trim().split(':');
+        if parts.next() != Some(SHARE_PREFIX) {
+            return Err(invalid("unknown share format"));
+        }
+        let mut number = |name: &str| {
+            parts
+                .next()
+                .and_then(|p| p.parse::<u8>().ok())
+                .ok_or_else(|| invalid(name))
+        };
+        let threshold = number("threshold")?;
+        let index = number("index")?;
+        let data = parts
+            .next()
+            .and_then(|p| URL_SAFE_NO_PAD.decode(p).ok())
+            .ok_or_else(|| invalid("data"))?;
+        if parts.next().is_some() || index == 0 || threshold < 2 || data.is_empty() {
+            return Err(invalid("malformed share"));
+        }
+        Ok(Self {
+            index,
+            threshold,
+            data,
+        })
+    }
+
+    /// SHA-256 of the encoded share, recorded so a custodian can confirm
+    /// they hold the share the ceremony gave them
+    pub fn fingerprint(&self) -> String {
+        hex::encode(Sha256::digest(self.encode().as_bytes()))
+    }
+}
+
+/// Split `secret` into `shares` parts, any `threshold` of which recover it
+pub fn split_secret(
+    secret: &[u8],
+    threshold: u8,
+    shares: u8,
+) -> Result<Vec<EscrowShare>, CeremonyError> {
+    if threshold < 2 || threshold > shares {
+        return Err(CeremonyError::InvalidParameters(format!(
+            "threshold must be between 2 and the number of shares ({}), got {}",
+            shares, threshold
+        )));
+    }
+    if secret.is_empty() {
+        return Err(CeremonyError::InvalidParameters(
+            "secret is empty".to_string(),
+        ));
+    }
+
+    let mut split: Vec<EscrowShare> = (1..=shares)
+        .map(|index| EscrowShare {
+            index,
+            threshold,
+            data: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+    let mut coefficients = vec![0u8; threshold as usize - 1];
+    for &byte in secret {
+        OsRng.fill_bytes(&mut coefficients);
+        for share in &mut split {
+            // Horner's rule, highest coefficient first
+            let y = coefficients
+                .iter()
+                .rev()
+                .fold(0u8, |acc, &c| gf_mul(acc, share.index) ^ c);
+            share.data.push(gf_mul(y, share.index) ^ byte);
+        }
+    }
+    Ok(split)
+}
+
+/// Recover a secret from at least `threshold` distinct shares
+pub fn combine_shares(shares: &[EscrowShare]) -> Result<Vec<u8>, CeremonyError> {
+    let first = shares
+        .first()
+        .ok_or(CeremonyError::NotEnoughShares { needed: 2, got: 0 })?;
+    let distinct: BTreeSet<u8> = shares.iter().map(|s| s.index).collect();
+    if distinct.len() < first.threshold as usize {
+        return Err(CeremonyError::NotEnoughShares {
+            needed: first.threshold,
+            got: distinct.len(),
+        });
+    }
+    if distinct.len() != shares.len() {
+        return Err(CeremonyError::InvalidShare(
+            "the same share was given twice".to_string(),
+        ));
+    }
+    if shares
+        .iter()
+        .any(|s| s.threshold != first.threshold || s.data.len() != first.data.len())
+    {
+        return Err(CeremonyError::InvalidShare(
+            "shares come from different splits".to_string(),
+        ));
+    }
+
+    let shares = &shares[..first.threshold as usize];
+    // Lagrange basis at x = 0; subtraction is XOR in GF(2^8)
+    let basis: Vec<u8> = shares
+        .iter()
+        .map(|share| {
+            shares
+                .iter()
+                .filter(|other| other.index != share.index)
+                .fold(1u8, |acc, other| {
+                    gf_mul(acc, gf_mul(other.index, gf_inv(other.index ^ share.index)))
+                })
+        })
+        .collect();
+    Ok((0..first.data.len())
+        .map(|i| {
+            shares
+                .iter()
+                .zip(&basis)
+                .fold(0u8, |acc, (share, &l)| acc ^ gf_mul(share.data[i], l))
+        })
+        .collect())
+}
+
+// ============================================================================
+// Ceremony
+// ============================================================================
+
+/// A custodian and the share they were given
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Custodian {
+    pub name: String,
+    pub share_index: u8,
+    pub share_fingerprint: String,
+}
+
+/// What a ceremony produced, without any secret material
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CeremonyRecord {
+    pub kid: String,
+    pub algorithm: String,
+    pub key_bits: usize,
+    pub created_at: DateTime<Utc>,
+    pub public_key_pem: String,
+    /// RFC 7638 JWK thumbprint of the public key
+    pub jwk_thumbprint: String,
+    pub threshold: u8,
+    pub custodians: Vec<Custodian>,
+}
+
+/// Output of [`run_ceremony`]. The private key is the live copy to install;
+/// each share goes to its custodian.
+pub struct CeremonyOutput {
+    pub record: CeremonyRecord,
+    pub private_key_pem: String,
+    pub shares: Vec<(String, EscrowShare)>,
+}
+
+/// RFC 7638 thumbprint of an RSA JWK
+pub fn jwk_thumbprint(jwk: &serde_json::Value) -> Result<String, CeremonyError> {
+    let member = |name: &str| {
+        jwk.get(name)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| CeremonyError::Mismatch(format!("JWK has no {}", name)))
+    };
+    if member("kty")? != "RSA" {
+        return Err(CeremonyError::Mismatch("JWK is not an RSA key".to_string()));
+    }
+    // Required members only, in lexicographic order, without whitespace
+    let canonical = format!(
+        r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#,
+        member("e")?,
+        member("n")?
+    );
+    Ok(URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes())))
+}
+
+fn thumbprint_of_pem(kid: &str, public_key_pem: &str) -> Result<String, CeremonyError> {
+    jwk_thumbprint(&rsa_jwk(kid, public_key_pem).map_err(key_err)?)
+}
+
+/// Generate a signing key and split its escrow copy between `custodians`
+pub fn run_ceremony(
+    kid: &str,
+    custodians: &[String],
+    threshold: u8,
+    key_bits: usize,
+    now: DateTime<Utc>,
+) -> Result<CeremonyOutput, CeremonyError> {
+    if kid.trim().is_empty() {
+        return Err(CeremonyError::InvalidParameters(
+            "kid is required".to_string(),
+        ));
+    }
+    let names: BTreeSet<&str> = custodians.iter().map(String::as_str).collect();
+    if names.len() != custodians.len() || names.contains("") {
+        return Err(CeremonyError::InvalidParameters(
+            "custodian names must be distinct and non-empty".to_string(),
+        ));
+    }
+    let count = u8::try_from(custodians.len()).map_err(|_| {
+        CeremonyError::InvalidParameters("at most 255 custodians are supported".to_string())
+    })?;
+    if key_bits < 2048 {
+        return Err(CeremonyError::InvalidParameters(
+            "signing keys must be at least 2048 bits".to_string(),
+        ));
+    }
+
+    let key = RsaPrivateKey::new(&mut OsRng, key_bits).map_err(key_err)?;
+    let der = key.to_pkcs8_der().map_err(key_err)?;
+    let private_key_pem = key.to_pkcs8_pem(LineEnding::LF).map_err(key_err)?;
+    let public_key_pem = RsaPublicKey::from(&key)
+        .to_public_key_pem(LineEnding::LF)
+        .map_err(key_err)?;
+
+    let shares = split_secret(der.as_bytes(), threshold, count)?;
+    let record = CeremonyRecord {
+        kid: kid.to_string(),
+        algorithm: "RS256".to_string(),
+        key_bits,
+        created_at: now,
+        jwk_thumbprint: thumbprint_of_pem(kid, &public_key_pem)?,
+        public_key_pem,
+        threshold,
+        custodians: custodians
+            .iter()
+            .zip(&shares)
+            .map(|(name, share)| Custodian {
+                name: name.clone(),
+                share_index: share.index,
+                share_fingerprint: share.fingerprint(),
+            })
+            .collect(),
+    };
+
+    Ok(CeremonyOutput {
+        record,
+        private_key_pem: private_key_pem.to_string(),
+        shares: custodians.iter().cloned().zip(shares).collect(),
+    })
+}
+
+/// Recover the escrowed key as a PKCS#8 PEM document
+///
+/// Every share must be one the record lists, and the recovered key must be
+/// the record's key.
+pub fn recover_key(
+    record: &CeremonyRecord,
+    shares: &[EscrowShare],
+) -> Result<String, CeremonyError> {
+    for share in shares {
+        let recorded = record
+            .custodians
+            .iter()
+            .any(|c| c.share_index == share.index && c.share_fingerprint == share.fingerprint());
+        if !recorded {
+            return Err(CeremonyError::InvalidShare(format!(
+                "share {} is not one recorded for {}",
+                share.index, record.kid
+            )));
+        }
+    }
+
+    let der = combine_shares(shares)?;
+    let key = RsaPrivateKey::from_pkcs8_der(&der)
+        .map_err(|_| CeremonyError::Mismatch("shares do not recover a key".to_string()))?;
+    let public_key_pem = RsaPublicKey::from(&key)
+        .to_public_key_pem(LineEnding::LF)
+        .map_err(key_err)?;
+    if thumbprint_of_pem(&record.kid, &public_key_pem)? != record.jwk_thumbprint {
+        return Err(CeremonyError::Mismatch(
+            "recovered key has a different thumbprint".to_string(),
+        ));
+    }
+    Ok(key
+        .to_pkcs8_pem(LineEnding::LF)
+        .map_err(key_err)?
+        .to_string())
+}
+
+/// Load a ceremony key into a [`SoftKeyProvider`], checking that what it
+/// signs verifies against the record's public key
+pub async fn import_key(
+    record: &CeremonyRecord,
+    private_key_pem: &str,
+) -> Result<SoftKeyProvider, CeremonyError> {
+    let provider = SoftKeyProvider::from_pkcs8_pem(private_key_pem).map_err(key_err)?;
+    let probe = format!("key ceremony import of {}", record.kid);
+    let signature = provider.sign(probe.as_bytes()).await.map_err(key_err)?;
+    let public_key = RsaPublicKey::from_public_key_pem(&record.public_key_pem).map_err(key_err)?;
+    public_key
+        .verify(
+            Pkcs1v15Sign::new::<Sha256>(),
+            &Sha256::digest(probe.as_bytes()),
+            &signature,
+        )
+        .map_err(|_| CeremonyError::Mismatch("imported key signs with another key".to_string()))?;
+    Ok(provider)
+}
+
+/// Check that a published JWKS carries the record's key under its kid
+pub fn verify_jwks(record: &CeremonyRecord, jwks: &serde_json::Value) -> Result<(), CeremonyError> {
+    let key = jwks
+        .get("keys")
+        .and_then(|keys| keys.as_array())
+        .and_then(|keys| {
+            keys.iter()
+                .find(|k| k.get("kid").and_then(|kid| kid.as_str()) == Some(record.kid.as_str()))
+        })
+        .ok_or_else(|| {
+            CeremonyError::Mismatch(format!("JWKS does not publish kid {}", record.kid))
+        })?;
+    let published = jwk_thumbprint(key)?;
+    if published != record.jwk_thumbprint {
+        return Err(CeremonyError::Mismatch(format!(
+            "kid {} is published with thumbprint {}, the ceremony recorded {}",
+            record.kid, published, record.jwk_thumbprint
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_any_threshold_shares_recover_the_secret() {
+        let secret = b"escrowed signing key".to_vec();
+        let shares = split_secret(&secret, 3, 5).unwrap();
+        assert_eq!(
+            combine_shares(&[shares[4].clone(), shares[0].clone(), shares[2].clone()]).unwrap(),
+            secret
+        );
+        assert_eq!(combine_shares(&shares).unwrap(), secret);
+        assert!(matches!(
+            combine_shares(&shares[..2]),
+            Err(CeremonyError::NotEnoughShares { needed: 3, got: 2 })
+        ));
+
+        let encoded = shares[1].encode();
+        assert_eq!(EscrowShare::decode(&encoded).unwrap(), shares[1]);
+        assert!(EscrowShare::decode("uac-share-v1:3:0:AAAA").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ceremony_recovers_imports_and_matches_jwks() {
+        let custodians: Vec<String> = ["alice", "bob", "carol"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let output = run_ceremony("signing-2026", &custodians, 2, 2048, Utc::now()).unwrap();
+        let record = &output.record;
+        assert_eq!(record.custodians.len(), 3);
+
+        let shares: Vec<EscrowShare> = output.shares[1..].iter().map(|(_, s)| s.clone()).collect();
+        let recovered = recover_key(record, &shares).unwrap();
+        import_key(record, &recovered).await.unwrap();
+
+        let jwk = rsa_jwk("signing-2026", &record.public_key_pem).unwrap();
+        verify_jwks(record, &serde_json::json!({ "keys": [jwk] })).unwrap();
+        let other = run_ceremony("signing-2026", &custodians, 2, 2048, Utc::now()).unwrap();
+        assert!(verify_jwks(&other.record, &serde_json::json!({ "keys": [jwk] })).is_err());
+        assert!(recover_key(&other.record, &shares).is_err());
+    }
+}
@@ -1,3 +1,137 @@
-//! Encryption utilities
+//! Symmetric authenticated encryption (AES-256-GCM)
 
-// Placeholder - will be implemented in later tasks
\ No newline at end of file
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use thiserror::Error;
+
+const NONCE_LEN: usize = 12;
+pub const KEY_LEN: usize = 32;
+
+#[derive(Debug, Error)]
+pub enum EncryptionError {
+    #[error("Invalid key length: expected {KEY_LEN} bytes")]
+    InvalidKey,
+    #[error("Encryption failed")]
+    EncryptionFailed,
+    #[error("Decryption failed")]
+    DecryptionFailed,
+    #[error("Invalid ciphertext encoding")]
+    InvalidEncoding,
+    #[error("Key management error: {0}")]
+    KeyManagement(String),
+}
+
+/// AES-256-GCM cipher. Ciphertexts are laid out as `nonce || ciphertext || tag`.
+#[derive(Clone)]
+pub struct SymmetricCipher {
+    cipher: Aes256Gcm,
+}
+
+impl SymmetricCipher {
+    pub fn new(key: &[u8]) -> Result<Self, EncryptionError> {
+        if key.len() != KEY_LEN {
+            return Err(EncryptionError::InvalidKey);
+        }
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| EncryptionError::InvalidKey)?;
+        Ok(Self { cipher })
+    }
+
+    /// Build a cipher from a base64url (no padding) encoded 32-byte key
+    pub fn from_base64(key: &str) -> Result<Self, EncryptionError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(key.trim())
+            .map_err(|_| EncryptionError::InvalidKey)?;
+        Self::new(&bytes)
+    }
+
+    /// Generate a fresh random key (base64url encoded)
+    pub fn generate_key() -> String {
+        let mut key = [0u8; KEY_LEN];
+        OsRng.fill_bytes(&mut key);
+        URL_SAFE_NO_PAD.encode(key)
+    }
+
+    /// Encrypt `plaintext`, binding it to `aad` (additional authenticated data)
+    pub fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce_bytes);
+        let nonce = Nonce::from_slice(&nonce_bytes);
+
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| EncryptionError::EncryptionFailed)?;
+
+        let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(&nonce_bytes);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    /// Decrypt data produced by [`SymmetricCipher::seal`] with the same `aad`
+    pub fn open(&self, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        if sealed.len() < NONCE_LEN {
+            return Err(EncryptionError::DecryptionFailed);
+        }
+        let (nonce_bytes, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(
+                Nonce::from_slice(nonce_bytes),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| EncryptionError::DecryptionFailed)
+    }
+
+    /// Encrypt and encode as a URL/cookie safe string
+    pub fn seal_to_string(&self, plaintext: &[u8], aad: &[u8]) -> Result<String, EncryptionError> {
+        Ok(URL_SAFE_NO_PAD.encode(self.seal(plaintext, aad)?))
+    }
+
+    /// Decode and decrypt a string produced by [`SymmetricCipher::seal_to_string`]
+    pub fn open_from_string(&self, sealed: &str, aad: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(sealed)
+            .map_err(|_| EncryptionError::InvalidEncoding)?;
+        self.open(&bytes, aad)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let cipher = SymmetricCipher::from_base64(&SymmetricCipher::generate_key()).unwrap();
+        let sealed = cipher.seal_to_string(b"secret", b"ctx").unwrap();
+        assert_eq!(cipher.open_from_string(&sealed, b"ctx").unwrap(), b"secret");
+    }
+
+    #[test]
+    fn test_open_rejects_wrong_aad_and_tampering() {
+        let cipher = SymmetricCipher::from_base64(&SymmetricCipher::generate_key()).unwrap();
+        let mut sealed = cipher.seal(b"secret", b"ctx").unwrap();
+        assert!(cipher.open(&sealed, b"other").is_err());
+
+        let last = sealed.len() - 1;
+        sealed[last] ^= 0x01;
+        assert!(cipher.open(&sealed, b"ctx").is_err());
+    }
+
+    #[test]
+    fn test_rejects_short_key() {
+        assert!(SymmetricCipher::new(&[0u8; 16]).is_err());
+    }
+}
@@ -4,6 +4,7 @@ use crate::keys::KeyManager;
 use chrono::{DateTime, Utc};
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -34,6 +35,20 @@ pub struct JwtClaims {
     pub permissions: Vec<String>, // User permissions
     pub roles: Vec<String>,       // User roles
     pub scope: Option<String>,    // OAuth scope
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>, // Session the token was issued under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>, // OAuth client the token was issued to
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ext: BTreeMap<String, serde_json::Value>, // Claims added by resolvers at issuance
+    #[serde(default, rename = "gen", skip_serializing_if = "is_zero")]
+    pub generation: u64, // Tenant token generation at issuance
+    #[serde(default, rename = "ugen", skip_serializing_if = "is_zero")]
+    pub user_generation: u64, // User token generation at issuance
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
 }
 
 #[derive(Debug, Clone)]
@@ -76,9 +91,30 @@ impl JwtService {
         permissions: Vec<String>,
         roles: Vec<String>,
         scope: Option<String>,
+    ) -> Result<String, JwtError> {
+        self.generate_access_token_with_ttl(
+            user_id,
+            tenant_id,
+            permissions,
+            roles,
+            scope,
+            self.config.access_token_ttl,
+        )
+        .await
+    }
+
+    /// Generate a new JWT access token with an explicit lifetime
+    pub async fn generate_access_token_with_ttl(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        permissions: Vec<String>,
+        roles: Vec<String>,
+        scope: Option<String>,
+        ttl: chrono::Duration,
     ) -> Result<String, JwtError> {
         let now = Utc::now();
-        let exp = now + self.config.access_token_ttl;
+        let exp = now + ttl;
 
         let claims = JwtClaims {
             sub: user_id.to_string(),
@@ -92,8 +128,35 @@ impl JwtService {
             permissions,
             roles,
             scope,
+            session_id: None,
+            client_id: None,
+            ext: BTreeMap::new(),
+            generation: 0,
+            user_generation: 0,
         };
 
+        self.sign_claims(&claims).await
+    }
+
+    /// Sign access token claims as given, keeping the caller's `jti` and
+    /// session; issuer, audience and validity are stamped from the config
+    pub async fn sign_access_token(
+        &self,
+        mut claims: JwtClaims,
+        ttl: chrono::Duration,
+    ) -> Result<String, JwtError> {
+        let now = Utc::now();
+        claims.iss = self.config.issuer.clone();
+        claims.aud = self.config.audience.clone();
+        claims.iat = now.timestamp();
+        claims.nbf = now.timestamp();
+        claims.exp = (now + ttl).timestamp();
+
+        self.sign_claims(&claims).await
+    }
+
+    /// Sign arbitrary claims (logout tokens, ID tokens) with the access token key
+    pub async fn sign_claims<T: Serialize>(&self, claims: &T) -> Result<String, JwtError> {
         let header = Header::new(self.config.algorithm);
         let encoding_key = self
             .key_manager
@@ -101,32 +164,30 @@ impl JwtService {
             .await
             .map_err(|e| JwtError::KeyError(e.to_string()))?;
 
-        encode(&header, &claims, &encoding_key).map_err(JwtError::EncodingError)
+        encode(&header, claims, &encoding_key).map_err(JwtError::EncodingError)
     }
 
     /// Validate and decode a JWT token
     pub async fn validate_token(&self, token: &str) -> Result<JwtClaims, JwtError> {
+        self.batch_validator().await?.validate(token)
+    }
+
+    /// A validator holding the decoded verification key, for checking many
+    /// tokens without loading the key for each
+    pub async fn batch_validator(&self) -> Result<BatchValidator, JwtError> {
         let mut validation = Validation::new(self.config.algorithm);
         validation.set_issuer(&[&self.config.issuer]);
         validation.set_audience(&[&self.config.audience]);
         validation.validate_exp = true;
         validation.validate_nbf = true;
 
-        let decoding_key = self
+        let key = self
             .key_manager
             .get_decoding_key()
             .await
             .map_err(|e| JwtError::KeyError(e.to_string()))?;
 
-        let token_data =
-            decode::<JwtClaims>(token, &decoding_key, &validation).map_err(|e| match e.kind() {
-                jsonwebtoken::errors::ErrorKind::ExpiredSignature => JwtError::TokenExpired,
-                _ => JwtError::ValidationError {
-                    reason: e.to_string(),
-                },
-            })?;
-
-        Ok(token_data.claims)
+        Ok(BatchValidator { key, validation })
     }
 
     /// Extract claims from token without validation (for introspection)
@@ -163,6 +224,26 @@ impl JwtService {
     }
 }
 
+/// Verifies tokens against one loaded key; cheap to clone across threads
+#[derive(Clone)]
+pub struct BatchValidator {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+impl BatchValidator {
+    pub fn validate(&self, token: &str) -> Result<JwtClaims, JwtError> {
+        decode::<JwtClaims>(token, &self.key, &self.validation)
+            .map(|data| data.claims)
+            .map_err(|e| match e.kind() {
+                jsonwebtoken::errors::ErrorKind::ExpiredSignature => JwtError::TokenExpired,
+                _ => JwtError::ValidationError {
+                    reason: e.to_string(),
+                },
+            })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
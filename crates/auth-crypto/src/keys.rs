@@ -5,7 +5,7 @@ use jsonwebtoken::{DecodingKey, EncodingKey};
 use rand::thread_rng;
 use rsa::{
     pkcs1::DecodeRsaPublicKey, pkcs1::EncodeRsaPrivateKey, pkcs1::EncodeRsaPublicKey,
-    traits::PublicKeyParts, RsaPrivateKey, RsaPublicKey,
+    pkcs8::DecodePublicKey, traits::PublicKeyParts, RsaPrivateKey, RsaPublicKey,
 };
 use std::sync::Arc;
 use thiserror::Error;
@@ -123,26 +123,33 @@ impl KeyManager {
 
     /// Get the JWK Set (public keys)
     pub fn get_jwk_set(&self) -> serde_json::Value {
-        // Parse public key PEM
-        let pub_key =
-            RsaPublicKey::from_pkcs1_pem(&self.public_key_pem).expect("Invalid Public Key PEM");
-
-        let n = URL_SAFE_NO_PAD.encode(pub_key.n().to_bytes_be());
-        let e = URL_SAFE_NO_PAD.encode(pub_key.e().to_bytes_be());
-
-        serde_json::json!({
-            "keys": [{
-                "kty": "RSA",
-                "use": "sig",
-                "kid": "auth-core-key-1",
-                "alg": "RS256",
-                "n": n,
-                "e": e
-            }]
-        })
+        let jwk = rsa_jwk(SIGNING_KEY_ID, &self.public_key_pem).expect("Invalid Public Key PEM");
+        serde_json::json!({ "keys": [jwk] })
     }
 }
 
+/// Key id of the signing key in the JWKS
+pub const SIGNING_KEY_ID: &str = "auth-core-key-1";
+
+/// RS256 verification JWK for an RSA public key in PKCS#1 or SPKI PEM
+pub fn rsa_jwk(kid: &str, public_key_pem: &str) -> Result<serde_json::Value, KeyError> {
+    let pub_key = RsaPublicKey::from_pkcs1_pem(public_key_pem)
+        .or_else(|_| RsaPublicKey::from_public_key_pem(public_key_pem))
+        .map_err(|e| KeyError::InvalidFormat(e.to_string()))?;
+
+    let n = URL_SAFE_NO_PAD.encode(pub_key.n().to_bytes_be());
+    let e = URL_SAFE_NO_PAD.encode(pub_key.e().to_bytes_be());
+
+    Ok(serde_json::json!({
+        "kty": "RSA",
+        "use": "sig",
+        "kid": kid,
+        "alg": "RS256",
+        "n": n,
+        "e": e
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
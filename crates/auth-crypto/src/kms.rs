@@ -1,8 +1,8 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use rand::rngs::OsRng;
-use rsa::pkcs8::{EncodePublicKey, LineEnding};
-use rsa::{Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use rsa::pkcs8::{DecodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::{Oaep, Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
 use sha2::{Digest, Sha256};
 
 #[async_trait]
@@ -10,6 +10,10 @@ pub trait KeyProvider: Send + Sync {
     async fn sign(&self, data: &[u8]) -> Result<Vec<u8>>;
     async fn verify(&self, data: &[u8], signature: &[u8]) -> Result<bool>;
     fn public_key_pem(&self) -> String;
+    /// Wrap a data key for envelope encryption
+    async fn wrap_key(&self, key: &[u8]) -> Result<Vec<u8>>;
+    /// Recover a data key produced by [`KeyProvider::wrap_key`]
+    async fn unwrap_key(&self, wrapped: &[u8]) -> Result<Vec<u8>>;
 }
 
 pub struct SoftKeyProvider {
@@ -23,6 +27,12 @@ impl SoftKeyProvider {
         let key = RsaPrivateKey::new(&mut rng, bits).expect("failed to generate a key");
         Self { key }
     }
+
+    /// Load a persistent key from a PKCS#8 PEM document
+    pub fn from_pkcs8_pem(pem: &str) -> Result<Self> {
+        let key = RsaPrivateKey::from_pkcs8_pem(pem)?;
+        Ok(Self { key })
+    }
 }
 
 #[async_trait]
@@ -60,6 +70,15 @@ impl KeyProvider for SoftKeyProvider {
             .to_public_key_pem(LineEnding::LF)
             .unwrap_or_default()
     }
+
+    async fn wrap_key(&self, key: &[u8]) -> Result<Vec<u8>> {
+        let public_key = RsaPublicKey::from(&self.key);
+        Ok(public_key.encrypt(&mut OsRng, Oaep::new::<Sha256>(), key)?)
+    }
+
+    async fn unwrap_key(&self, wrapped: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.key.decrypt(Oaep::new::<Sha256>(), wrapped)?)
+    }
 }
 
 pub struct HsmKeyProvider {
@@ -87,6 +106,15 @@ impl KeyProvider for HsmKeyProvider {
     fn public_key_pem(&self) -> String {
         "-----BEGIN PUBLIC KEY-----\nMOCK_HSM_KEY\n-----END PUBLIC KEY-----".to_string()
     }
+
+    async fn wrap_key(&self, _key: &[u8]) -> Result<Vec<u8>> {
+        // A dummy wrap would silently store keys in the clear
+        anyhow::bail!("HSM key wrapping is not implemented")
+    }
+
+    async fn unwrap_key(&self, _wrapped: &[u8]) -> Result<Vec<u8>> {
+        anyhow::bail!("HSM key unwrapping is not implemented")
+    }
 }
 
 impl Default for SoftKeyProvider {
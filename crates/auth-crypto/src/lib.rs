@@ -1,8 +1,24 @@
+pub mod ceremony;
+pub mod device_ca;
+pub mod encryption;
 pub mod hashing;
 pub mod jwt;
 pub mod keys;
 pub mod kms;
+pub mod pii;
+pub mod ssh_ca;
 
-pub use jwt::{JwtClaims, JwtConfig, JwtError, JwtService};
-pub use keys::{KeyError, KeyManager};
+pub use ceremony::{
+    combine_shares, import_key, jwk_thumbprint, recover_key, run_ceremony, split_secret,
+    verify_jwks, CeremonyError, CeremonyOutput, CeremonyRecord, Custodian, EscrowShare,
+};
+pub use device_ca::{DeviceCaError, IntermediateCa, IssuedCertificate, VerifiedCsr};
+pub use encryption::{EncryptionError, SymmetricCipher};
+pub use jwt::{BatchValidator, JwtClaims, JwtConfig, JwtError, JwtService};
+pub use keys::{rsa_jwk, KeyError, KeyManager, SIGNING_KEY_ID};
 pub use kms::{HsmKeyProvider, KeyProvider, SoftKeyProvider};
+pub use pii::{DataKeyStore, PiiField, PiiProtector};
+pub use ssh_ca::{
+    SshCaError, SshCaKey, SshCertificate, SshCertificateAuthority, SshCertificateRequest,
+    SshPublicKey,
+};
@@ -0,0 +1,264 @@
+//! Field-level encryption for PII columns
+//!
+//! Values are encrypted with a per-tenant AES-256-GCM data key. Data keys are
+//! stored wrapped by a [`KeyProvider`] (envelope encryption) and unwrapped
+//! lazily on first use. Because ciphertexts are randomized, equality lookups
+//! go through a blind index: an HMAC of the normalized value.
+
+use crate::encryption::{EncryptionError, SymmetricCipher, KEY_LEN};
+use crate::kms::KeyProvider;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Prefix marking an encrypted value; anything else is legacy plaintext
+const CIPHERTEXT_PREFIX: &str = "enc:v1:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiiField {
+    Email,
+    Phone,
+}
+
+impl PiiField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PiiField::Email => "email",
+            PiiField::Phone => "phone",
+        }
+    }
+
+    /// Canonical form used for the blind index, so lookups are not
+    /// sensitive to case or formatting
+    pub fn normalize(&self, value: &str) -> String {
+        let value = value.trim();
+        match self {
+            PiiField::Email => value.to_lowercase(),
+            PiiField::Phone => value
+                .chars()
+                .enumerate()
+                .filter(|(i, c)| c.is_ascii_digit() || (*i == 0 && *c == '+'))
+                .map(|(_, c)| c)
+                .collect(),
+        }
+    }
+}
+
+/// Persistence for wrapped tenant data keys
+#[async_trait]
+pub trait DataKeyStore: Send + Sync {
+    async fn get_wrapped_key(&self, tenant_id: Uuid) -> anyhow::Result<Option<Vec<u8>>>;
+    /// Store a key unless one already exists for the tenant
+    async fn insert_wrapped_key(&self, tenant_id: Uuid, wrapped: &[u8]) -> anyhow::Result<()>;
+}
+
+pub struct PiiProtector {
+    provider: Arc<dyn KeyProvider>,
+    store: Arc<dyn DataKeyStore>,
+    index_key: Vec<u8>,
+    data_keys: RwLock<HashMap<Uuid, SymmetricCipher>>,
+}
+
+impl PiiProtector {
+    pub fn new(
+        provider: Arc<dyn KeyProvider>,
+        store: Arc<dyn DataKeyStore>,
+        blind_index_key: &[u8],
+    ) -> Self {
+        Self {
+            provider,
+            store,
+            index_key: blind_index_key.to_vec(),
+            data_keys: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_encrypted(value: &str) -> bool {
+        value.starts_with(CIPHERTEXT_PREFIX)
+    }
+
+    pub async fn encrypt(
+        &self,
+        tenant_id: Uuid,
+        field: PiiField,
+        plaintext: &str,
+    ) -> Result<String, EncryptionError> {
+        let cipher = self.data_key(tenant_id).await?;
+        let sealed = cipher.seal_to_string(plaintext.as_bytes(), &Self::aad(tenant_id, field))?;
+        Ok(format!("{}{}", CIPHERTEXT_PREFIX, sealed))
+    }
+
+    /// Decrypt a stored value. Plaintext values (not yet backfilled) pass through.
+    pub async fn decrypt(
+        &self,
+        tenant_id: Uuid,
+        field: PiiField,
+        stored: &str,
+    ) -> Result<String, EncryptionError> {
+        let Some(sealed) = stored.strip_prefix(CIPHERTEXT_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let cipher = self.data_key(tenant_id).await?;
+        let plaintext = cipher.open_from_string(sealed, &Self::aad(tenant_id, field))?;
+        String::from_utf8(plaintext).map_err(|_| EncryptionError::DecryptionFailed)
+    }
+
+    /// Deterministic, tenant-scoped lookup token for a value
+    pub fn blind_index(&self, tenant_id: Uuid, field: PiiField, value: &str) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.index_key)
+            .expect("HMAC accepts keys of any length");
+        mac.update(tenant_id.as_bytes());
+        mac.update(field.as_str().as_bytes());
+        mac.update(field.normalize(value).as_bytes());
+        URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+    }
+
+    async fn data_key(&self, tenant_id: Uuid) -> Result<SymmetricCipher, EncryptionError> {
+        if let Some(cipher) = self.data_keys.read().await.get(&tenant_id) {
+            return Ok(cipher.clone());
+        }
+
+        let wrapped = match self.load_wrapped(tenant_id).await? {
+            Some(wrapped) => wrapped,
+            None => {
+                let mut key = [0u8; KEY_LEN];
+                OsRng.fill_bytes(&mut key);
+                let wrapped = self
+                    .provider
+                    .wrap_key(&key)
+                    .await
+                    .map_err(|e| EncryptionError::KeyManagement(e.to_string()))?;
+                self.store
+                    .insert_wrapped_key(tenant_id, &wrapped)
+                    .await
+                    .map_err(|e| EncryptionError::KeyManagement(e.to_string()))?;
+                // Another node may have won the race; always use the stored key
+                self.load_wrapped(tenant_id).await?.ok_or_else(|| {
+                    EncryptionError::KeyManagement("data key vanished after insert".to_string())
+                })?
+            }
+        };
+
+        let key = self
+            .provider
+            .unwrap_key(&wrapped)
+            .await
+            .map_err(|e| EncryptionError::KeyManagement(e.to_string()))?;
+        let cipher = SymmetricCipher::new(&key)?;
+        self.data_keys
+            .write()
+            .await
+            .insert(tenant_id, cipher.clone());
+        Ok(cipher)
+    }
+
+    async fn load_wrapped(&self, tenant_id: Uuid) -> Result<Option<Vec<u8>>, EncryptionError> {
+        self.store
+            .get_wrapped_key(tenant_id)
+            .await
+            .map_err(|e| EncryptionError::KeyManagement(e.to_string()))
+    }
+
+    /// Bind ciphertexts to their tenant and column so they cannot be swapped
+    fn aad(tenant_id: Uuid, field: PiiField) -> Vec<u8> {
+        let mut aad = tenant_id.as_bytes().to_vec();
+        aad.extend_from_slice(field.as_str().as_bytes());
+        aad
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kms::SoftKeyProvider;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemoryKeyStore {
+        keys: Mutex<HashMap<Uuid, Vec<u8>>>,
+    }
+
+    #[async_trait]
+    impl DataKeyStore for MemoryKeyStore {
+        async fn get_wrapped_key(&self, tenant_id: Uuid) -> anyhow::Result<Option<Vec<u8>>> {
+            Ok(self.keys.lock().unwrap().get(&tenant_id).cloned())
+        }
+
+        async fn insert_wrapped_key(&self, tenant_id: Uuid, wrapped: &[u8]) -> anyhow::Result<()> {
+            self.keys
+                .lock()
+                .unwrap()
+                .entry(tenant_id)
+                .or_insert_with(|| wrapped.to_vec());
+            Ok(())
+        }
+    }
+
+    fn protector() -> PiiProtector {
+        PiiProtector::new(
+            Arc::new(SoftKeyProvider::new()),
+            Arc::new(MemoryKeyStore::default()),
+            b"blind-index-key",
+        )
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_decrypt_roundtrip() {
+        let pii = protector();
+        let tenant = Uuid::new_v4();
+
+        let stored = pii
+            .encrypt(tenant, PiiField::Email, "alice@example.com")
+            .await
+            .unwrap();
+        assert!(PiiProtector::is_encrypted(&stored));
+        assert!(!stored.contains("alice"));
+        assert_eq!(
+            pii.decrypt(tenant, PiiField::Email, &stored).await.unwrap(),
+            "alice@example.com"
+        );
+
+        // Ciphertexts are bound to their tenant and column
+        assert!(pii
+            .decrypt(Uuid::new_v4(), PiiField::Email, &stored)
+            .await
+            .is_err());
+        assert!(pii.decrypt(tenant, PiiField::Phone, &stored).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_passes_through() {
+        let pii = protector();
+        let value = pii
+            .decrypt(Uuid::new_v4(), PiiField::Phone, "+15551234567")
+            .await
+            .unwrap();
+        assert_eq!(value, "+15551234567");
+    }
+
+    #[test]
+    fn test_blind_index_normalizes_and_scopes() {
+        let pii = protector();
+        let tenant = Uuid::new_v4();
+
+        assert_eq!(
+            pii.blind_index(tenant, PiiField::Email, " Alice@Example.com"),
+            pii.blind_index(tenant, PiiField::Email, "alice@example.com")
+        );
+        assert_eq!(
+            pii.blind_index(tenant, PiiField::Phone, "+1 (555) 123-4567"),
+            pii.blind_index(tenant, PiiField::Phone, "+15551234567")
+        );
+        assert_ne!(
+            pii.blind_index(tenant, PiiField::Email, "alice@example.com"),
+            pii.blind_index(Uuid::new_v4(), PiiField::Email, "alice@example.com")
+        );
+    }
+}
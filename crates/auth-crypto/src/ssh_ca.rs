@@ -0,0 +1,440 @@
+//! SSH certificate authority
+//!
+//! Signs user public keys into OpenSSH user certificates
+//! (`*-cert-v01@openssh.com`, see PROTOCOL.certkeys in the OpenSSH tree).
+//! CA keys are Ed25519. Servers trust the CA through `TrustedUserCAKeys`,
+//! so rotation keeps the previous public key published until it is retired.
+
+use base64::{
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD},
+    Engine as _,
+};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signer, SigningKey};
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::RwLock;
+use thiserror::Error;
+
+const ED25519: &str = "ssh-ed25519";
+const SSH_CERT_TYPE_USER: u32 = 1;
+
+/// Key types accepted for signing. Certificates are not re-signed.
+const SUPPORTED_KEY_TYPES: &[&str] = &[
+    ED25519,
+    "ssh-rsa",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+];
+
+#[derive(Debug, Error)]
+pub enum SshCaError {
+    #[error("Invalid SSH public key: {0}")]
+    InvalidPublicKey(String),
+    #[error("Invalid CA key: {0}")]
+    InvalidCaKey(String),
+    #[error("Invalid certificate request: {0}")]
+    InvalidRequest(String),
+}
+
+/// An OpenSSH public key, as found in `authorized_keys` or `id_*.pub`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshPublicKey {
+    key_type: String,
+    /// Wire encoding, starting with the key type string
+    blob: Vec<u8>,
+    comment: Option<String>,
+}
+
+impl SshPublicKey {
+    /// Parse `<type> <base64> [comment]`
+    pub fn parse(line: &str) -> Result<Self, SshCaError> {
+        let mut parts = line.split_whitespace();
+        let key_type = parts
+            .next()
+            .ok_or_else(|| SshCaError::InvalidPublicKey("empty key".to_string()))?;
+        if !SUPPORTED_KEY_TYPES.contains(&key_type) {
+            return Err(SshCaError::InvalidPublicKey(format!(
+                "unsupported key type '{}'",
+                key_type
+            )));
+        }
+
+        let encoded = parts
+            .next()
+            .ok_or_else(|| SshCaError::InvalidPublicKey("missing key data".to_string()))?;
+        let blob = STANDARD
+            .decode(encoded)
+            .map_err(|e| SshCaError::InvalidPublicKey(e.to_string()))?;
+
+        let mut reader = Reader::new(&blob);
+        let embedded = reader.string()?;
+        if embedded != key_type.as_bytes() {
+            return Err(SshCaError::InvalidPublicKey(
+                "key type does not match key data".to_string(),
+            ));
+        }
+        if reader.remaining().is_empty() {
+            return Err(SshCaError::InvalidPublicKey("truncated key".to_string()));
+        }
+
+        let comment: Vec<&str> = parts.collect();
+        Ok(Self {
+            key_type: key_type.to_string(),
+            blob,
+            comment: (!comment.is_empty()).then(|| comment.join(" ")),
+        })
+    }
+
+    pub fn key_type(&self) -> &str {
+        &self.key_type
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// `SHA256:<base64>` as printed by `ssh-keygen -l`
+    pub fn fingerprint(&self) -> String {
+        format!(
+            "SHA256:{}",
+            STANDARD_NO_PAD.encode(Sha256::digest(&self.blob))
+        )
+    }
+
+    /// `authorized_keys` line
+    pub fn to_openssh(&self) -> String {
+        let mut line = format!("{} {}", self.key_type, STANDARD.encode(&self.blob));
+        if let Some(comment) = &self.comment {
+            line.push(' ');
+            line.push_str(comment);
+        }
+        line
+    }
+
+    /// Key-specific fields, i.e. the blob without its leading type string
+    fn key_fields(&self) -> &[u8] {
+        &self.blob[4 + self.key_type.len()..]
+    }
+}
+
+/// An Ed25519 CA signing key
+pub struct SshCaKey {
+    signing: SigningKey,
+}
+
+impl SshCaKey {
+    pub fn generate() -> Self {
+        Self {
+            signing: SigningKey::generate(&mut OsRng),
+        }
+    }
+
+    /// Load from a base64 encoded 32-byte seed
+    pub fn from_seed_base64(seed: &str) -> Result<Self, SshCaError> {
+        let bytes = STANDARD
+            .decode(seed.trim())
+            .map_err(|e| SshCaError::InvalidCaKey(e.to_string()))?;
+        let seed: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| SshCaError::InvalidCaKey("seed must be 32 bytes".to_string()))?;
+        Ok(Self {
+            signing: SigningKey::from_bytes(&seed),
+        })
+    }
+
+    pub fn public_key(&self) -> SshPublicKey {
+        let mut blob = Vec::new();
+        put_string(&mut blob, ED25519.as_bytes());
+        put_string(&mut blob, self.signing.verifying_key().as_bytes());
+        SshPublicKey {
+            key_type: ED25519.to_string(),
+            blob,
+            comment: None,
+        }
+    }
+}
+
+/// What to put in a certificate. Options and extensions are kept sorted,
+/// which OpenSSH requires.
+#[derive(Debug, Clone)]
+pub struct SshCertificateRequest {
+    /// Shown in sshd logs; use something that identifies the SSO user
+    pub key_id: String,
+    pub principals: Vec<String>,
+    pub valid_after: DateTime<Utc>,
+    pub valid_before: DateTime<Utc>,
+    /// e.g. `force-command`, `source-address`
+    pub critical_options: BTreeMap<String, String>,
+    /// e.g. `permit-pty`
+    pub extensions: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SshCertificate {
+    pub serial: u64,
+    pub key_id: String,
+    pub principals: Vec<String>,
+    pub valid_after: DateTime<Utc>,
+    pub valid_before: DateTime<Utc>,
+    /// Fingerprint of the CA key that signed it
+    pub ca_fingerprint: String,
+    /// `<type>-cert-v01@openssh.com <base64> <key id>`, ready for `id_*-cert.pub`
+    pub certificate: String,
+}
+
+struct CaKeys {
+    active: SshCaKey,
+    /// Previous CA public keys that servers should still trust
+    retired: Vec<SshPublicKey>,
+}
+
+/// Signs user certificates with the active CA key
+pub struct SshCertificateAuthority {
+    keys: RwLock<CaKeys>,
+}
+
+impl SshCertificateAuthority {
+    pub fn new(active: SshCaKey) -> Self {
+        Self {
+            keys: RwLock::new(CaKeys {
+                active,
+                retired: Vec::new(),
+            }),
+        }
+    }
+
+    /// Keep trusting previously active keys
+    pub fn with_retired(self, retired: Vec<SshPublicKey>) -> Self {
+        self.keys.write().unwrap().retired = retired;
+        self
+    }
+
+    /// Make `next` the signing key. The old key stays in `trusted_keys`
+    /// until `retire` is called for it.
+    pub fn rotate(&self, next: SshCaKey) -> SshPublicKey {
+        let mut keys = self.keys.write().unwrap();
+        let previous = std::mem::replace(&mut keys.active, next);
+        keys.retired.insert(0, previous.public_key());
+        keys.active.public_key()
+    }
+
+    /// Stop publishing a retired key. The active key cannot be retired.
+    pub fn retire(&self, fingerprint: &str) -> bool {
+        let mut keys = self.keys.write().unwrap();
+        let before = keys.retired.len();
+        keys.retired.retain(|k| k.fingerprint() != fingerprint);
+        keys.retired.len() != before
+    }
+
+    /// Every CA key servers should accept, active key first
+    pub fn trusted_keys(&self) -> Vec<SshPublicKey> {
+        let keys = self.keys.read().unwrap();
+        std::iter::once(keys.active.public_key())
+            .chain(keys.retired.iter().cloned())
+            .collect()
+    }
+
+    pub fn sign(
+        &self,
+        key: &SshPublicKey,
+        request: &SshCertificateRequest,
+    ) -> Result<SshCertificate, SshCaError> {
+        if request.principals.is_empty() {
+            // An empty principal list means "any user" to sshd
+            return Err(SshCaError::InvalidRequest(
+                "at least one principal is required".to_string(),
+            ));
+        }
+        if request.valid_before <= request.valid_after {
+            return Err(SshCaError::InvalidRequest(
+                "valid_before must be after valid_after".to_string(),
+            ));
+        }
+
+        let keys = self.keys.read().unwrap();
+        let ca_public = keys.active.public_key();
+        let cert_type = format!("{}-cert-v01@openssh.com", key.key_type);
+
+        let mut nonce = [0u8; 32];
+        OsRng.fill_bytes(&mut nonce);
+        let serial = OsRng.next_u64();
+
+        let mut principals = Vec::new();
+        for principal in &request.principals {
+            put_string(&mut principals, principal.as_bytes());
+        }
+
+        let mut critical_options = Vec::new();
+        for (name, value) in &request.critical_options {
+            let mut data = Vec::new();
+            put_string(&mut data, value.as_bytes());
+            put_string(&mut critical_options, name.as_bytes());
+            put_string(&mut critical_options, &data);
+        }
+
+        let mut extensions = Vec::new();
+        for name in &request.extensions {
+            put_string(&mut extensions, name.as_bytes());
+            put_string(&mut extensions, &[]);
+        }
+
+        let mut cert = Vec::new();
+        put_string(&mut cert, cert_type.as_bytes());
+        put_string(&mut cert, &nonce);
+        cert.extend_from_slice(key.key_fields());
+        cert.extend_from_slice(&serial.to_be_bytes());
+        cert.extend_from_slice(&SSH_CERT_TYPE_USER.to_be_bytes());
+        put_string(&mut cert, request.key_id.as_bytes());
+        put_string(&mut cert, &principals);
+        cert.extend_from_slice(&unix_seconds(request.valid_after).to_be_bytes());
+        cert.extend_from_slice(&unix_seconds(request.valid_before).to_be_bytes());
+        put_string(&mut cert, &critical_options);
+        put_string(&mut cert, &extensions);
+        put_string(&mut cert, &[]); // reserved
+        put_string(&mut cert, &ca_public.blob);
+
+        let signature = keys.active.signing.sign(&cert);
+        let mut signature_blob = Vec::new();
+        put_string(&mut signature_blob, ED25519.as_bytes());
+        put_string(&mut signature_blob, &signature.to_bytes());
+        put_string(&mut cert, &signature_blob);
+
+        Ok(SshCertificate {
+            serial,
+            key_id: request.key_id.clone(),
+            principals: request.principals.clone(),
+            valid_after: request.valid_after,
+            valid_before: request.valid_before,
+            ca_fingerprint: ca_public.fingerprint(),
+            certificate: format!(
+                "{} {} {}",
+                cert_type,
+                STANDARD.encode(&cert),
+                request.key_id
+            ),
+        })
+    }
+}
+
+fn unix_seconds(at: DateTime<Utc>) -> u64 {
+    at.timestamp().max(0) as u64
+}
+
+fn put_string(buf: &mut Vec<u8>, data: &[u8]) {
+    buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    buf.extend_from_slice(data);
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn string(&mut self) -> Result<&'a [u8], SshCaError> {
+        let truncated = || SshCaError::InvalidPublicKey("truncated key".to_string());
+        let len: [u8; 4] = self
+            .data
+            .get(..4)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(truncated)?;
+        let len = u32::from_be_bytes(len) as usize;
+        let value = self.data.get(4..4 + len).ok_or_else(truncated)?;
+        self.data = &self.data[4 + len..];
+        Ok(value)
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use ed25519_dalek::{Signature, Verifier};
+
+    fn request(principals: &[&str]) -> SshCertificateRequest {
+        let now = Utc::now();
+        SshCertificateRequest {
+            key_id: "alice@example.com".to_string(),
+            principals: principals.iter().map(|p| p.to_string()).collect(),
+            valid_after: now,
+            valid_before: now + Duration::hours(1),
+            critical_options: BTreeMap::new(),
+            extensions: ["permit-pty".to_string()].into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn test_public_key_round_trip() {
+        let user = SshCaKey::generate().public_key();
+        let line = format!("{} alice@laptop", user.to_openssh());
+
+        let parsed = SshPublicKey::parse(&line).unwrap();
+        assert_eq!(parsed.key_type(), "ssh-ed25519");
+        assert_eq!(parsed.comment(), Some("alice@laptop"));
+        assert_eq!(parsed.fingerprint(), user.fingerprint());
+
+        assert!(SshPublicKey::parse("ssh-dss AAAA").is_err());
+        assert!(SshPublicKey::parse("ssh-rsa c3NoLWVkMjU1MTk=").is_err());
+    }
+
+    #[test]
+    fn test_certificate_is_signed_by_active_key() {
+        let seed = [7u8; 32];
+        let ca = SshCertificateAuthority::new(
+            SshCaKey::from_seed_base64(&STANDARD.encode(seed)).unwrap(),
+        );
+        let user = SshCaKey::generate().public_key();
+
+        let cert = ca.sign(&user, &request(&["alice"])).unwrap();
+        let mut parts = cert.certificate.split_whitespace();
+        assert_eq!(parts.next(), Some("ssh-ed25519-cert-v01@openssh.com"));
+        let blob = STANDARD.decode(parts.next().unwrap()).unwrap();
+
+        // Trailing signature: string("ssh-ed25519") + string(64 bytes), wrapped
+        let signature_len = 4 + (4 + ED25519.len()) + (4 + 64);
+        let (signed, signature_field) = blob.split_at(blob.len() - signature_len);
+        let signature =
+            Signature::from_slice(&signature_field[signature_field.len() - 64..]).unwrap();
+        SigningKey::from_bytes(&seed)
+            .verifying_key()
+            .verify(signed, &signature)
+            .unwrap();
+        assert_eq!(cert.ca_fingerprint, ca.trusted_keys()[0].fingerprint());
+    }
+
+    #[test]
+    fn test_rotation_keeps_old_key_trusted_until_retired() {
+        let ca = SshCertificateAuthority::new(SshCaKey::generate());
+        let old = ca.trusted_keys()[0].fingerprint();
+
+        let new = ca.rotate(SshCaKey::generate());
+        let trusted: Vec<String> = ca.trusted_keys().iter().map(|k| k.fingerprint()).collect();
+        assert_eq!(trusted, vec![new.fingerprint(), old.clone()]);
+
+        assert!(!ca.retire(&new.fingerprint()));
+        assert!(ca.retire(&old));
+        assert_eq!(ca.trusted_keys().len(), 1);
+    }
+
+    #[test]
+    fn test_rejects_unbounded_certificates() {
+        let ca = SshCertificateAuthority::new(SshCaKey::generate());
+        let user = SshCaKey::generate().public_key();
+
+        assert!(ca.sign(&user, &request(&[])).is_err());
+
+        let mut backwards = request(&["alice"]);
+        backwards.valid_before = backwards.valid_after;
+        assert!(ca.sign(&user, &backwards).is_err());
+    }
+}
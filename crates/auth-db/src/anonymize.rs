@@ -0,0 +1,342 @@
+//! Deterministic data anonymization for non-production copies
+//!
+//! Replaces emails, phones, names and IP addresses with synthetic values.
+//! Replacements are derived from a secret and a stable input (the user id for
+//! user fields, the original address for IPs), so the same user always gets
+//! the same identity and joins, dedup and test logins keep working. Values
+//! keep their shape: emails stay valid addresses, phones keep their length
+//! and country prefix, IPs stay in the same address family.
+//!
+//! Users and tenants under a legal hold are left as they are.
+
+use crate::repositories::legal_hold_repository::not_held;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{MySqlPool, Row};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use uuid::Uuid;
+
+/// Reserved TLD, so anonymized addresses can never reach a real mailbox
+pub const ANONYMIZED_EMAIL_DOMAIN: &str = "anon.example";
+
+const FIRST_NAMES: &[&str] = &[
+    "Alex", "Sam", "Jordan", "Taylor", "Morgan", "Casey", "Riley", "Jamie", "Avery", "Quinn",
+    "Robin", "Drew", "Skyler", "Reese", "Rowan", "Emerson",
+];
+const LAST_NAMES: &[&str] = &[
+    "Smith", "Lee", "Garcia", "Brown", "Khan", "Silva", "Novak", "Tanaka", "Okafor", "Muller",
+    "Rossi", "Jensen", "Kowalski", "Dubois", "Haddad", "Larsen",
+];
+
+/// Profile keys holding personal names
+const NAME_KEYS: &[&str] = &[
+    "name",
+    "given_name",
+    "family_name",
+    "first_name",
+    "last_name",
+];
+
+/// Tables with an `ip_address` column, besides `users.last_login_ip`, and
+/// their user id column
+const IP_TABLES: &[(&str, Option<&str>)] = &[
+    ("sessions", Some("user_id")),
+    ("refresh_tokens", Some("user_id")),
+    ("otp_sessions", Some("user_id")),
+    ("authorization_audit_logs", None),
+];
+
+pub struct Anonymizer {
+    secret: Vec<u8>,
+}
+
+impl Anonymizer {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: secret.to_vec(),
+        }
+    }
+
+    fn digest(&self, domain: &str, input: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.secret);
+        hasher.update(domain.as_bytes());
+        hasher.update(input);
+        hasher.finalize().into()
+    }
+
+    pub fn email(&self, user_id: Uuid) -> String {
+        let d = self.digest("email", user_id.as_bytes());
+        let local = hex::encode(&d[..6]);
+        format!("user.{}@{}", local, ANONYMIZED_EMAIL_DOMAIN)
+    }
+
+    /// Keeps the leading `+`, the first two digits (roughly the country code)
+    /// and the total number of digits
+    pub fn phone(&self, user_id: Uuid, original: &str) -> String {
+        let plus = original.trim_start().starts_with('+');
+        let digits: Vec<char> = original.chars().filter(|c| c.is_ascii_digit()).collect();
+        let d = self.digest("phone", user_id.as_bytes());
+
+        let mut out = String::with_capacity(digits.len() + 1);
+        if plus {
+            out.push('+');
+        }
+        for (i, c) in digits.iter().enumerate() {
+            if i < 2 {
+                out.push(*c);
+            } else {
+                out.push(char::from(b'0' + d[i % d.len()] % 10));
+            }
+        }
+        out
+    }
+
+    pub fn first_name(&self, user_id: Uuid) -> &'static str {
+        let d = self.digest("first_name", user_id.as_bytes());
+        FIRST_NAMES[d[0] as usize % FIRST_NAMES.len()]
+    }
+
+    pub fn last_name(&self, user_id: Uuid) -> &'static str {
+        let d = self.digest("last_name", user_id.as_bytes());
+        LAST_NAMES[d[0] as usize % LAST_NAMES.len()]
+    }
+
+    /// Rewrite name fields inside a profile document, leaving the rest intact
+    pub fn profile(&self, user_id: Uuid, profile: &mut Value) {
+        let Some(map) = profile.as_object_mut() else {
+            return;
+        };
+        let (first, last) = (self.first_name(user_id), self.last_name(user_id));
+        for key in NAME_KEYS {
+            if let Some(value) = map.get_mut(*key) {
+                *value = Value::String(match *key {
+                    "given_name" | "first_name" => first.to_string(),
+                    "family_name" | "last_name" => last.to_string(),
+                    _ => format!("{} {}", first, last),
+                });
+            }
+        }
+    }
+
+    /// Map into 10.0.0.0/8 or fd00::/8. Addresses already in those ranges are
+    /// kept, which also makes re-running the job a no-op. Unparseable values
+    /// are returned as `None` and should be cleared.
+    pub fn ip(&self, original: &str) -> Option<String> {
+        let ip: IpAddr = original.trim().parse().ok()?;
+        let d = self.digest("ip", original.trim().as_bytes());
+        let mapped = match ip {
+            IpAddr::V4(v4) if v4.octets()[0] == 10 => return Some(v4.to_string()),
+            IpAddr::V6(v6) if v6.octets()[0] == 0xfd => return Some(v6.to_string()),
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::new(10, d[0], d[1], d[2])),
+            IpAddr::V6(_) => {
+                let mut octets = [0u8; 16];
+                octets[0] = 0xfd;
+                octets[1..].copy_from_slice(&d[..15]);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+        };
+        Some(mapped.to_string())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AnonymizationReport {
+    pub users: usize,
+    pub ip_addresses: usize,
+    /// Active legal holds, whose users were skipped
+    pub legal_holds: u64,
+}
+
+/// Rewrites PII in place across the database
+pub struct AnonymizationJob {
+    pool: MySqlPool,
+    anonymizer: Anonymizer,
+    batch_size: u32,
+}
+
+impl AnonymizationJob {
+    pub fn new(pool: MySqlPool, anonymizer: Anonymizer, batch_size: u32) -> Self {
+        Self {
+            pool,
+            anonymizer,
+            batch_size,
+        }
+    }
+
+    pub async fn run(&self) -> Result<AnonymizationReport, sqlx::Error> {
+        let mut report = AnonymizationReport {
+            users: self.anonymize_users().await?,
+            legal_holds: sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM legal_holds WHERE released_at IS NULL \
+                 /* tenant:unscoped report */",
+            )
+            .fetch_one(&self.pool)
+            .await? as u64,
+            ..Default::default()
+        };
+        report.ip_addresses += self
+            .anonymize_ips("users", "last_login_ip", Some("id"))
+            .await?;
+        for (table, user_column) in IP_TABLES {
+            report.ip_addresses += self
+                .anonymize_ips(table, "ip_address", *user_column)
+                .await?;
+        }
+        Ok(report)
+    }
+
+    /// Users are paged by id; every user is rewritten from its id alone, so
+    /// the pass is idempotent
+    async fn anonymize_users(&self) -> Result<usize, sqlx::Error> {
+        let mut total = 0;
+        let mut after = String::new();
+
+        loop {
+            let rows = sqlx::query(&format!(
+                "SELECT id, email, phone, profile_data FROM users WHERE id > ? AND {} \
+                 ORDER BY id LIMIT ? /* tenant:unscoped anonymization of all tenants */",
+                not_held("users", Some("id"))
+            ))
+            .bind(&after)
+            .bind(self.batch_size)
+            .fetch_all(&self.pool)
+            .await?;
+
+            let Some(last) = rows.last() else {
+                break;
+            };
+            after = last.try_get("id")?;
+
+            for row in &rows {
+                let id_str: String = row.try_get("id")?;
+                let Ok(user_id) = Uuid::parse_str(&id_str) else {
+                    tracing::warn!("Skipping user with malformed id '{}'", id_str);
+                    continue;
+                };
+
+                let email: Option<String> = row.try_get("email")?;
+                let phone: Option<String> = row.try_get("phone")?;
+                let mut profile: Value = row
+                    .try_get::<Value, _>("profile_data")
+                    .unwrap_or(Value::Null);
+                self.anonymizer.profile(user_id, &mut profile);
+
+                // Blind indexes and canonical forms are cleared: they are
+                // derived from the real values. Usernames become the user id,
+                // which keeps them unique per tenant.
+                sqlx::query(
+                    r#"
+                    UPDATE users
+                    SET email = ?, phone = ?, profile_data = ?,
+                        email_bidx = NULL, phone_bidx = NULL, normalized_identifier = NULL,
+                        username = IF(username IS NULL, NULL, CONCAT('user-', REPLACE(id, '-', ''))),
+                        username_normalized = username
+                    WHERE id = ?
+                    "#,
+                )
+                .bind(email.map(|_| self.anonymizer.email(user_id)))
+                .bind(phone.map(|p| self.anonymizer.phone(user_id, &p)))
+                .bind(profile)
+                .bind(&id_str)
+                .execute(&self.pool)
+                .await?;
+            }
+
+            total += rows.len();
+            tracing::info!("Anonymized {} users", total);
+        }
+
+        Ok(total)
+    }
+
+    async fn anonymize_ips(
+        &self,
+        table: &str,
+        column: &str,
+        user_column: Option<&str>,
+    ) -> Result<usize, sqlx::Error> {
+        let held = not_held(table, user_column);
+        let distinct = format!(
+            "SELECT DISTINCT {column} FROM {table} WHERE {column} IS NOT NULL AND {held}",
+            column = column,
+            table = table,
+            held = held
+        );
+        let update = format!(
+            "UPDATE {table} SET {column} = ? WHERE {column} = ? AND {held}",
+            column = column,
+            table = table,
+            held = held
+        );
+
+        let originals: Vec<String> = sqlx::query_scalar(&distinct).fetch_all(&self.pool).await?;
+
+        let mut rewritten = 0;
+        for original in originals {
+            let replacement = self.anonymizer.ip(&original);
+            if replacement.as_deref() == Some(original.as_str()) {
+                continue;
+            }
+            sqlx::query(&update)
+                .bind(replacement)
+                .bind(&original)
+                .execute(&self.pool)
+                .await?;
+            rewritten += 1;
+        }
+        Ok(rewritten)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_fields_are_stable_and_shaped() {
+        let anon = Anonymizer::new(b"secret");
+        let user = Uuid::new_v4();
+
+        let email = anon.email(user);
+        assert_eq!(email, anon.email(user));
+        assert_ne!(email, anon.email(Uuid::new_v4()));
+        assert!(email.ends_with(ANONYMIZED_EMAIL_DOMAIN));
+
+        let phone = anon.phone(user, "+44 7700 900123");
+        assert!(phone.starts_with("+44"));
+        assert_eq!(phone.len(), "+447700900123".len());
+        assert_eq!(phone, anon.phone(user, "+44 7700 900123"));
+    }
+
+    #[test]
+    fn test_profile_names_rewritten() {
+        let anon = Anonymizer::new(b"secret");
+        let user = Uuid::new_v4();
+        let mut profile = serde_json::json!({
+            "name": "Real Person",
+            "given_name": "Real",
+            "locale": "en-GB",
+        });
+        anon.profile(user, &mut profile);
+
+        assert_eq!(profile["given_name"], anon.first_name(user));
+        assert_ne!(profile["name"], "Real Person");
+        assert_eq!(profile["locale"], "en-GB");
+    }
+
+    #[test]
+    fn test_ips_keep_family_and_are_idempotent() {
+        let anon = Anonymizer::new(b"secret");
+
+        let v4 = anon.ip("203.0.113.7").unwrap();
+        assert!(v4.starts_with("10."));
+        assert_eq!(anon.ip(&v4).unwrap(), v4);
+
+        let v6 = anon.ip("2001:db8::1").unwrap();
+        assert!(v6.starts_with("fd"));
+        assert_eq!(anon.ip(&v6).unwrap(), v6);
+
+        assert!(anon.ip("not-an-ip").is_none());
+    }
+}
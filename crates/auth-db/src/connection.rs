@@ -1,11 +1,16 @@
 //! Database connection management
 
-use anyhow::Result;
-use auth_config::DatabaseConfig;
-use secrecy::ExposeSecret;
-use sqlx::mysql::{MySqlConnectOptions, MySqlPoolOptions};
-use sqlx::{MySql, MySqlPool, Pool, Sqlite, SqlitePool};
-use std::time::Duration;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use auth_config::{DatabaseConfig, DatabaseCredentialsSource};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::mysql::{MySqlConnectOptions, MySqlConnection, MySqlDatabaseError, MySqlPoolOptions};
+use sqlx::{Connection, MySql, MySqlPool, Pool, Sqlite, SqlitePool};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 pub enum DatabasePool {
     MySql(MySqlPool),
@@ -35,3 +40,256 @@ pub async fn create_sqlite_pool(database_url: &str) -> Result<Pool<Sqlite>> {
     let pool = SqlitePool::connect(database_url).await?;
     Ok(pool)
 }
+
+// ============================================================================
+// Dynamic credentials
+// ============================================================================
+
+/// A database login
+#[derive(Clone, Deserialize)]
+pub struct DatabaseCredentials {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+impl DatabaseCredentials {
+    fn fingerprint(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.username.as_bytes());
+        hasher.update([0]);
+        hasher.update(self.password.expose_secret().as_bytes());
+        hasher.finalize().into()
+    }
+}
+
+/// Source of the current database login, asked on every refresh
+#[async_trait]
+pub trait CredentialsProvider: Send + Sync {
+    async fn credentials(&self) -> Result<DatabaseCredentials>;
+}
+
+/// Reads `{"username": "...", "password": "..."}` from a file kept current
+/// by an agent, e.g. a Vault Agent template
+pub struct FileCredentialsProvider {
+    path: PathBuf,
+}
+
+impl FileCredentialsProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl CredentialsProvider for FileCredentialsProvider {
+    async fn credentials(&self) -> Result<DatabaseCredentials> {
+        let contents = tokio::fs::read_to_string(&self.path)
+            .await
+            .with_context(|| format!("reading {}", self.path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("parsing credentials in {}", self.path.display()))
+    }
+}
+
+/// MySQL refused the login: ER_ACCESS_DENIED_ERROR
+pub fn is_access_denied(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Database(e) => e
+            .try_downcast_ref::<MySqlDatabaseError>()
+            .is_some_and(|e| e.number() == 1045),
+        _ => false,
+    }
+}
+
+/// Keeps a pool on the provider's current login
+///
+/// A changed login is tried on a connection of its own first and only then
+/// used for the pool's new connections. Connections opened before the
+/// switch are closed as they are next acquired, so queries in flight finish
+/// on them and the old login can be revoked once they are gone.
+pub struct CredentialRotation {
+    provider: Arc<dyn CredentialsProvider>,
+    base: MySqlConnectOptions,
+    current: Mutex<[u8; 32]>,
+    rotated_at: Mutex<Option<Instant>>,
+}
+
+impl CredentialRotation {
+    /// Open a pool with the provider's login
+    pub async fn connect(
+        pool_options: MySqlPoolOptions,
+        base: MySqlConnectOptions,
+        provider: Arc<dyn CredentialsProvider>,
+    ) -> Result<(MySqlPool, Arc<Self>)> {
+        let credentials = provider.credentials().await?;
+        let rotation = Arc::new(Self {
+            provider,
+            current: Mutex::new(credentials.fingerprint()),
+            rotated_at: Mutex::new(None),
+            base,
+        });
+
+        let drain = rotation.clone();
+        let pool = pool_options
+            .before_acquire(move |_conn, meta| {
+                let stale = drain.predates_rotation(meta.age);
+                Box::pin(async move {
+                    if stale {
+                        metrics::counter!("auth_db_stale_connections_drained_total", 1);
+                    }
+                    Ok(!stale)
+                })
+            })
+            .connect_with(rotation.options(&credentials))
+            .await
+            .inspect_err(|e| {
+                if is_access_denied(e) {
+                    count_auth_failure("connect");
+                }
+            })?;
+        Ok((pool, rotation))
+    }
+
+    fn options(&self, credentials: &DatabaseCredentials) -> MySqlConnectOptions {
+        self.base
+            .clone()
+            .username(&credentials.username)
+            .password(credentials.password.expose_secret())
+    }
+
+    /// Whether a connection `age` old was opened before the last switch
+    fn predates_rotation(&self, age: Duration) -> bool {
+        let rotated_at = *self.rotated_at.lock().expect("rotation lock");
+        rotated_at.is_some_and(|rotated_at| {
+            Instant::now()
+                .checked_sub(age)
+                .is_some_and(|opened| opened < rotated_at)
+        })
+    }
+
+    /// Ask the provider for the login and switch the pool to it if it
+    /// changed. Returns whether it switched.
+    ///
+    /// An unchanged login is still tried on a new connection, so a login
+    /// revoked before the provider noticed shows up in the metrics.
+    pub async fn refresh(&self, pool: &MySqlPool) -> Result<bool> {
+        let credentials = self.provider.credentials().await?;
+        let fingerprint = credentials.fingerprint();
+        let changed = *self.current.lock().expect("rotation lock") != fingerprint;
+        let options = self.options(&credentials);
+
+        match MySqlConnection::connect_with(&options).await {
+            Ok(probe) => {
+                let _ = probe.close().await;
+            }
+            Err(e) => {
+                if is_access_denied(&e) {
+                    count_auth_failure(if changed { "rotated" } else { "stale" });
+                }
+                return Err(e).context("database login check failed");
+            }
+        }
+        if !changed {
+            return Ok(false);
+        }
+
+        pool.set_connect_options(options);
+        *self.current.lock().expect("rotation lock") = fingerprint;
+        *self.rotated_at.lock().expect("rotation lock") = Some(Instant::now());
+        metrics::counter!("auth_db_credential_rotations_total", 1);
+        tracing::info!(
+            username = %credentials.username,
+            "Database credentials rotated; draining connections of the previous login"
+        );
+        Ok(true)
+    }
+
+    /// Refresh every `interval`
+    pub async fn run_refresh(self: Arc<Self>, pool: MySqlPool, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.refresh(&pool).await {
+                tracing::warn!("Failed to refresh database credentials: {:#}", e);
+            }
+        }
+    }
+}
+
+fn count_auth_failure(stage: &'static str) {
+    metrics::counter!("auth_db_credential_auth_failures_total", 1, "stage" => stage);
+}
+
+/// Open the pool with the login `config.credentials` points at. With
+/// dynamic credentials the rotation is returned, to be run with
+/// [`CredentialRotation::run_refresh`].
+pub async fn create_mysql_pool_with_credentials(
+    config: &DatabaseConfig,
+    pool_options: MySqlPoolOptions,
+) -> Result<(MySqlPool, Option<Arc<CredentialRotation>>)> {
+    let base = config
+        .mysql_url
+        .expose_secret()
+        .parse::<MySqlConnectOptions>()?;
+
+    match config.credentials.source {
+        DatabaseCredentialsSource::Static => Ok((pool_options.connect_with(base).await?, None)),
+        DatabaseCredentialsSource::File => {
+            let path = config
+                .credentials
+                .file_path
+                .clone()
+                .context("database.credentials.file_path is not set")?;
+            let provider = Arc::new(FileCredentialsProvider::new(path));
+            let (pool, rotation) =
+                CredentialRotation::connect(pool_options, base, provider).await?;
+            Ok((pool, Some(rotation)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed;
+
+    #[async_trait]
+    impl CredentialsProvider for Fixed {
+        async fn credentials(&self) -> Result<DatabaseCredentials> {
+            Ok(DatabaseCredentials {
+                username: "v-auth-1".to_string(),
+                password: Secret::new("secret".to_string()),
+            })
+        }
+    }
+
+    fn rotation() -> CredentialRotation {
+        CredentialRotation {
+            provider: Arc::new(Fixed),
+            base: "mysql://static:pw@localhost/auth".parse().unwrap(),
+            current: Mutex::new([0; 32]),
+            rotated_at: Mutex::new(None),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provider_login_replaces_url_login() {
+        let rotation = rotation();
+        let credentials = rotation.provider.credentials().await.unwrap();
+        let options = rotation.options(&credentials);
+        assert_eq!(options.get_username(), "v-auth-1");
+        assert_eq!(options.get_database(), Some("auth"));
+    }
+
+    #[test]
+    fn test_connections_before_the_switch_are_drained() {
+        let rotation = rotation();
+        assert!(!rotation.predates_rotation(Duration::from_secs(3600)));
+
+        *rotation.rotated_at.lock().unwrap() = Some(Instant::now() - Duration::from_secs(10));
+        assert!(rotation.predates_rotation(Duration::from_secs(60)));
+        assert!(!rotation.predates_rotation(Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,9 @@
+//! Errors of the database layer
+
+use auth_core::error::AuthError;
+
+/// The error a repository reports for a failed query, classified by its
+/// [`ErrorCode`](auth_core::error::ErrorCode) (`DB_`)
+pub(crate) fn db_err(e: sqlx::Error) -> AuthError {
+    AuthError::classified(e)
+}
@@ -1,11 +1,20 @@
 //! Database layer and migrations
 
+pub mod anonymize;
 pub mod connection;
+mod error;
+pub mod maintenance;
 pub mod migrations;
 pub mod models;
+pub mod online_migration;
 pub mod repositories;
+pub mod residency;
+pub mod schema;
+pub mod scoped;
+pub mod tenant_guard;
 
 pub use connection::*;
 pub use repositories::*;
+pub use residency::RegionRouter;
 pub mod sharding;
 pub use sharding::*;
@@ -0,0 +1,210 @@
+//! Periodic removal of expired rows
+//!
+//! Runs as singletons: deleting the same rows from every replica only adds
+//! lock contention, so `main` starts these under leader election. Rows
+//! under a legal hold are left in place.
+
+use crate::repositories::otp_repository::OtpRepository;
+use crate::repositories::{
+    NonceRepository, PermissionChangeRepository, RefreshTokenRepository, RevokedTokenRepository,
+};
+use auth_config::OtpPurgeConfig;
+use auth_core::error::AuthError;
+use chrono::{DateTime, TimeZone, Utc};
+use sqlx::MySqlPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How long permission changes stay in the feed. Replicas further behind
+/// resync from a snapshot.
+const PERMISSION_CHANGE_RETENTION_DAYS: i64 = 30;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SweepReport {
+    pub revoked_tokens: u64,
+    pub refresh_tokens: u64,
+    pub single_use_tokens: u64,
+    pub permission_changes: u64,
+}
+
+pub struct ExpiredRecordSweeper {
+    revoked_tokens: RevokedTokenRepository,
+    refresh_tokens: RefreshTokenRepository,
+    nonces: NonceRepository,
+    permission_changes: PermissionChangeRepository,
+}
+
+impl ExpiredRecordSweeper {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self {
+            revoked_tokens: RevokedTokenRepository::new(pool.clone()),
+            refresh_tokens: RefreshTokenRepository::new(pool.clone()),
+            nonces: NonceRepository::new(pool.clone()),
+            permission_changes: PermissionChangeRepository::new(pool),
+        }
+    }
+
+    /// One pass over every table. A failing table is logged and skipped.
+    pub async fn sweep(&self) -> SweepReport {
+        let mut report = SweepReport::default();
+
+        match self.revoked_tokens.cleanup_expired().await {
+            Ok(n) => report.revoked_tokens = n,
+            Err(e) => warn!("Failed to sweep revoked tokens: {}", e),
+        }
+        match self.refresh_tokens.cleanup_expired().await {
+            Ok(n) => report.refresh_tokens = n,
+            Err(e) => warn!("Failed to sweep refresh tokens: {}", e),
+        }
+        match self.nonces.purge_expired().await {
+            Ok(n) => report.single_use_tokens = n,
+            Err(e) => warn!("Failed to sweep single-use tokens: {}", e),
+        }
+        let cutoff = Utc::now() - chrono::Duration::days(PERMISSION_CHANGE_RETENTION_DAYS);
+        match self.permission_changes.prune_before(cutoff).await {
+            Ok(n) => report.permission_changes = n,
+            Err(e) => warn!("Failed to sweep permission changes: {}", e),
+        }
+
+        report
+    }
+
+    pub async fn run(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let report = self.sweep().await;
+            info!(
+                revoked_tokens = report.revoked_tokens,
+                refresh_tokens = report.refresh_tokens,
+                single_use_tokens = report.single_use_tokens,
+                permission_changes = report.permission_changes,
+                "Expired record sweep complete"
+            );
+        }
+    }
+}
+
+/// One purge pass
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OtpPurgeReport {
+    pub purged: u64,
+    pub batches: u32,
+    /// Age of the oldest spent session left behind, zero when none is
+    pub lag_seconds: i64,
+}
+
+/// Deletes spent OTP sessions a time bucket at a time, oldest first.
+/// Sign-ins insert and update rows in the same table, so batches are kept
+/// small and spaced out, and a run stops at a row limit rather than
+/// catching up in one go.
+pub struct OtpPurger {
+    otp_sessions: OtpRepository,
+    config: OtpPurgeConfig,
+}
+
+impl OtpPurger {
+    pub fn new(pool: MySqlPool, config: OtpPurgeConfig) -> Self {
+        Self {
+            otp_sessions: OtpRepository::new(pool),
+            config,
+        }
+    }
+
+    pub async fn purge(&self) -> Result<OtpPurgeReport, AuthError> {
+        let mut report = OtpPurgeReport::default();
+        let now = Utc::now();
+        let bucket = chrono::Duration::seconds(self.config.bucket_seconds as i64);
+        let pause = Duration::from_millis(self.config.batch_pause_ms);
+
+        while report.purged < self.config.max_rows_per_run {
+            let Some(oldest) = self.otp_sessions.oldest_purgeable(now).await? else {
+                break;
+            };
+            let from = bucket_start(oldest, self.config.bucket_seconds);
+            let until = (from + bucket).min(now);
+
+            // Drain the bucket before moving on to the next one
+            let before = report.purged;
+            loop {
+                let remaining = self.config.max_rows_per_run - report.purged;
+                let limit = (self.config.batch_size as u64).min(remaining) as u32;
+                let deleted = self.otp_sessions.purge_batch(from, until, limit).await?;
+                report.purged += deleted;
+                report.batches += 1;
+                metrics::counter!("auth_otp_sessions_purged_total", deleted);
+
+                if deleted < limit as u64 || report.purged >= self.config.max_rows_per_run {
+                    break;
+                }
+                tokio::time::sleep(pause).await;
+            }
+            // Another replica or an operator got there first
+            if report.purged == before {
+                break;
+            }
+            tokio::time::sleep(pause).await;
+        }
+
+        report.lag_seconds = self
+            .otp_sessions
+            .oldest_purgeable(Utc::now())
+            .await?
+            .map(|oldest| (Utc::now() - oldest).num_seconds().max(0))
+            .unwrap_or(0);
+        Ok(report)
+    }
+
+    pub async fn run(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            match self.purge().await {
+                Ok(report) => {
+                    metrics::gauge!("auth_otp_purge_lag_seconds", report.lag_seconds as f64);
+                    info!(
+                        purged = report.purged,
+                        batches = report.batches,
+                        lag_seconds = report.lag_seconds,
+                        "OTP session purge complete"
+                    );
+                }
+                Err(e) => warn!("Failed to purge OTP sessions: {}", e),
+            }
+            match self.otp_sessions.estimated_rows().await {
+                Ok(rows) => metrics::gauge!("auth_otp_sessions_rows", rows as f64),
+                Err(e) => warn!("Failed to read OTP session table size: {}", e),
+            }
+        }
+    }
+}
+
+/// Start of the bucket `at` falls in; buckets are aligned to the epoch
+fn bucket_start(at: DateTime<Utc>, bucket_seconds: u64) -> DateTime<Utc> {
+    let width = bucket_seconds.max(1) as i64;
+    let start = at.timestamp().div_euclid(width) * width;
+    Utc.timestamp_opt(start, 0).single().unwrap_or(at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets_are_aligned_to_the_epoch() {
+        let at = Utc.with_ymd_and_hms(2026, 2, 1, 10, 42, 17).unwrap();
+        assert_eq!(
+            bucket_start(at, 3600),
+            Utc.with_ymd_and_hms(2026, 2, 1, 10, 0, 0).unwrap()
+        );
+        assert_eq!(
+            bucket_start(at, 86_400),
+            Utc.with_ymd_and_hms(2026, 2, 1, 0, 0, 0).unwrap()
+        );
+        // A boundary opens its own bucket
+        let boundary = Utc.with_ymd_and_hms(2026, 2, 1, 11, 0, 0).unwrap();
+        assert_eq!(bucket_start(boundary, 3600), boundary);
+    }
+}
@@ -0,0 +1,215 @@
+use super::login_event_repository::{LoginEventRepository, COLUMNS as LOGIN_EVENT_COLUMNS};
+use crate::error::db_err;
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::analytics_export::{
+    AnalyticsExportStore, ExportDataset, ExportManifest, ExportRow, Watermark,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::types::Json;
+use sqlx::{MySqlPool, Row};
+
+pub struct AnalyticsExportRepository {
+    pool: MySqlPool,
+}
+
+impl AnalyticsExportRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    async fn login_events(
+        &self,
+        after: &Watermark,
+        until: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<ExportRow>, AuthError> {
+        let sql = format!(
+            "SELECT {} FROM login_events \
+             WHERE created_at < ? AND (created_at > ? OR (created_at = ? AND id > ?)) \
+             ORDER BY created_at, id LIMIT ? \
+             /* tenant:unscoped export of all tenants */",
+            LOGIN_EVENT_COLUMNS
+        );
+        let rows = sqlx::query(&sql)
+            .bind(until)
+            .bind(after.created_at)
+            .bind(after.created_at)
+            .bind(&after.id)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(|row| {
+                let event = LoginEventRepository::map_row(row).map_err(db_err)?;
+                let geo = event.geo.unwrap_or_default();
+                Ok(ExportRow {
+                    id: event.id.to_string(),
+                    created_at: event.created_at,
+                    values: vec![
+                        Some(event.id.to_string()),
+                        Some(event.user_id.to_string()),
+                        Some(event.tenant_id.to_string()),
+                        event.ip_address,
+                        event.user_agent,
+                        Some(event.success.to_string()),
+                        geo.country_code,
+                        geo.country,
+                        geo.city,
+                        geo.latitude.map(|v| v.to_string()),
+                        geo.longitude.map(|v| v.to_string()),
+                        geo.asn.map(|v| v.to_string()),
+                        geo.as_org,
+                        Some(timestamp(event.created_at)),
+                    ],
+                })
+            })
+            .collect()
+    }
+
+    async fn sessions(
+        &self,
+        after: &Watermark,
+        until: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<ExportRow>, AuthError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, user_id, tenant_id, device_fingerprint, user_agent, ip_address,
+                   CAST(risk_score AS DOUBLE) AS risk_score, last_activity, expires_at, created_at
+            FROM sessions
+            WHERE created_at < ? AND (created_at > ? OR (created_at = ? AND id > ?))
+            ORDER BY created_at, id
+            LIMIT ?
+            /* tenant:unscoped export of all tenants */
+            "#,
+        )
+        .bind(until)
+        .bind(after.created_at)
+        .bind(after.created_at)
+        .bind(&after.id)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(|row| {
+                let id: String = row.try_get("id")?;
+                let created_at: DateTime<Utc> = row.try_get("created_at")?;
+                let risk_score: Option<f64> = row.try_get("risk_score")?;
+                let last_activity: Option<DateTime<Utc>> = row.try_get("last_activity")?;
+                let expires_at: DateTime<Utc> = row.try_get("expires_at")?;
+                Ok(ExportRow {
+                    values: vec![
+                        Some(id.clone()),
+                        Some(row.try_get("user_id")?),
+                        Some(row.try_get("tenant_id")?),
+                        row.try_get("device_fingerprint")?,
+                        row.try_get("user_agent")?,
+                        row.try_get("ip_address")?,
+                        risk_score.map(|v| v.to_string()),
+                        last_activity.map(timestamp),
+                        Some(timestamp(expires_at)),
+                        Some(timestamp(created_at)),
+                    ],
+                    id,
+                    created_at,
+                })
+            })
+            .collect::<Result<_, sqlx::Error>>()
+            .map_err(db_err)
+    }
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[async_trait]
+impl AnalyticsExportStore for AnalyticsExportRepository {
+    async fn rows_after(
+        &self,
+        dataset: ExportDataset,
+        after: Option<&Watermark>,
+        until: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<ExportRow>, AuthError> {
+        // Sorts before every row
+        let start = Watermark {
+            created_at: DateTime::<Utc>::UNIX_EPOCH,
+            id: String::new(),
+        };
+        let after = after.unwrap_or(&start);
+        match dataset {
+            ExportDataset::Sessions => self.sessions(after, until, limit).await,
+            ExportDataset::LoginEvents => self.login_events(after, until, limit).await,
+        }
+    }
+
+    async fn last_export(
+        &self,
+        dataset: ExportDataset,
+    ) -> Result<Option<ExportManifest>, AuthError> {
+        let row = sqlx::query(
+            "SELECT manifest FROM analytics_exports WHERE dataset = ? \
+             ORDER BY completed_at DESC LIMIT 1",
+        )
+        .bind(dataset.as_str())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+        row.map(|row| row.try_get::<Json<ExportManifest>, _>("manifest"))
+            .transpose()
+            .map(|manifest| manifest.map(|Json(m)| m))
+            .map_err(db_err)
+    }
+
+    async fn record_export(&self, manifest: &ExportManifest) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO analytics_exports (id, dataset, completed_at, row_count, manifest)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(manifest.id.to_string())
+        .bind(manifest.dataset.as_str())
+        .bind(manifest.completed_at)
+        .bind(manifest.rows)
+        .bind(Json(manifest))
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn list_exports(
+        &self,
+        dataset: Option<ExportDataset>,
+        limit: usize,
+    ) -> Result<Vec<ExportManifest>, AuthError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT manifest FROM analytics_exports
+            WHERE (? IS NULL OR dataset = ?)
+            ORDER BY completed_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(dataset.map(|d| d.as_str()))
+        .bind(dataset.map(|d| d.as_str()))
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+        rows.into_iter()
+            .map(|row| {
+                row.try_get::<Json<ExportManifest>, _>("manifest")
+                    .map(|Json(m)| m)
+            })
+            .collect::<Result<_, sqlx::Error>>()
+            .map_err(db_err)
+    }
+}
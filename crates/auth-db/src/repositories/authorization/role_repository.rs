@@ -1,10 +1,41 @@
+use crate::error::db_err;
+use crate::tenant_guard::{tenant_query, TenantContext};
 use async_trait::async_trait;
 use auth_core::error::AuthError;
 use auth_core::models::{Role, RoleScope};
 use auth_core::services::authorization::RoleStore;
-use sqlx::MySqlPool;
+use auth_core::services::permission_sync::{Assignment, PermissionStateSource, RoleGrant};
+use sqlx::mysql::MySqlRow;
+use sqlx::{MySqlPool, Row};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
+const SELECT_ROLE_GRANTS: &str = r#"
+    SELECT r.id, r.name, r.parent_role_id, p.code
+    FROM roles r
+    LEFT JOIN role_permissions rp ON rp.role_id = r.id
+    LEFT JOIN permissions p ON p.id = rp.permission_id
+    WHERE r.tenant_id = ?
+"#;
+const SELECT_ROLE_GRANT: &str = r#"
+    SELECT r.id, r.name, r.parent_role_id, p.code
+    FROM roles r
+    LEFT JOIN role_permissions rp ON rp.role_id = r.id
+    LEFT JOIN permissions p ON p.id = rp.permission_id
+    WHERE r.tenant_id = ? AND r.id = ?
+"#;
+const SELECT_ASSIGNMENTS: &str = r#"
+    SELECT user_id, role_id, expires_at
+    FROM user_roles
+    WHERE tenant_id = ? AND revoked_at IS NULL
+"#;
+const SELECT_USER_ASSIGNMENTS: &str = r#"
+    SELECT user_id, role_id, expires_at
+    FROM user_roles
+    WHERE tenant_id = ? AND user_id = ? AND revoked_at IS NULL
+"#;
+
 pub struct RoleRepository {
     pool: MySqlPool,
 }
@@ -49,9 +80,7 @@ impl RoleStore for RoleRepository {
 
         match result {
             Ok(_) => Ok(role),
-            Err(e) => Err(AuthError::DatabaseError {
-                message: e.to_string(),
-            }),
+            Err(e) => Err(db_err(e)),
         }
     }
 
@@ -77,9 +106,7 @@ impl RoleStore for RoleRepository {
                     Ok(())
                 }
             }
-            Err(e) => Err(AuthError::DatabaseError {
-                message: e.to_string(),
-            }),
+            Err(e) => Err(db_err(e)),
         }
     }
 
@@ -99,11 +126,7 @@ impl RoleStore for RoleRepository {
         .bind(tenant_id.to_string())
         .fetch_optional(&self.pool)
         .await
-        .map_err(|e| AuthError::DatabaseError {
-            message: e.to_string(),
-        })?;
-
-        use sqlx::Row;
+        .map_err(db_err)?;
 
         if let Some(row) = rec {
             let id_str: String = row.try_get("id").unwrap_or_default();
@@ -162,9 +185,139 @@ impl RoleStore for RoleRepository {
 
         match result {
             Ok(_) => Ok(()),
-            Err(e) => Err(AuthError::DatabaseError {
-                message: e.to_string(),
-            }),
+            Err(e) => Err(db_err(e)),
         }
     }
+
+    async fn find_user_permissions(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+        resource_class: &str,
+    ) -> Result<Vec<String>, AuthError> {
+        sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT DISTINCT p.code
+            FROM user_roles ur
+            JOIN role_permissions rp ON rp.role_id = ur.role_id
+            JOIN permissions p ON p.id = rp.permission_id
+            WHERE ur.user_id = ? AND ur.tenant_id = ?
+              AND p.resource_type = ?
+              AND ur.revoked_at IS NULL
+              AND (ur.expires_at IS NULL OR ur.expires_at > NOW())
+            "#,
+        )
+        .bind(user_id.to_string())
+        .bind(tenant_id.to_string())
+        .bind(resource_class)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)
+    }
+
+    async fn find_user_role_names(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+    ) -> Result<Vec<String>, AuthError> {
+        sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT DISTINCT r.name
+            FROM user_roles ur
+            JOIN roles r ON r.id = ur.role_id
+            WHERE ur.user_id = ? AND ur.tenant_id = ?
+              AND ur.revoked_at IS NULL
+              AND (ur.expires_at IS NULL OR ur.expires_at > NOW())
+            "#,
+        )
+        .bind(user_id.to_string())
+        .bind(tenant_id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)
+    }
+}
+
+fn parse_id(row: &MySqlRow, column: &str) -> Result<Uuid, AuthError> {
+    let id: String = row.try_get(column).map_err(db_err)?;
+    Uuid::parse_str(&id).map_err(|e| AuthError::DatabaseError {
+        message: format!("malformed {} {}: {}", column, id, e),
+    })
+}
+
+/// One row per role and permission, folded into grants
+fn role_grants(rows: Vec<MySqlRow>) -> Result<Vec<RoleGrant>, AuthError> {
+    let mut grants: BTreeMap<Uuid, RoleGrant> = BTreeMap::new();
+    for row in rows {
+        let role_id = parse_id(&row, "id")?;
+        let grant = match grants.entry(role_id) {
+            Entry::Occupied(grant) => grant.into_mut(),
+            Entry::Vacant(slot) => {
+                let parent: Option<String> = row.try_get("parent_role_id").map_err(db_err)?;
+                slot.insert(RoleGrant {
+                    role_id,
+                    name: row.try_get("name").map_err(db_err)?,
+                    parent_role_id: parent.and_then(|p| Uuid::parse_str(&p).ok()),
+                    permissions: Vec::new(),
+                })
+            }
+        };
+        if let Some(code) = row.try_get::<Option<String>, _>("code").map_err(db_err)? {
+            grant.permissions.push(code);
+        }
+    }
+    Ok(grants.into_values().collect())
+}
+
+fn assignments(rows: Vec<MySqlRow>) -> Result<Vec<Assignment>, AuthError> {
+    rows.iter()
+        .map(|row| {
+            Ok(Assignment {
+                user_id: parse_id(row, "user_id")?,
+                role_id: parse_id(row, "role_id")?,
+                expires_at: row.try_get("expires_at").map_err(db_err)?,
+            })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl PermissionStateSource for RoleRepository {
+    async fn roles(&self, tenant_id: Uuid) -> Result<Vec<RoleGrant>, AuthError> {
+        let rows = tenant_query(&TenantContext::new(tenant_id), SELECT_ROLE_GRANTS)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_err)?;
+        role_grants(rows)
+    }
+
+    async fn role(&self, tenant_id: Uuid, role_id: Uuid) -> Result<Option<RoleGrant>, AuthError> {
+        let rows = tenant_query(&TenantContext::new(tenant_id), SELECT_ROLE_GRANT)
+            .bind(role_id.to_string())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_err)?;
+        Ok(role_grants(rows)?.pop())
+    }
+
+    async fn assignments(&self, tenant_id: Uuid) -> Result<Vec<Assignment>, AuthError> {
+        let rows = tenant_query(&TenantContext::new(tenant_id), SELECT_ASSIGNMENTS)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_err)?;
+        assignments(rows)
+    }
+
+    async fn user_assignments(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<Assignment>, AuthError> {
+        let rows = tenant_query(&TenantContext::new(tenant_id), SELECT_USER_ASSIGNMENTS)
+            .bind(user_id.to_string())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_err)?;
+        assignments(rows)
+    }
 }
@@ -0,0 +1,153 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::models::{OAuthClient, OAuthClientStore, TokenEndpointAuthMethod};
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+/// Columns of `oauth_clients` used here
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "oauth_clients",
+    columns: &[
+        Column::new("id", Text),
+        Column::new("tenant_id", Text),
+        Column::new("client_id", Text),
+        Column::new("client_secret", Text).nullable(),
+        Column::new("token_endpoint_auth_method", Text),
+        Column::new("redirect_uris", Text),
+        Column::new("grant_types", Text),
+        Column::new("scopes", Text),
+        Column::new("name", Text).nullable(),
+        Column::new("logo_uri", Text).nullable(),
+        Column::new("registration_token_hash", Text).nullable(),
+        Column::new("created_at", Timestamp),
+        Column::new("updated_at", Timestamp),
+    ],
+};
+
+const COLUMNS: &str = "id, tenant_id, client_id, client_secret, token_endpoint_auth_method, \
+     redirect_uris, grant_types, scopes, name, logo_uri, registration_token_hash, \
+     created_at, updated_at";
+
+pub struct ClientRepository {
+    pool: MySqlPool,
+}
+
+impl ClientRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    fn map_client(row: sqlx::mysql::MySqlRow) -> Result<OAuthClient, sqlx::Error> {
+        let id: String = row.try_get("id")?;
+        let tenant_id: String = row.try_get("tenant_id")?;
+        let auth_method: String = row.try_get("token_endpoint_auth_method")?;
+        let auth_method = TokenEndpointAuthMethod::parse(&auth_method).ok_or_else(|| {
+            sqlx::Error::Decode(
+                format!("unknown token endpoint auth method '{}'", auth_method).into(),
+            )
+        })?;
+        let json_list = |column: &str| -> Result<Vec<String>, sqlx::Error> {
+            let json: String = row.try_get(column)?;
+            serde_json::from_str(&json).map_err(|e| sqlx::Error::Decode(e.into()))
+        };
+
+        Ok(OAuthClient {
+            id: Uuid::parse_str(&id).unwrap_or_default(),
+            tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+            client_id: row.try_get("client_id")?,
+            secret_hash: row.try_get("client_secret")?,
+            token_endpoint_auth_method: auth_method,
+            redirect_uris: json_list("redirect_uris")?,
+            grant_types: json_list("grant_types")?,
+            scopes: json_list("scopes")?,
+            name: row.try_get("name")?,
+            logo_uri: row.try_get("logo_uri")?,
+            registration_token_hash: row.try_get("registration_token_hash")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+fn to_json(values: &[String]) -> String {
+    serde_json::to_string(values).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[async_trait]
+impl OAuthClientStore for ClientRepository {
+    async fn insert(&self, client: &OAuthClient) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO oauth_clients (
+                id, tenant_id, client_id, client_secret, token_endpoint_auth_method,
+                redirect_uris, grant_types, scopes, name, logo_uri, registration_token_hash,
+                created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(client.id.to_string())
+        .bind(client.tenant_id.to_string())
+        .bind(&client.client_id)
+        .bind(&client.secret_hash)
+        .bind(client.token_endpoint_auth_method.as_str())
+        .bind(to_json(&client.redirect_uris))
+        .bind(to_json(&client.grant_types))
+        .bind(to_json(&client.scopes))
+        .bind(&client.name)
+        .bind(&client.logo_uri)
+        .bind(&client.registration_token_hash)
+        .bind(client.created_at)
+        .bind(client.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn find(&self, client_id: &str) -> Result<Option<OAuthClient>, AuthError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM oauth_clients WHERE client_id = ?",
+            COLUMNS
+        ))
+        .bind(client_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+        row.map(Self::map_client).transpose().map_err(db_err)
+    }
+
+    async fn update(&self, client: &OAuthClient) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            UPDATE oauth_clients
+            SET client_secret = ?, token_endpoint_auth_method = ?, redirect_uris = ?,
+                grant_types = ?, scopes = ?, name = ?, logo_uri = ?, updated_at = ?
+            WHERE client_id = ?
+            "#,
+        )
+        .bind(&client.secret_hash)
+        .bind(client.token_endpoint_auth_method.as_str())
+        .bind(to_json(&client.redirect_uris))
+        .bind(to_json(&client.grant_types))
+        .bind(to_json(&client.scopes))
+        .bind(&client.name)
+        .bind(&client.logo_uri)
+        .bind(client.updated_at)
+        .bind(&client.client_id)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn delete(&self, client_id: &str) -> Result<bool, AuthError> {
+        let result = sqlx::query("DELETE FROM oauth_clients WHERE client_id = ?")
+            .bind(client_id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_err)?;
+        Ok(result.rows_affected() > 0)
+    }
+}
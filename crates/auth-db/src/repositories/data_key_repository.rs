@@ -0,0 +1,63 @@
+//! Storage for wrapped per-tenant PII data keys
+
+use async_trait::async_trait;
+use auth_config::PiiEncryptionConfig;
+use auth_crypto::{DataKeyStore, PiiProtector, SoftKeyProvider};
+use secrecy::ExposeSecret;
+use sqlx::MySqlPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct DataKeyRepository {
+    pool: MySqlPool,
+}
+
+impl DataKeyRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl DataKeyStore for DataKeyRepository {
+    async fn get_wrapped_key(&self, tenant_id: Uuid) -> anyhow::Result<Option<Vec<u8>>> {
+        let key = sqlx::query_scalar::<_, Vec<u8>>(
+            "SELECT wrapped_key FROM tenant_data_keys WHERE tenant_id = ?",
+        )
+        .bind(tenant_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(key)
+    }
+
+    async fn insert_wrapped_key(&self, tenant_id: Uuid, wrapped: &[u8]) -> anyhow::Result<()> {
+        // INSERT IGNORE keeps the first key if nodes race to create one
+        sqlx::query("INSERT IGNORE INTO tenant_data_keys (tenant_id, wrapped_key) VALUES (?, ?)")
+            .bind(tenant_id.to_string())
+            .bind(wrapped)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Build the PII protector described by `config`, or `None` when disabled
+pub fn pii_protector_from_config(
+    pool: MySqlPool,
+    config: &PiiEncryptionConfig,
+) -> anyhow::Result<Option<Arc<PiiProtector>>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    let (Some(master_key), Some(index_key)) = (&config.master_key_pem, &config.blind_index_key)
+    else {
+        anyhow::bail!("PII encryption requires master_key_pem and blind_index_key");
+    };
+
+    let provider = SoftKeyProvider::from_pkcs8_pem(master_key.expose_secret())?;
+    Ok(Some(Arc::new(PiiProtector::new(
+        Arc::new(provider),
+        Arc::new(DataKeyRepository::new(pool)),
+        index_key.expose_secret().as_bytes(),
+    ))))
+}
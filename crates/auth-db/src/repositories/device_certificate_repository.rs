@@ -0,0 +1,219 @@
+use crate::error::db_err;
+use crate::tenant_guard::{tenant_query, TenantContext};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::models::{DeviceBootstrapToken, DeviceCertificate};
+use auth_core::services::device_enrollment::DeviceCertificateStore;
+use chrono::{DateTime, Utc};
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+pub struct DeviceCertificateRepository {
+    pool: MySqlPool,
+}
+
+impl DeviceCertificateRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    fn map_token(row: sqlx::mysql::MySqlRow) -> Result<DeviceBootstrapToken, sqlx::Error> {
+        let id: String = row.try_get("id")?;
+        let tenant_id: String = row.try_get("tenant_id")?;
+
+        Ok(DeviceBootstrapToken {
+            id: Uuid::parse_str(&id).unwrap_or_default(),
+            tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+            device_id: row.try_get("device_id")?,
+            token_hash: row.try_get("token_hash")?,
+            expires_at: row.try_get("expires_at")?,
+            used_at: row.try_get("used_at")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+
+    pub(crate) fn map_certificate(
+        row: sqlx::mysql::MySqlRow,
+    ) -> Result<DeviceCertificate, sqlx::Error> {
+        let tenant_id: String = row.try_get("tenant_id")?;
+
+        Ok(DeviceCertificate {
+            serial: row.try_get("serial")?,
+            tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+            device_id: row.try_get("device_id")?,
+            certificate_pem: row.try_get("certificate_pem")?,
+            not_before: row.try_get("not_before")?,
+            not_after: row.try_get("not_after")?,
+            renewed_from: row.try_get("renewed_from")?,
+            revoked_at: row.try_get("revoked_at")?,
+            revocation_reason: row.try_get("revocation_reason")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+pub(crate) const CERTIFICATE_COLUMNS: &str =
+    "serial, tenant_id, device_id, certificate_pem, not_before, \
+     not_after, renewed_from, revoked_at, revocation_reason, created_at";
+
+#[async_trait]
+impl DeviceCertificateStore for DeviceCertificateRepository {
+    async fn create_bootstrap_token(&self, token: &DeviceBootstrapToken) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO device_bootstrap_tokens (id, tenant_id, device_id, token_hash, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(token.id.to_string())
+        .bind(token.tenant_id.to_string())
+        .bind(&token.device_id)
+        .bind(&token.token_hash)
+        .bind(token.expires_at)
+        .bind(token.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn consume_bootstrap_token(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DeviceBootstrapToken>, AuthError> {
+        // The conditional update is what makes the token single-use; only
+        // one concurrent caller can flip used_at
+        let consumed = sqlx::query(
+            r#"
+            UPDATE device_bootstrap_tokens SET used_at = ?
+            WHERE token_hash = ? AND used_at IS NULL AND expires_at > ?
+            "#,
+        )
+        .bind(now)
+        .bind(token_hash)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?
+        .rows_affected();
+        if consumed == 0 {
+            return Ok(None);
+        }
+
+        let row = sqlx::query(
+            r#"
+            SELECT id, tenant_id, device_id, token_hash, expires_at, used_at, created_at
+            FROM device_bootstrap_tokens
+            WHERE token_hash = ?
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        row.map(Self::map_token).transpose().map_err(db_err)
+    }
+
+    async fn insert_certificate(&self, certificate: &DeviceCertificate) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO device_certificates
+                (serial, tenant_id, device_id, certificate_pem, not_before, not_after, renewed_from, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&certificate.serial)
+        .bind(certificate.tenant_id.to_string())
+        .bind(&certificate.device_id)
+        .bind(&certificate.certificate_pem)
+        .bind(certificate.not_before)
+        .bind(certificate.not_after)
+        .bind(&certificate.renewed_from)
+        .bind(certificate.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn get_certificate(&self, serial: &str) -> Result<Option<DeviceCertificate>, AuthError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM device_certificates WHERE serial = ?",
+            CERTIFICATE_COLUMNS
+        ))
+        .bind(serial)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        row.map(Self::map_certificate).transpose().map_err(db_err)
+    }
+
+    async fn list_certificates(
+        &self,
+        tenant_id: Uuid,
+    ) -> Result<Vec<DeviceCertificate>, AuthError> {
+        let rows = tenant_query(
+            &TenantContext::new(tenant_id),
+            &format!(
+                "SELECT {} FROM device_certificates WHERE tenant_id = ? ORDER BY created_at",
+                CERTIFICATE_COLUMNS
+            ),
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(|row| Self::map_certificate(row).map_err(db_err))
+            .collect()
+    }
+
+    async fn revoke_certificate(
+        &self,
+        serial: &str,
+        reason: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            UPDATE device_certificates SET revoked_at = ?, revocation_reason = ?
+            WHERE serial = ? AND revoked_at IS NULL
+            "#,
+        )
+        .bind(at)
+        .bind(reason)
+        .bind(serial)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn revoked_certificates(
+        &self,
+        tenant_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<DeviceCertificate>, AuthError> {
+        let rows = tenant_query(
+            &TenantContext::new(tenant_id),
+            &format!(
+                r#"
+                SELECT {} FROM device_certificates
+                WHERE tenant_id = ? AND revoked_at IS NOT NULL AND not_after > ?
+                "#,
+                CERTIFICATE_COLUMNS
+            ),
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(|row| Self::map_certificate(row).map_err(db_err))
+            .collect()
+    }
+}
@@ -0,0 +1,80 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::feature_flags::{KillSwitch, KillSwitchStore};
+use sqlx::{MySqlPool, Row};
+
+/// Columns of `feature_kill_switches` used here
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "feature_kill_switches",
+    columns: &[
+        Column::new("feature", Text),
+        Column::new("reason", Text).nullable(),
+        Column::new("killed_by", Text).nullable(),
+        Column::new("killed_at", Timestamp),
+    ],
+};
+
+/// Kill switches are platform-wide, not per tenant
+pub struct FeatureKillSwitchRepository {
+    pool: MySqlPool,
+}
+
+impl FeatureKillSwitchRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl KillSwitchStore for FeatureKillSwitchRepository {
+    async fn list(&self) -> Result<Vec<KillSwitch>, AuthError> {
+        let rows =
+            sqlx::query("SELECT feature, reason, killed_by, killed_at FROM feature_kill_switches")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(db_err)?;
+        rows.into_iter()
+            .map(|row| {
+                Ok(KillSwitch {
+                    feature: row.try_get("feature")?,
+                    reason: row.try_get("reason")?,
+                    killed_by: row.try_get("killed_by")?,
+                    killed_at: row.try_get("killed_at")?,
+                })
+            })
+            .collect::<Result<_, sqlx::Error>>()
+            .map_err(db_err)
+    }
+
+    async fn set(&self, switch: &KillSwitch) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO feature_kill_switches (feature, reason, killed_by, killed_at)
+            VALUES (?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                reason = VALUES(reason),
+                killed_by = VALUES(killed_by),
+                killed_at = VALUES(killed_at)
+            "#,
+        )
+        .bind(&switch.feature)
+        .bind(&switch.reason)
+        .bind(&switch.killed_by)
+        .bind(switch.killed_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn clear(&self, feature: &str) -> Result<bool, AuthError> {
+        let result = sqlx::query("DELETE FROM feature_kill_switches WHERE feature = ?")
+            .bind(feature)
+            .execute(&self.pool)
+            .await
+            .map_err(db_err)?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,188 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::legal_hold::{LegalHold, LegalHoldStore};
+use chrono::Utc;
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+/// Columns of `legal_holds` used here
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "legal_holds",
+    columns: &[
+        Column::new("id", Text),
+        Column::new("tenant_id", Text),
+        Column::new("user_id", Text).nullable(),
+        Column::new("reason", Text),
+        Column::new("placed_by", Text),
+        Column::new("placed_at", Timestamp),
+        Column::new("released_by", Text).nullable(),
+        Column::new("released_at", Timestamp).nullable(),
+    ],
+};
+
+/// Predicate excluding rows of `table` that an active hold covers, for
+/// purges and rewrites to add to their `WHERE`. `user_column` names the
+/// table's user id column; without one only tenant-wide holds apply.
+pub fn not_held(table: &str, user_column: Option<&str>) -> String {
+    let user = match user_column {
+        Some(column) => format!("h.user_id IS NULL OR h.user_id = {}.{}", table, column),
+        None => "h.user_id IS NULL".to_string(),
+    };
+    format!(
+        "NOT EXISTS (SELECT 1 FROM legal_holds h WHERE h.released_at IS NULL \
+         AND h.tenant_id = {}.tenant_id AND ({}))",
+        table, user
+    )
+}
+
+pub struct LegalHoldRepository {
+    pool: MySqlPool,
+}
+
+impl LegalHoldRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    fn map_hold(row: sqlx::mysql::MySqlRow) -> Result<LegalHold, sqlx::Error> {
+        let id: String = row.try_get("id")?;
+        let tenant_id: String = row.try_get("tenant_id")?;
+        let user_id: Option<String> = row.try_get("user_id")?;
+
+        Ok(LegalHold {
+            id: Uuid::parse_str(&id).unwrap_or_default(),
+            tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+            user_id: user_id.and_then(|id| Uuid::parse_str(&id).ok()),
+            reason: row.try_get("reason")?,
+            placed_by: row.try_get("placed_by")?,
+            placed_at: row.try_get("placed_at")?,
+            released_by: row.try_get("released_by")?,
+            released_at: row.try_get("released_at")?,
+        })
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<LegalHold>, AuthError> {
+        let row = sqlx::query(&format!("SELECT {} FROM legal_holds WHERE id = ?", COLUMNS))
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_err)?;
+        row.map(Self::map_hold).transpose().map_err(db_err)
+    }
+}
+
+const COLUMNS: &str =
+    "id, tenant_id, user_id, reason, placed_by, placed_at, released_by, released_at";
+
+#[async_trait]
+impl LegalHoldStore for LegalHoldRepository {
+    async fn insert(&self, hold: &LegalHold) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO legal_holds (id, tenant_id, user_id, reason, placed_by, placed_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(hold.id.to_string())
+        .bind(hold.tenant_id.to_string())
+        .bind(hold.user_id.map(|id| id.to_string()))
+        .bind(&hold.reason)
+        .bind(&hold.placed_by)
+        .bind(hold.placed_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        tenant_id: Option<Uuid>,
+        include_released: bool,
+    ) -> Result<Vec<LegalHold>, AuthError> {
+        let mut conditions = Vec::new();
+        if tenant_id.is_some() {
+            conditions.push("tenant_id = ?");
+        }
+        if !include_released {
+            conditions.push("released_at IS NULL");
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let sql = format!(
+            // The filter names the tenant when one was given
+            "SELECT {} FROM legal_holds {} ORDER BY placed_at DESC \
+             /* tenant:unscoped admin listing */",
+            COLUMNS, filter
+        );
+
+        let mut query = sqlx::query(&sql);
+        if let Some(tenant_id) = tenant_id {
+            query = query.bind(tenant_id.to_string());
+        }
+        let rows = query.fetch_all(&self.pool).await.map_err(db_err)?;
+
+        rows.into_iter()
+            .map(Self::map_hold)
+            .collect::<Result<_, _>>()
+            .map_err(db_err)
+    }
+
+    async fn release(&self, id: Uuid, released_by: &str) -> Result<Option<LegalHold>, AuthError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE legal_holds SET released_by = ?, released_at = ?
+            WHERE id = ? AND released_at IS NULL
+            "#,
+        )
+        .bind(released_by)
+        .bind(Utc::now())
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.find(id).await
+    }
+
+    async fn is_held(&self, tenant_id: Uuid, user_id: Option<Uuid>) -> Result<bool, AuthError> {
+        let held: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT 1 FROM legal_holds
+            WHERE tenant_id = ? AND released_at IS NULL
+              AND (user_id IS NULL OR user_id = ?)
+            LIMIT 1
+            "#,
+        )
+        .bind(tenant_id.to_string())
+        .bind(user_id.map(|id| id.to_string()))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(held.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hold_predicate_names_the_outer_row() {
+        let predicate = not_held("refresh_tokens", Some("user_id"));
+        assert!(predicate.contains("h.tenant_id = refresh_tokens.tenant_id"));
+        assert!(predicate.contains("h.user_id = refresh_tokens.user_id"));
+
+        // Tables without a user column are only held tenant-wide
+        let predicate = not_held("permission_changes", None);
+        assert!(!predicate.contains("permission_changes.user_id"));
+        assert!(predicate.contains("h.user_id IS NULL"));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::geo::GeoLocation;
+use auth_core::services::login_history::{LoginEvent, LoginEventStore};
+use chrono::Utc;
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+/// Columns of `login_events` used here; the geo columns stay NULL until
+/// enrichment fills them in
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "login_events",
+    columns: &[
+        Column::new("id", Text),
+        Column::new("user_id", Text),
+        Column::new("tenant_id", Text),
+        Column::new("ip_address", Text).nullable(),
+        Column::new("user_agent", Text).nullable(),
+        Column::new("success", Boolean),
+        Column::new("country_code", Text).nullable(),
+        Column::new("country", Text).nullable(),
+        Column::new("city", Text).nullable(),
+        Column::new("latitude", Decimal).nullable(),
+        Column::new("longitude", Decimal).nullable(),
+        Column::new("asn", Integer).nullable(),
+        Column::new("as_org", Text).nullable(),
+        Column::new("created_at", Timestamp),
+    ],
+};
+
+pub struct LoginEventRepository {
+    pool: MySqlPool,
+}
+
+impl LoginEventRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    pub(crate) fn map_row(row: sqlx::mysql::MySqlRow) -> Result<LoginEvent, sqlx::Error> {
+        let id: String = row.try_get("id")?;
+        let user_id: String = row.try_get("user_id")?;
+        let tenant_id: String = row.try_get("tenant_id")?;
+
+        let geo = GeoLocation {
+            country_code: row.try_get("country_code")?,
+            country: row.try_get("country")?,
+            city: row.try_get("city")?,
+            latitude: row.try_get("latitude")?,
+            longitude: row.try_get("longitude")?,
+            asn: row.try_get("asn")?,
+            as_org: row.try_get("as_org")?,
+        };
+
+        Ok(LoginEvent {
+            id: Uuid::parse_str(&id).unwrap_or_default(),
+            user_id: Uuid::parse_str(&user_id).unwrap_or_default(),
+            tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+            ip_address: row.try_get("ip_address")?,
+            user_agent: row.try_get("user_agent")?,
+            success: row.try_get("success")?,
+            geo: (geo != GeoLocation::default()).then_some(geo),
+            created_at: row.try_get("created_at").unwrap_or_else(|_| Utc::now()),
+        })
+    }
+}
+
+pub(crate) const COLUMNS: &str = "id, user_id, tenant_id, ip_address, user_agent, success, \
+     country_code, country, city, latitude, longitude, asn, as_org, created_at";
+
+#[async_trait]
+impl LoginEventStore for LoginEventRepository {
+    async fn record(&self, event: &LoginEvent) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO login_events (id, user_id, tenant_id, ip_address, user_agent, success, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(event.id.to_string())
+        .bind(event.user_id.to_string())
+        .bind(event.tenant_id.to_string())
+        .bind(&event.ip_address)
+        .bind(&event.user_agent)
+        .bind(event.success)
+        .bind(event.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn set_geo(&self, event_id: Uuid, geo: &GeoLocation) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            UPDATE login_events
+            SET country_code = ?, country = ?, city = ?, latitude = ?, longitude = ?, asn = ?, as_org = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(&geo.country_code)
+        .bind(&geo.country)
+        .bind(&geo.city)
+        .bind(geo.latitude)
+        .bind(geo.longitude)
+        .bind(geo.asn)
+        .bind(&geo.as_org)
+        .bind(event_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn recent_for_user(
+        &self,
+        user_id: Uuid,
+        limit: u32,
+    ) -> Result<Vec<LoginEvent>, AuthError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, user_id, tenant_id, ip_address, user_agent, success,
+                   country_code, country, city, latitude, longitude, asn, as_org, created_at
+            FROM login_events
+            WHERE user_id = ?
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(user_id.to_string())
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(|row| Self::map_row(row).map_err(db_err))
+            .collect()
+    }
+}
@@ -0,0 +1,201 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use crate::tenant_guard::{tenant_query, TenantContext};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::login_heatmap::{
+    HeatmapBucket, HeatmapCell, HeatmapQuery, LoginHeatmapStore,
+};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+/// Columns of `login_heatmap_hourly` used here; an unresolved country is
+/// `''` and an unresolved ASN 0
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "login_heatmap_hourly",
+    columns: &[
+        Column::new("hour", Timestamp),
+        Column::new("tenant_id", Text),
+        Column::new("country_code", Text),
+        Column::new("asn", Integer),
+        Column::new("successes", Integer),
+        Column::new("failures", Integer),
+    ],
+};
+
+pub struct LoginHeatmapRepository {
+    pool: MySqlPool,
+}
+
+impl LoginHeatmapRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn count(row: &sqlx::mysql::MySqlRow, column: &str) -> Result<u64, sqlx::Error> {
+    let value: i64 = row.try_get(column)?;
+    Ok(value.max(0) as u64)
+}
+
+/// Country and ASN of a row, with the stand-ins for unresolved ones
+/// mapped back to `None`
+fn place(row: &sqlx::mysql::MySqlRow) -> Result<(Option<String>, Option<u32>), sqlx::Error> {
+    let country: String = row.try_get("country_code")?;
+    let asn: i64 = row.try_get("asn")?;
+    Ok((
+        (!country.is_empty()).then_some(country),
+        (asn > 0).then_some(asn as u32),
+    ))
+}
+
+#[async_trait]
+impl LoginHeatmapStore for LoginHeatmapRepository {
+    async fn count_hour(&self, hour: DateTime<Utc>) -> Result<Vec<HeatmapBucket>, AuthError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT tenant_id,
+                   COALESCE(country_code, '') AS country_code,
+                   CAST(COALESCE(asn, 0) AS SIGNED) AS asn,
+                   CAST(SUM(success) AS SIGNED) AS successes,
+                   CAST(SUM(1 - success) AS SIGNED) AS failures
+            FROM login_events
+            WHERE created_at >= ? AND created_at < ?
+            GROUP BY 1, 2, 3
+            /* tenant:unscoped hourly rollup of all tenants */
+            "#,
+        )
+        .bind(hour)
+        .bind(hour + Duration::hours(1))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(|row| {
+                let tenant_id: String = row.try_get("tenant_id")?;
+                let (country_code, asn) = place(&row)?;
+                Ok(HeatmapBucket {
+                    hour,
+                    tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+                    country_code,
+                    asn,
+                    successes: count(&row, "successes")?,
+                    failures: count(&row, "failures")?,
+                })
+            })
+            .collect::<Result<_, sqlx::Error>>()
+            .map_err(db_err)
+    }
+
+    async fn replace_hour(
+        &self,
+        hour: DateTime<Utc>,
+        buckets: &[HeatmapBucket],
+    ) -> Result<(), AuthError> {
+        let mut tx = self.pool.begin().await.map_err(db_err)?;
+        sqlx::query(
+            "DELETE FROM login_heatmap_hourly WHERE hour = ? \
+             /* tenant:unscoped hourly rollup of all tenants */",
+        )
+        .bind(hour)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+        for bucket in buckets {
+            sqlx::query(
+                r#"
+                INSERT INTO login_heatmap_hourly
+                    (hour, tenant_id, country_code, asn, successes, failures)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(hour)
+            .bind(bucket.tenant_id.to_string())
+            .bind(bucket.country_code.as_deref().unwrap_or_default())
+            .bind(bucket.asn.unwrap_or_default())
+            .bind(bucket.successes)
+            .bind(bucket.failures)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_err)?;
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO login_heatmap_hours (hour, rolled_up_at)
+            VALUES (?, ?)
+            ON DUPLICATE KEY UPDATE rolled_up_at = VALUES(rolled_up_at)
+            "#,
+        )
+        .bind(hour)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+        tx.commit().await.map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn latest_hour(&self) -> Result<Option<DateTime<Utc>>, AuthError> {
+        sqlx::query_scalar("SELECT MAX(hour) FROM login_heatmap_hours")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_err)
+    }
+
+    async fn cells(&self, query: &HeatmapQuery) -> Result<Vec<HeatmapCell>, AuthError> {
+        // Dimensions left out are folded into one stand-in value
+        let country = if query.by.by_country() {
+            "country_code"
+        } else {
+            "''"
+        };
+        let asn = if query.by.by_asn() { "asn" } else { "0" };
+        let sql = match query.tenant_id {
+            Some(_) => format!(
+                "SELECT hour, {} AS country_code, CAST({} AS SIGNED) AS asn, \
+                 CAST(SUM(successes) AS SIGNED) AS successes, \
+                 CAST(SUM(failures) AS SIGNED) AS failures \
+                 FROM login_heatmap_hourly \
+                 WHERE tenant_id = ? AND hour >= ? AND hour < ? \
+                 GROUP BY 1, 2, 3 ORDER BY 1, 2, 3",
+                country, asn
+            ),
+            None => format!(
+                "SELECT hour, {} AS country_code, CAST({} AS SIGNED) AS asn, \
+                 CAST(SUM(successes) AS SIGNED) AS successes, \
+                 CAST(SUM(failures) AS SIGNED) AS failures \
+                 FROM login_heatmap_hourly \
+                 WHERE hour >= ? AND hour < ? \
+                 /* tenant:unscoped heatmap of all tenants */ \
+                 GROUP BY 1, 2, 3 ORDER BY 1, 2, 3",
+                country, asn
+            ),
+        };
+        let statement = match query.tenant_id {
+            Some(tenant_id) => tenant_query(&TenantContext::new(tenant_id), &sql),
+            None => sqlx::query(&sql),
+        };
+        let rows = statement
+            .bind(query.since)
+            .bind(query.until)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(|row| {
+                let (country_code, asn) = place(&row)?;
+                Ok(HeatmapCell {
+                    hour: row.try_get("hour")?,
+                    country_code,
+                    asn,
+                    successes: count(&row, "successes")?,
+                    failures: count(&row, "failures")?,
+                })
+            })
+            .collect::<Result<_, sqlx::Error>>()
+            .map_err(db_err)
+    }
+}
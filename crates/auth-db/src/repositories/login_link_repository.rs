@@ -0,0 +1,102 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use crate::tenant_guard::{tenant_query, TenantContext};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::login_link::{LoginLink, LoginLinkStore};
+use chrono::{DateTime, Utc};
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+/// Columns of `login_links` used here
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "login_links",
+    columns: &[
+        Column::new("id", Text),
+        Column::new("tenant_id", Text),
+        Column::new("user_id", Text),
+        Column::new("issued_by", Text),
+        Column::new("redirect_uri", Text),
+        Column::new("reason", Text).nullable(),
+        Column::new("created_at", Timestamp),
+        Column::new("expires_at", Timestamp),
+        Column::new("redeemed_at", Timestamp).nullable(),
+    ],
+};
+
+pub struct LoginLinkRepository {
+    pool: MySqlPool,
+}
+
+impl LoginLinkRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl LoginLinkStore for LoginLinkRepository {
+    async fn create(&self, link: &LoginLink) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO login_links
+                (id, tenant_id, user_id, issued_by, redirect_uri, reason, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(link.id.to_string())
+        .bind(link.tenant_id.to_string())
+        .bind(link.user_id.to_string())
+        .bind(link.issued_by.to_string())
+        .bind(&link.redirect_uri)
+        .bind(&link.reason)
+        .bind(link.created_at)
+        .bind(link.expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn issued_since(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<u32, AuthError> {
+        let row = tenant_query(
+            &TenantContext::new(tenant_id),
+            r#"
+            SELECT COUNT(*) AS issued
+            FROM login_links
+            WHERE tenant_id = ? AND user_id = ? AND created_at >= ?
+            "#,
+        )
+        .bind(user_id.to_string())
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_err)?;
+        let issued: i64 = row.try_get("issued").map_err(db_err)?;
+        Ok(issued as u32)
+    }
+
+    async fn mark_redeemed(
+        &self,
+        tenant_id: Uuid,
+        link_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError> {
+        sqlx::query(
+            "UPDATE login_links SET redeemed_at = ? \
+             WHERE id = ? AND tenant_id = ? AND redeemed_at IS NULL",
+        )
+        .bind(at)
+        .bind(link_id.to_string())
+        .bind(tenant_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+}
@@ -1,17 +1,65 @@
 //! Database repository modules
 
+pub mod analytics_export_repository;
+pub mod client_repository;
+pub mod data_key_repository;
+pub mod device_certificate_repository;
+pub mod feature_kill_switch_repository;
+pub mod legal_hold_repository;
+pub mod login_event_repository;
+pub mod login_heatmap_repository;
+pub mod login_link_repository;
+pub mod nonce_repository;
+pub mod online_migration_repository;
 pub mod otp_repository;
+pub mod permission_change_repository;
+pub mod push_mfa_repository;
 pub mod refresh_token_repository;
+pub mod remember_me_repository;
 pub mod revoked_token_repository;
+pub mod service_account_repository;
 pub mod session_repository;
+pub mod signup_quarantine_repository;
+pub mod sms_quarantine_repository;
+pub mod sms_usage_repository;
+pub mod sso_session_repository;
 pub mod subscription_repository;
+pub mod tenant_metrics_repository;
+pub mod tenant_onboarding_repository;
+pub mod tenant_state_repository;
+pub mod token_generation_repository;
+pub mod upstream_session_repository;
 pub mod user_multi_channel;
 pub mod user_repository;
 
+pub use analytics_export_repository::AnalyticsExportRepository;
+pub use client_repository::ClientRepository;
+pub use data_key_repository::{pii_protector_from_config, DataKeyRepository};
+pub use device_certificate_repository::DeviceCertificateRepository;
+pub use feature_kill_switch_repository::FeatureKillSwitchRepository;
+pub use legal_hold_repository::LegalHoldRepository;
+pub use login_event_repository::LoginEventRepository;
+pub use login_heatmap_repository::LoginHeatmapRepository;
+pub use login_link_repository::LoginLinkRepository;
+pub use nonce_repository::NonceRepository;
+pub use online_migration_repository::OnlineMigrationRepository;
+pub use permission_change_repository::PermissionChangeRepository;
+pub use push_mfa_repository::PushMfaRepository;
 pub use refresh_token_repository::{RefreshTokenError, RefreshTokenRecord, RefreshTokenRepository};
+pub use remember_me_repository::RememberMeRepository;
 pub use revoked_token_repository::{
     RevokedTokenError, RevokedTokenRecord, RevokedTokenRepository, TokenType,
 };
+pub use service_account_repository::ServiceAccountRepository;
+pub use signup_quarantine_repository::SignupQuarantineRepository;
+pub use sms_quarantine_repository::SmsQuarantineRepository;
+pub use sms_usage_repository::SmsUsageRepository;
+pub use sso_session_repository::SsoSessionRepository;
+pub use tenant_metrics_repository::TenantMetricsRepository;
+pub use tenant_onboarding_repository::TenantOnboardingRepository;
+pub use tenant_state_repository::TenantStateRepository;
+pub use token_generation_repository::TokenGenerationRepository;
+pub use upstream_session_repository::UpstreamSessionRepository;
 pub mod authorization;
 pub mod webauthn_repository;
 pub use authorization::role_repository::*;
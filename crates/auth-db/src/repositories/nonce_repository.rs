@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use auth_core::services::nonce_store::{NonceBackend, Redemption};
+use sha2::{Digest, Sha256};
+use sqlx::{MySqlPool, Row};
+use std::time::Duration;
+
+/// Database fallback for the single-use token store. Keys are stored hashed
+/// since they embed the bearer token itself.
+pub struct NonceRepository {
+    pool: MySqlPool,
+}
+
+impl NonceRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    fn hash_key(key: &str) -> String {
+        hex::encode(Sha256::digest(key.as_bytes()))
+    }
+
+    /// Delete rows past their expiry, tombstones included
+    pub async fn purge_expired(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM single_use_tokens WHERE expires_at <= NOW(3)")
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl NonceBackend for NonceRepository {
+    async fn put_if_absent(&self, key: &str, payload: &str, ttl: Duration) -> anyhow::Result<bool> {
+        let token_key = Self::hash_key(key);
+
+        sqlx::query("DELETE FROM single_use_tokens WHERE token_key = ? AND expires_at <= NOW(3)")
+            .bind(&token_key)
+            .execute(&self.pool)
+            .await?;
+
+        let result = sqlx::query(
+            r#"
+            INSERT IGNORE INTO single_use_tokens (token_key, payload, expires_at)
+            VALUES (?, ?, NOW(3) + INTERVAL ? MICROSECOND)
+            "#,
+        )
+        .bind(&token_key)
+        .bind(payload)
+        .bind(ttl.as_micros() as u64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn redeem(&self, key: &str) -> anyhow::Result<Redemption> {
+        let token_key = Self::hash_key(key);
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT payload, expires_at > NOW(3) AS live
+            FROM single_use_tokens
+            WHERE token_key = ?
+            FOR UPDATE
+            "#,
+        )
+        .bind(&token_key)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(Redemption::Unknown);
+        };
+        if !row.try_get::<bool, _>("live")? {
+            return Ok(Redemption::Unknown);
+        }
+        let Some(payload) = row.try_get::<Option<String>, _>("payload")? else {
+            return Ok(Redemption::Replayed);
+        };
+
+        sqlx::query(
+            "UPDATE single_use_tokens SET payload = NULL, consumed_at = NOW(3) WHERE token_key = ?",
+        )
+        .bind(&token_key)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(Redemption::Redeemed(payload))
+    }
+}
@@ -0,0 +1,86 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use auth_config::MigrationPhase;
+use auth_core::error::AuthError;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{MySqlPool, Row};
+use tracing::warn;
+
+/// Columns of `online_migrations` used here
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "online_migrations",
+    columns: &[
+        Column::new("name", Text),
+        Column::new("phase", Text),
+        Column::new("changed_by", Text).nullable(),
+        Column::new("changed_at", Timestamp),
+    ],
+};
+
+/// A phase set from the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseChange {
+    pub name: String,
+    pub phase: MigrationPhase,
+    pub changed_by: Option<String>,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// Migrations span the whole database, not a tenant
+pub struct OnlineMigrationRepository {
+    pool: MySqlPool,
+}
+
+impl OnlineMigrationRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    /// Every recorded phase. A phase this build does not know, written by a
+    /// newer one, is skipped rather than guessed at.
+    pub async fn list(&self) -> Result<Vec<PhaseChange>, AuthError> {
+        let rows = sqlx::query("SELECT name, phase, changed_by, changed_at FROM online_migrations")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_err)?;
+
+        let mut changes = Vec::with_capacity(rows.len());
+        for row in rows {
+            let name: String = row.try_get("name").map_err(db_err)?;
+            let phase: String = row.try_get("phase").map_err(db_err)?;
+            let Some(phase) = MigrationPhase::parse(&phase) else {
+                warn!(migration = %name, phase = %phase, "Ignoring unknown migration phase");
+                continue;
+            };
+            changes.push(PhaseChange {
+                name,
+                phase,
+                changed_by: row.try_get("changed_by").map_err(db_err)?,
+                changed_at: row.try_get("changed_at").map_err(db_err)?,
+            });
+        }
+        Ok(changes)
+    }
+
+    pub async fn set(&self, change: &PhaseChange) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO online_migrations (name, phase, changed_by, changed_at)
+            VALUES (?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                phase = VALUES(phase),
+                changed_by = VALUES(changed_by),
+                changed_at = VALUES(changed_at)
+            "#,
+        )
+        .bind(&change.name)
+        .bind(change.phase.as_str())
+        .bind(&change.changed_by)
+        .bind(change.changed_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+}
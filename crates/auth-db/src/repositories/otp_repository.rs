@@ -1,11 +1,40 @@
 //! OTP Repository - Database layer for OTP sessions
 
+use crate::error::db_err;
+use crate::repositories::legal_hold_repository::not_held;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use async_trait::async_trait;
 use auth_core::error::AuthError;
-use auth_core::services::otp_service::{DeliveryMethod, OtpPurpose, OtpSession};
+use auth_core::services::otp_service::{
+    DeliveryMethod, OtpAttemptState, OtpAttemptStore, OtpError, OtpPurpose, OtpSession,
+};
 use chrono::{DateTime, Utc};
 use sqlx::{MySql, Pool, Row};
 use uuid::Uuid;
 
+/// Columns of `otp_sessions` used here
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "otp_sessions",
+    columns: &[
+        Column::new("id", Text),
+        Column::new("user_id", Text).nullable(),
+        Column::new("tenant_id", Text),
+        Column::new("identifier_type", Text),
+        Column::new("identifier", Text),
+        Column::new("otp_hash", Text),
+        Column::new("code_hash", Text).nullable(),
+        Column::new("delivery_method", Text),
+        Column::new("purpose", Text),
+        Column::new("sent_at", Timestamp),
+        Column::new("expires_at", Timestamp),
+        Column::new("verified_at", Timestamp).nullable(),
+        Column::new("attempts", Integer),
+        Column::new("max_attempts", Integer),
+        Column::new("created_at", Timestamp),
+        Column::new("purge_after", Timestamp),
+    ],
+};
+
 pub struct OtpRepository {
     pool: Pool<MySql>,
 }
@@ -20,14 +49,34 @@ impl OtpRepository {
         &self,
         session: &OtpSession,
         otp_hash: &str,
+    ) -> Result<(), AuthError> {
+        self.insert_session(session, otp_hash, None).await
+    }
+
+    /// Create a session that also accepts a typed code next to the link token
+    pub async fn create_session_with_code(
+        &self,
+        session: &OtpSession,
+        otp_hash: &str,
+        code_hash: &str,
+    ) -> Result<(), AuthError> {
+        self.insert_session(session, otp_hash, Some(code_hash))
+            .await
+    }
+
+    async fn insert_session(
+        &self,
+        session: &OtpSession,
+        otp_hash: &str,
+        code_hash: Option<&str>,
     ) -> Result<(), AuthError> {
         sqlx::query(
             r#"
             INSERT INTO otp_sessions (
                 id, user_id, tenant_id, identifier_type, identifier,
-                otp_hash, delivery_method, purpose, sent_at, expires_at,
+                otp_hash, code_hash, delivery_method, purpose, sent_at, expires_at,
                 attempts, max_attempts, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(session.id.to_string())
@@ -36,6 +85,7 @@ impl OtpRepository {
         .bind(&session.identifier_type)
         .bind(&session.identifier)
         .bind(otp_hash)
+        .bind(code_hash)
         .bind(match &session.delivery_method {
             DeliveryMethod::Email => "email",
             DeliveryMethod::Sms => "sms",
@@ -48,9 +98,7 @@ impl OtpRepository {
         .bind(Utc::now())
         .execute(&self.pool)
         .await
-        .map_err(|e| AuthError::DatabaseError {
-            message: e.to_string(),
-        })?;
+        .map_err(db_err)?;
 
         Ok(())
     }
@@ -72,9 +120,7 @@ impl OtpRepository {
         .bind(session_id.to_string())
         .fetch_optional(&self.pool)
         .await
-        .map_err(|e| AuthError::DatabaseError {
-            message: e.to_string(),
-        })?;
+        .map_err(db_err)?;
 
         if let Some(row) = row {
             let session = self.row_to_session(row)?;
@@ -83,9 +129,7 @@ impl OtpRepository {
                     .bind(session_id.to_string())
                     .fetch_one(&self.pool)
                     .await
-                    .map_err(|e| AuthError::DatabaseError {
-                        message: e.to_string(),
-                    })?;
+                    .map_err(db_err)?;
 
             Ok(Some((session, otp_hash)))
         } else {
@@ -93,39 +137,46 @@ impl OtpRepository {
         }
     }
 
-    /// Increment verification attempts
-    pub async fn increment_attempts(&self, session_id: Uuid) -> Result<u32, AuthError> {
-        sqlx::query("UPDATE otp_sessions SET attempts = attempts + 1 WHERE id = ?")
+    /// Every hash the session accepts: the primary token, then the typed
+    /// code if one was issued
+    pub async fn find_proof_hashes(&self, session_id: Uuid) -> Result<Vec<String>, AuthError> {
+        let row = sqlx::query("SELECT otp_hash, code_hash FROM otp_sessions WHERE id = ?")
             .bind(session_id.to_string())
-            .execute(&self.pool)
+            .fetch_optional(&self.pool)
             .await
-            .map_err(|e| AuthError::DatabaseError {
-                message: e.to_string(),
-            })?;
+            .map_err(db_err)?;
 
-        let attempts: i32 = sqlx::query_scalar("SELECT attempts FROM otp_sessions WHERE id = ?")
-            .bind(session_id.to_string())
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| AuthError::DatabaseError {
-                message: e.to_string(),
-            })?;
-
-        Ok(attempts as u32)
+        let Some(row) = row else {
+            return Ok(Vec::new());
+        };
+        let otp_hash: String = row.try_get("otp_hash").map_err(db_err)?;
+        let code_hash: Option<String> = row.try_get("code_hash").map_err(db_err)?;
+        Ok(std::iter::once(otp_hash).chain(code_hash).collect())
     }
 
-    /// Mark session as verified
-    pub async fn mark_verified(&self, session_id: Uuid) -> Result<(), AuthError> {
-        sqlx::query("UPDATE otp_sessions SET verified_at = ? WHERE id = ?")
-            .bind(Utc::now())
-            .bind(session_id.to_string())
-            .execute(&self.pool)
-            .await
-            .map_err(|e| AuthError::DatabaseError {
-                message: e.to_string(),
-            })?;
+    /// Explain why a conditional update matched no row
+    async fn classify_rejection(&self, session_id: Uuid) -> Result<OtpError, OtpError> {
+        let row = sqlx::query(
+            "SELECT attempts, max_attempts, expires_at, verified_at FROM otp_sessions WHERE id = ?",
+        )
+        .bind(session_id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(storage)?;
 
-        Ok(())
+        let Some(row) = row else {
+            return Ok(OtpError::NotFound);
+        };
+        let verified_at: Option<DateTime<Utc>> = row.try_get("verified_at").map_err(storage)?;
+        let expires_at: DateTime<Utc> = row.try_get("expires_at").map_err(storage)?;
+
+        Ok(if verified_at.is_some() {
+            OtpError::AlreadyVerified
+        } else if expires_at <= Utc::now() {
+            OtpError::Expired
+        } else {
+            OtpError::MaxAttemptsExceeded
+        })
     }
 
     /// Count recent OTP requests for rate limiting
@@ -146,33 +197,67 @@ impl OtpRepository {
         .bind(since)
         .fetch_one(&self.pool)
         .await
-        .map_err(|e| AuthError::DatabaseError {
-            message: e.to_string(),
-        })?;
+        .map_err(db_err)?;
 
         Ok(count)
     }
 
-    /// Cleanup expired sessions (run as background job)
-    pub async fn cleanup_expired(&self) -> Result<u64, AuthError> {
-        let result =
-            sqlx::query("DELETE FROM otp_sessions WHERE expires_at < ? OR verified_at IS NOT NULL")
-                .bind(Utc::now())
-                .execute(&self.pool)
-                .await
-                .map_err(|e| AuthError::DatabaseError {
-                    message: e.to_string(),
-                })?;
+    /// Earliest `purge_after` that has passed, if any session is spent.
+    /// Sessions under a legal hold are neither counted here nor purged.
+    pub async fn oldest_purgeable(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>, AuthError> {
+        sqlx::query_scalar(&format!(
+            "SELECT MIN(purge_after) FROM otp_sessions WHERE purge_after < ? AND {} \
+             /* tenant:unscoped expiry sweep */",
+            not_held("otp_sessions", Some("user_id"))
+        ))
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_err)
+    }
+
+    /// Delete up to `limit` spent sessions with `purge_after` in `[from, until)`
+    pub async fn purge_batch(
+        &self,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<u64, AuthError> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM otp_sessions WHERE purge_after >= ? AND purge_after < ? AND {} \
+             ORDER BY purge_after LIMIT ? /* tenant:unscoped expiry sweep */",
+            not_held("otp_sessions", Some("user_id"))
+        ))
+        .bind(from)
+        .bind(until)
+        .bind(limit)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
 
         Ok(result.rows_affected())
     }
 
+    /// Row count from table statistics; cheap, but only approximate
+    pub async fn estimated_rows(&self) -> Result<u64, AuthError> {
+        let rows: Option<u64> = sqlx::query_scalar(
+            "SELECT TABLE_ROWS FROM information_schema.TABLES \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = 'otp_sessions' \
+             /* tenant:unscoped table statistics */",
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?
+        .flatten();
+
+        Ok(rows.unwrap_or(0))
+    }
+
     fn row_to_session(&self, row: sqlx::mysql::MySqlRow) -> Result<OtpSession, AuthError> {
-        let user_id_str: Option<String> =
-            row.try_get("user_id")
-                .map_err(|e| AuthError::DatabaseError {
-                    message: e.to_string(),
-                })?;
+        let user_id_str: Option<String> = row.try_get("user_id").map_err(db_err)?;
 
         let user_id = user_id_str
             .map(|s: String| Uuid::parse_str(&s))
@@ -181,38 +266,24 @@ impl OtpRepository {
                 message: e.to_string(),
             })?;
 
-        let id_str: String = row.try_get("id").map_err(|e| AuthError::DatabaseError {
-            message: e.to_string(),
-        })?;
+        let id_str: String = row.try_get("id").map_err(db_err)?;
         let id = Uuid::parse_str(&id_str).map_err(|e| AuthError::DatabaseError {
             message: e.to_string(),
         })?;
 
-        let tenant_id_str: String =
-            row.try_get("tenant_id")
-                .map_err(|e| AuthError::DatabaseError {
-                    message: e.to_string(),
-                })?;
+        let tenant_id_str: String = row.try_get("tenant_id").map_err(db_err)?;
         let tenant_id = Uuid::parse_str(&tenant_id_str).map_err(|e| AuthError::DatabaseError {
             message: e.to_string(),
         })?;
 
-        let delivery_str: String =
-            row.try_get("delivery_method")
-                .map_err(|e| AuthError::DatabaseError {
-                    message: e.to_string(),
-                })?;
+        let delivery_str: String = row.try_get("delivery_method").map_err(db_err)?;
         let delivery_method = match delivery_str.as_str() {
             "email" => DeliveryMethod::Email,
             "sms" => DeliveryMethod::Sms,
             _ => DeliveryMethod::Email,
         };
 
-        let purpose_str: String = row
-            .try_get("purpose")
-            .map_err(|e| AuthError::DatabaseError {
-                message: e.to_string(),
-            })?;
+        let purpose_str: String = row.try_get("purpose").map_err(db_err)?;
         let purpose = match purpose_str.as_str() {
             "registration" => OtpPurpose::Registration,
             "login" => OtpPurpose::Login,
@@ -225,43 +296,73 @@ impl OtpRepository {
             id,
             user_id,
             tenant_id,
-            identifier_type: row.try_get("identifier_type").map_err(|e| {
-                AuthError::DatabaseError {
-                    message: e.to_string(),
-                }
-            })?,
-            identifier: row
-                .try_get("identifier")
-                .map_err(|e| AuthError::DatabaseError {
-                    message: e.to_string(),
-                })?,
+            identifier_type: row.try_get("identifier_type").map_err(db_err)?,
+            identifier: row.try_get("identifier").map_err(db_err)?,
             delivery_method,
             purpose,
-            attempts: row
-                .try_get::<i32, _>("attempts")
-                .map_err(|e| AuthError::DatabaseError {
-                    message: e.to_string(),
-                })? as u32,
-            max_attempts: row.try_get::<i32, _>("max_attempts").map_err(|e| {
-                AuthError::DatabaseError {
-                    message: e.to_string(),
-                }
-            })? as u32,
-            sent_at: row
-                .try_get("sent_at")
-                .map_err(|e| AuthError::DatabaseError {
-                    message: e.to_string(),
-                })?,
-            expires_at: row
-                .try_get("expires_at")
-                .map_err(|e| AuthError::DatabaseError {
-                    message: e.to_string(),
-                })?,
-            verified_at: row
-                .try_get("verified_at")
-                .map_err(|e| AuthError::DatabaseError {
-                    message: e.to_string(),
-                })?,
+            attempts: row.try_get::<i32, _>("attempts").map_err(db_err)? as u32,
+            max_attempts: row.try_get::<i32, _>("max_attempts").map_err(db_err)? as u32,
+            sent_at: row.try_get("sent_at").map_err(db_err)?,
+            expires_at: row.try_get("expires_at").map_err(db_err)?,
+            verified_at: row.try_get("verified_at").map_err(db_err)?,
         })
     }
 }
+
+fn storage(e: sqlx::Error) -> OtpError {
+    OtpError::StorageFailed(e.to_string())
+}
+
+#[async_trait]
+impl OtpAttemptStore for OtpRepository {
+    async fn reserve_attempt(&self, session_id: Uuid) -> Result<OtpAttemptState, OtpError> {
+        // LAST_INSERT_ID(expr) hands the post-update counter back on this
+        // connection, so no second read can observe another request's increment
+        let mut conn = self.pool.acquire().await.map_err(storage)?;
+        let updated = sqlx::query(
+            r#"
+            UPDATE otp_sessions
+            SET attempts = LAST_INSERT_ID(attempts + 1)
+            WHERE id = ? AND verified_at IS NULL AND expires_at > ? AND attempts < max_attempts
+            "#,
+        )
+        .bind(session_id.to_string())
+        .bind(Utc::now())
+        .execute(&mut *conn)
+        .await
+        .map_err(storage)?;
+
+        if updated.rows_affected() == 0 {
+            return Err(self.classify_rejection(session_id).await?);
+        }
+
+        let row = sqlx::query(
+            "SELECT LAST_INSERT_ID() AS attempts, max_attempts FROM otp_sessions WHERE id = ?",
+        )
+        .bind(session_id.to_string())
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(storage)?;
+
+        Ok(OtpAttemptState {
+            attempts: row.try_get::<u64, _>("attempts").map_err(storage)? as u32,
+            max_attempts: row.try_get::<i32, _>("max_attempts").map_err(storage)? as u32,
+        })
+    }
+
+    async fn mark_verified_once(&self, session_id: Uuid) -> Result<(), OtpError> {
+        let updated = sqlx::query(
+            "UPDATE otp_sessions SET verified_at = ? WHERE id = ? AND verified_at IS NULL",
+        )
+        .bind(Utc::now())
+        .bind(session_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(storage)?;
+
+        if updated.rows_affected() == 0 {
+            return Err(self.classify_rejection(session_id).await?);
+        }
+        Ok(())
+    }
+}
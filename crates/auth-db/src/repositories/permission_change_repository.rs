@@ -0,0 +1,148 @@
+use crate::error::db_err;
+use crate::repositories::legal_hold_repository::not_held;
+use crate::tenant_guard::{tenant_query, TenantContext};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::permission_sync::{ChangeEntry, PermissionChangeLog, PermissionSubject};
+use chrono::{DateTime, Utc};
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+const SELECT_HEAD: &str = "SELECT seq FROM permission_change_heads WHERE tenant_id = ?";
+
+pub struct PermissionChangeRepository {
+    pool: MySqlPool,
+}
+
+impl PermissionChangeRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    /// Delete entries recorded before `cutoff`. Replicas whose cursor falls
+    /// in the pruned range are told to resync. Held tenants keep theirs.
+    pub async fn prune_before(&self, cutoff: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(&format!(
+            r#"
+            DELETE FROM permission_changes
+            WHERE recorded_at < ? AND {}
+            /* tenant:unscoped retention sweep */
+            "#,
+            not_held("permission_changes", None)
+        ))
+        .bind(cutoff)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+fn subject_columns(subject: PermissionSubject) -> (&'static str, Uuid) {
+    match subject {
+        PermissionSubject::Role(role_id) => ("role", role_id),
+        PermissionSubject::UserRoles(user_id) => ("user_roles", user_id),
+    }
+}
+
+#[async_trait]
+impl PermissionChangeLog for PermissionChangeRepository {
+    async fn append(&self, tenant_id: Uuid, subject: PermissionSubject) -> Result<u64, AuthError> {
+        let tenant = TenantContext::new(tenant_id);
+        let (subject_type, subject_id) = subject_columns(subject);
+
+        // The head row stays locked until commit, so appends of a tenant
+        // commit in cursor order
+        let mut tx = self.pool.begin().await.map_err(db_err)?;
+        sqlx::query(
+            r#"
+            INSERT INTO permission_change_heads (tenant_id, seq)
+            VALUES (?, 1)
+            ON DUPLICATE KEY UPDATE seq = seq + 1
+            "#,
+        )
+        .bind(tenant_id.to_string())
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+        let seq: u64 = tenant_query(&tenant, SELECT_HEAD)
+            .fetch_one(&mut *tx)
+            .await
+            .and_then(|row| row.try_get("seq"))
+            .map_err(db_err)?;
+        sqlx::query(
+            r#"
+            INSERT INTO permission_changes (tenant_id, seq, subject_type, subject_id)
+            VALUES (?, ?, ?, ?)
+            "#,
+        )
+        .bind(tenant_id.to_string())
+        .bind(seq)
+        .bind(subject_type)
+        .bind(subject_id.to_string())
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+        tx.commit().await.map_err(db_err)?;
+        Ok(seq)
+    }
+
+    async fn read(
+        &self,
+        tenant_id: Uuid,
+        after: u64,
+        limit: usize,
+    ) -> Result<Vec<ChangeEntry>, AuthError> {
+        let rows = tenant_query(
+            &TenantContext::new(tenant_id),
+            r#"
+            SELECT seq, subject_type, subject_id, recorded_at
+            FROM permission_changes
+            WHERE tenant_id = ? AND seq > ?
+            ORDER BY seq
+            LIMIT ?
+            "#,
+        )
+        .bind(after)
+        .bind(limit as u64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        let mut entries = Vec::with_capacity(rows.len());
+        for row in rows {
+            let seq: u64 = row.try_get("seq").map_err(db_err)?;
+            let subject_type: String = row.try_get("subject_type").map_err(db_err)?;
+            let subject_id: String = row.try_get("subject_id").map_err(db_err)?;
+            let subject_id =
+                Uuid::parse_str(&subject_id).map_err(|e| AuthError::DatabaseError {
+                    message: format!("malformed subject of permission change {}: {}", seq, e),
+                })?;
+            let subject = match subject_type.as_str() {
+                "role" => PermissionSubject::Role(subject_id),
+                "user_roles" => PermissionSubject::UserRoles(subject_id),
+                other => {
+                    return Err(AuthError::DatabaseError {
+                        message: format!(
+                            "unknown subject type {} of permission change {}",
+                            other, seq
+                        ),
+                    })
+                }
+            };
+            entries.push(ChangeEntry {
+                cursor: seq,
+                subject,
+                recorded_at: row.try_get("recorded_at").map_err(db_err)?,
+            });
+        }
+        Ok(entries)
+    }
+
+    async fn head(&self, tenant_id: Uuid) -> Result<u64, AuthError> {
+        let row = tenant_query(&TenantContext::new(tenant_id), SELECT_HEAD)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_err)?;
+        row.map_or(Ok(0), |row| row.try_get("seq")).map_err(db_err)
+    }
+}
@@ -0,0 +1,212 @@
+use crate::error::db_err;
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::models::{PushChallenge, PushChallengeStatus, PushDevice, PushPlatform};
+use auth_core::services::push_mfa::PushMfaStore;
+use chrono::{DateTime, Utc};
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+pub struct PushMfaRepository {
+    pool: MySqlPool,
+}
+
+impl PushMfaRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    fn map_device(row: sqlx::mysql::MySqlRow) -> Result<PushDevice, sqlx::Error> {
+        let id: String = row.try_get("id")?;
+        let user_id: String = row.try_get("user_id")?;
+        let tenant_id: String = row.try_get("tenant_id")?;
+        let platform: String = row.try_get("platform")?;
+
+        Ok(PushDevice {
+            id: Uuid::parse_str(&id).unwrap_or_default(),
+            user_id: Uuid::parse_str(&user_id).unwrap_or_default(),
+            tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+            name: row.try_get("name")?,
+            platform: PushPlatform::parse(&platform).ok_or_else(|| {
+                sqlx::Error::Decode(format!("unknown push platform {}", platform).into())
+            })?,
+            push_token: row.try_get("push_token")?,
+            public_key_pem: row.try_get("public_key_pem")?,
+            created_at: row.try_get("created_at")?,
+            last_used_at: row.try_get("last_used_at")?,
+            revoked_at: row.try_get("revoked_at")?,
+        })
+    }
+
+    fn map_challenge(row: sqlx::mysql::MySqlRow) -> Result<PushChallenge, sqlx::Error> {
+        let id: String = row.try_get("id")?;
+        let user_id: String = row.try_get("user_id")?;
+        let tenant_id: String = row.try_get("tenant_id")?;
+        let status: String = row.try_get("status")?;
+        let responded_by: Option<String> = row.try_get("responded_by")?;
+
+        Ok(PushChallenge {
+            id: Uuid::parse_str(&id).unwrap_or_default(),
+            user_id: Uuid::parse_str(&user_id).unwrap_or_default(),
+            tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+            nonce: row.try_get("nonce")?,
+            status: PushChallengeStatus::parse(&status).ok_or_else(|| {
+                sqlx::Error::Decode(format!("unknown challenge status {}", status).into())
+            })?,
+            ip_address: row.try_get("ip_address")?,
+            user_agent: row.try_get("user_agent")?,
+            responded_by: responded_by.and_then(|d| Uuid::parse_str(&d).ok()),
+            expires_at: row.try_get("expires_at")?,
+            responded_at: row.try_get("responded_at")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+const DEVICE_COLUMNS: &str = "id, user_id, tenant_id, name, platform, push_token, \
+     public_key_pem, created_at, last_used_at, revoked_at";
+
+const CHALLENGE_COLUMNS: &str = "id, user_id, tenant_id, nonce, status, ip_address, \
+     user_agent, responded_by, expires_at, responded_at, created_at";
+
+#[async_trait]
+impl PushMfaStore for PushMfaRepository {
+    async fn insert_device(&self, device: &PushDevice) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO push_devices (id, user_id, tenant_id, name, platform, push_token, public_key_pem, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(device.id.to_string())
+        .bind(device.user_id.to_string())
+        .bind(device.tenant_id.to_string())
+        .bind(&device.name)
+        .bind(device.platform.as_str())
+        .bind(&device.push_token)
+        .bind(&device.public_key_pem)
+        .bind(device.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn get_device(&self, id: Uuid) -> Result<Option<PushDevice>, AuthError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM push_devices WHERE id = ?",
+            DEVICE_COLUMNS
+        ))
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        row.map(Self::map_device).transpose().map_err(db_err)
+    }
+
+    async fn list_devices(
+        &self,
+        user_id: Uuid,
+        tenant_id: Uuid,
+    ) -> Result<Vec<PushDevice>, AuthError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM push_devices \
+             WHERE user_id = ? AND tenant_id = ? AND revoked_at IS NULL \
+             ORDER BY created_at",
+            DEVICE_COLUMNS
+        ))
+        .bind(user_id.to_string())
+        .bind(tenant_id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(Self::map_device)
+            .collect::<Result<_, _>>()
+            .map_err(db_err)
+    }
+
+    async fn revoke_device(&self, id: Uuid, at: DateTime<Utc>) -> Result<(), AuthError> {
+        sqlx::query("UPDATE push_devices SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL")
+            .bind(at)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn touch_device(&self, id: Uuid, at: DateTime<Utc>) -> Result<(), AuthError> {
+        sqlx::query("UPDATE push_devices SET last_used_at = ? WHERE id = ?")
+            .bind(at)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn insert_challenge(&self, challenge: &PushChallenge) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO push_challenges (id, user_id, tenant_id, nonce, status, ip_address, user_agent, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(challenge.id.to_string())
+        .bind(challenge.user_id.to_string())
+        .bind(challenge.tenant_id.to_string())
+        .bind(&challenge.nonce)
+        .bind(challenge.status.as_str())
+        .bind(&challenge.ip_address)
+        .bind(&challenge.user_agent)
+        .bind(challenge.expires_at)
+        .bind(challenge.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn get_challenge(&self, id: Uuid) -> Result<Option<PushChallenge>, AuthError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM push_challenges WHERE id = ?",
+            CHALLENGE_COLUMNS
+        ))
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        row.map(Self::map_challenge).transpose().map_err(db_err)
+    }
+
+    async fn resolve_challenge(
+        &self,
+        id: Uuid,
+        status: PushChallengeStatus,
+        device_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<bool, AuthError> {
+        // Conditional on still pending so two devices cannot both answer
+        let resolved = sqlx::query(
+            r#"
+            UPDATE push_challenges SET status = ?, responded_by = ?, responded_at = ?
+            WHERE id = ? AND status = 'pending' AND expires_at > ?
+            "#,
+        )
+        .bind(status.as_str())
+        .bind(device_id.to_string())
+        .bind(at)
+        .bind(id.to_string())
+        .bind(at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?
+        .rows_affected();
+
+        Ok(resolved == 1)
+    }
+}
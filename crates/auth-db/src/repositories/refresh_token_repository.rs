@@ -1,6 +1,8 @@
 //! Refresh token repository for database operations
 //! Part of Task 3.3: Implement Refresh Token System with Family Tracking
 
+use crate::repositories::legal_hold_repository::not_held;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
 use chrono::{DateTime, Utc};
 use sqlx::{MySql, Pool, Row};
 use thiserror::Error;
@@ -34,8 +36,35 @@ pub struct RefreshTokenRecord {
     pub revoked_at: Option<DateTime<Utc>>,
     pub revoked_reason: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub session_id: Option<Uuid>,
+    pub access_token_jti: Option<Uuid>,
+    pub family_expires_at: Option<DateTime<Utc>>,
+    pub client_id: Option<String>,
 }
 
+/// Columns of `refresh_tokens` used here
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "refresh_tokens",
+    columns: &[
+        Column::new("id", Text),
+        Column::new("user_id", Text),
+        Column::new("tenant_id", Text),
+        Column::new("token_family", Text),
+        Column::new("token_hash", Text),
+        Column::new("device_fingerprint", Text).nullable(),
+        Column::new("user_agent", Text).nullable(),
+        Column::new("ip_address", Text).nullable(),
+        Column::new("expires_at", Timestamp),
+        Column::new("revoked_at", Timestamp).nullable(),
+        Column::new("revoked_reason", Text).nullable(),
+        Column::new("created_at", Timestamp),
+        Column::new("session_id", Text).nullable(),
+        Column::new("access_token_jti", Text).nullable(),
+        Column::new("family_expires_at", Timestamp).nullable(),
+        Column::new("client_id", Text).nullable(),
+    ],
+};
+
 pub struct RefreshTokenRepository {
     pool: Pool<MySql>,
 }
@@ -95,6 +124,10 @@ impl RefreshTokenRepository {
             revoked_at: None,
             revoked_reason: None,
             created_at: now,
+            session_id: None,
+            access_token_jti: None,
+            family_expires_at: None,
+            client_id: None,
         })
     }
 
@@ -107,7 +140,8 @@ impl RefreshTokenRepository {
             r#"
             SELECT id, user_id, tenant_id, token_family, token_hash,
                    device_fingerprint, user_agent, ip_address,
-                   expires_at, revoked_at, revoked_reason, created_at
+                   expires_at, revoked_at, revoked_reason, created_at,
+                   session_id, access_token_jti, family_expires_at, client_id
             FROM refresh_tokens
             WHERE token_hash = ?
             "#,
@@ -129,7 +163,8 @@ impl RefreshTokenRepository {
             r#"
             SELECT id, user_id, tenant_id, token_family, token_hash,
                    device_fingerprint, user_agent, ip_address,
-                   expires_at, revoked_at, revoked_reason, created_at
+                   expires_at, revoked_at, revoked_reason, created_at,
+                   session_id, access_token_jti, family_expires_at, client_id
             FROM refresh_tokens
             WHERE token_family = ?
             ORDER BY created_at DESC
@@ -144,6 +179,30 @@ impl RefreshTokenRepository {
             .collect()
     }
 
+    /// Find all tokens issued under a session, including rotated ones
+    pub async fn find_by_session_id(
+        &self,
+        session_id: Uuid,
+    ) -> Result<Vec<RefreshTokenRecord>, RefreshTokenError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, user_id, tenant_id, token_family, token_hash,
+                   device_fingerprint, user_agent, ip_address,
+                   expires_at, revoked_at, revoked_reason, created_at,
+                   session_id, access_token_jti, family_expires_at, client_id
+            FROM refresh_tokens
+            WHERE session_id = ?
+            "#,
+        )
+        .bind(session_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| self.row_to_record(row))
+            .collect()
+    }
+
     /// Find all active tokens for a user
     pub async fn find_by_user(
         &self,
@@ -155,7 +214,8 @@ impl RefreshTokenRepository {
             r#"
             SELECT id, user_id, tenant_id, token_family, token_hash,
                    device_fingerprint, user_agent, ip_address,
-                   expires_at, revoked_at, revoked_reason, created_at
+                   expires_at, revoked_at, revoked_reason, created_at,
+                   session_id, access_token_jti, family_expires_at, client_id
             FROM refresh_tokens
             WHERE user_id = ? AND tenant_id = ?
               AND revoked_at IS NULL
@@ -226,6 +286,77 @@ impl RefreshTokenRepository {
         Ok(result.rows_affected())
     }
 
+    /// Revoke every live token of the tenant (forced reauthentication)
+    pub async fn revoke_tenant(
+        &self,
+        tenant_id: Uuid,
+        reason: String,
+    ) -> Result<u64, RefreshTokenError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = ?, revoked_reason = ?
+            WHERE tenant_id = ? AND revoked_at IS NULL
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(reason)
+        .bind(tenant_id.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Revoke every live token of one user (account takeover response)
+    pub async fn revoke_user(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+        reason: String,
+    ) -> Result<u64, RefreshTokenError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = ?, revoked_reason = ?
+            WHERE tenant_id = ? AND user_id = ? AND revoked_at IS NULL
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(reason)
+        .bind(tenant_id.to_string())
+        .bind(user_id.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Revoke every live token issued to one OAuth client (leaked client
+    /// secret)
+    pub async fn revoke_client(
+        &self,
+        tenant_id: Uuid,
+        client_id: &str,
+        reason: String,
+    ) -> Result<u64, RefreshTokenError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = ?, revoked_reason = ?
+            WHERE tenant_id = ? AND client_id = ? AND revoked_at IS NULL
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(reason)
+        .bind(tenant_id.to_string())
+        .bind(client_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     /// Check if a token is valid (exists, not expired, not revoked)
     pub async fn is_token_valid(&self, token_hash: &str) -> Result<bool, RefreshTokenError> {
         let now = Utc::now();
@@ -248,16 +379,19 @@ impl RefreshTokenRepository {
         Ok(count > 0)
     }
 
-    /// Clean up expired tokens (for background job)
+    /// Clean up expired tokens (for background job), except those under a
+    /// legal hold
     pub async fn cleanup_expired(&self) -> Result<u64, RefreshTokenError> {
         let now = Utc::now();
 
-        let result = sqlx::query(
+        let result = sqlx::query(&format!(
             r#"
             DELETE FROM refresh_tokens
-            WHERE expires_at < ?
+            WHERE expires_at < ? AND {}
+            /* tenant:unscoped expiry sweep */
             "#,
-        )
+            not_held("refresh_tokens", Some("user_id"))
+        ))
         .bind(now)
         .execute(&self.pool)
         .await?;
@@ -300,6 +434,8 @@ impl RefreshTokenRepository {
         let user_id_str: String = row.try_get("user_id")?;
         let tenant_id_str: String = row.try_get("tenant_id")?;
         let family_str: String = row.try_get("token_family")?;
+        let session_id: Option<String> = row.try_get("session_id")?;
+        let access_token_jti: Option<String> = row.try_get("access_token_jti")?;
 
         Ok(RefreshTokenRecord {
             id: Uuid::parse_str(&id_str).map_err(|_| {
@@ -326,12 +462,16 @@ impl RefreshTokenRepository {
             revoked_at: row.try_get("revoked_at")?,
             revoked_reason: row.try_get("revoked_reason")?,
             created_at: row.try_get("created_at")?,
+            session_id: session_id.and_then(|s| Uuid::parse_str(&s).ok()),
+            access_token_jti: access_token_jti.and_then(|s| Uuid::parse_str(&s).ok()),
+            family_expires_at: row.try_get("family_expires_at")?,
+            client_id: row.try_get("client_id")?,
         })
     }
 }
 
 // Imports for trait implementation
-use auth_core::error::AuthError;
+use auth_core::error::{AuthError, ErrorCategory, ErrorCode, TokenErrorKind};
 use auth_core::models::RefreshToken;
 use auth_core::services::token_service::RefreshTokenStore;
 
@@ -347,8 +487,9 @@ impl RefreshTokenRepository {
             INSERT INTO refresh_tokens (
                 id, user_id, tenant_id, token_family, token_hash,
                 device_fingerprint, user_agent, ip_address, 
-                expires_at, revoked_at, revoked_reason, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                expires_at, revoked_at, revoked_reason, created_at,
+                session_id, access_token_jti, family_expires_at, client_id
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(record.id.to_string())
@@ -363,6 +504,10 @@ impl RefreshTokenRepository {
         .bind(record.revoked_at)
         .bind(&record.revoked_reason)
         .bind(record.created_at)
+        .bind(record.session_id.map(|id| id.to_string()))
+        .bind(record.access_token_jti.map(|id| id.to_string()))
+        .bind(record.family_expires_at)
+        .bind(&record.client_id)
         .execute(&self.pool)
         .await?;
 
@@ -370,6 +515,38 @@ impl RefreshTokenRepository {
     }
 }
 
+impl ErrorCode for RefreshTokenError {
+    fn code(&self) -> &'static str {
+        match self {
+            RefreshTokenError::DatabaseError(_) => "REFRESH_001",
+            RefreshTokenError::TokenNotFound => "REFRESH_002",
+            RefreshTokenError::TokenExpired => "REFRESH_003",
+            RefreshTokenError::TokenRevoked => "REFRESH_004",
+            RefreshTokenError::FamilyBreachDetected => "REFRESH_005",
+        }
+    }
+
+    fn category(&self) -> ErrorCategory {
+        match self {
+            RefreshTokenError::DatabaseError(e) => e.category(),
+            _ => ErrorCategory::Authentication,
+        }
+    }
+}
+
+impl From<RefreshTokenError> for AuthError {
+    fn from(err: RefreshTokenError) -> Self {
+        let kind = match err {
+            RefreshTokenError::DatabaseError(_) => return AuthError::classified(err),
+            RefreshTokenError::TokenNotFound => TokenErrorKind::Invalid,
+            RefreshTokenError::TokenExpired => TokenErrorKind::Expired,
+            RefreshTokenError::TokenRevoked => TokenErrorKind::Revoked,
+            RefreshTokenError::FamilyBreachDetected => TokenErrorKind::Replayed,
+        };
+        AuthError::TokenError { kind }
+    }
+}
+
 #[async_trait::async_trait]
 impl RefreshTokenStore for RefreshTokenRepository {
     async fn create(&self, token: RefreshToken) -> Result<(), AuthError> {
@@ -386,52 +563,78 @@ impl RefreshTokenStore for RefreshTokenRepository {
             revoked_at: token.revoked_at,
             revoked_reason: token.revoked_reason,
             created_at: token.created_at,
+            session_id: token.session_id,
+            access_token_jti: token.access_token_jti,
+            family_expires_at: token.family_expires_at,
+            client_id: token.client_id,
         };
 
-        self.save(record)
-            .await
-            .map_err(|e| AuthError::DatabaseError {
-                message: e.to_string(),
-            })
+        self.save(record).await.map_err(AuthError::from)
     }
 
     async fn find_by_hash(&self, hash: &str) -> Result<Option<RefreshToken>, AuthError> {
         match self.find_by_token_hash(hash).await {
-            Ok(record) => Ok(Some(RefreshToken {
-                id: record.id,
-                user_id: record.user_id,
-                tenant_id: record.tenant_id,
-                token_family: record.token_family,
-                token_hash: record.token_hash,
-                device_fingerprint: record.device_fingerprint,
-                user_agent: record.user_agent,
-                ip_address: record.ip_address,
-                expires_at: record.expires_at,
-                revoked_at: record.revoked_at,
-                revoked_reason: record.revoked_reason,
-                created_at: record.created_at,
-            })),
+            Ok(record) => Ok(Some(to_model(record))),
             Err(RefreshTokenError::TokenNotFound) => Ok(None),
-            Err(e) => Err(AuthError::DatabaseError {
-                message: e.to_string(),
-            }),
+            Err(e) => Err(e.into()),
         }
     }
 
     async fn revoke(&self, token_id: Uuid) -> Result<(), AuthError> {
         self.revoke_token(token_id, Some("Revoked by user/system".to_string()))
             .await
-            .map_err(|e| AuthError::DatabaseError {
-                message: e.to_string(),
-            })
+            .map_err(AuthError::from)
     }
 
-    async fn revoke_family(&self, family_id: Uuid) -> Result<(), AuthError> {
+    async fn revoke_family(&self, family_id: Uuid) -> Result<u64, AuthError> {
         self.revoke_family(family_id, "Family revocation".to_string())
             .await
-            .map(|_| ())
-            .map_err(|e| AuthError::DatabaseError {
-                message: e.to_string(),
-            })
+            .map_err(AuthError::from)
+    }
+
+    async fn find_by_session(&self, session_id: Uuid) -> Result<Vec<RefreshToken>, AuthError> {
+        self.find_by_session_id(session_id)
+            .await
+            .map(|records| records.into_iter().map(to_model).collect())
+            .map_err(AuthError::from)
+    }
+
+    async fn revoke_tenant(&self, tenant_id: Uuid) -> Result<u64, AuthError> {
+        self.revoke_tenant(tenant_id, "Tenant forced reauthentication".to_string())
+            .await
+            .map_err(AuthError::from)
+    }
+
+    async fn revoke_user(&self, tenant_id: Uuid, user_id: Uuid) -> Result<u64, AuthError> {
+        self.revoke_user(tenant_id, user_id, "Account takeover response".to_string())
+            .await
+            .map_err(AuthError::from)
+    }
+
+    async fn revoke_client(&self, tenant_id: Uuid, client_id: &str) -> Result<u64, AuthError> {
+        self.revoke_client(tenant_id, client_id, "Client revocation".to_string())
+            .await
+            .map_err(AuthError::from)
+    }
+}
+
+fn to_model(record: RefreshTokenRecord) -> RefreshToken {
+    RefreshToken {
+        id: record.id,
+        user_id: record.user_id,
+        tenant_id: record.tenant_id,
+        token_family: record.token_family,
+        token_hash: record.token_hash,
+        device_fingerprint: record.device_fingerprint,
+        user_agent: record.user_agent,
+        ip_address: record.ip_address,
+        expires_at: record.expires_at,
+        revoked_at: record.revoked_at,
+        revoked_reason: record.revoked_reason,
+        created_at: record.created_at,
+        session_id: record.session_id,
+        access_token_jti: record.access_token_jti,
+        family_expires_at: record.family_expires_at,
+        client_id: record.client_id,
     }
 }
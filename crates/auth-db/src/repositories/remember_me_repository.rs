@@ -0,0 +1,246 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use crate::tenant_guard::{tenant_query, TenantContext};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::remember_me::{RememberMeStore, RememberMeToken, RememberedDevice};
+use chrono::{DateTime, Utc};
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+/// Columns of `remembered_devices` used here
+pub const DEVICES_SCHEMA: TableSchema = TableSchema {
+    table: "remembered_devices",
+    columns: &[
+        Column::new("id", Text),
+        Column::new("tenant_id", Text),
+        Column::new("user_id", Text),
+        Column::new("fingerprint_hash", Text),
+        Column::new("name", Text).nullable(),
+        Column::new("created_at", Timestamp),
+        Column::new("last_used_at", Timestamp),
+        Column::new("revoked_at", Timestamp).nullable(),
+    ],
+};
+
+/// Columns of `remember_me_tokens` used here
+pub const TOKENS_SCHEMA: TableSchema = TableSchema {
+    table: "remember_me_tokens",
+    columns: &[
+        Column::new("id", Text),
+        Column::new("device_id", Text),
+        Column::new("tenant_id", Text),
+        Column::new("user_id", Text),
+        Column::new("token_hash", Text),
+        Column::new("created_at", Timestamp),
+        Column::new("expires_at", Timestamp),
+        Column::new("used_at", Timestamp).nullable(),
+    ],
+};
+
+pub struct RememberMeRepository {
+    pool: MySqlPool,
+}
+
+impl RememberMeRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn uuid(row: &sqlx::mysql::MySqlRow, column: &str) -> Result<Uuid, sqlx::Error> {
+    let value: String = row.try_get(column)?;
+    Ok(Uuid::parse_str(&value).unwrap_or_default())
+}
+
+fn device_from_row(row: &sqlx::mysql::MySqlRow) -> Result<RememberedDevice, sqlx::Error> {
+    Ok(RememberedDevice {
+        id: uuid(row, "id")?,
+        tenant_id: uuid(row, "tenant_id")?,
+        user_id: uuid(row, "user_id")?,
+        fingerprint_hash: row.try_get("fingerprint_hash")?,
+        name: row.try_get("name")?,
+        created_at: row.try_get("created_at")?,
+        last_used_at: row.try_get("last_used_at")?,
+        revoked_at: row.try_get("revoked_at")?,
+    })
+}
+
+fn token_from_row(row: &sqlx::mysql::MySqlRow) -> Result<RememberMeToken, sqlx::Error> {
+    Ok(RememberMeToken {
+        id: uuid(row, "id")?,
+        device_id: uuid(row, "device_id")?,
+        tenant_id: uuid(row, "tenant_id")?,
+        user_id: uuid(row, "user_id")?,
+        token_hash: row.try_get("token_hash")?,
+        created_at: row.try_get("created_at")?,
+        expires_at: row.try_get("expires_at")?,
+        used_at: row.try_get("used_at")?,
+    })
+}
+
+#[async_trait]
+impl RememberMeStore for RememberMeRepository {
+    async fn insert_device(&self, device: &RememberedDevice) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO remembered_devices
+                (id, tenant_id, user_id, fingerprint_hash, name, created_at, last_used_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(device.id.to_string())
+        .bind(device.tenant_id.to_string())
+        .bind(device.user_id.to_string())
+        .bind(&device.fingerprint_hash)
+        .bind(&device.name)
+        .bind(device.created_at)
+        .bind(device.last_used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn get_device(
+        &self,
+        tenant_id: Uuid,
+        device_id: Uuid,
+    ) -> Result<Option<RememberedDevice>, AuthError> {
+        let row = tenant_query(
+            &TenantContext::new(tenant_id),
+            r#"
+            SELECT id, tenant_id, user_id, fingerprint_hash, name,
+                   created_at, last_used_at, revoked_at
+            FROM remembered_devices
+            WHERE tenant_id = ? AND id = ?
+            "#,
+        )
+        .bind(device_id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+        row.as_ref()
+            .map(device_from_row)
+            .transpose()
+            .map_err(db_err)
+    }
+
+    async fn list_devices(
+        &self,
+        tenant_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<RememberedDevice>, AuthError> {
+        let rows = tenant_query(
+            &TenantContext::new(tenant_id),
+            r#"
+            SELECT id, tenant_id, user_id, fingerprint_hash, name,
+                   created_at, last_used_at, revoked_at
+            FROM remembered_devices
+            WHERE tenant_id = ? AND user_id = ? AND revoked_at IS NULL
+            ORDER BY last_used_at DESC
+            "#,
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+        rows.iter()
+            .map(device_from_row)
+            .collect::<Result<_, _>>()
+            .map_err(db_err)
+    }
+
+    async fn touch_device(
+        &self,
+        tenant_id: Uuid,
+        device_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError> {
+        sqlx::query(
+            "UPDATE remembered_devices SET last_used_at = ? WHERE id = ? AND tenant_id = ?",
+        )
+        .bind(at)
+        .bind(device_id.to_string())
+        .bind(tenant_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn revoke_device(
+        &self,
+        tenant_id: Uuid,
+        device_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError> {
+        sqlx::query(
+            "UPDATE remembered_devices SET revoked_at = ? \
+             WHERE id = ? AND tenant_id = ? AND revoked_at IS NULL",
+        )
+        .bind(at)
+        .bind(device_id.to_string())
+        .bind(tenant_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn insert_token(&self, token: &RememberMeToken) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO remember_me_tokens
+                (id, device_id, tenant_id, user_id, token_hash, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(token.id.to_string())
+        .bind(token.device_id.to_string())
+        .bind(token.tenant_id.to_string())
+        .bind(token.user_id.to_string())
+        .bind(&token.token_hash)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn find_token(&self, token_hash: &str) -> Result<Option<RememberMeToken>, AuthError> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, device_id, tenant_id, user_id, token_hash,
+                   created_at, expires_at, used_at
+            FROM remember_me_tokens
+            WHERE token_hash = ?
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+        row.as_ref().map(token_from_row).transpose().map_err(db_err)
+    }
+
+    async fn mark_token_used(
+        &self,
+        tenant_id: Uuid,
+        token_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<bool, AuthError> {
+        let result = sqlx::query(
+            "UPDATE remember_me_tokens SET used_at = ? \
+             WHERE id = ? AND tenant_id = ? AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(token_id.to_string())
+        .bind(tenant_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(result.rows_affected() == 1)
+    }
+}
@@ -1,8 +1,10 @@
 //! Revoked token repository for access token blacklist
 //! Part of Task 3.1: Implement JWT Token Engine with RS256
 
+use crate::repositories::legal_hold_repository::not_held;
 use chrono::{DateTime, Utc};
 use sqlx::{MySql, Pool, Row};
+use std::collections::HashSet;
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -131,6 +133,33 @@ impl RevokedTokenRepository {
         Ok(count > 0)
     }
 
+    /// The revoked, unexpired tokens among `token_jtis`, in one query
+    pub async fn find_revoked(
+        &self,
+        token_jtis: &[Uuid],
+    ) -> Result<HashSet<Uuid>, RevokedTokenError> {
+        if token_jtis.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let placeholders = vec!["?"; token_jtis.len()].join(", ");
+        let sql = format!(
+            "SELECT token_jti FROM revoked_tokens WHERE expires_at > ? AND token_jti IN ({})",
+            placeholders
+        );
+
+        let mut query = sqlx::query(&sql).bind(Utc::now());
+        for jti in token_jtis {
+            query = query.bind(jti.to_string());
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| row.try_get::<String, _>("token_jti").ok())
+            .filter_map(|jti| Uuid::parse_str(&jti).ok())
+            .collect())
+    }
+
     /// Revoke all tokens for a user (emergency revocation)
     pub async fn revoke_all_user_tokens(
         &self,
@@ -170,16 +199,19 @@ impl RevokedTokenRepository {
         Ok(1)
     }
 
-    /// Clean up expired revocation records (for background job)
+    /// Clean up expired revocation records (for background job), except
+    /// those under a legal hold
     pub async fn cleanup_expired(&self) -> Result<u64, RevokedTokenError> {
         let now = Utc::now();
 
-        let result = sqlx::query(
+        let result = sqlx::query(&format!(
             r#"
             DELETE FROM revoked_tokens
-            WHERE expires_at < ?
+            WHERE expires_at < ? AND {}
+            /* tenant:unscoped expiry sweep */
             "#,
-        )
+            not_held("revoked_tokens", Some("user_id"))
+        ))
         .bind(now)
         .execute(&self.pool)
         .await?;
@@ -220,6 +252,7 @@ impl RevokedTokenRepository {
             SELECT COUNT(*) as count
             FROM revoked_tokens
             WHERE expires_at > ?
+            /* tenant:unscoped count across tenants */
             "#,
         )
         .bind(now)
@@ -302,6 +335,14 @@ impl RevokedTokenStore for RevokedTokenRepository {
                 message: e.to_string(),
             })
     }
+
+    async fn revoked_among(&self, jtis: &[Uuid]) -> Result<HashSet<Uuid>, AuthError> {
+        self.find_revoked(jtis)
+            .await
+            .map_err(|e| AuthError::DatabaseError {
+                message: e.to_string(),
+            })
+    }
 }
 
 #[cfg(test)]
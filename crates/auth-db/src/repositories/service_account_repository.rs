@@ -0,0 +1,233 @@
+use crate::error::db_err;
+use crate::tenant_guard::{tenant_query, TenantContext};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::models::{KeyAlgorithm, ServiceAccount, ServiceAccountKey, ServiceAccountStatus};
+use auth_core::services::service_account::ServiceAccountStore;
+use chrono::{DateTime, Utc};
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+pub struct ServiceAccountRepository {
+    pool: MySqlPool,
+}
+
+impl ServiceAccountRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    pub(crate) fn map_account(row: sqlx::mysql::MySqlRow) -> Result<ServiceAccount, sqlx::Error> {
+        let id: String = row.try_get("id")?;
+        let tenant_id: String = row.try_get("tenant_id")?;
+        let scopes: sqlx::types::Json<Vec<String>> = row.try_get("scopes")?;
+        let status: String = row.try_get("status")?;
+
+        Ok(ServiceAccount {
+            id: Uuid::parse_str(&id).unwrap_or_default(),
+            tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+            name: row.try_get("name")?,
+            description: row.try_get("description")?,
+            scopes: scopes.0,
+            status: ServiceAccountStatus::parse(&status),
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+
+    fn map_key(row: sqlx::mysql::MySqlRow) -> Result<ServiceAccountKey, sqlx::Error> {
+        let id: String = row.try_get("id")?;
+        let account_id: String = row.try_get("service_account_id")?;
+        let algorithm: String = row.try_get("algorithm")?;
+        let algorithm = KeyAlgorithm::parse(&algorithm).ok_or_else(|| {
+            sqlx::Error::Decode(format!("unknown key algorithm '{}'", algorithm).into())
+        })?;
+
+        Ok(ServiceAccountKey {
+            id: Uuid::parse_str(&id).unwrap_or_default(),
+            service_account_id: Uuid::parse_str(&account_id).unwrap_or_default(),
+            algorithm,
+            public_key_pem: row.try_get("public_key_pem")?,
+            expires_at: row.try_get("expires_at")?,
+            created_at: row.try_get("created_at")?,
+            last_used_at: row.try_get("last_used_at")?,
+            revoked_at: row.try_get("revoked_at")?,
+            reminder_sent_at: row.try_get("reminder_sent_at")?,
+        })
+    }
+}
+
+pub(crate) const ACCOUNT_COLUMNS: &str =
+    "id, tenant_id, name, description, scopes, status, created_at, updated_at";
+
+const KEY_COLUMNS: &str = "id, service_account_id, algorithm, public_key_pem, expires_at, \
+     created_at, last_used_at, revoked_at, reminder_sent_at";
+
+#[async_trait]
+impl ServiceAccountStore for ServiceAccountRepository {
+    async fn create_account(&self, account: &ServiceAccount) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO service_accounts (id, tenant_id, name, description, scopes, status, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(account.id.to_string())
+        .bind(account.tenant_id.to_string())
+        .bind(&account.name)
+        .bind(&account.description)
+        .bind(sqlx::types::Json(&account.scopes))
+        .bind(account.status.as_str())
+        .bind(account.created_at)
+        .bind(account.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn get_account(&self, id: Uuid) -> Result<Option<ServiceAccount>, AuthError> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, tenant_id, name, description, scopes, status, created_at, updated_at
+            FROM service_accounts
+            WHERE id = ?
+            "#,
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        row.map(Self::map_account).transpose().map_err(db_err)
+    }
+
+    async fn list_accounts(&self, tenant_id: Uuid) -> Result<Vec<ServiceAccount>, AuthError> {
+        let rows = tenant_query(
+            &TenantContext::new(tenant_id),
+            r#"
+            SELECT id, tenant_id, name, description, scopes, status, created_at, updated_at
+            FROM service_accounts
+            WHERE tenant_id = ?
+            ORDER BY created_at
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(|row| Self::map_account(row).map_err(db_err))
+            .collect()
+    }
+
+    async fn set_status(&self, id: Uuid, status: ServiceAccountStatus) -> Result<(), AuthError> {
+        sqlx::query("UPDATE service_accounts SET status = ? WHERE id = ?")
+            .bind(status.as_str())
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn add_key(&self, key: &ServiceAccountKey) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO service_account_keys (id, service_account_id, algorithm, public_key_pem, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(key.id.to_string())
+        .bind(key.service_account_id.to_string())
+        .bind(key.algorithm.as_str())
+        .bind(&key.public_key_pem)
+        .bind(key.expires_at)
+        .bind(key.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn get_key(&self, key_id: Uuid) -> Result<Option<ServiceAccountKey>, AuthError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM service_account_keys WHERE id = ?",
+            KEY_COLUMNS
+        ))
+        .bind(key_id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        row.map(Self::map_key).transpose().map_err(db_err)
+    }
+
+    async fn list_keys(&self, account_id: Uuid) -> Result<Vec<ServiceAccountKey>, AuthError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM service_account_keys WHERE service_account_id = ? ORDER BY created_at",
+            KEY_COLUMNS
+        ))
+        .bind(account_id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(|row| Self::map_key(row).map_err(db_err))
+            .collect()
+    }
+
+    async fn revoke_key(&self, key_id: Uuid) -> Result<(), AuthError> {
+        sqlx::query(
+            "UPDATE service_account_keys SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL",
+        )
+        .bind(Utc::now())
+        .bind(key_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn touch_key(&self, key_id: Uuid, used_at: DateTime<Utc>) -> Result<(), AuthError> {
+        sqlx::query("UPDATE service_account_keys SET last_used_at = ? WHERE id = ?")
+            .bind(used_at)
+            .bind(key_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn keys_due_for_reminder(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<ServiceAccountKey>, AuthError> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {} FROM service_account_keys
+            WHERE revoked_at IS NULL AND reminder_sent_at IS NULL AND expires_at <= ?
+            "#,
+            KEY_COLUMNS
+        ))
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(|row| Self::map_key(row).map_err(db_err))
+            .collect()
+    }
+
+    async fn mark_reminded(&self, key_id: Uuid, at: DateTime<Utc>) -> Result<(), AuthError> {
+        sqlx::query("UPDATE service_account_keys SET reminder_sent_at = ? WHERE id = ?")
+            .bind(at)
+            .bind(key_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(db_err)?;
+        Ok(())
+    }
+}
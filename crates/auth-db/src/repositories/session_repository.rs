@@ -1,3 +1,5 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
 use anyhow::Result;
 use auth_core::error::AuthError;
 use auth_core::models::Session;
@@ -5,6 +7,24 @@ use auth_core::services::session_service::SessionStore;
 use sqlx::{MySql, Pool};
 use uuid::Uuid;
 
+/// Columns of `sessions` read into [`Session`]
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "sessions",
+    columns: &[
+        Column::new("id", Text),
+        Column::new("user_id", Text),
+        Column::new("tenant_id", Text),
+        Column::new("session_token", Text),
+        Column::new("device_fingerprint", Text).nullable(),
+        Column::new("user_agent", Text).nullable(),
+        Column::new("ip_address", Text).nullable(),
+        Column::new("risk_score", Decimal),
+        Column::new("last_activity", Timestamp),
+        Column::new("expires_at", Timestamp),
+        Column::new("created_at", Timestamp),
+    ],
+};
+
 pub struct SessionRepository {
     pool: Pool<MySql>,
 }
@@ -41,9 +61,7 @@ impl SessionStore for SessionRepository {
         .bind(session.created_at)
         .execute(&self.pool)
         .await
-        .map_err(|e| AuthError::DatabaseError {
-            message: e.to_string(),
-        })?;
+        .map_err(db_err)?;
 
         Ok(session)
     }
@@ -53,9 +71,7 @@ impl SessionStore for SessionRepository {
             .bind(session_token)
             .fetch_optional(&self.pool)
             .await
-            .map_err(|e| AuthError::DatabaseError {
-                message: e.to_string(),
-            })
+            .map_err(db_err)
     }
 
     async fn delete(&self, session_token: &str) -> Result<(), AuthError> {
@@ -63,9 +79,7 @@ impl SessionStore for SessionRepository {
             .bind(session_token)
             .execute(&self.pool)
             .await
-            .map_err(|e| AuthError::DatabaseError {
-                message: e.to_string(),
-            })?;
+            .map_err(db_err)?;
         Ok(())
     }
 
@@ -74,9 +88,16 @@ impl SessionStore for SessionRepository {
             .bind(user_id.to_string())
             .execute(&self.pool)
             .await
-            .map_err(|e| AuthError::DatabaseError {
-                message: e.to_string(),
-            })?;
+            .map_err(db_err)?;
         Ok(())
     }
+
+    async fn delete_by_tenant(&self, tenant_id: Uuid) -> Result<u64, AuthError> {
+        sqlx::query("DELETE FROM sessions WHERE tenant_id = ?")
+            .bind(tenant_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(db_err)
+    }
 }
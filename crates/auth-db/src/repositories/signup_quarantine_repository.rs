@@ -0,0 +1,167 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::signup_abuse::{QuarantinedSignup, SignupQuarantineStore};
+use auth_core::services::sms_risk::QuarantineStatus;
+use chrono::Utc;
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+/// Columns of `signup_quarantine` used here
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "signup_quarantine",
+    columns: &[
+        Column::new("id", Text),
+        Column::new("tenant_id", Text),
+        Column::new("user_id", Text),
+        Column::new("ip_address", Text).nullable(),
+        Column::new("device_fingerprint", Text).nullable(),
+        Column::new("email_domain", Text).nullable(),
+        Column::new("reasons", Text),
+        Column::new("status", Text),
+        Column::new("created_at", Timestamp),
+        Column::new("reviewed_by", Text).nullable(),
+        Column::new("reviewed_at", Timestamp).nullable(),
+    ],
+};
+
+pub struct SignupQuarantineRepository {
+    pool: MySqlPool,
+}
+
+impl SignupQuarantineRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    fn map_entry(row: sqlx::mysql::MySqlRow) -> Result<QuarantinedSignup, sqlx::Error> {
+        let id: String = row.try_get("id")?;
+        let tenant_id: String = row.try_get("tenant_id")?;
+        let user_id: String = row.try_get("user_id")?;
+        let status: String = row.try_get("status")?;
+        let reasons: String = row.try_get("reasons")?;
+
+        Ok(QuarantinedSignup {
+            id: Uuid::parse_str(&id).unwrap_or_default(),
+            tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+            user_id: Uuid::parse_str(&user_id).unwrap_or_default(),
+            ip_address: row.try_get("ip_address")?,
+            device_fingerprint: row.try_get("device_fingerprint")?,
+            email_domain: row.try_get("email_domain")?,
+            reasons: serde_json::from_str(&reasons).unwrap_or_default(),
+            status: QuarantineStatus::parse(&status).ok_or_else(|| {
+                sqlx::Error::Decode(format!("unknown quarantine status {}", status).into())
+            })?,
+            created_at: row.try_get("created_at")?,
+            reviewed_by: row.try_get("reviewed_by")?,
+            reviewed_at: row.try_get("reviewed_at")?,
+        })
+    }
+}
+
+const COLUMNS: &str = "id, tenant_id, user_id, ip_address, device_fingerprint, email_domain, \
+     reasons, status, created_at, reviewed_by, reviewed_at";
+
+#[async_trait]
+impl SignupQuarantineStore for SignupQuarantineRepository {
+    async fn insert(&self, entry: &QuarantinedSignup) -> Result<(), AuthError> {
+        let reasons = serde_json::to_string(&entry.reasons).unwrap_or_default();
+        sqlx::query(
+            r#"
+            INSERT INTO signup_quarantine (id, tenant_id, user_id, ip_address, device_fingerprint,
+                email_domain, reasons, status, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(entry.id.to_string())
+        .bind(entry.tenant_id.to_string())
+        .bind(entry.user_id.to_string())
+        .bind(&entry.ip_address)
+        .bind(&entry.device_fingerprint)
+        .bind(&entry.email_domain)
+        .bind(reasons)
+        .bind(entry.status.as_str())
+        .bind(entry.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<QuarantinedSignup>, AuthError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM signup_quarantine WHERE id = ?",
+            COLUMNS
+        ))
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+        row.map(Self::map_entry).transpose().map_err(db_err)
+    }
+
+    async fn list(
+        &self,
+        status: Option<QuarantineStatus>,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<QuarantinedSignup>, AuthError> {
+        let mut conditions = Vec::new();
+        if status.is_some() {
+            conditions.push("status = ?");
+        }
+        if tenant_id.is_some() {
+            conditions.push("tenant_id = ?");
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let sql = format!(
+            // The filter names the tenant when one was given
+            "SELECT {} FROM signup_quarantine {} ORDER BY created_at DESC \
+             /* tenant:unscoped admin listing */",
+            COLUMNS, filter
+        );
+
+        let mut query = sqlx::query(&sql);
+        if let Some(status) = status {
+            query = query.bind(status.as_str());
+        }
+        if let Some(tenant_id) = tenant_id {
+            query = query.bind(tenant_id.to_string());
+        }
+        let rows = query.fetch_all(&self.pool).await.map_err(db_err)?;
+
+        rows.into_iter()
+            .map(Self::map_entry)
+            .collect::<Result<_, _>>()
+            .map_err(db_err)
+    }
+
+    async fn review(
+        &self,
+        id: Uuid,
+        status: QuarantineStatus,
+        reviewed_by: Option<&str>,
+    ) -> Result<Option<QuarantinedSignup>, AuthError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE signup_quarantine SET status = ?, reviewed_by = ?, reviewed_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(status.as_str())
+        .bind(reviewed_by)
+        .bind(Utc::now())
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.find(id).await
+    }
+}
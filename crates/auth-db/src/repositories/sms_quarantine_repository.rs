@@ -0,0 +1,181 @@
+use crate::error::db_err;
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::sms_risk::{QuarantineStatus, QuarantinedDestination, SmsQuarantineStore};
+use chrono::Utc;
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+pub struct SmsQuarantineRepository {
+    pool: MySqlPool,
+}
+
+impl SmsQuarantineRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    fn map_entry(row: sqlx::mysql::MySqlRow) -> Result<QuarantinedDestination, sqlx::Error> {
+        let id: String = row.try_get("id")?;
+        let tenant_id: String = row.try_get("tenant_id")?;
+        let status: String = row.try_get("status")?;
+        let reasons: String = row.try_get("reasons")?;
+        let score: u32 = row.try_get("score")?;
+        let attempts: u32 = row.try_get("attempts")?;
+
+        Ok(QuarantinedDestination {
+            id: Uuid::parse_str(&id).unwrap_or_default(),
+            tenant_id: from_tenant_column(&tenant_id),
+            destination: row.try_get("destination")?,
+            score,
+            reasons: serde_json::from_str(&reasons).unwrap_or_default(),
+            status: QuarantineStatus::parse(&status).ok_or_else(|| {
+                sqlx::Error::Decode(format!("unknown quarantine status {}", status).into())
+            })?,
+            attempts,
+            created_at: row.try_get("created_at")?,
+            reviewed_by: row.try_get("reviewed_by")?,
+            reviewed_at: row.try_get("reviewed_at")?,
+        })
+    }
+}
+
+/// SMS sent without a tenant is stored under the nil id, so the unique key
+/// on (tenant_id, destination) covers it too
+fn tenant_column(tenant_id: Option<Uuid>) -> String {
+    tenant_id.unwrap_or_default().to_string()
+}
+
+fn from_tenant_column(value: &str) -> Option<Uuid> {
+    Uuid::parse_str(value).ok().filter(|id| !id.is_nil())
+}
+
+const COLUMNS: &str = "id, tenant_id, destination, score, reasons, status, attempts, \
+     created_at, reviewed_by, reviewed_at";
+
+#[async_trait]
+impl SmsQuarantineStore for SmsQuarantineRepository {
+    async fn find(
+        &self,
+        tenant_id: Option<Uuid>,
+        destination: &str,
+    ) -> Result<Option<QuarantinedDestination>, AuthError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM sms_quarantine WHERE tenant_id = ? AND destination = ?",
+            COLUMNS
+        ))
+        .bind(tenant_column(tenant_id))
+        .bind(destination)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        row.map(Self::map_entry).transpose().map_err(db_err)
+    }
+
+    async fn insert(&self, entry: &QuarantinedDestination) -> Result<(), AuthError> {
+        let reasons = serde_json::to_string(&entry.reasons).unwrap_or_default();
+        // Another instance may have quarantined the number first
+        sqlx::query(
+            r#"
+            INSERT INTO sms_quarantine (id, tenant_id, destination, score, reasons, status, attempts, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE attempts = attempts + 1
+            "#,
+        )
+        .bind(entry.id.to_string())
+        .bind(tenant_column(entry.tenant_id))
+        .bind(&entry.destination)
+        .bind(entry.score)
+        .bind(reasons)
+        .bind(entry.status.as_str())
+        .bind(entry.attempts)
+        .bind(entry.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn record_attempt(&self, id: Uuid) -> Result<(), AuthError> {
+        sqlx::query("UPDATE sms_quarantine SET attempts = attempts + 1 WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        status: Option<QuarantineStatus>,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<QuarantinedDestination>, AuthError> {
+        let mut conditions = Vec::new();
+        if status.is_some() {
+            conditions.push("status = ?");
+        }
+        if tenant_id.is_some() {
+            conditions.push("tenant_id = ?");
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let sql = format!(
+            // The filter names the tenant when one was given
+            "SELECT {} FROM sms_quarantine {} ORDER BY created_at DESC \
+             /* tenant:unscoped admin listing */",
+            COLUMNS, filter
+        );
+
+        let mut query = sqlx::query(&sql);
+        if let Some(status) = status {
+            query = query.bind(status.as_str());
+        }
+        if let Some(tenant_id) = tenant_id {
+            query = query.bind(tenant_id.to_string());
+        }
+        let rows = query.fetch_all(&self.pool).await.map_err(db_err)?;
+
+        rows.into_iter()
+            .map(Self::map_entry)
+            .collect::<Result<_, _>>()
+            .map_err(db_err)
+    }
+
+    async fn review(
+        &self,
+        id: Uuid,
+        status: QuarantineStatus,
+        reviewed_by: Option<&str>,
+    ) -> Result<Option<QuarantinedDestination>, AuthError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE sms_quarantine SET status = ?, reviewed_by = ?, reviewed_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(status.as_str())
+        .bind(reviewed_by)
+        .bind(Utc::now())
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM sms_quarantine WHERE id = ?",
+            COLUMNS
+        ))
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+        row.map(Self::map_entry).transpose().map_err(db_err)
+    }
+}
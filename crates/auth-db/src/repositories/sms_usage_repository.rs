@@ -0,0 +1,82 @@
+use crate::error::db_err;
+use crate::tenant_guard::{tenant_query, TenantContext};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::sms_budget::{DailySmsUsage, SmsUsageStore};
+use chrono::NaiveDate;
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+pub struct SmsUsageRepository {
+    pool: MySqlPool,
+}
+
+impl SmsUsageRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SmsUsageStore for SmsUsageRepository {
+    async fn record(&self, tenant_id: Uuid, day: NaiveDate) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO sms_usage_daily (tenant_id, day, sent)
+            VALUES (?, ?, 1)
+            ON DUPLICATE KEY UPDATE sent = sent + 1
+            "#,
+        )
+        .bind(tenant_id.to_string())
+        .bind(day)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn daily_usage(
+        &self,
+        tenant_id: Option<Uuid>,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<DailySmsUsage>, AuthError> {
+        let query = match tenant_id {
+            Some(tenant_id) => tenant_query(
+                &TenantContext::new(tenant_id),
+                r#"
+                SELECT tenant_id, day, sent FROM sms_usage_daily
+                WHERE tenant_id = ? AND day BETWEEN ? AND ?
+                ORDER BY day
+                "#,
+            ),
+            None => sqlx::query(
+                r#"
+                SELECT tenant_id, day, sent FROM sms_usage_daily
+                WHERE day BETWEEN ? AND ?
+                /* tenant:unscoped usage of all tenants */
+                ORDER BY tenant_id, day
+                "#,
+            ),
+        };
+        let rows = query
+            .bind(from)
+            .bind(to)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(|row| {
+                let tenant_id: String = row.try_get("tenant_id")?;
+                let sent: u32 = row.try_get("sent")?;
+                Ok(DailySmsUsage {
+                    tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+                    day: row.try_get("day")?,
+                    sent: u64::from(sent),
+                })
+            })
+            .collect::<Result<_, sqlx::Error>>()
+            .map_err(db_err)
+    }
+}
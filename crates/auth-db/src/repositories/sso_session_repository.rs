@@ -0,0 +1,148 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::models::{ClientLogoutEndpoints, ClientSession};
+use auth_core::services::sso_session::SsoSessionStore;
+use chrono::{DateTime, Utc};
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+/// Columns of `sso_client_sessions` used here
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "sso_client_sessions",
+    columns: &[
+        Column::new("sid", Text),
+        Column::new("session_id", Text),
+        Column::new("client_id", Text),
+        Column::new("user_id", Text),
+        Column::new("tenant_id", Text),
+        Column::new("created_at", Timestamp),
+        Column::new("last_authenticated_at", Timestamp),
+        Column::new("ended_at", Timestamp).nullable(),
+    ],
+};
+
+pub struct SsoSessionRepository {
+    pool: MySqlPool,
+}
+
+impl SsoSessionRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    fn map_client_session(row: sqlx::mysql::MySqlRow) -> Result<ClientSession, sqlx::Error> {
+        let session_id: String = row.try_get("session_id")?;
+        let user_id: String = row.try_get("user_id")?;
+        let tenant_id: String = row.try_get("tenant_id")?;
+
+        Ok(ClientSession {
+            sid: row.try_get("sid")?,
+            session_id: Uuid::parse_str(&session_id).unwrap_or_default(),
+            client_id: row.try_get("client_id")?,
+            user_id: Uuid::parse_str(&user_id).unwrap_or_default(),
+            tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+            created_at: row.try_get("created_at")?,
+            last_authenticated_at: row.try_get("last_authenticated_at")?,
+            ended_at: row.try_get("ended_at")?,
+        })
+    }
+}
+
+#[async_trait]
+impl SsoSessionStore for SsoSessionRepository {
+    async fn upsert_client_session(&self, session: &ClientSession) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO sso_client_sessions (sid, session_id, client_id, user_id, tenant_id, created_at, last_authenticated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE last_authenticated_at = VALUES(last_authenticated_at)
+            "#,
+        )
+        .bind(&session.sid)
+        .bind(session.session_id.to_string())
+        .bind(&session.client_id)
+        .bind(session.user_id.to_string())
+        .bind(session.tenant_id.to_string())
+        .bind(session.created_at)
+        .bind(session.last_authenticated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn active_client_sessions(
+        &self,
+        session_id: Uuid,
+    ) -> Result<Vec<ClientSession>, AuthError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT sid, session_id, client_id, user_id, tenant_id, created_at, last_authenticated_at, ended_at
+            FROM sso_client_sessions
+            WHERE session_id = ? AND ended_at IS NULL
+            "#,
+        )
+        .bind(session_id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(Self::map_client_session)
+            .collect::<Result<_, _>>()
+            .map_err(db_err)
+    }
+
+    async fn end_client_sessions(
+        &self,
+        session_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), AuthError> {
+        sqlx::query(
+            "UPDATE sso_client_sessions SET ended_at = ? WHERE session_id = ? AND ended_at IS NULL",
+        )
+        .bind(at)
+        .bind(session_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn logout_endpoints(
+        &self,
+        client_id: &str,
+    ) -> Result<Option<ClientLogoutEndpoints>, AuthError> {
+        let row = sqlx::query(
+            r#"
+            SELECT client_id, frontchannel_logout_uri, frontchannel_logout_session_required,
+                   backchannel_logout_uri, post_logout_redirect_uris
+            FROM oauth_clients WHERE client_id = ?
+            "#,
+        )
+        .bind(client_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let redirect_uris: Option<String> =
+            row.try_get("post_logout_redirect_uris").map_err(db_err)?;
+
+        Ok(Some(ClientLogoutEndpoints {
+            client_id: row.try_get("client_id").map_err(db_err)?,
+            frontchannel_logout_uri: row.try_get("frontchannel_logout_uri").map_err(db_err)?,
+            frontchannel_logout_session_required: row
+                .try_get("frontchannel_logout_session_required")
+                .map_err(db_err)?,
+            backchannel_logout_uri: row.try_get("backchannel_logout_uri").map_err(db_err)?,
+            post_logout_redirect_uris: redirect_uris
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+        }))
+    }
+}
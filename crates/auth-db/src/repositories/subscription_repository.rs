@@ -1,3 +1,5 @@
+use crate::error::db_err;
+use crate::tenant_guard::{tenant_query, TenantContext};
 use anyhow::Result;
 use auth_core::error::AuthError;
 use auth_core::models::subscription::TenantSubscription;
@@ -38,7 +40,7 @@ impl SubscriptionStore for SubscriptionRepository {
         .bind(sub.updated_at)
         .execute(&self.pool)
         .await
-        .map_err(|e| AuthError::DatabaseError { message: e.to_string() })?;
+        .map_err(db_err)?;
 
         Ok(sub)
     }
@@ -48,56 +50,28 @@ impl SubscriptionStore for SubscriptionRepository {
         tenant_id: Uuid,
     ) -> Result<Option<TenantSubscription>, AuthError> {
         // Semantic fix: Manually map row to handle potential UUID/String mismatches with sqlx
-        let row = sqlx::query("SELECT * FROM tenant_subscriptions WHERE tenant_id = ? ORDER BY created_at DESC LIMIT 1")
-            .bind(tenant_id.to_string())
+        let row = tenant_query(
+            &TenantContext::new(tenant_id),
+            "SELECT * FROM tenant_subscriptions WHERE tenant_id = ? ORDER BY created_at DESC LIMIT 1",
+        )
             .fetch_optional(&self.pool)
             .await
-            .map_err(|e| AuthError::DatabaseError { message: e.to_string() })?;
+            .map_err(db_err)?;
 
         if let Some(row) = row {
-            let id_str: String = row.try_get("id").map_err(|e| AuthError::DatabaseError {
-                message: e.to_string(),
-            })?;
-            let t_id_str: String =
-                row.try_get("tenant_id")
-                    .map_err(|e| AuthError::DatabaseError {
-                        message: e.to_string(),
-                    })?;
+            let id_str: String = row.try_get("id").map_err(db_err)?;
+            let t_id_str: String = row.try_get("tenant_id").map_err(db_err)?;
 
             Ok(Some(TenantSubscription {
                 id: Uuid::parse_str(&id_str).unwrap_or_default(),
                 tenant_id: Uuid::parse_str(&t_id_str).unwrap_or_default(),
-                plan_id: row
-                    .try_get("plan_id")
-                    .map_err(|e| AuthError::DatabaseError {
-                        message: e.to_string(),
-                    })?,
-                status: row
-                    .try_get("status")
-                    .map_err(|e| AuthError::DatabaseError {
-                        message: e.to_string(),
-                    })?,
-                start_date: row
-                    .try_get("start_date")
-                    .map_err(|e| AuthError::DatabaseError {
-                        message: e.to_string(),
-                    })?,
+                plan_id: row.try_get("plan_id").map_err(db_err)?,
+                status: row.try_get("status").map_err(db_err)?,
+                start_date: row.try_get("start_date").map_err(db_err)?,
                 end_date: row.try_get("end_date").unwrap_or(None),
-                current_usage: row.try_get("current_usage").map_err(|e| {
-                    AuthError::DatabaseError {
-                        message: e.to_string(),
-                    }
-                })?,
-                created_at: row
-                    .try_get("created_at")
-                    .map_err(|e| AuthError::DatabaseError {
-                        message: e.to_string(),
-                    })?,
-                updated_at: row
-                    .try_get("updated_at")
-                    .map_err(|e| AuthError::DatabaseError {
-                        message: e.to_string(),
-                    })?,
+                current_usage: row.try_get("current_usage").map_err(db_err)?,
+                created_at: row.try_get("created_at").map_err(db_err)?,
+                updated_at: row.try_get("updated_at").map_err(db_err)?,
             }))
         } else {
             Ok(None)
@@ -127,9 +101,7 @@ impl SubscriptionStore for SubscriptionRepository {
         .bind(sub.id.to_string())
         .execute(&self.pool)
         .await
-        .map_err(|e| AuthError::DatabaseError {
-            message: e.to_string(),
-        })?;
+        .map_err(db_err)?;
 
         Ok(())
     }
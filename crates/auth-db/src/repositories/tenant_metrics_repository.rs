@@ -0,0 +1,107 @@
+use crate::error::db_err;
+use crate::tenant_guard::{tenant_query, TenantContext};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::tenant_metrics::{TenantMetric, TenantMetricsStore};
+use chrono::{DateTime, Utc};
+use sqlx::{MySqlPool, Row};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+pub struct TenantMetricsRepository {
+    pool: MySqlPool,
+}
+
+impl TenantMetricsRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn count(row: &sqlx::mysql::MySqlRow, column: &str) -> Result<u64, AuthError> {
+    let value: i64 = row.try_get(column).map_err(db_err)?;
+    Ok(value.max(0) as u64)
+}
+
+#[async_trait]
+impl TenantMetricsStore for TenantMetricsRepository {
+    async fn counts(
+        &self,
+        tenant_id: Uuid,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<BTreeMap<TenantMetric, u64>, AuthError> {
+        let tenant = TenantContext::new(tenant_id);
+        let mut counts = BTreeMap::new();
+
+        let users = tenant_query(
+            &tenant,
+            r#"
+            SELECT
+                COUNT(*) AS users,
+                CAST(COALESCE(SUM(mfa_enabled), 0) AS SIGNED) AS mfa_users
+            FROM users
+            WHERE tenant_id = ? AND status <> 'deleted'
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_err)?;
+        counts.insert(TenantMetric::Users, count(&users, "users")?);
+        counts.insert(TenantMetric::MfaUsers, count(&users, "mfa_users")?);
+
+        let locked = tenant_query(
+            &tenant,
+            r#"
+            SELECT COUNT(*) AS locked
+            FROM users
+            WHERE tenant_id = ? AND status <> 'deleted' AND locked_until > ?
+            "#,
+        )
+        .bind(until)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_err)?;
+        counts.insert(TenantMetric::LockedUsers, count(&locked, "locked")?);
+
+        let logins = tenant_query(
+            &tenant,
+            r#"
+            SELECT
+                CAST(COALESCE(SUM(success), 0) AS SIGNED) AS successful,
+                CAST(COALESCE(SUM(NOT success), 0) AS SIGNED) AS failed,
+                COUNT(DISTINCT CASE WHEN success THEN user_id END) AS active_users
+            FROM login_events
+            WHERE tenant_id = ? AND created_at >= ? AND created_at < ?
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_err)?;
+        counts.insert(
+            TenantMetric::SuccessfulLogins,
+            count(&logins, "successful")?,
+        );
+        counts.insert(TenantMetric::FailedLogins, count(&logins, "failed")?);
+        counts.insert(TenantMetric::ActiveUsers, count(&logins, "active_users")?);
+
+        let sessions = tenant_query(
+            &tenant,
+            r#"
+            SELECT COUNT(*) AS created
+            FROM sessions
+            WHERE tenant_id = ? AND created_at >= ? AND created_at < ?
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_err)?;
+        counts.insert(TenantMetric::SessionsCreated, count(&sessions, "created")?);
+
+        Ok(counts)
+    }
+}
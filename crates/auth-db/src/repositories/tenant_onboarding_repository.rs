@@ -0,0 +1,113 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind, ColumnKind::*, ColumnSchema as Column, TableSchema};
+use crate::tenant_guard::{tenant_query, TenantContext};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::tenant_onboarding::{OnboardingStore, TenantSettings};
+use auth_core::services::workflow::FlowContext;
+use chrono::Utc;
+use sqlx::types::Json;
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+/// Columns of `tenant_onboarding` used here
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "tenant_onboarding",
+    columns: &[
+        Column::new("tenant_id", Text),
+        Column::new("state", ColumnKind::Json),
+        Column::new("version", Integer),
+        Column::new("updated_at", Timestamp),
+    ],
+};
+
+pub struct TenantOnboardingRepository {
+    pool: MySqlPool,
+}
+
+impl TenantOnboardingRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn conflict() -> AuthError {
+    AuthError::Conflict {
+        message: "onboarding was changed concurrently".to_string(),
+    }
+}
+
+#[async_trait]
+impl OnboardingStore for TenantOnboardingRepository {
+    async fn load(&self, tenant_id: Uuid) -> Result<Option<FlowContext>, AuthError> {
+        let row = tenant_query(
+            &TenantContext::new(tenant_id),
+            "SELECT state FROM tenant_onboarding WHERE tenant_id = ?",
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+        row.map(|row| row.try_get::<Json<FlowContext>, _>("state"))
+            .transpose()
+            .map(|state| state.map(|Json(ctx)| ctx))
+            .map_err(db_err)
+    }
+
+    async fn save(&self, ctx: &FlowContext, expected_version: u64) -> Result<(), AuthError> {
+        let written = if expected_version == 0 {
+            sqlx::query(
+                r#"
+                INSERT IGNORE INTO tenant_onboarding (tenant_id, state, version, updated_at)
+                VALUES (?, ?, ?, ?)
+                "#,
+            )
+            .bind(ctx.tenant_id.to_string())
+            .bind(Json(ctx))
+            .bind(ctx.version)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await
+        } else {
+            sqlx::query(
+                r#"
+                UPDATE tenant_onboarding SET state = ?, version = ?, updated_at = ?
+                WHERE tenant_id = ? AND version = ?
+                "#,
+            )
+            .bind(Json(ctx))
+            .bind(ctx.version)
+            .bind(Utc::now())
+            .bind(ctx.tenant_id.to_string())
+            .bind(expected_version)
+            .execute(&self.pool)
+            .await
+        }
+        .map_err(db_err)?
+        .rows_affected();
+
+        if written == 0 {
+            return Err(conflict());
+        }
+        Ok(())
+    }
+
+    async fn apply(&self, tenant_id: Uuid, settings: &TenantSettings) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            UPDATE tenants SET
+                custom_domain = COALESCE(?, custom_domain),
+                auth_config = JSON_MERGE_PATCH(COALESCE(auth_config, JSON_OBJECT()), ?),
+                branding_config = COALESCE(?, branding_config)
+            WHERE id = ?
+            "#,
+        )
+        .bind(&settings.custom_domain)
+        .bind(Json(&settings.auth_config))
+        .bind(settings.branding_config.as_ref().map(Json))
+        .bind(tenant_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::models::tenant::TenantStatus;
+use auth_core::services::tenant_context::{TenantState, TenantStateStore};
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+/// Columns of `tenants` read for token validation
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "tenants",
+    columns: &[
+        Column::new("id", Text),
+        Column::new("status", Text).nullable(),
+        Column::new("data_region", Text).nullable(),
+        Column::new("auth_config", Json).nullable(),
+        Column::new("policy_version", Integer),
+    ],
+};
+
+pub struct TenantStateRepository {
+    pool: MySqlPool,
+}
+
+impl TenantStateRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn parse_status(status: Option<&str>) -> TenantStatus {
+    match status {
+        Some("suspended") => TenantStatus::Suspended,
+        Some("deleted") => TenantStatus::Deleted,
+        // The column defaults to 'active'
+        _ => TenantStatus::Active,
+    }
+}
+
+#[async_trait]
+impl TenantStateStore for TenantStateRepository {
+    async fn load(&self, tenant_id: Uuid) -> Result<Option<TenantState>, AuthError> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, status, data_region, auth_config, policy_version
+            FROM tenants
+            WHERE id = ?
+            "#,
+        )
+        .bind(tenant_id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        row.map(|row| {
+            let status: Option<String> = row.try_get("status")?;
+            let auth_config: Option<sqlx::types::Json<serde_json::Value>> =
+                row.try_get("auth_config")?;
+            Ok(TenantState {
+                tenant_id,
+                status: parse_status(status.as_deref()),
+                data_region: row.try_get("data_region")?,
+                auth_config: auth_config.map(|c| c.0).unwrap_or_default(),
+                version: row.try_get("policy_version")?,
+            })
+        })
+        .transpose()
+        .map_err(db_err)
+    }
+
+    async fn bump_version(&self, tenant_id: Uuid) -> Result<u64, AuthError> {
+        let mut tx = self.pool.begin().await.map_err(db_err)?;
+        let updated =
+            sqlx::query("UPDATE tenants SET policy_version = policy_version + 1 WHERE id = ?")
+                .bind(tenant_id.to_string())
+                .execute(&mut *tx)
+                .await
+                .map_err(db_err)?;
+        if updated.rows_affected() == 0 {
+            return Err(AuthError::TenantNotFound {
+                tenant_id: tenant_id.to_string(),
+            });
+        }
+        let version: u64 = sqlx::query_scalar("SELECT policy_version FROM tenants WHERE id = ?")
+            .bind(tenant_id.to_string())
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(db_err)?;
+        tx.commit().await.map_err(db_err)?;
+        Ok(version)
+    }
+}
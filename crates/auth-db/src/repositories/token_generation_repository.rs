@@ -0,0 +1,89 @@
+use crate::error::db_err;
+use crate::tenant_guard::{tenant_query, TenantContext};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::services::forced_reauth::{GenerationScope, TokenGenerationStore};
+use sqlx::mysql::MySqlArguments;
+use sqlx::query::Query;
+use sqlx::{MySql, MySqlPool, Row, Transaction};
+use uuid::Uuid;
+
+const SELECT_TENANT_GENERATION: &str =
+    "SELECT generation FROM tenant_token_generations WHERE tenant_id = ?";
+const SELECT_USER_GENERATION: &str =
+    "SELECT generation FROM user_token_generations WHERE user_id = ?";
+
+pub struct TokenGenerationRepository {
+    pool: MySqlPool,
+}
+
+impl TokenGenerationRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn select(scope: GenerationScope) -> Query<'static, MySql, MySqlArguments> {
+    match scope {
+        GenerationScope::Tenant(tenant_id) => {
+            tenant_query(&TenantContext::new(tenant_id), SELECT_TENANT_GENERATION)
+        }
+        GenerationScope::User(user_id) => {
+            sqlx::query(SELECT_USER_GENERATION).bind(user_id.to_string())
+        }
+    }
+}
+
+async fn increment(
+    tx: &mut Transaction<'_, MySql>,
+    scope: GenerationScope,
+) -> Result<(), sqlx::Error> {
+    let (sql, id): (&str, Uuid) = match scope {
+        GenerationScope::Tenant(tenant_id) => (
+            r#"
+            INSERT INTO tenant_token_generations (tenant_id, generation)
+            VALUES (?, 1)
+            ON DUPLICATE KEY UPDATE generation = generation + 1
+            "#,
+            tenant_id,
+        ),
+        GenerationScope::User(user_id) => (
+            r#"
+            INSERT INTO user_token_generations (user_id, generation)
+            VALUES (?, 1)
+            ON DUPLICATE KEY UPDATE generation = generation + 1
+            "#,
+            user_id,
+        ),
+    };
+    sqlx::query(sql)
+        .bind(id.to_string())
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl TokenGenerationStore for TokenGenerationRepository {
+    async fn generation(&self, scope: GenerationScope) -> Result<u64, AuthError> {
+        let row = select(scope)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_err)?;
+        row.map_or(Ok(0), |row| row.try_get("generation"))
+            .map_err(db_err)
+    }
+
+    async fn bump(&self, scope: GenerationScope) -> Result<u64, AuthError> {
+        let mut tx = self.pool.begin().await.map_err(db_err)?;
+        increment(&mut tx, scope).await.map_err(db_err)?;
+        // The row stays locked until commit, so this reads our own bump
+        let generation: u64 = select(scope)
+            .fetch_one(&mut *tx)
+            .await
+            .and_then(|row| row.try_get("generation"))
+            .map_err(db_err)?;
+        tx.commit().await.map_err(db_err)?;
+        Ok(generation)
+    }
+}
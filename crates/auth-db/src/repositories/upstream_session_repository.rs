@@ -0,0 +1,117 @@
+use crate::error::db_err;
+use crate::schema::{ColumnKind::*, ColumnSchema as Column, TableSchema};
+use crate::tenant_guard::{tenant_query, TenantContext};
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::models::{UpstreamSession, UpstreamSessionStore};
+use sqlx::{MySqlPool, Row};
+use uuid::Uuid;
+
+/// Columns of `upstream_sessions` used here
+pub const SCHEMA: TableSchema = TableSchema {
+    table: "upstream_sessions",
+    columns: &[
+        Column::new("id", Text),
+        Column::new("session_id", Text),
+        Column::new("tenant_id", Text),
+        Column::new("user_id", Text),
+        Column::new("provider", Text),
+        Column::new("id_token", Text).nullable(),
+        Column::new("name_id", Text).nullable(),
+        Column::new("session_index", Text).nullable(),
+        Column::new("created_at", Timestamp),
+    ],
+};
+
+pub struct UpstreamSessionRepository {
+    pool: MySqlPool,
+}
+
+impl UpstreamSessionRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn uuid(row: &sqlx::mysql::MySqlRow, column: &str) -> Result<Uuid, sqlx::Error> {
+    let value: String = row.try_get(column)?;
+    Ok(Uuid::parse_str(&value).unwrap_or_default())
+}
+
+fn session_from_row(row: &sqlx::mysql::MySqlRow) -> Result<UpstreamSession, sqlx::Error> {
+    Ok(UpstreamSession {
+        id: uuid(row, "id")?,
+        session_id: uuid(row, "session_id")?,
+        tenant_id: uuid(row, "tenant_id")?,
+        user_id: uuid(row, "user_id")?,
+        provider: row.try_get("provider")?,
+        id_token: row.try_get("id_token")?,
+        name_id: row.try_get("name_id")?,
+        session_index: row.try_get("session_index")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+#[async_trait]
+impl UpstreamSessionStore for UpstreamSessionRepository {
+    async fn record(&self, session: &UpstreamSession) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            INSERT INTO upstream_sessions
+                (id, session_id, tenant_id, user_id, provider, id_token, name_id,
+                 session_index, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(session.id.to_string())
+        .bind(session.session_id.to_string())
+        .bind(session.tenant_id.to_string())
+        .bind(session.user_id.to_string())
+        .bind(&session.provider)
+        .bind(&session.id_token)
+        .bind(&session.name_id)
+        .bind(&session.session_index)
+        .bind(session.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    async fn for_session(
+        &self,
+        tenant_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<Vec<UpstreamSession>, AuthError> {
+        let rows = tenant_query(
+            &TenantContext::new(tenant_id),
+            r#"
+            SELECT id, session_id, tenant_id, user_id, provider, id_token, name_id,
+                   session_index, created_at
+            FROM upstream_sessions
+            WHERE tenant_id = ? AND session_id = ?
+            ORDER BY created_at
+            "#,
+        )
+        .bind(session_id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+        rows.iter()
+            .map(session_from_row)
+            .collect::<Result<_, _>>()
+            .map_err(db_err)
+    }
+
+    async fn remove(&self, tenant_id: Uuid, session_id: Uuid) -> Result<(), AuthError> {
+        tenant_query(
+            &TenantContext::new(tenant_id),
+            "DELETE FROM upstream_sessions WHERE tenant_id = ? AND session_id = ?",
+        )
+        .bind(session_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(db_err)?;
+        Ok(())
+    }
+}
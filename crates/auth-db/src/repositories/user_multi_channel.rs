@@ -3,6 +3,7 @@
 //! Additional methods for email/phone identifier support
 
 use super::user_repository::UserRepository;
+use crate::error::db_err;
 use auth_core::error::AuthError;
 use auth_core::models::User;
 use uuid::Uuid;
@@ -45,9 +46,7 @@ impl UserRepository {
         .bind(user_id.to_string())
         .execute(&self.pool)
         .await
-        .map_err(|e| AuthError::DatabaseError {
-            message: e.to_string(),
-        })?;
+        .map_err(db_err)?;
 
         Ok(())
     }
@@ -60,9 +59,7 @@ impl UserRepository {
         .bind(user_id.to_string())
         .execute(&self.pool)
         .await
-        .map_err(|e| AuthError::DatabaseError {
-            message: e.to_string(),
-        })?;
+        .map_err(db_err)?;
 
         Ok(())
     }
@@ -70,9 +67,7 @@ impl UserRepository {
     /// Helper to convert database row to User model
     #[allow(dead_code)]
     fn row_to_user(&self, row: sqlx::mysql::MySqlRow) -> Result<User, AuthError> {
-        self.map_row(row).map_err(|e| AuthError::DatabaseError {
-            message: e.to_string(),
-        })
+        self.map_row(row).map_err(db_err)
     }
 }
 
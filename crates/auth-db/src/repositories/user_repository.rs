@@ -1,14 +1,25 @@
-use auth_core::models::user::{CreateUserRequest, UpdateUserRequest, UserStatus};
+use crate::online_migration::DualWrite;
+use auth_config::MigrationPhase;
+use auth_core::models::user::{
+    CreateUserRequest, IdentifierType, PrimaryIdentifier, UpdateUserRequest, UserStatus,
+};
+use auth_core::models::validation::detect_identifier_type;
 use auth_core::models::User;
-use chrono::Utc;
+use auth_core::services::identifier::IdentifierNormalizer;
+use auth_crypto::{EncryptionError, PiiField, PiiProtector};
+use chrono::{DateTime, Utc};
 use serde_json;
 use sqlx::MySqlPool;
 use sqlx::Row;
+use std::sync::Arc;
 use uuid::Uuid;
 
 use async_trait::async_trait;
 use auth_core::error::AuthError;
+use auth_core::services::auth_methods::{PasswordOnlyCount, PasswordOnlyStore, PasswordOnlyUser};
 use auth_core::services::identity::UserStore;
+use auth_core::services::password_expiry::{AgingPassword, PasswordExpiryStore};
+use auth_core::services::recovery_codes::RecoveryCodeStore;
 
 #[async_trait]
 impl UserStore for UserRepository {
@@ -24,6 +35,16 @@ impl UserStore for UserRepository {
             .map_err(AuthError::from)
     }
 
+    async fn find_by_username(
+        &self,
+        username: &str,
+        tenant_id: Uuid,
+    ) -> Result<Option<User>, AuthError> {
+        self.find_by_username(username, tenant_id)
+            .await
+            .map_err(AuthError::from)
+    }
+
     async fn find_by_identifier(
         &self,
         identifier: &str,
@@ -94,14 +115,269 @@ impl UserStore for UserRepository {
     }
 }
 
+#[async_trait]
+impl RecoveryCodeStore for UserRepository {
+    async fn recovery_codes(&self, user_id: Uuid) -> Result<Vec<String>, AuthError> {
+        let codes: Option<serde_json::Value> =
+            sqlx::query_scalar("SELECT backup_codes FROM users WHERE id = ?")
+                .bind(user_id.to_string())
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(AuthError::from)?
+                .flatten();
+        Ok(codes
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        hashes: &[String],
+    ) -> Result<(), AuthError> {
+        sqlx::query("UPDATE users SET backup_codes = ?, updated_at = ? WHERE id = ?")
+            .bind(serde_json::json!(hashes))
+            .bind(Utc::now())
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(AuthError::from)?;
+        Ok(())
+    }
+
+    async fn swap_recovery_codes(
+        &self,
+        user_id: Uuid,
+        expected: &[String],
+        hashes: &[String],
+    ) -> Result<bool, AuthError> {
+        let updated = sqlx::query(
+            "UPDATE users SET backup_codes = ?, updated_at = ? \
+             WHERE id = ? AND backup_codes = CAST(? AS JSON)",
+        )
+        .bind(serde_json::json!(hashes))
+        .bind(Utc::now())
+        .bind(user_id.to_string())
+        .bind(serde_json::json!(expected).to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(AuthError::from)?
+        .rows_affected();
+        Ok(updated == 1)
+    }
+}
+
+#[async_trait]
+impl PasswordExpiryStore for UserRepository {
+    async fn aging_passwords(
+        &self,
+        set_before: DateTime<Utc>,
+        after: Option<Uuid>,
+        limit: usize,
+    ) -> Result<Vec<AgingPassword>, AuthError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, tenant_id, email, email_verified, email_verified_at, phone, phone_verified, phone_verified_at, username, password_hash, password_changed_at, failed_login_attempts, locked_until, last_login_at, last_login_ip, mfa_enabled, mfa_secret, backup_codes, risk_score, profile_data, preferences, status, created_at, updated_at, deleted_at, identifier_type, primary_identifier
+            FROM users
+            WHERE password_hash IS NOT NULL AND email IS NOT NULL AND deleted_at IS NULL
+              AND COALESCE(password_changed_at, created_at) < ? AND id > ?
+            ORDER BY id
+            LIMIT ?
+            /* tenant:unscoped expiry sweep of all tenants */
+            "#,
+        )
+        .bind(set_before)
+        .bind(after.map(|id| id.to_string()).unwrap_or_default())
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut passwords = Vec::with_capacity(rows.len());
+        for row in rows {
+            let user = self.reveal(self.map_row(row)?).await?;
+            if !matches!(user.status, UserStatus::Active) {
+                continue;
+            }
+            let password_set_at = auth_core::services::password_expiry::password_set_at(&user);
+            if let Some(email) = user.email {
+                passwords.push(AgingPassword {
+                    user_id: user.id,
+                    tenant_id: user.tenant_id,
+                    email,
+                    password_set_at,
+                });
+            }
+        }
+        Ok(passwords)
+    }
+
+    async fn claim_reminder(
+        &self,
+        user_id: Uuid,
+        password_set_at: DateTime<Utc>,
+        threshold_days: u32,
+    ) -> Result<bool, AuthError> {
+        let inserted = sqlx::query(
+            "INSERT IGNORE INTO password_expiry_reminders \
+             (user_id, password_set_at, threshold_days, sent_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(password_set_at)
+        .bind(threshold_days)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(inserted == 1)
+    }
+
+    async fn release_reminder(
+        &self,
+        user_id: Uuid,
+        password_set_at: DateTime<Utc>,
+        threshold_days: u32,
+    ) -> Result<(), AuthError> {
+        sqlx::query(
+            "DELETE FROM password_expiry_reminders \
+             WHERE user_id = ? AND password_set_at = ? AND threshold_days = ?",
+        )
+        .bind(user_id.to_string())
+        .bind(password_set_at)
+        .bind(threshold_days)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PasswordOnlyStore for UserRepository {
+    async fn password_only_counts(
+        &self,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<PasswordOnlyCount>, AuthError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT u.tenant_id, COUNT(*) AS users
+            FROM users u
+            WHERE u.password_hash IS NOT NULL AND u.deleted_at IS NULL
+              AND (? IS NULL OR u.tenant_id = ?)
+              AND NOT EXISTS (SELECT 1 FROM passkeys p WHERE p.user_id = u.id)
+            GROUP BY u.tenant_id
+            ORDER BY users DESC
+            "#,
+        )
+        .bind(tenant_id.map(|id| id.to_string()))
+        .bind(tenant_id.map(|id| id.to_string()))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut counts = Vec::with_capacity(rows.len());
+        for row in rows {
+            let tenant_id: String = row.try_get("tenant_id")?;
+            let users: i64 = row.try_get("users")?;
+            counts.push(PasswordOnlyCount {
+                tenant_id: Uuid::parse_str(&tenant_id).unwrap_or_default(),
+                users: users as u64,
+            });
+        }
+        Ok(counts)
+    }
+
+    async fn password_only_users(
+        &self,
+        tenant_id: Uuid,
+        after: Option<Uuid>,
+        limit: usize,
+    ) -> Result<Vec<PasswordOnlyUser>, AuthError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, tenant_id, email, email_verified, email_verified_at, phone, phone_verified, phone_verified_at, username, password_hash, password_changed_at, failed_login_attempts, locked_until, last_login_at, last_login_ip, mfa_enabled, mfa_secret, backup_codes, risk_score, profile_data, preferences, status, created_at, updated_at, deleted_at, identifier_type, primary_identifier
+            FROM users
+            WHERE tenant_id = ? AND password_hash IS NOT NULL AND deleted_at IS NULL AND id > ?
+              AND NOT EXISTS (SELECT 1 FROM passkeys p WHERE p.user_id = users.id)
+            ORDER BY id
+            LIMIT ?
+            "#,
+        )
+        .bind(tenant_id.to_string())
+        .bind(after.map(|id| id.to_string()).unwrap_or_default())
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut users = Vec::with_capacity(rows.len());
+        for row in rows {
+            let user = self.reveal(self.map_row(row)?).await?;
+            if matches!(user.status, UserStatus::Active) {
+                users.push(PasswordOnlyUser {
+                    user_id: user.id,
+                    tenant_id: user.tenant_id,
+                    email: user.email,
+                });
+            }
+        }
+        Ok(users)
+    }
+}
+
+/// Name of the online migration that moves email lookups to
+/// `normalized_identifier`
+pub const IDENTIFIER_NORMALIZATION: &str = "identifier_normalization";
+
 #[derive(Clone)]
 pub struct UserRepository {
     pub(crate) pool: MySqlPool,
+    pii: Option<Arc<PiiProtector>>,
+    identifiers: Arc<IdentifierNormalizer>,
+    normalization: Option<DualWrite>,
 }
 
 impl UserRepository {
     pub fn new(pool: MySqlPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            pii: None,
+            identifiers: Arc::new(IdentifierNormalizer::default()),
+            normalization: None,
+        }
+    }
+
+    /// Encrypt email/phone at rest and look them up through blind indexes
+    pub fn with_pii_protection(mut self, pii: Arc<PiiProtector>) -> Self {
+        self.pii = Some(pii);
+        self
+    }
+
+    /// Store the canonical email in `normalized_identifier` and look it up
+    /// there, as far as `route` (the `identifier_normalization` online
+    /// migration) has got
+    pub fn with_identifier_normalization(
+        mut self,
+        identifiers: Arc<IdentifierNormalizer>,
+        route: DualWrite,
+    ) -> Self {
+        self.identifiers = identifiers;
+        self.normalization = Some(route);
+        self
+    }
+
+    fn normalization_phase(&self) -> MigrationPhase {
+        self.normalization
+            .as_ref()
+            .map(DualWrite::phase)
+            .unwrap_or_default()
+    }
+
+    /// Value of `normalized_identifier` for an email. Under PII protection
+    /// the canonical form is itself personal data, so its blind index is
+    /// stored instead.
+    fn normalized_email(&self, tenant_id: Uuid, email: &str) -> String {
+        let canonical = self.identifiers.email(tenant_id, email);
+        match &self.pii {
+            Some(pii) => pii.blind_index(tenant_id, PiiField::Email, &canonical),
+            None => canonical,
+        }
     }
 
     pub async fn create(
@@ -118,21 +394,52 @@ impl UserRepository {
 
         let profile = serde_json::to_value(&request.profile_data).unwrap_or(serde_json::json!({}));
 
+        let (email, email_bidx) = self
+            .protect(tenant_id, PiiField::Email, request.email.as_deref())
+            .await?;
+        let (phone, phone_bidx) = self
+            .protect(tenant_id, PiiField::Phone, request.phone.as_deref())
+            .await?;
+        let normalized = request
+            .email
+            .as_deref()
+            .filter(|_| self.normalization_phase().writes_target())
+            .map(|email| self.normalized_email(tenant_id, email));
+        // The policy was checked by the caller; this only folds
+        let username = request.username.as_deref().map(str::trim);
+        let username_normalized = username.map(|u| self.identifiers.username(tenant_id, u));
+        let primary = request
+            .primary_identifier
+            .unwrap_or(match request.identifier_type {
+                IdentifierType::Phone => PrimaryIdentifier::Phone,
+                IdentifierType::Username => PrimaryIdentifier::Username,
+                IdentifierType::Email | IdentifierType::Both => PrimaryIdentifier::Email,
+            });
+
         // 1. INSERT
         sqlx::query(
             r#"
             INSERT INTO users (
-                id, tenant_id, email, password_hash, status, 
-                created_at, updated_at, email_verified, phone_verified,
+                id, tenant_id, email, email_bidx, normalized_identifier, phone, phone_bidx,
+                username, username_normalized, identifier_type, primary_identifier,
+                password_hash, status, created_at, updated_at, email_verified, phone_verified,
                 failed_login_attempts, risk_score, mfa_enabled,
                 profile_data, preferences
             )
-            VALUES (?, ?, ?, ?, ?, ?, ?, false, false, 0, 0.0, false, ?, '{}')
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, false, false, 0, 0.0, false, ?, '{}')
             "#,
         )
         .bind(id.to_string())
         .bind(tenant_id.to_string())
-        .bind(&request.email)
+        .bind(email)
+        .bind(email_bidx)
+        .bind(normalized)
+        .bind(phone)
+        .bind(phone_bidx)
+        .bind(username)
+        .bind(username_normalized)
+        .bind(request.identifier_type)
+        .bind(primary)
         .bind(&password_hash)
         .bind(&status_str)
         .bind(now)
@@ -149,30 +456,47 @@ impl UserRepository {
         &self,
         email: &str,
         tenant_id: Uuid,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let legacy = self.find_by_pii(PiiField::Email, email, tenant_id);
+        let Some(route) = &self.normalization else {
+            return legacy.await;
+        };
+        let (repository, normalized) = (self.clone(), self.normalized_email(tenant_id, email));
+        let target = async move { repository.find_by_normalized(&normalized, tenant_id).await };
+        route
+            .read_by(legacy, target, |user: &Option<User>| {
+                user.as_ref().map(|u| u.id)
+            })
+            .await
+    }
+
+    async fn find_by_normalized(
+        &self,
+        normalized: &str,
+        tenant_id: Uuid,
     ) -> Result<Option<User>, sqlx::Error> {
         let row = sqlx::query(
             r#"
-            SELECT id, tenant_id, email, email_verified, email_verified_at, phone, phone_verified, phone_verified_at, password_hash, password_changed_at, failed_login_attempts, locked_until, last_login_at, last_login_ip, mfa_enabled, mfa_secret, backup_codes, risk_score, profile_data, preferences, status, created_at, updated_at, deleted_at, identifier_type, primary_identifier
-            FROM users 
-            WHERE email = ? AND tenant_id = ? AND deleted_at IS NULL
+            SELECT id, tenant_id, email, email_verified, email_verified_at, phone, phone_verified, phone_verified_at, username, password_hash, password_changed_at, failed_login_attempts, locked_until, last_login_at, last_login_ip, mfa_enabled, mfa_secret, backup_codes, risk_score, profile_data, preferences, status, created_at, updated_at, deleted_at, identifier_type, primary_identifier
+            FROM users
+            WHERE normalized_identifier = ? AND tenant_id = ? AND deleted_at IS NULL
             "#,
         )
-        .bind(email)
+        .bind(normalized)
         .bind(tenant_id.to_string())
         .fetch_optional(&self.pool)
         .await?;
 
-        if let Some(row) = row {
-            Ok(Some(self.map_row(row)?))
-        } else {
-            Ok(None)
+        match row {
+            Some(row) => Ok(Some(self.reveal(self.map_row(row)?).await?)),
+            None => Ok(None),
         }
     }
 
     pub async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, sqlx::Error> {
         let row = sqlx::query(
             r#"
-            SELECT id, tenant_id, email, email_verified, email_verified_at, phone, phone_verified, phone_verified_at, password_hash, password_changed_at, failed_login_attempts, locked_until, last_login_at, last_login_ip, mfa_enabled, mfa_secret, backup_codes, risk_score, profile_data, preferences, status, created_at, updated_at, deleted_at, identifier_type, primary_identifier
+            SELECT id, tenant_id, email, email_verified, email_verified_at, phone, phone_verified, phone_verified_at, username, password_hash, password_changed_at, failed_login_attempts, locked_until, last_login_at, last_login_ip, mfa_enabled, mfa_secret, backup_codes, risk_score, profile_data, preferences, status, created_at, updated_at, deleted_at, identifier_type, primary_identifier
             FROM users 
             WHERE id = ?
             "#,
@@ -182,7 +506,7 @@ impl UserRepository {
         .await?;
 
         if let Some(row) = row {
-            Ok(Some(self.map_row(row)?))
+            Ok(Some(self.reveal(self.map_row(row)?).await?))
         } else {
             Ok(None)
         }
@@ -207,6 +531,7 @@ impl UserRepository {
             phone: row.try_get("phone")?,
             phone_verified: row.try_get("phone_verified")?,
             phone_verified_at: row.try_get("phone_verified_at").unwrap_or(None),
+            username: row.try_get("username")?,
             password_hash: Some(row.try_get("password_hash")?),
             password_changed_at: row.try_get("password_changed_at")?,
             failed_login_attempts: row.try_get::<i32, _>("failed_login_attempts").unwrap_or(0)
@@ -295,12 +620,16 @@ impl UserRepository {
         id: Uuid,
         password_hash: String,
     ) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE users SET password_hash = ?, updated_at = ? WHERE id = ?")
-            .bind(password_hash)
-            .bind(Utc::now())
-            .bind(id.to_string())
-            .execute(&self.pool)
-            .await?;
+        let now = Utc::now();
+        sqlx::query(
+            "UPDATE users SET password_hash = ?, password_changed_at = ?, updated_at = ? WHERE id = ?",
+        )
+        .bind(password_hash)
+        .bind(now)
+        .bind(now)
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 
@@ -329,22 +658,75 @@ impl UserRepository {
         phone: &str,
         tenant_id: Uuid,
     ) -> Result<Option<User>, sqlx::Error> {
-        let row = sqlx::query(
+        self.find_by_pii(PiiField::Phone, phone, tenant_id).await
+    }
+
+    /// Lookup on a PII column. With protection enabled the blind index is
+    /// matched, falling back to plaintext for rows not yet backfilled.
+    async fn find_by_pii(
+        &self,
+        field: PiiField,
+        value: &str,
+        tenant_id: Uuid,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let column = field.as_str();
+        let condition = match &self.pii {
+            Some(_) => format!(
+                "({column}_bidx = ? OR ({column}_bidx IS NULL AND {column} = ?))",
+                column = column
+            ),
+            None => format!("{} = ?", column),
+        };
+        let sql = format!(
             r#"
-            SELECT id, tenant_id, email, email_verified, email_verified_at, phone, phone_verified, phone_verified_at, password_hash, password_changed_at, failed_login_attempts, locked_until, last_login_at, last_login_ip, mfa_enabled, mfa_secret, backup_codes, risk_score, profile_data, preferences, status, created_at, updated_at, deleted_at, identifier_type, primary_identifier
+            SELECT id, tenant_id, email, email_verified, email_verified_at, phone, phone_verified, phone_verified_at, username, password_hash, password_changed_at, failed_login_attempts, locked_until, last_login_at, last_login_ip, mfa_enabled, mfa_secret, backup_codes, risk_score, profile_data, preferences, status, created_at, updated_at, deleted_at, identifier_type, primary_identifier
             FROM users 
-            WHERE phone = ? AND tenant_id = ? AND deleted_at IS NULL
+            WHERE {} AND tenant_id = ? AND deleted_at IS NULL
+            "#,
+            condition
+        );
+
+        let mut query = sqlx::query(&sql);
+        if let Some(pii) = &self.pii {
+            query = query.bind(pii.blind_index(tenant_id, field, value));
+        }
+        let row = query
+            .bind(value)
+            .bind(tenant_id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match row {
+            Some(row) => Ok(Some(self.reveal(self.map_row(row)?).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Lookup by the tenant's canonical form of `username`. Tenants without
+    /// usernames enabled find no one, whatever rows exist.
+    pub async fn find_by_username(
+        &self,
+        username: &str,
+        tenant_id: Uuid,
+    ) -> Result<Option<User>, sqlx::Error> {
+        if !self.identifiers.usernames_enabled(tenant_id) {
+            return Ok(None);
+        }
+        let row = sqlx::query(
+            r#"
+            SELECT id, tenant_id, email, email_verified, email_verified_at, phone, phone_verified, phone_verified_at, username, password_hash, password_changed_at, failed_login_attempts, locked_until, last_login_at, last_login_ip, mfa_enabled, mfa_secret, backup_codes, risk_score, profile_data, preferences, status, created_at, updated_at, deleted_at, identifier_type, primary_identifier
+            FROM users
+            WHERE username_normalized = ? AND tenant_id = ? AND deleted_at IS NULL
             "#,
         )
-        .bind(phone)
+        .bind(self.identifiers.username(tenant_id, username))
         .bind(tenant_id.to_string())
         .fetch_optional(&self.pool)
         .await?;
 
-        if let Some(row) = row {
-            Ok(Some(self.map_row(row)?))
-        } else {
-            Ok(None)
+        match row {
+            Some(row) => Ok(Some(self.reveal(self.map_row(row)?).await?)),
+            None => Ok(None),
         }
     }
 
@@ -353,32 +735,64 @@ impl UserRepository {
         identifier: &str,
         tenant_id: Uuid,
     ) -> Result<Option<User>, sqlx::Error> {
-        // Try to find by either email or phone depending on the format
-        // First, try email
-        if identifier.contains('@') {
-            self.find_by_email(identifier, tenant_id).await
-        } else {
-            // Assume it's a phone number
-            self.find_by_phone(identifier, tenant_id).await
+        match detect_identifier_type(identifier) {
+            IdentifierType::Phone => self.find_by_phone(identifier, tenant_id).await,
+            IdentifierType::Username => self.find_by_username(identifier, tenant_id).await,
+            IdentifierType::Email | IdentifierType::Both => {
+                self.find_by_email(identifier, tenant_id).await
+            }
         }
     }
 
     pub async fn update(&self, request: UpdateUserRequest) -> Result<User, sqlx::Error> {
+        let protects = self.pii.is_some() && (request.email.is_some() || request.phone.is_some());
+        let normalizes = request.email.is_some() && self.normalization_phase().writes_target();
+        let tenant_id = if protects || normalizes {
+            let user = self.find_by_id(request.id).await?;
+            Some(user.ok_or(sqlx::Error::RowNotFound)?.tenant_id)
+        } else {
+            None
+        };
+        let normalized = match (&request.email, tenant_id) {
+            (Some(email), Some(tenant_id)) if normalizes => {
+                Some(self.normalized_email(tenant_id, email))
+            }
+            _ => None,
+        };
+        let (email, email_bidx, phone, phone_bidx) = match tenant_id {
+            Some(tenant_id) if protects => {
+                let (email, email_bidx) = self
+                    .protect(tenant_id, PiiField::Email, request.email.as_deref())
+                    .await?;
+                let (phone, phone_bidx) = self
+                    .protect(tenant_id, PiiField::Phone, request.phone.as_deref())
+                    .await?;
+                (email, email_bidx, phone, phone_bidx)
+            }
+            _ => (request.email, None, request.phone, None),
+        };
+
         // Update only the fields that are provided
         sqlx::query(
             r#"
             UPDATE users 
             SET 
                 email = COALESCE(?, email),
+                email_bidx = COALESCE(?, email_bidx),
+                normalized_identifier = COALESCE(?, normalized_identifier),
                 phone = COALESCE(?, phone),
+                phone_bidx = COALESCE(?, phone_bidx),
                 profile_data = COALESCE(?, profile_data),
                 preferences = COALESCE(?, preferences),
                 updated_at = ?
             WHERE id = ?
             "#,
         )
-        .bind(request.email)
-        .bind(request.phone)
+        .bind(email)
+        .bind(email_bidx)
+        .bind(normalized)
+        .bind(phone)
+        .bind(phone_bidx)
         .bind(
             request
                 .profile_data
@@ -401,4 +815,172 @@ impl UserRepository {
             .await?
             .ok_or(sqlx::Error::RowNotFound)
     }
+
+    /// Encrypt up to `batch_size` users still holding plaintext PII and fill
+    /// in their blind indexes. Returns the number of rows migrated, so callers
+    /// loop until it reaches zero.
+    pub async fn backfill_pii_batch(&self, batch_size: u32) -> Result<usize, sqlx::Error> {
+        let Some(pii) = &self.pii else {
+            return Ok(0);
+        };
+
+        let rows = sqlx::query(
+            r#"
+            SELECT id, tenant_id, email, phone
+            FROM users
+            WHERE (email IS NOT NULL AND email_bidx IS NULL)
+               OR (phone IS NOT NULL AND phone_bidx IS NULL)
+            LIMIT ?
+            /* tenant:unscoped backfill of all tenants */
+            "#,
+        )
+        .bind(batch_size)
+        .fetch_all(&self.pool)
+        .await?;
+
+        for row in &rows {
+            let id: String = row.try_get("id")?;
+            let tenant_id = Uuid::parse_str(&row.try_get::<String, _>("tenant_id")?)
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+            // A row may be half-migrated: decrypt first so the index is over plaintext
+            let mut plain = Vec::with_capacity(2);
+            for field in [PiiField::Email, PiiField::Phone] {
+                let stored: Option<String> = row.try_get(field.as_str())?;
+                let value = match stored {
+                    Some(v) => Some(pii.decrypt(tenant_id, field, &v).await.map_err(pii_err)?),
+                    None => None,
+                };
+                plain.push(value);
+            }
+
+            let (email, email_bidx) = self
+                .protect(tenant_id, PiiField::Email, plain[0].as_deref())
+                .await?;
+            let (phone, phone_bidx) = self
+                .protect(tenant_id, PiiField::Phone, plain[1].as_deref())
+                .await?;
+
+            sqlx::query(
+                "UPDATE users SET email = ?, email_bidx = ?, phone = ?, phone_bidx = ? WHERE id = ?",
+            )
+            .bind(email)
+            .bind(email_bidx)
+            .bind(phone)
+            .bind(phone_bidx)
+            .bind(&id)
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok(rows.len())
+    }
+
+    /// Fill in `normalized_identifier` for up to `batch_size` users with an
+    /// id after `after`, in id order. A user whose canonical email another
+    /// user of the tenant already holds keeps a null and is reported in
+    /// `conflicts`; one of the two accounts has to be merged or renamed.
+    pub async fn backfill_normalized_batch(
+        &self,
+        after: &str,
+        batch_size: u32,
+    ) -> Result<NormalizedBatch, sqlx::Error> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, tenant_id, email
+            FROM users
+            WHERE id > ? AND email IS NOT NULL AND normalized_identifier IS NULL
+            ORDER BY id
+            LIMIT ?
+            /* tenant:unscoped backfill of all tenants */
+            "#,
+        )
+        .bind(after)
+        .bind(batch_size)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut batch = NormalizedBatch::default();
+        for row in &rows {
+            let id: String = row.try_get("id")?;
+            let tenant_id = Uuid::parse_str(&row.try_get::<String, _>("tenant_id")?)
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+            let stored: String = row.try_get("email")?;
+            let email = match &self.pii {
+                Some(pii) => pii
+                    .decrypt(tenant_id, PiiField::Email, &stored)
+                    .await
+                    .map_err(pii_err)?,
+                None => stored,
+            };
+
+            let result = sqlx::query("UPDATE users SET normalized_identifier = ? WHERE id = ?")
+                .bind(self.normalized_email(tenant_id, &email))
+                .bind(&id)
+                .execute(&self.pool)
+                .await;
+            match result {
+                Ok(_) => batch.updated += 1,
+                Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                    batch.conflicts.push(id.clone())
+                }
+                Err(e) => return Err(e),
+            }
+            batch.last_id = Some(id);
+        }
+
+        Ok(batch)
+    }
+
+    /// Value to store and its blind index. Without PII protection the value
+    /// is stored as-is and no index is written.
+    async fn protect(
+        &self,
+        tenant_id: Uuid,
+        field: PiiField,
+        value: Option<&str>,
+    ) -> Result<(Option<String>, Option<String>), sqlx::Error> {
+        match (&self.pii, value) {
+            (Some(pii), Some(v)) => {
+                let encrypted = pii.encrypt(tenant_id, field, v).await.map_err(pii_err)?;
+                Ok((Some(encrypted), Some(pii.blind_index(tenant_id, field, v))))
+            }
+            (_, v) => Ok((v.map(str::to_string), None)),
+        }
+    }
+
+    /// Decrypt PII columns of a freshly mapped row
+    async fn reveal(&self, mut user: User) -> Result<User, sqlx::Error> {
+        if let Some(pii) = &self.pii {
+            if let Some(email) = &user.email {
+                user.email = Some(
+                    pii.decrypt(user.tenant_id, PiiField::Email, email)
+                        .await
+                        .map_err(pii_err)?,
+                );
+            }
+            if let Some(phone) = &user.phone {
+                user.phone = Some(
+                    pii.decrypt(user.tenant_id, PiiField::Phone, phone)
+                        .await
+                        .map_err(pii_err)?,
+                );
+            }
+        }
+        Ok(user)
+    }
+}
+
+/// One batch of [`UserRepository::backfill_normalized_batch`]
+#[derive(Debug, Default)]
+pub struct NormalizedBatch {
+    pub updated: usize,
+    /// Ids of users left without a canonical email
+    pub conflicts: Vec<String>,
+    /// Where the next batch starts; `None` once every user has been seen
+    pub last_id: Option<String>,
+}
+
+fn pii_err(e: EncryptionError) -> sqlx::Error {
+    sqlx::Error::Protocol(format!("PII encryption failed: {}", e))
 }
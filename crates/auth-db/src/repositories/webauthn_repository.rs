@@ -1,7 +1,9 @@
+use crate::error::db_err;
 use async_trait::async_trait;
-use auth_core::services::webauthn_service::{Passkey, WebauthnStore};
+use auth_core::error::AuthError;
+use auth_core::services::webauthn_service::{credential_key, Passkey, WebauthnStore};
 use serde::{Deserialize, Serialize};
-use sqlx::{MySql, Pool};
+use sqlx::{MySql, Pool, Row};
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,10 +25,16 @@ impl WebauthnRepository {
     }
 }
 
+fn encode_err(e: serde_json::Error) -> AuthError {
+    AuthError::DatabaseError {
+        message: format!("Invalid stored passkey: {}", e),
+    }
+}
+
 #[async_trait]
 impl WebauthnStore for WebauthnRepository {
-    async fn save_passkey(&self, user_id: Uuid, passkey: &Passkey) -> anyhow::Result<()> {
-        let passkey_json = serde_json::to_string(passkey).unwrap();
+    async fn save_passkey(&self, user_id: Uuid, passkey: &Passkey) -> Result<(), AuthError> {
+        let passkey_json = serde_json::to_string(passkey).map_err(encode_err)?;
 
         sqlx::query(
             r#"
@@ -34,12 +42,42 @@ impl WebauthnStore for WebauthnRepository {
             VALUES (?, ?, ?, NOW())
             "#,
         )
-        .bind(passkey.cred_id().to_string())
+        .bind(credential_key(passkey.cred_id()))
         .bind(user_id.to_string())
         .bind(passkey_json)
         .execute(&self.pool)
         .await
-        .map_err(|e| anyhow::anyhow!("Database error: {}", e))?; // Convert sqlx::Error to anyhow::Error
+        .map_err(db_err)?;
+
+        Ok(())
+    }
+
+    async fn passkeys(&self, user_id: Uuid) -> Result<Vec<Passkey>, AuthError> {
+        let rows =
+            sqlx::query("SELECT passkey_json FROM passkeys WHERE user_id = ? ORDER BY created_at")
+                .bind(user_id.to_string())
+                .fetch_all(&self.pool)
+                .await
+                .map_err(db_err)?;
+
+        rows.into_iter()
+            .map(|row| {
+                let json: String = row.try_get("passkey_json").map_err(db_err)?;
+                serde_json::from_str(&json).map_err(encode_err)
+            })
+            .collect()
+    }
+
+    async fn update_passkey(&self, user_id: Uuid, passkey: &Passkey) -> Result<(), AuthError> {
+        let passkey_json = serde_json::to_string(passkey).map_err(encode_err)?;
+
+        sqlx::query("UPDATE passkeys SET passkey_json = ? WHERE id = ? AND user_id = ?")
+            .bind(passkey_json)
+            .bind(credential_key(passkey.cred_id()))
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(db_err)?;
 
         Ok(())
     }
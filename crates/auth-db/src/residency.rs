@@ -0,0 +1,198 @@
+//! Tenant data residency
+//!
+//! A tenant pinned to a region (`tenants.data_region`) keeps its rows in
+//! that region's database. A deployment serves one home region from its
+//! primary pool and may hold pools for other regions it is allowed to reach;
+//! repository operations for a tenant go through [`RegionRouter::pool_for`],
+//! which refuses tenants pinned to any other region rather than touching
+//! the wrong database. Unpinned tenants live in the primary database.
+
+use crate::error::db_err;
+use auth_core::error::AuthError;
+use sqlx::{MySqlPool, Row};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use uuid::Uuid;
+
+pub struct RegionRouter {
+    region: Option<String>,
+    primary: MySqlPool,
+    pools: HashMap<String, MySqlPool>,
+    pins: RwLock<HashMap<Uuid, String>>,
+}
+
+impl RegionRouter {
+    /// A router serving `region`, or no region at all, from `primary`
+    pub fn new(region: Option<String>, primary: MySqlPool) -> Self {
+        Self {
+            region,
+            primary,
+            pools: HashMap::new(),
+            pins: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Route tenants pinned to `region` to `pool`
+    pub fn with_region_pool(mut self, region: impl Into<String>, pool: MySqlPool) -> Self {
+        self.pools.insert(region.into(), pool);
+        self
+    }
+
+    /// The deployment's home region
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    pub fn pin_tenant(&self, tenant_id: Uuid, region: impl Into<String>) {
+        self.pins
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(tenant_id, region.into());
+    }
+
+    /// Region the tenant is pinned to, if any
+    pub fn tenant_region(&self, tenant_id: Uuid) -> Option<String> {
+        self.pins
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&tenant_id)
+            .cloned()
+    }
+
+    /// The pool holding the tenant's rows
+    pub fn pool_for(&self, tenant_id: Uuid) -> Result<MySqlPool, AuthError> {
+        let Some(pinned) = self.tenant_region(tenant_id) else {
+            return Ok(self.primary.clone());
+        };
+        if self.region.as_deref() == Some(pinned.as_str()) {
+            return Ok(self.primary.clone());
+        }
+        match self.pools.get(&pinned) {
+            Some(pool) => Ok(pool.clone()),
+            None => {
+                tracing::warn!(tenant_id = %tenant_id, region = %pinned, "Refused tenant pinned to another region");
+                Err(AuthError::TenantMisrouted {
+                    tenant_id: tenant_id.to_string(),
+                    region: pinned,
+                })
+            }
+        }
+    }
+
+    /// Replace the pins with those recorded in the primary database
+    pub async fn load_pins(&self) -> Result<usize, AuthError> {
+        let rows = sqlx::query("SELECT id, data_region FROM tenants WHERE data_region IS NOT NULL")
+            .fetch_all(&self.primary)
+            .await
+            .map_err(db_err)?;
+
+        let mut pins = HashMap::with_capacity(rows.len());
+        for row in rows {
+            let id: String = row.try_get("id").map_err(db_err)?;
+            let region: String = row.try_get("data_region").map_err(db_err)?;
+            match Uuid::parse_str(&id) {
+                Ok(id) => {
+                    pins.insert(id, region);
+                }
+                Err(_) => tracing::warn!(tenant_id = %id, "Skipping pin of malformed tenant id"),
+            }
+        }
+        let count = pins.len();
+        *self.pins.write().unwrap_or_else(|e| e.into_inner()) = pins;
+        Ok(count)
+    }
+
+    /// Startup check. A deployment without a home region would serve
+    /// pinned tenants from whatever database it has, so it refuses to start
+    /// while any tenant is pinned. Tenants pinned to regions this
+    /// deployment cannot reach are refused per request, and counted here.
+    pub fn verify(&self) -> Result<(), AuthError> {
+        let pins = self.pins.read().unwrap_or_else(|e| e.into_inner());
+        let Some(region) = &self.region else {
+            return match pins.len() {
+                0 => Ok(()),
+                pinned => Err(AuthError::ConfigurationError {
+                    message: format!(
+                        "{} tenants are pinned to a data region but database.residency.region is not set",
+                        pinned
+                    ),
+                }),
+            };
+        };
+        let elsewhere = pins
+            .values()
+            .filter(|pinned| *pinned != region && !self.pools.contains_key(*pinned))
+            .count();
+        if elsewhere > 0 {
+            tracing::info!(
+                region = %region,
+                "{} tenants are pinned to other regions and will be refused here",
+                elsewhere
+            );
+        }
+        Ok(())
+    }
+
+    /// Reload the pins every `interval`, so a tenant pinned after startup
+    /// is routed without a restart
+    pub async fn run_refresh(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.load_pins().await {
+                tracing::warn!("Failed to reload tenant data regions: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(name: &str) -> MySqlPool {
+        MySqlPool::connect_lazy(&format!("mysql://localhost/{}", name)).unwrap()
+    }
+
+    fn database(pool: &MySqlPool) -> String {
+        pool.connect_options().get_database().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_tenants_are_routed_to_their_region() {
+        let router = RegionRouter::new(Some("eu".to_string()), pool("eu"))
+            .with_region_pool("eu-2", pool("eu2"));
+        let (unpinned, home, routed, elsewhere) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        router.pin_tenant(home, "eu");
+        router.pin_tenant(routed, "eu-2");
+        router.pin_tenant(elsewhere, "us");
+
+        assert_eq!(database(&router.pool_for(unpinned).unwrap()), "eu");
+        assert_eq!(database(&router.pool_for(home).unwrap()), "eu");
+        assert_eq!(database(&router.pool_for(routed).unwrap()), "eu2");
+        assert!(matches!(
+            router.pool_for(elsewhere),
+            Err(AuthError::TenantMisrouted { region, .. }) if region == "us"
+        ));
+        assert!(router.verify().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_deployment_without_region_refuses_pinned_tenants() {
+        let router = RegionRouter::new(None, pool("primary"));
+        assert!(router.verify().is_ok());
+
+        router.pin_tenant(Uuid::new_v4(), "eu");
+        assert!(matches!(
+            router.verify(),
+            Err(AuthError::ConfigurationError { .. })
+        ));
+    }
+}
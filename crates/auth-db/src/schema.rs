@@ -0,0 +1,317 @@
+//! Schema drift detection
+//!
+//! Repositories declare the columns they read and write as a
+//! [`TableSchema`] next to their queries. At startup these are compared
+//! with `information_schema`, so a migration that was skipped, reverted by
+//! hand or written differently from what the code expects is reported
+//! before a query fails on it.
+//!
+//! Only what the code depends on is checked: that each column exists, that
+//! its type is of the expected kind, and that columns the code writes NULL
+//! to accept NULL. Columns the code does not use are ignored.
+
+use sqlx::{MySqlPool, Row};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Kind of value the code reads from or writes to a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    Text,
+    Integer,
+    Boolean,
+    Decimal,
+    Timestamp,
+    Json,
+    Binary,
+}
+
+impl ColumnKind {
+    /// `information_schema.COLUMNS.DATA_TYPE` values that hold this kind
+    fn accepts(&self, data_type: &str) -> bool {
+        let accepted: &[&str] = match self {
+            ColumnKind::Text => &[
+                "char",
+                "varchar",
+                "tinytext",
+                "text",
+                "mediumtext",
+                "longtext",
+                "enum",
+            ],
+            ColumnKind::Integer => &["tinyint", "smallint", "mediumint", "int", "bigint"],
+            ColumnKind::Boolean => &["tinyint", "bit"],
+            ColumnKind::Decimal => &["decimal", "float", "double"],
+            ColumnKind::Timestamp => &["timestamp", "datetime"],
+            // MariaDB reports JSON columns as longtext
+            ColumnKind::Json => &["json", "longtext"],
+            ColumnKind::Binary => &[
+                "binary",
+                "varbinary",
+                "tinyblob",
+                "blob",
+                "mediumblob",
+                "longblob",
+            ],
+        };
+        accepted.contains(&data_type.to_ascii_lowercase().as_str())
+    }
+}
+
+impl fmt::Display for ColumnKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnSchema {
+    pub name: &'static str,
+    pub kind: ColumnKind,
+    /// The code writes NULL to it
+    pub nullable: bool,
+}
+
+impl ColumnSchema {
+    pub const fn new(name: &'static str, kind: ColumnKind) -> Self {
+        Self {
+            name,
+            kind,
+            nullable: false,
+        }
+    }
+
+    pub const fn nullable(mut self) -> Self {
+        self.nullable = true;
+        self
+    }
+}
+
+/// Columns a repository depends on
+#[derive(Debug, Clone, Copy)]
+pub struct TableSchema {
+    pub table: &'static str,
+    pub columns: &'static [ColumnSchema],
+}
+
+/// Tables whose repositories declare their columns
+pub fn expected_schema() -> Vec<&'static TableSchema> {
+    use crate::repositories::*;
+
+    vec![
+        &client_repository::SCHEMA,
+        &feature_kill_switch_repository::SCHEMA,
+        &legal_hold_repository::SCHEMA,
+        &login_event_repository::SCHEMA,
+        &login_heatmap_repository::SCHEMA,
+        &login_link_repository::SCHEMA,
+        &online_migration_repository::SCHEMA,
+        &otp_repository::SCHEMA,
+        &refresh_token_repository::SCHEMA,
+        &remember_me_repository::DEVICES_SCHEMA,
+        &remember_me_repository::TOKENS_SCHEMA,
+        &session_repository::SCHEMA,
+        &signup_quarantine_repository::SCHEMA,
+        &sso_session_repository::SCHEMA,
+        &tenant_onboarding_repository::SCHEMA,
+        &tenant_state_repository::SCHEMA,
+        &upstream_session_repository::SCHEMA,
+    ]
+}
+
+/// A column as the database has it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActualColumn {
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+/// Columns of the connected database, by table and column name
+pub type ActualSchema = HashMap<String, HashMap<String, ActualColumn>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaDrift {
+    MissingTable {
+        table: String,
+    },
+    MissingColumn {
+        table: String,
+        column: String,
+    },
+    WrongType {
+        table: String,
+        column: String,
+        expected: ColumnKind,
+        actual: String,
+    },
+    NotNullable {
+        table: String,
+        column: String,
+    },
+}
+
+impl fmt::Display for SchemaDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaDrift::MissingTable { table } => write!(f, "table {} is missing", table),
+            SchemaDrift::MissingColumn { table, column } => {
+                write!(f, "column {}.{} is missing", table, column)
+            }
+            SchemaDrift::WrongType {
+                table,
+                column,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "column {}.{} is {}, the code expects {}",
+                table, column, actual, expected
+            ),
+            SchemaDrift::NotNullable { table, column } => write!(
+                f,
+                "column {}.{} is NOT NULL, the code writes NULL to it",
+                table, column
+            ),
+        }
+    }
+}
+
+/// Differences between what the repositories expect and `actual`
+pub fn compare(expected: &[&TableSchema], actual: &ActualSchema) -> Vec<SchemaDrift> {
+    let mut drift = Vec::new();
+    for table in expected {
+        let Some(columns) = actual.get(table.table) else {
+            drift.push(SchemaDrift::MissingTable {
+                table: table.table.to_string(),
+            });
+            continue;
+        };
+        for column in table.columns {
+            let Some(found) = columns.get(column.name) else {
+                drift.push(SchemaDrift::MissingColumn {
+                    table: table.table.to_string(),
+                    column: column.name.to_string(),
+                });
+                continue;
+            };
+            if !column.kind.accepts(&found.data_type) {
+                drift.push(SchemaDrift::WrongType {
+                    table: table.table.to_string(),
+                    column: column.name.to_string(),
+                    expected: column.kind,
+                    actual: found.data_type.clone(),
+                });
+            }
+            if column.nullable && !found.nullable {
+                drift.push(SchemaDrift::NotNullable {
+                    table: table.table.to_string(),
+                    column: column.name.to_string(),
+                });
+            }
+        }
+    }
+    drift
+}
+
+/// Read the columns of the connected database
+pub async fn introspect(pool: &MySqlPool) -> Result<ActualSchema, sqlx::Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT
+            CAST(TABLE_NAME AS CHAR) AS table_name,
+            CAST(COLUMN_NAME AS CHAR) AS column_name,
+            CAST(DATA_TYPE AS CHAR) AS data_type,
+            CAST(IS_NULLABLE AS CHAR) AS is_nullable
+        FROM information_schema.COLUMNS
+        WHERE TABLE_SCHEMA = DATABASE() /* tenant:unscoped schema metadata */
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut schema = ActualSchema::new();
+    for row in rows {
+        let table: String = row.try_get("table_name")?;
+        let column: String = row.try_get("column_name")?;
+        let is_nullable: String = row.try_get("is_nullable")?;
+        schema.entry(table).or_default().insert(
+            column,
+            ActualColumn {
+                data_type: row.try_get("data_type")?,
+                nullable: is_nullable.eq_ignore_ascii_case("YES"),
+            },
+        );
+    }
+    Ok(schema)
+}
+
+/// Compare the connected database with every repository's declaration
+pub async fn verify_schema(pool: &MySqlPool) -> Result<Vec<SchemaDrift>, sqlx::Error> {
+    Ok(compare(&expected_schema(), &introspect(pool).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKENS: TableSchema = TableSchema {
+        table: "tokens",
+        columns: &[
+            ColumnSchema::new("id", ColumnKind::Text),
+            ColumnSchema::new("expires_at", ColumnKind::Timestamp),
+            ColumnSchema::new("revoked_at", ColumnKind::Timestamp).nullable(),
+            ColumnSchema::new("session_id", ColumnKind::Text).nullable(),
+        ],
+    };
+
+    fn column(data_type: &str, nullable: bool) -> ActualColumn {
+        ActualColumn {
+            data_type: data_type.to_string(),
+            nullable,
+        }
+    }
+
+    #[test]
+    fn test_drift_is_reported_per_column() {
+        let actual = ActualSchema::from([(
+            "tokens".to_string(),
+            HashMap::from([
+                ("id".to_string(), column("char", false)),
+                ("expires_at".to_string(), column("varchar", false)),
+                ("revoked_at".to_string(), column("datetime", false)),
+                ("unused".to_string(), column("int", false)),
+            ]),
+        )]);
+
+        let drift = compare(&[&TOKENS], &actual);
+        assert_eq!(drift.len(), 3, "{:?}", drift);
+        assert!(drift.contains(&SchemaDrift::MissingColumn {
+            table: "tokens".to_string(),
+            column: "session_id".to_string(),
+        }));
+        assert!(drift.iter().any(|d| matches!(
+            d,
+            SchemaDrift::WrongType { column, .. } if column == "expires_at"
+        )));
+        assert!(drift.iter().any(|d| matches!(
+            d,
+            SchemaDrift::NotNullable { column, .. } if column == "revoked_at"
+        )));
+
+        assert_eq!(
+            compare(&[&TOKENS], &ActualSchema::new()),
+            vec![SchemaDrift::MissingTable {
+                table: "tokens".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_repository_declarations_are_unique() {
+        let mut tables: Vec<&str> = expected_schema().iter().map(|t| t.table).collect();
+        tables.sort();
+        let count = tables.len();
+        tables.dedup();
+        assert_eq!(tables.len(), count);
+    }
+}
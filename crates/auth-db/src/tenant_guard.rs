@@ -0,0 +1,302 @@
+//! Tenant filters on repository queries
+//!
+//! Every row of a tenant-scoped table belongs to one tenant, so a statement
+//! on one has to say which: by a `tenant_id` predicate, or by a key unique
+//! across tenants (a row id, token hash, certificate serial) that the
+//! caller can only hold for a row it was given. [`check_statement`] checks
+//! this on the SQL text; [`tenant_query`] runs it in debug builds on queries
+//! bound to a [`TenantContext`], and the tenant isolation test runs it on
+//! every statement in the repositories. Statements that are meant to span
+//! tenants, such as expiry sweeps, say so with [`UNSCOPED_MARKER`] in a
+//! comment.
+
+use sqlx::mysql::MySqlArguments;
+use sqlx::query::Query;
+use sqlx::MySql;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Marks a statement as deliberately spanning tenants, e.g.
+/// `DELETE FROM sessions WHERE expires_at < ? /* tenant:unscoped expiry sweep */`
+pub const UNSCOPED_MARKER: &str = "tenant:unscoped";
+
+/// Tables with a `tenant_id` column, with the columns that identify a
+/// single row across tenants
+pub const TENANT_SCOPED_TABLES: &[(&str, &[&str])] = &[
+    ("authorization_audit_logs", &["id"]),
+    ("device_bootstrap_tokens", &["id", "token_hash"]),
+    ("device_certificates", &["id", "serial"]),
+    ("legal_holds", &["id"]),
+    ("login_events", &["id", "user_id"]),
+    ("login_heatmap_hourly", &[]),
+    ("login_links", &["id"]),
+    ("oauth_clients", &["id", "client_id"]),
+    ("otp_sessions", &["id"]),
+    ("permission_change_heads", &[]),
+    ("permission_changes", &[]),
+    ("permissions", &["id"]),
+    ("push_challenges", &["id"]),
+    ("push_devices", &["id", "user_id"]),
+    (
+        "refresh_tokens",
+        &["id", "token_hash", "token_family", "user_id", "session_id"],
+    ),
+    ("remember_me_tokens", &["id", "token_hash"]),
+    ("remembered_devices", &["id"]),
+    ("revoked_tokens", &["token_jti"]),
+    ("roles", &["id"]),
+    ("service_accounts", &["id"]),
+    ("sessions", &["id", "session_token", "user_id"]),
+    ("signup_quarantine", &["id"]),
+    ("sms_quarantine", &["id"]),
+    ("sms_usage_daily", &[]),
+    ("sso_client_sessions", &["sid", "session_id", "user_id"]),
+    ("tenant_data_keys", &[]),
+    ("tenant_onboarding", &[]),
+    ("tenant_subscriptions", &["id"]),
+    ("tenant_token_generations", &[]),
+    ("upstream_sessions", &["id", "session_id"]),
+    ("user_roles", &["user_id"]),
+    ("user_tenants", &["user_id"]),
+    ("users", &["id"]),
+];
+
+/// The tenant a repository call acts for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TenantContext {
+    tenant_id: Uuid,
+}
+
+impl TenantContext {
+    pub fn new(tenant_id: Uuid) -> Self {
+        Self { tenant_id }
+    }
+
+    pub fn tenant_id(&self) -> Uuid {
+        self.tenant_id
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TenantGuardError {
+    #[error("statement on {table} filters neither by tenant_id nor by a unique key")]
+    MissingFilter { table: String },
+    #[error("insert into {table} does not set tenant_id")]
+    MissingColumn { table: String },
+    #[error("first parameter of the statement on {table} is not its tenant_id")]
+    TenantNotFirst { table: String },
+}
+
+/// A query on a tenant-scoped table whose first parameter is `tenant_id`,
+/// with the tenant already bound
+pub fn tenant_query<'q>(tenant: &TenantContext, sql: &'q str) -> Query<'q, MySql, MySqlArguments> {
+    if cfg!(debug_assertions) {
+        if let Err(e) = check_statement(sql).and_then(|_| check_tenant_first(sql)) {
+            panic!("{}: {}", e, sql.trim());
+        }
+    }
+    sqlx::query(sql).bind(tenant.tenant_id.to_string())
+}
+
+/// Whether `sql` names the tenant of every tenant-scoped row it touches.
+/// Statements on other tables always pass.
+pub fn check_statement(sql: &str) -> Result<(), TenantGuardError> {
+    if sql.contains(UNSCOPED_MARKER) {
+        return Ok(());
+    }
+    let tokens = tokenize(sql);
+    let Some((table, keys)) = scoped_table(&tokens) else {
+        return Ok(());
+    };
+
+    if matches!(
+        tokens.first().map(String::as_str),
+        Some("insert" | "replace")
+    ) {
+        let mut columns = tokens
+            .iter()
+            .skip_while(|t| *t != "(")
+            .take_while(|t| *t != ")");
+        if columns.any(|t| t == "tenant_id") {
+            return Ok(());
+        }
+        return Err(TenantGuardError::MissingColumn {
+            table: table.to_string(),
+        });
+    }
+
+    if predicates(&tokens).any(|column| column == "tenant_id" || keys.contains(&column)) {
+        Ok(())
+    } else {
+        Err(TenantGuardError::MissingFilter {
+            table: table.to_string(),
+        })
+    }
+}
+
+/// Whether the first `?` of `sql` compares `tenant_id`
+fn check_tenant_first(sql: &str) -> Result<(), TenantGuardError> {
+    let tokens = tokenize(sql);
+    let table = scoped_table(&tokens).map_or("", |(table, _)| table);
+    let first = tokens.iter().position(|t| t == "?");
+    match first {
+        Some(i) if i >= 2 && tokens[i - 1] == "=" && column_name(&tokens[i - 2]) == "tenant_id" => {
+            Ok(())
+        }
+        _ => Err(TenantGuardError::TenantNotFirst {
+            table: table.to_string(),
+        }),
+    }
+}
+
+/// The first tenant-scoped table the statement reads or writes
+fn scoped_table(tokens: &[String]) -> Option<(&'static str, &'static [&'static str])> {
+    tokens
+        .windows(2)
+        .filter(|pair| matches!(pair[0].as_str(), "from" | "join" | "update" | "into"))
+        .find_map(|pair| {
+            let name = column_name(&pair[1]);
+            TENANT_SCOPED_TABLES
+                .iter()
+                .find(|(table, _)| *table == name)
+                .copied()
+        })
+}
+
+/// Columns compared with `=` or `IN` after the first `WHERE`
+fn predicates(tokens: &[String]) -> impl Iterator<Item = &str> {
+    let clause = tokens
+        .iter()
+        .position(|t| t == "where")
+        .map_or(&[][..], |i| &tokens[i + 1..]);
+    clause
+        .windows(3)
+        .filter(|w| (w[1] == "=" && !matches!(w[0].as_str(), "<" | ">" | "!")) || w[1] == "in")
+        .map(|w| column_name(&w[0]))
+}
+
+/// `t.tenant_id` and `tenant_id` alike
+fn column_name(token: &str) -> &str {
+    token.rsplit('.').next().unwrap_or(token)
+}
+
+/// Lowercased identifiers and single-character punctuation, with comments
+/// and quoted strings dropped
+fn tokenize(sql: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'*') => {
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '\'' | '"' => {
+                for next in chars.by_ref() {
+                    if next == c {
+                        break;
+                    }
+                }
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '`' => {
+                let mut ident = String::new();
+                let mut c = Some(c);
+                while let Some(ch) = c {
+                    if ch != '`' {
+                        ident.extend(ch.to_lowercase());
+                    }
+                    match chars.peek().copied() {
+                        Some(n) if n.is_alphanumeric() || matches!(n, '_' | '.' | '`') => {
+                            c = chars.next()
+                        }
+                        _ => c = None,
+                    }
+                }
+                tokens.push(ident);
+            }
+            c if c.is_whitespace() => {}
+            c => tokens.push(c.to_string()),
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_predicate_or_unique_key_required() {
+        assert!(check_statement("SELECT * FROM service_accounts WHERE tenant_id = ?").is_ok());
+        assert!(check_statement("SELECT * FROM service_accounts WHERE id = ?").is_ok());
+        assert!(check_statement(
+            "SELECT r.name FROM roles r JOIN user_roles ur ON ur.role_id = r.id \
+             WHERE ur.user_id = ? AND ur.tenant_id = ?"
+        )
+        .is_ok());
+        assert_eq!(
+            check_statement("SELECT * FROM service_accounts WHERE name = ?"),
+            Err(TenantGuardError::MissingFilter {
+                table: "service_accounts".to_string()
+            })
+        );
+        // A comparison is not a filter on the key
+        assert!(check_statement("DELETE FROM `sessions` WHERE id > ?").is_err());
+        assert!(check_statement("SELECT * FROM push_devices WHERE id != ?").is_err());
+        assert!(check_statement("UPDATE service_accounts SET status = ?").is_err());
+        // Strings and comments are not predicates
+        assert!(check_statement("SELECT * FROM roles WHERE name = 'tenant_id = 1'").is_err());
+        // Emails are unique per tenant only
+        assert_eq!(
+            check_statement("SELECT * FROM users WHERE email = ?"),
+            Err(TenantGuardError::MissingFilter {
+                table: "users".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_inserts_must_set_tenant() {
+        assert!(
+            check_statement("INSERT INTO roles (id, tenant_id, name) VALUES (?, ?, ?)").is_ok()
+        );
+        assert_eq!(
+            check_statement("INSERT INTO roles (id, name) VALUES (?, ?)"),
+            Err(TenantGuardError::MissingColumn {
+                table: "roles".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_unscoped_marker_and_tenant_first() {
+        assert!(check_statement(
+            "DELETE FROM sessions WHERE expires_at < ? /* tenant:unscoped expiry sweep */"
+        )
+        .is_ok());
+        assert!(check_tenant_first("SELECT * FROM roles WHERE tenant_id = ? AND id = ?").is_ok());
+        assert!(check_tenant_first("SELECT * FROM roles WHERE id = ? AND tenant_id = ?").is_err());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "filters neither by tenant_id")]
+    fn test_tenant_query_asserts_filter() {
+        let _ = tenant_query(
+            &TenantContext::new(Uuid::new_v4()),
+            "SELECT * FROM service_accounts WHERE name = ?",
+        );
+    }
+}
@@ -1,7 +1,11 @@
 pub mod graphql;
 pub mod plugin;
 pub mod webhook;
+pub mod webhook_schema;
 
 pub use graphql::create_schema;
 pub use plugin::PluginEngine;
-pub use webhook::WebhookDispatcher;
+pub use webhook::{WebhookDelivery, WebhookDispatcher, WebhookRelay};
+pub use webhook_schema::{
+    parse_envelope, EnvelopeV1, EnvelopeV2, WebhookEvent, WebhookSubscription, WebhookVersion,
+};
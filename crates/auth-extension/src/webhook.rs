@@ -1,17 +1,37 @@
-use reqwest::Client;
+use crate::webhook_schema::{
+    WebhookEvent, WebhookSubscription, WebhookVersion, WEBHOOK_VERSION_HEADER,
+};
+use auth_core::context::RequestContext;
+use auth_platform::{
+    DurableQueue, EgressConfig, EgressError, EgressPolicy, HttpClient, HttpClientConfig,
+    HttpClientError, WalError,
+};
+use chrono::{DateTime, Utc};
+use reqwest::header::LOCATION;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tracing::info;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
 
+/// Sends webhooks to tenant-registered URLs. Every destination, including
+/// each redirect target, is checked against the egress policy with the
+/// tenant of the request that caused the event.
 #[derive(Clone)]
 pub struct WebhookDispatcher {
-    _client: Client,
+    client: HttpClient,
+    egress: Arc<EgressPolicy>,
 }
 
 impl WebhookDispatcher {
-    pub fn new() -> Self {
-        Self {
-            _client: Client::new(),
-        }
+    pub fn new(http: HttpClientConfig, egress: EgressConfig) -> Result<Self, HttpClientError> {
+        let egress = Arc::new(EgressPolicy::new(egress));
+        Ok(Self {
+            client: HttpClient::with_egress_policy(http, egress.clone())?,
+            egress,
+        })
     }
 
     pub async fn dispatch(
@@ -19,39 +39,283 @@ impl WebhookDispatcher {
         url: &str,
         event: &str,
         payload: Value,
-    ) -> Result<(), reqwest::Error> {
-        info!("Dispatching webhook: {} -> {}", event, url);
-
-        let _body = serde_json::json!({
-            "event": event,
-            "timestamp": chrono::Utc::now(),
-            "payload": payload,
-        });
-
-        // In a real system, we'd add retry logic (backoff) here or via a queue.
-        // For MVP, fire and forget (await response).
-
-        // Mocking for tests requires a server, or we just rely on unit tests mocking reqwest.
-        // Or we just implementation logic.
-
-        // let res = self.client.post(url).json(&body).send().await?;
-        // res.error_for_status()?;
-
-        // For test safety (not hitting real URLs), we log only unless confident.
-        // But to implement "real" code:
-        if !url.starts_with("mock") {
-            // self.client.post(url).json(&body).send().await?;
-            // Commented out to prevent unintended network calls during 'cargo run' unless strictly controlled.
-            // We will simulate success for safety.
+    ) -> Result<(), HttpClientError> {
+        self.dispatch_with_context(url, event, payload, RequestContext::current().as_ref())
+            .await
+    }
+
+    /// Dispatch with the context of the request that caused the event, which
+    /// is carried in the envelope so receivers can quote the request id.
+    /// Sent as v1, the format of receivers without a subscription.
+    pub async fn dispatch_with_context(
+        &self,
+        url: &str,
+        event: &str,
+        payload: Value,
+        context: Option<&RequestContext>,
+    ) -> Result<(), HttpClientError> {
+        let subscription = WebhookSubscription::pinned(url, WebhookVersion::V1);
+        let event = WebhookEvent::new(event, payload, context.cloned());
+        self.dispatch_event(&subscription, &event).await
+    }
+
+    /// Send `event` in the version the subscription is pinned to
+    pub async fn dispatch_event(
+        &self,
+        subscription: &WebhookSubscription,
+        event: &WebhookEvent,
+    ) -> Result<(), HttpClientError> {
+        let url = subscription.url.as_str();
+        info!(
+            request_id = ?event.context.as_ref().map(|c| c.request_id),
+            version = subscription.version.as_str(),
+            "Dispatching webhook: {} -> {}", event.event_type, url
+        );
+
+        let body = event.render(subscription.version);
+
+        // mock:// receivers are for local runs without a listening endpoint
+        if url.starts_with("mock") {
+            info!("Webhook dispatched successfully (simulated)");
+            return Ok(());
         }
 
-        info!("Webhook dispatched successfully (simulated)");
+        let tenant_id = event.tenant_id();
+        let mut target = Url::parse(url).map_err(|e| EgressError::InvalidUrl(e.to_string()))?;
+        let mut redirects = 0;
+        loop {
+            self.egress.check_destination(tenant_id, &target).await?;
+
+            // The client retries what the receiver did not process and stops
+            // calling a receiver whose circuit is open; the relay retries the
+            // rest later
+            let response = self
+                .client
+                .send(
+                    self.client
+                        .post(target.as_str())
+                        .header(WEBHOOK_VERSION_HEADER, subscription.version.as_str())
+                        .json(&body),
+                )
+                .await?;
+            if !response.status().is_redirection() {
+                response.error_for_status()?;
+                break;
+            }
+
+            // Redirects are followed with the same POST, each target checked
+            // like the first
+            if redirects == self.egress.max_redirects() {
+                return Err(EgressError::TooManyRedirects(redirects).into());
+            }
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| {
+                    EgressError::InvalidUrl(format!("{} redirect without Location", target))
+                })?;
+            target = target
+                .join(location)
+                .map_err(|e| EgressError::InvalidUrl(e.to_string()))?;
+            redirects += 1;
+            info!("Webhook redirected to {}", target);
+        }
+
+        info!("Webhook dispatched successfully");
         Ok(())
     }
 }
 
 impl Default for WebhookDispatcher {
+    /// Default client settings and egress policy: public HTTPS hosts only
     fn default() -> Self {
-        Self::new()
+        Self::new(HttpClientConfig::default(), EgressConfig::default())
+            .expect("default webhook client configuration is valid")
+    }
+}
+
+/// A webhook persisted for delivery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    /// Deliveries queued before event ids existed are assigned one when read
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub url: String,
+    /// Version of the subscription when the event was queued
+    #[serde(default)]
+    pub version: WebhookVersion,
+    pub event: String,
+    pub payload: Value,
+    pub enqueued_at: DateTime<Utc>,
+    /// Request that caused the event; deliveries run detached from it
+    #[serde(default)]
+    pub context: Option<RequestContext>,
+}
+
+/// Delivers webhooks from a durable queue so pending deliveries survive restarts.
+/// Delivery is at-least-once: a crash between dispatch and acknowledgement
+/// resends the batch, so receivers should deduplicate.
+pub struct WebhookRelay {
+    queue: DurableQueue,
+    dispatcher: WebhookDispatcher,
+    max_attempts: u32,
+}
+
+impl WebhookRelay {
+    pub fn new(queue: DurableQueue, dispatcher: WebhookDispatcher) -> Self {
+        Self {
+            queue,
+            dispatcher,
+            max_attempts: 5,
+        }
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub async fn enqueue(
+        &self,
+        subscription: &WebhookSubscription,
+        event: &str,
+        payload: Value,
+    ) -> Result<(), WalError> {
+        let delivery = WebhookDelivery {
+            id: Uuid::new_v4(),
+            url: subscription.url.clone(),
+            version: subscription.version,
+            event: event.to_string(),
+            payload,
+            enqueued_at: Utc::now(),
+            context: RequestContext::current(),
+        };
+        let bytes = serde_json::to_vec(&delivery).map_err(|e| WalError::Io(e.into()))?;
+        self.queue.append(bytes).await
+    }
+
+    pub async fn run(self) {
+        info!("Webhook relay started");
+        loop {
+            let batch = match self.queue.next_batch(32).await {
+                Ok(batch) => batch,
+                Err(e) => {
+                    error!("Failed to read webhook queue: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+
+            for record in &batch {
+                match serde_json::from_slice::<WebhookDelivery>(&record.payload) {
+                    Ok(delivery) => self.deliver(delivery).await,
+                    Err(e) => warn!("Skipping unreadable queued webhook: {}", e),
+                }
+            }
+
+            if let Some(last) = batch.last() {
+                if let Err(e) = self.queue.ack(last.next).await {
+                    error!("Failed to acknowledge webhook batch: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Retry with exponential backoff; give up after `max_attempts`. Every
+    /// attempt sends the same event id and time.
+    async fn deliver(&self, delivery: WebhookDelivery) {
+        let subscription = WebhookSubscription::pinned(&delivery.url, delivery.version);
+        let event = WebhookEvent {
+            id: delivery.id,
+            event_type: delivery.event,
+            occurred_at: delivery.enqueued_at,
+            data: delivery.payload,
+            context: delivery.context,
+        };
+        let mut backoff = Duration::from_secs(1);
+        for attempt in 1..=self.max_attempts {
+            match self.dispatcher.dispatch_event(&subscription, &event).await {
+                Ok(()) => return,
+                Err(e) if attempt < self.max_attempts => {
+                    warn!(
+                        "Webhook {} -> {} failed (attempt {}): {}",
+                        event.event_type, subscription.url, attempt, e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                Err(e) => error!(
+                    "Giving up on webhook {} -> {} after {} attempts: {}",
+                    event.event_type, subscription.url, attempt, e
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_internal_destinations_refused_before_sending() {
+        let dispatcher = WebhookDispatcher::default();
+        for url in [
+            "https://169.254.169.254/latest/meta-data",
+            "https://127.0.0.1/hook",
+            "http://hooks.example.com/hook",
+            "https://localhost/hook",
+        ] {
+            let result = dispatcher
+                .dispatch_with_context(url, "user.created", serde_json::json!({}), None)
+                .await;
+            assert!(
+                matches!(result, Err(HttpClientError::Egress(_))),
+                "{} should be refused, got {:?}",
+                url,
+                result
+            );
+        }
+    }
+
+    /// Deliveries queued by a release without versioning are sent as v1
+    #[test]
+    fn test_queued_deliveries_without_version_read_as_v1() {
+        let delivery: WebhookDelivery = serde_json::from_value(serde_json::json!({
+            "url": "https://hooks.example.com/hook",
+            "event": "user.created",
+            "payload": {},
+            "enqueued_at": "2026-01-22T10:15:30Z",
+        }))
+        .unwrap();
+        assert_eq!(delivery.version, WebhookVersion::V1);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_allowlist_applies_to_its_events() {
+        let tenant_id = Uuid::new_v4();
+        let dispatcher = WebhookDispatcher::new(
+            HttpClientConfig::default(),
+            EgressConfig {
+                tenant_allowlists: [(tenant_id, vec!["hooks.example.com".to_string()])].into(),
+                ..EgressConfig::default()
+            },
+        )
+        .unwrap();
+        let context = RequestContext::new(Uuid::new_v4()).with_tenant(Some(tenant_id));
+
+        let result = dispatcher
+            .dispatch_with_context(
+                "https://elsewhere.example.net/hook",
+                "user.created",
+                serde_json::json!({}),
+                Some(&context),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(HttpClientError::Egress(EgressError::NotAllowlisted(_)))
+        ));
     }
 }
@@ -0,0 +1,258 @@
+//! Versioned wire format of webhooks
+//!
+//! Every webhook is built once as a [`WebhookEvent`] and rendered in the
+//! schema version its subscription is pinned to. A released version is
+//! frozen: changing what receivers get means adding a version, and the
+//! tests below hold each released version to its exact JSON.
+//! [`parse_envelope`] reads a body of any released version and translates
+//! it to the latest, so an SDK only has to model one.
+
+use auth_core::context::RequestContext;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Header naming the schema version of a delivered body
+pub const WEBHOOK_VERSION_HEADER: &str = "webhook-version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookVersion {
+    /// The original, unversioned format. The default, since receivers
+    /// registered before versioning expect it.
+    #[default]
+    V1,
+    /// Envelope with an event id for deduplication and the tenant, without
+    /// the internal request context
+    V2,
+}
+
+impl WebhookVersion {
+    /// What new subscriptions are pinned to
+    pub const LATEST: Self = Self::V2;
+
+    /// Every released version, oldest first
+    pub const ALL: [Self; 2] = [Self::V1, Self::V2];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::V1 => "v1",
+            Self::V2 => "v2",
+        }
+    }
+}
+
+/// A webhook receiver and the schema version it is pinned to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookSubscription {
+    pub url: String,
+    #[serde(default)]
+    pub version: WebhookVersion,
+}
+
+impl WebhookSubscription {
+    /// A subscription pinned to the latest version
+    pub fn new(url: impl Into<String>) -> Self {
+        Self::pinned(url, WebhookVersion::LATEST)
+    }
+
+    pub fn pinned(url: impl Into<String>, version: WebhookVersion) -> Self {
+        Self {
+            url: url.into(),
+            version,
+        }
+    }
+}
+
+/// A webhook independent of the version it is sent in
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookEvent {
+    /// The same for every delivery of the event, retries included
+    pub id: Uuid,
+    pub event_type: String,
+    pub occurred_at: DateTime<Utc>,
+    pub data: Value,
+    /// Request that caused the event
+    pub context: Option<RequestContext>,
+}
+
+impl WebhookEvent {
+    pub fn new(event_type: &str, data: Value, context: Option<RequestContext>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            event_type: event_type.to_string(),
+            occurred_at: Utc::now(),
+            data,
+            context,
+        }
+    }
+
+    pub fn tenant_id(&self) -> Option<Uuid> {
+        self.context.as_ref().and_then(|c| c.tenant_id)
+    }
+
+    /// The body sent to a receiver pinned to `version`
+    pub fn render(&self, version: WebhookVersion) -> Value {
+        let rendered = match version {
+            WebhookVersion::V1 => serde_json::to_value(EnvelopeV1 {
+                event: self.event_type.clone(),
+                timestamp: self.occurred_at,
+                request_id: self.context.as_ref().map(|c| c.request_id),
+                context: self.context.clone(),
+                payload: self.data.clone(),
+            }),
+            WebhookVersion::V2 => serde_json::to_value(EnvelopeV2 {
+                id: self.id,
+                event_type: self.event_type.clone(),
+                version: WebhookVersion::V2,
+                occurred_at: self.occurred_at,
+                tenant: self.tenant_id(),
+                request_id: self.context.as_ref().map(|c| c.request_id),
+                data: self.data.clone(),
+            }),
+        };
+        rendered.expect("envelopes serialize to JSON")
+    }
+}
+
+/// Body of a v1 webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnvelopeV1 {
+    pub event: String,
+    pub timestamp: DateTime<Utc>,
+    pub request_id: Option<Uuid>,
+    pub context: Option<RequestContext>,
+    pub payload: Value,
+}
+
+/// Body of a v2 webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnvelopeV2 {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub version: WebhookVersion,
+    pub occurred_at: DateTime<Utc>,
+    pub tenant: Option<Uuid>,
+    pub request_id: Option<Uuid>,
+    pub data: Value,
+}
+
+impl From<EnvelopeV1> for EnvelopeV2 {
+    /// v1 bodies carry no event id; the upgrade assigns a fresh one, so
+    /// receivers of v1 cannot deduplicate by it
+    fn from(v1: EnvelopeV1) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            event_type: v1.event,
+            version: WebhookVersion::V2,
+            occurred_at: v1.timestamp,
+            tenant: v1.context.and_then(|c| c.tenant_id),
+            request_id: v1.request_id,
+            data: v1.payload,
+        }
+    }
+}
+
+/// Read a webhook body of any released version as the latest. v1 bodies
+/// are recognized by their missing `version`.
+pub fn parse_envelope(body: &[u8]) -> Result<EnvelopeV2, serde_json::Error> {
+    let value: Value = serde_json::from_slice(body)?;
+    match value.get("version") {
+        None => serde_json::from_value::<EnvelopeV1>(value).map(EnvelopeV2::from),
+        Some(_) => serde_json::from_value(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event() -> WebhookEvent {
+        WebhookEvent {
+            id: Uuid::parse_str("6f1c2a9e-4b7d-4e21-9a55-0d3e8c1f7b42").unwrap(),
+            event_type: "user.created".to_string(),
+            occurred_at: DateTime::parse_from_rfc3339("2026-01-22T10:15:30Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            data: json!({ "user_id": "0b6d5a3e-1f2c-4d8e-9b7a-6c5d4e3f2a1b" }),
+            context: Some(RequestContext {
+                request_id: Uuid::parse_str("1d2e3f40-5a6b-4c7d-8e9f-a0b1c2d3e4f5").unwrap(),
+                tenant_id: Some(Uuid::parse_str("9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d").unwrap()),
+                actor_id: None,
+                ip_address: Some("203.0.113.7".to_string()),
+            }),
+        }
+    }
+
+    /// The v1 wire format is frozen; receivers built before versioning
+    /// parse exactly this
+    #[test]
+    fn test_v1_wire_format() {
+        assert_eq!(
+            event().render(WebhookVersion::V1),
+            json!({
+                "event": "user.created",
+                "timestamp": "2026-01-22T10:15:30Z",
+                "request_id": "1d2e3f40-5a6b-4c7d-8e9f-a0b1c2d3e4f5",
+                "context": {
+                    "request_id": "1d2e3f40-5a6b-4c7d-8e9f-a0b1c2d3e4f5",
+                    "tenant_id": "9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d",
+                    "actor_id": null,
+                    "ip_address": "203.0.113.7"
+                },
+                "payload": { "user_id": "0b6d5a3e-1f2c-4d8e-9b7a-6c5d4e3f2a1b" }
+            })
+        );
+    }
+
+    /// The v2 wire format is frozen
+    #[test]
+    fn test_v2_wire_format() {
+        assert_eq!(
+            event().render(WebhookVersion::V2),
+            json!({
+                "id": "6f1c2a9e-4b7d-4e21-9a55-0d3e8c1f7b42",
+                "type": "user.created",
+                "version": "v2",
+                "occurred_at": "2026-01-22T10:15:30Z",
+                "tenant": "9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d",
+                "request_id": "1d2e3f40-5a6b-4c7d-8e9f-a0b1c2d3e4f5",
+                "data": { "user_id": "0b6d5a3e-1f2c-4d8e-9b7a-6c5d4e3f2a1b" }
+            })
+        );
+    }
+
+    #[test]
+    fn test_every_version_parses_as_latest() {
+        let event = event();
+        let v2 = parse_envelope(&serde_json::to_vec(&event.render(WebhookVersion::V2)).unwrap())
+            .unwrap();
+        assert_eq!(v2.id, event.id);
+
+        let upgraded =
+            parse_envelope(&serde_json::to_vec(&event.render(WebhookVersion::V1)).unwrap())
+                .unwrap();
+        assert_eq!(
+            EnvelopeV2 {
+                id: v2.id,
+                ..upgraded
+            },
+            v2
+        );
+    }
+
+    #[test]
+    fn test_subscriptions_pin_versions() {
+        assert_eq!(
+            WebhookSubscription::new("https://hooks.example.com").version,
+            WebhookVersion::LATEST
+        );
+        // Subscriptions stored before versioning stay on v1
+        let stored: WebhookSubscription =
+            serde_json::from_value(json!({ "url": "https://hooks.example.com" })).unwrap();
+        assert_eq!(stored.version, WebhookVersion::V1);
+    }
+}
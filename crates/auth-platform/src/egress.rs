@@ -0,0 +1,385 @@
+//! Outbound destination checks for tenant-supplied URLs
+//!
+//! A webhook URL is chosen by a tenant, so without checks it can point the
+//! server at its own network: the metadata endpoint, an admin port on
+//! localhost, a database on a private subnet. [`EgressPolicy`] limits the
+//! scheme and port, optionally restricts each tenant to an allowlist of
+//! hosts, and refuses any destination that is or resolves to a non-public
+//! address. The check on resolved addresses runs inside the HTTP client's
+//! resolver, on the addresses it then connects to, so a name that resolves
+//! differently the second time (DNS rebinding) is caught as well.
+//!
+//! Behind an egress proxy the proxy resolves names, so it has to enforce the
+//! same ranges itself.
+
+use hyper_014::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use url::Host;
+use uuid::Uuid;
+
+/// Where tenant-supplied URLs may point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EgressConfig {
+    #[serde(default = "default_allowed_schemes")]
+    pub allowed_schemes: Vec<String>,
+
+    #[serde(default = "default_allowed_ports")]
+    pub allowed_ports: Vec<u16>,
+
+    /// Allow loopback, private and link-local destinations. For local
+    /// development only.
+    #[serde(default)]
+    pub allow_private_networks: bool,
+
+    /// Redirects followed per delivery; each target is checked again
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: usize,
+
+    /// Hosts each tenant may send to, keyed by tenant id. `*.example.com`
+    /// matches subdomains of `example.com`. Tenants without an entry may
+    /// send to any public host.
+    #[serde(default)]
+    pub tenant_allowlists: HashMap<Uuid, Vec<String>>,
+}
+
+fn default_allowed_schemes() -> Vec<String> {
+    vec!["https".to_string()]
+}
+
+fn default_allowed_ports() -> Vec<u16> {
+    vec![443]
+}
+
+fn default_max_redirects() -> usize {
+    3
+}
+
+impl Default for EgressConfig {
+    fn default() -> Self {
+        Self {
+            allowed_schemes: default_allowed_schemes(),
+            allowed_ports: default_allowed_ports(),
+            allow_private_networks: false,
+            max_redirects: default_max_redirects(),
+            tenant_allowlists: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EgressError {
+    #[error("Invalid destination URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("Scheme {0} is not allowed")]
+    Scheme(String),
+
+    #[error("Port {0} is not allowed")]
+    Port(u16),
+
+    #[error("Host {0} is not on the tenant's allowlist")]
+    NotAllowlisted(String),
+
+    #[error("{host} resolves to non-public address {ip}")]
+    Blocked { host: String, ip: IpAddr },
+
+    #[error("Could not resolve {host}: {reason}")]
+    Resolve { host: String, reason: String },
+
+    #[error("More than {0} redirects")]
+    TooManyRedirects(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct EgressPolicy {
+    config: EgressConfig,
+}
+
+impl EgressPolicy {
+    pub fn new(config: EgressConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn max_redirects(&self) -> usize {
+        self.config.max_redirects
+    }
+
+    /// Check everything about `url` that does not need DNS: scheme, port,
+    /// the tenant's allowlist and literal IP addresses
+    pub fn check_url(&self, tenant_id: Option<Uuid>, url: &Url) -> Result<(), EgressError> {
+        if !self
+            .config
+            .allowed_schemes
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(url.scheme()))
+        {
+            return Err(EgressError::Scheme(url.scheme().to_string()));
+        }
+
+        let port = url.port_or_known_default().unwrap_or_default();
+        if !self.config.allowed_ports.contains(&port) {
+            return Err(EgressError::Port(port));
+        }
+
+        let host = url
+            .host()
+            .ok_or_else(|| EgressError::InvalidUrl(format!("{} has no host", url)))?;
+        let host_str = url.host_str().unwrap_or_default();
+        if let Some(allowlist) = tenant_id.and_then(|t| self.config.tenant_allowlists.get(&t)) {
+            if !allowlist
+                .iter()
+                .any(|pattern| host_matches(pattern, host_str))
+            {
+                return Err(EgressError::NotAllowlisted(host_str.to_string()));
+            }
+        }
+
+        match host {
+            Host::Ipv4(ip) => self.check_ip(host_str, IpAddr::V4(ip)),
+            Host::Ipv6(ip) => self.check_ip(host_str, IpAddr::V6(ip)),
+            Host::Domain(_) => Ok(()),
+        }
+    }
+
+    /// [`Self::check_url`], then resolve a named host and check its
+    /// addresses. The client's resolver checks again when connecting; this
+    /// pass turns a refused name into an error instead of a failed connect.
+    pub async fn check_destination(
+        &self,
+        tenant_id: Option<Uuid>,
+        url: &Url,
+    ) -> Result<(), EgressError> {
+        self.check_url(tenant_id, url)?;
+        if let Some(Host::Domain(host)) = url.host() {
+            self.resolve(host).await?;
+        }
+        Ok(())
+    }
+
+    /// Resolve `host`, refusing it if any of its addresses is not public
+    pub async fn resolve(&self, host: &str) -> Result<Vec<SocketAddr>, EgressError> {
+        let resolve_error = |reason: String| EgressError::Resolve {
+            host: host.to_string(),
+            reason,
+        };
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+            .await
+            .map_err(|e| resolve_error(e.to_string()))?
+            .collect();
+        if addrs.is_empty() {
+            return Err(resolve_error("no addresses".to_string()));
+        }
+        // One private address is enough to refuse; connecting would try it
+        for addr in &addrs {
+            self.check_ip(host, addr.ip())?;
+        }
+        Ok(addrs)
+    }
+
+    fn check_ip(&self, host: &str, ip: IpAddr) -> Result<(), EgressError> {
+        if self.config.allow_private_networks || is_public(ip) {
+            Ok(())
+        } else {
+            Err(EgressError::Blocked {
+                host: host.to_string(),
+                ip,
+            })
+        }
+    }
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Resolver for HTTP clients that must only reach public addresses
+pub(crate) struct GuardedResolver(pub(crate) Arc<EgressPolicy>);
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.0.clone();
+        Box::pin(async move {
+            let addrs: Addrs = Box::new(policy.resolve(name.as_str()).await?.into_iter());
+            Ok::<_, BoxError>(addrs)
+        })
+    }
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain.to_ascii_lowercase().as_str())
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+/// Globally routable unicast. IPv6 forms that embed an IPv4 address are
+/// judged by that address.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_v4(mapped),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // Carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking
+        || (a == 198 && (b == 18 || b == 19))
+        // Reserved
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let s = ip.segments();
+    let embedded =
+        |hi: u16, lo: u16| Ipv4Addr::new((hi >> 8) as u8, hi as u8, (lo >> 8) as u8, lo as u8);
+    if s[0] == 0x64 && s[1] == 0xff9b && s[2..6] == [0; 4] {
+        // NAT64
+        return is_public_v4(embedded(s[6], s[7]));
+    }
+    if s[0] == 0x2002 {
+        // 6to4
+        return is_public_v4(embedded(s[1], s[2]));
+    }
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // IPv4-compatible (deprecated)
+        || s[..6] == [0; 6]
+        // Unique local
+        || (s[0] & 0xfe00) == 0xfc00
+        // Link-local and site-local
+        || (s[0] & 0xffc0) == 0xfe80
+        || (s[0] & 0xffc0) == 0xfec0
+        // Documentation
+        || (s[0] == 0x2001 && s[1] == 0x0db8)
+        // Teredo
+        || (s[0] == 0x2001 && s[1] == 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_internal_ranges_are_not_public() {
+        for blocked in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:10.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+            "2002:7f00:1::",
+        ] {
+            assert!(!is_public(ip(blocked)), "{} should be blocked", blocked);
+        }
+        for allowed in ["8.8.8.8", "2606:4700:4700::1111", "::ffff:1.1.1.1"] {
+            assert!(is_public(ip(allowed)), "{} should be allowed", allowed);
+        }
+    }
+
+    #[test]
+    fn test_scheme_port_and_literal_addresses_checked() {
+        let policy = EgressPolicy::new(EgressConfig::default());
+        assert!(policy
+            .check_url(None, &url("https://hooks.example.com/x"))
+            .is_ok());
+        assert!(matches!(
+            policy.check_url(None, &url("http://hooks.example.com/x")),
+            Err(EgressError::Scheme(_))
+        ));
+        assert!(matches!(
+            policy.check_url(None, &url("https://hooks.example.com:8443/x")),
+            Err(EgressError::Port(8443))
+        ));
+        assert!(matches!(
+            policy.check_url(None, &url("https://169.254.169.254/latest")),
+            Err(EgressError::Blocked { .. })
+        ));
+        assert!(matches!(
+            policy.check_url(None, &url("https://[::1]/")),
+            Err(EgressError::Blocked { .. })
+        ));
+    }
+
+    #[test]
+    fn test_tenant_allowlist_limits_hosts() {
+        let tenant = Uuid::new_v4();
+        let policy = EgressPolicy::new(EgressConfig {
+            tenant_allowlists: HashMap::from([(
+                tenant,
+                vec!["hooks.example.com".to_string(), "*.acme.io".to_string()],
+            )]),
+            ..EgressConfig::default()
+        });
+
+        assert!(policy
+            .check_url(Some(tenant), &url("https://hooks.example.com/"))
+            .is_ok());
+        assert!(policy
+            .check_url(Some(tenant), &url("https://eu.acme.io/"))
+            .is_ok());
+        assert!(matches!(
+            policy.check_url(Some(tenant), &url("https://acme.io/")),
+            Err(EgressError::NotAllowlisted(_))
+        ));
+        assert!(matches!(
+            policy.check_url(Some(tenant), &url("https://evilacme.io/")),
+            Err(EgressError::NotAllowlisted(_))
+        ));
+        // Tenants without an allowlist may use any public host
+        assert!(policy
+            .check_url(Some(Uuid::new_v4()), &url("https://acme.io/"))
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_names_resolving_to_loopback_are_refused() {
+        let policy = EgressPolicy::new(EgressConfig::default());
+        assert!(matches!(
+            policy.resolve("localhost").await,
+            Err(EgressError::Blocked { .. })
+        ));
+
+        let permissive = EgressPolicy::new(EgressConfig {
+            allow_private_networks: true,
+            ..EgressConfig::default()
+        });
+        assert!(permissive.resolve("localhost").await.is_ok());
+    }
+}
@@ -0,0 +1,569 @@
+//! Shared outbound HTTP client
+//!
+//! Calls to third parties (SMS gateways, Firebase, webhook receivers) go
+//! through one configured client so they share connection pools and the same
+//! protections: connect and request timeouts, an optional egress proxy,
+//! optional CA pinning, retries with jittered backoff, and per-host caps on
+//! requests in flight. Each host also has a circuit breaker, so a provider
+//! that keeps failing is cut off for a while instead of holding up every
+//! caller for a full timeout.
+
+use crate::egress::{EgressError, EgressPolicy, GuardedResolver};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use rand::Rng;
+use reqwest::{Method, Request, RequestBuilder, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+/// Outbound HTTP settings shared by every provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpClientConfig {
+    #[serde(default = "default_connect_timeout_ms")]
+    pub connect_timeout_ms: u64,
+
+    /// Whole request, from connecting to reading the response
+    #[serde(default = "default_request_timeout_ms")]
+    pub request_timeout_ms: u64,
+
+    #[serde(default = "default_pool_idle_timeout")]
+    pub pool_idle_timeout_seconds: u64,
+
+    /// Requests in flight to one host; further requests wait for a slot
+    #[serde(default = "default_max_connections_per_host")]
+    pub max_connections_per_host: usize,
+
+    /// Egress proxy for all outbound traffic, e.g. `http://proxy:3128`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+
+    /// Comma-separated hosts that bypass the proxy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+
+    /// PEM files of the CAs to trust. When set, the built-in roots are not
+    /// trusted, pinning outbound TLS to these CAs.
+    #[serde(default)]
+    pub pinned_ca_certs: Vec<String>,
+
+    #[serde(default)]
+    pub retry: HttpRetryConfig,
+
+    #[serde(default)]
+    pub circuit_breaker: HttpCircuitBreakerConfig,
+}
+
+/// Retries of requests the receiver did not process, or that are safe to
+/// repeat
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpRetryConfig {
+    /// Attempts including the first; 1 disables retries
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_base_delay_ms")]
+    pub base_delay_ms: u64,
+    #[serde(default = "default_max_delay_ms")]
+    pub max_delay_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpCircuitBreakerConfig {
+    /// Consecutive failures (transport errors or 5xx) that open the circuit
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// How long an open circuit rejects requests before letting one through
+    #[serde(default = "default_open_seconds")]
+    pub open_seconds: u64,
+}
+
+fn default_connect_timeout_ms() -> u64 {
+    5_000
+}
+
+fn default_request_timeout_ms() -> u64 {
+    15_000
+}
+
+fn default_pool_idle_timeout() -> u64 {
+    90
+}
+
+fn default_max_connections_per_host() -> usize {
+    32
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_base_delay_ms() -> u64 {
+    100
+}
+
+fn default_max_delay_ms() -> u64 {
+    2_000
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_open_seconds() -> u64 {
+    30
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_ms: default_connect_timeout_ms(),
+            request_timeout_ms: default_request_timeout_ms(),
+            pool_idle_timeout_seconds: default_pool_idle_timeout(),
+            max_connections_per_host: default_max_connections_per_host(),
+            proxy: None,
+            no_proxy: None,
+            pinned_ca_certs: Vec::new(),
+            retry: HttpRetryConfig::default(),
+            circuit_breaker: HttpCircuitBreakerConfig::default(),
+        }
+    }
+}
+
+impl Default for HttpRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            base_delay_ms: default_base_delay_ms(),
+            max_delay_ms: default_max_delay_ms(),
+        }
+    }
+}
+
+impl Default for HttpCircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: default_failure_threshold(),
+            open_seconds: default_open_seconds(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HttpClientError {
+    #[error("Invalid HTTP client configuration: {0}")]
+    Config(String),
+
+    #[error("Circuit open for {host}")]
+    CircuitOpen { host: String },
+
+    #[error("Destination refused: {0}")]
+    Egress(#[from] EgressError),
+
+    #[error("HTTP request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+/// Cheap to clone; clones share pools, slots and breakers
+#[derive(Clone)]
+pub struct HttpClient {
+    inner: reqwest::Client,
+    config: Arc<HttpClientConfig>,
+    hosts: Arc<DashMap<String, Arc<HostState>>>,
+}
+
+struct HostState {
+    slots: Semaphore,
+    breaker: Mutex<Breaker>,
+}
+
+impl HttpClient {
+    pub fn new(config: HttpClientConfig) -> Result<Self, HttpClientError> {
+        let builder = Self::builder(&config)?;
+        Self::build(builder, config)
+    }
+
+    /// Client for tenant-supplied URLs. It connects only to addresses the
+    /// policy allows and does not follow redirects, so callers can check
+    /// each redirect target before sending to it.
+    pub fn with_egress_policy(
+        config: HttpClientConfig,
+        policy: Arc<EgressPolicy>,
+    ) -> Result<Self, HttpClientError> {
+        let builder = Self::builder(&config)?
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(GuardedResolver(policy)));
+        Self::build(builder, config)
+    }
+
+    fn builder(config: &HttpClientConfig) -> Result<reqwest::ClientBuilder, HttpClientError> {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+            .timeout(Duration::from_millis(config.request_timeout_ms))
+            .pool_idle_timeout(Duration::from_secs(config.pool_idle_timeout_seconds))
+            .pool_max_idle_per_host(config.max_connections_per_host)
+            .min_tls_version(reqwest::tls::Version::TLS_1_2);
+
+        if let Some(url) = &config.proxy {
+            let mut proxy = reqwest::Proxy::all(url)
+                .map_err(|e| HttpClientError::Config(format!("proxy {}: {}", url, e)))?;
+            if let Some(no_proxy) = &config.no_proxy {
+                proxy = proxy.no_proxy(reqwest::NoProxy::from_string(no_proxy));
+            }
+            builder = builder.proxy(proxy);
+        }
+
+        if !config.pinned_ca_certs.is_empty() {
+            builder = builder.tls_built_in_root_certs(false);
+            for path in &config.pinned_ca_certs {
+                let pem = std::fs::read(path).map_err(|e| {
+                    HttpClientError::Config(format!("CA certificate {}: {}", path, e))
+                })?;
+                let cert = reqwest::Certificate::from_pem(&pem).map_err(|e| {
+                    HttpClientError::Config(format!("CA certificate {}: {}", path, e))
+                })?;
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        Ok(builder)
+    }
+
+    fn build(
+        builder: reqwest::ClientBuilder,
+        config: HttpClientConfig,
+    ) -> Result<Self, HttpClientError> {
+        let inner = builder
+            .build()
+            .map_err(|e| HttpClientError::Config(e.to_string()))?;
+        Ok(Self {
+            inner,
+            config: Arc::new(config),
+            hosts: Arc::new(DashMap::new()),
+        })
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.inner.get(url)
+    }
+
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.inner.post(url)
+    }
+
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.inner.request(method, url)
+    }
+
+    /// Send a request built from this client, retrying and tripping the
+    /// host's breaker as configured. Non-2xx responses are returned as-is
+    /// once retries are exhausted.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, HttpClientError> {
+        let request = request.build()?;
+        let host = host_key(request.url());
+        let state = self.host(&host);
+        let idempotent = is_idempotent(request.method());
+        let max_attempts = self.config.retry.max_attempts.max(1);
+
+        let mut request = request;
+        let mut attempt = 1;
+        loop {
+            // Keep a copy for the next attempt; streamed bodies cannot be replayed
+            let next = if attempt < max_attempts {
+                request.try_clone()
+            } else {
+                None
+            };
+
+            let outcome = self.execute(&host, &state, request).await;
+            let retryable = match &outcome {
+                Ok(response) => retryable_status(response.status(), idempotent),
+                Err(HttpClientError::Request(e)) => {
+                    e.is_connect() || (idempotent && e.is_timeout())
+                }
+                Err(_) => false,
+            };
+            let Some(next) = next.filter(|_| retryable) else {
+                return outcome;
+            };
+
+            let delay = backoff(&self.config.retry, attempt);
+            tracing::warn!(
+                "Request to {} failed (attempt {}/{}), retrying in {:?}",
+                host,
+                attempt,
+                max_attempts,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+            request = next;
+            attempt += 1;
+        }
+    }
+
+    async fn execute(
+        &self,
+        host: &str,
+        state: &HostState,
+        request: Request,
+    ) -> Result<Response, HttpClientError> {
+        let breaker = &self.config.circuit_breaker;
+        if !state.breaker.lock().allow(Instant::now(), breaker) {
+            return Err(HttpClientError::CircuitOpen {
+                host: host.to_string(),
+            });
+        }
+
+        // The semaphore is never closed, so acquiring only waits
+        let _slot = state.slots.acquire().await.ok();
+        let result = self.inner.execute(request).await;
+
+        let succeeded = matches!(&result, Ok(response) if !response.status().is_server_error());
+        let opened = state
+            .breaker
+            .lock()
+            .record(succeeded, Instant::now(), breaker);
+        if opened {
+            tracing::error!(
+                "Circuit opened for {} after {} consecutive failures",
+                host,
+                breaker.failure_threshold
+            );
+        }
+        result.map_err(HttpClientError::from)
+    }
+
+    /// Breaker state of every host called so far
+    pub fn circuit_states(&self) -> Vec<CircuitStatus> {
+        let now = Instant::now();
+        let mut states: Vec<CircuitStatus> = self
+            .hosts
+            .iter()
+            .map(|entry| {
+                let breaker = entry.value().breaker.lock();
+                CircuitStatus {
+                    host: entry.key().clone(),
+                    consecutive_failures: breaker.consecutive_failures,
+                    open: breaker.open_until.is_some_and(|until| now < until),
+                }
+            })
+            .collect();
+        states.sort_by(|a, b| a.host.cmp(&b.host));
+        states
+    }
+
+    fn host(&self, host: &str) -> Arc<HostState> {
+        self.hosts
+            .entry(host.to_string())
+            .or_insert_with(|| {
+                Arc::new(HostState {
+                    slots: Semaphore::new(self.config.max_connections_per_host.max(1)),
+                    breaker: Mutex::new(Breaker::default()),
+                })
+            })
+            .clone()
+    }
+}
+
+impl Default for HttpClient {
+    /// Client with the default configuration. Panics if the TLS backend
+    /// cannot be initialized, as `reqwest::Client::new` does.
+    fn default() -> Self {
+        Self::new(HttpClientConfig::default()).expect("default HTTP client configuration is valid")
+    }
+}
+
+/// Operator view of a host's circuit breaker
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CircuitStatus {
+    pub host: String,
+    pub consecutive_failures: u32,
+    /// Whether requests to the host are being rejected right now
+    pub open: bool,
+}
+
+/// Consecutive-failure breaker. Once the open period ends one request is let
+/// through and the period restarts, so a slow probe cannot leave it stuck.
+#[derive(Debug, Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl Breaker {
+    fn allow(&mut self, now: Instant, config: &HttpCircuitBreakerConfig) -> bool {
+        match self.open_until {
+            Some(until) if now < until => false,
+            Some(_) => {
+                self.open_until = Some(now + Duration::from_secs(config.open_seconds));
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Returns true when this failure opened the circuit
+    fn record(&mut self, succeeded: bool, now: Instant, config: &HttpCircuitBreakerConfig) -> bool {
+        if succeeded {
+            self.consecutive_failures = 0;
+            self.open_until = None;
+            return false;
+        }
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if self.consecutive_failures < config.failure_threshold.max(1) {
+            return false;
+        }
+        let was_closed = self.open_until.is_none();
+        self.open_until = Some(now + Duration::from_secs(config.open_seconds));
+        was_closed
+    }
+}
+
+fn host_key(url: &Url) -> String {
+    format!(
+        "{}:{}",
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or_default()
+    )
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE
+    )
+}
+
+/// 429 and 503 mean the request was not processed. A gateway error may come
+/// after the upstream acted, so those are only retried when repeating is safe.
+fn retryable_status(status: StatusCode, idempotent: bool) -> bool {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => true,
+        StatusCode::BAD_GATEWAY | StatusCode::GATEWAY_TIMEOUT => idempotent,
+        _ => false,
+    }
+}
+
+/// Exponential backoff with jitter between half and all of the step
+fn backoff(config: &HttpRetryConfig, attempt: u32) -> Duration {
+    let step = config
+        .base_delay_ms
+        .saturating_mul(1u64 << attempt.saturating_sub(1).min(16))
+        .min(config.max_delay_ms);
+    Duration::from_millis(rand::thread_rng().gen_range(step / 2..=step))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_defaults_from_empty_document() {
+        let config: HttpClientConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.request_timeout_ms, 15_000);
+        assert_eq!(config.retry.max_attempts, 3);
+        assert_eq!(config.circuit_breaker.failure_threshold, 5);
+        assert!(config.pinned_ca_certs.is_empty());
+    }
+
+    #[test]
+    fn test_breaker_opens_and_lets_one_probe_through() {
+        let config = HttpCircuitBreakerConfig {
+            failure_threshold: 3,
+            open_seconds: 30,
+        };
+        let mut breaker = Breaker::default();
+        let start = Instant::now();
+
+        assert!(!breaker.record(false, start, &config));
+        assert!(!breaker.record(false, start, &config));
+        assert!(breaker.record(false, start, &config));
+        assert!(!breaker.allow(start + Duration::from_secs(10), &config));
+
+        let later = start + Duration::from_secs(31);
+        assert!(breaker.allow(later, &config), "probe after the open period");
+        assert!(!breaker.allow(later, &config), "only one probe at a time");
+
+        breaker.record(true, later, &config);
+        assert!(breaker.allow(later, &config));
+    }
+
+    #[test]
+    fn test_failed_probe_reopens_without_reaching_threshold_again() {
+        let config = HttpCircuitBreakerConfig {
+            failure_threshold: 2,
+            open_seconds: 30,
+        };
+        let mut breaker = Breaker::default();
+        let start = Instant::now();
+        breaker.record(false, start, &config);
+        breaker.record(false, start, &config);
+
+        let probe = start + Duration::from_secs(31);
+        assert!(breaker.allow(probe, &config));
+        breaker.record(false, probe, &config);
+        assert!(!breaker.allow(probe + Duration::from_secs(10), &config));
+    }
+
+    #[test]
+    fn test_backoff_grows_with_jitter_and_is_capped() {
+        let config = HttpRetryConfig {
+            max_attempts: 10,
+            base_delay_ms: 100,
+            max_delay_ms: 1_000,
+        };
+        for _ in 0..50 {
+            let first = backoff(&config, 1).as_millis();
+            assert!((50..=100).contains(&first));
+            let third = backoff(&config, 3).as_millis();
+            assert!((200..=400).contains(&third));
+            let capped = backoff(&config, 9).as_millis();
+            assert!((500..=1_000).contains(&capped));
+        }
+    }
+
+    #[test]
+    fn test_gateway_errors_only_retried_when_idempotent() {
+        assert!(retryable_status(StatusCode::SERVICE_UNAVAILABLE, false));
+        assert!(retryable_status(StatusCode::TOO_MANY_REQUESTS, false));
+        assert!(!retryable_status(StatusCode::BAD_GATEWAY, false));
+        assert!(retryable_status(StatusCode::BAD_GATEWAY, true));
+        assert!(!retryable_status(StatusCode::INTERNAL_SERVER_ERROR, true));
+    }
+
+    #[test]
+    fn test_hosts_keyed_with_default_port() {
+        let url = Url::parse("https://fcm.googleapis.com/v1/projects").unwrap();
+        assert_eq!(host_key(&url), "fcm.googleapis.com:443");
+        let url = Url::parse("http://sms.local:8080/send").unwrap();
+        assert_eq!(host_key(&url), "sms.local:8080");
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_rejects_without_sending() {
+        let client = HttpClient::new(HttpClientConfig {
+            circuit_breaker: HttpCircuitBreakerConfig {
+                failure_threshold: 1,
+                open_seconds: 60,
+            },
+            ..HttpClientConfig::default()
+        })
+        .unwrap();
+        let state = client.host("sms.invalid:443");
+        state
+            .breaker
+            .lock()
+            .record(false, Instant::now(), &client.config.circuit_breaker);
+
+        let result = client.send(client.post("https://sms.invalid/send")).await;
+        assert!(matches!(result, Err(HttpClientError::CircuitOpen { .. })));
+        assert_eq!(
+            client.circuit_states(),
+            vec![CircuitStatus {
+                host: "sms.invalid:443".to_string(),
+                consecutive_failures: 1,
+                open: true,
+            }]
+        );
+    }
+}
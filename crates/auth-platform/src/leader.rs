@@ -0,0 +1,347 @@
+//! Leader election for singleton background work
+//!
+//! Every replica campaigns for the same [`DistributedLock`]; the holder is
+//! the leader until its lease is lost, at which point another replica takes
+//! over on its next attempt. Work that must run on exactly one node is
+//! started with [`run_singleton`], which stops it as soon as leadership ends.
+
+use crate::lock::{DistributedLock, LockGuard};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+const DEFAULT_TTL: Duration = Duration::from_secs(15);
+const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeadershipEvent {
+    Elected { fencing_token: u64 },
+    Revoked,
+}
+
+/// Called on every leadership change of this node
+pub type LeadershipHook = Arc<dyn Fn(LeadershipEvent) + Send + Sync>;
+
+pub struct LeaderElection {
+    lock: Arc<dyn DistributedLock>,
+    name: String,
+    node_id: String,
+    ttl: Duration,
+    retry_interval: Duration,
+    hooks: Vec<LeadershipHook>,
+}
+
+impl LeaderElection {
+    pub fn new(
+        lock: Arc<dyn DistributedLock>,
+        name: impl Into<String>,
+        node_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            lock,
+            name: name.into(),
+            node_id: node_id.into(),
+            ttl: DEFAULT_TTL,
+            retry_interval: DEFAULT_RETRY_INTERVAL,
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Lease length; bounds how long a dead leader blocks failover
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Pause between campaigns while another node leads
+    pub fn with_retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    pub fn on_change(mut self, hook: impl Fn(LeadershipEvent) + Send + Sync + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Start campaigning in the background
+    pub fn start(self) -> LeaderHandle {
+        let (state_tx, state) = watch::channel(None);
+        let (stop_tx, stop) = watch::channel(false);
+        let task = tokio::spawn(self.campaign(state_tx, stop));
+        LeaderHandle {
+            state,
+            stop: stop_tx,
+            task,
+        }
+    }
+
+    async fn campaign(self, state: watch::Sender<Option<u64>>, mut stop: watch::Receiver<bool>) {
+        self.report(false);
+
+        while !*stop.borrow() {
+            match LockGuard::try_acquire(self.lock.clone(), &self.name, self.ttl).await {
+                Ok(Some(mut guard)) => {
+                    let fencing_token = guard.fencing_token();
+                    let _ = state.send(Some(fencing_token));
+                    self.transition(LeadershipEvent::Elected { fencing_token });
+
+                    let stopping = tokio::select! {
+                        _ = guard.lost() => false,
+                        _ = stop.changed() => true,
+                    };
+
+                    let _ = state.send(None);
+                    self.transition(LeadershipEvent::Revoked);
+                    if stopping {
+                        // Step down promptly so another node can take over
+                        if let Err(e) = guard.release().await {
+                            warn!(election = %self.name, error = %e, "Failed to release leadership");
+                        }
+                        return;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    warn!(election = %self.name, error = %e, "Leader election attempt failed");
+                }
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(self.retry_interval) => {}
+                _ = stop.changed() => {}
+            }
+        }
+    }
+
+    fn transition(&self, event: LeadershipEvent) {
+        let is_leader = matches!(event, LeadershipEvent::Elected { .. });
+        info!(
+            event = "leader.changed",
+            election = %self.name,
+            node_id = %self.node_id,
+            is_leader = is_leader,
+            "Leadership changed"
+        );
+        self.report(is_leader);
+        metrics::counter!(
+            "auth_leader_transitions_total",
+            1,
+            "election" => self.name.clone(),
+            "node_id" => self.node_id.clone()
+        );
+        for hook in &self.hooks {
+            hook(event);
+        }
+    }
+
+    /// One series per node; the leader is whichever node reports 1
+    fn report(&self, is_leader: bool) {
+        metrics::gauge!(
+            "auth_leader",
+            if is_leader { 1.0 } else { 0.0 },
+            "election" => self.name.clone(),
+            "node_id" => self.node_id.clone()
+        );
+    }
+}
+
+/// Observes and controls a running election
+pub struct LeaderHandle {
+    state: watch::Receiver<Option<u64>>,
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl LeaderHandle {
+    pub fn is_leader(&self) -> bool {
+        self.state.borrow().is_some()
+    }
+
+    /// Fencing token of the current term, while leader
+    pub fn fencing_token(&self) -> Option<u64> {
+        *self.state.borrow()
+    }
+
+    /// Receiver yielding the fencing token while leader and `None` otherwise
+    pub fn subscribe(&self) -> watch::Receiver<Option<u64>> {
+        self.state.clone()
+    }
+
+    /// Stop campaigning, stepping down if currently leader
+    pub async fn stop(self) {
+        let _ = self.stop.send(true);
+        let _ = self.task.await;
+    }
+}
+
+/// Run `worker` on this node only while it leads the election. The worker is
+/// aborted when leadership is lost and restarted on re-election. If the
+/// worker returns on its own it is not restarted until the next term.
+pub fn run_singleton<F, Fut>(handle: &LeaderHandle, name: &str, worker: F) -> JoinHandle<()>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut state = handle.subscribe();
+    let name = name.to_string();
+
+    tokio::spawn(async move {
+        loop {
+            if state.wait_for(Option::is_some).await.is_err() {
+                return;
+            }
+
+            info!(worker = %name, "Starting singleton worker");
+            let mut task = tokio::spawn(worker());
+            let finished = tokio::select! {
+                _ = &mut task => true,
+                changed = state.wait_for(Option::is_none) => changed.is_err(),
+            };
+            if !finished {
+                info!(worker = %name, "Stopping singleton worker: leadership lost");
+            }
+            task.abort();
+
+            if state.wait_for(Option::is_none).await.is_err() {
+                return;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::{LockError, LockLease};
+    use async_trait::async_trait;
+    use parking_lot::Mutex;
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+    /// One lock name, with a switch that makes the current holder lose it
+    #[derive(Default)]
+    struct SingleLock {
+        holder: Mutex<Option<String>>,
+        fence: AtomicU64,
+        revoke: AtomicBool,
+    }
+
+    #[async_trait]
+    impl DistributedLock for SingleLock {
+        async fn try_acquire(
+            &self,
+            name: &str,
+            ttl: Duration,
+        ) -> Result<Option<LockLease>, LockError> {
+            let mut holder = self.holder.lock();
+            if holder.is_some() {
+                return Ok(None);
+            }
+            let owner = uuid::Uuid::new_v4().to_string();
+            *holder = Some(owner.clone());
+            Ok(Some(LockLease {
+                name: name.to_string(),
+                owner,
+                fencing_token: self.fence.fetch_add(1, Ordering::SeqCst) + 1,
+                ttl,
+            }))
+        }
+
+        async fn renew(&self, lease: &LockLease) -> Result<bool, LockError> {
+            let mut holder = self.holder.lock();
+            if self.revoke.swap(false, Ordering::SeqCst) {
+                *holder = None;
+            }
+            Ok(holder.as_deref() == Some(lease.owner.as_str()))
+        }
+
+        async fn release(&self, lease: &LockLease) -> Result<(), LockError> {
+            let mut holder = self.holder.lock();
+            if holder.as_deref() == Some(lease.owner.as_str()) {
+                *holder = None;
+            }
+            Ok(())
+        }
+    }
+
+    fn election(lock: &Arc<SingleLock>, node: &str) -> LeaderElection {
+        LeaderElection::new(lock.clone(), "singleton", node)
+            .with_ttl(Duration::from_millis(60))
+            .with_retry_interval(Duration::from_millis(20))
+    }
+
+    async fn eventually(check: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if check() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("condition not reached");
+    }
+
+    #[tokio::test]
+    async fn test_single_leader_and_failover() {
+        let lock = Arc::new(SingleLock::default());
+        let a = election(&lock, "a").start();
+        let b = election(&lock, "b").start();
+
+        eventually(|| a.is_leader() || b.is_leader()).await;
+        assert!(!(a.is_leader() && b.is_leader()));
+
+        let (leader, follower) = if a.is_leader() { (a, b) } else { (b, a) };
+        let first_term = leader.fencing_token().unwrap();
+
+        leader.stop().await;
+        eventually(|| follower.is_leader()).await;
+        assert!(follower.fencing_token().unwrap() > first_term);
+    }
+
+    #[tokio::test]
+    async fn test_hooks_see_election_and_revocation() {
+        let lock = Arc::new(SingleLock::default());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        let handle = election(&lock, "a")
+            .on_change(move |e| seen.lock().push(e))
+            .start();
+
+        eventually(|| handle.is_leader()).await;
+        lock.revoke.store(true, Ordering::SeqCst);
+        eventually(|| events.lock().contains(&LeadershipEvent::Revoked)).await;
+
+        assert!(matches!(
+            events.lock()[0],
+            LeadershipEvent::Elected { fencing_token: 1 }
+        ));
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_singleton_stops_when_leadership_lost() {
+        let lock = Arc::new(SingleLock::default());
+        let handle = election(&lock, "a").start();
+        let running = Arc::new(AtomicUsize::new(0));
+        let starts = Arc::new(AtomicUsize::new(0));
+
+        let (r, s) = (running.clone(), starts.clone());
+        let _worker = run_singleton(&handle, "job", move || {
+            let (r, s) = (r.clone(), s.clone());
+            async move {
+                s.fetch_add(1, Ordering::SeqCst);
+                r.store(1, Ordering::SeqCst);
+                std::future::pending::<()>().await;
+            }
+        });
+
+        eventually(|| running.load(Ordering::SeqCst) == 1).await;
+
+        // Losing the lease aborts the worker; re-election starts a new one
+        lock.revoke.store(true, Ordering::SeqCst);
+        eventually(|| starts.load(Ordering::SeqCst) == 2).await;
+        handle.stop().await;
+    }
+}
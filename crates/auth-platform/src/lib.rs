@@ -5,19 +5,57 @@
 //!
 //! - **Port Management**: Production-grade port binding with OS-level safety,
 //!   multi-process coordination, security classification, and graceful lifecycle
-//! - **Future**: Circuit breakers, distributed tracing coordination, etc.
+//! - **Distributed Locks**: Cross-replica mutual exclusion with fencing tokens
+//!   and lease renewal (Redis or MySQL)
+//! - **Leader Election**: Singleton background workers with automatic failover
+//! - **Durable Queue**: Segmented write-ahead log for events that must survive
+//!   restarts (audit, webhooks)
+//! - **Outbound HTTP**: Shared client with timeouts, proxy, CA pinning,
+//!   retries and per-host circuit breakers for third-party calls
+//! - **Object Storage**: Blob store over a local directory or an
+//!   S3-compatible bucket, with multipart uploads and server-side encryption
+//! - **Egress Policy**: SSRF checks for tenant-supplied URLs (scheme, port,
+//!   per-tenant allowlists, non-public address blocking)
+//! - **Lifecycle**: Ordered init, ready and shutdown hooks for subsystems,
+//!   with dependencies between them
+//! - **Worker Supervision**: Panic reporting and restart with backoff for
+//!   background workers
+//! - **Future**: Distributed tracing coordination, etc.
 
+pub mod egress;
+pub mod http_client;
+pub mod leader;
+pub mod lifecycle;
+pub mod lock;
+pub mod object_store;
 pub mod port_authority;
 pub mod port_lease;
 pub mod port_policy;
 pub mod safe_socket;
 pub mod shutdown;
+pub mod supervisor;
+pub mod wal;
 
-pub use port_authority::PortAuthority;
-pub use port_lease::PortLease;
+pub use egress::{EgressConfig, EgressError, EgressPolicy};
+pub use http_client::{
+    CircuitStatus, HttpCircuitBreakerConfig, HttpClient, HttpClientConfig, HttpClientError,
+    HttpRetryConfig,
+};
+pub use leader::{run_singleton, LeaderElection, LeaderHandle, LeadershipEvent};
+pub use lifecycle::{Component, LifecycleError, ServiceRegistry};
+pub use lock::{DistributedLock, LockError, LockGuard, LockLease, MySqlLock, RedisLock};
+pub use object_store::{
+    object_store_from_config, LocalObjectStore, MultipartUpload, ObjectMeta, ObjectStore,
+    ObjectStoreBackend, ObjectStoreConfig, ObjectStoreError, S3Config, S3ObjectStore,
+    ServerSideEncryption,
+};
+pub use port_authority::{bind_addrs, LeaseStatus, PortAuthority};
+pub use port_lease::{AddressFamily, PortLease};
 pub use port_policy::{PortClass, PortPolicy};
 pub use safe_socket::ManagedListener;
 pub use shutdown::{shutdown_signal, GracefulShutdown};
+pub use supervisor::{panic_message, SupervisorConfig, WorkerSupervisor};
+pub use wal::{DurableQueue, FsyncPolicy, WalConfig, WalError, WalPosition, WalRecord, WalStats};
 
 /// Platform-level errors
 #[derive(Debug, thiserror::Error)]
@@ -31,6 +69,21 @@ pub enum PlatformError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
+    #[error("Lock error: {0}")]
+    Lock(#[from] lock::LockError),
+
+    #[error("Queue error: {0}")]
+    Wal(#[from] wal::WalError),
+
+    #[error("HTTP client error: {0}")]
+    Http(#[from] http_client::HttpClientError),
+
+    #[error("Object store error: {0}")]
+    ObjectStore(#[from] object_store::ObjectStoreError),
+
+    #[error("Lifecycle error: {0}")]
+    Lifecycle(#[from] lifecycle::LifecycleError),
+
     #[error("Shutdown error: {0}")]
     Shutdown(String),
 }
@@ -0,0 +1,420 @@
+//! Startup and shutdown ordering for subsystems
+//!
+//! Each subsystem registers a [`Component`] with hooks for the three phases
+//! and the names of the components it needs. [`ServiceRegistry::start`]
+//! orders them so a component's dependencies are initialised and ready
+//! before it is, and [`ServiceRegistry::shutdown`] runs the shutdown hooks
+//! in the reverse order, so nothing is stopped while a dependent still runs.
+//!
+//! - `init`: acquire what the component needs; a failure aborts startup and
+//!   shuts down what was already initialised.
+//! - `ready`: every component is initialised; start background work.
+//! - `shutdown`: stop and release; failures and timeouts are logged and the
+//!   remaining components are still shut down.
+
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+type HookFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+type Hook = Box<dyn FnOnce() -> HookFuture + Send>;
+
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum LifecycleError {
+    #[error("Component {0} registered twice")]
+    Duplicate(String),
+
+    #[error("Component {component} depends on unknown component {dependency}")]
+    UnknownDependency {
+        component: String,
+        dependency: String,
+    },
+
+    #[error("Dependency cycle between components: {}", .0.join(", "))]
+    Cycle(Vec<String>),
+
+    #[error("Component {component} failed to {phase}: {source}")]
+    Hook {
+        component: String,
+        phase: &'static str,
+        source: anyhow::Error,
+    },
+}
+
+/// A subsystem and its lifecycle hooks
+pub struct Component {
+    name: String,
+    depends_on: Vec<String>,
+    init: Option<Hook>,
+    ready: Option<Hook>,
+    shutdown: Option<Hook>,
+    shutdown_timeout: Option<Duration>,
+}
+
+impl Component {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            depends_on: Vec::new(),
+            init: None,
+            ready: None,
+            shutdown: None,
+            shutdown_timeout: None,
+        }
+    }
+
+    /// A background task started once every component is initialised and
+    /// aborted on shutdown
+    pub fn task<F>(name: impl Into<String>, start: F) -> Self
+    where
+        F: FnOnce() -> JoinHandle<()> + Send + 'static,
+    {
+        let handle: Arc<Mutex<Option<JoinHandle<()>>>> = Arc::default();
+        let started = handle.clone();
+        Self::new(name)
+            .on_ready(move || async move {
+                *started.lock() = Some(start());
+                Ok(())
+            })
+            .on_shutdown(move || async move {
+                if let Some(task) = handle.lock().take() {
+                    task.abort();
+                }
+                Ok(())
+            })
+    }
+
+    pub fn depends_on(mut self, component: impl Into<String>) -> Self {
+        self.depends_on.push(component.into());
+        self
+    }
+
+    pub fn on_init<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.init = Some(boxed(hook));
+        self
+    }
+
+    pub fn on_ready<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.ready = Some(boxed(hook));
+        self
+    }
+
+    pub fn on_shutdown<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.shutdown = Some(boxed(hook));
+        self
+    }
+
+    /// Bound on this component's shutdown hook, instead of the registry's
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = Some(timeout);
+        self
+    }
+}
+
+fn boxed<F, Fut>(hook: F) -> Hook
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    Box::new(move || Box::pin(hook()) as HookFuture)
+}
+
+/// Shutdown hook of a component that has been initialised
+struct Started {
+    name: String,
+    shutdown: Option<Hook>,
+    timeout: Duration,
+}
+
+pub struct ServiceRegistry {
+    components: Vec<Component>,
+    started: Vec<Started>,
+    shutdown_timeout: Duration,
+}
+
+impl Default for ServiceRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServiceRegistry {
+    pub fn new() -> Self {
+        Self {
+            components: Vec::new(),
+            started: Vec::new(),
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+        }
+    }
+
+    /// Default bound on each shutdown hook
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    pub fn register(&mut self, component: Component) -> &mut Self {
+        self.components.push(component);
+        self
+    }
+
+    /// Names in start order: dependencies first, otherwise in registration
+    /// order
+    pub fn order(&self) -> Result<Vec<String>, LifecycleError> {
+        Ok(self
+            .ordered_indices()?
+            .into_iter()
+            .map(|i| self.components[i].name.clone())
+            .collect())
+    }
+
+    /// Run every init hook, then every ready hook, in dependency order
+    pub async fn start(&mut self) -> Result<(), LifecycleError> {
+        let order = self.ordered_indices()?;
+        let mut slots: Vec<Option<Component>> = std::mem::take(&mut self.components)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut components: Vec<Component> =
+            order.into_iter().filter_map(|i| slots[i].take()).collect();
+
+        for component in &mut components {
+            if let Some(init) = component.init.take() {
+                if let Err(source) = init().await {
+                    self.shutdown().await;
+                    return Err(LifecycleError::Hook {
+                        component: component.name.clone(),
+                        phase: "initialise",
+                        source,
+                    });
+                }
+            }
+            info!(component = %component.name, "Component initialised");
+            self.started.push(Started {
+                name: component.name.clone(),
+                shutdown: component.shutdown.take(),
+                timeout: component.shutdown_timeout.unwrap_or(self.shutdown_timeout),
+            });
+        }
+
+        for component in &mut components {
+            if let Some(ready) = component.ready.take() {
+                if let Err(source) = ready().await {
+                    self.shutdown().await;
+                    return Err(LifecycleError::Hook {
+                        component: component.name.clone(),
+                        phase: "become ready",
+                        source,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the shutdown hooks of started components, dependents first
+    pub async fn shutdown(&mut self) {
+        while let Some(component) = self.started.pop() {
+            let Some(hook) = component.shutdown else {
+                continue;
+            };
+            match tokio::time::timeout(component.timeout, hook()).await {
+                Ok(Ok(())) => info!(component = %component.name, "Component shut down"),
+                Ok(Err(e)) => {
+                    warn!(component = %component.name, error = %e, "Component shutdown failed")
+                }
+                Err(_) => warn!(
+                    component = %component.name,
+                    timeout_secs = component.timeout.as_secs(),
+                    "Component shutdown timed out"
+                ),
+            }
+        }
+    }
+
+    /// Topological order, stable with respect to registration
+    fn ordered_indices(&self) -> Result<Vec<usize>, LifecycleError> {
+        let mut index = HashMap::new();
+        for (i, component) in self.components.iter().enumerate() {
+            if index.insert(component.name.as_str(), i).is_some() {
+                return Err(LifecycleError::Duplicate(component.name.clone()));
+            }
+        }
+        for component in &self.components {
+            if let Some(dependency) = component
+                .depends_on
+                .iter()
+                .find(|d| !index.contains_key(d.as_str()))
+            {
+                return Err(LifecycleError::UnknownDependency {
+                    component: component.name.clone(),
+                    dependency: dependency.clone(),
+                });
+            }
+        }
+
+        let mut placed = HashSet::new();
+        let mut order = Vec::with_capacity(self.components.len());
+        while order.len() < self.components.len() {
+            // The first component, by registration, whose dependencies are placed
+            let next = self.components.iter().enumerate().find(|(i, component)| {
+                !placed.contains(i)
+                    && component
+                        .depends_on
+                        .iter()
+                        .all(|d| placed.contains(&index[d.as_str()]))
+            });
+            match next {
+                Some((i, _)) => {
+                    placed.insert(i);
+                    order.push(i);
+                }
+                None => {
+                    return Err(LifecycleError::Cycle(
+                        self.components
+                            .iter()
+                            .enumerate()
+                            .filter(|(i, _)| !placed.contains(i))
+                            .map(|(_, c)| c.name.clone())
+                            .collect(),
+                    ))
+                }
+            }
+        }
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    fn component(log: &Log, name: &str) -> Component {
+        let (init, ready, shutdown) = (log.clone(), log.clone(), log.clone());
+        let (a, b, c) = (name.to_string(), name.to_string(), name.to_string());
+        Component::new(name)
+            .on_init(move || async move {
+                init.lock().push(format!("init {}", a));
+                Ok(())
+            })
+            .on_ready(move || async move {
+                ready.lock().push(format!("ready {}", b));
+                Ok(())
+            })
+            .on_shutdown(move || async move {
+                shutdown.lock().push(format!("shutdown {}", c));
+                Ok(())
+            })
+    }
+
+    #[tokio::test]
+    async fn test_dependencies_start_first_and_stop_last() {
+        let log = Log::default();
+        let mut registry = ServiceRegistry::new();
+        registry
+            .register(component(&log, "scheduler").depends_on("cache"))
+            .register(component(&log, "cache"))
+            .register(component(&log, "consumer").depends_on("scheduler"));
+
+        assert_eq!(
+            registry.order().unwrap(),
+            ["cache", "scheduler", "consumer"]
+        );
+        registry.start().await.unwrap();
+        registry.shutdown().await;
+
+        assert_eq!(
+            *log.lock(),
+            [
+                "init cache",
+                "init scheduler",
+                "init consumer",
+                "ready cache",
+                "ready scheduler",
+                "ready consumer",
+                "shutdown consumer",
+                "shutdown scheduler",
+                "shutdown cache",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_init_shuts_down_started_components() {
+        let log = Log::default();
+        let mut registry = ServiceRegistry::new();
+        registry
+            .register(component(&log, "cache"))
+            .register(Component::new("broken").on_init(|| async { anyhow::bail!("no broker") }))
+            .register(component(&log, "late"));
+
+        let err = registry.start().await.unwrap_err();
+        assert!(matches!(err, LifecycleError::Hook { ref component, .. } if component == "broken"));
+        assert_eq!(*log.lock(), ["init cache", "shutdown cache"]);
+    }
+
+    #[test]
+    fn test_invalid_graphs_rejected() {
+        let mut registry = ServiceRegistry::new();
+        registry
+            .register(Component::new("a").depends_on("b"))
+            .register(Component::new("b").depends_on("a"))
+            .register(Component::new("c"));
+        assert!(
+            matches!(registry.order(), Err(LifecycleError::Cycle(names)) if names == ["a", "b"])
+        );
+
+        let mut registry = ServiceRegistry::new();
+        registry.register(Component::new("a").depends_on("missing"));
+        assert!(matches!(
+            registry.order(),
+            Err(LifecycleError::UnknownDependency { .. })
+        ));
+
+        let mut registry = ServiceRegistry::new();
+        registry
+            .register(Component::new("a"))
+            .register(Component::new("a"));
+        assert!(matches!(
+            registry.order(),
+            Err(LifecycleError::Duplicate(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_task_aborted_on_shutdown() {
+        let mut registry = ServiceRegistry::new();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        registry.register(Component::task("worker", move || {
+            tokio::spawn(async move {
+                let _tx = tx;
+                std::future::pending::<()>().await;
+            })
+        }));
+        registry.start().await.unwrap();
+        registry.shutdown().await;
+        // The sender is dropped with the aborted task
+        assert!(rx.await.is_err());
+    }
+}
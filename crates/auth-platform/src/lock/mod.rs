@@ -0,0 +1,338 @@
+//! Cross-replica mutual exclusion
+//!
+//! A [`DistributedLock`] backend hands out leases identified by a random
+//! owner token and a monotonically increasing fencing token. Callers should
+//! pass the fencing token to whatever the lock protects, so a holder that
+//! stalled past its lease cannot overwrite the work of its successor.
+//!
+//! [`LockGuard`] keeps a lease alive in the background and tells the holder
+//! when it has been lost, e.g. after a network partition from the backend.
+
+mod mysql;
+mod redis;
+
+pub use self::mysql::MySqlLock;
+pub use self::redis::RedisLock;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Pause between attempts while waiting for a contended lock
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Debug, thiserror::Error)]
+pub enum LockError {
+    #[error("Invalid lock name '{0}'")]
+    InvalidName(String),
+
+    #[error("Timed out waiting for lock '{0}'")]
+    Timeout(String),
+
+    #[error("Lock backend error: {0}")]
+    Backend(String),
+}
+
+/// A held lock
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockLease {
+    pub name: String,
+    /// Identifies this holder to the backend
+    pub owner: String,
+    /// Strictly increasing per lock name across all holders
+    pub fencing_token: u64,
+    pub ttl: Duration,
+}
+
+#[async_trait]
+pub trait DistributedLock: Send + Sync {
+    /// Single attempt. Returns `None` when another holder has the lock.
+    async fn try_acquire(&self, name: &str, ttl: Duration) -> Result<Option<LockLease>, LockError>;
+
+    /// Extend the lease by its TTL. Returns false once the lease has been lost.
+    async fn renew(&self, lease: &LockLease) -> Result<bool, LockError>;
+
+    /// Release the lease if it is still held by its owner
+    async fn release(&self, lease: &LockLease) -> Result<(), LockError>;
+}
+
+/// A lease that renews itself until released or dropped
+pub struct LockGuard {
+    lock: Arc<dyn DistributedLock>,
+    lease: LockLease,
+    lost: watch::Receiver<bool>,
+    renewal: JoinHandle<()>,
+    released: bool,
+}
+
+impl LockGuard {
+    /// Try to take the lock once
+    pub async fn try_acquire(
+        lock: Arc<dyn DistributedLock>,
+        name: &str,
+        ttl: Duration,
+    ) -> Result<Option<Self>, LockError> {
+        Ok(lock
+            .try_acquire(name, ttl)
+            .await?
+            .map(|lease| Self::hold(lock, lease)))
+    }
+
+    /// Retry until the lock is acquired or `wait` elapses
+    pub async fn acquire(
+        lock: Arc<dyn DistributedLock>,
+        name: &str,
+        ttl: Duration,
+        wait: Duration,
+    ) -> Result<Self, LockError> {
+        let deadline = Instant::now() + wait;
+        loop {
+            if let Some(guard) = Self::try_acquire(lock.clone(), name, ttl).await? {
+                return Ok(guard);
+            }
+            if Instant::now() + RETRY_DELAY > deadline {
+                return Err(LockError::Timeout(name.to_string()));
+            }
+            tokio::time::sleep(RETRY_DELAY).await;
+        }
+    }
+
+    fn hold(lock: Arc<dyn DistributedLock>, lease: LockLease) -> Self {
+        let (lost_tx, lost) = watch::channel(false);
+        let renewal = tokio::spawn(Self::renew_loop(lock.clone(), lease.clone(), lost_tx));
+        debug!(
+            lock = %lease.name,
+            fencing_token = lease.fencing_token,
+            "Lock acquired"
+        );
+        Self {
+            lock,
+            lease,
+            lost,
+            renewal,
+            released: false,
+        }
+    }
+
+    /// Renew at a third of the TTL. Transient backend errors are retried
+    /// until the lease would have expired; after that it is reported lost.
+    async fn renew_loop(
+        lock: Arc<dyn DistributedLock>,
+        lease: LockLease,
+        lost: watch::Sender<bool>,
+    ) {
+        let interval = lease.ttl / 3;
+        let mut expires_at = Instant::now() + lease.ttl;
+
+        loop {
+            tokio::time::sleep(interval).await;
+            match lock.renew(&lease).await {
+                Ok(true) => expires_at = Instant::now() + lease.ttl,
+                Ok(false) => {
+                    warn!(lock = %lease.name, "Lock lost: lease taken over or expired");
+                    break;
+                }
+                Err(e) if Instant::now() >= expires_at => {
+                    warn!(lock = %lease.name, error = %e, "Lock lost: renewal failed until expiry");
+                    break;
+                }
+                Err(e) => {
+                    debug!(lock = %lease.name, error = %e, "Lock renewal failed; retrying");
+                }
+            }
+        }
+        let _ = lost.send(true);
+    }
+
+    pub fn lease(&self) -> &LockLease {
+        &self.lease
+    }
+
+    pub fn fencing_token(&self) -> u64 {
+        self.lease.fencing_token
+    }
+
+    pub fn is_lost(&self) -> bool {
+        *self.lost.borrow()
+    }
+
+    /// Resolves once the lease is lost. Meant for `tokio::select!` against
+    /// the protected work, which should stop as soon as this fires.
+    pub async fn lost(&mut self) {
+        while !*self.lost.borrow() {
+            if self.lost.changed().await.is_err() {
+                // The renewal task is gone, so nothing keeps the lease alive
+                return;
+            }
+        }
+    }
+
+    pub async fn release(mut self) -> Result<(), LockError> {
+        self.renewal.abort();
+        self.released = true;
+        self.lock.release(&self.lease).await
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        // Dropped without `release`: stop renewing and release in the background.
+        // A lost lease is released too; the backend ignores a stale owner.
+        self.renewal.abort();
+        let lock = self.lock.clone();
+        let lease = self.lease.clone();
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                if let Err(e) = lock.release(&lease).await {
+                    debug!(lock = %lease.name, error = %e, "Background lock release failed");
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    /// Single-process lock with controllable failures
+    #[derive(Default)]
+    struct MemoryLock {
+        held: Mutex<HashMap<String, (String, std::time::Instant)>>,
+        fence: AtomicU64,
+        fail_renewals: AtomicBool,
+    }
+
+    #[async_trait]
+    impl DistributedLock for MemoryLock {
+        async fn try_acquire(
+            &self,
+            name: &str,
+            ttl: Duration,
+        ) -> Result<Option<LockLease>, LockError> {
+            let mut held = self.held.lock();
+            let now = std::time::Instant::now();
+            if held.get(name).is_some_and(|(_, exp)| *exp > now) {
+                return Ok(None);
+            }
+            let owner = uuid::Uuid::new_v4().to_string();
+            held.insert(name.to_string(), (owner.clone(), now + ttl));
+            Ok(Some(LockLease {
+                name: name.to_string(),
+                owner,
+                fencing_token: self.fence.fetch_add(1, Ordering::SeqCst) + 1,
+                ttl,
+            }))
+        }
+
+        async fn renew(&self, lease: &LockLease) -> Result<bool, LockError> {
+            if self.fail_renewals.load(Ordering::SeqCst) {
+                return Err(LockError::Backend("unreachable".to_string()));
+            }
+            let mut held = self.held.lock();
+            match held.get_mut(&lease.name) {
+                Some((owner, exp)) if *owner == lease.owner => {
+                    *exp = std::time::Instant::now() + lease.ttl;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        }
+
+        async fn release(&self, lease: &LockLease) -> Result<(), LockError> {
+            let mut held = self.held.lock();
+            if held
+                .get(&lease.name)
+                .is_some_and(|(o, _)| *o == lease.owner)
+            {
+                held.remove(&lease.name);
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exclusive_with_increasing_fencing_tokens() {
+        let lock: Arc<dyn DistributedLock> = Arc::new(MemoryLock::default());
+        let ttl = Duration::from_secs(5);
+
+        let first = LockGuard::try_acquire(lock.clone(), "job", ttl)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(LockGuard::try_acquire(lock.clone(), "job", ttl)
+            .await
+            .unwrap()
+            .is_none());
+
+        let token = first.fencing_token();
+        first.release().await.unwrap();
+
+        let second = LockGuard::acquire(lock, "job", ttl, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(second.fencing_token() > token);
+    }
+
+    #[tokio::test]
+    async fn test_renewal_keeps_lease_alive() {
+        let lock: Arc<dyn DistributedLock> = Arc::new(MemoryLock::default());
+        let guard = LockGuard::try_acquire(lock.clone(), "job", Duration::from_millis(90))
+            .await
+            .unwrap()
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(!guard.is_lost());
+        assert!(
+            LockGuard::try_acquire(lock, "job", Duration::from_millis(90))
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_holder_notified_when_renewals_fail() {
+        let backend = Arc::new(MemoryLock::default());
+        let lock: Arc<dyn DistributedLock> = backend.clone();
+        let mut guard = LockGuard::try_acquire(lock, "job", Duration::from_millis(60))
+            .await
+            .unwrap()
+            .unwrap();
+
+        backend.fail_renewals.store(true, Ordering::SeqCst);
+        tokio::time::timeout(Duration::from_secs(2), guard.lost())
+            .await
+            .expect("loss should be signalled");
+        assert!(guard.is_lost());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_times_out() {
+        let lock: Arc<dyn DistributedLock> = Arc::new(MemoryLock::default());
+        let _held = LockGuard::try_acquire(lock.clone(), "job", Duration::from_secs(5))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let result = LockGuard::acquire(
+            lock,
+            "job",
+            Duration::from_secs(5),
+            Duration::from_millis(300),
+        )
+        .await;
+        assert!(matches!(result, Err(LockError::Timeout(_))));
+    }
+}
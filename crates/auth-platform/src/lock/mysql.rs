@@ -0,0 +1,126 @@
+//! MySQL lock built on `GET_LOCK`
+//!
+//! MySQL named locks belong to a connection, so each lease pins one pooled
+//! connection until it is released; the lease is lost if that connection
+//! dies. Named locks have no expiry, so renewal is a liveness check on the
+//! pinned connection. Fencing tokens come from a counter row per lock name
+//! in `distributed_lock_fences`.
+
+use super::{DistributedLock, LockError, LockLease};
+use async_trait::async_trait;
+use dashmap::DashMap;
+use sqlx::pool::PoolConnection;
+use sqlx::{MySql, MySqlPool};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// MySQL's limit on named lock length
+const MAX_NAME_LEN: usize = 64;
+
+pub struct MySqlLock {
+    pool: MySqlPool,
+    /// Pinned connection per held lease, keyed by owner token
+    connections: DashMap<String, Arc<Mutex<PoolConnection<MySql>>>>,
+}
+
+impl MySqlLock {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self {
+            pool,
+            connections: DashMap::new(),
+        }
+    }
+
+    async fn next_fencing_token(&self, name: &str) -> Result<u64, LockError> {
+        let mut tx = self.pool.begin().await.map_err(backend)?;
+        sqlx::query(
+            r#"
+            INSERT INTO distributed_lock_fences (name, token) VALUES (?, LAST_INSERT_ID(1))
+            ON DUPLICATE KEY UPDATE token = LAST_INSERT_ID(token + 1)
+            "#,
+        )
+        .bind(name)
+        .execute(&mut *tx)
+        .await
+        .map_err(backend)?;
+        let token: u64 = sqlx::query_scalar("SELECT LAST_INSERT_ID()")
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(backend)?;
+        tx.commit().await.map_err(backend)?;
+        Ok(token)
+    }
+}
+
+fn backend(e: sqlx::Error) -> LockError {
+    LockError::Backend(e.to_string())
+}
+
+#[async_trait]
+impl DistributedLock for MySqlLock {
+    async fn try_acquire(&self, name: &str, ttl: Duration) -> Result<Option<LockLease>, LockError> {
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(LockError::InvalidName(name.to_string()));
+        }
+
+        let mut conn = self.pool.acquire().await.map_err(backend)?;
+        let acquired: Option<i64> = sqlx::query_scalar("SELECT GET_LOCK(?, 0)")
+            .bind(name)
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(backend)?;
+        if acquired != Some(1) {
+            return Ok(None);
+        }
+
+        let fencing_token = match self.next_fencing_token(name).await {
+            Ok(token) => token,
+            Err(e) => {
+                let _ = sqlx::query("SELECT RELEASE_LOCK(?)")
+                    .bind(name)
+                    .execute(&mut *conn)
+                    .await;
+                return Err(e);
+            }
+        };
+
+        let owner = Uuid::new_v4().to_string();
+        self.connections
+            .insert(owner.clone(), Arc::new(Mutex::new(conn)));
+
+        Ok(Some(LockLease {
+            name: name.to_string(),
+            owner,
+            fencing_token,
+            ttl,
+        }))
+    }
+
+    async fn renew(&self, lease: &LockLease) -> Result<bool, LockError> {
+        let Some(conn) = self.connections.get(&lease.owner).map(|c| c.clone()) else {
+            return Ok(false);
+        };
+        let mut conn = conn.lock().await;
+        let held: Option<i64> = sqlx::query_scalar("SELECT IS_USED_LOCK(?) = CONNECTION_ID()")
+            .bind(&lease.name)
+            .fetch_one(&mut **conn)
+            .await
+            .map_err(backend)?;
+        Ok(held == Some(1))
+    }
+
+    async fn release(&self, lease: &LockLease) -> Result<(), LockError> {
+        let Some((_, conn)) = self.connections.remove(&lease.owner) else {
+            return Ok(());
+        };
+        let mut conn = conn.lock().await;
+        sqlx::query("SELECT RELEASE_LOCK(?)")
+            .bind(&lease.name)
+            .execute(&mut **conn)
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+//! Redis lock: `SET NX PX` on the lock key, with a per-name `INCR` counter
+//! for fencing tokens. Renew and release only act if the key still holds
+//! this owner's token, so an expired holder can never free a successor's lock.
+
+use super::{DistributedLock, LockError, LockLease};
+use ::redis::{Client, Script};
+use async_trait::async_trait;
+use std::time::Duration;
+use uuid::Uuid;
+
+const ACQUIRE_SCRIPT: &str = r#"
+if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+    return redis.call('INCR', KEYS[2])
+end
+return 0
+"#;
+
+const RENEW_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+pub struct RedisLock {
+    client: Client,
+    acquire: Script,
+    renew: Script,
+    release: Script,
+}
+
+impl RedisLock {
+    pub fn new(redis_url: &str) -> Result<Self, LockError> {
+        Ok(Self {
+            client: Client::open(redis_url).map_err(backend)?,
+            acquire: Script::new(ACQUIRE_SCRIPT),
+            renew: Script::new(RENEW_SCRIPT),
+            release: Script::new(RELEASE_SCRIPT),
+        })
+    }
+
+    fn lock_key(name: &str) -> String {
+        format!("lock:{}", name)
+    }
+
+    fn fence_key(name: &str) -> String {
+        format!("lock:{}:fence", name)
+    }
+}
+
+fn backend(e: ::redis::RedisError) -> LockError {
+    LockError::Backend(e.to_string())
+}
+
+fn ttl_millis(ttl: Duration) -> u64 {
+    (ttl.as_millis() as u64).max(1)
+}
+
+#[async_trait]
+impl DistributedLock for RedisLock {
+    async fn try_acquire(&self, name: &str, ttl: Duration) -> Result<Option<LockLease>, LockError> {
+        if name.is_empty() {
+            return Err(LockError::InvalidName(name.to_string()));
+        }
+        let owner = Uuid::new_v4().to_string();
+        let mut conn = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(backend)?;
+
+        let fencing_token: u64 = self
+            .acquire
+            .key(Self::lock_key(name))
+            .key(Self::fence_key(name))
+            .arg(&owner)
+            .arg(ttl_millis(ttl))
+            .invoke_async(&mut conn)
+            .await
+            .map_err(backend)?;
+
+        Ok((fencing_token > 0).then(|| LockLease {
+            name: name.to_string(),
+            owner,
+            fencing_token,
+            ttl,
+        }))
+    }
+
+    async fn renew(&self, lease: &LockLease) -> Result<bool, LockError> {
+        let mut conn = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(backend)?;
+        let renewed: i64 = self
+            .renew
+            .key(Self::lock_key(&lease.name))
+            .arg(&lease.owner)
+            .arg(ttl_millis(lease.ttl))
+            .invoke_async(&mut conn)
+            .await
+            .map_err(backend)?;
+        Ok(renewed == 1)
+    }
+
+    async fn release(&self, lease: &LockLease) -> Result<(), LockError> {
+        let mut conn = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(backend)?;
+        let _: i64 = self
+            .release
+            .key(Self::lock_key(&lease.name))
+            .arg(&lease.owner)
+            .invoke_async(&mut conn)
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+}
@@ -4,13 +4,15 @@
 //! - Policy enforcement (security classification)
 //! - Multi-process coordination (file-based leasing)
 //! - OS-level safety (socket reuse options)
+//! - Dual-stack binding (IPv4 and IPv6 on one port, leased per family)
 //! - Observability (structured logging)
 
-use crate::port_lease::{default_lease_dir, PortLease};
+use crate::port_lease::{default_lease_dir, AddressFamily, PortLease};
 use crate::port_policy::{PortClass, PortPolicy};
-use crate::safe_socket::{bind_with_reuse, ManagedListener};
+use crate::safe_socket::{bind_v6_only, bind_with_reuse, ManagedListener};
 use dashmap::DashMap;
-use std::net::SocketAddr;
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::PathBuf;
 use std::sync::Arc;
 use tracing::{debug, error, info, warn};
@@ -18,7 +20,7 @@ use tracing::{debug, error, info, warn};
 /// Port authority - manages port lifecycle
 pub struct PortAuthority {
     /// In-memory registry of active leases
-    lease_registry: Arc<DashMap<u16, PortLease>>,
+    lease_registry: Arc<DashMap<(u16, AddressFamily), PortLease>>,
 
     /// Directory for lease files
     lease_dir: PathBuf,
@@ -51,6 +53,50 @@ pub enum PortError {
 
     #[error("Lease error: {0}")]
     Lease(String),
+
+    #[error("No lease exists for port {0}")]
+    LeaseNotFound(u16),
+
+    #[error("Port {0} is leased by this process")]
+    OwnLease(u16),
+
+    #[error("Invalid bind host '{host}': {reason}")]
+    InvalidHost { host: String, reason: &'static str },
+}
+
+/// Addresses to bind for `host`: the host itself and, for `dual_stack`, its
+/// counterpart in the other family. `host` is an IP literal, with IPv6
+/// optionally in brackets (`[::1]`), or `localhost`. Only wildcard and
+/// loopback hosts have a counterpart.
+pub fn bind_addrs(host: &str, port: u16, dual_stack: bool) -> Result<Vec<SocketAddr>, PortError> {
+    let invalid = |reason| PortError::InvalidHost {
+        host: host.to_string(),
+        reason,
+    };
+    let literal = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    let ip: IpAddr = if literal.eq_ignore_ascii_case("localhost") {
+        Ipv4Addr::LOCALHOST.into()
+    } else {
+        literal
+            .parse()
+            .map_err(|_| invalid("expected an IP address"))?
+    };
+
+    let mut addrs = vec![SocketAddr::new(ip, port)];
+    if dual_stack {
+        let other: IpAddr = match ip {
+            IpAddr::V4(v4) if v4.is_unspecified() => Ipv6Addr::UNSPECIFIED.into(),
+            IpAddr::V4(v4) if v4.is_loopback() => Ipv6Addr::LOCALHOST.into(),
+            IpAddr::V6(v6) if v6.is_unspecified() => Ipv4Addr::UNSPECIFIED.into(),
+            IpAddr::V6(v6) if v6.is_loopback() => Ipv4Addr::LOCALHOST.into(),
+            _ => return Err(invalid("dual-stack needs a wildcard or loopback host")),
+        };
+        addrs.push(SocketAddr::new(other, port));
+    }
+    Ok(addrs)
 }
 
 impl PortAuthority {
@@ -71,20 +117,45 @@ impl PortAuthority {
         })
     }
 
-    /// Acquire a port with policy enforcement
+    /// Acquire a port on `host` alone, ignoring the policy's `dual_stack`
+    ///
+    /// See [`PortAuthority::acquire_listeners`].
+    pub async fn acquire(
+        &self,
+        policy: &PortPolicy,
+        host: &str,
+    ) -> Result<ManagedListener, PortError> {
+        let addrs = bind_addrs(host, 0, false)?;
+        let mut listeners = self.acquire_on(policy, &addrs).await?;
+        Ok(listeners.remove(0))
+    }
+
+    /// Acquire a port with policy enforcement, one listener per address
+    /// family: two for a dual-stack policy, on the same port
     ///
     /// This is the main entry point for securing a port. It will:
     /// 1. Validate the policy
     /// 2. Try preferred port first
     /// 3. Fall back to range if allowed by policy
     /// 4. Bind with OS-level safety options
-    /// 5. Create a lease file
+    /// 5. Create a lease file per family
     /// 6. Log the acquisition with full context
-    pub async fn acquire(
+    ///
+    /// A candidate port is only taken if every family can bind it.
+    pub async fn acquire_listeners(
         &self,
         policy: &PortPolicy,
         host: &str,
-    ) -> Result<ManagedListener, PortError> {
+    ) -> Result<Vec<ManagedListener>, PortError> {
+        let addrs = bind_addrs(host, 0, policy.dual_stack)?;
+        self.acquire_on(policy, &addrs).await
+    }
+
+    async fn acquire_on(
+        &self,
+        policy: &PortPolicy,
+        addrs: &[SocketAddr],
+    ) -> Result<Vec<ManagedListener>, PortError> {
         // Validate policy first (fail fast)
         policy.validate()?;
 
@@ -103,11 +174,12 @@ impl PortAuthority {
 
         // Try each candidate port
         for (index, port) in candidates.iter().enumerate() {
-            match self.try_acquire_port(*port, host, policy, index > 0).await {
-                Ok(listener) => {
+            match self.try_acquire_all(*port, addrs, policy, index > 0).await {
+                Ok(listeners) => {
                     info!(
                         event = "port.bound",
-                        port = port,
+                        port = listeners[0].port(),
+                        families = listeners.len(),
                         pid = pid,
                         service = %policy.service_name,
                         class = ?policy.class,
@@ -116,7 +188,7 @@ impl PortAuthority {
                         "Port successfully acquired"
                     );
 
-                    return Ok(listener);
+                    return Ok(listeners);
                 }
                 Err(e) => {
                     debug!(
@@ -151,18 +223,60 @@ impl PortAuthority {
         }
     }
 
-    /// Try to acquire a specific port
-    async fn try_acquire_port(
+    /// Try to acquire a specific port on every address. On failure the
+    /// families already bound are given back, so the next candidate starts
+    /// clean.
+    async fn try_acquire_all(
         &self,
         port: u16,
-        host: &str,
+        addrs: &[SocketAddr],
+        policy: &PortPolicy,
+        is_fallback: bool,
+    ) -> Result<Vec<ManagedListener>, PortError> {
+        let dual_stack = addrs.len() > 1;
+        let mut listeners: Vec<ManagedListener> = Vec::with_capacity(addrs.len());
+        for addr in addrs {
+            // Port 0 is resolved by the first bind; the others follow it
+            let port = listeners.first().map_or(port, |l| l.port());
+            let addr = SocketAddr::new(addr.ip(), port);
+            match self
+                .try_acquire_port(
+                    addr,
+                    policy,
+                    is_fallback && listeners.is_empty(),
+                    dual_stack,
+                )
+                .await
+            {
+                Ok(listener) => listeners.push(listener),
+                Err(e) => {
+                    for bound in &listeners {
+                        let family = AddressFamily::of(&bound.local_addr()?);
+                        self.lease_registry.remove(&(bound.port(), family));
+                        PortLease::delete(&self.lease_dir, bound.port(), family).await?;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(listeners)
+    }
+
+    /// Try to acquire a specific port on one address
+    async fn try_acquire_port(
+        &self,
+        addr: SocketAddr,
         policy: &PortPolicy,
         is_fallback: bool,
+        dual_stack: bool,
     ) -> Result<ManagedListener, PortError> {
+        let port = addr.port();
+        let family = AddressFamily::of(&addr);
+
         // Check if port is available (reclaim zombie leases)
-        if !PortLease::is_port_available(&self.lease_dir, port).await? {
+        if !PortLease::is_port_available(&self.lease_dir, port, family).await? {
             // Port has a valid lease
-            if let Some(existing_lease) = PortLease::load(&self.lease_dir, port).await? {
+            if let Some(existing_lease) = PortLease::load(&self.lease_dir, port, family).await? {
                 return Err(PortError::PortOccupied {
                     port,
                     service: existing_lease.service_name.clone(),
@@ -197,22 +311,23 @@ impl PortAuthority {
             }
         }
 
-        // Try to bind with OS-level safety
-        let addr: SocketAddr = format!("{}:{}", host, port)
-            .parse()
-            .map_err(|e| PortError::Lease(format!("Invalid address: {}", e)))?;
-
-        let listener = bind_with_reuse(addr, &policy.service_name)?;
+        // Try to bind with OS-level safety; with both families bound, the
+        // IPv6 socket must not claim IPv4 as well
+        let listener = if dual_stack {
+            bind_v6_only(addr, &policy.service_name)?
+        } else {
+            bind_with_reuse(addr, &policy.service_name)?
+        };
 
         // Get the actual bound port (important for port 0 = OS-assigned)
         let actual_port = listener.port();
 
         // Create and save lease with actual port
-        let lease = PortLease::new(actual_port, &policy.service_name);
+        let lease = PortLease::new(actual_port, &policy.service_name).with_family(family);
         lease.save(&self.lease_dir)?;
 
         // Register in memory with actual port
-        self.lease_registry.insert(actual_port, lease);
+        self.lease_registry.insert((actual_port, family), lease);
 
         Ok(listener)
     }
@@ -242,20 +357,25 @@ impl PortAuthority {
         candidates
     }
 
-    /// Release a port and cleanup lease
+    /// Release a port and cleanup its leases, in every family this
+    /// process holds it in
     pub async fn release(&self, port: u16) -> Result<(), PortError> {
-        // Remove from in-memory registry
-        if let Some((_, lease)) = self.lease_registry.remove(&port) {
+        for family in [AddressFamily::V4, AddressFamily::V6] {
+            // Remove from in-memory registry
+            let Some((_, lease)) = self.lease_registry.remove(&(port, family)) else {
+                continue;
+            };
             info!(
                 event = "port.released",
                 port = port,
+                family = ?family,
                 service = %lease.service_name,
                 "Port released"
             );
-        }
 
-        // Delete lease file
-        PortLease::delete(&self.lease_dir, port).await?;
+            // Delete lease file
+            PortLease::delete(&self.lease_dir, port, family).await?;
+        }
 
         Ok(())
     }
@@ -269,18 +389,9 @@ impl PortAuthority {
 
         for entry in entries {
             let entry = entry?;
-            let path = entry.path();
-
-            if path.extension().and_then(|s| s.to_str()) == Some("lease") {
-                // Extract port from filename
-                if let Some(filename) = path.file_stem().and_then(|s| s.to_str()) {
-                    if let Some(port_str) = filename.strip_prefix("port-") {
-                        if let Ok(port) = port_str.parse::<u16>() {
-                            if PortLease::reclaim(&self.lease_dir, port).await? {
-                                reclaimed.push(port);
-                            }
-                        }
-                    }
+            if let Some((port, family)) = PortLease::key_from_path(&entry.path()) {
+                if PortLease::reclaim(&self.lease_dir, port, family).await? {
+                    reclaimed.push(port);
                 }
             }
         }
@@ -302,6 +413,84 @@ impl PortAuthority {
             .map(|entry| entry.value().clone())
             .collect()
     }
+
+    /// Status of every lease on disk, across all processes sharing the lease directory
+    pub async fn lease_status(&self) -> Result<Vec<LeaseStatus>, PortError> {
+        Ok(PortLease::list(&self.lease_dir)
+            .await?
+            .into_iter()
+            .map(|lease| LeaseStatus {
+                port: lease.port,
+                family: lease.family,
+                pid: lease.pid,
+                service_name: lease.service_name.clone(),
+                age_seconds: lease.age().as_secs(),
+                owner_alive: lease.is_valid(),
+                held_by_self: self
+                    .lease_registry
+                    .contains_key(&(lease.port, lease.family)),
+            })
+            .collect())
+    }
+
+    /// Remove a lease on operator request.
+    ///
+    /// Leases whose owner is dead are always reclaimed. A lease whose PID is
+    /// still alive is only removed with `force`, for the case where the PID
+    /// was recycled by an unrelated process. Leases held by this process are
+    /// never reclaimed; release them through [`PortAuthority::release`].
+    pub async fn reclaim_lease(
+        &self,
+        port: u16,
+        family: AddressFamily,
+        force: bool,
+    ) -> Result<PortLease, PortError> {
+        if self.lease_registry.contains_key(&(port, family)) {
+            return Err(PortError::OwnLease(port));
+        }
+
+        let lease = PortLease::load(&self.lease_dir, port, family)
+            .await?
+            .ok_or(PortError::LeaseNotFound(port))?;
+
+        let alive = lease.is_valid();
+        if alive && !force {
+            return Err(PortError::PortOccupied {
+                port,
+                service: lease.service_name.clone(),
+                pid: lease.pid,
+            });
+        }
+
+        PortLease::delete(&self.lease_dir, port, family).await?;
+
+        warn!(
+            event = "port.lease.reclaimed",
+            port = port,
+            family = ?family,
+            previous_pid = lease.pid,
+            previous_service = %lease.service_name,
+            owner_alive = alive,
+            forced = force,
+            "Lease reclaimed by operator"
+        );
+
+        Ok(lease)
+    }
+}
+
+/// Operator view of a lease
+#[derive(Debug, Clone, Serialize)]
+pub struct LeaseStatus {
+    pub port: u16,
+    pub family: AddressFamily,
+    pub pid: u32,
+    pub service_name: String,
+    pub age_seconds: u64,
+    /// Whether the owning PID is still running
+    pub owner_alive: bool,
+    /// Whether the lease belongs to this process
+    pub held_by_self: bool,
 }
 
 #[cfg(test)]
@@ -377,4 +566,140 @@ mod tests {
         let listener2 = authority.acquire(&policy2, "127.0.0.1").await.unwrap();
         assert_eq!(listener2.port(), port);
     }
+
+    #[tokio::test]
+    async fn test_lease_status_lists_all_processes() {
+        let temp_dir = TempDir::new().unwrap();
+        let authority = PortAuthority::with_lease_dir(temp_dir.path().to_path_buf()).unwrap();
+
+        let policy = PortPolicy::new(0, PortClass::Public, "own");
+        let listener = authority.acquire(&policy, "127.0.0.1").await.unwrap();
+
+        let mut zombie = PortLease::new(1, "zombie");
+        zombie.pid = 99999;
+        zombie.save(temp_dir.path()).unwrap();
+
+        let status = authority.lease_status().await.unwrap();
+        assert_eq!(status.len(), 2);
+
+        let own = status.iter().find(|s| s.port == listener.port()).unwrap();
+        assert!(own.held_by_self && own.owner_alive);
+
+        let zombie = status.iter().find(|s| s.port == 1).unwrap();
+        assert!(!zombie.held_by_self && !zombie.owner_alive);
+    }
+
+    #[tokio::test]
+    async fn test_reclaim_lease() {
+        let temp_dir = TempDir::new().unwrap();
+        let authority = PortAuthority::with_lease_dir(temp_dir.path().to_path_buf()).unwrap();
+
+        // Dead owner: reclaimed without force
+        let mut zombie = PortLease::new(1, "zombie");
+        zombie.pid = 99999;
+        zombie.save(temp_dir.path()).unwrap();
+        assert_eq!(
+            authority
+                .reclaim_lease(1, AddressFamily::V4, false)
+                .await
+                .unwrap()
+                .pid,
+            99999
+        );
+        assert!(matches!(
+            authority.reclaim_lease(1, AddressFamily::V4, false).await,
+            Err(PortError::LeaseNotFound(1))
+        ));
+
+        // Live owner (the test runner, standing in for another process): force required
+        PortLease::new(2, "other").save(temp_dir.path()).unwrap();
+        assert!(matches!(
+            authority.reclaim_lease(2, AddressFamily::V4, false).await,
+            Err(PortError::PortOccupied { .. })
+        ));
+        assert!(authority
+            .reclaim_lease(2, AddressFamily::V4, true)
+            .await
+            .is_ok());
+
+        // Own leases are never reclaimed
+        let policy = PortPolicy::new(0, PortClass::Public, "own");
+        let listener = authority.acquire(&policy, "127.0.0.1").await.unwrap();
+        assert!(matches!(
+            authority
+                .reclaim_lease(listener.port(), AddressFamily::V4, true)
+                .await,
+            Err(PortError::OwnLease(_))
+        ));
+    }
+
+    #[test]
+    fn test_bind_addrs() {
+        let addrs = |host, dual| {
+            bind_addrs(host, 8080, dual)
+                .map(|a| a.iter().map(|addr| addr.to_string()).collect::<Vec<_>>())
+        };
+
+        assert_eq!(addrs("::1", false).unwrap(), vec!["[::1]:8080"]);
+        assert_eq!(addrs("[::1]", false).unwrap(), vec!["[::1]:8080"]);
+        assert_eq!(
+            addrs("0.0.0.0", true).unwrap(),
+            vec!["0.0.0.0:8080", "[::]:8080"]
+        );
+        assert_eq!(
+            addrs("[::1]", true).unwrap(),
+            vec!["[::1]:8080", "127.0.0.1:8080"]
+        );
+        assert!(matches!(
+            addrs("10.0.0.1", true),
+            Err(PortError::InvalidHost { .. })
+        ));
+        assert!(matches!(
+            addrs("::1:8080:", false),
+            Err(PortError::InvalidHost { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dual_stack_leases_each_family() {
+        // Hosts without IPv6 loopback cannot run this
+        if std::net::TcpListener::bind("[::1]:0").is_err() {
+            return;
+        }
+        let temp_dir = TempDir::new().unwrap();
+        let authority = PortAuthority::with_lease_dir(temp_dir.path().to_path_buf()).unwrap();
+
+        // A free port above the privileged range, picked by the OS
+        let free = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let free_port = free.local_addr().unwrap().port();
+        drop(free);
+
+        let policy = PortPolicy::new(free_port, PortClass::Public, "dual").with_dual_stack();
+        let listeners = authority
+            .acquire_listeners(&policy, "127.0.0.1")
+            .await
+            .unwrap();
+        assert_eq!(listeners.len(), 2);
+        let port = listeners[0].port();
+        assert_eq!(listeners[1].port(), port);
+        assert!(listeners[1].local_addr().unwrap().is_ipv6());
+
+        let families: Vec<_> = authority
+            .lease_status()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.port, s.family, s.held_by_self))
+            .collect();
+        assert_eq!(
+            families,
+            vec![
+                (port, AddressFamily::V4, true),
+                (port, AddressFamily::V6, true)
+            ]
+        );
+
+        authority.release(port).await.unwrap();
+        assert!(authority.lease_status().await.unwrap().is_empty());
+    }
 }
@@ -6,17 +6,45 @@
 
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use sysinfo::{Pid, System};
 use tracing::{debug, info};
 
+/// Address family of a leased port. IPv4 and IPv6 listeners on the same
+/// port are leased separately.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFamily {
+    /// Also what leases written before families were tracked are read as
+    #[default]
+    V4,
+    V6,
+}
+
+impl AddressFamily {
+    pub fn of(addr: &SocketAddr) -> Self {
+        if addr.is_ipv4() {
+            Self::V4
+        } else {
+            Self::V6
+        }
+    }
+}
+
 /// Port lease with process ownership tracking
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortLease {
     /// Leased port number
     pub port: u16,
 
+    /// Family of the listener holding the port
+    #[serde(default)]
+    pub family: AddressFamily,
+
     /// Process ID of the owner
     pub pid: u32,
 
@@ -36,6 +64,7 @@ impl PortLease {
     pub fn new(port: u16, service_name: impl Into<String>) -> Self {
         Self {
             port,
+            family: AddressFamily::V4,
             pid: std::process::id(),
             service_name: service_name.into(),
             acquired_at: SystemTime::now(),
@@ -43,6 +72,12 @@ impl PortLease {
         }
     }
 
+    /// Lease the port for `family` instead of IPv4
+    pub fn with_family(mut self, family: AddressFamily) -> Self {
+        self.family = family;
+        self
+    }
+
     /// Check if the owning process is still alive
     pub fn is_valid(&self) -> bool {
         let mut system = System::new();
@@ -63,6 +98,11 @@ impl PortLease {
         exists
     }
 
+    /// Time since the lease was acquired
+    pub fn age(&self) -> Duration {
+        self.acquired_at.elapsed().unwrap_or_default()
+    }
+
     /// Get a boot identifier (simple timestamp-based for now)
     fn get_boot_id() -> String {
         // In production, this could read from /proc/sys/kernel/random/boot_id on Linux
@@ -74,12 +114,13 @@ impl PortLease {
     pub fn save(&self, lease_dir: &Path) -> std::io::Result<()> {
         fs::create_dir_all(lease_dir)?;
 
-        let lease_path = Self::lease_path(lease_dir, self.port);
+        let lease_path = Self::lease_path(lease_dir, self.port, self.family);
         let json = serde_json::to_string_pretty(self)?;
         fs::write(&lease_path, json)?;
 
         debug!(
             port = self.port,
+            family = ?self.family,
             pid = self.pid,
             path = ?lease_path,
             "Lease saved"
@@ -89,43 +130,54 @@ impl PortLease {
     }
 
     /// Load lease from file
-    pub async fn load(lease_dir: &Path, port: u16) -> std::io::Result<Option<Self>> {
-        let lease_path = Self::lease_path(lease_dir, port);
+    pub async fn load(
+        lease_dir: &Path,
+        port: u16,
+        family: AddressFamily,
+    ) -> std::io::Result<Option<Self>> {
+        let lease_path = Self::lease_path(lease_dir, port, family);
 
         if !lease_path.exists() {
             return Ok(None);
         }
 
         let json = fs::read_to_string(&lease_path)?;
-        let lease: Self = serde_json::from_str(&json)?;
+        let mut lease: Self = serde_json::from_str(&json)?;
+        // The file name is authoritative for leases written before the field
+        lease.family = family;
 
         Ok(Some(lease))
     }
 
     /// Delete lease file
-    pub async fn delete(lease_dir: &Path, port: u16) -> std::io::Result<()> {
-        let lease_path = Self::lease_path(lease_dir, port);
+    pub async fn delete(lease_dir: &Path, port: u16, family: AddressFamily) -> std::io::Result<()> {
+        let lease_path = Self::lease_path(lease_dir, port, family);
 
         if lease_path.exists() {
             fs::remove_file(&lease_path)?;
-            debug!(port = port, path = ?lease_path, "Lease deleted");
+            debug!(port = port, family = ?family, path = ?lease_path, "Lease deleted");
         }
 
         Ok(())
     }
 
     /// Reclaim lease from a dead process
-    pub async fn reclaim(lease_dir: &Path, port: u16) -> std::io::Result<bool> {
-        if let Some(lease) = Self::load(lease_dir, port).await? {
+    pub async fn reclaim(
+        lease_dir: &Path,
+        port: u16,
+        family: AddressFamily,
+    ) -> std::io::Result<bool> {
+        if let Some(lease) = Self::load(lease_dir, port, family).await? {
             if !lease.is_valid() {
                 info!(
                     port = port,
+                    family = ?family,
                     previous_pid = lease.pid,
                     previous_service = %lease.service_name,
                     "Reclaiming zombie lease"
                 );
 
-                Self::delete(lease_dir, port).await?;
+                Self::delete(lease_dir, port, family).await?;
                 return Ok(true);
             }
         }
@@ -133,16 +185,55 @@ impl PortLease {
         Ok(false)
     }
 
+    /// Load every lease in the directory, including other processes' leases.
+    /// Unreadable or malformed files are skipped.
+    pub async fn list(lease_dir: &Path) -> std::io::Result<Vec<Self>> {
+        let mut leases = Vec::new();
+
+        for entry in fs::read_dir(lease_dir)? {
+            let path = entry?.path();
+            let Some((port, family)) = Self::key_from_path(&path) else {
+                continue;
+            };
+            match Self::load(lease_dir, port, family).await {
+                Ok(Some(lease)) => leases.push(lease),
+                Ok(None) => {}
+                Err(e) => debug!(path = ?path, error = %e, "Skipping unreadable lease"),
+            }
+        }
+
+        leases.sort_by_key(|lease| (lease.port, lease.family));
+        Ok(leases)
+    }
+
+    /// Parse the port and family out of a `port-<n>.lease` (IPv4) or
+    /// `port-<n>-v6.lease` file name
+    pub(crate) fn key_from_path(path: &Path) -> Option<(u16, AddressFamily)> {
+        if path.extension().and_then(|s| s.to_str()) != Some("lease") {
+            return None;
+        }
+        let stem = path.file_stem()?.to_str()?.strip_prefix("port-")?;
+        match stem.strip_suffix("-v6") {
+            Some(port) => Some((port.parse().ok()?, AddressFamily::V6)),
+            None => Some((stem.parse().ok()?, AddressFamily::V4)),
+        }
+    }
+
     /// Check if port is available (no valid lease exists)
-    pub async fn is_port_available(lease_dir: &Path, port: u16) -> std::io::Result<bool> {
+    pub async fn is_port_available(
+        lease_dir: &Path,
+        port: u16,
+        family: AddressFamily,
+    ) -> std::io::Result<bool> {
         // First try to reclaim any zombie leases
-        Self::reclaim(lease_dir, port).await?;
+        Self::reclaim(lease_dir, port, family).await?;
 
         // Then check if a valid lease exists
-        if let Some(lease) = Self::load(lease_dir, port).await? {
+        if let Some(lease) = Self::load(lease_dir, port, family).await? {
             if lease.is_valid() {
                 debug!(
                     port = port,
+                    family = ?family,
                     owner_pid = lease.pid,
                     owner_service = %lease.service_name,
                     "Port already leased"
@@ -154,9 +245,13 @@ impl PortLease {
         Ok(true)
     }
 
-    /// Get the lease file path for a port
-    fn lease_path(lease_dir: &Path, port: u16) -> PathBuf {
-        lease_dir.join(format!("port-{}.lease", port))
+    /// Get the lease file path for a port; IPv4 keeps the name used before
+    /// leases were per family
+    fn lease_path(lease_dir: &Path, port: u16, family: AddressFamily) -> PathBuf {
+        match family {
+            AddressFamily::V4 => lease_dir.join(format!("port-{}.lease", port)),
+            AddressFamily::V6 => lease_dir.join(format!("port-{}-v6.lease", port)),
+        }
     }
 }
 
@@ -179,7 +274,9 @@ mod tests {
         let lease = PortLease::new(8081, "test-service");
         lease.save(lease_dir).unwrap();
 
-        let loaded = PortLease::load(lease_dir, 8081).unwrap();
+        let loaded = PortLease::load(lease_dir, 8081, AddressFamily::V4)
+            .await
+            .unwrap();
         assert!(loaded.is_some());
 
         let loaded = loaded.unwrap();
@@ -213,11 +310,15 @@ mod tests {
         zombie_lease.save(lease_dir).unwrap();
 
         // Reclaim should succeed
-        let reclaimed = PortLease::reclaim(lease_dir, 8081).await.unwrap();
+        let reclaimed = PortLease::reclaim(lease_dir, 8081, AddressFamily::V4)
+            .await
+            .unwrap();
         assert!(reclaimed);
 
         // Lease should be gone
-        let loaded = PortLease::load(lease_dir, 8081).await.unwrap();
+        let loaded = PortLease::load(lease_dir, 8081, AddressFamily::V4)
+            .await
+            .unwrap();
         assert!(loaded.is_none());
     }
 
@@ -227,19 +328,58 @@ mod tests {
         let lease_dir = temp_dir.path();
 
         // Port should be available initially
-        assert!(PortLease::is_port_available(lease_dir, 8081).unwrap());
+        assert!(
+            PortLease::is_port_available(lease_dir, 8081, AddressFamily::V4)
+                .await
+                .unwrap()
+        );
 
         // Lease the port
         let lease = PortLease::new(8081, "test");
         lease.save(lease_dir).unwrap();
 
         // Port should NOT be available
-        assert!(!PortLease::is_port_available(lease_dir, 8081).unwrap());
+        assert!(
+            !PortLease::is_port_available(lease_dir, 8081, AddressFamily::V4)
+                .await
+                .unwrap()
+        );
 
         // Delete the lease
-        PortLease::delete(lease_dir, 8081).unwrap();
+        PortLease::delete(lease_dir, 8081, AddressFamily::V4)
+            .await
+            .unwrap();
 
         // Port should be available again
-        assert!(PortLease::is_port_available(lease_dir, 8081).await.unwrap());
+        assert!(
+            PortLease::is_port_available(lease_dir, 8081, AddressFamily::V4)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_families_are_leased_separately() {
+        let temp_dir = TempDir::new().unwrap();
+        let lease_dir = temp_dir.path();
+
+        PortLease::new(8081, "v4").save(lease_dir).unwrap();
+        assert!(
+            PortLease::is_port_available(lease_dir, 8081, AddressFamily::V6)
+                .await
+                .unwrap()
+        );
+
+        PortLease::new(8081, "v6")
+            .with_family(AddressFamily::V6)
+            .save(lease_dir)
+            .unwrap();
+        let leases = PortLease::list(lease_dir).await.unwrap();
+        let families: Vec<_> = leases.iter().map(|l| (l.port, l.family)).collect();
+        assert_eq!(
+            families,
+            vec![(8081, AddressFamily::V4), (8081, AddressFamily::V6)]
+        );
+        assert_eq!(leases[1].service_name, "v6");
     }
 }
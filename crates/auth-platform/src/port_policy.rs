@@ -33,6 +33,11 @@ pub struct PortPolicy {
 
     /// Service name for logging and lease tracking
     pub service_name: String,
+
+    /// Listen on IPv4 and IPv6 together: the host's address in the other
+    /// family is bound on the same port, each leased separately
+    #[serde(default)]
+    pub dual_stack: bool,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -58,6 +63,7 @@ impl PortPolicy {
             fallback_range: None,
             class,
             service_name: service_name.into(),
+            dual_stack: false,
         }
     }
 
@@ -67,6 +73,12 @@ impl PortPolicy {
         self
     }
 
+    /// Bind both address families
+    pub fn with_dual_stack(mut self) -> Self {
+        self.dual_stack = true;
+        self
+    }
+
     /// Validate port policy before use
     pub fn validate(&self) -> Result<(), PolicyError> {
         // Prevent privileged ports
@@ -120,6 +132,7 @@ impl Default for PortPolicy {
             fallback_range: Some(8082..=8090),
             class: PortClass::Public,
             service_name: "default".to_string(),
+            dual_stack: false,
         }
     }
 }
@@ -59,6 +59,17 @@ impl ManagedListener {
 /// - SO_REUSEADDR: Allows immediate reuse of ports in TIME_WAIT (critical for Windows)
 /// - SO_REUSEPORT: On Unix, allows multiple processes to bind to the same port
 pub fn bind_with_reuse(addr: SocketAddr, service_name: &str) -> std::io::Result<ManagedListener> {
+    bind(addr, service_name, false)
+}
+
+/// Like [`bind_with_reuse`], but an IPv6 socket accepts IPv6 connections
+/// only (IPV6_V6ONLY), leaving the port free for an IPv4 listener. Whether
+/// a plain IPv6 socket also takes IPv4 connections differs by OS.
+pub fn bind_v6_only(addr: SocketAddr, service_name: &str) -> std::io::Result<ManagedListener> {
+    bind(addr, service_name, addr.is_ipv6())
+}
+
+fn bind(addr: SocketAddr, service_name: &str, only_v6: bool) -> std::io::Result<ManagedListener> {
     let domain = if addr.is_ipv4() {
         Domain::IPV4
     } else {
@@ -71,6 +82,10 @@ pub fn bind_with_reuse(addr: SocketAddr, service_name: &str) -> std::io::Result<
     // Without this, ports remain in TIME_WAIT and refuse to bind
     socket.set_reuse_address(true)?;
 
+    if only_v6 {
+        socket.set_only_v6(true)?;
+    }
+
     #[cfg(unix)]
     {
         // On Unix, also set SO_REUSEPORT for load balancing across processes
@@ -2,22 +2,42 @@
 //!
 //! Provides utilities for gracefully shutting down services while draining
 //! active connections to prevent dropped requests during restarts.
+//!
+//! Load balancers keep routing to an instance until its health checks fail,
+//! so on the shutdown signal the instance first reports itself not ready,
+//! then waits out the deregistration delay while still serving, and only
+//! then stops accepting connections and drains the ones it has.
 
 use std::future::Future;
 use std::time::Duration;
 #[cfg(not(unix))]
 use tokio::signal;
+use tokio::sync::watch;
 use tracing::info;
 
 /// Graceful shutdown coordinator
 pub struct GracefulShutdown {
     drain_timeout: Duration,
+    deregistration_delay: Duration,
+    /// Flipped once listeners should stop accepting connections
+    draining: watch::Sender<bool>,
 }
 
 impl GracefulShutdown {
     /// Create a new graceful shutdown coordinator
     pub fn new(drain_timeout: Duration) -> Self {
-        Self { drain_timeout }
+        Self {
+            drain_timeout,
+            deregistration_delay: Duration::ZERO,
+            draining: watch::channel(false).0,
+        }
+    }
+
+    /// Keep serving for `delay` after the signal, so load balancers notice
+    /// the failing readiness check and stop sending new requests
+    pub fn with_deregistration_delay(mut self, delay: Duration) -> Self {
+        self.deregistration_delay = delay;
+        self
     }
 
     /// Get the drain timeout
@@ -25,6 +45,42 @@ impl GracefulShutdown {
         self.drain_timeout
     }
 
+    /// Get the deregistration delay
+    pub fn deregistration_delay(&self) -> Duration {
+        self.deregistration_delay
+    }
+
+    /// Resolves when listeners should stop accepting connections; pass to
+    /// `axum::serve(..).with_graceful_shutdown`
+    pub fn draining(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut rx = self.draining.subscribe();
+        async move {
+            // A dropped coordinator also ends serving
+            let _ = rx.wait_for(|draining| *draining).await;
+        }
+    }
+
+    /// Wait for the shutdown signal, run `on_signal` (flip readiness), wait
+    /// out the deregistration delay and then start draining
+    pub async fn signal_then_drain<F: FnOnce()>(&self, on_signal: F) {
+        self.wait_for_signal().await;
+        info!("Shutdown signal received, initiating graceful shutdown");
+        on_signal();
+        self.begin_draining().await;
+    }
+
+    async fn begin_draining(&self) {
+        if !self.deregistration_delay.is_zero() {
+            info!(
+                delay_seconds = self.deregistration_delay.as_secs_f64(),
+                "Reporting not ready; waiting for load balancers to deregister"
+            );
+            tokio::time::sleep(self.deregistration_delay).await;
+        }
+        info!("Draining connections");
+        self.draining.send_replace(true);
+    }
+
     /// Wait for shutdown signal (Ctrl+C or SIGTERM)
     pub async fn wait_for_signal(&self) {
         #[cfg(unix)]
@@ -117,4 +173,19 @@ mod tests {
         let shutdown = GracefulShutdown::default();
         assert_eq!(shutdown.drain_timeout(), Duration::from_secs(30));
     }
+
+    #[tokio::test]
+    async fn test_draining_waits_for_deregistration_delay() {
+        let shutdown = GracefulShutdown::new(Duration::from_secs(10))
+            .with_deregistration_delay(Duration::from_millis(50));
+        let draining = tokio::spawn(shutdown.draining());
+
+        let start = std::time::Instant::now();
+        shutdown.begin_draining().await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        draining.await.unwrap();
+
+        // Listeners that start waiting afterwards see it too
+        shutdown.draining().await;
+    }
 }
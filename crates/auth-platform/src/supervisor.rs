@@ -0,0 +1,216 @@
+//! Supervision of background workers
+//!
+//! A worker spawned with a bare `tokio::spawn` that panics is gone for the
+//! life of the process, and nothing but a log line says so. Workers started
+//! through a [`WorkerSupervisor`] have their panics counted, and those that
+//! can be rebuilt are restarted with exponential backoff, since state they
+//! held across the panic (locks, half-applied batches) cannot be trusted.
+
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::{error, info};
+
+/// How panicked background workers are handled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupervisorConfig {
+    /// Restart workers that panic; when off they stay stopped
+    #[serde(default = "default_restart_on_panic")]
+    pub restart_on_panic: bool,
+
+    /// Delay before the first restart, doubled after each further panic
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+
+    #[serde(default = "default_max_backoff_seconds")]
+    pub max_backoff_seconds: u64,
+
+    /// Running this long without a panic resets the backoff
+    #[serde(default = "default_healthy_after_seconds")]
+    pub healthy_after_seconds: u64,
+}
+
+fn default_restart_on_panic() -> bool {
+    true
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    1000
+}
+
+fn default_max_backoff_seconds() -> u64 {
+    60
+}
+
+fn default_healthy_after_seconds() -> u64 {
+    300
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            restart_on_panic: default_restart_on_panic(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_seconds: default_max_backoff_seconds(),
+            healthy_after_seconds: default_healthy_after_seconds(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WorkerSupervisor {
+    config: SupervisorConfig,
+}
+
+impl WorkerSupervisor {
+    pub fn new(config: SupervisorConfig) -> Self {
+        Self { config }
+    }
+
+    /// Run the worker built by `make`, building and starting a fresh one
+    /// whenever it panics. A worker that returns or is cancelled is not
+    /// restarted.
+    pub fn spawn<F, Fut>(&self, name: &str, mut make: F) -> JoinHandle<()>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let config = self.config.clone();
+        let name = name.to_string();
+        let initial = Duration::from_millis(config.initial_backoff_ms);
+        let max = Duration::from_secs(config.max_backoff_seconds).max(initial);
+        let healthy_after = Duration::from_secs(config.healthy_after_seconds);
+
+        tokio::spawn(async move {
+            let mut backoff = initial;
+            loop {
+                let started = tokio::time::Instant::now();
+                let worker = tokio::spawn(make());
+                let _abort = AbortOnDrop(worker.abort_handle());
+                let panic = match worker.await {
+                    Err(e) if e.is_panic() => e.into_panic(),
+                    _ => return,
+                };
+                report_panic(&name, panic.as_ref());
+                if !config.restart_on_panic {
+                    return;
+                }
+
+                if started.elapsed() >= healthy_after {
+                    backoff = initial;
+                }
+                info!(worker = %name, backoff_ms = backoff.as_millis() as u64, "Restarting worker");
+                tokio::time::sleep(backoff).await;
+                metrics::counter!("auth_worker_restarts_total", 1, "worker" => name.clone());
+                backoff = (backoff * 2).min(max);
+            }
+        })
+    }
+
+    /// Run a worker that cannot be rebuilt (it owns a channel receiver, say),
+    /// reporting a panic instead of losing it silently
+    pub fn spawn_once<Fut>(&self, name: &str, worker: Fut) -> JoinHandle<()>
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let name = name.to_string();
+        tokio::spawn(async move {
+            let worker = tokio::spawn(worker);
+            let _abort = AbortOnDrop(worker.abort_handle());
+            if let Err(e) = worker.await {
+                if e.is_panic() {
+                    report_panic(&name, e.into_panic().as_ref());
+                }
+            }
+        })
+    }
+}
+
+/// Aborting the supervising task takes the worker down with it
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+fn report_panic(name: &str, panic: &(dyn Any + Send)) {
+    error!(
+        event = "worker.panicked",
+        worker = %name,
+        panic = %panic_message(panic),
+        "Background worker panicked"
+    );
+    metrics::counter!("auth_worker_panics_total", 1, "worker" => name.to_string());
+}
+
+/// The message passed to `panic!`, when it was a string
+pub fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn supervisor(restart_on_panic: bool) -> WorkerSupervisor {
+        WorkerSupervisor::new(SupervisorConfig {
+            restart_on_panic,
+            initial_backoff_ms: 1,
+            ..SupervisorConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_panicked_worker_restarted_until_it_finishes() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        supervisor(true)
+            .spawn("flaky", move || {
+                let run = counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if run < 2 {
+                        panic!("poisoned on run {}", run);
+                    }
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_restart_disabled_leaves_worker_stopped() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        supervisor(false)
+            .spawn("flaky", move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async {
+                    panic!("poisoned");
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_panic_message() {
+        let payload: Box<dyn Any + Send> = Box::new("static");
+        assert_eq!(panic_message(payload.as_ref()), "static");
+        let payload: Box<dyn Any + Send> = Box::new(format!("owned {}", 1));
+        assert_eq!(panic_message(payload.as_ref()), "owned 1");
+        let payload: Box<dyn Any + Send> = Box::new(7u8);
+        assert_eq!(panic_message(payload.as_ref()), "non-string panic payload");
+    }
+}
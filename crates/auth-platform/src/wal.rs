@@ -0,0 +1,559 @@
+//! Write-ahead durable queue
+//!
+//! An append-only log split into segment files under one directory. Producers
+//! append records; a single consumer reads from the acknowledged position and
+//! acknowledges once it has processed a batch, so delivery is at-least-once
+//! across restarts. Segments wholly behind the acknowledged position are
+//! deleted, and appends fail with [`WalError::Full`] rather than exceed the
+//! configured disk budget.
+//!
+//! Each record is `len (u32 LE) | crc32 (u32 LE) | payload`. A torn record at
+//! the tail of the last segment (crash mid-write) is truncated on open.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+const HEADER_LEN: u64 = 8;
+const SEGMENT_EXT: &str = "seg";
+const CURSOR_FILE: &str = "cursor";
+
+#[derive(Debug, thiserror::Error)]
+pub enum WalError {
+    #[error("WAL I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("WAL is full ({max_bytes} bytes)")]
+    Full { max_bytes: u64 },
+
+    #[error("Record of {0} bytes exceeds the segment size")]
+    RecordTooLarge(usize),
+}
+
+/// When appended records are flushed to stable storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// After every append; nothing acknowledged is lost on power failure
+    #[default]
+    Always,
+    /// After every N appends; up to N records may be lost on power failure
+    Batch(u32),
+    /// Leave it to the OS; survives process crashes but not power loss
+    Never,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalConfig {
+    pub dir: PathBuf,
+    #[serde(default = "default_segment_bytes")]
+    pub segment_bytes: u64,
+    /// Upper bound on the directory's total segment size
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: u64,
+    #[serde(default)]
+    pub fsync: FsyncPolicy,
+}
+
+fn default_segment_bytes() -> u64 {
+    8 * 1024 * 1024
+}
+
+fn default_max_bytes() -> u64 {
+    256 * 1024 * 1024
+}
+
+impl WalConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            segment_bytes: default_segment_bytes(),
+            max_bytes: default_max_bytes(),
+            fsync: FsyncPolicy::default(),
+        }
+    }
+}
+
+/// A point in the log; ordered by segment, then byte offset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct WalPosition {
+    pub segment: u64,
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct WalRecord {
+    pub payload: Vec<u8>,
+    /// Position just past this record; pass it to [`DurableQueue::ack`]
+    pub next: WalPosition,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct WalStats {
+    pub segments: usize,
+    pub disk_bytes: u64,
+    /// Bytes appended but not yet acknowledged
+    pub pending_bytes: u64,
+}
+
+struct Inner {
+    config: WalConfig,
+    /// Segment id to committed size in bytes
+    segments: BTreeMap<u64, u64>,
+    writer: File,
+    acked: WalPosition,
+    unsynced: u32,
+}
+
+/// Disk-backed FIFO shared by producers and a single consumer
+#[derive(Clone)]
+pub struct DurableQueue {
+    inner: Arc<Mutex<Inner>>,
+    appended: Arc<Notify>,
+}
+
+impl DurableQueue {
+    /// Open or create the queue, recovering unacknowledged records
+    pub fn open(config: WalConfig) -> Result<Self, WalError> {
+        fs::create_dir_all(&config.dir)?;
+
+        let mut segments = BTreeMap::new();
+        for entry in fs::read_dir(&config.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SEGMENT_EXT) {
+                continue;
+            }
+            if let Some(id) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u64>().ok())
+            {
+                segments.insert(id, fs::metadata(&path)?.len());
+            }
+        }
+
+        let mut acked = read_cursor(&config.dir)?.unwrap_or_default();
+        if segments.is_empty() {
+            File::create(segment_path(&config.dir, acked.segment))?;
+            segments.insert(acked.segment, 0);
+        }
+        let first = *segments.keys().next().expect("at least one segment");
+        let last = *segments.keys().next_back().expect("at least one segment");
+        if acked.segment < first {
+            acked = WalPosition {
+                segment: first,
+                offset: 0,
+            };
+        }
+
+        let tail = recover_tail(&segment_path(&config.dir, last))?;
+        if tail < segments[&last] {
+            warn!(
+                dir = %config.dir.display(),
+                segment = last,
+                truncated_bytes = segments[&last] - tail,
+                "Truncated torn record at WAL tail"
+            );
+        }
+        segments.insert(last, tail);
+
+        let writer = OpenOptions::new()
+            .append(true)
+            .open(segment_path(&config.dir, last))?;
+
+        let queue = Self {
+            inner: Arc::new(Mutex::new(Inner {
+                config,
+                segments,
+                writer,
+                acked,
+                unsynced: 0,
+            })),
+            appended: Arc::new(Notify::new()),
+        };
+
+        let stats = queue.stats();
+        if stats.pending_bytes > 0 {
+            info!(
+                pending_bytes = stats.pending_bytes,
+                "Recovered unacknowledged WAL records"
+            );
+        }
+        Ok(queue)
+    }
+
+    pub async fn append(&self, payload: Vec<u8>) -> Result<(), WalError> {
+        let inner = self.inner.clone();
+        blocking(move || inner.lock().append(&payload)).await?;
+        self.appended.notify_one();
+        Ok(())
+    }
+
+    /// Read up to `max` unacknowledged records without consuming them
+    pub async fn peek(&self, max: usize) -> Result<Vec<WalRecord>, WalError> {
+        let inner = self.inner.clone();
+        blocking(move || inner.lock().read(max)).await
+    }
+
+    /// Wait until at least one unacknowledged record is available
+    pub async fn next_batch(&self, max: usize) -> Result<Vec<WalRecord>, WalError> {
+        loop {
+            let appended = self.appended.notified();
+            tokio::pin!(appended);
+            appended.as_mut().enable();
+
+            let batch = self.peek(max).await?;
+            if !batch.is_empty() {
+                return Ok(batch);
+            }
+            appended.await;
+        }
+    }
+
+    /// Mark everything before `through` as processed and reclaim its segments
+    pub async fn ack(&self, through: WalPosition) -> Result<(), WalError> {
+        let inner = self.inner.clone();
+        blocking(move || inner.lock().ack(through)).await
+    }
+
+    pub fn stats(&self) -> WalStats {
+        let inner = self.inner.lock();
+        let pending_bytes = inner
+            .segments
+            .range(inner.acked.segment..)
+            .map(|(_, size)| size)
+            .sum::<u64>()
+            .saturating_sub(inner.acked.offset);
+        WalStats {
+            segments: inner.segments.len(),
+            disk_bytes: inner.segments.values().sum(),
+            pending_bytes,
+        }
+    }
+}
+
+impl Inner {
+    fn dir(&self) -> &Path {
+        &self.config.dir
+    }
+
+    fn current(&self) -> (u64, u64) {
+        let (&id, &size) = self
+            .segments
+            .iter()
+            .next_back()
+            .expect("at least one segment");
+        (id, size)
+    }
+
+    fn append(&mut self, payload: &[u8]) -> Result<(), WalError> {
+        let record_len = HEADER_LEN + payload.len() as u64;
+        if record_len > self.config.segment_bytes {
+            return Err(WalError::RecordTooLarge(payload.len()));
+        }
+
+        let (mut id, mut size) = self.current();
+        if size > 0 && size + record_len > self.config.segment_bytes {
+            self.writer.sync_data()?;
+            id += 1;
+            self.writer = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(segment_path(self.dir(), id))?;
+            self.segments.insert(id, 0);
+            size = 0;
+        }
+
+        let disk_bytes: u64 = self.segments.values().sum();
+        if disk_bytes + record_len > self.config.max_bytes {
+            return Err(WalError::Full {
+                max_bytes: self.config.max_bytes,
+            });
+        }
+
+        let mut record = Vec::with_capacity(record_len as usize);
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+        record.extend_from_slice(payload);
+        if let Err(e) = self.writer.write_all(&record) {
+            // Drop any partial write so the segment stays well-formed
+            let _ = self.writer.set_len(size);
+            return Err(e.into());
+        }
+        self.segments.insert(id, size + record_len);
+
+        self.unsynced += 1;
+        let sync = match self.config.fsync {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::Batch(every) => self.unsynced >= every.max(1),
+            FsyncPolicy::Never => false,
+        };
+        if sync {
+            self.writer.sync_data()?;
+            self.unsynced = 0;
+        }
+        Ok(())
+    }
+
+    fn read(&self, max: usize) -> Result<Vec<WalRecord>, WalError> {
+        let mut records = Vec::new();
+        let mut pos = self.acked;
+
+        for (&id, &size) in self.segments.range(self.acked.segment..) {
+            if records.len() >= max {
+                break;
+            }
+            if id != pos.segment {
+                pos = WalPosition {
+                    segment: id,
+                    offset: 0,
+                };
+            }
+            if pos.offset >= size {
+                continue;
+            }
+
+            let mut file = File::open(segment_path(self.dir(), id))?;
+            file.seek(SeekFrom::Start(pos.offset))?;
+            let mut reader = BufReader::new(file);
+            while pos.offset < size && records.len() < max {
+                let payload = read_record(&mut reader)?.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "corrupt WAL record")
+                })?;
+                pos.offset += HEADER_LEN + payload.len() as u64;
+                records.push(WalRecord { payload, next: pos });
+            }
+        }
+        Ok(records)
+    }
+
+    fn ack(&mut self, through: WalPosition) -> Result<(), WalError> {
+        if through <= self.acked {
+            return Ok(());
+        }
+        self.acked = through;
+        write_cursor(self.dir(), through, self.config.fsync != FsyncPolicy::Never)?;
+
+        // Segments before the cursor's are fully consumed; never delete the writer's
+        let (current, _) = self.current();
+        let consumed: Vec<u64> = self
+            .segments
+            .range(..through.segment.min(current))
+            .map(|(&id, _)| id)
+            .collect();
+        for id in consumed {
+            fs::remove_file(segment_path(self.dir(), id))?;
+            self.segments.remove(&id);
+        }
+        Ok(())
+    }
+}
+
+async fn blocking<T, F>(f: F) -> Result<T, WalError>
+where
+    F: FnOnce() -> Result<T, WalError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| WalError::Io(io::Error::other(e)))?
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", id, SEGMENT_EXT))
+}
+
+/// Read one record; `None` on a short or corrupt record
+fn read_record(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0u8; HEADER_LEN as usize];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let crc = u32::from_le_bytes(header[4..].try_into().unwrap());
+
+    let mut payload = vec![0u8; len];
+    match reader.read_exact(&mut payload) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    Ok((crc32fast::hash(&payload) == crc).then_some(payload))
+}
+
+/// Length of the valid prefix of a segment, truncating anything after it
+fn recover_tail(path: &Path) -> io::Result<u64> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    let len = file.metadata()?.len();
+    let mut reader = BufReader::new(&file);
+    let mut valid = 0;
+    while valid < len {
+        match read_record(&mut reader)? {
+            Some(payload) => valid += HEADER_LEN + payload.len() as u64,
+            None => break,
+        }
+    }
+    if valid < len {
+        file.set_len(valid)?;
+        file.sync_data()?;
+    }
+    Ok(valid)
+}
+
+fn read_cursor(dir: &Path) -> io::Result<Option<WalPosition>> {
+    let contents = match fs::read_to_string(dir.join(CURSOR_FILE)) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut parts = contents.split_whitespace().map(str::parse::<u64>);
+    match (parts.next(), parts.next()) {
+        (Some(Ok(segment)), Some(Ok(offset))) => Ok(Some(WalPosition { segment, offset })),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "malformed WAL cursor",
+        )),
+    }
+}
+
+/// Replace the cursor atomically so a crash leaves either the old or new one
+fn write_cursor(dir: &Path, pos: WalPosition, sync: bool) -> io::Result<()> {
+    let tmp = dir.join(format!("{}.tmp", CURSOR_FILE));
+    let mut file = File::create(&tmp)?;
+    writeln!(file, "{} {}", pos.segment, pos.offset)?;
+    if sync {
+        file.sync_data()?;
+    }
+    fs::rename(tmp, dir.join(CURSOR_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dir: &Path) -> WalConfig {
+        WalConfig {
+            segment_bytes: 64,
+            max_bytes: 1024,
+            ..WalConfig::new(dir)
+        }
+    }
+
+    fn payloads(records: &[WalRecord]) -> Vec<String> {
+        records
+            .iter()
+            .map(|r| String::from_utf8(r.payload.clone()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_append_peek_ack() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = DurableQueue::open(config(dir.path())).unwrap();
+        for p in ["a", "b", "c"] {
+            queue.append(p.as_bytes().to_vec()).await.unwrap();
+        }
+
+        let batch = queue.peek(2).await.unwrap();
+        assert_eq!(payloads(&batch), vec!["a", "b"]);
+        // Peeking does not consume
+        assert_eq!(
+            payloads(&queue.peek(10).await.unwrap()),
+            vec!["a", "b", "c"]
+        );
+
+        queue.ack(batch[1].next).await.unwrap();
+        assert_eq!(payloads(&queue.next_batch(10).await.unwrap()), vec!["c"]);
+    }
+
+    #[tokio::test]
+    async fn test_unacked_records_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let queue = DurableQueue::open(config(dir.path())).unwrap();
+            for i in 0..10 {
+                queue
+                    .append(format!("event-{}", i).into_bytes())
+                    .await
+                    .unwrap();
+            }
+            let batch = queue.peek(4).await.unwrap();
+            queue.ack(batch[3].next).await.unwrap();
+        }
+
+        let queue = DurableQueue::open(config(dir.path())).unwrap();
+        let batch = queue.peek(100).await.unwrap();
+        assert_eq!(batch.len(), 6);
+        assert_eq!(payloads(&batch)[0], "event-4");
+    }
+
+    #[tokio::test]
+    async fn test_torn_tail_is_truncated() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let queue = DurableQueue::open(config(dir.path())).unwrap();
+            queue.append(b"whole".to_vec()).await.unwrap();
+        }
+        let mut segment = OpenOptions::new()
+            .append(true)
+            .open(segment_path(dir.path(), 0))
+            .unwrap();
+        segment.write_all(&[9, 0, 0, 0, 1, 2]).unwrap();
+
+        let queue = DurableQueue::open(config(dir.path())).unwrap();
+        assert_eq!(payloads(&queue.peek(10).await.unwrap()), vec!["whole"]);
+        queue.append(b"next".to_vec()).await.unwrap();
+        assert_eq!(
+            payloads(&queue.peek(10).await.unwrap()),
+            vec!["whole", "next"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ack_compacts_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = DurableQueue::open(config(dir.path())).unwrap();
+        for _ in 0..20 {
+            queue.append(vec![0u8; 24]).await.unwrap();
+        }
+        assert!(queue.stats().segments > 5);
+
+        let batch = queue.peek(100).await.unwrap();
+        queue.ack(batch.last().unwrap().next).await.unwrap();
+        let stats = queue.stats();
+        assert_eq!(stats.segments, 1);
+        assert_eq!(stats.pending_bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_disk_budget_is_enforced() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = DurableQueue::open(WalConfig {
+            max_bytes: 128,
+            ..config(dir.path())
+        })
+        .unwrap();
+
+        let mut result = Ok(());
+        for _ in 0..10 {
+            result = queue.append(vec![0u8; 24]).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(matches!(result, Err(WalError::Full { max_bytes: 128 })));
+        assert!(queue.stats().disk_bytes <= 128);
+        assert!(matches!(
+            queue.append(vec![0u8; 100]).await,
+            Err(WalError::RecordTooLarge(100))
+        ));
+    }
+}
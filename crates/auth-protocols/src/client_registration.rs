@@ -0,0 +1,571 @@
+//! OAuth 2.0 Dynamic Client Registration (RFC 7591) and its management
+//! protocol (RFC 7592)
+//!
+//! A tenant registers a client with its metadata and gets back a client id,
+//! a secret for confidential clients, and a registration access token. The
+//! token is the only credential for reading, updating or deleting the
+//! registration later. Secrets and tokens are returned once, when issued,
+//! and stored as SHA-256 hashes: both are random, so a slow hash adds
+//! nothing.
+
+use async_trait::async_trait;
+use auth_core::error::AuthError;
+use auth_core::models::{OAuthClient, OAuthClientStore, TokenEndpointAuthMethod};
+use auth_core::services::timing::constant_time_eq;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::Utc;
+use dashmap::DashMap;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use thiserror::Error;
+use url::Url;
+use uuid::Uuid;
+
+pub const GRANT_AUTHORIZATION_CODE: &str = "authorization_code";
+pub const GRANT_REFRESH_TOKEN: &str = "refresh_token";
+pub const GRANT_CLIENT_CREDENTIALS: &str = "client_credentials";
+
+const SUPPORTED_GRANTS: &[&str] = &[
+    GRANT_AUTHORIZATION_CODE,
+    GRANT_REFRESH_TOKEN,
+    GRANT_CLIENT_CREDENTIALS,
+];
+
+/// Client metadata as sent to the registration endpoint
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ClientMetadata {
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    pub token_endpoint_auth_method: Option<TokenEndpointAuthMethod>,
+    /// Defaults to `authorization_code`
+    #[serde(default)]
+    pub grant_types: Vec<String>,
+    pub client_name: Option<String>,
+    pub logo_uri: Option<String>,
+    /// Space-separated scopes the client may request
+    pub scope: Option<String>,
+}
+
+/// Registration response: the client's metadata with its credentials
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientInformation {
+    pub client_id: String,
+    /// Only when the secret was issued by this request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    pub client_id_issued_at: i64,
+    /// `0`: secrets do not expire. Sent along with a secret.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret_expires_at: Option<i64>,
+    /// Only when the token was issued by this request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registration_access_token: Option<String>,
+    /// Set by the endpoint, which knows its own URL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registration_client_uri: Option<String>,
+    pub redirect_uris: Vec<String>,
+    pub token_endpoint_auth_method: TokenEndpointAuthMethod,
+    pub grant_types: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logo_uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+impl ClientInformation {
+    fn from_client(client: &OAuthClient) -> Self {
+        Self {
+            client_id: client.client_id.clone(),
+            client_secret: None,
+            client_id_issued_at: client.created_at.timestamp(),
+            client_secret_expires_at: None,
+            registration_access_token: None,
+            registration_client_uri: None,
+            redirect_uris: client.redirect_uris.clone(),
+            token_endpoint_auth_method: client.token_endpoint_auth_method,
+            grant_types: client.grant_types.clone(),
+            client_name: client.name.clone(),
+            logo_uri: client.logo_uri.clone(),
+            scope: (!client.scopes.is_empty()).then(|| client.scopes.join(" ")),
+        }
+    }
+
+    fn with_secret(mut self, secret: Option<String>) -> Self {
+        self.client_secret_expires_at = secret.as_ref().map(|_| 0);
+        self.client_secret = secret;
+        self
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ClientRegistrationError {
+    #[error("{0}")]
+    InvalidRedirectUri(String),
+    #[error("{0}")]
+    InvalidClientMetadata(String),
+    /// Unknown client, or the registration access token does not match.
+    /// The two are not told apart.
+    #[error("invalid registration access token")]
+    InvalidToken,
+    /// Unknown client, or the secret does not match
+    #[error("client authentication failed")]
+    InvalidClient,
+    #[error(transparent)]
+    Store(#[from] AuthError),
+}
+
+impl ClientRegistrationError {
+    /// RFC 7591 section 3.2.2 error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            ClientRegistrationError::InvalidRedirectUri(_) => "invalid_redirect_uri",
+            ClientRegistrationError::InvalidClientMetadata(_) => "invalid_client_metadata",
+            ClientRegistrationError::InvalidToken => "invalid_token",
+            ClientRegistrationError::InvalidClient => "invalid_client",
+            ClientRegistrationError::Store(_) => "server_error",
+        }
+    }
+}
+
+pub struct ClientService {
+    store: Arc<dyn OAuthClientStore>,
+}
+
+impl ClientService {
+    pub fn new(store: Arc<dyn OAuthClientStore>) -> Self {
+        Self { store }
+    }
+
+    /// Register a client for the tenant. The response carries the secret,
+    /// for confidential clients, and the registration access token; neither
+    /// can be read again.
+    pub async fn register(
+        &self,
+        tenant_id: Uuid,
+        metadata: ClientMetadata,
+    ) -> Result<ClientInformation, ClientRegistrationError> {
+        let metadata = validate(metadata)?;
+        let secret = (!metadata.auth_method_is_public()).then(random_token);
+        let registration_token = random_token();
+        let now = Utc::now();
+
+        let client = OAuthClient {
+            id: Uuid::new_v4(),
+            tenant_id,
+            client_id: Uuid::new_v4().simple().to_string(),
+            secret_hash: secret.as_deref().map(hash_credential),
+            token_endpoint_auth_method: metadata.token_endpoint_auth_method,
+            redirect_uris: metadata.redirect_uris,
+            grant_types: metadata.grant_types,
+            scopes: metadata.scopes,
+            name: metadata.client_name,
+            logo_uri: metadata.logo_uri,
+            registration_token_hash: Some(hash_credential(&registration_token)),
+            created_at: now,
+            updated_at: now,
+        };
+        self.store.insert(&client).await?;
+        tracing::info!(
+            tenant_id = %tenant_id,
+            client_id = %client.client_id,
+            "OAuth client registered"
+        );
+
+        let mut info = ClientInformation::from_client(&client).with_secret(secret);
+        info.registration_access_token = Some(registration_token);
+        Ok(info)
+    }
+
+    /// The client's current registration
+    pub async fn read(
+        &self,
+        client_id: &str,
+        registration_token: &str,
+    ) -> Result<ClientInformation, ClientRegistrationError> {
+        let client = self.managed_client(client_id, registration_token).await?;
+        Ok(ClientInformation::from_client(&client))
+    }
+
+    /// Replace the client's metadata. A client that becomes confidential is
+    /// issued a secret; one that becomes public loses its secret.
+    pub async fn update(
+        &self,
+        client_id: &str,
+        registration_token: &str,
+        metadata: ClientMetadata,
+    ) -> Result<ClientInformation, ClientRegistrationError> {
+        let mut client = self.managed_client(client_id, registration_token).await?;
+        let metadata = validate(metadata)?;
+
+        let secret = match (metadata.auth_method_is_public(), client.is_public()) {
+            (true, _) => {
+                client.secret_hash = None;
+                None
+            }
+            (false, true) => {
+                let secret = random_token();
+                client.secret_hash = Some(hash_credential(&secret));
+                Some(secret)
+            }
+            (false, false) => None,
+        };
+        client.token_endpoint_auth_method = metadata.token_endpoint_auth_method;
+        client.redirect_uris = metadata.redirect_uris;
+        client.grant_types = metadata.grant_types;
+        client.scopes = metadata.scopes;
+        client.name = metadata.client_name;
+        client.logo_uri = metadata.logo_uri;
+        client.updated_at = Utc::now();
+        self.store.update(&client).await?;
+
+        Ok(ClientInformation::from_client(&client).with_secret(secret))
+    }
+
+    /// Delete the registration; the client can no longer get tokens
+    pub async fn delete(
+        &self,
+        client_id: &str,
+        registration_token: &str,
+    ) -> Result<(), ClientRegistrationError> {
+        let client = self.managed_client(client_id, registration_token).await?;
+        self.store.delete(&client.client_id).await?;
+        tracing::info!(
+            tenant_id = %client.tenant_id,
+            client_id = %client.client_id,
+            "OAuth client deleted"
+        );
+        Ok(())
+    }
+
+    /// Authenticate a client at the token endpoint. Confidential clients
+    /// must present their secret; public clients must not send one.
+    pub async fn authenticate(
+        &self,
+        client_id: &str,
+        secret: Option<&str>,
+    ) -> Result<OAuthClient, ClientRegistrationError> {
+        let client = self
+            .store
+            .find(client_id)
+            .await?
+            .ok_or(ClientRegistrationError::InvalidClient)?;
+        let authenticated = match (&client.secret_hash, secret) {
+            (Some(hash), Some(secret)) => {
+                constant_time_eq(hash.as_bytes(), hash_credential(secret).as_bytes())
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        if !authenticated {
+            return Err(ClientRegistrationError::InvalidClient);
+        }
+        Ok(client)
+    }
+
+    /// A registered client, without checking any credential. For the
+    /// authorization endpoint, which only needs its redirect URIs and grants.
+    pub async fn find(&self, client_id: &str) -> Result<Option<OAuthClient>, AuthError> {
+        self.store.find(client_id).await
+    }
+
+    async fn managed_client(
+        &self,
+        client_id: &str,
+        registration_token: &str,
+    ) -> Result<OAuthClient, ClientRegistrationError> {
+        let client = self
+            .store
+            .find(client_id)
+            .await?
+            .ok_or(ClientRegistrationError::InvalidToken)?;
+        let matches = client.registration_token_hash.as_ref().is_some_and(|hash| {
+            constant_time_eq(
+                hash.as_bytes(),
+                hash_credential(registration_token).as_bytes(),
+            )
+        });
+        if !matches {
+            return Err(ClientRegistrationError::InvalidToken);
+        }
+        Ok(client)
+    }
+}
+
+/// Metadata after defaults and checks
+struct ValidMetadata {
+    redirect_uris: Vec<String>,
+    token_endpoint_auth_method: TokenEndpointAuthMethod,
+    grant_types: Vec<String>,
+    scopes: Vec<String>,
+    client_name: Option<String>,
+    logo_uri: Option<String>,
+}
+
+impl ValidMetadata {
+    fn auth_method_is_public(&self) -> bool {
+        self.token_endpoint_auth_method == TokenEndpointAuthMethod::None
+    }
+}
+
+fn validate(metadata: ClientMetadata) -> Result<ValidMetadata, ClientRegistrationError> {
+    let invalid = |message: &str| ClientRegistrationError::InvalidClientMetadata(message.into());
+    let auth_method = metadata.token_endpoint_auth_method.unwrap_or_default();
+    let public = auth_method == TokenEndpointAuthMethod::None;
+
+    let mut grant_types = metadata.grant_types;
+    if grant_types.is_empty() {
+        grant_types.push(GRANT_AUTHORIZATION_CODE.to_string());
+    }
+    grant_types.sort();
+    grant_types.dedup();
+    if let Some(grant) = grant_types
+        .iter()
+        .find(|grant| !SUPPORTED_GRANTS.contains(&grant.as_str()))
+    {
+        return Err(ClientRegistrationError::InvalidClientMetadata(format!(
+            "unsupported grant type {}",
+            grant
+        )));
+    }
+    let has_grant = |grant: &str| grant_types.iter().any(|g| g == grant);
+    if public && has_grant(GRANT_CLIENT_CREDENTIALS) {
+        return Err(invalid("public clients cannot use client_credentials"));
+    }
+
+    if has_grant(GRANT_AUTHORIZATION_CODE) && metadata.redirect_uris.is_empty() {
+        return Err(ClientRegistrationError::InvalidRedirectUri(
+            "authorization_code requires at least one redirect URI".to_string(),
+        ));
+    }
+    for uri in &metadata.redirect_uris {
+        check_redirect_uri(uri, public)?;
+    }
+
+    if let Some(name) = &metadata.client_name {
+        if name.trim().is_empty() || name.len() > 255 {
+            return Err(invalid("client_name must have 1 to 255 characters"));
+        }
+    }
+    if let Some(logo) = &metadata.logo_uri {
+        if !Url::parse(logo).is_ok_and(|url| url.scheme() == "https") {
+            return Err(invalid("logo_uri must be an https URL"));
+        }
+    }
+
+    Ok(ValidMetadata {
+        redirect_uris: metadata.redirect_uris,
+        token_endpoint_auth_method: auth_method,
+        grant_types,
+        scopes: metadata
+            .scope
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        client_name: metadata.client_name,
+        logo_uri: metadata.logo_uri,
+    })
+}
+
+/// Redirect URIs are absolute and without a fragment (RFC 6749 section
+/// 3.1.2). Plain http is only accepted on the loopback interface, and
+/// private-use schemes only for public clients, which are the native apps
+/// that need them (RFC 8252).
+fn check_redirect_uri(uri: &str, public: bool) -> Result<(), ClientRegistrationError> {
+    let invalid =
+        |reason: &str| ClientRegistrationError::InvalidRedirectUri(format!("{}: {}", uri, reason));
+    let url = Url::parse(uri).map_err(|_| invalid("not an absolute URI"))?;
+    if url.fragment().is_some() {
+        return Err(invalid("must not contain a fragment"));
+    }
+    match url.scheme() {
+        "https" => Ok(()),
+        "http" => match url.host_str() {
+            Some("localhost" | "127.0.0.1" | "[::1]") => Ok(()),
+            _ => Err(invalid("http is only allowed for loopback addresses")),
+        },
+        _ if public => Ok(()),
+        _ => Err(invalid("confidential clients must use https")),
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn hash_credential(credential: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(credential.as_bytes()))
+}
+
+#[derive(Default)]
+pub struct InMemoryClientStore {
+    clients: DashMap<String, OAuthClient>,
+}
+
+#[async_trait]
+impl OAuthClientStore for InMemoryClientStore {
+    async fn insert(&self, client: &OAuthClient) -> Result<(), AuthError> {
+        self.clients
+            .insert(client.client_id.clone(), client.clone());
+        Ok(())
+    }
+
+    async fn find(&self, client_id: &str) -> Result<Option<OAuthClient>, AuthError> {
+        Ok(self.clients.get(client_id).map(|c| c.value().clone()))
+    }
+
+    async fn update(&self, client: &OAuthClient) -> Result<(), AuthError> {
+        if let Some(mut stored) = self.clients.get_mut(&client.client_id) {
+            *stored = client.clone();
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, client_id: &str) -> Result<bool, AuthError> {
+        Ok(self.clients.remove(client_id).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> ClientService {
+        ClientService::new(Arc::new(InMemoryClientStore::default()))
+    }
+
+    fn web_app() -> ClientMetadata {
+        ClientMetadata {
+            redirect_uris: vec!["https://app.example.com/callback".to_string()],
+            grant_types: vec![
+                GRANT_AUTHORIZATION_CODE.to_string(),
+                GRANT_REFRESH_TOKEN.to_string(),
+            ],
+            client_name: Some("Example".to_string()),
+            scope: Some("openid profile".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_confidential_client_gets_a_secret_once() {
+        let clients = service();
+        let info = clients.register(Uuid::new_v4(), web_app()).await.unwrap();
+        let secret = info.client_secret.clone().unwrap();
+        let token = info.registration_access_token.clone().unwrap();
+        assert_eq!(info.client_secret_expires_at, Some(0));
+        assert_eq!(info.scope.as_deref(), Some("openid profile"));
+
+        assert!(clients
+            .authenticate(&info.client_id, Some(&secret))
+            .await
+            .is_ok());
+        assert!(matches!(
+            clients.authenticate(&info.client_id, Some("wrong")).await,
+            Err(ClientRegistrationError::InvalidClient)
+        ));
+        assert!(clients.authenticate(&info.client_id, None).await.is_err());
+
+        // Reading the registration back does not reveal either credential
+        let read = clients.read(&info.client_id, &token).await.unwrap();
+        assert!(read.client_secret.is_none());
+        assert!(read.registration_access_token.is_none());
+        assert!(matches!(
+            clients.read(&info.client_id, "not-the-token").await,
+            Err(ClientRegistrationError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_public_native_client() {
+        let clients = service();
+        let metadata = ClientMetadata {
+            redirect_uris: vec!["com.example.app:/oauth".to_string()],
+            token_endpoint_auth_method: Some(TokenEndpointAuthMethod::None),
+            ..Default::default()
+        };
+        let info = clients.register(Uuid::new_v4(), metadata).await.unwrap();
+        assert!(info.client_secret.is_none());
+        assert!(clients.authenticate(&info.client_id, None).await.is_ok());
+        let client = clients.find(&info.client_id).await.unwrap().unwrap();
+        assert!(client.is_public());
+        assert!(clients.find("unknown").await.unwrap().is_none());
+
+        // Public clients cannot use grants that need a secret
+        let metadata = ClientMetadata {
+            token_endpoint_auth_method: Some(TokenEndpointAuthMethod::None),
+            grant_types: vec![GRANT_CLIENT_CREDENTIALS.to_string()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            clients.register(Uuid::new_v4(), metadata).await,
+            Err(ClientRegistrationError::InvalidClientMetadata(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_uris_are_checked() {
+        let clients = service();
+        for uri in [
+            "/callback",
+            "https://app.example.com/cb#frag",
+            "http://app.example.com/cb",
+            "com.example.app:/oauth",
+        ] {
+            let metadata = ClientMetadata {
+                redirect_uris: vec![uri.to_string()],
+                ..Default::default()
+            };
+            assert!(
+                matches!(
+                    clients.register(Uuid::new_v4(), metadata).await,
+                    Err(ClientRegistrationError::InvalidRedirectUri(_))
+                ),
+                "{} was accepted",
+                uri
+            );
+        }
+        let metadata = ClientMetadata {
+            redirect_uris: vec!["http://127.0.0.1:8400/cb".to_string()],
+            ..Default::default()
+        };
+        assert!(clients.register(Uuid::new_v4(), metadata).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete_need_the_registration_token() {
+        let clients = service();
+        let info = clients.register(Uuid::new_v4(), web_app()).await.unwrap();
+        let token = info.registration_access_token.unwrap();
+
+        let mut metadata = web_app();
+        metadata.token_endpoint_auth_method = Some(TokenEndpointAuthMethod::None);
+        let updated = clients
+            .update(&info.client_id, &token, metadata)
+            .await
+            .unwrap();
+        assert_eq!(
+            updated.token_endpoint_auth_method,
+            TokenEndpointAuthMethod::None
+        );
+        // The old secret went with the switch to a public client
+        assert!(clients
+            .authenticate(&info.client_id, info.client_secret.as_deref())
+            .await
+            .is_err());
+
+        assert!(clients.delete(&info.client_id, "wrong").await.is_err());
+        clients.delete(&info.client_id, &token).await.unwrap();
+        assert!(matches!(
+            clients.read(&info.client_id, &token).await,
+            Err(ClientRegistrationError::InvalidToken)
+        ));
+    }
+}
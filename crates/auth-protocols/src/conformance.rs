@@ -0,0 +1,830 @@
+//! OIDC self-check
+//!
+//! Drives a running instance the way a relying party would (discovery,
+//! JWKS, code flow with PKCE, userinfo, refresh, logout) and reports each
+//! requirement of the OIDC Basic and PKCE profiles it touches. It is an early
+//! warning before submitting to the certification suite, not a substitute.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use reqwest::{header, redirect::Policy, Client, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use uuid::Uuid;
+
+/// Relying party and test account the check signs in with
+#[derive(Clone, Deserialize)]
+pub struct SelfCheckConfig {
+    /// Issuer URL of the instance under test
+    pub base_url: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    pub email: String,
+    pub password: String,
+    pub tenant_id: Uuid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Profile {
+    Basic,
+    Pkce,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// A prerequisite failed, so the requirement could not be exercised
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub id: &'static str,
+    pub profile: Profile,
+    /// Section of the spec the requirement comes from
+    pub reference: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConformanceReport {
+    pub base_url: String,
+    pub results: Vec<CheckResult>,
+}
+
+impl ConformanceReport {
+    pub fn passed(&self) -> bool {
+        !self.results.iter().any(|r| r.outcome == Outcome::Fail)
+    }
+
+    pub fn count(&self, outcome: Outcome) -> usize {
+        self.results.iter().filter(|r| r.outcome == outcome).count()
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "OIDC self-check against {}: {} passed, {} failed, {} skipped",
+            self.base_url,
+            self.count(Outcome::Pass),
+            self.count(Outcome::Fail),
+            self.count(Outcome::Skip)
+        )?;
+        for r in &self.results {
+            let tag = match r.outcome {
+                Outcome::Pass => "PASS",
+                Outcome::Fail => "FAIL",
+                Outcome::Skip => "SKIP",
+            };
+            writeln!(
+                f,
+                "  [{}] {:<24} {:?} ({}): {}",
+                tag, r.id, r.profile, r.reference, r.detail
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every check against `config.base_url`
+pub async fn run_self_check(config: SelfCheckConfig) -> ConformanceReport {
+    let mut check = SelfCheck::new(config);
+    check.run().await;
+    ConformanceReport {
+        base_url: check.config.base_url,
+        results: check.results,
+    }
+}
+
+type CheckOutcome = Result<String, String>;
+
+/// Checks that depend on the code flow, skipped when it cannot start
+const FLOW_CHECKS: &[(&str, Profile, &str)] = &[
+    ("authorize.code", Profile::Basic, "Core 3.1.2.5"),
+    ("token.exchange", Profile::Basic, "Core 3.1.3.3"),
+    ("token.no_store", Profile::Basic, "Core 3.1.3.3"),
+    ("token.id_token", Profile::Basic, "Core 3.1.3.7"),
+    ("token.code_replay", Profile::Basic, "RFC 6749 4.1.2"),
+    ("pkce.verifier_checked", Profile::Pkce, "RFC 7636 4.6"),
+    ("userinfo.sub", Profile::Basic, "Core 5.3.2"),
+    ("refresh.grant", Profile::Basic, "Core 12.2"),
+    (
+        "logout.end_session",
+        Profile::Basic,
+        "RP-Initiated Logout 2",
+    ),
+    ("logout.prompt_none", Profile::Basic, "Core 3.1.2.6"),
+];
+
+struct SelfCheck {
+    config: SelfCheckConfig,
+    http: Client,
+    /// Cookies set by the instance, replayed like a browser would
+    cookies: Vec<(String, String)>,
+    results: Vec<CheckResult>,
+}
+
+struct Tokens {
+    access_token: String,
+    refresh_token: Option<String>,
+    id_token_sub: Option<String>,
+}
+
+impl SelfCheck {
+    fn new(mut config: SelfCheckConfig) -> Self {
+        config.base_url = config.base_url.trim_end_matches('/').to_string();
+        Self {
+            config,
+            // Redirects are assertions, not something to follow
+            http: Client::builder()
+                .redirect(Policy::none())
+                .build()
+                .unwrap_or_default(),
+            cookies: Vec::new(),
+            results: Vec::new(),
+        }
+    }
+
+    fn record(
+        &mut self,
+        id: &'static str,
+        profile: Profile,
+        reference: &'static str,
+        outcome: CheckOutcome,
+    ) {
+        let (outcome, detail) = match outcome {
+            Ok(detail) => (Outcome::Pass, detail),
+            Err(detail) => (Outcome::Fail, detail),
+        };
+        self.results.push(CheckResult {
+            id,
+            profile,
+            reference,
+            outcome,
+            detail,
+        });
+    }
+
+    fn skip_remaining(&mut self, reason: &str) {
+        for &(id, profile, reference) in FLOW_CHECKS {
+            if !self.results.iter().any(|r| r.id == id) {
+                self.results.push(CheckResult {
+                    id,
+                    profile,
+                    reference,
+                    outcome: Outcome::Skip,
+                    detail: reason.to_string(),
+                });
+            }
+        }
+    }
+
+    async fn run(&mut self) {
+        let Some(metadata) = self.discovery().await else {
+            self.skip_remaining("discovery failed");
+            return;
+        };
+        self.jwks(&metadata).await;
+
+        if let Err(e) = self.sign_in().await {
+            self.record("setup.sign_in", Profile::Basic, "test account", Err(e));
+            self.skip_remaining("could not sign in the test account");
+            return;
+        }
+
+        let nonce = Uuid::new_v4().to_string();
+        let verifier = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let code = match self.authorize(&metadata, &nonce, &verifier, None).await {
+            Ok(code) => {
+                self.record(
+                    "authorize.code",
+                    Profile::Basic,
+                    "Core 3.1.2.5",
+                    Ok("code returned with matching state".to_string()),
+                );
+                code
+            }
+            Err(e) => {
+                self.record("authorize.code", Profile::Basic, "Core 3.1.2.5", Err(e));
+                self.skip_remaining("no authorization code");
+                return;
+            }
+        };
+
+        let tokens = self.exchange(&metadata, &code, &verifier, &nonce).await;
+        self.code_replay(&metadata, &code, &verifier).await;
+        self.pkce_verifier_checked(&metadata).await;
+
+        match tokens {
+            Some(tokens) => {
+                self.userinfo(&metadata, &tokens).await;
+                self.refresh(&metadata, &tokens).await;
+            }
+            None => self.skip_remaining("token exchange failed"),
+        }
+        self.logout(&metadata).await;
+        self.skip_remaining("not reached");
+    }
+
+    async fn discovery(&mut self) -> Option<Value> {
+        let url = format!("{}/.well-known/openid-configuration", self.config.base_url);
+        let metadata = match self.get_json(&url).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                self.record("discovery.fetch", Profile::Basic, "Discovery 4", Err(e));
+                return None;
+            }
+        };
+        self.record(
+            "discovery.fetch",
+            Profile::Basic,
+            "Discovery 4",
+            Ok(url.clone()),
+        );
+
+        let issuer = metadata["issuer"].as_str().unwrap_or_default();
+        let outcome = if issuer == self.config.base_url {
+            Ok(issuer.to_string())
+        } else {
+            Err(format!(
+                "issuer {:?} does not match {:?}",
+                issuer, self.config.base_url
+            ))
+        };
+        self.record("discovery.issuer", Profile::Basic, "Discovery 4.3", outcome);
+
+        let mut missing = Vec::new();
+        for field in ["authorization_endpoint", "token_endpoint", "jwks_uri"] {
+            if metadata[field].as_str().is_none() {
+                missing.push(field.to_string());
+            }
+        }
+        for (field, value) in [
+            ("response_types_supported", "code"),
+            ("subject_types_supported", "public"),
+            ("id_token_signing_alg_values_supported", "RS256"),
+        ] {
+            if !array_contains(&metadata[field], value) {
+                missing.push(format!("{} ∋ {}", field, value));
+            }
+        }
+        let outcome = if missing.is_empty() {
+            Ok("required metadata present".to_string())
+        } else {
+            Err(format!("missing: {}", missing.join(", ")))
+        };
+        self.record("discovery.required", Profile::Basic, "Discovery 3", outcome);
+
+        let outcome = if array_contains(&metadata["code_challenge_methods_supported"], "S256") {
+            Ok("S256 advertised".to_string())
+        } else {
+            Err("code_challenge_methods_supported does not list S256".to_string())
+        };
+        self.record("discovery.pkce", Profile::Pkce, "RFC 8414 2", outcome);
+
+        Some(metadata)
+    }
+
+    async fn jwks(&mut self, metadata: &Value) {
+        let Some(url) = metadata["jwks_uri"].as_str() else {
+            self.record(
+                "jwks.keys",
+                Profile::Basic,
+                "Core 10.1",
+                Err("no jwks_uri".to_string()),
+            );
+            return;
+        };
+        let outcome = match self.get_json(url).await {
+            Ok(jwks) => match jwks["keys"].as_array() {
+                Some(keys) if !keys.is_empty() => keys
+                    .iter()
+                    .find_map(jwk_problem)
+                    .map_or(Ok(format!("{} signing keys", keys.len())), Err),
+                _ => Err("no keys published".to_string()),
+            },
+            Err(e) => Err(e),
+        };
+        self.record("jwks.keys", Profile::Basic, "Core 10.1", outcome);
+    }
+
+    /// Password login, then trade the token for the SSO cookie
+    async fn sign_in(&mut self) -> Result<(), String> {
+        let response = self
+            .http
+            .post(format!("{}/auth/login", self.config.base_url))
+            .json(&serde_json::json!({
+                "email": self.config.email,
+                "password": self.config.password,
+                "tenant_id": self.config.tenant_id,
+            }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let body = json_body(response, 200).await?;
+        let access_token = body["access_token"]
+            .as_str()
+            .ok_or("login returned no access_token")?;
+
+        let response = self
+            .http
+            .post(format!("{}/auth/sso/session", self.config.base_url))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("SSO session: HTTP {}", response.status()));
+        }
+        self.absorb_cookies(&response);
+        Ok(())
+    }
+
+    /// Returns the code, or why none was issued
+    async fn authorize(
+        &mut self,
+        metadata: &Value,
+        nonce: &str,
+        verifier: &str,
+        prompt: Option<&str>,
+    ) -> Result<String, String> {
+        let endpoint = metadata["authorization_endpoint"]
+            .as_str()
+            .ok_or("no authorization_endpoint")?;
+        let state = Uuid::new_v4().to_string();
+        let mut url = url::Url::parse(endpoint).map_err(|e| e.to_string())?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", self.config.redirect_uri.as_str())
+            .append_pair("scope", "openid profile email")
+            .append_pair("state", &state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", &pkce_challenge(verifier))
+            .append_pair("code_challenge_method", "S256");
+        if let Some(prompt) = prompt {
+            url.query_pairs_mut().append_pair("prompt", prompt);
+        }
+
+        let response = self
+            .http
+            .get(url)
+            .header(header::COOKIE, self.cookie_header())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_redirection() {
+            return Err(format!(
+                "expected a redirect, got HTTP {}",
+                response.status()
+            ));
+        }
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|h| h.to_str().ok())
+            .ok_or("redirect without Location")?;
+        if !location.starts_with(&self.config.redirect_uri) {
+            return Err(format!("redirected to {} instead of the client", location));
+        }
+
+        let location = url::Url::parse(location).map_err(|e| e.to_string())?;
+        let param = |name: &str| {
+            location
+                .query_pairs()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.into_owned())
+        };
+        if let Some(error) = param("error") {
+            return Err(format!("error={}", error));
+        }
+        if param("state").as_deref() != Some(state.as_str()) {
+            return Err("state not echoed".to_string());
+        }
+        param("code").ok_or_else(|| "no code in redirect".to_string())
+    }
+
+    async fn token_request(
+        &self,
+        metadata: &Value,
+        form: &[(&str, &str)],
+    ) -> Result<Response, String> {
+        let endpoint = metadata["token_endpoint"]
+            .as_str()
+            .ok_or("no token_endpoint")?;
+        let mut form: Vec<(&str, &str)> = form.to_vec();
+        form.push(("client_id", &self.config.client_id));
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret));
+        }
+        self.http
+            .post(endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn exchange(
+        &mut self,
+        metadata: &Value,
+        code: &str,
+        verifier: &str,
+        nonce: &str,
+    ) -> Option<Tokens> {
+        let response = self
+            .token_request(
+                metadata,
+                &[
+                    ("grant_type", "authorization_code"),
+                    ("code", code),
+                    ("redirect_uri", self.config.redirect_uri.as_str()),
+                    ("code_verifier", verifier),
+                ],
+            )
+            .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                self.record("token.exchange", Profile::Basic, "Core 3.1.3.3", Err(e));
+                return None;
+            }
+        };
+
+        let no_store = response
+            .headers()
+            .get(header::CACHE_CONTROL)
+            .and_then(|h| h.to_str().ok())
+            .is_some_and(|v| v.contains("no-store"));
+        let body = match json_body(response, 200).await {
+            Ok(body) => body,
+            Err(e) => {
+                self.record("token.exchange", Profile::Basic, "Core 3.1.3.3", Err(e));
+                return None;
+            }
+        };
+
+        let token_type_ok = body["token_type"]
+            .as_str()
+            .is_some_and(|t| t.eq_ignore_ascii_case("bearer"));
+        let Some(access_token) = body["access_token"].as_str().filter(|_| token_type_ok) else {
+            self.record(
+                "token.exchange",
+                Profile::Basic,
+                "Core 3.1.3.3",
+                Err("access_token or Bearer token_type missing".to_string()),
+            );
+            return None;
+        };
+        self.record(
+            "token.exchange",
+            Profile::Basic,
+            "Core 3.1.3.3",
+            Ok("access token issued".to_string()),
+        );
+        self.record(
+            "token.no_store",
+            Profile::Basic,
+            "Core 3.1.3.3",
+            if no_store {
+                Ok("Cache-Control: no-store".to_string())
+            } else {
+                Err("token response is cacheable".to_string())
+            },
+        );
+
+        let issuer = metadata["issuer"].as_str().unwrap_or_default();
+        let id_token = body["id_token"].as_str().unwrap_or_default();
+        let id_token_claims = check_id_token(id_token, issuer, &self.config.client_id, nonce);
+        let id_token_sub = id_token_claims
+            .as_ref()
+            .ok()
+            .and_then(|c| c["sub"].as_str().map(str::to_string));
+        self.record(
+            "token.id_token",
+            Profile::Basic,
+            "Core 3.1.3.7",
+            id_token_claims.map(|_| "iss, aud, sub, exp, iat and nonce valid".to_string()),
+        );
+
+        Some(Tokens {
+            access_token: access_token.to_string(),
+            refresh_token: body["refresh_token"].as_str().map(str::to_string),
+            id_token_sub,
+        })
+    }
+
+    async fn code_replay(&mut self, metadata: &Value, code: &str, verifier: &str) {
+        let outcome = match self
+            .token_request(
+                metadata,
+                &[
+                    ("grant_type", "authorization_code"),
+                    ("code", code),
+                    ("redirect_uri", self.config.redirect_uri.as_str()),
+                    ("code_verifier", verifier),
+                ],
+            )
+            .await
+        {
+            Ok(r) if r.status().is_client_error() => Ok(format!("rejected with {}", r.status())),
+            Ok(r) => Err(format!("second use answered HTTP {}", r.status())),
+            Err(e) => Err(e),
+        };
+        self.record(
+            "token.code_replay",
+            Profile::Basic,
+            "RFC 6749 4.1.2",
+            outcome,
+        );
+    }
+
+    /// A code bound to one verifier must not redeem with another
+    async fn pkce_verifier_checked(&mut self, metadata: &Value) {
+        let verifier = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let nonce = Uuid::new_v4().to_string();
+        let outcome = match self.authorize(metadata, &nonce, &verifier, None).await {
+            Ok(code) => {
+                let wrong = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+                match self
+                    .token_request(
+                        metadata,
+                        &[
+                            ("grant_type", "authorization_code"),
+                            ("code", code.as_str()),
+                            ("redirect_uri", self.config.redirect_uri.as_str()),
+                            ("code_verifier", wrong.as_str()),
+                        ],
+                    )
+                    .await
+                {
+                    Ok(r) if r.status().is_client_error() => {
+                        Ok(format!("wrong verifier rejected with {}", r.status()))
+                    }
+                    Ok(r) => Err(format!("wrong verifier answered HTTP {}", r.status())),
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(format!("could not start a second flow: {}", e)),
+        };
+        self.record(
+            "pkce.verifier_checked",
+            Profile::Pkce,
+            "RFC 7636 4.6",
+            outcome,
+        );
+    }
+
+    async fn userinfo(&mut self, metadata: &Value, tokens: &Tokens) {
+        let outcome = match metadata["userinfo_endpoint"].as_str() {
+            Some(endpoint) => match self
+                .http
+                .get(endpoint)
+                .bearer_auth(&tokens.access_token)
+                .send()
+                .await
+            {
+                Ok(response) => match json_body(response, 200).await {
+                    Ok(body) => match (body["sub"].as_str(), tokens.id_token_sub.as_deref()) {
+                        (None, _) => Err("no sub claim".to_string()),
+                        (Some(sub), Some(expected)) if sub != expected => Err(format!(
+                            "sub {} differs from the ID token's {}",
+                            sub, expected
+                        )),
+                        (Some(sub), _) => Ok(format!("sub {}", sub)),
+                    },
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e.to_string()),
+            },
+            None => Err("no userinfo_endpoint".to_string()),
+        };
+        self.record("userinfo.sub", Profile::Basic, "Core 5.3.2", outcome);
+    }
+
+    async fn refresh(&mut self, metadata: &Value, tokens: &Tokens) {
+        let Some(refresh_token) = &tokens.refresh_token else {
+            self.record(
+                "refresh.grant",
+                Profile::Basic,
+                "Core 12.2",
+                Err("no refresh_token issued".to_string()),
+            );
+            return;
+        };
+        let outcome = match self
+            .token_request(
+                metadata,
+                &[
+                    ("grant_type", "refresh_token"),
+                    ("refresh_token", refresh_token.as_str()),
+                ],
+            )
+            .await
+        {
+            Ok(response) => json_body(response, 200).await.and_then(|body| {
+                body["access_token"]
+                    .as_str()
+                    .map(|_| "new access token issued".to_string())
+                    .ok_or_else(|| "no access_token".to_string())
+            }),
+            Err(e) => Err(e),
+        };
+        self.record("refresh.grant", Profile::Basic, "Core 12.2", outcome);
+    }
+
+    async fn logout(&mut self, metadata: &Value) {
+        let outcome = match metadata["end_session_endpoint"].as_str() {
+            Some(endpoint) => match self
+                .http
+                .get(endpoint)
+                .header(header::COOKIE, self.cookie_header())
+                .send()
+                .await
+            {
+                Ok(r) if r.status().is_success() || r.status().is_redirection() => {
+                    Ok(format!("HTTP {}", r.status()))
+                }
+                Ok(r) => Err(format!("HTTP {}", r.status())),
+                Err(e) => Err(e.to_string()),
+            },
+            None => Err("end_session_endpoint not advertised".to_string()),
+        };
+        let logged_out = outcome.is_ok();
+        self.record(
+            "logout.end_session",
+            Profile::Basic,
+            "RP-Initiated Logout 2",
+            outcome,
+        );
+        if !logged_out {
+            return;
+        }
+
+        // The browser still replays the old cookie; the session behind it
+        // must be gone
+        let verifier = Uuid::new_v4().simple().to_string().repeat(2);
+        let outcome = match self.authorize(metadata, "n", &verifier, Some("none")).await {
+            Err(e) if e == "error=login_required" => Ok("login_required".to_string()),
+            Err(e) => Err(e),
+            Ok(_) => Err("code issued after logout".to_string()),
+        };
+        self.record(
+            "logout.prompt_none",
+            Profile::Basic,
+            "Core 3.1.2.6",
+            outcome,
+        );
+    }
+
+    async fn get_json(&self, url: &str) -> Result<Value, String> {
+        let response = self.http.get(url).send().await.map_err(|e| e.to_string())?;
+        json_body(response, 200).await
+    }
+
+    fn absorb_cookies(&mut self, response: &Response) {
+        for value in response.headers().get_all(header::SET_COOKIE) {
+            let Some((name, value)) = value
+                .to_str()
+                .ok()
+                .and_then(|v| v.split(';').next())
+                .and_then(|pair| pair.split_once('='))
+            else {
+                continue;
+            };
+            self.cookies.retain(|(n, _)| n != name);
+            self.cookies.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    fn cookie_header(&self) -> String {
+        self.cookies
+            .iter()
+            .map(|(n, v)| format!("{}={}", n, v))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+async fn json_body(response: Response, expected: u16) -> Result<Value, String> {
+    let status = response.status();
+    if status.as_u16() != expected {
+        return Err(format!("HTTP {}", status));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("invalid JSON: {}", e))
+}
+
+fn array_contains(value: &Value, item: &str) -> bool {
+    value
+        .as_array()
+        .is_some_and(|a| a.iter().any(|v| v.as_str() == Some(item)))
+}
+
+fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// What is wrong with a published JWK, if anything
+fn jwk_problem(key: &Value) -> Option<String> {
+    let kid = key["kid"].as_str().unwrap_or("<no kid>");
+    match key["kty"].as_str() {
+        Some("RSA") if key["n"].is_string() && key["e"].is_string() => None,
+        Some("RSA") => Some(format!("RSA key {} lacks n or e", kid)),
+        Some("EC") if key["x"].is_string() && key["y"].is_string() => None,
+        Some("EC") => Some(format!("EC key {} lacks x or y", kid)),
+        Some(kty) => Some(format!("key {} has unusable kty {}", kid, kty)),
+        None => Some(format!("key {} has no kty", kid)),
+    }
+}
+
+/// Claims of an ID token after checking the ones Core 3.1.3.7 requires.
+/// The signature is not verified here; the JWKS check covers key publication.
+fn check_id_token(
+    token: &str,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+) -> Result<Value, String> {
+    let payload = token
+        .split('.')
+        .nth(1)
+        .filter(|_| token.split('.').count() == 3)
+        .ok_or("id_token is not a JWT")?;
+    let claims: Value = URL_SAFE_NO_PAD
+        .decode(payload)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or("id_token payload is not JSON")?;
+
+    if claims["iss"].as_str() != Some(issuer) {
+        return Err(format!("iss {} is not {}", claims["iss"], issuer));
+    }
+    let audience_ok = match &claims["aud"] {
+        Value::String(aud) => aud == client_id,
+        aud => array_contains(aud, client_id),
+    };
+    if !audience_ok {
+        return Err(format!(
+            "aud {} does not include {}",
+            claims["aud"], client_id
+        ));
+    }
+    if claims["sub"].as_str().is_none() {
+        return Err("no sub".to_string());
+    }
+    let now = chrono::Utc::now().timestamp();
+    match (claims["exp"].as_i64(), claims["iat"].as_i64()) {
+        (Some(exp), Some(_)) if exp > now => {}
+        (Some(_), Some(_)) => return Err("id_token already expired".to_string()),
+        _ => return Err("exp or iat missing".to_string()),
+    }
+    if claims["nonce"].as_str() != Some(nonce) {
+        return Err("nonce not echoed".to_string());
+    }
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jwt(claims: Value) -> String {
+        format!(
+            "e30.{}.sig",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap())
+        )
+    }
+
+    #[test]
+    fn test_id_token_claims_are_checked() {
+        let exp = chrono::Utc::now().timestamp() + 300;
+        let good = jwt(serde_json::json!({
+            "iss": "https://idp", "aud": ["rp"], "sub": "u1",
+            "exp": exp, "iat": exp - 600, "nonce": "n1",
+        }));
+        assert!(check_id_token(&good, "https://idp", "rp", "n1").is_ok());
+        assert!(check_id_token(&good, "https://idp", "rp", "other").is_err());
+        assert!(check_id_token(&good, "https://other", "rp", "n1").is_err());
+        assert!(check_id_token("mock_id_token_jwt", "https://idp", "rp", "n1").is_err());
+    }
+
+    #[test]
+    fn test_pkce_challenge_matches_rfc_example() {
+        // RFC 7636 Appendix B
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+}
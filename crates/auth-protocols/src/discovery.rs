@@ -7,11 +7,29 @@ pub struct OidcProviderMetadata {
     pub token_endpoint: String,
     pub userinfo_endpoint: String,
     pub jwks_uri: String,
+    /// RFC 7591 dynamic client registration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registration_endpoint: Option<String>,
     pub scopes_supported: Vec<String>,
     pub response_types_supported: Vec<String>,
+    pub response_modes_supported: Vec<String>,
     pub grant_types_supported: Vec<String>,
     pub subject_types_supported: Vec<String>,
     pub id_token_signing_alg_values_supported: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check_session_iframe: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_session_endpoint: Option<String>,
+    pub frontchannel_logout_supported: bool,
+    pub frontchannel_logout_session_supported: bool,
+    pub backchannel_logout_supported: bool,
+    pub backchannel_logout_session_supported: bool,
+    /// Default access token lifetime in seconds (non-standard)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token_lifetime: Option<u64>,
+    /// Default refresh token lifetime in seconds (non-standard)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token_lifetime: Option<u64>,
 }
 
 impl Default for OidcProviderMetadata {
@@ -22,18 +40,33 @@ impl Default for OidcProviderMetadata {
             token_endpoint: "http://localhost:8080/auth/token".to_string(),
             userinfo_endpoint: "http://localhost:8080/auth/userinfo".to_string(),
             jwks_uri: "http://localhost:8080/auth/certs".to_string(),
+            registration_endpoint: Some("http://localhost:8080/oauth/register".to_string()),
             scopes_supported: vec![
                 "openid".to_string(),
                 "profile".to_string(),
                 "email".to_string(),
             ],
             response_types_supported: vec!["code".to_string()],
+            response_modes_supported: vec![
+                "query".to_string(),
+                "fragment".to_string(),
+                "form_post".to_string(),
+            ],
             grant_types_supported: vec![
                 "authorization_code".to_string(),
                 "refresh_token".to_string(),
+                "urn:ietf:params:oauth:grant-type:jwt-bearer".to_string(),
             ],
             subject_types_supported: vec!["public".to_string()],
             id_token_signing_alg_values_supported: vec!["RS256".to_string()],
+            check_session_iframe: Some("http://localhost:8080/auth/check_session".to_string()),
+            end_session_endpoint: Some("http://localhost:8080/auth/logout".to_string()),
+            frontchannel_logout_supported: true,
+            frontchannel_logout_session_supported: true,
+            backchannel_logout_supported: true,
+            backchannel_logout_session_supported: true,
+            access_token_lifetime: None,
+            refresh_token_lifetime: None,
         }
     }
 }
@@ -45,6 +78,9 @@ pub fn generate_oidc_metadata(base_url: &str) -> OidcProviderMetadata {
         token_endpoint: format!("{}/auth/token", base_url),
         userinfo_endpoint: format!("{}/auth/userinfo", base_url),
         jwks_uri: format!("{}/auth/certs", base_url),
+        registration_endpoint: Some(format!("{}/oauth/register", base_url)),
+        check_session_iframe: Some(format!("{}/auth/check_session", base_url)),
+        end_session_endpoint: Some(format!("{}/auth/logout", base_url)),
         ..Default::default()
     }
 }
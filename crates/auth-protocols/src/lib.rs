@@ -1,8 +1,15 @@
+pub mod client_registration;
+pub mod conformance;
 pub mod discovery;
 pub mod oauth;
 pub mod oidc;
+#[cfg(feature = "radius")]
+pub mod radius;
 pub mod saml;
+pub mod upstream_logout;
 
+pub use client_registration::ClientService;
 pub use oauth::OAuthService;
 pub use oidc::OidcService;
 pub use saml::SamlService;
+pub use upstream_logout::UpstreamLogoutService;
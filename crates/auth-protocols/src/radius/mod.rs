@@ -0,0 +1,178 @@
+//! RADIUS server for VPNs and network devices
+//!
+//! Supports PAP and MS-CHAPv2 on the authentication port and logs
+//! accounting packets to the audit trail. Only registered NAS clients are
+//! answered, each bound to a tenant and its shared secret.
+
+pub mod mschap;
+pub mod packet;
+mod server;
+
+pub use server::{NtHashStore, RadiusServer};
+
+use auth_config::RadiusServerConfig;
+use secrecy::{ExposeSecret, Secret};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::RwLock;
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum RadiusError {
+    #[error("Invalid RADIUS configuration: {0}")]
+    Config(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A registered network access server
+#[derive(Clone)]
+pub struct NasClient {
+    pub name: String,
+    pub address: IpAddr,
+    pub tenant_id: Uuid,
+    secret: Secret<String>,
+}
+
+impl NasClient {
+    pub fn new(
+        name: impl Into<String>,
+        address: IpAddr,
+        tenant_id: Uuid,
+        secret: Secret<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            address,
+            tenant_id,
+            secret,
+        }
+    }
+
+    pub(crate) fn secret(&self) -> &[u8] {
+        self.secret.expose_secret().as_bytes()
+    }
+}
+
+/// NAS clients keyed by source address
+#[derive(Default)]
+pub struct NasRegistry {
+    clients: RwLock<HashMap<IpAddr, NasClient>>,
+}
+
+impl NasRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Clients from config; a client without its own secret uses its tenant's
+    pub fn from_config(config: &RadiusServerConfig) -> Result<Self, RadiusError> {
+        let registry = Self::new();
+        for client in &config.clients {
+            let address = client
+                .address
+                .parse()
+                .map_err(|_| RadiusError::Config(format!("client '{}' address", client.name)))?;
+            let tenant_id = Uuid::parse_str(&client.tenant_id)
+                .map_err(|_| RadiusError::Config(format!("client '{}' tenant id", client.name)))?;
+            let secret = client
+                .secret
+                .clone()
+                .or_else(|| {
+                    config
+                        .tenants
+                        .get(&client.tenant_id)
+                        .map(|t| t.shared_secret.clone())
+                })
+                .ok_or_else(|| {
+                    RadiusError::Config(format!("client '{}' has no secret", client.name))
+                })?;
+            registry.register(NasClient::new(
+                client.name.clone(),
+                address,
+                tenant_id,
+                secret,
+            ));
+        }
+        Ok(registry)
+    }
+
+    pub fn register(&self, client: NasClient) {
+        self.clients
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(client.address, client);
+    }
+
+    pub fn remove(&self, address: &IpAddr) -> Option<NasClient> {
+        self.clients
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(address)
+    }
+
+    pub fn get(&self, address: &IpAddr) -> Option<NasClient> {
+        self.clients
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(address)
+            .cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auth_config::{NasClientConfig, RadiusTenantConfig};
+
+    #[test]
+    fn test_registry_resolves_tenant_secret() {
+        let tenant = Uuid::new_v4();
+        let mut config = RadiusServerConfig {
+            host: "127.0.0.1".to_string(),
+            auth_port: 1812,
+            acct_port: 1813,
+            tenants: HashMap::new(),
+            clients: vec![
+                NasClientConfig {
+                    name: "vpn".to_string(),
+                    address: "10.0.0.1".to_string(),
+                    tenant_id: tenant.to_string(),
+                    secret: None,
+                },
+                NasClientConfig {
+                    name: "wifi".to_string(),
+                    address: "10.0.0.2".to_string(),
+                    tenant_id: tenant.to_string(),
+                    secret: Some(Secret::new("wifi-controller-secret".to_string())),
+                },
+            ],
+        };
+        assert!(NasRegistry::from_config(&config).is_err());
+
+        config.tenants.insert(
+            tenant.to_string(),
+            RadiusTenantConfig {
+                shared_secret: Secret::new("tenant-shared-secret".to_string()),
+            },
+        );
+        let registry = NasRegistry::from_config(&config).unwrap();
+        assert_eq!(registry.len(), 2);
+
+        let vpn = registry.get(&"10.0.0.1".parse().unwrap()).unwrap();
+        assert_eq!(vpn.tenant_id, tenant);
+        assert_eq!(vpn.secret(), b"tenant-shared-secret");
+        let wifi = registry.get(&"10.0.0.2".parse().unwrap()).unwrap();
+        assert_eq!(wifi.secret(), b"wifi-controller-secret");
+        assert!(registry.get(&"10.0.0.3".parse().unwrap()).is_none());
+    }
+}
@@ -0,0 +1,319 @@
+//! UDP listeners and request handling
+
+use super::mschap::{self, MsChap2Response};
+use super::packet::{self, attr, ms, Code, Packet, MAX_PACKET_LEN};
+use super::{NasClient, NasRegistry};
+use async_trait::async_trait;
+use auth_core::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use auth_core::error::AuthError;
+use auth_core::models::SensitiveString;
+use auth_core::services::identity::IdentityService;
+use auth_core::services::timing;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use uuid::Uuid;
+
+/// Source of NT password hashes for MS-CHAPv2. Without one, MS-CHAPv2
+/// requests are rejected and only PAP is available.
+#[async_trait]
+pub trait NtHashStore: Send + Sync {
+    async fn nt_hash(&self, tenant_id: Uuid, username: &str)
+        -> Result<Option<[u8; 16]>, AuthError>;
+}
+
+enum Method {
+    Pap,
+    MsChapV2,
+}
+
+impl Method {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Method::Pap => "pap",
+            Method::MsChapV2 => "mschapv2",
+        }
+    }
+}
+
+pub struct RadiusServer {
+    registry: Arc<NasRegistry>,
+    identity: Arc<IdentityService>,
+    nt_hashes: Option<Arc<dyn NtHashStore>>,
+    audit: Arc<dyn AuditLogger>,
+}
+
+impl RadiusServer {
+    pub fn new(
+        registry: Arc<NasRegistry>,
+        identity: Arc<IdentityService>,
+        audit: Arc<dyn AuditLogger>,
+    ) -> Self {
+        Self {
+            registry,
+            identity,
+            nt_hashes: None,
+            audit,
+        }
+    }
+
+    pub fn with_nt_hashes(mut self, store: Arc<dyn NtHashStore>) -> Self {
+        self.nt_hashes = Some(store);
+        self
+    }
+
+    /// Answer Access-Requests until the socket fails
+    pub async fn serve_auth(self: Arc<Self>, socket: UdpSocket) -> std::io::Result<()> {
+        self.serve(socket, Code::AccessRequest).await
+    }
+
+    /// Answer Accounting-Requests until the socket fails
+    pub async fn serve_accounting(self: Arc<Self>, socket: UdpSocket) -> std::io::Result<()> {
+        self.serve(socket, Code::AccountingRequest).await
+    }
+
+    async fn serve(self: Arc<Self>, socket: UdpSocket, expected: Code) -> std::io::Result<()> {
+        let socket = Arc::new(socket);
+        let mut buf = vec![0u8; MAX_PACKET_LEN];
+        loop {
+            let (len, peer) = socket.recv_from(&mut buf).await?;
+            let raw = buf[..len].to_vec();
+            let server = self.clone();
+            let socket = socket.clone();
+            // Password hashing is slow; never let one request hold up the socket
+            tokio::spawn(async move {
+                if let Some(reply) = server.handle(&raw, peer, expected).await {
+                    if let Err(e) = socket.send_to(&reply, peer).await {
+                        tracing::warn!(peer = %peer, error = %e, "Failed to send RADIUS reply");
+                    }
+                }
+            });
+        }
+    }
+
+    /// Handle one datagram. Anything that cannot be authenticated as coming
+    /// from a registered NAS is silently dropped, as RFC 2865 requires.
+    async fn handle(&self, raw: &[u8], peer: SocketAddr, expected: Code) -> Option<Vec<u8>> {
+        let Some(nas) = self.registry.get(&peer.ip()) else {
+            tracing::warn!(peer = %peer, "Dropping RADIUS packet from unregistered client");
+            return None;
+        };
+        let request = match Packet::decode(raw) {
+            Ok(request) if request.code == expected => request,
+            Ok(request) => {
+                tracing::debug!(nas = %nas.name, code = ?request.code, "Unexpected RADIUS code");
+                return None;
+            }
+            Err(e) => {
+                tracing::debug!(nas = %nas.name, error = %e, "Malformed RADIUS packet");
+                return None;
+            }
+        };
+
+        let reply = match expected {
+            Code::AccountingRequest => self.handle_accounting(raw, &request, &nas).await?,
+            _ => self.handle_access(raw, &request, &nas).await?,
+        };
+        match reply.encode_response(&request.authenticator, nas.secret()) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                tracing::warn!(nas = %nas.name, error = %e, "Failed to encode RADIUS reply");
+                None
+            }
+        }
+    }
+
+    async fn handle_access(&self, raw: &[u8], request: &Packet, nas: &NasClient) -> Option<Packet> {
+        if packet::verify_message_authenticator(raw, nas.secret()) == Some(false) {
+            tracing::warn!(nas = %nas.name, "Dropping Access-Request with bad Message-Authenticator");
+            return None;
+        }
+        let username = request.string(attr::USER_NAME)?;
+
+        let (method, result) = if let Some(hidden) = request.attribute(attr::USER_PASSWORD) {
+            let password =
+                packet::decrypt_user_password(hidden, &request.authenticator, nas.secret())
+                    .map(|p| SensitiveString::new(String::from_utf8_lossy(&p).into_owned()));
+            (
+                Method::Pap,
+                self.authenticate_pap(nas.tenant_id, &username, password)
+                    .await,
+            )
+        } else if let (Some(challenge), Some(response)) = (
+            request.vendor_attribute(ms::VENDOR_ID, ms::CHAP_CHALLENGE),
+            request.vendor_attribute(ms::VENDOR_ID, ms::CHAP2_RESPONSE),
+        ) {
+            (
+                Method::MsChapV2,
+                self.authenticate_mschapv2(nas.tenant_id, &username, challenge, response)
+                    .await,
+            )
+        } else {
+            tracing::debug!(nas = %nas.name, "Access-Request without a supported method");
+            return Some(request.reply(Code::AccessReject));
+        };
+
+        let mut reply;
+        let mut event = AuditEvent::new(
+            AuditCategory::Authentication,
+            "radius.access",
+            AuditSeverity::Info,
+        )
+        .with_context(Some(nas.address.to_string()), None, Some(nas.tenant_id))
+        .with_resource(nas.name.clone())
+        .with_metadata(json!({ "username": username, "method": method.as_str() }));
+
+        match result {
+            Ok(Accepted {
+                user_id,
+                mschap_success,
+            }) => {
+                reply = request.reply(Code::AccessAccept);
+                if let Some(success) = mschap_success {
+                    reply.add_vendor(ms::VENDOR_ID, ms::CHAP2_SUCCESS, &success);
+                }
+                event = event.with_actor(user_id);
+            }
+            Err(reason) => {
+                reply = request.reply(Code::AccessReject);
+                if let (Method::MsChapV2, Some(&ident)) = (
+                    &method,
+                    request
+                        .vendor_attribute(ms::VENDOR_ID, ms::CHAP2_RESPONSE)
+                        .and_then(|r| r.first()),
+                ) {
+                    // E=691 is "authentication failure"; R=0 disallows retry
+                    let mut error = vec![ident];
+                    error.extend_from_slice(b"E=691 R=0 V=3");
+                    reply.add_vendor(ms::VENDOR_ID, ms::CHAP_ERROR, &error);
+                }
+                event = event.failure(reason);
+            }
+        }
+        self.audit.log(event).await;
+        Some(reply)
+    }
+
+    async fn authenticate_pap(
+        &self,
+        tenant_id: Uuid,
+        username: &str,
+        password: Option<SensitiveString>,
+    ) -> Result<Accepted, String> {
+        let password = password.ok_or("malformed User-Password")?;
+        let user = self
+            .identity
+            .find_user_by_identifier(tenant_id, username)
+            .await
+            .map_err(|e| e.to_string())?;
+        let Some(user) = user else {
+            // Same cost as a real verification, so unknown users do not stand out
+            timing::verify_password_uniform(password, None).await;
+            return Err("unknown user".to_string());
+        };
+        if !user.can_authenticate() {
+            return Err("account locked or suspended".to_string());
+        }
+        match self.identity.verify_password(user.id, &password).await {
+            Ok(true) => Ok(Accepted {
+                user_id: user.id,
+                mschap_success: None,
+            }),
+            Ok(false) => Err("invalid credentials".to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    async fn authenticate_mschapv2(
+        &self,
+        tenant_id: Uuid,
+        username: &str,
+        challenge: &[u8],
+        response: &[u8],
+    ) -> Result<Accepted, String> {
+        let store = self.nt_hashes.as_ref().ok_or("MS-CHAPv2 not configured")?;
+        let challenge: [u8; 16] = challenge
+            .try_into()
+            .map_err(|_| "malformed MS-CHAP-Challenge")?;
+        let response = MsChap2Response::parse(response).ok_or("malformed MS-CHAP2-Response")?;
+
+        let user = self
+            .identity
+            .find_user_by_identifier(tenant_id, username)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("unknown user")?;
+        if !user.can_authenticate() {
+            return Err("account locked or suspended".to_string());
+        }
+        let nt_hash = store
+            .nt_hash(tenant_id, username)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("no NT hash enrolled")?;
+
+        let authenticator = mschap::verify(&challenge, &response, username, &nt_hash)
+            .ok_or("invalid credentials")?;
+        let mut success = vec![response.ident];
+        success.extend_from_slice(authenticator.as_bytes());
+        Ok(Accepted {
+            user_id: user.id,
+            mschap_success: Some(success),
+        })
+    }
+
+    async fn handle_accounting(
+        &self,
+        raw: &[u8],
+        request: &Packet,
+        nas: &NasClient,
+    ) -> Option<Packet> {
+        if !packet::verify_accounting_request(raw, nas.secret()) {
+            tracing::warn!(nas = %nas.name, "Dropping Accounting-Request with bad authenticator");
+            return None;
+        }
+
+        let status = match request.integer(attr::ACCT_STATUS_TYPE) {
+            Some(1) => "start",
+            Some(2) => "stop",
+            Some(3) => "interim_update",
+            Some(7) => "accounting_on",
+            Some(8) => "accounting_off",
+            _ => "unknown",
+        };
+        let framed_ip = request
+            .attribute(attr::FRAMED_IP_ADDRESS)
+            .and_then(|v| <[u8; 4]>::try_from(v).ok())
+            .map(|octets| std::net::Ipv4Addr::from(octets).to_string());
+
+        let event = AuditEvent::new(
+            AuditCategory::Authentication,
+            format!("radius.accounting.{}", status),
+            AuditSeverity::Info,
+        )
+        .with_context(Some(nas.address.to_string()), None, Some(nas.tenant_id))
+        .with_resource(nas.name.clone())
+        .with_metadata(json!({
+            "username": request.string(attr::USER_NAME),
+            "session_id": request.string(attr::ACCT_SESSION_ID),
+            "session_time_seconds": request.integer(attr::ACCT_SESSION_TIME),
+            "input_octets": request.integer(attr::ACCT_INPUT_OCTETS),
+            "output_octets": request.integer(attr::ACCT_OUTPUT_OCTETS),
+            "terminate_cause": request.integer(attr::ACCT_TERMINATE_CAUSE),
+            "framed_ip": framed_ip,
+            "calling_station_id": request.string(attr::CALLING_STATION_ID),
+            "nas_identifier": request.string(attr::NAS_IDENTIFIER),
+            "nas_port": request.integer(attr::NAS_PORT),
+        }));
+        self.audit.log(event).await;
+
+        // The reply only acknowledges that the record was stored
+        Some(request.reply(Code::AccountingResponse))
+    }
+}
+
+struct Accepted {
+    user_id: Uuid,
+    mschap_success: Option<Vec<u8>>,
+}
@@ -0,0 +1,665 @@
+//! Logout at the upstream identity providers users signed in through
+//!
+//! Ending the local session leaves the user's session at Google or AD FS
+//! alive, and the next sign-in goes straight through. For providers
+//! configured with `propagate_logout`, the browser is sent through each
+//! provider's logout endpoint in turn: OIDC RP-Initiated Logout, or a SAML
+//! `LogoutRequest` over the HTTP-Redirect binding. Every provider returns to
+//! `/auth/logout/upstream`, which resumes the chain. The progress between
+//! hops is kept in the nonce store under a single-use `state`.
+//!
+//! The summary at the end names the providers the user is still signed in
+//! to, so the landing page can tell them.
+
+use async_trait::async_trait;
+use auth_config::{SsoConfig, UpstreamIdpConfig, UpstreamProtocol};
+use auth_core::audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
+use auth_core::error::AuthError;
+use auth_core::models::{Session, UpstreamSession, UpstreamSessionStore};
+use auth_core::services::id_token::Issuers;
+use auth_core::services::nonce_store::{NonceNamespace, NonceStore};
+use auth_crypto::{KeyProvider, SoftKeyProvider};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine as _,
+};
+use chrono::{SecondsFormat, Utc};
+use dashmap::DashMap;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use rand::RngCore;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::Arc;
+use url::form_urlencoded;
+use uuid::Uuid;
+
+/// Path every provider sends the browser back to
+pub const UPSTREAM_LOGOUT_PATH: &str = "/auth/logout/upstream";
+
+const SIG_ALG_RSA_SHA256: &str = "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256";
+const SAML_STATUS_SUCCESS: &str = "urn:oasis:names:tc:SAML:2.0:status:Success";
+
+/// Why the user is still signed in at a provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeptReason {
+    /// The provider is not configured to propagate logout
+    NotPropagated,
+    /// Logout cannot be requested there, e.g. the provider was removed from
+    /// the configuration or the sign-in left no SAML subject
+    Unsupported,
+    /// The provider did not confirm the logout
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpstreamStillSignedIn {
+    pub provider: String,
+    pub display_name: String,
+    pub reason: KeptReason,
+}
+
+/// Outcome of the upstream part of a logout
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogoutSummary {
+    /// Providers whose session was ended
+    pub signed_out: Vec<String>,
+    pub still_signed_in: Vec<UpstreamStillSignedIn>,
+    /// Where the client asked to go after logout, already checked against
+    /// its registered URIs
+    pub final_redirect: Option<String>,
+}
+
+impl LogoutSummary {
+    /// The user is still signed in somewhere and should be told before
+    /// being sent on
+    pub fn is_partial(&self) -> bool {
+        !self.still_signed_in.is_empty()
+    }
+}
+
+/// Where the browser goes next
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogoutStep {
+    /// A provider's logout endpoint
+    Upstream(String),
+    Done(LogoutSummary),
+}
+
+/// Progress kept between hops
+#[derive(Debug, Serialize, Deserialize)]
+struct Progress {
+    tenant_id: Uuid,
+    user_id: Uuid,
+    session_id: Uuid,
+    pending: Vec<UpstreamSession>,
+    /// Provider whose logout page the browser was sent to
+    current: Option<String>,
+    summary: LogoutSummary,
+}
+
+pub struct UpstreamLogoutService {
+    store: Arc<dyn UpstreamSessionStore>,
+    nonces: Arc<NonceStore>,
+    providers: HashMap<String, UpstreamIdpConfig>,
+    signing_keys: HashMap<String, Arc<dyn KeyProvider>>,
+    issuers: Issuers,
+    audit: Option<Arc<dyn AuditLogger>>,
+}
+
+impl UpstreamLogoutService {
+    pub fn new(
+        store: Arc<dyn UpstreamSessionStore>,
+        nonces: Arc<NonceStore>,
+        config: &SsoConfig,
+        issuers: impl Into<Issuers>,
+    ) -> Result<Self, AuthError> {
+        let mut signing_keys: HashMap<String, Arc<dyn KeyProvider>> = HashMap::new();
+        for (id, idp) in &config.upstream_idps {
+            if let Some(pem) = &idp.signing_key_pem {
+                let key = SoftKeyProvider::from_pkcs8_pem(pem.expose_secret()).map_err(|e| {
+                    AuthError::ConfigurationError {
+                        message: format!("Upstream IdP {} signing key: {}", id, e),
+                    }
+                })?;
+                signing_keys.insert(id.clone(), Arc::new(key));
+            }
+        }
+        Ok(Self {
+            store,
+            nonces,
+            providers: config.upstream_idps.clone(),
+            signing_keys,
+            issuers: issuers.into(),
+            audit: None,
+        })
+    }
+
+    pub fn with_audit(mut self, audit: Arc<dyn AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Remember a sign-in through an upstream provider
+    pub async fn record(&self, session: &UpstreamSession) -> Result<(), AuthError> {
+        self.store.record(session).await
+    }
+
+    /// Start the upstream part of logging out `session`. The caller has
+    /// ended the local session already.
+    pub async fn begin(
+        &self,
+        session: &Session,
+        final_redirect: Option<String>,
+    ) -> Result<LogoutStep, AuthError> {
+        let upstream = self
+            .store
+            .for_session(session.tenant_id, session.id)
+            .await?;
+        self.store.remove(session.tenant_id, session.id).await?;
+
+        let mut progress = Progress {
+            tenant_id: session.tenant_id,
+            user_id: session.user_id,
+            session_id: session.id,
+            pending: Vec::new(),
+            current: None,
+            summary: LogoutSummary {
+                final_redirect,
+                ..Default::default()
+            },
+        };
+        for upstream_session in upstream {
+            let kept = match self.providers.get(&upstream_session.provider) {
+                None => Some(KeptReason::Unsupported),
+                Some(idp) if !idp.propagate_logout => Some(KeptReason::NotPropagated),
+                Some(idp)
+                    if idp.protocol == UpstreamProtocol::Saml
+                        && upstream_session.name_id.is_none() =>
+                {
+                    Some(KeptReason::Unsupported)
+                }
+                Some(_) => None,
+            };
+            match kept {
+                Some(reason) => {
+                    self.keep(&mut progress.summary, &upstream_session.provider, reason)
+                }
+                None => progress.pending.push(upstream_session),
+            }
+        }
+        self.advance(progress).await
+    }
+
+    /// Continue after a provider sent the browser back. `saml_response` is
+    /// the `SAMLResponse` parameter of a SAML provider.
+    pub async fn resume(
+        &self,
+        state: &str,
+        saml_response: Option<&str>,
+    ) -> Result<LogoutStep, AuthError> {
+        let payload = self
+            .nonces
+            .consume(NonceNamespace::UpstreamLogout, state)
+            .await?;
+        let mut progress: Progress =
+            serde_json::from_str(&payload).map_err(|_| AuthError::InternalError)?;
+
+        if let Some(provider) = progress.current.take() {
+            let confirmed = match self.providers.get(&provider).map(|idp| idp.protocol) {
+                // The provider only redirects to the registered
+                // post_logout_redirect_uri once its session is gone
+                Some(UpstreamProtocol::Oidc) => true,
+                Some(UpstreamProtocol::Saml) => saml_response.is_some_and(saml_logout_succeeded),
+                None => false,
+            };
+            if confirmed {
+                progress.summary.signed_out.push(provider);
+            } else {
+                self.keep(&mut progress.summary, &provider, KeptReason::Failed);
+            }
+        }
+        self.advance(progress).await
+    }
+
+    /// Send the browser to the next provider, or finish
+    async fn advance(&self, mut progress: Progress) -> Result<LogoutStep, AuthError> {
+        while !progress.pending.is_empty() {
+            let upstream_session = progress.pending.remove(0);
+            let state = random_state();
+            let url = match self.logout_url(&upstream_session, &state).await {
+                Ok(url) => url,
+                Err(e) => {
+                    tracing::warn!(
+                        provider = %upstream_session.provider,
+                        error = %e,
+                        "Cannot build upstream logout request"
+                    );
+                    self.keep(
+                        &mut progress.summary,
+                        &upstream_session.provider,
+                        KeptReason::Failed,
+                    );
+                    continue;
+                }
+            };
+            progress.current = Some(upstream_session.provider);
+            let payload = serde_json::to_string(&progress).map_err(|_| AuthError::InternalError)?;
+            self.nonces
+                .issue(NonceNamespace::UpstreamLogout, &state, &payload, None)
+                .await?;
+            return Ok(LogoutStep::Upstream(url));
+        }
+
+        if !progress.summary.signed_out.is_empty() || progress.summary.is_partial() {
+            self.log_summary(&progress).await;
+        }
+        Ok(LogoutStep::Done(progress.summary))
+    }
+
+    async fn logout_url(
+        &self,
+        upstream_session: &UpstreamSession,
+        state: &str,
+    ) -> Result<String, AuthError> {
+        let provider = &upstream_session.provider;
+        let idp = self
+            .providers
+            .get(provider)
+            .ok_or_else(|| AuthError::ConfigurationError {
+                message: format!("Unknown upstream IdP {}", provider),
+            })?;
+        let endpoint = idp
+            .logout_url
+            .as_deref()
+            .ok_or_else(|| AuthError::ConfigurationError {
+                message: format!("Upstream IdP {} has no logout_url", provider),
+            })?;
+
+        let query = match idp.protocol {
+            UpstreamProtocol::Oidc => {
+                let return_uri = format!(
+                    "{}{}",
+                    self.issuers.for_tenant(upstream_session.tenant_id),
+                    UPSTREAM_LOGOUT_PATH
+                );
+                let mut query = form_urlencoded::Serializer::new(String::new());
+                if let Some(id_token) = &upstream_session.id_token {
+                    query.append_pair("id_token_hint", id_token);
+                }
+                query
+                    .append_pair("client_id", &idp.client_id)
+                    .append_pair("post_logout_redirect_uri", &return_uri)
+                    .append_pair("state", state)
+                    .finish()
+            }
+            UpstreamProtocol::Saml => {
+                let request = saml_logout_request(idp, endpoint, upstream_session);
+                let mut query = form_urlencoded::Serializer::new(String::new())
+                    .append_pair("SAMLRequest", &deflate_base64(&request)?)
+                    .append_pair("RelayState", state)
+                    .finish();
+                if let Some(key) = self.signing_keys.get(provider) {
+                    // The signature covers the query exactly as sent
+                    query = form_urlencoded::Serializer::new(query)
+                        .append_pair("SigAlg", SIG_ALG_RSA_SHA256)
+                        .finish();
+                    let signature = key
+                        .sign(query.as_bytes())
+                        .await
+                        .map_err(|e| AuthError::UTCryptoError(e.to_string()))?;
+                    query = form_urlencoded::Serializer::new(query)
+                        .append_pair("Signature", &STANDARD.encode(signature))
+                        .finish();
+                }
+                query
+            }
+        };
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        Ok(format!("{}{}{}", endpoint, separator, query))
+    }
+
+    fn keep(&self, summary: &mut LogoutSummary, provider: &str, reason: KeptReason) {
+        summary.still_signed_in.push(UpstreamStillSignedIn {
+            provider: provider.to_string(),
+            display_name: self
+                .providers
+                .get(provider)
+                .map_or_else(|| provider.to_string(), |idp| idp.display_name.clone()),
+            reason,
+        });
+    }
+
+    async fn log_summary(&self, progress: &Progress) {
+        let Some(audit) = &self.audit else {
+            return;
+        };
+        let summary = &progress.summary;
+        audit
+            .log(
+                AuditEvent::new(
+                    AuditCategory::Authentication,
+                    "sso.upstream_logout",
+                    if summary.is_partial() {
+                        AuditSeverity::Warning
+                    } else {
+                        AuditSeverity::Info
+                    },
+                )
+                .with_actor(progress.user_id)
+                .with_context(None, None, Some(progress.tenant_id))
+                .with_resource(progress.session_id.to_string())
+                .with_metadata(serde_json::json!({
+                    "signed_out": &summary.signed_out,
+                    "still_signed_in": &summary.still_signed_in,
+                })),
+            )
+            .await;
+    }
+}
+
+fn random_state() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn saml_logout_request(
+    idp: &UpstreamIdpConfig,
+    destination: &str,
+    upstream_session: &UpstreamSession,
+) -> String {
+    let session_index = upstream_session
+        .session_index
+        .as_deref()
+        .map(|index| {
+            format!(
+                "<samlp:SessionIndex>{}</samlp:SessionIndex>",
+                xml_escape(index)
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        concat!(
+            r#"<samlp:LogoutRequest xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol" "#,
+            r#"xmlns:saml="urn:oasis:names:tc:SAML:2.0:assertion" "#,
+            r#"ID="_{id}" Version="2.0" IssueInstant="{instant}" Destination="{destination}">"#,
+            "<saml:Issuer>{issuer}</saml:Issuer>",
+            "<saml:NameID>{name_id}</saml:NameID>",
+            "{session_index}",
+            "</samlp:LogoutRequest>"
+        ),
+        id = Uuid::new_v4().simple(),
+        instant = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        destination = xml_escape(destination),
+        issuer = xml_escape(&idp.client_id),
+        name_id = xml_escape(upstream_session.name_id.as_deref().unwrap_or_default()),
+        session_index = session_index,
+    )
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Raw DEFLATE and base64, as the HTTP-Redirect binding wants
+fn deflate_base64(xml: &str) -> Result<String, AuthError> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(xml.as_bytes())
+        .and_then(|_| encoder.finish())
+        .map(|deflated| STANDARD.encode(deflated))
+        .map_err(|_| AuthError::InternalError)
+}
+
+/// Whether a `LogoutResponse` reports success. It arrives deflated over
+/// HTTP-Redirect or as plain XML over HTTP-POST. The answer only decides
+/// what the landing page says and grants nothing, so the response's
+/// signature is not checked.
+fn saml_logout_succeeded(saml_response: &str) -> bool {
+    let compact: String = saml_response.split_whitespace().collect();
+    let Ok(bytes) = STANDARD.decode(compact) else {
+        return false;
+    };
+    let mut inflated = String::new();
+    let xml = match DeflateDecoder::new(bytes.as_slice()).read_to_string(&mut inflated) {
+        Ok(_) => inflated,
+        Err(_) => String::from_utf8_lossy(&bytes).into_owned(),
+    };
+    // The first StatusCode is the top-level one; nested ones only refine it
+    xml.find("StatusCode")
+        .map(|at| &xml[at..])
+        .and_then(|rest| rest.split_once("Value=\""))
+        .and_then(|(_, value)| value.split_once('"'))
+        .is_some_and(|(value, _)| value == SAML_STATUS_SUCCESS)
+}
+
+#[derive(Default)]
+pub struct InMemoryUpstreamSessionStore {
+    sessions: DashMap<Uuid, UpstreamSession>,
+}
+
+#[async_trait]
+impl UpstreamSessionStore for InMemoryUpstreamSessionStore {
+    async fn record(&self, session: &UpstreamSession) -> Result<(), AuthError> {
+        self.sessions.insert(session.id, session.clone());
+        Ok(())
+    }
+
+    async fn for_session(
+        &self,
+        tenant_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<Vec<UpstreamSession>, AuthError> {
+        let mut sessions: Vec<_> = self
+            .sessions
+            .iter()
+            .filter(|s| s.tenant_id == tenant_id && s.session_id == session_id)
+            .map(|s| s.clone())
+            .collect();
+        sessions.sort_by_key(|s| s.created_at);
+        Ok(sessions)
+    }
+
+    async fn remove(&self, tenant_id: Uuid, session_id: Uuid) -> Result<(), AuthError> {
+        self.sessions
+            .retain(|_, s| !(s.tenant_id == tenant_id && s.session_id == session_id));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auth_core::services::nonce_store::MemoryNonceBackend;
+
+    fn idp(protocol: UpstreamProtocol, propagate_logout: bool) -> UpstreamIdpConfig {
+        UpstreamIdpConfig {
+            display_name: format!("{:?} IdP", protocol),
+            protocol,
+            propagate_logout,
+            logout_url: Some("https://idp.example.com/logout".to_string()),
+            client_id: "https://auth.example.com".to_string(),
+            signing_key_pem: None,
+        }
+    }
+
+    fn service(providers: &[(&str, UpstreamIdpConfig)]) -> UpstreamLogoutService {
+        let config = SsoConfig {
+            upstream_idps: providers
+                .iter()
+                .map(|(id, idp)| (id.to_string(), idp.clone()))
+                .collect(),
+            ..Default::default()
+        };
+        UpstreamLogoutService::new(
+            Arc::new(InMemoryUpstreamSessionStore::default()),
+            Arc::new(NonceStore::new(Arc::new(MemoryNonceBackend::default()))),
+            &config,
+            "https://auth.example.com",
+        )
+        .unwrap()
+    }
+
+    fn session() -> Session {
+        Session {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            tenant_id: Uuid::new_v4(),
+            session_token: "token".to_string(),
+            device_fingerprint: None,
+            user_agent: None,
+            ip_address: None,
+            risk_score: 0.0,
+            created_at: Utc::now(),
+            last_activity: Utc::now(),
+            expires_at: Utc::now() + chrono::Duration::hours(1),
+        }
+    }
+
+    async fn signed_in(
+        service: &UpstreamLogoutService,
+        session: &Session,
+        provider: &str,
+    ) -> UpstreamSession {
+        let upstream = UpstreamSession {
+            id: Uuid::new_v4(),
+            session_id: session.id,
+            tenant_id: session.tenant_id,
+            user_id: session.user_id,
+            provider: provider.to_string(),
+            id_token: Some("eyJ.id.token".to_string()),
+            name_id: Some("alice@example.com".to_string()),
+            session_index: Some("_idx<1>".to_string()),
+            created_at: Utc::now(),
+        };
+        service.record(&upstream).await.unwrap();
+        upstream
+    }
+
+    fn query(url: &str) -> HashMap<String, String> {
+        url::Url::parse(url)
+            .unwrap()
+            .query_pairs()
+            .into_owned()
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_oidc_logout_returns_to_us_with_state() {
+        let service = service(&[("google", idp(UpstreamProtocol::Oidc, true))]);
+        let session = session();
+        signed_in(&service, &session, "google").await;
+
+        let LogoutStep::Upstream(url) = service
+            .begin(&session, Some("https://app.example/bye".to_string()))
+            .await
+            .unwrap()
+        else {
+            panic!("expected a redirect to the provider");
+        };
+        assert!(url.starts_with("https://idp.example.com/logout?"));
+        let params = query(&url);
+        assert_eq!(params["id_token_hint"], "eyJ.id.token");
+        assert_eq!(
+            params["post_logout_redirect_uri"],
+            "https://auth.example.com/auth/logout/upstream"
+        );
+
+        let LogoutStep::Done(summary) = service.resume(&params["state"], None).await.unwrap()
+        else {
+            panic!("expected the logout to finish");
+        };
+        assert_eq!(summary.signed_out, vec!["google".to_string()]);
+        assert!(!summary.is_partial());
+        assert_eq!(
+            summary.final_redirect.as_deref(),
+            Some("https://app.example/bye")
+        );
+
+        // The state works once
+        assert!(service.resume(&params["state"], None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_saml_logout_request_is_deflated() {
+        let service = service(&[("adfs", idp(UpstreamProtocol::Saml, true))]);
+        let session = session();
+        signed_in(&service, &session, "adfs").await;
+
+        let LogoutStep::Upstream(url) = service.begin(&session, None).await.unwrap() else {
+            panic!("expected a redirect to the provider");
+        };
+        let params = query(&url);
+        let deflated = STANDARD.decode(&params["SAMLRequest"]).unwrap();
+        let mut xml = String::new();
+        DeflateDecoder::new(deflated.as_slice())
+            .read_to_string(&mut xml)
+            .unwrap();
+        assert!(xml.contains("<saml:NameID>alice@example.com</saml:NameID>"));
+        assert!(xml.contains("<samlp:SessionIndex>_idx&lt;1&gt;</samlp:SessionIndex>"));
+        assert!(xml.contains(r#"Destination="https://idp.example.com/logout""#));
+        assert!(!params.contains_key("Signature"));
+
+        let failed = STANDARD.encode(
+            r#"<samlp:LogoutResponse><samlp:Status><samlp:StatusCode Value="urn:oasis:names:tc:SAML:2.0:status:Responder"/></samlp:Status></samlp:LogoutResponse>"#,
+        );
+        let LogoutStep::Done(summary) = service
+            .resume(&params["RelayState"], Some(&failed))
+            .await
+            .unwrap()
+        else {
+            panic!("expected the logout to finish");
+        };
+        assert!(summary.is_partial());
+        assert_eq!(summary.still_signed_in[0].reason, KeptReason::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_providers_without_propagation_are_reported() {
+        let service = service(&[
+            ("google", idp(UpstreamProtocol::Oidc, false)),
+            ("adfs", idp(UpstreamProtocol::Saml, true)),
+        ]);
+        let session = session();
+        signed_in(&service, &session, "google").await;
+        signed_in(&service, &session, "adfs").await;
+
+        let LogoutStep::Upstream(url) = service.begin(&session, None).await.unwrap() else {
+            panic!("expected a redirect to adfs");
+        };
+        let success = STANDARD.encode(format!(
+            r#"<samlp:LogoutResponse><samlp:Status><samlp:StatusCode Value="{}"/></samlp:Status></samlp:LogoutResponse>"#,
+            SAML_STATUS_SUCCESS
+        ));
+        let LogoutStep::Done(summary) = service
+            .resume(&query(&url)["RelayState"], Some(&success))
+            .await
+            .unwrap()
+        else {
+            panic!("expected the logout to finish");
+        };
+        assert_eq!(summary.signed_out, vec!["adfs".to_string()]);
+        assert_eq!(
+            summary.still_signed_in,
+            vec![UpstreamStillSignedIn {
+                provider: "google".to_string(),
+                display_name: "Oidc IdP".to_string(),
+                reason: KeptReason::NotPropagated,
+            }]
+        );
+
+        // Logging out again finds nothing upstream
+        assert_eq!(
+            service.begin(&session, None).await.unwrap(),
+            LogoutStep::Done(LogoutSummary::default())
+        );
+    }
+}
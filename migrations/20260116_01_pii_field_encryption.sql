@@ -0,0 +1,21 @@
+-- Migration: Application-level encryption for PII columns
+-- Description: Widens email/phone to hold ciphertext, adds blind index columns
+-- for equality lookups and a table for wrapped per-tenant data keys.
+-- Existing rows stay plaintext until the backfill tool (backfill_pii) runs.
+
+-- 1. Ciphertext is longer than the plaintext it replaces
+ALTER TABLE users MODIFY COLUMN email VARCHAR(512) NULL;
+ALTER TABLE users MODIFY COLUMN phone VARCHAR(255) NULL;
+
+-- 2. Blind indexes (HMAC-SHA256 of the normalized value, base64url)
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_bidx VARCHAR(64) NULL;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS phone_bidx VARCHAR(64) NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_tenant_email_bidx ON users(tenant_id, email_bidx);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_tenant_phone_bidx ON users(tenant_id, phone_bidx);
+
+-- 3. Per-tenant data keys, wrapped by the master key
+CREATE TABLE IF NOT EXISTS tenant_data_keys (
+    tenant_id CHAR(36) PRIMARY KEY,
+    wrapped_key VARBINARY(1024) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
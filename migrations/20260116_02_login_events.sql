@@ -0,0 +1,27 @@
+-- Migration: Login history with geo-enrichment
+-- Description: One row per login attempt. Geo columns are filled in
+-- asynchronously by the enrichment worker after the row is written.
+
+CREATE TABLE IF NOT EXISTS login_events (
+    id CHAR(36) PRIMARY KEY,
+    user_id CHAR(36) NOT NULL,
+    tenant_id CHAR(36) NOT NULL,
+    ip_address VARCHAR(45) NULL,
+    user_agent VARCHAR(512) NULL,
+    success BOOLEAN NOT NULL,
+
+    -- Geo-enrichment (NULL until resolved)
+    country_code CHAR(2) NULL,
+    country VARCHAR(100) NULL,
+    city VARCHAR(100) NULL,
+    latitude DOUBLE NULL,
+    longitude DOUBLE NULL,
+    asn INT UNSIGNED NULL,
+    as_org VARCHAR(255) NULL,
+
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    INDEX idx_login_events_user_created (user_id, created_at DESC),
+    INDEX idx_login_events_tenant_created (tenant_id, created_at DESC),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,15 @@
+-- Migration: Single-use token store
+-- Description: Database fallback for redeem-once tokens (authorization codes,
+-- magic links, logout tokens) when Redis is unavailable. A NULL payload marks
+-- a redeemed token; the row is kept until expiry so replays are detectable.
+
+CREATE TABLE IF NOT EXISTS single_use_tokens (
+    -- SHA-256 of the namespaced key; the token itself is never stored
+    token_key CHAR(64) PRIMARY KEY,
+    payload TEXT NULL,
+    expires_at TIMESTAMP(3) NOT NULL,
+    consumed_at TIMESTAMP(3) NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    INDEX idx_single_use_tokens_expires (expires_at)
+);
@@ -0,0 +1,9 @@
+-- Migration: Fencing tokens for MySQL-backed distributed locks
+-- Description: One counter per lock name, bumped on every acquisition so
+-- each holder gets a token greater than all previous holders'.
+
+CREATE TABLE IF NOT EXISTS distributed_lock_fences (
+    name VARCHAR(64) PRIMARY KEY,
+    token BIGINT UNSIGNED NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP NOT NULL
+);
@@ -0,0 +1,33 @@
+-- Migration: Service accounts with key-pair authentication
+-- Description: Non-human principals that obtain tokens with the RFC 7523
+-- JWT bearer grant. Only public keys are stored; callers hold the private keys.
+
+CREATE TABLE IF NOT EXISTS service_accounts (
+    id CHAR(36) PRIMARY KEY,
+    tenant_id CHAR(36) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    description VARCHAR(1024) NULL,
+    scopes JSON NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'active',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP NOT NULL,
+
+    INDEX idx_service_accounts_tenant (tenant_id)
+);
+
+CREATE TABLE IF NOT EXISTS service_account_keys (
+    -- Also the `kid` callers put in the assertion header
+    id CHAR(36) PRIMARY KEY,
+    service_account_id CHAR(36) NOT NULL,
+    algorithm VARCHAR(10) NOT NULL,
+    public_key_pem TEXT NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_used_at TIMESTAMP NULL,
+    revoked_at TIMESTAMP NULL,
+    reminder_sent_at TIMESTAMP NULL,
+
+    INDEX idx_service_account_keys_account (service_account_id),
+    INDEX idx_service_account_keys_expiry (expires_at),
+    FOREIGN KEY (service_account_id) REFERENCES service_accounts(id) ON DELETE CASCADE
+);
@@ -0,0 +1,34 @@
+-- Migration: Device certificate enrollment
+-- Description: Single-use bootstrap tokens and the X.509 client certificates
+-- issued to devices from each tenant's intermediate CA.
+
+CREATE TABLE IF NOT EXISTS device_bootstrap_tokens (
+    id CHAR(36) PRIMARY KEY,
+    tenant_id CHAR(36) NOT NULL,
+    device_id VARCHAR(128) NOT NULL,
+    -- SHA-256 of the token, hex encoded
+    token_hash CHAR(64) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    UNIQUE KEY uk_device_bootstrap_tokens_hash (token_hash),
+    INDEX idx_device_bootstrap_tokens_tenant (tenant_id)
+);
+
+CREATE TABLE IF NOT EXISTS device_certificates (
+    -- Hex certificate serial
+    serial VARCHAR(40) PRIMARY KEY,
+    tenant_id CHAR(36) NOT NULL,
+    device_id VARCHAR(128) NOT NULL,
+    certificate_pem TEXT NOT NULL,
+    not_before TIMESTAMP NOT NULL,
+    not_after TIMESTAMP NOT NULL,
+    renewed_from VARCHAR(40) NULL,
+    revoked_at TIMESTAMP NULL,
+    revocation_reason VARCHAR(255) NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    INDEX idx_device_certificates_tenant (tenant_id, device_id),
+    INDEX idx_device_certificates_revoked (tenant_id, revoked_at)
+);
@@ -0,0 +1,37 @@
+-- Migration: Push MFA
+-- Description: Authenticator app installations and the approve/deny
+-- challenges pushed to them.
+
+CREATE TABLE IF NOT EXISTS push_devices (
+    id CHAR(36) PRIMARY KEY,
+    user_id CHAR(36) NOT NULL,
+    tenant_id CHAR(36) NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    -- 'fcm' or 'apns'
+    platform VARCHAR(16) NOT NULL,
+    push_token TEXT NOT NULL,
+    public_key_pem TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_used_at TIMESTAMP NULL,
+    revoked_at TIMESTAMP NULL,
+
+    INDEX idx_push_devices_user (user_id, tenant_id, revoked_at)
+);
+
+CREATE TABLE IF NOT EXISTS push_challenges (
+    id CHAR(36) PRIMARY KEY,
+    user_id CHAR(36) NOT NULL,
+    tenant_id CHAR(36) NOT NULL,
+    nonce CHAR(32) NOT NULL,
+    -- 'pending', 'approved' or 'denied'; expiry is derived from expires_at
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    ip_address VARCHAR(45) NULL,
+    user_agent VARCHAR(512) NULL,
+    responded_by CHAR(36) NULL,
+    expires_at TIMESTAMP NOT NULL,
+    responded_at TIMESTAMP NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    INDEX idx_push_challenges_user (user_id, created_at),
+    INDEX idx_push_challenges_expires (expires_at)
+);
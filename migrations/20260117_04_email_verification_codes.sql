@@ -0,0 +1,6 @@
+-- Migration: Email verification codes
+-- Description: A second proof on email verification sessions. The session
+-- accepts either the magic link token (otp_hash) or a typed 6-digit code
+-- (code_hash); whichever is used first completes it.
+
+ALTER TABLE otp_sessions ADD COLUMN code_hash VARCHAR(255) NULL AFTER otp_hash;
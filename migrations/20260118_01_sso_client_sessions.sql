@@ -0,0 +1,24 @@
+-- Migration: Central SSO client sessions
+-- Description: Per-client sessions derived from the IdP SSO session (the
+-- session index used for logout), and the logout endpoints clients register.
+
+CREATE TABLE IF NOT EXISTS sso_client_sessions (
+    sid VARCHAR(64) PRIMARY KEY,
+    session_id CHAR(36) NOT NULL,
+    client_id VARCHAR(255) NOT NULL,
+    user_id CHAR(36) NOT NULL,
+    tenant_id CHAR(36) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_authenticated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    ended_at TIMESTAMP NULL,
+
+    UNIQUE KEY uq_sso_client_session (session_id, client_id),
+    INDEX idx_sso_client_sessions_user (user_id, ended_at)
+);
+
+ALTER TABLE oauth_clients
+    ADD COLUMN frontchannel_logout_uri VARCHAR(2048) NULL,
+    ADD COLUMN frontchannel_logout_session_required BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN backchannel_logout_uri VARCHAR(2048) NULL,
+    -- JSON array
+    ADD COLUMN post_logout_redirect_uris TEXT NULL;
@@ -0,0 +1,8 @@
+-- Migration: Bind refresh tokens to sessions
+-- Description: The session a refresh token was issued under and the access
+-- token issued alongside it, so revoking the session reaches both.
+
+ALTER TABLE refresh_tokens
+    ADD COLUMN session_id CHAR(36) NULL,
+    ADD COLUMN access_token_jti CHAR(36) NULL,
+    ADD INDEX idx_rt_session (session_id);
@@ -0,0 +1,13 @@
+-- Migration: SMS usage per tenant and day
+-- Description: Counts behind per-tenant SMS budgets and the admin usage
+-- report. Monthly usage is the sum of the month's days.
+
+CREATE TABLE IF NOT EXISTS sms_usage_daily (
+    tenant_id CHAR(36) NOT NULL,
+    day DATE NOT NULL,
+    sent INT UNSIGNED NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (tenant_id, day),
+    INDEX idx_sms_usage_day (day)
+);
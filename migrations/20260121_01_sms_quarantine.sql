@@ -0,0 +1,20 @@
+-- Migration: SMS destination quarantine
+-- Description: Numbers held for manual review by toll fraud screening, and
+-- the review decision. One entry per tenant and number; SMS without a
+-- tenant uses the nil id.
+
+CREATE TABLE IF NOT EXISTS sms_quarantine (
+    id CHAR(36) PRIMARY KEY,
+    tenant_id CHAR(36) NOT NULL,
+    destination VARCHAR(32) NOT NULL,
+    score INT UNSIGNED NOT NULL,
+    reasons TEXT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    attempts INT UNSIGNED NOT NULL DEFAULT 1,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    reviewed_by VARCHAR(255) NULL,
+    reviewed_at TIMESTAMP NULL,
+
+    UNIQUE KEY uk_sms_quarantine_destination (tenant_id, destination),
+    INDEX idx_sms_quarantine_status (status, created_at)
+);
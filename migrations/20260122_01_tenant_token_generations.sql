@@ -0,0 +1,12 @@
+-- Migration: Tenant token generations
+-- Description: Bumped when a tenant is forced to reauthenticate. Access
+-- tokens carry the generation they were issued under; older ones are
+-- rejected. Tenants without a row are at generation 0.
+
+CREATE TABLE IF NOT EXISTS tenant_token_generations (
+    tenant_id CHAR(36) NOT NULL,
+    generation BIGINT UNSIGNED NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (tenant_id)
+);
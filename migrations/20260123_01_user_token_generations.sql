@@ -0,0 +1,13 @@
+-- Migration: User token generations
+-- Description: Bumped when a user's password or roles change. Access tokens
+-- carry the generation they were issued under; older ones are rejected.
+-- Users without a row are at generation 0.
+
+CREATE TABLE IF NOT EXISTS user_token_generations (
+    user_id CHAR(36) NOT NULL,
+    generation BIGINT UNSIGNED NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (user_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,6 @@
+-- Migration: Tenant data region
+-- Description: The region whose database holds the tenant's rows. Tenants
+-- without one are not pinned and live in the primary database.
+
+ALTER TABLE tenants ADD COLUMN data_region VARCHAR(32) NULL;
+CREATE INDEX idx_tenants_data_region ON tenants (data_region);
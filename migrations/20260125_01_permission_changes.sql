@@ -0,0 +1,23 @@
+-- Migration: Permission change feed
+-- Description: Per-tenant log of role and role assignment changes, read by
+-- downstream services to keep their permission replicas current. Cursors
+-- are allocated from permission_change_heads in the inserting transaction,
+-- so entries become visible in cursor order.
+
+CREATE TABLE IF NOT EXISTS permission_change_heads (
+    tenant_id CHAR(36) NOT NULL,
+    seq BIGINT UNSIGNED NOT NULL DEFAULT 0,
+
+    PRIMARY KEY (tenant_id)
+);
+
+CREATE TABLE IF NOT EXISTS permission_changes (
+    tenant_id CHAR(36) NOT NULL,
+    seq BIGINT UNSIGNED NOT NULL,
+    subject_type VARCHAR(16) NOT NULL,
+    subject_id CHAR(36) NOT NULL,
+    recorded_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (tenant_id, seq),
+    INDEX idx_permission_changes_recorded (recorded_at)
+);
@@ -0,0 +1,14 @@
+-- Migration: Password expiry reminders
+-- Description: Reminders sent as a password nears its maximum age, one row
+-- per password and threshold so that each is sent once. Keyed by when the
+-- password was set, so a new password starts a new round.
+
+CREATE TABLE IF NOT EXISTS password_expiry_reminders (
+    user_id CHAR(36) NOT NULL,
+    password_set_at TIMESTAMP NOT NULL,
+    threshold_days INT UNSIGNED NOT NULL,
+    sent_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (user_id, password_set_at, threshold_days),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,18 @@
+-- Migration: One-time login links
+-- Description: Sign-in links that support staff had emailed to users. The
+-- link token is kept in the nonce store only; these rows are the audit
+-- trail and back the per-user issuance limit.
+
+CREATE TABLE IF NOT EXISTS login_links (
+    id CHAR(36) PRIMARY KEY,
+    tenant_id CHAR(36) NOT NULL,
+    user_id CHAR(36) NOT NULL,
+    issued_by CHAR(36) NOT NULL,
+    redirect_uri VARCHAR(2048) NOT NULL,
+    reason VARCHAR(512) NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    redeemed_at TIMESTAMP NULL,
+
+    INDEX idx_login_links_user (tenant_id, user_id, created_at)
+);
@@ -0,0 +1,18 @@
+-- Migration: Analytics exports for the SOC
+-- Description: One row per completed export of a dataset (sessions or
+-- login_events). The manifest holds the files written and the watermark
+-- the next export of the dataset starts after.
+
+CREATE TABLE IF NOT EXISTS analytics_exports (
+    id CHAR(36) PRIMARY KEY,
+    dataset VARCHAR(32) NOT NULL,
+    completed_at TIMESTAMP NOT NULL,
+    row_count BIGINT UNSIGNED NOT NULL,
+    manifest JSON NOT NULL,
+
+    INDEX idx_analytics_exports_dataset (dataset, completed_at)
+);
+
+-- Exports read across tenants in creation order
+CREATE INDEX idx_login_events_created ON login_events (created_at, id);
+CREATE INDEX idx_sessions_created ON sessions (created_at, id);
@@ -0,0 +1,8 @@
+-- Migration: Absolute lifetime of refresh token families
+-- Description: Rotation issues each token for the idle lifetime again, but
+-- never past the family's absolute expiry, which is set when the family is
+-- started and copied to every token rotated from it. NULL for tokens
+-- issued before this column existed.
+
+ALTER TABLE refresh_tokens
+    ADD COLUMN family_expires_at TIMESTAMP NULL;
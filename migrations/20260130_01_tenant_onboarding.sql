@@ -0,0 +1,14 @@
+-- Migration: Tenant onboarding progress
+-- Description: The guided setup of each tenant as a saved workflow context.
+-- `version` is the context's version; a write succeeds only if the stored
+-- version is the one the writer read. Finishing copies the settings to
+-- `tenants`, so the row is kept only as the record of the setup.
+
+CREATE TABLE IF NOT EXISTS tenant_onboarding (
+    tenant_id CHAR(36) PRIMARY KEY,
+    state JSON NOT NULL,
+    version BIGINT UNSIGNED NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
@@ -0,0 +1,11 @@
+-- Migration: Feature kill switches
+-- Description: A row per feature whose canary cohort has been reverted to
+-- the behaviour everyone else has. Replicas poll the table; deleting the
+-- row turns the feature back on for its cohort.
+
+CREATE TABLE IF NOT EXISTS feature_kill_switches (
+    feature VARCHAR(128) PRIMARY KEY,
+    reason VARCHAR(512) NULL,
+    killed_by VARCHAR(255) NULL,
+    killed_at TIMESTAMP NOT NULL
+);
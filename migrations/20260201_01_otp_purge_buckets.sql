@@ -0,0 +1,14 @@
+-- Migration: OTP purge buckets
+-- Description: `purge_after` is when a session stops being usable: when it
+-- was verified, or when it expires. The purge deletes by ranges of it, oldest
+-- first, so each batch walks one index range instead of scanning for
+-- `expires_at < ? OR verified_at IS NOT NULL`.
+
+ALTER TABLE otp_sessions
+    ADD COLUMN purge_after TIMESTAMP
+        AS (LEAST(expires_at, COALESCE(verified_at, expires_at))) STORED NOT NULL;
+
+CREATE INDEX idx_otp_purge_after ON otp_sessions(purge_after);
+
+-- Superseded by idx_otp_purge_after
+DROP INDEX idx_otp_cleanup ON otp_sessions;
@@ -0,0 +1,11 @@
+-- Migration: Online migrations
+-- Description: The phase of each schema change that runs while the old and
+-- new layouts are both in use. Replicas poll the table, so a cutover (or a
+-- step back from one) reaches all of them without a deploy.
+
+CREATE TABLE IF NOT EXISTS online_migrations (
+    name VARCHAR(128) PRIMARY KEY,
+    phase VARCHAR(32) NOT NULL,
+    changed_by VARCHAR(255) NULL,
+    changed_at TIMESTAMP NOT NULL
+);
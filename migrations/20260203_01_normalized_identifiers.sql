@@ -0,0 +1,12 @@
+-- Migration: Normalized identifiers
+-- Description: Canonical form of each user's email (trimmed, NFC, lowercased,
+-- and dot/plus-folded where the tenant's policy says so), or its blind index
+-- under PII encryption. Unique per tenant, so two spellings of one address
+-- cannot both register. Null until written by the application or the
+-- backfill_identifiers tool; the identifier_normalization online migration
+-- decides when lookups move to it.
+
+ALTER TABLE users ADD COLUMN normalized_identifier VARCHAR(320) NULL AFTER email_bidx;
+
+CREATE UNIQUE INDEX idx_users_tenant_normalized_identifier
+    ON users(tenant_id, normalized_identifier);
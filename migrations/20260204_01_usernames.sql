@@ -0,0 +1,29 @@
+-- Migration: Usernames
+-- Description: Usernames as a third sign-in identifier, alongside email and
+-- phone. `username` is kept as the user typed it; `username_normalized` is
+-- the tenant's canonical form (NFC, and lowercased unless the tenant's
+-- username policy is case-sensitive) and is unique per tenant. Usernames are
+-- not encrypted under PII protection: they are chosen handles, not contact
+-- details.
+
+ALTER TABLE users
+    ADD COLUMN username VARCHAR(64) NULL AFTER phone_verified_at,
+    ADD COLUMN username_normalized VARCHAR(64) NULL AFTER username;
+
+ALTER TABLE users
+    DROP CHECK chk_identifier_type,
+    DROP CHECK chk_primary_identifier,
+    DROP CHECK chk_has_identifier;
+
+ALTER TABLE users
+    ADD CONSTRAINT chk_identifier_type
+        CHECK (identifier_type IN ('email', 'phone', 'both', 'username')),
+    ADD CONSTRAINT chk_primary_identifier
+        CHECK (primary_identifier IN ('email', 'phone', 'username')),
+    ADD CONSTRAINT chk_has_identifier
+        CHECK (email IS NOT NULL OR phone IS NOT NULL OR username IS NOT NULL),
+    ADD CONSTRAINT chk_username_normalized
+        CHECK ((username IS NULL) = (username_normalized IS NULL));
+
+CREATE UNIQUE INDEX idx_users_tenant_username
+    ON users(tenant_id, username_normalized);
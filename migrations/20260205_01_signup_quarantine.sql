@@ -0,0 +1,22 @@
+-- Migration: Signup quarantine
+-- Description: Accounts shadow-created by signup velocity rules, held
+-- suspended for manual review, and the review decision.
+
+CREATE TABLE IF NOT EXISTS signup_quarantine (
+    id CHAR(36) PRIMARY KEY,
+    tenant_id CHAR(36) NOT NULL,
+    user_id CHAR(36) NOT NULL,
+    ip_address VARCHAR(45) NULL,
+    device_fingerprint VARCHAR(255) NULL,
+    email_domain VARCHAR(255) NULL,
+    reasons TEXT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    reviewed_by VARCHAR(255) NULL,
+    reviewed_at TIMESTAMP NULL,
+
+    INDEX idx_signup_quarantine_status (status, created_at),
+    INDEX idx_signup_quarantine_tenant (tenant_id, created_at),
+    INDEX idx_signup_quarantine_user (user_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,18 @@
+-- Migration: Legal holds
+-- Description: Users and tenants whose data is preserved for litigation.
+-- A NULL user_id holds the whole tenant. Rows are released, never deleted,
+-- so who placed and who released each hold stays on record.
+
+CREATE TABLE IF NOT EXISTS legal_holds (
+    id CHAR(36) PRIMARY KEY,
+    tenant_id CHAR(36) NOT NULL,
+    user_id CHAR(36) NULL,
+    reason VARCHAR(500) NOT NULL,
+    placed_by VARCHAR(255) NOT NULL,
+    placed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    released_by VARCHAR(255) NULL,
+    released_at TIMESTAMP NULL,
+
+    INDEX idx_legal_holds_active (tenant_id, released_at, user_id),
+    INDEX idx_legal_holds_placed (placed_at)
+);
@@ -0,0 +1,9 @@
+-- Migration: Bind refresh tokens to OAuth clients
+-- Description: The client a refresh token family was issued to. Only that
+-- client may rotate it, and all of a client's tokens can be revoked at once
+-- when its secret leaks. NULL for first-party sign-in and for tokens issued
+-- before this column existed.
+
+ALTER TABLE refresh_tokens
+    ADD COLUMN client_id VARCHAR(255) NULL,
+    ADD INDEX idx_rt_tenant_client (tenant_id, client_id);
@@ -0,0 +1,11 @@
+-- Migration: Dynamic client registration
+-- Description: Clients registered through /oauth/register. client_secret
+-- now holds the SHA-256 of the secret and is NULL for public clients,
+-- which authenticate with token_endpoint_auth_method 'none'.
+-- registration_token_hash is the SHA-256 of the RFC 7592 registration
+-- access token that manages the registration.
+
+ALTER TABLE oauth_clients
+    MODIFY client_secret VARCHAR(255) NULL,
+    ADD COLUMN token_endpoint_auth_method VARCHAR(32) NOT NULL DEFAULT 'client_secret_basic',
+    ADD COLUMN registration_token_hash VARCHAR(64) NULL;
@@ -0,0 +1,23 @@
+-- Migration: Login heatmap rollup
+-- Description: Successful and failed sign-ins per UTC hour, tenant,
+-- country and ASN, counted from login_events by the heatmap rollup job.
+-- An unresolved country is stored as '' and an unresolved ASN as 0, so
+-- they can be part of the key. login_heatmap_hours records each hour the
+-- job has counted, including hours without sign-ins.
+
+CREATE TABLE IF NOT EXISTS login_heatmap_hourly (
+    hour TIMESTAMP NOT NULL,
+    tenant_id CHAR(36) NOT NULL,
+    country_code CHAR(2) NOT NULL DEFAULT '',
+    asn INT UNSIGNED NOT NULL DEFAULT 0,
+    successes INT UNSIGNED NOT NULL DEFAULT 0,
+    failures INT UNSIGNED NOT NULL DEFAULT 0,
+
+    PRIMARY KEY (hour, tenant_id, country_code, asn),
+    INDEX idx_login_heatmap_tenant_hour (tenant_id, hour)
+);
+
+CREATE TABLE IF NOT EXISTS login_heatmap_hours (
+    hour TIMESTAMP PRIMARY KEY,
+    rolled_up_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,7 @@
+-- Migration: Tenant policy version
+-- Description: Bumped on every change to a tenant's status or settings.
+-- Instances cache tenant state for token validation and drop entries
+-- older than the version announced with the change.
+
+ALTER TABLE tenants
+ADD COLUMN policy_version BIGINT UNSIGNED NOT NULL DEFAULT 0;
@@ -0,0 +1,35 @@
+-- Migration: Remember-me devices
+-- Description: Devices users chose to stay signed in on, and the chain of
+-- single-use tokens each device signs in with. Only SHA-256 hashes of the
+-- device fingerprint and of the tokens are stored.
+
+CREATE TABLE IF NOT EXISTS remembered_devices (
+    id CHAR(36) PRIMARY KEY,
+    tenant_id CHAR(36) NOT NULL,
+    user_id CHAR(36) NOT NULL,
+    -- SHA-256 of the device fingerprint, hex encoded
+    fingerprint_hash CHAR(64) NOT NULL,
+    name VARCHAR(255) NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_used_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP NULL,
+
+    INDEX idx_remembered_devices_user (tenant_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS remember_me_tokens (
+    id CHAR(36) PRIMARY KEY,
+    device_id CHAR(36) NOT NULL,
+    tenant_id CHAR(36) NOT NULL,
+    user_id CHAR(36) NOT NULL,
+    -- SHA-256 of the token, hex encoded
+    token_hash CHAR(64) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP NULL,
+
+    UNIQUE KEY uk_remember_me_tokens_hash (token_hash),
+    INDEX idx_remember_me_tokens_device (device_id),
+    CONSTRAINT fk_remember_me_tokens_device FOREIGN KEY (device_id)
+        REFERENCES remembered_devices(id) ON DELETE CASCADE
+);
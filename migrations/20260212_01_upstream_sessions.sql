@@ -0,0 +1,21 @@
+-- Migration: Upstream identity provider sessions
+-- Description: Sessions users hold at the identity providers they signed in
+-- through, so local logout can end them there too. Rows are deleted when the
+-- SSO session they belong to is logged out.
+
+CREATE TABLE IF NOT EXISTS upstream_sessions (
+    id CHAR(36) PRIMARY KEY,
+    session_id CHAR(36) NOT NULL,
+    tenant_id CHAR(36) NOT NULL,
+    user_id CHAR(36) NOT NULL,
+    -- Key of the provider in security.sso.upstream_idps
+    provider VARCHAR(64) NOT NULL,
+    -- OIDC: ID token sent back as id_token_hint
+    id_token TEXT NULL,
+    -- SAML: NameID and SessionIndex of the assertion
+    name_id VARCHAR(512) NULL,
+    session_index VARCHAR(255) NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    INDEX idx_upstream_sessions_session (tenant_id, session_id)
+);
@@ -0,0 +1,57 @@
+//! Data Anonymizer
+//!
+//! Rewrites emails, phones, names and IP addresses in a database restored
+//! from production so it can be used in staging. Output is deterministic for
+//! a given ANONYMIZE_SECRET, so repeated refreshes produce the same identities.
+//!
+//! Refuses to run when AUTH__ENVIRONMENT is "production". Users and tenants
+//! under a legal hold are skipped.
+
+use auth_config::{ConfigLoader, ConfigManager};
+use auth_db::anonymize::{AnonymizationJob, Anonymizer};
+use secrecy::ExposeSecret;
+use sqlx::mysql::MySqlPoolOptions;
+
+const BATCH_SIZE: u32 = 500;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("🕶  Starting data anonymization...");
+
+    // 1. Guard against pointing this at production
+    let environment =
+        std::env::var("AUTH__ENVIRONMENT").unwrap_or_else(|_| "development".to_string());
+    if environment == "production" {
+        return Err("refusing to anonymize a production environment".into());
+    }
+
+    let secret = std::env::var("ANONYMIZE_SECRET")
+        .map_err(|_| "ANONYMIZE_SECRET must be set (it keeps output stable across runs)")?;
+
+    // 2. Load Config
+    let config_loader = ConfigLoader::new("config", &environment);
+    let config_manager = ConfigManager::new(config_loader)?;
+    let config = config_manager.get_config();
+
+    // 3. Connect DB
+    let pool = MySqlPoolOptions::new()
+        .max_connections(5)
+        .connect(config.database.mysql_url.expose_secret())
+        .await?;
+
+    // 4. Rewrite
+    let job = AnonymizationJob::new(pool, Anonymizer::new(secret.as_bytes()), BATCH_SIZE);
+    let report = job.run().await?;
+
+    println!(
+        "✅ Anonymization complete: {} users, {} IP addresses rewritten",
+        report.users, report.ip_addresses
+    );
+    if report.legal_holds > 0 {
+        println!(
+            "⚖️  {} legal holds in place; their users were left untouched",
+            report.legal_holds
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Normalized Identifier Backfill Tool
+//!
+//! Fills in `users.normalized_identifier` for users created before the
+//! `identifier_normalization` online migration reached `double_write`. Run it
+//! once the migration is in `double_write`, and before `shadow_read`. Safe to
+//! re-run: only rows without a value are touched.
+//!
+//! Users whose canonical email another user of the same tenant already has
+//! are listed at the end; they stay without a value until one of the two
+//! accounts is merged or renamed.
+
+use auth_config::{ConfigLoader, ConfigManager};
+use auth_core::services::identifier::IdentifierNormalizer;
+use auth_db::online_migration::OnlineMigrations;
+use auth_db::repositories::{
+    pii_protector_from_config,
+    user_repository::{UserRepository, IDENTIFIER_NORMALIZATION},
+};
+use secrecy::ExposeSecret;
+use sqlx::mysql::MySqlPoolOptions;
+use std::sync::Arc;
+
+const BATCH_SIZE: u32 = 500;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("🔤 Starting normalized identifier backfill...");
+
+    // 1. Load Config
+    let environment =
+        std::env::var("AUTH__ENVIRONMENT").unwrap_or_else(|_| "development".to_string());
+    let config_loader = ConfigLoader::new("config", &environment);
+    let config_manager = ConfigManager::new(config_loader)?;
+    let config = config_manager.get_config();
+
+    // 2. Connect DB
+    let pool = MySqlPoolOptions::new()
+        .max_connections(5)
+        .connect(config.database.mysql_url.expose_secret())
+        .await?;
+
+    let migrations = Arc::new(OnlineMigrations::new(
+        pool.clone(),
+        &config.database.online_migrations,
+    ));
+    migrations.refresh().await?;
+    let phase = migrations.phase(IDENTIFIER_NORMALIZATION);
+    if !phase.writes_target() {
+        println!(
+            "The {} migration is in {}; move it to double_write first, or new users \
+             will be created without a value.",
+            IDENTIFIER_NORMALIZATION,
+            phase.as_str()
+        );
+        return Ok(());
+    }
+
+    let mut users = UserRepository::new(pool.clone()).with_identifier_normalization(
+        Arc::new(IdentifierNormalizer::new(&config.security.identifiers)),
+        migrations.migration(IDENTIFIER_NORMALIZATION),
+    );
+    if let Some(pii) = pii_protector_from_config(pool, &config.security.pii)? {
+        users = users.with_pii_protection(pii);
+    }
+
+    // 3. Walk every user once, in id order
+    let (mut total, mut conflicts, mut after) = (0, Vec::new(), String::new());
+    loop {
+        let batch = users.backfill_normalized_batch(&after, BATCH_SIZE).await?;
+        total += batch.updated;
+        conflicts.extend(batch.conflicts);
+        let Some(last_id) = batch.last_id else {
+            break;
+        };
+        after = last_id;
+        println!("  ... {} users normalized", total);
+    }
+
+    println!(
+        "✅ Normalized identifier backfill complete: {} users",
+        total
+    );
+    if !conflicts.is_empty() {
+        println!(
+            "⚠️  {} users share a canonical email with another user of their tenant:",
+            conflicts.len()
+        );
+        for id in conflicts {
+            println!("  {}", id);
+        }
+    }
+    Ok(())
+}
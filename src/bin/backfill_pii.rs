@@ -0,0 +1,50 @@
+//! PII Backfill Tool
+//!
+//! Encrypts email/phone columns written before PII encryption was enabled and
+//! fills in their blind indexes. Safe to re-run: only rows without an index
+//! are touched, and it can run while the platform is serving traffic.
+
+use auth_config::{ConfigLoader, ConfigManager};
+use auth_db::repositories::{pii_protector_from_config, user_repository::UserRepository};
+use secrecy::ExposeSecret;
+use sqlx::mysql::MySqlPoolOptions;
+
+const BATCH_SIZE: u32 = 500;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("🔐 Starting PII backfill...");
+
+    // 1. Load Config
+    let environment =
+        std::env::var("AUTH__ENVIRONMENT").unwrap_or_else(|_| "development".to_string());
+    let config_loader = ConfigLoader::new("config", &environment);
+    let config_manager = ConfigManager::new(config_loader)?;
+    let config = config_manager.get_config();
+
+    // 2. Connect DB
+    let pool = MySqlPoolOptions::new()
+        .max_connections(5)
+        .connect(config.database.mysql_url.expose_secret())
+        .await?;
+
+    let Some(pii) = pii_protector_from_config(pool.clone(), &config.security.pii)? else {
+        println!("PII encryption is disabled (security.pii.enabled = false); nothing to do.");
+        return Ok(());
+    };
+    let users = UserRepository::new(pool).with_pii_protection(pii);
+
+    // 3. Migrate in batches until no plaintext rows remain
+    let mut total = 0;
+    loop {
+        let migrated = users.backfill_pii_batch(BATCH_SIZE).await?;
+        if migrated == 0 {
+            break;
+        }
+        total += migrated;
+        println!("  ... {} users migrated", total);
+    }
+
+    println!("✅ PII backfill complete: {} users encrypted", total);
+    Ok(())
+}
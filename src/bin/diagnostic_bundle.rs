@@ -0,0 +1,52 @@
+//! Diagnostic Bundle
+//!
+//! Downloads the diagnostic bundle of a running instance from its admin
+//! listener, for attaching to a support ticket. The instance is found from
+//! `server.admin` in the configuration of AUTH__ENVIRONMENT, or given as
+//! DIAGNOSTICS_ADMIN_URL (e.g. `http://127.0.0.1:9090`).
+//!
+//! Writes `diagnostics-<timestamp>.json`, or the path passed as the first
+//! argument.
+
+use auth_config::{ConfigLoader, ConfigManager};
+use auth_platform::HttpClient;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let admin_url = match std::env::var("DIAGNOSTICS_ADMIN_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            let environment =
+                std::env::var("AUTH__ENVIRONMENT").unwrap_or_else(|_| "development".to_string());
+            let config =
+                ConfigManager::new(ConfigLoader::new("config", &environment))?.get_config();
+            let admin = config
+                .server
+                .admin
+                .ok_or("server.admin is not configured; set DIAGNOSTICS_ADMIN_URL")?;
+            format!("http://{}:{}", admin.host, admin.port)
+        }
+    };
+
+    let client = HttpClient::default();
+    let url = format!("{}/admin/diagnostics", admin_url.trim_end_matches('/'));
+    let response = client.send(client.get(&url)).await?;
+    if !response.status().is_success() {
+        return Err(format!("{} answered {}", url, response.status()).into());
+    }
+    let bundle = response.bytes().await?;
+
+    let path = std::env::args().nth(1).unwrap_or_else(|| {
+        format!(
+            "diagnostics-{}.json",
+            chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+        )
+    });
+    std::fs::write(&path, &bundle)?;
+    println!(
+        "📦 Diagnostic bundle written to {} ({} bytes)",
+        path,
+        bundle.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,199 @@
+//! Key Ceremony Tool
+//!
+//! Generates a JWT signing key with an escrow copy split between custodians,
+//! recovers it from their shares, and checks a live JWKS against the
+//! ceremony record.
+//!
+//!   key_ceremony generate --kid <kid> --custodian <name> ... --threshold <n>
+//!                         [--bits 3072] --out <dir>
+//!   key_ceremony recover --record <record.json> --share <file> ... --out <key.pem>
+//!   key_ceremony verify-jwks --record <record.json> --jwks <url or file>
+//!
+//! `generate` writes the ceremony record, the public key, the live private
+//! key and one share file per custodian. Hand each share to its custodian
+//! and move the private key into the key store before the output directory
+//! leaves the ceremony room.
+
+use auth_crypto::ceremony::{
+    import_key, recover_key, run_ceremony, verify_jwks, CeremonyRecord, EscrowShare,
+};
+use std::path::{Path, PathBuf};
+
+type CliResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+const USAGE: &str = "usage: key_ceremony <generate|recover|verify-jwks> [options]";
+
+/// `--name value` options; repeated options keep every value
+struct Options(Vec<(String, String)>);
+
+impl Options {
+    fn parse(args: &[String]) -> CliResult<Self> {
+        let mut options = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument {}", arg))?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("--{} needs a value", name))?;
+            options.push((name.to_string(), value.clone()));
+        }
+        Ok(Self(options))
+    }
+
+    fn all(&self, name: &str) -> Vec<String> {
+        self.0
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+            .collect()
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        self.all(name).pop()
+    }
+
+    fn required(&self, name: &str) -> CliResult<String> {
+        Ok(self
+            .get(name)
+            .ok_or_else(|| format!("--{} is required", name))?)
+    }
+}
+
+/// Create a file readable by its owner only
+fn write_private(path: &Path, contents: &str) -> CliResult<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+fn read_record(path: &str) -> CliResult<CeremonyRecord> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+fn generate(options: &Options) -> CliResult<()> {
+    let kid = options.required("kid")?;
+    let custodians = options.all("custodian");
+    let threshold: u8 = options.required("threshold")?.parse()?;
+    let bits: usize = options.get("bits").as_deref().unwrap_or("3072").parse()?;
+    let out = PathBuf::from(options.required("out")?);
+
+    println!(
+        "🔑 Generating {} ({} bits), escrow split {} of {}...",
+        kid,
+        bits,
+        threshold,
+        custodians.len()
+    );
+    let output = run_ceremony(&kid, &custodians, threshold, bits, chrono::Utc::now())?;
+
+    std::fs::create_dir_all(out.join("shares"))?;
+    write_private(
+        &out.join(format!("{}.ceremony.json", kid)),
+        &serde_json::to_string_pretty(&output.record)?,
+    )?;
+    write_private(
+        &out.join(format!("{}.public.pem", kid)),
+        &output.record.public_key_pem,
+    )?;
+    write_private(
+        &out.join(format!("{}.private.pem", kid)),
+        &output.private_key_pem,
+    )?;
+    for (custodian, share) in &output.shares {
+        write_private(
+            &out.join("shares").join(format!("{}.share", custodian)),
+            &format!("{}\n", share.encode()),
+        )?;
+    }
+
+    println!("  JWK thumbprint: {}", output.record.jwk_thumbprint);
+    for custodian in &output.record.custodians {
+        println!(
+            "  share {} -> {} (sha256 {})",
+            custodian.share_index, custodian.name, custodian.share_fingerprint
+        );
+    }
+    println!("✅ Ceremony output written to {}", out.display());
+    Ok(())
+}
+
+async fn recover(options: &Options) -> CliResult<()> {
+    let record = read_record(&options.required("record")?)?;
+    let out = PathBuf::from(options.required("out")?);
+    let shares = options
+        .all("share")
+        .iter()
+        .map(|path| Ok(EscrowShare::decode(&std::fs::read_to_string(path)?)?))
+        .collect::<CliResult<Vec<_>>>()?;
+
+    println!(
+        "🔐 Recovering {} from {} shares ({} needed)...",
+        record.kid,
+        shares.len(),
+        record.threshold
+    );
+    let private_key_pem = recover_key(&record, &shares)?;
+    import_key(&record, &private_key_pem).await?;
+    write_private(&out, &private_key_pem)?;
+    println!(
+        "✅ Key {} recovered and imported; written to {}",
+        record.kid,
+        out.display()
+    );
+    Ok(())
+}
+
+async fn verify(options: &Options) -> CliResult<()> {
+    let record = read_record(&options.required("record")?)?;
+    let source = options.required("jwks")?;
+    let jwks: serde_json::Value = if source.starts_with("https://") || source.starts_with("http://")
+    {
+        reqwest::get(&source)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?
+    } else {
+        serde_json::from_str(&std::fs::read_to_string(&source)?)?
+    };
+
+    verify_jwks(&record, &jwks)?;
+    println!(
+        "✅ {} publishes {} with thumbprint {}",
+        source, record.kid, record.jwk_thumbprint
+    );
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> CliResult<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, rest)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    let options = Options::parse(rest)?;
+
+    match command.as_str() {
+        "generate" => generate(&options),
+        "recover" => recover(&options).await,
+        "verify-jwks" => verify(&options).await,
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
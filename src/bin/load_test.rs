@@ -0,0 +1,262 @@
+//! Load Test
+//!
+//! Drives one hot path from many concurrent tasks with in-memory storage and
+//! reports latency percentiles. Configured from the environment:
+//!
+//! LOAD_SCENARIO      issue | validate | login (default: validate)
+//! LOAD_CONCURRENCY   concurrent tasks (default: 64)
+//! LOAD_REQUESTS      total operations (default: 10000)
+//! LOAD_MAX_P99_MS    exit non-zero when p99 exceeds this (optional)
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+    Argon2,
+};
+use async_trait::async_trait;
+use auth_core::audit::TracingAuditLogger;
+use auth_core::error::AuthError;
+use auth_core::models::{
+    Claims, CreateUserRequest, SensitiveString, UpdateUserRequest, User, UserStatus,
+};
+use auth_core::services::identity::{AuthRequest, IdentityService, UserStore};
+use auth_core::services::token_service::{TokenEngine, TokenProvider};
+use auth_crypto::JwtConfig;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const PASSWORD: &str = "correct-horse-battery-staple";
+
+#[derive(Clone, Copy)]
+enum Scenario {
+    Issue,
+    Validate,
+    Login,
+}
+
+/// Serves one user; writes are accepted and dropped
+struct SingleUserStore {
+    user: User,
+}
+
+#[async_trait]
+impl UserStore for SingleUserStore {
+    async fn find_by_email(
+        &self,
+        email: &str,
+        _tenant_id: Uuid,
+    ) -> Result<Option<User>, AuthError> {
+        Ok((self.user.email.as_deref() == Some(email)).then(|| self.user.clone()))
+    }
+    async fn find_by_phone(
+        &self,
+        _phone: &str,
+        _tenant_id: Uuid,
+    ) -> Result<Option<User>, AuthError> {
+        Ok(None)
+    }
+    async fn find_by_username(
+        &self,
+        _username: &str,
+        _tenant_id: Uuid,
+    ) -> Result<Option<User>, AuthError> {
+        Ok(None)
+    }
+    async fn find_by_identifier(
+        &self,
+        identifier: &str,
+        tenant_id: Uuid,
+    ) -> Result<Option<User>, AuthError> {
+        self.find_by_email(identifier, tenant_id).await
+    }
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, AuthError> {
+        Ok((self.user.id == id).then(|| self.user.clone()))
+    }
+    async fn create(
+        &self,
+        _user: CreateUserRequest,
+        _password_hash: String,
+        _tenant_id: Uuid,
+    ) -> Result<User, AuthError> {
+        Ok(self.user.clone())
+    }
+    async fn update_status(&self, _id: Uuid, _status: UserStatus) -> Result<(), AuthError> {
+        Ok(())
+    }
+    async fn increment_failed_attempts(&self, _id: Uuid) -> Result<u32, AuthError> {
+        Ok(1)
+    }
+    async fn reset_failed_attempts(&self, _id: Uuid) -> Result<(), AuthError> {
+        Ok(())
+    }
+    async fn record_login(&self, _id: Uuid, _ip: Option<String>) -> Result<(), AuthError> {
+        Ok(())
+    }
+    async fn update(&self, _user: UpdateUserRequest) -> Result<User, AuthError> {
+        Ok(self.user.clone())
+    }
+    async fn update_password_hash(&self, _id: Uuid, _hash: String) -> Result<(), AuthError> {
+        Ok(())
+    }
+    async fn set_email_verified(&self, _id: Uuid, _verified: bool) -> Result<(), AuthError> {
+        Ok(())
+    }
+    async fn set_phone_verified(&self, _id: Uuid, _verified: bool) -> Result<(), AuthError> {
+        Ok(())
+    }
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> Result<T, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| format!("{} has an invalid value: {}", name, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+fn claims(user_id: Uuid, tenant_id: Uuid) -> Claims {
+    let now = chrono::Utc::now().timestamp();
+    Claims {
+        sub: user_id.to_string(),
+        iss: "auth-service".to_string(),
+        aud: "auth-service".to_string(),
+        exp: now + 900,
+        iat: now,
+        nbf: now,
+        jti: Uuid::new_v4().to_string(),
+        tenant_id: tenant_id.to_string(),
+        permissions: vec![],
+        roles: vec![],
+        scope: None,
+        session_id: None,
+        client_id: None,
+        ext: Default::default(),
+    }
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = ((sorted.len() as f64 * p).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let scenario = match std::env::var("LOAD_SCENARIO").as_deref() {
+        Ok("issue") => Scenario::Issue,
+        Ok("validate") | Err(_) => Scenario::Validate,
+        Ok("login") => Scenario::Login,
+        Ok(other) => return Err(format!("unknown LOAD_SCENARIO {}", other).into()),
+    };
+    let concurrency: usize = env_or("LOAD_CONCURRENCY", 64)?;
+    let total: usize = env_or("LOAD_REQUESTS", 10_000)?;
+    let max_p99_ms: Option<u64> = std::env::var("LOAD_MAX_P99_MS")
+        .ok()
+        .map(|v| v.parse())
+        .transpose()?;
+
+    let tokens = Arc::new(TokenEngine::new_with_config(JwtConfig::default()).await?);
+    let salt = SaltString::generate(&mut OsRng);
+    let user = User {
+        email: Some("load@example.com".to_string()),
+        password_hash: Some(
+            Argon2::default()
+                .hash_password(PASSWORD.as_bytes(), &salt)
+                .map_err(|e| e.to_string())?
+                .to_string(),
+        ),
+        ..User::default()
+    };
+    let (user_id, tenant_id) = (user.id, user.tenant_id);
+    let identity = Arc::new(IdentityService::new(
+        Arc::new(SingleUserStore { user }),
+        tokens.clone(),
+        Arc::new(TracingAuditLogger),
+    ));
+    let token = Arc::new(
+        tokens
+            .issue_access_token(claims(user_id, tenant_id))
+            .await?
+            .token,
+    );
+
+    println!(
+        "🔥 Load test: {} operations, {} concurrent tasks",
+        total, concurrency
+    );
+
+    let next = Arc::new(AtomicUsize::new(0));
+    let errors = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+    let mut workers = Vec::with_capacity(concurrency);
+    for _ in 0..concurrency {
+        let (next, errors) = (next.clone(), errors.clone());
+        let (tokens, identity, token) = (tokens.clone(), identity.clone(), token.clone());
+        workers.push(tokio::spawn(async move {
+            let mut latencies = Vec::new();
+            while next.fetch_add(1, Ordering::Relaxed) < total {
+                let op_started = Instant::now();
+                let ok = match scenario {
+                    Scenario::Issue => tokens
+                        .issue_access_token(claims(user_id, tenant_id))
+                        .await
+                        .is_ok(),
+                    Scenario::Validate => tokens.validate_token(&token).await.is_ok(),
+                    Scenario::Login => identity
+                        .login(AuthRequest {
+                            email: "load@example.com".to_string(),
+                            password: SensitiveString::new(PASSWORD.to_string()),
+                            tenant_id,
+                            ip_address: None,
+                            user_agent: None,
+                            captcha_token: None,
+                        })
+                        .await
+                        .is_ok(),
+                };
+                latencies.push(op_started.elapsed());
+                if !ok {
+                    errors.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            latencies
+        }));
+    }
+
+    let mut latencies = Vec::with_capacity(total);
+    for worker in workers {
+        latencies.extend(worker.await?);
+    }
+    let elapsed = started.elapsed();
+    latencies.sort_unstable();
+    if latencies.is_empty() {
+        return Err("no operations ran".into());
+    }
+
+    let p99 = percentile(&latencies, 0.99);
+    println!(
+        "   throughput: {:.0} ops/s over {:.2?}",
+        latencies.len() as f64 / elapsed.as_secs_f64(),
+        elapsed
+    );
+    println!(
+        "   p50 {:.2?}  p95 {:.2?}  p99 {:.2?}  max {:.2?}",
+        percentile(&latencies, 0.50),
+        percentile(&latencies, 0.95),
+        p99,
+        latencies[latencies.len() - 1]
+    );
+    println!("   errors: {}", errors.load(Ordering::Relaxed));
+
+    if errors.load(Ordering::Relaxed) > 0 {
+        return Err("operations failed during the run".into());
+    }
+    if let Some(max) = max_p99_ms {
+        if p99 > Duration::from_millis(max) {
+            return Err(format!("p99 {:.2?} exceeds the {}ms budget", p99, max).into());
+        }
+        println!("✅ p99 within the {}ms budget", max);
+    }
+    Ok(())
+}
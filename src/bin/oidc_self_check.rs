@@ -0,0 +1,44 @@
+//! OIDC Self-Check
+//!
+//! Runs the conformance self-check against a running instance and exits
+//! non-zero if any OIDC Basic or PKCE profile requirement failed. The relying
+//! party and test account come from the environment:
+//!
+//! SELF_CHECK_BASE_URL (the issuer), SELF_CHECK_CLIENT_ID,
+//! SELF_CHECK_CLIENT_SECRET (optional), SELF_CHECK_REDIRECT_URI,
+//! SELF_CHECK_EMAIL, SELF_CHECK_PASSWORD, SELF_CHECK_TENANT_ID.
+//!
+//! Pass `--json` to print the report as JSON instead of text.
+
+use auth_protocols::conformance::{run_self_check, SelfCheckConfig};
+
+fn var(name: &str) -> Result<String, String> {
+    std::env::var(name).map_err(|_| format!("{} must be set", name))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let json = std::env::args().any(|arg| arg == "--json");
+
+    let config = SelfCheckConfig {
+        base_url: var("SELF_CHECK_BASE_URL")?,
+        client_id: var("SELF_CHECK_CLIENT_ID")?,
+        client_secret: std::env::var("SELF_CHECK_CLIENT_SECRET").ok(),
+        redirect_uri: var("SELF_CHECK_REDIRECT_URI")?,
+        email: var("SELF_CHECK_EMAIL")?,
+        password: var("SELF_CHECK_PASSWORD")?,
+        tenant_id: var("SELF_CHECK_TENANT_ID")?.parse()?,
+    };
+
+    let report = run_self_check(config).await;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report);
+    }
+
+    if !report.passed() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -86,8 +86,9 @@ async fn main() -> anyhow::Result<()> {
         identifier_type: auth_core::models::user::IdentifierType::Email,
         email: Some(email.clone()),
         phone: None,
+        username: None,
         primary_identifier: Some(auth_core::models::user::PrimaryIdentifier::Email),
-        password: Some(password.to_string()),
+        password: Some(password.into()),
         profile_data: None,
         require_verification: Some(true),
     };
@@ -113,10 +114,11 @@ async fn main() -> anyhow::Result<()> {
     info!("Step C: Login with correct credentials...");
     let login_req = AuthRequest {
         email: email.clone(),
-        password: password.to_string(),
+        password: password.into(),
         tenant_id,
         ip_address: Some("127.0.0.1".to_string()),
         user_agent: Some("TestAgent".to_string()),
+        captcha_token: None,
     };
 
     let auth_resp = identity_service.login(login_req.clone()).await?;
@@ -128,7 +130,7 @@ async fn main() -> anyhow::Result<()> {
     // D. Login (Failure - Wrong Password)
     info!("Step D: Login with wrong network...");
     let mut bad_req = login_req.clone();
-    bad_req.password = "WrongPass".to_string();
+    bad_req.password = "WrongPass".into();
 
     let result = identity_service.login(bad_req).await;
     assert!(result.is_err());
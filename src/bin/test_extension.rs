@@ -18,7 +18,7 @@ async fn main() {
     assert_eq!(result, 30);
 
     // 2. Test Webhook Dispatcher
-    let dispatcher = WebhookDispatcher::new();
+    let dispatcher = WebhookDispatcher::default();
     dispatcher
         .dispatch("mock://webhook", "user.created", json!({"id": "123"}))
         .await
@@ -18,15 +18,20 @@ async fn main() {
     let repo = WebauthnRepository::new(pool);
     let service = WebauthnService::new(
         std::sync::Arc::new(repo),
-        "https://localhost:8080",
-        "localhost",
-    );
+        &auth_config::WebauthnConfig {
+            rp_origin: "https://localhost:8080".to_string(),
+            ..Default::default()
+        },
+    )
+    .expect("Invalid relying party");
 
     let user_id = Uuid::new_v4();
     let username = "test_user_passwordless";
 
     println!("Starting Registration...");
-    let result = service.start_registration(user_id, username).await;
+    let result = service
+        .start_registration(user_id, username, username)
+        .await;
 
     match result {
         Ok(_) => println!("Registration Start: PASSED"),
@@ -93,6 +93,7 @@ async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
         email_verified_at: Some(Utc::now()),
         identifier_type: auth_core::models::user::IdentifierType::Email,
         phone_verified_at: None,
+        username: None,
         primary_identifier: auth_core::models::user::PrimaryIdentifier::Email,
     };
 
@@ -109,6 +110,7 @@ async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
             timestamp: Utc::now(),
             ip_address: "192.168.1.1".to_string(),
             success: true,
+            geo: None,
         }],
     };
 
@@ -134,6 +136,7 @@ async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
             timestamp: Utc::now(),
             ip_address: "192.168.1.1".to_string(),
             success: true,
+            geo: None,
         }],
     };
 
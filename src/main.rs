@@ -1,38 +1,110 @@
 //! Main application entry point for the SSO Platform
 
 use anyhow::Result;
-use auth_config::{ConfigLoader, ConfigManager};
+use auth_config::{
+    ConfigLoader, ConfigManager, ConfigValidator, PostureCheck, SchemaCheckMode, SecurityPosture,
+    SessionBackend,
+};
 use secrecy::ExposeSecret;
 use sqlx::mysql::MySqlPoolOptions;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 // Port management
-use auth_platform::{shutdown_signal, PortAuthority, PortClass, PortPolicy};
+use auth_platform::{
+    object_store_from_config, run_singleton, Component, DistributedLock, GracefulShutdown,
+    HttpClient, LeaderElection, MySqlLock, PortAuthority, PortClass, PortPolicy, RedisLock,
+    ServiceRegistry, WorkerSupervisor,
+};
 
 // Repositories
+use auth_db::maintenance::{ExpiredRecordSweeper, OtpPurger};
+use auth_db::online_migration::OnlineMigrations;
+use auth_db::repositories::user_repository::IDENTIFIER_NORMALIZATION;
 use auth_db::repositories::{
-    otp_repository::OtpRepository, session_repository::SessionRepository,
-    subscription_repository::SubscriptionRepository, user_repository::UserRepository,
-    RefreshTokenRepository, RevokedTokenRepository, RoleRepository,
+    otp_repository::OtpRepository, pii_protector_from_config,
+    session_repository::SessionRepository, subscription_repository::SubscriptionRepository,
+    user_repository::UserRepository, AnalyticsExportRepository, ClientRepository,
+    DeviceCertificateRepository, FeatureKillSwitchRepository, LegalHoldRepository,
+    LoginEventRepository, LoginHeatmapRepository, LoginLinkRepository, NonceRepository,
+    PermissionChangeRepository, PushMfaRepository, RefreshTokenRepository, RememberMeRepository,
+    RevokedTokenRepository, RoleRepository, ServiceAccountRepository, SignupQuarantineRepository,
+    SmsQuarantineRepository, SmsUsageRepository, SsoSessionRepository, TenantMetricsRepository,
+    TenantOnboardingRepository, TenantStateRepository, TokenGenerationRepository,
+    UpstreamSessionRepository, WebauthnRepository,
 };
+use auth_db::residency::RegionRouter;
+use auth_protocols::{ClientService, UpstreamLogoutService};
 
 // Services
 use async_trait::async_trait;
 use auth_core::services::{
-    authorization::AuthorizationService, lazy_registration::LazyRegistrationService,
-    otp_delivery::OtpDeliveryService, otp_service::OtpService, rate_limiter::RateLimiter,
-    risk_assessment::RiskEngine, session_service::SessionService,
+    analytics_export::AnalyticsExporter,
+    auth_methods::{AuthMethodPolicies, PasskeyNudge},
+    authorization::AuthorizationService,
+    captcha::CaptchaService,
+    claims_enrichment::{ClaimsEnricher, ResolverOptions, RoleClaims, SubscriptionClaims},
+    credential::CredentialService,
+    device_enrollment::DeviceEnrollmentService,
+    feature_flags::FeatureFlagService,
+    forced_reauth::{ForcedReauthService, TokenGenerations},
+    geo::{GeoResolver, MaxMindGeoResolver, NoopGeoResolver},
+    id_token::Issuers,
+    identifier::IdentifierNormalizer,
+    jwks::JwksService,
+    lazy_registration::LazyRegistrationService,
+    legal_hold::LegalHoldService,
+    login_heatmap::LoginHeatmapService,
+    login_history::LoginHistoryService,
+    login_link::LoginLinkService,
+    nonce_store::{NonceBackend, NonceStore},
+    otp_delivery::OtpDeliveryService,
+    otp_service::OtpService,
+    password_expiry::{
+        ExpiredPasswordGate, PasswordExpiryCampaign, PasswordExpiryStore, ReminderTemplate,
+    },
+    permission_sync::PermissionSyncService,
+    push_mfa::PushMfaService,
+    rate_limiter::RateLimiter,
+    recovery_codes::RecoveryCodeService,
+    remember_me::RememberMeService,
+    risk_assessment::RiskEngine,
+    service_account::ServiceAccountService,
+    session_service::{SessionService, SessionStore},
+    signup_abuse::SignupAbuseService,
+    sms_budget::SmsBudgetService,
+    sms_compliance::SmsComplianceService,
+    sms_risk::SmsRiskService,
+    ssh_ca::SshCaService,
+    sso_session::SsoSessionService,
     subscription_service::SubscriptionService,
+    takeover_response::{TakeoverResponder, TakeoverWebhook},
+    tenant_context::TenantContexts,
+    tenant_metrics::TenantMetricsService,
+    tenant_onboarding::{DiscoveryProbe, TenantOnboardingService},
+    tenant_quota::TenantQuotaService,
+    token_ttl::TokenTtlPolicy,
+    webauthn_service::WebauthnService,
+    workflow::FlowStateSealer,
 };
 
 use auth_audit::AuditService;
-use auth_core::audit::{AuditLogger, TracingAuditLogger};
+use auth_core::audit::{AuditLogger, RoutingAuditLogger, TracingAuditLogger};
+use auth_core::events::EventBus;
 use auth_core::services::background::audit_worker::{AsyncAuditLogger, AuditWorker};
+use auth_core::services::token_service::RevokedTokenStore;
 
+use auth_api::bff::BffService;
+use auth_api::response_mode::ResponseModes;
+use auth_api::revocation::CachedRevokedTokenStore;
+use auth_api::sso::SsoCookie;
+use auth_api::warmup::Readiness;
 use auth_api::AppState;
-use auth_cache::{Cache, MultiLevelCache};
+use auth_cache::{Cache, MultiLevelCache, PrefixedCache};
+use auth_crypto::SymmetricCipher;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -58,16 +130,36 @@ async fn main() -> Result<()> {
     let config = config_manager.get_config();
     info!("Configuration loaded for environment: {}", environment);
 
+    // Preflight: surface misconfiguration here rather than deep in a service
+    let preflight = ConfigValidator::preflight(&config).await;
+    println!("\n{}", preflight);
+    if preflight.has_failures() {
+        anyhow::bail!("Configuration preflight failed; see the report above");
+    }
+    // Advertised at /.well-known/uac-capabilities
+    let capabilities = Arc::new(auth_api::handlers::capabilities::Capabilities::from_config(
+        &config,
+    ));
+
     // Initialize Database - Use MySQL from config
-    let database_url = config.database.mysql_url.expose_secret();
-    let pool = MySqlPoolOptions::new()
-        .max_connections(config.database.max_connections)
-        .connect(database_url)
-        .await
-        .expect("Failed to connect to MySQL database");
+    let (pool, credential_rotation) = auth_db::create_mysql_pool_with_credentials(
+        &config.database,
+        MySqlPoolOptions::new().max_connections(config.database.max_connections),
+    )
+    .await
+    .expect("Failed to connect to MySQL database");
 
     info!("Database connection established");
 
+    // Dynamic credentials: follow the login the agent renders, draining
+    // connections of the previous one
+    if let Some(rotation) = credential_rotation {
+        tokio::spawn(rotation.run_refresh(
+            pool.clone(),
+            Duration::from_secs(config.database.credentials.refresh_interval_seconds),
+        ));
+    }
+
     // Run migrations - Handle dirty migrations gracefully
     if let Err(e) = sqlx::migrate!().run(&pool).await {
         match e {
@@ -91,54 +183,279 @@ async fn main() -> Result<()> {
         info!("Migrations applied successfully");
     }
 
+    // Schema drift: columns the repositories use that the database lacks
+    // or defines differently
+    if config.database.schema_check != SchemaCheckMode::Off {
+        let drift = match auth_db::schema::verify_schema(&pool).await {
+            Ok(drift) => drift,
+            Err(e) if config.database.schema_check == SchemaCheckMode::Warn => {
+                tracing::warn!("Schema check could not run: {}", e);
+                Vec::new()
+            }
+            Err(e) => return Err(e.into()),
+        };
+        for difference in &drift {
+            tracing::warn!("Schema drift: {}", difference);
+        }
+        if !drift.is_empty() && config.database.schema_check == SchemaCheckMode::Strict {
+            anyhow::bail!(
+                "{} schema differences found; refusing to start (database.schema_check = \"strict\")",
+                drift.len()
+            );
+        }
+    }
+
+    // Data residency: tenants pinned to a region are served from that
+    // region's database and refused where it cannot be reached. A
+    // deployment without a region refuses to start while any are pinned.
+    let mut regions = RegionRouter::new(config.database.residency.region.clone(), pool.clone());
+    for (region, region_db) in &config.database.residency.regions {
+        let region_pool = MySqlPoolOptions::new()
+            .max_connections(config.database.max_connections)
+            .connect_lazy(region_db.mysql_url.expose_secret())?;
+        regions = regions.with_region_pool(region.clone(), region_pool);
+    }
+    let regions = Arc::new(regions);
+    let pinned = regions.load_pins().await?;
+    regions.verify()?;
+    info!(
+        "Data residency: region {}, {} pinned tenants",
+        regions.region().unwrap_or("none"),
+        pinned
+    );
+
+    // Record what each security-relevant role is backed by; enforced below
+    let mut posture = SecurityPosture::new(&environment);
+
+    // Phases of online schema migrations, as set from the admin API
+    let online_migrations = Arc::new(OnlineMigrations::new(
+        pool.clone(),
+        &config.database.online_migrations,
+    ));
+    if let Err(e) = online_migrations.refresh().await {
+        tracing::warn!("Failed to load online migration phases: {}", e);
+    }
+
     // Initialize Repositories
     let role_repo = Arc::new(RoleRepository::new(pool.clone()));
     let session_repo = Arc::new(SessionRepository::new(pool.clone()));
     let subscription_repo = Arc::new(SubscriptionRepository::new(pool.clone()));
-    let user_repo = Arc::new(UserRepository::new(pool.clone()));
+    let identifiers = Arc::new(IdentifierNormalizer::new(&config.security.identifiers));
+    let user_repo = UserRepository::new(pool.clone()).with_identifier_normalization(
+        identifiers.clone(),
+        online_migrations.migration(IDENTIFIER_NORMALIZATION),
+    );
+    let user_repo = match pii_protector_from_config(pool.clone(), &config.security.pii)? {
+        Some(pii) => Arc::new(user_repo.with_pii_protection(pii)),
+        None => Arc::new(user_repo),
+    };
     let otp_repo = Arc::new(OtpRepository::new(pool.clone()));
 
     // Initialize Services
-    // We use AuthorizationService for RBAC instead of legacy RoleService
-    let role_service = Arc::new(AuthorizationService::new(role_repo));
-
     let risk_engine = Arc::new(RiskEngine::new()); // Config thresholds could be passed here
-    let session_service = Arc::new(SessionService::new(session_repo, risk_engine));
 
     let subscription_service = Arc::new(SubscriptionService::new(subscription_repo));
 
-    // Initialize Token Engine with persistent stores
-    let revoked_token_repo = Arc::new(RevokedTokenRepository::new(pool.clone()));
+    // Shared outbound HTTP client: pooled connections, retries and per-host
+    // circuit breakers for calls to third parties
+    let http_client = HttpClient::new(config.external_services.http.clone())?;
+    // Blob storage for exports and archives, local disk or S3-compatible
+    let object_store =
+        object_store_from_config(&config.external_services.object_store, http_client.clone())?;
+
+    let (redis_url, cache_degradation) = match config.external_services.redis {
+        Some(redis_config) => (Some(redis_config.url), redis_config.degradation),
+        None => (None, Default::default()),
+    };
+
+    // Initialize Token Engine with persistent stores; revocation lookups
+    // are cached in Redis when available
+    let revoked_token_repo: Arc<dyn RevokedTokenStore> =
+        Arc::new(RevokedTokenRepository::new(pool.clone()));
+    let revoked_token_repo: Arc<dyn RevokedTokenStore> = match &redis_url {
+        Some(url) => Arc::new(CachedRevokedTokenStore::new(revoked_token_repo, url)?),
+        None => revoked_token_repo,
+    };
     let refresh_token_repo = Arc::new(RefreshTokenRepository::new(pool.clone()));
 
-    let token_service: Arc<dyn auth_core::services::token_service::TokenProvider> = Arc::new(
-        auth_core::services::token_service::TokenEngine::new_with_stores(
-            revoked_token_repo,
-            refresh_token_repo,
-        )
-        .await
-        .expect("Failed to initialize TokenEngine"),
-    );
+    let token_ttl_policy = Arc::new(TokenTtlPolicy::from_security_config(&config.security));
+    // Bumped by forced reauthentication and password and role changes;
+    // other instances see a bump within the cache TTL
+    let token_generations = Arc::new(TokenGenerations::new(
+        Arc::new(TokenGenerationRepository::new(pool.clone())),
+        Duration::from_secs(5),
+    ));
 
     // Initialize Async Audit
     // We use TracingAuditLogger as the underlying persistent logger (or DbAuditLogger in real life)
     let persistent_logger = Arc::new(TracingAuditLogger);
-    let (async_logger, audit_rx) = AsyncAuditLogger::new(1000);
-    let audit_logger: Arc<dyn AuditLogger> = Arc::new(async_logger);
+    let (async_logger, audit_rx) = AsyncAuditLogger::new(&config.logging.audit)?;
+    // Security events go through the pipeline; informational ones are
+    // sampled and logged
+    let audit_logger: Arc<dyn AuditLogger> = Arc::new(RoutingAuditLogger::new(
+        config.logging.audit.routing.clone(),
+        Arc::new(async_logger),
+        Arc::new(TracingAuditLogger),
+    ));
+
+    // Background workers are restarted or at least reported if they panic
+    let supervisor = WorkerSupervisor::new(config.server.supervisor.clone());
+
+    // Subsystems register here; their background work starts once all are
+    // initialised and is stopped in reverse order on shutdown
+    let mut registry = ServiceRegistry::new()
+        .with_shutdown_timeout(Duration::from_secs(config.server.drain_timeout_seconds));
 
     // Spawn Audit Worker
     let audit_worker = AuditWorker::new(audit_rx, persistent_logger);
-    tokio::spawn(audit_worker.run());
+    supervisor.spawn_once("audit", audit_worker.run());
+    posture.insecure(
+        PostureCheck::AuditSink,
+        "TracingAuditLogger only (no durable audit store)",
+    );
+
+    // Initialize single-use token store (Redis with database fallback)
+    let nonce_db: Arc<dyn NonceBackend> = Arc::new(NonceRepository::new(pool.clone()));
+    let nonces = match &redis_url {
+        Some(url) => NonceStore::new(Arc::new(auth_api::nonces::RedisNonceBackend::new(url)?))
+            .with_fallback(nonce_db),
+        None => NonceStore::new(nonce_db),
+    };
+    let nonces = Arc::new(nonces);
+
+    // Passwords past the policy's maximum age must be changed before login
+    let password_expiry = config.security.password_expiry.clone();
+    let password_credentials = CredentialService::with_template(&password_expiry.policy);
+    let password_expiry_store: Arc<dyn PasswordExpiryStore> = user_repo.clone();
+
+    // Preflight has checked that Redis is configured for Redis sessions
+    let sessions = &config.security.sessions;
+    let session_store: Arc<dyn SessionStore> = match (sessions.backend, &redis_url) {
+        (SessionBackend::Redis, Some(url)) => {
+            // Validated in Redis; the database keeps a write-behind copy
+            let (store, write_behind) = auth_api::sessions::RedisSessionStore::new(
+                url,
+                Duration::from_secs(sessions.idle_timeout_seconds),
+                session_repo,
+                sessions.write_behind_capacity,
+            )?;
+            supervisor.spawn_once("session_write_behind", write_behind.run());
+            Arc::new(store)
+        }
+        _ => session_repo,
+    };
+
+    // Replayed refresh tokens and impossible travel run the tenant's
+    // takeover playbook
+    struct TakeoverWebhookDispatcher(auth_extension::WebhookDispatcher);
+
+    #[async_trait]
+    impl TakeoverWebhook for TakeoverWebhookDispatcher {
+        async fn raise(&self, url: &str, payload: serde_json::Value) -> Result<(), String> {
+            self.0
+                .dispatch(url, "user.takeover_suspected", payload)
+                .await
+                .map_err(|e| e.to_string())
+        }
+    }
+
+    let takeover = Arc::new(
+        TakeoverResponder::new(
+            config.security.takeover_response.clone(),
+            user_repo.clone(),
+            session_store.clone(),
+            refresh_token_repo.clone(),
+            token_generations.clone(),
+            Arc::new(SimpleEmailProvider),
+        )
+        .with_webhook(Arc::new(TakeoverWebhookDispatcher(
+            auth_extension::WebhookDispatcher::new(
+                config.external_services.http.clone(),
+                config.external_services.webhook_egress.clone(),
+            )?,
+        )))
+        .with_audit(audit_logger.clone()),
+    );
+
+    // Initialize domain event bus (relayed across nodes through Redis when available)
+    let events = match &redis_url {
+        Some(url) => auth_api::events::redis_event_bus(url)?,
+        None => Arc::new(EventBus::new()),
+    };
+
+    // Roles, permissions and plan resolved into access tokens, cached per
+    // user until a role change is announced
+    let token_claims = &config.security.token_claims;
+    let mut claims_enricher = ClaimsEnricher::new();
+    if token_claims.enabled {
+        let options = ResolverOptions {
+            timeout: Duration::from_millis(token_claims.resolver_timeout_ms),
+            cache_ttl: Duration::from_secs(token_claims.cache_ttl_seconds),
+            required: false,
+        };
+        claims_enricher = claims_enricher.with_resolver(
+            Arc::new(RoleClaims::new(
+                role_repo.clone(),
+                token_claims.permission_classes.clone(),
+            )),
+            options,
+        );
+        if token_claims.subscription_plan {
+            claims_enricher = claims_enricher.with_resolver(
+                Arc::new(SubscriptionClaims::new(subscription_service.clone())),
+                options,
+            );
+        }
+    }
+    let claims_enricher = Arc::new(claims_enricher.with_events(events.clone()));
+    let token_service: Arc<dyn auth_core::services::token_service::TokenProvider> = Arc::new(
+        auth_core::services::token_service::TokenEngine::new_with_stores(
+            revoked_token_repo,
+            refresh_token_repo.clone(),
+        )
+        .await
+        .expect("Failed to initialize TokenEngine")
+        .with_ttl_policy(token_ttl_policy.clone())
+        .with_generations(token_generations.clone())
+        .with_takeover_response(takeover.clone())
+        .with_claims_enricher(claims_enricher),
+    );
+    posture.secure(
+        PostureCheck::TokenStore,
+        "MySQL-backed revocation and refresh stores",
+    );
+
+    // Token validation checks the tenant's status, cached per tenant and
+    // reloaded when a change to the tenant is announced on the event bus
+    let tenant_contexts = Arc::new(
+        TenantContexts::new(
+            Arc::new(TenantStateRepository::new(pool.clone())),
+            Duration::from_secs(60),
+        )
+        .with_events(events.clone()),
+    );
 
     // Initialize Identity Service
-    let identity_service = Arc::new(auth_core::services::identity::IdentityService::new(
-        user_repo as Arc<dyn auth_core::services::identity::UserStore>,
-        token_service,
+    let mut identity_service = auth_core::services::identity::IdentityService::new(
+        user_repo.clone() as Arc<dyn auth_core::services::identity::UserStore>,
+        token_service.clone(),
         audit_logger.clone(),
-    ));
+    )
+    .with_token_generations(token_generations.clone())
+    .with_identifiers(identifiers.clone())
+    .with_tenant_contexts(tenant_contexts.clone());
+    if password_expiry.enabled {
+        identity_service =
+            identity_service.with_password_expiry(Arc::new(ExpiredPasswordGate::new(
+                password_credentials.clone(),
+                nonces.clone(),
+                Duration::from_secs(password_expiry.change_ticket_ttl_seconds),
+            )));
+    }
+    let identity_service = Arc::new(identity_service);
 
     // Initialize OTP Service
-    let otp_service = Arc::new(OtpService::new());
+    let otp_service = Arc::new(OtpService::with_policies(&config.security.otp_policies));
 
     // Initialize OTP Delivery Service (using mock providers for now)
     // Since the test mocks aren't available publicly, create simple implementations
@@ -171,7 +488,40 @@ async fn main() -> Result<()> {
 
     let sms_provider = Arc::new(SimpleSmsProvider);
     let email_provider = Arc::new(SimpleEmailProvider);
-    let otp_delivery_service = Arc::new(OtpDeliveryService::new(sms_provider, email_provider));
+    let sms_compliance = Arc::new(SmsComplianceService::new(
+        config.external_services.sms_compliance.clone(),
+    ));
+    // Per-tenant SMS budgets and spike alerts against SMS pumping
+    let sms_budget = Arc::new(
+        SmsBudgetService::new(
+            config.external_services.sms_budget.clone(),
+            Arc::new(SmsUsageRepository::new(pool.clone())),
+        )
+        .with_audit(audit_logger.clone()),
+    );
+    // Toll fraud screening of SMS destinations, with a review queue
+    let sms_risk = Arc::new(
+        SmsRiskService::new(
+            config.external_services.sms_risk.clone(),
+            Arc::new(SmsQuarantineRepository::new(pool.clone())),
+        )
+        .with_audit(audit_logger.clone()),
+    );
+    let otp_delivery_service = Arc::new(
+        OtpDeliveryService::new(sms_provider, email_provider)
+            .with_sms_compliance(sms_compliance)
+            .with_sms_budget(sms_budget.clone())
+            .with_sms_risk(sms_risk.clone()),
+    );
+    posture
+        .insecure(
+            PostureCheck::Sms,
+            "stub SMS provider (messages are not sent)",
+        )
+        .insecure(
+            PostureCheck::Smtp,
+            "stub email provider (messages are not sent)",
+        );
 
     // Initialize Lazy Registration Service
     let lazy_registration_service =
@@ -183,101 +533,732 @@ async fn main() -> Result<()> {
     // Initialize Audit Service
     let _audit_service = Arc::new(AuditService::new(pool.clone()));
 
-    // Initialize Cache
-    let redis_url = if let Some(redis_config) = config.external_services.redis {
-        Some(redis_config.url)
-    } else {
-        None
+    // Initialize login history with background geo-enrichment
+    let geo_resolver: Arc<dyn GeoResolver> = match &config.external_services.geoip {
+        Some(geoip) => {
+            let resolver = Arc::new(MaxMindGeoResolver::open(
+                geoip.city_db_path.as_ref().map(PathBuf::from),
+                geoip.asn_db_path.as_ref().map(PathBuf::from),
+            )?);
+            resolver
+                .clone()
+                .spawn_refresh(Duration::from_secs(geoip.refresh_interval_seconds));
+            resolver
+        }
+        None => Arc::new(NoopGeoResolver),
     };
+    let (login_history, geo_worker) = LoginHistoryService::new(
+        Arc::new(LoginEventRepository::new(pool.clone())),
+        geo_resolver,
+        1000,
+    );
+    let login_history = Arc::new(login_history);
+    supervisor.spawn_once("geo", geo_worker.run());
 
-    if redis_url.is_none() && environment == "production" {
-        tracing::error!("Production environment detected but Redis is not configured! Falling back to in-memory cache.");
-    }
+    // Initialize Cache
 
-    let cache: Arc<dyn Cache> = match MultiLevelCache::new(redis_url.clone()) {
-        Ok(c) => Arc::new(c),
+    match &redis_url {
+        Some(_) => posture.secure(PostureCheck::Redis, "Redis cache, nonces and event relay"),
+        None => posture.insecure(PostureCheck::Redis, "not configured, in-memory cache"),
+    };
+
+    let multi_level_cache = match MultiLevelCache::new(redis_url.clone()) {
+        Ok(c) => Arc::new(
+            c.with_degradation(cache_degradation)
+                .with_local(&config.server.local_cache),
+        ),
         Err(e) => {
             tracing::error!(
                 "Failed to connect to Redis: {}. Falling back to in-memory.",
                 e
             );
-            Arc::new(MultiLevelCache::new(None).unwrap())
+            posture.insecure(PostureCheck::Redis, "unreachable, in-memory cache fallback");
+            Arc::new(
+                MultiLevelCache::new(None)
+                    .unwrap()
+                    .with_local(&config.server.local_cache),
+            )
         }
     };
+    // Opens the circuit while Redis is down and re-syncs on recovery
+    let (probed, probe_supervisor) = (multi_level_cache.clone(), supervisor.clone());
+    registry.register(Component::task("cache_probe", move || {
+        probe_supervisor.spawn("cache_probe", move || probed.clone().run_probe())
+    }));
+    // Drops local copies of keys other instances changed
+    let (invalidated, invalidation_supervisor) = (multi_level_cache.clone(), supervisor.clone());
+    registry.register(Component::task("cache_invalidation", move || {
+        invalidation_supervisor.spawn("cache_invalidation", move || {
+            invalidated.clone().run_invalidation()
+        })
+    }));
+    // Expired L1 entries would otherwise wait for eviction
+    let (swept, sweep_supervisor) = (multi_level_cache.clone(), supervisor.clone());
+    registry.register(Component::task("cache_sweep", move || {
+        sweep_supervisor.spawn("cache_sweep", move || swept.clone().run_sweeper())
+    }));
+    // Regions may share a Redis; their entries must not mix
+    let cache: Arc<dyn Cache> = match regions.region() {
+        Some(region) => Arc::new(PrefixedCache::new(
+            multi_level_cache,
+            format!("region:{}:", region),
+        )),
+        None => multi_level_cache,
+    };
 
-    let app_state = AppState {
-        db: pool,
-        role_service,
-        session_service,
-        subscription_service,
-        identity_service,
-        otp_service,
-        otp_delivery_service,
-        lazy_registration_service,
-        rate_limiter,
-        otp_repository: otp_repo,
-        audit_logger,
-        cache,
+    // Sign-in methods per tenant, and the passkey campaign that lets a
+    // tenant retire passwords
+    let auth_methods = Arc::new(AuthMethodPolicies::new(&config.security.auth_methods));
+    let passkey_nudge = Arc::new(PasskeyNudge::new(
+        auth_methods.clone(),
+        user_repo.clone(),
+        Arc::new(SimpleEmailProvider),
+        &config.security.auth_methods,
+    ));
+
+    // Velocity rules on public signup, with a review queue
+    let signup_abuse = Arc::new(
+        SignupAbuseService::new(
+            config.security.signup_abuse.clone(),
+            Arc::new(auth_api::signup_abuse::CacheSignupCounter::new(
+                cache.clone(),
+            )),
+            Arc::new(SignupQuarantineRepository::new(pool.clone())),
+            user_repo,
+        )
+        .with_audit(audit_logger.clone()),
+    );
+    // Preserves held users' data from purges and anonymization
+    let legal_holds = Arc::new(
+        LegalHoldService::new(Arc::new(LegalHoldRepository::new(pool.clone())))
+            .with_audit(audit_logger.clone()),
+    );
+
+    // Sessions publish revocations so WebSocket subscribers are pushed them,
+    // and take the tokens issued under them down with them
+    let session_service = Arc::new(
+        SessionService::new(session_store.clone(), risk_engine)
+            .with_events(events.clone())
+            .with_token_revocation(token_service.clone())
+            .with_takeover_response(takeover),
+    );
+    let forced_reauth = Arc::new(
+        ForcedReauthService::new(token_generations.clone(), session_store, refresh_token_repo)
+            .with_audit(audit_logger.clone()),
+    );
+
+    // Online migration phases changed on other replicas are picked up by polling
+    let (migration_refresh, migration_supervisor) = (online_migrations.clone(), supervisor.clone());
+    let migration_refresh_interval =
+        Duration::from_secs(config.database.online_migrations.refresh_seconds);
+    registry.register(Component::task("online_migration_refresh", move || {
+        migration_supervisor.spawn("online_migration_refresh", move || {
+            migration_refresh.clone().run(migration_refresh_interval)
+        })
+    }));
+
+    // Elect one replica to run singleton background jobs
+    let election_lock: Arc<dyn DistributedLock> = match &redis_url {
+        Some(url) => Arc::new(RedisLock::new(url)?),
+        None => Arc::new(MySqlLock::new(pool.clone())),
+    };
+    let leadership = LeaderElection::new(
+        election_lock,
+        "background-jobs",
+        events.node_id().to_string(),
+    )
+    .start();
+    let sweeper = Arc::new(ExpiredRecordSweeper::new(pool.clone()));
+    run_singleton(&leadership, "expired-record-sweeper", move || {
+        sweeper.clone().run(Duration::from_secs(300))
+    });
+    let otp_purger = Arc::new(OtpPurger::new(
+        pool.clone(),
+        config.database.otp_purge.clone(),
+    ));
+    let otp_purge_interval = Duration::from_secs(config.database.otp_purge.interval_seconds);
+    run_singleton(&leadership, "otp-session-purge", move || {
+        otp_purger.clone().run(otp_purge_interval)
+    });
+
+    // Service accounts authenticate with registered keys; owners are
+    // reminded before a key expires
+    let service_accounts = Arc::new(
+        ServiceAccountService::new(
+            Arc::new(ServiceAccountRepository::new(pool.clone())),
+            nonces.clone(),
+            config.security.service_accounts.clone(),
+        )
+        .with_audit(audit_logger.clone()),
+    );
+    // OAuth clients tenants register through /oauth/register
+    let clients = Arc::new(ClientService::new(Arc::new(ClientRepository::new(
+        pool.clone(),
+    ))));
+    let reminders = service_accounts.clone();
+    run_singleton(&leadership, "service-account-key-reminders", move || {
+        reminders.clone().run_reminders(Duration::from_secs(3600))
+    });
+    // Sign-in links support staff can have emailed to a user
+    let login_links = Arc::new(
+        LoginLinkService::new(
+            Arc::new(LoginLinkRepository::new(pool.clone())),
+            nonces.clone(),
+            Arc::new(SimpleEmailProvider),
+            config.security.login_links.clone(),
+        )
+        .with_audit(audit_logger.clone()),
+    );
+    // "Keep me signed in" on devices users register
+    let remember_me = Arc::new(
+        RememberMeService::new(
+            Arc::new(RememberMeRepository::new(pool.clone())),
+            config.security.remember_me.clone(),
+        )
+        .with_audit(audit_logger.clone()),
+    );
+    // Usage figures for tenant administrators; exact values on the admin
+    // listener only
+    let tenant_metrics = Arc::new(TenantMetricsService::new(
+        Arc::new(TenantMetricsRepository::new(pool.clone())),
+        config.logging.tenant_metrics.clone(),
+    ));
+    // Guided setup of new tenants; issuers under test are tenant-supplied
+    // URLs, so they are fetched under the webhook egress policy
+    let onboarding = Arc::new(
+        TenantOnboardingService::new(
+            Arc::new(TenantOnboardingRepository::new(pool.clone())),
+            Arc::new(SimpleEmailProvider),
+            Arc::new(DiscoveryProbe::new(
+                config.external_services.http.clone(),
+                config.external_services.webhook_egress.clone(),
+            )?),
+        )
+        .with_tenant_contexts(tenant_contexts),
+    );
+    // Users are reminded before their password expires
+    if password_expiry.enabled {
+        let campaign = Arc::new(PasswordExpiryCampaign::new(
+            password_expiry_store,
+            Arc::new(SimpleEmailProvider),
+            password_credentials,
+            &password_expiry.reminder_days,
+            ReminderTemplate {
+                subject: password_expiry.reminder_subject.clone(),
+                body: password_expiry.reminder_body.clone(),
+            },
+        ));
+        let interval = Duration::from_secs(password_expiry.scan_interval_seconds);
+        run_singleton(&leadership, "password-expiry-reminders", move || {
+            campaign.clone().run(interval)
+        });
+    }
+    // Sessions and login events are exported for the SOC
+    let analytics_export = &config.logging.analytics_export;
+    let exporter = if analytics_export.enabled {
+        let exporter = Arc::new(AnalyticsExporter::new(
+            Arc::new(AnalyticsExportRepository::new(pool.clone())),
+            object_store.clone(),
+            analytics_export,
+        ));
+        let job = exporter.clone();
+        let interval = Duration::from_secs(analytics_export.interval_seconds);
+        run_singleton(&leadership, "analytics-export", move || {
+            job.clone().run(interval)
+        });
+        Some(exporter)
+    } else {
+        None
+    };
+    // Sign-ins per hour, country and ASN for the admin dashboard's heatmap
+    let login_heatmap = &config.logging.login_heatmap;
+    let heatmap = Arc::new(LoginHeatmapService::new(
+        Arc::new(LoginHeatmapRepository::new(pool.clone())),
+        login_heatmap,
+    ));
+    if login_heatmap.enabled {
+        let job = heatmap.clone();
+        let interval = Duration::from_secs(login_heatmap.interval_seconds);
+        run_singleton(&leadership, "login-heatmap-rollup", move || {
+            job.clone().run(interval)
+        });
+    }
+    // Hand singleton jobs over to another replica on shutdown
+    registry.register(
+        Component::new("leader_election").on_shutdown(move || async move {
+            leadership.stop().await;
+            Ok(())
+        }),
+    );
+
+    // We use AuthorizationService for RBAC instead of legacy RoleService.
+    // Cached permission decisions are invalidated from the event bus, and
+    // every role change is recorded for the permission sync feed.
+    let permission_changes = Arc::new(PermissionChangeRepository::new(pool.clone()));
+    let role_service = Arc::new(
+        AuthorizationService::new(role_repo.clone())
+            .with_events(events.clone())
+            .with_token_generations(token_generations)
+            .with_change_log(permission_changes.clone()),
+    );
+    let permission_sync = Arc::new(
+        PermissionSyncService::new(permission_changes, role_repo).with_events(events.clone()),
+    );
+
+    // SSH certificates for infrastructure access, principals mapped from roles
+    let ssh_ca = Arc::new(
+        SshCaService::new(config.security.ssh_ca.clone(), role_service.clone())?
+            .with_audit(audit_logger.clone()),
+    );
+
+    // Device client certificates from per-tenant intermediate CAs
+    let devices = Arc::new(
+        DeviceEnrollmentService::new(
+            Arc::new(DeviceCertificateRepository::new(pool.clone())),
+            config.security.device_enrollment.clone(),
+        )?
+        .with_audit(audit_logger.clone()),
+    );
+
+    // Push approval through FCM/APNs, with TOTP as the fallback factor
+    let push_mfa = Arc::new(
+        PushMfaService::new(
+            Arc::new(PushMfaRepository::new(pool.clone())),
+            config.security.push_mfa.clone(),
+            http_client.clone(),
+        )?
+        .with_audit(audit_logger.clone()),
+    );
+
+    // Single-use recovery codes, hashed into users.backup_codes
+    let recovery_codes = Arc::new(
+        RecoveryCodeService::new(Arc::new(UserRepository::new(pool.clone())))
+            .with_audit(audit_logger.clone()),
+    );
+
+    // Discoverable passkeys for usernameless sign-in
+    let webauthn = Arc::new(
+        WebauthnService::new(
+            Arc::new(WebauthnRepository::new(pool.clone())),
+            &config.security.webauthn,
+        )?
+        .with_audit(audit_logger.clone()),
+    );
+
+    // ID token issuer per tenant; logout tokens are issued by the same one
+    let issuers = Arc::new(Issuers::new(
+        std::env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:8080".to_string()),
+        &config.security.sso.tenant_issuers,
+    ));
+
+    // Central SSO session index with front/back-channel logout
+    let sso = Arc::new(
+        SsoSessionService::new(
+            Arc::new(SsoSessionRepository::new(pool.clone())),
+            identity_service.clone(),
+            (*issuers).clone(),
+            Duration::from_secs(config.security.sso.backchannel_timeout_seconds),
+        )
+        .with_audit(audit_logger.clone()),
+    );
+    let sso_cookie = Arc::new(SsoCookie::new(config.security.sso.clone()));
+    // RP-initiated logout and SAML SLO at the identity providers users
+    // signed in through
+    let upstream_logout = Arc::new(
+        UpstreamLogoutService::new(
+            Arc::new(UpstreamSessionRepository::new(pool.clone())),
+            nonces.clone(),
+            &config.security.sso,
+            (*issuers).clone(),
+        )?
+        .with_audit(audit_logger.clone()),
+    );
+    let response_modes = Arc::new(ResponseModes::new(&config.security.sso));
+
+    // Per-tenant rate and concurrency quotas, tiered by subscription plan
+    let quotas = Arc::new(TenantQuotaService::new(
+        config.server.quotas.clone(),
+        subscription_service.clone(),
+    ));
+    // Feature flags and canary cohorts; kill switches set on other
+    // replicas are picked up by polling
+    let features = Arc::new(FeatureFlagService::new(
+        &config.features,
+        Arc::new(FeatureKillSwitchRepository::new(pool.clone())),
+    ));
+    if let Err(e) = features.refresh().await {
+        tracing::warn!("Could not load feature kill switches: {}", e);
+    }
+    let (refreshed, refresh_supervisor) = (features.clone(), supervisor.clone());
+    let refresh_interval = Duration::from_secs(config.features.cohorts.kill_switch_refresh_seconds);
+    registry.register(Component::task("feature_kill_switch_refresh", move || {
+        refresh_supervisor.spawn("feature_kill_switch_refresh", move || {
+            refreshed.clone().run_refresh(refresh_interval)
+        })
+    }));
+
+    let (pruned, pruning_supervisor) = (quotas.clone(), supervisor.clone());
+    registry.register(Component::task("quota_pruning", move || {
+        pruning_supervisor.spawn("quota_pruning", move || {
+            pruned.clone().run_pruning(Duration::from_secs(60))
+        })
+    }));
+
+    // Initialize BFF session handling (cookie-bound server-side tokens)
+    let bff = Arc::new(BffService::new(config.security.bff.clone(), cache.clone())?);
+
+    // Initialize stateless auth flow sealing
+    let flow_cipher = match &config.security.flow_state_key {
+        Some(key) => SymmetricCipher::from_base64(key.expose_secret())?,
+        None => SymmetricCipher::from_base64(&SymmetricCipher::generate_key())?,
     };
+    let flow_sealer = Arc::new(FlowStateSealer::new(flow_cipher, 900));
+
+    // RADIUS listeners for VPNs and network devices
+    #[cfg(feature = "radius")]
+    if let Some(radius) = &config.server.radius {
+        use auth_protocols::radius::{NasRegistry, RadiusServer};
+
+        let registry = Arc::new(NasRegistry::from_config(radius)?);
+        let server = Arc::new(RadiusServer::new(
+            registry.clone(),
+            identity_service.clone(),
+            audit_logger.clone(),
+        ));
+        let auth_socket =
+            tokio::net::UdpSocket::bind((radius.host.as_str(), radius.auth_port)).await?;
+        let acct_socket =
+            tokio::net::UdpSocket::bind((radius.host.as_str(), radius.acct_port)).await?;
+        info!(
+            "RADIUS listening on {}:{} (auth) and {}:{} (accounting) for {} clients",
+            radius.host,
+            radius.auth_port,
+            radius.host,
+            radius.acct_port,
+            registry.len()
+        );
+
+        let auth_server = server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = auth_server.serve_auth(auth_socket).await {
+                tracing::error!("RADIUS auth listener failed: {}", e);
+            }
+        });
+        tokio::spawn(async move {
+            if let Err(e) = server.serve_accounting(acct_socket).await {
+                tracing::error!("RADIUS accounting listener failed: {}", e);
+            }
+        });
+    }
+    #[cfg(not(feature = "radius"))]
+    if config.server.radius.is_some() {
+        tracing::warn!(
+            "server.radius is configured but the binary was built without the 'radius' feature"
+        );
+    }
+
+    // Documented operations without declared access are refused at runtime
+    let route_registry = Arc::new(auth_api::router::route_registry());
+    let undeclared = route_registry.undeclared_documented();
+    for route in &undeclared {
+        tracing::warn!(
+            "{} {} declares no access and will be refused",
+            route.method,
+            route.path
+        );
+    }
+    if undeclared.is_empty() {
+        posture.secure(
+            PostureCheck::RoutePolicy,
+            format!("{} routes declare their access", route_registry.len()),
+        );
+    } else {
+        posture.insecure(
+            PostureCheck::RoutePolicy,
+            format!(
+                "{} documented operations declare no access",
+                undeclared.len()
+            ),
+        );
+    }
+
+    // Production must not run on dev-grade components unless explicitly waived
+    println!("{}", posture);
+    posture.enforce(&config.security.posture)?;
+
+    // JWKS publication for partners, with notices ahead of key retirement
+    let jwks = Arc::new(JwksService::from_config(config.security.jwks.clone())?);
+    if !config.security.jwks.partner_webhooks.is_empty() {
+        let dispatcher = auth_extension::WebhookDispatcher::new(
+            config.external_services.http.clone(),
+            config.external_services.webhook_egress.clone(),
+        )?;
+        let (notifier_jwks, partners) =
+            (jwks.clone(), config.security.jwks.partner_webhooks.clone());
+        let notifier_supervisor = supervisor.clone();
+        registry.register(Component::task("jwks_retirement_notices", move || {
+            notifier_supervisor.spawn("jwks_retirement_notices", move || {
+                auth_api::jwks_admin::run_retirement_notices(
+                    notifier_jwks.clone(),
+                    dispatcher.clone(),
+                    partners.clone(),
+                    Duration::from_secs(300),
+                )
+            })
+        }));
+    }
+
+    // Not ready until warmed up, and again once shutdown begins
+    let readiness = Arc::new(if config.server.warmup.enabled {
+        Readiness::default()
+    } else {
+        Readiness::ready()
+    });
+
+    let app_state = AppState::builder()
+        .db(pool)
+        .role_service(role_service)
+        .session_service(session_service)
+        .subscription_service(subscription_service)
+        .identity_service(identity_service)
+        .otp_service(otp_service)
+        .otp_delivery_service(otp_delivery_service)
+        .lazy_registration_service(lazy_registration_service)
+        .rate_limiter(rate_limiter)
+        .otp_repository(otp_repo)
+        .audit_logger(audit_logger)
+        .cache(cache)
+        .bff(bff)
+        .flow_sealer(flow_sealer)
+        .token_ttl_policy(token_ttl_policy)
+        .captcha(Arc::new(CaptchaService::from_security_config(
+            &config.security,
+        )))
+        .signup_abuse(signup_abuse.clone())
+        .auth_methods(auth_methods)
+        .events(events)
+        .login_history(login_history)
+        .nonces(nonces)
+        .service_accounts(service_accounts)
+        .clients(clients)
+        .ssh_ca(ssh_ca)
+        .devices(devices)
+        .push_mfa(push_mfa)
+        .recovery_codes(recovery_codes)
+        .webauthn(webauthn)
+        .sso(sso)
+        .sso_cookie(sso_cookie)
+        .upstream_logout(upstream_logout)
+        .response_modes(response_modes)
+        .issuers(issuers)
+        .quotas(quotas)
+        .readiness(readiness.clone())
+        .jwks(jwks.clone())
+        .forced_reauth(forced_reauth)
+        .permission_sync(permission_sync)
+        .login_links(login_links)
+        .remember_me(remember_me)
+        .tenant_metrics(tenant_metrics.clone())
+        .onboarding(onboarding)
+        .regions(regions.clone())
+        .capabilities(capabilities)
+        .features(features.clone())
+        .build()?;
+
+    // Tenants pinned after startup are routed without a restart
+    let (refreshed, residency_supervisor) = (regions.clone(), supervisor.clone());
+    let residency_interval =
+        Duration::from_secs(config.database.residency.refresh_interval_seconds);
+    registry.register(Component::task("residency", move || {
+        residency_supervisor.spawn("residency", move || {
+            refreshed.clone().run_refresh(residency_interval)
+        })
+    }));
 
-    // Initialize Router
-    let app = auth_api::app(app_state);
+    // Preload caches in the background; /ready answers 503 until done
+    if config.server.warmup.enabled {
+        let (warmed, warmup) = (app_state.clone(), config.server.warmup.clone());
+        let warmup_supervisor = supervisor.clone();
+        registry.register(
+            Component::task("warmup", move || {
+                warmup_supervisor.spawn_once("warmup", auth_api::warmup::warm_up(warmed, warmup))
+            })
+            .depends_on("cache_probe"),
+        );
+    }
 
     // Initialize Port Authority for production-grade port management
-    let port_authority = PortAuthority::new().await?;
+    let port_authority = Arc::new(PortAuthority::new().await?);
+
+    // Snapshot for support tickets, served on the admin listener
+    let diagnostics = Arc::new(auth_api::diagnostics::Diagnostics::new(
+        config_manager.clone(),
+        app_state.clone(),
+        http_client,
+        port_authority.clone(),
+    ));
+
+    // Initialize Router; staged configuration versions are served to their
+    // share of requests and compared against the current one
+    let app = auth_api::app(app_state)
+        .layer(axum::middleware::from_fn_with_state(
+            config_manager.clone(),
+            auth_api::config_admin::config_rollout_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            regions,
+            auth_api::residency::residency_middleware,
+        ));
+    let (rollout, rollout_supervisor) = (config_manager.clone(), supervisor.clone());
+    let rollout_interval =
+        Duration::from_secs(config.server.config_rollout.evaluation_interval_seconds);
+    registry.register(Component::task("config_rollout", move || {
+        rollout_supervisor.spawn("config_rollout", move || {
+            rollout.clone().run_canary_evaluation(rollout_interval)
+        })
+    }));
 
     // Get or create port policy
-    let port_policy = config.server.port_policy.clone().unwrap_or_else(|| {
-        // Fallback to legacy port configuration
-        PortPolicy::new(config.server.port, PortClass::Public, "http")
-            .with_fallback_range((config.server.port + 1)..=(config.server.port + 9))
-    });
+    let port_policy = config.server.effective_port_policy();
 
-    // Acquire port with policy enforcement
-    let managed_listener = port_authority
-        .acquire(&port_policy, &config.server.host)
+    // Acquire port with policy enforcement; a dual-stack policy yields an
+    // IPv6 listener on the same port after the host's own
+    let mut managed_listeners = port_authority
+        .acquire_listeners(&port_policy, &config.server.host)
         .await?;
+    let managed_listener = managed_listeners.remove(0);
 
     let bound_port = managed_listener.port();
 
-    // Determine display host (localhost for 0.0.0.0 binding)
-    let display_host = if config.server.host == "0.0.0.0" {
-        "localhost"
-    } else {
-        &config.server.host
+    // Determine display host (localhost for wildcard binding, IPv6 bracketed)
+    let display_host = match config
+        .server
+        .host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+    {
+        "0.0.0.0" | "::" => "localhost".to_string(),
+        host if host.contains(':') => format!("[{}]", host),
+        host => host.to_string(),
     };
 
     // User-facing startup message
     println!("\n🚀 SSO Platform Starting...");
     println!("📍 Server URL: http://{}:{}", display_host, bound_port);
     println!("🔧 Service: {}", managed_listener.service_name());
+    for extra in &managed_listeners {
+        println!("🌐 Also listening on {}", extra.local_addr()?);
+    }
     println!(
         "✅ Port Management: Production-grade (PID: {})",
         std::process::id()
     );
     println!(
-        "⏱  Graceful Shutdown: {}s drain timeout",
-        config.server.drain_timeout_seconds
+        "⏱  Graceful Shutdown: {}s deregistration delay, {}s drain timeout",
+        config.server.deregistration_delay_seconds, config.server.drain_timeout_seconds
     );
     println!("📊 Health: http://{}:{}/health", display_host, bound_port);
+    println!("🚦 Readiness: http://{}:{}/ready", display_host, bound_port);
     println!("📖 Docs: http://{}:{}/swagger-ui", display_host, bound_port);
     println!("\n✨ Ready to accept connections!\n");
 
+    // Internal admin listener for port lease inspection
+    let admin_port = match &config.server.admin {
+        Some(admin) => {
+            let policy = PortPolicy::new(admin.port, PortClass::Admin, "admin");
+            let admin_listener = port_authority
+                .acquire(&policy, &admin.host)
+                .await?
+                .into_tokio_listener()?;
+            let mut admin_app = auth_api::port_admin::router(port_authority.clone())
+                .merge(auth_api::sms_admin::router(
+                    sms_budget.clone(),
+                    sms_risk.clone(),
+                ))
+                .merge(auth_api::signup_admin::router(signup_abuse.clone()))
+                .merge(auth_api::legal_hold_admin::router(legal_holds.clone()))
+                .merge(auth_api::auth_method_admin::router(passkey_nudge))
+                .merge(auth_api::jwks_admin::router(jwks.clone()))
+                .merge(auth_api::config_admin::router(config_manager.clone()))
+                .merge(auth_api::route_admin::router(route_registry))
+                .merge(auth_api::metrics_admin::router(tenant_metrics))
+                .merge(auth_api::heatmap_admin::router(heatmap))
+                .merge(auth_api::feature_admin::router(features))
+                .merge(auth_api::migration_admin::router(online_migrations))
+                .merge(auth_api::diagnostics::router(diagnostics));
+            if let Some(exporter) = exporter {
+                admin_app = admin_app.merge(auth_api::export_admin::router(exporter));
+            }
+            println!(
+                "🛠  Admin: http://{}:{}/admin/ports/leases",
+                admin.host, admin.port
+            );
+            registry.register(Component::task("admin_listener", move || {
+                tokio::spawn(async move {
+                    if let Err(e) = axum::serve(admin_listener, admin_app).await {
+                        tracing::error!("Admin listener failed: {}", e);
+                    }
+                })
+            }));
+            Some(admin.port)
+        }
+        None => None,
+    };
+
+    // Start background work of every registered subsystem
+    registry.start().await?;
+
     // Convert to tokio listener
     let listener = managed_listener.into_tokio_listener()?;
 
+    // On the shutdown signal /ready fails first; listeners stop accepting
+    // once load balancers have had time to deregister the instance
+    let shutdown = GracefulShutdown::new(Duration::from_secs(config.server.drain_timeout_seconds))
+        .with_deregistration_delay(Duration::from_secs(
+            config.server.deregistration_delay_seconds,
+        ));
+
+    // The other family of a dual-stack policy serves the same app
+    for extra in managed_listeners {
+        let (extra, extra_app) = (extra.into_tokio_listener()?, app.clone());
+        let draining = shutdown.draining();
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(extra, extra_app)
+                .with_graceful_shutdown(draining)
+                .await
+            {
+                tracing::error!("Dual-stack listener failed: {}", e);
+            }
+        });
+    }
+
     // Start server with graceful shutdown
+    let draining = shutdown.draining();
+    let mut server = tokio::spawn(async move {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(draining)
+            .await
+    });
 
     tokio::select! {
-        result = axum::serve(listener, app) => {
-            result?;
+        result = &mut server => {
+            result??;
         }
-        _ = shutdown_signal() => {
-            info!("Shutdown signal received, initiating graceful shutdown");
+        _ = shutdown.signal_then_drain(|| readiness.mark_draining()) => {
+            // Requests in flight finish, up to the drain timeout
+            if tokio::time::timeout(shutdown.drain_timeout(), &mut server).await.is_err() {
+                tracing::warn!("Drain timeout reached; closing remaining connections");
+                server.abort();
+            }
+
+            // Stop subsystems, dependents first
+            registry.shutdown().await;
 
             // Release port lease
-            if let Err(e) = port_authority.release(bound_port).await {
-                tracing::warn!("Failed to release port lease: {}", e);
+            for port in std::iter::once(bound_port).chain(admin_port) {
+                if let Err(e) = port_authority.release(port).await {
+                    tracing::warn!("Failed to release port lease: {}", e);
+                }
             }
 
             info!("Graceful shutdown complete");
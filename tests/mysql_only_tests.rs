@@ -208,6 +208,11 @@ fn create_test_database_config(url: &str) -> auth_config::DatabaseConfig {
         connection_timeout: 30,
         idle_timeout: 600,
         max_lifetime: 3600,
+        residency: Default::default(),
+        credentials: Default::default(),
+        schema_check: Default::default(),
+        otp_purge: Default::default(),
+        online_migrations: Default::default(),
     }
 }
 
@@ -0,0 +1,227 @@
+//! Tenant isolation tests
+//!
+//! Every SQL statement in the repositories must name the tenant of the
+//! tenant-scoped rows it touches (see `auth_db::tenant_guard`). The
+//! database-backed test checks that tenant-keyed listings only return the
+//! tenant's own rows; it is skipped unless TEST_MYSQL_URL is set.
+
+use auth_core::models::{DeviceCertificate, ServiceAccount, ServiceAccountStatus};
+use auth_core::services::device_enrollment::DeviceCertificateStore;
+use auth_core::services::service_account::ServiceAccountStore;
+use auth_db::repositories::{DeviceCertificateRepository, ServiceAccountRepository};
+use auth_db::tenant_guard::check_statement;
+use chrono::{Duration, Utc};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+const SQL_KEYWORDS: &[&str] = &["select", "insert", "update", "delete", "replace", "with"];
+
+/// Contents of the string literals in Rust source, raw or not
+fn string_literals(source: &str) -> Vec<String> {
+    let mut literals = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            // Char literals; a quote not closed two characters later starts
+            // a lifetime
+            '\'' => {
+                let mut lookahead = chars.clone();
+                match (lookahead.next(), lookahead.next()) {
+                    (Some('\\'), _) => {
+                        chars.nth(1);
+                        for c in chars.by_ref() {
+                            if c == '\'' {
+                                break;
+                            }
+                        }
+                    }
+                    (Some(_), Some('\'')) => {
+                        chars.next();
+                        chars.next();
+                    }
+                    _ => {}
+                }
+            }
+            'r' if matches!(chars.peek(), Some('#' | '"')) => {
+                let mut hashes = 0;
+                while chars.peek() == Some(&'#') {
+                    chars.next();
+                    hashes += 1;
+                }
+                if chars.next() != Some('"') {
+                    continue;
+                }
+                let closing = format!("\"{}", "#".repeat(hashes));
+                let mut literal = String::new();
+                for c in chars.by_ref() {
+                    literal.push(c);
+                    if literal.ends_with(&closing) {
+                        literal.truncate(literal.len() - closing.len());
+                        break;
+                    }
+                }
+                literals.push(literal);
+            }
+            '"' => {
+                let mut literal = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                            literal.push(' ');
+                        }
+                        '"' => break,
+                        c => literal.push(c),
+                    }
+                }
+                literals.push(literal);
+            }
+            _ => {}
+        }
+    }
+    literals
+}
+
+fn rust_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).expect("readable source directory") {
+        let path = entry.expect("directory entry").path();
+        if path.is_dir() {
+            rust_files(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+}
+
+#[test]
+fn test_repository_statements_name_their_tenant() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("crates/auth-db/src");
+    let mut files = Vec::new();
+    rust_files(&root, &mut files);
+    assert!(!files.is_empty(), "no sources under {}", root.display());
+
+    let mut checked = 0;
+    let mut violations = Vec::new();
+    for file in files {
+        // The guard's own examples are deliberately unscoped
+        if file.ends_with("tenant_guard.rs") {
+            continue;
+        }
+        let source = std::fs::read_to_string(&file).unwrap();
+        for sql in string_literals(&source) {
+            let first = sql.split_whitespace().next().unwrap_or_default();
+            if !SQL_KEYWORDS.contains(&first.to_lowercase().as_str()) {
+                continue;
+            }
+            checked += 1;
+            if let Err(e) = check_statement(&sql) {
+                violations.push(format!(
+                    "{}: {}\n    {}",
+                    file.strip_prefix(&root).unwrap_or(&file).display(),
+                    e,
+                    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+                ));
+            }
+        }
+    }
+
+    assert!(checked > 50, "only {} statements found", checked);
+    assert!(
+        violations.is_empty(),
+        "statements without a tenant filter; add one, or mark a deliberate \
+         cross-tenant statement with /* tenant:unscoped <reason> */:\n{}",
+        violations.join("\n")
+    );
+}
+
+#[test]
+fn test_literal_extraction() {
+    let source = r##"
+        let c = '"'; // "not a literal"
+        fn f<'a>(x: &'a str) {}
+        let q = r#"SELECT "x" FROM t"#;
+        let s = "a \"b\" c";
+    "##;
+    assert_eq!(
+        string_literals(source),
+        vec!["SELECT \"x\" FROM t".to_string(), "a  b  c".to_string()]
+    );
+}
+
+fn account(tenant_id: Uuid, name: &str) -> ServiceAccount {
+    ServiceAccount {
+        id: Uuid::new_v4(),
+        tenant_id,
+        name: name.to_string(),
+        description: None,
+        scopes: vec!["read".to_string()],
+        status: ServiceAccountStatus::Active,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    }
+}
+
+#[tokio::test]
+async fn test_tenant_listings_are_isolated() {
+    let Ok(url) = std::env::var("TEST_MYSQL_URL") else {
+        println!("Skipping tenant isolation test - TEST_MYSQL_URL not set");
+        return;
+    };
+    let pool = sqlx::MySqlPool::connect(&url).await.unwrap();
+    let (tenant_a, tenant_b) = (Uuid::new_v4(), Uuid::new_v4());
+
+    let accounts = ServiceAccountRepository::new(pool.clone());
+    accounts
+        .create_account(&account(tenant_a, "a-ci"))
+        .await
+        .unwrap();
+    accounts
+        .create_account(&account(tenant_b, "b-ci"))
+        .await
+        .unwrap();
+    let listed = accounts.list_accounts(tenant_a).await.unwrap();
+    assert_eq!(listed.len(), 1);
+    assert!(listed.iter().all(|a| a.tenant_id == tenant_a));
+
+    let certificates = DeviceCertificateRepository::new(pool);
+    let now = Utc::now();
+    for tenant_id in [tenant_a, tenant_b] {
+        let serial = Uuid::new_v4().simple().to_string();
+        certificates
+            .insert_certificate(&DeviceCertificate {
+                serial: serial.clone(),
+                tenant_id,
+                device_id: "device-1".to_string(),
+                certificate_pem: String::new(),
+                not_before: now,
+                not_after: now + Duration::days(1),
+                renewed_from: None,
+                revoked_at: None,
+                revocation_reason: None,
+                created_at: now,
+            })
+            .await
+            .unwrap();
+        certificates
+            .revoke_certificate(&serial, None, now)
+            .await
+            .unwrap();
+    }
+    for tenant_id in [tenant_a, tenant_b] {
+        let listed = certificates.list_certificates(tenant_id).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        let revoked = certificates
+            .revoked_certificates(tenant_id, now)
+            .await
+            .unwrap();
+        assert!(revoked.iter().all(|c| c.tenant_id == tenant_id));
+        assert_eq!(revoked.len(), 1);
+    }
+}